@@ -87,6 +87,11 @@ impl Attr {
         self.class.is_value()
     }
 
+    /// Returns the name and description of the attribute class.
+    pub fn metadata(&self) -> &Metadata {
+        &self.class.meta
+    }
+
     /// Returns the byte range of self.
     pub fn range(&self) -> Range<usize> {
         self.class.range(self)
@@ -386,7 +391,7 @@ extern "C" fn abi_get(
             _ => ValueType::Nil,
         },
         Err(e) => {
-            unsafe { *err = Error::new(::std::error::Error::description(&*e)) }
+            unsafe { *err = Error::new(&e.to_string()) }
             ValueType::Error
         }
     }
@@ -0,0 +1,210 @@
+//! Adapters for plugins built against older revisions of the ABI.
+//!
+//! The host wraps the decoders and readers of such a plugin, so that the
+//! layers allocated by the plugin are converted to the current layout and
+//! the metadata is read in the encoding of the plugin.
+
+use bincode;
+use context::Context;
+use decoder::{self, Decoder, DecoderBox, ExecType, Status};
+use layer::{Layer, LayerStack, Parent};
+use reader::{self, Reader, ReaderBox};
+use result::Result;
+use std::ops::DerefMut;
+
+/// The decoder metadata of revision 1, without options.
+#[derive(Deserialize)]
+struct DecoderMetadataV1 {
+    id: String,
+    name: String,
+    description: String,
+    exec_type: ExecType,
+}
+
+/// A decoder of a plugin built against an older revision.
+#[derive(Clone)]
+pub struct LegacyDecoder {
+    decoder: DecoderBox,
+    revision: u32,
+}
+
+impl LegacyDecoder {
+    pub fn new(decoder: DecoderBox, revision: u32) -> LegacyDecoder {
+        LegacyDecoder { decoder, revision }
+    }
+}
+
+impl Decoder for LegacyDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<decoder::Worker> {
+        let mut decoder = self.decoder;
+        Box::new(LegacyDecoderWorker {
+            worker: decoder.new_worker(ctx),
+            revision: self.revision,
+        })
+    }
+
+    fn metadata(&self) -> decoder::Metadata {
+        if self.revision >= 2 {
+            return self.decoder.metadata();
+        }
+        let meta: DecoderMetadataV1 = bincode::deserialize(&self.decoder.raw_metadata()).unwrap();
+        decoder::Metadata {
+            id: meta.id,
+            name: meta.name,
+            description: meta.description,
+            exec_type: meta.exec_type,
+            options: Vec::new(),
+        }
+    }
+}
+
+struct LegacyDecoderWorker {
+    worker: decoder::WorkerBox,
+    revision: u32,
+}
+
+impl decoder::Worker for LegacyDecoderWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        // The children are added to a proxy of the same layer, and then to
+        // `parent` after conversion.
+        let layer = unsafe { &mut *(parent.deref_mut() as *mut Layer) };
+        let mut proxy = Parent::from_mut_ref(layer);
+        let done = self.worker.decode(ctx, stack.as_fixed(), &mut proxy)?;
+        for child in proxy.children() {
+            parent.add_child(unsafe { Layer::upgrade(*child, self.revision) });
+        }
+        Ok(if done { Status::Done } else { Status::Skip })
+    }
+}
+
+/// A reader of a plugin built against an older revision.
+pub struct LegacyReader {
+    reader: ReaderBox,
+    revision: u32,
+}
+
+impl LegacyReader {
+    pub fn new(reader: ReaderBox, revision: u32) -> LegacyReader {
+        LegacyReader { reader, revision }
+    }
+}
+
+impl Reader for LegacyReader {
+    fn new_worker(&self, ctx: &Context, arg: &str) -> Result<Box<reader::Worker>> {
+        Ok(Box::new(LegacyReaderWorker {
+            worker: self.reader.new_worker(ctx, arg)?,
+            revision: self.revision,
+        }))
+    }
+
+    fn metadata(&self) -> reader::Metadata {
+        self.reader.metadata()
+    }
+}
+
+/// The worker of a `LegacyReader`.
+///
+/// The `WorkerBox` of a plugin before revision 3 ends before the statistics
+/// function, so the worker never reports statistics.
+struct LegacyReaderWorker {
+    worker: reader::WorkerBox,
+    revision: u32,
+}
+
+impl reader::Worker for LegacyReaderWorker {
+    fn read(&mut self, ctx: &mut Context) -> Result<Vec<Layer>> {
+        let layers = self.worker.read(ctx)?;
+        Ok(layers
+            .into_iter()
+            .map(|layer| unsafe { Layer::upgrade(layer.as_mut_ptr(), self.revision) })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use compat::LegacyDecoder;
+    use context::Context;
+    use decoder::{Decoder, DecoderBox, ExecType, Metadata, Status, Worker};
+    use fixed::{Fixed, MutFixed};
+    use fnv::FnvHashMap;
+    use layer::{Layer, LayerClass, LayerStack, Parent, Payload};
+    use result::Result;
+    use slice::ByteSlice;
+    use token::Token;
+
+    /// The layout of `Layer` in revision 1.
+    #[repr(C)]
+    struct LayerV1 {
+        class: Fixed<LayerClass>,
+        data: ByteSlice,
+        attrs: Vec<Fixed<::attr::Attr>>,
+        payloads: Vec<Payload>,
+    }
+
+    /// Adds a child in the layout of revision 1.
+    struct V1Worker {}
+
+    impl Worker for V1Worker {
+        fn decode(
+            &mut self,
+            _ctx: &mut Context,
+            _stack: &LayerStack,
+            parent: &mut Parent,
+        ) -> Result<Status> {
+            let layer = Box::new(LayerV1 {
+                class: Fixed::new(LayerClass::builder(Token::from(1234)).build()),
+                data: ByteSlice::from(&b"v1"[..]),
+                attrs: Vec::new(),
+                payloads: vec![Payload::new(ByteSlice::new(), Token::from(5678))],
+            });
+            let layer = Box::into_raw(layer) as *mut Layer;
+            parent.add_child(unsafe { MutFixed::from_ptr(layer) });
+            Ok(Status::Done)
+        }
+    }
+
+    #[derive(Clone)]
+    struct V1Decoder {}
+
+    impl Decoder for V1Decoder {
+        fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+            Box::new(V1Worker {})
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                id: "v1".to_string(),
+                exec_type: ExecType::SerialSync,
+                ..Metadata::default()
+            }
+        }
+    }
+
+    #[test]
+    fn legacy_decoder() {
+        let decoder = LegacyDecoder::new(DecoderBox::new(V1Decoder {}), 1);
+        let meta = decoder.metadata();
+        assert_eq!(meta.id, "v1");
+        assert_eq!(meta.exec_type, ExecType::SerialSync);
+
+        let mut ctx = Context::new(FnvHashMap::default());
+        let mut decoder = DecoderBox::new(decoder);
+        let mut worker = decoder.new_worker(&ctx);
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        let mut parent = Parent::from_mut_ref(&mut layer);
+        assert!(worker.decode(&mut ctx, &[], &mut parent).unwrap());
+
+        let child = unsafe { &*parent.children()[0] };
+        assert_eq!(child.id(), Token::from(1234));
+        assert_eq!(&child.data()[..], b"v1");
+        assert_eq!(child.payloads()[0].id(), Token::from(5678));
+        assert_eq!(child.frame_metadata().link_type(), None);
+    }
+}
@@ -1,20 +1,35 @@
 use fixed::Fixed;
 use fnv::FnvHashMap;
 use std::{slice, str};
+use table::{DissectorTables, SessionMetadata};
+use token::Token;
 
 /// A context object.
 #[repr(C)]
 pub struct Context {
     class: Fixed<ContextClass>,
     config: FnvHashMap<String, String>,
+    tables: DissectorTables,
+    metadata: SessionMetadata,
 }
 
 impl Context {
     /// Creates a new Context.
     pub fn new(config: FnvHashMap<String, String>) -> Context {
+        Self::with_shared(config, DissectorTables::new(), SessionMetadata::new())
+    }
+
+    /// Creates a new Context sharing the given dissector tables and session metadata.
+    pub fn with_shared(
+        config: FnvHashMap<String, String>,
+        tables: DissectorTables,
+        metadata: SessionMetadata,
+    ) -> Context {
         Self {
             class: CONTEXT_CLASS.clone(),
             config,
+            tables,
+            metadata,
         }
     }
 
@@ -24,17 +39,75 @@ impl Context {
         let data = (self.class.get_config)(self, key.as_ptr(), &mut len);
         unsafe { str::from_utf8_unchecked(slice::from_raw_parts(data, len as usize)) }
     }
+
+    /// Sets a session metadata value.
+    pub fn set_metadata(&self, key: &str, value: &str) {
+        (self.class.set_metadata)(
+            self,
+            key.as_ptr(),
+            key.len() as u64,
+            value.as_ptr(),
+            value.len() as u64,
+        );
+    }
+
+    /// Returns a dissector table in the current profile.
+    pub fn dissector_table<T: Into<Token>>(&self, id: T) -> DissectorTable<'_> {
+        DissectorTable {
+            ctx: self,
+            id: id.into(),
+        }
+    }
+}
+
+/// A proxy for a dissector table.
+pub struct DissectorTable<'a> {
+    ctx: &'a Context,
+    id: Token,
+}
+
+impl<'a> DissectorTable<'a> {
+    /// Returns the decoder registered for the key.
+    pub fn get(&self, key: u64) -> Option<Token> {
+        let decoder = (self.ctx.class.get_table_entry)(self.ctx, self.id, key);
+        if decoder == Token::null() {
+            None
+        } else {
+            Some(decoder)
+        }
+    }
+
+    /// Registers a decoder for the key, replacing the existing entry.
+    pub fn add<T: Into<Token>>(&self, key: u64, decoder: T) {
+        (self.ctx.class.set_table_entry)(self.ctx, self.id, key, decoder.into(), 1);
+    }
+
+    /// Registers a decoder for the key unless the key already has an entry.
+    pub fn add_default<T: Into<Token>>(&self, key: u64, decoder: T) {
+        (self.ctx.class.set_table_entry)(self.ctx, self.id, key, decoder.into(), 0);
+    }
+
+    /// Removes the entry for the key.
+    pub fn remove(&self, key: u64) {
+        (self.ctx.class.set_table_entry)(self.ctx, self.id, key, Token::null(), 1);
+    }
 }
 
 #[repr(C)]
 pub struct ContextClass {
     get_config: extern "C" fn(*const Context, *const u8, *mut u64) -> *const u8,
+    get_table_entry: extern "C" fn(*const Context, Token, u64) -> Token,
+    set_table_entry: extern "C" fn(*const Context, Token, u64, Token, u8),
+    set_metadata: extern "C" fn(*const Context, *const u8, u64, *const u8, u64),
 }
 
 impl ContextClass {
     fn new() -> ContextClass {
         Self {
             get_config: abi_get_config,
+            get_table_entry: abi_get_table_entry,
+            set_table_entry: abi_set_table_entry,
+            set_metadata: abi_set_metadata,
         }
     }
 }
@@ -48,6 +121,81 @@ extern "C" fn abi_get_config(ctx: *const Context, data: *const u8, len: *mut u64
     }
 }
 
+extern "C" fn abi_get_table_entry(ctx: *const Context, table: Token, key: u64) -> Token {
+    let tables = unsafe { &(*ctx).tables };
+    tables.get(table, key).unwrap_or_else(Token::null)
+}
+
+extern "C" fn abi_set_table_entry(
+    ctx: *const Context,
+    table: Token,
+    key: u64,
+    decoder: Token,
+    overwrite: u8,
+) {
+    let tables = unsafe { &(*ctx).tables };
+    if decoder == Token::null() {
+        tables.remove(table, key);
+    } else if overwrite != 0 {
+        tables.add(table, key, decoder);
+    } else {
+        tables.add_default(table, key, decoder);
+    }
+}
+
+extern "C" fn abi_set_metadata(
+    ctx: *const Context,
+    key: *const u8,
+    key_len: u64,
+    value: *const u8,
+    value_len: u64,
+) {
+    unsafe {
+        let key = str::from_utf8_unchecked(slice::from_raw_parts(key, key_len as usize));
+        let value = str::from_utf8_unchecked(slice::from_raw_parts(value, value_len as usize));
+        (*ctx).metadata.set(key, value);
+    }
+}
+
 lazy_static! {
     static ref CONTEXT_CLASS: Fixed<ContextClass> = Fixed::new(ContextClass::new());
 }
+
+#[cfg(test)]
+mod tests {
+    use context::Context;
+    use fnv::FnvHashMap;
+    use table::{DissectorTables, SessionMetadata};
+    use token::Token;
+
+    #[test]
+    fn dissector_table() {
+        let tables = DissectorTables::new();
+        let ctx = Context::with_shared(
+            FnvHashMap::default(),
+            tables.clone(),
+            SessionMetadata::new(),
+        );
+        let table = ctx.dissector_table("tcp.port");
+        assert_eq!(table.get(8443), None);
+        table.add(8443, "@data:tls");
+        assert_eq!(table.get(8443), Some(Token::from("@data:tls")));
+        assert_eq!(tables.get("tcp.port", 8443), Some(Token::from("@data:tls")));
+        table.add_default(8443, "@data:http");
+        assert_eq!(table.get(8443), Some(Token::from("@data:tls")));
+        table.remove(8443);
+        assert_eq!(tables.get("tcp.port", 8443), None);
+    }
+
+    #[test]
+    fn metadata() {
+        let metadata = SessionMetadata::new();
+        let ctx = Context::with_shared(
+            FnvHashMap::default(),
+            DissectorTables::new(),
+            metadata.clone(),
+        );
+        ctx.set_metadata("pcapng.comment", "capture");
+        assert_eq!(metadata.get("pcapng.comment"), Some("capture".to_string()));
+    }
+}
@@ -5,7 +5,7 @@ use fixed::MutFixed;
 use layer::{Layer, LayerStack, Parent};
 use result::Result;
 use serde::ser::{Serialize, Serializer};
-use std::ptr;
+use std::{fmt, ops::RangeInclusive, ptr};
 use vec::SafeVec;
 
 /// Execution type.
@@ -29,6 +29,7 @@ pub struct Metadata {
     pub name: String,
     pub description: String,
     pub exec_type: ExecType,
+    pub options: Vec<DecoderOption>,
 }
 
 impl Default for Metadata {
@@ -38,10 +39,81 @@ impl Default for Metadata {
             name: String::new(),
             description: String::new(),
             exec_type: ExecType::ParallelSync,
+            options: Vec::new(),
         }
     }
 }
 
+/// The value type of a decoder option.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum OptionType {
+    Bool,
+    Int { min: i64, max: i64 },
+    Enum(Vec<String>),
+    Path,
+    String,
+}
+
+/// A config value declared by a decoder.
+///
+/// The value is read by `Context::get_config` as JSON text, which is the
+/// default value unless the session sets another one. Changing the value
+/// decodes the frames again with new workers.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct DecoderOption {
+    pub key: String,
+    pub description: String,
+    pub typ: OptionType,
+    /// The default value in JSON.
+    pub default: String,
+}
+
+fn json_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl DecoderOption {
+    fn new(key: &str, typ: OptionType, default: String) -> DecoderOption {
+        DecoderOption {
+            key: key.to_string(),
+            description: String::new(),
+            typ,
+            default,
+        }
+    }
+
+    pub fn bool(key: &str, default: bool) -> DecoderOption {
+        Self::new(key, OptionType::Bool, default.to_string())
+    }
+
+    pub fn int(key: &str, range: RangeInclusive<i64>, default: i64) -> DecoderOption {
+        let typ = OptionType::Int {
+            min: *range.start(),
+            max: *range.end(),
+        };
+        Self::new(key, typ, default.to_string())
+    }
+
+    pub fn enumeration(key: &str, values: &[&str], default: &str) -> DecoderOption {
+        let values = values.iter().map(|v| v.to_string()).collect();
+        Self::new(key, OptionType::Enum(values), json_string(default))
+    }
+
+    /// Creates a file path option, empty by default.
+    pub fn path(key: &str) -> DecoderOption {
+        Self::new(key, OptionType::Path, json_string(""))
+    }
+
+    pub fn string(key: &str, default: &str) -> DecoderOption {
+        Self::new(key, OptionType::String, json_string(default))
+    }
+
+    pub fn description(mut self, description: &str) -> DecoderOption {
+        self.description = description.to_string();
+        self
+    }
+}
+
 /// Decoder worker trait.
 pub trait Worker {
     fn decode(&mut self, &mut Context, &LayerStack, &mut Parent) -> Result<Status>;
@@ -104,7 +176,7 @@ extern "C" fn abi_decode(
         },
         Err(err) => {
             unsafe {
-                ptr::write(error, Error::new(err.description()));
+                ptr::write(error, Error::new(&err.to_string()));
             }
             0
         }
@@ -151,6 +223,12 @@ pub struct DecoderBox {
 
 unsafe impl Send for DecoderBox {}
 
+impl fmt::Debug for DecoderBox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DecoderBox")
+    }
+}
+
 impl DecoderBox {
     pub fn new<T: 'static + Decoder>(diss: T) -> DecoderBox {
         let diss: Box<Decoder> = Box::new(diss);
@@ -168,6 +246,11 @@ impl DecoderBox {
     pub fn metadata(&self) -> Metadata {
         bincode::deserialize(&(self.metadata)(self)).unwrap()
     }
+
+    /// Returns the metadata in bincode.
+    pub(crate) fn raw_metadata(&self) -> SafeVec<u8> {
+        (self.metadata)(self)
+    }
 }
 
 impl Serialize for DecoderBox {
@@ -193,7 +276,9 @@ extern "C" fn abi_metadata(diss: *const DecoderBox) -> SafeVec<u8> {
 #[cfg(test)]
 mod tests {
     use context::Context;
-    use decoder::{Decoder, DecoderBox, ExecType, Metadata, Status, Worker};
+    use decoder::{
+        Decoder, DecoderBox, DecoderOption, ExecType, Metadata, OptionType, Status, Worker,
+    };
     use fixed::Fixed;
     use fnv::FnvHashMap;
     use layer::{Layer, LayerClass, LayerStack, Parent};
@@ -245,4 +330,39 @@ mod tests {
 
         assert_eq!(worker.decode(&mut ctx, &[], &mut layer).unwrap(), true);
     }
+
+    #[test]
+    fn options() {
+        #[derive(Clone)]
+        struct TestDecoder {}
+
+        impl Decoder for TestDecoder {
+            fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+                unimplemented!()
+            }
+
+            fn metadata(&self) -> Metadata {
+                Metadata {
+                    options: vec![
+                        DecoderOption::int("test.int", 1..=9, 3),
+                        DecoderOption::enumeration("test.enum", &["a", "b"], "b")
+                            .description("an enum"),
+                        DecoderOption::string("test.string", r#"a"\"#),
+                    ],
+                    ..Metadata::default()
+                }
+            }
+        }
+
+        let options = DecoderBox::new(TestDecoder {}).metadata().options;
+        assert_eq!(options[0].typ, OptionType::Int { min: 1, max: 9 });
+        assert_eq!(options[0].default, "3");
+        assert_eq!(
+            options[1].typ,
+            OptionType::Enum(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(options[1].default, r#""b""#);
+        assert_eq!(options[1].description, "an enum");
+        assert_eq!(options[2].default, r#""a\"\\""#);
+    }
 }
@@ -13,6 +13,23 @@ pub extern "C" fn genet_abi_version() -> u64 {
     major << 32 | minor
 }
 
+/// The revision of the ABI, increased whenever a struct shared with plugins
+/// changes its layout or encoding.
+///
+/// - 1: plugins without `genet_abi_revision`.
+/// - 2: `Layer` has the frame metadata, and the decoder metadata has options.
+/// - 3: reader workers report the statistics of live captures.
+pub const ABI_REVISION: u32 = 3;
+
+/// The oldest revision of plugins the host can adapt.
+pub const MIN_ABI_REVISION: u32 = 1;
+
+#[cfg(not(feature = "genet-static"))]
+#[no_mangle]
+pub extern "C" fn genet_abi_revision() -> u32 {
+    ABI_REVISION
+}
+
 #[cfg(not(feature = "genet-static"))]
 #[no_mangle]
 pub extern "C" fn genet_abi_v1_register_get_token(ptr: extern "C" fn(*const u8, u64) -> Token) {
@@ -0,0 +1,73 @@
+//! Severities of expert information.
+//!
+//! Decoders report a finding by adding an attribute whose type is
+//! `@expert:<severity>`, e.g. `@expert:warn`.
+
+use std::fmt;
+
+/// The prefix of the attribute types of findings.
+pub const TYPE_PREFIX: &str = "@expert:";
+
+/// The attribute holding the highest severity of the findings in a frame.
+pub const SEVERITY_ATTR: &str = "expert.severity";
+
+/// The severity of a finding, in ascending order.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Chat = 1,
+    Note = 2,
+    Warn = 3,
+    Error = 4,
+}
+
+impl Severity {
+    pub fn from_name(name: &str) -> Option<Severity> {
+        match name {
+            "chat" => Some(Severity::Chat),
+            "note" => Some(Severity::Note),
+            "warn" => Some(Severity::Warn),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+
+    /// Returns the severity of an attribute type, e.g. `@expert:warn`.
+    pub fn from_typ(typ: &str) -> Option<Severity> {
+        typ.strip_prefix(TYPE_PREFIX).and_then(Self::from_name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Chat => "chat",
+            Severity::Note => "note",
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+        }
+    }
+
+    /// Returns the numeric level used as the value of `expert.severity`.
+    pub fn level(self) -> u64 {
+        self as u64
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expert::Severity;
+
+    #[test]
+    fn from_typ() {
+        assert_eq!(Severity::from_typ("@expert:warn"), Some(Severity::Warn));
+        assert_eq!(Severity::from_typ("@expert:fatal"), None);
+        assert_eq!(Severity::from_typ("@novalue"), None);
+        assert!(Severity::Note < Severity::Error);
+        assert_eq!(Severity::Error.level(), 4);
+    }
+}
@@ -12,4 +12,13 @@ impl FileType {
             extensions: extensions.iter().map(|s| s.to_string()).collect(),
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the file extensions without a leading dot.
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
 }
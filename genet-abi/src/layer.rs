@@ -47,9 +47,22 @@ impl<'a> LayerStack<'a> {
         self.layers().find(|layer| layer.id() == id)
     }
 
-    fn layers(&self) -> impl DoubleEndedIterator<Item = &'a Layer> {
+    /// Returns an iterator over the layers from the bottom.
+    pub fn layers(&self) -> impl DoubleEndedIterator<Item = &'a Layer> {
         self.buffer.iter().map(|layer| unsafe { &**layer })
     }
+
+    pub(crate) fn as_fixed(&self) -> &[MutFixed<Layer>] {
+        let ptr = self.buffer.as_ptr() as *const MutFixed<Layer>;
+        unsafe { slice::from_raw_parts(ptr, self.buffer.len()) }
+    }
+
+    /// Returns the capture metadata of the frame.
+    pub fn frame_metadata(&self) -> FrameMetadata {
+        self.bottom()
+            .map(|layer| layer.frame_metadata())
+            .unwrap_or_default()
+    }
 }
 
 /// A mutable proxy for a layer object.
@@ -85,6 +98,11 @@ impl<'a> Parent<'a> {
         self.deref().data()
     }
 
+    /// Returns the capture metadata of the frame if self is a root layer.
+    pub fn frame_metadata(&self) -> FrameMetadata {
+        self.deref().frame_metadata()
+    }
+
     /// Returns the slice of headers.
     pub fn headers(&self) -> &[Fixed<Attr>] {
         self.deref().headers()
@@ -152,6 +170,116 @@ extern "C" fn abi_children_data(layer: *const Parent) -> *const *mut Layer {
     unsafe { (*layer).children.as_ptr() }
 }
 
+/// An iterator over the headers and the attributes of a layer.
+pub struct AttrIter<'a> {
+    headers: slice::Iter<'a, Fixed<Attr>>,
+    attrs: slice::Iter<'a, Fixed<Attr>>,
+}
+
+impl<'a> Iterator for AttrIter<'a> {
+    type Item = &'a Attr;
+
+    fn next(&mut self) -> Option<&'a Attr> {
+        self.headers
+            .next()
+            .or_else(|| self.attrs.next())
+            .map(|attr| attr.as_ref())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.headers.len() + self.attrs.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for AttrIter<'a> {}
+
+/// The direction of a frame relative to the capturing interface.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    Unknown = 0,
+    Inbound = 1,
+    Outbound = 2,
+}
+
+const HAS_LINK_TYPE: u8 = 0x01;
+const HAS_INTERFACE: u8 = 0x02;
+const HAS_FCS_LEN: u8 = 0x04;
+
+/// Capture metadata of a frame, set by readers on the root layer.
+///
+/// Each value is unknown unless the reader sets it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameMetadata {
+    link_type: u32,
+    interface: u32,
+    direction: Direction,
+    fcs_len: u8,
+    flags: u8,
+}
+
+impl FrameMetadata {
+    /// Creates a new FrameMetadata with the link type (`LINKTYPE_*`).
+    pub fn new(link_type: u32) -> FrameMetadata {
+        FrameMetadata {
+            link_type,
+            flags: HAS_LINK_TYPE,
+            ..FrameMetadata::default()
+        }
+    }
+
+    /// Sets the ID of the capturing interface.
+    pub fn with_interface(mut self, interface: u32) -> FrameMetadata {
+        self.interface = interface;
+        self.flags |= HAS_INTERFACE;
+        self
+    }
+
+    pub fn with_direction(mut self, direction: Direction) -> FrameMetadata {
+        self.direction = direction;
+        self
+    }
+
+    /// Sets the length of the FCS at the end of the frame, which is 0 if
+    /// the frame has no FCS.
+    pub fn with_fcs_len(mut self, len: u8) -> FrameMetadata {
+        self.fcs_len = len;
+        self.flags |= HAS_FCS_LEN;
+        self
+    }
+
+    pub fn link_type(&self) -> Option<u32> {
+        if self.flags & HAS_LINK_TYPE != 0 {
+            Some(self.link_type)
+        } else {
+            None
+        }
+    }
+
+    pub fn interface(&self) -> Option<u32> {
+        if self.flags & HAS_INTERFACE != 0 {
+            Some(self.interface)
+        } else {
+            None
+        }
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn fcs_len(&self) -> Option<u8> {
+        if self.flags & HAS_FCS_LEN != 0 {
+            Some(self.fcs_len)
+        } else {
+            None
+        }
+    }
+}
+
 /// A layer object.
 #[repr(C)]
 pub struct Layer {
@@ -159,6 +287,7 @@ pub struct Layer {
     data: ByteSlice,
     attrs: Vec<Fixed<Attr>>,
     payloads: Vec<Payload>,
+    frame: FrameMetadata,
 }
 
 unsafe impl Send for Layer {}
@@ -171,6 +300,7 @@ impl Layer {
             data: data.into(),
             attrs: Vec::new(),
             payloads: Vec::new(),
+            frame: FrameMetadata::default(),
         }
     }
 
@@ -179,11 +309,40 @@ impl Layer {
         self.class.id()
     }
 
+    /// Returns the name and description of the layer class.
+    pub fn metadata(&self) -> &Metadata {
+        &self.class.meta
+    }
+
     /// Returns the type of self.
     pub fn data(&self) -> ByteSlice {
         self.class.data(self)
     }
 
+    /// Returns a copy of self with the data replaced.
+    ///
+    /// Attributes and the frame metadata are shared with self; payloads are
+    /// not copied.
+    pub fn with_data<B: Into<ByteSlice>>(&self, data: B) -> Layer {
+        let mut layer = Layer::new(self.class.clone(), data);
+        for attr in self.attrs() {
+            layer.add_attr(attr.clone());
+        }
+        layer.frame = self.frame;
+        layer
+    }
+
+    /// Returns the capture metadata of the frame if self is a root layer.
+    pub fn frame_metadata(&self) -> FrameMetadata {
+        self.frame
+    }
+
+    /// Sets the capture metadata of the frame. Readers set it on root
+    /// layers.
+    pub fn set_frame_metadata(&mut self, metadata: FrameMetadata) {
+        self.frame = metadata;
+    }
+
     /// Returns the slice of headers.
     pub fn headers(&self) -> &[Fixed<Attr>] {
         self.class.headers()
@@ -194,6 +353,40 @@ impl Layer {
         self.class.attrs(self)
     }
 
+    /// Returns an iterator over the headers and the attributes.
+    ///
+    /// The slices are fetched once, so iterating every attribute of a layer
+    /// makes no further calls across the ABI.
+    pub fn attrs_iter(&self) -> AttrIter<'_> {
+        AttrIter {
+            headers: self.headers().iter(),
+            attrs: self.attrs().iter(),
+        }
+    }
+
+    /// Calls `f` with each header and attribute.
+    pub fn for_each_attr<F: FnMut(&Attr)>(&self, f: F) {
+        self.attrs_iter().for_each(f)
+    }
+
+    /// Returns the number of the headers and the attributes.
+    pub fn attrs_len(&self) -> usize {
+        self.headers().len() + self.attrs().len()
+    }
+
+    /// Returns the header or the attribute at `index` in the order of
+    /// `attrs_iter()`.
+    pub fn attr_at(&self, index: usize) -> Option<&Attr> {
+        let headers = self.headers();
+        if index < headers.len() {
+            Some(headers[index].as_ref())
+        } else {
+            self.attrs()
+                .get(index - headers.len())
+                .map(|attr| attr.as_ref())
+        }
+    }
+
     /// Find the attribute in the Layer.
     pub fn attr<T: Into<Token>>(&self, id: T) -> Option<&Attr> {
         let id = id.into();
@@ -216,6 +409,14 @@ impl Layer {
         (func)(self, attr.into());
     }
 
+    /// Removes the attributes for which `f` returns false.
+    ///
+    /// Removing elements never reallocates the vector, so this does not need
+    /// to call into the module which created the layer.
+    pub fn retain_attrs<F: FnMut(&Attr) -> bool>(&mut self, mut f: F) {
+        self.attrs.retain(|attr| f(attr));
+    }
+
     /// Returns the slice of payloads.
     pub fn payloads(&self) -> &[Payload] {
         self.class.payloads(self)
@@ -226,6 +427,13 @@ impl Layer {
         let func = self.class.add_payload;
         (func)(self, payload);
     }
+
+    /// Returns the mutable slice of payloads.
+    pub fn payloads_mut(&mut self) -> &mut [Payload] {
+        let data = (self.class.payloads_data)(self) as *mut Payload;
+        let len = (self.class.payloads_len)(self) as usize;
+        unsafe { slice::from_raw_parts_mut(data, len) }
+    }
 }
 
 impl fmt::Debug for Layer {
@@ -234,12 +442,42 @@ impl fmt::Debug for Layer {
     }
 }
 
+/// The layout of `Layer` in ABI revision 1, without the frame metadata.
+#[repr(C)]
+struct LayerV1 {
+    class: Fixed<LayerClass>,
+    data: ByteSlice,
+    attrs: Vec<Fixed<Attr>>,
+    payloads: Vec<Payload>,
+}
+
+impl Layer {
+    /// Takes a layer allocated by a plugin of the ABI `revision`, converting
+    /// it to the current layout.
+    pub(crate) unsafe fn upgrade(layer: *mut Layer, revision: u32) -> Layer {
+        if revision >= 2 {
+            return *Box::from_raw(layer);
+        }
+        let layer = *Box::from_raw(layer as *mut LayerV1);
+        Layer {
+            class: layer.class,
+            data: layer.data,
+            attrs: layer.attrs,
+            payloads: layer.payloads,
+            frame: FrameMetadata::default(),
+        }
+    }
+}
+
 impl Into<MutFixed<Layer>> for Layer {
     fn into(self) -> MutFixed<Layer> {
         MutFixed::new(self)
     }
 }
 
+/// The type of the payloads created by `Payload::embedded`.
+pub const EMBEDDED_TYPE: &str = "@embedded";
+
 /// A payload object.
 #[repr(C)]
 pub struct Payload {
@@ -270,6 +508,13 @@ impl Payload {
         }
     }
 
+    /// Creates a payload of bytes embedded in a layer, e.g. the datagram
+    /// quoted by an ICMP error, which is dissected as `id` from scratch:
+    /// the decoders already used for the frame are run again on it.
+    pub fn embedded<B: Into<ByteSlice>, T: Into<Token>>(data: B, id: T) -> Payload {
+        Self::with_typ(data, id, EMBEDDED_TYPE)
+    }
+
     /// Returns the ID of self.
     pub fn id(&self) -> Token {
         self.id
@@ -280,6 +525,16 @@ impl Payload {
         self.typ
     }
 
+    /// Returns true if self is created by `Payload::embedded`.
+    pub fn is_embedded(&self) -> bool {
+        self.typ == Token::from(EMBEDDED_TYPE)
+    }
+
+    /// Replaces the ID of self.
+    pub fn set_id<T: Into<Token>>(&mut self, id: T) {
+        self.id = id.into();
+    }
+
     /// Returns the data of self.
     pub fn data(&self) -> ByteSlice {
         unsafe { ByteSlice::from_raw_parts(self.data, self.len as usize) }
@@ -484,7 +739,7 @@ mod tests {
     use attr::{Attr, AttrClass};
     use cast::Cast;
     use fixed::Fixed;
-    use layer::{Layer, LayerClass, Payload};
+    use layer::{Direction, FrameMetadata, Layer, LayerClass, Payload};
     use slice::ByteSlice;
     use std::io::Result;
     use token::Token;
@@ -506,6 +761,45 @@ mod tests {
         assert_eq!(layer.data(), ByteSlice::from(&data[..]));
     }
 
+    #[test]
+    fn with_data() {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let attr = Fixed::new(AttrClass::builder("length").build());
+        let mut layer = Layer::new(class, ByteSlice::from(&b"hello"[..]));
+        layer.add_attr(Attr::builder(attr).value(5u64).build());
+        layer.add_payload(Payload::new(ByteSlice::new(), "@data:test"));
+
+        let copy = layer.with_data(ByteSlice::from(&b"world"[..]));
+        assert_eq!(copy.id(), layer.id());
+        assert_eq!(copy.data(), ByteSlice::from(&b"world"[..]));
+        assert_eq!(copy.attrs().len(), 1);
+        assert!(copy.payloads().is_empty());
+    }
+
+    #[test]
+    fn frame_metadata() {
+        let class = Fixed::new(LayerClass::builder("[link-1]").build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        let metadata = layer.frame_metadata();
+        assert_eq!(metadata.link_type(), None);
+        assert_eq!(metadata.interface(), None);
+        assert_eq!(metadata.direction(), Direction::Unknown);
+        assert_eq!(metadata.fcs_len(), None);
+
+        let metadata = FrameMetadata::new(1)
+            .with_interface(0)
+            .with_direction(Direction::Outbound)
+            .with_fcs_len(4);
+        layer.set_frame_metadata(metadata);
+        let copy = layer.with_data(ByteSlice::new());
+        let metadata = copy.frame_metadata();
+        assert_eq!(metadata.link_type(), Some(1));
+        assert_eq!(metadata.interface(), Some(0));
+        assert_eq!(metadata.direction(), Direction::Outbound);
+        assert_eq!(metadata.fcs_len(), Some(4));
+        assert_eq!(FrameMetadata::new(1).with_fcs_len(0).fcs_len(), Some(0));
+    }
+
     #[test]
     fn payloads() {
         let class = Fixed::new(LayerClass::builder(Token::null()).build());
@@ -528,6 +822,17 @@ mod tests {
         assert!(iter.next().is_none());
     }
 
+    #[test]
+    fn payloads_mut() {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        layer.add_payload(Payload::new(ByteSlice::new(), "@data:tcp"));
+        for payload in layer.payloads_mut() {
+            payload.set_id("@data:http");
+        }
+        assert_eq!(layer.payloads()[0].id(), Token::from("@data:http"));
+    }
+
     #[test]
     fn attrs() {
         let class = Fixed::new(LayerClass::builder(Token::null()).build());
@@ -563,4 +868,42 @@ mod tests {
         }
         assert!(iter.next().is_none());
     }
+
+    #[test]
+    fn attrs_iter() {
+        let header = Fixed::new(AttrClass::builder("header").build());
+        let class = Fixed::new(
+            LayerClass::builder(Token::null())
+                .header(Attr::builder(header).build())
+                .build(),
+        );
+        let mut layer = Layer::new(class, ByteSlice::new());
+        let attr = Fixed::new(AttrClass::builder("attr").build());
+        layer.add_attr(Attr::builder(attr.clone()).range(0..1).build());
+        layer.add_attr(Attr::builder(attr).range(1..2).build());
+
+        let iter = layer.attrs_iter();
+        assert_eq!(iter.len(), 3);
+        let ids = iter.map(|attr| attr.id()).collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec![
+                Token::from("header"),
+                Token::from("attr"),
+                Token::from("attr")
+            ]
+        );
+
+        let mut ranges = Vec::new();
+        layer.for_each_attr(|attr| ranges.push(attr.range()));
+        assert_eq!(ranges, vec![0..0, 0..1, 1..2]);
+
+        assert_eq!(layer.attrs_len(), 3);
+        assert_eq!(
+            layer.attr_at(0).map(|a| a.id()),
+            Some(Token::from("header"))
+        );
+        assert_eq!(layer.attr_at(2).map(|a| a.range()), Some(1..2));
+        assert!(layer.attr_at(3).is_none());
+    }
 }
@@ -12,10 +12,12 @@ extern crate lazy_static;
 
 pub mod attr;
 pub mod cast;
+pub mod compat;
 pub mod context;
 pub mod decoder;
 pub mod env;
 pub mod error;
+pub mod expert;
 pub mod file;
 pub mod fixed;
 pub mod layer;
@@ -23,6 +25,8 @@ pub mod metadata;
 pub mod reader;
 pub mod result;
 pub mod slice;
+pub mod table;
+pub mod timestamp;
 pub mod token;
 pub mod variant;
 pub mod writer;
@@ -109,7 +109,7 @@ extern "C" fn abi_reader_new_worker(
             1
         }
         Err(e) => {
-            unsafe { *err = Error::new(e.description()) };
+            unsafe { *err = Error::new(&e.to_string()) };
             0
         }
     }
@@ -120,17 +120,55 @@ extern "C" fn abi_metadata(reader: *const ReaderBox) -> SafeVec<u8> {
     bincode::serialize(&reader.metadata()).unwrap().into()
 }
 
+/// Statistics of a live capture, counted since the capture started.
+#[repr(C)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureStats {
+    /// The number of frames received by the capture.
+    pub received: u64,
+    /// The number of frames dropped because the capture buffer was full.
+    pub dropped: u64,
+    /// The number of frames dropped by the interface or its driver.
+    pub if_dropped: u64,
+    /// The number of receive errors of the interface.
+    pub errors: u64,
+    /// The number of times the capture buffer overran.
+    pub overruns: u64,
+}
+
+impl CaptureStats {
+    /// Returns the number of frames which were not captured.
+    pub fn lost(&self) -> u64 {
+        self.dropped + self.if_dropped
+    }
+}
+
 /// Reader worker trait.
 pub trait Worker: Send {
-    fn read(&mut self) -> Result<Vec<Layer>>;
+    fn read(&mut self, ctx: &mut Context) -> Result<Vec<Layer>>;
+
+    /// Returns the statistics of the capture, or None if the worker does not
+    /// capture live traffic.
+    fn stats(&mut self) -> Option<CaptureStats> {
+        None
+    }
 }
 
-type ReaderFunc = extern "C" fn(*mut Box<Worker>, *mut SafeVec<MutFixed<Layer>>, *mut Error) -> u8;
+type ReaderFunc = extern "C" fn(
+    *mut Box<Worker>,
+    *mut Context,
+    *mut SafeVec<MutFixed<Layer>>,
+    *mut Error,
+) -> u8;
+
+type StatsFunc = extern "C" fn(*mut Box<Worker>, *mut CaptureStats) -> u8;
 
 pub struct WorkerBox {
     worker: *mut Box<Worker>,
     read: ReaderFunc,
     drop: extern "C" fn(*mut Box<Worker>),
+    stats: StatsFunc,
 }
 
 unsafe impl Send for WorkerBox {}
@@ -141,18 +179,28 @@ impl WorkerBox {
             worker: Box::into_raw(Box::new(worker)),
             read: abi_reader_worker_read,
             drop: abi_reader_worker_drop,
+            stats: abi_reader_worker_stats,
         }
     }
 
-    pub fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+    pub fn read(&mut self, ctx: &mut Context) -> Result<Vec<MutFixed<Layer>>> {
         let mut v = SafeVec::new();
         let mut e = Error::new("");
-        if (self.read)(self.worker, &mut v, &mut e) == 0 {
+        if (self.read)(self.worker, ctx, &mut v, &mut e) == 0 {
             Err(Box::new(e))
         } else {
             Ok(v.into_iter().collect())
         }
     }
+
+    pub fn stats(&mut self) -> Option<CaptureStats> {
+        let mut stats = CaptureStats::default();
+        if (self.stats)(self.worker, &mut stats) == 0 {
+            None
+        } else {
+            Some(stats)
+        }
+    }
 }
 
 impl fmt::Debug for WorkerBox {
@@ -173,11 +221,13 @@ extern "C" fn abi_reader_worker_drop(worker: *mut Box<Worker>) {
 
 extern "C" fn abi_reader_worker_read(
     worker: *mut Box<Worker>,
+    ctx: *mut Context,
     out: *mut SafeVec<MutFixed<Layer>>,
     err: *mut Error,
 ) -> u8 {
     let worker = unsafe { &mut *worker };
-    match worker.read() {
+    let ctx = unsafe { &mut *ctx };
+    match worker.read(ctx) {
         Ok(layers) => {
             let mut safe = SafeVec::with_capacity(layers.len() as u64);
             for layer in layers {
@@ -187,8 +237,19 @@ extern "C" fn abi_reader_worker_read(
             1
         }
         Err(e) => {
-            unsafe { *err = Error::new(e.description()) };
+            unsafe { *err = Error::new(&e.to_string()) };
             0
         }
     }
 }
+
+extern "C" fn abi_reader_worker_stats(worker: *mut Box<Worker>, out: *mut CaptureStats) -> u8 {
+    let worker = unsafe { &mut *worker };
+    match worker.stats() {
+        Some(stats) => {
+            unsafe { *out = stats };
+            1
+        }
+        None => 0,
+    }
+}
@@ -0,0 +1,209 @@
+use fnv::FnvHashMap;
+use parking_lot::RwLock;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::{collections::BTreeMap, sync::Arc};
+use token::Token;
+
+type TableMap = FnvHashMap<Token, FnvHashMap<u64, Token>>;
+
+/// A set of dissector tables shared between a profile and its contexts.
+///
+/// Each table maps a key (e.g. a port number) to a payload type token
+/// (e.g. `@data:ntp`) which transport decoders use to choose the next layer.
+///
+/// Decoders register their defaults whenever a worker is created, so a
+/// removed entry is kept as a null token and defaults do not restore it.
+#[derive(Clone, Default)]
+pub struct DissectorTables {
+    tables: Arc<RwLock<TableMap>>,
+}
+
+impl DissectorTables {
+    /// Creates a new empty DissectorTables.
+    pub fn new() -> DissectorTables {
+        Self::default()
+    }
+
+    /// Returns the decoder registered for the key in the table.
+    pub fn get<T: Into<Token>>(&self, table: T, key: u64) -> Option<Token> {
+        self.tables
+            .read()
+            .get(&table.into())
+            .and_then(|t| t.get(&key))
+            .cloned()
+            .filter(|decoder| *decoder != Token::null())
+    }
+
+    /// Registers a decoder for the key, replacing the existing entry.
+    pub fn add<T: Into<Token>, D: Into<Token>>(&self, table: T, key: u64, decoder: D) {
+        self.tables
+            .write()
+            .entry(table.into())
+            .or_default()
+            .insert(key, decoder.into());
+    }
+
+    /// Registers a decoder for the key unless the key already has an entry or
+    /// its entry has been removed.
+    pub fn add_default<T: Into<Token>, D: Into<Token>>(&self, table: T, key: u64, decoder: D) {
+        self.tables
+            .write()
+            .entry(table.into())
+            .or_default()
+            .entry(key)
+            .or_insert_with(|| decoder.into());
+    }
+
+    /// Removes the entry for the key from the table.
+    pub fn remove<T: Into<Token>>(&self, table: T, key: u64) {
+        self.tables
+            .write()
+            .entry(table.into())
+            .or_default()
+            .insert(key, Token::null());
+    }
+
+    /// Returns all entries of the table sorted by key.
+    pub fn entries<T: Into<Token>>(&self, table: T) -> Vec<(u64, Token)> {
+        let mut entries = self
+            .tables
+            .read()
+            .get(&table.into())
+            .map(|t| {
+                t.iter()
+                    .filter(|(_, v)| **v != Token::null())
+                    .map(|(k, v)| (*k, *v))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        entries.sort_by_key(|(k, _)| *k);
+        entries
+    }
+}
+
+/// Session metadata shared between a profile and its contexts.
+///
+/// Readers use it to publish capture-wide information such as interface
+/// descriptions or name resolution records.
+#[derive(Clone, Default)]
+pub struct SessionMetadata {
+    entries: Arc<RwLock<BTreeMap<String, String>>>,
+}
+
+impl SessionMetadata {
+    /// Creates a new empty SessionMetadata.
+    pub fn new() -> SessionMetadata {
+        Self::default()
+    }
+
+    /// Returns the value for the key.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.entries.read().get(key).cloned()
+    }
+
+    /// Sets the value for the key.
+    pub fn set(&self, key: &str, value: &str) {
+        self.entries
+            .write()
+            .insert(key.to_string(), value.to_string());
+    }
+
+    /// Returns all entries sorted by key.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        self.entries
+            .read()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+}
+
+impl Serialize for SessionMetadata {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let entries = self.entries.read();
+        let mut s = serializer.serialize_map(Some(entries.len()))?;
+        for (key, value) in entries.iter() {
+            s.serialize_entry(key, value)?;
+        }
+        s.end()
+    }
+}
+
+impl Serialize for DissectorTables {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let tables = self.tables.read();
+        let mut s = serializer.serialize_map(Some(tables.len()))?;
+        for (id, table) in tables.iter() {
+            let mut entries = table
+                .iter()
+                .filter(|(_, v)| **v != Token::null())
+                .map(|(k, v)| (*k, v.to_string()))
+                .collect::<Vec<_>>();
+            entries.sort_by_key(|(k, _)| *k);
+            s.serialize_entry(&id.to_string(), &entries)?;
+        }
+        s.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use table::{DissectorTables, SessionMetadata};
+    use token::Token;
+
+    #[test]
+    fn add_remove() {
+        let tables = DissectorTables::new();
+        assert_eq!(tables.get("udp.port", 123), None);
+        tables.add("udp.port", 123, "@data:ntp");
+        assert_eq!(tables.get("udp.port", 123), Some(Token::from("@data:ntp")));
+        assert_eq!(tables.get("tcp.port", 123), None);
+        tables.remove("udp.port", 123);
+        assert_eq!(tables.get("udp.port", 123), None);
+    }
+
+    #[test]
+    fn add_default() {
+        let tables = DissectorTables::new();
+        tables.add("tcp.port", 8443, "@data:tls");
+        tables.add_default("tcp.port", 8443, "@data:http");
+        tables.add_default("tcp.port", 80, "@data:http");
+        assert_eq!(
+            tables.entries("tcp.port"),
+            vec![
+                (80, Token::from("@data:http")),
+                (8443, Token::from("@data:tls"))
+            ]
+        );
+
+        // A removed default is not registered again by new workers.
+        tables.remove("tcp.port", 80);
+        tables.add_default("tcp.port", 80, "@data:http");
+        assert_eq!(tables.get("tcp.port", 80), None);
+        assert_eq!(tables.entries("tcp.port").len(), 1);
+        tables.add("tcp.port", 80, "@data:http");
+        assert_eq!(tables.get("tcp.port", 80), Some(Token::from("@data:http")));
+    }
+
+    #[test]
+    fn metadata() {
+        let metadata = SessionMetadata::new();
+        metadata.clone().set("pcapng.comment", "capture");
+        assert_eq!(metadata.get("pcapng.comment"), Some("capture".to_string()));
+        assert_eq!(metadata.get("pcapng.interfaces"), None);
+    }
+
+    #[test]
+    fn shared() {
+        let tables = DissectorTables::new();
+        let cloned = tables.clone();
+        cloned.add("udp.port", 5353, "@data:dns");
+        assert_eq!(tables.get("udp.port", 5353), Some(Token::from("@data:dns")));
+    }
+}
@@ -0,0 +1,541 @@
+//! Timestamp display configuration.
+
+use context::Context;
+use std::{fmt, str::FromStr};
+
+/// Config key for the time zone.
+pub const ZONE_KEY: &str = "_.timestamp.zone";
+
+/// Config key for the timestamp reference.
+pub const REFERENCE_KEY: &str = "_.timestamp.reference";
+
+/// Config key for the timestamp resolution.
+pub const RESOLUTION_KEY: &str = "_.timestamp.resolution";
+
+/// A time zone used to display absolute timestamps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Zone {
+    Utc,
+    Local,
+    /// A fixed offset in seconds east of UTC.
+    Fixed(i32),
+}
+
+impl Zone {
+    /// Returns the offset from UTC in seconds at the given UNIX time.
+    pub fn offset(self, sec: i64) -> i32 {
+        match self {
+            Zone::Utc => 0,
+            Zone::Local => local_offset(sec),
+            Zone::Fixed(offset) => offset,
+        }
+    }
+}
+
+impl FromStr for Zone {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Zone, ()> {
+        match s {
+            "utc" | "UTC" | "Z" => Ok(Zone::Utc),
+            "local" => Ok(Zone::Local),
+            _ => parse_offset(s).map(Zone::Fixed).ok_or(()),
+        }
+    }
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Zone::Utc => write!(f, "utc"),
+            Zone::Local => write!(f, "local"),
+            Zone::Fixed(offset) => write_offset(f, *offset),
+        }
+    }
+}
+
+/// A reference point of displayed timestamps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reference {
+    /// Wall-clock time.
+    Absolute,
+    /// Seconds since the first frame.
+    Relative,
+    /// Seconds since the previous frame.
+    Delta,
+}
+
+impl FromStr for Reference {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Reference, ()> {
+        match s {
+            "absolute" => Ok(Reference::Absolute),
+            "relative" => Ok(Reference::Relative),
+            "delta" => Ok(Reference::Delta),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reference::Absolute => write!(f, "absolute"),
+            Reference::Relative => write!(f, "relative"),
+            Reference::Delta => write!(f, "delta"),
+        }
+    }
+}
+
+/// A number of fractional digits of displayed timestamps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    Sec,
+    Milli,
+    Micro,
+    Nano,
+}
+
+impl Resolution {
+    /// Returns the number of fractional digits.
+    pub fn digits(self) -> usize {
+        match self {
+            Resolution::Sec => 0,
+            Resolution::Milli => 3,
+            Resolution::Micro => 6,
+            Resolution::Nano => 9,
+        }
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Resolution, ()> {
+        match s {
+            "s" => Ok(Resolution::Sec),
+            "ms" => Ok(Resolution::Milli),
+            "us" => Ok(Resolution::Micro),
+            "ns" => Ok(Resolution::Nano),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Resolution::Sec => write!(f, "s"),
+            Resolution::Milli => write!(f, "ms"),
+            Resolution::Micro => write!(f, "us"),
+            Resolution::Nano => write!(f, "ns"),
+        }
+    }
+}
+
+/// A timestamp display configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimestampFormat {
+    pub zone: Zone,
+    pub reference: Reference,
+    pub resolution: Resolution,
+}
+
+impl Default for TimestampFormat {
+    fn default() -> TimestampFormat {
+        TimestampFormat {
+            zone: Zone::Local,
+            reference: Reference::Absolute,
+            resolution: Resolution::Micro,
+        }
+    }
+}
+
+impl TimestampFormat {
+    /// Creates a TimestampFormat from config values.
+    ///
+    /// Missing or invalid values fall back to the defaults.
+    pub fn from_config<F: Fn(&str) -> Option<String>>(get: F) -> TimestampFormat {
+        // Config values set from the UI are JSON-encoded strings.
+        let get = |key| get(key).map(|v| v.trim_matches('"').to_string());
+        let def = Self::default();
+        TimestampFormat {
+            zone: get(ZONE_KEY)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(def.zone),
+            reference: get(REFERENCE_KEY)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(def.reference),
+            resolution: get(RESOLUTION_KEY)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(def.resolution),
+        }
+    }
+
+    /// Creates a TimestampFormat from the config in the context.
+    pub fn from_context(ctx: &Context) -> TimestampFormat {
+        Self::from_config(|key| Some(ctx.get_config(key).to_string()))
+    }
+
+    /// Returns the config entries representing self.
+    pub fn to_config(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (ZONE_KEY, self.zone.to_string()),
+            (REFERENCE_KEY, self.reference.to_string()),
+            (RESOLUTION_KEY, self.resolution.to_string()),
+        ]
+    }
+
+    /// Returns the displayed value of a UNIX timestamp, which is the
+    /// timestamp itself or the seconds since the reference frame.
+    ///
+    /// `first` and `prev` are the timestamps of the first and the previous frames,
+    /// used by the relative and delta references respectively.
+    pub fn value(&self, ts: f64, first: f64, prev: f64) -> f64 {
+        match self.reference {
+            Reference::Absolute => ts,
+            Reference::Relative => ts - first,
+            Reference::Delta => ts - prev,
+        }
+    }
+
+    /// Formats a UNIX timestamp.
+    ///
+    /// `first` and `prev` are used as in `value`.
+    pub fn format(&self, ts: f64, first: f64, prev: f64) -> String {
+        let value = self.value(ts, first, prev);
+        match self.reference {
+            Reference::Absolute => self.format_absolute(value),
+            _ => self.format_duration(value),
+        }
+    }
+
+    /// Formats a UNIX timestamp as a wall-clock time, ignoring the reference.
+    pub fn format_absolute(&self, ts: f64) -> String {
+        let digits = self.resolution.digits();
+        let scale = 10i64.pow(digits as u32);
+        let ticks = (ts * scale as f64).floor() as i64;
+        let sec = ticks.div_euclid(scale);
+        let frac = ticks.rem_euclid(scale);
+        let offset = self.zone.offset(sec);
+        let local = sec + i64::from(offset);
+        let (year, month, day) = civil_from_days(local.div_euclid(86400));
+        let secs = local.rem_euclid(86400);
+        let mut s = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        );
+        if digits > 0 {
+            s += &format!(".{:0width$}", frac, width = digits);
+        }
+        if self.zone == Zone::Utc {
+            s.push('Z');
+        } else {
+            s += &Zone::Fixed(offset).to_string();
+        }
+        s
+    }
+
+    fn format_duration(&self, duration: f64) -> String {
+        format!("{:.*}", self.resolution.digits(), duration)
+    }
+
+    /// Parses a datetime literal and returns a UNIX timestamp.
+    ///
+    /// The format is `YYYY-MM-DD[THH:MM[:SS[.FFF]]][Z|+HH:MM]`.
+    /// A datetime without an explicit offset is interpreted in the configured zone.
+    pub fn parse(&self, s: &str) -> Option<f64> {
+        let (date, rest) = if s.len() > 10 && s.is_char_boundary(10) {
+            s.split_at(10)
+        } else {
+            (s, "")
+        };
+        let mut date = date.splitn(3, '-');
+        let year: i64 = parse_digits(date.next()?, 4)?;
+        let month: u32 = parse_digits(date.next()?, 2)?;
+        let day: u32 = parse_digits(date.next()?, 2)?;
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+
+        let (time, offset) = match rest.find(&['Z', '+', '-'][..]) {
+            Some(pos) => {
+                let (time, zone) = rest.split_at(pos);
+                (time, Some(zone.parse::<Zone>().ok()?.offset(0)))
+            }
+            None => (rest, None),
+        };
+
+        let mut secs = 0f64;
+        if !time.is_empty() {
+            if !time.starts_with('T') && !time.starts_with(' ') {
+                return None;
+            }
+            let mut time = time[1..].splitn(3, ':');
+            let hour: u32 = parse_digits(time.next()?, 2)?;
+            let min: u32 = parse_digits(time.next()?, 2)?;
+            let sec = match time.next() {
+                Some(sec) => parse_seconds(sec)?,
+                None => 0.0,
+            };
+            if hour > 23 || min > 59 || sec >= 61.0 {
+                return None;
+            }
+            secs = f64::from(hour * 3600 + min * 60) + sec;
+        }
+
+        let naive = days_from_civil(year, month, day) * 86400;
+        let offset = offset.unwrap_or_else(|| {
+            let offset = self.zone.offset(naive);
+            self.zone.offset(naive - i64::from(offset))
+        });
+        Some((naive - i64::from(offset)) as f64 + secs)
+    }
+}
+
+/// Formats the timestamps of consecutive frames, remembering the first and
+/// the previous ones for the relative and delta references.
+#[derive(Clone, Debug)]
+pub struct Timeline {
+    format: TimestampFormat,
+    first: Option<f64>,
+    prev: Option<f64>,
+}
+
+impl Timeline {
+    pub fn new(format: TimestampFormat) -> Timeline {
+        Timeline {
+            format,
+            first: None,
+            prev: None,
+        }
+    }
+
+    /// Formats the timestamp of the frame following the ones passed before.
+    pub fn format(&mut self, ts: f64) -> String {
+        let first = *self.first.get_or_insert(ts);
+        let prev = self.prev.replace(ts).unwrap_or(ts);
+        self.format.format(ts, first, prev)
+    }
+
+    /// Passes the timestamp of a frame which is not displayed.
+    pub fn skip(&mut self, ts: f64) {
+        self.first.get_or_insert(ts);
+        self.prev = Some(ts);
+    }
+}
+
+fn parse_digits<T: FromStr>(s: &str, len: usize) -> Option<T> {
+    if s.len() == len && s.bytes().all(|c| c.is_ascii_digit()) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn parse_seconds(s: &str) -> Option<f64> {
+    if s.len() >= 2 && s.bytes().all(|c| c.is_ascii_digit() || c == b'.') {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn parse_offset(s: &str) -> Option<i32> {
+    let sign = match s.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let s = s[1..].replace(":", "");
+    let hour: i32 = parse_digits(s.get(0..2)?, 2)?;
+    let min: i32 = parse_digits(s.get(2..)?, 2)?;
+    if hour > 23 || min > 59 {
+        return None;
+    }
+    Some(sign * (hour * 3600 + min * 60))
+}
+
+fn write_offset(f: &mut fmt::Formatter, offset: i32) -> fmt::Result {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    write!(f, "{}{:02}:{:02}", sign, offset / 3600, offset / 60 % 60)
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(unix)]
+fn local_offset(sec: i64) -> i32 {
+    use libc;
+    use std::mem;
+    unsafe {
+        let time = sec as libc::time_t;
+        let mut tm: libc::tm = mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            0
+        } else {
+            tm.tm_gmtoff as i32
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn local_offset(_sec: i64) -> i32 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use timestamp::{Reference, Resolution, Timeline, TimestampFormat, Zone, ZONE_KEY};
+
+    fn utc() -> TimestampFormat {
+        TimestampFormat {
+            zone: Zone::Utc,
+            ..TimestampFormat::default()
+        }
+    }
+
+    #[test]
+    fn format_absolute() {
+        let format = utc();
+        assert_eq!(format.format_absolute(0.0), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(
+            format.format_absolute(1_525_176_000.25),
+            "2018-05-01T12:00:00.250000Z"
+        );
+
+        let format = TimestampFormat {
+            zone: Zone::Fixed(9 * 3600),
+            resolution: Resolution::Milli,
+            ..TimestampFormat::default()
+        };
+        assert_eq!(
+            format.format_absolute(1_525_176_000.25),
+            "2018-05-01T21:00:00.250+09:00"
+        );
+    }
+
+    #[test]
+    fn format_reference() {
+        let format = TimestampFormat {
+            reference: Reference::Relative,
+            resolution: Resolution::Milli,
+            ..utc()
+        };
+        assert_eq!(format.format(12.5, 10.0, 12.0), "2.500");
+
+        let format = TimestampFormat {
+            reference: Reference::Delta,
+            ..format
+        };
+        assert_eq!(format.format(12.5, 10.0, 12.0), "0.500");
+        assert_eq!(format.value(12.5, 10.0, 12.0), 0.5);
+    }
+
+    #[test]
+    fn timeline() {
+        let format = TimestampFormat {
+            reference: Reference::Delta,
+            resolution: Resolution::Milli,
+            ..utc()
+        };
+        let mut timeline = Timeline::new(format);
+        assert_eq!(timeline.format(10.0), "0.000");
+        assert_eq!(timeline.format(10.25), "0.250");
+        assert_eq!(timeline.format(11.0), "0.750");
+        timeline.skip(11.5);
+        assert_eq!(timeline.format(13.0), "1.500");
+
+        let mut timeline = Timeline::new(TimestampFormat {
+            reference: Reference::Relative,
+            ..format
+        });
+        timeline.skip(9.5);
+        assert_eq!(timeline.format(10.0), "0.500");
+        assert_eq!(timeline.format(10.25), "0.750");
+        assert_eq!(timeline.format(11.0), "1.500");
+
+        let mut timeline = Timeline::new(utc());
+        assert_eq!(timeline.format(0.0), "1970-01-01T00:00:00.000000Z");
+    }
+
+    #[test]
+    fn parse() {
+        let format = utc();
+        assert_eq!(format.parse("1970-01-01"), Some(0.0));
+        assert_eq!(format.parse("2018-05-01T12:00:00Z"), Some(1_525_176_000.0));
+        assert_eq!(format.parse("2018-05-01T12:00"), Some(1_525_176_000.0));
+        assert_eq!(
+            format.parse("2018-05-01T21:00:00.5+09:00"),
+            Some(1_525_176_000.5)
+        );
+        assert_eq!(format.parse("2018-13-01"), None);
+        assert_eq!(format.parse("2018-05-01X"), None);
+        assert_eq!(format.parse("127.0.0.1"), None);
+
+        let format = TimestampFormat {
+            zone: Zone::Fixed(-3600),
+            ..format
+        };
+        assert_eq!(format.parse("2018-05-01T11:00:00"), Some(1_525_176_000.0));
+    }
+
+    #[test]
+    fn config() {
+        let format = TimestampFormat {
+            zone: Zone::Fixed(-(5 * 3600 + 1800)),
+            reference: Reference::Delta,
+            resolution: Resolution::Nano,
+        };
+        let config = format.to_config();
+        assert_eq!(config[0].1, "-05:30");
+        let parsed = TimestampFormat::from_config(|key| {
+            config
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.clone())
+        });
+        assert_eq!(parsed, format);
+        assert_eq!(
+            TimestampFormat::from_config(|key| if key == ZONE_KEY {
+                Some("\"utc\"".to_string())
+            } else {
+                None
+            })
+            .zone,
+            Zone::Utc
+        );
+        assert_eq!(
+            TimestampFormat::from_config(|_| None),
+            TimestampFormat::default()
+        );
+    }
+}
@@ -109,7 +109,7 @@ extern "C" fn abi_writer_new_worker(
             1
         }
         Err(e) => {
-            unsafe { *err = Error::new(e.description()) };
+            unsafe { *err = Error::new(&e.to_string()) };
             0
         }
     }
@@ -199,7 +199,7 @@ extern "C" fn abi_writer_worker_write(
     match worker.write(index, &stack) {
         Ok(()) => 1,
         Err(e) => {
-            unsafe { *err = Error::new(e.description()) };
+            unsafe { *err = Error::new(&e.to_string()) };
             0
         }
     }
@@ -210,7 +210,7 @@ extern "C" fn abi_writer_worker_end(worker: *mut Box<Worker>, err: *mut Error) -
     match worker.end() {
         Ok(()) => 1,
         Err(e) => {
-            unsafe { *err = Error::new(e.description()) };
+            unsafe { *err = Error::new(&e.to_string()) };
             0
         }
     }
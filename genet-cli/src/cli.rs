@@ -0,0 +1,383 @@
+use genet_abi::timestamp::Timeline;
+use genet_filter::Filter;
+use genet_kernel::{
+    extract::Fields,
+    index::Summary,
+    profile::Profile,
+    session::{Callback, Event, Session},
+    tree,
+};
+use serde_json;
+use std::{
+    io::{self, Write},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+const POLL_INTERVAL_MS: u64 = 10;
+
+/// The frame timestamp, printed according to the timestamp format.
+const TIMESTAMP_ATTR: &str = "link.timestamp";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// A summary line per frame.
+    Text,
+    /// The values of the selected fields per frame.
+    Fields,
+    /// The layer tree of each frame.
+    Json,
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub read: String,
+    pub reader: Option<String>,
+    pub filter: Option<String>,
+    /// Whether the filter is written in the Wireshark syntax.
+    pub wireshark: bool,
+    pub format: Format,
+    pub fields: Vec<String>,
+    pub separator: String,
+    pub header: bool,
+    pub write: Option<String>,
+    pub writer: Option<String>,
+}
+
+#[derive(Clone)]
+struct EventCallback {
+    sender: Sender<Event>,
+}
+
+impl Callback for EventCallback {
+    fn on_event(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+}
+
+/// Waits until the input or the output `id` is done.
+fn wait(events: &Receiver<Event>, id: u32) -> Result<(), String> {
+    loop {
+        match events.recv() {
+            Ok(Event::Input(input, _)) if input == id => return Ok(()),
+            Ok(Event::Output(output, err)) if output == id => {
+                return err.map_or(Ok(()), |err| Err(err.to_string()))
+            }
+            Ok(Event::Error(err)) => return Err(err.to_string()),
+            Ok(_) => {}
+            Err(_) => return Err("session closed".to_string()),
+        }
+    }
+}
+
+/// Returns the first error reported by the session, if any.
+fn last_error(events: &Receiver<Event>) -> Option<String> {
+    events.try_iter().find_map(|event| match event {
+        Event::Error(err) => Some(err.to_string()),
+        _ => None,
+    })
+}
+
+/// Reads the capture file, and prints the frames matching the filter to
+/// `out` or writes them to the output file.
+pub fn run(profile: Profile, opts: &Options, out: &mut Write) -> Result<(), String> {
+    let filter = match &opts.filter {
+        Some(filter) if opts.wireshark => {
+            Some(Filter::compile_wireshark(filter).map_err(|err| err.to_string())?)
+        }
+        Some(filter) => Some(Filter::compile(filter).map_err(|err| err.to_string())?),
+        None => None,
+    };
+
+    let (sender, events) = mpsc::channel();
+    let mut session = Session::new(profile, EventCallback { sender });
+    let reader = opts
+        .reader
+        .clone()
+        .or_else(|| session.profile().reader_for_file(&opts.read))
+        .ok_or_else(|| format!("{}: no reader found", opts.read))?;
+    let arg = json!({ "file": opts.read }).to_string();
+    let input = session.create_reader(&reader, &arg);
+    if input == 0 {
+        return Err(last_error(&events).unwrap_or_else(|| format!("{}: no such reader", reader)));
+    }
+    // Readers of files report the end of the file as an error.
+    wait(&events, input)?;
+    while session.pending_frames() > 0 {
+        thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+    }
+
+    if let Some(path) = &opts.write {
+        let writer = opts
+            .writer
+            .clone()
+            .or_else(|| session.profile().writer_for_file(path))
+            .ok_or_else(|| format!("{}: no writer found", path))?;
+        let arg = json!({ "file": path }).to_string();
+        let output = session.create_writer(&writer, &arg, filter, None);
+        if output == 0 {
+            return Err(
+                last_error(&events).unwrap_or_else(|| format!("{}: no such writer", writer))
+            );
+        }
+        return wait(&events, output);
+    }
+
+    print(&session, filter.as_ref(), opts, out).map_err(|err| err.to_string())
+}
+
+fn print(
+    session: &Session,
+    filter: Option<&Filter>,
+    opts: &Options,
+    out: &mut Write,
+) -> io::Result<()> {
+    let fields = Fields::new(&opts.fields);
+    if opts.format == Format::Fields && opts.header {
+        writeln!(out, "{}", opts.fields.join(&opts.separator))?;
+    }
+    if opts.format == Format::Json {
+        write!(out, "[")?;
+    }
+    let mut result = Ok(());
+    let mut count = 0;
+    let mut timeline = Timeline::new(session.timestamp_format());
+    // The filter is tested here so that delta timestamps refer to the
+    // previous captured frame.
+    session.scan_from(0, None, |frame| {
+        let summary = Summary::new(frame);
+        if let Some(filter) = filter {
+            if !filter.test(&session.frame_flags().context(frame)) {
+                timeline.skip(summary.timestamp());
+                return true;
+            }
+        }
+        let timestamp = timeline.format(summary.timestamp());
+        result = match opts.format {
+            Format::Text => writeln!(
+                out,
+                "{:>6} {} {} {}",
+                summary.index, timestamp, summary.protocol, summary.length
+            ),
+            Format::Fields => {
+                let mut values = fields.extract(frame.layers());
+                for (value, id) in values.iter_mut().zip(fields.ids()) {
+                    if id == TIMESTAMP_ATTR && !value.is_empty() {
+                        *value = timestamp.clone();
+                    }
+                }
+                writeln!(out, "{}", values.join(&opts.separator))
+            }
+            Format::Json => {
+                let entry = json!({
+                    "index": frame.index(),
+                    "layers": tree::tree(frame.layers(), frame.tree_indices()),
+                });
+                out.write_all(if count == 0 { b"\n" } else { b",\n" })
+                    .and_then(|_| Ok(serde_json::to_writer_pretty(&mut *out, &entry)?))
+            }
+        };
+        count += 1;
+        result.is_ok()
+    });
+    result?;
+    if opts.format == Format::Json {
+        writeln!(out, "\n]")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cli::{run, Format, Options};
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        context::Context,
+        error::Error,
+        file::FileType,
+        fixed::Fixed,
+        layer::{Layer, LayerClass, LayerStack},
+        reader::{self, Reader, ReaderBox},
+        result::Result,
+        slice::ByteSlice,
+        writer::{self, Writer, WriterBox},
+    };
+    use genet_kernel::profile::Profile;
+    use serde_json::{self, Value};
+    use std::sync::{Arc, Mutex};
+
+    struct TestReaderWorker {
+        count: u64,
+    }
+
+    impl reader::Worker for TestReaderWorker {
+        fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
+            if self.count == 0 {
+                return Err(Box::new(Error::new("end of stream")));
+            }
+            let layers = (0..self.count)
+                .map(|i| {
+                    let class = Fixed::new(LayerClass::builder("eth").build());
+                    let mut layer = Layer::new(class, ByteSlice::from(&b"abcd"[..]));
+                    let class = Fixed::new(AttrClass::builder("eth.len").build());
+                    layer.add_attr(Attr::builder(class).value(i).build());
+                    let class = Fixed::new(AttrClass::builder("link.timestamp").build());
+                    layer.add_attr(Attr::builder(class).value(i as f64).build());
+                    let class = Fixed::new(AttrClass::builder("link.timestamp.sec").build());
+                    layer.add_attr(Attr::builder(class).value(i as i64).build());
+                    layer
+                })
+                .collect();
+            self.count = 0;
+            Ok(layers)
+        }
+    }
+
+    struct TestReader {}
+
+    impl Reader for TestReader {
+        fn new_worker(&self, _ctx: &Context, _arg: &str) -> Result<Box<reader::Worker>> {
+            Ok(Box::new(TestReaderWorker { count: 5 }))
+        }
+
+        fn metadata(&self) -> reader::Metadata {
+            reader::Metadata {
+                id: "test-reader".to_string(),
+                filters: vec![FileType::new("Test", &["test"])],
+                ..reader::Metadata::default()
+            }
+        }
+    }
+
+    struct TestWriterWorker {
+        indices: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl writer::Worker for TestWriterWorker {
+        fn write(&mut self, index: u32, _stack: &LayerStack) -> Result<()> {
+            self.indices.lock().unwrap().push(index);
+            Ok(())
+        }
+    }
+
+    struct TestWriter {
+        indices: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl Writer for TestWriter {
+        fn new_worker(&self, _ctx: &Context, _arg: &str) -> Result<Box<writer::Worker>> {
+            Ok(Box::new(TestWriterWorker {
+                indices: self.indices.clone(),
+            }))
+        }
+
+        fn metadata(&self) -> writer::Metadata {
+            writer::Metadata {
+                id: "test-writer".to_string(),
+                filters: vec![FileType::new("Test", &["test"])],
+                ..writer::Metadata::default()
+            }
+        }
+    }
+
+    fn profile() -> Profile {
+        let mut profile = Profile::new();
+        profile.add_reader(ReaderBox::new(TestReader {}));
+        profile
+    }
+
+    fn options(format: Format) -> Options {
+        Options {
+            read: "capture.test".to_string(),
+            reader: None,
+            filter: Some("eth.len >= 3".to_string()),
+            wireshark: false,
+            format,
+            fields: vec!["eth.len".to_string(), "tcp.dst".to_string()],
+            separator: ",".to_string(),
+            header: true,
+            write: None,
+            writer: None,
+        }
+    }
+
+    fn output(profile: Profile, opts: &Options) -> String {
+        let mut out = Vec::new();
+        run(profile, opts, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn formats() {
+        let text = output(profile(), &options(Format::Text));
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.starts_with("     3 "));
+
+        let fields = output(profile(), &options(Format::Fields));
+        assert_eq!(fields, "eth.len,tcp.dst\n3,\n4,\n");
+
+        let json = output(profile(), &options(Format::Json));
+        let json: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json[1]["index"], 4);
+        assert_eq!(json[1]["layers"]["id"], "eth");
+        assert_eq!(json[1]["layers"]["attrs"][0]["value"], 4);
+    }
+
+    #[test]
+    fn timestamps() {
+        let mut profile = profile();
+        profile.set_config("_.timestamp.reference", "\"delta\"");
+        profile.set_config("_.timestamp.resolution", "\"ms\"");
+        let text = output(profile.clone(), &options(Format::Text));
+        assert_eq!(text, "     3 1.000 eth 4\n     4 1.000 eth 4\n");
+
+        profile.update_config("_.timestamp.reference", "\"relative\"");
+        let mut opts = options(Format::Fields);
+        opts.fields = vec!["link.timestamp".to_string(), "eth.len".to_string()];
+        assert_eq!(
+            output(profile, &opts),
+            "link.timestamp,eth.len\n3.000,3\n4.000,4\n"
+        );
+    }
+
+    #[test]
+    fn wireshark() {
+        let mut opts = options(Format::Fields);
+        opts.wireshark = true;
+        opts.filter = Some("eth.len ge 3 and not tcp".to_string());
+        assert_eq!(output(profile(), &opts), "eth.len,tcp.dst\n3,\n4,\n");
+    }
+
+    #[test]
+    fn write() {
+        let indices = Arc::new(Mutex::new(Vec::new()));
+        let mut profile = profile();
+        profile.add_writer(WriterBox::new(TestWriter {
+            indices: indices.clone(),
+        }));
+        let mut opts = options(Format::Text);
+        opts.write = Some("filtered.test".to_string());
+        assert_eq!(output(profile, &opts), "");
+        assert_eq!(*indices.lock().unwrap(), vec![3, 4]);
+    }
+
+    #[test]
+    fn errors() {
+        let mut opts = options(Format::Text);
+        opts.read = "capture.pcap".to_string();
+        assert_eq!(
+            run(profile(), &opts, &mut Vec::new()),
+            Err("capture.pcap: no reader found".to_string())
+        );
+
+        let mut opts = options(Format::Text);
+        opts.filter = Some("eth.len >".to_string());
+        assert!(run(profile(), &opts, &mut Vec::new()).is_err());
+
+        let mut opts = options(Format::Text);
+        opts.wireshark = true;
+        opts.filter = Some("eth.len & 1".to_string());
+        assert!(run(profile(), &opts, &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! A command-line frontend for genet sessions.
+//!
+//! ```text
+//! $ genet-cli -l libpcap_file.so -l libeth.so -r dump.pcap -Y "tcp.dst == 443" \
+//!     -T fields -e ipv4.src -e tcp.src
+//! $ genet-cli -l libpcap_file.so -r dump.pcap -Y "udp" -w udp.pcap
+//! ```
+
+extern crate clap;
+extern crate genet_abi;
+extern crate genet_filter;
+extern crate genet_kernel;
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
+
+mod cli;
+
+use clap::{App, Arg};
+use cli::{Format, Options};
+use genet_kernel::{profile::Profile, signature::Verification};
+use std::{io, process};
+
+fn main() {
+    let matches = App::new("genet-cli")
+        .about("Reads, filters and writes captures with genet")
+        .arg(
+            Arg::with_name("read")
+                .short("r")
+                .long("read")
+                .value_name("FILE")
+                .required(true)
+                .help("Reads frames from FILE"),
+        )
+        .arg(
+            Arg::with_name("reader")
+                .long("reader")
+                .value_name("ID")
+                .help("Uses the reader ID instead of one chosen by the file extension"),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .short("Y")
+                .long("filter")
+                .value_name("FILTER")
+                .help("Selects the frames matching the display filter"),
+        )
+        .arg(
+            Arg::with_name("wireshark")
+                .long("wireshark")
+                .requires("filter")
+                .help("Reads the display filter in the Wireshark syntax"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .short("T")
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(&["text", "fields", "json"])
+                .default_value("text")
+                .help("Prints a summary line, the selected fields or the layer tree of frames"),
+        )
+        .arg(
+            Arg::with_name("field")
+                .short("e")
+                .long("field")
+                .value_name("ID")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Adds an attribute to print with -T fields"),
+        )
+        .arg(
+            Arg::with_name("separator")
+                .long("separator")
+                .value_name("SEP")
+                .default_value("\t")
+                .help("Separates the fields"),
+        )
+        .arg(
+            Arg::with_name("header")
+                .long("header")
+                .help("Prints the field IDs first"),
+        )
+        .arg(
+            Arg::with_name("write")
+                .short("w")
+                .long("write")
+                .value_name("FILE")
+                .help("Writes the frames to FILE instead of printing them"),
+        )
+        .arg(
+            Arg::with_name("writer")
+                .long("writer")
+                .value_name("ID")
+                .help("Uses the writer ID instead of one chosen by the file extension"),
+        )
+        .arg(
+            Arg::with_name("library")
+                .short("l")
+                .long("library")
+                .value_name("PATH")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Loads a plugin library"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .short("o")
+                .long("config")
+                .value_name("KEY=VALUE")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Sets a config value in JSON"),
+        )
+        .get_matches();
+
+    let mut profile = Profile::new();
+    for path in matches.values_of("library").into_iter().flatten() {
+        match profile.load_library(path) {
+            Ok(Verification::Untrusted(reason)) => eprintln!("{}: untrusted: {}", path, reason),
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+    for config in matches.values_of("config").into_iter().flatten() {
+        match config.find('=') {
+            Some(pos) => profile.set_config(&config[..pos], &config[pos + 1..]),
+            None => {
+                eprintln!("{}: expected KEY=VALUE", config);
+                process::exit(1);
+            }
+        }
+    }
+
+    let format = match matches.value_of("format") {
+        Some("fields") => Format::Fields,
+        Some("json") => Format::Json,
+        _ => Format::Text,
+    };
+    let fields = matches
+        .values_of("field")
+        .into_iter()
+        .flatten()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>();
+    if format == Format::Fields && fields.is_empty() {
+        eprintln!("-T fields requires at least one -e");
+        process::exit(1);
+    }
+    let opts = Options {
+        read: matches.value_of("read").unwrap().to_string(),
+        reader: matches.value_of("reader").map(|id| id.to_string()),
+        filter: matches.value_of("filter").map(|filter| filter.to_string()),
+        wireshark: matches.is_present("wireshark"),
+        format,
+        fields,
+        separator: matches.value_of("separator").unwrap().to_string(),
+        header: matches.is_present("header"),
+        write: matches.value_of("write").map(|path| path.to_string()),
+        writer: matches.value_of("writer").map(|id| id.to_string()),
+    };
+
+    let stdout = io::stdout();
+    if let Err(err) = cli::run(profile, &opts, &mut stdout.lock()) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
@@ -1,5 +1,9 @@
 use context::Context;
+use functions::{self, Function};
 use genet_abi::{token::Token, variant::Variant};
+use pattern::{self, Pattern};
+use protocols::Protocols;
+use set::Set;
 use variant::VariantExt;
 
 #[derive(PartialEq, Clone, Debug)]
@@ -7,12 +11,24 @@ pub enum Expr {
     Literal(Variant),
     Token(Token),
     Macro(String),
+    Protocols,
+    Marked,
+    Call(Function, Vec<Expr>),
+    Count(Token),
+    Slice(Box<Expr>, Option<i64>, Option<i64>),
+    Index(Box<Expr>, i64),
     CmpEq(Box<Expr>, Box<Expr>),
     CmpNotEq(Box<Expr>, Box<Expr>),
     CmpLt(Box<Expr>, Box<Expr>),
     CmpGt(Box<Expr>, Box<Expr>),
     CmpLte(Box<Expr>, Box<Expr>),
     CmpGte(Box<Expr>, Box<Expr>),
+    Contains(Box<Expr>, Box<Expr>),
+    ContainsIgnoreCase(Box<Expr>, Box<Expr>),
+    StartsWith(Box<Expr>, Box<Expr>),
+    StartsWithIgnoreCase(Box<Expr>, Box<Expr>),
+    Matches(Box<Expr>, Pattern),
+    In(Box<Expr>, Set),
     LogicalAnd(Box<Expr>, Box<Expr>),
     LogicalOr(Box<Expr>, Box<Expr>),
     LogicalNegation(Box<Expr>),
@@ -24,12 +40,43 @@ impl Expr {
     pub fn eval(&self, ctx: &Context) -> Variant {
         match self {
             Expr::Literal(v) => v.clone(),
+            Expr::Call(f, args) => {
+                let args = args.iter().map(|arg| arg.eval(ctx)).collect::<Vec<_>>();
+                f.call(&args)
+            }
+            Expr::Count(t) => {
+                let count = ctx
+                    .layers()
+                    .iter()
+                    .map(|layer| {
+                        let attrs = layer.attrs_iter().filter(|a| a.id() == *t).count();
+                        attrs + if layer.id() == *t { 1 } else { 0 }
+                    })
+                    .sum::<usize>();
+                Variant::UInt64(count as u64)
+            }
+            Expr::Slice(v, start, end) => functions::slice(&v.eval(ctx), *start, *end),
+            Expr::Index(v, index) => functions::index(&v.eval(ctx), *index),
             Expr::CmpEq(l, r) => Variant::Bool(l.eval(ctx).op_eq(&r.eval(ctx))),
             Expr::CmpNotEq(l, r) => Variant::Bool(!l.eval(ctx).op_eq(&r.eval(ctx))),
             Expr::CmpLt(l, r) => Variant::Bool(l.eval(ctx).op_lt(&r.eval(ctx))),
             Expr::CmpGt(l, r) => Variant::Bool(l.eval(ctx).op_gt(&r.eval(ctx))),
             Expr::CmpLte(l, r) => Variant::Bool(l.eval(ctx).op_lte(&r.eval(ctx))),
             Expr::CmpGte(l, r) => Variant::Bool(l.eval(ctx).op_gte(&r.eval(ctx))),
+            Expr::Contains(l, r) => {
+                Variant::Bool(pattern::contains(&l.eval(ctx), &r.eval(ctx), false))
+            }
+            Expr::ContainsIgnoreCase(l, r) => {
+                Variant::Bool(pattern::contains(&l.eval(ctx), &r.eval(ctx), true))
+            }
+            Expr::StartsWith(l, r) => {
+                Variant::Bool(pattern::starts_with(&l.eval(ctx), &r.eval(ctx), false))
+            }
+            Expr::StartsWithIgnoreCase(l, r) => {
+                Variant::Bool(pattern::starts_with(&l.eval(ctx), &r.eval(ctx), true))
+            }
+            Expr::Matches(v, p) => Variant::Bool(p.is_match(&v.eval(ctx))),
+            Expr::In(v, set) => Variant::Bool(set.contains(&v.eval(ctx))),
             Expr::LogicalAnd(l, r) => {
                 Variant::Bool(l.eval(ctx).is_truthy() && r.eval(ctx).is_truthy())
             }
@@ -39,17 +86,25 @@ impl Expr {
             Expr::LogicalNegation(v) => Variant::Bool(!v.eval(ctx).is_truthy()),
             Expr::UnaryPlus(v) => v.eval(ctx).op_unary_plus(),
             Expr::UnaryNegation(v) => v.eval(ctx).op_unary_negation(),
+            Expr::Protocols => {
+                let path = match ctx.protocols() {
+                    Some(protocols) => protocols.path().to_string(),
+                    None => Protocols::new(ctx.layers()).path().to_string(),
+                };
+                Variant::String(path.into_boxed_str())
+            }
+            Expr::Marked => Variant::Bool(ctx.marked()),
             Expr::Token(t) => {
+                if let Some(protocols) = ctx.protocols() {
+                    if protocols.contains(*t) {
+                        return Variant::Bool(true);
+                    }
+                }
                 for layer in ctx.layers().iter().rev() {
                     if layer.id() == *t {
                         return Variant::Bool(true);
                     }
-                    if let Some(attr) = layer
-                        .headers()
-                        .iter()
-                        .chain(layer.attrs().iter())
-                        .find(|a| a.id() == *t)
-                    {
+                    if let Some(attr) = layer.attrs_iter().find(|a| a.id() == *t) {
                         if let Ok(val) = attr.try_get(layer) {
                             return val;
                         }
@@ -60,4 +115,35 @@ impl Expr {
             Expr::Macro(_) => Variant::Nil,
         }
     }
+
+    /// Returns true if `f` returns true for self or any of its
+    /// subexpressions.
+    pub fn any<F: Fn(&Expr) -> bool>(&self, f: &F) -> bool {
+        if f(self) {
+            return true;
+        }
+        match self {
+            Expr::Call(_, args) => args.iter().any(|arg| arg.any(f)),
+            Expr::Slice(v, _, _)
+            | Expr::Index(v, _)
+            | Expr::Matches(v, _)
+            | Expr::In(v, _)
+            | Expr::LogicalNegation(v)
+            | Expr::UnaryPlus(v)
+            | Expr::UnaryNegation(v) => v.any(f),
+            Expr::CmpEq(l, r)
+            | Expr::CmpNotEq(l, r)
+            | Expr::CmpLt(l, r)
+            | Expr::CmpGt(l, r)
+            | Expr::CmpLte(l, r)
+            | Expr::CmpGte(l, r)
+            | Expr::Contains(l, r)
+            | Expr::ContainsIgnoreCase(l, r)
+            | Expr::StartsWith(l, r)
+            | Expr::StartsWithIgnoreCase(l, r)
+            | Expr::LogicalAnd(l, r)
+            | Expr::LogicalOr(l, r) => l.any(f) || r.any(f),
+            _ => false,
+        }
+    }
 }
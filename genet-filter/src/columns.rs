@@ -0,0 +1,363 @@
+//! Columnar evaluation of simple numeric filters.
+//!
+//! Filters which only compare a few attributes with unsigned integer
+//! literals (e.g. `tcp.dst == 80 && ipv4.src == 192.168.0.1`) are compiled
+//! into a `Plan`, which evaluates over columns of attribute values instead of
+//! walking the attribute tree of each frame. The comparisons run over fixed
+//! size lanes so that the compiler can vectorize them.
+
+use ast::Expr;
+use context::Context;
+use genet_abi::{token::Token, variant::Variant};
+use std::ops::Range;
+
+/// The maximum number of attributes referenced by a plan.
+pub const MAX_COLUMNS: usize = 4;
+
+const LANES: usize = 8;
+
+// Int64 cells are compared with literals as signed integers.
+const MAX_CONSTANT: u64 = 0x7fff_ffff_ffff_ffff;
+
+/// A column of the values of an attribute, one cell per frame.
+///
+/// Cells which are not representable as unsigned integers (e.g. strings) are
+/// recorded as unsupported, and ranges containing them are evaluated with the
+/// tree walk instead.
+#[derive(Clone, Debug, Default)]
+pub struct Column {
+    values: Vec<u64>,
+    present: Vec<u8>,
+    unsupported: Vec<usize>,
+}
+
+impl Column {
+    pub fn new() -> Column {
+        Column::default()
+    }
+
+    /// Appends the value of the attribute `id` in `ctx`.
+    pub fn push(&mut self, ctx: &Context, id: Token) {
+        let index = self.values.len();
+        let (value, present) = match Expr::Token(id).eval(ctx) {
+            Variant::Nil => (0, 0),
+            value => match cell(&value) {
+                Some(value) => (value, 1),
+                None => {
+                    self.unsupported.push(index);
+                    (0, 0)
+                }
+            },
+        };
+        self.values.push(value);
+        self.present.push(present);
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns true if all cells in `range` are supported.
+    pub fn is_supported(&self, range: &Range<usize>) -> bool {
+        let pos = match self.unsupported.binary_search(&range.start) {
+            Ok(_) => return false,
+            Err(pos) => pos,
+        };
+        match self.unsupported.get(pos) {
+            Some(index) => *index >= range.end,
+            None => true,
+        }
+    }
+}
+
+fn cell(value: &Variant) -> Option<u64> {
+    match value {
+        Variant::UInt64(v) => Some(*v),
+        Variant::Int64(v) if *v >= 0 => Some(*v as u64),
+        Variant::Slice(s) => bytes(s),
+        Variant::Buffer(b) => bytes(b),
+        _ => None,
+    }
+}
+
+fn bytes(data: &[u8]) -> Option<u64> {
+    if data.len() > 8 {
+        return None;
+    }
+    Some(data.iter().fold(0, |acc, b| acc << 8 | u64::from(*b)))
+}
+
+fn constant(value: &Variant) -> Option<u64> {
+    match value {
+        Variant::UInt64(v) if *v <= MAX_CONSTANT => Some(*v),
+        Variant::Buffer(b) => bytes(b).filter(|v| *v <= MAX_CONSTANT),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    NotEq,
+    Lt,
+    Gt,
+    Lte,
+    Gte,
+}
+
+impl Op {
+    fn flip(self) -> Op {
+        match self {
+            Op::Lt => Op::Gt,
+            Op::Gt => Op::Lt,
+            Op::Lte => Op::Gte,
+            Op::Gte => Op::Lte,
+            op => op,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Cmp(Op, usize, u64),
+    Present(usize),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+}
+
+/// A filter compiled for columnar evaluation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    tokens: Vec<Token>,
+    root: Node,
+}
+
+impl Plan {
+    /// Compiles `expr`, or returns `None` if it needs the tree walk.
+    pub fn new(expr: &Expr) -> Option<Plan> {
+        let mut tokens = Vec::new();
+        let mut cmps = 0;
+        let root = Self::compile(expr, &mut tokens, &mut cmps)?;
+        if cmps == 0 || tokens.len() > MAX_COLUMNS {
+            return None;
+        }
+        Some(Plan { tokens, root })
+    }
+
+    fn column(id: Token, tokens: &mut Vec<Token>) -> usize {
+        if let Some(index) = tokens.iter().position(|t| *t == id) {
+            index
+        } else {
+            tokens.push(id);
+            tokens.len() - 1
+        }
+    }
+
+    fn compile(expr: &Expr, tokens: &mut Vec<Token>, cmps: &mut usize) -> Option<Node> {
+        let (op, lhs, rhs) = match expr {
+            Expr::Token(id) => return Some(Node::Present(Self::column(*id, tokens))),
+            Expr::LogicalAnd(l, r) => {
+                return Some(Node::And(
+                    Box::new(Self::compile(l, tokens, cmps)?),
+                    Box::new(Self::compile(r, tokens, cmps)?),
+                ))
+            }
+            Expr::LogicalOr(l, r) => {
+                return Some(Node::Or(
+                    Box::new(Self::compile(l, tokens, cmps)?),
+                    Box::new(Self::compile(r, tokens, cmps)?),
+                ))
+            }
+            Expr::LogicalNegation(v) => {
+                return Some(Node::Not(Box::new(Self::compile(v, tokens, cmps)?)))
+            }
+            Expr::CmpEq(l, r) => (Op::Eq, l, r),
+            Expr::CmpNotEq(l, r) => (Op::NotEq, l, r),
+            Expr::CmpLt(l, r) => (Op::Lt, l, r),
+            Expr::CmpGt(l, r) => (Op::Gt, l, r),
+            Expr::CmpLte(l, r) => (Op::Lte, l, r),
+            Expr::CmpGte(l, r) => (Op::Gte, l, r),
+            _ => return None,
+        };
+        let (op, id, value) = match (lhs.as_ref(), rhs.as_ref()) {
+            (Expr::Token(id), Expr::Literal(value)) => (op, *id, value),
+            (Expr::Literal(value), Expr::Token(id)) => (op.flip(), *id, value),
+            _ => return None,
+        };
+        *cmps += 1;
+        Some(Node::Cmp(op, Self::column(id, tokens), constant(value)?))
+    }
+
+    /// Returns the attributes referenced by the plan, in column order.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Evaluates the plan over `range` of `columns`, which must be in the
+    /// order of `tokens()`. Returns `None` if the range has cells which need
+    /// the tree walk.
+    pub fn eval(&self, columns: &[&Column], range: Range<usize>) -> Option<Vec<bool>> {
+        if columns.len() != self.tokens.len()
+            || columns
+                .iter()
+                .any(|c| c.len() < range.end || !c.is_supported(&range))
+        {
+            return None;
+        }
+        let mask = Self::eval_node(&self.root, columns, &range);
+        Some(mask.into_iter().map(|v| v != 0).collect())
+    }
+
+    fn eval_node(node: &Node, columns: &[&Column], range: &Range<usize>) -> Vec<u8> {
+        match node {
+            Node::Cmp(op, index, value) => {
+                let column = columns[*index];
+                compare(
+                    *op,
+                    &column.values[range.clone()],
+                    &column.present[range.clone()],
+                    *value,
+                )
+            }
+            Node::Present(index) => columns[*index].present[range.clone()].to_vec(),
+            Node::And(l, r) => {
+                let mut mask = Self::eval_node(l, columns, range);
+                for (m, r) in mask.iter_mut().zip(Self::eval_node(r, columns, range)) {
+                    *m &= r;
+                }
+                mask
+            }
+            Node::Or(l, r) => {
+                let mut mask = Self::eval_node(l, columns, range);
+                for (m, r) in mask.iter_mut().zip(Self::eval_node(r, columns, range)) {
+                    *m |= r;
+                }
+                mask
+            }
+            Node::Not(v) => {
+                let mut mask = Self::eval_node(v, columns, range);
+                for m in &mut mask {
+                    *m ^= 1;
+                }
+                mask
+            }
+        }
+    }
+}
+
+fn compare(op: Op, values: &[u64], present: &[u8], value: u64) -> Vec<u8> {
+    let cmp: fn(u64, u64) -> bool = match op {
+        Op::Eq | Op::NotEq => |a, b| a == b,
+        Op::Lt => |a, b| a < b,
+        Op::Gt => |a, b| a > b,
+        Op::Lte => |a, b| a <= b,
+        Op::Gte => |a, b| a >= b,
+    };
+    let mut mask = vec![0u8; values.len()];
+    {
+        let mut lanes = mask.chunks_mut(LANES);
+        let mut values = values.chunks(LANES);
+        let mut present = present.chunks(LANES);
+        while let (Some(m), Some(v), Some(p)) = (lanes.next(), values.next(), present.next()) {
+            for i in 0..m.len() {
+                m[i] = p[i] & cmp(v[i], value) as u8;
+            }
+        }
+    }
+    // A missing value is not equal to anything.
+    if op == Op::NotEq {
+        for m in &mut mask {
+            *m ^= 1;
+        }
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use columns::{Column, Plan};
+    use context::Context;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        token::Token,
+    };
+    use parser::parse;
+    use variant::VariantExt;
+
+    fn layer(port: Option<u64>, name: Option<&'static str>) -> MutFixed<Layer> {
+        let class = Fixed::new(LayerClass::builder("tcp").build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        if let Some(port) = port {
+            let attr = Fixed::new(AttrClass::builder("tcp.dst").build());
+            layer.add_attr(Attr::builder(attr).value(port).build());
+        }
+        if let Some(name) = name {
+            let attr = Fixed::new(AttrClass::builder("tcp.name").build());
+            let name = name.to_string().into_boxed_str();
+            layer.add_attr(Attr::builder(attr).value(name).build());
+        }
+        MutFixed::new(layer)
+    }
+
+    #[test]
+    fn plan() {
+        assert!(Plan::new(&parse("tcp.dst == 80").unwrap()).is_some());
+        assert!(Plan::new(&parse("(80 < tcp.dst) || !tcp.src").unwrap()).is_some());
+        assert!(Plan::new(&parse("tcp").unwrap()).is_none());
+        assert!(Plan::new(&parse("tcp.dst == \"80\"").unwrap()).is_none());
+        assert!(Plan::new(&parse("tcp.dst == tcp.src").unwrap()).is_none());
+        assert!(Plan::new(
+            &parse("(a == 1) && (b == 1) && (c == 1) && (d == 1) && (e == 1)").unwrap()
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn eval() {
+        let frames = vec![
+            vec![layer(Some(80), None)],
+            vec![layer(Some(443), None)],
+            vec![layer(None, None)],
+            vec![layer(Some(8080), Some("http"))],
+        ];
+        let filters = [
+            "tcp.dst == 80",
+            "tcp.dst != 80",
+            "tcp.dst > 80",
+            "443 >= tcp.dst",
+            "!(tcp.dst < 443) || (tcp.dst == 80)",
+            "tcp.dst && (tcp.dst <= 8080)",
+        ];
+        let id = Token::from("tcp.dst");
+        let mut column = Column::new();
+        for layers in &frames {
+            column.push(&Context::new(layers), id);
+        }
+        for filter in filters.iter() {
+            let expr = parse(filter).unwrap();
+            let plan = Plan::new(&expr).unwrap();
+            let expected = frames
+                .iter()
+                .map(|layers| expr.eval(&Context::new(layers)).is_truthy())
+                .collect::<Vec<_>>();
+            assert_eq!(plan.eval(&[&column], 0..frames.len()), Some(expected));
+        }
+
+        let id = Token::from("tcp.name");
+        let mut column = Column::new();
+        for layers in &frames {
+            column.push(&Context::new(layers), id);
+        }
+        let plan = Plan::new(&parse("tcp.name == 1").unwrap()).unwrap();
+        assert!(plan.eval(&[&column], 0..3).is_some());
+        assert!(plan.eval(&[&column], 0..4).is_none());
+    }
+}
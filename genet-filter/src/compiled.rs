@@ -0,0 +1,259 @@
+//! Compilation of filter expressions into closures.
+//!
+//! `Expr::eval` walks the AST for each frame, cloning literals and boxing
+//! intermediate booleans into variants. A compiled filter lowers the AST
+//! once into nested closures: constant subexpressions are folded, literal
+//! operands are captured by the comparisons, and logical operators return
+//! plain booleans.
+
+use ast::Expr;
+use context::Context;
+use functions;
+use genet_abi::variant::Variant;
+use pattern;
+use std::{fmt, sync::Arc};
+use variant::VariantExt;
+
+type ValueFn = Box<Fn(&Context) -> Variant + Send + Sync>;
+type PredFn = Box<Fn(&Context) -> bool + Send + Sync>;
+type OpFn = fn(&Variant, &Variant) -> bool;
+
+enum Value {
+    Const(Variant),
+    Fn(ValueFn),
+}
+
+enum Pred {
+    Const(bool),
+    Fn(PredFn),
+}
+
+/// A filter expression compiled into a predicate.
+#[derive(Clone)]
+pub struct Compiled {
+    pred: Arc<Fn(&Context) -> bool + Send + Sync>,
+}
+
+impl fmt::Debug for Compiled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Compiled")
+    }
+}
+
+impl Compiled {
+    pub fn new(expr: &Expr) -> Compiled {
+        let pred: Arc<Fn(&Context) -> bool + Send + Sync> = match pred(expr) {
+            Pred::Const(b) => Arc::new(move |_| b),
+            Pred::Fn(f) => Arc::from(f),
+        };
+        Compiled { pred }
+    }
+
+    /// Returns true if the frame in `ctx` matches the filter.
+    pub fn test(&self, ctx: &Context) -> bool {
+        (self.pred)(ctx)
+    }
+}
+
+fn value(expr: &Expr) -> Value {
+    match expr {
+        Expr::Literal(v) => Value::Const(v.clone()),
+        Expr::Macro(_) => Value::Const(Variant::Nil),
+        Expr::Token(id) => {
+            let id = *id;
+            Value::Fn(Box::new(move |ctx| Expr::Token(id).eval(ctx)))
+        }
+        Expr::Protocols => Value::Fn(Box::new(|ctx| Expr::Protocols.eval(ctx))),
+        Expr::Marked => Value::Fn(Box::new(|ctx| Variant::Bool(ctx.marked()))),
+        Expr::Count(id) => {
+            let id = *id;
+            Value::Fn(Box::new(move |ctx| Expr::Count(id).eval(ctx)))
+        }
+        Expr::Call(f, args) => {
+            let args = args.iter().map(value).collect::<Vec<_>>();
+            if args.iter().all(|arg| match arg {
+                Value::Const(_) => true,
+                Value::Fn(_) => false,
+            }) {
+                let args = args
+                    .into_iter()
+                    .map(|arg| match arg {
+                        Value::Const(v) => v,
+                        Value::Fn(_) => unreachable!(),
+                    })
+                    .collect::<Vec<_>>();
+                return Value::Const(f.call(&args));
+            }
+            let f = f.clone();
+            Value::Fn(Box::new(move |ctx| {
+                let args = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Value::Const(v) => v.clone(),
+                        Value::Fn(f) => f(ctx),
+                    })
+                    .collect::<Vec<_>>();
+                f.call(&args)
+            }))
+        }
+        Expr::Slice(v, start, end) => {
+            let (start, end) = (*start, *end);
+            match value(v) {
+                Value::Const(v) => Value::Const(functions::slice(&v, start, end)),
+                Value::Fn(f) => {
+                    Value::Fn(Box::new(move |ctx| functions::slice(&f(ctx), start, end)))
+                }
+            }
+        }
+        Expr::Index(v, index) => {
+            let index = *index;
+            match value(v) {
+                Value::Const(v) => Value::Const(functions::index(&v, index)),
+                Value::Fn(f) => Value::Fn(Box::new(move |ctx| functions::index(&f(ctx), index))),
+            }
+        }
+        Expr::UnaryPlus(v) => match value(v) {
+            Value::Const(v) => Value::Const(v.op_unary_plus()),
+            Value::Fn(f) => Value::Fn(Box::new(move |ctx| f(ctx).op_unary_plus())),
+        },
+        Expr::UnaryNegation(v) => match value(v) {
+            Value::Const(v) => Value::Const(v.op_unary_negation()),
+            Value::Fn(f) => Value::Fn(Box::new(move |ctx| f(ctx).op_unary_negation())),
+        },
+        _ => match pred(expr) {
+            Pred::Const(b) => Value::Const(Variant::Bool(b)),
+            Pred::Fn(f) => Value::Fn(Box::new(move |ctx| Variant::Bool(f(ctx)))),
+        },
+    }
+}
+
+fn pred(expr: &Expr) -> Pred {
+    match expr {
+        Expr::CmpEq(l, r) => cmp(l, r, |a, b| a.op_eq(b)),
+        Expr::CmpNotEq(l, r) => cmp(l, r, |a, b| !a.op_eq(b)),
+        Expr::CmpLt(l, r) => cmp(l, r, |a, b| a.op_lt(b)),
+        Expr::CmpGt(l, r) => cmp(l, r, |a, b| a.op_gt(b)),
+        Expr::CmpLte(l, r) => cmp(l, r, |a, b| a.op_lte(b)),
+        Expr::CmpGte(l, r) => cmp(l, r, |a, b| a.op_gte(b)),
+        Expr::Contains(l, r) => cmp(l, r, |a, b| pattern::contains(a, b, false)),
+        Expr::ContainsIgnoreCase(l, r) => cmp(l, r, |a, b| pattern::contains(a, b, true)),
+        Expr::StartsWith(l, r) => cmp(l, r, |a, b| pattern::starts_with(a, b, false)),
+        Expr::StartsWithIgnoreCase(l, r) => cmp(l, r, |a, b| pattern::starts_with(a, b, true)),
+        Expr::Matches(v, p) => match value(v) {
+            Value::Const(v) => Pred::Const(p.is_match(&v)),
+            Value::Fn(f) => {
+                let p = p.clone();
+                Pred::Fn(Box::new(move |ctx| p.is_match(&f(ctx))))
+            }
+        },
+        Expr::In(v, set) => match value(v) {
+            Value::Const(v) => Pred::Const(set.contains(&v)),
+            Value::Fn(f) => {
+                let set = set.clone();
+                Pred::Fn(Box::new(move |ctx| set.contains(&f(ctx))))
+            }
+        },
+        Expr::LogicalAnd(l, r) => match (pred(l), pred(r)) {
+            (Pred::Const(false), _) | (_, Pred::Const(false)) => Pred::Const(false),
+            (Pred::Const(true), p) | (p, Pred::Const(true)) => p,
+            (Pred::Fn(l), Pred::Fn(r)) => Pred::Fn(Box::new(move |ctx| l(ctx) && r(ctx))),
+        },
+        Expr::LogicalOr(l, r) => match (pred(l), pred(r)) {
+            (Pred::Const(true), _) | (_, Pred::Const(true)) => Pred::Const(true),
+            (Pred::Const(false), p) | (p, Pred::Const(false)) => p,
+            (Pred::Fn(l), Pred::Fn(r)) => Pred::Fn(Box::new(move |ctx| l(ctx) || r(ctx))),
+        },
+        Expr::LogicalNegation(v) => match pred(v) {
+            Pred::Const(b) => Pred::Const(!b),
+            Pred::Fn(f) => Pred::Fn(Box::new(move |ctx| !f(ctx))),
+        },
+        _ => match value(expr) {
+            Value::Const(v) => Pred::Const(v.is_truthy()),
+            Value::Fn(f) => Pred::Fn(Box::new(move |ctx| f(ctx).is_truthy())),
+        },
+    }
+}
+
+fn cmp(lhs: &Expr, rhs: &Expr, op: OpFn) -> Pred {
+    match (value(lhs), value(rhs)) {
+        (Value::Const(l), Value::Const(r)) => Pred::Const(op(&l, &r)),
+        (Value::Fn(l), Value::Const(r)) => Pred::Fn(Box::new(move |ctx| op(&l(ctx), &r))),
+        (Value::Const(l), Value::Fn(r)) => Pred::Fn(Box::new(move |ctx| op(&l, &r(ctx)))),
+        (Value::Fn(l), Value::Fn(r)) => Pred::Fn(Box::new(move |ctx| op(&l(ctx), &r(ctx)))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use compiled::Compiled;
+    use context::Context;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+    };
+    use parser::parse;
+    use variant::VariantExt;
+
+    fn layer(port: u64, name: &'static str) -> MutFixed<Layer> {
+        let class = Fixed::new(LayerClass::builder("tcp").build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        let attr = Fixed::new(AttrClass::builder("tcp.dst").build());
+        layer.add_attr(Attr::builder(attr).value(port).build());
+        let attr = Fixed::new(AttrClass::builder("tcp.name").build());
+        let name = name.to_string().into_boxed_str();
+        layer.add_attr(Attr::builder(attr).value(name).build());
+        MutFixed::new(layer)
+    }
+
+    #[test]
+    fn compile() {
+        let frames = vec![
+            vec![layer(80, "http")],
+            vec![layer(443, "https")],
+            vec![layer(8080, "http")],
+        ];
+        let filters = [
+            "tcp",
+            "udp",
+            "!tcp",
+            "tcp.dst == 80",
+            "80 < tcp.dst",
+            "tcp.name == \"http\"",
+            "(tcp.dst >= 443) && (tcp.name != \"http\")",
+            "(1 == 2) || (tcp.dst == 443)",
+            "(1 == 1) || udp",
+            "udp && (1 == 1)",
+            "-tcp.dst",
+            "(-1 < 0) && tcp.dst",
+            "frame.protocols == \"tcp\"",
+            "tcp.name contains \"tt\"",
+            "tcp.name istarts_with \"HTTP\"",
+            "tcp.name ~ \"^h.*s$\"",
+            "\"http\" ~ \"p$\"",
+            "tcp.dst in {80, 8000..8999}",
+            "!tcp.dst in {443}",
+            "8080 in {80, 8000..8999}",
+            "len(tcp.name) > 4",
+            "upper(tcp.name) == \"HTTP\"",
+            "len(\"abc\") == 3",
+            "tcp.name[1:3] == \"tt\"",
+            "tcp.name[-1] == \"s\"",
+            "count(tcp.dst) == 1",
+        ];
+        for filter in filters.iter() {
+            let expr = parse(filter).unwrap();
+            let compiled = Compiled::new(&expr);
+            for layers in &frames {
+                let ctx = Context::new(layers);
+                assert_eq!(
+                    compiled.test(&ctx),
+                    expr.eval(&ctx).is_truthy(),
+                    "{}",
+                    filter
+                );
+            }
+        }
+    }
+}
@@ -1,15 +1,45 @@
 use genet_abi::{fixed::MutFixed, layer::Layer};
+use protocols::Protocols;
 
 pub struct Context<'a> {
     layers: &'a [MutFixed<Layer>],
+    protocols: Option<&'a Protocols>,
+    marked: bool,
 }
 
 impl<'a> Context<'a> {
     pub fn new(layers: &'a [MutFixed<Layer>]) -> Self {
-        Context { layers }
+        Context {
+            layers,
+            protocols: None,
+            marked: false,
+        }
+    }
+
+    pub fn with_protocols(layers: &'a [MutFixed<Layer>], protocols: &'a Protocols) -> Self {
+        Context {
+            layers,
+            protocols: Some(protocols),
+            marked: false,
+        }
+    }
+
+    /// Sets whether the frame is marked by the user, which `frame.marked`
+    /// evaluates to.
+    pub fn with_marked(mut self, marked: bool) -> Self {
+        self.marked = marked;
+        self
     }
 
     pub fn layers(&self) -> &'a [MutFixed<Layer>] {
         self.layers
     }
+
+    pub fn protocols(&self) -> Option<&'a Protocols> {
+        self.protocols
+    }
+
+    pub fn marked(&self) -> bool {
+        self.marked
+    }
 }
@@ -0,0 +1,224 @@
+//! Functions callable from filter expressions.
+//!
+//! Functions are looked up by name in a global registry when a filter is
+//! parsed. The registry contains the built-in functions `len`, `upper` and
+//! `lower`, and the host can extend it with `register`.
+
+use genet_abi::{slice::TryGet, variant::Variant};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+type FunctionFn = Fn(&[Variant]) -> Variant + Send + Sync;
+
+/// A function of a fixed number of arguments.
+#[derive(Clone)]
+pub struct Function {
+    name: String,
+    args: usize,
+    func: Arc<FunctionFn>,
+}
+
+impl Function {
+    pub fn new<F>(name: &str, args: usize, func: F) -> Function
+    where
+        F: 'static + Fn(&[Variant]) -> Variant + Send + Sync,
+    {
+        Function {
+            name: name.to_string(),
+            args,
+            func: Arc::new(func),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of arguments.
+    pub fn args(&self) -> usize {
+        self.args
+    }
+
+    pub fn call(&self, args: &[Variant]) -> Variant {
+        (self.func)(args)
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Function({:?})", self.name)
+    }
+}
+
+lazy_static! {
+    static ref REGISTRY: RwLock<HashMap<String, Function>> = {
+        let mut functions = HashMap::new();
+        for func in builtins() {
+            functions.insert(func.name.clone(), func);
+        }
+        RwLock::new(functions)
+    };
+}
+
+/// Registers a function, replacing a function of the same name.
+///
+/// Filters parsed before the registration are not affected.
+pub fn register(func: Function) {
+    REGISTRY.write().unwrap().insert(func.name.clone(), func);
+}
+
+/// Returns the function registered as `name`.
+pub fn get(name: &str) -> Option<Function> {
+    REGISTRY.read().unwrap().get(name).cloned()
+}
+
+fn builtins() -> Vec<Function> {
+    vec![
+        Function::new("len", 1, |args| match &args[0] {
+            Variant::String(s) => Variant::UInt64(s.chars().count() as u64),
+            Variant::Buffer(b) => Variant::UInt64(b.len() as u64),
+            Variant::Slice(s) => Variant::UInt64(s.len() as u64),
+            _ => Variant::Nil,
+        }),
+        Function::new("upper", 1, |args| match &args[0] {
+            Variant::String(s) => Variant::String(s.to_uppercase().into_boxed_str()),
+            _ => Variant::Nil,
+        }),
+        Function::new("lower", 1, |args| match &args[0] {
+            Variant::String(s) => Variant::String(s.to_lowercase().into_boxed_str()),
+            _ => Variant::Nil,
+        }),
+    ]
+}
+
+/// Resolves an index counted from the end if negative.
+fn resolve(index: i64, len: usize) -> usize {
+    if index < 0 {
+        len.saturating_sub(index.unsigned_abs() as usize)
+    } else {
+        (index as usize).min(len)
+    }
+}
+
+/// Returns the bytes or characters of `value` in `start..end`.
+///
+/// Negative indices count from the end, and out of range indices are
+/// clamped as in Python.
+pub fn slice(value: &Variant, start: Option<i64>, end: Option<i64>) -> Variant {
+    let range = |len| {
+        let start = start.map_or(0, |i| resolve(i, len));
+        let end = end.map_or(len, |i| resolve(i, len));
+        start..end.max(start)
+    };
+    match value {
+        Variant::Slice(s) => s
+            .try_get(range(s.len()))
+            .map(Variant::Slice)
+            .unwrap_or(Variant::Nil),
+        Variant::Buffer(b) => Variant::Buffer(b[range(b.len())].to_vec().into_boxed_slice()),
+        Variant::String(s) => {
+            let chars = s.chars().collect::<Vec<_>>();
+            let s = chars[range(chars.len())].iter().collect::<String>();
+            Variant::String(s.into_boxed_str())
+        }
+        _ => Variant::Nil,
+    }
+}
+
+/// Returns the byte or character of `value` at `index`.
+pub fn index(value: &Variant, index: i64) -> Variant {
+    let resolve = |len: usize| {
+        if index < 0 {
+            len.checked_sub(index.unsigned_abs() as usize)
+        } else {
+            Some(index as usize).filter(|&i| i < len)
+        }
+    };
+    match value {
+        Variant::Slice(s) => resolve(s.len())
+            .and_then(|i| s.try_get(i).ok())
+            .map_or(Variant::Nil, |b| Variant::UInt64(u64::from(b))),
+        Variant::Buffer(b) => {
+            resolve(b.len()).map_or(Variant::Nil, |i| Variant::UInt64(u64::from(b[i])))
+        }
+        Variant::String(s) => resolve(s.chars().count())
+            .and_then(|i| s.chars().nth(i))
+            .map_or(Variant::Nil, |c| {
+                Variant::String(c.to_string().into_boxed_str())
+            }),
+        _ => Variant::Nil,
+    }
+}
+
+/// Returns a byte slice literal, e.g. `16:03:01`.
+pub fn bytes(s: &str) -> Variant {
+    let bytes = s
+        .split(':')
+        .filter_map(|b| u8::from_str_radix(b, 16).ok())
+        .collect::<Vec<_>>();
+    Variant::Buffer(bytes.into_boxed_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use functions::{self, index, slice, Function};
+    use genet_abi::{slice::ByteSlice, variant::Variant};
+
+    fn string(s: &str) -> Variant {
+        Variant::String(s.to_string().into_boxed_str())
+    }
+
+    #[test]
+    fn call() {
+        let len = functions::get("len").unwrap();
+        assert_eq!(len.call(&[string("日本語")]), Variant::UInt64(3));
+        assert_eq!(len.call(&[Variant::Nil]), Variant::Nil);
+        let upper = functions::get("upper").unwrap();
+        assert_eq!(upper.call(&[string("example")]), string("EXAMPLE"));
+        assert!(functions::get("twice").is_none());
+
+        functions::register(Function::new("twice", 1, |args| match args[0] {
+            Variant::UInt64(v) => Variant::UInt64(v * 2),
+            _ => Variant::Nil,
+        }));
+        let twice = functions::get("twice").unwrap();
+        assert_eq!(twice.call(&[Variant::UInt64(2)]), Variant::UInt64(4));
+    }
+
+    #[test]
+    fn slicing() {
+        let data = Variant::Slice(ByteSlice::from(&[0x16, 0x03, 0x01, 0x00, 0x2a][..]));
+        assert_eq!(
+            slice(&data, Some(0), Some(3)),
+            Variant::Slice(ByteSlice::from(&[0x16, 0x03, 0x01][..]))
+        );
+        assert_eq!(
+            slice(&data, Some(-2), None),
+            Variant::Slice(ByteSlice::from(&[0x00, 0x2a][..]))
+        );
+        assert_eq!(
+            slice(&data, Some(4), Some(2)),
+            Variant::Slice(ByteSlice::new())
+        );
+        assert_eq!(slice(&data, None, Some(100)), data);
+        assert_eq!(index(&data, 0), Variant::UInt64(0x16));
+        assert_eq!(index(&data, -1), Variant::UInt64(0x2a));
+        assert_eq!(index(&data, 5), Variant::Nil);
+        assert_eq!(index(&data, -6), Variant::Nil);
+        assert_eq!(
+            slice(&string("example"), Some(1), Some(-1)),
+            string("xampl")
+        );
+        assert_eq!(index(&string("example"), 2), string("a"));
+        assert_eq!(slice(&Variant::UInt64(1), None, None), Variant::Nil);
+    }
+}
@@ -3,6 +3,7 @@ extern crate hwaddr;
 extern crate num_bigint;
 extern crate num_traits;
 extern crate pest;
+extern crate regex;
 extern crate serde;
 extern crate serde_json;
 
@@ -12,35 +13,113 @@ extern crate pest_derive;
 #[macro_use]
 extern crate arrayref;
 
+#[macro_use]
+extern crate lazy_static;
+
 use ast::Expr;
+use columns::Plan;
+use compiled::Compiled;
 use context::Context;
-use parser::parse;
+use genet_abi::{timestamp::Zone, token::Token};
+use parser::parse_with_zone;
 use result::Result;
 use std::fmt;
-use variant::VariantExt;
 
 pub mod ast;
+pub mod columns;
+pub mod compiled;
 pub mod context;
+pub mod functions;
+pub mod macros;
 pub mod parser;
+pub mod pattern;
+pub mod protocols;
 pub mod result;
+pub mod set;
 pub mod unparser;
 pub mod variant;
+pub mod wireshark;
 
 #[derive(Clone, Debug)]
 pub struct Filter {
     expr: Expr,
+    // A bare protocol filter (e.g. `tcp`) is answered from the protocol bitmap.
+    protocol: Option<Token>,
+    plan: Option<Plan>,
+    compiled: Compiled,
 }
 
 impl Filter {
     pub fn compile(filter: &str) -> Result<Filter> {
-        match parse(filter) {
-            Ok(expr) => Ok(Filter { expr }),
+        Self::compile_with_zone(filter, Zone::Local)
+    }
+
+    pub fn compile_with_zone(filter: &str, zone: Zone) -> Result<Filter> {
+        match parse_with_zone(filter, zone) {
+            Ok(expr) => Ok(Filter::from_expr(expr)),
             Err(err) => Err(Box::new(Error(format!("{}", err)))),
         }
     }
 
+    /// Compiles a filter written in the Wireshark display filter syntax.
+    pub fn compile_wireshark(filter: &str) -> Result<Filter> {
+        match wireshark::parse(filter) {
+            Ok(expr) => Ok(Filter::from_expr(expr)),
+            Err(err) => Err(Box::new(Error(format!("{}", err)))),
+        }
+    }
+
+    fn from_expr(expr: Expr) -> Filter {
+        let protocol = match expr {
+            Expr::Token(id) if !id.to_string().contains('.') => Some(id),
+            _ => None,
+        };
+        let plan = Plan::new(&expr);
+        let compiled = Compiled::new(&expr);
+        Filter {
+            expr,
+            protocol,
+            plan,
+            compiled,
+        }
+    }
+
+    /// Splits the filter at the top-level `&&` operators.
+    ///
+    /// A frame matches the filter if and only if it matches every conjunct.
+    pub fn conjuncts(&self) -> Vec<Filter> {
+        fn flatten<'a>(expr: &'a Expr, exprs: &mut Vec<&'a Expr>) {
+            match expr {
+                Expr::LogicalAnd(lhs, rhs) => {
+                    flatten(lhs, exprs);
+                    flatten(rhs, exprs);
+                }
+                _ => exprs.push(expr),
+            }
+        }
+        let mut exprs = Vec::new();
+        flatten(&self.expr, &mut exprs);
+        exprs
+            .into_iter()
+            .map(|expr| Filter::from_expr(expr.clone()))
+            .collect()
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// Returns the columnar plan of the filter if it only compares a few
+    /// attributes with integer literals.
+    pub fn plan(&self) -> Option<&Plan> {
+        self.plan.as_ref()
+    }
+
     pub fn test(&self, ctx: &Context) -> bool {
-        self.expr.eval(ctx).is_truthy()
+        if let (Some(id), Some(protocols)) = (self.protocol, ctx.protocols()) {
+            return protocols.contains(id);
+        }
+        self.compiled.test(ctx)
     }
 }
 
@@ -58,3 +137,42 @@ impl fmt::Display for Error {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ast::Expr;
+    use context::Context;
+    use unparser::unparse;
+    use Filter;
+
+    #[test]
+    fn conjuncts() {
+        let filter = Filter::compile("tcp && (ipv4.src == 1 || udp) && tcp.dst == 80").unwrap();
+        let conjuncts = filter
+            .conjuncts()
+            .iter()
+            .map(|filter| unparse(filter.expr()))
+            .collect::<Vec<_>>();
+        assert_eq!(conjuncts.len(), 3);
+        assert_eq!(conjuncts[0], "tcp");
+        assert_eq!(
+            conjuncts[2],
+            unparse(Filter::compile("tcp.dst == 80").unwrap().expr())
+        );
+
+        let filter = Filter::compile("tcp || udp").unwrap();
+        assert_eq!(filter.conjuncts()[0].expr(), filter.expr());
+    }
+
+    #[test]
+    fn marked() {
+        let filter = Filter::compile("!frame.marked").unwrap();
+        assert!(filter.test(&Context::new(&[])));
+        assert!(!filter.test(&Context::new(&[]).with_marked(true)));
+        assert!(filter.expr().any(&|expr| *expr == Expr::Marked));
+        assert!(!Filter::compile("tcp && !(udp.dst == 53)")
+            .unwrap()
+            .expr()
+            .any(&|expr| *expr == Expr::Marked));
+    }
+}
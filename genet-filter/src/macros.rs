@@ -0,0 +1,150 @@
+//! User-defined filter macros.
+//!
+//! A macro names a filter expression, e.g. `$web := tcp.port in {80, 443}`,
+//! and is referenced as `$web` in other filters. References are expanded when
+//! a filter is parsed, so redefining a macro does not affect filters parsed
+//! before.
+
+use parser::parse;
+use serde_json;
+use std::{cell::RefCell, collections::BTreeMap, sync::RwLock};
+
+lazy_static! {
+    static ref REGISTRY: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+}
+
+thread_local! {
+    static EXPANDING: RefCell<Vec<String>> = RefCell::default();
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Defines a macro, replacing a macro of the same name.
+///
+/// Returns an error if `name` is not an identifier or `expr` is not a valid
+/// filter, including references to unknown or recursive macros.
+pub fn define(name: &str, expr: &str) -> Result<(), String> {
+    if !is_identifier(name) {
+        return Err(format!("invalid macro name: ${}", name));
+    }
+    expand(name, || parse(expr))
+        .ok_or_else(|| format!("recursive macro: ${}", name))?
+        .map_err(|err| format!("macro ${}: {}", name, err))?;
+    REGISTRY
+        .write()
+        .unwrap()
+        .insert(name.to_string(), expr.to_string());
+    Ok(())
+}
+
+/// Removes the macro `name`.
+pub fn undefine(name: &str) {
+    REGISTRY.write().unwrap().remove(name);
+}
+
+/// Returns the expression of the macro `name`.
+pub fn get(name: &str) -> Option<String> {
+    REGISTRY.read().unwrap().get(name).cloned()
+}
+
+/// Returns the macros sorted by name.
+pub fn list() -> Vec<(String, String)> {
+    REGISTRY
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(name, expr)| (name.clone(), expr.clone()))
+        .collect()
+}
+
+/// Parses a definition in the `$<name> := <expr>` form.
+pub fn parse_definition(def: &str) -> Option<(String, String)> {
+    let mut def = def.splitn(2, ":=");
+    let name = def.next()?.trim();
+    let expr = def.next()?.trim();
+    if !name.starts_with('$') || expr.is_empty() {
+        return None;
+    }
+    Some((name[1..].to_string(), expr.to_string()))
+}
+
+/// Replaces all macros with the JSON-encoded array of definitions in `value`.
+///
+/// Definitions are applied in order, so a macro can refer to the macros
+/// defined before it. Invalid definitions are skipped.
+pub fn load_config(value: &str) {
+    let entries: Vec<String> = serde_json::from_str(value).unwrap_or_default();
+    REGISTRY.write().unwrap().clear();
+    for (name, expr) in entries.iter().filter_map(|e| parse_definition(e)) {
+        let _ = define(&name, &expr);
+    }
+}
+
+/// Calls `f` while `name` is being expanded.
+///
+/// Returns None if `name` is already being expanded on this thread.
+pub(crate) fn expand<F, T>(name: &str, f: F) -> Option<T>
+where
+    F: FnOnce() -> T,
+{
+    let recursive = EXPANDING.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        if stack.iter().any(|n| n == name) {
+            true
+        } else {
+            stack.push(name.to_string());
+            false
+        }
+    });
+    if recursive {
+        return None;
+    }
+    let result = f();
+    EXPANDING.with(|stack| stack.borrow_mut().pop());
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use macros::{self, parse_definition};
+    use parser::parse;
+
+    #[test]
+    fn define() {
+        assert_eq!(
+            parse_definition("$web := tcp.port in {80, 443}"),
+            Some(("web".to_string(), "tcp.port in {80, 443}".to_string()))
+        );
+        assert_eq!(parse_definition("web := tcp"), None);
+        assert_eq!(parse_definition("$web :="), None);
+
+        macros::define("test_web", "tcp.port in {80, 443, 8080}").unwrap();
+        assert_eq!(
+            parse("$test_web && ipv4"),
+            parse("(tcp.port in {80, 443, 8080}) && ipv4")
+        );
+        macros::define("test_web_v4", "$test_web && ipv4").unwrap();
+        assert_eq!(
+            parse("!$test_web_v4"),
+            parse("!((tcp.port in {80, 443, 8080}) && ipv4)")
+        );
+
+        assert!(macros::define("test_loop", "$test_loop || tcp").is_err());
+        assert!(macros::define("test_unknown", "$test_missing").is_err());
+        assert!(macros::define("test bad", "tcp").is_err());
+        assert!(macros::define("test_bad", "tcp ==").is_err());
+        assert!(parse("$test_missing").is_err());
+
+        macros::undefine("test_web");
+        assert!(macros::get("test_web").is_none());
+        assert!(parse("$test_web_v4").is_err());
+    }
+}
@@ -1,15 +1,25 @@
 use ast::Expr;
-use genet_abi::{token::Token, variant::Variant};
+use context::Context;
+use functions;
+use genet_abi::{
+    expert::{Severity, SEVERITY_ATTR},
+    timestamp::{TimestampFormat, Zone},
+    token::Token,
+    variant::Variant,
+};
 use hwaddr::HwAddr;
+use macros;
 use num_bigint::BigInt;
 use num_traits::Num;
+use pattern::Pattern;
 use pest::{
-    error::Error,
+    error::{Error, ErrorVariant},
     iterators::Pair,
     prec_climber::{Assoc, Operator, PrecClimber},
     Parser,
 };
 use serde_json;
+use set::{Cidr, Member, Set};
 use std::net::{Ipv4Addr, Ipv6Addr};
 use variant::VariantExt;
 
@@ -18,16 +28,36 @@ use variant::VariantExt;
 pub struct FilterParser;
 
 pub fn parse(filter: &str) -> Result<Expr, Error<Rule>> {
-    let result = FilterParser::parse(Rule::filter, filter);
-    match result {
-        Ok(mut expr) => Ok(consume_expr(
-            expr.next().unwrap().into_inner().next().unwrap(),
+    parse_with_zone(filter, TimestampFormat::default().zone)
+}
+
+/// Parses a filter, interpreting datetime literals without an offset in `zone`.
+pub fn parse_with_zone(filter: &str, zone: Zone) -> Result<Expr, Error<Rule>> {
+    let format = TimestampFormat {
+        zone,
+        ..TimestampFormat::default()
+    };
+    parse_with_format(filter, &format)
+}
+
+fn parse_with_format(filter: &str, format: &TimestampFormat) -> Result<Expr, Error<Rule>> {
+    let mut expr = FilterParser::parse(Rule::filter, filter)?;
+    consume_expr(expr.next().unwrap().into_inner().next().unwrap(), format)
+}
+
+/// Compiles the right operand of a regex operator, which must be a string.
+fn consume_pattern(op: &Pair<Rule>, rhs: Expr) -> Result<Pattern, Error<Rule>> {
+    let error =
+        |message: String| Error::new_from_span(ErrorVariant::CustomError { message }, op.as_span());
+    match rhs {
+        Expr::Literal(Variant::String(pattern)) => Pattern::new(&pattern).map_err(error),
+        _ => Err(error(
+            "regular expression must be a string literal".to_string(),
         )),
-        Err(e) => Err(e),
     }
 }
 
-fn parse_macro(exp: String) -> Expr {
+fn parse_macro(exp: String, format: &TimestampFormat) -> Expr {
     if let Ok(addr) = exp.parse::<Ipv4Addr>() {
         return Expr::Literal(Variant::Buffer(addr.octets().to_vec().into_boxed_slice()));
     }
@@ -37,26 +67,51 @@ fn parse_macro(exp: String) -> Expr {
     if let Ok(addr) = exp.parse::<HwAddr>() {
         return Expr::Literal(Variant::Buffer(addr.octets().to_vec().into_boxed_slice()));
     }
+    if let Some(ts) = format.parse(&exp) {
+        return Expr::Literal(Variant::Float64(ts));
+    }
     Expr::Macro(exp)
 }
 
-fn consume_expr(pair: Pair<Rule>) -> Expr {
+/// Returns the seconds of a duration literal, e.g. `1.5s` or `200ms`.
+fn parse_duration(s: &str) -> f64 {
+    let pos = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let scale = match &s[pos..] {
+        "h" => 3600.0,
+        "m" => 60.0,
+        "ms" => 1e-3,
+        "us" => 1e-6,
+        "ns" => 1e-9,
+        _ => 1.0,
+    };
+    s[..pos].parse::<f64>().unwrap_or(0.0) * scale
+}
+
+fn consume_expr(pair: Pair<Rule>, format: &TimestampFormat) -> Result<Expr, Error<Rule>> {
     let cmp = Operator::new(Rule::op_lt, Assoc::Left)
         | Operator::new(Rule::op_lte, Assoc::Left)
         | Operator::new(Rule::op_gt, Assoc::Left)
         | Operator::new(Rule::op_gte, Assoc::Left);
+    // Operators are listed in the order of increasing precedence.
     let climber = PrecClimber::new(vec![
-        cmp,
-        Operator::new(Rule::op_eq, Assoc::Left) | Operator::new(Rule::op_ne, Assoc::Left),
-        Operator::new(Rule::op_logical_and, Assoc::Left),
         Operator::new(Rule::op_logical_or, Assoc::Left),
+        Operator::new(Rule::op_logical_and, Assoc::Left),
+        Operator::new(Rule::op_eq, Assoc::Left)
+            | Operator::new(Rule::op_ne, Assoc::Left)
+            | Operator::new(Rule::op_contains, Assoc::Left)
+            | Operator::new(Rule::op_icontains, Assoc::Left)
+            | Operator::new(Rule::op_starts_with, Assoc::Left)
+            | Operator::new(Rule::op_istarts_with, Assoc::Left)
+            | Operator::new(Rule::op_matches, Assoc::Left),
+        cmp,
     ]);
     let primary = |pair: Pair<Rule>| match pair.as_rule() {
-        Rule::primary => consume_primary(pair),
-        _ => Expr::Literal(Variant::Nil),
+        Rule::primary => consume_primary(pair, format),
+        _ => Ok(Expr::Literal(Variant::Nil)),
     };
-    let infix = |lhs: Expr, op: Pair<Rule>, rhs: Expr| -> Expr {
-        match op.as_rule() {
+    let infix = |lhs: Result<Expr, Error<Rule>>, op: Pair<Rule>, rhs: Result<Expr, Error<Rule>>| {
+        let (lhs, rhs) = resolve_severity(lhs?, rhs?);
+        Ok(match op.as_rule() {
             Rule::op_lt => Expr::CmpLt(Box::new(lhs), Box::new(rhs)),
             Rule::op_lte => Expr::CmpLte(Box::new(lhs), Box::new(rhs)),
             Rule::op_gt => Expr::CmpGt(Box::new(lhs), Box::new(rhs)),
@@ -65,48 +120,215 @@ fn consume_expr(pair: Pair<Rule>) -> Expr {
             Rule::op_ne => Expr::CmpNotEq(Box::new(lhs), Box::new(rhs)),
             Rule::op_logical_and => Expr::LogicalAnd(Box::new(lhs), Box::new(rhs)),
             Rule::op_logical_or => Expr::LogicalOr(Box::new(lhs), Box::new(rhs)),
+            Rule::op_contains => Expr::Contains(Box::new(lhs), Box::new(rhs)),
+            Rule::op_icontains => Expr::ContainsIgnoreCase(Box::new(lhs), Box::new(rhs)),
+            Rule::op_starts_with => Expr::StartsWith(Box::new(lhs), Box::new(rhs)),
+            Rule::op_istarts_with => Expr::StartsWithIgnoreCase(Box::new(lhs), Box::new(rhs)),
+            Rule::op_matches => Expr::Matches(Box::new(lhs), consume_pattern(&op, rhs)?),
             _ => Expr::Literal(Variant::Nil),
-        }
+        })
     };
     climber.climb(pair.into_inner(), primary, infix)
 }
 
-fn consume_primary(pair: Pair<Rule>) -> Expr {
-    let mut result = None;
-    for item in pair.into_inner().rev() {
-        result = Some(match item.as_rule() {
-            Rule::expression => consume_expr(item),
-            Rule::op_unary_plus => Expr::UnaryPlus(Box::new(result.take().unwrap())),
-            Rule::op_unary_negation => Expr::UnaryNegation(Box::new(result.take().unwrap())),
-            Rule::op_logical_negation => Expr::LogicalNegation(Box::new(result.take().unwrap())),
-            Rule::bin_integer => {
-                let v = BigInt::from_str_radix(&item.as_str()[2..], 2).unwrap();
-                Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
-            }
-            Rule::oct_integer => {
-                let v = BigInt::from_str_radix(&item.as_str()[2..], 8).unwrap();
-                Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
-            }
-            Rule::hex_integer => {
-                let v = BigInt::from_str_radix(&item.as_str()[2..], 16).unwrap();
-                Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+/// Replaces a severity name compared with `expert.severity` with its level,
+/// e.g. `warn` in `expert.severity >= warn`.
+fn resolve_severity(lhs: Expr, rhs: Expr) -> (Expr, Expr) {
+    let level = |expr: &Expr| match expr {
+        Expr::Token(name) => Severity::from_name(&name.to_string())
+            .map(|severity| Expr::Literal(Variant::UInt64(severity.level()))),
+        _ => None,
+    };
+    let severity = Token::from(SEVERITY_ATTR);
+    match (&lhs, &rhs) {
+        (Expr::Token(id), _) if *id == severity => {
+            let rhs = level(&rhs).unwrap_or(rhs);
+            (lhs, rhs)
+        }
+        (_, Expr::Token(id)) if *id == severity => {
+            let lhs = level(&lhs).unwrap_or(lhs);
+            (lhs, rhs)
+        }
+        _ => (lhs, rhs),
+    }
+}
+
+/// Parses the set or CIDR block on the right of an `in` operator.
+fn consume_membership(pair: Pair<Rule>, format: &TimestampFormat) -> Result<Set, Error<Rule>> {
+    let item = pair.into_inner().nth(1).unwrap();
+    let members = match item.as_rule() {
+        Rule::set => item
+            .into_inner()
+            .map(|member| consume_member(member, format))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => vec![consume_member(item, format)?],
+    };
+    Ok(Set::new(members))
+}
+
+fn consume_member(pair: Pair<Rule>, format: &TimestampFormat) -> Result<Member, Error<Rule>> {
+    let error = |message: String, pair: &Pair<Rule>| {
+        Error::new_from_span(ErrorVariant::CustomError { message }, pair.as_span())
+    };
+    match pair.as_rule() {
+        Rule::cidr => {
+            let cidr = pair.as_str().trim_start_matches('@');
+            Cidr::parse(cidr)
+                .map(Member::Cidr)
+                .map_err(|message| error(message, &pair))
+        }
+        Rule::range => {
+            let mut bounds = pair.clone().into_inner();
+            let lo = consume_member(bounds.next().unwrap(), format)?;
+            let hi = consume_member(bounds.next().unwrap(), format)?;
+            match (lo, hi) {
+                (Member::Value(lo), Member::Value(hi)) => Ok(Member::Range(lo, hi)),
+                _ => Err(error("invalid range".to_string(), &pair)),
             }
-            Rule::dec_integer => {
-                let v = BigInt::from_str_radix(item.as_str(), 10).unwrap();
-                Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+        }
+        _ => match consume_primary(pair.clone(), format)? {
+            Expr::Macro(_) => Err(error("invalid set member".to_string(), &pair)),
+            expr => Ok(Member::Value(expr.eval(&Context::new(&[])))),
+        },
+    }
+}
+
+fn consume_primary(pair: Pair<Rule>, format: &TimestampFormat) -> Result<Expr, Error<Rule>> {
+    // unary* operand index* membership?
+    let mut items = pair.into_inner().collect::<Vec<_>>();
+    let membership = match items.last().map(|item| item.as_rule()) {
+        Some(Rule::membership) => items.pop(),
+        _ => None,
+    };
+    let postfix = items
+        .iter()
+        .rposition(|item| item.as_rule() != Rule::index)
+        .map_or(0, |pos| pos + 1);
+    let indices = items.split_off(postfix);
+    let mut result = consume_operand(items.pop().unwrap(), format)?;
+    for index in indices {
+        result = consume_index(result, index);
+    }
+    // `in` binds tighter than unary operators, so `!a in {..}` negates the membership.
+    if let Some(membership) = membership {
+        let set = consume_membership(membership, format)?;
+        result = Expr::In(Box::new(result), set);
+    }
+    for item in items.into_iter().rev() {
+        result = match item.as_rule() {
+            Rule::op_unary_plus => Expr::UnaryPlus(Box::new(result)),
+            Rule::op_unary_negation => Expr::UnaryNegation(Box::new(result)),
+            Rule::op_logical_negation => Expr::LogicalNegation(Box::new(result)),
+            _ => result,
+        };
+    }
+    Ok(result)
+}
+
+fn consume_index(expr: Expr, pair: Pair<Rule>) -> Expr {
+    let mut start = None;
+    let mut end = None;
+    let mut colon = false;
+    for item in pair.into_inner() {
+        match item.as_rule() {
+            Rule::slice_colon => colon = true,
+            _ => {
+                // Out of range bounds are clamped when the slice is evaluated.
+                let bound = item.as_str().parse::<i64>().unwrap_or_else(|_| {
+                    if item.as_str().starts_with('-') {
+                        i64::MIN
+                    } else {
+                        i64::MAX
+                    }
+                });
+                if colon {
+                    end = Some(bound);
+                } else {
+                    start = Some(bound);
+                }
             }
-            Rule::string => Expr::Literal(Variant::String(
-                serde_json::from_str(item.as_str()).unwrap(),
-            )),
-            Rule::macro_exp => parse_macro(item.as_str()[1..].to_string()),
-            Rule::float => Expr::Literal(Variant::Float64(item.as_str().parse().unwrap())),
-            Rule::nil => Expr::Literal(Variant::Nil),
-            Rule::boolean => Expr::Literal(Variant::Bool(item.as_str() == "true")),
-            Rule::member => Expr::Token(Token::from(item.as_str())),
-            _ => Expr::Literal(Variant::Nil),
-        });
+        }
+    }
+    match (colon, start) {
+        (false, Some(index)) => Expr::Index(Box::new(expr), index),
+        _ => Expr::Slice(Box::new(expr), start, end),
+    }
+}
+
+fn consume_call(pair: Pair<Rule>, format: &TimestampFormat) -> Result<Expr, Error<Rule>> {
+    let span = pair.as_span();
+    let error = |message: String| Error::new_from_span(ErrorVariant::CustomError { message }, span);
+    let mut items = pair.into_inner();
+    let name = items.next().unwrap().as_str();
+    let args = items
+        .map(|item| consume_expr(item, format))
+        .collect::<Result<Vec<_>, _>>()?;
+    if name == "count" {
+        return match args.as_slice() {
+            [Expr::Token(id)] => Ok(Expr::Count(*id)),
+            _ => Err(error("count() takes an attribute or a layer".to_string())),
+        };
+    }
+    let func = functions::get(name).ok_or_else(|| error(format!("unknown function: {}", name)))?;
+    if func.args() != args.len() {
+        return Err(error(format!(
+            "{}() takes {} argument(s) but {} given",
+            name,
+            func.args(),
+            args.len()
+        )));
     }
-    result.unwrap()
+    Ok(Expr::Call(func, args))
+}
+
+/// Expands a `$name` reference into the expression of the macro.
+fn consume_macro_ref(pair: Pair<Rule>, format: &TimestampFormat) -> Result<Expr, Error<Rule>> {
+    let span = pair.as_span();
+    let error = |message: String| Error::new_from_span(ErrorVariant::CustomError { message }, span);
+    let name = &pair.as_str()[1..];
+    let expr = macros::get(name).ok_or_else(|| error(format!("unknown macro: ${}", name)))?;
+    macros::expand(name, || parse_with_format(&expr, format))
+        .ok_or_else(|| error(format!("recursive macro: ${}", name)))?
+        .map_err(|err| error(format!("macro ${}: {}", name, err)))
+}
+
+fn consume_operand(item: Pair<Rule>, format: &TimestampFormat) -> Result<Expr, Error<Rule>> {
+    Ok(match item.as_rule() {
+        Rule::expression => consume_expr(item, format)?,
+        Rule::call => consume_call(item, format)?,
+        Rule::macro_ref => consume_macro_ref(item, format)?,
+        Rule::bin_integer => {
+            let v = BigInt::from_str_radix(&item.as_str()[2..], 2).unwrap();
+            Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+        }
+        Rule::oct_integer => {
+            let v = BigInt::from_str_radix(&item.as_str()[2..], 8).unwrap();
+            Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+        }
+        Rule::hex_integer => {
+            let v = BigInt::from_str_radix(&item.as_str()[2..], 16).unwrap();
+            Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+        }
+        Rule::dec_integer => {
+            let v = BigInt::from_str_radix(item.as_str(), 10).unwrap();
+            Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+        }
+        Rule::bytes => Expr::Literal(functions::bytes(item.as_str())),
+        Rule::string => Expr::Literal(Variant::String(
+            serde_json::from_str(item.as_str()).unwrap(),
+        )),
+        Rule::macro_exp | Rule::set_macro => parse_macro(item.as_str()[1..].to_string(), format),
+        Rule::float => Expr::Literal(Variant::Float64(item.as_str().parse().unwrap())),
+        Rule::duration => Expr::Literal(Variant::Float64(parse_duration(item.as_str()))),
+        Rule::nil => Expr::Literal(Variant::Nil),
+        Rule::boolean => Expr::Literal(Variant::Bool(item.as_str() == "true")),
+        Rule::member => match item.as_str() {
+            "frame.protocols" => Expr::Protocols,
+            "frame.marked" => Expr::Marked,
+            member => Expr::Token(Token::from(member)),
+        },
+        _ => Expr::Literal(Variant::Nil),
+    })
 }
 
 #[cfg(test)]
@@ -190,6 +412,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn duration() {
+        assert_eq!(parse("1s"), Ok(Literal(Variant::Float64(1.0))));
+        assert_eq!(parse("1.5s"), Ok(Literal(Variant::Float64(1.5))));
+        assert_eq!(parse("250ms"), Ok(Literal(Variant::Float64(0.25))));
+        assert_eq!(parse("2m"), Ok(Literal(Variant::Float64(120.0))));
+        assert_eq!(parse("1h"), Ok(Literal(Variant::Float64(3600.0))));
+        assert!(parse("1sec").is_err());
+        assert_eq!(
+            parse("frame.time_delta > 1s"),
+            Ok(CmpGt(
+                Box::new(Token(Token::from("frame.time_delta"))),
+                Box::new(Literal(Variant::Float64(1.0)))
+            ))
+        );
+    }
+
+    #[test]
+    fn protocols() {
+        assert_eq!(parse("frame.protocols"), Ok(Protocols));
+        assert_eq!(parse("frame.marked"), Ok(Marked));
+        assert_eq!(
+            parse("frame.protocolsx"),
+            Ok(Token(Token::from("frame.protocolsx")))
+        );
+    }
+
+    #[test]
+    fn datetime() {
+        assert_eq!(
+            parse("@2018-05-01T12:00:00Z"),
+            Ok(Literal(Variant::Float64(1_525_176_000.0)))
+        );
+        assert_eq!(
+            parse_with_zone("@2018-05-01T21:00:00", Zone::Fixed(9 * 3600)),
+            Ok(Literal(Variant::Float64(1_525_176_000.0)))
+        );
+        assert_eq!(
+            parse("@2018-05-01T12:00:00X"),
+            Ok(Macro("2018-05-01T12:00:00X".to_string()))
+        );
+    }
+
     #[test]
     fn group() {
         assert_eq!(parse("0xff5678"), Ok(Literal(Variant::UInt64(16_733_816))));
@@ -272,6 +537,176 @@ mod tests {
         );
     }
 
+    #[test]
+    fn precedence() {
+        let token = |id| Box::new(Token(Token::from(id)));
+        let literal = |v| Box::new(Literal(Variant::UInt64(v)));
+        assert_eq!(
+            parse("a.b == 1 || c && d.e < 2 == e"),
+            Ok(LogicalOr(
+                Box::new(CmpEq(token("a.b"), literal(1))),
+                Box::new(LogicalAnd(
+                    token("c"),
+                    Box::new(CmpEq(Box::new(CmpLt(token("d.e"), literal(2))), token("e")))
+                ))
+            ))
+        );
+    }
+
+    #[test]
+    fn string_operators() {
+        let host = || Box::new(Token(Token::from("http.host")));
+        let example = || Box::new(Literal(Variant::String("example".into())));
+        assert_eq!(
+            parse(r#"http.host contains "example""#),
+            Ok(Contains(host(), example()))
+        );
+        assert_eq!(
+            parse(r#"http.host icontains "example""#),
+            Ok(ContainsIgnoreCase(host(), example()))
+        );
+        assert_eq!(
+            parse(r#"http.host starts_with "example""#),
+            Ok(StartsWith(host(), example()))
+        );
+        assert_eq!(
+            parse(r#"http.host istarts_with "example""#),
+            Ok(StartsWithIgnoreCase(host(), example()))
+        );
+        assert_eq!(
+            parse(r#"http.host ~ "(?i)example\\.""#),
+            Ok(Matches(host(), Pattern::new(r"(?i)example\.").unwrap()))
+        );
+        assert_eq!(
+            parse(r#"http.host matches "example""#),
+            Ok(Matches(host(), Pattern::new("example").unwrap()))
+        );
+        assert_eq!(parse("contains"), Ok(Token(Token::from("contains"))));
+        assert_eq!(parse("matches.x"), Ok(Token(Token::from("matches.x"))));
+        assert!(parse("http.host containsx \"a\"").is_err());
+        assert!(parse(r#"http.host ~ "(""#).is_err());
+        assert!(parse("http.host ~ 1").is_err());
+    }
+
+    #[test]
+    fn membership() {
+        let port = || Box::new(Token(Token::from("tcp.port")));
+        let src = || Box::new(Token(Token::from("ip.src")));
+        assert_eq!(
+            parse("tcp.port in {80, 443, 8000..8999}"),
+            Ok(In(
+                port(),
+                Set::new(vec![
+                    Member::Value(Variant::UInt64(80)),
+                    Member::Value(Variant::UInt64(443)),
+                    Member::Range(Variant::UInt64(8000), Variant::UInt64(8999)),
+                ])
+            ))
+        );
+        assert_eq!(
+            parse("!tcp.port in {-1, }"),
+            Ok(LogicalNegation(Box::new(In(
+                port(),
+                Set::new(vec![Member::Value(Variant::Int64(-1))])
+            ))))
+        );
+        assert_eq!(
+            parse("ip.src in 10.0.0.0/8"),
+            Ok(In(
+                src(),
+                Set::new(vec![Member::Cidr(Cidr::parse("10.0.0.0/8").unwrap())])
+            ))
+        );
+        assert_eq!(
+            parse("ip.src in {@10.0.0.1,@fe80::/10, @10.0.0.5 .. @10.0.0.9}"),
+            Ok(In(
+                src(),
+                Set::new(vec![
+                    Member::Value(Variant::Buffer(Box::new([10, 0, 0, 1]))),
+                    Member::Cidr(Cidr::parse("fe80::/10").unwrap()),
+                    Member::Range(
+                        Variant::Buffer(Box::new([10, 0, 0, 5])),
+                        Variant::Buffer(Box::new([10, 0, 0, 9]))
+                    ),
+                ])
+            ))
+        );
+        assert_eq!(parse("tcp.port in {}"), Ok(In(port(), Set::new(vec![]))));
+        assert_eq!(parse("in"), Ok(Token(Token::from("in"))));
+        assert!(parse("tcp.port in {tcp.dst}").is_err());
+        assert!(parse("tcp.port in {@foo}").is_err());
+        assert!(parse("ip.src in 10.0.0.0/40").is_err());
+        assert!(parse("tcp.port in 80").is_err());
+    }
+
+    #[test]
+    fn functions() {
+        let name = || Box::new(Token(Token::from("dns.qry.name")));
+        let payload = || Box::new(Token(Token::from("tcp.payload")));
+        assert_eq!(
+            parse("len(dns.qry.name) > 50"),
+            Ok(CmpGt(
+                Box::new(Call(functions::get("len").unwrap(), vec![*name()])),
+                Box::new(Literal(Variant::UInt64(50)))
+            ))
+        );
+        assert_eq!(
+            parse("tcp.payload[0:4] == 16:03:01"),
+            Ok(CmpEq(
+                Box::new(Slice(payload(), Some(0), Some(4))),
+                Box::new(Literal(Variant::Buffer(Box::new([0x16, 0x03, 0x01]))))
+            ))
+        );
+        assert_eq!(
+            parse("tcp.payload[-2:]"),
+            Ok(Slice(payload(), Some(-2), None))
+        );
+        assert_eq!(parse("tcp.payload[:]"), Ok(Slice(payload(), None, None)));
+        assert_eq!(
+            parse("-tcp.payload[0]"),
+            Ok(UnaryNegation(Box::new(Index(payload(), 0))))
+        );
+        assert_eq!(
+            parse("count(ipv4) > 1"),
+            Ok(CmpGt(
+                Box::new(Count(Token::from("ipv4"))),
+                Box::new(Literal(Variant::UInt64(1)))
+            ))
+        );
+        assert_eq!(
+            parse("upper(lower(dns.qry.name))"),
+            Ok(Call(
+                functions::get("upper").unwrap(),
+                vec![Call(functions::get("lower").unwrap(), vec![*name()])]
+            ))
+        );
+        assert!(parse("unknown(dns.qry.name)").is_err());
+        assert!(parse("len(dns.qry.name, 1)").is_err());
+        assert!(parse("count(1)").is_err());
+        assert!(parse("tcp.payload[]").is_err());
+        assert!(parse("16:03:0").is_err());
+    }
+
+    #[test]
+    fn severity() {
+        let severity = || Box::new(Token(Token::from("expert.severity")));
+        assert_eq!(
+            parse("expert.severity >= warn"),
+            Ok(CmpGte(severity(), Box::new(Literal(Variant::UInt64(3)))))
+        );
+        assert_eq!(
+            parse("error == expert.severity"),
+            Ok(CmpEq(Box::new(Literal(Variant::UInt64(4))), severity()))
+        );
+        assert_eq!(
+            parse("tcp.flags >= warn"),
+            Ok(CmpGte(
+                Box::new(Token(Token::from("tcp.flags"))),
+                Box::new(Token(Token::from("warn")))
+            ))
+        );
+    }
+
     #[test]
     fn error() {
         assert!(parse("| 12.5").is_err());
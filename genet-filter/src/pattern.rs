@@ -0,0 +1,136 @@
+//! String operators over string and byte-slice values.
+//!
+//! Operands are compared as bytes, so the operators apply to both string
+//! attributes and raw payloads. Only the first `MAX_SCAN_LEN` bytes of a
+//! value are scanned to bound the cost of filtering large payloads.
+
+use genet_abi::variant::Variant;
+use regex::bytes::{Regex, RegexBuilder};
+use std::fmt;
+
+/// The maximum number of bytes of a value scanned by the operators.
+pub const MAX_SCAN_LEN: usize = 64 * 1024;
+
+/// The maximum size of a compiled regular expression.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+fn bytes(value: &Variant) -> Option<&[u8]> {
+    let bytes: &[u8] = match value {
+        Variant::String(s) => s.as_bytes(),
+        Variant::Buffer(b) => b,
+        Variant::Slice(s) => s,
+        _ => return None,
+    };
+    Some(&bytes[..bytes.len().min(MAX_SCAN_LEN)])
+}
+
+fn eq(a: &[u8], b: &[u8], ignore_case: bool) -> bool {
+    if ignore_case {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+/// Returns true if `value` contains `needle`.
+pub fn contains(value: &Variant, needle: &Variant, ignore_case: bool) -> bool {
+    match (bytes(value), bytes(needle)) {
+        (Some(value), Some(needle)) => {
+            needle.is_empty()
+                || value
+                    .windows(needle.len())
+                    .any(|window| eq(window, needle, ignore_case))
+        }
+        _ => false,
+    }
+}
+
+/// Returns true if `value` starts with `prefix`.
+pub fn starts_with(value: &Variant, prefix: &Variant, ignore_case: bool) -> bool {
+    match (bytes(value), bytes(prefix)) {
+        (Some(value), Some(prefix)) => {
+            value.len() >= prefix.len() && eq(&value[..prefix.len()], prefix, ignore_case)
+        }
+        _ => false,
+    }
+}
+
+/// A regular expression compiled when the filter is parsed.
+#[derive(Clone)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Pattern, String> {
+        RegexBuilder::new(pattern)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .build()
+            .map(Pattern)
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Returns true if the pattern matches `value`.
+    pub fn is_match(&self, value: &Variant) -> bool {
+        match bytes(value) {
+            Some(value) => self.0.is_match(value),
+            None => false,
+        }
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Pattern({:?})", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{slice::ByteSlice, variant::Variant};
+    use pattern::{contains, starts_with, Pattern, MAX_SCAN_LEN};
+
+    fn string(s: &str) -> Variant {
+        Variant::String(s.to_string().into_boxed_str())
+    }
+
+    #[test]
+    fn operators() {
+        let host = string("www.Example.com");
+        assert!(contains(&host, &string("Example"), false));
+        assert!(!contains(&host, &string("example"), false));
+        assert!(contains(&host, &string("example"), true));
+        assert!(contains(&host, &string(""), false));
+        assert!(starts_with(&host, &string("www."), false));
+        assert!(starts_with(&host, &string("WWW."), true));
+        assert!(!starts_with(&host, &string("Example"), false));
+        assert!(!contains(&Variant::UInt64(1), &string("1"), false));
+
+        let payload = Variant::Slice(ByteSlice::from(&b"GET / HTTP/1.1\r\n"[..]));
+        assert!(contains(&payload, &string("HTTP/1.1"), false));
+        assert!(starts_with(
+            &payload,
+            &Variant::Buffer(Box::new(*b"GET")),
+            false
+        ));
+
+        let pattern = Pattern::new(r"(?i)example\.").unwrap();
+        assert!(pattern.is_match(&host));
+        assert!(!pattern.is_match(&string("example")));
+        assert!(Pattern::new("(").is_err());
+
+        let mut large = vec![b'a'; MAX_SCAN_LEN];
+        large.extend_from_slice(b"needle");
+        let large = Variant::Buffer(large.into_boxed_slice());
+        assert!(!contains(&large, &string("needle"), false));
+        assert!(!Pattern::new("needle").unwrap().is_match(&large));
+    }
+}
@@ -0,0 +1,79 @@
+use genet_abi::{fixed::MutFixed, layer::Layer, token::Token};
+
+/// A precomputed set of layer ids present in a frame.
+///
+/// Layer ids are stored as a bitmap indexed by the token value, so
+/// bare-protocol filters can be answered without walking the layer tree.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Protocols {
+    bitmap: Vec<u64>,
+    path: String,
+}
+
+impl Protocols {
+    pub fn new(layers: &[MutFixed<Layer>]) -> Protocols {
+        let mut protocols = Protocols::default();
+        for layer in layers {
+            protocols.insert(layer.id());
+        }
+        protocols.path = layers
+            .iter()
+            .map(|layer| layer.id().to_string())
+            .filter(|id| !id.starts_with('['))
+            .collect::<Vec<_>>()
+            .join(":");
+        protocols
+    }
+
+    fn insert(&mut self, id: Token) {
+        let id: u32 = id.into();
+        let index = id as usize / 64;
+        if self.bitmap.len() <= index {
+            self.bitmap.resize(index + 1, 0);
+        }
+        self.bitmap[index] |= 1 << (id % 64);
+    }
+
+    /// Returns true if a layer with the given id is present.
+    pub fn contains(&self, id: Token) -> bool {
+        let id: u32 = id.into();
+        match self.bitmap.get(id as usize / 64) {
+            Some(bits) => bits & (1 << (id % 64)) != 0,
+            None => false,
+        }
+    }
+
+    /// Returns the protocol path string (e.g. `eth:ipv4:tcp`).
+    ///
+    /// Pseudo layers such as `[link-1]` are omitted.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        token::Token,
+    };
+    use protocols::Protocols;
+
+    #[test]
+    fn protocols() {
+        let layers = ["[link-1]", "eth", "ipv4", "tcp"]
+            .iter()
+            .map(|id| {
+                let class = Fixed::new(LayerClass::builder(*id).build());
+                MutFixed::new(Layer::new(class, ByteSlice::new()))
+            })
+            .collect::<Vec<_>>();
+        let protocols = Protocols::new(&layers);
+        assert_eq!(protocols.path(), "eth:ipv4:tcp");
+        assert!(protocols.contains(Token::from("[link-1]")));
+        assert!(protocols.contains(Token::from("tcp")));
+        assert!(!protocols.contains(Token::from("udp")));
+    }
+}
@@ -0,0 +1,193 @@
+//! Set literals of the `in` operator.
+//!
+//! A set consists of constant values, inclusive ranges and CIDR blocks.
+//! Integer values and ranges are merged into sorted intervals when the
+//! filter is parsed, so that port sets are tested by a binary search.
+
+use genet_abi::variant::Variant;
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+use variant::VariantExt;
+
+/// An address block, e.g. `10.0.0.0/8`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cidr {
+    addr: Vec<u8>,
+    prefix: usize,
+}
+
+impl Cidr {
+    pub fn parse(s: &str) -> Result<Cidr, String> {
+        let error = || format!("invalid CIDR block: {}", s);
+        let mut parts = s.splitn(2, '/');
+        let addr = parts.next().ok_or_else(error)?;
+        let prefix = parts
+            .next()
+            .and_then(|prefix| prefix.parse::<usize>().ok())
+            .ok_or_else(error)?;
+        let addr = if let Ok(addr) = addr.parse::<Ipv4Addr>() {
+            addr.octets().to_vec()
+        } else if let Ok(addr) = addr.parse::<Ipv6Addr>() {
+            addr.octets().to_vec()
+        } else {
+            return Err(error());
+        };
+        if prefix > addr.len() * 8 {
+            return Err(error());
+        }
+        Ok(Cidr { addr, prefix })
+    }
+
+    /// Returns true if `value` is an address of the same family in the block.
+    pub fn contains(&self, value: &Variant) -> bool {
+        let addr: &[u8] = match value {
+            Variant::Buffer(b) => b,
+            Variant::Slice(s) => s,
+            _ => return false,
+        };
+        if addr.len() != self.addr.len() {
+            return false;
+        }
+        let bytes = self.prefix / 8;
+        let bits = self.prefix % 8;
+        if addr[..bytes] != self.addr[..bytes] {
+            return false;
+        }
+        bits == 0 || {
+            let mask = !(0xffu8 >> bits);
+            addr[bytes] & mask == self.addr[bytes] & mask
+        }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.addr.len() == 4 {
+            let addr = Ipv4Addr::from(*array_ref![self.addr, 0, 4]);
+            write!(f, "{}/{}", addr, self.prefix)
+        } else {
+            let addr = Ipv6Addr::from(*array_ref![self.addr, 0, 16]);
+            write!(f, "{}/{}", addr, self.prefix)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Member {
+    Value(Variant),
+    Range(Variant, Variant),
+    Cidr(Cidr),
+}
+
+impl Member {
+    fn contains(&self, value: &Variant) -> bool {
+        match self {
+            Member::Value(v) => value.op_eq(v),
+            Member::Range(lo, hi) => value.op_gte(lo) && value.op_lte(hi),
+            Member::Cidr(cidr) => cidr.contains(value),
+        }
+    }
+
+    fn interval(&self) -> Option<(u64, u64)> {
+        match self {
+            Member::Value(Variant::UInt64(v)) => Some((*v, *v)),
+            Member::Range(Variant::UInt64(lo), Variant::UInt64(hi)) => Some((*lo, *hi)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Set {
+    members: Vec<Member>,
+    intervals: Vec<(u64, u64)>,
+    others: Vec<Member>,
+}
+
+impl Set {
+    pub fn new(members: Vec<Member>) -> Set {
+        let mut intervals = members
+            .iter()
+            .filter_map(|m| m.interval())
+            .filter(|(lo, hi)| lo <= hi)
+            .collect::<Vec<_>>();
+        intervals.sort();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+        for (lo, hi) in intervals {
+            match merged.last_mut() {
+                Some(last) if lo <= last.1.saturating_add(1) => last.1 = last.1.max(hi),
+                _ => merged.push((lo, hi)),
+            }
+        }
+        let others = members
+            .iter()
+            .filter(|m| m.interval().is_none())
+            .cloned()
+            .collect();
+        Set {
+            members,
+            intervals: merged,
+            others,
+        }
+    }
+
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+
+    /// Returns true if `value` is equal to a value or within a range or
+    /// block of the set.
+    pub fn contains(&self, value: &Variant) -> bool {
+        let int = match value {
+            Variant::UInt64(v) => *v,
+            Variant::Int64(v) if *v >= 0 => *v as u64,
+            _ => return self.members.iter().any(|m| m.contains(value)),
+        };
+        let index = match self.intervals.binary_search_by(|&(lo, _)| lo.cmp(&int)) {
+            Ok(_) => return true,
+            Err(index) => index,
+        };
+        (index > 0 && int <= self.intervals[index - 1].1)
+            || self.others.iter().any(|m| m.contains(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{slice::ByteSlice, variant::Variant};
+    use set::{Cidr, Member, Set};
+
+    #[test]
+    fn contains() {
+        let set = Set::new(vec![
+            Member::Value(Variant::UInt64(80)),
+            Member::Value(Variant::UInt64(443)),
+            Member::Range(Variant::UInt64(8000), Variant::UInt64(8999)),
+            Member::Range(Variant::UInt64(8500), Variant::UInt64(9100)),
+            Member::Value(Variant::Int64(-1)),
+        ]);
+        assert!(set.contains(&Variant::UInt64(80)));
+        assert!(set.contains(&Variant::UInt64(8000)));
+        assert!(set.contains(&Variant::UInt64(9100)));
+        assert!(set.contains(&Variant::Int64(443)));
+        assert!(set.contains(&Variant::Int64(-1)));
+        assert!(set.contains(&Variant::Float64(8080.0)));
+        assert!(!set.contains(&Variant::UInt64(81)));
+        assert!(!set.contains(&Variant::UInt64(9101)));
+        assert!(!set.contains(&Variant::UInt64(0)));
+        assert!(!set.contains(&Variant::Nil));
+
+        let lan = Cidr::parse("192.168.0.0/23").unwrap();
+        assert!(lan.contains(&Variant::Slice(ByteSlice::from(&[192, 168, 1, 20][..]))));
+        assert!(!lan.contains(&Variant::Slice(ByteSlice::from(&[192, 168, 2, 1][..]))));
+        assert!(!lan.contains(&Variant::Buffer(Box::new([0; 16]))));
+        assert!(Cidr::parse("::/0")
+            .unwrap()
+            .contains(&Variant::Buffer(Box::new([0; 16]))));
+        assert_eq!(lan.to_string(), "192.168.0.0/23");
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+        assert!(Cidr::parse("10.0.0/8").is_err());
+    }
+}
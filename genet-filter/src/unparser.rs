@@ -1,6 +1,8 @@
 use ast::Expr;
 use genet_abi::{token::Token, variant::Variant};
 use hwaddr::HwAddr;
+use serde_json;
+use set::Member;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use variant::VariantExt;
 
@@ -32,28 +34,104 @@ pub fn unparse(expr: &Expr) -> String {
         Expr::Literal(var) => var.to_string(),
         Expr::Token(t) => t.to_string(),
         Expr::Macro(expr) => format!("@{}", expr),
+        Expr::Protocols => "frame.protocols".to_string(),
+        Expr::Marked => "frame.marked".to_string(),
+        Expr::Call(f, args) => {
+            let args = args.iter().map(unparse).collect::<Vec<_>>();
+            format!("{}({})", f.name(), args.join(", "))
+        }
+        Expr::Count(t) => format!("count({})", t),
+        Expr::Slice(expr, start, end) => {
+            let bound = |i: &Option<i64>| i.map(|i| i.to_string()).unwrap_or_default();
+            format!("{}[{}:{}]", group(expr, ATOM), bound(start), bound(end))
+        }
+        Expr::Index(expr, index) => format!("{}[{}]", group(expr, ATOM), index),
         Expr::CmpEq(lhs, rhs) => match (lhs.as_ref(), rhs.as_ref()) {
-            (lhs, &Expr::Literal(Variant::Bool(true))) => unparse(lhs),
-            (lhs, &Expr::Literal(Variant::Bool(false))) => format!("!{}", unparse(lhs)),
-            (&Expr::Literal(Variant::Bool(true)), rhs) => unparse(rhs),
-            (&Expr::Literal(Variant::Bool(false)), rhs) => format!("!{}", unparse(rhs)),
-            (lhs, rhs) => format!("{} == {}", unparse(lhs), unparse(rhs)),
+            (lhs, &Expr::Literal(Variant::Bool(true))) => group(lhs, EQ),
+            (lhs, &Expr::Literal(Variant::Bool(false))) => format!("!{}", group(lhs, UNARY)),
+            (&Expr::Literal(Variant::Bool(true)), rhs) => group(rhs, EQ),
+            (&Expr::Literal(Variant::Bool(false)), rhs) => format!("!{}", group(rhs, UNARY)),
+            (lhs, rhs) => binary(lhs, "==", rhs, EQ),
         },
         Expr::CmpNotEq(lhs, rhs) => match (lhs.as_ref(), rhs.as_ref()) {
-            (lhs, &Expr::Literal(Variant::Bool(false))) => unparse(lhs),
-            (lhs, &Expr::Literal(Variant::Bool(true))) => format!("!{}", unparse(lhs)),
-            (&Expr::Literal(Variant::Bool(false)), rhs) => unparse(rhs),
-            (&Expr::Literal(Variant::Bool(true)), rhs) => format!("!{}", unparse(rhs)),
-            (lhs, rhs) => format!("{} != {}", unparse(lhs), unparse(rhs)),
+            (lhs, &Expr::Literal(Variant::Bool(false))) => group(lhs, EQ),
+            (lhs, &Expr::Literal(Variant::Bool(true))) => format!("!{}", group(lhs, UNARY)),
+            (&Expr::Literal(Variant::Bool(false)), rhs) => group(rhs, EQ),
+            (&Expr::Literal(Variant::Bool(true)), rhs) => format!("!{}", group(rhs, UNARY)),
+            (lhs, rhs) => binary(lhs, "!=", rhs, EQ),
         },
-        Expr::CmpLt(lhs, rhs) => format!("{} < {}", unparse(lhs), unparse(rhs)),
-        Expr::CmpGt(lhs, rhs) => format!("{} > {}", unparse(lhs), unparse(rhs)),
-        Expr::CmpLte(lhs, rhs) => format!("{} <= {}", unparse(lhs), unparse(rhs)),
-        Expr::CmpGte(lhs, rhs) => format!("{} >= {}", unparse(lhs), unparse(rhs)),
-        Expr::LogicalAnd(lhs, rhs) => format!("{} && {}", unparse(lhs), unparse(rhs)),
-        Expr::LogicalOr(lhs, rhs) => format!("{} || {}", unparse(lhs), unparse(rhs)),
-        Expr::LogicalNegation(expr) => format!("!{}", unparse(expr)),
-        Expr::UnaryPlus(expr) => format!("+{}", unparse(expr)),
-        Expr::UnaryNegation(expr) => format!("-{}", unparse(expr)),
+        Expr::CmpLt(lhs, rhs) => binary(lhs, "<", rhs, REL),
+        Expr::CmpGt(lhs, rhs) => binary(lhs, ">", rhs, REL),
+        Expr::CmpLte(lhs, rhs) => binary(lhs, "<=", rhs, REL),
+        Expr::CmpGte(lhs, rhs) => binary(lhs, ">=", rhs, REL),
+        Expr::Contains(lhs, rhs) => binary(lhs, "contains", rhs, EQ),
+        Expr::ContainsIgnoreCase(lhs, rhs) => binary(lhs, "icontains", rhs, EQ),
+        Expr::StartsWith(lhs, rhs) => binary(lhs, "starts_with", rhs, EQ),
+        Expr::StartsWithIgnoreCase(lhs, rhs) => binary(lhs, "istarts_with", rhs, EQ),
+        Expr::Matches(expr, pattern) => format!(
+            "{} ~ {}",
+            group(expr, EQ),
+            serde_json::to_string(pattern.as_str()).unwrap()
+        ),
+        Expr::In(expr, set) => {
+            let members = set
+                .members()
+                .iter()
+                .map(|member| match member {
+                    Member::Value(v) => v.to_string(),
+                    Member::Range(lo, hi) => format!("{}..{}", lo.to_string(), hi.to_string()),
+                    Member::Cidr(cidr) => cidr.to_string(),
+                })
+                .collect::<Vec<_>>();
+            format!("{} in {{{}}}", group(expr, ATOM), members.join(", "))
+        }
+        Expr::LogicalAnd(lhs, rhs) => binary(lhs, "&&", rhs, AND),
+        Expr::LogicalOr(lhs, rhs) => binary(lhs, "||", rhs, OR),
+        Expr::LogicalNegation(expr) => format!("!{}", group(expr, UNARY)),
+        Expr::UnaryPlus(expr) => format!("+{}", group(expr, UNARY)),
+        Expr::UnaryNegation(expr) => format!("-{}", group(expr, UNARY)),
     }
 }
+
+// The precedence levels of the operators, from the loosest.
+const OR: u8 = 1;
+const AND: u8 = 2;
+const EQ: u8 = 3;
+const REL: u8 = 4;
+const UNARY: u8 = 5;
+const IN: u8 = 6;
+const ATOM: u8 = 7;
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::LogicalOr(_, _) => OR,
+        Expr::LogicalAnd(_, _) => AND,
+        Expr::CmpEq(_, _)
+        | Expr::CmpNotEq(_, _)
+        | Expr::Contains(_, _)
+        | Expr::ContainsIgnoreCase(_, _)
+        | Expr::StartsWith(_, _)
+        | Expr::StartsWithIgnoreCase(_, _)
+        | Expr::Matches(_, _) => EQ,
+        Expr::CmpLt(_, _) | Expr::CmpGt(_, _) | Expr::CmpLte(_, _) | Expr::CmpGte(_, _) => REL,
+        Expr::LogicalNegation(_) | Expr::UnaryPlus(_) | Expr::UnaryNegation(_) => UNARY,
+        Expr::In(_, _) => IN,
+        _ => ATOM,
+    }
+}
+
+/// Unparses `expr`, enclosing it in parentheses if it binds looser than `min`.
+fn group(expr: &Expr, min: u8) -> String {
+    if precedence(expr) < min {
+        format!("({})", unparse(expr))
+    } else {
+        unparse(expr)
+    }
+}
+
+/// Unparses a left-associative binary operation.
+fn binary(lhs: &Expr, op: &str, rhs: &Expr, prec: u8) -> String {
+    // The logical operators are associative.
+    let rhs_prec = if prec <= AND { prec } else { prec + 1 };
+    format!("{} {} {}", group(lhs, prec), op, group(rhs, rhs_prec))
+}
@@ -158,6 +158,11 @@ impl VariantExt for Variant {
         if self == other {
             return true;
         }
+        match (self, other) {
+            (Variant::Buffer(a), Variant::Slice(b)) => return a[..] == b[..],
+            (Variant::Slice(a), Variant::Buffer(b)) => return a[..] == b[..],
+            _ => {}
+        }
         match self.ord(other) {
             Some(Ordering::Equal) => true,
             _ => false,
@@ -0,0 +1,969 @@
+//! Wireshark display filter compatibility.
+//!
+//! Parses a filter in the Wireshark syntax into the same expression as a
+//! genet filter, so that filters written for Wireshark work as they are,
+//! e.g. `ip.addr == 10.0.0.1 and tcp.flags.syn == 1`. Well-known field
+//! names are mapped to genet attributes, e.g. `ip.src` to `ipv4.src` and
+//! `frame.len` to `link.length`, and the other names are used as they are.
+//!
+//! - A field standing for several attributes, e.g. `ip.addr` or `tcp.port`,
+//!   is compared with each of them. `==` matches if any of them is equal,
+//!   and `!=` if the field is present and none of them is equal.
+//! - Flags compared with `0` or `1` are compared with booleans, and a flag
+//!   alone tests its presence, e.g. `tcp.flags.syn` matches any TCP segment.
+//! - `matches` is case-insensitive, and a slice `[i:n]` takes `n` bytes
+//!   from `i`, while `[i-j]` takes the bytes from `i` to `j`.
+//!
+//! The features without a counterpart, e.g. bitwise and arithmetic
+//! operators, layer operators and field references, are reported as
+//! errors at their position.
+
+use ast::Expr;
+use functions;
+use genet_abi::{token::Token, variant::Variant};
+use num_bigint::BigInt;
+use num_traits::Num;
+use pattern::Pattern;
+use pest::{
+    error::{Error, ErrorVariant},
+    iterators::Pair,
+    prec_climber::{Assoc, Operator, PrecClimber},
+    Parser, Span,
+};
+use set::{Cidr, Member, Set};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use unparser::unparse;
+use variant::VariantExt;
+
+#[derive(Parser)]
+#[grammar = "wireshark.pest"]
+pub struct WiresharkParser;
+
+/// The Wireshark fields with different names in genet.
+const FIELDS: &[(&str, &[&str])] = &[
+    ("frame.len", &["link.length"]),
+    ("frame.time", &["link.timestamp"]),
+    ("frame.time_epoch", &["link.timestamp"]),
+    ("frame.interface_name", &["link.interface.name"]),
+    ("frame.comment", &["link.comment"]),
+    ("eth.addr", &["eth.src", "eth.dst"]),
+    ("vlan.etype", &["vlan.type"]),
+    ("vlan.cfi", &["vlan.dei"]),
+    ("ip", &["ipv4"]),
+    ("ip.addr", &["ipv4.src", "ipv4.dst"]),
+    ("ip.src", &["ipv4.src"]),
+    ("ip.dst", &["ipv4.dst"]),
+    ("ip.version", &["ipv4.version"]),
+    ("ip.hdr_len", &["ipv4.headerLength"]),
+    ("ip.dsfield", &["ipv4.tos"]),
+    ("ip.len", &["ipv4.totalLength"]),
+    ("ip.id", &["ipv4.id"]),
+    ("ip.flags", &["ipv4.flags"]),
+    ("ip.flags.rb", &["ipv4.flags.reserved"]),
+    ("ip.flags.df", &["ipv4.flags.dontFragment"]),
+    ("ip.flags.mf", &["ipv4.flags.moreFragments"]),
+    ("ip.frag_offset", &["ipv4.fragmentOffset"]),
+    ("ip.ttl", &["ipv4.ttl"]),
+    ("ip.proto", &["ipv4.protocol"]),
+    ("ip.checksum", &["ipv4.checksum"]),
+    ("ipv6.addr", &["ipv6.src", "ipv6.dst"]),
+    ("ipv6.tclass", &["ipv6.trafficClass"]),
+    ("ipv6.flow", &["ipv6.flowLabel"]),
+    ("ipv6.plen", &["ipv6.payloadLength"]),
+    ("ipv6.nxt", &["ipv6.nextHeader"]),
+    ("ipv6.hlim", &["ipv6.hopLimit"]),
+    ("tcp.port", &["tcp.src", "tcp.dst"]),
+    ("tcp.srcport", &["tcp.src"]),
+    ("tcp.dstport", &["tcp.dst"]),
+    ("tcp.seq_raw", &["tcp.seq"]),
+    ("tcp.ack_raw", &["tcp.ack"]),
+    ("tcp.flags.ae", &["tcp.flags.ns"]),
+    ("tcp.flags.ecn", &["tcp.flags.ece"]),
+    ("tcp.flags.push", &["tcp.flags.psh"]),
+    ("tcp.flags.reset", &["tcp.flags.rst"]),
+    ("tcp.window_size_value", &["tcp.window"]),
+    ("tcp.urgent_pointer", &["tcp.urgent"]),
+    ("udp.port", &["udp.src", "udp.dst"]),
+    ("udp.srcport", &["udp.src"]),
+    ("udp.dstport", &["udp.dst"]),
+    ("icmp.ident", &["icmp.id"]),
+    ("icmpv6.echo.identifier", &["icmpv6.id"]),
+    ("icmpv6.echo.sequence_number", &["icmpv6.seq"]),
+    ("arp.opcode", &["arp.op"]),
+    ("arp.hw.type", &["arp.hwtype"]),
+    ("arp.proto.type", &["arp.protocol"]),
+    ("arp.src.hw_mac", &["arp.sha"]),
+    ("arp.src.proto_ipv4", &["arp.spa"]),
+    ("arp.dst.hw_mac", &["arp.tha"]),
+    ("arp.dst.proto_ipv4", &["arp.tpa"]),
+    ("arp.isgratuitous", &["arp.gratuitous"]),
+    ("dns.qry.name", &["dns.query.name"]),
+    ("dns.qry.type", &["dns.query.type"]),
+    ("dns.qry.class", &["dns.query.class"]),
+    ("dns.flags.opcode", &["dns.opcode"]),
+    ("dns.flags.rcode", &["dns.rcode"]),
+    ("dns.flags.recdesired", &["dns.flags.recursionDesired"]),
+    ("dns.flags.recavail", &["dns.flags.recursionAvailable"]),
+    ("dns.flags.authenticated", &["dns.flags.authenticData"]),
+    ("dns.flags.checkdisable", &["dns.flags.checkingDisabled"]),
+    ("dns.count.queries", &["dns.questionCount"]),
+    ("dns.count.answers", &["dns.answerCount"]),
+    ("dns.count.auth_rr", &["dns.authorityCount"]),
+    ("dns.count.add_rr", &["dns.additionalCount"]),
+    ("dns.resp.name", &["dns.rr.name"]),
+    ("dns.resp.type", &["dns.rr.type"]),
+    ("dns.resp.ttl", &["dns.rr.ttl"]),
+    ("dns.a", &["dns.rr.a"]),
+    ("dns.aaaa", &["dns.rr.aaaa"]),
+    ("dns.cname", &["dns.rr.cname"]),
+    ("dns.ns", &["dns.rr.ns"]),
+    ("dhcp.flags.bc", &["dhcp.flags.broadcast"]),
+    ("dhcp.hw.mac_addr", &["dhcp.chaddr"]),
+    ("dhcp.ip.client", &["dhcp.ciaddr"]),
+    ("dhcp.ip.your", &["dhcp.yiaddr"]),
+    ("dhcp.ip.server", &["dhcp.siaddr"]),
+    ("dhcp.ip.relay", &["dhcp.giaddr"]),
+    ("dhcp.id", &["dhcp.xid"]),
+    ("dhcp.option.dhcp", &["dhcp.messageType"]),
+    ("dhcp.option.hostname", &["dhcp.hostName"]),
+    ("http.request", &["http.method"]),
+    ("http.response", &["http.status"]),
+    ("http.request.method", &["http.method"]),
+    ("http.request.uri", &["http.uri"]),
+    ("http.request.version", &["http.version"]),
+    ("http.response.version", &["http.version"]),
+    ("http.response.code", &["http.status"]),
+    ("http.response.phrase", &["http.reason"]),
+    ("http.user_agent", &["http.userAgent"]),
+    ("http.content_type", &["http.contentType"]),
+    ("http.content_length", &["http.contentLength"]),
+    ("http.content_encoding", &["http.contentEncoding"]),
+    ("http.transfer_encoding", &["http.transferEncoding"]),
+    ("http.file_data", &["http.body"]),
+    ("tls.handshake.extensions_server_name", &["tls.sni"]),
+    ("tls.handshake.ja3", &["tls.ja3"]),
+    ("tls.handshake.ja3_full", &["tls.ja3.full"]),
+    ("tls.handshake.ja3s", &["tls.ja3s"]),
+    ("tls.handshake.ja3s_full", &["tls.ja3s.full"]),
+    ("tls.handshake.ja4", &["tls.ja4"]),
+    ("tls.handshake.ciphersuite", &["tls.handshake.cipherSuite"]),
+    ("tls.handshake.session_id", &["tls.handshake.sessionId"]),
+    (
+        "tls.handshake.extensions_alpn_str",
+        &["tls.handshake.extension.alpn"],
+    ),
+    (
+        "tls.handshake.extension.type",
+        &["tls.handshake.extension.type"],
+    ),
+    ("tls.record.content_type", &["tls.record.contentType"]),
+    ("tls.alert_message.level", &["tls.alert.level"]),
+    ("tls.alert_message.desc", &["tls.alert.description"]),
+];
+
+/// The genet attributes whose values are booleans.
+const FLAGS: &[&str] = &[
+    "ipv4.flags.reserved",
+    "ipv4.flags.dontFragment",
+    "ipv4.flags.moreFragments",
+    "tcp.flags.ns",
+    "tcp.flags.cwr",
+    "tcp.flags.ece",
+    "tcp.flags.urg",
+    "tcp.flags.ack",
+    "tcp.flags.psh",
+    "tcp.flags.rst",
+    "tcp.flags.syn",
+    "tcp.flags.fin",
+    "vlan.dei",
+    "arp.gratuitous",
+    "dns.flags.response",
+    "dns.flags.authoritative",
+    "dns.flags.truncated",
+    "dns.flags.recursionDesired",
+    "dns.flags.recursionAvailable",
+    "dns.flags.authenticData",
+    "dns.flags.checkingDisabled",
+    "dhcp.flags.broadcast",
+];
+
+/// The Wireshark fields without a counterpart, and the reasons.
+const UNSUPPORTED_FIELDS: &[(&str, &str)] = &[
+    ("frame.number", "frames are not numbered by an attribute"),
+    ("frame.cap_len", "use len() of a payload instead"),
+    (
+        "tcp.seq",
+        "sequence numbers are not relative, use tcp.seq_raw",
+    ),
+    (
+        "tcp.ack",
+        "acknowledgment numbers are not relative, use tcp.ack_raw",
+    ),
+    ("tcp.nxtseq", "sequence numbers are not relative"),
+    ("tcp.len", "use len(tcp.payload) instead"),
+];
+
+/// An operand, which stands for several attributes if it is a field like
+/// `ip.addr`.
+enum Operand {
+    /// Any of the expressions, and whether they are flags.
+    Exprs(Vec<Expr>, bool),
+    Cidr(Cidr),
+}
+
+fn error<T>(span: Span, message: String) -> Result<T, Error<Rule>> {
+    Err(Error::new_from_span(
+        ErrorVariant::CustomError { message },
+        span,
+    ))
+}
+
+/// Parses a filter in the Wireshark syntax.
+pub fn parse(filter: &str) -> Result<Expr, Error<Rule>> {
+    let mut expr = WiresharkParser::parse(Rule::filter, filter)?;
+    consume_expr(expr.next().unwrap().into_inner().next().unwrap())
+}
+
+/// Translates a filter in the Wireshark syntax into the genet syntax.
+pub fn translate(filter: &str) -> Result<String, Error<Rule>> {
+    parse(filter).map(|expr| unparse(&expr))
+}
+
+fn any(exprs: Vec<Expr>) -> Expr {
+    exprs
+        .into_iter()
+        .fold1(|lhs, rhs| Expr::LogicalOr(Box::new(lhs), Box::new(rhs)))
+}
+
+fn all(exprs: Vec<Expr>) -> Expr {
+    exprs
+        .into_iter()
+        .fold1(|lhs, rhs| Expr::LogicalAnd(Box::new(lhs), Box::new(rhs)))
+}
+
+trait Fold1: Iterator {
+    fn fold1<F: FnMut(Self::Item, Self::Item) -> Self::Item>(self, f: F) -> Self::Item;
+}
+
+impl<I: Iterator<Item = Expr>> Fold1 for I {
+    fn fold1<F: FnMut(Expr, Expr) -> Expr>(mut self, f: F) -> Expr {
+        let first = self.next().unwrap_or(Expr::Literal(Variant::Bool(false)));
+        self.fold(first, f)
+    }
+}
+
+/// Returns an expression testing the presence of a field.
+fn present(expr: Expr, flag: bool) -> Expr {
+    match expr {
+        Expr::Token(id) if flag => Expr::CmpGt(
+            Box::new(Expr::Count(id)),
+            Box::new(Expr::Literal(Variant::UInt64(0))),
+        ),
+        expr => expr,
+    }
+}
+
+fn consume_expr(pair: Pair<Rule>) -> Result<Expr, Error<Rule>> {
+    // Operators are listed in the order of increasing precedence.
+    let climber = PrecClimber::new(vec![
+        Operator::new(Rule::op_or, Assoc::Left),
+        Operator::new(Rule::op_xor, Assoc::Left),
+        Operator::new(Rule::op_and, Assoc::Left),
+    ]);
+    let primary = |pair: Pair<Rule>| consume_term(pair);
+    let infix = |lhs: Result<Expr, Error<Rule>>, op: Pair<Rule>, rhs: Result<Expr, Error<Rule>>| {
+        let (lhs, rhs) = (Box::new(lhs?), Box::new(rhs?));
+        Ok(match op.as_rule() {
+            Rule::op_or => Expr::LogicalOr(lhs, rhs),
+            Rule::op_and => Expr::LogicalAnd(lhs, rhs),
+            _ => {
+                let both = Expr::LogicalAnd(lhs.clone(), rhs.clone());
+                Expr::LogicalAnd(
+                    Box::new(Expr::LogicalOr(lhs, rhs)),
+                    Box::new(Expr::LogicalNegation(Box::new(both))),
+                )
+            }
+        })
+    };
+    climber.climb(pair.into_inner(), primary, infix)
+}
+
+fn consume_term(pair: Pair<Rule>) -> Result<Expr, Error<Rule>> {
+    // op_not* relation
+    let mut items = pair.into_inner().collect::<Vec<_>>();
+    let mut result = consume_relation(items.pop().unwrap())?;
+    for _ in items {
+        result = Expr::LogicalNegation(Box::new(result));
+    }
+    Ok(result)
+}
+
+fn consume_relation(pair: Pair<Rule>) -> Result<Expr, Error<Rule>> {
+    let span = pair.as_span();
+    let mut items = pair.into_inner();
+    let lhs_span = items.peek().unwrap().as_span();
+    let lhs = consume_operand(items.next().unwrap())?;
+    let op = match items.next() {
+        Some(op) => op,
+        None => {
+            return match lhs {
+                Operand::Exprs(exprs, flag) => Ok(any(exprs
+                    .into_iter()
+                    .map(|expr| present(expr, flag))
+                    .collect())),
+                Operand::Cidr(_) => error(lhs_span, "address block without a field".to_string()),
+            };
+        }
+    };
+    if op.as_rule() == Rule::membership {
+        return consume_membership(op, lhs, span);
+    }
+    let rhs = consume_operand(items.next().unwrap())?;
+    compare(&op, lhs, rhs)
+}
+
+fn compare(op: &Pair<Rule>, lhs: Operand, rhs: Operand) -> Result<Expr, Error<Rule>> {
+    let span = op.as_span();
+    let rule = op.as_rule();
+    let (lhs, rhs, lflag, rflag) = match (lhs, rhs) {
+        (Operand::Exprs(lhs, lflag), Operand::Exprs(rhs, rflag)) => (lhs, rhs, lflag, rflag),
+        (Operand::Exprs(exprs, flag), Operand::Cidr(cidr))
+        | (Operand::Cidr(cidr), Operand::Exprs(exprs, flag)) => {
+            let set = Set::new(vec![Member::Cidr(cidr)]);
+            let tests = exprs
+                .iter()
+                .map(|expr| Expr::In(Box::new(expr.clone()), set.clone()))
+                .collect::<Vec<_>>();
+            return match rule {
+                Rule::op_eq => Ok(any(tests)),
+                Rule::op_all_eq => Ok(all(tests)),
+                Rule::op_ne | Rule::op_any_ne => {
+                    let tests = tests
+                        .into_iter()
+                        .map(|test| Expr::LogicalNegation(Box::new(test)))
+                        .collect::<Vec<_>>();
+                    let tests = if rule == Rule::op_ne {
+                        all(tests)
+                    } else {
+                        any(tests)
+                    };
+                    let present = any(exprs.into_iter().map(|e| present(e, flag)).collect());
+                    Ok(Expr::LogicalAnd(Box::new(present), Box::new(tests)))
+                }
+                _ => error(
+                    span,
+                    format!("address block cannot be compared with {}", op.as_str()),
+                ),
+            };
+        }
+        (Operand::Cidr(_), Operand::Cidr(_)) => {
+            return error(span, "address blocks cannot be compared".to_string())
+        }
+    };
+
+    // Flags are compared with booleans.
+    let boolean = |exprs: Vec<Expr>, flag: bool| {
+        exprs
+            .into_iter()
+            .map(|expr| match expr {
+                Expr::Literal(Variant::UInt64(n)) if flag && n <= 1 => {
+                    Expr::Literal(Variant::Bool(n == 1))
+                }
+                expr => expr,
+            })
+            .collect::<Vec<_>>()
+    };
+    let lhs = boolean(lhs, rflag);
+    let rhs = boolean(rhs, lflag);
+
+    let pattern = if rule == Rule::op_matches {
+        match rhs.as_slice() {
+            [Expr::Literal(Variant::String(pattern))] => Some(
+                Pattern::new(&format!("(?i){}", pattern))
+                    .or_else(|message| error(span, message))?,
+            ),
+            _ => {
+                return error(
+                    span,
+                    "regular expression must be a string literal".to_string(),
+                )
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut tests = Vec::new();
+    for l in &lhs {
+        for r in &rhs {
+            let (l, r) = (Box::new(l.clone()), Box::new(r.clone()));
+            tests.push(match rule {
+                Rule::op_eq | Rule::op_all_eq => Expr::CmpEq(l, r),
+                Rule::op_ne | Rule::op_any_ne => Expr::CmpNotEq(l, r),
+                Rule::op_gt => Expr::CmpGt(l, r),
+                Rule::op_lt => Expr::CmpLt(l, r),
+                Rule::op_gte => Expr::CmpGte(l, r),
+                Rule::op_lte => Expr::CmpLte(l, r),
+                Rule::op_contains => Expr::Contains(l, r),
+                _ => Expr::Matches(l, pattern.clone().unwrap()),
+            });
+        }
+    }
+    Ok(match rule {
+        Rule::op_all_eq => all(tests),
+        Rule::op_ne | Rule::op_any_ne => {
+            let tests = if rule == Rule::op_ne {
+                all(tests)
+            } else {
+                any(tests)
+            };
+            // A missing field is not unequal.
+            let fields = lhs
+                .into_iter()
+                .map(|expr| present(expr, lflag))
+                .filter(|expr| !is_literal(expr))
+                .collect::<Vec<_>>();
+            if fields.is_empty() {
+                tests
+            } else {
+                Expr::LogicalAnd(Box::new(any(fields)), Box::new(tests))
+            }
+        }
+        _ => any(tests),
+    })
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    matches!(expr, Expr::Literal(_))
+}
+
+fn consume_membership(pair: Pair<Rule>, lhs: Operand, span: Span) -> Result<Expr, Error<Rule>> {
+    let (exprs, flag) = match lhs {
+        Operand::Exprs(exprs, flag) => (exprs, flag),
+        Operand::Cidr(_) => return error(span, "address block without a field".to_string()),
+    };
+    let mut negated = false;
+    let mut members = Vec::new();
+    for item in pair.into_inner() {
+        match item.as_rule() {
+            Rule::op_not => negated = true,
+            Rule::set => {
+                for member in item.into_inner() {
+                    members.push(consume_member(member, flag)?);
+                }
+            }
+            _ => {}
+        }
+    }
+    let set = Set::new(members);
+    let tests = exprs
+        .into_iter()
+        .map(|expr| Expr::In(Box::new(expr), set.clone()))
+        .collect();
+    Ok(if negated {
+        Expr::LogicalNegation(Box::new(any(tests)))
+    } else {
+        any(tests)
+    })
+}
+
+fn consume_member(pair: Pair<Rule>, flag: bool) -> Result<Member, Error<Rule>> {
+    let span = pair.as_span();
+    if pair.as_rule() == Rule::range {
+        let mut bounds = pair.into_inner();
+        let lo = consume_member(bounds.next().unwrap(), flag)?;
+        let hi = consume_member(bounds.next().unwrap(), flag)?;
+        return match (lo, hi) {
+            (Member::Value(lo), Member::Value(hi)) => Ok(Member::Range(lo, hi)),
+            _ => error(span, "invalid range".to_string()),
+        };
+    }
+    match consume_operand(pair)? {
+        Operand::Cidr(cidr) => Ok(Member::Cidr(cidr)),
+        Operand::Exprs(ref exprs, _) if exprs.len() == 1 => match &exprs[0] {
+            Expr::Literal(Variant::UInt64(n)) if flag && *n <= 1 => {
+                Ok(Member::Value(Variant::Bool(*n == 1)))
+            }
+            Expr::Literal(value) => Ok(Member::Value(value.clone())),
+            _ => error(span, "set members must be values".to_string()),
+        },
+        _ => error(span, "set members must be values".to_string()),
+    }
+}
+
+fn consume_operand(pair: Pair<Rule>) -> Result<Operand, Error<Rule>> {
+    let mut items = pair.into_inner();
+    let value = consume_value(items.next().unwrap())?;
+    if let Some(op) = items.next() {
+        return error(
+            op.as_span(),
+            format!("operator {} is not supported", op.as_str().trim()),
+        );
+    }
+    Ok(value)
+}
+
+fn consume_value(pair: Pair<Rule>) -> Result<Operand, Error<Rule>> {
+    let mut items = pair.into_inner();
+    let mut value = consume_atom(items.next().unwrap())?;
+    for item in items {
+        let span = item.as_span();
+        if item.as_rule() == Rule::layer {
+            return error(span, "layer operators are not supported".to_string());
+        }
+        let mut ranges = item.into_inner();
+        let range = ranges.next().unwrap();
+        if ranges.next().is_some() {
+            return error(
+                span,
+                "slices of several ranges are not supported".to_string(),
+            );
+        }
+        let (start, end) = match slice_range(range.as_str()) {
+            Some(range) => range,
+            None => return error(range.as_span(), "invalid slice".to_string()),
+        };
+        value = match value {
+            Operand::Exprs(exprs, _) => Operand::Exprs(
+                exprs
+                    .into_iter()
+                    .map(|expr| Expr::Slice(Box::new(expr), start, end))
+                    .collect(),
+                false,
+            ),
+            Operand::Cidr(_) => return error(span, "slice of an address block".to_string()),
+        };
+    }
+    Ok(value)
+}
+
+/// Parses a slice range into the start and the end, e.g. `1:2` of two
+/// bytes or `1-2` of the bytes from 1 to 2.
+fn slice_range(s: &str) -> Option<(Option<i64>, Option<i64>)> {
+    // The end of a slice to the end of the data is given as None.
+    let end = |start: i64, end: i64| {
+        if start < 0 && end >= 0 {
+            None
+        } else {
+            Some(end)
+        }
+    };
+    let s = s.trim();
+    let (negative, rest) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let start = match &rest[..digits] {
+        "" if negative => return None,
+        "" => None,
+        n => Some(n.parse::<i64>().ok()? * if negative { -1 } else { 1 }),
+    };
+    let mut rest = rest[digits..].chars();
+    let sep = match rest.next() {
+        Some(sep) => sep,
+        None => {
+            let start = start?;
+            return Some((Some(start), end(start, start + 1)));
+        }
+    };
+    let n = rest.as_str().trim();
+    let n = if n.is_empty() {
+        None
+    } else {
+        Some(n.parse::<i64>().ok()?)
+    };
+    match (sep, start, n) {
+        (':', start, Some(n)) if n >= 0 => {
+            let start = start.unwrap_or(0);
+            Some((Some(start), end(start, start + n)))
+        }
+        (':', start, None) => Some((Some(start.unwrap_or(0)), None)),
+        ('-', Some(start), Some(n)) => Some((Some(start), end(start, n + 1))),
+        _ => None,
+    }
+}
+
+fn consume_atom(pair: Pair<Rule>) -> Result<Operand, Error<Rule>> {
+    let span = pair.as_span();
+    let literal = |value| Ok(Operand::Exprs(vec![Expr::Literal(value)], false));
+    match pair.as_rule() {
+        Rule::expression => Ok(Operand::Exprs(vec![consume_expr(pair)?], false)),
+        Rule::braces => Ok(Operand::Exprs(
+            vec![consume_expr(pair.into_inner().next().unwrap())?],
+            false,
+        )),
+        Rule::call => consume_call(pair),
+        Rule::field_ref => error(span, "field references are not supported".to_string()),
+        Rule::string => match unquote(pair.as_str()) {
+            Some(s) => literal(Variant::String(s.into_boxed_str())),
+            None => error(span, "invalid string".to_string()),
+        },
+        Rule::char => {
+            let s = unquote(pair.as_str());
+            let mut chars = s.as_ref().map(|s| s.chars());
+            match chars.as_mut().map(|chars| (chars.next(), chars.next())) {
+                Some((Some(c), None)) => literal(Variant::UInt64(u64::from(u32::from(c)))),
+                _ => error(span, "invalid character".to_string()),
+            }
+        }
+        Rule::cidr => Cidr::parse(pair.as_str())
+            .map(Operand::Cidr)
+            .or_else(|message| error(span, message)),
+        _ => consume_word(pair),
+    }
+}
+
+/// Decodes a string or a character literal, e.g. `"a\x41"` or `'a'`.
+fn unquote(s: &str) -> Option<String> {
+    if s.starts_with('r') || s.starts_with('R') {
+        return Some(s[2..s.len() - 1].to_string());
+    }
+    let mut result = String::new();
+    let mut chars = s[1..s.len() - 1].chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        let c = chars.next()?;
+        let (radix, len) = match c {
+            'x' => (16, 2),
+            '0'..='7' => (8, 3),
+            _ => {
+                result.push(match c {
+                    'a' => '\x07',
+                    'b' => '\x08',
+                    'f' => '\x0c',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'v' => '\x0b',
+                    c => c,
+                });
+                continue;
+            }
+        };
+        let mut digits = if radix == 8 {
+            c.to_string()
+        } else {
+            String::new()
+        };
+        while digits.len() < len {
+            match chars.peek() {
+                Some(c) if c.is_digit(radix) => digits.push(*c),
+                _ => break,
+            }
+            chars.next();
+        }
+        result.push(char::from(u8::from_str_radix(&digits, radix).ok()?));
+    }
+    Some(result)
+}
+
+fn consume_call(pair: Pair<Rule>) -> Result<Operand, Error<Rule>> {
+    let span = pair.as_span();
+    let mut items = pair.into_inner();
+    let name = items.next().unwrap().as_str();
+    let args = items.collect::<Vec<_>>();
+    if args.len() != 1 {
+        return error(span, format!("{}() takes 1 argument", name));
+    }
+    let arg = args.into_iter().next().unwrap();
+    let arg_span = arg.as_span();
+    let exprs = match consume_arg(arg)? {
+        Operand::Exprs(exprs, _) => exprs,
+        Operand::Cidr(_) => return error(arg_span, "invalid argument".to_string()),
+    };
+    let exprs = match name {
+        "count" => match exprs.as_slice() {
+            [Expr::Token(id)] => vec![Expr::Count(*id)],
+            _ => return error(arg_span, "count() takes a field".to_string()),
+        },
+        "len" | "upper" | "lower" => {
+            let func = functions::get(name).unwrap();
+            exprs
+                .into_iter()
+                .map(|expr| Expr::Call(func.clone(), vec![expr]))
+                .collect()
+        }
+        _ => return error(span, format!("function {}() is not supported", name)),
+    };
+    Ok(Operand::Exprs(exprs, false))
+}
+
+/// Parses an argument of a function, which stands for several attributes if
+/// it is a field like `ip.addr`.
+fn consume_arg(pair: Pair<Rule>) -> Result<Operand, Error<Rule>> {
+    let mut terms = pair.clone().into_inner();
+    if let (Some(term), None) = (terms.next(), terms.next()) {
+        let mut items = term.into_inner();
+        if let (Some(relation), None) = (items.next(), items.next()) {
+            if relation.as_rule() == Rule::relation {
+                let mut items = relation.into_inner();
+                if let (Some(operand), None) = (items.next(), items.next()) {
+                    return consume_operand(operand);
+                }
+            }
+        }
+    }
+    Ok(Operand::Exprs(vec![consume_expr(pair)?], false))
+}
+
+fn consume_word(pair: Pair<Rule>) -> Result<Operand, Error<Rule>> {
+    let span = pair.as_span();
+    let word = pair.as_str();
+    let literal = |value| Ok(Operand::Exprs(vec![Expr::Literal(value)], false));
+    let buffer = |bytes: Vec<u8>| literal(Variant::Buffer(bytes.into_boxed_slice()));
+    if let Some(value) = number(word) {
+        return literal(value);
+    }
+    if word.contains(':') || word.starts_with(|c: char| c.is_ascii_digit()) {
+        if let Ok(addr) = word.parse::<Ipv4Addr>() {
+            return buffer(addr.octets().to_vec());
+        }
+        if let Some(bytes) = bytes(word) {
+            return buffer(bytes);
+        }
+        if let Ok(addr) = word.parse::<Ipv6Addr>() {
+            return buffer(addr.octets().to_vec());
+        }
+        return error(span, format!("invalid value: {}", word));
+    }
+    match word {
+        "True" | "true" | "TRUE" => return literal(Variant::Bool(true)),
+        "False" | "false" | "FALSE" => return literal(Variant::Bool(false)),
+        _ => {}
+    }
+    field(word).or_else(|message| error(span, message))
+}
+
+/// Parses an integer or a float, e.g. `10`, `0x0a`, `012` or `1.5`.
+fn number(word: &str) -> Option<Variant> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word),
+    };
+    let (digits, radix) = if digits.starts_with("0x") || digits.starts_with("0X") {
+        (&digits[2..], 16)
+    } else if digits.starts_with("0b") || digits.starts_with("0B") {
+        (&digits[2..], 2)
+    } else if digits.len() > 1 && digits.starts_with('0') && !digits.contains('.') {
+        (&digits[1..], 8)
+    } else {
+        (digits, 10)
+    };
+    if !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix)) {
+        let mut v = BigInt::from_str_radix(digits, radix).ok()?;
+        if negative {
+            v = -v;
+        }
+        return Some(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink());
+    }
+    let mut parts = word.splitn(2, '.');
+    let (int, frac) = (parts.next()?, parts.next()?);
+    let int = int.trim_start_matches('-');
+    if int.is_empty()
+        || frac.is_empty()
+        || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    word.parse().ok().map(Variant::Float64)
+}
+
+/// Parses a byte string separated by `:`, `-` or `.`, e.g. `aa:bb:cc`.
+fn bytes(word: &str) -> Option<Vec<u8>> {
+    let sep = word.chars().find(|c| *c == ':' || *c == '-' || *c == '.')?;
+    word.split(sep)
+        .map(|b| {
+            if b.len() == 2 {
+                u8::from_str_radix(b, 16).ok()
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Maps a Wireshark field name to the genet attributes.
+fn field(name: &str) -> Result<Operand, String> {
+    let alias;
+    let name = if name.starts_with("ssl.") || name == "ssl" {
+        alias = format!("tls{}", &name[3..]);
+        alias.as_str()
+    } else {
+        name
+    };
+    if let Some((_, reason)) = UNSUPPORTED_FIELDS.iter().find(|(n, _)| *n == name) {
+        return Err(format!("field {} is not supported: {}", name, reason));
+    }
+    let expr = match name {
+        "frame.protocols" => Some(Expr::Protocols),
+        "frame.marked" => Some(Expr::Marked),
+        _ => None,
+    };
+    if let Some(expr) = expr {
+        return Ok(Operand::Exprs(vec![expr], false));
+    }
+    let ids = FIELDS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map_or_else(|| vec![name], |(_, ids)| ids.to_vec());
+    let flag = ids.iter().all(|id| FLAGS.contains(id));
+    let exprs = ids
+        .into_iter()
+        .map(|id| Expr::Token(Token::from(id)))
+        .collect();
+    Ok(Operand::Exprs(exprs, flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use context::Context;
+    use Filter;
+
+    fn translated(filter: &str) -> String {
+        translate(filter).unwrap()
+    }
+
+    fn message(filter: &str) -> String {
+        match parse(filter).unwrap_err().variant {
+            ErrorVariant::CustomError { message } => message,
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(translated("ip"), "ipv4");
+        assert_eq!(translated("frame.len > 100"), "link.length > 100");
+        assert_eq!(
+            translated("ip.addr == 10.0.0.1"),
+            unparse(&parse("ipv4.src == 10.0.0.1 || ipv4.dst == 10.0.0.1").unwrap())
+        );
+        assert_eq!(
+            translated("tcp.port != 80"),
+            "(tcp.src || tcp.dst) && tcp.src != 80 && tcp.dst != 80"
+        );
+        assert_eq!(translated("tcp.flags.syn == 1"), "tcp.flags.syn");
+        assert_eq!(translated("tcp.flags.push eq 0"), "!tcp.flags.psh");
+        assert_eq!(translated("tcp.flags.syn"), "count(tcp.flags.syn) > 0");
+        assert_eq!(translated("ssl.handshake.ja3"), "tls.ja3");
+        assert_eq!(translated("frame.marked"), "frame.marked");
+        assert_eq!(translated("dns.custom.field"), "dns.custom.field");
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(
+            translated("tcp and not udp or !arp && dns"),
+            "tcp && !udp || !arp && dns"
+        );
+        assert_eq!(
+            parse("a xor b").unwrap(),
+            ::parser::parse("(a || b) && !(a && b)").unwrap()
+        );
+        assert_eq!(
+            translated(r#"http.host contains "example""#),
+            r#"http.host contains "example""#
+        );
+        assert_eq!(
+            translated(r#"http.user_agent matches "curl""#),
+            r#"http.userAgent ~ "(?i)curl""#
+        );
+        assert_eq!(
+            translated("tcp.dstport in {80 443 8000..8080}"),
+            "tcp.dst in {80, 443, 8000..8080}"
+        );
+        assert_eq!(
+            translated("udp.port not in {53, 5353}"),
+            "!(udp.src in {53, 5353} || udp.dst in {53, 5353})"
+        );
+        assert_eq!(
+            translated("len(dns.qry.name) ge 50"),
+            "len(dns.query.name) >= 50"
+        );
+        assert_eq!(translated("count(ip) > 1"), "count(ipv4) > 1");
+    }
+
+    #[test]
+    fn values() {
+        let test = |filter: &str, expr: &str| {
+            assert_eq!(
+                parse(filter).unwrap(),
+                ::parser::parse(expr).unwrap(),
+                "{}",
+                filter
+            );
+        };
+        test("ip.src == 10.0.0.1", "ipv4.src == @10.0.0.1");
+        test("ipv6.src == fe80::1", "ipv6.src == @fe80::1");
+        test(
+            "eth.src == 00:11:22:33:44:55",
+            "eth.src == @00:11:22:33:44:55",
+        );
+        test(
+            "eth.src == 00-11-22-33-44-55",
+            "eth.src == @00:11:22:33:44:55",
+        );
+        test("ip.ttl == 010", "ipv4.ttl == 8");
+        test("ip.ttl == 0x40", "ipv4.ttl == 64");
+        test("frame.time_delta > 1.5", "frame.time_delta > 1.5");
+        test("ip.src == 10.0.0.0/8", "ipv4.src in 10.0.0.0/8");
+        test(r#"http.host == "a\x41\"""#, r#"http.host == "aA\"""#);
+        test(r#"http.uri == r"\d""#, r#"http.uri == "\\d""#);
+        test("tcp.window == 'A'", "tcp.window == 65");
+        test("eth.src[0:3] == 00:11:22", "eth.src[0:3] == 00:11:22");
+        test("eth.src[1-2] == 11:22", "eth.src[1:3] == 11:22");
+        test("eth.src[-1] == 0x55", "eth.src[-1:] == 0x55");
+        test("eth.src[:2] == 00:11", "eth.src[0:2] == 00:11");
+        test("eth.src[4:] == 44:55", "eth.src[4:] == 44:55");
+        test("tcp.flags.syn == True", "tcp.flags.syn == true");
+    }
+
+    #[test]
+    fn unsupported() {
+        assert_eq!(message("tcp.flags & 0x02"), "operator & is not supported");
+        assert_eq!(
+            message("tcp.srcport + 1 == 80"),
+            "operator + is not supported"
+        );
+        assert_eq!(
+            message("ip.src#2 == 1.1.1.1"),
+            "layer operators are not supported"
+        );
+        assert_eq!(
+            message("ip.src == ${ip.dst}"),
+            "field references are not supported"
+        );
+        assert_eq!(
+            message("eth.src[0:1,3:1]"),
+            "slices of several ranges are not supported"
+        );
+        assert_eq!(message("abs(tcp.port)"), "function abs() is not supported");
+        assert_eq!(
+            message("frame.number == 1"),
+            "field frame.number is not supported: frames are not numbered by an attribute"
+        );
+        assert_eq!(
+            message("ip.src > 10.0.0.0/8"),
+            "address block cannot be compared with >"
+        );
+        assert!(parse("ip.src ==").is_err());
+        assert!(parse("tcp.port in {tcp.dst}").is_err());
+        assert!(parse("ip.src == 1.2.3").is_err());
+    }
+
+    #[test]
+    fn compile() {
+        let filter = Filter::compile_wireshark("!tcp && !frame.len").unwrap();
+        assert!(filter.test(&Context::new(&[])));
+        let filter = Filter::compile_wireshark("tcp.port != 80").unwrap();
+        assert!(!filter.test(&Context::new(&[])));
+        assert!(Filter::compile_wireshark("tcp.flags & 2").is_err());
+    }
+}
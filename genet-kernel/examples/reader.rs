@@ -38,7 +38,7 @@ impl Reader for TestReader {
 struct TestWorker {}
 
 impl Worker for TestWorker {
-    fn read(&mut self) -> Result<Vec<Layer>> {
+    fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
         let layers = iter::repeat(())
             .take(1000)
             .map(|_| Layer::new(&ETH_CLASS, ByteSlice::from(tcp_ipv4_pcap())))
@@ -0,0 +1,1192 @@
+//! Cross-frame analysis.
+//!
+//! Decoders see a single frame at a time, so attributes relating a frame to
+//! the other frames are computed by a pass over the decoded frames in
+//! capture order. The results are added to the layers as attributes, so
+//! filters reference them like the attributes of the decoders:
+//!
+//! - `frame.time_delta`, `frame.time_relative`: the seconds since the
+//!   previous and the first frame.
+//! - `frame.analysis.reordered`: the seconds the timestamp precedes the
+//!   latest timestamp seen, if within the reorder window, e.g. in a capture
+//!   merged from the queues of a multi-queue NIC.
+//! - `frame.analysis.clock_skew`: the same as `frame.analysis.reordered`
+//!   beyond the reorder window, e.g. after the clock is stepped back.
+//! - `tcp.stream`, `udp.stream`: the index of the conversation, shared by
+//!   both directions. The datagrams of a QUIC connection identified by the
+//!   decoder with `quic.connection` share the `udp.stream` regardless of
+//!   the addresses and ports, so the stream survives a connection
+//!   migration.
+//! - `tcp.time_delta`, `udp.time_delta`: the seconds since the previous
+//!   frame of the conversation.
+//! - `tcp.seq_relative`, `tcp.ack_relative`: the sequence and
+//!   acknowledgment numbers relative to the first sequence number seen in
+//!   the direction.
+//! - `tcp.analysis.retransmission`: set on segments whose sequence space
+//!   has already been seen in the same direction.
+//! - `tcp.analysis.out_of_order`: set instead of a retransmission if the
+//!   segment follows the previous one of the direction within the RTT,
+//!   e.g. a segment reordered in the network.
+//! - `tcp.analysis.dup_ack`: the number of the duplicate ACK, set on pure
+//!   ACKs repeating the acknowledgment number and window of the previous
+//!   ACK of the direction.
+//! - `tcp.analysis.zero_window`: set on segments advertising a zero
+//!   receive window.
+//! - `tcp.analysis.ack_rtt`: the seconds since the data segment
+//!   acknowledged by the segment was sent. Retransmitted data is not used
+//!   for measurement.
+//! - `tcp.analysis.acks_frame`: the index of the frame with the last data
+//!   segment acknowledged by the segment.
+//!
+//! - `dns.response_to`: the index of the frame with the query answered by a
+//!   DNS response, matched by the transaction ID within the `udp.stream`.
+//! - `dns.time`: the seconds since the query answered by a DNS response.
+//! - `dns.analysis.unanswered`: the index of the frame with an unanswered
+//!   query, set on a query reusing its transaction ID in the same
+//!   `udp.stream`, e.g. a retransmission after a timeout. Since the frames
+//!   are passed once, the flag is set on the later query.
+//! - `http.response_to`: the index of the frame with the request answered by
+//!   an HTTP response, matched in order within the `tcp.stream`, since
+//!   pipelined responses follow the order of the requests.
+//! - `http.time`: the seconds since the request answered by an HTTP
+//!   response. Interim 1xx responses are not matched.
+//!
+//! The `tcp.analysis.*` and `dns.analysis.*` flags are reported as expert
+//! information, except `tcp.analysis.ack_rtt` and `tcp.analysis.acks_frame`,
+//! and so are the `frame.analysis.*` attributes.
+//!
+//! Timestamps going backwards are handled according to the
+//! `TimestampPolicy` set by `TIMESTAMP_POLICY_KEY`. By default, the
+//! timestamps used by the analysis are clamped to the latest timestamp seen,
+//! so that time deltas, RTTs and idle timeouts are never negative. The
+//! timestamps of the frames are not modified.
+//!
+//! A 4-tuple reused by a new connection starts a new `tcp.stream` according
+//! to the `SplitPolicy` set by `SPLIT_POLICY_KEY`: by default on a SYN after
+//! a FIN or RST, and optionally after an idle gap, which also applies to
+//! `udp.stream`. The sequence analysis of the new stream starts over.
+//!
+//! Since frames are passed in capture order, the attributes do not depend
+//! on the number of decoder threads.
+//!
+//! Frames decoded lazily get the `frame.*` attributes when stored, and the
+//! others each time their upper layers are decoded.
+
+use frame::Frame;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::Fixed,
+    layer::Layer,
+    token::Token,
+    variant::{Value, Variant},
+};
+use profile::Profile;
+use serde_json;
+use std::collections::{HashMap, VecDeque};
+
+/// The config key of the `SplitPolicy`, a JSON object.
+pub const SPLIT_POLICY_KEY: &str = "_.analysis.splitPolicy";
+
+/// The config key of the `TimestampPolicy`, a JSON object.
+pub const TIMESTAMP_POLICY_KEY: &str = "_.analysis.timestampPolicy";
+
+/// The maximum number of unacknowledged segments kept per direction.
+const MAX_UNACKED: usize = 1024;
+
+/// The maximum number of HTTP requests waiting for a response per
+/// conversation.
+const MAX_PENDING_REQUESTS: usize = 1024;
+
+/// The reordering window used until the RTT of a flow is measured.
+const DEFAULT_REORDER_NANOS: i128 = 3_000_000;
+
+/// Returns the value of the attribute `id` of `layer` as `T`.
+pub(crate) fn attr<T>(layer: &Layer, id: impl Into<Token>) -> Option<T>
+where
+    Variant: Value<T>,
+{
+    layer
+        .attr(id)
+        .and_then(|attr| attr.try_get(layer).ok())
+        .and_then(|value| value.try_into().ok())
+}
+
+/// Returns the timestamp of a root layer in nanoseconds.
+///
+/// Timestamps are subtracted as integers, since a UNIX time in seconds as
+/// `f64` has no nanosecond precision.
+pub(crate) fn timestamp(root: &Layer) -> Option<i128> {
+    let sec = attr::<i64>(root, "link.timestamp.sec")?;
+    let nsec = attr::<u64>(root, "link.timestamp.nsec")
+        .or_else(|| attr::<u64>(root, "link.timestamp.usec").map(|usec| usec * 1000))
+        .unwrap_or(0);
+    Some(i128::from(sec) * 1_000_000_000 + i128::from(nsec))
+}
+
+/// Returns the seconds between two timestamps in nanoseconds.
+fn seconds(from: i128, to: i128) -> f64 {
+    (to - from) as f64 / 1e9
+}
+
+/// The policy starting a new conversation on a reused 4-tuple.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SplitPolicy {
+    /// Starts a new TCP stream on a SYN without ACK after a FIN or RST.
+    pub on_close: bool,
+    /// Starts a new stream on a frame following the previous frame of the
+    /// conversation by more than this number of seconds.
+    pub idle_timeout: Option<f64>,
+}
+
+impl Default for SplitPolicy {
+    fn default() -> SplitPolicy {
+        SplitPolicy {
+            on_close: true,
+            idle_timeout: None,
+        }
+    }
+}
+
+impl SplitPolicy {
+    /// Creates a new SplitPolicy from the value of `SPLIT_POLICY_KEY`, or the
+    /// default policy if the value is invalid.
+    pub fn from_config(value: &str) -> SplitPolicy {
+        serde_json::from_str(value).unwrap_or_default()
+    }
+
+    /// Returns true if a frame at `ts` starts a new conversation instead of
+    /// continuing `flow`.
+    fn splits(&self, flow: &Flow, syn: bool, ts: Option<i128>) -> bool {
+        if self.on_close && syn && flow.closed {
+            return true;
+        }
+        match (self.idle_timeout, flow.last, ts) {
+            (Some(timeout), Some(last), Some(ts)) => seconds(last, ts) > timeout,
+            _ => false,
+        }
+    }
+}
+
+/// The handling of timestamps going backwards.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TimestampPolicy {
+    /// Clamps the timestamps used by the analysis to the latest timestamp
+    /// seen.
+    pub clamp: bool,
+    /// The seconds a timestamp may go backwards to be regarded as reordered
+    /// instead of clock skew.
+    pub reorder_window: f64,
+}
+
+impl Default for TimestampPolicy {
+    fn default() -> TimestampPolicy {
+        TimestampPolicy {
+            clamp: true,
+            reorder_window: 0.01,
+        }
+    }
+}
+
+impl TimestampPolicy {
+    /// Creates a new TimestampPolicy from the value of
+    /// `TIMESTAMP_POLICY_KEY`, or the default policy if the value is invalid.
+    pub fn from_config(value: &str) -> TimestampPolicy {
+        serde_json::from_str(value).unwrap_or_default()
+    }
+}
+
+/// The endpoints of a conversation in a canonical order, so that both
+/// directions have the same key.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct FlowKey {
+    proto: Token,
+    lower: (Vec<u8>, u16),
+    upper: (Vec<u8>, u16),
+}
+
+impl FlowKey {
+    /// Returns the key and the direction of a frame from `src` to `dst`.
+    fn new(proto: Token, src: (Vec<u8>, u16), dst: (Vec<u8>, u16)) -> (FlowKey, usize) {
+        if src <= dst {
+            let key = FlowKey {
+                proto,
+                lower: src,
+                upper: dst,
+            };
+            (key, 0)
+        } else {
+            let key = FlowKey {
+                proto,
+                lower: dst,
+                upper: src,
+            };
+            (key, 1)
+        }
+    }
+}
+
+/// The TCP state of a direction of a conversation.
+#[derive(Default)]
+struct Direction {
+    /// The first sequence number seen.
+    isn: Option<u32>,
+    next_seq: Option<u32>,
+    last: Option<i128>,
+    ack: Option<(u32, u16)>,
+    dup_acks: u64,
+    /// The end sequence numbers, timestamps and frame indices of the data
+    /// segments waiting for an acknowledgment.
+    unacked: VecDeque<(u32, Option<i128>, u32)>,
+}
+
+/// The header fields of a TCP segment used by the analysis.
+struct Segment {
+    seq: u32,
+    /// The length in sequence space.
+    len: u32,
+    ack: Option<u32>,
+    window: Option<u16>,
+    /// True if any of SYN, FIN and RST is set.
+    control: bool,
+}
+
+#[derive(Default)]
+struct TcpAnalysis {
+    retransmission: bool,
+    out_of_order: bool,
+    dup_ack: Option<u64>,
+    zero_window: bool,
+    ack_rtt: Option<f64>,
+    acks_frame: Option<u32>,
+    seq_relative: u32,
+    ack_relative: Option<u32>,
+}
+
+struct Flow {
+    index: u64,
+    last: Option<i128>,
+    /// True if a FIN or RST has been seen in either direction.
+    closed: bool,
+    dirs: [Direction; 2],
+    rtt: Option<i128>,
+    /// The timestamps and frame indices of the DNS queries waiting for a
+    /// response, by transaction ID.
+    queries: HashMap<u16, (Option<i128>, u32)>,
+    /// The timestamps and frame indices of the HTTP requests waiting for a
+    /// response, in order.
+    requests: VecDeque<(Option<i128>, u32)>,
+}
+
+impl Flow {
+    fn analyze_tcp(
+        &mut self,
+        dir: usize,
+        seg: &Segment,
+        ts: Option<i128>,
+        frame: u32,
+    ) -> TcpAnalysis {
+        let mut result = TcpAnalysis::default();
+        let reorder = self.rtt.unwrap_or(DEFAULT_REORDER_NANOS);
+        {
+            let d = &mut self.dirs[dir];
+            result.seq_relative = seg.seq.wrapping_sub(*d.isn.get_or_insert(seg.seq));
+            let end = seg.seq.wrapping_add(seg.len);
+            match d.next_seq {
+                Some(next) if seg.len > 0 && next.wrapping_sub(end) as i32 >= 0 => {
+                    match (d.last, ts) {
+                        (Some(last), Some(ts)) if ts - last < reorder => result.out_of_order = true,
+                        _ => {
+                            result.retransmission = true;
+                            d.unacked.clear();
+                        }
+                    }
+                }
+                _ => {
+                    if seg.len > 0 {
+                        if d.unacked.len() >= MAX_UNACKED {
+                            d.unacked.pop_front();
+                        }
+                        d.unacked.push_back((end, ts, frame));
+                    }
+                }
+            }
+            let next = match d.next_seq {
+                Some(next) if (end.wrapping_sub(next) as i32) < 0 => next,
+                _ => end,
+            };
+            d.next_seq = Some(next);
+            if ts.is_some() {
+                d.last = ts;
+            }
+
+            result.zero_window = seg.window == Some(0) && !seg.control;
+            if let Some(ack) = seg.ack {
+                let ack = (ack, seg.window.unwrap_or(0));
+                if seg.len == 0 && !seg.control && d.ack == Some(ack) {
+                    d.dup_acks += 1;
+                    result.dup_ack = Some(d.dup_acks);
+                } else {
+                    d.dup_acks = 0;
+                }
+                d.ack = Some(ack);
+            }
+        }
+
+        if let Some(ack) = seg.ack {
+            let other = &mut self.dirs[1 - dir];
+            result.ack_relative = other.isn.map(|isn| ack.wrapping_sub(isn));
+            let mut sent = None;
+            while let Some(&(end, sent_ts, sent_frame)) = other.unacked.front() {
+                if (ack.wrapping_sub(end) as i32) < 0 {
+                    break;
+                }
+                sent = sent_ts;
+                result.acks_frame = Some(sent_frame);
+                other.unacked.pop_front();
+            }
+            if let (Some(sent), Some(ts)) = (sent, ts) {
+                self.rtt = Some(ts - sent);
+                result.ack_rtt = Some(seconds(sent, ts));
+            }
+        }
+        result
+    }
+}
+
+struct Classes {
+    time_delta: Fixed<AttrClass>,
+    time_relative: Fixed<AttrClass>,
+    reordered: Fixed<AttrClass>,
+    clock_skew: Fixed<AttrClass>,
+    tcp_stream: Fixed<AttrClass>,
+    tcp_time_delta: Fixed<AttrClass>,
+    tcp_seq_relative: Fixed<AttrClass>,
+    tcp_ack_relative: Fixed<AttrClass>,
+    tcp_retransmission: Fixed<AttrClass>,
+    tcp_out_of_order: Fixed<AttrClass>,
+    tcp_dup_ack: Fixed<AttrClass>,
+    tcp_zero_window: Fixed<AttrClass>,
+    tcp_ack_rtt: Fixed<AttrClass>,
+    tcp_acks_frame: Fixed<AttrClass>,
+    udp_stream: Fixed<AttrClass>,
+    udp_time_delta: Fixed<AttrClass>,
+    dns_response_to: Fixed<AttrClass>,
+    dns_time: Fixed<AttrClass>,
+    dns_unanswered: Fixed<AttrClass>,
+    http_response_to: Fixed<AttrClass>,
+    http_time: Fixed<AttrClass>,
+}
+
+impl Classes {
+    fn new() -> Classes {
+        let class = |id: &str| Fixed::new(AttrClass::builder(id).build());
+        let expert = |id: &str, typ: &str, desc: &'static str| {
+            Fixed::new(AttrClass::builder(id).typ(typ).description(desc).build())
+        };
+        Classes {
+            time_delta: class("frame.time_delta"),
+            time_relative: class("frame.time_relative"),
+            reordered: expert(
+                "frame.analysis.reordered",
+                "@expert:note",
+                "Timestamp earlier than the previous frame",
+            ),
+            clock_skew: expert(
+                "frame.analysis.clock_skew",
+                "@expert:warn",
+                "Timestamp goes backwards",
+            ),
+            tcp_stream: class("tcp.stream"),
+            tcp_time_delta: class("tcp.time_delta"),
+            tcp_seq_relative: class("tcp.seq_relative"),
+            tcp_ack_relative: class("tcp.ack_relative"),
+            tcp_retransmission: expert(
+                "tcp.analysis.retransmission",
+                "@expert:note",
+                "Retransmission",
+            ),
+            tcp_out_of_order: expert(
+                "tcp.analysis.out_of_order",
+                "@expert:warn",
+                "Out-of-order segment",
+            ),
+            tcp_dup_ack: expert("tcp.analysis.dup_ack", "@expert:note", "Duplicate ACK"),
+            tcp_zero_window: expert("tcp.analysis.zero_window", "@expert:warn", "Zero window"),
+            tcp_ack_rtt: class("tcp.analysis.ack_rtt"),
+            tcp_acks_frame: class("tcp.analysis.acks_frame"),
+            udp_stream: class("udp.stream"),
+            udp_time_delta: class("udp.time_delta"),
+            dns_response_to: class("dns.response_to"),
+            dns_time: class("dns.time"),
+            dns_unanswered: expert(
+                "dns.analysis.unanswered",
+                "@expert:warn",
+                "Query without a response",
+            ),
+            http_response_to: class("http.response_to"),
+            http_time: class("http.time"),
+        }
+    }
+}
+
+/// Computes the cross-frame attributes of frames passed in capture order.
+pub struct Analyzer {
+    classes: Classes,
+    split: SplitPolicy,
+    timestamps: TimestampPolicy,
+    first: Option<i128>,
+    last: Option<i128>,
+    /// The latest timestamp seen.
+    latest: Option<i128>,
+    flows: HashMap<FlowKey, Flow>,
+    streams: [u64; 2],
+}
+
+impl Default for Analyzer {
+    fn default() -> Analyzer {
+        Analyzer::new()
+    }
+}
+
+impl Analyzer {
+    pub fn new() -> Analyzer {
+        Self::with_policies(SplitPolicy::default(), TimestampPolicy::default())
+    }
+
+    /// Creates an analyzer with the policies set in the config of `profile`.
+    pub fn from_profile(profile: &Profile) -> Analyzer {
+        let split = profile
+            .get_config(SPLIT_POLICY_KEY)
+            .map_or_else(SplitPolicy::default, |value| {
+                SplitPolicy::from_config(&value)
+            });
+        let timestamps = profile
+            .get_config(TIMESTAMP_POLICY_KEY)
+            .map_or_else(TimestampPolicy::default, |value| {
+                TimestampPolicy::from_config(&value)
+            });
+        Self::with_policies(split, timestamps)
+    }
+
+    pub fn with_policies(split: SplitPolicy, timestamps: TimestampPolicy) -> Analyzer {
+        Analyzer {
+            classes: Classes::new(),
+            split,
+            timestamps,
+            first: None,
+            last: None,
+            latest: None,
+            flows: HashMap::new(),
+            streams: [0; 2],
+        }
+    }
+
+    /// Adds the cross-frame attributes to `frame`, which must follow the
+    /// frames previously passed.
+    pub fn process(&mut self, frame: &mut Frame) {
+        let tcp = Token::from("tcp");
+        let udp = Token::from("udp");
+        let dns = Token::from("dns");
+        let http = Token::from("http");
+        let ts = frame.layers().first().and_then(|root| timestamp(root));
+        let frame_index = frame.index();
+        let ts = self.process_root(frame, ts);
+        let connection = frame
+            .layers()
+            .iter()
+            .find(|layer| layer.id() == Token::from("quic"))
+            .and_then(|layer| attr::<Vec<u8>>(layer, "quic.connection"));
+        let mut tcp_flow = None;
+        let mut udp_flow = None;
+        for index in 1..frame.layers().len() {
+            let id = frame.layers()[index].id();
+            if id == dns {
+                if let Some(key) = &udp_flow {
+                    let layer = &mut frame.layers_mut()[index];
+                    self.process_dns(layer, key, ts, frame_index);
+                }
+                continue;
+            }
+            if id == http {
+                if let Some(key) = &tcp_flow {
+                    let layer = &mut frame.layers_mut()[index];
+                    self.process_http(layer, key, ts, frame_index);
+                }
+                continue;
+            }
+            if id != tcp && id != udp {
+                continue;
+            }
+            let addrs = frame.layers()[..index]
+                .iter()
+                .rev()
+                .filter_map(|layer| {
+                    Some((
+                        attr::<Vec<u8>>(layer, "_.src")?,
+                        attr::<Vec<u8>>(layer, "_.dst")?,
+                    ))
+                })
+                .next();
+            if let Some((src, dst)) = addrs {
+                let layer = &mut frame.layers_mut()[index];
+                if id == tcp {
+                    tcp_flow = self.process_tcp(layer, (src, dst), ts, frame_index);
+                } else {
+                    udp_flow = self.process_udp(layer, (src, dst), connection.clone(), ts);
+                }
+            }
+        }
+    }
+
+    /// Adds the `frame.*` attributes and returns the timestamp used by the
+    /// analysis, which is clamped if the policy says so.
+    fn process_root(&mut self, frame: &mut Frame, ts: Option<i128>) -> Option<i128> {
+        let raw = ts?;
+        let first = *self.first.get_or_insert(raw);
+        let latest = self.latest.map_or(raw, |latest| latest.max(raw));
+        self.latest = Some(latest);
+        let ts = if self.timestamps.clamp { latest } else { raw };
+        let delta = self.last.map_or(0.0, |last| seconds(last, ts));
+        self.last = Some(ts);
+        if let Some(root) = frame.layers_mut().first_mut() {
+            // The root layer is kept when frames are decoded again.
+            if root.attr("frame.time_delta").is_none() {
+                let class = self.classes.time_delta.clone();
+                root.add_attr(Attr::builder(class).value(delta).build());
+                let class = self.classes.time_relative.clone();
+                root.add_attr(Attr::builder(class).value(seconds(first, ts)).build());
+                if raw < latest {
+                    let backwards = seconds(raw, latest);
+                    let class = if backwards <= self.timestamps.reorder_window {
+                        self.classes.reordered.clone()
+                    } else {
+                        self.classes.clock_skew.clone()
+                    };
+                    root.add_attr(Attr::builder(class).value(backwards).build());
+                }
+            }
+        }
+        Some(ts)
+    }
+
+    /// Returns the conversation of a frame and its direction. A new
+    /// conversation replaces the previous one of the same 4-tuple if the
+    /// split policy applies, where `syn` is true for a SYN without ACK.
+    fn flow(
+        &mut self,
+        proto: Token,
+        src: (Vec<u8>, u16),
+        dst: (Vec<u8>, u16),
+        ts: Option<i128>,
+        syn: bool,
+    ) -> (&mut Flow, usize, f64) {
+        let (key, dir) = FlowKey::new(proto, src, dst);
+        let split = self
+            .flows
+            .get(&key)
+            .is_some_and(|flow| self.split.splits(flow, syn, ts));
+        if split {
+            self.flows.remove(&key);
+        }
+        let streams = &mut self.streams[if proto == Token::from("tcp") { 0 } else { 1 }];
+        let flow = self.flows.entry(key).or_insert_with(|| {
+            let index = *streams;
+            *streams += 1;
+            Flow {
+                index,
+                last: None,
+                closed: false,
+                dirs: Default::default(),
+                rtt: None,
+                queries: HashMap::new(),
+                requests: VecDeque::new(),
+            }
+        });
+        let delta = match (flow.last, ts) {
+            (Some(last), Some(ts)) => seconds(last, ts),
+            _ => 0.0,
+        };
+        if ts.is_some() {
+            flow.last = ts;
+        }
+        (flow, dir, delta)
+    }
+
+    /// Adds the `tcp.*` attributes, and returns the key of the conversation.
+    fn process_tcp(
+        &mut self,
+        layer: &mut Layer,
+        (src, dst): (Vec<u8>, Vec<u8>),
+        ts: Option<i128>,
+        frame: u32,
+    ) -> Option<FlowKey> {
+        let ports = (attr::<u16>(layer, "tcp.src"), attr::<u16>(layer, "tcp.dst"));
+        let (sport, dport) = match ports {
+            (Some(sport), Some(dport)) => (sport, dport),
+            _ => return None,
+        };
+        let seq = attr::<u32>(layer, "tcp.seq").unwrap_or(0);
+        let flags = attr::<u64>(layer, "tcp.flags").unwrap_or(0);
+        let payload = layer
+            .payloads()
+            .iter()
+            .find(|p| p.id() == Token::from("@data:tcp"))
+            .map_or(0, |p| p.data().len());
+
+        // SYN and FIN occupy a sequence number.
+        let len = payload as u32 + (flags & 0x2 != 0) as u32 + (flags & 0x1 != 0) as u32;
+        let seg = Segment {
+            seq,
+            len,
+            ack: if flags & 0x10 != 0 {
+                attr::<u32>(layer, "tcp.ack")
+            } else {
+                None
+            },
+            window: attr::<u16>(layer, "tcp.window"),
+            control: flags & 0x7 != 0,
+        };
+        let proto = Token::from("tcp");
+        let (key, _) = FlowKey::new(proto, (src.clone(), sport), (dst.clone(), dport));
+        let (index, delta, analysis) = {
+            let syn = flags & 0x12 == 0x2;
+            let (flow, dir, delta) = self.flow(proto, (src, sport), (dst, dport), ts, syn);
+            // FIN or RST.
+            flow.closed |= flags & 0x5 != 0;
+            (flow.index, delta, flow.analyze_tcp(dir, &seg, ts, frame))
+        };
+
+        let class = self.classes.tcp_stream.clone();
+        layer.add_attr(Attr::builder(class).value(index).build());
+        let class = self.classes.tcp_time_delta.clone();
+        layer.add_attr(Attr::builder(class).value(delta).build());
+        let class = self.classes.tcp_seq_relative.clone();
+        let seq = u64::from(analysis.seq_relative);
+        layer.add_attr(Attr::builder(class).value(seq).build());
+        if let Some(ack) = analysis.ack_relative {
+            let class = self.classes.tcp_ack_relative.clone();
+            layer.add_attr(Attr::builder(class).value(u64::from(ack)).build());
+        }
+        if analysis.retransmission {
+            let class = self.classes.tcp_retransmission.clone();
+            layer.add_attr(Attr::builder(class).value(true).build());
+        }
+        if analysis.out_of_order {
+            let class = self.classes.tcp_out_of_order.clone();
+            layer.add_attr(Attr::builder(class).value(true).build());
+        }
+        if let Some(num) = analysis.dup_ack {
+            let class = self.classes.tcp_dup_ack.clone();
+            layer.add_attr(Attr::builder(class).value(num).build());
+        }
+        if analysis.zero_window {
+            let class = self.classes.tcp_zero_window.clone();
+            layer.add_attr(Attr::builder(class).value(true).build());
+        }
+        if let Some(rtt) = analysis.ack_rtt {
+            let class = self.classes.tcp_ack_rtt.clone();
+            layer.add_attr(Attr::builder(class).value(rtt).build());
+        }
+        if let Some(acked) = analysis.acks_frame {
+            let class = self.classes.tcp_acks_frame.clone();
+            layer.add_attr(Attr::builder(class).value(u64::from(acked)).build());
+        }
+        Some(key)
+    }
+
+    /// Adds the `udp.*` attributes, and returns the key of the conversation,
+    /// which is the QUIC connection ID `connection` if any.
+    fn process_udp(
+        &mut self,
+        layer: &mut Layer,
+        (src, dst): (Vec<u8>, Vec<u8>),
+        connection: Option<Vec<u8>>,
+        ts: Option<i128>,
+    ) -> Option<FlowKey> {
+        let ports = (attr::<u16>(layer, "udp.src"), attr::<u16>(layer, "udp.dst"));
+        let (src, dst, proto) = match (ports, connection) {
+            (_, Some(connection)) => ((connection, 0), (Vec::new(), 0), Token::from("quic")),
+            ((Some(sport), Some(dport)), None) => ((src, sport), (dst, dport), Token::from("udp")),
+            _ => return None,
+        };
+        let (key, _) = FlowKey::new(proto, src.clone(), dst.clone());
+        let (index, delta) = {
+            let (flow, _, delta) = self.flow(proto, src, dst, ts, false);
+            (flow.index, delta)
+        };
+        let class = self.classes.udp_stream.clone();
+        layer.add_attr(Attr::builder(class).value(index).build());
+        let class = self.classes.udp_time_delta.clone();
+        layer.add_attr(Attr::builder(class).value(delta).build());
+        Some(key)
+    }
+
+    /// Matches a DNS response with the query of the same transaction ID in
+    /// the conversation `key`.
+    fn process_dns(&mut self, layer: &mut Layer, key: &FlowKey, ts: Option<i128>, frame: u32) {
+        let (id, flags) = match (
+            attr::<u16>(layer, "dns.id"),
+            attr::<u16>(layer, "dns.flags"),
+        ) {
+            (Some(id), Some(flags)) => (id, flags),
+            _ => return,
+        };
+        let flow = match self.flows.get_mut(key) {
+            Some(flow) => flow,
+            None => return,
+        };
+        if flags & 0x8000 == 0 {
+            if let Some((_, query)) = flow.queries.insert(id, (ts, frame)) {
+                let class = self.classes.dns_unanswered.clone();
+                layer.add_attr(Attr::builder(class).value(u64::from(query)).build());
+            }
+        } else if let Some((query_ts, query)) = flow.queries.remove(&id) {
+            let class = self.classes.dns_response_to.clone();
+            layer.add_attr(Attr::builder(class).value(u64::from(query)).build());
+            if let (Some(query_ts), Some(ts)) = (query_ts, ts) {
+                let class = self.classes.dns_time.clone();
+                layer.add_attr(Attr::builder(class).value(seconds(query_ts, ts)).build());
+            }
+        }
+    }
+
+    /// Matches an HTTP response with the oldest request waiting for a
+    /// response in the conversation `key`.
+    fn process_http(&mut self, layer: &mut Layer, key: &FlowKey, ts: Option<i128>, frame: u32) {
+        let flow = match self.flows.get_mut(key) {
+            Some(flow) => flow,
+            None => return,
+        };
+        if layer.attr("http.method").is_some() {
+            if flow.requests.len() >= MAX_PENDING_REQUESTS {
+                flow.requests.pop_front();
+            }
+            flow.requests.push_back((ts, frame));
+            return;
+        }
+        match attr::<u64>(layer, "http.status") {
+            // 101 Switching Protocols is the final response.
+            Some(status) if status >= 200 || status == 101 => {}
+            _ => return,
+        }
+        if let Some((request_ts, request)) = flow.requests.pop_front() {
+            let class = self.classes.http_response_to.clone();
+            layer.add_attr(Attr::builder(class).value(u64::from(request)).build());
+            if let (Some(request_ts), Some(ts)) = (request_ts, ts) {
+                let class = self.classes.http_time.clone();
+                layer.add_attr(Attr::builder(class).value(seconds(request_ts, ts)).build());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use analysis::{Analyzer, SplitPolicy, TimestampPolicy};
+    use frame::Frame;
+    use genet_abi::{
+        fixed::MutFixed,
+        layer::{Layer, Payload},
+        slice::ByteSlice,
+        variant::Variant,
+    };
+    use genet_filter::{context::Context, Filter};
+    use test_util::{self, LayerBuilder};
+
+    /// Returns a frame of an IPv4 packet carrying `layers`.
+    fn packet(index: u32, usec: u64, (src, dst): (u8, u8), layers: Vec<MutFixed<Layer>>) -> Frame {
+        let root = test_util::root()
+            .attr("link.timestamp.sec", 10u64)
+            .attr("link.timestamp.usec", usec);
+        let ipv4 = test_util::layer("ipv4")
+            .attr("_.src", vec![10, 0, 0, src].into_boxed_slice())
+            .attr("_.dst", vec![10, 0, 0, dst].into_boxed_slice());
+        let mut all = vec![root.build(), ipv4.build()];
+        all.extend(layers);
+        test_util::frame(index, all)
+    }
+
+    fn tcp((src, dst): (u8, u8), seq: u64, len: usize, flags: u64) -> LayerBuilder {
+        test_util::layer("tcp")
+            .attr("tcp.src", u64::from(src) + 1000)
+            .attr("tcp.dst", u64::from(dst) + 1000)
+            .attr("tcp.seq", seq)
+            .attr("tcp.flags", flags)
+            .payload(Payload::new(ByteSlice::from(vec![0; len]), "@data:tcp"))
+    }
+
+    fn udp((src, dst): (u8, u8)) -> MutFixed<Layer> {
+        test_util::layer("udp")
+            .attr("udp.src", u64::from(src) + 1000)
+            .attr("udp.dst", u64::from(dst) + 1000)
+            .build()
+    }
+
+    fn frame(index: u32, usec: u64, src: u8, dst: u8, seq: u64, len: usize) -> Frame {
+        segment(index, usec, (src, dst), seq, len, 0x10)
+    }
+
+    fn segment(
+        index: u32,
+        usec: u64,
+        (src, dst): (u8, u8),
+        seq: u64,
+        len: usize,
+        flags: u64,
+    ) -> Frame {
+        let tcp = tcp((src, dst), seq, len, flags);
+        packet(index, usec, (src, dst), vec![tcp.build()])
+    }
+
+    #[test]
+    fn process() {
+        let mut frames = vec![
+            frame(0, 0, 1, 2, 100, 10),
+            frame(1, 500_000, 2, 1, 900, 0),
+            frame(2, 750_000, 3, 4, 0, 10),
+            frame(3, 1_000_000, 1, 2, 110, 10),
+            frame(4, 3_500_000, 1, 2, 100, 10),
+            frame(5, 3_600_000, 1, 2, 115, 10),
+        ];
+        let mut analyzer = Analyzer::new();
+        for frame in &mut frames {
+            analyzer.process(frame);
+        }
+
+        let matches = |filter: &str| {
+            let filter = Filter::compile(filter).unwrap();
+            frames
+                .iter()
+                .filter(|frame| filter.test(&Context::new(frame.layers())))
+                .map(|frame| frame.index())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matches("tcp.stream == 0"), vec![0, 1, 3, 4, 5]);
+        assert_eq!(matches("tcp.stream == 1"), vec![2]);
+        assert_eq!(matches("frame.time_delta > 1"), vec![4]);
+        assert_eq!(matches("frame.time_relative >= 3.5"), vec![4, 5]);
+        assert_eq!(matches("tcp.time_delta > 0.4"), vec![1, 3, 4]);
+        assert_eq!(matches("tcp.analysis.retransmission"), vec![4]);
+
+        // Attributes of the root layer are not added twice.
+        analyzer.process(&mut frames[0]);
+        assert_eq!(frames[0].layers()[0].attrs().len(), 4);
+    }
+
+    /// Returns a segment with an acknowledgment number and a window.
+    fn ack(
+        index: u32,
+        usec: u64,
+        (src, dst): (u8, u8),
+        seq: u64,
+        len: usize,
+        (ack, window): (u64, u64),
+    ) -> Frame {
+        let tcp = tcp((src, dst), seq, len, 0x10)
+            .attr("tcp.ack", ack)
+            .attr("tcp.window", window);
+        packet(index, usec, (src, dst), vec![tcp.build()])
+    }
+
+    #[test]
+    fn tcp_analysis() {
+        let mut frames = vec![
+            ack(0, 0, (1, 2), 100, 10, (500, 100)),
+            ack(1, 50_000, (2, 1), 500, 0, (110, 100)),
+            frame(2, 60_000, 1, 2, 110, 10),
+            frame(3, 61_000, 1, 2, 130, 10),
+            frame(4, 62_000, 1, 2, 120, 10),
+            ack(5, 70_000, (2, 1), 500, 0, (120, 100)),
+            ack(6, 71_000, (2, 1), 500, 0, (120, 100)),
+            ack(7, 72_000, (2, 1), 500, 0, (120, 100)),
+            frame(8, 1_000_000, 1, 2, 110, 10),
+            ack(9, 1_100_000, (2, 1), 500, 0, (140, 0)),
+        ];
+        let mut analyzer = Analyzer::new();
+        for frame in &mut frames {
+            analyzer.process(frame);
+        }
+
+        let matches = |filter: &str| {
+            let filter = Filter::compile(filter).unwrap();
+            frames
+                .iter()
+                .filter(|frame| filter.test(&Context::new(frame.layers())))
+                .map(|frame| frame.index())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matches("tcp.analysis.retransmission"), vec![8]);
+        assert_eq!(matches("tcp.analysis.out_of_order"), vec![4]);
+        assert_eq!(matches("tcp.analysis.dup_ack"), vec![6, 7]);
+        assert_eq!(matches("tcp.analysis.dup_ack == 2"), vec![7]);
+        assert_eq!(matches("tcp.analysis.zero_window"), vec![9]);
+        assert_eq!(matches("tcp.analysis.ack_rtt"), vec![1, 5]);
+        assert_eq!(matches("tcp.analysis.ack_rtt > 40ms"), vec![1]);
+        assert_eq!(matches("tcp.analysis.acks_frame == 2"), vec![5]);
+        assert_eq!(matches("tcp.seq_relative == 10"), vec![2, 8]);
+        assert_eq!(matches("tcp.ack_relative == 20"), vec![5, 6, 7]);
+    }
+
+    #[test]
+    fn port_reuse() {
+        let frames = || {
+            vec![
+                segment(0, 0, (1, 2), 100, 0, 0x02),
+                segment(1, 1_000, (2, 1), 500, 0, 0x12),
+                frame(2, 2_000, 1, 2, 101, 10),
+                segment(3, 3_000, (1, 2), 111, 0, 0x11),
+                segment(4, 4_000, (2, 1), 501, 0, 0x11),
+                segment(5, 5_000, (1, 2), 9000, 0, 0x02),
+                segment(6, 6_000, (2, 1), 7000, 0, 0x12),
+                frame(7, 7_000, 1, 2, 9001, 10),
+                frame(8, 5_000_000, 1, 2, 9011, 10),
+            ]
+        };
+        let streams = |policy: SplitPolicy| {
+            let mut analyzer = Analyzer::with_policies(policy, TimestampPolicy::default());
+            let mut frames = frames();
+            for frame in &mut frames {
+                analyzer.process(frame);
+            }
+            let filter = Filter::compile("(tcp.stream == 1) && (tcp.seq_relative == 1)").unwrap();
+            let relative = frames
+                .iter()
+                .filter(|frame| filter.test(&Context::new(frame.layers())))
+                .map(|frame| frame.index())
+                .collect::<Vec<_>>();
+            let streams = frames
+                .iter()
+                .map(|frame| {
+                    let tcp = &frame.layers()[2];
+                    let value = tcp.attr("tcp.stream").unwrap().try_get(tcp).unwrap();
+                    match value {
+                        Variant::UInt64(stream) => stream,
+                        _ => unreachable!(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            (streams, relative)
+        };
+
+        let (merged, _) = streams(SplitPolicy {
+            on_close: false,
+            idle_timeout: None,
+        });
+        assert_eq!(merged, vec![0; 9]);
+
+        let (split, relative) = streams(SplitPolicy::default());
+        assert_eq!(split, vec![0, 0, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(relative, vec![7]);
+
+        let (idle, _) = streams(SplitPolicy {
+            on_close: true,
+            idle_timeout: Some(1.0),
+        });
+        assert_eq!(idle, vec![0, 0, 0, 0, 0, 1, 1, 1, 2]);
+
+        assert_eq!(
+            SplitPolicy::from_config(r#"{"idleTimeout": 60}"#),
+            SplitPolicy {
+                on_close: true,
+                idle_timeout: Some(60.0),
+            }
+        );
+        assert_eq!(SplitPolicy::from_config("x"), SplitPolicy::default());
+    }
+
+    #[test]
+    fn timestamps() {
+        let frames = || {
+            vec![
+                frame(0, 100_000, 1, 2, 100, 10),
+                frame(1, 95_000, 2, 1, 500, 0),
+                frame(2, 110_000, 1, 2, 110, 10),
+                frame(3, 50_000, 1, 2, 120, 10),
+                frame(4, 120_000, 1, 2, 130, 10),
+            ]
+        };
+        let analyze = |policy: TimestampPolicy| {
+            let mut analyzer = Analyzer::with_policies(SplitPolicy::default(), policy);
+            let mut frames = frames();
+            for frame in &mut frames {
+                analyzer.process(frame);
+            }
+            frames
+        };
+        let matches = |frames: &[Frame], filter: &str| {
+            let filter = Filter::compile(filter).unwrap();
+            frames
+                .iter()
+                .filter(|frame| filter.test(&Context::new(frame.layers())))
+                .map(|frame| frame.index())
+                .collect::<Vec<_>>()
+        };
+
+        let clamped = analyze(TimestampPolicy::default());
+        assert!(matches(&clamped, "frame.time_delta < 0").is_empty());
+        assert!(matches(&clamped, "tcp.time_delta < 0").is_empty());
+        assert_eq!(matches(&clamped, "frame.analysis.reordered"), vec![1]);
+        assert_eq!(matches(&clamped, "frame.analysis.clock_skew"), vec![3]);
+        assert_eq!(
+            matches(&clamped, "frame.analysis.clock_skew > 0.05"),
+            vec![3]
+        );
+        assert_eq!(matches(&clamped, "frame.time_delta > 0"), vec![2, 4]);
+
+        let raw = analyze(TimestampPolicy {
+            clamp: false,
+            reorder_window: 0.0,
+        });
+        assert_eq!(matches(&raw, "frame.time_delta < 0"), vec![1, 3]);
+        assert_eq!(matches(&raw, "frame.time_relative < 0"), vec![1, 3]);
+        assert_eq!(matches(&raw, "frame.analysis.clock_skew"), vec![1, 3]);
+
+        assert_eq!(
+            TimestampPolicy::from_config(r#"{"clamp": false}"#),
+            TimestampPolicy {
+                clamp: false,
+                reorder_window: 0.01,
+            }
+        );
+    }
+
+    #[test]
+    fn nanoseconds() {
+        let mut analyzer = Analyzer::new();
+        let deltas = [1_700_000_000_000_000_001u64, 1_700_000_000_000_000_003]
+            .iter()
+            .enumerate()
+            .map(|(index, ts)| {
+                let root = test_util::root()
+                    .attr("link.timestamp.sec", ts / 1_000_000_000)
+                    .attr("link.timestamp.nsec", ts % 1_000_000_000);
+                let mut frame = test_util::frame(index as u32, vec![root.build()]);
+                analyzer.process(&mut frame);
+                let root = &frame.layers()[0];
+                let delta = root.attr("frame.time_delta").unwrap();
+                delta.try_get(root).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(deltas, vec![Variant::Float64(0.0), Variant::Float64(2e-9)]);
+    }
+
+    fn dns(index: u32, usec: u64, (src, dst): (u8, u8), id: u64, response: bool) -> Frame {
+        let dns = test_util::layer("dns")
+            .attr("dns.id", id)
+            .attr("dns.flags", if response { 0x8180u64 } else { 0x0100 });
+        packet(index, usec, (src, dst), vec![udp((src, dst)), dns.build()])
+    }
+
+    #[test]
+    fn dns_transactions() {
+        let mut frames = vec![
+            dns(0, 0, (1, 2), 10, false),
+            dns(1, 1_000, (1, 2), 11, false),
+            dns(2, 21_000, (2, 1), 10, true),
+            dns(3, 30_000, (3, 2), 11, true),
+            dns(4, 2_000_000, (1, 2), 11, false),
+            dns(5, 2_005_000, (2, 1), 11, true),
+            dns(6, 2_006_000, (2, 1), 11, true),
+        ];
+        let mut analyzer = Analyzer::new();
+        for frame in &mut frames {
+            analyzer.process(frame);
+        }
+
+        let matches = |filter: &str| {
+            let filter = Filter::compile(filter).unwrap();
+            frames
+                .iter()
+                .filter(|frame| filter.test(&Context::new(frame.layers())))
+                .map(|frame| frame.index())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matches("dns.response_to == 0"), vec![2]);
+        assert_eq!(matches("dns.response_to == 4"), vec![5]);
+        assert_eq!(matches("dns.response_to"), vec![2, 5]);
+        assert_eq!(matches("dns.time > 0.019"), vec![2]);
+        assert_eq!(matches("dns.time < 0.006"), vec![5]);
+        assert_eq!(matches("dns.analysis.unanswered == 1"), vec![4]);
+    }
+
+    fn http(
+        index: u32,
+        usec: u64,
+        (src, dst): (u8, u8),
+        messages: &[(&'static str, u64)],
+    ) -> Frame {
+        let mut layers = vec![tcp((src, dst), 0, 0, 0x18).build()];
+        for (id, value) in messages {
+            layers.push(test_util::layer("http").attr(id, *value).build());
+        }
+        packet(index, usec, (src, dst), layers)
+    }
+
+    #[test]
+    fn http_pipelining() {
+        let mut frames = vec![
+            http(0, 0, (1, 2), &[("http.method", 0), ("http.method", 0)]),
+            http(1, 10_000, (2, 1), &[("http.status", 100)]),
+            http(2, 20_000, (2, 1), &[("http.status", 200)]),
+            http(3, 20_000, (3, 2), &[("http.status", 200)]),
+            http(4, 50_000, (2, 1), &[("http.status", 404)]),
+            http(5, 60_000, (2, 1), &[("http.status", 200)]),
+        ];
+        let mut analyzer = Analyzer::new();
+        for frame in &mut frames {
+            analyzer.process(frame);
+        }
+
+        let matches = |filter: &str| {
+            let filter = Filter::compile(filter).unwrap();
+            frames
+                .iter()
+                .filter(|frame| filter.test(&Context::new(frame.layers())))
+                .map(|frame| frame.index())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matches("http.response_to == 0"), vec![2, 4]);
+        assert_eq!(matches("http.response_to"), vec![2, 4]);
+        assert_eq!(matches("http.time > 0.04"), vec![4]);
+        assert_eq!(matches("http.time < 0.03"), vec![2]);
+    }
+
+    fn quic(index: u32, usec: u64, (src, dst): (u8, u8), connection: Option<&[u8]>) -> Frame {
+        let mut quic = test_util::layer("quic");
+        if let Some(connection) = connection {
+            quic = quic.attr("quic.connection", connection.to_vec().into_boxed_slice());
+        }
+        packet(index, usec, (src, dst), vec![udp((src, dst)), quic.build()])
+    }
+
+    #[test]
+    fn quic_migration() {
+        let mut frames = vec![
+            quic(0, 0, (1, 2), Some(b"abc")),
+            quic(1, 1_000, (2, 1), Some(b"abc")),
+            quic(2, 2_000, (3, 2), Some(b"abc")),
+            quic(3, 3_000, (3, 2), Some(b"def")),
+            quic(4, 4_000, (1, 2), None),
+            quic(5, 5_000, (2, 3), Some(b"abc")),
+        ];
+        let mut analyzer = Analyzer::new();
+        for frame in &mut frames {
+            analyzer.process(frame);
+        }
+
+        let matches = |filter: &str| {
+            let filter = Filter::compile(filter).unwrap();
+            frames
+                .iter()
+                .filter(|frame| filter.test(&Context::new(frame.layers())))
+                .map(|frame| frame.index())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matches("udp.stream == 0"), vec![0, 1, 2, 5]);
+        assert_eq!(matches("udp.stream == 1"), vec![3]);
+        assert_eq!(matches("udp.stream == 2"), vec![4]);
+        assert_eq!(matches("udp.time_delta > 0.002"), vec![5]);
+    }
+}
@@ -0,0 +1,359 @@
+//! User annotations of a capture, exported as a sidecar file.
+//!
+//! Comments, bookmarks, coloring rules and Decode-As rules are kept apart
+//! from the capture so that they can be shared without modifying it. The
+//! sidecar records a fingerprint of the first frames, and importing it into
+//! a session with different frames fails.
+
+use decode_as::{Conversation, DecodeAs, Endpoint};
+use fnv::FnvHasher;
+use genet_abi::token::Token;
+use genet_filter::{unparser, Filter};
+use serde_json;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    hash::Hasher,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The number of frames used for the fingerprint.
+pub const FINGERPRINT_FRAMES: usize = 64;
+
+/// Returns the sidecar path of a capture, e.g. `dump.pcap.genet.json`.
+pub fn sidecar_path(capture: &Path) -> PathBuf {
+    let mut path = capture.as_os_str().to_owned();
+    path.push(".genet.json");
+    PathBuf::from(path)
+}
+
+/// A fingerprint of the beginning of a capture.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Fingerprint {
+    /// The number of frames hashed.
+    pub frames: usize,
+    pub hash: u64,
+}
+
+impl Fingerprint {
+    /// Creates a new Fingerprint from the data of the first frames.
+    pub fn new<'a, I>(frames: I) -> Fingerprint
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let mut hasher = FnvHasher::default();
+        let mut count = 0;
+        for data in frames.into_iter().take(FINGERPRINT_FRAMES) {
+            hasher.write_usize(data.len());
+            hasher.write(data);
+            count += 1;
+        }
+        Fingerprint {
+            frames: count,
+            hash: hasher.finish(),
+        }
+    }
+}
+
+/// A coloring rule. Frames are colored by the first rule they match.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ColorRule {
+    pub name: String,
+    pub filter: String,
+    /// The text color, or empty for the default color.
+    #[serde(default)]
+    pub foreground: String,
+    /// The background color, or empty for the default color.
+    #[serde(default, alias = "color")]
+    pub background: String,
+}
+
+/// A note on a byte range of a frame.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ByteAnnotation {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// The comment and the byte annotations of a frame.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct FrameAnnotations {
+    pub comment: Option<String>,
+    pub bytes: Vec<ByteAnnotation>,
+}
+
+/// An endpoint of a Decode-As conversation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConversationEndpoint {
+    pub addr: Vec<u8>,
+    pub port: u64,
+}
+
+/// A Decode-As rule in a serializable form.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DecodeAsEntry {
+    pub id: String,
+    pub decoder: String,
+    #[serde(default)]
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub endpoints: Option<(ConversationEndpoint, ConversationEndpoint)>,
+}
+
+impl DecodeAsEntry {
+    pub fn new(id: u32, rule: &DecodeAs) -> DecodeAsEntry {
+        let mut entry = DecodeAsEntry {
+            id: Token::from(id).to_string(),
+            decoder: rule.decoder().to_string(),
+            filter: rule.filter().map(|f| unparser::unparse(f.expr())),
+            layer: None,
+            endpoints: None,
+        };
+        if let Some(conv) = rule.conversation() {
+            let endpoint = |(addr, port): &Endpoint| ConversationEndpoint {
+                addr: addr.clone(),
+                port: *port,
+            };
+            let (a, b) = conv.endpoints();
+            entry.layer = Some(conv.layer().to_string());
+            entry.endpoints = Some((endpoint(a), endpoint(b)));
+        }
+        entry
+    }
+
+    /// Returns the ID and the rule, or None if the filter cannot be parsed.
+    pub fn rule(&self) -> Option<(u32, DecodeAs)> {
+        let id = Token::from(self.id.as_str()).into();
+        let decoder = self.decoder.as_str();
+        let rule = match (&self.filter, &self.layer, &self.endpoints) {
+            (Some(filter), _, _) => DecodeAs::new(Filter::compile(filter).ok()?, decoder),
+            (None, Some(layer), Some((a, b))) => {
+                let conv = Conversation::new(
+                    layer.as_str(),
+                    (a.addr.clone(), a.port),
+                    (b.addr.clone(), b.port),
+                );
+                DecodeAs::with_conversation(conv, decoder)
+            }
+            _ => return None,
+        };
+        Some((id, rule))
+    }
+}
+
+/// The annotations of a session.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Annotations {
+    #[serde(default)]
+    pub fingerprint: Fingerprint,
+    /// Comments by frame index.
+    #[serde(default)]
+    pub comments: BTreeMap<u32, String>,
+    /// Byte annotations by frame index.
+    #[serde(default)]
+    pub byte_annotations: BTreeMap<u32, Vec<ByteAnnotation>>,
+    #[serde(default)]
+    pub bookmarks: BTreeSet<u32>,
+    #[serde(default)]
+    pub color_rules: Vec<ColorRule>,
+    #[serde(default)]
+    pub decode_as: Vec<DecodeAsEntry>,
+}
+
+impl Annotations {
+    pub fn new() -> Annotations {
+        Self::default()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Annotations> {
+        let data = fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self).unwrap())
+    }
+
+    /// Sets or removes the comment of the frame `index`.
+    pub fn set_comment(&mut self, index: u32, comment: Option<&str>) {
+        match comment {
+            Some(comment) if !comment.is_empty() => {
+                self.comments.insert(index, comment.to_string());
+            }
+            _ => {
+                self.comments.remove(&index);
+            }
+        }
+    }
+
+    /// Replaces the byte annotations of the frame `index`.
+    pub fn set_byte_annotations(
+        &mut self,
+        index: u32,
+        annotations: Vec<ByteAnnotation>,
+    ) -> Result<(), String> {
+        if let Some(a) = annotations.iter().find(|a| a.start >= a.end) {
+            return Err(format!("invalid byte range: {}..{}", a.start, a.end));
+        }
+        if annotations.is_empty() {
+            self.byte_annotations.remove(&index);
+        } else {
+            self.byte_annotations.insert(index, annotations);
+        }
+        Ok(())
+    }
+
+    pub fn frame(&self, index: u32) -> FrameAnnotations {
+        FrameAnnotations {
+            comment: self.comments.get(&index).cloned(),
+            bytes: self
+                .byte_annotations
+                .get(&index)
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns the indices of the frames with a comment or byte annotations
+    /// in ascending order.
+    pub fn commented_frames(&self) -> Vec<u32> {
+        let mut indices = self
+            .comments
+            .keys()
+            .chain(self.byte_annotations.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    /// Returns the comment of each annotated frame as written to capture
+    /// files, with a line per byte annotation.
+    pub fn export_comments(&self) -> BTreeMap<u32, String> {
+        self.commented_frames()
+            .into_iter()
+            .map(|index| {
+                let frame = self.frame(index);
+                let lines = frame.comment.into_iter().chain(
+                    frame
+                        .bytes
+                        .iter()
+                        .map(|a| format!("bytes {}..{}: {}", a.start, a.end, a.text)),
+                );
+                (index, lines.collect::<Vec<_>>().join("\n"))
+            })
+            .collect()
+    }
+
+    pub fn set_bookmark(&mut self, index: u32, bookmarked: bool) {
+        if bookmarked {
+            self.bookmarks.insert(index);
+        } else {
+            self.bookmarks.remove(&index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use annotations::{
+        sidecar_path, Annotations, ByteAnnotation, ColorRule, DecodeAsEntry, Fingerprint,
+    };
+    use decode_as::{Conversation, DecodeAs};
+    use genet_abi::token::Token;
+    use genet_filter::Filter;
+    use serde_json;
+    use std::path::Path;
+
+    #[test]
+    fn sidecar() {
+        assert_eq!(
+            sidecar_path(Path::new("/tmp/dump.pcap")),
+            Path::new("/tmp/dump.pcap.genet.json")
+        );
+
+        let frames: Vec<&[u8]> = vec![b"abc", b"def"];
+        let fingerprint = Fingerprint::new(frames.iter().cloned());
+        assert_eq!(fingerprint.frames, 2);
+        assert_ne!(fingerprint, Fingerprint::new(frames.iter().rev().cloned()));
+
+        let mut annotations = Annotations::new();
+        annotations.fingerprint = fingerprint;
+        annotations.set_comment(1, Some("handshake"));
+        annotations.set_comment(2, Some("x"));
+        annotations.set_comment(2, None);
+        annotations.set_bookmark(3, true);
+
+        let id = Token::from("decode-as-test").into();
+        let rule = DecodeAs::new(Filter::compile("tcp.dst == 8080").unwrap(), "@data:http");
+        annotations.decode_as.push(DecodeAsEntry::new(id, &rule));
+        let conv = Conversation::new("udp", (vec![10, 0, 0, 1], 5000), (vec![10, 0, 0, 2], 53));
+        let rule = DecodeAs::with_conversation(conv.clone(), "@data:dns");
+        annotations.decode_as.push(DecodeAsEntry::new(id, &rule));
+
+        let json = serde_json::to_string(&annotations).unwrap();
+        let loaded: Annotations = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, annotations);
+        assert_eq!(loaded.comments.len(), 1);
+
+        let (rule_id, rule) = loaded.decode_as[0].rule().unwrap();
+        assert_eq!(rule_id, id);
+        assert_eq!(rule.decoder(), Token::from("@data:http"));
+        assert!(rule.filter().is_some());
+        let (_, rule) = loaded.decode_as[1].rule().unwrap();
+        assert_eq!(rule.conversation(), Some(&conv));
+
+        let empty: Annotations = serde_json::from_str("{}").unwrap();
+        assert_eq!(empty, Annotations::new());
+
+        let json = r##"{"colorRules":[{"name":"TCP","filter":"tcp","color":"#cef"}]}"##;
+        let loaded: Annotations = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            loaded.color_rules,
+            vec![ColorRule {
+                name: "TCP".to_string(),
+                filter: "tcp".to_string(),
+                foreground: String::new(),
+                background: "#cef".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let annotation = |start, end, text: &str| ByteAnnotation {
+            start,
+            end,
+            text: text.to_string(),
+        };
+        let mut annotations = Annotations::new();
+        annotations.set_comment(5, Some("reset"));
+        annotations.set_comment(2, Some("handshake"));
+        annotations
+            .set_byte_annotations(2, vec![annotation(14, 34, "ipv4 header")])
+            .unwrap();
+        annotations
+            .set_byte_annotations(9, vec![annotation(0, 6, "dst"), annotation(6, 12, "src")])
+            .unwrap();
+        assert!(annotations
+            .set_byte_annotations(9, vec![annotation(6, 6, "empty")])
+            .is_err());
+        assert_eq!(annotations.commented_frames(), vec![2, 5, 9]);
+        assert_eq!(annotations.frame(9).bytes.len(), 2);
+        assert_eq!(annotations.frame(9).comment, None);
+
+        let comments = annotations.export_comments();
+        assert_eq!(comments[&2], "handshake\nbytes 14..34: ipv4 header");
+        assert_eq!(comments[&5], "reset");
+        assert_eq!(comments[&9], "bytes 0..6: dst\nbytes 6..12: src");
+
+        annotations.set_byte_annotations(9, Vec::new()).unwrap();
+        assert_eq!(annotations.commented_frames(), vec![2, 5]);
+    }
+}
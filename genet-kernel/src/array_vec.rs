@@ -1,4 +1,4 @@
-use std::{mem, ptr};
+use std::{mem, ops::Range, ptr};
 
 const BLOCK_SIZE: usize = 1024;
 
@@ -8,6 +8,7 @@ where
     T: Sized,
 {
     buckets: Vec<*mut [T; BLOCK_SIZE]>,
+    start: usize,
     len: usize,
 }
 
@@ -18,6 +19,7 @@ impl<T> ArrayVec<T> {
     pub fn new() -> ArrayVec<T> {
         Self {
             buckets: Vec::new(),
+            start: 0,
             len: 0,
         }
     }
@@ -26,8 +28,13 @@ impl<T> ArrayVec<T> {
         self.len
     }
 
+    /// Returns the index of the first element which has not been released.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
     pub fn get(&self, index: usize) -> Option<&T> {
-        if index < self.len {
+        if index >= self.start && index < self.len {
             let bucket = index / BLOCK_SIZE;
             let offset = index % BLOCK_SIZE;
             unsafe { Some(&(*self.buckets[bucket])[offset]) }
@@ -36,6 +43,16 @@ impl<T> ArrayVec<T> {
         }
     }
 
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.start && index < self.len {
+            let bucket = index / BLOCK_SIZE;
+            let offset = index % BLOCK_SIZE;
+            unsafe { Some(&mut (*self.buckets[bucket])[offset]) }
+        } else {
+            None
+        }
+    }
+
     pub fn push(&mut self, val: T) {
         let bucket = self.len / BLOCK_SIZE;
         let offset = self.len % BLOCK_SIZE;
@@ -49,8 +66,38 @@ impl<T> ArrayVec<T> {
         self.len += 1;
     }
 
+    /// Moves the elements before `start` out, keeping the indices of the
+    /// others. The blocks of the elements are freed.
+    pub fn release_front(&mut self, start: usize) -> Vec<T> {
+        let start = start.min(self.len);
+        if start <= self.start {
+            return Vec::new();
+        }
+        let released = (self.start..start)
+            .map(|index| unsafe { ptr::read(self.get(index).unwrap()) })
+            .collect();
+        for bucket in &mut self.buckets[self.start / BLOCK_SIZE..start / BLOCK_SIZE] {
+            if !bucket.is_null() {
+                drop(unsafe { Box::from_raw(*bucket as *mut mem::ManuallyDrop<[T; BLOCK_SIZE]>) });
+                *bucket = ptr::null_mut();
+            }
+        }
+        self.start = start;
+        released
+    }
+
     pub fn iter(&self) -> Iter<T> {
-        Iter { v: self, offset: 0 }
+        self.range(0..self.len)
+    }
+
+    /// Returns an iterator over the elements in `range` which have not been
+    /// released.
+    pub fn range(&self, range: Range<usize>) -> Iter<'_, T> {
+        Iter {
+            v: self,
+            offset: range.start.max(self.start),
+            end: range.end.min(self.len),
+        }
     }
 }
 
@@ -60,18 +107,51 @@ where
 {
     v: &'a ArrayVec<T>,
     offset: usize,
+    end: usize,
 }
 
 impl<'a, T> Iterator for Iter<'a, T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.end {
+            return None;
+        }
         let val = self.v.get(self.offset);
         self.offset += 1;
         val
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.v.len(), Some(self.v.len()))
+        let len = self.end.saturating_sub(self.offset);
+        (len, Some(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use array_vec::ArrayVec;
+
+    #[test]
+    fn release_front() {
+        let mut v = ArrayVec::new();
+        for i in 0..3000 {
+            v.push(i.to_string());
+        }
+        let released = v.release_front(1500);
+        assert_eq!(released.len(), 1500);
+        assert_eq!(released[1499], "1499");
+        assert_eq!(v.start(), 1500);
+        assert_eq!(v.len(), 3000);
+        assert_eq!(v.get(1499), None);
+        assert_eq!(v.get(1500).map(|s| s.as_str()), Some("1500"));
+        assert_eq!(v.iter().count(), 1500);
+        assert_eq!(v.range(1000..1600).count(), 100);
+
+        assert_eq!(v.release_front(1000).len(), 0);
+        assert_eq!(v.release_front(2100).len(), 600);
+        v.push("3000".to_string());
+        assert_eq!(v.iter().next().map(|s| s.as_str()), Some("2100"));
+        assert_eq!(v.get(3000).map(|s| s.as_str()), Some("3000"));
     }
 }
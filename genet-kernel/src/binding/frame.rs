@@ -1,11 +1,42 @@
-use binding::{attr::AttrWrapper, JsClass};
-use frame::Frame;
+use binding::{attr::AttrWrapper, retain, JsClass};
+use frame::{Frame, OwnedFrame};
 use genet_abi::token::Token;
 use genet_napi::napi::{
     CallbackInfo, Env, PropertyAttributes, PropertyDescriptor, Result, Status, Value, ValueRef,
     ValueType,
 };
-use std::rc::Rc;
+use lazy::Hold;
+use std::{any::Any, rc::Rc};
+
+/// A frame wrapped by a `Frame` object.
+pub struct FrameRef {
+    frame: *const Frame,
+    /// Frees the frame or releases its hold when the object is collected.
+    _owner: Option<Box<Any>>,
+}
+
+impl FrameRef {
+    /// Refers to a frame in the store, which `hold` keeps decoded in lazy
+    /// mode.
+    pub fn held(frame: *const Frame, hold: Option<Hold>) -> FrameRef {
+        FrameRef {
+            frame,
+            _owner: hold.map(|hold| Box::new(hold) as Box<Any>),
+        }
+    }
+
+    pub fn owned(frame: OwnedFrame) -> FrameRef {
+        let frame = Box::new(frame);
+        FrameRef {
+            frame: &**frame as *const Frame,
+            _owner: Some(frame),
+        }
+    }
+
+    fn frame(&self) -> &Frame {
+        unsafe { &*self.frame }
+    }
+}
 
 pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     fn ctor<'env>(env: &'env Env, _info: &CallbackInfo) -> Result<&'env Value> {
@@ -13,12 +44,17 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     }
 
     fn frame_index<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let frame = env.unwrap::<Frame>(info.this())?;
+        let frame = env.unwrap::<FrameRef>(info.this())?.frame();
         env.create_uint32(frame.index())
     }
 
+    fn frame_protocols<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let frame = env.unwrap::<FrameRef>(info.this())?.frame();
+        env.create_string(frame.protocols().path())
+    }
+
     fn frame_tree_indices<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let frame = env.unwrap::<Frame>(info.this())?;
+        let frame = env.unwrap::<FrameRef>(info.this())?.frame();
         let indices = frame.tree_indices();
         let array = env.create_array(indices.len())?;
         for (i, item) in indices.iter().enumerate() {
@@ -28,7 +64,7 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     }
 
     fn frame_query<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let frame = env.unwrap::<Frame>(info.this())?;
+        let frame = env.unwrap::<FrameRef>(info.this())?.frame();
         if let Some(id) = info.argv().get(0) {
             let id = match env.type_of(id)? {
                 ValueType::Number => Token::from(env.get_value_uint32(id)?),
@@ -40,12 +76,14 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                     let layer_class = env.get_constructor(JsClass::Layer as usize).unwrap();
                     let instance = env.new_instance(&layer_class, &[])?;
                     env.wrap_mut_fixed(instance, layer)?;
+                    retain(env, instance, info.this())?;
                     return Ok(instance);
                 }
                 if let Some(attr) = layer.attr(id) {
                     let attr_class = env.get_constructor(JsClass::Attr as usize).unwrap();
                     let instance = env.new_instance(&attr_class, &[])?;
                     env.wrap(instance, AttrWrapper::new(attr, layer))?;
+                    retain(env, instance, info.this())?;
                     return Ok(instance);
                 }
             }
@@ -56,13 +94,14 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     }
 
     fn frame_layers<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let frame = env.unwrap::<Frame>(info.this())?;
+        let frame = env.unwrap::<FrameRef>(info.this())?.frame();
         let layers = frame.layers();
         let layer_class = env.get_constructor(JsClass::Layer as usize).unwrap();
         let array = env.create_array(layers.len())?;
         for (i, item) in layers.iter().enumerate() {
             let instance = env.new_instance(&layer_class, &[])?;
             env.wrap_mut_fixed(instance, item)?;
+            retain(env, instance, info.this())?;
             env.set_element(array, i as u32, instance)?;
         }
         Ok(array)
@@ -94,6 +133,13 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                     frame_tree_indices,
                     false,
                 ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "protocols",
+                    PropertyAttributes::DEFAULT,
+                    frame_protocols,
+                    false,
+                ),
                 PropertyDescriptor::new_method(
                     env,
                     "query",
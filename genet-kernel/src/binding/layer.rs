@@ -1,4 +1,4 @@
-use binding::{attr::AttrWrapper, JsClass};
+use binding::{attr::AttrWrapper, retain, JsClass};
 use genet_abi::{layer::Layer, token::Token};
 use genet_filter::{ast::Expr, unparser::unparse};
 use genet_napi::napi::{
@@ -28,6 +28,7 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                 let attr_class = env.get_constructor(JsClass::Attr as usize).unwrap();
                 let instance = env.new_instance(&attr_class, &[])?;
                 env.wrap(instance, AttrWrapper::new(attr, layer))?;
+                retain(env, instance, info.this())?;
                 Ok(instance)
             } else {
                 env.get_null()
@@ -39,20 +40,15 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
 
     fn layer_attrs<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let layer = env.unwrap::<Layer>(info.this())?;
-        let headers = layer.headers();
-        let attrs = layer.attrs();
+        let attrs = layer.attrs_iter();
         let attr_class = env.get_constructor(JsClass::Attr as usize).unwrap();
-        let array = env.create_array(headers.len() + attrs.len())?;
-        for (i, item) in headers.iter().enumerate() {
+        let array = env.create_array(attrs.len())?;
+        for (i, item) in attrs.enumerate() {
             let instance = env.new_instance(&attr_class, &[])?;
             env.wrap(instance, AttrWrapper::new(item, layer))?;
+            retain(env, instance, info.this())?;
             env.set_element(array, i as u32, instance)?;
         }
-        for (i, item) in attrs.iter().enumerate() {
-            let instance = env.new_instance(&attr_class, &[])?;
-            env.wrap(instance, AttrWrapper::new(item, layer))?;
-            env.set_element(array, (headers.len() + i) as u32, instance)?;
-        }
         Ok(array)
     }
 
@@ -1,4 +1,4 @@
-use genet_napi::napi::{Env, Value};
+use genet_napi::napi::{Env, Result, Value};
 use std::{ffi::CString, os::raw::c_char};
 
 mod attr;
@@ -28,6 +28,13 @@ pub unsafe extern "C" fn genet_napi_init(env: *const Env, exports: *const Value)
     exports
 }
 
+/// Keeps `owner` reachable as long as `instance`, so that the wrapped data of
+/// `instance`, which lives in `owner`, is not freed by the finalizer of `owner`.
+pub fn retain(env: &Env, instance: &Value, owner: &Value) -> Result<()> {
+    let key = env.create_symbol(env.create_string("owner")?)?;
+    env.set_property(instance, key, owner)
+}
+
 pub enum JsClass {
     SessionProfile = 0,
     Frame = 1,
@@ -1,17 +1,24 @@
-use binding::JsClass;
+use annotations::Annotations;
+use binding::{frame::FrameRef, JsClass};
+use decode_as::DecodeAs;
+use genet_abi::timestamp::TimestampFormat;
 use genet_filter::Filter;
 use genet_napi::{
     napi::{
         CallbackInfo, Env, HandleScope, PropertyAttributes, PropertyDescriptor, Result, Status,
-        Value, ValueRef,
+        TypedArrayType, Value, ValueRef, ValueType,
     },
     uv,
 };
+use iograph::{Aggregation, IoGraph};
 use parking_lot::Mutex;
+use patch::Patch;
 use profile::Profile;
 use serde_json;
 use session::{Callback, Event, Session};
-use std::{collections::VecDeque, rc::Rc, sync::Arc};
+use signature::Verification;
+use std::{collections::VecDeque, path::Path, rc::Rc, slice, sync::Arc};
+use stream::Side;
 
 #[derive(Clone)]
 struct SessionCallback {
@@ -80,10 +87,14 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
     fn profile_load_library<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let profile = env.unwrap::<Profile>(info.this())?;
         if let Some(value) = info.argv().get(0) {
-            if let Err(err) = profile.load_library(&env.get_value_string(value)?) {
-                env.throw_error("load_library", &err.to_string())?;
+            match profile.load_library(&env.get_value_string(value)?) {
+                Ok(Verification::Untrusted(reason)) => env.create_string(&reason),
+                Ok(_) => env.get_null(),
+                Err(err) => {
+                    env.throw_error("load_library", &err.to_string())?;
+                    env.get_null()
+                }
             }
-            env.get_null()
         } else {
             Err(Status::InvalidArg)
         }
@@ -99,17 +110,30 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         }
     }
 
+    fn profile_serial_concurrency<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        if let Some(value) = info.argv().get(0) {
+            profile.set_serial_concurrency(env.get_value_uint32(value)?);
+            env.get_null()
+        } else {
+            env.create_uint32(profile.serial_concurrency())
+        }
+    }
+
     fn session_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
         if let Some([start, end]) = info.argv().get(0..2) {
             let start = env.get_value_uint32(start)?;
             let end = env.get_value_uint32(end)?;
-            let frames = session.frames(start as usize..end as usize);
+            let frames = session.held_frames(start as usize..end as usize);
             let frame_class = env.get_constructor(JsClass::Frame as usize).unwrap();
             let array = env.create_array(frames.len())?;
-            for (i, item) in frames.iter().enumerate() {
+            for (i, (frame, hold)) in frames.into_iter().enumerate() {
                 let instance = env.new_instance(&frame_class, &[])?;
-                env.wrap_ptr(instance, *item)?;
+                env.wrap(instance, FrameRef::held(frame, hold))?;
                 env.set_element(array, i as u32, instance)?;
             }
             Ok(array)
@@ -135,165 +159,1495 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         }
     }
 
-    fn session_set_filter<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+    fn session_filtered_len<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
-        if let Some([id, filter]) = info.argv().get(0..2) {
-            let filter = env.get_value_string(filter)?;
-            session.set_filter(
-                env.get_value_uint32(id)?,
-                if filter.is_empty() {
-                    None
-                } else {
-                    match Filter::compile(&filter) {
-                        Ok(filter) => Some(filter),
-                        Err(err) => {
-                            env.throw_error("load_library", &err.to_string())?;
-                            None
-                        }
-                    }
-                },
-            );
+        if let Some(id) = info.argv().first() {
+            let id = env.get_value_uint32(id)?;
+            env.create_uint32(session.filtered_len(id) as u32)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_filtered_rank<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, index]) = info.argv().get(0..2) {
+            let id = env.get_value_uint32(id)?;
+            let index = env.get_value_uint32(index)?;
+            env.create_uint32(session.filtered_rank(id, index) as u32)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_filtered_select<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, row]) = info.argv().get(0..2) {
+            let id = env.get_value_uint32(id)?;
+            let row = env.get_value_uint32(row)?;
+            match session.filtered_select(id, row as usize) {
+                Some(index) => env.create_uint32(index),
+                None => env.get_null(),
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_filtered_bitmap<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(id) = info.argv().first() {
+            let id = env.get_value_uint32(id)?;
+            match session.filtered_bitmap(id) {
+                Some(data) => env.create_arraybuffer_copy(&data),
+                None => env.get_null(),
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_marked<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([indices, marked]) = info.argv().get(0..2) {
+            match serde_json::from_str::<Vec<u32>>(&env.get_value_string(indices)?) {
+                Ok(indices) => session.set_marked(&indices, env.get_value_bool(marked)?),
+                Err(err) => env.throw_error("indices", &err.to_string())?,
+            }
             env.get_null()
         } else {
             Err(Status::InvalidArg)
         }
     }
 
-    fn session_create_reader<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+    fn session_set_ignored<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
-        if let Some([id, arg]) = info.argv().get(0..2) {
-            let handle =
-                session.create_reader(&env.get_value_string(id)?, &env.get_value_string(arg)?);
-            env.create_uint32(handle)
+        if let Some([indices, ignored]) = info.argv().get(0..2) {
+            match serde_json::from_str::<Vec<u32>>(&env.get_value_string(indices)?) {
+                Ok(indices) => session.set_ignored(&indices, env.get_value_bool(ignored)?),
+                Err(err) => env.throw_error("indices", &err.to_string())?,
+            }
+            env.get_null()
         } else {
             Err(Status::InvalidArg)
         }
     }
 
-    fn session_create_writer<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+    fn session_marked_bitmap<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
-        if let Some([id, arg, filter]) = info.argv().get(0..3) {
+        env.create_arraybuffer_copy(&session.marked_bitmap())
+    }
+
+    fn session_ignored_bitmap<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        env.create_arraybuffer_copy(&session.ignored_bitmap())
+    }
+
+    fn session_frame_summaries<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([start, end]) = info.argv().get(0..2) {
+            let start = env.get_value_uint32(start)?;
+            let end = env.get_value_uint32(end)?;
+            let summaries = session.frame_summaries(start as usize..end as usize);
+            env.create_string(&serde_json::to_string(&summaries).unwrap())
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_value_counts<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, filter, top_n]) = info.argv().get(0..3) {
             let filter = env.get_value_string(filter)?;
-            let handle = session.create_writer(
+            let filter = if filter.is_empty() {
+                None
+            } else {
+                match Filter::compile_with_zone(&filter, session.timestamp_format().zone) {
+                    Ok(filter) => Some(filter),
+                    Err(err) => {
+                        env.throw_error("value_counts", &err.to_string())?;
+                        return env.get_null();
+                    }
+                }
+            };
+            let counts = session.value_counts(
                 &env.get_value_string(id)?,
-                &env.get_value_string(arg)?,
-                if filter.is_empty() {
-                    None
-                } else {
-                    match Filter::compile(&filter) {
-                        Ok(filter) => Some(filter),
-                        Err(err) => {
-                            env.throw_error("load_library", &err.to_string())?;
-                            None
-                        }
+                filter.as_ref(),
+                env.get_value_uint32(top_n)? as usize,
+            );
+            env.create_string(&serde_json::to_string(&counts).unwrap())
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_coverage<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([filter]) = info.argv().get(0..1) {
+            let filter = env.get_value_string(filter)?;
+            let filter = if filter.is_empty() {
+                None
+            } else {
+                match Filter::compile_with_zone(&filter, session.timestamp_format().zone) {
+                    Ok(filter) => Some(filter),
+                    Err(err) => {
+                        env.throw_error("coverage", &err.to_string())?;
+                        return env.get_null();
                     }
-                },
+                }
+            };
+            let report = session.coverage(filter.as_ref());
+            env.create_string(&serde_json::to_string(&report).unwrap())
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_stream_text<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([layer, stream]) = info.argv().get(0..2) {
+            let text = session.stream_text(
+                &env.get_value_string(layer)?,
+                env.get_value_uint32(stream)? as u64,
             );
-            env.create_uint32(handle)
+            env.create_string(&serde_json::to_string(&text).unwrap())
         } else {
             Err(Status::InvalidArg)
         }
     }
 
-    fn session_close_reader<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+    fn session_follow_stream<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
-        if let Some(value) = info.argv().get(0) {
-            session.close_reader(env.get_value_uint32(value)?);
+        if let Some([layer, stream, side]) = info.argv().get(0..3) {
+            let side = env.get_value_string(side)?;
+            let side = if side.is_empty() {
+                None
+            } else if let Some(side) = Side::from_name(&side) {
+                Some(side)
+            } else {
+                env.throw_error("follow_stream", &format!("unknown direction: {}", side))?;
+                return env.get_null();
+            };
+            let chunks = session.follow_stream(
+                &env.get_value_string(layer)?,
+                env.get_value_uint32(stream)? as u64,
+                side,
+            );
+            let array = env.create_array(chunks.len())?;
+            for (i, chunk) in chunks.iter().enumerate() {
+                let object = env.create_object()?;
+                env.set_named_property(object, "frame", env.create_uint32(chunk.frame)?)?;
+                env.set_named_property(object, "side", env.create_string(chunk.side.name())?)?;
+                env.set_named_property(object, "offset", env.create_double(chunk.offset as f64)?)?;
+                env.set_named_property(
+                    object,
+                    "data",
+                    env.create_typedarray(
+                        TypedArrayType::Uint8Array,
+                        chunk.data.len(),
+                        env.create_arraybuffer_copy(&chunk.data)?,
+                        0,
+                    )?,
+                )?;
+                env.set_element(array, i as u32, object)?;
+            }
+            Ok(array)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_conversations<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(level) = info.argv().first() {
+            let level = env.get_value_string(level)?;
+            if let Some(convs) = session.conversations(&level) {
+                env.create_string(&serde_json::to_string(&convs).unwrap())
+            } else {
+                env.throw_error("conversations", &format!("unknown level: {}", level))?;
+                env.get_null()
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_annotations<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        env.create_string(&serde_json::to_string(session.annotations()).unwrap())
+    }
+
+    fn session_set_comment<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([index, comment]) = info.argv().get(0..2) {
+            let comment = env.get_value_string(comment)?;
+            session.set_comment(env.get_value_uint32(index)?, Some(comment.as_str()));
             env.get_null()
         } else {
             Err(Status::InvalidArg)
         }
     }
 
-    fn session_length<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+    fn session_set_byte_annotations<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
-        env.create_uint32(session.len() as u32)
+        if let Some([index, annotations]) = info.argv().get(0..2) {
+            let index = env.get_value_uint32(index)?;
+            let result = serde_json::from_str(&env.get_value_string(annotations)?)
+                .map_err(|err| err.to_string())
+                .and_then(|annotations| session.set_byte_annotations(index, annotations));
+            if let Err(err) = result {
+                env.throw_error("annotations", &err)?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
     }
 
-    fn session_profile<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+    fn session_frame_annotations<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
-        let json = serde_json::to_string(&session.profile()).unwrap();
-        env.create_string(&json)
+        if let Some(index) = info.argv().first() {
+            let annotations = session.frame_annotations(env.get_value_uint32(index)?);
+            env.create_string(&serde_json::to_string(&annotations).unwrap())
+        } else {
+            Err(Status::InvalidArg)
+        }
     }
 
-    let session_class = env.define_class(
-        "Session",
-        session_ctor,
-        &[
-            PropertyDescriptor::new_method(
-                env,
-                "frames",
-                PropertyAttributes::DEFAULT,
-                session_frames,
-            ),
-            PropertyDescriptor::new_method(
-                env,
-                "filteredFrames",
-                PropertyAttributes::DEFAULT,
-                session_filtered_frames,
-            ),
-            PropertyDescriptor::new_method(
-                env,
-                "setFilter",
-                PropertyAttributes::DEFAULT,
-                session_set_filter,
-            ),
-            PropertyDescriptor::new_method(
-                env,
-                "createReader",
-                PropertyAttributes::DEFAULT,
-                session_create_reader,
-            ),
-            PropertyDescriptor::new_method(
-                env,
-                "createWriter",
-                PropertyAttributes::DEFAULT,
-                session_create_writer,
-            ),
-            PropertyDescriptor::new_method(
-                env,
-                "closeReader",
-                PropertyAttributes::DEFAULT,
-                session_close_reader,
-            ),
-            PropertyDescriptor::new_property(
-                env,
-                "length",
-                PropertyAttributes::DEFAULT,
-                session_length,
-                true,
-            ),
-            PropertyDescriptor::new_property(
-                env,
-                "profile",
-                PropertyAttributes::DEFAULT,
-                session_profile,
-                true,
-            ),
-        ],
-    )?;
+    fn session_commented_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        let frames = session.commented_frames();
+        let array = env.create_array(frames.len())?;
+        for (i, item) in frames.iter().enumerate() {
+            env.set_element(array, i as u32, env.create_uint32(*item)?)?;
+        }
+        Ok(array)
+    }
 
-    let profile_class = env.define_class(
-        "Profile",
-        profile_ctor,
-        &[
-            PropertyDescriptor::new_method(
-                env,
-                "setConfig",
-                PropertyAttributes::DEFAULT,
-                profile_set_config,
-            ),
-            PropertyDescriptor::new_method(
-                env,
-                "loadLibrary",
-                PropertyAttributes::DEFAULT,
-                profile_load_library,
-            ),
-            PropertyDescriptor::new_property(
-                env,
-                "concurrency",
-                PropertyAttributes::DEFAULT,
-                profile_concurrency,
+    fn session_set_bookmark<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([index, bookmarked]) = info.argv().get(0..2) {
+            session.set_bookmark(
+                env.get_value_uint32(index)?,
+                env.get_value_bool(bookmarked)?,
+            );
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_color_rules<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(rules) = info.argv().first() {
+            let result = serde_json::from_str(&env.get_value_string(rules)?)
+                .map_err(|err| err.to_string())
+                .and_then(|rules| session.set_color_rules(rules));
+            if let Err(err) = result {
+                env.throw_error("color_rules", &err)?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_columns<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(columns) = info.argv().first() {
+            let result = serde_json::from_str(&env.get_value_string(columns)?)
+                .map_err(|err| err.to_string())
+                .and_then(|columns| session.set_columns(columns));
+            if let Err(err) = result {
+                env.throw_error("columns", &err)?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_column_rows<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([start, end]) = info.argv().get(0..2) {
+            let start = env.get_value_uint32(start)?;
+            let end = env.get_value_uint32(end)?;
+            let id = match info.argv().get(2) {
+                Some(id) => Some(env.get_value_uint32(id)?),
+                None => None,
+            };
+            let rows = session.column_rows(start as usize..end as usize, id);
+            env.create_string(&serde_json::to_string(&rows).unwrap())
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_frame_colors<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([start, end]) = info.argv().get(0..2) {
+            let start = env.get_value_uint32(start)?;
+            let end = env.get_value_uint32(end)?;
+            let id = match info.argv().get(2) {
+                Some(id) => Some(env.get_value_uint32(id)?),
+                None => None,
+            };
+            let colors = session.frame_colors(start as usize..end as usize, id);
+            env.create_string(&serde_json::to_string(&colors).unwrap())
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_export_annotations<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(path) = info.argv().first() {
+            let path = env.get_value_string(path)?;
+            if let Err(err) = session.export_annotations().save(Path::new(&path)) {
+                env.throw_error("annotations", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_import_annotations<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(path) = info.argv().first() {
+            let path = env.get_value_string(path)?;
+            let result = Annotations::load(Path::new(&path))
+                .map_err(|err| err.to_string())
+                .and_then(|annotations| session.import_annotations(annotations));
+            if let Err(err) = result {
+                env.throw_error("annotations", &err)?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_save<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(path) = info.argv().first() {
+            let path = env.get_value_string(path)?;
+            if let Err(err) = session.save(Path::new(&path)) {
+                env.throw_error("session", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_restore<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(path) = info.argv().first() {
+            let path = env.get_value_string(path)?;
+            if let Err(err) = session.restore(Path::new(&path)) {
+                env.throw_error("session", &err)?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_compare<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, arg, options]) = info.argv().get(0..3) {
+            let id = env.get_value_string(id)?;
+            let arg = env.get_value_string(arg)?;
+            let result = serde_json::from_str(&env.get_value_string(options)?)
+                .map_err(|err| err.to_string())
+                .and_then(|options| session.compare(&id, &arg, &options));
+            match result {
+                Ok(report) => env.create_string(&serde_json::to_string(&report).unwrap()),
+                Err(err) => {
+                    env.throw_error("compare", &err)?;
+                    env.get_null()
+                }
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_comparison_report<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        env.create_string(&serde_json::to_string(&session.comparison_report()).unwrap())
+    }
+
+    fn session_clear_comparison<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        session.clear_comparison();
+        env.get_null()
+    }
+
+    fn session_shift_timestamps<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(correction) = info.argv().first() {
+            let result = serde_json::from_str(&env.get_value_string(correction)?)
+                .map_err(|err| err.to_string())
+                .and_then(|correction| session.shift_timestamps(&correction));
+            match result {
+                Ok(shift) => env.create_string(&serde_json::to_string(&shift).unwrap()),
+                Err(err) => {
+                    env.throw_error("shift_timestamps", &err)?;
+                    env.get_null()
+                }
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_time_shift<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        env.create_string(&serde_json::to_string(&session.time_shift()).unwrap())
+    }
+
+    fn session_clear_time_shift<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        session.set_time_shift(None);
+        env.get_null()
+    }
+
+    fn session_expert_summary<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        env.create_string(&serde_json::to_string(&session.expert_summary()).unwrap())
+    }
+
+    fn session_expert_findings<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(index) = info.argv().first() {
+            let findings = session.expert_findings(env.get_value_uint32(index)?);
+            env.create_string(&serde_json::to_string(&findings).unwrap())
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_attribute_catalog<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        let json = serde_json::to_string(&session.attribute_catalog()).unwrap();
+        env.create_string(&json)
+    }
+
+    fn session_diff_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([a, b]) = info.argv().get(0..2) {
+            let a = env.get_value_uint32(a)?;
+            let b = env.get_value_uint32(b)?;
+            if let Some(changes) = session.diff_frames(a as usize, b as usize) {
+                env.create_string(&serde_json::to_string(&changes).unwrap())
+            } else {
+                env.get_null()
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_decode_patched<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([index, patches]) = info.argv().get(0..2) {
+            let index = env.get_value_uint32(index)?;
+            let patches: Vec<Patch> = match serde_json::from_str(&env.get_value_string(patches)?) {
+                Ok(patches) => patches,
+                Err(err) => {
+                    env.throw_error("patch", &err.to_string())?;
+                    return env.get_null();
+                }
+            };
+            if let Some(frame) = session.decode_patched(index as usize, &patches) {
+                let frame_class = env.get_constructor(JsClass::Frame as usize).unwrap();
+                let instance = env.new_instance(&frame_class, &[])?;
+                env.wrap(instance, FrameRef::owned(frame))?;
+                Ok(instance)
+            } else {
+                env.get_null()
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_pin_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, start, end]) = info.argv().get(0..3) {
+            let start = env.get_value_uint32(start)?;
+            let end = env.get_value_uint32(end)?;
+            session.pin_frames(env.get_value_uint32(id)?, start as usize..end as usize);
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_unpin_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(id) = info.argv().get(0) {
+            session.unpin_frames(env.get_value_uint32(id)?);
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_cache_stats<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        env.create_string(&serde_json::to_string(&session.cache_stats()).unwrap())
+    }
+
+    fn session_pipeline_stats<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        env.create_string(&serde_json::to_string(&session.pipeline_stats()).unwrap())
+    }
+
+    fn session_capture_stats<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(handle) = info.argv().first() {
+            let stats = session.capture_stats(env.get_value_uint32(handle)?);
+            env.create_string(&serde_json::to_string(&stats).unwrap())
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_filter<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, filter]) = info.argv().get(0..2) {
+            let filter = env.get_value_string(filter)?;
+            session.set_filter(
+                env.get_value_uint32(id)?,
+                if filter.is_empty() {
+                    None
+                } else {
+                    match Filter::compile_with_zone(&filter, session.timestamp_format().zone) {
+                        Ok(filter) => Some(filter),
+                        Err(err) => {
+                            env.throw_error("load_library", &err.to_string())?;
+                            None
+                        }
+                    }
+                },
+            );
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_io_graph<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, interval, filter, aggregation, attr]) = info.argv().get(0..5) {
+            let id = env.get_value_uint32(id)?;
+            let aggregation = env.get_value_string(aggregation)?;
+            if aggregation.is_empty() {
+                session.set_io_graph(id, None);
+                return env.get_null();
+            }
+            let aggregation = match Aggregation::new(&aggregation, &env.get_value_string(attr)?) {
+                Ok(aggregation) => aggregation,
+                Err(err) => {
+                    env.throw_error("io_graph", &err)?;
+                    return env.get_null();
+                }
+            };
+            let filter = env.get_value_string(filter)?;
+            let filter = if filter.is_empty() {
+                None
+            } else {
+                match Filter::compile_with_zone(&filter, session.timestamp_format().zone) {
+                    Ok(filter) => Some(filter),
+                    Err(err) => {
+                        env.throw_error("io_graph", &err.to_string())?;
+                        return env.get_null();
+                    }
+                }
+            };
+            let interval = env.get_value_double(interval)?;
+            session.set_io_graph(id, Some(IoGraph::new(interval, filter, aggregation)));
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_io_graph<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(id) = info.argv().first() {
+            if let Some(points) = session.io_graph(env.get_value_uint32(id)?) {
+                env.create_string(&serde_json::to_string(&points).unwrap())
+            } else {
+                env.get_null()
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_sort_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, expr, filter, descending]) = info.argv().get(0..4) {
+            let id = env.get_value_uint32(id)?;
+            let filter = env.get_value_string(filter)?;
+            let filter = if filter.is_empty() {
+                None
+            } else {
+                match Filter::compile_with_zone(&filter, session.timestamp_format().zone) {
+                    Ok(filter) => Some(filter),
+                    Err(err) => {
+                        env.throw_error("sort", &err.to_string())?;
+                        return env.get_null();
+                    }
+                }
+            };
+            let descending = env.get_value_bool(descending)?;
+            match session.sort_frames(
+                id,
+                &env.get_value_string(expr)?,
+                filter.as_ref(),
+                descending,
+            ) {
+                Ok(len) => env.create_uint32(len as u32),
+                Err(err) => {
+                    env.throw_error("sort", &err)?;
+                    env.get_null()
+                }
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_sorted_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, start, end]) = info.argv().get(0..3) {
+            let id = env.get_value_uint32(id)?;
+            let start = env.get_value_uint32(start)?;
+            let end = env.get_value_uint32(end)?;
+            let frames = session.sorted_frames(id, start as usize..end as usize);
+            let array = env.create_array(frames.len())?;
+            for (i, item) in frames.iter().enumerate() {
+                env.set_element(array, i as u32, env.create_uint32(*item)?)?;
+            }
+            Ok(array)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_clear_sort<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(id) = info.argv().first() {
+            session.clear_sort(env.get_value_uint32(id)?);
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_create_reader<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, arg]) = info.argv().get(0..2) {
+            let handle =
+                session.create_reader(&env.get_value_string(id)?, &env.get_value_string(arg)?);
+            env.create_uint32(handle)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_create_writer<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, arg, filter]) = info.argv().get(0..3) {
+            let filter = env.get_value_string(filter)?;
+            let handle = session.create_writer(
+                &env.get_value_string(id)?,
+                &env.get_value_string(arg)?,
+                if filter.is_empty() {
+                    None
+                } else {
+                    match Filter::compile_with_zone(&filter, session.timestamp_format().zone) {
+                        Ok(filter) => Some(filter),
+                        Err(err) => {
+                            env.throw_error("load_library", &err.to_string())?;
+                            None
+                        }
+                    }
+                },
+                match info.argv().get(3..5) {
+                    Some([start, end]) => {
+                        Some(env.get_value_uint32(start)?..env.get_value_uint32(end)?)
+                    }
+                    _ => None,
+                },
+            );
+            env.create_uint32(handle)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_write_raw_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, arg, link, array]) = info.argv().get(0..4) {
+            let mut frames = Vec::new();
+            for i in 0..env.get_array_length(array)? {
+                let frame = env.get_element(array, i)?;
+                if !env.is_typedarray(frame)? {
+                    return Err(Status::InvalidArg);
+                }
+                let (ptr, len, _) = env.get_typedarray_info(frame)?;
+                frames.push(unsafe { slice::from_raw_parts(ptr, len) }.to_vec());
+            }
+            let handle = session.write_raw_frames(
+                &env.get_value_string(id)?,
+                &env.get_value_string(arg)?,
+                env.get_value_uint32(link)?,
+                frames,
+            );
+            env.create_uint32(handle)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_dissector_table_entry<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([table, key, decoder]) = info.argv().get(0..3) {
+            session.set_dissector_table_entry(
+                &env.get_value_string(table)?,
+                env.get_value_int64(key)? as u64,
+                &env.get_value_string(decoder)?,
+            );
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_config<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([key, value]) = info.argv().get(0..2) {
+            let key = env.get_value_string(key)?;
+            if let Err(err) = session.set_config(&key, &env.get_value_string(value)?) {
+                env.throw_error("set_config", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_reload_library<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(value) = info.argv().get(0) {
+            match session.reload_library(&env.get_value_string(value)?) {
+                Ok(Verification::Untrusted(reason)) => env.create_string(&reason),
+                Ok(_) => env.get_null(),
+                Err(err) => {
+                    env.throw_error("reload_library", &err.to_string())?;
+                    env.get_null()
+                }
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_decode_as<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, filter, decoder]) = info.argv().get(0..3) {
+            let filter = env.get_value_string(filter)?;
+            let rule = if filter.is_empty() {
+                None
+            } else {
+                match Filter::compile_with_zone(&filter, session.timestamp_format().zone) {
+                    Ok(filter) => Some(DecodeAs::new(
+                        filter,
+                        env.get_value_string(decoder)?.as_str(),
+                    )),
+                    Err(err) => {
+                        // Keeps the current rule.
+                        env.throw_error("decode_as", &err.to_string())?;
+                        return env.get_null();
+                    }
+                }
+            };
+            session.set_decode_as(env.get_value_uint32(id)?, rule);
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_conversation_decode_as<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, index, layer, decoder]) = info.argv().get(0..4) {
+            if !session.set_conversation_decode_as(
+                env.get_value_uint32(id)?,
+                env.get_value_uint32(index)? as usize,
+                &env.get_value_string(layer)?,
+                &env.get_value_string(decoder)?,
+            ) {
+                env.throw_error("decode_as", "no conversation in the frame")?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_timestamp_format<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([zone, reference, resolution]) = info.argv().get(0..3) {
+            let zone = env.get_value_string(zone)?;
+            let reference = env.get_value_string(reference)?;
+            let resolution = env.get_value_string(resolution)?;
+            match (zone.parse(), reference.parse(), resolution.parse()) {
+                (Ok(zone), Ok(reference), Ok(resolution)) => {
+                    session.set_timestamp_format(TimestampFormat {
+                        zone,
+                        reference,
+                        resolution,
+                    });
+                }
+                _ => {
+                    env.throw_error("timestamp_format", "invalid timestamp format")?;
+                }
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_saved_filters<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        let json = serde_json::to_string(&session.saved_filters()).unwrap();
+        env.create_string(&json)
+    }
+
+    fn session_save_filter<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([name, filter]) = info.argv().get(0..2) {
+            let filter = env.get_value_string(filter)?;
+            let filter = if filter.is_empty() {
+                None
+            } else {
+                Some(filter.as_str())
+            };
+            if let Err(err) = session.save_filter(&env.get_value_string(name)?, filter) {
+                env.throw_error("save_filter", &err)?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_macro<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([name, expr]) = info.argv().get(0..2) {
+            let expr = env.get_value_string(expr)?;
+            let expr = if expr.is_empty() {
+                None
+            } else {
+                Some(expr.as_str())
+            };
+            if let Err(err) = session.set_macro(&env.get_value_string(name)?, expr) {
+                env.throw_error("set_macro", &err)?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_close_reader<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(value) = info.argv().get(0) {
+            session.close_reader(env.get_value_uint32(value)?);
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_start_spill<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(value) = info.argv().get(0) {
+            if let Err(err) = session.start_spill(&env.get_value_string(value)?) {
+                env.throw_error("start_spill", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_stop_spill<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        session.stop_spill();
+        env.get_null()
+    }
+
+    fn session_start_ring_buffer<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, arg, options]) = info.argv().get(0..3) {
+            let id = env.get_value_string(id)?;
+            let arg = env.get_value_string(arg)?;
+            let result = serde_json::from_str(&env.get_value_string(options)?)
+                .map_err(|err| err.to_string())
+                .and_then(|options| session.start_ring_buffer(&id, &arg, &options));
+            if let Err(err) = result {
+                env.throw_error("start_ring_buffer", &err)?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_stop_ring_buffer<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        session.stop_ring_buffer();
+        env.get_null()
+    }
+
+    fn session_set_frame_window<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        let window = match info.argv().first() {
+            Some(window) if env.type_of(window)? == ValueType::Number => {
+                Some(env.get_value_uint32(window)? as usize)
+            }
+            _ => None,
+        };
+        session.set_frame_window(window);
+        env.get_null()
+    }
+
+    fn session_first_frame<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        env.create_uint32(session.first_frame() as u32)
+    }
+
+    fn session_length<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        env.create_uint32(session.len() as u32)
+    }
+
+    fn session_profile<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        let json = serde_json::to_string(&session.profile()).unwrap();
+        env.create_string(&json)
+    }
+
+    fn session_metadata<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        let json = serde_json::to_string(&session.metadata()).unwrap();
+        env.create_string(&json)
+    }
+
+    let session_class = env.define_class(
+        "Session",
+        session_ctor,
+        &[
+            PropertyDescriptor::new_method(
+                env,
+                "frames",
+                PropertyAttributes::DEFAULT,
+                session_frames,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "filteredFrames",
+                PropertyAttributes::DEFAULT,
+                session_filtered_frames,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "filteredLength",
+                PropertyAttributes::DEFAULT,
+                session_filtered_len,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "filteredRank",
+                PropertyAttributes::DEFAULT,
+                session_filtered_rank,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "filteredSelect",
+                PropertyAttributes::DEFAULT,
+                session_filtered_select,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "filteredBitmap",
+                PropertyAttributes::DEFAULT,
+                session_filtered_bitmap,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setMarked",
+                PropertyAttributes::DEFAULT,
+                session_set_marked,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setIgnored",
+                PropertyAttributes::DEFAULT,
+                session_set_ignored,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "markedBitmap",
+                PropertyAttributes::DEFAULT,
+                session_marked_bitmap,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "ignoredBitmap",
+                PropertyAttributes::DEFAULT,
+                session_ignored_bitmap,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "frameSummaries",
+                PropertyAttributes::DEFAULT,
+                session_frame_summaries,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "valueCounts",
+                PropertyAttributes::DEFAULT,
+                session_value_counts,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "coverage",
+                PropertyAttributes::DEFAULT,
+                session_coverage,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "pinFrames",
+                PropertyAttributes::DEFAULT,
+                session_pin_frames,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "unpinFrames",
+                PropertyAttributes::DEFAULT,
+                session_unpin_frames,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "cacheStats",
+                PropertyAttributes::DEFAULT,
+                session_cache_stats,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "pipelineStats",
+                PropertyAttributes::DEFAULT,
+                session_pipeline_stats,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "captureStats",
+                PropertyAttributes::DEFAULT,
+                session_capture_stats,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "streamText",
+                PropertyAttributes::DEFAULT,
+                session_stream_text,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "followStream",
+                PropertyAttributes::DEFAULT,
+                session_follow_stream,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "conversations",
+                PropertyAttributes::DEFAULT,
+                session_conversations,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "annotations",
+                PropertyAttributes::DEFAULT,
+                session_annotations,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setComment",
+                PropertyAttributes::DEFAULT,
+                session_set_comment,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setByteAnnotations",
+                PropertyAttributes::DEFAULT,
+                session_set_byte_annotations,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "frameAnnotations",
+                PropertyAttributes::DEFAULT,
+                session_frame_annotations,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "commentedFrames",
+                PropertyAttributes::DEFAULT,
+                session_commented_frames,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setBookmark",
+                PropertyAttributes::DEFAULT,
+                session_set_bookmark,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setColorRules",
+                PropertyAttributes::DEFAULT,
+                session_set_color_rules,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setColumns",
+                PropertyAttributes::DEFAULT,
+                session_set_columns,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "columnRows",
+                PropertyAttributes::DEFAULT,
+                session_column_rows,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "frameColors",
+                PropertyAttributes::DEFAULT,
+                session_frame_colors,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "sortFrames",
+                PropertyAttributes::DEFAULT,
+                session_sort_frames,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "sortedFrames",
+                PropertyAttributes::DEFAULT,
+                session_sorted_frames,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "clearSort",
+                PropertyAttributes::DEFAULT,
+                session_clear_sort,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "exportAnnotations",
+                PropertyAttributes::DEFAULT,
+                session_export_annotations,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "importAnnotations",
+                PropertyAttributes::DEFAULT,
+                session_import_annotations,
+            ),
+            PropertyDescriptor::new_method(env, "save", PropertyAttributes::DEFAULT, session_save),
+            PropertyDescriptor::new_method(
+                env,
+                "restore",
+                PropertyAttributes::DEFAULT,
+                session_restore,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "compare",
+                PropertyAttributes::DEFAULT,
+                session_compare,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "comparisonReport",
+                PropertyAttributes::DEFAULT,
+                session_comparison_report,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "clearComparison",
+                PropertyAttributes::DEFAULT,
+                session_clear_comparison,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "shiftTimestamps",
+                PropertyAttributes::DEFAULT,
+                session_shift_timestamps,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "timeShift",
+                PropertyAttributes::DEFAULT,
+                session_time_shift,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "clearTimeShift",
+                PropertyAttributes::DEFAULT,
+                session_clear_time_shift,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setIoGraph",
+                PropertyAttributes::DEFAULT,
+                session_set_io_graph,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "ioGraph",
+                PropertyAttributes::DEFAULT,
+                session_io_graph,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "expertSummary",
+                PropertyAttributes::DEFAULT,
+                session_expert_summary,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "expertFindings",
+                PropertyAttributes::DEFAULT,
+                session_expert_findings,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "attributeCatalog",
+                PropertyAttributes::DEFAULT,
+                session_attribute_catalog,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "diffFrames",
+                PropertyAttributes::DEFAULT,
+                session_diff_frames,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "decodePatched",
+                PropertyAttributes::DEFAULT,
+                session_decode_patched,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setFilter",
+                PropertyAttributes::DEFAULT,
+                session_set_filter,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "createReader",
+                PropertyAttributes::DEFAULT,
+                session_create_reader,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "createWriter",
+                PropertyAttributes::DEFAULT,
+                session_create_writer,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "writeRawFrames",
+                PropertyAttributes::DEFAULT,
+                session_write_raw_frames,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setDissectorTableEntry",
+                PropertyAttributes::DEFAULT,
+                session_set_dissector_table_entry,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setConfig",
+                PropertyAttributes::DEFAULT,
+                session_set_config,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "reloadLibrary",
+                PropertyAttributes::DEFAULT,
+                session_reload_library,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setDecodeAs",
+                PropertyAttributes::DEFAULT,
+                session_set_decode_as,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setConversationDecodeAs",
+                PropertyAttributes::DEFAULT,
+                session_set_conversation_decode_as,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setTimestampFormat",
+                PropertyAttributes::DEFAULT,
+                session_set_timestamp_format,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "savedFilters",
+                PropertyAttributes::DEFAULT,
+                session_saved_filters,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "saveFilter",
+                PropertyAttributes::DEFAULT,
+                session_save_filter,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setMacro",
+                PropertyAttributes::DEFAULT,
+                session_set_macro,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "closeReader",
+                PropertyAttributes::DEFAULT,
+                session_close_reader,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "startSpill",
+                PropertyAttributes::DEFAULT,
+                session_start_spill,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "stopSpill",
+                PropertyAttributes::DEFAULT,
+                session_stop_spill,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "startRingBuffer",
+                PropertyAttributes::DEFAULT,
+                session_start_ring_buffer,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "stopRingBuffer",
+                PropertyAttributes::DEFAULT,
+                session_stop_ring_buffer,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setFrameWindow",
+                PropertyAttributes::DEFAULT,
+                session_set_frame_window,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "firstFrame",
+                PropertyAttributes::DEFAULT,
+                session_first_frame,
+            ),
+            PropertyDescriptor::new_property(
+                env,
+                "length",
+                PropertyAttributes::DEFAULT,
+                session_length,
+                true,
+            ),
+            PropertyDescriptor::new_property(
+                env,
+                "profile",
+                PropertyAttributes::DEFAULT,
+                session_profile,
+                true,
+            ),
+            PropertyDescriptor::new_property(
+                env,
+                "metadata",
+                PropertyAttributes::DEFAULT,
+                session_metadata,
+                true,
+            ),
+        ],
+    )?;
+
+    let profile_class = env.define_class(
+        "Profile",
+        profile_ctor,
+        &[
+            PropertyDescriptor::new_method(
+                env,
+                "setConfig",
+                PropertyAttributes::DEFAULT,
+                profile_set_config,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "loadLibrary",
+                PropertyAttributes::DEFAULT,
+                profile_load_library,
+            ),
+            PropertyDescriptor::new_property(
+                env,
+                "concurrency",
+                PropertyAttributes::DEFAULT,
+                profile_concurrency,
+                true,
+            ),
+            PropertyDescriptor::new_property(
+                env,
+                "serialConcurrency",
+                PropertyAttributes::DEFAULT,
+                profile_serial_concurrency,
                 true,
             ),
         ],
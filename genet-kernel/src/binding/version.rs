@@ -6,6 +6,7 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
     let abi = env::genet_abi_version();
     let abi = format!("{}.{}", abi >> 32, abi & 0xffff_ffff);
     env.set_named_property(tk, "abi", env.create_string(&abi)?)?;
+    env.set_named_property(tk, "abiRevision", env.create_uint32(env::ABI_REVISION)?)?;
     env.set_named_property(exports, "version", tk)?;
     Ok(())
 }
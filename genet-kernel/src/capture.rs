@@ -0,0 +1,643 @@
+//! Merging, splitting and de-duplication of capture files.
+//!
+//! `edit` reads the frames of one or more readers and writes them to one or
+//! more writers without a session, like `mergecap` and `editcap`:
+//!
+//! - The frames of several readers are merged in chronological order. The
+//!   frames of each reader are expected to be in chronological order, and
+//!   the frames with the same timestamp are written in the order of the
+//!   readers.
+//! - A frame with the same data as one of the previous frames within the
+//!   `DedupWindow` is dropped.
+//! - The frames are written to a new part every number of frames, every
+//!   interval of time from the first frame, or for each flow, i.e. each pair
+//!   of IP addresses and TCP or UDP ports. Splitting by flow decodes the
+//!   frames with the decoders of the profile.
+//!
+//! `raw_frames` makes frames of raw bytes, e.g. crafted frames, which are
+//! written like the frames of a reader.
+
+use analysis::{self, attr};
+use decoder::dispatcher::Dispatcher;
+use extract;
+use fnv::{FnvHashMap, FnvHasher};
+use frame::Frame;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    decoder::ExecType,
+    fixed::{Fixed, MutFixed},
+    layer::{Layer, LayerClass},
+    result::Result,
+    slice::ByteSlice,
+    writer,
+};
+use io::Input;
+use profile::Profile;
+use session::WorkerInput;
+use std::{
+    collections::VecDeque,
+    hash::Hasher,
+    path::Path,
+    slice,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Frees the layers of a frame which has been written.
+fn release(layers: Vec<MutFixed<Layer>>) {
+    for layer in layers {
+        drop(unsafe { Box::from_raw(layer.as_mut_ptr()) });
+    }
+}
+
+/// The frames read by a reader.
+pub struct Source {
+    input: Box<Input>,
+    buffer: VecDeque<(i128, MutFixed<Layer>)>,
+    time: i128,
+    done: bool,
+}
+
+impl Source {
+    /// Creates a worker of the reader `id` with `arg`.
+    pub fn open(profile: &Profile, id: &str, arg: &str) -> ::std::result::Result<Source, String> {
+        let reader = profile
+            .readers()
+            .find(|&&r| r.metadata().id.as_str() == id)
+            .ok_or_else(|| format!("{}: no such reader", id))?;
+        let ctx = profile.context();
+        let worker = reader
+            .new_worker(&ctx, arg)
+            .map_err(|err| format!("{}: {}", id, err))?;
+        Ok(Source::new(Box::new(WorkerInput::new(worker, ctx))))
+    }
+
+    pub(crate) fn new(input: Box<Input>) -> Source {
+        Source {
+            input,
+            buffer: VecDeque::new(),
+            time: 0,
+            done: false,
+        }
+    }
+
+    /// Returns the timestamp of the next frame, reading the next block of
+    /// frames if needed. A frame without a timestamp has the timestamp of the
+    /// previous frame.
+    fn peek(&mut self) -> Option<i128> {
+        while self.buffer.is_empty() && !self.done {
+            // Readers report the end of the input as an error.
+            match self.input.read() {
+                Ok(ref layers) if layers.is_empty() => self.done = true,
+                Ok(layers) => {
+                    for root in layers {
+                        self.time = analysis::timestamp(&root).unwrap_or(self.time);
+                        self.buffer.push_back((self.time, root));
+                    }
+                }
+                Err(_) => self.done = true,
+            }
+        }
+        self.buffer.front().map(|(time, _)| *time)
+    }
+}
+
+/// Returns the next frame of `sources` in chronological order.
+fn next(sources: &mut [Source]) -> Option<(i128, MutFixed<Layer>)> {
+    let (_, source) = sources
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(i, source)| source.peek().map(|time| ((time, i), source)))
+        .min_by_key(|(key, _)| *key)?;
+    source.buffer.pop_front()
+}
+
+/// A destination of frames, e.g. a writer.
+pub trait Sink {
+    fn write(&mut self, index: u32, layers: &[MutFixed<Layer>]) -> Result<()>;
+    fn end(&mut self) -> Result<()>;
+}
+
+impl Sink for writer::WorkerBox {
+    fn write(&mut self, index: u32, layers: &[MutFixed<Layer>]) -> Result<()> {
+        writer::WorkerBox::write(self, index, layers)
+    }
+
+    fn end(&mut self) -> Result<()> {
+        writer::WorkerBox::end(self)
+    }
+}
+
+/// Creates a worker of the writer `id` with `arg`.
+pub fn open_writer(
+    profile: &Profile,
+    id: &str,
+    arg: &str,
+) -> ::std::result::Result<Box<Sink + Send>, String> {
+    let writer = profile
+        .writers()
+        .find(|&&w| w.metadata().id.as_str() == id)
+        .ok_or_else(|| format!("{}: no such writer", id))?;
+    let worker = writer
+        .new_worker(&profile.context(), arg)
+        .map_err(|err| format!("{}: {}", id, err))?;
+    Ok(Box::new(worker))
+}
+
+/// Creates the root layers of frames of the link type `link` from their
+/// bytes, stamped with the current time.
+pub fn raw_frames(link: u32, frames: Vec<Vec<u8>>) -> Vec<MutFixed<Layer>> {
+    let class = |id: &str| Fixed::new(AttrClass::builder(id).build());
+    let type_class = class("link.type");
+    let length_class = class("link.length");
+    let sec_class = class("link.timestamp.sec");
+    let nsec_class = class("link.timestamp.nsec");
+    let layer_class = Fixed::new(LayerClass::builder(format!("[link-{}]", link)).build());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    frames
+        .into_iter()
+        .map(|data| {
+            let len = data.len() as u64;
+            let mut layer = Layer::new(layer_class.clone(), ByteSlice::from(data));
+            layer.add_attr(
+                Attr::builder(type_class.clone())
+                    .value(u64::from(link))
+                    .build(),
+            );
+            layer.add_attr(Attr::builder(length_class.clone()).value(len).build());
+            layer.add_attr(
+                Attr::builder(sec_class.clone())
+                    .value(now.as_secs())
+                    .build(),
+            );
+            layer.add_attr(
+                Attr::builder(nsec_class.clone())
+                    .value(u64::from(now.subsec_nanos()))
+                    .build(),
+            );
+            MutFixed::new(layer)
+        })
+        .collect()
+}
+
+/// Writes the root layers `layers` to `sink` and frees them.
+pub fn write_frames(sink: &mut Sink, layers: Vec<MutFixed<Layer>>) -> Result<()> {
+    let result = layers
+        .iter()
+        .enumerate()
+        .try_for_each(|(index, root)| sink.write(index as u32, slice::from_ref(root)))
+        .and_then(|_| sink.end());
+    release(layers);
+    result
+}
+
+/// The window of the previous frames compared with a frame to find
+/// duplicates.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct DedupWindow {
+    /// The number of previous frames, or zero for no limit.
+    pub frames: usize,
+    /// The seconds before the frame, or None for no limit.
+    pub seconds: Option<f64>,
+}
+
+impl Default for DedupWindow {
+    fn default() -> Self {
+        DedupWindow {
+            frames: 5,
+            seconds: None,
+        }
+    }
+}
+
+/// Finds the frames with the same data as one of the previous frames.
+pub struct Dedup {
+    window: DedupWindow,
+    previous: VecDeque<(i128, u64)>,
+}
+
+impl Dedup {
+    pub fn new(window: DedupWindow) -> Dedup {
+        Dedup {
+            window,
+            previous: VecDeque::new(),
+        }
+    }
+
+    /// Returns whether the frame at `time` with `data` is a duplicate, and
+    /// adds it to the window otherwise.
+    pub fn is_duplicate(&mut self, time: i128, data: &[u8]) -> bool {
+        if let Some(seconds) = self.window.seconds {
+            let start = time - (seconds * 1e9) as i128;
+            while self.previous.front().is_some_and(|(t, _)| *t < start) {
+                self.previous.pop_front();
+            }
+        }
+        let mut hasher = FnvHasher::default();
+        hasher.write(data);
+        let hash = hasher.finish();
+        if self.previous.iter().any(|(_, h)| *h == hash) {
+            return true;
+        }
+        self.previous.push_back((time, hash));
+        if self.window.frames > 0 && self.previous.len() > self.window.frames {
+            self.previous.pop_front();
+        }
+        false
+    }
+}
+
+/// The policy dividing the frames into parts.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SplitPolicy {
+    /// A new part every number of frames.
+    Count(u64),
+    /// A new part every number of seconds from the first frame.
+    Interval(f64),
+    /// A part per flow.
+    Flow,
+}
+
+/// A part of the output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Part {
+    /// The number of the part from zero, in the order of the first frames.
+    pub index: usize,
+    /// The endpoints of the flow when splitting by flow, or None for the
+    /// frames out of flows.
+    pub flow: Option<String>,
+}
+
+/// The options of `edit`.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct EditOptions {
+    pub dedup: Option<DedupWindow>,
+    pub split: Option<SplitPolicy>,
+}
+
+/// The counters of `edit`.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EditStats {
+    pub frames: u64,
+    pub written: u64,
+    pub duplicates: u64,
+    pub parts: usize,
+}
+
+/// Returns the endpoints of the flow of a decoded frame, e.g.
+/// `10.0.0.1:1000 10.0.0.2:80 tcp`, sorted so that both directions have the
+/// same key.
+fn flow(layers: &[MutFixed<Layer>]) -> Option<String> {
+    let ip = layers
+        .iter()
+        .rposition(|layer| ["ipv4", "ipv6"].contains(&layer.id().to_string().as_str()))?;
+    let address = |id: &str| {
+        let attr = layers[ip].attr(id)?;
+        let value = attr.try_get(&layers[ip]).ok()?;
+        Some(extract::format(attr, value))
+    };
+    let id = layers[ip].id().to_string();
+    let src = address(&format!("{}.src", id))?;
+    let dst = address(&format!("{}.dst", id))?;
+    let transport = layers[ip + 1..]
+        .iter()
+        .find(|layer| ["tcp", "udp"].contains(&layer.id().to_string().as_str()));
+    let (mut a, mut b, proto) = match transport {
+        Some(layer) => {
+            let proto = layer.id().to_string();
+            let port = |end: &str| attr::<u64>(layer, format!("{}.{}", proto, end));
+            (
+                format!("{}:{}", src, port("src")?),
+                format!("{}:{}", dst, port("dst")?),
+                proto,
+            )
+        }
+        None => (src, dst, id),
+    };
+    if b < a {
+        ::std::mem::swap(&mut a, &mut b);
+    }
+    Some(format!("{} {} {}", a, b, proto))
+}
+
+struct Splitter<'a, F> {
+    policy: Option<SplitPolicy>,
+    open: F,
+    dispatchers: Option<(Dispatcher, Dispatcher)>,
+    parts: Vec<Box<Sink + 'a>>,
+    flows: FnvHashMap<Option<String>, usize>,
+    written: u64,
+    start: Option<i128>,
+}
+
+impl<'a, F> Splitter<'a, F>
+where
+    F: FnMut(&Part) -> ::std::result::Result<Box<Sink + 'a>, String>,
+{
+    fn new(profile: &Profile, policy: Option<SplitPolicy>, open: F) -> Self {
+        let dispatchers = match policy {
+            Some(SplitPolicy::Flow) => Some((
+                Dispatcher::new(&ExecType::ParallelSync, profile),
+                Dispatcher::new(&ExecType::SerialSync, profile),
+            )),
+            _ => None,
+        };
+        Splitter {
+            policy,
+            open,
+            dispatchers,
+            parts: Vec::new(),
+            flows: FnvHashMap::default(),
+            written: 0,
+            start: None,
+        }
+    }
+
+    /// Returns the index of the part of the frame, and the key of its flow.
+    fn part(&mut self, time: i128, layers: &[MutFixed<Layer>]) -> (usize, Option<String>) {
+        let start = *self.start.get_or_insert(time);
+        let next = self.parts.len();
+        match self.policy {
+            None => (0, None),
+            Some(SplitPolicy::Count(count)) => ((self.written / count.max(1)) as usize, None),
+            Some(SplitPolicy::Interval(seconds)) => {
+                let interval = ((seconds * 1e9) as i128).max(1);
+                let index = ((time - start).max(0) / interval) as usize;
+                // Every part is created, so that the parts are not renumbered
+                // by empty intervals.
+                (index.max(next.saturating_sub(1)), None)
+            }
+            Some(SplitPolicy::Flow) => {
+                let key = flow(layers);
+                let index = *self.flows.entry(key.clone()).or_insert(next);
+                (index, key)
+            }
+        }
+    }
+
+    fn write(&mut self, time: i128, root: MutFixed<Layer>) -> ::std::result::Result<(), String> {
+        let mut frame = Frame::new(self.written as u32, root);
+        if let Some((pdisp, sdisp)) = &mut self.dispatchers {
+            pdisp.process_frame(&mut frame);
+            sdisp.process_frame(&mut frame);
+        }
+        let layers = frame.fetch_layers();
+        let (index, flow) = self.part(time, &layers);
+        while self.parts.len() <= index {
+            let part = Part {
+                index: self.parts.len(),
+                flow: flow.clone(),
+            };
+            self.parts.push((self.open)(&part)?);
+        }
+        let result = self.parts[index]
+            .write(self.written as u32, &layers[..1])
+            .map_err(|err| err.to_string());
+        release(layers);
+        self.written += 1;
+        result
+    }
+
+    fn end(mut self) -> ::std::result::Result<usize, String> {
+        for part in &mut self.parts {
+            part.end().map_err(|err| err.to_string())?;
+        }
+        Ok(self.parts.len())
+    }
+}
+
+/// Reads the frames of `sources`, and writes them to the sinks created by
+/// `open` for each part.
+pub fn edit<'a, F>(
+    profile: &Profile,
+    mut sources: Vec<Source>,
+    options: &EditOptions,
+    open: F,
+) -> ::std::result::Result<EditStats, String>
+where
+    F: FnMut(&Part) -> ::std::result::Result<Box<Sink + 'a>, String>,
+{
+    let mut stats = EditStats::default();
+    let mut dedup = options.dedup.map(Dedup::new);
+    let mut splitter = Splitter::new(profile, options.split, open);
+    while let Some((time, root)) = next(&mut sources) {
+        stats.frames += 1;
+        if let Some(dedup) = &mut dedup {
+            if dedup.is_duplicate(time, &root.data()) {
+                stats.duplicates += 1;
+                release(vec![root]);
+                continue;
+            }
+        }
+        splitter.write(time, root)?;
+    }
+    stats.written = splitter.written;
+    stats.parts = splitter.end()?;
+    Ok(stats)
+}
+
+/// Returns the path of a part, e.g. `dump_00002.pcap` for the part 2 of
+/// `dump.pcap`.
+pub fn part_path(path: &str, part: &Part) -> String {
+    let path = Path::new(path);
+    let stem = path
+        .file_stem()
+        .map_or("", |stem| stem.to_str().unwrap_or(""));
+    let name = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{}_{:05}.{}", stem, part.index, ext),
+        None => format!("{}_{:05}", stem, part.index),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use capture::{
+        self, edit, part_path, Dedup, DedupWindow, EditOptions, Part, Sink, Source, SplitPolicy,
+    };
+    use genet_abi::{
+        error::Error, fixed::MutFixed, layer::Layer, result::Result, slice::ByteSlice,
+        token::Token, variant::Variant,
+    };
+    use io::Input;
+    use profile::Profile;
+    use std::{
+        fmt,
+        sync::{Arc, Mutex},
+    };
+    use test_util;
+
+    /// Reads the frames of `(milliseconds, data)`, two at a time.
+    struct TestInput {
+        frames: Vec<(u64, u8)>,
+    }
+
+    impl fmt::Debug for TestInput {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "TestInput")
+        }
+    }
+
+    impl Input for TestInput {
+        fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+            if self.frames.is_empty() {
+                return Err(Box::new(Error::new("end of stream")));
+            }
+            let len = self.frames.len().min(2);
+            Ok(self
+                .frames
+                .drain(..len)
+                .map(|(ms, data)| {
+                    test_util::root()
+                        .data(ByteSlice::from(vec![data]))
+                        .attr("link.timestamp.sec", ms / 1000)
+                        .attr("link.timestamp.nsec", ms % 1000 * 1_000_000)
+                        .build()
+                })
+                .collect())
+        }
+    }
+
+    fn source(frames: &[(u64, u8)]) -> Source {
+        Source::new(Box::new(TestInput {
+            frames: frames.to_vec(),
+        }))
+    }
+
+    type Parts = Arc<Mutex<Vec<Vec<u8>>>>;
+
+    struct TestSink {
+        parts: Parts,
+        index: usize,
+    }
+
+    impl Sink for TestSink {
+        fn write(&mut self, _index: u32, layers: &[MutFixed<Layer>]) -> Result<()> {
+            self.parts.lock().unwrap()[self.index].push(layers[0].data()[0]);
+            Ok(())
+        }
+
+        fn end(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(sources: Vec<Source>, options: EditOptions) -> Vec<Vec<u8>> {
+        let parts = Parts::default();
+        edit(&Profile::new(), sources, &options, |part: &Part| {
+            parts.lock().unwrap().push(Vec::new());
+            Ok(Box::new(TestSink {
+                parts: parts.clone(),
+                index: part.index,
+            }) as Box<Sink>)
+        })
+        .unwrap();
+        let parts = parts.lock().unwrap().clone();
+        parts
+    }
+
+    #[test]
+    fn merge() {
+        let sources = vec![
+            source(&[(0, 1), (20, 3), (30, 4), (50, 6)]),
+            source(&[(10, 2), (30, 5), (60, 7)]),
+        ];
+        assert_eq!(
+            run(sources, EditOptions::default()),
+            vec![vec![1, 2, 3, 4, 5, 6, 7]]
+        );
+    }
+
+    #[test]
+    fn dedup() {
+        let frames = [(0, 1), (1, 1), (2, 2), (3, 1), (2000, 2), (2001, 3)];
+        let options = EditOptions {
+            dedup: Some(DedupWindow::default()),
+            ..EditOptions::default()
+        };
+        assert_eq!(run(vec![source(&frames)], options), vec![vec![1, 2, 3]]);
+
+        let options = EditOptions {
+            dedup: Some(DedupWindow {
+                frames: 0,
+                seconds: Some(1.0),
+            }),
+            ..EditOptions::default()
+        };
+        assert_eq!(run(vec![source(&frames)], options), vec![vec![1, 2, 2, 3]]);
+
+        let mut dedup = Dedup::new(DedupWindow {
+            frames: 1,
+            seconds: None,
+        });
+        assert!(!dedup.is_duplicate(0, b"a"));
+        assert!(!dedup.is_duplicate(0, b"b"));
+        assert!(!dedup.is_duplicate(0, b"a"));
+    }
+
+    #[test]
+    fn split() {
+        let frames = [(0, 1), (500, 2), (1200, 3), (3100, 4), (3200, 5)];
+        let options = EditOptions {
+            split: Some(SplitPolicy::Count(2)),
+            ..EditOptions::default()
+        };
+        assert_eq!(
+            run(vec![source(&frames)], options),
+            vec![vec![1, 2], vec![3, 4], vec![5]]
+        );
+
+        let options = EditOptions {
+            split: Some(SplitPolicy::Interval(1.0)),
+            ..EditOptions::default()
+        };
+        assert_eq!(
+            run(vec![source(&frames)], options),
+            vec![vec![1, 2], vec![3], vec![], vec![4, 5]]
+        );
+
+        // Frames without IP layers are written to a single part.
+        let options = EditOptions {
+            split: Some(SplitPolicy::Flow),
+            ..EditOptions::default()
+        };
+        assert_eq!(
+            run(vec![source(&frames)], options),
+            vec![vec![1, 2, 3, 4, 5]]
+        );
+    }
+
+    #[test]
+    fn paths() {
+        let part = |index| Part { index, flow: None };
+        assert_eq!(
+            part_path("/tmp/dump.pcap", &part(2)),
+            "/tmp/dump_00002.pcap"
+        );
+        assert_eq!(part_path("dump", &part(12)), "dump_00012");
+    }
+
+    #[test]
+    fn raw_frames() {
+        let parts = Parts::default();
+        parts.lock().unwrap().push(Vec::new());
+        let mut sink = TestSink {
+            parts: parts.clone(),
+            index: 0,
+        };
+        let layers = capture::raw_frames(127, vec![vec![3, 0], vec![4]]);
+        assert_eq!(layers[0].id(), Token::from("[link-127]"));
+        let link = layers[0].attr("link.type").unwrap().try_get(&layers[0]);
+        assert_eq!(link.unwrap(), Variant::UInt64(127));
+        let length = layers[0].attr("link.length").unwrap().try_get(&layers[0]);
+        assert_eq!(length.unwrap(), Variant::UInt64(2));
+        assert!(layers[1].attr("link.timestamp.sec").is_some());
+        capture::write_frames(&mut sink, layers).unwrap();
+        assert_eq!(*parts.lock().unwrap(), vec![vec![3, 4]]);
+    }
+}
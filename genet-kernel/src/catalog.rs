@@ -0,0 +1,131 @@
+//! A registry of the layers and attributes seen in decoded frames.
+//!
+//! Attribute classes are defined by the decoders and are not known until
+//! they are used, so the catalog is gathered from the layers of the frames
+//! as they are stored. Frontends use it for filter autocompletion and
+//! column pickers.
+
+use fnv::FnvHashSet;
+use frame::Frame;
+use genet_abi::{layer::Layer, metadata::Metadata, token::Token};
+use parking_lot::RwLock;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// The type of the catalog entries for layers.
+pub const LAYER_TYPE: &str = "@layer";
+
+/// A layer or an attribute.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CatalogEntry {
+    pub id: String,
+    pub typ: String,
+    /// The ID of the layer which has the attribute.
+    pub protocol: String,
+    pub name: String,
+    pub description: String,
+}
+
+impl CatalogEntry {
+    fn new(id: Token, typ: &str, protocol: Token, meta: &Metadata) -> CatalogEntry {
+        CatalogEntry {
+            id: id.to_string(),
+            typ: typ.to_string(),
+            protocol: protocol.to_string(),
+            name: meta.name().to_string(),
+            description: meta.description().to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Entries {
+    seen: FnvHashSet<(Token, Token)>,
+    entries: BTreeMap<String, CatalogEntry>,
+}
+
+impl Entries {
+    fn add(&mut self, layer: &Layer) {
+        let protocol = layer.id();
+        if self.seen.insert((protocol, protocol)) {
+            let entry = CatalogEntry::new(protocol, LAYER_TYPE, protocol, layer.metadata());
+            self.entries.insert(entry.id.clone(), entry);
+        }
+        for attr in layer.attrs_iter() {
+            let id = attr.id();
+            if self.seen.insert((protocol, id)) {
+                let typ = attr.typ().to_string();
+                let entry = CatalogEntry::new(id, &typ, protocol, attr.metadata());
+                self.entries.entry(entry.id.clone()).or_insert(entry);
+            }
+        }
+    }
+}
+
+/// A catalog shared between a store and its lazy decoder.
+#[derive(Clone, Default)]
+pub struct Catalog {
+    entries: Arc<RwLock<Entries>>,
+}
+
+impl Catalog {
+    pub fn new() -> Catalog {
+        Self::default()
+    }
+
+    /// Adds the layers and attributes of `frames` which are not in the
+    /// catalog yet.
+    pub fn update(&self, frames: &[Frame]) {
+        let mut entries = self.entries.write();
+        for frame in frames {
+            for layer in frame.layers() {
+                entries.add(layer);
+            }
+        }
+    }
+
+    /// Returns the entries sorted by ID.
+    pub fn entries(&self) -> Vec<CatalogEntry> {
+        self.entries.read().entries.values().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use catalog::{Catalog, LAYER_TYPE};
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::Fixed,
+    };
+    use test_util;
+
+    #[test]
+    fn update() {
+        let attr = Fixed::new(
+            AttrClass::builder("tcp.dst")
+                .typ("@port")
+                .description("Destination port")
+                .build(),
+        );
+        let layer = test_util::layer("tcp")
+            .name("TCP")
+            .add_attr(Attr::builder(attr).value(80u64).build())
+            .build();
+        let frame = test_util::frame(0, vec![layer]);
+
+        let catalog = Catalog::new();
+        catalog.update(&[frame]);
+        let entries = catalog.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            (entries[0].id.as_str(), entries[0].typ.as_str()),
+            ("tcp", LAYER_TYPE)
+        );
+        assert_eq!(entries[0].name, "TCP");
+        assert_eq!(
+            (entries[1].id.as_str(), entries[1].typ.as_str()),
+            ("tcp.dst", "@port")
+        );
+        assert_eq!(entries[1].protocol, "tcp");
+        assert_eq!(entries[1].description, "Destination port");
+    }
+}
@@ -0,0 +1,139 @@
+//! Coloring rules of the frame list.
+//!
+//! The rules are tested in order and a frame is colored by the first rule it
+//! matches. The kernel caches the index of the matched rule per frame, so a
+//! frontend reads an integer per row instead of running the filters itself.
+
+use columns;
+use fnv::FnvHashMap;
+use genet_filter::Filter;
+use store::Store;
+
+/// The maximum number of cached frames. The whole cache is dropped when it
+/// grows beyond this.
+const CACHE_CAPACITY: usize = 1 << 20;
+
+/// The compiled coloring rules and the matched rule of each frame.
+#[derive(Default)]
+pub struct Coloring {
+    filters: Vec<Filter>,
+    cache: FnvHashMap<u32, Option<u32>>,
+    generation: usize,
+}
+
+impl Coloring {
+    pub fn new() -> Coloring {
+        Coloring::default()
+    }
+
+    /// Replaces the filters of the rules.
+    pub fn set(&mut self, filters: Vec<Filter>) {
+        self.filters = filters;
+        self.cache.clear();
+    }
+
+    /// Returns the index of the first rule matched by each frame in
+    /// `indices`, or None if no rule matches. Unknown frames are skipped.
+    pub fn rules(&mut self, store: &Store, indices: &[u32]) -> Vec<Option<u32>> {
+        if self.filters.is_empty() {
+            let len = store.len() as u32;
+            return indices
+                .iter()
+                .filter(|&&index| index < len)
+                .map(|_| None)
+                .collect();
+        }
+        if self.generation != store.generation() {
+            self.generation = store.generation();
+            self.cache.clear();
+        }
+        let mut missing = indices
+            .iter()
+            .filter(|index| !self.cache.contains_key(index))
+            .cloned()
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+        missing.dedup();
+        if self.cache.len() + missing.len() > CACHE_CAPACITY {
+            self.cache.clear();
+        }
+        for run in columns::runs(&missing) {
+            let start = run[0] as usize;
+            for (&frame, &index) in store.frames(start..start + run.len()).iter().zip(run) {
+                let frame = unsafe { &*frame };
+                let ctx = store.frame_flags().context(frame);
+                let rule = self
+                    .filters
+                    .iter()
+                    .position(|filter| filter.test(&ctx))
+                    .map(|rule| rule as u32);
+                self.cache.insert(index, rule);
+            }
+        }
+        indices
+            .iter()
+            .filter_map(|index| self.cache.get(index).cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use coloring::Coloring;
+    use genet_abi::{fixed::MutFixed, layer::Layer, result::Result};
+    use genet_filter::Filter;
+    use io::Input;
+    use profile::Profile;
+    use std::{thread, time::Duration};
+    use store::{Callback, Store};
+    use test_util;
+
+    #[derive(Clone)]
+    struct TestCallback {}
+    impl Callback for TestCallback {}
+
+    #[derive(Debug)]
+    struct TestInput {
+        len: u64,
+    }
+
+    impl Input for TestInput {
+        fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+            let layers = (0..self.len)
+                .map(|i| test_util::layer("eth").attr("eth.len", i).build())
+                .collect();
+            self.len = 0;
+            Ok(layers)
+        }
+    }
+
+    #[test]
+    fn first_match() {
+        let mut store = Store::new(Profile::new(), TestCallback {});
+        store.set_input(1, TestInput { len: 20 });
+        while store.len() < 20 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut coloring = Coloring::new();
+        assert_eq!(coloring.rules(&store, &[0, 1, 30]), vec![None, None]);
+
+        coloring.set(
+            [
+                "eth.len < 5",
+                "eth.len < 10",
+                "eth.len == 3 || eth.len == 15",
+            ]
+            .iter()
+            .map(|filter| Filter::compile(filter).unwrap())
+            .collect(),
+        );
+        assert_eq!(
+            coloring.rules(&store, &[3, 7, 15, 19, 30]),
+            vec![Some(0), Some(1), Some(2), None]
+        );
+
+        coloring.set(vec![Filter::compile("eth.len >= 15").unwrap()]);
+        assert_eq!(coloring.rules(&store, &[3, 15]), vec![None, Some(0)]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Attribute columns for filters with a columnar plan.
+
+use array_vec::ArrayVec;
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::token::Token;
+use genet_filter::{
+    columns::{Column, Plan},
+    context::Context,
+};
+
+/// Columns of the attributes referenced by the active filters, extended as
+/// decoded frames are stored.
+#[derive(Default)]
+pub struct ColumnStore {
+    enabled: bool,
+    columns: FnvHashMap<Token, Column>,
+}
+
+impl ColumnStore {
+    pub fn new(enabled: bool) -> ColumnStore {
+        ColumnStore {
+            enabled,
+            columns: FnvHashMap::default(),
+        }
+    }
+
+    fn push(column: &mut Column, id: Token, frame: &Frame) {
+        let ctx = Context::with_protocols(frame.layers(), frame.protocols());
+        column.push(&ctx, id);
+    }
+
+    /// Builds the missing columns of `plan` from the stored frames.
+    pub fn register(&mut self, plan: &Plan, frames: &ArrayVec<Frame>) {
+        if !self.enabled {
+            return;
+        }
+        for id in plan.tokens() {
+            self.columns.entry(*id).or_insert_with(|| {
+                let mut column = Column::new();
+                for frame in frames.iter() {
+                    Self::push(&mut column, *id, frame);
+                }
+                column
+            });
+        }
+    }
+
+    /// Drops the columns which are not referenced by `plans`.
+    pub fn retain<'a, I: Iterator<Item = &'a Plan> + Clone>(&mut self, plans: I) {
+        self.columns
+            .retain(|id, _| plans.clone().any(|plan| plan.tokens().contains(id)));
+    }
+
+    /// Appends the values of newly stored frames.
+    pub fn append(&mut self, frames: &[Frame]) {
+        for (id, column) in &mut self.columns {
+            for frame in frames {
+                Self::push(column, *id, frame);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.columns.clear();
+    }
+
+    /// Returns the columns of `plan` in the order of its tokens.
+    pub fn get(&self, plan: &Plan) -> Option<Vec<&Column>> {
+        plan.tokens()
+            .iter()
+            .map(|id| self.columns.get(id))
+            .collect()
+    }
+}
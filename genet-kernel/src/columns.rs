@@ -0,0 +1,468 @@
+//! User-defined columns of the frame list.
+//!
+//! A column is an attribute ID such as `tcp.dst` or an expression of the
+//! filter language such as `len(http.host)`. The values are formatted in the
+//! kernel and cached per frame, so a frontend fetches the rows to render in a
+//! batch instead of querying each frame. The cache is dropped when the
+//! columns change or the frames are decoded again.
+//!
+//! The frame timestamp `link.timestamp` is formatted according to the
+//! timestamp format of the session. Relative and delta timestamps refer to
+//! the first and the previous captured frames regardless of filters, so that
+//! the cached values do not depend on them.
+
+use extract;
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::{timestamp::TimestampFormat, token::Token, variant::Variant};
+use genet_filter::{ast::Expr, variant::VariantExt, Filter};
+use index::Summary;
+use std::cmp::Ordering;
+use store::Store;
+
+const TIMESTAMP_ATTR: &str = "link.timestamp";
+
+/// The maximum number of cached rows. The whole cache is dropped when it
+/// grows beyond this.
+const CACHE_CAPACITY: usize = 1 << 18;
+
+/// How the values of a column are formatted.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnFormat {
+    /// Addresses are formatted according to the type of the attribute.
+    #[default]
+    Auto,
+    /// Integers and byte strings are formatted in hexadecimal.
+    Hex,
+    /// The type of the attribute is ignored.
+    Raw,
+}
+
+/// The definition of a column.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ColumnDef {
+    pub title: String,
+    /// An attribute ID or a filter expression.
+    pub expr: String,
+    #[serde(default)]
+    pub format: ColumnFormat,
+}
+
+/// The formatted values of a frame.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Row {
+    pub index: u32,
+    pub values: Vec<String>,
+}
+
+struct Column {
+    def: ColumnDef,
+    expr: Expr,
+}
+
+impl Column {
+    fn new(def: ColumnDef) -> Result<Column, String> {
+        let filter = Filter::compile(&def.expr).map_err(|err| format!("{}: {}", def.expr, err))?;
+        Ok(Column {
+            expr: filter.expr().clone(),
+            def,
+        })
+    }
+
+    fn eval(&self, store: &Store, timestamp: &TimestampFormat, frame: &Frame) -> Cell {
+        // An attribute is formatted according to its type, e.g. as an IPv4
+        // address.
+        if let Expr::Token(id) = self.expr {
+            if id == Token::from(TIMESTAMP_ATTR) && self.def.format == ColumnFormat::Auto {
+                return self::timestamp(store, timestamp, frame);
+            }
+            if let Some(cell) = self.attr(frame, id) {
+                return cell;
+            }
+        }
+        let ctx = store.frame_flags().context(frame);
+        let key = owned(self.expr.eval(&ctx));
+        Cell {
+            text: format(&key, None, self.def.format),
+            key,
+        }
+    }
+
+    fn attr(&self, frame: &Frame, id: Token) -> Option<Cell> {
+        frame.layers().iter().rev().find_map(|layer| {
+            let attr = layer.attr(id)?;
+            let key = owned(attr.try_get(layer).ok()?);
+            let typ = attr.typ().to_string();
+            let text = match self.def.format {
+                ColumnFormat::Auto => extract::format(attr, key.clone()),
+                format => self::format(&key, Some(&typ), format),
+            };
+            Some(Cell { text, key })
+        })
+    }
+}
+
+/// Formats the timestamp of `frame` according to `format`.
+fn timestamp(store: &Store, format: &TimestampFormat, frame: &Frame) -> Cell {
+    let index = frame.index() as usize;
+    let ts = Summary::new(frame).timestamp();
+    let summary = |range| {
+        store
+            .summaries(range)
+            .first()
+            .map_or(ts, Summary::timestamp)
+    };
+    let first = summary(0..1);
+    let prev = summary(index.saturating_sub(1)..index);
+    Cell {
+        text: format.format(ts, first, prev),
+        key: Variant::Float64(format.value(ts, first, prev)),
+    }
+}
+
+/// Copies a value borrowing the frame data, so that the cached value stays
+/// valid after the frame is evicted.
+pub(crate) fn owned(value: Variant) -> Variant {
+    match value {
+        Variant::Slice(slice) => Variant::Buffer(slice.to_vec().into_boxed_slice()),
+        value => value,
+    }
+}
+
+fn format(value: &Variant, typ: Option<&str>, format: ColumnFormat) -> String {
+    match (value, format) {
+        (Variant::Nil, _) => String::new(),
+        (Variant::UInt64(val), ColumnFormat::Hex) => format!("{:#x}", val),
+        (Variant::Int64(val), ColumnFormat::Hex) if *val >= 0 => format!("{:#x}", val),
+        (Variant::Int64(val), ColumnFormat::Hex) => format!("-{:#x}", val.unsigned_abs()),
+        (Variant::Buffer(data), ColumnFormat::Auto) => {
+            extract::format_bytes(typ.unwrap_or_default(), data)
+        }
+        (Variant::Buffer(data), _) | (Variant::BigInt(data), ColumnFormat::Hex) => {
+            data.iter().map(|b| format!("{:02x}", b)).collect()
+        }
+        (Variant::Bool(val), _) => val.to_string(),
+        (Variant::Int64(val), _) => val.to_string(),
+        (Variant::UInt64(val), _) => val.to_string(),
+        (Variant::Float64(val), _) => val.to_string(),
+        (Variant::String(val), _) => val.to_string(),
+        (value, _) => VariantExt::to_string(value),
+    }
+}
+
+/// Orders the values of a column for sorting. Missing values come first,
+/// followed by booleans, numbers, strings and byte strings.
+pub fn compare(a: &Variant, b: &Variant) -> Ordering {
+    fn rank(value: &Variant) -> u8 {
+        match value {
+            Variant::Nil => 0,
+            Variant::Bool(_) => 1,
+            Variant::Int64(_) | Variant::UInt64(_) | Variant::Float64(_) | Variant::BigInt(_) => 2,
+            Variant::String(_) => 3,
+            Variant::Buffer(_) | Variant::Slice(_) => 4,
+        }
+    }
+    match (a, b) {
+        (Variant::String(a), Variant::String(b)) => a.cmp(b),
+        (Variant::Buffer(a), Variant::Buffer(b)) => a.cmp(b),
+        _ => rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.ord(b).unwrap_or(Ordering::Equal)),
+    }
+}
+
+#[derive(Clone)]
+struct Cell {
+    text: String,
+    key: Variant,
+}
+
+/// The columns of the frame list and the cache of their values.
+#[derive(Default)]
+pub struct Columns {
+    columns: Vec<Column>,
+    timestamp: TimestampFormat,
+    cache: FnvHashMap<u32, Vec<Cell>>,
+    generation: usize,
+}
+
+impl Columns {
+    pub fn new() -> Columns {
+        Columns::default()
+    }
+
+    /// Replaces the columns. Fails without changing the columns if an
+    /// expression is invalid.
+    pub fn set(&mut self, defs: Vec<ColumnDef>) -> Result<(), String> {
+        self.columns = defs
+            .into_iter()
+            .map(Column::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.cache.clear();
+        Ok(())
+    }
+
+    pub fn defs(&self) -> Vec<ColumnDef> {
+        self.columns.iter().map(|c| c.def.clone()).collect()
+    }
+
+    /// Sets the format of the frame timestamps.
+    pub fn set_timestamp_format(&mut self, format: TimestampFormat) {
+        if self.timestamp != format {
+            self.timestamp = format;
+            self.cache.clear();
+        }
+    }
+
+    /// Returns the rows of the frames `indices`. Unknown frames are skipped.
+    pub fn rows(&mut self, store: &Store, indices: &[u32]) -> Vec<Row> {
+        self.fetch(store, indices, |index, cells| Row {
+            index,
+            values: cells.iter().map(|cell| cell.text.clone()).collect(),
+        })
+    }
+
+    /// Returns the values of the column `column` of the frames `indices`
+    /// to sort them with `compare`.
+    pub fn keys(&mut self, store: &Store, column: usize, indices: &[u32]) -> Vec<Variant> {
+        self.fetch(store, indices, |_, cells| {
+            cells
+                .get(column)
+                .map_or(Variant::Nil, |cell| cell.key.clone())
+        })
+    }
+
+    fn fetch<T, F>(&mut self, store: &Store, indices: &[u32], mut f: F) -> Vec<T>
+    where
+        F: FnMut(u32, &[Cell]) -> T,
+    {
+        if self.generation != store.generation() {
+            self.generation = store.generation();
+            self.cache.clear();
+        }
+        let mut missing = indices
+            .iter()
+            .filter(|index| !self.cache.contains_key(index))
+            .cloned()
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+        missing.dedup();
+        if self.cache.len() + missing.len() > CACHE_CAPACITY {
+            self.cache.clear();
+        }
+        for run in runs(&missing) {
+            let start = run[0] as usize;
+            for (&frame, &index) in store.frames(start..start + run.len()).iter().zip(run) {
+                let frame = unsafe { &*frame };
+                let cells = self
+                    .columns
+                    .iter()
+                    .map(|c| c.eval(store, &self.timestamp, frame))
+                    .collect();
+                self.cache.insert(index, cells);
+            }
+        }
+        indices
+            .iter()
+            .filter_map(|index| Some(f(*index, self.cache.get(index)?)))
+            .collect()
+    }
+}
+
+/// Splits sorted indices into runs of consecutive indices.
+pub(crate) fn runs(indices: &[u32]) -> Vec<&[u32]> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for i in 1..=indices.len() {
+        if i == indices.len() || indices[i] != indices[i - 1] + 1 {
+            if start < i {
+                runs.push(&indices[start..i]);
+            }
+            start = i;
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use columns::{compare, runs, ColumnDef, ColumnFormat, Columns};
+    use genet_abi::{
+        fixed::MutFixed,
+        layer::Layer,
+        result::Result,
+        slice::ByteSlice,
+        timestamp::{Reference, Resolution, TimestampFormat, Zone},
+        variant::Variant,
+    };
+    use io::Input;
+    use profile::Profile;
+    use std::{cmp::Ordering, thread, time::Duration};
+    use store::{Callback, Store};
+    use test_util;
+
+    #[derive(Clone)]
+    struct TestCallback {}
+    impl Callback for TestCallback {}
+
+    #[derive(Debug)]
+    struct TestInput {
+        len: u8,
+    }
+
+    impl Input for TestInput {
+        fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+            let layers = (0..self.len)
+                .map(|i| {
+                    let addr = ByteSlice::from(vec![10, 0, 0, i]);
+                    // Every odd frame is half a second late.
+                    let ts = 1_525_176_000 + i64::from(i);
+                    let half = u64::from(i % 2) * 500_000_000;
+                    test_util::layer("ipv4")
+                        .typed_attr("ipv4.src", "@ipv4:addr", addr)
+                        .attr("ipv4.len", u64::from(i) * 10)
+                        .attr("link.timestamp.sec", ts)
+                        .attr("link.timestamp.nsec", half)
+                        .build()
+                })
+                .collect();
+            self.len = 0;
+            Ok(layers)
+        }
+    }
+
+    fn def(expr: &str, format: ColumnFormat) -> ColumnDef {
+        ColumnDef {
+            title: expr.to_string(),
+            expr: expr.to_string(),
+            format,
+        }
+    }
+
+    #[test]
+    fn rows() {
+        let mut store = Store::new(Profile::new(), TestCallback {});
+        store.set_input(1, TestInput { len: 20 });
+        while store.len() < 20 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut columns = Columns::new();
+        columns
+            .set(vec![
+                def("ipv4.src", ColumnFormat::Auto),
+                def("ipv4.src", ColumnFormat::Raw),
+                def("ipv4.len", ColumnFormat::Hex),
+                def("ipv4.len >= 100", ColumnFormat::Auto),
+                def("tcp.dst", ColumnFormat::Auto),
+            ])
+            .unwrap();
+        let rows = columns.rows(&store, &[12, 3, 30]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].index, 12);
+        assert_eq!(
+            rows[0].values,
+            vec!["10.0.0.12", "0a00000c", "0x78", "true", ""]
+        );
+        assert_eq!(
+            rows[1].values,
+            vec!["10.0.0.3", "0a000003", "0x1e", "false", ""]
+        );
+
+        let keys = columns.keys(&store, 2, &[3, 12]);
+        assert_eq!(keys, vec![Variant::UInt64(30), Variant::UInt64(120)]);
+    }
+
+    #[test]
+    fn timestamps() {
+        let mut store = Store::new(Profile::new(), TestCallback {});
+        store.set_input(1, TestInput { len: 20 });
+        while store.len() < 20 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut columns = Columns::new();
+        columns
+            .set(vec![
+                def("link.timestamp", ColumnFormat::Auto),
+                def("link.timestamp", ColumnFormat::Raw),
+            ])
+            .unwrap();
+        let mut format = TimestampFormat {
+            zone: Zone::Utc,
+            reference: Reference::Absolute,
+            resolution: Resolution::Milli,
+        };
+        columns.set_timestamp_format(format);
+        let rows = columns.rows(&store, &[3]);
+        assert_eq!(rows[0].values, vec!["2018-05-01T12:00:03.500Z", ""]);
+
+        format.reference = Reference::Relative;
+        columns.set_timestamp_format(format);
+        let rows = columns.rows(&store, &[0, 3]);
+        assert_eq!(rows[0].values[0], "0.000");
+        assert_eq!(rows[1].values[0], "3.500");
+
+        // The previous frame is the previous captured one.
+        format.reference = Reference::Delta;
+        columns.set_timestamp_format(format);
+        let rows = columns.rows(&store, &[0, 3, 12]);
+        assert_eq!(rows[0].values[0], "0.000");
+        assert_eq!(rows[1].values[0], "1.500");
+        assert_eq!(rows[2].values[0], "0.500");
+
+        let keys = columns.keys(&store, 0, &[3, 12]);
+        assert_eq!(keys, vec![Variant::Float64(1.5), Variant::Float64(0.5)]);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut columns = Columns::new();
+        columns
+            .set(vec![def("tcp.dst", ColumnFormat::Auto)])
+            .unwrap();
+        assert!(columns
+            .set(vec![def("tcp.dst ==", ColumnFormat::Auto)])
+            .is_err());
+        assert_eq!(columns.defs(), vec![def("tcp.dst", ColumnFormat::Auto)]);
+    }
+
+    #[test]
+    fn order() {
+        let values = vec![
+            Variant::String("b".into()),
+            Variant::UInt64(10),
+            Variant::Nil,
+            Variant::Int64(-1),
+            Variant::String("a".into()),
+            Variant::Bool(true),
+        ];
+        let mut sorted = values.clone();
+        sorted.sort_by(compare);
+        assert_eq!(
+            sorted,
+            vec![
+                Variant::Nil,
+                Variant::Bool(true),
+                Variant::Int64(-1),
+                Variant::UInt64(10),
+                Variant::String("a".into()),
+                Variant::String("b".into()),
+            ]
+        );
+        assert_eq!(
+            compare(&Variant::UInt64(3), &Variant::Float64(2.5)),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn split_runs() {
+        assert_eq!(
+            runs(&[1, 2, 3, 5, 7, 8]),
+            vec![&[1, 2, 3][..], &[5][..], &[7, 8][..]]
+        );
+        assert!(runs(&[]).is_empty());
+    }
+}
@@ -0,0 +1,508 @@
+//! Comparison of two captures of the same traffic.
+//!
+//! The frames of a session are aligned with the frames of a capture taken
+//! at another point of the network, e.g. on the other side of a middlebox,
+//! to find the packets dropped, duplicated, reordered or modified in
+//! between.
+//!
+//! A frame is matched with the frame of the other capture carrying the same
+//! network payload, i.e. the payload of the IPv4 or IPv6 layer or the data
+//! of the frame without one, whose timestamp is the closest to its own
+//! shifted by the clock offset, within the window. The frames left are
+//! matched by the IPv4 ID in the same way and reported as modified, e.g.
+//! the packets rewritten by a NAT.
+//!
+//! The results are added to the frames of the session as attributes:
+//!
+//! - `compare.status`: `matched`, `modified`, `reordered` or `dropped`.
+//! - `compare.peer`: the index of the matched frame in the other capture.
+//! - `compare.delay`: the seconds from the frame to the matched frame.
+//! - `compare.duplicates`: the number of extra copies of the frame in the
+//!   other capture.
+
+use analysis::{self, attr};
+use fnv::{FnvHashMap, FnvHasher};
+use frame::Frame;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::Fixed,
+    token::Token,
+};
+use parking_lot::RwLock;
+use std::{fmt, hash::Hasher, sync::Arc};
+
+const STATUS_ATTR: &str = "compare.status";
+
+/// The options of a comparison.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct CompareOptions {
+    /// The maximum seconds between the timestamps of matched frames.
+    pub window: f64,
+    /// The seconds added to the timestamps of the session to get the ones
+    /// of the other capture, e.g. the offset between the clocks.
+    pub offset: f64,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        CompareOptions {
+            window: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+/// The keys of a frame used to align it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Packet {
+    index: u32,
+    time: i128,
+    hash: u64,
+    ip_id: Option<u64>,
+}
+
+impl Packet {
+    pub fn new(frame: &Frame) -> Packet {
+        let layers = frame.layers();
+        let network = layers
+            .iter()
+            .find(|layer| layer.id() == Token::from("ipv4") || layer.id() == Token::from("ipv6"));
+        let mut hasher = FnvHasher::default();
+        match network {
+            Some(layer) => {
+                for payload in layer.payloads() {
+                    hasher.write(&payload.data());
+                }
+            }
+            None => hasher.write(&layers[0].data()),
+        }
+        Packet {
+            index: frame.index(),
+            time: analysis::timestamp(&layers[0]).unwrap_or(0),
+            hash: hasher.finish(),
+            ip_id: network.and_then(|layer| attr(layer, "ipv4.id")),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Status {
+    Matched,
+    Modified,
+    Reordered,
+    Dropped,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Status::Matched => "matched",
+            Status::Modified => "modified",
+            Status::Reordered => "reordered",
+            Status::Dropped => "dropped",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The result of a frame of the session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub status: Status,
+    pub peer: Option<u32>,
+    pub delay: Option<f64>,
+    pub duplicates: u32,
+}
+
+/// The summary of a comparison. The indices of `duplicated` and `added`
+/// are the ones of the other capture.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonReport {
+    pub frames: u32,
+    pub peer_frames: u32,
+    pub matched: u32,
+    pub modified: Vec<u32>,
+    pub reordered: Vec<u32>,
+    pub dropped: Vec<u32>,
+    pub duplicated: Vec<u32>,
+    pub added: Vec<u32>,
+    pub mean_delay: Option<f64>,
+}
+
+/// The frames of a session aligned with the frames of another capture.
+#[derive(Clone, Debug)]
+pub struct Alignment {
+    matches: FnvHashMap<u32, Match>,
+    report: ComparisonReport,
+}
+
+impl Alignment {
+    pub fn new(packets: &[Packet], peers: &[Packet], options: &CompareOptions) -> Alignment {
+        let window = (options.window * 1e9) as i128;
+        let offset = (options.offset * 1e9) as i128;
+        let distance = |a: &Packet, b: &Packet| (b.time - a.time - offset).abs();
+
+        // Finds the closest unused peer with the same key within the window.
+        let closest = |a: &Packet, candidates: Option<&Vec<usize>>, used: &[bool]| {
+            candidates?
+                .iter()
+                .filter(|&&b| !used[b] && distance(a, &peers[b]) <= window)
+                .min_by_key(|&&b| distance(a, &peers[b]))
+                .cloned()
+        };
+
+        let mut by_hash = FnvHashMap::<u64, Vec<usize>>::default();
+        let mut by_id = FnvHashMap::<u64, Vec<usize>>::default();
+        for (b, peer) in peers.iter().enumerate() {
+            by_hash.entry(peer.hash).or_default().push(b);
+            if let Some(id) = peer.ip_id {
+                by_id.entry(id).or_default().push(b);
+            }
+        }
+
+        let mut used = vec![false; peers.len()];
+        let mut pairs: Vec<Option<(usize, Status)>> = packets
+            .iter()
+            .map(|a| {
+                let b = closest(a, by_hash.get(&a.hash), &used)?;
+                used[b] = true;
+                Some((b, Status::Matched))
+            })
+            .collect();
+        for (a, pair) in packets.iter().zip(pairs.iter_mut()) {
+            if pair.is_some() {
+                continue;
+            }
+            if let Some(b) = a.ip_id.and_then(|id| closest(a, by_id.get(&id), &used)) {
+                used[b] = true;
+                *pair = Some((b, Status::Modified));
+            }
+        }
+
+        // The unused peers carrying the payload of a matched frame are
+        // duplicates, and the others are added between the capture points.
+        let mut by_packet_hash = FnvHashMap::<u64, Vec<usize>>::default();
+        for (a, packet) in packets.iter().enumerate() {
+            if pairs[a].is_some() {
+                by_packet_hash.entry(packet.hash).or_default().push(a);
+            }
+        }
+        let mut duplicates = vec![0; packets.len()];
+        let mut report = ComparisonReport {
+            frames: packets.len() as u32,
+            peer_frames: peers.len() as u32,
+            ..ComparisonReport::default()
+        };
+        for (_, peer) in peers.iter().enumerate().filter(|(b, _)| !used[*b]) {
+            let original = by_packet_hash.get(&peer.hash).and_then(|candidates| {
+                candidates
+                    .iter()
+                    .filter(|&&a| distance(&packets[a], peer) <= window)
+                    .min_by_key(|&&a| distance(&packets[a], peer))
+            });
+            match original {
+                Some(&a) => {
+                    duplicates[a] += 1;
+                    report.duplicated.push(peer.index);
+                }
+                None => report.added.push(peer.index),
+            }
+        }
+
+        // The matched frames out of the longest subsequence kept in order
+        // are reordered.
+        let matched = pairs
+            .iter()
+            .enumerate()
+            .filter_map(|(a, pair)| pair.map(|(b, _)| (a, b)))
+            .collect::<Vec<_>>();
+        for i in reordered(&matched.iter().map(|(_, b)| *b).collect::<Vec<_>>()) {
+            if let Some((_, status)) = &mut pairs[matched[i].0] {
+                if *status == Status::Matched {
+                    *status = Status::Reordered;
+                }
+            }
+        }
+
+        let mut matches = FnvHashMap::default();
+        let mut delays = Vec::new();
+        for (a, packet) in packets.iter().enumerate() {
+            let m = match pairs[a] {
+                Some((b, status)) => {
+                    let delay = (peers[b].time - packet.time) as f64 / 1e9;
+                    delays.push(delay);
+                    Match {
+                        status,
+                        peer: Some(peers[b].index),
+                        delay: Some(delay),
+                        duplicates: duplicates[a],
+                    }
+                }
+                None => Match {
+                    status: Status::Dropped,
+                    peer: None,
+                    delay: None,
+                    duplicates: 0,
+                },
+            };
+            match m.status {
+                Status::Matched => report.matched += 1,
+                Status::Modified => report.modified.push(packet.index),
+                Status::Reordered => report.reordered.push(packet.index),
+                Status::Dropped => report.dropped.push(packet.index),
+            }
+            matches.insert(packet.index, m);
+        }
+        if !delays.is_empty() {
+            report.mean_delay = Some(delays.iter().sum::<f64>() / delays.len() as f64);
+        }
+        Alignment { matches, report }
+    }
+
+    pub fn get(&self, index: u32) -> Option<&Match> {
+        self.matches.get(&index)
+    }
+
+    pub fn report(&self) -> &ComparisonReport {
+        &self.report
+    }
+}
+
+/// Returns the positions of `seq` out of a longest increasing subsequence.
+fn reordered(seq: &[usize]) -> Vec<usize> {
+    // The last position of the subsequences of each length, and the
+    // previous position of each position.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![None; seq.len()];
+    for (i, value) in seq.iter().enumerate() {
+        let len = tails
+            .binary_search_by(|&t| seq[t].cmp(value))
+            .unwrap_or_else(|len| len);
+        if len > 0 {
+            prev[i] = Some(tails[len - 1]);
+        }
+        if len == tails.len() {
+            tails.push(i);
+        } else {
+            tails[len] = i;
+        }
+    }
+    let mut kept = vec![false; seq.len()];
+    let mut next = tails.last().cloned();
+    while let Some(i) = next {
+        kept[i] = true;
+        next = prev[i];
+    }
+    (0..seq.len()).filter(|i| !kept[*i]).collect()
+}
+
+struct Classes {
+    status: Fixed<AttrClass>,
+    peer: Fixed<AttrClass>,
+    delay: Fixed<AttrClass>,
+    duplicates: Fixed<AttrClass>,
+}
+
+/// The comparison of a session, shared with the event loop which adds the
+/// results to the frames.
+#[derive(Clone)]
+pub struct Comparison {
+    alignment: Arc<RwLock<Option<Alignment>>>,
+    classes: Arc<Classes>,
+}
+
+impl fmt::Debug for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Comparison")
+    }
+}
+
+impl Default for Comparison {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Comparison {
+    pub fn new() -> Comparison {
+        let class = |id: &str| Fixed::new(AttrClass::builder(id).build());
+        Comparison {
+            alignment: Arc::new(RwLock::new(None)),
+            classes: Arc::new(Classes {
+                status: class(STATUS_ATTR),
+                peer: class("compare.peer"),
+                delay: class("compare.delay"),
+                duplicates: class("compare.duplicates"),
+            }),
+        }
+    }
+
+    /// Replaces the alignment, or removes it if `alignment` is None. The
+    /// frames decoded after this call have the new results.
+    pub fn set(&self, alignment: Option<Alignment>) {
+        *self.alignment.write() = alignment;
+    }
+
+    pub fn report(&self) -> Option<ComparisonReport> {
+        self.alignment
+            .read()
+            .as_ref()
+            .map(|alignment| alignment.report().clone())
+    }
+
+    /// Adds the results of the comparison to `frames`.
+    pub fn update(&self, frames: &mut [Frame]) {
+        let alignment = self.alignment.read();
+        let alignment = match &*alignment {
+            Some(alignment) => alignment,
+            None => return,
+        };
+        for frame in frames {
+            let m = match alignment.get(frame.index()) {
+                Some(m) => m,
+                None => continue,
+            };
+            let top = match frame.layers_mut().last_mut() {
+                Some(top) => top,
+                None => continue,
+            };
+            // A frame can be updated again without its top layer being decoded
+            // again, e.g. if it only has the root layer, which is kept; the
+            // results are added once.
+            if top.attr(STATUS_ATTR).is_some() {
+                continue;
+            }
+            let classes = &self.classes;
+            let status = m.status.to_string().into_boxed_str();
+            top.add_attr(Attr::builder(classes.status.clone()).value(status).build());
+            if let Some(peer) = m.peer {
+                top.add_attr(
+                    Attr::builder(classes.peer.clone())
+                        .value(u64::from(peer))
+                        .build(),
+                );
+            }
+            if let Some(delay) = m.delay {
+                top.add_attr(Attr::builder(classes.delay.clone()).value(delay).build());
+            }
+            if m.duplicates > 0 {
+                top.add_attr(
+                    Attr::builder(classes.duplicates.clone())
+                        .value(u64::from(m.duplicates))
+                        .build(),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use compare::{reordered, Alignment, CompareOptions, Comparison, Packet, Status};
+    use frame::Frame;
+    use genet_abi::{slice::ByteSlice, token::Token, variant::Variant};
+    use test_util;
+
+    fn packet(index: u32, time_ms: i128, hash: u64, ip_id: Option<u64>) -> Packet {
+        Packet {
+            index,
+            time: time_ms * 1_000_000,
+            hash,
+            ip_id,
+        }
+    }
+
+    #[test]
+    fn align() {
+        let packets = vec![
+            packet(0, 0, 10, Some(1)),
+            packet(1, 10, 11, Some(2)),
+            packet(2, 20, 12, Some(3)),
+            packet(3, 30, 13, Some(4)),
+            packet(4, 40, 14, Some(5)),
+            packet(5, 50, 15, None),
+        ];
+        let peers = vec![
+            packet(0, 5, 10, Some(1)),
+            // 1 is dropped, and 3 arrives before 2.
+            packet(1, 31, 13, Some(4)),
+            packet(2, 32, 12, Some(3)),
+            // 4 is rewritten and duplicated.
+            packet(3, 45, 99, Some(5)),
+            packet(4, 51, 15, None),
+            packet(5, 52, 15, None),
+            // 15 is out of the window.
+            packet(6, 5000, 15, None),
+        ];
+        let alignment = Alignment::new(&packets, &peers, &CompareOptions::default());
+        let status = |index| alignment.get(index).unwrap().status;
+        assert_eq!(status(0), Status::Matched);
+        assert_eq!(status(1), Status::Dropped);
+        assert_eq!(status(4), Status::Modified);
+        assert_eq!(status(2), Status::Reordered);
+        assert_eq!(status(3), Status::Matched);
+        assert_eq!(alignment.get(0).unwrap().delay, Some(0.005));
+        assert_eq!(alignment.get(4).unwrap().peer, Some(3));
+        assert_eq!(alignment.get(5).unwrap().duplicates, 1);
+
+        let report = alignment.report();
+        assert_eq!(report.matched, 3);
+        assert_eq!(report.dropped, vec![1]);
+        assert_eq!(report.reordered, vec![2]);
+        assert_eq!(report.modified, vec![4]);
+        assert_eq!(report.duplicated, vec![5]);
+        assert_eq!(report.added, vec![6]);
+
+        // The offset between the clocks moves the window.
+        let options = CompareOptions {
+            window: 0.001,
+            offset: 0.005,
+        };
+        let alignment = Alignment::new(&packets[..1], &peers[..1], &options);
+        assert_eq!(alignment.get(0).unwrap().status, Status::Matched);
+    }
+
+    #[test]
+    fn subsequence() {
+        assert_eq!(reordered(&[0, 1, 2]), Vec::<usize>::new());
+        assert_eq!(reordered(&[0, 2, 1, 3]), vec![1]);
+        assert_eq!(reordered(&[3, 0, 1, 2]), vec![0]);
+    }
+
+    #[test]
+    fn update() {
+        let data = ByteSlice::from(&b"abcd"[..]);
+        let mut frames = (0..2)
+            .map(|index| test_util::frame(index, vec![test_util::root().data(data).build()]))
+            .collect::<Vec<_>>();
+        let packets = frames.iter().map(Packet::new).collect::<Vec<_>>();
+        let comparison = Comparison::new();
+        comparison.set(Some(Alignment::new(
+            &packets,
+            &packets[1..],
+            &CompareOptions::default(),
+        )));
+        comparison.update(&mut frames);
+        comparison.update(&mut frames);
+
+        let value = |frame: &Frame, id: &str| {
+            frame
+                .attr(Token::from(id))
+                .map(|attr| attr.try_get(&frame.layers()[0]).unwrap())
+        };
+        assert_eq!(
+            value(&frames[0], "compare.status"),
+            Some(Variant::String("matched".into()))
+        );
+        assert_eq!(value(&frames[0], "compare.peer"), Some(1u64.into()));
+        assert_eq!(
+            value(&frames[1], "compare.status"),
+            Some(Variant::String("dropped".into()))
+        );
+        assert_eq!(frames[1].layers()[0].attrs().len(), 1);
+        assert_eq!(comparison.report().unwrap().dropped, vec![1]);
+    }
+}
@@ -0,0 +1,344 @@
+//! Conversation tables built while frames are decoded.
+//!
+//! Frames are counted once for each layer of a level, so that e.g. both the
+//! outer and the inner addresses of a tunneled IP packet are counted. The
+//! levels are:
+//!
+//! - `eth`: the MAC addresses of Ethernet layers.
+//! - `ip`: the addresses of IPv4 and IPv6 layers.
+//! - `tcp`, `udp`: the ports and the addresses of the nearest lower layer.
+//!
+//! TCP and UDP conversations are also keyed by `tcp.stream` and
+//! `udp.stream`, so that a 4-tuple reused by a new connection is counted
+//! separately.
+//!
+//! The endpoints of a conversation are sorted, so that `a` is not always the
+//! sender of the first frame. Frames decoded lazily are not counted.
+
+use analysis::{self, attr};
+use extract;
+use frame::Frame;
+use genet_abi::{layer::Layer, token::Token};
+use parking_lot::RwLock;
+use std::{collections::HashMap, fmt, sync::Arc};
+
+/// The conversation levels.
+pub const LEVELS: &[&str] = &["eth", "ip", "tcp", "udp"];
+
+/// Returns the address of `layer` and the type of the address attribute.
+fn address(layer: &Layer, id: &str) -> Option<(Vec<u8>, Token)> {
+    let typ = layer.attr(id)?.typ();
+    Some((attr(layer, id)?, typ))
+}
+
+/// An endpoint of a conversation.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Endpoint {
+    pub address: String,
+    pub port: Option<u64>,
+}
+
+/// The counters of a conversation.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationStats {
+    pub a: Endpoint,
+    pub b: Endpoint,
+    pub frames_ab: u64,
+    pub bytes_ab: u64,
+    pub frames_ba: u64,
+    pub bytes_ba: u64,
+    /// The UNIX time of the first frame in seconds.
+    pub start: f64,
+    /// The seconds between the first and the last frame.
+    pub duration: f64,
+    /// The bits per second from `a` to `b`, or zero if the duration is zero.
+    pub bps_ab: f64,
+    pub bps_ba: f64,
+}
+
+type Key = (Vec<u8>, Option<u64>);
+
+#[derive(PartialEq, Eq, Hash)]
+struct ConversationKey {
+    level: usize,
+    stream: Option<u64>,
+    lower: Key,
+    upper: Key,
+}
+
+struct Entry {
+    lower: Key,
+    upper: Key,
+    typ: Token,
+    frames: [u64; 2],
+    bytes: [u64; 2],
+    first: Option<i128>,
+    last: Option<i128>,
+}
+
+impl Entry {
+    fn stats(&self) -> ConversationStats {
+        let typ = self.typ.to_string();
+        let endpoint = |(addr, port): &Key| Endpoint {
+            address: extract::format_bytes(&typ, addr),
+            port: *port,
+        };
+        let start = self.first.map_or(0.0, |ts| ts as f64 / 1e9);
+        let duration = match (self.first, self.last) {
+            (Some(first), Some(last)) => (last - first) as f64 / 1e9,
+            _ => 0.0,
+        };
+        let bps = |bytes: u64| {
+            if duration > 0.0 {
+                bytes as f64 * 8.0 / duration
+            } else {
+                0.0
+            }
+        };
+        ConversationStats {
+            a: endpoint(&self.lower),
+            b: endpoint(&self.upper),
+            frames_ab: self.frames[0],
+            bytes_ab: self.bytes[0],
+            frames_ba: self.frames[1],
+            bytes_ba: self.bytes[1],
+            start,
+            duration,
+            bps_ab: bps(self.bytes[0]),
+            bps_ba: bps(self.bytes[1]),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tables {
+    keys: HashMap<ConversationKey, usize>,
+    entries: Vec<(usize, Entry)>,
+}
+
+impl Tables {
+    /// Counts a frame of `len` bytes from `src` to `dst`, where `level` is an
+    /// index into `LEVELS`.
+    fn add(
+        &mut self,
+        (level, stream): (usize, Option<u64>),
+        src: Key,
+        dst: Key,
+        typ: Token,
+        len: u64,
+        ts: Option<i128>,
+    ) {
+        let (lower, upper, dir) = if src <= dst {
+            (src, dst, 0)
+        } else {
+            (dst, src, 1)
+        };
+        let key = ConversationKey {
+            level,
+            stream,
+            lower: lower.clone(),
+            upper: upper.clone(),
+        };
+        let entries = &mut self.entries;
+        let index = *self.keys.entry(key).or_insert_with(|| {
+            entries.push((
+                level,
+                Entry {
+                    lower,
+                    upper,
+                    typ,
+                    frames: [0; 2],
+                    bytes: [0; 2],
+                    first: None,
+                    last: None,
+                },
+            ));
+            entries.len() - 1
+        });
+        let entry = &mut self.entries[index].1;
+        entry.frames[dir] += 1;
+        entry.bytes[dir] += len;
+        if let Some(ts) = ts {
+            entry.first = Some(entry.first.map_or(ts, |first| first.min(ts)));
+            entry.last = Some(entry.last.map_or(ts, |last| last.max(ts)));
+        }
+    }
+
+    fn process(&mut self, frame: &Frame) {
+        let layers = frame.layers();
+        let root = match layers.first() {
+            Some(root) => root,
+            None => return,
+        };
+        let len = attr(root, "link.length").unwrap_or(root.data().len() as u64);
+        let ts = analysis::timestamp(root);
+        for (index, layer) in layers.iter().enumerate() {
+            let id = layer.id().to_string();
+            match id.as_str() {
+                "eth" | "ipv4" | "ipv6" => {
+                    let (src, dst) = if id == "eth" {
+                        ("eth.src", "eth.dst")
+                    } else {
+                        ("_.src", "_.dst")
+                    };
+                    if let (Some((src, typ)), Some((dst, _))) =
+                        (address(layer, src), address(layer, dst))
+                    {
+                        let level = if id == "eth" { 0 } else { 1 };
+                        self.add((level, None), (src, None), (dst, None), typ, len, ts);
+                    }
+                }
+                "tcp" | "udp" => {
+                    let ports = (
+                        attr::<u64>(layer, format!("{}.src", id)),
+                        attr::<u64>(layer, format!("{}.dst", id)),
+                    );
+                    let addrs = layers[..index]
+                        .iter()
+                        .rev()
+                        .filter_map(|layer| {
+                            Some((address(layer, "_.src")?, address(layer, "_.dst")?))
+                        })
+                        .next();
+                    if let (Some(src_port), Some(dst_port), Some(((src, typ), (dst, _)))) =
+                        (ports.0, ports.1, addrs)
+                    {
+                        let level = if id == "tcp" { 2 } else { 3 };
+                        let stream = attr::<u64>(layer, format!("{}.stream", id));
+                        let (src, dst) = ((src, Some(src_port)), (dst, Some(dst_port)));
+                        self.add((level, stream), src, dst, typ, len, ts);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Conversation tables shared between a store and its session.
+#[derive(Clone, Default)]
+pub struct Conversations {
+    tables: Arc<RwLock<Tables>>,
+}
+
+impl fmt::Debug for Conversations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Conversations")
+    }
+}
+
+impl Conversations {
+    pub fn new() -> Conversations {
+        Self::default()
+    }
+
+    /// Adds `frames` to the tables.
+    pub fn update(&self, frames: &[Frame]) {
+        let mut tables = self.tables.write();
+        for frame in frames {
+            tables.process(frame);
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut tables = self.tables.write();
+        tables.keys.clear();
+        tables.entries.clear();
+    }
+
+    /// Returns the conversations of `level` in the order they were first
+    /// seen, or None if `level` is unknown.
+    pub fn get(&self, level: &str) -> Option<Vec<ConversationStats>> {
+        let level = LEVELS.iter().position(|l| *l == level)?;
+        Some(
+            self.tables
+                .read()
+                .entries
+                .iter()
+                .filter(|(l, _)| *l == level)
+                .map(|(_, entry)| entry.stats())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use conversation::{Conversations, Endpoint};
+    use frame::Frame;
+    use genet_abi::slice::ByteSlice;
+    use test_util;
+
+    fn frame(
+        sec: i64,
+        src: &'static [u8],
+        dst: &'static [u8],
+        ports: (u64, u64),
+        stream: Option<u64>,
+    ) -> Frame {
+        let root = test_util::root()
+            .attr("link.timestamp.sec", sec)
+            .attr("link.length", 100u64);
+        let ipv4 = test_util::layer("ipv4")
+            .typed_attr("_.src", "@ipv4:addr", ByteSlice::from(src))
+            .typed_attr("_.dst", "@ipv4:addr", ByteSlice::from(dst));
+        let mut tcp = test_util::layer("tcp")
+            .attr("tcp.src", ports.0)
+            .attr("tcp.dst", ports.1);
+        if let Some(stream) = stream {
+            tcp = tcp.attr("tcp.stream", stream);
+        }
+        test_util::frame(0, vec![root.build(), ipv4.build(), tcp.build()])
+    }
+
+    #[test]
+    fn update() {
+        let a: &'static [u8] = &[10, 0, 0, 1];
+        let b: &'static [u8] = &[10, 0, 0, 2];
+        let convs = Conversations::new();
+        convs.update(&[
+            frame(10, b, a, (80, 5000), None),
+            frame(11, a, b, (5000, 80), None),
+            frame(14, b, a, (80, 5000), None),
+            frame(15, a, b, (5001, 80), None),
+        ]);
+
+        let ip = convs.get("ip").unwrap();
+        assert_eq!(ip.len(), 1);
+        assert_eq!(ip[0].a.address, "10.0.0.1");
+        assert_eq!((ip[0].frames_ab, ip[0].frames_ba), (2, 2));
+        assert_eq!((ip[0].start, ip[0].duration), (10.0, 5.0));
+        assert_eq!(ip[0].bps_ab, 200.0 * 8.0 / 5.0);
+
+        let tcp = convs.get("tcp").unwrap();
+        assert_eq!(tcp.len(), 2);
+        assert_eq!(
+            tcp[0].a,
+            Endpoint {
+                address: "10.0.0.1".to_string(),
+                port: Some(5000),
+            }
+        );
+        assert_eq!((tcp[0].bytes_ab, tcp[0].bytes_ba), (100, 200));
+        assert_eq!(tcp[0].duration, 4.0);
+        assert_eq!(tcp[1].duration, 0.0);
+        assert_eq!(tcp[1].bps_ab, 0.0);
+
+        // A reused 4-tuple with a new stream index is a new conversation.
+        let convs = Conversations::new();
+        let frames = (0..3)
+            .map(|stream| frame(10 + stream, a, b, (5000, 80), Some((stream / 2) as u64)))
+            .collect::<Vec<_>>();
+        convs.update(&frames);
+        let tcp = convs.get("tcp").unwrap();
+        assert_eq!(tcp.len(), 2);
+        assert_eq!((tcp[0].frames_ab, tcp[1].frames_ab), (2, 1));
+        assert_eq!(convs.get("ip").unwrap().len(), 1);
+
+        assert!(convs.get("eth").unwrap().is_empty());
+        assert!(convs.get("sctp").is_none());
+        convs.clear();
+        assert!(convs.get("ip").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,401 @@
+use analysis::attr;
+use genet_abi::{fixed::MutFixed, layer::Layer, token::Token};
+use genet_filter::{context::Context, Filter};
+use parking_lot::RwLock;
+use serde_json;
+use std::{collections::BTreeMap, str, sync::Arc};
+
+/// Config key for byte-pattern rules.
+///
+/// Each entry has the form `<layer>:<hex bytes>[@<offset>]=<id>`,
+/// e.g. `tcp:474554=@data:http` or `udp:cafe@4=@data:custom`.
+pub const PATTERNS_KEY: &str = "_.decodeAs.patterns";
+
+/// An `(address, port)` pair.
+pub type Endpoint = (Vec<u8>, u64);
+
+/// The endpoints of a transport layer, matched in both directions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conversation {
+    layer: Token,
+    lower: Endpoint,
+    upper: Endpoint,
+}
+
+impl Conversation {
+    pub fn new<T: Into<Token>>(layer: T, a: Endpoint, b: Endpoint) -> Conversation {
+        let (lower, upper) = if a <= b { (a, b) } else { (b, a) };
+        Conversation {
+            layer: layer.into(),
+            lower,
+            upper,
+        }
+    }
+
+    pub fn layer(&self) -> Token {
+        self.layer
+    }
+
+    /// Returns the endpoints as `(address, port)` pairs in canonical order.
+    pub fn endpoints(&self) -> (&Endpoint, &Endpoint) {
+        (&self.lower, &self.upper)
+    }
+
+    /// Returns the conversation of the top layer of `layers`.
+    ///
+    /// The ports are read from `<layer>.src` and `<layer>.dst`, and the
+    /// addresses from the nearest lower layer which has `_.src` and `_.dst`.
+    pub fn from_layers(layers: &[MutFixed<Layer>]) -> Option<Conversation> {
+        let (top, lower) = layers.split_last()?;
+        let id = top.id().to_string();
+        let src_port = attr::<u64>(top, format!("{}.src", id))?;
+        let dst_port = attr::<u64>(top, format!("{}.dst", id))?;
+        let (src, dst) = lower
+            .iter()
+            .rev()
+            .filter_map(|layer| {
+                Some((
+                    attr::<Vec<u8>>(layer, "_.src")?,
+                    attr::<Vec<u8>>(layer, "_.dst")?,
+                ))
+            })
+            .next()?;
+        Some(Conversation::new(
+            top.id(),
+            (src, src_port),
+            (dst, dst_port),
+        ))
+    }
+
+    fn test(&self, layers: &[MutFixed<Layer>]) -> bool {
+        match layers.last() {
+            Some(top) if top.id() == self.layer => {
+                Conversation::from_layers(layers).as_ref() == Some(self)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Condition {
+    Filter(Filter),
+    Conversation(Conversation),
+}
+
+/// A rule forcing the payload of matching layers to be decoded as `decoder`.
+#[derive(Clone, Debug)]
+pub struct DecodeAs {
+    cond: Condition,
+    decoder: Token,
+}
+
+impl DecodeAs {
+    pub fn new<T: Into<Token>>(filter: Filter, decoder: T) -> DecodeAs {
+        DecodeAs {
+            cond: Condition::Filter(filter),
+            decoder: decoder.into(),
+        }
+    }
+
+    /// Creates a rule applied to the layers of a single conversation.
+    pub fn with_conversation<T: Into<Token>>(conv: Conversation, decoder: T) -> DecodeAs {
+        DecodeAs {
+            cond: Condition::Conversation(conv),
+            decoder: decoder.into(),
+        }
+    }
+
+    pub fn filter(&self) -> Option<&Filter> {
+        match &self.cond {
+            Condition::Filter(filter) => Some(filter),
+            _ => None,
+        }
+    }
+
+    pub fn conversation(&self) -> Option<&Conversation> {
+        match &self.cond {
+            Condition::Conversation(conv) => Some(conv),
+            _ => None,
+        }
+    }
+
+    pub fn decoder(&self) -> Token {
+        self.decoder
+    }
+
+    fn test(&self, layers: &[MutFixed<Layer>]) -> bool {
+        match &self.cond {
+            Condition::Filter(filter) => filter.test(&Context::new(layers)),
+            Condition::Conversation(conv) => conv.test(layers),
+        }
+    }
+}
+
+/// A set of Decode-As rules shared between a session and its decoder pools.
+#[derive(Clone, Default, Debug)]
+pub struct DecodeAsRules {
+    rules: Arc<RwLock<BTreeMap<u32, DecodeAs>>>,
+}
+
+impl DecodeAsRules {
+    pub fn new() -> DecodeAsRules {
+        Self::default()
+    }
+
+    pub fn set(&self, id: u32, rule: Option<DecodeAs>) {
+        let mut rules = self.rules.write();
+        if let Some(rule) = rule {
+            rules.insert(id, rule);
+        } else {
+            rules.remove(&id);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.read().is_empty()
+    }
+
+    /// Returns the rules sorted by ID.
+    pub fn rules(&self) -> Vec<(u32, DecodeAs)> {
+        self.rules
+            .read()
+            .iter()
+            .map(|(id, rule)| (*id, rule.clone()))
+            .collect()
+    }
+
+    /// Applies the rules to `layers[index]`.
+    ///
+    /// Each rule is applied to the first layer at which its condition matches:
+    /// the IDs of the layer's payloads are replaced by the rule's decoder.
+    /// The root layer is left untouched so that rules can be withdrawn.
+    pub fn apply(&self, layers: &mut [MutFixed<Layer>], index: usize) {
+        if index == 0 {
+            return;
+        }
+        let rules = self.rules.read();
+        for rule in rules.values() {
+            if !rule.test(&layers[..=index]) {
+                continue;
+            }
+            if !rule.test(&layers[..index]) {
+                for payload in layers[index].payloads_mut() {
+                    payload.set_id(rule.decoder);
+                }
+            }
+        }
+    }
+}
+
+/// A rule forcing payloads of `layer` starting with `magic` at `offset`
+/// to be decoded as `decoder`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    layer: Token,
+    magic: Vec<u8>,
+    offset: usize,
+    decoder: Token,
+}
+
+impl Pattern {
+    pub fn new<T: Into<Token>, U: Into<Token>>(
+        layer: T,
+        magic: &[u8],
+        offset: usize,
+        decoder: U,
+    ) -> Pattern {
+        Pattern {
+            layer: layer.into(),
+            magic: magic.to_vec(),
+            offset,
+            decoder: decoder.into(),
+        }
+    }
+
+    /// Parses a rule in the `<layer>:<hex bytes>[@<offset>]=<id>` form.
+    pub fn parse(rule: &str) -> Option<Pattern> {
+        let mut rule = rule.splitn(2, '=');
+        let cond = rule.next()?.trim();
+        let decoder = rule.next()?.trim();
+        let mut cond = cond.splitn(2, ':');
+        let layer = cond.next()?.trim();
+        let mut magic = cond.next()?.splitn(2, '@');
+        let hex = magic.next()?.trim();
+        let offset = match magic.next() {
+            Some(offset) => offset.trim().parse().ok()?,
+            None => 0,
+        };
+        let hex = hex.trim_start_matches("0x").replace(' ', "");
+        if layer.is_empty()
+            || decoder.is_empty()
+            || hex.is_empty()
+            || hex.len() % 2 != 0
+            || !hex.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            return None;
+        }
+        let magic = hex
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(str::from_utf8(pair).ok()?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Some(Pattern::new(layer, &magic, offset, decoder))
+    }
+
+    fn matches(&self, data: &[u8]) -> bool {
+        match data.get(self.offset..) {
+            Some(data) => data.starts_with(&self.magic),
+            None => false,
+        }
+    }
+}
+
+/// A set of byte-pattern rules read from the profile.
+#[derive(Clone, Default, Debug)]
+pub struct Patterns {
+    rules: Arc<Vec<Pattern>>,
+}
+
+impl Patterns {
+    pub fn new() -> Patterns {
+        Self::default()
+    }
+
+    /// Creates a new Patterns from the JSON-encoded value of `_.decodeAs.patterns`.
+    pub fn from_config(value: &str) -> Patterns {
+        let entries: Vec<String> = serde_json::from_str(value).unwrap_or_default();
+        Patterns {
+            rules: Arc::new(entries.iter().filter_map(|e| Pattern::parse(e)).collect()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Applies the rules to the payloads of `layer`.
+    ///
+    /// The first matching rule wins for each payload.
+    pub fn apply(&self, layer: &mut Layer) {
+        let id = layer.id();
+        for payload in layer.payloads_mut() {
+            let data = payload.data();
+            if let Some(rule) = self
+                .rules
+                .iter()
+                .find(|rule| rule.layer == id && rule.matches(&data))
+            {
+                payload.set_id(rule.decoder);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use decode_as::{Conversation, DecodeAs, DecodeAsRules, Pattern, Patterns};
+    use genet_abi::{
+        fixed::MutFixed,
+        layer::{Layer, Payload},
+        slice::ByteSlice,
+        token::Token,
+    };
+    use genet_filter::Filter;
+    use test_util;
+
+    #[test]
+    fn apply() {
+        let mut layers = vec![
+            test_util::root().build(),
+            test_util::layer("tcp")
+                .payload(Payload::new(ByteSlice::new(), "@data:tcp"))
+                .build(),
+        ];
+
+        let rules = DecodeAsRules::new();
+        rules.set(
+            1,
+            Some(DecodeAs::new(Filter::compile("tcp").unwrap(), "@data:http")),
+        );
+        rules.apply(&mut layers, 1);
+        assert_eq!(layers[1].payloads()[0].id(), Token::from("@data:http"));
+
+        rules.set(1, None);
+        assert!(rules.is_empty());
+    }
+
+    fn layers(src: u8, dst: u8, sport: u64, dport: u64) -> Vec<MutFixed<Layer>> {
+        let addr = |addr| vec![10, 0, 0, addr].into_boxed_slice();
+        vec![
+            test_util::root().build(),
+            test_util::layer("ipv4")
+                .attr("_.src", addr(src))
+                .attr("_.dst", addr(dst))
+                .build(),
+            test_util::layer("tcp")
+                .attr("tcp.src", sport)
+                .attr("tcp.dst", dport)
+                .payload(Payload::new(ByteSlice::new(), "@data:tcp"))
+                .build(),
+        ]
+    }
+
+    #[test]
+    fn apply_conversation() {
+        let conv = Conversation::from_layers(&layers(1, 2, 50000, 9000)).unwrap();
+        assert_eq!(
+            conv,
+            Conversation::new("tcp", (vec![10, 0, 0, 2], 9000), (vec![10, 0, 0, 1], 50000))
+        );
+        let rules = DecodeAsRules::new();
+        rules.set(1, Some(DecodeAs::with_conversation(conv, "@data:http")));
+
+        let cases = [
+            ((1, 2, 50000, 9000), true),
+            ((2, 1, 9000, 50000), true),
+            ((1, 2, 50001, 9000), false),
+            ((3, 2, 50000, 9000), false),
+        ];
+        for ((src, dst, sport, dport), matched) in cases.iter() {
+            let mut layers = layers(*src, *dst, *sport, *dport);
+            rules.apply(&mut layers, 1);
+            rules.apply(&mut layers, 2);
+            let id = if *matched { "@data:http" } else { "@data:tcp" };
+            assert_eq!(layers[2].payloads()[0].id(), Token::from(id));
+        }
+    }
+
+    #[test]
+    fn parse_pattern() {
+        assert_eq!(
+            Pattern::parse("tcp:474554=@data:http"),
+            Some(Pattern::new("tcp", b"GET", 0, "@data:http"))
+        );
+        assert_eq!(
+            Pattern::parse(" udp : 0xca fe @4 = @data:custom"),
+            Some(Pattern::new("udp", &[0xca, 0xfe], 4, "@data:custom"))
+        );
+        assert_eq!(Pattern::parse("tcp:474=@data:http"), None);
+        assert_eq!(Pattern::parse("tcp:zz=@data:http"), None);
+        assert_eq!(Pattern::parse("tcp:4745"), None);
+        assert_eq!(Pattern::parse("tcp:aéb=@data:http"), None);
+        assert_eq!(Pattern::parse("tcp:+1=@data:http"), None);
+    }
+
+    #[test]
+    fn apply_patterns() {
+        let mut layer = test_util::layer("udp")
+            .payload(Payload::new(
+                ByteSlice::from(&b"\x00\x00\xca\xfe"[..]),
+                "@data:udp",
+            ))
+            .payload(Payload::new(ByteSlice::from(&b"\xca\xfe"[..]), "@data:udp"))
+            .build();
+
+        let patterns = Patterns::from_config(r#"["udp:cafe@2=@data:custom", "tcp:cafe=@x"]"#);
+        patterns.apply(&mut layer);
+        assert_eq!(layer.payloads()[0].id(), Token::from("@data:custom"));
+        assert_eq!(layer.payloads()[1].id(), Token::from("@data:udp"));
+    }
+}
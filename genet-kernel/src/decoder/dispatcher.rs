@@ -1,3 +1,5 @@
+use decode_as::{DecodeAsRules, Patterns};
+use flags::FrameFlags;
 use frame::Frame;
 use genet_abi::{
     context::Context,
@@ -5,10 +7,15 @@ use genet_abi::{
     fixed::MutFixed,
     layer::{Layer, Parent},
 };
+use link::LinkMap;
 use profile::Profile;
 
 pub struct Dispatcher {
     runners: Vec<Runner>,
+    decode_as: DecodeAsRules,
+    link_map: LinkMap,
+    patterns: Patterns,
+    flags: FrameFlags,
 }
 
 impl Dispatcher {
@@ -17,21 +24,32 @@ impl Dispatcher {
             .decoders()
             .map(|d| Runner::new(typ, profile.context(), *d))
             .collect();
-        Dispatcher { runners }
-    }
-
-    pub fn runners(&mut self) -> Vec<OnceRunner> {
-        self.runners
-            .iter_mut()
-            .map(|r| OnceRunner::new(r))
-            .collect()
+        Dispatcher {
+            runners,
+            decode_as: profile.decode_as().clone(),
+            link_map: profile.link_map().clone(),
+            patterns: profile.patterns().clone(),
+            flags: profile.frame_flags().clone(),
+        }
     }
 
+    /// Decodes a frame. In the serial stage, the layers added by serial
+    /// decoders are also passed to the parallel decoders, so that e.g. a
+    /// decrypted payload is decoded like a plain one.
+    ///
+    /// Ignored frames are left with their root layer.
     pub fn process_frame(&mut self, frame: &mut Frame) {
+        if self.flags.is_ignored(frame.index()) {
+            return;
+        }
         let mut indices = frame.fetch_tree_indices();
         let mut layers = frame.fetch_layers();
+        let decoded = layers.len();
         let mut offset = 0;
-        let mut runners = self.runners();
+        let decode_as = self.decode_as.clone();
+        let link_map = self.link_map.clone();
+        let patterns = self.patterns.clone();
+        let mut scopes = Scopes::new(self.runners.len(), &layers, &indices);
         loop {
             let len = layers.len() - offset;
             for index in offset..layers.len() {
@@ -40,14 +58,29 @@ impl Dispatcher {
                         continue;
                     }
                 }
+                if index == 0 {
+                    if let Some(layer) = link_map.layer(&layers[0]) {
+                        layers.push(layer);
+                        indices.push(1);
+                        scopes.assign(1, 1, 0);
+                        continue;
+                    }
+                }
+                patterns.apply(&mut layers[index]);
+                decode_as.apply(&mut layers, index);
+                let scope = scopes.enter(&layers[index], index);
                 let mut children = 0;
                 loop {
                     let mut executed = 0;
-                    for mut r in &mut runners.iter_mut() {
+                    for (id, r) in self.runners.iter_mut().enumerate() {
+                        if scopes.is_used(scope, id) {
+                            continue;
+                        }
                         let mut layer =
                             Parent::from_mut_ref(unsafe { &mut *layers[index].as_mut_ptr() });
-                        let done = r.execute(&layers, &mut layer);
+                        let done = r.execute(&layers, &mut layer, index >= decoded);
                         if done {
+                            scopes.set_used(scope, id);
                             executed += 1;
                         }
                         let mut results: Vec<MutFixed<Layer>> = layer
@@ -56,13 +89,19 @@ impl Dispatcher {
                             .map(|v| unsafe { MutFixed::from_ptr(*v) })
                             .collect();
                         children += results.len();
+                        scopes.assign(layers.len(), results.len(), scope);
                         layers.append(&mut results);
                     }
                     if executed == 0 {
                         break;
                     }
                 }
-                indices.push(children as u8);
+                // Leaves of the parallel stage already have an entry.
+                if index < indices.len() {
+                    indices[index] = children as u8;
+                } else {
+                    indices.push(children as u8);
+                }
             }
 
             offset += len;
@@ -76,28 +115,103 @@ impl Dispatcher {
     }
 }
 
+/// The maximum depth of the embedded payloads dissected from scratch.
+const MAX_EMBEDDED_DEPTH: usize = 4;
+
+/// The scopes of the decoders in a frame.
+///
+/// Each decoder runs at most once in a scope. A layer with an embedded
+/// payload opens a new scope for its children, so that e.g. the datagram
+/// quoted by an ICMP error is decoded by the IPv4 decoder again.
+struct Scopes {
+    runners: usize,
+    /// The scope of each layer.
+    layers: Vec<usize>,
+    /// The decoders used in each scope.
+    used: Vec<Vec<bool>>,
+    depths: Vec<usize>,
+}
+
+impl Scopes {
+    /// Creates the scopes of the layers decoded in the previous stage,
+    /// whose children are counted by `indices`.
+    fn new(runners: usize, layers: &[MutFixed<Layer>], indices: &[u8]) -> Scopes {
+        let mut scopes = Scopes {
+            runners,
+            layers: vec![0],
+            used: vec![vec![false; runners]],
+            depths: vec![0],
+        };
+        let mut next = 1;
+        for (index, children) in indices.iter().enumerate() {
+            if *children == 0 || index >= layers.len() {
+                continue;
+            }
+            let scope = scopes.enter(&layers[index], index);
+            let children = usize::from(*children).min(layers.len().saturating_sub(next));
+            scopes.assign(next, children, scope);
+            next += children;
+        }
+        scopes
+    }
+
+    /// Returns the scope in which `layer` at `index` is decoded.
+    fn enter(&mut self, layer: &Layer, index: usize) -> usize {
+        let scope = self.layers.get(index).cloned().unwrap_or(0);
+        let embedded = layer.payloads().iter().any(|payload| payload.is_embedded());
+        if !embedded || self.depths[scope] >= MAX_EMBEDDED_DEPTH {
+            return scope;
+        }
+        self.used.push(vec![false; self.runners]);
+        self.depths.push(self.depths[scope] + 1);
+        self.used.len() - 1
+    }
+
+    /// Puts the `len` layers from `start` in `scope`.
+    fn assign(&mut self, start: usize, len: usize, scope: usize) {
+        self.layers.resize(start, 0);
+        self.layers.extend((0..len).map(|_| scope));
+    }
+
+    fn is_used(&self, scope: usize, runner: usize) -> bool {
+        self.used[scope][runner]
+    }
+
+    fn set_used(&mut self, scope: usize, runner: usize) {
+        self.used[scope][runner] = true;
+    }
+}
+
 struct Runner {
     ctx: Context,
     typ: ExecType,
     decoder: DecoderBox,
     metadata: Metadata,
     worker: Option<WorkerBox>,
+    /// A parallel decoder running in the serial stage.
+    nested: bool,
 }
 
 impl Runner {
     fn new(typ: &ExecType, ctx: Context, decoder: DecoderBox) -> Runner {
+        let metadata = decoder.metadata();
+        let nested = *typ == ExecType::SerialSync && metadata.exec_type == ExecType::ParallelSync;
         let mut runner = Runner {
             ctx,
             typ: typ.clone(),
             decoder,
-            metadata: decoder.metadata(),
+            metadata,
             worker: None,
+            nested,
         };
         runner.reset();
         runner
     }
 
-    fn execute(&mut self, layers: &[MutFixed<Layer>], layer: &mut Parent) -> bool {
+    fn execute(&mut self, layers: &[MutFixed<Layer>], layer: &mut Parent, fresh: bool) -> bool {
+        if self.nested && !fresh {
+            return false;
+        }
         if let Some(worker) = &mut self.worker {
             match worker.decode(&mut self.ctx, layers, layer) {
                 Ok(done) => done,
@@ -109,7 +223,7 @@ impl Runner {
     }
 
     fn reset(&mut self) {
-        self.worker = if self.metadata.exec_type == self.typ {
+        self.worker = if self.metadata.exec_type == self.typ || self.nested {
             Some(self.decoder.new_worker(&self.ctx))
         } else {
             None
@@ -117,28 +231,129 @@ impl Runner {
     }
 }
 
-pub struct OnceRunner<'a> {
-    runner: &'a mut Runner,
-    used: bool,
-}
+#[cfg(test)]
+mod tests {
+    use decoder::dispatcher::{Dispatcher, MAX_EMBEDDED_DEPTH};
+    use genet_abi::{
+        context::Context,
+        decoder::{Decoder, DecoderBox, ExecType, Metadata, Status, Worker},
+        fixed::Fixed,
+        layer::{Layer, LayerClass, LayerStack, Parent, Payload},
+        result::Result,
+        slice::ByteSlice,
+        token::Token,
+    };
+    use profile::Profile;
+    use test_util;
+
+    /// Adds a `child` layer to `parent` layers, or to the layers with a
+    /// `parent` payload. The child has an `embedded` payload if any.
+    #[derive(Clone)]
+    struct ChildDecoder {
+        parent: &'static str,
+        child: &'static str,
+        exec_type: ExecType,
+        embedded: Option<&'static str>,
+    }
+
+    impl Decoder for ChildDecoder {
+        fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+            Box::new(ChildWorker {
+                parent: Token::from(self.parent),
+                class: Fixed::new(LayerClass::builder(self.child).build()),
+                embedded: self.embedded.map(Token::from),
+            })
+        }
 
-impl<'a> OnceRunner<'a> {
-    fn new(runner: &'a mut Runner) -> OnceRunner {
-        OnceRunner {
-            runner,
-            used: false,
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                exec_type: self.exec_type.clone(),
+                ..Metadata::default()
+            }
         }
     }
 
-    fn execute(&mut self, layers: &[MutFixed<Layer>], layer: &mut Parent) -> bool {
-        if !self.used {
-            let done = self.runner.execute(layers, layer);
-            if done {
-                self.used = true;
+    struct ChildWorker {
+        parent: Token,
+        class: Fixed<LayerClass>,
+        embedded: Option<Token>,
+    }
+
+    impl Worker for ChildWorker {
+        fn decode(
+            &mut self,
+            _ctx: &mut Context,
+            _stack: &LayerStack,
+            parent: &mut Parent,
+        ) -> Result<Status> {
+            let payload = parent.payloads().iter().any(|p| p.id() == self.parent);
+            if parent.id() != self.parent && !payload {
+                return Ok(Status::Skip);
             }
-            done
-        } else {
-            false
+            let mut layer = Layer::new(self.class.clone(), ByteSlice::new());
+            if let Some(id) = self.embedded {
+                layer.add_payload(Payload::embedded(ByteSlice::new(), id));
+            }
+            parent.add_child(layer);
+            Ok(Status::Done)
+        }
+    }
+
+    #[test]
+    fn nested_parallel_decoders() {
+        let mut profile = Profile::new();
+        let decoders = [
+            ("[link-1]", "outer", ExecType::ParallelSync),
+            ("outer", "inner", ExecType::SerialSync),
+            ("inner", "leaf", ExecType::ParallelSync),
+        ];
+        for (parent, child, exec_type) in decoders.iter() {
+            profile.add_decoder(DecoderBox::new(ChildDecoder {
+                parent,
+                child,
+                exec_type: exec_type.clone(),
+                embedded: None,
+            }));
         }
+
+        let mut frame = test_util::frame(0, vec![test_util::root().build()]);
+        Dispatcher::new(&ExecType::ParallelSync, &profile).process_frame(&mut frame);
+        assert_eq!(frame.layers().len(), 2);
+        Dispatcher::new(&ExecType::SerialSync, &profile).process_frame(&mut frame);
+        let ids: Vec<Token> = frame.layers().iter().map(|layer| layer.id()).collect();
+        let expected: Vec<Token> = ["[link-1]", "outer", "inner", "leaf"]
+            .iter()
+            .map(|id| Token::from(*id))
+            .collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn embedded_payloads() {
+        // An "icmp" layer quotes a frame, which has an "icmp" layer again.
+        let mut profile = Profile::new();
+        profile.add_decoder(DecoderBox::new(ChildDecoder {
+            parent: "[link-1]",
+            child: "ip",
+            exec_type: ExecType::ParallelSync,
+            embedded: None,
+        }));
+        profile.add_decoder(DecoderBox::new(ChildDecoder {
+            parent: "ip",
+            child: "icmp",
+            exec_type: ExecType::ParallelSync,
+            embedded: Some("[link-1]"),
+        }));
+
+        let mut frame = test_util::frame(0, vec![test_util::root().build()]);
+        Dispatcher::new(&ExecType::ParallelSync, &profile).process_frame(&mut frame);
+        let ids: Vec<Token> = frame.layers().iter().map(|layer| layer.id()).collect();
+        assert_eq!(ids.len(), 1 + 2 * (MAX_EMBEDDED_DEPTH + 1));
+        assert_eq!(ids[3], Token::from("ip"));
+        assert_eq!(ids[4], Token::from("icmp"));
+
+        // The scopes are restored in the serial stage.
+        Dispatcher::new(&ExecType::SerialSync, &profile).process_frame(&mut frame);
+        assert_eq!(frame.layers().len(), ids.len());
     }
 }
@@ -0,0 +1,52 @@
+use stats::{PipelineStats, StageStats};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// Counters of a pipeline stage, shared by its threads.
+#[derive(Default, Debug)]
+pub struct Stage {
+    threads: AtomicUsize,
+    frames: AtomicUsize,
+    batches: AtomicUsize,
+    busy_micros: AtomicUsize,
+}
+
+impl Stage {
+    pub fn set_threads(&self, threads: usize) {
+        self.threads.store(threads, Ordering::Relaxed);
+    }
+
+    pub fn record(&self, frames: usize, elapsed: Duration) {
+        let micros = elapsed.as_secs() * 1_000_000 + u64::from(elapsed.subsec_micros());
+        self.frames.fetch_add(frames, Ordering::Relaxed);
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        self.busy_micros
+            .fetch_add(micros as usize, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> StageStats {
+        StageStats {
+            threads: self.threads.load(Ordering::Relaxed),
+            frames: self.frames.load(Ordering::Relaxed),
+            batches: self.batches.load(Ordering::Relaxed),
+            busy_micros: self.busy_micros.load(Ordering::Relaxed) as u64,
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct Metrics {
+    pub parallel: Stage,
+    pub serial: Stage,
+}
+
+impl Metrics {
+    pub fn stats(&self) -> PipelineStats {
+        PipelineStats {
+            parallel: self.parallel.stats(),
+            serial: self.serial.stats(),
+        }
+    }
+}
@@ -1,3 +1,21 @@
-mod dispatcher;
+//! The decoder pipeline.
+//!
+//! Frames are numbered in the order they are read, and the attributes
+//! visible in a frame are the same for any number of threads:
+//!
+//! - Parallel workers decode batches of frames on any thread and in any
+//!   order. A parallel worker may be reused for frames of different
+//!   batches, so it must not keep state affecting the output between frames.
+//! - Serial workers receive the frames in index order. With more than one
+//!   flow shard, a worker only receives the frames of the flows assigned to
+//!   its shard, so the state affecting the output must be kept per flow.
+//!   A counter shared by all flows, such as a stream index, may differ.
+//! - The results are merged back into index order before frames are stored,
+//!   and attributes relating frames to each other, such as `tcp.stream`,
+//!   relative sequence numbers and `tcp.analysis.acks_frame`, are computed
+//!   in that order by the `analysis` module.
+
+pub mod dispatcher;
+pub mod metrics;
 pub mod parallel;
 pub mod serial;
@@ -1,9 +1,17 @@
 use crossbeam_channel;
-use decoder::dispatcher::Dispatcher;
+use decoder::{dispatcher::Dispatcher, metrics::Metrics};
 use frame::Frame;
 use genet_abi::decoder::ExecType;
 use profile::Profile;
-use std::thread::{self, JoinHandle};
+use std::{
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+/// Batches are split into chunks of this size, so that idle workers pick up
+/// the rest of a large batch instead of waiting for a single worker.
+const CHUNK_SIZE: usize = 256;
 
 pub trait Callback: Sync + Send + Clone {
     fn done(&self, result: Vec<Frame>);
@@ -15,12 +23,22 @@ pub struct Pool {
 }
 
 impl Pool {
-    pub fn new<C: 'static + Callback>(profile: &Profile, callback: &C) -> Pool {
+    pub fn new<C: 'static + Callback>(
+        profile: &Profile,
+        callback: &C,
+        metrics: &Arc<Metrics>,
+    ) -> Pool {
         let (send, recv) = crossbeam_channel::unbounded::<Option<Vec<Frame>>>();
         let mut handles = Vec::new();
         for _ in 0..profile.concurrency() {
-            handles.push(Self::spawn(profile.clone(), callback.clone(), recv.clone()));
+            handles.push(Self::spawn(
+                profile.clone(),
+                callback.clone(),
+                recv.clone(),
+                metrics.clone(),
+            ));
         }
+        metrics.parallel.set_threads(handles.len());
         Pool {
             sender: send,
             handles,
@@ -31,15 +49,18 @@ impl Pool {
         profile: Profile,
         callback: C,
         recv: crossbeam_channel::Receiver<Option<Vec<Frame>>>,
+        metrics: Arc<Metrics>,
     ) -> JoinHandle<()> {
         thread::spawn(move || {
             let mut disp = Dispatcher::new(&ExecType::ParallelSync, &profile);
             loop {
                 if let Some(frames) = recv.recv() {
                     if let Some(mut frames) = frames {
+                        let start = Instant::now();
                         for mut f in &mut frames {
                             disp.process_frame(f);
                         }
+                        metrics.parallel.record(frames.len(), start.elapsed());
                         callback.done(frames);
                     } else {
                         return;
@@ -52,6 +73,12 @@ impl Pool {
     }
 
     pub fn process(&mut self, frames: Vec<Frame>) {
+        let mut frames = frames;
+        while frames.len() > CHUNK_SIZE {
+            let rest = frames.split_off(CHUNK_SIZE);
+            self.sender.send(Some(frames));
+            frames = rest;
+        }
         self.sender.send(Some(frames));
     }
 }
@@ -1,54 +1,92 @@
 use crossbeam_channel;
-use decoder::dispatcher::Dispatcher;
+use decoder::{dispatcher::Dispatcher, metrics::Metrics};
+use fnv::FnvHasher;
 use frame::Frame;
-use genet_abi::decoder::ExecType;
+use genet_abi::{decoder::ExecType, token::Token, variant::Variant};
 use profile::Profile;
 use std::{
     collections::BTreeMap,
+    hash::{Hash, Hasher},
+    sync::Arc,
     thread::{self, JoinHandle},
+    time::Instant,
 };
 
 pub trait Callback: Sync + Send {
     fn done(&self, result: Vec<Frame>);
 }
 
+type Part = (usize, Vec<(usize, Frame)>);
+
+/// Runs the serial decoders in frame order.
+///
+/// With more than one shard, frames are partitioned by flow and each shard
+/// runs its own set of serial workers, so that the state of a flow is kept
+/// by a single worker. The results are merged back into frame order.
 pub struct Pool {
     sender: crossbeam_channel::Sender<Option<Vec<Frame>>>,
     handles: Vec<JoinHandle<()>>,
 }
 
 impl Pool {
-    pub fn new<C: 'static + Callback>(profile: Profile, callback: C) -> Pool {
-        let callback = Box::new(callback);
+    /// Creates a new Pool decoding the frames from the index `start`.
+    pub fn new<C: 'static + Callback>(
+        profile: Profile,
+        callback: C,
+        metrics: &Arc<Metrics>,
+        start: usize,
+    ) -> Pool {
+        let shards = profile.serial_concurrency().max(1) as usize;
         let (send, recv) = crossbeam_channel::unbounded::<Option<Vec<Frame>>>();
         let mut handles = Vec::new();
+        metrics.serial.set_threads(shards);
 
-        let handle = thread::spawn(move || {
-            let mut disp = Dispatcher::new(&ExecType::SerialSync, &profile);
-            let mut map = BTreeMap::new();
-            let mut next = 0;
-            loop {
-                if let Some(frames) = recv.recv() {
-                    if let Some(frames) = frames {
-                        if !frames.is_empty() {
-                            map.insert(frames[0].index() as usize, frames);
-                        }
-                        while let Some(mut frames) = map.remove(&next) {
-                            next = frames.last().unwrap().index() as usize + 1;
-                            for frame in &mut frames {
-                                disp.process_frame(frame);
-                            }
-                            callback.done(frames);
-                        }
-                    } else {
-                        return;
+        if shards == 1 {
+            let metrics = metrics.clone();
+            handles.push(thread::spawn(move || {
+                let mut disp = Dispatcher::new(&ExecType::SerialSync, &profile);
+                Self::sequence(&recv, start, |frames| {
+                    let mut frames = frames;
+                    let start = Instant::now();
+                    for frame in &mut frames {
+                        disp.process_frame(frame);
                     }
-                } else {
-                    return;
-                }
-            }
-        });
-        handles.push(handle);
+                    metrics.serial.record(frames.len(), start.elapsed());
+                    callback.done(frames);
+                });
+            }));
+        } else {
+            let (merge_send, merge_recv) = crossbeam_channel::unbounded::<Part>();
+            let senders = (0..shards)
+                .map(|_| {
+                    let (send, recv) = crossbeam_channel::unbounded::<Part>();
+                    handles.push(Self::spawn_shard(
+                        profile.clone(),
+                        recv,
+                        merge_send.clone(),
+                        metrics.clone(),
+                    ));
+                    send
+                })
+                .collect::<Vec<_>>();
+            handles.push(Self::spawn_merger(shards, merge_recv, callback));
+            handles.push(thread::spawn(move || {
+                let src = Token::from("_.src");
+                let dst = Token::from("_.dst");
+                let mut seq = 0;
+                Self::sequence(&recv, start, |frames| {
+                    let mut parts = (0..shards).map(|_| Vec::new()).collect::<Vec<_>>();
+                    for (pos, frame) in frames.into_iter().enumerate() {
+                        let shard = flow_hash(&frame, src, dst) as usize % shards;
+                        parts[shard].push((pos, frame));
+                    }
+                    for (sender, part) in senders.iter().zip(parts) {
+                        sender.send((seq, part));
+                    }
+                    seq += 1;
+                });
+            }));
+        }
 
         Pool {
             sender: send,
@@ -56,6 +94,69 @@ impl Pool {
         }
     }
 
+    /// Passes the batches from `recv` to `f` in frame order until the pool
+    /// is closed.
+    fn sequence<F: FnMut(Vec<Frame>)>(
+        recv: &crossbeam_channel::Receiver<Option<Vec<Frame>>>,
+        start: usize,
+        mut f: F,
+    ) {
+        let mut map = BTreeMap::new();
+        let mut next = start;
+        while let Some(Some(frames)) = recv.recv() {
+            if !frames.is_empty() {
+                map.insert(frames[0].index() as usize, frames);
+            }
+            while let Some(frames) = map.remove(&next) {
+                next = frames.last().unwrap().index() as usize + 1;
+                f(frames);
+            }
+        }
+    }
+
+    fn spawn_shard(
+        profile: Profile,
+        recv: crossbeam_channel::Receiver<Part>,
+        merge: crossbeam_channel::Sender<Part>,
+        metrics: Arc<Metrics>,
+    ) -> JoinHandle<()> {
+        thread::spawn(move || {
+            let mut disp = Dispatcher::new(&ExecType::SerialSync, &profile);
+            while let Some((seq, mut part)) = recv.recv() {
+                let start = Instant::now();
+                for (_, frame) in &mut part {
+                    disp.process_frame(frame);
+                }
+                metrics.serial.record(part.len(), start.elapsed());
+                merge.send((seq, part));
+            }
+        })
+    }
+
+    fn spawn_merger<C: 'static + Callback>(
+        shards: usize,
+        recv: crossbeam_channel::Receiver<Part>,
+        callback: C,
+    ) -> JoinHandle<()> {
+        thread::spawn(move || {
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
+            while let Some((seq, part)) = recv.recv() {
+                {
+                    let entry = pending.entry(seq).or_insert_with(|| (0, Vec::new()));
+                    entry.0 += 1;
+                    entry.1.extend(part);
+                }
+                while pending.get(&next).map(|entry| entry.0) == Some(shards) {
+                    let (_, mut frames) = pending.remove(&next).unwrap();
+                    frames.sort_by_key(|(pos, _)| *pos);
+                    callback.done(frames.into_iter().map(|(_, frame)| frame).collect());
+                    next += 1;
+                }
+            }
+        })
+    }
+
     pub fn process(&mut self, frames: Vec<Frame>) {
         self.sender.send(Some(frames));
     }
@@ -63,11 +164,43 @@ impl Pool {
 
 impl Drop for Pool {
     fn drop(&mut self) {
-        for _ in 0..self.handles.len() {
-            self.sender.send(None);
-        }
+        // The other threads stop when their input channels are closed.
+        self.sender.send(None);
         while let Some(h) = self.handles.pop() {
             h.join().unwrap();
         }
     }
 }
+
+/// Returns a hash of the unordered pair of the innermost source and
+/// destination addresses of a frame, so that both directions of a flow
+/// have the same hash.
+fn flow_hash(frame: &Frame, src: Token, dst: Token) -> u64 {
+    for layer in frame.layers().iter().rev() {
+        if let (Some(s), Some(d)) = (layer.attr(src), layer.attr(dst)) {
+            if let (Ok(s), Ok(d)) = (s.try_get(layer), d.try_get(layer)) {
+                let (s, d) = (variant_hash(&s), variant_hash(&d));
+                let mut hasher = FnvHasher::default();
+                s.min(d).hash(&mut hasher);
+                s.max(d).hash(&mut hasher);
+                return hasher.finish();
+            }
+        }
+    }
+    0
+}
+
+fn variant_hash(value: &Variant) -> u64 {
+    let mut hasher = FnvHasher::default();
+    match value {
+        Variant::Nil => {}
+        Variant::Bool(b) => b.hash(&mut hasher),
+        Variant::Int64(v) => v.hash(&mut hasher),
+        Variant::UInt64(v) => v.hash(&mut hasher),
+        Variant::Float64(v) => v.to_bits().hash(&mut hasher),
+        Variant::String(s) => s.hash(&mut hasher),
+        Variant::BigInt(b) | Variant::Buffer(b) => b.hash(&mut hasher),
+        Variant::Slice(s) => s[..].hash(&mut hasher),
+    }
+    hasher.finish()
+}
@@ -0,0 +1,159 @@
+use genet_abi::{fixed::MutFixed, layer::Layer, variant::Variant};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A difference of an attribute between two frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub id: String,
+    pub index: usize,
+    pub a: Option<Variant>,
+    pub b: Option<Variant>,
+}
+
+pub(crate) struct VariantRef<'a>(pub &'a Variant);
+
+impl<'a> Serialize for VariantRef<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            Variant::Nil => serializer.serialize_unit(),
+            Variant::Bool(v) => serializer.serialize_bool(*v),
+            Variant::Int64(v) => serializer.serialize_i64(*v),
+            Variant::UInt64(v) => serializer.serialize_u64(*v),
+            Variant::Float64(v) => serializer.serialize_f64(*v),
+            Variant::String(v) => serializer.serialize_str(v),
+            Variant::BigInt(v) | Variant::Buffer(v) => v.serialize(serializer),
+            Variant::Slice(v) => v.as_ref().serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for Change {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(5))?;
+        map.serialize_entry("kind", &self.kind)?;
+        map.serialize_entry("id", &self.id)?;
+        map.serialize_entry("index", &self.index)?;
+        map.serialize_entry("a", &self.a.as_ref().map(VariantRef))?;
+        map.serialize_entry("b", &self.b.as_ref().map(VariantRef))?;
+        map.end()
+    }
+}
+
+/// Returns the attributes of `layers` in order, keyed by their ID and the
+/// number of preceding attributes with the same ID.
+fn attributes(layers: &[MutFixed<Layer>]) -> Vec<((String, usize), Variant)> {
+    let mut counts = HashMap::new();
+    let mut attrs = Vec::new();
+    for layer in layers {
+        for attr in layer.attrs_iter() {
+            let id = attr.id().to_string();
+            let count = counts.entry(id.clone()).or_insert(0);
+            let value = attr.try_get(layer).unwrap_or(Variant::Nil);
+            attrs.push(((id, *count), value));
+            *count += 1;
+        }
+    }
+    attrs
+}
+
+/// Compares the decoded attributes of two frames.
+///
+/// Attributes are matched by their ID and occurrence, so that e.g. the
+/// addresses of an encapsulated IP header are compared with each other.
+pub fn diff(a: &[MutFixed<Layer>], b: &[MutFixed<Layer>]) -> Vec<Change> {
+    let a = attributes(a);
+    let b = attributes(b);
+    let b_map = b.iter().cloned().collect::<HashMap<_, _>>();
+    let a_map = a.iter().cloned().collect::<HashMap<_, _>>();
+
+    let mut changes = Vec::new();
+    for ((id, index), value) in &a {
+        match b_map.get(&(id.clone(), *index)) {
+            Some(other) if other == value => {}
+            Some(other) => changes.push(Change {
+                kind: ChangeKind::Changed,
+                id: id.clone(),
+                index: *index,
+                a: Some(value.clone()),
+                b: Some(other.clone()),
+            }),
+            None => changes.push(Change {
+                kind: ChangeKind::Removed,
+                id: id.clone(),
+                index: *index,
+                a: Some(value.clone()),
+                b: None,
+            }),
+        }
+    }
+    for ((id, index), value) in b {
+        if !a_map.contains_key(&(id.clone(), index)) {
+            changes.push(Change {
+                kind: ChangeKind::Added,
+                id,
+                index,
+                a: None,
+                b: Some(value),
+            });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use diff::{diff, ChangeKind};
+    use genet_abi::{fixed::MutFixed, layer::Layer, variant::Variant};
+    use test_util;
+
+    fn layer(attrs: &[(&'static str, u64)]) -> MutFixed<Layer> {
+        attrs
+            .iter()
+            .fold(test_util::layer("test"), |layer, (id, value)| {
+                layer.attr(id, *value)
+            })
+            .build()
+    }
+
+    #[test]
+    fn compare() {
+        let a = vec![
+            layer(&[("ip.ttl", 64), ("ip.id", 1)]),
+            layer(&[("ip.ttl", 3)]),
+        ];
+        let b = vec![
+            layer(&[("ip.ttl", 64), ("ip.id", 2)]),
+            layer(&[("ip.ttl", 3), ("ip.flags", 0)]),
+        ];
+        let changes = diff(&a, &b);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, ChangeKind::Changed);
+        assert_eq!(changes[0].id, "ip.id");
+        assert_eq!(changes[0].a, Some(Variant::UInt64(1)));
+        assert_eq!(changes[0].b, Some(Variant::UInt64(2)));
+        assert_eq!(changes[1].kind, ChangeKind::Added);
+        assert_eq!(changes[1].id, "ip.flags");
+
+        let changes = diff(&b, &a[..1]);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[1].kind, ChangeKind::Removed);
+        assert_eq!(changes[1].id, "ip.ttl");
+        assert_eq!(changes[1].index, 1);
+    }
+}
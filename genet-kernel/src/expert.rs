@@ -0,0 +1,223 @@
+//! Expert information gathered from decoded frames.
+//!
+//! Decoders report findings as attributes whose type is `@expert:<severity>`,
+//! and the description of the attribute class is used as the message. The
+//! findings of each frame are recorded in a session-wide registry, and the
+//! highest severity is added to the top layer as `expert.severity`, so that
+//! e.g. `expert.severity >= warn` matches the frames with warnings or errors.
+
+use frame::Frame;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    expert::{Severity, SEVERITY_ATTR},
+    fixed::Fixed,
+};
+use parking_lot::RwLock;
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+/// A finding reported by a decoder.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub frame: u32,
+    pub severity: Severity,
+    /// The ID of the attribute.
+    pub id: String,
+    /// The ID of the layer which has the attribute.
+    pub protocol: String,
+    pub message: String,
+}
+
+/// The number of the findings with the same attribute.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ExpertGroup {
+    pub severity: Severity,
+    pub id: String,
+    pub protocol: String,
+    pub message: String,
+    pub count: usize,
+}
+
+/// The numbers of the findings by severity and by attribute.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ExpertSummary {
+    pub chat: usize,
+    pub note: usize,
+    pub warn: usize,
+    pub error: usize,
+    /// Sorted by descending severity and ID.
+    pub groups: Vec<ExpertGroup>,
+}
+
+/// A registry of findings shared between a store and its lazy decoder.
+#[derive(Clone)]
+pub struct Expert {
+    findings: Arc<RwLock<BTreeMap<u32, Vec<Finding>>>>,
+    class: Fixed<AttrClass>,
+}
+
+impl fmt::Debug for Expert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expert")
+    }
+}
+
+impl Default for Expert {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Expert {
+    pub fn new() -> Expert {
+        Expert {
+            findings: Arc::new(RwLock::new(BTreeMap::new())),
+            class: Fixed::new(AttrClass::builder(SEVERITY_ATTR).typ("@enum").build()),
+        }
+    }
+
+    /// Records the findings of `frames`, replacing the findings recorded
+    /// before for the same frames, and adds `expert.severity` to the frames
+    /// which have findings.
+    pub fn update(&self, frames: &mut [Frame]) {
+        let mut findings = self.findings.write();
+        for frame in frames {
+            let index = frame.index();
+            let list = frame
+                .layers()
+                .iter()
+                .flat_map(|layer| {
+                    layer.attrs_iter().filter_map(move |attr| {
+                        let severity = Severity::from_typ(&attr.typ().to_string())?;
+                        Some(Finding {
+                            frame: index,
+                            severity,
+                            id: attr.id().to_string(),
+                            protocol: layer.id().to_string(),
+                            message: attr.metadata().description().to_string(),
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+            let max = list.iter().map(|f| f.severity).max();
+            if let (Some(max), Some(top)) = (max, frame.layers_mut().last_mut()) {
+                // A frame can be updated again without its top layer being
+                // decoded again, e.g. if it only has the root layer, which is
+                // kept; the severity is added once.
+                if top.attr(SEVERITY_ATTR).is_none() {
+                    let class = self.class.clone();
+                    top.add_attr(Attr::builder(class).value(max.level()).build());
+                }
+            }
+            if list.is_empty() {
+                findings.remove(&index);
+            } else {
+                findings.insert(index, list);
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        self.findings.write().clear();
+    }
+
+    /// Returns the findings of the frame `index`.
+    pub fn findings(&self, index: u32) -> Vec<Finding> {
+        self.findings
+            .read()
+            .get(&index)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn summary(&self) -> ExpertSummary {
+        let mut summary = ExpertSummary::default();
+        let mut groups = BTreeMap::new();
+        for finding in self.findings.read().values().flat_map(|list| list.iter()) {
+            match finding.severity {
+                Severity::Chat => summary.chat += 1,
+                Severity::Note => summary.note += 1,
+                Severity::Warn => summary.warn += 1,
+                Severity::Error => summary.error += 1,
+            }
+            groups
+                .entry((finding.severity, finding.id.clone()))
+                .or_insert_with(|| ExpertGroup {
+                    severity: finding.severity,
+                    id: finding.id.clone(),
+                    protocol: finding.protocol.clone(),
+                    message: finding.message.clone(),
+                    count: 0,
+                })
+                .count += 1;
+        }
+        summary.groups = groups.into_iter().rev().map(|(_, group)| group).collect();
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expert::Expert;
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        expert::Severity,
+        fixed::Fixed,
+        token::Token,
+    };
+    use test_util;
+
+    fn frame(index: u32, findings: &[(&'static str, &'static str)]) -> Frame {
+        let layer = findings
+            .iter()
+            .fold(test_util::layer("tcp"), |layer, (id, typ)| {
+                let class = Fixed::new(
+                    AttrClass::builder(*id)
+                        .typ(*typ)
+                        .description("Finding")
+                        .build(),
+                );
+                layer.add_attr(Attr::builder(class).value(true).build())
+            });
+        test_util::frame(index, vec![layer.build()])
+    }
+
+    #[test]
+    fn update() {
+        let expert = Expert::new();
+        let mut frames = vec![
+            frame(0, &[("tcp.a", "@expert:note"), ("tcp.b", "@expert:warn")]),
+            frame(1, &[("tcp.a", "@expert:note"), ("tcp.c", "@novalue")]),
+            frame(2, &[]),
+        ];
+        expert.update(&mut frames);
+        let severity = |frame: &Frame| {
+            frame
+                .attr(Token::from("expert.severity"))
+                .map(|attr| attr.try_get(&frame.layers()[0]).unwrap())
+        };
+        assert_eq!(severity(&frames[0]), Some(3u64.into()));
+        assert_eq!(severity(&frames[1]), Some(2u64.into()));
+        assert_eq!(severity(&frames[2]), None);
+
+        // Decoding a frame again replaces its findings.
+        expert.update(&mut frames[1..2]);
+        assert_eq!(expert.findings(1).len(), 1);
+        assert_eq!(expert.findings(1)[0].message, "Finding");
+
+        let summary = expert.summary();
+        assert_eq!((summary.note, summary.warn), (2, 1));
+        let groups = summary
+            .groups
+            .iter()
+            .map(|g| (g.severity, g.id.as_str(), g.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![(Severity::Warn, "tcp.b", 1), (Severity::Note, "tcp.a", 2),]
+        );
+
+        expert.clear();
+        assert_eq!(expert.summary().groups.len(), 0);
+    }
+}
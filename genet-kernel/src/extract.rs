@@ -0,0 +1,235 @@
+//! Extraction of attribute values for external commands.
+//!
+//! An `Exec` runs a program for each matching frame, or once per flow when a
+//! key attribute such as `tcp.stream` is given. The selected attribute values
+//! are passed as `GENET_<ID>` environment variables and can be substituted
+//! into the arguments as `{<id>}`.
+
+use genet_abi::{
+    attr::Attr,
+    fixed::MutFixed,
+    layer::Layer,
+    token::Token,
+    variant::{Value, Variant},
+};
+use std::{
+    collections::HashSet,
+    io,
+    net::{Ipv4Addr, Ipv6Addr},
+    process::{self, ExitStatus},
+};
+
+/// A list of attributes to extract from frames.
+#[derive(Clone, Debug)]
+pub struct Fields {
+    ids: Vec<(String, Token)>,
+}
+
+impl Fields {
+    pub fn new<I, S>(ids: I) -> Fields
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Fields {
+            ids: ids
+                .into_iter()
+                .map(|id| (id.as_ref().to_string(), Token::from(id.as_ref())))
+                .collect(),
+        }
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.ids.iter().map(|(id, _)| id.as_str())
+    }
+
+    /// Returns the value of each field as text.
+    ///
+    /// As in filters, the topmost layer which has the attribute is used. A
+    /// missing attribute results in an empty string.
+    pub fn extract(&self, layers: &[MutFixed<Layer>]) -> Vec<String> {
+        self.ids
+            .iter()
+            .map(|(_, id)| extract(layers, *id).unwrap_or_default())
+            .collect()
+    }
+
+    /// Returns the environment variable names of the fields, e.g.
+    /// `GENET_TCP_DST` for `tcp.dst`.
+    pub fn env_names(&self) -> Vec<String> {
+        self.ids.iter().map(|(id, _)| env_name(id)).collect()
+    }
+}
+
+fn env_name(id: &str) -> String {
+    let name = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("GENET_{}", name)
+}
+
+fn extract(layers: &[MutFixed<Layer>], id: Token) -> Option<String> {
+    layers.iter().rev().find_map(|layer| {
+        let attr = layer.attr(id)?;
+        attr.try_get(layer).ok().map(|value| format(attr, value))
+    })
+}
+
+/// Formats `value` according to the type of `attr`.
+pub fn format(attr: &Attr, value: Variant) -> String {
+    let typ = attr.typ().to_string();
+    match value {
+        Variant::Nil => String::new(),
+        Variant::Bool(val) => val.to_string(),
+        Variant::Int64(val) => val.to_string(),
+        Variant::UInt64(val) => val.to_string(),
+        Variant::Float64(val) => val.to_string(),
+        Variant::String(val) => val.to_string(),
+        value => {
+            let data: Vec<u8> = value.try_into().unwrap_or_default();
+            format_bytes(&typ, &data)
+        }
+    }
+}
+
+/// Formats an address or another byte string according to `typ`.
+pub(crate) fn format_bytes(typ: &str, data: &[u8]) -> String {
+    match (typ, data.len()) {
+        ("@ipv4:addr", 4) => Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string(),
+        ("@ipv6:addr", 16) => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(data);
+            Ipv6Addr::from(octets).to_string()
+        }
+        ("@eth:mac", _) => data
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":"),
+        _ => data.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+}
+
+/// An external command run with the fields of frames.
+#[derive(Debug)]
+pub struct Exec {
+    program: String,
+    args: Vec<String>,
+    fields: Fields,
+    key: Option<Token>,
+    seen: HashSet<String>,
+}
+
+impl Exec {
+    pub fn new<S: AsRef<str>>(program: &str, args: &[S], fields: Fields) -> Exec {
+        Exec {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.as_ref().to_string()).collect(),
+            fields,
+            key: None,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Runs the command only for the first frame of each distinct value of
+    /// `key`, e.g. `tcp.stream` to run it once per flow.
+    pub fn per_key(mut self, key: &str) -> Exec {
+        self.key = Some(Token::from(key));
+        self
+    }
+
+    /// Returns the arguments with the `{<id>}` placeholders replaced by
+    /// `values`.
+    pub fn args(&self, values: &[String]) -> Vec<String> {
+        self.args
+            .iter()
+            .map(|arg| {
+                self.fields
+                    .ids()
+                    .zip(values)
+                    .fold(arg.clone(), |arg, (id, value)| {
+                        arg.replace(&format!("{{{}}}", id), value)
+                    })
+            })
+            .collect()
+    }
+
+    /// Runs the command for `layers` and waits for it to exit.
+    ///
+    /// Returns None if the command is skipped because the frame has no key
+    /// or belongs to a flow which has already been processed.
+    pub fn run(&mut self, layers: &[MutFixed<Layer>]) -> io::Result<Option<ExitStatus>> {
+        if let Some(key) = self.key {
+            match extract(layers, key) {
+                Some(value) => {
+                    if !self.seen.insert(value) {
+                        return Ok(None);
+                    }
+                }
+                None => return Ok(None),
+            }
+        }
+        let values = self.fields.extract(layers);
+        process::Command::new(&self.program)
+            .args(self.args(&values))
+            .envs(self.fields.env_names().into_iter().zip(values))
+            .status()
+            .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use extract::{Exec, Fields};
+    use genet_abi::{fixed::MutFixed, layer::Layer, slice::ByteSlice};
+    use test_util;
+
+    fn frame(dst: u64, stream: u64) -> Vec<MutFixed<Layer>> {
+        let addr = ByteSlice::from(&[192, 168, 0, 1][..]);
+        vec![
+            test_util::layer("ipv4")
+                .typed_attr("ipv4.src", "@ipv4:addr", addr)
+                .build(),
+            test_util::layer("tcp")
+                .attr("tcp.dst", dst)
+                .attr("tcp.stream", stream)
+                .build(),
+        ]
+    }
+
+    #[test]
+    fn extract() {
+        let fields = Fields::new(["ipv4.src", "tcp.dst", "udp.dst"]);
+        assert_eq!(
+            fields.extract(&frame(80, 0)),
+            vec!["192.168.0.1".to_string(), "80".to_string(), String::new()]
+        );
+        assert_eq!(
+            fields.env_names(),
+            vec!["GENET_IPV4_SRC", "GENET_TCP_DST", "GENET_UDP_DST"]
+        );
+    }
+
+    #[test]
+    fn exec() {
+        let fields = Fields::new(["tcp.dst"]);
+        let exec = Exec::new("sh", &["-c", "port={tcp.dst}"], fields.clone());
+        assert_eq!(
+            exec.args(&["80".to_string()]),
+            vec!["-c".to_string(), "port=80".to_string()]
+        );
+
+        let mut exec =
+            Exec::new("sh", &["-c", "test \"$GENET_TCP_DST\" = 80"], fields).per_key("tcp.stream");
+        assert!(exec.run(&frame(80, 0)).unwrap().unwrap().success());
+        assert_eq!(exec.run(&frame(80, 0)).unwrap(), None);
+        assert!(!exec.run(&frame(443, 1)).unwrap().unwrap().success());
+    }
+}
@@ -0,0 +1,113 @@
+//! Marked and ignored frames.
+//!
+//! Marked frames match `frame.marked` in filters. Ignored frames are not
+//! decoded beyond their root layer, so that they are left out of
+//! reassembly, the statistics and the analysis of conversations.
+
+use frame::Frame;
+use genet_filter::{ast::Expr, context::Context, Filter};
+use parking_lot::RwLock;
+use roaring::RoaringBitmap;
+use std::{fmt, sync::Arc};
+
+#[derive(Default)]
+struct Flags {
+    marked: RoaringBitmap,
+    ignored: RoaringBitmap,
+}
+
+/// The sets of marked and ignored frames shared by the decoders and the
+/// filters of a session.
+#[derive(Clone, Default)]
+pub struct FrameFlags {
+    flags: Arc<RwLock<Flags>>,
+}
+
+impl fmt::Debug for FrameFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FrameFlags")
+    }
+}
+
+impl FrameFlags {
+    pub fn new() -> FrameFlags {
+        Self::default()
+    }
+
+    /// Marks or unmarks the frames `indices`, and returns whether any of
+    /// them has changed.
+    pub fn set_marked(&self, indices: &[u32], marked: bool) -> bool {
+        update(&mut self.flags.write().marked, indices, marked)
+    }
+
+    /// Ignores or restores the frames `indices`, and returns whether any of
+    /// them has changed.
+    pub fn set_ignored(&self, indices: &[u32], ignored: bool) -> bool {
+        update(&mut self.flags.write().ignored, indices, ignored)
+    }
+
+    pub fn is_marked(&self, index: u32) -> bool {
+        self.flags.read().marked.contains(index)
+    }
+
+    pub fn is_ignored(&self, index: u32) -> bool {
+        self.flags.read().ignored.contains(index)
+    }
+
+    pub fn marked(&self) -> RoaringBitmap {
+        self.flags.read().marked.clone()
+    }
+
+    pub fn ignored(&self) -> RoaringBitmap {
+        self.flags.read().ignored.clone()
+    }
+
+    /// Returns the filter context of `frame`.
+    pub fn context<'a>(&self, frame: &'a Frame) -> Context<'a> {
+        Context::with_protocols(frame.layers(), frame.protocols())
+            .with_marked(self.is_marked(frame.index()))
+    }
+}
+
+fn update(bitmap: &mut RoaringBitmap, indices: &[u32], value: bool) -> bool {
+    let len = bitmap.len();
+    if value {
+        bitmap.extend(indices.iter().cloned());
+    } else {
+        for index in indices {
+            bitmap.remove(*index);
+        }
+    }
+    bitmap.len() != len
+}
+
+/// Returns whether the result of `filter` depends on the marked frames.
+pub fn uses_marks(filter: &Filter) -> bool {
+    filter.expr().any(&|expr| *expr == Expr::Marked)
+}
+
+#[cfg(test)]
+mod tests {
+    use flags::{uses_marks, FrameFlags};
+    use genet_filter::Filter;
+
+    #[test]
+    fn update() {
+        let flags = FrameFlags::new();
+        assert!(flags.set_marked(&[1, 5, 9], true));
+        assert!(!flags.set_marked(&[5], true));
+        assert!(flags.set_marked(&[5, 6], false));
+        assert_eq!(flags.marked().iter().collect::<Vec<_>>(), vec![1, 9]);
+        assert!(flags.is_marked(9));
+        assert!(!flags.is_ignored(9));
+
+        assert!(flags.set_ignored(&[3], true));
+        assert!(flags.is_ignored(3));
+        assert_eq!(flags.ignored().len(), 1);
+
+        assert!(uses_marks(
+            &Filter::compile("tcp && !frame.marked").unwrap()
+        ));
+        assert!(!uses_marks(&Filter::compile("tcp").unwrap()));
+    }
+}
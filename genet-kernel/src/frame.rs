@@ -1,10 +1,12 @@
 use genet_abi::{attr::Attr, fixed::MutFixed, layer::Layer, token::Token};
-use std::{fmt, mem};
+use genet_filter::protocols::Protocols;
+use std::{fmt, mem, ops::Deref};
 
 pub struct Frame {
     index: u32,
     layers: Vec<MutFixed<Layer>>,
     tree_indices: Vec<u8>,
+    protocols: Protocols,
 }
 
 impl fmt::Debug for Frame {
@@ -21,6 +23,7 @@ impl Frame {
             index,
             layers: vec![root],
             tree_indices: Vec::new(),
+            protocols: Protocols::default(),
         }
     }
 
@@ -32,6 +35,10 @@ impl Frame {
         &self.layers
     }
 
+    pub fn layers_mut(&mut self) -> &mut [MutFixed<Layer>] {
+        &mut self.layers
+    }
+
     pub fn attr(&self, id: Token) -> Option<&Attr> {
         for layer in self.layers().iter().rev() {
             if let Some(attr) = layer.attr(id) {
@@ -48,9 +55,14 @@ impl Frame {
     }
 
     pub fn set_layers(&mut self, layers: Vec<MutFixed<Layer>>) {
+        self.protocols = Protocols::new(&layers);
         self.layers = layers;
     }
 
+    pub fn protocols(&self) -> &Protocols {
+        &self.protocols
+    }
+
     pub fn tree_indices(&self) -> &[u8] {
         &self.tree_indices
     }
@@ -65,3 +77,31 @@ impl Frame {
         self.tree_indices = tree_indices;
     }
 }
+
+/// A frame which owns its layers, unlike the frames in the store.
+#[derive(Debug)]
+pub struct OwnedFrame {
+    frame: Frame,
+}
+
+impl OwnedFrame {
+    pub fn new(frame: Frame) -> OwnedFrame {
+        OwnedFrame { frame }
+    }
+}
+
+impl Deref for OwnedFrame {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        &self.frame
+    }
+}
+
+impl Drop for OwnedFrame {
+    fn drop(&mut self) {
+        for layer in self.frame.fetch_layers() {
+            drop(unsafe { Box::from_raw(layer.as_mut_ptr()) });
+        }
+    }
+}
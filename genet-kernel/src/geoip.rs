@@ -0,0 +1,351 @@
+//! GeoIP enrichment from MaxMind databases.
+//!
+//! If databases are configured by `_.geoip.databases`, the addresses of the
+//! IPv4 and IPv6 layers are looked up and the results are added as:
+//!
+//! - `ipv4.geoip.src_country`, `ipv4.geoip.dst_country`: the ISO 3166
+//!   country code.
+//! - `ipv4.geoip.src_city`, `ipv4.geoip.dst_city`: the English city name.
+//! - `ipv4.geoip.src_asn`, `ipv4.geoip.dst_asn`: the autonomous system
+//!   number.
+//!
+//! and the same for `ipv6`. City, Country and ASN databases can be combined,
+//! and the first database with a value is used for each attribute.
+//!
+//! The databases are opened when the first frame is enriched, so that a
+//! profile without databases does not read any file and frames are left
+//! unchanged.
+
+use analysis::attr;
+use frame::Frame;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::Fixed,
+    layer::Layer,
+    token::Token,
+};
+use maxminddb::{geoip2, Reader};
+use parking_lot::RwLock;
+use serde_json;
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+};
+
+/// The config key of the database paths, a JSON array.
+pub const DATABASES_KEY: &str = "_.geoip.databases";
+
+/// The location of an address.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Location {
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub asn: Option<u32>,
+}
+
+impl Location {
+    fn is_complete(&self) -> bool {
+        self.country.is_some() && self.city.is_some() && self.asn.is_some()
+    }
+}
+
+struct Side {
+    addr: Token,
+    country: Fixed<AttrClass>,
+    city: Fixed<AttrClass>,
+    asn: Fixed<AttrClass>,
+}
+
+struct Target {
+    layer: Token,
+    sides: Vec<Side>,
+}
+
+type Readers = Vec<Reader<Vec<u8>>>;
+
+/// GeoIP databases shared between a profile and its sessions.
+#[derive(Clone, Default)]
+pub struct GeoIp {
+    paths: Arc<Vec<String>>,
+    readers: Arc<RwLock<Option<Readers>>>,
+    targets: Arc<Vec<Target>>,
+}
+
+impl fmt::Debug for GeoIp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GeoIp")
+    }
+}
+
+impl GeoIp {
+    /// Creates a new GeoIp reading the databases at `paths`.
+    pub fn new(paths: Vec<String>) -> GeoIp {
+        let targets = if paths.is_empty() {
+            Vec::new()
+        } else {
+            ["ipv4", "ipv6"]
+                .iter()
+                .map(|layer| {
+                    let class = |id: String| Fixed::new(AttrClass::builder(id.as_str()).build());
+                    let sides = ["src", "dst"]
+                        .iter()
+                        .map(|side| Side {
+                            addr: Token::from(format!("{}.{}", layer, side).as_str()),
+                            country: class(format!("{}.geoip.{}_country", layer, side)),
+                            city: class(format!("{}.geoip.{}_city", layer, side)),
+                            asn: class(format!("{}.geoip.{}_asn", layer, side)),
+                        })
+                        .collect();
+                    Target {
+                        layer: Token::from(*layer),
+                        sides,
+                    }
+                })
+                .collect()
+        };
+        GeoIp {
+            paths: Arc::new(paths),
+            readers: Arc::new(RwLock::new(None)),
+            targets: Arc::new(targets),
+        }
+    }
+
+    /// Creates a new GeoIp from the value of `DATABASES_KEY`.
+    pub fn from_config(value: &str) -> GeoIp {
+        Self::new(serde_json::from_str(value).unwrap_or_default())
+    }
+
+    #[cfg(test)]
+    fn with_readers(readers: Readers) -> GeoIp {
+        let geoip = Self::new(vec![String::new()]);
+        *geoip.readers.write() = Some(readers);
+        geoip
+    }
+
+    /// Opens the databases unless they are opened. Databases which cannot be
+    /// opened are ignored.
+    fn open(&self) {
+        if self.readers.read().is_some() {
+            return;
+        }
+        let mut readers = self.readers.write();
+        if readers.is_none() {
+            *readers = Some(
+                self.paths
+                    .iter()
+                    .filter_map(|path| Reader::open_readfile(path).ok())
+                    .collect(),
+            );
+        }
+    }
+
+    /// Returns the location of an IPv4 or IPv6 address, or None if no
+    /// database has it.
+    pub fn lookup(&self, addr: &[u8]) -> Option<Location> {
+        let addr = match addr.len() {
+            4 => IpAddr::V4(Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3])),
+            16 => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(addr);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => return None,
+        };
+        self.open();
+        let readers = self.readers.read();
+        let mut location = Location::default();
+        for reader in readers.iter().flatten() {
+            if let Ok(city) = reader.lookup::<geoip2::City>(addr) {
+                if location.country.is_none() {
+                    location.country = city
+                        .country
+                        .and_then(|country| country.iso_code)
+                        .map(|code| code.to_string());
+                }
+                if location.city.is_none() {
+                    location.city = city
+                        .city
+                        .and_then(|city| city.names)
+                        .and_then(|names| names.get("en").map(|name| name.to_string()));
+                }
+            }
+            if location.asn.is_none() {
+                if let Ok(asn) = reader.lookup::<geoip2::Asn>(addr) {
+                    location.asn = asn.autonomous_system_number;
+                }
+            }
+            if location.is_complete() {
+                break;
+            }
+        }
+        if location == Location::default() {
+            None
+        } else {
+            Some(location)
+        }
+    }
+
+    /// Adds the locations of the addresses to `frames`.
+    pub fn update(&self, frames: &mut [Frame]) {
+        if self.targets.is_empty() {
+            return;
+        }
+        for frame in frames {
+            for layer in frame.layers_mut() {
+                let id = layer.id();
+                if let Some(target) = self.targets.iter().find(|t| t.layer == id) {
+                    self.process(layer, target);
+                }
+            }
+        }
+    }
+
+    fn process(&self, layer: &mut Layer, target: &Target) {
+        for side in &target.sides {
+            let location = match attr::<Vec<u8>>(layer, side.addr).and_then(|a| self.lookup(&a)) {
+                Some(location) => location,
+                None => continue,
+            };
+            if let Some(country) = location.country {
+                let attr = Attr::builder(side.country.clone()).value(country.into_boxed_str());
+                layer.add_attr(attr.build());
+            }
+            if let Some(city) = location.city {
+                let attr = Attr::builder(side.city.clone()).value(city.into_boxed_str());
+                layer.add_attr(attr.build());
+            }
+            if let Some(asn) = location.asn {
+                let attr = Attr::builder(side.asn.clone()).value(u64::from(asn));
+                layer.add_attr(attr.build());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{slice::ByteSlice, variant::Variant};
+    use geoip::{GeoIp, Location};
+    use maxminddb::Reader;
+    use test_util;
+
+    fn ctrl(buf: &mut Vec<u8>, typ: u8, size: usize) {
+        if typ <= 7 {
+            buf.push(typ << 5 | size as u8);
+        } else {
+            buf.push(size as u8);
+            buf.push(typ - 7);
+        }
+    }
+
+    fn string(buf: &mut Vec<u8>, s: &str) {
+        ctrl(buf, 2, s.len());
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn uint(buf: &mut Vec<u8>, typ: u8, value: u64) {
+        let bytes = value.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        ctrl(buf, typ, 8 - skip);
+        buf.extend_from_slice(&bytes[skip..]);
+    }
+
+    /// Returns an IPv4 database with a record for 10.0.0.0/8.
+    fn database() -> Vec<u8> {
+        let prefix = 10u8;
+        let node_count = 8u32;
+        let mut buf = Vec::new();
+        for bit in 0..8 {
+            let next = if bit == 7 { node_count + 16 } else { bit + 1 };
+            let records = if prefix >> (7 - bit) & 1 == 0 {
+                [next, node_count]
+            } else {
+                [node_count, next]
+            };
+            for record in &records {
+                buf.extend_from_slice(&record.to_be_bytes()[1..]);
+            }
+        }
+        buf.extend_from_slice(&[0; 16]);
+
+        ctrl(&mut buf, 7, 3);
+        string(&mut buf, "country");
+        ctrl(&mut buf, 7, 1);
+        string(&mut buf, "iso_code");
+        string(&mut buf, "JP");
+        string(&mut buf, "city");
+        ctrl(&mut buf, 7, 1);
+        string(&mut buf, "names");
+        ctrl(&mut buf, 7, 1);
+        string(&mut buf, "en");
+        string(&mut buf, "Tokyo");
+        string(&mut buf, "autonomous_system_number");
+        uint(&mut buf, 6, 64500);
+
+        buf.extend_from_slice(b"\xab\xcd\xefMaxMind.com");
+        ctrl(&mut buf, 7, 9);
+        string(&mut buf, "binary_format_major_version");
+        uint(&mut buf, 5, 2);
+        string(&mut buf, "binary_format_minor_version");
+        uint(&mut buf, 5, 0);
+        string(&mut buf, "build_epoch");
+        uint(&mut buf, 9, 0);
+        string(&mut buf, "database_type");
+        string(&mut buf, "Test");
+        string(&mut buf, "description");
+        ctrl(&mut buf, 7, 0);
+        string(&mut buf, "ip_version");
+        uint(&mut buf, 5, 4);
+        string(&mut buf, "languages");
+        ctrl(&mut buf, 11, 0);
+        string(&mut buf, "node_count");
+        uint(&mut buf, 6, u64::from(node_count));
+        string(&mut buf, "record_size");
+        uint(&mut buf, 5, 24);
+        buf
+    }
+
+    #[test]
+    fn lookup() {
+        let geoip = GeoIp::with_readers(vec![Reader::from_source(database()).unwrap()]);
+        assert_eq!(
+            geoip.lookup(&[10, 1, 2, 3]),
+            Some(Location {
+                country: Some("JP".to_string()),
+                city: Some("Tokyo".to_string()),
+                asn: Some(64500),
+            })
+        );
+        assert_eq!(geoip.lookup(&[192, 168, 0, 1]), None);
+        assert_eq!(geoip.lookup(&[1, 2]), None);
+    }
+
+    #[test]
+    fn update() {
+        let ipv4 = test_util::layer("ipv4")
+            .attr("ipv4.src", ByteSlice::from(vec![10, 0, 0, 1]))
+            .attr("ipv4.dst", ByteSlice::from(vec![8, 8, 8, 8]))
+            .build();
+        let mut frames = vec![test_util::frame(0, vec![test_util::root().build(), ipv4])];
+
+        // Without databases, frames are unchanged.
+        GeoIp::from_config("[]").update(&mut frames);
+        assert_eq!(frames[0].layers()[1].attrs().len(), 2);
+
+        let geoip = GeoIp::with_readers(vec![Reader::from_source(database()).unwrap()]);
+        geoip.update(&mut frames);
+        let ipv4 = &frames[0].layers()[1];
+        let value = |id: &str| ipv4.attr(id).map(|attr| attr.try_get(ipv4).unwrap());
+        assert_eq!(
+            value("ipv4.geoip.src_country"),
+            Some(Variant::String("JP".into()))
+        );
+        assert_eq!(
+            value("ipv4.geoip.src_city"),
+            Some(Variant::String("Tokyo".into()))
+        );
+        assert_eq!(value("ipv4.geoip.src_asn"), Some(Variant::UInt64(64500)));
+        assert_eq!(value("ipv4.geoip.dst_country"), None);
+    }
+}
@@ -0,0 +1,453 @@
+//! An append-only frame index backed by memory-mapped files.
+//!
+//! Every frame gets a fixed-size entry holding the offset of its raw data
+//! and a few summary columns, so that any frame of a huge capture can be
+//! listed in constant time without touching its decoded layers.
+
+use analysis::{attr, timestamp};
+use frame::Frame;
+use genet_abi::{slice::ByteSlice, token::Token};
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The JSON-encoded directory for the index files. Frames are not indexed
+/// if it is empty.
+pub const DIR_KEY: &str = "_.frameIndex.dir";
+
+/// A decoder-defined alias to a flow fingerprint, e.g. a JA4 hash.
+const FINGERPRINT_ATTR: &str = "_.fingerprint";
+
+const INITIAL_CAPACITY: usize = 65536;
+
+static INDEX_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct Entry {
+    offset: u64,
+    ts_sec: i64,
+    ts_nsec: u32,
+    caplen: u32,
+    length: u32,
+    link: u32,
+    protocol: u32,
+    depth: u32,
+    fingerprint: u32,
+}
+
+impl Entry {
+    fn new(frame: &Frame, offset: u64) -> Entry {
+        let mut entry = Entry {
+            offset,
+            ..Entry::default()
+        };
+        if let Some(root) = frame.layers().first() {
+            if let Some(ts) = timestamp(root) {
+                entry.ts_sec = ts.div_euclid(1_000_000_000) as i64;
+                entry.ts_nsec = ts.rem_euclid(1_000_000_000) as u32;
+            }
+            entry.caplen = root.data().len() as u32;
+            entry.length = attr(root, "link.length").unwrap_or(entry.caplen);
+            entry.link = attr(root, "link.type").unwrap_or(0);
+        }
+        entry.update(frame);
+        entry
+    }
+
+    /// Refreshes the columns which depend on the decoders.
+    fn update(&mut self, frame: &Frame) {
+        self.protocol = frame
+            .layers()
+            .last()
+            .map(|layer| layer.id().into())
+            .unwrap_or(0);
+        self.depth = frame.layers().len() as u32;
+        self.fingerprint = frame
+            .layers()
+            .iter()
+            .rev()
+            .filter_map(|layer| attr::<String>(layer, FINGERPRINT_ATTR))
+            .next()
+            .map(|value| Token::from(value).into())
+            .unwrap_or(0);
+    }
+
+    fn summary(&self, index: usize) -> Summary {
+        Summary {
+            index: index as u32,
+            timestamp_sec: self.ts_sec,
+            timestamp_nsec: self.ts_nsec,
+            length: self.length,
+            captured_length: self.caplen,
+            link: self.link,
+            protocol: Token::from(self.protocol).to_string(),
+            depth: self.depth,
+            fingerprint: if self.fingerprint == 0 {
+                String::new()
+            } else {
+                Token::from(self.fingerprint).to_string()
+            },
+        }
+    }
+}
+
+/// The summary columns of a frame.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    pub index: u32,
+    pub timestamp_sec: i64,
+    pub timestamp_nsec: u32,
+    pub length: u32,
+    pub captured_length: u32,
+    pub link: u32,
+    pub protocol: String,
+    pub depth: u32,
+    pub fingerprint: String,
+}
+
+impl Summary {
+    pub fn new(frame: &Frame) -> Summary {
+        Entry::new(frame, 0).summary(frame.index() as usize)
+    }
+
+    /// Returns the timestamp as a UNIX time in seconds.
+    pub fn timestamp(&self) -> f64 {
+        self.timestamp_sec as f64 + f64::from(self.timestamp_nsec) / 1e9
+    }
+}
+
+#[cfg(unix)]
+mod entries {
+    use super::Entry;
+    use libc;
+    use std::{fs::File, io, mem, os::unix::io::AsRawFd, ptr};
+
+    /// Fixed-size entries in a shared file mapping.
+    pub struct Entries {
+        file: File,
+        map: *mut Entry,
+        capacity: usize,
+    }
+
+    unsafe impl Send for Entries {}
+    unsafe impl Sync for Entries {}
+
+    impl Entries {
+        pub fn new(file: File, capacity: usize) -> io::Result<Entries> {
+            let mut entries = Entries {
+                file,
+                map: ptr::null_mut(),
+                capacity: 0,
+            };
+            entries.reserve(capacity)?;
+            Ok(entries)
+        }
+
+        fn reserve(&mut self, capacity: usize) -> io::Result<()> {
+            let size = capacity * mem::size_of::<Entry>();
+            self.file.set_len(size as u64)?;
+            let map = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    size,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    self.file.as_raw_fd(),
+                    0,
+                )
+            };
+            if map == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            self.unmap();
+            self.map = map as *mut Entry;
+            self.capacity = capacity;
+            Ok(())
+        }
+
+        fn unmap(&mut self) {
+            if !self.map.is_null() {
+                unsafe {
+                    libc::munmap(
+                        self.map as *mut libc::c_void,
+                        self.capacity * mem::size_of::<Entry>(),
+                    );
+                }
+                self.map = ptr::null_mut();
+            }
+        }
+
+        pub fn get(&self, index: usize) -> Entry {
+            assert!(index < self.capacity);
+            unsafe { *self.map.add(index) }
+        }
+
+        pub fn set(&mut self, index: usize, entry: Entry) -> io::Result<()> {
+            if index >= self.capacity {
+                let capacity = (self.capacity * 2).max(index + 1);
+                self.reserve(capacity)?;
+            }
+            unsafe { *self.map.add(index) = entry };
+            Ok(())
+        }
+    }
+
+    impl Drop for Entries {
+        fn drop(&mut self) {
+            self.unmap();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod entries {
+    use super::Entry;
+    use std::{fs::File, io};
+
+    /// Fixed-size entries kept in memory on platforms without mmap support.
+    pub struct Entries {
+        vec: Vec<Entry>,
+    }
+
+    impl Entries {
+        pub fn new(_file: File, capacity: usize) -> io::Result<Entries> {
+            Ok(Entries {
+                vec: Vec::with_capacity(capacity),
+            })
+        }
+
+        pub fn get(&self, index: usize) -> Entry {
+            self.vec[index]
+        }
+
+        pub fn set(&mut self, index: usize, entry: Entry) -> io::Result<()> {
+            if index >= self.vec.len() {
+                self.vec.resize(index + 1, Entry::default());
+            }
+            self.vec[index] = entry;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset)? {
+            0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+            n => {
+                let tmp = buf;
+                buf = &mut tmp[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An append-only index of frames and their raw data.
+///
+/// The files are created in a directory given by the profile and removed
+/// when the index is dropped.
+pub struct FrameIndex {
+    entries: entries::Entries,
+    writer: BufWriter<File>,
+    reader: File,
+    data_len: u64,
+    len: usize,
+    paths: Vec<PathBuf>,
+}
+
+impl FrameIndex {
+    pub fn create(dir: &Path) -> io::Result<FrameIndex> {
+        let name = format!(
+            "genet-{}-{}",
+            process::id(),
+            INDEX_COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let index_path = dir.join(format!("{}.idx", name));
+        let data_path = dir.join(format!("{}.dat", name));
+        let open = |path: &Path| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(path)
+        };
+        let index = open(&index_path)?;
+        let data = match open(&data_path) {
+            Ok(data) => data,
+            Err(err) => {
+                let _ = fs::remove_file(&index_path);
+                return Err(err);
+            }
+        };
+        let paths = vec![index_path, data_path];
+        let entries = match entries::Entries::new(index, INITIAL_CAPACITY) {
+            Ok(entries) => entries,
+            Err(err) => {
+                for path in &paths {
+                    let _ = fs::remove_file(path);
+                }
+                return Err(err);
+            }
+        };
+        Ok(FrameIndex {
+            entries,
+            reader: data.try_clone()?,
+            writer: BufWriter::new(data),
+            data_len: 0,
+            len: 0,
+            paths,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends frames in index order along with their raw data.
+    pub fn append(&mut self, frames: &[Frame]) -> io::Result<()> {
+        for frame in frames {
+            let data = frame
+                .layers()
+                .first()
+                .map(|root| root.data())
+                .unwrap_or_else(ByteSlice::new);
+            self.writer.write_all(&data)?;
+            let entry = Entry::new(frame, self.data_len);
+            self.entries.set(self.len, entry)?;
+            self.data_len += data.len() as u64;
+            self.len += 1;
+        }
+        self.writer.flush()
+    }
+
+    /// Refreshes the summary of a redecoded frame.
+    pub fn update(&mut self, frame: &Frame) -> io::Result<()> {
+        let index = frame.index() as usize;
+        if index < self.len {
+            let mut entry = self.entries.get(index);
+            entry.update(frame);
+            self.entries.set(index, entry)?;
+        }
+        Ok(())
+    }
+
+    pub fn summary(&self, index: usize) -> Option<Summary> {
+        if index < self.len {
+            Some(self.entries.get(index).summary(index))
+        } else {
+            None
+        }
+    }
+
+    /// Reads the raw data of the frame at `index` back from the disk.
+    pub fn data(&self, index: usize) -> io::Result<Vec<u8>> {
+        if index >= self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame index out of range",
+            ));
+        }
+        let entry = self.entries.get(index);
+        let mut buf = vec![0u8; entry.caplen as usize];
+        read_at(&self.reader, &mut buf, entry.offset)?;
+        Ok(buf)
+    }
+}
+
+impl fmt::Debug for FrameIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FrameIndex {}", self.len)
+    }
+}
+
+impl Drop for FrameIndex {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use frame::Frame;
+    use genet_abi::slice::ByteSlice;
+    use index::FrameIndex;
+    use std::env;
+    use test_util;
+
+    fn frame(index: u32, data: &[u8], sec: u64) -> Frame {
+        let root = test_util::root()
+            .data(ByteSlice::from(data.to_vec()))
+            .attr("link.type", 1u64)
+            .attr("link.length", data.len() as u64 + 4)
+            .attr("link.timestamp.sec", sec)
+            .attr("link.timestamp.usec", 250u64);
+        test_util::frame(index, vec![root.build()])
+    }
+
+    #[test]
+    fn append() {
+        let mut index = FrameIndex::create(&env::temp_dir()).unwrap();
+        let paths = index.paths.clone();
+        let frames = (0..100_000)
+            .map(|i| frame(i, &[i as u8; 3], u64::from(i)))
+            .collect::<Vec<_>>();
+        index.append(&frames[..10]).unwrap();
+        index.append(&frames[10..]).unwrap();
+        assert_eq!(index.len(), 100_000);
+
+        let summary = index.summary(70_000).unwrap();
+        assert_eq!(summary.index, 70_000);
+        assert_eq!(summary.timestamp_sec, 70_000);
+        assert_eq!(summary.timestamp_nsec, 250_000);
+        assert_eq!(summary.length, 7);
+        assert_eq!(summary.captured_length, 3);
+        assert_eq!(summary.link, 1);
+        assert_eq!(summary.protocol, "[link-1]");
+        assert_eq!(summary.depth, 1);
+        assert_eq!(summary.fingerprint, "");
+        assert_eq!(index.summary(100_000), None);
+
+        assert_eq!(index.data(70_000).unwrap(), vec![70_000u32 as u8; 3]);
+        assert!(index.data(100_000).is_err());
+
+        drop(index);
+        assert!(paths.iter().all(|path| !path.exists()));
+    }
+
+    #[test]
+    fn jumbo() {
+        let mut index = FrameIndex::create(&env::temp_dir()).unwrap();
+        let data = (0..200_000).map(|i| i as u8).collect::<Vec<_>>();
+        let frames = vec![frame(0, &data, 0), frame(1, &[1, 2, 3], 1)];
+        index.append(&frames).unwrap();
+
+        let summary = index.summary(0).unwrap();
+        assert_eq!(summary.length, 200_004);
+        assert_eq!(summary.captured_length, 200_000);
+        assert_eq!(index.data(0).unwrap(), data);
+        assert_eq!(index.data(1).unwrap(), vec![1, 2, 3]);
+    }
+}
@@ -1,5 +1,5 @@
 use frame::Frame;
-use genet_abi::{fixed::MutFixed, layer::Layer, result::Result};
+use genet_abi::{fixed::MutFixed, layer::Layer, reader::CaptureStats, result::Result};
 use std::fmt::Debug;
 
 pub trait Output: Send + Debug {
@@ -9,4 +9,9 @@ pub trait Output: Send + Debug {
 
 pub trait Input: Send + Debug {
     fn read(&mut self) -> Result<Vec<MutFixed<Layer>>>;
+
+    /// Returns the statistics of a live capture.
+    fn stats(&mut self) -> Option<CaptureStats> {
+        None
+    }
 }
@@ -0,0 +1,298 @@
+//! Time-bucketed series for IO graphs.
+//!
+//! Frames are put into buckets of a fixed interval, counted from the
+//! timestamp of the first frame of the capture. A graph keeps its buckets
+//! and the number of frames scanned, so that updating it after frames are
+//! appended only scans the new frames. Frames without a timestamp or with
+//! a timestamp before the first frame are not counted.
+//!
+//! If a frame has `frame.time_relative`, it is used instead of the
+//! timestamp, so that frames whose timestamps go backwards are put into
+//! buckets according to the timestamp policy of the analysis.
+
+use analysis;
+use frame::Frame;
+use genet_abi::{
+    layer::Layer,
+    token::Token,
+    variant::{Value, Variant},
+};
+use genet_filter::Filter;
+use store::Store;
+
+/// The aggregation of the frames or the values of a bucket.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Aggregation {
+    /// The number of frames.
+    Count,
+    Sum(Token),
+    Min(Token),
+    Max(Token),
+    Avg(Token),
+}
+
+impl Aggregation {
+    /// Creates a new Aggregation from its name, e.g. `sum`, and the ID of
+    /// the attribute, which is ignored for `count`.
+    pub fn new(name: &str, id: &str) -> Result<Aggregation, String> {
+        let id = Token::from(id);
+        match name {
+            "count" => Ok(Aggregation::Count),
+            "sum" => Ok(Aggregation::Sum(id)),
+            "min" => Ok(Aggregation::Min(id)),
+            "max" => Ok(Aggregation::Max(id)),
+            "avg" => Ok(Aggregation::Avg(id)),
+            _ => Err(format!("unknown aggregation: {}", name)),
+        }
+    }
+
+    fn id(&self) -> Option<Token> {
+        match self {
+            Aggregation::Count => None,
+            Aggregation::Sum(id)
+            | Aggregation::Min(id)
+            | Aggregation::Max(id)
+            | Aggregation::Avg(id) => Some(*id),
+        }
+    }
+}
+
+/// A bucket of a series.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Point {
+    /// The seconds from the first frame to the start of the bucket.
+    pub time: f64,
+    /// The aggregated value, or None if no frame in the bucket has the
+    /// attribute aggregated by `min`, `max` or `avg`.
+    pub value: Option<f64>,
+}
+
+#[derive(Clone, Default)]
+struct Bucket {
+    frames: u64,
+    values: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+/// A time series of the frames matching a filter.
+pub struct IoGraph {
+    interval: i128,
+    filter: Option<Filter>,
+    aggregation: Aggregation,
+    start: Option<i128>,
+    buckets: Vec<Bucket>,
+    scanned: usize,
+    generation: usize,
+}
+
+impl IoGraph {
+    /// Creates a new IoGraph with buckets of `interval` seconds.
+    pub fn new(interval: f64, filter: Option<Filter>, aggregation: Aggregation) -> IoGraph {
+        IoGraph {
+            interval: ((interval * 1e9) as i128).max(1),
+            filter,
+            aggregation,
+            start: None,
+            buckets: Vec::new(),
+            scanned: 0,
+            generation: 0,
+        }
+    }
+
+    /// Adds the frames appended to `store` since the last update.
+    ///
+    /// The buckets are computed again if the frames have been decoded again
+    /// since the last update.
+    pub fn update(&mut self, store: &Store) {
+        if self.generation != store.generation() {
+            self.generation = store.generation();
+            self.clear();
+        }
+        if self.start.is_none() {
+            self.start = store
+                .frames(0..1)
+                .first()
+                .and_then(|&frame| unsafe { (*frame).layers().first() })
+                .and_then(|root| analysis::timestamp(root));
+            if self.start.is_none() {
+                return;
+            }
+        }
+        let filter = self.filter.take();
+        self.scanned = store.scan_from(self.scanned, filter.as_ref(), |frame| {
+            self.add(frame);
+            true
+        });
+        self.filter = filter;
+    }
+
+    /// Adds a frame matching the filter. The timestamp of the first frame
+    /// added is used as the start unless the graph is updated from a store.
+    pub fn add(&mut self, frame: &Frame) {
+        let offset = match frame.layers().first().and_then(|root| self.offset(root)) {
+            Some(offset) => offset,
+            None => return,
+        };
+        if offset < 0 {
+            return;
+        }
+        let index = (offset / self.interval) as usize;
+        if self.buckets.len() <= index {
+            self.buckets.resize(index + 1, Bucket::default());
+        }
+        let bucket = &mut self.buckets[index];
+        bucket.frames += 1;
+        if let Some(id) = self.aggregation.id() {
+            for layer in frame.layers() {
+                let value = layer
+                    .attr(id)
+                    .and_then(|attr| attr.try_get(layer).ok())
+                    .and_then(|value: Variant| Value::<f64>::try_into(value).ok());
+                if let Some(value) = value {
+                    if bucket.values == 0 {
+                        bucket.min = value;
+                        bucket.max = value;
+                    } else {
+                        bucket.min = bucket.min.min(value);
+                        bucket.max = bucket.max.max(value);
+                    }
+                    bucket.values += 1;
+                    bucket.sum += value;
+                }
+            }
+        }
+    }
+
+    /// Returns the nanoseconds from the start to a frame.
+    fn offset(&mut self, root: &Layer) -> Option<i128> {
+        let relative = root
+            .attr("frame.time_relative")
+            .and_then(|attr| attr.try_get(root).ok())
+            .and_then(|value: Variant| Value::<f64>::try_into(value).ok());
+        if let Some(relative) = relative {
+            return Some((relative * 1e9).round() as i128);
+        }
+        let ts = analysis::timestamp(root)?;
+        let start = *self.start.get_or_insert(ts);
+        Some(ts - start)
+    }
+
+    /// Returns the buckets from the first frame to the last frame added.
+    pub fn points(&self) -> Vec<Point> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(index, bucket)| {
+                let value = match self.aggregation {
+                    Aggregation::Count => Some(bucket.frames as f64),
+                    Aggregation::Sum(_) => Some(bucket.sum),
+                    _ if bucket.values == 0 => None,
+                    Aggregation::Min(_) => Some(bucket.min),
+                    Aggregation::Max(_) => Some(bucket.max),
+                    Aggregation::Avg(_) => Some(bucket.sum / bucket.values as f64),
+                };
+                Point {
+                    time: (index as i128 * self.interval) as f64 / 1e9,
+                    value,
+                }
+            })
+            .collect()
+    }
+
+    /// Removes the buckets, so that the frames are scanned again.
+    pub fn clear(&mut self) {
+        self.start = None;
+        self.buckets.clear();
+        self.scanned = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use frame::Frame;
+    use iograph::{Aggregation, IoGraph, Point};
+    use test_util::{self, LayerBuilder};
+
+    fn root(msec: u64) -> LayerBuilder {
+        test_util::root()
+            .attr("link.timestamp.sec", 100 + msec / 1000)
+            .attr("link.timestamp.usec", msec % 1000 * 1000)
+    }
+
+    fn frame(msec: u64, len: Option<u64>) -> Frame {
+        let mut root = root(msec);
+        if let Some(len) = len {
+            root = root.attr("link.length", len);
+        }
+        test_util::frame(0, vec![root.build()])
+    }
+
+    fn values(graph: &IoGraph) -> Vec<Option<f64>> {
+        graph.points().into_iter().map(|p| p.value).collect()
+    }
+
+    #[test]
+    fn aggregation() {
+        let frames = vec![
+            frame(0, Some(60)),
+            frame(400, Some(1500)),
+            frame(1200, None),
+            frame(3100, Some(40)),
+        ];
+        let graph = |name: &str| {
+            let mut graph = IoGraph::new(1.0, None, Aggregation::new(name, "link.length").unwrap());
+            for frame in &frames {
+                graph.add(frame);
+            }
+            graph
+        };
+
+        assert_eq!(
+            graph("count").points()[..2],
+            [
+                Point {
+                    time: 0.0,
+                    value: Some(2.0),
+                },
+                Point {
+                    time: 1.0,
+                    value: Some(1.0),
+                },
+            ]
+        );
+        assert_eq!(
+            values(&graph("sum")),
+            vec![Some(1560.0), Some(0.0), Some(0.0), Some(40.0)]
+        );
+        assert_eq!(
+            values(&graph("min")),
+            vec![Some(60.0), None, None, Some(40.0)]
+        );
+        assert_eq!(values(&graph("max"))[0], Some(1500.0));
+        assert_eq!(values(&graph("avg"))[0], Some(780.0));
+        assert!(Aggregation::new("median", "").is_err());
+    }
+
+    #[test]
+    fn time_relative() {
+        let mut graph = IoGraph::new(1.0, None, Aggregation::Count);
+        let late = test_util::frame(0, vec![root(0).attr("frame.time_relative", 2.5).build()]);
+        graph.add(&frame(0, None));
+        graph.add(&late);
+        assert_eq!(values(&graph), vec![Some(1.0), Some(0.0), Some(1.0)]);
+    }
+
+    #[test]
+    fn incremental() {
+        let mut graph = IoGraph::new(0.5, None, Aggregation::Count);
+        graph.add(&frame(100, None));
+        assert_eq!(values(&graph), vec![Some(1.0)]);
+        graph.add(&frame(50, None));
+        graph.add(&frame(1100, None));
+        assert_eq!(values(&graph), vec![Some(1.0), Some(0.0), Some(1.0)]);
+        graph.clear();
+        assert!(graph.points().is_empty());
+    }
+}
@@ -0,0 +1,541 @@
+//! Decoders described by Kaitai Struct definitions.
+//!
+//! A `.ksy` file is translated into a data pattern (see `pattern`), and the
+//! types are expanded into nested structures. The attribute IDs follow the
+//! field names in camel case, e.g. `<meta.id>.header.bodyLen`.
+//!
+//! ```yaml
+//! meta:
+//!   id: foo
+//!   endian: be
+//!   -genet-typ: "@data:foo"
+//!   -genet-tables:
+//!     udp.port: [9999]
+//! seq:
+//!   - id: kind
+//!     type: u1
+//!     enum: kind
+//!   - id: len
+//!     type: u2
+//!   - id: body
+//!     type: str
+//!     size: len
+//!     encoding: UTF-8
+//!     if: kind == kind::request
+//! enums:
+//!   kind:
+//!     1: request
+//!     2: response
+//! ```
+//!
+//! The decoder reads the parent payloads of the type `-genet-typ`, or
+//! `@data:<meta.id>` without it, and registers it for the keys of
+//! `-genet-tables`.
+//!
+//! The supported subset is the fixed-size integer and float types, `str`
+//! and byte arrays with a `size` or `size-eos`, `contents`, user types,
+//! `enum`, `repeat: eos` and `repeat: expr`, and instances with a `pos`.
+//! Sizes, counts and positions are numbers or names of integer fields of
+//! the same type, and an `if` compares such a field with a number or an
+//! enum value. The `pos` of an instance is relative to the start of the
+//! type. Value instances are skipped, and the other keys which change the
+//! layout, e.g. `terminator` or `switch-on`, are rejected.
+
+use pattern::{parse_number, Condition, Endian, FieldPattern, Pattern, PatternDecoder, Ref};
+use serde_yaml::{self, Value};
+use std::{collections::HashMap, fs, io};
+
+/// The keys changing the layout in a way a data pattern cannot describe.
+const UNSUPPORTED_KEYS: &[&str] = &[
+    "terminator",
+    "consume",
+    "include",
+    "pad-right",
+    "process",
+    "io",
+    "repeat-until",
+];
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Loads a decoder from a `.ksy` file.
+pub fn from_file(path: &str) -> io::Result<PatternDecoder> {
+    from_bytes(&fs::read(path)?)
+}
+
+/// Loads a decoder from the YAML text of a Kaitai Struct definition.
+pub fn from_bytes(bytes: &[u8]) -> io::Result<PatternDecoder> {
+    PatternDecoder::new(translate(bytes)?)
+}
+
+/// Translates the YAML text of a Kaitai Struct definition into a data
+/// pattern.
+pub fn translate(bytes: &[u8]) -> io::Result<Pattern> {
+    let ksy: Value = serde_yaml::from_slice(bytes).map_err(|err| invalid(err.to_string()))?;
+    let meta = &ksy["meta"];
+    let name = meta["id"]
+        .as_str()
+        .ok_or_else(|| invalid("definition without meta/id".to_string()))?;
+    if !meta["imports"].is_null() {
+        return Err(invalid("imports are not supported".to_string()));
+    }
+    let layer = camel(name);
+    let endian = endian(&meta["endian"], &layer)?.unwrap_or_default();
+    let typ = meta["-genet-typ"]
+        .as_str()
+        .map(|typ| typ.to_string())
+        .unwrap_or_else(|| format!("@data:{}", layer));
+    let mut tables = HashMap::new();
+    if let Some(mapping) = meta["-genet-tables"].as_mapping() {
+        for (table, keys) in mapping {
+            let keys = keys
+                .as_sequence()
+                .map(|keys| keys.iter().filter_map(|key| key.as_u64()).collect())
+                .unwrap_or_default();
+            if let Some(table) = table.as_str() {
+                tables.insert(table.to_string(), keys);
+            }
+        }
+    }
+
+    let mut translator = Translator {
+        scopes: vec![&ksy],
+        stack: Vec::new(),
+    };
+    let fields = translator.fields(&ksy, &layer)?;
+    Ok(Pattern {
+        id: format!("kaitai-{}", name),
+        name: meta["title"].as_str().unwrap_or(name).to_string(),
+        description: ksy["doc"].as_str().unwrap_or_default().trim().to_string(),
+        layer,
+        typ,
+        tables,
+        endian,
+        fields,
+        payload: None,
+    })
+}
+
+fn endian(value: &Value, id: &str) -> io::Result<Option<Endian>> {
+    match value {
+        Value::Null => Ok(None),
+        Value::String(endian) if endian == "be" => Ok(Some(Endian::Big)),
+        Value::String(endian) if endian == "le" => Ok(Some(Endian::Little)),
+        _ => Err(invalid(format!("{}: unsupported endian", id))),
+    }
+}
+
+/// Converts a Kaitai Struct identifier into camel case, e.g. `body_len` to
+/// `bodyLen`.
+fn camel(name: &str) -> String {
+    let mut id = String::new();
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = !id.is_empty();
+        } else if upper {
+            id.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            id.push(c);
+        }
+    }
+    id
+}
+
+fn is_identifier(expr: &str) -> bool {
+    expr.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && expr.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns the pattern type of a primitive type, e.g. `uint16le` for `u2le`.
+fn primitive(typ: &str) -> Option<String> {
+    let (base, suffix) = if typ.ends_with("le") || typ.ends_with("be") {
+        typ.split_at(typ.len() - 2)
+    } else {
+        (typ, "")
+    };
+    let kind = match base {
+        "u1" | "s1" if !suffix.is_empty() => return None,
+        "u1" => "uint8",
+        "u2" => "uint16",
+        "u4" => "uint32",
+        "u8" => "uint64",
+        "s1" => "int8",
+        "s2" => "int16",
+        "s4" => "int32",
+        "s8" => "int64",
+        "f4" => "float32",
+        "f8" => "float64",
+        _ => return None,
+    };
+    Some(format!("{}{}", kind, suffix))
+}
+
+struct Translator<'a> {
+    /// The types enclosing the current one, from the outermost.
+    scopes: Vec<&'a Value>,
+    /// The names of the types being expanded, to reject recursive types.
+    stack: Vec<String>,
+}
+
+impl<'a> Translator<'a> {
+    /// Translates the sequence and the instances of the type `spec`.
+    fn fields(&mut self, spec: &'a Value, prefix: &str) -> io::Result<Vec<FieldPattern>> {
+        let mut fields = Vec::new();
+        if let Some(seq) = spec["seq"].as_sequence() {
+            for field in seq {
+                let name = field["id"]
+                    .as_str()
+                    .ok_or_else(|| invalid(format!("{}: field without id", prefix)))?;
+                fields.push(self.field(name, field, prefix)?);
+            }
+        }
+        if let Some(instances) = spec["instances"].as_mapping() {
+            for (name, instance) in instances {
+                let name = name.as_str().unwrap_or_default();
+                // Value instances are computed by expressions.
+                if !instance["value"].is_null() {
+                    continue;
+                }
+                let id = format!("{}.{}", prefix, camel(name));
+                if instance["pos"].is_null() {
+                    return Err(invalid(format!("{}: instance without pos", id)));
+                }
+                let mut field = self.field(name, instance, prefix)?;
+                field.offset = Some(self.reference(&id, &instance["pos"], prefix)?);
+                fields.push(field);
+            }
+        }
+        Ok(fields)
+    }
+
+    fn field(&mut self, name: &str, spec: &'a Value, prefix: &str) -> io::Result<FieldPattern> {
+        let id = format!("{}.{}", prefix, camel(name));
+        if let Some(key) = UNSUPPORTED_KEYS.iter().find(|key| !spec[**key].is_null()) {
+            return Err(invalid(format!("{}: {} is not supported", id, key)));
+        }
+        let mut field = FieldPattern {
+            id: id.clone(),
+            ..FieldPattern::default()
+        };
+        let size = &spec["size"];
+        let sized = !size.is_null() || spec["size-eos"].as_bool() == Some(true);
+        match &spec["type"] {
+            Value::Null if !spec["contents"].is_null() => {
+                field.kind = "bytes".to_string();
+                field.length = Some(Ref::Value(contents_len(&id, &spec["contents"])?));
+            }
+            Value::Null if sized => field.kind = "bytes".to_string(),
+            Value::Null => return Err(invalid(format!("{}: field without type or size", id))),
+            Value::String(typ) if typ == "str" && sized => field.kind = "utf8".to_string(),
+            Value::String(typ) if typ == "str" || typ == "strz" => {
+                return Err(invalid(format!("{}: string without size", id)))
+            }
+            Value::String(typ) => match primitive(typ) {
+                Some(kind) => field.kind = kind,
+                None => {
+                    field.kind = "struct".to_string();
+                    field.fields = self.user_type(&id, typ)?;
+                    let meta = &self.lookup("types", typ).unwrap_or(&Value::Null)["meta"];
+                    field.endian = endian(&meta["endian"], &id)?;
+                }
+            },
+            _ => return Err(invalid(format!("{}: switch-on is not supported", id))),
+        }
+        if !size.is_null() {
+            field.length = Some(self.reference(&id, size, prefix)?);
+        }
+        field.repeat = match spec["repeat"].as_str() {
+            None => None,
+            Some("eos") => Some(Ref::Field("eos".to_string())),
+            Some("expr") => Some(self.reference(&id, &spec["repeat-expr"], prefix)?),
+            Some(repeat) => {
+                return Err(invalid(format!(
+                    "{}: repeat {} is not supported",
+                    id, repeat
+                )))
+            }
+        };
+        if let Some(expr) = spec["if"].as_str() {
+            field.condition = Some(self.condition(&id, expr, prefix)?);
+        }
+        if let Some(name) = spec["enum"].as_str() {
+            field.enums = self
+                .enum_values(name)
+                .ok_or_else(|| invalid(format!("{}: unknown enum {}", id, name)))?
+                .into_iter()
+                .map(|(value, name)| (value.to_string(), camel(&name)))
+                .collect();
+        }
+        Ok(field)
+    }
+
+    /// Expands the user type `name` into the fields of a structure.
+    fn user_type(&mut self, id: &str, name: &str) -> io::Result<Vec<FieldPattern>> {
+        let index = (0..self.scopes.len())
+            .rev()
+            .find(|i| !self.scopes[*i]["types"][name].is_null())
+            .ok_or_else(|| invalid(format!("{}: unknown type {}", id, name)))?;
+        if self.stack.iter().any(|typ| typ == name) {
+            return Err(invalid(format!("{}: recursive type {}", id, name)));
+        }
+        let spec = &self.scopes[index]["types"][name];
+        // The names in the type resolve from the scope it is declared in.
+        let outer = self.scopes.split_off(index + 1);
+        self.scopes.push(spec);
+        self.stack.push(name.to_string());
+        let fields = self.fields(spec, id);
+        self.stack.pop();
+        self.scopes.pop();
+        self.scopes.extend(outer);
+        fields
+    }
+
+    /// Returns the type or the enum `name` in the innermost scope declaring
+    /// it.
+    fn lookup(&self, key: &str, name: &str) -> Option<&'a Value> {
+        self.scopes
+            .iter()
+            .rev()
+            .map(|scope| &scope[key][name])
+            .find(|value| !value.is_null())
+    }
+
+    fn enum_values(&self, name: &str) -> Option<Vec<(i64, String)>> {
+        let mapping = self.lookup("enums", name)?.as_mapping()?;
+        let values = mapping
+            .iter()
+            .filter_map(|(value, name)| {
+                let value = match value {
+                    Value::Number(n) => n.as_i64()?,
+                    Value::String(s) => parse_number(s)?,
+                    _ => return None,
+                };
+                let name = name.as_str().or_else(|| name["id"].as_str())?;
+                Some((value, name.to_string()))
+            })
+            .collect();
+        Some(values)
+    }
+
+    /// Resolves a size, a count or a position.
+    fn reference(&self, id: &str, expr: &Value, prefix: &str) -> io::Result<Ref> {
+        match expr {
+            Value::Number(n) if n.as_u64().is_some() => Ok(Ref::Value(n.as_u64().unwrap())),
+            Value::String(expr) => match parse_number(expr) {
+                Some(n) if n >= 0 => Ok(Ref::Value(n as u64)),
+                _ if is_identifier(expr.trim()) => {
+                    Ok(Ref::Field(format!("{}.{}", prefix, camel(expr.trim()))))
+                }
+                _ => Err(invalid(format!("{}: unsupported expression {}", id, expr))),
+            },
+            _ => Err(invalid(format!("{}: unsupported expression", id))),
+        }
+    }
+
+    /// Parses a condition comparing a field with a number or an enum value,
+    /// e.g. `version >= 2` or `kind == kind::request`.
+    fn condition(&self, id: &str, expr: &str, prefix: &str) -> io::Result<Condition> {
+        let unsupported = || invalid(format!("{}: unsupported expression {}", id, expr));
+        let (field, op, value) = ["==", "!=", "<=", ">=", "<", ">"]
+            .iter()
+            .filter_map(|op| expr.find(op).map(|pos| (pos, op)))
+            .min_by_key(|(pos, _)| *pos)
+            .map(|(pos, op)| (&expr[..pos], *op, &expr[pos + op.len()..]))
+            .unwrap_or((expr, "!=", "0"));
+        let (field, value) = (field.trim(), value.trim());
+        if !is_identifier(field) {
+            return Err(unsupported());
+        }
+        let value = match parse_number(value) {
+            Some(value) => value,
+            None => {
+                let mut path = value.splitn(2, "::");
+                let (name, member) = match (path.next(), path.next()) {
+                    (Some(name), Some(member)) => (name, member),
+                    _ => return Err(unsupported()),
+                };
+                self.enum_values(name)
+                    .and_then(|values| values.into_iter().find(|(_, m)| m == member))
+                    .map(|(value, _)| value)
+                    .ok_or_else(unsupported)?
+            }
+        };
+        Ok(Condition {
+            field: format!("{}.{}", prefix, camel(field)),
+            op: op.to_string(),
+            value,
+        })
+    }
+}
+
+/// Returns the length of the magic bytes `contents`, a string or an array
+/// of bytes and strings.
+fn contents_len(id: &str, contents: &Value) -> io::Result<u64> {
+    let len = |value: &Value| match value {
+        Value::String(s) => Some(s.len() as u64),
+        Value::Number(n) if n.as_u64().is_some_and(|n| n <= 0xff) => Some(1),
+        _ => None,
+    };
+    let len = match contents {
+        Value::Sequence(items) => items.iter().map(len).sum(),
+        _ => len(contents),
+    };
+    len.ok_or_else(|| invalid(format!("{}: invalid contents", id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use fnv::FnvHashMap;
+    use genet_abi::{
+        context::Context,
+        decoder::{Decoder, DecoderBox},
+        layer::{Layer, Parent, Payload},
+        slice::ByteSlice,
+        token::Token,
+        variant::Variant,
+    };
+    use kaitai;
+    use test_util;
+
+    const FOO: &str = r#"
+meta:
+  id: foo_proto
+  title: Foo
+  endian: be
+seq:
+  - id: magic
+    contents: [0xca, "FE"]
+  - id: version
+    type: u1
+  - id: msg_kind
+    type: u1
+    enum: msg_kind
+  - id: num_items
+    type: u2
+  - id: extra
+    type: s4le
+    if: version >= 2
+  - id: greeting
+    size: 2
+    type: str
+    encoding: ASCII
+    if: msg_kind == msg_kind::request
+  - id: items
+    type: item
+    repeat: expr
+    repeat-expr: num_items
+types:
+  item:
+    seq:
+      - id: name_len
+        type: u1
+      - id: name
+        type: str
+        size: name_len
+        encoding: UTF-8
+instances:
+  first:
+    pos: 3
+    type: u1
+enums:
+  msg_kind:
+    1: request
+    2:
+      id: server_reply
+"#;
+
+    fn decode<F: FnOnce(&Layer)>(data: &'static [u8], f: F) {
+        let decoder = kaitai::from_bytes(FOO.as_bytes()).unwrap();
+        assert_eq!(decoder.metadata().id, "kaitai-foo_proto");
+        assert_eq!(decoder.metadata().name, "Foo");
+        let mut decoder = DecoderBox::new(decoder);
+        let mut ctx = Context::new(FnvHashMap::default());
+        let mut worker = decoder.new_worker(&ctx);
+        let mut layer = test_util::layer("udp")
+            .payload(Payload::with_typ(
+                ByteSlice::from(data),
+                "@data:udp",
+                "@data:fooProto",
+            ))
+            .build();
+        let mut parent = Parent::from_mut_ref(&mut layer);
+        assert!(worker.decode(&mut ctx, &[], &mut parent).unwrap());
+        f(unsafe { &*parent.children()[0] });
+    }
+
+    #[test]
+    fn decode_fields() {
+        let data = b"\xcaFE\x02\x01\x00\x02\xfe\xff\xff\xffhi\x02ab\x01c";
+        decode(data, |layer| {
+            assert_eq!(layer.id(), Token::from("fooProto"));
+            let value = |id: &str| layer.attr(id).unwrap().try_get(layer).unwrap();
+            assert_eq!(value("fooProto.version"), Variant::UInt64(2));
+            assert_eq!(value("fooProto.msgKind.request"), Variant::Bool(true));
+            assert_eq!(value("fooProto.extra"), Variant::Int64(-2));
+            assert_eq!(value("fooProto.greeting"), Variant::String("hi".into()));
+            assert_eq!(value("fooProto.first"), Variant::UInt64(2));
+            let names = layer
+                .attrs()
+                .iter()
+                .filter(|attr| attr.id() == Token::from("fooProto.items.name"))
+                .map(|attr| attr.try_get(layer).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                names,
+                vec![Variant::String("ab".into()), Variant::String("c".into())]
+            );
+        });
+
+        // No extra field and greeting for version 1 and a reply.
+        decode(b"\xcaFE\x01\x02\x00\x01\x02ab", |layer| {
+            assert!(layer.attr("fooProto.extra").is_none());
+            assert!(layer.attr("fooProto.greeting").is_none());
+            assert!(layer.attr("fooProto.msgKind.serverReply").is_some());
+            assert!(layer.attr("fooProto.malformed").is_none());
+        });
+    }
+
+    #[test]
+    fn translate_errors() {
+        let translate = |seq: &str| {
+            let ksy = format!("meta:\n  id: foo\nseq:\n{}", seq);
+            kaitai::translate(ksy.as_bytes())
+                .err()
+                .map(|err| err.to_string())
+        };
+        assert_eq!(translate("  - id: a\n    type: u1\n"), None);
+        assert_eq!(
+            translate("  - id: a\n    type: strz\n    encoding: ASCII\n"),
+            Some("foo.a: string without size".to_string())
+        );
+        assert_eq!(
+            translate("  - id: a\n    size: 4\n    process: zlib\n"),
+            Some("foo.a: process is not supported".to_string())
+        );
+        assert_eq!(
+            translate("  - id: a\n    type: u1\n    if: _io.eof\n"),
+            Some("foo.a: unsupported expression _io.eof".to_string())
+        );
+        assert_eq!(
+            translate("  - id: a\n    type: b\n"),
+            Some("foo.a: unknown type b".to_string())
+        );
+        assert_eq!(
+            translate("  - id: a\n    type:\n      switch-on: x\n      cases: {}\n"),
+            Some("foo.a: switch-on is not supported".to_string())
+        );
+        let recursive = "meta:\n  id: foo\nseq:\n  - id: a\n    type: b\n\
+                         types:\n  b:\n    seq:\n      - id: c\n        type: b\n";
+        assert_eq!(
+            kaitai::translate(recursive.as_bytes())
+                .err()
+                .map(|err| err.to_string()),
+            Some("foo.a.c: recursive type b".to_string())
+        );
+        assert!(kaitai::translate(b"seq: []").is_err());
+    }
+}
@@ -0,0 +1,438 @@
+//! On-demand decoding with an LRU cache of decoded layer trees.
+//!
+//! In lazy mode, frames are stored with their link-layer envelope only, and
+//! the layer trees are decoded when frames are first read or filtered.
+//! Decoded trees beyond the cache capacity are evicted in least recently
+//! used order unless they are in a pinned range or held.
+//!
+//! Frames handed out with a `Hold`, e.g. to JavaScript objects, are never
+//! evicted. If their trees are cleared, the layers are freed once the last
+//! hold is dropped.
+//!
+//! The decoders and the cross-frame analysis see the frames in capture
+//! order, as in eager mode, so that e.g. stream reassembly gives the same
+//! results. The decoders are kept at a few positions in the capture between
+//! cache fills, and the frames from the nearest position before a missing
+//! frame are decoded again to bring the decoders up to it. Their trees are
+//! discarded unless they are missing from the cache.
+
+use analysis::Analyzer;
+use array_vec::ArrayVec;
+use decoder::dispatcher::Dispatcher;
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::{
+    decoder::{DecoderBox, ExecType},
+    fixed::MutFixed,
+    layer::Layer,
+};
+use parking_lot::{Mutex, MutexGuard, RwLock};
+use profile::Profile;
+use serde_json;
+use std::{collections::BTreeMap, fmt, ops::Range, sync::Arc};
+
+/// Enables lazy decoding if set to `true`.
+pub const LAZY_KEY: &str = "_.decode.lazy";
+
+/// The maximum number of decoded layer trees held in memory.
+pub const CACHE_SIZE_KEY: &str = "_.decode.cacheSize";
+
+const DEFAULT_CACHE_SIZE: usize = 65536;
+
+/// The maximum number of positions at which the decoders are kept.
+const MAX_CURSORS: usize = 4;
+
+/// Statistics of the decoded tree cache.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub capacity: usize,
+    pub decoded: usize,
+    pub pinned: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// The holds of a frame, and the trees cleared while it was held.
+#[derive(Default)]
+struct Held {
+    count: usize,
+    cleared: Vec<Vec<MutFixed<Layer>>>,
+}
+
+fn free_layers(layers: Vec<MutFixed<Layer>>) {
+    for layer in layers {
+        drop(unsafe { Box::from_raw(layer.as_mut_ptr()) });
+    }
+}
+
+/// Decoders which have seen the frames before `next` in capture order.
+struct Cursor {
+    pdisp: Dispatcher,
+    sdisp: Dispatcher,
+    analyzer: Analyzer,
+    next: usize,
+}
+
+// The workers are only used under the lock of the cache.
+unsafe impl Send for Cursor {}
+
+impl Cursor {
+    fn new(profile: &Profile) -> Cursor {
+        Cursor {
+            pdisp: Dispatcher::new(&ExecType::ParallelSync, profile),
+            sdisp: Dispatcher::new(&ExecType::SerialSync, profile),
+            analyzer: Analyzer::from_profile(profile),
+            next: 0,
+        }
+    }
+
+    /// Decodes the frame `index`, which must follow the frames passed before.
+    fn process(&mut self, profile: &Profile, frame: &mut Frame) {
+        self.pdisp.process_frame(frame);
+        self.sdisp.process_frame(frame);
+        if !profile.frame_flags().is_ignored(frame.index()) {
+            self.analyzer.process(frame);
+        }
+        self.next = frame.index() as usize + 1;
+    }
+}
+
+pub struct Cache {
+    profile: Profile,
+    /// The cursors from the least recently used.
+    cursors: Vec<Cursor>,
+    ticks: FnvHashMap<usize, u64>,
+    lru: BTreeMap<u64, usize>,
+    tick: u64,
+    pinned: FnvHashMap<u32, Range<usize>>,
+    held: FnvHashMap<usize, Held>,
+    stats: CacheStats,
+}
+
+impl Cache {
+    fn new(profile: Profile, capacity: usize) -> Cache {
+        Cache {
+            profile,
+            cursors: Vec::new(),
+            ticks: FnvHashMap::default(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            pinned: FnvHashMap::default(),
+            held: FnvHashMap::default(),
+            stats: CacheStats {
+                capacity,
+                ..CacheStats::default()
+            },
+        }
+    }
+
+    fn is_pinned(&self, index: usize) -> bool {
+        self.pinned.values().any(|range| range.contains(&index))
+    }
+
+    fn touch(&mut self, index: usize) {
+        if let Some(tick) = self.ticks.insert(index, self.tick) {
+            self.lru.remove(&tick);
+        }
+        self.lru.insert(self.tick, index);
+        self.tick += 1;
+    }
+
+    /// Decodes the frames in `range` which have no layer tree.
+    fn decode(&mut self, frames: &RwLock<ArrayVec<Frame>>, range: Range<usize>) {
+        let (start, missing) = {
+            let frames = frames.read();
+            let range = range.start..range.end.min(frames.len());
+            let missing = range
+                .filter(|index| !self.ticks.contains_key(index) && frames.get(*index).is_some())
+                .collect::<Vec<_>>();
+            (frames.start(), missing)
+        };
+        let len = frames.read().len();
+        let range = range.start..range.end.min(len);
+        self.stats.hits += (range.len() - missing.len()) as u64;
+        self.stats.misses += missing.len() as u64;
+
+        if let (Some(first), Some(last)) = (missing.first(), missing.last()) {
+            let mut cursor = self.cursor(*first);
+            let roots = {
+                let frames = frames.read();
+                (cursor.next.max(start)..=*last)
+                    .filter_map(|index| {
+                        let root = &frames.get(index)?.layers()[0];
+                        Some((index, unsafe { MutFixed::from_ptr(root.as_mut_ptr()) }))
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let mut decoded = Vec::new();
+            for (index, root) in roots {
+                if missing.binary_search(&index).is_ok() {
+                    let mut frame = Frame::new(index as u32, root);
+                    cursor.process(&self.profile, &mut frame);
+                    decoded.push(frame);
+                } else {
+                    // The tree in the store may be read by others, so a copy
+                    // of the root is decoded and discarded.
+                    let root = MutFixed::new(root.with_data(root.data()));
+                    let mut frame = Frame::new(index as u32, root);
+                    cursor.process(&self.profile, &mut frame);
+                    free_layers(frame.fetch_layers());
+                }
+            }
+            self.cursors.push(cursor);
+
+            self.profile.resolver().update(&mut decoded);
+            self.profile.geoip().update(&mut decoded);
+            self.profile.expert().update(&mut decoded);
+            self.profile.comparison().update(&mut decoded);
+            self.profile.catalog().update(&decoded);
+            let mut frames = frames.write();
+            for mut frame in decoded {
+                if let Some(f) = frames.get_mut(frame.index() as usize) {
+                    f.set_layers(frame.fetch_layers());
+                    f.set_tree_indices(frame.fetch_tree_indices());
+                }
+            }
+        }
+
+        for index in range.clone() {
+            self.touch(index);
+        }
+        self.evict(frames, &range);
+    }
+
+    /// Takes the cursor nearest before the frame `index`, or a new cursor at
+    /// the start of the capture.
+    fn cursor(&mut self, index: usize) -> Cursor {
+        let nearest = self
+            .cursors
+            .iter()
+            .enumerate()
+            .filter(|(_, cursor)| cursor.next <= index)
+            .max_by_key(|(_, cursor)| cursor.next)
+            .map(|(pos, _)| pos);
+        match nearest {
+            Some(pos) => self.cursors.remove(pos),
+            None => {
+                if self.cursors.len() >= MAX_CURSORS {
+                    self.cursors.remove(0);
+                }
+                Cursor::new(&self.profile)
+            }
+        }
+    }
+
+    /// Evicts the least recently used trees outside `keep` and the pinned
+    /// ranges until the cache fits in its capacity.
+    fn evict(&mut self, frames: &RwLock<ArrayVec<Frame>>, keep: &Range<usize>) {
+        if self.ticks.len() <= self.stats.capacity {
+            return;
+        }
+        let mut excess = self.ticks.len() - self.stats.capacity;
+        let victims = self
+            .lru
+            .iter()
+            .filter(|(_, index)| {
+                !keep.contains(*index)
+                    && !self.is_pinned(**index)
+                    && !self.held.contains_key(*index)
+            })
+            .take_while(|_| {
+                let more = excess > 0;
+                excess = excess.saturating_sub(1);
+                more
+            })
+            .map(|(tick, index)| (*tick, *index))
+            .collect::<Vec<_>>();
+
+        let mut frames = frames.write();
+        for (tick, index) in victims {
+            self.lru.remove(&tick);
+            self.ticks.remove(&index);
+            self.stats.evictions += 1;
+            if let Some(frame) = frames.get_mut(index) {
+                free_layers(Self::take_tree(frame));
+            }
+        }
+    }
+
+    /// Removes the decoded tree from `frame` and returns its layers.
+    fn take_tree(frame: &mut Frame) -> Vec<MutFixed<Layer>> {
+        let mut layers = frame.fetch_layers();
+        let tree = layers.split_off(1);
+        frame.set_layers(layers);
+        frame.set_tree_indices(Vec::new());
+        tree
+    }
+
+    /// Evicts all decoded trees, e.g. after the decoders have changed.
+    ///
+    /// The trees of held frames are cleared too, so that they are decoded
+    /// again, but their layers are freed when the frames are released.
+    fn clear(&mut self, frames: &RwLock<ArrayVec<Frame>>) {
+        self.cursors.clear();
+        let capacity = self.stats.capacity;
+        self.stats.capacity = 0;
+        self.evict(frames, &(0..0));
+        self.stats.capacity = capacity;
+
+        let indices = self
+            .held
+            .keys()
+            .filter(|index| self.ticks.contains_key(index) && !self.is_pinned(**index))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut frames = frames.write();
+        for index in indices {
+            if let Some(tick) = self.ticks.remove(&index) {
+                self.lru.remove(&tick);
+            }
+            self.stats.evictions += 1;
+            if let (Some(frame), Some(held)) = (frames.get_mut(index), self.held.get_mut(&index)) {
+                held.cleared.push(Self::take_tree(frame));
+            }
+        }
+    }
+
+    fn release_hold(&mut self, index: usize) {
+        let released = match self.held.get_mut(&index) {
+            Some(held) => {
+                held.count -= 1;
+                held.count == 0
+            }
+            None => false,
+        };
+        if released {
+            if let Some(held) = self.held.remove(&index) {
+                held.cleared.into_iter().for_each(free_layers);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            decoded: self.ticks.len(),
+            pinned: self.pinned.values().map(|range| range.len()).sum(),
+            ..self.stats
+        }
+    }
+}
+
+/// Keeps a decoded frame from being evicted until dropped.
+pub struct Hold {
+    cache: Arc<Mutex<Cache>>,
+    index: usize,
+}
+
+impl fmt::Debug for Hold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Hold {}", self.index)
+    }
+}
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        self.cache.lock().release_hold(self.index);
+    }
+}
+
+/// A shared handle to the decoded tree cache.
+#[derive(Clone)]
+pub struct Lazy {
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl fmt::Debug for Lazy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Lazy")
+    }
+}
+
+impl Lazy {
+    /// Returns a cache if lazy decoding is enabled in the profile.
+    pub fn from_profile(profile: &Profile) -> Option<Lazy> {
+        let config = |key| {
+            profile
+                .get_config(key)
+                .and_then(|value| serde_json::from_str::<serde_json::Value>(&value).ok())
+        };
+        if config(LAZY_KEY).and_then(|value| value.as_bool()) != Some(true) {
+            return None;
+        }
+        let capacity = config(CACHE_SIZE_KEY)
+            .and_then(|value| value.as_u64())
+            .map_or(DEFAULT_CACHE_SIZE, |size| size as usize);
+        Some(Lazy {
+            cache: Arc::new(Mutex::new(Cache::new(profile.clone(), capacity))),
+        })
+    }
+
+    /// Decodes the frames in `range` and returns a guard which keeps the
+    /// cache from evicting them until it is dropped.
+    pub fn decode(
+        &self,
+        frames: &RwLock<ArrayVec<Frame>>,
+        range: Range<usize>,
+    ) -> MutexGuard<'_, Cache> {
+        let mut cache = self.cache.lock();
+        cache.decode(frames, range);
+        cache
+    }
+
+    /// Keeps the frame `index` decoded until the returned hold is dropped.
+    ///
+    /// `cache` is the guard returned by `decode`, so that the frame can't be
+    /// evicted between decoding and holding it.
+    pub fn hold(&self, cache: &mut Cache, index: usize) -> Hold {
+        cache.held.entry(index).or_default().count += 1;
+        Hold {
+            cache: self.cache.clone(),
+            index,
+        }
+    }
+
+    pub fn clear(&self, frames: &RwLock<ArrayVec<Frame>>) {
+        self.cache.lock().clear(frames);
+    }
+
+    /// Moves the frames before `start` out of `frames`, and forgets their
+    /// decoded trees.
+    pub fn release(&self, frames: &RwLock<ArrayVec<Frame>>, start: usize) -> Vec<Frame> {
+        let mut cache = self.cache.lock();
+        let released = frames.write().release_front(start);
+        for frame in &released {
+            if let Some(tick) = cache.ticks.remove(&(frame.index() as usize)) {
+                cache.lru.remove(&tick);
+            }
+        }
+        released
+    }
+
+    /// Updates the config used by the decoders created for the next frames.
+    pub fn update_config(&self, key: &str, value: &str) {
+        let mut cache = self.cache.lock();
+        cache.profile.update_config(key, value);
+        cache.cursors.clear();
+    }
+
+    /// Replaces the decoders used for the next frames.
+    pub fn set_decoders(&self, decoders: Vec<DecoderBox>) {
+        let mut cache = self.cache.lock();
+        cache.profile.set_decoders(decoders);
+        cache.cursors.clear();
+    }
+
+    /// Keeps the frames in `range` decoded until unpinned.
+    pub fn pin(&self, id: u32, range: Range<usize>) {
+        self.cache.lock().pinned.insert(id, range);
+    }
+
+    pub fn unpin(&self, id: u32) {
+        self.cache.lock().pinned.remove(&id);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.cache.lock().stats()
+    }
+}
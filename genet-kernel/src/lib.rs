@@ -1,25 +1,76 @@
+extern crate byteorder;
 extern crate crossbeam_channel;
+extern crate ed25519_compact;
+extern crate flate2;
 extern crate fnv;
 extern crate genet_abi;
 extern crate genet_filter;
+#[cfg(feature = "napi")]
 extern crate genet_napi;
 extern crate libc;
 extern crate libloading;
+extern crate maxminddb;
 extern crate num_cpus;
 extern crate parking_lot;
+extern crate roaring;
 extern crate serde;
 extern crate serde_json;
+extern crate serde_yaml;
+
+extern crate genet_sdk;
+#[cfg(feature = "wasm")]
+extern crate wasmtime;
 
 #[macro_use]
 extern crate serde_derive;
 
+pub mod annotations;
+#[cfg(feature = "napi")]
 pub mod binding;
+pub mod capture;
+pub mod catalog;
+pub mod coloring;
+pub mod columns;
+pub mod compare;
+pub mod conversation;
+pub mod decode_as;
+pub mod diff;
+pub mod expert;
+pub mod extract;
+pub mod flags;
+pub mod geoip;
+pub mod index;
+pub mod iograph;
+pub mod kaitai;
+pub mod lazy;
+pub mod link;
+pub mod options;
+pub mod patch;
+pub mod pattern;
 pub mod profile;
+pub mod resolver;
+pub mod ring;
+pub mod saved;
 pub mod session;
+pub mod session_file;
+pub mod signature;
+pub mod sort;
+pub mod spill;
+pub mod stats;
+pub mod stream;
+pub mod time_shift;
+pub mod tree;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+mod analysis;
 mod array_vec;
+mod column;
 mod decoder;
 mod frame;
 mod io;
+mod refilter;
 mod result;
 mod store;
+#[cfg(test)]
+mod test_util;
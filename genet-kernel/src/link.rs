@@ -0,0 +1,109 @@
+use fnv::FnvHashMap;
+use genet_abi::{
+    fixed::{Fixed, MutFixed},
+    layer::{Layer, LayerClass, Payload},
+    token::Token,
+};
+use serde_json;
+use std::sync::Arc;
+
+/// Config key for user-defined link type mappings.
+///
+/// Each entry has the form `<link type>=<id>`, e.g. `147=[link-1]` or
+/// `148=@data:ipv4`.
+pub const USER_DLT_KEY: &str = "_.link.userDlt";
+
+const USER_LINK_ID: &str = "[user-link]";
+
+#[derive(Clone, Debug)]
+struct Target {
+    class: Fixed<LayerClass>,
+    payload: Option<Token>,
+}
+
+/// Maps link types to starting decoders.
+///
+/// A root layer with a mapped link type gets a pseudo child layer covering
+/// the whole frame: either a link layer with the target ID (`[link-N]`),
+/// or a `[user-link]` layer with a payload of the target ID.
+#[derive(Clone, Default, Debug)]
+pub struct LinkMap {
+    map: Arc<FnvHashMap<Token, Target>>,
+}
+
+impl LinkMap {
+    pub fn new() -> LinkMap {
+        Self::default()
+    }
+
+    /// Creates a new LinkMap from the JSON-encoded value of `_.link.userDlt`.
+    pub fn from_config(value: &str) -> LinkMap {
+        let entries: Vec<String> = serde_json::from_str(value).unwrap_or_default();
+        let mut map = FnvHashMap::default();
+        for entry in entries {
+            let mut pair = entry.splitn(2, '=').map(|s| s.trim());
+            let link = pair.next().and_then(|link| link.parse::<u32>().ok());
+            let target = pair.next().filter(|id| !id.is_empty());
+            if let (Some(link), Some(target)) = (link, target) {
+                let target = if target.starts_with('[') {
+                    Target {
+                        class: Fixed::new(LayerClass::builder(target).build()),
+                        payload: None,
+                    }
+                } else {
+                    Target {
+                        class: Fixed::new(LayerClass::builder(USER_LINK_ID).build()),
+                        payload: Some(Token::from(target)),
+                    }
+                };
+                map.insert(Token::from(format!("[link-{}]", link)), target);
+            }
+        }
+        LinkMap { map: Arc::new(map) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the pseudo layer for `root` if its link type is mapped.
+    pub fn layer(&self, root: &Layer) -> Option<MutFixed<Layer>> {
+        self.map.get(&root.id()).map(|target| {
+            let mut layer = Layer::new(target.class.clone(), root.data());
+            if let Some(id) = target.payload {
+                layer.add_payload(Payload::new(root.data(), id));
+            }
+            MutFixed::new(layer)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{slice::ByteSlice, token::Token};
+    use link::LinkMap;
+    use test_util;
+
+    #[test]
+    fn from_config() {
+        let map = LinkMap::from_config(r#"["147=[link-1]", "148 = @data:ipv4", "x=eth", "149"]"#);
+        let root = |id| {
+            test_util::layer(id)
+                .data(ByteSlice::from(vec![1, 2, 3]))
+                .build()
+        };
+
+        let layer = map.layer(&root("[link-147]")).unwrap();
+        assert_eq!(layer.id(), Token::from("[link-1]"));
+        assert_eq!(layer.data().len(), 3);
+        assert!(layer.payloads().is_empty());
+
+        let layer = map.layer(&root("[link-148]")).unwrap();
+        assert_eq!(layer.id(), Token::from("[user-link]"));
+        assert_eq!(layer.payloads()[0].id(), Token::from("@data:ipv4"));
+
+        assert!(map.layer(&root("[link-149]")).is_none());
+        assert!(map.layer(&root("[link-1]")).is_none());
+        assert!(LinkMap::from_config("").is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+//! Typed config values declared by the decoders.
+//!
+//! A decoder lists its options in `Metadata::options`. The default value of
+//! an option is used until the session sets another one, and a value is
+//! checked against the type of the option before it is set.
+
+use fnv::FnvHashMap;
+use genet_abi::decoder::{DecoderBox, DecoderOption, OptionType};
+use serde_json::{self, Value};
+use std::io::{Error, ErrorKind, Result};
+
+/// The options declared by the decoders of a profile.
+#[derive(Clone, Default, Debug)]
+pub struct Options {
+    options: FnvHashMap<String, DecoderOption>,
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options::default()
+    }
+
+    /// Adds the options of `decoder`. An option declared twice keeps the
+    /// first declaration.
+    pub fn add(&mut self, decoder: &DecoderBox) {
+        for option in decoder.metadata().options {
+            self.options.entry(option.key.clone()).or_insert(option);
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&DecoderOption> {
+        self.options.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DecoderOption> {
+        self.options.values()
+    }
+
+    /// Returns the default value of the option `key` in JSON.
+    pub fn default_value(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(|option| option.default.as_str())
+    }
+
+    /// Checks `value` against the type of the option `key`. Keys without a
+    /// declared option accept any value.
+    pub fn validate(&self, key: &str, value: &str) -> Result<()> {
+        let option = match self.options.get(key) {
+            Some(option) => option,
+            None => return Ok(()),
+        };
+        let invalid = |reason: &str| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid value for {}: {}", key, reason),
+            )
+        };
+        let value: Value = serde_json::from_str(value).map_err(|_| invalid("not JSON"))?;
+        match &option.typ {
+            OptionType::Bool if value.is_boolean() => Ok(()),
+            OptionType::Bool => Err(invalid("expected a boolean")),
+            OptionType::Int { min, max } => match value.as_i64() {
+                Some(v) if v >= *min && v <= *max => Ok(()),
+                Some(_) => Err(invalid(&format!("expected {}..={}", min, max))),
+                None => Err(invalid("expected an integer")),
+            },
+            OptionType::Enum(values) => match value.as_str() {
+                Some(v) if values.iter().any(|e| e == v) => Ok(()),
+                _ => Err(invalid(&format!("expected one of {}", values.join(", ")))),
+            },
+            OptionType::Path | OptionType::String if value.is_string() => Ok(()),
+            OptionType::Path | OptionType::String => Err(invalid("expected a string")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{
+        context::Context,
+        decoder::{Decoder, DecoderBox, DecoderOption, Metadata, Worker},
+    };
+    use options::Options;
+    use profile::Profile;
+
+    #[derive(Clone)]
+    struct OptionDecoder {}
+
+    impl Decoder for OptionDecoder {
+        fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+            unimplemented!()
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                options: vec![
+                    DecoderOption::bool("test.bool", true),
+                    DecoderOption::int("test.int", 1..=8, 4),
+                    DecoderOption::enumeration("test.enum", &["low", "high"], "low"),
+                    DecoderOption::path("test.path"),
+                ],
+                ..Metadata::default()
+            }
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let mut options = Options::new();
+        options.add(&DecoderBox::new(OptionDecoder {}));
+        assert_eq!(options.default_value("test.int"), Some("4"));
+        assert_eq!(options.default_value("test.path"), Some(r#""""#));
+        assert_eq!(options.default_value("test.none"), None);
+
+        let valid = |key, value| options.validate(key, value).is_ok();
+        assert!(valid("test.bool", "false"));
+        assert!(!valid("test.bool", "1"));
+        assert!(valid("test.int", "8"));
+        assert!(!valid("test.int", "9"));
+        assert!(!valid("test.int", r#""4""#));
+        assert!(valid("test.enum", r#""high""#));
+        assert!(!valid("test.enum", r#""medium""#));
+        assert!(valid("test.path", r#""/tmp/keys""#));
+        assert!(!valid("test.path", "/tmp/keys"));
+        assert!(valid("test.none", "anything"));
+    }
+
+    #[test]
+    fn defaults() {
+        let mut profile = Profile::new();
+        profile.add_decoder(DecoderBox::new(OptionDecoder {}));
+        profile.update_config("test.int", "2");
+        let ctx = profile.context();
+        assert_eq!(ctx.get_config("test.int"), "2");
+        assert_eq!(ctx.get_config("test.enum"), r#""low""#);
+        assert_eq!(profile.get_config("test.bool"), Some("true".to_string()));
+    }
+}
@@ -0,0 +1,50 @@
+/// A byte patch applied to a copy of a frame.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Patch {
+    pub offset: usize,
+    pub data: Vec<u8>,
+}
+
+/// Returns a copy of `data` with `patches` applied in order.
+///
+/// Returns `None` if a patch does not fit in `data`.
+pub fn apply(data: &[u8], patches: &[Patch]) -> Option<Vec<u8>> {
+    let mut data = data.to_vec();
+    for patch in patches {
+        let end = patch.offset.checked_add(patch.data.len())?;
+        data.get_mut(patch.offset..end)?
+            .copy_from_slice(&patch.data);
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use patch::{apply, Patch};
+
+    #[test]
+    fn apply_patches() {
+        let data = [0u8; 4];
+        let patches = vec![
+            Patch {
+                offset: 1,
+                data: vec![0x45, 0x00],
+            },
+            Patch {
+                offset: 2,
+                data: vec![0xff],
+            },
+        ];
+        assert_eq!(apply(&data, &patches), Some(vec![0, 0x45, 0xff, 0]));
+        assert_eq!(
+            apply(
+                &data,
+                &[Patch {
+                    offset: 3,
+                    data: vec![1, 2],
+                }]
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,771 @@
+//! Decoders described by data patterns.
+//!
+//! A data pattern is a JSON file describing the layout of a layer, for
+//! formats without a decoder written in Rust. The pattern is compiled into
+//! attribute classes when loaded, and the fields are read in order from
+//! the payload of the parent layer.
+//!
+//! ```json
+//! {
+//!   "id": "pattern-foo",
+//!   "name": "Foo",
+//!   "layer": "foo",
+//!   "typ": "@data:foo",
+//!   "tables": { "udp.port": [9999] },
+//!   "fields": [
+//!     { "id": "foo.version", "type": "uint8" },
+//!     { "id": "foo.kind", "type": "uint8", "enum": { "1": "request", "2": "response" } },
+//!     { "id": "foo.flags", "type": "uint8", "flags": { "0x80": "last" } },
+//!     { "id": "foo.count", "type": "uint16" },
+//!     { "id": "foo.extra", "type": "uint32le", "if": { "field": "foo.version", "op": ">=", "value": 2 } },
+//!     { "id": "foo.item", "type": "struct", "repeat": "foo.count", "fields": [
+//!       { "id": "foo.item.length", "type": "uint8" },
+//!       { "id": "foo.item.name", "type": "utf8", "length": "foo.item.length" }
+//!     ] }
+//!   ],
+//!   "payload": { "id": "@data:foo", "typ": "@data:bar" }
+//! }
+//! ```
+//!
+//! The decoder reads the parent payloads of the type `typ`, and registers
+//! it for the keys of `tables`. The type of a field is a cast of
+//! `dynamic::CastKind`, e.g. `uint16le`, an integer or a float type taking
+//! the endianness of the field or the pattern, `utf8`, `bytes` or `struct`.
+//!
+//! - `length` is the length of a `utf8`, `bytes` or `struct` field, as a
+//!   number or the ID of a preceding integer field. The field spans the
+//!   rest of the data without it.
+//! - `repeat` repeats the field by a number, the value of a preceding
+//!   integer field or `eos` until the end of the data.
+//! - `if` reads the field only if the value of a preceding integer field
+//!   satisfies the condition. The operators are `==`, `!=`, `<`, `<=`, `>`,
+//!   `>=` and `&`, which tests a bit mask.
+//! - `offset` reads the field at the offset from the start of the enclosing
+//!   structure, as a number or the ID of a preceding integer field, without
+//!   moving the position of the following fields.
+//! - `enum` and `flags` add an attribute `<id>.<name>` for the matching
+//!   value and for each bit set.
+//!
+//! The value of an integer field referred to by another one is the last
+//! value read. A layer exceeding the data is marked with `<layer>.malformed`.
+
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    context::Context,
+    decoder::{self, Decoder, ExecType, Status},
+    error::Error,
+    fixed::Fixed,
+    layer::{Layer, LayerClass, LayerStack, Parent, Payload},
+    result::Result,
+    slice::TryGet,
+    token::Token,
+};
+use genet_sdk::dynamic::{CastKind, Classes};
+use serde_json;
+use std::{collections::HashMap, fs, io, ops::Range, sync::Arc};
+
+/// Nested structures deeper than this are rejected when compiled.
+const MAX_DEPTH: usize = 16;
+
+/// A data pattern.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Pattern {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// The ID of the layer.
+    pub layer: String,
+    /// The type of the parent payloads to decode.
+    pub typ: String,
+    /// The keys to register in dissector tables by table ID.
+    pub tables: HashMap<String, Vec<u64>>,
+    pub endian: Endian,
+    pub fields: Vec<FieldPattern>,
+    /// The payload carrying the data following the fields.
+    pub payload: Option<PayloadPattern>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    #[default]
+    Big,
+    Little,
+}
+
+/// A field of a data pattern.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FieldPattern {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The type of the attribute, e.g. `@ipv4:addr`.
+    pub typ: String,
+    pub endian: Option<Endian>,
+    pub length: Option<Ref>,
+    pub repeat: Option<Ref>,
+    pub offset: Option<Ref>,
+    #[serde(rename = "if")]
+    pub condition: Option<Condition>,
+    #[serde(rename = "enum")]
+    pub enums: HashMap<String, String>,
+    pub flags: HashMap<String, String>,
+    pub fields: Vec<FieldPattern>,
+}
+
+/// A number, or the value of a preceding field.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Ref {
+    Value(u64),
+    Field(String),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Condition {
+    pub field: String,
+    #[serde(default = "default_op")]
+    pub op: String,
+    pub value: i64,
+}
+
+fn default_op() -> String {
+    "==".to_string()
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PayloadPattern {
+    pub id: String,
+    #[serde(default)]
+    pub typ: String,
+}
+
+/// A value of a compiled field.
+enum Kind {
+    /// An integer of the size, read as signed or not.
+    Int(usize, bool, Endian),
+    Float(usize),
+    Utf8,
+    Bytes,
+    Struct(Vec<Node>),
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Mask,
+}
+
+enum Count {
+    Value(u64),
+    Field(String),
+    Eos,
+}
+
+/// A compiled field.
+struct Node {
+    id: String,
+    class: Fixed<AttrClass>,
+    kind: Kind,
+    length: Option<Count>,
+    repeat: Option<Count>,
+    offset: Option<Count>,
+    condition: Option<(String, Op, i64)>,
+    enums: Vec<(i64, Fixed<AttrClass>)>,
+    flags: Vec<(u64, Fixed<AttrClass>)>,
+}
+
+struct Compiled {
+    pattern: Pattern,
+    layer: Fixed<LayerClass>,
+    malformed: Fixed<AttrClass>,
+    nodes: Vec<Node>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub(crate) fn parse_number(s: &str) -> Option<i64> {
+    let s = s.trim();
+    if s.starts_with("0x") || s.starts_with("0X") {
+        u64::from_str_radix(&s[2..], 16).ok().map(|n| n as i64)
+    } else {
+        s.parse::<i64>().ok()
+    }
+}
+
+struct Compiler {
+    classes: Classes,
+    /// The integer fields declared so far.
+    ints: Vec<String>,
+}
+
+impl Compiler {
+    fn nodes(
+        &mut self,
+        fields: &[FieldPattern],
+        endian: Endian,
+        depth: usize,
+    ) -> io::Result<Vec<Node>> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("structures nested too deep".to_string()));
+        }
+        fields
+            .iter()
+            .map(|field| self.node(field, endian, depth))
+            .collect()
+    }
+
+    fn count(&self, id: &str, value: &Ref, eos: bool) -> io::Result<Count> {
+        match value {
+            Ref::Value(n) => Ok(Count::Value(*n)),
+            Ref::Field(name) if eos && name == "eos" => Ok(Count::Eos),
+            Ref::Field(name) => self.int_field(id, name).map(Count::Field),
+        }
+    }
+
+    fn int_field(&self, id: &str, name: &str) -> io::Result<String> {
+        if self.ints.iter().any(|int| int == name) {
+            Ok(name.to_string())
+        } else {
+            Err(invalid(format!("{}: unknown integer field {}", id, name)))
+        }
+    }
+
+    fn node(&mut self, field: &FieldPattern, endian: Endian, depth: usize) -> io::Result<Node> {
+        let id = &field.id;
+        if id.is_empty() {
+            return Err(invalid("field without id".to_string()));
+        }
+        let endian = field.endian.unwrap_or(endian);
+        let suffix = match endian {
+            Endian::Big => "be",
+            Endian::Little => "le",
+        };
+        let cast = field
+            .kind
+            .parse::<CastKind>()
+            .or_else(|_| format!("{}{}", field.kind, suffix).parse::<CastKind>());
+        let (kind, cast) = match (field.kind.as_str(), cast) {
+            ("struct", _) => {
+                let nodes = self.nodes(&field.fields, endian, depth + 1)?;
+                (Kind::Struct(nodes), CastKind::None)
+            }
+            (_, Ok(CastKind::Utf8)) => (Kind::Utf8, CastKind::Utf8),
+            (_, Ok(CastKind::Bytes)) => (Kind::Bytes, CastKind::Bytes),
+            (_, Ok(cast)) => match int_kind(cast) {
+                Some((size, signed, Some(endian))) => (Kind::Int(size, signed, endian), cast),
+                Some((size, _, None)) => (Kind::Float(size), cast),
+                None => return Err(invalid(format!("{}: unknown type {}", id, field.kind))),
+            },
+            _ => return Err(invalid(format!("{}: unknown type {}", id, field.kind))),
+        };
+
+        let length = match (&kind, &field.length) {
+            (Kind::Utf8, Some(length))
+            | (Kind::Bytes, Some(length))
+            | (Kind::Struct(_), Some(length)) => Some(self.count(id, length, false)?),
+            (_, Some(_)) => return Err(invalid(format!("{}: fixed-size type with length", id))),
+            _ => None,
+        };
+        let repeat = match &field.repeat {
+            Some(repeat) => Some(self.count(id, repeat, true)?),
+            None => None,
+        };
+        let offset = match &field.offset {
+            Some(offset) => Some(self.count(id, offset, false)?),
+            None => None,
+        };
+        let condition = match &field.condition {
+            Some(cond) => {
+                let op = match cond.op.as_str() {
+                    "==" => Op::Eq,
+                    "!=" => Op::Ne,
+                    "<" => Op::Lt,
+                    "<=" => Op::Le,
+                    ">" => Op::Gt,
+                    ">=" => Op::Ge,
+                    "&" => Op::Mask,
+                    op => return Err(invalid(format!("{}: unknown operator {}", id, op))),
+                };
+                Some((self.int_field(id, &cond.field)?, op, cond.value))
+            }
+            None => None,
+        };
+
+        let is_int = matches!(kind, Kind::Int(..));
+        if !is_int && (!field.enums.is_empty() || !field.flags.is_empty()) {
+            return Err(invalid(format!("{}: enum or flags on a non-integer", id)));
+        }
+        let mut enums = Vec::new();
+        for (value, name) in &field.enums {
+            let value = parse_number(value)
+                .ok_or_else(|| invalid(format!("{}: invalid enum value {}", id, value)))?;
+            let class = self
+                .classes
+                .attr(&format!("{}.{}", id, name), "@novalue", CastKind::None);
+            enums.push((value, class));
+        }
+        enums.sort_by_key(|(value, _)| *value);
+        let mut flags = Vec::new();
+        for (mask, name) in &field.flags {
+            let mask = parse_number(mask)
+                .ok_or_else(|| invalid(format!("{}: invalid flag {}", id, mask)))?;
+            let class = self
+                .classes
+                .attr(&format!("{}.{}", id, name), "", CastKind::None);
+            flags.push((mask as u64, class));
+        }
+        flags.sort_by_key(|(mask, _)| *mask);
+
+        let typ = if !field.typ.is_empty() {
+            field.typ.as_str()
+        } else if !enums.is_empty() {
+            "@enum"
+        } else if !flags.is_empty() {
+            "@flags"
+        } else if let Kind::Struct(_) = kind {
+            "@nested"
+        } else {
+            ""
+        };
+        if is_int {
+            self.ints.push(id.clone());
+        }
+        Ok(Node {
+            id: id.clone(),
+            class: self.classes.attr(id, typ, cast),
+            kind,
+            length,
+            repeat,
+            offset,
+            condition,
+            enums,
+            flags,
+        })
+    }
+}
+
+/// Returns the size, the signedness and the endianness of an integer cast,
+/// or no endianness for a float.
+fn int_kind(cast: CastKind) -> Option<(usize, bool, Option<Endian>)> {
+    let big = Some(Endian::Big);
+    let little = Some(Endian::Little);
+    match cast {
+        CastKind::UInt8 => Some((1, false, big)),
+        CastKind::Int8 => Some((1, true, big)),
+        CastKind::UInt16BE => Some((2, false, big)),
+        CastKind::UInt32BE => Some((4, false, big)),
+        CastKind::UInt64BE => Some((8, false, big)),
+        CastKind::Int16BE => Some((2, true, big)),
+        CastKind::Int32BE => Some((4, true, big)),
+        CastKind::Int64BE => Some((8, true, big)),
+        CastKind::UInt16LE => Some((2, false, little)),
+        CastKind::UInt32LE => Some((4, false, little)),
+        CastKind::UInt64LE => Some((8, false, little)),
+        CastKind::Int16LE => Some((2, true, little)),
+        CastKind::Int32LE => Some((4, true, little)),
+        CastKind::Int64LE => Some((8, true, little)),
+        CastKind::Float32BE | CastKind::Float32LE => Some((4, false, None)),
+        CastKind::Float64BE | CastKind::Float64LE => Some((8, false, None)),
+        _ => None,
+    }
+}
+
+/// The data of a layer exceeded while decoding.
+struct Truncated(usize);
+
+struct State<'a> {
+    data: &'a [u8],
+    /// The attributes in order, added to the layer at the end since the
+    /// range of a structure is known after its fields.
+    attrs: Vec<Attr>,
+    values: HashMap<&'a str, i64>,
+}
+
+impl Compiled {
+    fn decode<'a>(&'a self, state: &mut State<'a>) -> ::std::result::Result<usize, Truncated> {
+        let len = state.data.len();
+        Self::nodes(&self.nodes, state, 0..len)
+    }
+
+    fn count(state: &State, count: &Count) -> u64 {
+        match count {
+            Count::Value(n) => *n,
+            Count::Field(id) => state.values.get(id.as_str()).cloned().unwrap_or(0) as u64,
+            Count::Eos => u64::MAX,
+        }
+    }
+
+    /// Reads `nodes` from `range` of the data, and returns the offset
+    /// following the last field.
+    fn nodes<'a>(
+        nodes: &'a [Node],
+        state: &mut State<'a>,
+        range: Range<usize>,
+    ) -> ::std::result::Result<usize, Truncated> {
+        let mut offset = range.start;
+        for node in nodes {
+            if let Some((id, op, value)) = &node.condition {
+                let field = state.values.get(id.as_str()).cloned().unwrap_or(0);
+                let matched = match op {
+                    Op::Eq => field == *value,
+                    Op::Ne => field != *value,
+                    Op::Lt => field < *value,
+                    Op::Le => field <= *value,
+                    Op::Gt => field > *value,
+                    Op::Ge => field >= *value,
+                    Op::Mask => field & *value != 0,
+                };
+                if !matched {
+                    continue;
+                }
+            }
+            let repeat = match &node.repeat {
+                Some(count) => Self::count(state, count),
+                None => 1,
+            };
+            // A field at an offset does not move the following fields.
+            let mut pos = match &node.offset {
+                Some(count) => range
+                    .start
+                    .saturating_add(Self::count(state, count) as usize),
+                None => offset,
+            };
+            if pos > range.end {
+                return Err(Truncated(range.end));
+            }
+            let mut n = 0;
+            while n < repeat {
+                if let Some(Count::Eos) = node.repeat {
+                    if pos >= range.end {
+                        break;
+                    }
+                }
+                pos = Self::node(node, state, pos..range.end)?;
+                n += 1;
+            }
+            if node.offset.is_none() {
+                offset = pos;
+            }
+        }
+        Ok(offset)
+    }
+
+    fn node<'a>(
+        node: &'a Node,
+        state: &mut State<'a>,
+        range: Range<usize>,
+    ) -> ::std::result::Result<usize, Truncated> {
+        let start = range.start;
+        let size = match (&node.kind, &node.length) {
+            (Kind::Int(size, ..), _) | (Kind::Float(size), _) => *size as u64,
+            (_, Some(length)) => Self::count(state, length),
+            (_, None) => (range.end - start) as u64,
+        };
+        if size > (range.end - start) as u64 {
+            return Err(Truncated(start));
+        }
+        let mut end = start + size as usize;
+        let class = node.class.clone();
+        match &node.kind {
+            Kind::Int(size, signed, endian) => {
+                let bytes = &state.data[start..end];
+                let fold = |n: u64, b: &u8| n << 8 | u64::from(*b);
+                let raw = match endian {
+                    Endian::Big => bytes.iter().fold(0, fold),
+                    Endian::Little => bytes.iter().rev().fold(0, fold),
+                };
+                let shift = 64 - 8 * *size as u32;
+                let value = if *signed {
+                    (raw << shift) as i64 >> shift
+                } else {
+                    raw as i64
+                };
+                state.values.insert(&node.id, value);
+                state
+                    .attrs
+                    .push(Attr::builder(class).range(start..end).build());
+                for (n, class) in &node.enums {
+                    if *n == value {
+                        let attr = Attr::builder(class.clone()).range(start..end).value(true);
+                        state.attrs.push(attr.build());
+                    }
+                }
+                for (mask, class) in &node.flags {
+                    let attr = Attr::builder(class.clone())
+                        .range(start..end)
+                        .value(raw & mask == *mask);
+                    state.attrs.push(attr.build());
+                }
+            }
+            Kind::Struct(nodes) => {
+                let index = state.attrs.len();
+                let fields_end = Self::nodes(nodes, state, start..end)?;
+                // A structure without a length ends at its last field.
+                if node.length.is_none() {
+                    end = fields_end;
+                }
+                let attr = Attr::builder(class).range(start..end).value(true);
+                state.attrs.insert(index, attr.build());
+            }
+            _ => {
+                state
+                    .attrs
+                    .push(Attr::builder(class).range(start..end).build());
+            }
+        }
+        Ok(end)
+    }
+}
+
+/// A decoder compiled from a data pattern.
+#[derive(Clone)]
+pub struct PatternDecoder {
+    compiled: Arc<Compiled>,
+}
+
+impl PatternDecoder {
+    /// Loads a decoder from a pattern file.
+    pub fn from_file(path: &str) -> io::Result<PatternDecoder> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Loads a decoder from the JSON text of a pattern.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<PatternDecoder> {
+        let pattern: Pattern = serde_json::from_slice(bytes)?;
+        Self::new(pattern)
+    }
+
+    /// Compiles a pattern into a decoder.
+    pub fn new(pattern: Pattern) -> io::Result<PatternDecoder> {
+        if pattern.layer.is_empty() || pattern.typ.is_empty() {
+            return Err(invalid("pattern without layer or typ".to_string()));
+        }
+        let mut compiler = Compiler {
+            classes: Classes::new(),
+            ints: Vec::new(),
+        };
+        let nodes = compiler.nodes(&pattern.fields, pattern.endian, 0)?;
+        let layer = compiler.classes.layer(&pattern.layer);
+        let malformed = compiler.classes.attr(
+            &format!("{}.malformed", pattern.layer),
+            "@expert:error",
+            CastKind::None,
+        );
+        Ok(PatternDecoder {
+            compiled: Arc::new(Compiled {
+                pattern,
+                layer,
+                malformed,
+                nodes,
+            }),
+        })
+    }
+}
+
+impl Decoder for PatternDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<decoder::Worker> {
+        let pattern = &self.compiled.pattern;
+        for (table, keys) in &pattern.tables {
+            let table = ctx.dissector_table(table.as_str());
+            for key in keys {
+                table.add_default(*key, pattern.typ.as_str());
+            }
+        }
+        Box::new(PatternWorker {
+            compiled: self.compiled.clone(),
+            typ: Token::from(pattern.typ.as_str()),
+        })
+    }
+
+    fn metadata(&self) -> decoder::Metadata {
+        let pattern = &self.compiled.pattern;
+        decoder::Metadata {
+            id: pattern.id.clone(),
+            name: pattern.name.clone(),
+            description: pattern.description.clone(),
+            exec_type: ExecType::ParallelSync,
+            ..decoder::Metadata::default()
+        }
+    }
+}
+
+struct PatternWorker {
+    compiled: Arc<Compiled>,
+    typ: Token,
+}
+
+impl decoder::Worker for PatternWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data = match parent.payloads().iter().find(|p| p.typ() == self.typ) {
+            Some(payload) => payload.data(),
+            None => return Ok(Status::Skip),
+        };
+        let compiled = &*self.compiled;
+        let mut layer = Layer::new(compiled.layer.clone(), data);
+        let mut state = State {
+            data: &data,
+            attrs: Vec::new(),
+            values: HashMap::new(),
+        };
+        match compiled.decode(&mut state) {
+            Ok(end) => {
+                if let Some(payload) = &compiled.pattern.payload {
+                    let rest = data
+                        .try_get(end..data.len())
+                        .map_err(|err| Box::new(Error::new(&err.to_string())))?;
+                    let payload =
+                        Payload::with_typ(rest, payload.id.as_str(), payload.typ.as_str());
+                    layer.add_payload(payload);
+                }
+            }
+            Err(Truncated(offset)) => {
+                let attr = Attr::builder(compiled.malformed.clone())
+                    .range(offset..data.len())
+                    .value(true);
+                state.attrs.push(attr.build());
+            }
+        }
+        for attr in state.attrs {
+            layer.add_attr(attr);
+        }
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fnv::FnvHashMap;
+    use genet_abi::{
+        context::Context,
+        decoder::{Decoder, DecoderBox},
+        layer::{Layer, Parent, Payload},
+        slice::ByteSlice,
+        token::Token,
+        variant::Variant,
+    };
+    use pattern::PatternDecoder;
+    use test_util;
+
+    const FOO: &str = r#"{
+        "id": "pattern-foo",
+        "name": "Foo",
+        "layer": "foo",
+        "typ": "@data:foo",
+        "fields": [
+            { "id": "foo.version", "type": "uint8" },
+            { "id": "foo.kind", "type": "uint8", "enum": { "1": "request", "2": "response" } },
+            { "id": "foo.flags", "type": "uint8", "flags": { "0x80": "last", "0x01": "ack" } },
+            { "id": "foo.count", "type": "uint16" },
+            { "id": "foo.extra", "type": "int32", "endian": "little",
+              "if": { "field": "foo.version", "op": ">=", "value": 2 } },
+            { "id": "foo.item", "type": "struct", "repeat": "foo.count", "fields": [
+                { "id": "foo.item.length", "type": "uint8" },
+                { "id": "foo.item.name", "type": "utf8", "length": "foo.item.length" }
+            ] }
+        ],
+        "payload": { "id": "@data:foo", "typ": "@data:bar" }
+    }"#;
+
+    fn decode<F: FnOnce(&Layer)>(decoder: &PatternDecoder, data: &'static [u8], f: F) {
+        let mut decoder = DecoderBox::new(decoder.clone());
+        let mut ctx = Context::new(FnvHashMap::default());
+        let mut worker = decoder.new_worker(&ctx);
+        let mut layer = test_util::layer("udp")
+            .payload(Payload::with_typ(
+                ByteSlice::from(data),
+                "@data:udp",
+                "@data:foo",
+            ))
+            .build();
+        let mut parent = Parent::from_mut_ref(&mut layer);
+        assert!(worker.decode(&mut ctx, &[], &mut parent).unwrap());
+        f(unsafe { &*parent.children()[0] });
+    }
+
+    #[test]
+    fn decode_fields() {
+        let decoder = PatternDecoder::from_bytes(FOO.as_bytes()).unwrap();
+        assert_eq!(decoder.metadata().id, "pattern-foo");
+
+        let data = b"\x02\x01\x81\x00\x02\xfe\xff\xff\xff\x02hi\x03abcrest";
+        decode(&decoder, data, |layer| {
+            assert_eq!(layer.id(), Token::from("foo"));
+            let value = |id: &str| layer.attr(id).unwrap().try_get(layer).unwrap();
+            assert_eq!(value("foo.version"), Variant::UInt64(2));
+            assert_eq!(value("foo.kind.request"), Variant::Bool(true));
+            assert!(layer.attr("foo.kind.response").is_none());
+            assert_eq!(value("foo.flags.last"), Variant::Bool(true));
+            assert_eq!(value("foo.flags.ack"), Variant::Bool(true));
+            assert_eq!(value("foo.extra"), Variant::Int64(-2));
+            let names = layer
+                .attrs()
+                .iter()
+                .filter(|attr| attr.id() == Token::from("foo.item.name"))
+                .map(|attr| attr.try_get(layer).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                names,
+                vec![Variant::String("hi".into()), Variant::String("abc".into())]
+            );
+            assert_eq!(&layer.payloads()[0].data()[..], b"rest");
+            assert_eq!(layer.payloads()[0].typ(), Token::from("@data:bar"));
+        });
+
+        // The condition does not hold for version 1.
+        decode(&decoder, b"\x01\x02\x00\x00\x01\x05hello", |layer| {
+            assert!(layer.attr("foo.extra").is_none());
+            assert!(layer.attr("foo.kind.response").is_some());
+            assert!(layer.attr("foo.malformed").is_none());
+        });
+        decode(&decoder, b"\x01\x02\x00\x00\x01\x05hel", |layer| {
+            assert!(layer.attr("foo.malformed").is_some());
+        });
+    }
+
+    #[test]
+    fn compile_errors() {
+        let compile = |fields: &str| {
+            let json = format!(
+                r#"{{ "layer": "foo", "typ": "@data:foo", "fields": {} }}"#,
+                fields
+            );
+            PatternDecoder::from_bytes(json.as_bytes())
+                .err()
+                .map(|err| err.to_string())
+        };
+        assert_eq!(compile(r#"[{ "id": "a", "type": "uint8" }]"#), None);
+        assert_eq!(
+            compile(r#"[{ "id": "a", "type": "uint24" }]"#),
+            Some("a: unknown type uint24".to_string())
+        );
+        assert_eq!(
+            compile(r#"[{ "id": "a", "type": "bytes", "length": "b" }]"#),
+            Some("a: unknown integer field b".to_string())
+        );
+        assert_eq!(
+            compile(r#"[{ "id": "a", "type": "uint8", "length": 2 }]"#),
+            Some("a: fixed-size type with length".to_string())
+        );
+        assert_eq!(
+            compile(r#"[{ "id": "a", "type": "utf8", "enum": { "1": "x" } }]"#),
+            Some("a: enum or flags on a non-integer".to_string())
+        );
+        assert!(PatternDecoder::from_bytes(br#"{ "fields": [] }"#).is_err());
+    }
+}
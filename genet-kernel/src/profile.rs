@@ -1,24 +1,111 @@
+use catalog::Catalog;
+use compare::Comparison;
+use conversation::Conversations;
+use decode_as::{self, DecodeAsRules, Patterns};
+use expert::Expert;
+use flags::FrameFlags;
 use fnv::FnvHashMap;
 use genet_abi::{
+    compat::{LegacyDecoder, LegacyReader},
     context::Context,
     decoder::DecoderBox,
     env::{self, Allocator},
+    file::FileType,
     fixed::Fixed,
     reader::ReaderBox,
+    table::{DissectorTables, SessionMetadata},
     token::Token,
     writer::WriterBox,
 };
+use genet_filter::macros;
+use geoip::{self, GeoIp};
+use kaitai;
 use libloading::Library;
+use link::{self, LinkMap};
 use num_cpus;
-use std::{fmt, io, mem};
+use options::Options;
+use pattern::PatternDecoder;
+use resolver::{self, Resolver};
+use saved;
+use signature::{Verification, Verifier};
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    mem,
+    path::Path,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use time_shift::TimeShift;
+#[cfg(feature = "wasm")]
+use wasm::WasmDecoder;
 
 #[derive(Serialize, Clone, Default)]
 pub struct Profile {
     concurrency: u32,
+    serial_concurrency: u32,
     decoders: Vec<DecoderBox>,
     readers: Vec<ReaderBox>,
     writers: Vec<WriterBox>,
     config: FnvHashMap<String, String>,
+    tables: DissectorTables,
+    metadata: SessionMetadata,
+    #[serde(skip)]
+    decode_as: DecodeAsRules,
+    #[serde(skip)]
+    link_map: LinkMap,
+    #[serde(skip)]
+    patterns: Patterns,
+    #[serde(skip)]
+    catalog: Catalog,
+    #[serde(skip)]
+    expert: Expert,
+    #[serde(skip)]
+    conversations: Conversations,
+    #[serde(skip)]
+    frame_flags: FrameFlags,
+    #[serde(skip)]
+    comparison: Comparison,
+    #[serde(skip)]
+    time_shift: TimeShift,
+    #[serde(skip)]
+    resolver: Resolver,
+    #[serde(skip)]
+    geoip: GeoIp,
+    #[serde(skip)]
+    options: Options,
+    #[serde(skip)]
+    sources: Sources,
+}
+
+/// The library paths of the decoders, readers and writers, or None for the
+/// ones added by the host.
+#[derive(Clone, Default)]
+struct Sources {
+    decoders: Vec<Option<String>>,
+    readers: Vec<Option<String>>,
+    writers: Vec<Option<String>>,
+}
+
+/// Replaces the items loaded from `path` with `new`.
+fn replace<T>(items: &mut Vec<T>, sources: &mut Vec<Option<String>>, path: &str, new: Vec<T>) {
+    let mut kept = sources.iter().map(|source| source.as_deref() != Some(path));
+    items.retain(|_| kept.next().unwrap_or(true));
+    sources.retain(|source| source.as_deref() != Some(path));
+    sources.extend(new.iter().map(|_| Some(path.to_string())));
+    items.extend(new);
+}
+
+/// Returns the lowercase extension of the file `path`.
+fn extension(path: &str) -> Option<String> {
+    Some(Path::new(path).extension()?.to_str()?.to_lowercase())
+}
+
+fn supports(filters: &[FileType], ext: &str) -> bool {
+    filters
+        .iter()
+        .any(|filter| filter.extensions().iter().any(|e| e == ext))
 }
 
 impl fmt::Debug for Profile {
@@ -31,10 +118,26 @@ impl Profile {
     pub fn new() -> Profile {
         Profile {
             concurrency: 4,
+            serial_concurrency: 1,
             decoders: Vec::new(),
             readers: Vec::new(),
             writers: Vec::new(),
             config: FnvHashMap::default(),
+            tables: DissectorTables::new(),
+            metadata: SessionMetadata::new(),
+            decode_as: DecodeAsRules::new(),
+            link_map: LinkMap::new(),
+            patterns: Patterns::new(),
+            catalog: Catalog::new(),
+            expert: Expert::new(),
+            conversations: Conversations::new(),
+            frame_flags: FrameFlags::new(),
+            comparison: Comparison::new(),
+            time_shift: TimeShift::new(),
+            resolver: Resolver::default(),
+            geoip: GeoIp::default(),
+            options: Options::new(),
+            sources: Sources::default(),
         }
     }
 
@@ -50,14 +153,92 @@ impl Profile {
         self.concurrency
     }
 
+    /// Sets the number of flow shards of the serial decoders.
+    pub fn set_serial_concurrency(&mut self, concurrency: u32) {
+        self.serial_concurrency = if concurrency > 0 {
+            concurrency
+        } else {
+            num_cpus::get() as u32
+        };
+    }
+
+    pub fn serial_concurrency(&self) -> u32 {
+        self.serial_concurrency
+    }
+
+    /// Returns a config value, or the default value if it is an option
+    /// declared by a decoder.
     pub fn get_config(&self, key: &str) -> Option<String> {
-        self.config.get(key).map(|s| s.to_string())
+        self.config
+            .get(key)
+            .map(|s| s.as_str())
+            .or_else(|| self.options.default_value(key))
+            .map(|s| s.to_string())
     }
 
     pub fn set_config(&mut self, key: &str, value: &str) {
         self.config
             .entry(String::from(key))
             .or_insert_with(|| String::from(value));
+        self.config_updated(key);
+    }
+
+    pub fn update_config(&mut self, key: &str, value: &str) {
+        self.config.insert(String::from(key), String::from(value));
+        self.config_updated(key);
+    }
+
+    fn config_updated(&mut self, key: &str) {
+        if key == link::USER_DLT_KEY {
+            self.link_map = LinkMap::from_config(&self.config[key]);
+        } else if key == decode_as::PATTERNS_KEY {
+            self.patterns = Patterns::from_config(&self.config[key]);
+        } else if key == saved::MACROS_KEY {
+            macros::load_config(&self.config[key]);
+        } else if key == resolver::RESOLVER_KEY {
+            self.resolver = Resolver::from_config(&self.config[key]);
+        } else if key == geoip::DATABASES_KEY {
+            self.geoip = GeoIp::from_config(&self.config[key]);
+        }
+    }
+
+    /// Adds a decoder, e.g. one linked into the host instead of a library.
+    pub fn add_decoder(&mut self, decoder: DecoderBox) {
+        self.options.add(&decoder);
+        self.decoders.push(decoder);
+        self.sources.decoders.push(None);
+    }
+
+    /// Adds a reader, e.g. one linked into the host instead of a library.
+    pub fn add_reader(&mut self, reader: ReaderBox) {
+        self.readers.push(reader);
+        self.sources.readers.push(None);
+    }
+
+    /// Adds a writer, e.g. one linked into the host instead of a library.
+    pub fn add_writer(&mut self, writer: WriterBox) {
+        self.writers.push(writer);
+        self.sources.writers.push(None);
+    }
+
+    /// Replaces the decoders, e.g. with the ones of a profile which has
+    /// reloaded a library.
+    pub fn set_decoders(&mut self, decoders: Vec<DecoderBox>) {
+        self.sources.decoders = vec![None; decoders.len()];
+        self.decoders = decoders;
+        self.update_options();
+    }
+
+    fn update_options(&mut self) {
+        self.options = Options::new();
+        for decoder in &self.decoders {
+            self.options.add(decoder);
+        }
+    }
+
+    /// Returns the options declared by the decoders.
+    pub fn options(&self) -> &Options {
+        &self.options
     }
 
     pub fn decoders(&self) -> impl Iterator<Item = &DecoderBox> {
@@ -68,18 +249,178 @@ impl Profile {
         self.readers.iter()
     }
 
+    /// Returns the ID of the first reader which supports the extension of
+    /// the file `path`.
+    pub fn reader_for_file(&self, path: &str) -> Option<String> {
+        let ext = extension(path)?;
+        self.readers()
+            .map(|reader| reader.metadata())
+            .find(|meta| supports(&meta.filters, &ext))
+            .map(|meta| meta.id)
+    }
+
     pub fn writers(&self) -> impl Iterator<Item = &WriterBox> {
         self.writers.iter()
     }
 
+    /// Returns the ID of the first writer which supports the extension of
+    /// the file `path`.
+    pub fn writer_for_file(&self, path: &str) -> Option<String> {
+        let ext = extension(path)?;
+        self.writers()
+            .map(|writer| writer.metadata())
+            .find(|meta| supports(&meta.filters, &ext))
+            .map(|meta| meta.id)
+    }
+
+    pub fn dissector_tables(&self) -> &DissectorTables {
+        &self.tables
+    }
+
+    pub fn metadata(&self) -> &SessionMetadata {
+        &self.metadata
+    }
+
+    pub fn decode_as(&self) -> &DecodeAsRules {
+        &self.decode_as
+    }
+
+    pub fn link_map(&self) -> &LinkMap {
+        &self.link_map
+    }
+
+    pub fn patterns(&self) -> &Patterns {
+        &self.patterns
+    }
+
+    /// Returns the catalog of the layers and attributes seen in the frames
+    /// decoded with this profile.
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// Returns the findings of the frames decoded by the store this profile
+    /// was passed to.
+    pub fn expert(&self) -> &Expert {
+        &self.expert
+    }
+
+    /// Returns the conversation tables of the frames decoded by the store
+    /// this profile was passed to.
+    pub fn conversations(&self) -> &Conversations {
+        &self.conversations
+    }
+
+    /// Returns the marked and ignored frames of the session.
+    pub fn frame_flags(&self) -> &FrameFlags {
+        &self.frame_flags
+    }
+
+    /// Returns the comparison of the session with another capture.
+    pub fn comparison(&self) -> &Comparison {
+        &self.comparison
+    }
+
+    /// Returns the correction of the timestamps of the session.
+    pub fn time_shift(&self) -> &TimeShift {
+        &self.time_shift
+    }
+
+    /// Returns the resolver adding the names of addresses and ports to the
+    /// frames.
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    /// Returns the GeoIP databases adding the locations of addresses to the
+    /// frames.
+    pub fn geoip(&self) -> &GeoIp {
+        &self.geoip
+    }
+
+    /// Replaces the findings, the conversation tables, the frame flags, the
+    /// comparison and the time shift, so that sessions created from the same
+    /// profile are kept apart.
+    pub fn reset_session_state(&mut self) {
+        self.expert = Expert::new();
+        self.conversations = Conversations::new();
+        self.frame_flags = FrameFlags::new();
+        self.comparison = Comparison::new();
+        self.time_shift = TimeShift::new();
+    }
+
     pub fn context(&self) -> Context {
-        Context::new(self.config.clone())
+        let mut config = self.config.clone();
+        for option in self.options.iter() {
+            config
+                .entry(option.key.clone())
+                .or_insert_with(|| option.default.clone());
+        }
+        Context::with_shared(config, self.tables.clone(), self.metadata.clone())
+    }
+
+    /// Loads a plugin library, a decoder compiled to WebAssembly if the
+    /// path ends with `.wasm`, a data pattern if it ends with `.json`, or a
+    /// Kaitai Struct definition if it ends with `.ksy`, after verifying its
+    /// signature according to the signature policy.
+    ///
+    /// The file is read once, and the verified bytes are loaded from a
+    /// private copy, so that the file can't be replaced after verification.
+    /// A copy also makes each load a new library, since loading the same
+    /// path again returns the library loaded first.
+    pub fn load_library(&mut self, path: &str) -> Result<Verification, io::Error> {
+        static COPIES: AtomicUsize = AtomicUsize::new(0);
+
+        let data = fs::read(path)?;
+        let verification = Verifier::from_profile(self).verify(Path::new(path), &data)?;
+        let name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("plugin");
+        let copy = std::env::temp_dir().join(format!(
+            "genet-load-{}-{}-{}",
+            process::id(),
+            COPIES.fetch_add(1, Ordering::Relaxed),
+            name
+        ));
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&copy)?;
+        let written = file.write_all(&data);
+        drop(file);
+        let result = written.and_then(|()| self.load(&copy.to_string_lossy(), path));
+        // The loaded library stays mapped after the file is removed, except
+        // on Windows, where the copy is left in the temporary directory.
+        let _ = fs::remove_file(&copy);
+        result.map(|()| verification)
     }
 
-    pub fn load_library(&mut self, path: &str) -> Result<(), io::Error> {
-        let lib = Library::new(path)?;
+    /// Loads a rebuilt version of a plugin library loaded before, and
+    /// replaces the decoders, readers and writers of the previous version.
+    ///
+    /// The previous version is never unloaded, because the frames decoded by
+    /// it refer to its layer and attribute classes.
+    pub fn reload_library(&mut self, path: &str) -> Result<Verification, io::Error> {
+        self.load_library(path)
+    }
+
+    /// Loads the library `file` and replaces the plugins loaded from `path`
+    /// with its ones.
+    fn load(&mut self, file: &str, path: &str) -> Result<(), io::Error> {
+        if Path::new(path).extension() == Some("wasm".as_ref()) {
+            return self.load_wasm(file, path);
+        }
+        if Path::new(path).extension() == Some("json".as_ref())
+            || Path::new(path).extension() == Some("ksy".as_ref())
+        {
+            return self.load_pattern(file, path);
+        }
+
+        let lib = Library::new(file)?;
 
         type FnVersion = extern "C" fn() -> u64;
+        type FnRevision = extern "C" fn() -> u32;
         type FnRegisterGetToken = extern "C" fn(unsafe extern "C" fn(*const u8, u64) -> Token);
         type FnRegisterGetString =
             extern "C" fn(unsafe extern "C" fn(Token, *mut u64) -> *const u8);
@@ -88,7 +429,9 @@ impl Profile {
         type FnGetReaders = extern "C" fn(*mut u64) -> *const ReaderBox;
         type FnGetWriters = extern "C" fn(*mut u64) -> *const WriterBox;
 
-        {
+        let revision = if let Ok(func) = unsafe { lib.get::<FnRevision>(b"genet_abi_revision") } {
+            func()
+        } else {
             let func = unsafe { lib.get::<FnVersion>(b"genet_abi_version")? };
 
             // In the initial development, minor version changes may break ABI.
@@ -101,9 +444,19 @@ impl Profile {
             }
 
             if canonical(env::genet_abi_version()) != canonical(func()) {
-                return Err(io::Error::new(io::ErrorKind::Other, "abi version mismatch"));
+                return Err(io::Error::other("abi version mismatch"));
             }
+            1
+        };
 
+        if !(env::MIN_ABI_REVISION..=env::ABI_REVISION).contains(&revision) {
+            return Err(io::Error::other(format!(
+                "unsupported abi revision: {}",
+                revision
+            )));
+        }
+
+        {
             let func =
                 unsafe { lib.get::<FnRegisterGetToken>(b"genet_abi_v1_register_get_token")? };
             func(env::abi_genet_get_token);
@@ -118,31 +471,88 @@ impl Profile {
             func(env::abi_genet_get_allocator);
         }
 
+        let mut decoders = Vec::new();
         if let Ok(func) = unsafe { lib.get::<FnGetDecoders>(b"genet_abi_v1_get_decoders") } {
             let mut len = 0;
             let ptr = func(&mut len);
             for i in 0..len {
-                self.decoders.push(unsafe { (*ptr.offset(i as isize)) });
+                let decoder = unsafe { *ptr.offset(i as isize) };
+                decoders.push(if revision < env::ABI_REVISION {
+                    DecoderBox::new(LegacyDecoder::new(decoder, revision))
+                } else {
+                    decoder
+                });
             }
         }
 
+        let mut readers = Vec::new();
         if let Ok(func) = unsafe { lib.get::<FnGetReaders>(b"genet_abi_v1_get_readers") } {
             let mut len = 0;
             let ptr = func(&mut len);
             for i in 0..len {
-                self.readers.push(unsafe { (*ptr.offset(i as isize)) });
+                let reader = unsafe { *ptr.offset(i as isize) };
+                readers.push(if revision < env::ABI_REVISION {
+                    ReaderBox::new(LegacyReader::new(reader, revision))
+                } else {
+                    reader
+                });
             }
         }
 
+        let mut writers = Vec::new();
         if let Ok(func) = unsafe { lib.get::<FnGetWriters>(b"genet_abi_v1_get_writers") } {
             let mut len = 0;
             let ptr = func(&mut len);
             for i in 0..len {
-                self.writers.push(unsafe { (*ptr.offset(i as isize)) });
+                writers.push(unsafe { *ptr.offset(i as isize) });
             }
         }
 
         mem::forget(lib);
+        let sources = &mut self.sources;
+        replace(&mut self.decoders, &mut sources.decoders, path, decoders);
+        replace(&mut self.readers, &mut sources.readers, path, readers);
+        replace(&mut self.writers, &mut sources.writers, path, writers);
+        self.update_options();
+        Ok(())
+    }
+
+    fn load_pattern(&mut self, file: &str, path: &str) -> Result<(), io::Error> {
+        let decoder = if Path::new(path).extension() == Some("ksy".as_ref()) {
+            kaitai::from_file(file)?
+        } else {
+            PatternDecoder::from_file(file)?
+        };
+        let decoder = DecoderBox::new(decoder);
+        let sources = &mut self.sources;
+        replace(
+            &mut self.decoders,
+            &mut sources.decoders,
+            path,
+            vec![decoder],
+        );
+        self.update_options();
+        Ok(())
+    }
+
+    #[cfg(feature = "wasm")]
+    fn load_wasm(&mut self, file: &str, path: &str) -> Result<(), io::Error> {
+        let decoder = DecoderBox::new(WasmDecoder::from_file(file)?);
+        let sources = &mut self.sources;
+        replace(
+            &mut self.decoders,
+            &mut sources.decoders,
+            path,
+            vec![decoder],
+        );
+        self.update_options();
         Ok(())
     }
+
+    #[cfg(not(feature = "wasm"))]
+    fn load_wasm(&mut self, _file: &str, _path: &str) -> Result<(), io::Error> {
+        Err(io::Error::other(
+            "wasm decoders are not supported in this build",
+        ))
+    }
 }
@@ -0,0 +1,319 @@
+//! Incremental evaluation of display filters.
+//!
+//! A filter is split into its top-level conjuncts, and the result of each
+//! conjunct is kept per frame. When the filter of a view is edited, the
+//! results of the conjuncts shared with the previous filter are reused, so
+//! appending `&& tcp.dst == 80` only evaluates the new conjunct over the
+//! frames matched by the rest, and removing it again evaluates nothing.
+
+use array_vec::ArrayVec;
+use column::ColumnStore;
+use flags::{self, FrameFlags};
+use frame::Frame;
+use genet_filter::{columns::Plan, Filter};
+use roaring::RoaringBitmap;
+use std::ops::Range;
+
+/// A set of frame indices.
+#[derive(Clone, Default)]
+struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    fn contains(&self, index: usize) -> bool {
+        self.words
+            .get(index / 64)
+            .is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    fn insert(&mut self, index: usize) {
+        let word = index / 64;
+        if self.words.len() <= word {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << (index % 64);
+    }
+}
+
+struct Conjunct {
+    filter: Filter,
+    evaluated: BitSet,
+    matched: BitSet,
+}
+
+impl Conjunct {
+    fn new(filter: Filter) -> Conjunct {
+        Conjunct {
+            filter,
+            evaluated: BitSet::default(),
+            matched: BitSet::default(),
+        }
+    }
+
+    fn result(&self, index: usize) -> Option<bool> {
+        if self.evaluated.contains(index) {
+            Some(self.matched.contains(index))
+        } else {
+            None
+        }
+    }
+
+    fn record(&mut self, index: usize, matched: bool) {
+        self.evaluated.insert(index);
+        if matched {
+            self.matched.insert(index);
+        }
+    }
+}
+
+/// A filter which keeps the results of its conjuncts.
+pub struct IncrementalFilter {
+    conjuncts: Vec<Conjunct>,
+}
+
+impl IncrementalFilter {
+    pub fn new(filter: Filter) -> IncrementalFilter {
+        let conjuncts = filter.conjuncts().into_iter().map(Conjunct::new).collect();
+        IncrementalFilter { conjuncts }
+    }
+
+    /// Creates a filter which reuses the results of the conjuncts shared
+    /// with `prev`.
+    pub fn with_previous(filter: Filter, prev: IncrementalFilter) -> IncrementalFilter {
+        let mut reusable = prev.conjuncts;
+        let conjuncts = filter
+            .conjuncts()
+            .into_iter()
+            .map(|filter| {
+                match reusable
+                    .iter()
+                    .position(|c| c.filter.expr() == filter.expr())
+                {
+                    Some(pos) => reusable.swap_remove(pos),
+                    None => Conjunct::new(filter),
+                }
+            })
+            .collect();
+        IncrementalFilter { conjuncts }
+    }
+
+    /// Returns the columnar plans of the conjuncts.
+    pub fn plans(&self) -> impl Iterator<Item = &Plan> + Clone {
+        self.conjuncts.iter().filter_map(|c| c.filter.plan())
+    }
+
+    /// Drops the results, e.g. after the frames are decoded again.
+    pub fn clear(&mut self) {
+        for c in &mut self.conjuncts {
+            c.evaluated = BitSet::default();
+            c.matched = BitSet::default();
+        }
+    }
+
+    /// Drops the results of the conjuncts depending on the marked frames,
+    /// and returns whether there was any.
+    pub fn clear_marked(&mut self) -> bool {
+        let mut cleared = false;
+        for c in &mut self.conjuncts {
+            if flags::uses_marks(&c.filter) {
+                c.evaluated = BitSet::default();
+                c.matched = BitSet::default();
+                cleared = true;
+            }
+        }
+        cleared
+    }
+
+    /// Returns whether evaluating the frames in `range` needs their layers.
+    pub fn needs_frames(&self, range: Range<usize>) -> bool {
+        range
+            .into_iter()
+            .any(|index| result(&self.conjuncts, index).is_none())
+    }
+
+    /// Evaluates the frames in `range` and returns the indices of the
+    /// matched frames.
+    pub fn eval(
+        &mut self,
+        frames: &ArrayVec<Frame>,
+        columns: &mut ColumnStore,
+        flags: &FrameFlags,
+        range: Range<usize>,
+    ) -> Vec<u32> {
+        // Columnar plans answer a whole range at once.
+        for c in &mut self.conjuncts {
+            let plan = match c.filter.plan() {
+                Some(plan) => plan,
+                None => continue,
+            };
+            if range.clone().all(|index| c.evaluated.contains(index)) {
+                continue;
+            }
+            columns.register(plan, frames);
+            let mask = columns
+                .get(plan)
+                .and_then(|columns| plan.eval(&columns, range.clone()));
+            if let Some(mask) = mask {
+                for (index, matched) in range.clone().zip(mask) {
+                    c.record(index, matched);
+                }
+            }
+        }
+
+        let conjuncts = &mut self.conjuncts;
+        range
+            .filter(|index| {
+                // The conjuncts with a result are checked first, so a frame
+                // rejected by one of them is not evaluated again.
+                if let Some(matched) = result(conjuncts, *index) {
+                    return matched;
+                }
+                let frame = match frames.get(*index) {
+                    Some(frame) => frame,
+                    None => return false,
+                };
+                let ctx = flags.context(frame);
+                for c in conjuncts.iter_mut() {
+                    if c.result(*index).is_none() {
+                        let matched = c.filter.test(&ctx);
+                        c.record(*index, matched);
+                        if !matched {
+                            return false;
+                        }
+                    }
+                }
+                true
+            })
+            .map(|index| index as u32)
+            .collect()
+    }
+}
+
+/// Returns whether the frame at `index` matches all `conjuncts`, or None if
+/// it depends on a conjunct which has not been evaluated.
+fn result(conjuncts: &[Conjunct], index: usize) -> Option<bool> {
+    let mut result = Some(true);
+    for c in conjuncts {
+        match c.result(index) {
+            Some(false) => return Some(false),
+            None => result = None,
+            Some(true) => {}
+        }
+    }
+    result
+}
+
+/// Compares two filter results, and returns the indices only in `new` and
+/// the ones only in `old`.
+pub fn diff(old: &RoaringBitmap, new: &RoaringBitmap) -> (Vec<u32>, Vec<u32>) {
+    ((new - old).iter().collect(), (old - new).iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use array_vec::ArrayVec;
+    use column::ColumnStore;
+    use flags::FrameFlags;
+    use frame::Frame;
+    use genet_filter::Filter;
+    use refilter::{diff, IncrementalFilter};
+    use roaring::RoaringBitmap;
+    use test_util;
+
+    fn frames() -> ArrayVec<Frame> {
+        let mut frames = ArrayVec::new();
+        for index in 0..100u64 {
+            let layer = test_util::layer("eth")
+                .attr("eth.a", index % 2)
+                .attr("eth.b", index % 3)
+                .build();
+            frames.push(test_util::frame(index as u32, vec![layer]));
+        }
+        frames
+    }
+
+    fn expected(frames: &ArrayVec<Frame>, filter: &str) -> Vec<u32> {
+        let filter = Filter::compile(filter).unwrap();
+        frames
+            .iter()
+            .filter(|frame| {
+                let ctx = genet_filter::context::Context::new(frame.layers());
+                filter.test(&ctx)
+            })
+            .map(|frame| frame.index())
+            .collect()
+    }
+
+    #[test]
+    fn reuse() {
+        let frames = frames();
+        let mut columns = ColumnStore::new(false);
+        let flags = FrameFlags::new();
+
+        let mut filter = IncrementalFilter::new(Filter::compile("eth.a == 0").unwrap());
+        assert!(filter.needs_frames(0..100));
+        let indices = filter.eval(&frames, &mut columns, &flags, 0..100);
+        assert_eq!(indices, expected(&frames, "eth.a == 0"));
+        assert!(!filter.needs_frames(0..100));
+
+        // Only the frames matching `eth.a == 0` are evaluated by the new
+        // conjunct.
+        let narrowed = "eth.a == 0 && eth.b == 0";
+        let mut filter =
+            IncrementalFilter::with_previous(Filter::compile(narrowed).unwrap(), filter);
+        assert!(filter.needs_frames(0..100));
+        assert!(!filter.needs_frames(1..2));
+        let indices = filter.eval(&frames, &mut columns, &flags, 0..100);
+        assert_eq!(indices, expected(&frames, narrowed));
+
+        // Removing a conjunct needs no evaluation, so the frames are not
+        // read.
+        let mut filter =
+            IncrementalFilter::with_previous(Filter::compile("eth.a == 0").unwrap(), filter);
+        assert!(!filter.needs_frames(0..100));
+        let indices = filter.eval(&ArrayVec::new(), &mut columns, &flags, 0..100);
+        assert_eq!(indices, expected(&frames, "eth.a == 0"));
+
+        filter.clear();
+        assert!(filter.needs_frames(0..100));
+    }
+
+    #[test]
+    fn marked() {
+        let frames = frames();
+        let mut columns = ColumnStore::new(false);
+        let flags = FrameFlags::new();
+        flags.set_marked(&[2, 3, 4], true);
+
+        let mut filter =
+            IncrementalFilter::new(Filter::compile("eth.a == 0 && frame.marked").unwrap());
+        assert_eq!(
+            filter.eval(&frames, &mut columns, &flags, 0..100),
+            vec![2, 4]
+        );
+
+        // Only the results of `frame.marked` are dropped.
+        flags.set_marked(&[6], true);
+        assert!(filter.clear_marked());
+        assert!(!filter.needs_frames(1..2));
+        assert_eq!(
+            filter.eval(&frames, &mut columns, &flags, 0..100),
+            vec![2, 4, 6]
+        );
+
+        let mut filter = IncrementalFilter::new(Filter::compile("eth.a == 0").unwrap());
+        assert!(!filter.clear_marked());
+    }
+
+    #[test]
+    fn diff_indices() {
+        let bitmap = |indices: &[u32]| indices.iter().cloned().collect::<RoaringBitmap>();
+        assert_eq!(
+            diff(&bitmap(&[1, 3, 5, 7]), &bitmap(&[1, 2, 5, 8, 9])),
+            (vec![2, 8, 9], vec![3, 7])
+        );
+        assert_eq!(diff(&bitmap(&[]), &bitmap(&[1])), (vec![1], vec![]));
+        assert_eq!(diff(&bitmap(&[1]), &bitmap(&[])), (vec![], vec![1]));
+    }
+}
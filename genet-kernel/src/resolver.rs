@@ -0,0 +1,596 @@
+//! Name resolution.
+//!
+//! Resolved names are added to the layers as companion attributes, so that
+//! filters and columns can use them like the attributes of the decoders:
+//!
+//! - `eth.src.oui`, `eth.dst.oui`: the vendor of the MAC address.
+//! - `ipv4.src_host`, `ipv4.dst_host`, `ipv6.src_host`, `ipv6.dst_host`: the
+//!   host name of the address.
+//! - `tcp.src_service`, `tcp.dst_service`, `udp.src_service`,
+//!   `udp.dst_service`: the service name of the port.
+//!
+//! The names come from a bundled table of common OUIs, optionally extended
+//! by a file in the format of Wireshark's `manuf`, a services file, the hosts
+//! files given by the user and reverse DNS. Hosts files take precedence over
+//! DNS.
+//!
+//! Decoding never waits for DNS. An address missing in the cache is queued
+//! to a background thread, and the frames decoded before the answer arrives
+//! have no host name until they are decoded again.
+
+use analysis::attr;
+use crossbeam_channel;
+use frame::Frame;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::Fixed,
+    layer::Layer,
+    token::Token,
+};
+use parking_lot::RwLock;
+use serde_json;
+use std::{collections::HashMap, fmt, fs, net::IpAddr, str::FromStr, sync::Arc, thread};
+
+/// The config key of the resolver, a JSON object deserialized to
+/// `ResolverConfig`.
+pub const RESOLVER_KEY: &str = "_.resolver";
+
+/// The OUIs bundled with the resolver.
+const BUNDLED_OUI: &[([u8; 3], &str)] = &[
+    ([0x00, 0x00, 0x0c], "Cisco"),
+    ([0x00, 0x00, 0x5e], "IANA"),
+    ([0x00, 0x03, 0x93], "Apple"),
+    ([0x00, 0x03, 0xff], "Microsoft"),
+    ([0x00, 0x05, 0x69], "VMware"),
+    ([0x00, 0x05, 0x85], "Juniper"),
+    ([0x00, 0x09, 0x0f], "Fortinet"),
+    ([0x00, 0x09, 0x5b], "Netgear"),
+    ([0x00, 0x0a, 0x95], "Apple"),
+    ([0x00, 0x0c, 0x29], "VMware"),
+    ([0x00, 0x0d, 0xb9], "PC Engines"),
+    ([0x00, 0x10, 0x18], "Broadcom"),
+    ([0x00, 0x12, 0x17], "Cisco-Linksys"),
+    ([0x00, 0x14, 0x22], "Dell"),
+    ([0x00, 0x14, 0x6c], "Netgear"),
+    ([0x00, 0x15, 0x5d], "Microsoft"),
+    ([0x00, 0x16, 0x3e], "Xensource"),
+    ([0x00, 0x17, 0xf2], "Apple"),
+    ([0x00, 0x18, 0x0a], "Cisco Meraki"),
+    ([0x00, 0x1a, 0x11], "Google"),
+    ([0x00, 0x1a, 0xa0], "Dell"),
+    ([0x00, 0x1b, 0x17], "Palo Alto Networks"),
+    ([0x00, 0x1b, 0x21], "Intel"),
+    ([0x00, 0x1c, 0x42], "Parallels"),
+    ([0x00, 0x25, 0x64], "Dell"),
+    ([0x00, 0x25, 0x90], "Super Micro"),
+    ([0x00, 0x26, 0xbb], "Apple"),
+    ([0x00, 0x50, 0x56], "VMware"),
+    ([0x00, 0x50, 0xf2], "Microsoft"),
+    ([0x00, 0x80, 0xc2], "IEEE 802.1"),
+    ([0x00, 0xa0, 0xc9], "Intel"),
+    ([0x00, 0xe0, 0x4c], "Realtek"),
+    ([0x08, 0x00, 0x27], "PCS Systemtechnik"),
+    ([0xb8, 0x27, 0xeb], "Raspberry Pi"),
+    ([0xdc, 0xa6, 0x32], "Raspberry Pi"),
+];
+
+/// The kinds of the resolved names.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Vendor,
+    Host,
+    Service,
+}
+
+/// The address attributes of a layer with the attributes added for them.
+type TargetAttrs = [(&'static str, &'static str); 2];
+
+/// The layers with resolved names: the layer, the kind of the names, and
+/// the attributes.
+const TARGETS: &[(&str, Kind, TargetAttrs)] = &[
+    (
+        "eth",
+        Kind::Vendor,
+        [("eth.src", "eth.src.oui"), ("eth.dst", "eth.dst.oui")],
+    ),
+    (
+        "ipv4",
+        Kind::Host,
+        [("ipv4.src", "ipv4.src_host"), ("ipv4.dst", "ipv4.dst_host")],
+    ),
+    (
+        "ipv6",
+        Kind::Host,
+        [("ipv6.src", "ipv6.src_host"), ("ipv6.dst", "ipv6.dst_host")],
+    ),
+    (
+        "tcp",
+        Kind::Service,
+        [
+            ("tcp.src", "tcp.src_service"),
+            ("tcp.dst", "tcp.dst_service"),
+        ],
+    ),
+    (
+        "udp",
+        Kind::Service,
+        [
+            ("udp.src", "udp.src_service"),
+            ("udp.dst", "udp.dst_service"),
+        ],
+    ),
+];
+
+/// The backends enabled for a session.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ResolverConfig {
+    /// Resolves the vendors of MAC addresses.
+    pub mac: bool,
+    /// Resolves the host names of IP addresses from the hosts files.
+    pub network: bool,
+    /// Resolves the host names missing in the hosts files by reverse DNS.
+    /// Ignored unless `network` is enabled.
+    pub dns: bool,
+    /// Resolves the service names of TCP and UDP ports.
+    pub transport: bool,
+    /// A file in the format of Wireshark's `manuf` extending the bundled
+    /// OUIs.
+    pub manuf_file: Option<String>,
+    pub services_file: String,
+    pub hosts_files: Vec<String>,
+}
+
+impl Default for ResolverConfig {
+    fn default() -> ResolverConfig {
+        ResolverConfig {
+            mac: false,
+            network: false,
+            dns: false,
+            transport: false,
+            manuf_file: None,
+            services_file: "/etc/services".to_string(),
+            hosts_files: Vec::new(),
+        }
+    }
+}
+
+/// Parses OUIs in the format of Wireshark's `manuf`, e.g.
+/// `00:00:0C<TAB>Cisco<TAB>Cisco Systems, Inc`. Entries with a mask longer
+/// than 24 bits are ignored.
+pub fn parse_manuf(text: &str) -> HashMap<[u8; 3], String> {
+    let mut map = HashMap::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (prefix, name) = match (fields.next(), fields.next()) {
+            (Some(prefix), Some(name)) => (prefix, name),
+            _ => continue,
+        };
+        let bytes = prefix
+            .split(&[':', '-', '.'][..])
+            .map(|b| u8::from_str_radix(b, 16).ok())
+            .collect::<Option<Vec<u8>>>();
+        if let Some(bytes) = bytes {
+            if bytes.len() == 3 {
+                map.entry([bytes[0], bytes[1], bytes[2]])
+                    .or_insert_with(|| name.to_string());
+            }
+        }
+    }
+    map
+}
+
+/// Parses a services file, e.g. `http 80/tcp www`. The first name of a port
+/// is used.
+pub fn parse_services(text: &str) -> HashMap<(String, u16), String> {
+    let mut map = HashMap::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (name, port) = match (fields.next(), fields.next()) {
+            (Some(name), Some(port)) => (name, port),
+            _ => continue,
+        };
+        let mut port = port.splitn(2, '/');
+        if let (Some(Ok(port)), Some(proto)) = (port.next().map(u16::from_str), port.next()) {
+            map.entry((proto.to_string(), port))
+                .or_insert_with(|| name.to_string());
+        }
+    }
+    map
+}
+
+/// Parses a hosts file, e.g. `10.0.0.1 gateway`. The first name of an
+/// address is used.
+pub fn parse_hosts(text: &str) -> HashMap<Vec<u8>, String> {
+    let mut map = HashMap::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (addr, name) = match (fields.next(), fields.next()) {
+            (Some(addr), Some(name)) => (addr, name),
+            _ => continue,
+        };
+        let addr = match IpAddr::from_str(addr) {
+            Ok(IpAddr::V4(addr)) => addr.octets().to_vec(),
+            Ok(IpAddr::V6(addr)) => addr.octets().to_vec(),
+            Err(_) => continue,
+        };
+        map.entry(addr).or_insert_with(|| name.to_string());
+    }
+    map
+}
+
+#[cfg(unix)]
+fn reverse_lookup(addr: &[u8]) -> Option<String> {
+    use libc;
+    use std::{ffi::CStr, mem, os::raw::c_char, ptr};
+
+    let mut host = [0 as c_char; 1025];
+    let ret = unsafe {
+        match addr.len() {
+            4 => {
+                let mut sa: libc::sockaddr_in = mem::zeroed();
+                sa.sin_family = libc::AF_INET as libc::sa_family_t;
+                sa.sin_addr.s_addr = u32::from_ne_bytes([addr[0], addr[1], addr[2], addr[3]]);
+                libc::getnameinfo(
+                    &sa as *const libc::sockaddr_in as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    host.len() as libc::socklen_t,
+                    ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+            16 => {
+                let mut sa: libc::sockaddr_in6 = mem::zeroed();
+                sa.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sa.sin6_addr.s6_addr.copy_from_slice(addr);
+                libc::getnameinfo(
+                    &sa as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    host.len() as libc::socklen_t,
+                    ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+            _ => return None,
+        }
+    };
+    if ret != 0 {
+        return None;
+    }
+    let host = unsafe { CStr::from_ptr(host.as_ptr()) };
+    host.to_str().ok().map(|host| host.to_string())
+}
+
+#[cfg(not(unix))]
+fn reverse_lookup(_addr: &[u8]) -> Option<String> {
+    None
+}
+
+/// A cache of reverse DNS lookups performed by a background thread.
+#[derive(Clone)]
+struct ReverseDns {
+    cache: Arc<RwLock<HashMap<Vec<u8>, Option<String>>>>,
+    sender: crossbeam_channel::Sender<Vec<u8>>,
+}
+
+impl ReverseDns {
+    /// Starts a thread looking up the queued addresses by `lookup`. The
+    /// thread stops when the last clone is dropped.
+    fn new(lookup: fn(&[u8]) -> Option<String>) -> ReverseDns {
+        let cache = Arc::new(RwLock::new(HashMap::new()));
+        let (sender, receiver) = crossbeam_channel::unbounded::<Vec<u8>>();
+        let results = cache.clone();
+        thread::spawn(move || {
+            while let Some(addr) = receiver.recv() {
+                let name = lookup(&addr);
+                results.write().insert(addr, name);
+            }
+        });
+        ReverseDns { cache, sender }
+    }
+
+    /// Returns the cached name of `addr`, or queues a lookup and returns
+    /// None if `addr` has not been looked up yet. Failed lookups are not
+    /// retried.
+    fn get(&self, addr: &[u8]) -> Option<String> {
+        if let Some(name) = self.cache.read().get(addr) {
+            return name.clone();
+        }
+        let mut cache = self.cache.write();
+        if !cache.contains_key(addr) {
+            cache.insert(addr.to_vec(), None);
+            self.sender.send(addr.to_vec());
+        }
+        None
+    }
+
+    /// Returns the resolved addresses.
+    fn entries(&self) -> Vec<(Vec<u8>, String)> {
+        self.cache
+            .read()
+            .iter()
+            .filter_map(|(addr, name)| Some((addr.clone(), name.clone()?)))
+            .collect()
+    }
+
+    /// Adds names resolved before, e.g. by a previous session.
+    fn extend<I: IntoIterator<Item = (Vec<u8>, String)>>(&self, entries: I) {
+        let mut cache = self.cache.write();
+        for (addr, name) in entries {
+            cache.insert(addr, Some(name));
+        }
+    }
+}
+
+struct Target {
+    name: &'static str,
+    layer: Token,
+    kind: Kind,
+    attrs: Vec<(Token, Token, Fixed<AttrClass>)>,
+}
+
+/// A resolver shared between a profile and its sessions.
+#[derive(Clone)]
+pub struct Resolver {
+    vendors: Arc<HashMap<[u8; 3], String>>,
+    services: Arc<HashMap<(String, u16), String>>,
+    hosts: Arc<HashMap<Vec<u8>, String>>,
+    dns: Option<ReverseDns>,
+    targets: Arc<Vec<Target>>,
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Resolver")
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new(&ResolverConfig::default())
+    }
+}
+
+impl Resolver {
+    /// Creates a new Resolver. Files which cannot be read are ignored.
+    pub fn new(config: &ResolverConfig) -> Resolver {
+        let read = |path: &str| fs::read_to_string(path).unwrap_or_default();
+        let mut vendors = HashMap::new();
+        if config.mac {
+            for (prefix, name) in BUNDLED_OUI {
+                vendors.insert(*prefix, name.to_string());
+            }
+            if let Some(path) = &config.manuf_file {
+                vendors.extend(parse_manuf(&read(path)));
+            }
+        }
+        let services = if config.transport {
+            parse_services(&read(&config.services_file))
+        } else {
+            HashMap::new()
+        };
+        let mut hosts = HashMap::new();
+        if config.network {
+            for path in config.hosts_files.iter().rev() {
+                hosts.extend(parse_hosts(&read(path)));
+            }
+        }
+        let dns = if config.network && config.dns {
+            Some(ReverseDns::new(reverse_lookup))
+        } else {
+            None
+        };
+        let targets = TARGETS
+            .iter()
+            .filter(|(_, kind, _)| match kind {
+                Kind::Vendor => config.mac,
+                Kind::Host => config.network,
+                Kind::Service => config.transport,
+            })
+            .map(|(layer, kind, attrs)| Target {
+                name: layer,
+                layer: Token::from(*layer),
+                kind: *kind,
+                attrs: attrs
+                    .iter()
+                    .map(|(addr, id)| {
+                        (
+                            Token::from(*addr),
+                            Token::from(*id),
+                            Fixed::new(AttrClass::builder(*id).build()),
+                        )
+                    })
+                    .collect(),
+            })
+            .collect();
+        Resolver {
+            vendors: Arc::new(vendors),
+            services: Arc::new(services),
+            hosts: Arc::new(hosts),
+            dns,
+            targets: Arc::new(targets),
+        }
+    }
+
+    /// Creates a new Resolver from the value of `RESOLVER_KEY`, or a
+    /// disabled one if the value is invalid.
+    pub fn from_config(value: &str) -> Resolver {
+        Self::new(&serde_json::from_str(value).unwrap_or_default())
+    }
+
+    /// Returns the vendor of a MAC address.
+    pub fn vendor(&self, mac: &[u8]) -> Option<String> {
+        if mac.len() < 3 {
+            return None;
+        }
+        self.vendors.get(&[mac[0], mac[1], mac[2]]).cloned()
+    }
+
+    /// Returns the host name of an IPv4 or IPv6 address. The name is
+    /// looked up in the background if it is missing in the hosts files.
+    pub fn host(&self, addr: &[u8]) -> Option<String> {
+        if let Some(name) = self.hosts.get(addr) {
+            return Some(name.clone());
+        }
+        self.dns.as_ref().and_then(|dns| dns.get(addr))
+    }
+
+    /// Returns the host names resolved by reverse DNS.
+    pub fn dns_cache(&self) -> Vec<(Vec<u8>, String)> {
+        self.dns
+            .as_ref()
+            .map(|dns| dns.entries())
+            .unwrap_or_default()
+    }
+
+    /// Adds host names to the reverse DNS cache, so that they are not
+    /// looked up again. Does nothing if reverse DNS is disabled.
+    pub fn extend_dns_cache<I: IntoIterator<Item = (Vec<u8>, String)>>(&self, entries: I) {
+        if let Some(dns) = &self.dns {
+            dns.extend(entries);
+        }
+    }
+
+    /// Returns the service name of a port of `proto`, e.g. `tcp`.
+    pub fn service(&self, proto: &str, port: u16) -> Option<String> {
+        self.services.get(&(proto.to_string(), port)).cloned()
+    }
+
+    /// Adds the resolved names to `frames`.
+    pub fn update(&self, frames: &mut [Frame]) {
+        if self.targets.is_empty() {
+            return;
+        }
+        for frame in frames {
+            for layer in frame.layers_mut() {
+                let id = layer.id();
+                if let Some(target) = self.targets.iter().find(|t| t.layer == id) {
+                    self.process(layer, target);
+                }
+            }
+        }
+    }
+
+    fn process(&self, layer: &mut Layer, target: &Target) {
+        for (addr, id, class) in &target.attrs {
+            if layer.attr(*id).is_some() {
+                continue;
+            }
+            let name = match target.kind {
+                Kind::Vendor => attr::<Vec<u8>>(layer, *addr).and_then(|mac| self.vendor(&mac)),
+                Kind::Host => attr::<Vec<u8>>(layer, *addr).and_then(|addr| self.host(&addr)),
+                Kind::Service => {
+                    attr::<u16>(layer, *addr).and_then(|port| self.service(target.name, port))
+                }
+            };
+            if let Some(name) = name {
+                layer.add_attr(
+                    Attr::builder(class.clone())
+                        .value(name.into_boxed_str())
+                        .build(),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{slice::ByteSlice, variant::Variant};
+    use resolver::{
+        parse_hosts, parse_manuf, parse_services, Resolver, ResolverConfig, ReverseDns,
+    };
+    use std::{thread, time::Duration};
+    use test_util;
+
+    #[test]
+    fn parse() {
+        let manuf = parse_manuf(
+            "00:00:0C\tCisco\tCisco Systems, Inc\n00-50-56 VMware\n00:1B:C5:00:00:00/36\tX\n",
+        );
+        assert_eq!(manuf.len(), 2);
+        assert_eq!(manuf[&[0x00, 0x50, 0x56]], "VMware");
+
+        let services =
+            parse_services("# comment\nhttp\t80/tcp\twww # WWW\nhttp-alt 80/tcp\ndomain 53/udp\n");
+        assert_eq!(services[&("tcp".to_string(), 80)], "http");
+        assert_eq!(services[&("udp".to_string(), 53)], "domain");
+        assert_eq!(services.len(), 2);
+
+        let hosts = parse_hosts("10.0.0.1 gateway gw\n::1 localhost\nbad host\n");
+        assert_eq!(hosts[&vec![10, 0, 0, 1]], "gateway");
+        assert_eq!(hosts.len(), 2);
+    }
+
+    #[test]
+    fn update() {
+        let resolver = Resolver::new(&ResolverConfig {
+            mac: true,
+            transport: true,
+            ..ResolverConfig::default()
+        });
+        assert_eq!(
+            resolver.vendor(&[0, 0x0c, 0x29, 1, 2, 3]),
+            Some("VMware".into())
+        );
+        assert_eq!(resolver.host(&[10, 0, 0, 1]), None);
+
+        let layers = vec![
+            test_util::root().attr("link.length", 60u64).build(),
+            test_util::layer("eth")
+                .attr("eth.src", ByteSlice::from(vec![0, 0x0c, 0x29, 1, 2, 3]))
+                .attr("eth.dst", ByteSlice::from(vec![0xff; 6]))
+                .build(),
+            test_util::layer("ipv4")
+                .attr("ipv4.src", ByteSlice::from(vec![10, 0, 0, 1]))
+                .build(),
+        ];
+        let mut frames = vec![test_util::frame(0, layers)];
+        resolver.update(&mut frames);
+        resolver.update(&mut frames);
+
+        let eth = &frames[0].layers()[1];
+        let value = eth.attr("eth.src.oui").unwrap().try_get(eth).unwrap();
+        assert_eq!(value, Variant::String("VMware".into()));
+        assert!(eth.attr("eth.dst.oui").is_none());
+        assert_eq!(eth.attrs().len(), 3);
+        assert!(frames[0].layers()[2].attr("ipv4.src_host").is_none());
+    }
+
+    #[test]
+    fn reverse_dns() {
+        fn lookup(addr: &[u8]) -> Option<String> {
+            if addr == [10, 0, 0, 1] {
+                Some("gateway".to_string())
+            } else {
+                None
+            }
+        }
+        let dns = ReverseDns::new(lookup);
+        assert_eq!(dns.get(&[10, 0, 0, 1]), None);
+        let mut name = None;
+        for _ in 0..100 {
+            name = dns.get(&[10, 0, 0, 1]);
+            if name.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(name, Some("gateway".to_string()));
+        assert_eq!(dns.get(&[10, 0, 0, 2]), None);
+
+        assert_eq!(
+            dns.entries(),
+            vec![(vec![10, 0, 0, 1], "gateway".to_string())]
+        );
+        dns.extend(vec![(vec![10, 0, 0, 3], "printer".to_string())]);
+        assert_eq!(dns.get(&[10, 0, 0, 3]), Some("printer".to_string()));
+    }
+}
@@ -0,0 +1,293 @@
+//! Ring buffers of live captures.
+//!
+//! For long-running captures, the raw frames of the inputs can be written
+//! to a sequence of files like the ring buffer of `dumpcap`: the file is
+//! rotated when its frames reach a size or span a duration, and only the
+//! latest files are kept. The files are written by a writer, and named
+//! after the `file` of its argument with the number of the file, e.g.
+//! `dump_00003.pcap` for `dump.pcap`.
+
+use analysis;
+use capture::{self, Part, Sink};
+use genet_abi::{fixed::MutFixed, layer::Layer};
+use profile::Profile;
+use serde_json::{self, Value};
+use std::{collections::VecDeque, fmt, fs, slice};
+
+/// The bytes written for each frame in addition to its data, i.e. the
+/// record header of a pcap file.
+const RECORD_OVERHEAD: u64 = 16;
+
+/// The limits of a ring buffer.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RingOptions {
+    /// Rotates the file when the frames written to it reach this number of
+    /// bytes.
+    pub file_size: Option<u64>,
+    /// Rotates the file when its frames span this number of seconds.
+    pub duration: Option<f64>,
+    /// The number of files kept. The oldest file is removed when a new one
+    /// exceeds it.
+    pub files: Option<usize>,
+}
+
+/// A rotation of a ring buffer.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Rotation {
+    /// The path of the completed file.
+    pub closed: String,
+    /// The path of the new file.
+    pub opened: String,
+    /// The path of the file removed to keep the number of files, if any.
+    pub removed: Option<String>,
+}
+
+type Open = Box<FnMut(&str) -> Result<Box<Sink + Send>, String> + Send>;
+
+/// Writes frames to a rotated sequence of files.
+pub struct RingBuffer {
+    path: String,
+    options: RingOptions,
+    open: Open,
+    sink: Box<Sink + Send>,
+    files: VecDeque<String>,
+    count: usize,
+    written: u32,
+    bytes: u64,
+    start: Option<i128>,
+}
+
+impl fmt::Debug for RingBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RingBuffer {}", self.path)
+    }
+}
+
+impl RingBuffer {
+    /// Creates a ring buffer of the files based on `path`, which are created
+    /// by `open`, and opens the first file.
+    pub fn create(path: &str, options: RingOptions, mut open: Open) -> Result<RingBuffer, String> {
+        let first = file_path(path, 0);
+        let sink = open(&first)?;
+        Ok(RingBuffer {
+            path: path.to_string(),
+            options,
+            open,
+            sink,
+            files: vec![first].into_iter().collect(),
+            count: 1,
+            written: 0,
+            bytes: 0,
+            start: None,
+        })
+    }
+
+    /// Creates a ring buffer of the files written by `writer` with `arg`,
+    /// a JSON object whose `file` is replaced by the path of each file.
+    pub fn with_writer(
+        profile: &Profile,
+        writer: &str,
+        arg: &str,
+        options: RingOptions,
+    ) -> Result<RingBuffer, String> {
+        let arg: Value = serde_json::from_str(arg).map_err(|err| err.to_string())?;
+        let path = arg
+            .get("file")
+            .and_then(|file| file.as_str())
+            .ok_or_else(|| "no file in the argument".to_string())?
+            .to_string();
+        let profile = profile.clone();
+        let writer = writer.to_string();
+        let open = move |file: &str| {
+            let mut arg = arg.clone();
+            arg["file"] = Value::String(file.to_string());
+            let sink = capture::open_writer(&profile, &writer, &arg.to_string())?;
+            Ok(sink)
+        };
+        Self::create(&path, options, Box::new(open))
+    }
+
+    /// Returns the paths of the files kept, from the oldest.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|file| file.as_str())
+    }
+
+    /// Writes the root layers `layers`, and returns the rotations done
+    /// before them.
+    pub fn append(&mut self, layers: &[MutFixed<Layer>]) -> Result<Vec<Rotation>, String> {
+        let mut rotations = Vec::new();
+        for root in layers {
+            let time = analysis::timestamp(root);
+            if self.is_due(time) {
+                rotations.push(self.rotate()?);
+            }
+            if let Some(time) = time {
+                self.start.get_or_insert(time);
+            }
+            self.sink
+                .write(self.written, slice::from_ref(root))
+                .map_err(|err| err.to_string())?;
+            self.written += 1;
+            self.bytes += root.data().len() as u64 + RECORD_OVERHEAD;
+        }
+        Ok(rotations)
+    }
+
+    /// Completes the current file.
+    pub fn end(&mut self) -> Result<(), String> {
+        self.sink.end().map_err(|err| err.to_string())
+    }
+
+    fn is_due(&self, time: Option<i128>) -> bool {
+        if self.written == 0 {
+            return false;
+        }
+        let full = self
+            .options
+            .file_size
+            .is_some_and(|size| self.bytes >= size);
+        let expired = match (self.options.duration, self.start, time) {
+            (Some(duration), Some(start), Some(time)) => time - start >= (duration * 1e9) as i128,
+            _ => false,
+        };
+        full || expired
+    }
+
+    fn rotate(&mut self) -> Result<Rotation, String> {
+        self.end()?;
+        let opened = file_path(&self.path, self.count);
+        self.sink = (self.open)(&opened)?;
+        self.count += 1;
+        self.written = 0;
+        self.bytes = 0;
+        self.start = None;
+        let closed = self.files.back().cloned().unwrap_or_default();
+        self.files.push_back(opened.clone());
+        let removed = match self.options.files {
+            Some(files) if self.files.len() > files.max(1) => self.files.pop_front(),
+            _ => None,
+        };
+        if let Some(removed) = &removed {
+            // A file removed by the user is not an error.
+            let _ = fs::remove_file(removed);
+        }
+        Ok(Rotation {
+            closed,
+            opened,
+            removed,
+        })
+    }
+}
+
+fn file_path(path: &str, index: usize) -> String {
+    capture::part_path(path, &Part { index, flow: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use capture::Sink;
+    use genet_abi::{fixed::MutFixed, layer::Layer, result::Result, slice::ByteSlice};
+    use ring::{RingBuffer, RingOptions, Rotation};
+    use std::sync::{Arc, Mutex};
+    use test_util;
+
+    type Files = Arc<Mutex<Vec<(String, usize)>>>;
+
+    struct TestSink {
+        files: Files,
+    }
+
+    impl Sink for TestSink {
+        fn write(&mut self, _index: u32, _layers: &[MutFixed<Layer>]) -> Result<()> {
+            self.files.lock().unwrap().last_mut().unwrap().1 += 1;
+            Ok(())
+        }
+
+        fn end(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn ring(options: RingOptions) -> (RingBuffer, Files) {
+        let files = Files::default();
+        let open = {
+            let files = files.clone();
+            move |file: &str| {
+                files.lock().unwrap().push((file.to_string(), 0));
+                Ok(Box::new(TestSink {
+                    files: files.clone(),
+                }) as Box<Sink + Send>)
+            }
+        };
+        let ring = RingBuffer::create("/tmp/genet-ring.pcap", options, Box::new(open)).unwrap();
+        (ring, files)
+    }
+
+    fn frames(sec: &[u64]) -> Vec<MutFixed<Layer>> {
+        sec.iter()
+            .map(|sec| {
+                test_util::root()
+                    .data(ByteSlice::from(vec![0; 84]))
+                    .attr("link.timestamp.sec", *sec)
+                    .build()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rotate_by_size() {
+        let (mut ring, files) = ring(RingOptions {
+            file_size: Some(200),
+            files: Some(2),
+            ..RingOptions::default()
+        });
+        assert_eq!(ring.append(&frames(&[0, 0])).unwrap(), vec![]);
+        let rotations = ring.append(&frames(&[0, 0, 0, 0])).unwrap();
+        assert_eq!(
+            rotations,
+            vec![
+                Rotation {
+                    closed: "/tmp/genet-ring_00000.pcap".to_string(),
+                    opened: "/tmp/genet-ring_00001.pcap".to_string(),
+                    removed: None,
+                },
+                Rotation {
+                    closed: "/tmp/genet-ring_00001.pcap".to_string(),
+                    opened: "/tmp/genet-ring_00002.pcap".to_string(),
+                    removed: Some("/tmp/genet-ring_00000.pcap".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            ring.files().collect::<Vec<_>>(),
+            vec!["/tmp/genet-ring_00001.pcap", "/tmp/genet-ring_00002.pcap"]
+        );
+        let counts = files
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, count)| *count)
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![2, 2, 2]);
+    }
+
+    #[test]
+    fn rotate_by_duration() {
+        let (mut ring, files) = ring(RingOptions {
+            duration: Some(10.0),
+            ..RingOptions::default()
+        });
+        let rotations = ring.append(&frames(&[100, 105, 109, 110, 125])).unwrap();
+        assert_eq!(rotations.len(), 2);
+        let counts = files
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, count)| *count)
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![3, 1, 1]);
+        assert_eq!(ring.files().count(), 3);
+    }
+}
@@ -0,0 +1,116 @@
+use genet_filter::{macros, parser};
+use serde_json;
+use std::collections::BTreeMap;
+
+/// Config key for filter macros.
+///
+/// The value is an array of definitions in the `$<name> := <expr>` form,
+/// e.g. `["$web := tcp.port in {80, 443, 8080}"]`.
+pub const MACROS_KEY: &str = "_.filter.macros";
+
+/// Config key for saved filters, an object mapping names to filters.
+pub const SAVED_FILTERS_KEY: &str = "_.filter.saved";
+
+/// A set of named filters read from the profile.
+#[derive(Serialize, Clone, Default, Debug, PartialEq)]
+pub struct SavedFilters {
+    filters: BTreeMap<String, String>,
+}
+
+impl SavedFilters {
+    pub fn new() -> SavedFilters {
+        Self::default()
+    }
+
+    /// Creates a new SavedFilters from the JSON-encoded value of `_.filter.saved`.
+    pub fn from_config(value: &str) -> SavedFilters {
+        SavedFilters {
+            filters: serde_json::from_str(value).unwrap_or_default(),
+        }
+    }
+
+    pub fn to_config(&self) -> String {
+        serde_json::to_string(&self.filters).unwrap()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.filters.get(name).map(|s| s.as_str())
+    }
+
+    /// Returns the filters sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.filters.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Saves `filter` as `name`, or removes `name` if `filter` is None.
+    ///
+    /// Returns an error if the filter cannot be parsed.
+    pub fn set(&mut self, name: &str, filter: Option<&str>) -> Result<(), String> {
+        if let Some(filter) = filter {
+            parser::parse(filter).map_err(|err| err.to_string())?;
+            self.filters.insert(name.to_string(), filter.to_string());
+        } else {
+            self.filters.remove(name);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the JSON-encoded value of `_.filter.macros` with the macro `name`
+/// replaced by `expr`, or removed if `expr` is None.
+pub fn update_macros(value: &str, name: &str, expr: Option<&str>) -> String {
+    let mut entries: Vec<String> = serde_json::from_str(value).unwrap_or_default();
+    let pos = entries
+        .iter()
+        .position(|e| match macros::parse_definition(e) {
+            Some((n, _)) => n == name,
+            None => false,
+        });
+    // A redefined macro keeps its position so that the macros referring to
+    // it are still defined after it.
+    match (pos, expr) {
+        (Some(pos), Some(expr)) => entries[pos] = format!("${} := {}", name, expr),
+        (Some(pos), None) => {
+            entries.remove(pos);
+        }
+        (None, Some(expr)) => entries.push(format!("${} := {}", name, expr)),
+        (None, None) => {}
+    }
+    serde_json::to_string(&entries).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use saved::{update_macros, SavedFilters};
+
+    #[test]
+    fn saved_filters() {
+        let mut filters = SavedFilters::from_config(r#"{"web": "tcp.dst == 80"}"#);
+        assert_eq!(filters.get("web"), Some("tcp.dst == 80"));
+        assert!(filters.set("dns", Some("udp.dst == 53")).is_ok());
+        assert!(filters.set("bad", Some("udp.dst ==")).is_err());
+        assert_eq!(
+            filters.iter().collect::<Vec<_>>(),
+            vec![("dns", "udp.dst == 53"), ("web", "tcp.dst == 80")]
+        );
+        filters.set("web", None).unwrap();
+        assert_eq!(SavedFilters::from_config(&filters.to_config()), filters);
+        assert_eq!(SavedFilters::from_config("nil"), SavedFilters::new());
+    }
+
+    #[test]
+    fn macros() {
+        let value = update_macros("", "web", Some("tcp.dst in {80, 443}"));
+        assert_eq!(value, r#"["$web := tcp.dst in {80, 443}"]"#);
+        let value = update_macros(&value, "dns", Some("udp.dst == 53"));
+        let value = update_macros(&value, "web", Some("tcp.dst == 80"));
+        assert_eq!(
+            value,
+            r#"["$web := tcp.dst == 80","$dns := udp.dst == 53"]"#
+        );
+        assert_eq!(
+            update_macros(&value, "dns", None),
+            r#"["$web := tcp.dst == 80"]"#
+        );
+    }
+}
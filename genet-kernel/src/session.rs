@@ -1,22 +1,83 @@
-use frame::Frame;
-use genet_abi::{self, fixed::MutFixed, layer::Layer, reader, writer};
-use genet_filter::Filter;
+use annotations::{
+    self, Annotations, ByteAnnotation, ColorRule, DecodeAsEntry, Fingerprint, FrameAnnotations,
+};
+use capture;
+use catalog::CatalogEntry;
+use coloring::Coloring;
+use columns::{ColumnDef, Columns, Row};
+use compare::{Alignment, CompareOptions, ComparisonReport, Packet};
+use conversation::ConversationStats;
+use decode_as::{Conversation, DecodeAs};
+use decoder::dispatcher::Dispatcher;
+use diff::{self, Change};
+use expert::{ExpertSummary, Finding};
+use extract::Exec;
+use flags::FrameFlags;
+use fnv::FnvHashMap;
+use frame::{Frame, OwnedFrame};
+use genet_abi::{
+    self,
+    attr::{Attr, AttrClass},
+    context::Context,
+    decoder::ExecType,
+    fixed::{Fixed, MutFixed},
+    layer::{Layer, LayerClass},
+    reader::{self, CaptureStats},
+    table::SessionMetadata,
+    timestamp::TimestampFormat,
+    token::Token,
+    variant::Variant,
+    writer,
+};
+use genet_filter::{macros, unparser, Filter};
+use index::{self, Summary};
 use io::{Input, Output};
+use iograph::{IoGraph, Point};
+use lazy::{CacheStats, Hold};
+use patch::{self, Patch};
 use profile::Profile;
+use ring::{RingBuffer, RingOptions, Rotation};
+use saved::{self, SavedFilters};
 use serde::ser::{Serialize, SerializeMap, Serializer};
-use std::{fmt, ops::Range};
+use serde_json;
+use session_file::{HostEntry, InputRef, SessionFile};
+use signature::Verification;
+use sort::Sorted;
+use spill::{self, SpillInput, SpillWriter};
+use stats::{CaptureSample, CoverageReport, PipelineStats, ValueCount};
+use std::{
+    collections::BTreeMap,
+    env, fmt, io,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread,
+    time::Duration,
+};
 use store::{self, Store};
+use stream::{Side, StreamBuilder, StreamChunk, TextStream};
+use time_shift::{self, Correction, ReferencePoint, Shift};
+use tree::{self, LayerNode};
+
+const COMPARE_POLL_INTERVAL_MS: u64 = 10;
 
 pub struct Session {
     store: Store,
     callback: Box<Callback>,
     profile: Profile,
     io_cnt: u32,
+    annotations: Annotations,
+    io_graphs: FnvHashMap<u32, IoGraph>,
+    columns: Columns,
+    coloring: Coloring,
+    sorts: FnvHashMap<u32, Sorted>,
+    inputs: Vec<InputRef>,
+    filters: BTreeMap<u32, Filter>,
 }
 
 impl Session {
     pub fn new<C: 'static + Callback + Clone>(profile: Profile, callback: C) -> Session {
-        Session {
+        let mut session = Session {
             store: Store::new(
                 profile.clone(),
                 StoreCallback {
@@ -26,22 +87,462 @@ impl Session {
             callback: Box::new(callback),
             profile,
             io_cnt: 0,
-        }
+            annotations: Annotations::new(),
+            io_graphs: FnvHashMap::default(),
+            columns: Columns::new(),
+            coloring: Coloring::new(),
+            sorts: FnvHashMap::default(),
+            inputs: Vec::new(),
+            filters: BTreeMap::new(),
+        };
+        let format = session.timestamp_format();
+        session.columns.set_timestamp_format(format);
+        session
     }
 
     pub fn frames(&self, range: Range<usize>) -> Vec<*const Frame> {
         self.store.frames(range)
     }
 
+    /// Returns the frames in `range` with the holds which keep them decoded
+    /// in lazy mode, see `Store::held_frames`.
+    pub fn held_frames(&self, range: Range<usize>) -> Vec<(*const Frame, Option<Hold>)> {
+        self.store.held_frames(range)
+    }
+
+    /// Returns the summary columns of the frames in `range`.
+    pub fn frame_summaries(&self, range: Range<usize>) -> Vec<Summary> {
+        self.store.summaries(range)
+    }
+
+    pub fn frame_flags(&self) -> &FrameFlags {
+        self.store.frame_flags()
+    }
+
+    /// Replaces the columns of the frame list.
+    pub fn set_columns(&mut self, defs: Vec<ColumnDef>) -> Result<(), String> {
+        self.columns.set(defs)
+    }
+
+    pub fn columns(&self) -> Vec<ColumnDef> {
+        self.columns.defs()
+    }
+
+    /// Returns the column values of the frames in `range`, or of the frames
+    /// at the rows `range` of the filter `filter_id`.
+    pub fn column_rows(&mut self, range: Range<usize>, filter_id: Option<u32>) -> Vec<Row> {
+        let indices = self.row_indices(range, filter_id);
+        self.columns.rows(&self.store, &indices)
+    }
+
+    /// Returns the index of the coloring rule matched first by each frame in
+    /// `range`, or by each frame at the rows `range` of the filter
+    /// `filter_id`.
+    pub fn frame_colors(
+        &mut self,
+        range: Range<usize>,
+        filter_id: Option<u32>,
+    ) -> Vec<Option<u32>> {
+        let indices = self.row_indices(range, filter_id);
+        self.coloring.rules(&self.store, &indices)
+    }
+
+    fn row_indices(&self, range: Range<usize>, filter_id: Option<u32>) -> Vec<u32> {
+        match filter_id {
+            Some(id) => self.store.filtered_frames(id, range),
+            None => {
+                let end = range.end.min(self.store.len());
+                (range.start as u32..end as u32).collect()
+            }
+        }
+    }
+
+    /// Sorts the frames matching `filter` by the value of `expr` as the sort
+    /// `id`, and returns the number of frames sorted.
+    ///
+    /// Large sorts are spilled to the directory of the frame index, or to the
+    /// temporary directory.
+    pub fn sort_frames(
+        &mut self,
+        id: u32,
+        expr: &str,
+        filter: Option<&Filter>,
+        descending: bool,
+    ) -> Result<usize, String> {
+        let expr = Filter::compile_with_zone(expr, self.timestamp_format().zone)
+            .map_err(|err| err.to_string())?;
+        let dir = self
+            .profile
+            .get_config(index::DIR_KEY)
+            .and_then(|value| serde_json::from_str::<String>(&value).ok())
+            .filter(|dir| !dir.is_empty())
+            .map_or_else(env::temp_dir, PathBuf::from);
+        let sorted = Sorted::new(&self.store, &expr, filter, descending, &dir)
+            .map_err(|err| err.to_string())?;
+        let len = sorted.len();
+        self.sorts.insert(id, sorted);
+        Ok(len)
+    }
+
+    /// Returns the indices of the frames at the rows `range` of the sort
+    /// `id`.
+    pub fn sorted_frames(&self, id: u32, range: Range<usize>) -> Vec<u32> {
+        self.sorts
+            .get(&id)
+            .map(|sorted| sorted.indices(range))
+            .unwrap_or_default()
+    }
+
+    pub fn clear_sort(&mut self, id: u32) {
+        self.sorts.remove(&id);
+    }
+
+    /// Reads the raw data of the frame at `index` back from the frame index.
+    pub fn frame_data(&self, index: usize) -> Option<Vec<u8>> {
+        self.store.frame_data(index)
+    }
+
+    /// Returns the `top_n` most frequent values of the attribute `id` in the
+    /// frames matching `filter`, or all values if `top_n` is zero.
+    pub fn value_counts(&self, id: &str, filter: Option<&Filter>, top_n: usize) -> Vec<ValueCount> {
+        self.store.value_counts(id, filter, top_n)
+    }
+
+    /// Returns the frames and bytes left in undecoded payloads of the frames
+    /// matching `filter`, grouped by the last decoded layer and the port.
+    pub fn coverage(&self, filter: Option<&Filter>) -> CoverageReport {
+        self.store.coverage(filter)
+    }
+
+    /// Runs `exec` for each frame matching `filter` and returns the number of
+    /// times the command was run.
+    ///
+    /// Stops at the first error, e.g. if the program cannot be spawned.
+    pub fn exec(&self, exec: &mut Exec, filter: Option<&Filter>) -> io::Result<usize> {
+        let mut count = 0;
+        let mut result = Ok(());
+        self.store
+            .scan(filter, |frame| match exec.run(frame.layers()) {
+                Ok(status) => {
+                    count += status.map_or(0, |_| 1);
+                    true
+                }
+                Err(err) => {
+                    result = Err(err);
+                    false
+                }
+            });
+        result.map(|_| count)
+    }
+
+    /// Calls `f` with each frame matching `filter` from the frame `start`
+    /// in order until it returns false, and returns the index following the
+    /// last frame scanned.
+    pub fn scan_from<F>(&self, start: usize, filter: Option<&Filter>, f: F) -> usize
+    where
+        F: FnMut(&Frame) -> bool,
+    {
+        self.store.scan_from(start, filter, f)
+    }
+
+    fn stream(&self, layer: &str, stream: u64) -> StreamBuilder {
+        let mut builder = StreamBuilder::new(layer, stream);
+        self.store.scan(None, |frame| {
+            builder.add(frame.index(), frame.layers());
+            true
+        });
+        builder
+    }
+
+    /// Renders the payloads of the conversation `stream` of `layer` (e.g.
+    /// `tcp`) as text.
+    ///
+    /// Frames decoded lazily have no stream index, so their payloads are not
+    /// included.
+    pub fn stream_text(&self, layer: &str, stream: u64) -> TextStream {
+        self.stream(layer, stream).build()
+    }
+
+    /// Returns the reassembled payloads of the conversation `stream` of
+    /// `layer` sent by `side`, or by both sides if `side` is None.
+    ///
+    /// Frames decoded lazily have no stream index, so their payloads are not
+    /// included.
+    pub fn follow_stream(&self, layer: &str, stream: u64, side: Option<Side>) -> Vec<StreamChunk> {
+        self.stream(layer, stream).chunks(side)
+    }
+
+    /// Returns the numbers of the findings reported by the decoders.
+    pub fn expert_summary(&self) -> ExpertSummary {
+        self.store.expert().summary()
+    }
+
+    /// Returns the findings of the frame `index`.
+    pub fn expert_findings(&self, index: u32) -> Vec<Finding> {
+        self.store.expert().findings(index)
+    }
+
+    /// Returns the conversations of `level` (`eth`, `ip`, `tcp` or `udp`),
+    /// or None if `level` is unknown.
+    pub fn conversations(&self, level: &str) -> Option<Vec<ConversationStats>> {
+        self.store.conversations().get(level)
+    }
+
+    /// Keeps the frames in `range` decoded in lazy mode, e.g. the visible
+    /// window of a frame list.
+    pub fn pin_frames(&self, id: u32, range: Range<usize>) {
+        self.store.pin_frames(id, range)
+    }
+
+    pub fn unpin_frames(&self, id: u32) {
+        self.store.unpin_frames(id)
+    }
+
+    /// Returns the statistics of the decoded tree cache in lazy mode.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.store.cache_stats()
+    }
+
+    /// Returns the thread counts and timings of the decode stages.
+    pub fn pipeline_stats(&self) -> PipelineStats {
+        self.store.pipeline_stats()
+    }
+
+    /// Returns the drop and error counts reported by the live capture of the
+    /// input `handle` over time. A `CaptureStats` event is sent for each
+    /// new sample.
+    pub fn capture_stats(&self, handle: u32) -> Vec<CaptureSample> {
+        self.store.capture_stats(handle)
+    }
+
+    /// Returns the layers and attributes seen in the decoded frames.
+    pub fn attribute_catalog(&self) -> Vec<CatalogEntry> {
+        self.profile.catalog().entries()
+    }
+
+    /// Compares the decoded attributes of the frames at `a` and `b`.
+    pub fn diff_frames(&self, a: usize, b: usize) -> Option<Vec<Change>> {
+        let a = *self.store.frames(a..a + 1).first()?;
+        let b = *self.store.frames(b..b + 1).first()?;
+        unsafe { Some(diff::diff((*a).layers(), (*b).layers())) }
+    }
+
+    /// Reads the capture `arg` with the reader `id`, and aligns the frames of
+    /// the session with its frames. The results are added to the frames as
+    /// `compare.*` attributes when they are decoded again.
+    ///
+    /// The frames appended to the session after this call are not compared.
+    pub fn compare(
+        &mut self,
+        id: &str,
+        arg: &str,
+        options: &CompareOptions,
+    ) -> Result<ComparisonReport, String> {
+        let reader = self
+            .profile
+            .readers()
+            .find(|&&r| r.metadata().id.as_str() == id)
+            .ok_or_else(|| format!("{}: no such reader", id))?;
+        let ctx = self.profile.context();
+        let input = reader
+            .new_worker(&ctx, arg)
+            .map_err(|err| err.to_string())?;
+
+        let (sender, done) = mpsc::channel();
+        let mut peer = Store::new(self.profile.clone(), CompareCallback { sender });
+        peer.set_input(1, WorkerInput::new(input, ctx));
+        let _ = done.recv();
+        while peer.pending() > 0 {
+            thread::sleep(Duration::from_millis(COMPARE_POLL_INTERVAL_MS));
+        }
+
+        let packets = |store: &Store| {
+            let mut packets = Vec::with_capacity(store.len());
+            store.scan(None, |frame| {
+                packets.push(Packet::new(frame));
+                true
+            });
+            packets
+        };
+        let alignment = Alignment::new(&packets(&self.store), &packets(&peer), options);
+        let report = alignment.report().clone();
+        self.store.comparison().set(Some(alignment));
+        self.store.redecode();
+        Ok(report)
+    }
+
+    /// Returns the summary of the last comparison, if any.
+    pub fn comparison_report(&self) -> Option<ComparisonReport> {
+        self.store.comparison().report()
+    }
+
+    /// Removes the results of the comparison from the frames.
+    pub fn clear_comparison(&mut self) {
+        self.store.comparison().set(None);
+        self.store.redecode();
+    }
+
+    /// Corrects the timestamps of the frames, replacing the previous
+    /// correction, and returns the shift applied. The timestamps are
+    /// replaced when the frames are decoded again, which also computes the
+    /// time-based attributes of the analysis again.
+    ///
+    /// A skew correction takes the original timestamps of the reference
+    /// frames, so they must have been read.
+    pub fn shift_timestamps(&mut self, correction: &Correction) -> Result<Shift, String> {
+        let shift = match correction {
+            Correction::Offset(seconds) => Shift::offset(*seconds),
+            Correction::Skew(points) => {
+                let point = |point: &ReferencePoint| {
+                    let index = point.frame as usize;
+                    let frame = *self
+                        .store
+                        .frames(index..index + 1)
+                        .first()
+                        .ok_or_else(|| format!("{}: no such frame", point.frame))?;
+                    let root = unsafe { &(*frame).layers()[0] };
+                    let time = time_shift::original(root)
+                        .ok_or_else(|| format!("{}: no timestamp", point.frame))?;
+                    Ok::<_, String>((time, (point.time * 1e9) as i128))
+                };
+                Shift::skew(point(&points[0])?, point(&points[1])?)
+                    .ok_or_else(|| "the reference frames have the same timestamp".to_string())?
+            }
+        };
+        self.set_time_shift(Some(shift));
+        Ok(shift)
+    }
+
+    /// Returns the shift applied to the timestamps, if any.
+    pub fn time_shift(&self) -> Option<Shift> {
+        self.store.time_shift().get()
+    }
+
+    /// Replaces the shift applied to the timestamps, or restores the
+    /// original timestamps if `shift` is None.
+    pub fn set_time_shift(&mut self, shift: Option<Shift>) {
+        self.store.time_shift().set(shift);
+        self.store.redecode();
+    }
+
+    /// Returns the layer tree of the frame at `index`.
+    pub fn layer_tree(&self, index: usize) -> Option<LayerNode> {
+        let frame = *self.store.frames(index..index + 1).first()?;
+        unsafe { tree::tree((*frame).layers(), (*frame).tree_indices()) }
+    }
+
+    /// Decodes a copy of the frame at `index` with `patches` applied.
+    ///
+    /// The copy is decoded with fresh decoder workers and owned by the
+    /// caller.
+    pub fn decode_patched(&self, index: usize, patches: &[Patch]) -> Option<OwnedFrame> {
+        let root = {
+            let frame = *self.store.frames(index..index + 1).first()?;
+            let root = unsafe { &(*frame).layers()[0] };
+            let data = patch::apply(&root.data(), patches)?;
+            root.with_data(data)
+        };
+        let mut frame = Frame::new(index as u32, MutFixed::new(root));
+        Dispatcher::new(&ExecType::ParallelSync, &self.profile).process_frame(&mut frame);
+        Dispatcher::new(&ExecType::SerialSync, &self.profile).process_frame(&mut frame);
+        Some(OwnedFrame::new(frame))
+    }
+
     pub fn filtered_frames(&self, id: u32, range: Range<usize>) -> Vec<u32> {
         self.store.filtered_frames(id, range)
     }
 
+    pub fn filtered_len(&self, id: u32) -> usize {
+        self.store.filtered_len(id)
+    }
+
+    /// Returns the row of the frame `index` in the filter `id`, or the row
+    /// it would be at if it does not match.
+    pub fn filtered_rank(&self, id: u32, index: u32) -> usize {
+        self.store.filtered_rank(id, index)
+    }
+
+    /// Returns the index of the frame at the row `row` of the filter `id`.
+    pub fn filtered_select(&self, id: u32, row: usize) -> Option<u32> {
+        self.store.filtered_select(id, row)
+    }
+
+    /// Returns the frames matched by the filter `id` as a serialized Roaring
+    /// bitmap.
+    pub fn filtered_bitmap(&self, id: u32) -> Option<Vec<u8>> {
+        self.store.filtered_bitmap(id)
+    }
+
+    /// Marks or unmarks the frames `indices`, which `frame.marked` matches.
+    pub fn set_marked(&mut self, indices: &[u32], marked: bool) {
+        self.store.set_marked(indices, marked);
+    }
+
+    /// Ignores or restores the frames `indices`. Ignored frames are not
+    /// decoded, so that they are left out of reassembly and statistics.
+    pub fn set_ignored(&mut self, indices: &[u32], ignored: bool) {
+        self.store.set_ignored(indices, ignored);
+    }
+
+    pub fn marked_frames(&self) -> Vec<u32> {
+        self.store.frame_flags().marked().iter().collect()
+    }
+
+    pub fn ignored_frames(&self) -> Vec<u32> {
+        self.store.frame_flags().ignored().iter().collect()
+    }
+
+    /// Returns the marked frames as a serialized Roaring bitmap.
+    pub fn marked_bitmap(&self) -> Vec<u8> {
+        self.store.marked_bitmap()
+    }
+
+    /// Returns the ignored frames as a serialized Roaring bitmap.
+    pub fn ignored_bitmap(&self) -> Vec<u8> {
+        self.store.ignored_bitmap()
+    }
+
     pub fn set_filter(&mut self, id: u32, filter: Option<Filter>) {
+        match &filter {
+            Some(filter) => self.filters.insert(id, filter.clone()),
+            None => self.filters.remove(&id),
+        };
         self.store.set_filter(id, filter);
     }
 
+    /// Sets the IO graph `id`, or removes it if `graph` is None.
+    pub fn set_io_graph(&mut self, id: u32, graph: Option<IoGraph>) {
+        if let Some(graph) = graph {
+            self.io_graphs.insert(id, graph);
+        } else {
+            self.io_graphs.remove(&id);
+        }
+    }
+
+    /// Updates the IO graph `id` with the frames appended since the last
+    /// call and returns its buckets.
+    pub fn io_graph(&mut self, id: u32) -> Option<Vec<Point>> {
+        let graph = self.io_graphs.get_mut(&id)?;
+        graph.update(&self.store);
+        Some(graph.points())
+    }
+
     pub fn create_reader(&mut self, id: &str, arg: &str) -> u32 {
+        let handle = if id == spill::READER_ID {
+            self.create_spill_reader(arg)
+        } else {
+            self.create_worker_reader(id, arg)
+        };
+        if handle != 0 {
+            self.inputs.push(InputRef {
+                reader: id.to_string(),
+                arg: arg.to_string(),
+            });
+        }
+        handle
+    }
+
+    fn create_worker_reader(&mut self, id: &str, arg: &str) -> u32 {
         if let Some(reader) = self
             .profile
             .readers()
@@ -51,11 +552,12 @@ impl Session {
             let ctx = self.profile.context();
             match reader.new_worker(&ctx, arg) {
                 Ok(input) => {
-                    self.store.set_input(self.io_cnt, WorkerInput::new(input));
+                    self.store
+                        .set_input(self.io_cnt, WorkerInput::new(input, ctx));
                     return self.io_cnt;
                 }
                 Err(err) => {
-                    let err = Error(err.description().to_string());
+                    let err = Error(err.to_string());
                     self.callback.on_event(Event::Error(Box::new(err)));
                 }
             }
@@ -63,7 +565,73 @@ impl Session {
         0
     }
 
-    pub fn create_writer(&mut self, id: &str, arg: &str, filter: Option<Filter>) -> u32 {
+    fn create_spill_reader(&mut self, arg: &str) -> u32 {
+        let file = serde_json::from_str::<serde_json::Value>(arg)
+            .ok()
+            .and_then(|arg| arg["file"].as_str().map(|file| file.to_string()))
+            .unwrap_or_default();
+        match SpillInput::open(&file) {
+            Ok(input) => {
+                self.io_cnt += 1;
+                self.store.set_input(self.io_cnt, input);
+                self.io_cnt
+            }
+            Err(err) => {
+                let err = Error(format!("failed to open spill file {}: {}", file, err));
+                self.callback.on_event(Event::Error(Box::new(err)));
+                0
+            }
+        }
+    }
+
+    /// Starts teeing the raw frames of the inputs into a spill file at
+    /// `path`, which can be replayed later with the spill reader.
+    pub fn start_spill(&mut self, path: &str) -> io::Result<()> {
+        self.store.set_spill(Some(SpillWriter::create(path)?));
+        Ok(())
+    }
+
+    pub fn stop_spill(&mut self) {
+        self.store.set_spill(None);
+    }
+
+    /// Starts writing the raw frames of the inputs to a ring buffer of files
+    /// written by the writer `id`. The `file` of `arg` is the path the files
+    /// are named after. A `Rotated` event is sent when a file is rotated.
+    pub fn start_ring_buffer(
+        &mut self,
+        id: &str,
+        arg: &str,
+        options: &RingOptions,
+    ) -> Result<(), String> {
+        let ring = RingBuffer::with_writer(&self.profile, id, arg, *options)?;
+        self.store.set_ring_buffer(Some(ring));
+        Ok(())
+    }
+
+    pub fn stop_ring_buffer(&mut self) {
+        self.store.set_ring_buffer(None);
+    }
+
+    /// Keeps only the latest `window` frames in memory, or every frame if
+    /// it is `None`. A `FramesEvicted` event is sent when older frames are
+    /// released.
+    pub fn set_frame_window(&mut self, window: Option<usize>) {
+        self.store.set_window(window);
+    }
+
+    /// Returns the index of the first frame kept in memory.
+    pub fn first_frame(&self) -> usize {
+        self.store.first()
+    }
+
+    pub fn create_writer(
+        &mut self,
+        id: &str,
+        arg: &str,
+        filter: Option<Filter>,
+        range: Option<Range<u32>>,
+    ) -> u32 {
         if let Some(writer) = self
             .profile
             .writers()
@@ -73,12 +641,12 @@ impl Session {
             let ctx = self.profile.context();
             match writer.new_worker(&ctx, arg) {
                 Ok(output) => {
-                    self.store
-                        .push_output(self.io_cnt, WorkerOutput::new(output), filter);
+                    let output = WorkerOutput::new(output, self.annotations.export_comments());
+                    self.store.push_output(self.io_cnt, output, filter, range);
                     return self.io_cnt;
                 }
                 Err(err) => {
-                    let err = Error(err.description().to_string());
+                    let err = Error(err.to_string());
                     self.callback.on_event(Event::Error(Box::new(err)));
                 }
             }
@@ -86,14 +654,353 @@ impl Session {
         0
     }
 
+    /// Writes frames given as raw bytes of the link type `link` with the
+    /// writer `id`, e.g. to transmit crafted frames with the injection writer.
+    ///
+    /// Returns the handle of the output like `create_writer`, or 0 if the
+    /// writer cannot be created. The frames are written in the background,
+    /// and an `Output` event is sent when they are done.
+    pub fn write_raw_frames(
+        &mut self,
+        id: &str,
+        arg: &str,
+        link: u32,
+        frames: Vec<Vec<u8>>,
+    ) -> u32 {
+        let mut sink = match capture::open_writer(&self.profile, id, arg) {
+            Ok(sink) => sink,
+            Err(err) => {
+                self.callback.on_event(Event::Error(Box::new(Error(err))));
+                return 0;
+            }
+        };
+        self.io_cnt += 1;
+        let handle = self.io_cnt;
+        let callback = self.callback.clone();
+        thread::spawn(move || {
+            let layers = capture::raw_frames(link, frames);
+            let err = capture::write_frames(&mut *sink, layers)
+                .err()
+                .map(|err| Box::new(Error(err.to_string())) as Box<::std::error::Error + Send>);
+            callback.on_event(Event::Output(handle, err));
+        });
+        handle
+    }
+
+    pub fn set_dissector_table_entry(&mut self, table: &str, key: u64, decoder: &str) {
+        let tables = self.profile.dissector_tables();
+        if decoder.is_empty() {
+            tables.remove(table, key);
+        } else {
+            tables.add(table, key, decoder);
+        }
+    }
+
+    /// Updates a config value of this session, e.g. the key log file of the
+    /// TLS decoder, and decodes the frames again.
+    ///
+    /// A value of an option declared by a decoder must match its type, and
+    /// the frames are left unchanged if the value is the same.
+    pub fn set_config(&mut self, key: &str, value: &str) -> io::Result<()> {
+        self.profile.options().validate(key, value)?;
+        if self.profile.options().get(key).is_some()
+            && self.profile.get_config(key).as_deref() == Some(value)
+        {
+            return Ok(());
+        }
+        self.profile.update_config(key, value);
+        self.store.update_config(key, value);
+        self.store.redecode();
+        Ok(())
+    }
+
+    /// Loads a rebuilt version of the plugin library `path` and decodes the
+    /// frames again with its decoders.
+    ///
+    /// All the frames are decoded again, because the new decoders may match
+    /// frames skipped by the previous ones.
+    pub fn reload_library(&mut self, path: &str) -> io::Result<Verification> {
+        let verification = self.profile.reload_library(path)?;
+        self.store
+            .set_decoders(self.profile.decoders().cloned().collect());
+        self.store.redecode();
+        Ok(verification)
+    }
+
+    pub fn set_decode_as(&mut self, id: u32, rule: Option<DecodeAs>) {
+        self.profile.decode_as().set(id, rule);
+        self.store.redecode();
+    }
+
+    /// Decodes the payloads of the conversation of the `layer` layer of the
+    /// frame at `index` as `decoder`, leaving other conversations untouched.
+    ///
+    /// Returns false if the frame has no such layer or its endpoints are
+    /// unknown.
+    pub fn set_conversation_decode_as(
+        &mut self,
+        id: u32,
+        index: usize,
+        layer: &str,
+        decoder: &str,
+    ) -> bool {
+        let conv = match self.store.frames(index..index + 1).first() {
+            Some(&frame) => {
+                let layers = unsafe { (*frame).layers() };
+                let layer = Token::from(layer);
+                layers
+                    .iter()
+                    .position(|l| l.id() == layer)
+                    .and_then(|pos| Conversation::from_layers(&layers[..=pos]))
+            }
+            None => None,
+        };
+        if let Some(conv) = conv {
+            self.set_decode_as(id, Some(DecodeAs::with_conversation(conv, decoder)));
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+
+    /// Sets or removes the comment of the frame `index`.
+    pub fn set_comment(&mut self, index: u32, comment: Option<&str>) {
+        self.annotations.set_comment(index, comment);
+    }
+
+    /// Replaces the byte annotations of the frame `index`.
+    pub fn set_byte_annotations(
+        &mut self,
+        index: u32,
+        annotations: Vec<ByteAnnotation>,
+    ) -> Result<(), String> {
+        self.annotations.set_byte_annotations(index, annotations)
+    }
+
+    pub fn frame_annotations(&self, index: u32) -> FrameAnnotations {
+        self.annotations.frame(index)
+    }
+
+    /// Returns the indices of the frames with a comment or byte annotations.
+    pub fn commented_frames(&self) -> Vec<u32> {
+        self.annotations.commented_frames()
+    }
+
+    pub fn set_bookmark(&mut self, index: u32, bookmarked: bool) {
+        self.annotations.set_bookmark(index, bookmarked);
+    }
+
+    /// Replaces the coloring rules. Fails without changing the rules if a
+    /// filter is invalid.
+    pub fn set_color_rules(&mut self, rules: Vec<ColorRule>) -> Result<(), String> {
+        self.coloring.set(self.compile_color_rules(&rules)?);
+        self.annotations.color_rules = rules;
+        Ok(())
+    }
+
+    fn compile_color_rules(&self, rules: &[ColorRule]) -> Result<Vec<Filter>, String> {
+        let zone = self.timestamp_format().zone;
+        rules
+            .iter()
+            .map(|rule| {
+                Filter::compile_with_zone(&rule.filter, zone)
+                    .map_err(|err| format!("{}: {}", rule.name, err))
+            })
+            .collect()
+    }
+
+    fn fingerprint(&self, frames: usize) -> Fingerprint {
+        let data = self
+            .store
+            .frames(0..frames)
+            .iter()
+            .filter_map(|&frame| unsafe { (*frame).layers().first() })
+            .map(|root| root.data())
+            .collect::<Vec<_>>();
+        Fingerprint::new(data.iter().map(|data| data.as_ref()))
+    }
+
+    /// Returns the annotations with the Decode-As rules and the fingerprint
+    /// of the loaded frames, to be saved as a sidecar of the capture.
+    pub fn export_annotations(&self) -> Annotations {
+        let mut annotations = self.annotations.clone();
+        annotations.fingerprint = self.fingerprint(annotations::FINGERPRINT_FRAMES);
+        annotations.decode_as = self
+            .profile
+            .decode_as()
+            .rules()
+            .iter()
+            .map(|(id, rule)| DecodeAsEntry::new(*id, rule))
+            .collect();
+        annotations
+    }
+
+    /// Replaces the annotations and applies their Decode-As rules.
+    ///
+    /// Returns an error if the annotations were exported from a capture with
+    /// different frames, including a capture which is not loaded yet.
+    pub fn import_annotations(&mut self, annotations: Annotations) -> Result<(), String> {
+        let expected = annotations.fingerprint;
+        if expected.frames > 0 && self.fingerprint(expected.frames) != expected {
+            return Err("the annotations belong to a different capture".to_string());
+        }
+        self.apply_annotations(annotations)
+    }
+
+    fn apply_annotations(&mut self, annotations: Annotations) -> Result<(), String> {
+        let color_rules = self.compile_color_rules(&annotations.color_rules)?;
+        let rules = annotations
+            .decode_as
+            .iter()
+            .filter_map(|entry| entry.rule())
+            .collect::<Vec<_>>();
+        if !rules.is_empty() {
+            for (id, rule) in rules {
+                self.profile.decode_as().set(id, Some(rule));
+            }
+            self.store.redecode();
+        }
+        self.coloring.set(color_rules);
+        self.annotations = Annotations {
+            decode_as: Vec::new(),
+            ..annotations
+        };
+        Ok(())
+    }
+
+    /// Saves the inputs, the display filters, the annotations, the Decode-As
+    /// rules, the time shift and the host names resolved by reverse DNS to
+    /// `path`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = SessionFile::new();
+        file.inputs = self.inputs.clone();
+        file.filters = self
+            .filters
+            .iter()
+            .map(|(id, filter)| (*id, unparser::unparse(filter.expr())))
+            .collect();
+        file.annotations = self.export_annotations();
+        file.marked = self.marked_frames();
+        file.ignored = self.ignored_frames();
+        file.time_shift = self.time_shift();
+        file.hosts = self
+            .profile
+            .resolver()
+            .dns_cache()
+            .into_iter()
+            .map(|(addr, name)| HostEntry { addr, name })
+            .collect();
+        file.save(path)
+    }
+
+    /// Restores a session saved by `save` into this session, which is
+    /// expected to be empty, and opens its inputs again.
+    ///
+    /// The annotations are applied without checking the fingerprint, since
+    /// the frames are read from the inputs of the saved session.
+    pub fn restore(&mut self, path: &Path) -> Result<(), String> {
+        let file = SessionFile::load(path).map_err(|err| err.to_string())?;
+        self.restore_file(file)
+    }
+
+    /// Restores a session file loaded by the caller, see `restore`.
+    pub fn restore_file(&mut self, file: SessionFile) -> Result<(), String> {
+        let zone = self.timestamp_format().zone;
+        let filters = file
+            .filters
+            .iter()
+            .map(|(id, filter)| {
+                Filter::compile_with_zone(filter, zone)
+                    .map(|filter| (*id, filter))
+                    .map_err(|err| format!("{}: {}", filter, err))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.profile
+            .resolver()
+            .extend_dns_cache(file.hosts.into_iter().map(|host| (host.addr, host.name)));
+        self.apply_annotations(file.annotations)?;
+        self.set_marked(&file.marked, true);
+        self.set_ignored(&file.ignored, true);
+        if file.time_shift.is_some() {
+            self.set_time_shift(file.time_shift);
+        }
+        for (id, filter) in filters {
+            self.set_filter(id, Some(filter));
+        }
+        for input in &file.inputs {
+            if self.create_reader(&input.reader, &input.arg) == 0 {
+                return Err(format!("{}: failed to open the input", input.reader));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn timestamp_format(&self) -> TimestampFormat {
+        TimestampFormat::from_config(|key| self.profile.get_config(key))
+    }
+
+    pub fn set_timestamp_format(&mut self, format: TimestampFormat) {
+        for (key, value) in format.to_config() {
+            self.profile.update_config(key, &value);
+        }
+        self.columns.set_timestamp_format(format);
+    }
+
+    pub fn saved_filters(&self) -> SavedFilters {
+        self.profile
+            .get_config(saved::SAVED_FILTERS_KEY)
+            .map(|value| SavedFilters::from_config(&value))
+            .unwrap_or_default()
+    }
+
+    /// Saves `filter` as `name` in the profile, or removes `name` if `filter`
+    /// is None.
+    pub fn save_filter(&mut self, name: &str, filter: Option<&str>) -> Result<(), String> {
+        let mut filters = self.saved_filters();
+        filters.set(name, filter)?;
+        self.profile
+            .update_config(saved::SAVED_FILTERS_KEY, &filters.to_config());
+        Ok(())
+    }
+
+    /// Defines the filter macro `name` in the profile, or removes it if `expr`
+    /// is None.
+    pub fn set_macro(&mut self, name: &str, expr: Option<&str>) -> Result<(), String> {
+        if let Some(expr) = expr {
+            macros::define(name, expr)?;
+        }
+        let value = self
+            .profile
+            .get_config(saved::MACROS_KEY)
+            .unwrap_or_default();
+        let value = saved::update_macros(&value, name, expr);
+        self.profile.update_config(saved::MACROS_KEY, &value);
+        Ok(())
+    }
+
     pub fn close_reader(&mut self, handle: u32) {
         self.store.unset_input(handle);
     }
 
+    pub fn metadata(&self) -> &SessionMetadata {
+        self.profile.metadata()
+    }
+
     pub fn len(&self) -> usize {
         self.store.len()
     }
 
+    /// Returns the number of frames read from the inputs but not decoded
+    /// and stored yet.
+    pub fn pending_frames(&self) -> usize {
+        self.store.pending()
+    }
+
     pub fn profile(&self) -> &Profile {
         &self.profile
     }
@@ -118,6 +1025,18 @@ impl fmt::Display for Error {
     }
 }
 
+/// Reports the end of the capture read by `Session::compare`.
+#[derive(Clone)]
+struct CompareCallback {
+    sender: Sender<()>,
+}
+
+impl store::Callback for CompareCallback {
+    fn on_input_done(&self, _id: u32, _error: Option<Box<::std::error::Error + Send>>) {
+        let _ = self.sender.send(());
+    }
+}
+
 #[derive(Clone)]
 struct StoreCallback {
     callback: Box<Callback>,
@@ -136,6 +1055,11 @@ impl store::Callback for StoreCallback {
         self.callback.on_event(Event::FilteredFrames(id, frames));
     }
 
+    fn on_filtered_frames_diff(&self, id: u32, added: Vec<u32>, removed: Vec<u32>) {
+        self.callback
+            .on_event(Event::FilteredFramesDiff(id, added, removed));
+    }
+
     fn on_output_done(&self, id: u32, error: Option<Box<::std::error::Error + Send>>) {
         self.callback.on_event(Event::Output(id, error));
     }
@@ -147,6 +1071,22 @@ impl store::Callback for StoreCallback {
     fn on_error(&self, error: Box<::std::error::Error + Send>) {
         self.callback.on_event(Event::Error(error));
     }
+
+    fn on_frames_evicted(&self, start: u32) {
+        self.callback.on_event(Event::FramesEvicted(start));
+    }
+
+    fn on_rotated(&self, rotation: Rotation) {
+        self.callback.on_event(Event::Rotated(rotation));
+    }
+
+    fn on_capture_stats(&self, id: u32, sample: CaptureSample) {
+        self.callback.on_event(Event::CaptureStats(id, sample));
+    }
+
+    fn on_redecode_progress(&self, frames: u32, total: u32) {
+        self.callback.on_event(Event::Redecode(frames, total));
+    }
 }
 
 #[derive(Debug)]
@@ -154,9 +1094,18 @@ pub enum Event {
     Frames(u32),
     AsyncFrames(u32),
     FilteredFrames(u32, u32),
+    /// The frames added to and removed from a view by editing its filter.
+    FilteredFramesDiff(u32, Vec<u32>, Vec<u32>),
     Input(u32, Option<Box<::std::error::Error + Send>>),
     Output(u32, Option<Box<::std::error::Error + Send>>),
     Error(Box<::std::error::Error + Send>),
+    /// The frames before the index have been released.
+    FramesEvicted(u32),
+    Rotated(Rotation),
+    /// A sample of the statistics of the live capture of an input.
+    CaptureStats(u32, CaptureSample),
+    /// The number of frames decoded again and of the frames to decode again.
+    Redecode(u32, u32),
 }
 
 pub trait Callback: CallbackClone + Send {
@@ -207,6 +1156,14 @@ impl Serialize for Event {
                 s.serialize_entry("length", &len)?;
                 s.end()
             }
+            Event::FilteredFramesDiff(id, added, removed) => {
+                let mut s = serializer.serialize_map(Some(4))?;
+                s.serialize_entry("type", "filtered_frames_diff")?;
+                s.serialize_entry("id", &id)?;
+                s.serialize_entry("added", &added)?;
+                s.serialize_entry("removed", &removed)?;
+                s.end()
+            }
             Event::Input(id, err) => {
                 let mut s = serializer.serialize_map(Some(3))?;
                 s.serialize_entry("type", "input")?;
@@ -227,6 +1184,37 @@ impl Serialize for Event {
                 s.serialize_entry("error", &format!("{}", err))?;
                 s.end()
             }
+            Event::FramesEvicted(start) => {
+                let mut s = serializer.serialize_map(Some(2))?;
+                s.serialize_entry("type", "frames_evicted")?;
+                s.serialize_entry("start", &start)?;
+                s.end()
+            }
+            Event::Rotated(rotation) => {
+                let mut s = serializer.serialize_map(Some(4))?;
+                s.serialize_entry("type", "rotated")?;
+                s.serialize_entry("closed", &rotation.closed)?;
+                s.serialize_entry("opened", &rotation.opened)?;
+                s.serialize_entry("removed", &rotation.removed)?;
+                s.end()
+            }
+            Event::CaptureStats(id, sample) => {
+                let mut s = serializer.serialize_map(Some(6))?;
+                s.serialize_entry("type", "capture_stats")?;
+                s.serialize_entry("id", &id)?;
+                s.serialize_entry("time", &sample.time)?;
+                s.serialize_entry("frames", &sample.frames)?;
+                s.serialize_entry("stats", &sample.stats)?;
+                s.serialize_entry("lost", &sample.stats.lost())?;
+                s.end()
+            }
+            Event::Redecode(frames, total) => {
+                let mut s = serializer.serialize_map(Some(3))?;
+                s.serialize_entry("type", "redecode")?;
+                s.serialize_entry("frames", &frames)?;
+                s.serialize_entry("length", &total)?;
+                s.end()
+            }
         }
     }
 }
@@ -234,18 +1222,70 @@ impl Serialize for Event {
 #[derive(Debug)]
 struct WorkerOutput {
     worker: writer::WorkerBox,
+    comments: BTreeMap<u32, String>,
+    comment_class: Fixed<AttrClass>,
+    classes: FnvHashMap<Token, Fixed<LayerClass>>,
 }
 
 impl WorkerOutput {
-    fn new(worker: writer::WorkerBox) -> WorkerOutput {
-        Self { worker }
+    /// Creates a new WorkerOutput which writes `comments` as the
+    /// `link.comment` attribute of the frames.
+    fn new(worker: writer::WorkerBox, comments: BTreeMap<u32, String>) -> WorkerOutput {
+        Self {
+            worker,
+            comments,
+            comment_class: Fixed::new(AttrClass::builder("link.comment").build()),
+            classes: FnvHashMap::default(),
+        }
+    }
+
+    /// Returns a copy of the root layer with `comment` appended to its
+    /// comment.
+    fn annotate(&mut self, root: &Layer, comment: &str) -> Layer {
+        let id = root.id();
+        let class = self
+            .classes
+            .entry(id)
+            .or_insert_with(|| Fixed::new(LayerClass::builder(id).build()))
+            .clone();
+        let mut layer = Layer::new(class, root.data());
+        let comment_id = Token::from("link.comment");
+        let mut lines = Vec::new();
+        for attr in root.headers().iter().chain(root.attrs()) {
+            if attr.id() != comment_id {
+                layer.add_attr(attr.clone());
+            } else if let Ok(Variant::String(line)) = attr.try_get(root) {
+                lines.push(line.to_string());
+            }
+        }
+        lines.push(comment.to_string());
+        layer.add_attr(
+            Attr::builder(self.comment_class.clone())
+                .value(lines.join("\n").into_boxed_str())
+                .build(),
+        );
+        layer.set_frame_metadata(root.frame_metadata());
+        layer
     }
 }
 
 impl Output for WorkerOutput {
     fn write(&mut self, frames: &[&Frame]) -> genet_abi::result::Result<()> {
         for frame in frames.iter() {
-            self.worker.write(frame.index(), frame.layers())?;
+            let layers = frame.layers();
+            let comment = self.comments.get(&frame.index()).cloned();
+            match (comment, layers.first()) {
+                (Some(comment), Some(root)) => {
+                    let mut root = self.annotate(root, &comment);
+                    let stack = Some(&mut root as *mut Layer)
+                        .into_iter()
+                        .chain(layers[1..].iter().map(|layer| layer.as_mut_ptr()))
+                        .map(|layer| unsafe { MutFixed::from_ptr(layer) })
+                        .collect::<Vec<_>>();
+                    self.worker.write(frame.index(), &stack)?;
+                }
+                _ => self.worker.write(frame.index(), layers)?,
+            }
         }
         Ok(())
     }
@@ -255,19 +1295,29 @@ impl Output for WorkerOutput {
     }
 }
 
-#[derive(Debug)]
-struct WorkerInput {
+pub(crate) struct WorkerInput {
     worker: reader::WorkerBox,
+    ctx: Context,
 }
 
 impl WorkerInput {
-    fn new(worker: reader::WorkerBox) -> WorkerInput {
-        Self { worker }
+    pub(crate) fn new(worker: reader::WorkerBox, ctx: Context) -> WorkerInput {
+        Self { worker, ctx }
+    }
+}
+
+impl fmt::Debug for WorkerInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WorkerInput")
     }
 }
 
 impl Input for WorkerInput {
     fn read(&mut self) -> genet_abi::result::Result<Vec<MutFixed<Layer>>> {
-        self.worker.read()
+        self.worker.read(&mut self.ctx)
+    }
+
+    fn stats(&mut self) -> Option<CaptureStats> {
+        self.worker.stats()
     }
 }
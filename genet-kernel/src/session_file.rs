@@ -0,0 +1,117 @@
+//! Session files to resume an analysis.
+//!
+//! A session file refers to the inputs of a session instead of containing
+//! the frames. Restoring it opens the inputs again and applies the display
+//! filters, the annotations, the Decode-As rules, the marked and ignored
+//! frames, the time shift and the host names resolved by reverse DNS, so
+//! that the names are not looked up again.
+
+use annotations::Annotations;
+use serde_json;
+use std::{collections::BTreeMap, fs, io, path::Path};
+use time_shift::Shift;
+
+/// The version of the format written by this version of genet.
+pub const VERSION: u32 = 1;
+
+/// An input of a session.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InputRef {
+    /// The ID of the reader.
+    pub reader: String,
+    /// The argument of the reader, e.g. the path of a capture file.
+    pub arg: String,
+}
+
+/// A host name resolved by reverse DNS.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HostEntry {
+    pub addr: Vec<u8>,
+    pub name: String,
+}
+
+/// The state of a session.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionFile {
+    pub version: u32,
+    #[serde(default)]
+    pub inputs: Vec<InputRef>,
+    /// Display filters by ID.
+    #[serde(default)]
+    pub filters: BTreeMap<u32, String>,
+    #[serde(default)]
+    pub annotations: Annotations,
+    #[serde(default)]
+    pub marked: Vec<u32>,
+    #[serde(default)]
+    pub ignored: Vec<u32>,
+    #[serde(default)]
+    pub time_shift: Option<Shift>,
+    #[serde(default)]
+    pub hosts: Vec<HostEntry>,
+}
+
+impl SessionFile {
+    pub fn new() -> SessionFile {
+        SessionFile {
+            version: VERSION,
+            ..SessionFile::default()
+        }
+    }
+
+    /// Loads a session file. Fails if it was written by a newer version of
+    /// genet.
+    pub fn load(path: &Path) -> io::Result<SessionFile> {
+        let data = fs::read(path)?;
+        let file: SessionFile = serde_json::from_slice(&data)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if file.version > VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported session file version: {}", file.version),
+            ));
+        }
+        Ok(file)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use session_file::{HostEntry, InputRef, SessionFile, VERSION};
+    use std::{env, fs, process};
+    use time_shift::Shift;
+
+    #[test]
+    fn save_load() {
+        let mut file = SessionFile::new();
+        file.inputs.push(InputRef {
+            reader: "app.genet.reader.pcap-file".to_string(),
+            arg: r#"{"file":"dump.pcap"}"#.to_string(),
+        });
+        file.filters.insert(1, "tcp.dst == 80".to_string());
+        file.annotations.set_comment(3, Some("handshake"));
+        file.annotations.set_bookmark(7, true);
+        file.marked = vec![3, 4];
+        file.ignored = vec![5];
+        file.time_shift = Some(Shift::offset(1.5));
+        file.hosts.push(HostEntry {
+            addr: vec![10, 0, 0, 1],
+            name: "gateway".to_string(),
+        });
+
+        let path = env::temp_dir().join(format!("genet-session-{}.json", process::id()));
+        file.save(&path).unwrap();
+        assert_eq!(SessionFile::load(&path).unwrap(), file);
+
+        fs::write(&path, format!(r#"{{"version": {}}}"#, VERSION + 1)).unwrap();
+        assert!(SessionFile::load(&path).is_err());
+        fs::write(&path, r#"{"version": 1}"#).unwrap();
+        assert_eq!(SessionFile::load(&path).unwrap(), SessionFile::new());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,276 @@
+//! Signature verification of plugin libraries.
+//!
+//! A library `libfoo.so` is signed by a detached ed25519 signature in
+//! `libfoo.so.sig`, hex-encoded. The signed message is the library binary
+//! followed by the manifest (`package.json`) of the package containing it,
+//! so that a signed binary cannot be shipped under another package.
+
+use ed25519_compact::{PublicKey, Signature};
+use profile::Profile;
+use serde_json;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The path of the trust store, a file of hex-encoded ed25519 public keys,
+/// one per line. Lines starting with `#` are ignored.
+pub const TRUST_STORE_KEY: &str = "_.plugin.trustStore";
+
+/// What to do with unsigned libraries: `allow`, `warn` or `deny`.
+pub const POLICY_KEY: &str = "_.plugin.signaturePolicy";
+
+const SIGNATURE_EXT: &str = "sig";
+const MANIFEST: &str = "package.json";
+
+/// The most directories between a library and the `target` directory of its
+/// package, e.g. `target/x86_64-unknown-linux-gnu/release`.
+const MAX_TARGET_DEPTH: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// The result of verifying a library.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verification {
+    /// The policy does not require signatures.
+    Unchecked,
+    /// The library is signed by a trusted key.
+    Trusted,
+    /// The library is not signed by a trusted key, but loaded under the
+    /// `warn` policy.
+    Untrusted(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Verifier {
+    policy: Policy,
+    keys: Result<Vec<PublicKey>, String>,
+}
+
+impl Verifier {
+    pub fn new(policy: Policy, keys: Vec<PublicKey>) -> Verifier {
+        Verifier {
+            policy,
+            keys: Ok(keys),
+        }
+    }
+
+    pub fn from_profile(profile: &Profile) -> Verifier {
+        let config = |key| {
+            profile
+                .get_config(key)
+                .and_then(|value| serde_json::from_str::<serde_json::Value>(&value).ok())
+        };
+        let policy = match config(POLICY_KEY).as_ref().and_then(|v| v.as_str()) {
+            Some("warn") => Policy::Warn,
+            Some("deny") => Policy::Deny,
+            _ => Policy::Allow,
+        };
+        let keys = match config(TRUST_STORE_KEY).as_ref().and_then(|v| v.as_str()) {
+            Some(path) if !path.is_empty() => load_keys(Path::new(path)),
+            _ => Ok(Vec::new()),
+        };
+        Verifier { policy, keys }
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    /// Verifies `data`, the binary of the library at `path`, and returns an
+    /// error if the policy denies loading it.
+    pub fn verify(&self, path: &Path, data: &[u8]) -> io::Result<Verification> {
+        if self.policy == Policy::Allow {
+            return Ok(Verification::Unchecked);
+        }
+        match self.check(path, data) {
+            Ok(()) => Ok(Verification::Trusted),
+            Err(reason) => {
+                let reason = format!("{}: {}", path.display(), reason);
+                if self.policy == Policy::Deny {
+                    Err(io::Error::new(io::ErrorKind::PermissionDenied, reason))
+                } else {
+                    Ok(Verification::Untrusted(reason))
+                }
+            }
+        }
+    }
+
+    fn check(&self, path: &Path, data: &[u8]) -> Result<(), String> {
+        let keys = self.keys.as_ref().map_err(|err| err.to_string())?;
+        let sig = fs::read_to_string(signature_path(path)).map_err(|_| "unsigned library")?;
+        let sig = decode_hex(sig.trim())
+            .and_then(|sig| Signature::from_slice(&sig).ok())
+            .ok_or("malformed signature")?;
+        let message = message(path, data).map_err(|err| err.to_string())?;
+        if keys.iter().any(|key| key.verify(&message, &sig).is_ok()) {
+            Ok(())
+        } else {
+            Err("not signed by a trusted key".to_string())
+        }
+    }
+}
+
+/// Returns the path of the detached signature of the library at `path`.
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(SIGNATURE_EXT);
+    path.with_file_name(name)
+}
+
+/// Returns the root directory of the package containing the library at
+/// `path`: the parent of the `target` directory if the library is a build
+/// output, or else the directory of the library.
+fn package_root(path: &Path) -> Option<&Path> {
+    let dir = path.parent()?;
+    let target = dir
+        .ancestors()
+        .take(MAX_TARGET_DEPTH)
+        .find(|dir| dir.file_name() == Some("target".as_ref()));
+    match target {
+        Some(target) => target.parent(),
+        None => Some(dir),
+    }
+}
+
+/// Returns the manifest of the package containing the library at `path`.
+fn manifest_path(path: &Path) -> Option<PathBuf> {
+    let manifest = package_root(path)?.join(MANIFEST);
+    if manifest.is_file() {
+        Some(manifest)
+    } else {
+        None
+    }
+}
+
+/// Returns the signed message of the library at `path`, whose binary is
+/// `data`.
+pub fn message(path: &Path, data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut message = data.to_vec();
+    if let Some(manifest) = manifest_path(path) {
+        message.extend(fs::read(manifest)?);
+    }
+    Ok(message)
+}
+
+fn load_keys(path: &Path) -> Result<Vec<PublicKey>, String> {
+    let store = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read trust store {}: {}", path.display(), err))?;
+    store
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            decode_hex(line)
+                .and_then(|key| PublicKey::from_slice(&key).ok())
+                .ok_or_else(|| format!("invalid key in trust store: {}", line))
+        })
+        .collect()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_compact::{KeyPair, Seed};
+    use signature::{manifest_path, message, signature_path, Policy, Verification, Verifier};
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+    };
+
+    fn package(name: &str) -> PathBuf {
+        let dir = env::temp_dir()
+            .join(format!("genet-signature-{}", name))
+            .join("target/release");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("../../package.json"), r#"{"name":"test"}"#).unwrap();
+        let lib = dir.join("libtest.so");
+        fs::write(&lib, b"binary").unwrap();
+        let _ = fs::remove_file(signature_path(&lib));
+        lib
+    }
+
+    fn sign(lib: &Path, pair: &KeyPair) {
+        let data = fs::read(lib).unwrap();
+        let sig = pair.sk.sign(message(lib, &data).unwrap(), None);
+        let hex = sig.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        fs::write(signature_path(lib), hex).unwrap();
+    }
+
+    #[test]
+    fn verify() {
+        let trusted = KeyPair::from_seed(Seed::new([1; 32]));
+        let other = KeyPair::from_seed(Seed::new([2; 32]));
+        let lib = package("verify");
+
+        let allow = Verifier::new(Policy::Allow, vec![trusted.pk]);
+        let warn = Verifier::new(Policy::Warn, vec![trusted.pk]);
+        let deny = Verifier::new(Policy::Deny, vec![trusted.pk]);
+        assert_eq!(
+            allow.verify(&lib, b"binary").unwrap(),
+            Verification::Unchecked
+        );
+        match warn.verify(&lib, b"binary").unwrap() {
+            Verification::Untrusted(reason) => assert!(reason.ends_with("unsigned library")),
+            v => panic!("unexpected {:?}", v),
+        }
+        assert!(deny.verify(&lib, b"binary").is_err());
+
+        sign(&lib, &trusted);
+        assert_eq!(warn.verify(&lib, b"binary").unwrap(), Verification::Trusted);
+        assert_eq!(deny.verify(&lib, b"binary").unwrap(), Verification::Trusted);
+
+        // The manifest is covered by the signature.
+        fs::write(
+            lib.with_file_name("../../package.json"),
+            r#"{"name":"other"}"#,
+        )
+        .unwrap();
+        assert!(deny.verify(&lib, b"binary").is_err());
+
+        sign(&lib, &other);
+        assert!(deny.verify(&lib, b"binary").is_err());
+    }
+
+    #[test]
+    fn verify_data() {
+        let trusted = KeyPair::from_seed(Seed::new([1; 32]));
+        let lib = package("verify-data");
+        sign(&lib, &trusted);
+
+        // The bytes to be loaded are verified, not the file.
+        let deny = Verifier::new(Policy::Deny, vec![trusted.pk]);
+        assert!(deny.verify(&lib, b"binary").is_ok());
+        assert!(deny.verify(&lib, b"replaced").is_err());
+    }
+
+    #[test]
+    fn manifest() {
+        let lib = package("manifest");
+        let root = lib.ancestors().nth(3).unwrap();
+        assert_eq!(manifest_path(&lib), Some(root.join("package.json")));
+
+        // A manifest outside the package of the library is not used.
+        let dir = root.join("plugins/extra");
+        fs::create_dir_all(&dir).unwrap();
+        let _ = fs::remove_file(dir.join("package.json"));
+        assert_eq!(manifest_path(&dir.join("libextra.so")), None);
+        fs::write(dir.join("package.json"), r#"{"name":"extra"}"#).unwrap();
+        assert_eq!(
+            manifest_path(&dir.join("libextra.so")),
+            Some(dir.join("package.json"))
+        );
+    }
+}
@@ -0,0 +1,457 @@
+//! Sorting of frames by a column expression.
+//!
+//! The sort keys are computed in chunks of frames. If all frames fit in one
+//! chunk, they are sorted in memory. Otherwise each chunk is sorted and
+//! written to a temporary file as a run, and the runs are merged into a
+//! permutation file which is read page by page, so that sorting a large
+//! capture does not hold all the keys in memory.
+//!
+//! A sort is a snapshot: the frames appended later are not included.
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use columns::{self, compare};
+use flags::FrameFlags;
+use frame::Frame;
+use genet_abi::variant::Variant;
+use genet_filter::Filter;
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+};
+use store::Store;
+
+/// The number of keys sorted in memory.
+const CHUNK_LEN: usize = 1 << 20;
+
+static SORT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The order of a sort.
+#[derive(Clone)]
+struct Order {
+    descending: bool,
+}
+
+impl Order {
+    /// Frames with the same key stay in the order of their indices.
+    fn cmp(&self, a: &(Variant, u32), b: &(Variant, u32)) -> Ordering {
+        let order = compare(&a.0, &b.0);
+        let order = if self.descending {
+            order.reverse()
+        } else {
+            order
+        };
+        order.then(a.1.cmp(&b.1))
+    }
+}
+
+/// A temporary file removed when dropped.
+struct TempFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl TempFile {
+    fn create(dir: &Path, ext: &str) -> io::Result<TempFile> {
+        let path = dir.join(format!(
+            "genet-sort-{}-{}.{}",
+            process::id(),
+            SORT_COUNT.fetch_add(1, AtomicOrdering::Relaxed),
+            ext
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(TempFile { path, file })
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+enum Permutation {
+    Memory(Vec<u32>),
+    File(TempFile, usize),
+}
+
+/// The indices of frames sorted by a column expression.
+pub struct Sorted {
+    permutation: Permutation,
+}
+
+impl Sorted {
+    /// Sorts the frames matching `filter` by the value of `expr`. The
+    /// temporary files are created in `dir`.
+    pub fn new(
+        store: &Store,
+        expr: &Filter,
+        filter: Option<&Filter>,
+        descending: bool,
+        dir: &Path,
+    ) -> io::Result<Sorted> {
+        Sorted::with_chunk_len(store, expr, filter, descending, dir, CHUNK_LEN)
+    }
+
+    fn with_chunk_len(
+        store: &Store,
+        expr: &Filter,
+        filter: Option<&Filter>,
+        descending: bool,
+        dir: &Path,
+        chunk_len: usize,
+    ) -> io::Result<Sorted> {
+        let order = Order { descending };
+        let mut chunk = Vec::new();
+        let mut runs: Option<(TempFile, Vec<u64>)> = None;
+        let mut result = Ok(());
+        store.scan(filter, |frame| {
+            chunk.push((key(expr, store.frame_flags(), frame), frame.index()));
+            if chunk.len() < chunk_len {
+                return true;
+            }
+            result = write_run(&mut runs, &mut chunk, &order, dir);
+            result.is_ok()
+        });
+        result?;
+
+        match runs {
+            None => {
+                chunk.sort_by(|a, b| order.cmp(a, b));
+                Ok(Sorted {
+                    permutation: Permutation::Memory(
+                        chunk.into_iter().map(|(_, index)| index).collect(),
+                    ),
+                })
+            }
+            Some(_) => {
+                if !chunk.is_empty() {
+                    write_run(&mut runs, &mut chunk, &order, dir)?;
+                }
+                let (file, offsets) = runs.unwrap();
+                let perm = TempFile::create(dir, "perm")?;
+                let len = merge(&file.path, &offsets, &perm.file, &order)?;
+                Ok(Sorted {
+                    permutation: Permutation::File(perm, len),
+                })
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.permutation {
+            Permutation::Memory(indices) => indices.len(),
+            Permutation::File(_, len) => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the indices of the frames at the rows `range`.
+    pub fn indices(&self, range: Range<usize>) -> Vec<u32> {
+        let end = range.end.min(self.len());
+        if range.start >= end {
+            return Vec::new();
+        }
+        match &self.permutation {
+            Permutation::Memory(indices) => indices[range.start..end].to_vec(),
+            Permutation::File(perm, _) => {
+                let mut file = &perm.file;
+                let mut indices = vec![0; end - range.start];
+                let result = file
+                    .seek(SeekFrom::Start(range.start as u64 * 4))
+                    .and_then(|_| file.read_u32_into::<LittleEndian>(&mut indices));
+                result.map(|_| indices).unwrap_or_default()
+            }
+        }
+    }
+}
+
+fn key(expr: &Filter, flags: &FrameFlags, frame: &Frame) -> Variant {
+    let ctx = flags.context(frame);
+    columns::owned(expr.expr().eval(&ctx))
+}
+
+/// Sorts `chunk` and appends it to the runs file.
+fn write_run(
+    runs: &mut Option<(TempFile, Vec<u64>)>,
+    chunk: &mut Vec<(Variant, u32)>,
+    order: &Order,
+    dir: &Path,
+) -> io::Result<()> {
+    if runs.is_none() {
+        *runs = Some((TempFile::create(dir, "runs")?, Vec::new()));
+    }
+    let (file, offsets) = runs.as_mut().unwrap();
+    chunk.sort_by(|a, b| order.cmp(a, b));
+    let mut file = &file.file;
+    offsets.push(file.seek(SeekFrom::End(0))?);
+    let mut writer = BufWriter::new(file);
+    writer.write_u64::<LittleEndian>(chunk.len() as u64)?;
+    for (key, index) in chunk.drain(..) {
+        writer.write_u32::<LittleEndian>(index)?;
+        write_key(&mut writer, &key)?;
+    }
+    writer.flush()
+}
+
+struct Run {
+    reader: BufReader<File>,
+    remaining: u64,
+}
+
+impl Run {
+    fn open(path: &Path, offset: u64) -> io::Result<Run> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::new(file);
+        let remaining = reader.read_u64::<LittleEndian>()?;
+        Ok(Run { reader, remaining })
+    }
+
+    fn next(&mut self) -> io::Result<Option<(Variant, u32)>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let index = self.reader.read_u32::<LittleEndian>()?;
+        let key = read_key(&mut self.reader)?;
+        Ok(Some((key, index)))
+    }
+}
+
+/// The smallest entry of a run in the merge heap.
+struct Head {
+    entry: (Variant, u32),
+    run: usize,
+    order: Order,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Head) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Head) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    // BinaryHeap is a max-heap.
+    fn cmp(&self, other: &Head) -> Ordering {
+        self.order.cmp(&other.entry, &self.entry)
+    }
+}
+
+/// Merges the sorted runs and writes the frame indices to `perm`.
+fn merge(path: &Path, offsets: &[u64], perm: &File, order: &Order) -> io::Result<usize> {
+    let mut runs = offsets
+        .iter()
+        .map(|offset| Run::open(path, *offset))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::new();
+    for (run, reader) in runs.iter_mut().enumerate() {
+        if let Some(entry) = reader.next()? {
+            heap.push(Head {
+                entry,
+                run,
+                order: order.clone(),
+            });
+        }
+    }
+    let mut writer = BufWriter::new(perm);
+    let mut len = 0;
+    while let Some(head) = heap.pop() {
+        writer.write_u32::<LittleEndian>(head.entry.1)?;
+        len += 1;
+        if let Some(entry) = runs[head.run].next()? {
+            heap.push(Head { entry, ..head });
+        }
+    }
+    writer.flush()?;
+    Ok(len)
+}
+
+fn write_key<W: Write>(writer: &mut W, key: &Variant) -> io::Result<()> {
+    let bytes = |writer: &mut W, tag: u8, data: &[u8]| {
+        writer.write_u8(tag)?;
+        writer.write_u32::<LittleEndian>(data.len() as u32)?;
+        writer.write_all(data)
+    };
+    match key {
+        Variant::Nil => writer.write_u8(0),
+        Variant::Bool(val) => {
+            writer.write_u8(1)?;
+            writer.write_u8(*val as u8)
+        }
+        Variant::Int64(val) => {
+            writer.write_u8(2)?;
+            writer.write_i64::<LittleEndian>(*val)
+        }
+        Variant::UInt64(val) => {
+            writer.write_u8(3)?;
+            writer.write_u64::<LittleEndian>(*val)
+        }
+        Variant::Float64(val) => {
+            writer.write_u8(4)?;
+            writer.write_f64::<LittleEndian>(*val)
+        }
+        Variant::String(val) => bytes(writer, 5, val.as_bytes()),
+        Variant::BigInt(data) => bytes(writer, 6, data),
+        Variant::Buffer(data) => bytes(writer, 7, data),
+        Variant::Slice(data) => bytes(writer, 7, data),
+    }
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Box<[u8]>> {
+    let len = reader.read_u32::<LittleEndian>()? as usize;
+    let mut data = vec![0; len];
+    reader.read_exact(&mut data)?;
+    Ok(data.into_boxed_slice())
+}
+
+fn read_key<R: Read>(reader: &mut R) -> io::Result<Variant> {
+    match reader.read_u8()? {
+        0 => Ok(Variant::Nil),
+        1 => Ok(Variant::Bool(reader.read_u8()? != 0)),
+        2 => Ok(Variant::Int64(reader.read_i64::<LittleEndian>()?)),
+        3 => Ok(Variant::UInt64(reader.read_u64::<LittleEndian>()?)),
+        4 => Ok(Variant::Float64(reader.read_f64::<LittleEndian>()?)),
+        5 => String::from_utf8(read_bytes(reader)?.into_vec())
+            .map(|val| Variant::String(val.into_boxed_str()))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        6 => Ok(Variant::BigInt(read_bytes(reader)?)),
+        7 => Ok(Variant::Buffer(read_bytes(reader)?)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unknown key type",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{fixed::MutFixed, layer::Layer, result::Result, variant::Variant};
+    use genet_filter::Filter;
+    use io::Input;
+    use profile::Profile;
+    use sort::{read_key, write_key, Sorted};
+    use std::{env, io::Cursor, thread, time::Duration};
+    use store::{Callback, Store};
+    use test_util;
+
+    #[derive(Clone)]
+    struct TestCallback {}
+    impl Callback for TestCallback {}
+
+    /// Reads frames with `test.key` of `index * 7 % 10`, except for every
+    /// 13th frame.
+    #[derive(Debug)]
+    struct TestInput {
+        len: u64,
+    }
+
+    impl Input for TestInput {
+        fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+            let layers = (0..self.len)
+                .map(|i| {
+                    let layer = test_util::layer("test");
+                    if i % 13 != 0 {
+                        layer.attr("test.key", i * 7 % 10).build()
+                    } else {
+                        layer.build()
+                    }
+                })
+                .collect();
+            self.len = 0;
+            Ok(layers)
+        }
+    }
+
+    fn expected(filtered: bool, descending: bool) -> Vec<u32> {
+        let mut indices = (0..100u32)
+            .map(|i| (if i % 13 == 0 { None } else { Some(i * 7 % 10) }, i))
+            .filter(|(key, _)| !filtered || key.is_some_and(|key| key >= 5))
+            .collect::<Vec<_>>();
+        indices.sort_by(|a, b| {
+            let order = a.0.cmp(&b.0);
+            let order = if descending { order.reverse() } else { order };
+            order.then(a.1.cmp(&b.1))
+        });
+        indices.into_iter().map(|(_, i)| i).collect()
+    }
+
+    #[test]
+    fn sort() {
+        let mut store = Store::new(Profile::new(), TestCallback {});
+        store.set_input(1, TestInput { len: 100 });
+        while store.len() < 100 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let expr = Filter::compile("test.key").unwrap();
+        let filter = Filter::compile("test.key >= 5").unwrap();
+        let dir = env::temp_dir();
+
+        // A chunk of 7 keys spills the runs to a file.
+        for &chunk_len in &[1000, 7] {
+            for &descending in &[false, true] {
+                let sorted =
+                    Sorted::with_chunk_len(&store, &expr, None, descending, &dir, chunk_len)
+                        .unwrap();
+                assert_eq!(sorted.indices(0..100), expected(false, descending));
+                assert_eq!(sorted.indices(95..200), expected(false, descending)[95..]);
+                assert!(sorted.indices(100..200).is_empty());
+
+                let sorted = Sorted::with_chunk_len(
+                    &store,
+                    &expr,
+                    Some(&filter),
+                    descending,
+                    &dir,
+                    chunk_len,
+                )
+                .unwrap();
+                assert_eq!(sorted.len(), expected(true, descending).len());
+                assert_eq!(sorted.indices(0..100), expected(true, descending));
+            }
+        }
+    }
+
+    #[test]
+    fn keys() {
+        let keys = vec![
+            Variant::Nil,
+            Variant::Bool(true),
+            Variant::Int64(-3),
+            Variant::UInt64(3),
+            Variant::Float64(0.5),
+            Variant::String("abc".into()),
+            Variant::BigInt(vec![1, 2].into_boxed_slice()),
+            Variant::Buffer(vec![3, 4].into_boxed_slice()),
+        ];
+        let mut data = Vec::new();
+        for key in &keys {
+            write_key(&mut data, key).unwrap();
+        }
+        let mut reader = Cursor::new(data);
+        for key in &keys {
+            assert_eq!(&read_key(&mut reader).unwrap(), key);
+        }
+    }
+}
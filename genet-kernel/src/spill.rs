@@ -0,0 +1,481 @@
+//! Spill files of live captures.
+//!
+//! While capturing live, the raw frames of the inputs can be teed into a
+//! spill file, so that the full capture can be replayed and lazily decoded
+//! again later. A spill file is a sequence of deflate-compressed chunks of
+//! link-layer frames. Each chunk starts with a header holding the index of
+//! its first frame, the number of frames and the compressed length, so that
+//! any frame can be located by scanning the chunk headers only.
+//!
+//! Version 2 records also hold the frame metadata set by the reader.
+
+use analysis::{attr, timestamp};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use fnv::FnvHashMap;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::{Fixed, MutFixed},
+    layer::{Direction, FrameMetadata, Layer, LayerClass},
+    result::Result,
+    slice::ByteSlice,
+    token::Token,
+};
+use io::Input;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::Path,
+};
+
+/// The reader ID to replay a spill file, e.g. with `{"file": "..."}`.
+pub const READER_ID: &str = "app.genet.reader.spill";
+
+const MAGIC: &[u8; 8] = b"GENETSPL";
+const VERSION: u32 = 2;
+const HEADER_LEN: u64 = 12;
+const CHUNK_HEADER_LEN: u64 = 16;
+const CHUNK_FRAMES: usize = 1024;
+const CHUNK_BYTES: usize = 1 << 20;
+
+/// Returns the metadata of a frame with `link` and the optional values of
+/// `other`.
+fn frame_metadata(link: u32, other: &FrameMetadata) -> FrameMetadata {
+    let mut metadata = FrameMetadata::new(link).with_direction(other.direction());
+    if let Some(interface) = other.interface() {
+        metadata = metadata.with_interface(interface);
+    }
+    if let Some(len) = other.fcs_len() {
+        metadata = metadata.with_fcs_len(len);
+    }
+    metadata
+}
+
+/// A raw link-layer frame.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Record {
+    pub link: u32,
+    pub ts_sec: i64,
+    pub ts_nsec: u32,
+    pub length: u32,
+    pub metadata: FrameMetadata,
+    pub data: Vec<u8>,
+}
+
+impl Record {
+    pub fn new(root: &Layer) -> Record {
+        let metadata = root.frame_metadata();
+        let link = metadata
+            .link_type()
+            .or_else(|| {
+                root.id()
+                    .to_string()
+                    .trim_start_matches("[link-")
+                    .trim_end_matches(']')
+                    .parse()
+                    .ok()
+            })
+            .or_else(|| attr(root, "link.type"))
+            .unwrap_or(0);
+        let ts = timestamp(root).unwrap_or(0);
+        let data = root.data().to_vec();
+        Record {
+            link,
+            ts_sec: ts.div_euclid(1_000_000_000) as i64,
+            ts_nsec: ts.rem_euclid(1_000_000_000) as u32,
+            length: attr(root, "link.length").unwrap_or(data.len() as u32),
+            metadata: frame_metadata(link, &metadata),
+            data,
+        }
+    }
+
+    fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_u32::<LittleEndian>(self.link)?;
+        w.write_i64::<LittleEndian>(self.ts_sec)?;
+        w.write_u32::<LittleEndian>(self.ts_nsec)?;
+        w.write_u32::<LittleEndian>(self.length)?;
+        w.write_u32::<LittleEndian>(self.metadata.interface().unwrap_or(u32::MAX))?;
+        w.write_u8(self.metadata.direction() as u8)?;
+        w.write_u8(self.metadata.fcs_len().unwrap_or(u8::MAX))?;
+        w.write_u32::<LittleEndian>(self.data.len() as u32)?;
+        w.write_all(&self.data)
+    }
+
+    fn read<R: Read>(r: &mut R, version: u32) -> io::Result<Record> {
+        let mut record = Record {
+            link: r.read_u32::<LittleEndian>()?,
+            ts_sec: r.read_i64::<LittleEndian>()?,
+            ts_nsec: r.read_u32::<LittleEndian>()?,
+            length: r.read_u32::<LittleEndian>()?,
+            metadata: FrameMetadata::default(),
+            data: Vec::new(),
+        };
+        let mut metadata = FrameMetadata::default();
+        if version >= 2 {
+            let interface = r.read_u32::<LittleEndian>()?;
+            if interface != u32::MAX {
+                metadata = metadata.with_interface(interface);
+            }
+            metadata = metadata.with_direction(match r.read_u8()? {
+                1 => Direction::Inbound,
+                2 => Direction::Outbound,
+                _ => Direction::Unknown,
+            });
+            let fcs_len = r.read_u8()?;
+            if fcs_len != u8::MAX {
+                metadata = metadata.with_fcs_len(fcs_len);
+            }
+        }
+        record.metadata = frame_metadata(record.link, &metadata);
+        let caplen = r.read_u32::<LittleEndian>()?;
+        r.take(u64::from(caplen)).read_to_end(&mut record.data)?;
+        if record.data.len() != caplen as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        Ok(record)
+    }
+}
+
+/// Appends frames to a spill file.
+pub struct SpillWriter {
+    file: BufWriter<File>,
+    chunk: Vec<u8>,
+    chunk_frames: usize,
+    len: u64,
+}
+
+impl SpillWriter {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<SpillWriter> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_u32::<LittleEndian>(VERSION)?;
+        Ok(SpillWriter {
+            file,
+            chunk: Vec::new(),
+            chunk_frames: 0,
+            len: 0,
+        })
+    }
+
+    /// Returns the number of frames written.
+    pub fn len(&self) -> u64 {
+        self.len + self.chunk_frames as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends the raw frames of root layers.
+    pub fn append(&mut self, roots: &[MutFixed<Layer>]) -> io::Result<()> {
+        for root in roots {
+            Record::new(root).write(&mut self.chunk)?;
+            self.chunk_frames += 1;
+            if self.chunk_frames >= CHUNK_FRAMES || self.chunk.len() >= CHUNK_BYTES {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the pending frames as a chunk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.chunk_frames > 0 {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(&self.chunk)?;
+            let compressed = encoder.finish()?;
+            self.file.write_u64::<LittleEndian>(self.len)?;
+            self.file
+                .write_u32::<LittleEndian>(self.chunk_frames as u32)?;
+            self.file
+                .write_u32::<LittleEndian>(compressed.len() as u32)?;
+            self.file.write_all(&compressed)?;
+            self.len += self.chunk_frames as u64;
+            self.chunk.clear();
+            self.chunk_frames = 0;
+        }
+        self.file.flush()
+    }
+}
+
+impl fmt::Debug for SpillWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SpillWriter {}", self.len())
+    }
+}
+
+impl Drop for SpillWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Chunk {
+    first: u64,
+    frames: u32,
+    offset: u64,
+    len: u32,
+}
+
+/// Reads frames from a spill file at random.
+pub struct SpillReader {
+    file: BufReader<File>,
+    version: u32,
+    chunks: Vec<Chunk>,
+    cache: Option<(usize, Vec<Record>)>,
+}
+
+impl SpillReader {
+    /// Opens a spill file and scans its chunk headers. A truncated chunk at
+    /// the end, e.g. after a crash, is ignored.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<SpillReader> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        file.read_exact(&mut magic)?;
+        let version = file.read_u32::<LittleEndian>()?;
+        if &magic != MAGIC || version == 0 || version > VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a spill file",
+            ));
+        }
+        let size = file.get_ref().metadata()?.len();
+        let mut chunks = Vec::new();
+        let mut offset = HEADER_LEN;
+        while offset + CHUNK_HEADER_LEN <= size {
+            file.seek(SeekFrom::Start(offset))?;
+            let chunk = Chunk {
+                first: file.read_u64::<LittleEndian>()?,
+                frames: file.read_u32::<LittleEndian>()?,
+                offset: offset + CHUNK_HEADER_LEN,
+                len: file.read_u32::<LittleEndian>()?,
+            };
+            if chunk.offset + u64::from(chunk.len) > size {
+                break;
+            }
+            offset = chunk.offset + u64::from(chunk.len);
+            chunks.push(chunk);
+        }
+        Ok(SpillReader {
+            file,
+            version,
+            chunks,
+            cache: None,
+        })
+    }
+
+    /// Returns the number of frames.
+    pub fn len(&self) -> u64 {
+        self.chunks
+            .last()
+            .map_or(0, |c| c.first + u64::from(c.frames))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn chunk(&mut self, index: usize) -> io::Result<&[Record]> {
+        if self.cache.as_ref().map(|c| c.0) != Some(index) {
+            let chunk = self.chunks[index];
+            self.file.seek(SeekFrom::Start(chunk.offset))?;
+            let mut decoder = DeflateDecoder::new((&mut self.file).take(u64::from(chunk.len)));
+            let version = self.version;
+            let records = (0..chunk.frames)
+                .map(|_| Record::read(&mut decoder, version))
+                .collect::<io::Result<Vec<_>>>()?;
+            self.cache = Some((index, records));
+        }
+        Ok(self.cache.as_ref().map(|c| &c.1[..]).unwrap_or(&[]))
+    }
+
+    /// Reads the frames in `range`.
+    pub fn read(&mut self, range: Range<u64>) -> io::Result<Vec<Record>> {
+        let start = match self.chunks.binary_search_by_key(&range.start, |c| c.first) {
+            Ok(index) => index,
+            Err(index) => index.saturating_sub(1),
+        };
+        let mut records = Vec::new();
+        for index in start..self.chunks.len() {
+            let first = self.chunks[index].first;
+            if first >= range.end {
+                break;
+            }
+            let chunk = self.chunk(index)?;
+            let skip = range.start.saturating_sub(first) as usize;
+            let take = (range.end - first) as usize;
+            records.extend(chunk.iter().take(take).skip(skip).cloned());
+        }
+        Ok(records)
+    }
+}
+
+impl fmt::Debug for SpillReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SpillReader {}", self.len())
+    }
+}
+
+/// An input replaying a spill file from the beginning.
+pub struct SpillInput {
+    reader: SpillReader,
+    offset: u64,
+    links: FnvHashMap<u32, Fixed<LayerClass>>,
+    length: Fixed<AttrClass>,
+    ts: Fixed<AttrClass>,
+    ts_sec: Fixed<AttrClass>,
+    ts_nsec: Fixed<AttrClass>,
+}
+
+impl SpillInput {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<SpillInput> {
+        Ok(SpillInput {
+            reader: SpillReader::open(path)?,
+            offset: 0,
+            links: FnvHashMap::default(),
+            length: Fixed::new(AttrClass::builder("link.length").build()),
+            ts: Fixed::new(
+                AttrClass::builder("link.timestamp")
+                    .typ("@datetime:unix")
+                    .build(),
+            ),
+            ts_sec: Fixed::new(AttrClass::builder("link.timestamp.sec").build()),
+            ts_nsec: Fixed::new(AttrClass::builder("link.timestamp.nsec").build()),
+        })
+    }
+
+    fn layer(&mut self, record: Record) -> MutFixed<Layer> {
+        let class = self
+            .links
+            .entry(record.link)
+            .or_insert_with(|| {
+                let id = Token::from(format!("[link-{}]", record.link));
+                Fixed::new(LayerClass::builder(id).build())
+            })
+            .clone();
+        let ts = record.ts_sec as f64 + f64::from(record.ts_nsec) / 1_000_000_000f64;
+        let mut layer = Layer::new(class, ByteSlice::from(record.data));
+        layer.set_frame_metadata(record.metadata);
+        layer.add_attr(
+            Attr::builder(self.length.clone())
+                .value(u64::from(record.length))
+                .build(),
+        );
+        layer.add_attr(Attr::builder(self.ts.clone()).value(ts).build());
+        layer.add_attr(
+            Attr::builder(self.ts_sec.clone())
+                .value(record.ts_sec)
+                .build(),
+        );
+        layer.add_attr(
+            Attr::builder(self.ts_nsec.clone())
+                .value(u64::from(record.ts_nsec))
+                .build(),
+        );
+        MutFixed::new(layer)
+    }
+}
+
+impl fmt::Debug for SpillInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SpillInput {}", self.offset)
+    }
+}
+
+impl Input for SpillInput {
+    fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+        let end = (self.offset + CHUNK_FRAMES as u64).min(self.reader.len());
+        let records = self.reader.read(self.offset..end)?;
+        self.offset = end;
+        Ok(records
+            .into_iter()
+            .map(|record| self.layer(record))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{
+        fixed::MutFixed,
+        layer::{Direction, FrameMetadata, Layer},
+        slice::ByteSlice,
+    };
+    use io::Input;
+    use spill::{Record, SpillInput, SpillReader, SpillWriter, CHUNK_FRAMES};
+    use std::{env, fs};
+    use test_util;
+
+    fn root(index: u32) -> MutFixed<Layer> {
+        let mut layer = test_util::root()
+            .data(ByteSlice::from(vec![index as u8; 3]))
+            .attr("link.length", 7u64)
+            .attr("link.timestamp.sec", u64::from(index))
+            .attr("link.timestamp.usec", 250u64)
+            .build();
+        layer.set_frame_metadata(
+            FrameMetadata::new(1)
+                .with_interface(index & 1)
+                .with_direction(Direction::Outbound),
+        );
+        layer
+    }
+
+    #[test]
+    fn spill() {
+        let path = env::temp_dir().join("genet-spill-test.gspill");
+        let len = CHUNK_FRAMES as u32 * 2 + 100;
+        {
+            let mut writer = SpillWriter::create(&path).unwrap();
+            let roots = (0..len).map(root).collect::<Vec<_>>();
+            writer.append(&roots[..10]).unwrap();
+            writer.append(&roots[10..]).unwrap();
+            assert_eq!(writer.len(), u64::from(len));
+        }
+
+        let mut reader = SpillReader::open(&path).unwrap();
+        assert_eq!(reader.len(), u64::from(len));
+        let records = reader.read(1000..1030).unwrap();
+        assert_eq!(records.len(), 30);
+        assert_eq!(
+            records[25],
+            Record {
+                link: 1,
+                ts_sec: 1025,
+                ts_nsec: 250_000,
+                length: 7,
+                metadata: FrameMetadata::new(1)
+                    .with_interface(1)
+                    .with_direction(Direction::Outbound),
+                data: vec![1025u32 as u8; 3],
+            }
+        );
+        assert_eq!(
+            reader
+                .read(u64::from(len) - 1..u64::from(len) + 10)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let mut input = SpillInput::open(&path).unwrap();
+        let mut frames = 0;
+        loop {
+            let layers = input.read().unwrap();
+            if layers.is_empty() {
+                break;
+            }
+            assert_eq!(Record::new(&layers[0]).ts_sec, frames);
+            assert_eq!(
+                layers[0].frame_metadata().interface(),
+                Some(frames as u32 & 1)
+            );
+            frames += layers.len() as i64;
+        }
+        assert_eq!(frames, i64::from(len));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,352 @@
+//! Attribute value, protocol coverage and decode pipeline statistics.
+
+use analysis::attr;
+use diff::VariantRef;
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::{
+    fixed::MutFixed, layer::Layer, reader::CaptureStats, token::Token, variant::Variant,
+};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json;
+use std::collections::{HashMap, VecDeque};
+
+/// The number of samples kept for each input, a day at the interval of the
+/// store.
+const MAX_CAPTURE_SAMPLES: usize = 86_400;
+
+/// The number of occurrences of an attribute value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueCount {
+    pub value: Variant,
+    pub count: u64,
+}
+
+impl Serialize for ValueCount {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("value", &VariantRef(&self.value))?;
+        map.serialize_entry("count", &self.count)?;
+        map.end()
+    }
+}
+
+/// Statistics of a stage of the decode pipeline.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StageStats {
+    pub threads: usize,
+    pub frames: usize,
+    pub batches: usize,
+    pub busy_micros: u64,
+}
+
+/// Statistics of the parallel and serial decode stages.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct PipelineStats {
+    pub parallel: StageStats,
+    pub serial: StageStats,
+}
+
+/// Counts the values of an attribute over frames.
+///
+/// A value is counted once for each layer which has the attribute, so that
+/// e.g. both addresses of a tunneled IP packet are counted.
+pub struct ValueCounter {
+    id: Token,
+    counts: HashMap<String, ValueCount>,
+}
+
+impl ValueCounter {
+    pub fn new(id: &str) -> ValueCounter {
+        ValueCounter {
+            id: Token::from(id),
+            counts: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, layers: &[MutFixed<Layer>]) {
+        for layer in layers {
+            let value = match layer.attr(self.id).map(|attr| attr.try_get(layer)) {
+                Some(Ok(value)) => value,
+                _ => continue,
+            };
+            let key = serde_json::to_string(&VariantRef(&value)).unwrap_or_default();
+            self.counts
+                .entry(key)
+                .or_insert_with(|| ValueCount { value, count: 0 })
+                .count += 1;
+        }
+    }
+
+    /// Returns the `n` most frequent values, or all values if `n` is zero.
+    pub fn top(self, n: usize) -> Vec<ValueCount> {
+        let mut counts = self.counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(&b.0)));
+        if n > 0 {
+            counts.truncate(n);
+        }
+        counts.into_iter().map(|(_, count)| count).collect()
+    }
+}
+
+/// The undecoded payloads ending at a layer and a port.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CoverageEntry {
+    /// The ID of the last decoded layer.
+    pub layer: String,
+    /// The lower port of the nearest layer with ports, which is usually the
+    /// port of the server, e.g. 443 for both directions of an HTTPS session.
+    pub port: Option<u64>,
+    pub frames: u64,
+    pub bytes: u64,
+}
+
+/// The number of frames and bytes left in undecoded payloads.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageReport {
+    pub frames: u64,
+    pub bytes: u64,
+    pub undecoded_frames: u64,
+    /// The bytes of the undecoded payloads.
+    pub undecoded_bytes: u64,
+    /// Sorted by descending bytes.
+    pub entries: Vec<CoverageEntry>,
+}
+
+/// Counts the undecoded payloads of frames.
+///
+/// A payload is undecoded if no decoder has added a child layer to its
+/// layer. The bytes of the `@data:` payloads are counted, or those of the
+/// other payloads if the layer has no `@data:` payload, so that data
+/// reassembled from the payloads of other frames is not counted twice.
+#[derive(Default)]
+pub struct CoverageCounter {
+    frames: u64,
+    bytes: u64,
+    undecoded_frames: u64,
+    entries: HashMap<(Token, Option<u64>), (u64, u64)>,
+}
+
+impl CoverageCounter {
+    pub fn new() -> CoverageCounter {
+        Self::default()
+    }
+
+    pub fn add(&mut self, frame: &Frame) {
+        let layers = frame.layers();
+        let root = match layers.first() {
+            Some(root) => root,
+            None => return,
+        };
+        self.frames += 1;
+        self.bytes += attr(root, "link.length").unwrap_or(root.data().len() as u64);
+        let mut undecoded = false;
+        for (index, layer) in layers.iter().enumerate() {
+            if frame.tree_indices().get(index).is_some_and(|n| *n > 0) {
+                continue;
+            }
+            let bytes = Self::undecoded_bytes(layer);
+            if bytes == 0 {
+                continue;
+            }
+            undecoded = true;
+            let port = layers[..=index].iter().rev().find_map(|layer| {
+                let id = layer.id().to_string();
+                let src = attr::<u64>(layer, format!("{}.src", id))?;
+                let dst = attr::<u64>(layer, format!("{}.dst", id))?;
+                Some(src.min(dst))
+            });
+            let entry = self.entries.entry((layer.id(), port)).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += bytes;
+        }
+        if undecoded {
+            self.undecoded_frames += 1;
+        }
+    }
+
+    fn undecoded_bytes(layer: &Layer) -> u64 {
+        let payloads = layer.payloads();
+        let (data, other) = payloads
+            .iter()
+            .partition::<Vec<_>, _>(|p| p.id().to_string().starts_with("@data:"));
+        let payloads = if data.is_empty() { other } else { data };
+        payloads.iter().map(|p| p.data().len() as u64).sum()
+    }
+
+    pub fn report(self) -> CoverageReport {
+        let mut entries = self
+            .entries
+            .into_iter()
+            .map(|((layer, port), (frames, bytes))| CoverageEntry {
+                layer: layer.to_string(),
+                port,
+                frames,
+                bytes,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            b.bytes
+                .cmp(&a.bytes)
+                .then_with(|| a.layer.cmp(&b.layer))
+                .then_with(|| a.port.cmp(&b.port))
+        });
+        CoverageReport {
+            frames: self.frames,
+            bytes: self.bytes,
+            undecoded_frames: self.undecoded_frames,
+            undecoded_bytes: entries.iter().map(|e| e.bytes).sum(),
+            entries,
+        }
+    }
+}
+
+/// The statistics of a live capture at a point in time.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct CaptureSample {
+    /// The UNIX time in seconds.
+    pub time: f64,
+    /// The number of frames read from the input.
+    pub frames: u64,
+    pub stats: CaptureStats,
+}
+
+/// The statistics of the live captures of a session over time.
+#[derive(Default, Debug)]
+pub struct CaptureHistory {
+    inputs: FnvHashMap<u32, VecDeque<CaptureSample>>,
+}
+
+impl CaptureHistory {
+    pub fn new() -> CaptureHistory {
+        Self::default()
+    }
+
+    /// Adds a sample of the input `id`, removing the oldest one if the
+    /// input has too many samples.
+    pub fn push(&mut self, id: u32, sample: CaptureSample) {
+        let samples = self.inputs.entry(id).or_default();
+        if samples.len() >= MAX_CAPTURE_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// Returns the samples of the input `id`, from the oldest.
+    pub fn samples(&self, id: u32) -> Vec<CaptureSample> {
+        self.inputs
+            .get(&id)
+            .map(|samples| samples.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use frame::Frame;
+    use genet_abi::{
+        fixed::MutFixed,
+        layer::{Layer, Payload},
+        slice::ByteSlice,
+        variant::Variant,
+    };
+    use stats::{CoverageCounter, CoverageEntry, ValueCounter};
+    use test_util;
+
+    fn layer(attrs: &[(&'static str, &'static str)]) -> MutFixed<Layer> {
+        attrs
+            .iter()
+            .fold(test_util::layer("test"), |layer, (id, value)| {
+                layer.attr(id, value.to_string().into_boxed_str())
+            })
+            .build()
+    }
+
+    #[test]
+    fn top() {
+        let mut counter = ValueCounter::new("dns.name");
+        for name in &["b", "a", "b", "c", "a", "b"] {
+            counter.add(&[layer(&[("dns.name", name)])]);
+        }
+        counter.add(&[layer(&[("dns.type", "a")])]);
+        counter.add(&[layer(&[("dns.name", "c")]), layer(&[("dns.name", "d")])]);
+
+        let top = counter.top(3);
+        assert_eq!(top.len(), 3);
+        assert_eq!(
+            top[0].value,
+            Variant::String("b".to_string().into_boxed_str())
+        );
+        assert_eq!(top[0].count, 3);
+        assert_eq!(
+            top[1].value,
+            Variant::String("a".to_string().into_boxed_str())
+        );
+        assert_eq!(top[1].count, 2);
+        assert_eq!(
+            top[2].value,
+            Variant::String("c".to_string().into_boxed_str())
+        );
+        assert_eq!(top[2].count, 2);
+    }
+
+    fn frame(sport: u64, dport: u64, payload: usize, decoded: bool) -> Frame {
+        let data = ByteSlice::from(vec![0; payload]);
+        let mut layers = vec![
+            test_util::root()
+                .data(ByteSlice::from(vec![0; 60 + payload]))
+                .build(),
+            test_util::layer("tcp")
+                .attr("tcp.src", sport)
+                .attr("tcp.dst", dport)
+                .payload(Payload::new(data, "@data:tcp"))
+                .payload(Payload::new(data, "@stream:tcp"))
+                .build(),
+        ];
+        let mut indices = vec![1, 0];
+        if decoded {
+            layers.push(test_util::layer("http").build());
+            indices = vec![1, 1, 0];
+        }
+        let mut frame = test_util::frame(0, layers);
+        frame.set_tree_indices(indices);
+        frame
+    }
+
+    #[test]
+    fn coverage() {
+        let mut counter = CoverageCounter::new();
+        counter.add(&frame(50000, 443, 100, false));
+        counter.add(&frame(443, 50000, 300, false));
+        counter.add(&frame(50001, 5432, 50, false));
+        counter.add(&frame(50002, 80, 200, true));
+        counter.add(&frame(50002, 443, 0, false));
+        let report = counter.report();
+        assert_eq!(report.frames, 5);
+        assert_eq!(report.bytes, 5 * 60 + 650);
+        assert_eq!(report.undecoded_frames, 3);
+        assert_eq!(report.undecoded_bytes, 450);
+        assert_eq!(
+            report.entries,
+            vec![
+                CoverageEntry {
+                    layer: "tcp".to_string(),
+                    port: Some(443),
+                    frames: 2,
+                    bytes: 400,
+                },
+                CoverageEntry {
+                    layer: "tcp".to_string(),
+                    port: Some(5432),
+                    frames: 1,
+                    bytes: 50,
+                },
+            ]
+        );
+    }
+}
@@ -1,32 +1,67 @@
+use analysis::Analyzer;
 use array_vec::ArrayVec;
+use column::ColumnStore;
+use compare::Comparison;
+use conversation::Conversations;
 use crossbeam_channel;
-use decoder::{parallel, serial};
+use decoder::{metrics::Metrics, parallel, serial};
+use expert::Expert;
+use flags::FrameFlags;
 use fnv::FnvHashMap;
 use frame::Frame;
-use genet_abi::{fixed::MutFixed, layer::Layer};
-use genet_filter::{self, Filter};
+use genet_abi::{decoder::DecoderBox, fixed::MutFixed, layer::Layer};
+use genet_filter::Filter;
+use index::{self, FrameIndex, Summary};
 use io::{Input, Output};
+use lazy::{CacheStats, Hold, Lazy};
 use parking_lot::RwLock;
 use profile::Profile;
+use refilter::{self, IncrementalFilter};
 use result::Result;
+use ring::{RingBuffer, Rotation};
+use roaring::RoaringBitmap;
+use serde_json;
+use spill::SpillWriter;
+use stats::{
+    CaptureHistory, CaptureSample, CoverageCounter, CoverageReport, PipelineStats, ValueCount,
+    ValueCounter,
+};
 use std::{
-    fmt,
+    collections::VecDeque,
+    fmt, mem,
     ops::Range,
     panic::{self, AssertUnwindSafe},
-    sync::Arc,
+    path::Path,
+    slice,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use time_shift::TimeShift;
 
 const OUTPUT_BLOCK_SIZE: usize = 65536;
 const MAX_FILTER_SIZE: usize = 16384;
+const MAX_PENDING_FRAMES: usize = 262_144;
+const BACKPRESSURE_WAIT_MS: u64 = 10;
+const CAPTURE_STATS_INTERVAL_MS: u64 = 1000;
+const REDECODE_BATCH_SIZE: usize = 4096;
+const MAX_REDECODE_BATCHES: usize = 4;
 
 pub trait Callback: Send {
     fn on_frames_updated(&self, _frames: u32) {}
     fn on_async_frames_updated(&self, _frames: u32) {}
     fn on_filtered_frames_updated(&self, _id: u32, _frames: u32) {}
+    fn on_filtered_frames_diff(&self, _id: u32, _added: Vec<u32>, _removed: Vec<u32>) {}
     fn on_output_done(&self, _id: u32, _error: Option<Box<::std::error::Error + Send>>) {}
     fn on_input_done(&self, _id: u32, _error: Option<Box<::std::error::Error + Send>>) {}
     fn on_error(&self, _error: Box<::std::error::Error + Send>) {}
+    fn on_frames_evicted(&self, _start: u32) {}
+    fn on_rotated(&self, _rotation: Rotation) {}
+    fn on_capture_stats(&self, _id: u32, _sample: CaptureSample) {}
+    fn on_redecode_progress(&self, _frames: u32, _total: u32) {}
 }
 
 #[derive(Debug)]
@@ -34,8 +69,18 @@ enum Command {
     PushFrames(Option<u32>, Result<Vec<MutFixed<Layer>>>),
     PushSerialFrames(Vec<Frame>),
     StoreFrames(Vec<Frame>),
+    PushRedecodedFrames(usize, Vec<Frame>),
+    StoreRedecodedFrames(usize, Vec<Frame>),
     SetFilter(u32, Option<Filter>),
-    PushOutput(u32, Box<Output>, Option<Filter>),
+    PushOutput(u32, Box<Output>, Option<Filter>, Option<Range<u32>>),
+    SetSpill(Option<SpillWriter>),
+    SetRing(Option<RingBuffer>),
+    SetWindow(Option<usize>),
+    CaptureStats(u32, CaptureSample),
+    UpdateConfig(String, String),
+    SetDecoders(Vec<DecoderBox>),
+    Redecode,
+    RefreshMarks,
     Close,
 }
 
@@ -55,7 +100,8 @@ impl fmt::Display for Error {
 }
 
 type FrameStore = Arc<RwLock<ArrayVec<Frame>>>;
-type FilteredFrameStore = Arc<RwLock<FnvHashMap<u32, Vec<u32>>>>;
+type FilteredFrameStore = Arc<RwLock<FnvHashMap<u32, RoaringBitmap>>>;
+type FrameIndexStore = Option<Arc<RwLock<FrameIndex>>>;
 
 #[derive(Debug)]
 pub struct Store {
@@ -63,48 +109,322 @@ pub struct Store {
     ev: EventLoop,
     frames: FrameStore,
     filtered: FilteredFrameStore,
+    index: FrameIndexStore,
+    lazy: Option<Lazy>,
+    expert: Expert,
+    conversations: Conversations,
+    flags: FrameFlags,
+    comparison: Comparison,
+    time_shift: TimeShift,
+    capture_stats: Arc<RwLock<CaptureHistory>>,
+    pending: Arc<AtomicUsize>,
     inputs: FnvHashMap<u32, InputContext>,
     inputs_trash: Vec<InputContext>,
+    generation: usize,
 }
 
 impl Store {
-    pub fn new<C: 'static + Callback + Clone>(profile: Profile, callback: C) -> Store {
+    pub fn new<C: 'static + Callback + Clone>(mut profile: Profile, callback: C) -> Store {
+        profile.reset_session_state();
+        let expert = profile.expert().clone();
+        let conversations = profile.conversations().clone();
+        let flags = profile.frame_flags().clone();
+        let comparison = profile.comparison().clone();
+        let time_shift = profile.time_shift().clone();
         let frames = Arc::new(RwLock::new(ArrayVec::new()));
         let filtered = Arc::new(RwLock::new(FnvHashMap::default()));
-        let (ev, send) = EventLoop::new(profile, callback, frames.clone(), filtered.clone());
+        let pending = Arc::new(AtomicUsize::new(0));
+        let dir = profile
+            .get_config(index::DIR_KEY)
+            .and_then(|value| serde_json::from_str::<String>(&value).ok());
+        let index = match dir {
+            Some(ref dir) if !dir.is_empty() => match FrameIndex::create(Path::new(dir)) {
+                Ok(index) => Some(Arc::new(RwLock::new(index))),
+                Err(err) => {
+                    let err = Error(format!("failed to create frame index: {}", err));
+                    callback.on_error(Box::new(err));
+                    None
+                }
+            },
+            _ => None,
+        };
+        let lazy = Lazy::from_profile(&profile);
+        let (ev, send) = EventLoop::new(
+            profile,
+            callback,
+            frames.clone(),
+            filtered.clone(),
+            index.clone(),
+            lazy.clone(),
+            pending.clone(),
+        );
         Store {
             sender: send,
             ev,
             frames,
             filtered,
+            index,
+            lazy,
+            expert,
+            conversations,
+            flags,
+            comparison,
+            time_shift,
+            capture_stats: Arc::new(RwLock::new(CaptureHistory::new())),
+            pending,
             inputs: FnvHashMap::default(),
             inputs_trash: Vec::new(),
+            generation: 0,
         }
     }
 
+    /// Returns the frames in `range`.
+    ///
+    /// In lazy mode, the frames are decoded before being returned and may be
+    /// evicted later unless they are pinned with `pin_frames` or held, see
+    /// `held_frames`.
     pub fn frames(&self, range: Range<usize>) -> Vec<*const Frame> {
+        let _cache = self
+            .lazy
+            .as_ref()
+            .map(|lazy| lazy.decode(&self.frames, range.clone()));
         self.frames
             .read()
-            .iter()
-            .skip(range.start)
-            .take(range.end.saturating_sub(range.start))
+            .range(range)
             .map(|f| f as *const Frame)
             .collect::<Vec<_>>()
     }
 
+    /// Returns the frames in `range` like `frames`.
+    ///
+    /// In lazy mode, each frame comes with a hold which keeps its layers
+    /// from being freed until it is dropped, e.g. by the finalizer of a
+    /// JavaScript object.
+    pub fn held_frames(&self, range: Range<usize>) -> Vec<(*const Frame, Option<Hold>)> {
+        let mut cache = self
+            .lazy
+            .as_ref()
+            .map(|lazy| (lazy, lazy.decode(&self.frames, range.clone())));
+        self.frames
+            .read()
+            .range(range)
+            .map(|f| {
+                let hold = cache
+                    .as_mut()
+                    .map(|(lazy, cache)| lazy.hold(cache, f.index() as usize));
+                (f as *const Frame, hold)
+            })
+            .collect::<Vec<_>>()
+    }
+
+    /// Returns the summary columns of frames.
+    ///
+    /// The summaries are read from the frame index if there is one, so that
+    /// listing frames does not touch their layers.
+    pub fn summaries(&self, range: Range<usize>) -> Vec<Summary> {
+        let len = range.end.saturating_sub(range.start);
+        if let Some(ref index) = self.index {
+            let index = index.read();
+            if index.len() >= self.len().min(range.end) {
+                return range
+                    .take(len)
+                    .filter_map(|i| index.summary(i))
+                    .collect::<Vec<_>>();
+            }
+        }
+        self.frames
+            .read()
+            .range(range)
+            .map(Summary::new)
+            .collect::<Vec<_>>()
+    }
+
+    /// Reads the raw data of a frame back from the frame index.
+    pub fn frame_data(&self, index: usize) -> Option<Vec<u8>> {
+        self.index.as_ref()?.read().data(index).ok()
+    }
+
+    /// Returns the most frequent values of the attribute `id` in the frames
+    /// matching `filter`, except the ignored ones.
+    pub fn value_counts(&self, id: &str, filter: Option<&Filter>, top_n: usize) -> Vec<ValueCount> {
+        let mut counter = ValueCounter::new(id);
+        self.scan(filter, |frame| {
+            if !self.flags.is_ignored(frame.index()) {
+                counter.add(frame.layers());
+            }
+            true
+        });
+        counter.top(top_n)
+    }
+
+    /// Returns the undecoded payloads of the frames matching `filter`, except
+    /// the ignored ones.
+    pub fn coverage(&self, filter: Option<&Filter>) -> CoverageReport {
+        let mut counter = CoverageCounter::new();
+        self.scan(filter, |frame| {
+            if !self.flags.is_ignored(frame.index()) {
+                counter.add(frame);
+            }
+            true
+        });
+        counter.report()
+    }
+
+    /// Calls `f` with each frame matching `filter` in order until it
+    /// returns false.
+    pub fn scan<F>(&self, filter: Option<&Filter>, f: F)
+    where
+        F: FnMut(&Frame) -> bool,
+    {
+        self.scan_from(0, filter, f);
+    }
+
+    /// Calls `f` with each frame matching `filter` from the frame `start`
+    /// in order until it returns false.
+    ///
+    /// Returns the index following the last frame scanned.
+    pub fn scan_from<F>(&self, start: usize, filter: Option<&Filter>, mut f: F) -> usize
+    where
+        F: FnMut(&Frame) -> bool,
+    {
+        let len = self.len();
+        let mut offset = start;
+        while offset < len {
+            let range = offset..len.min(offset + MAX_FILTER_SIZE);
+            let _cache = self
+                .lazy
+                .as_ref()
+                .map(|lazy| lazy.decode(&self.frames, range.clone()));
+            for frame in self.frames.read().range(range.clone()) {
+                let ctx = self.flags.context(frame);
+                let matched = match filter {
+                    Some(filter) => filter.test(&ctx),
+                    None => true,
+                };
+                if matched && !f(frame) {
+                    return frame.index() as usize + 1;
+                }
+            }
+            offset = range.end;
+        }
+        offset.max(start)
+    }
+
+    /// Keeps the frames in `range` decoded until `unpin_frames` is called
+    /// with the same `id`. Does nothing unless lazy decoding is enabled.
+    pub fn pin_frames(&self, id: u32, range: Range<usize>) {
+        if let Some(lazy) = &self.lazy {
+            lazy.pin(id, range);
+        }
+    }
+
+    pub fn unpin_frames(&self, id: u32) {
+        if let Some(lazy) = &self.lazy {
+            lazy.unpin(id);
+        }
+    }
+
+    /// Returns the statistics of the decoded tree cache, or `None` unless
+    /// lazy decoding is enabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.lazy.as_ref().map(|lazy| lazy.stats())
+    }
+
+    /// Returns the findings of the decoded frames.
+    pub fn expert(&self) -> &Expert {
+        &self.expert
+    }
+
+    /// Returns the conversation tables of the decoded frames.
+    pub fn conversations(&self) -> &Conversations {
+        &self.conversations
+    }
+
+    /// Returns the marked and ignored frames.
+    pub fn frame_flags(&self) -> &FrameFlags {
+        &self.flags
+    }
+
+    /// Returns the comparison with another capture, which is added to the
+    /// frames decoded after it is set.
+    pub fn comparison(&self) -> &Comparison {
+        &self.comparison
+    }
+
+    /// Returns the correction of the timestamps, which is applied to the
+    /// frames decoded after it is set.
+    pub fn time_shift(&self) -> &TimeShift {
+        &self.time_shift
+    }
+
+    /// Returns the thread counts and timings of the decode stages.
+    pub fn pipeline_stats(&self) -> PipelineStats {
+        self.ev.metrics.stats()
+    }
+
+    /// Returns the statistics of the live capture of the input `id` over
+    /// time, from the oldest.
+    pub fn capture_stats(&self, id: u32) -> Vec<CaptureSample> {
+        self.capture_stats.read().samples(id)
+    }
+
+    /// Returns the indices of the matched frames at the rows `range` of the
+    /// filter `id`.
     pub fn filtered_frames(&self, id: u32, range: Range<usize>) -> Vec<u32> {
         let filtered = self.filtered.read();
-        if let Some(vec) = filtered.get(&id) {
-            vec.iter()
-                .skip(range.start)
+        let bitmap = match filtered.get(&id) {
+            Some(bitmap) => bitmap,
+            None => return Vec::new(),
+        };
+        match bitmap.select(range.start as u32) {
+            Some(first) => bitmap
+                .range(first..)
                 .take(range.end.saturating_sub(range.start))
-                .cloned()
-                .collect::<Vec<_>>()
-        } else {
-            Vec::new()
+                .collect(),
+            None => Vec::new(),
         }
     }
 
+    /// Returns the number of frames matched by the filter `id`.
+    pub fn filtered_len(&self, id: u32) -> usize {
+        let filtered = self.filtered.read();
+        filtered.get(&id).map_or(0, |bitmap| bitmap.len() as usize)
+    }
+
+    /// Returns the number of frames before the frame `index` matched by the
+    /// filter `id`, i.e. the row of the frame if it matches.
+    pub fn filtered_rank(&self, id: u32, index: u32) -> usize {
+        let filtered = self.filtered.read();
+        filtered.get(&id).map_or(0, |bitmap| {
+            (bitmap.rank(index) - u64::from(bitmap.contains(index))) as usize
+        })
+    }
+
+    /// Returns the index of the frame at the row `row` of the filter `id`.
+    pub fn filtered_select(&self, id: u32, row: usize) -> Option<u32> {
+        let filtered = self.filtered.read();
+        filtered
+            .get(&id)
+            .and_then(|bitmap| bitmap.select(row as u32))
+    }
+
+    /// Returns the frames matched by the filter `id` as a bitmap in the
+    /// portable Roaring format.
+    pub fn filtered_bitmap(&self, id: u32) -> Option<Vec<u8>> {
+        let filtered = self.filtered.read();
+        filtered.get(&id).map(serialize)
+    }
+
+    /// Returns the marked frames as a bitmap in the portable Roaring format.
+    pub fn marked_bitmap(&self) -> Vec<u8> {
+        serialize(&self.flags.marked())
+    }
+
+    /// Returns the ignored frames as a bitmap in the portable Roaring format.
+    pub fn ignored_bitmap(&self) -> Vec<u8> {
+        serialize(&self.flags.ignored())
+    }
+
     pub fn len(&self) -> usize {
         let frames = self.frames.read();
         frames.len()
@@ -114,25 +434,140 @@ impl Store {
         self.sender.send(Command::SetFilter(id, filter));
     }
 
-    pub fn push_output<O: 'static + Output>(&mut self, id: u32, output: O, filter: Option<Filter>) {
+    pub fn push_output<O: 'static + Output>(
+        &mut self,
+        id: u32,
+        output: O,
+        filter: Option<Filter>,
+        range: Option<Range<u32>>,
+    ) {
+        self.sender
+            .send(Command::PushOutput(id, Box::new(output), filter, range));
+    }
+
+    pub fn redecode(&mut self) {
+        self.generation += 1;
+        self.sender.send(Command::Redecode);
+    }
+
+    /// Marks or unmarks the frames `indices`, and evaluates the filters
+    /// using `frame.marked` again.
+    pub fn set_marked(&mut self, indices: &[u32], marked: bool) {
+        if self.flags.set_marked(indices, marked) {
+            self.generation += 1;
+            self.sender.send(Command::RefreshMarks);
+        }
+    }
+
+    /// Ignores or restores the frames `indices`, and decodes the frames
+    /// again. Ignored frames are left with their root layer.
+    pub fn set_ignored(&mut self, indices: &[u32], ignored: bool) {
+        if self.flags.set_ignored(indices, ignored) {
+            self.redecode();
+        }
+    }
+
+    /// Updates a config value and creates the decoders again, so that the
+    /// frames decoded after this call use the new value.
+    pub fn update_config(&mut self, key: &str, value: &str) {
         self.sender
-            .send(Command::PushOutput(id, Box::new(output), filter));
+            .send(Command::UpdateConfig(key.to_string(), value.to_string()));
+    }
+
+    /// Replaces the decoders, so that the frames decoded after this call use
+    /// the new ones.
+    pub fn set_decoders(&mut self, decoders: Vec<DecoderBox>) {
+        self.sender.send(Command::SetDecoders(decoders));
+    }
+
+    /// Returns the number of frames read from the inputs but not stored
+    /// yet.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of times the frames have been decoded again.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Tees the raw frames of the inputs into `spill`, or stops teeing if
+    /// it is `None`.
+    pub fn set_spill(&mut self, spill: Option<SpillWriter>) {
+        self.sender.send(Command::SetSpill(spill));
+    }
+
+    /// Writes the raw frames of the inputs to `ring`, or stops writing if
+    /// it is `None`.
+    pub fn set_ring_buffer(&mut self, ring: Option<RingBuffer>) {
+        self.sender.send(Command::SetRing(ring));
+    }
+
+    /// Keeps only the latest `window` frames in memory, or every frame if
+    /// it is `None`. The older frames are released with their layers and
+    /// removed from the filter results, but keep their indices, so the
+    /// frames start at `first()`.
+    ///
+    /// The columnar cache of the filters is disabled, since it is indexed by
+    /// position. The conversation tables and the findings keep counting the
+    /// released frames.
+    pub fn set_window(&mut self, window: Option<usize>) {
+        self.sender.send(Command::SetWindow(window));
+    }
+
+    /// Returns the index of the first frame which has not been released.
+    pub fn first(&self) -> usize {
+        self.frames.read().start()
     }
 
     pub fn set_input<I: 'static + Input>(&mut self, id: u32, input: I) {
         let holder = Arc::new(self.sender.clone());
         let sender = Arc::downgrade(&holder);
+        let pending = self.pending.clone();
+        let history = self.capture_stats.clone();
         let mut input = input;
         let handle = thread::spawn(move || {
+            let mut frames = 0;
+            let mut sampled = Instant::now();
+            // Samples the statistics of a live capture, which are polled once
+            // an interval and when the input ends.
+            let sample = |input: &mut I, frames: u64, sender: &crossbeam_channel::Sender<_>| {
+                if let Some(stats) = input.stats() {
+                    let time = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|time| time.as_secs_f64())
+                        .unwrap_or(0.0);
+                    let sample = CaptureSample {
+                        time,
+                        frames,
+                        stats,
+                    };
+                    history.write().push(id, sample);
+                    sender.send(Command::CaptureStats(id, sample));
+                }
+            };
             while let Some(sender) = sender.upgrade() {
+                // Stop reading until the decoders catch up, so that a fast input
+                // blocks instead of queueing an unbounded number of frames.
+                if pending.load(Ordering::Relaxed) >= MAX_PENDING_FRAMES {
+                    thread::sleep(Duration::from_millis(BACKPRESSURE_WAIT_MS));
+                    continue;
+                }
                 match input.read() {
                     Ok(layers) => {
                         if !layers.is_empty() {
+                            frames += layers.len() as u64;
+                            pending.fetch_add(layers.len(), Ordering::Relaxed);
                             sender.send(Command::PushFrames(Some(id), Ok(layers)));
                         }
+                        if sampled.elapsed() >= Duration::from_millis(CAPTURE_STATS_INTERVAL_MS) {
+                            sampled = Instant::now();
+                            sample(&mut input, frames, &sender);
+                        }
                     }
                     Err(err) => {
-                        let err = Error(err.description().to_string());
+                        sample(&mut input, frames, &sender);
+                        let err = Error(err.to_string());
                         sender.send(Command::PushFrames(Some(id), Err(Box::new(err))));
                         break;
                     }
@@ -169,47 +604,169 @@ impl Drop for InputContext {
     }
 }
 
+/// Passes the frames decoded by a parallel pool to the serial pool, or to
+/// the serial pool of the re-decode `redecode`.
 #[derive(Clone)]
 struct ParallelCallback {
     sender: crossbeam_channel::Sender<Command>,
+    redecode: Option<usize>,
 }
 
 impl parallel::Callback for ParallelCallback {
     fn done(&self, result: Vec<Frame>) {
-        self.sender.send(Command::PushSerialFrames(result));
+        self.sender.send(match self.redecode {
+            Some(id) => Command::PushRedecodedFrames(id, result),
+            None => Command::PushSerialFrames(result),
+        });
     }
 }
 
 #[derive(Clone)]
 struct SerialCallback {
     sender: crossbeam_channel::Sender<Command>,
+    redecode: Option<usize>,
 }
 
 impl serial::Callback for SerialCallback {
     fn done(&self, result: Vec<Frame>) {
-        self.sender.send(Command::StoreFrames(result));
+        self.sender.send(match self.redecode {
+            Some(id) => Command::StoreRedecodedFrames(id, result),
+            None => Command::StoreFrames(result),
+        });
+    }
+}
+
+/// Decodes the stored frames again in the pools, in batches of
+/// `REDECODE_BATCH_SIZE` frames.
+struct Redecode {
+    id: usize,
+    ppool: parallel::Pool,
+    spool: serial::Pool,
+    start: usize,
+    end: usize,
+    /// The index of the next frame sent to the pools.
+    next: usize,
+    /// The index of the next frame to be stored.
+    stored: usize,
+}
+
+impl Redecode {
+    /// Starts decoding the stored frames again, or returns None if there are
+    /// no frames.
+    fn new(
+        id: usize,
+        profile: &Profile,
+        frames: &FrameStore,
+        sender: &crossbeam_channel::Sender<Command>,
+        metrics: &Arc<Metrics>,
+    ) -> Option<Redecode> {
+        let (start, end) = {
+            let frames = frames.read();
+            (frames.start(), frames.len())
+        };
+        if start >= end {
+            return None;
+        }
+        let ppool = parallel::Pool::new(
+            profile,
+            &ParallelCallback {
+                sender: sender.clone(),
+                redecode: Some(id),
+            },
+            metrics,
+        );
+        let spool = serial::Pool::new(
+            profile.clone(),
+            SerialCallback {
+                sender: sender.clone(),
+                redecode: Some(id),
+            },
+            metrics,
+            start,
+        );
+        let mut redecode = Redecode {
+            id,
+            ppool,
+            spool,
+            start,
+            end,
+            next: start,
+            stored: start,
+        };
+        redecode.send(profile, frames);
+        Some(redecode)
+    }
+
+    /// Sends the next batches to the pools, up to `MAX_REDECODE_BATCHES`
+    /// batches at a time.
+    fn send(&mut self, profile: &Profile, frames: &FrameStore) {
+        while self.next < self.end
+            && self.next - self.stored < REDECODE_BATCH_SIZE * MAX_REDECODE_BATCHES
+        {
+            let end = self.end.min(self.next + REDECODE_BATCH_SIZE);
+            let mut batch = {
+                let frames = frames.read();
+                (self.next..end)
+                    .map(|index| {
+                        let root = &frames.get(index).unwrap().layers()[0];
+                        let root = unsafe { MutFixed::from_ptr(root.as_mut_ptr()) };
+                        Frame::new(index as u32, root)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            profile.time_shift().update(&mut batch);
+            self.ppool.process(batch);
+            self.next = end;
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.stored >= self.end
     }
 }
 
 struct FilterContext {
-    filter: Filter,
+    filter: IncrementalFilter,
     offset: usize,
+    refilter: Option<Refilter>,
+}
+
+/// The state of a filter replacing the previous filter of a view.
+///
+/// The previous result stays visible until the new filter has evaluated
+/// the same frames, and then the difference is reported at once.
+struct Refilter {
+    previous: RoaringBitmap,
+    end: usize,
+    indices: RoaringBitmap,
+}
+
+fn serialize(bitmap: &RoaringBitmap) -> Vec<u8> {
+    let mut data = Vec::with_capacity(bitmap.serialized_size());
+    bitmap.serialize_into(&mut data).unwrap();
+    data
 }
 
 struct EventLoop {
     handle: Option<JoinHandle<()>>,
     sender: crossbeam_channel::Sender<Command>,
+    metrics: Arc<Metrics>,
 }
 
 impl EventLoop {
     pub fn new<C: 'static + Callback + Clone>(
-        profile: Profile,
+        mut profile: Profile,
         callback: C,
         frames: FrameStore,
         filtered: FilteredFrameStore,
+        index: FrameIndexStore,
+        lazy: Option<Lazy>,
+        pending: Arc<AtomicUsize>,
     ) -> (EventLoop, crossbeam_channel::Sender<Command>) {
         let (send, recv) = crossbeam_channel::unbounded();
         let sender = send.clone();
+        let metrics = Arc::new(Metrics::default());
+        let ev_metrics = metrics.clone();
         let handle = thread::spawn(move || {
             let err_callback = callback.clone();
             let result = panic::catch_unwind(AssertUnwindSafe(move || {
@@ -218,27 +775,89 @@ impl EventLoop {
                     &profile,
                     &ParallelCallback {
                         sender: sender.clone(),
+                        redecode: None,
                     },
+                    &metrics,
                 );
                 let mut spool = serial::Pool::new(
                     profile.clone(),
                     SerialCallback {
                         sender: sender.clone(),
+                        redecode: None,
                     },
+                    &metrics,
+                    0,
                 );
+                // The serial pool replaced by a config update, which decodes
+                // the frames before the index until they are stored.
+                let mut retired: Option<(serial::Pool, usize)> = None;
+                let mut deferred = Vec::new();
                 let mut cnt = 0;
+                let mut index = index;
+                // Lazy frames are stored before decoding, so they have no columns.
+                let mut columns = ColumnStore::new(lazy.is_none());
+                let mut analyzer = Analyzer::from_profile(&profile);
+                let mut spill: Option<SpillWriter> = None;
+                let mut ring: Option<RingBuffer> = None;
+                let mut window = None;
+                let mut redecode: Option<Redecode> = None;
+                let mut redecodes = 0;
+                // The frames stored during a re-decode are held until it is
+                // done, so that the analysis processes the frames in order.
+                let mut held = VecDeque::new();
                 callback.on_frames_updated(0);
                 callback.on_async_frames_updated(0);
                 loop {
-                    if let Some(cmd) = recv.recv() {
+                    let cmd = if redecode.is_none() && !held.is_empty() {
+                        held.pop_front().map(Command::StoreFrames)
+                    } else {
+                        recv.recv()
+                    };
+                    if let Some(cmd) = cmd {
+                        let mut renew = false;
                         match cmd {
                             Command::PushFrames(id, result) => {
-                                Self::process_input(id, result, &mut cnt, &mut ppool, &callback)
-                            }
-                            Command::PushSerialFrames(vec) => {
-                                spool.process(vec);
+                                if let Ok(layers) = &result {
+                                    Self::process_spill(&mut spill, layers, &callback);
+                                    Self::process_ring(&mut ring, layers, &callback);
+                                }
+                                if let Some(vec) =
+                                    Self::process_input(id, result, &mut cnt, &callback)
+                                {
+                                    // Lazy frames are stored with the root layer only.
+                                    if lazy.is_some() {
+                                        sender.send(Command::StoreFrames(vec));
+                                    } else {
+                                        ppool.process(vec);
+                                    }
+                                }
                             }
+                            Command::PushSerialFrames(vec) => match &mut retired {
+                                Some((pool, end))
+                                    if vec.first().is_some_and(|f| (f.index() as usize) < *end) =>
+                                {
+                                    pool.process(vec)
+                                }
+                                Some(_) => deferred.push(vec),
+                                None => spool.process(vec),
+                            },
+                            Command::StoreFrames(vec) if redecode.is_some() => held.push_back(vec),
                             Command::StoreFrames(mut vec) => {
+                                let stored = vec.len();
+                                profile.time_shift().update(&mut vec);
+                                for frame in &mut vec {
+                                    if !profile.frame_flags().is_ignored(frame.index()) {
+                                        analyzer.process(frame);
+                                    }
+                                }
+                                profile.resolver().update(&mut vec);
+                                profile.geoip().update(&mut vec);
+                                profile.expert().update(&mut vec);
+                                profile.comparison().update(&mut vec);
+                                profile.catalog().update(&vec);
+                                profile.conversations().update(&vec);
+                                Self::process_index(&mut index, &vec, &callback);
+                                columns.append(&vec);
                                 let len = {
                                     let mut frames = frames.write();
                                     for f in vec {
@@ -246,23 +865,200 @@ impl EventLoop {
                                     }
                                     frames.len()
                                 };
+                                pending.fetch_sub(stored, Ordering::Relaxed);
                                 callback.on_frames_updated(len as u32);
                                 callback.on_async_frames_updated(len as u32);
+                                if retired.as_ref().is_some_and(|(_, end)| len >= *end) {
+                                    retired = None;
+                                    for vec in deferred.drain(..) {
+                                        spool.process(vec);
+                                    }
+                                }
+                                if let Some(window) = window {
+                                    Self::process_window(
+                                        window,
+                                        &frames,
+                                        &lazy,
+                                        &filtered,
+                                        &mut filter_map,
+                                        &callback,
+                                    );
+                                }
                             }
-                            Command::SetFilter(id, filter) => Self::process_push_filter(
+                            Command::SetFilter(id, filter) => {
+                                Self::process_push_filter(
+                                    id,
+                                    filter,
+                                    &filtered,
+                                    &mut filter_map,
+                                    &callback,
+                                );
+                                columns.retain(
+                                    filter_map.values().flat_map(|fctx| fctx.filter.plans()),
+                                );
+                            }
+                            Command::PushOutput(id, output, filter, range) => Self::process_output(
                                 id,
-                                filter,
-                                &filtered,
-                                &mut filter_map,
+                                output,
+                                &filter,
+                                range,
+                                &frames,
+                                &lazy,
+                                profile.frame_flags(),
                                 &callback,
                             ),
-                            Command::PushOutput(id, output, filter) => {
-                                Self::process_output(id, output, &filter, &frames, &callback)
+                            Command::SetSpill(writer) => spill = writer,
+                            Command::SetRing(writer) => {
+                                if let Some(mut ring) = mem::replace(&mut ring, writer) {
+                                    if let Err(err) = ring.end() {
+                                        let err =
+                                            Error(format!("failed to write ring buffer: {}", err));
+                                        callback.on_error(Box::new(err));
+                                    }
+                                }
+                            }
+                            Command::SetWindow(size) => {
+                                window = size;
+                                if window.is_some() {
+                                    columns = ColumnStore::new(false);
+                                }
+                                // The frames being decoded again are released
+                                // after the re-decode.
+                                if let (Some(window), None) = (window, &redecode) {
+                                    Self::process_window(
+                                        window,
+                                        &frames,
+                                        &lazy,
+                                        &filtered,
+                                        &mut filter_map,
+                                        &callback,
+                                    );
+                                }
+                            }
+                            Command::UpdateConfig(key, value) => {
+                                profile.update_config(&key, &value);
+                                if let Some(lazy) = &lazy {
+                                    lazy.update_config(&key, &value);
+                                }
+                                renew = true;
+                            }
+                            Command::SetDecoders(decoders) => {
+                                if let Some(lazy) = &lazy {
+                                    lazy.set_decoders(decoders.clone());
+                                }
+                                profile.set_decoders(decoders);
+                                renew = true;
+                            }
+                            Command::Redecode => {
+                                columns.clear();
+                                analyzer = Self::process_redecode(
+                                    &profile,
+                                    &frames,
+                                    &index,
+                                    &lazy,
+                                    &filtered,
+                                    &mut filter_map,
+                                    &callback,
+                                );
+                                if lazy.is_none() {
+                                    redecodes += 1;
+                                    redecode = Redecode::new(
+                                        redecodes, &profile, &frames, &sender, &metrics,
+                                    );
+                                    if redecode.is_none() {
+                                        Self::reset_filters(&filtered, &mut filter_map, &callback);
+                                    }
+                                }
+                            }
+                            Command::PushRedecodedFrames(id, vec) => {
+                                if let Some(redecode) = redecode.as_mut().filter(|r| r.id == id) {
+                                    redecode.spool.process(vec);
+                                }
+                            }
+                            Command::StoreRedecodedFrames(id, vec) => {
+                                let done = match redecode.as_mut().filter(|r| r.id == id) {
+                                    Some(redecode) => {
+                                        redecode.stored += vec.len();
+                                        Self::process_redecoded(
+                                            vec,
+                                            &profile,
+                                            &mut analyzer,
+                                            &frames,
+                                            &index,
+                                        );
+                                        callback.on_redecode_progress(
+                                            (redecode.stored - redecode.start) as u32,
+                                            (redecode.end - redecode.start) as u32,
+                                        );
+                                        redecode.send(&profile, &frames);
+                                        redecode.is_done()
+                                    }
+                                    None => false,
+                                };
+                                if done {
+                                    redecode = None;
+                                    columns.clear();
+                                    callback.on_frames_updated(frames.read().len() as u32);
+                                    Self::reset_filters(&filtered, &mut filter_map, &callback);
+                                    if let Some(window) = window {
+                                        Self::process_window(
+                                            window,
+                                            &frames,
+                                            &lazy,
+                                            &filtered,
+                                            &mut filter_map,
+                                            &callback,
+                                        );
+                                    }
+                                }
+                            }
+                            Command::RefreshMarks => {
+                                Self::process_refresh_marks(&filtered, &mut filter_map)
+                            }
+                            Command::CaptureStats(id, sample) => {
+                                callback.on_capture_stats(id, sample)
                             }
                             Command::Close => return,
                         }
+                        if renew {
+                            // Decoders read the config when their workers are
+                            // created, so the frames read from now on are decoded
+                            // by new pools.
+                            ppool = parallel::Pool::new(
+                                &profile,
+                                &ParallelCallback {
+                                    sender: sender.clone(),
+                                    redecode: None,
+                                },
+                                &metrics,
+                            );
+                            let end = retired.as_ref().map_or(cnt as usize, |(_, end)| *end);
+                            let pool = mem::replace(
+                                &mut spool,
+                                serial::Pool::new(
+                                    profile.clone(),
+                                    SerialCallback {
+                                        sender: sender.clone(),
+                                        redecode: None,
+                                    },
+                                    &metrics,
+                                    end,
+                                ),
+                            );
+                            if retired.is_none() && frames.read().len() < end {
+                                retired = Some((pool, end));
+                            }
+                        }
                     }
-                    Self::process_filters(&frames, &filtered, &mut filter_map, &callback);
+                    Self::process_filters(
+                        &frames,
+                        &lazy,
+                        &mut columns,
+                        profile.frame_flags(),
+                        &filtered,
+                        &mut filter_map,
+                        &callback,
+                    );
                 }
             }));
             if let Err(err) = result {
@@ -282,6 +1078,7 @@ impl EventLoop {
         let ev = EventLoop {
             handle: Some(handle),
             sender: send.clone(),
+            metrics: ev_metrics,
         };
         (ev, send)
     }
@@ -290,15 +1087,15 @@ impl EventLoop {
         id: Option<u32>,
         result: Result<Vec<MutFixed<Layer>>>,
         cnt: &mut u32,
-        pool: &mut parallel::Pool,
         callback: &Callback,
-    ) {
+    ) -> Option<Vec<Frame>> {
         match result {
             Ok(layers) => {
                 if layers.is_empty() {
                     if let Some(id) = id {
                         callback.on_input_done(id, None);
                     }
+                    None
                 } else {
                     let frames = layers
                         .into_iter()
@@ -309,48 +1106,148 @@ impl EventLoop {
                         })
                         .collect::<Vec<_>>();
                     *cnt += frames.len() as u32;
-                    pool.process(frames);
+                    Some(frames)
                 }
             }
             Err(err) => {
                 if let Some(id) = id {
                     callback.on_input_done(id, Some(err));
                 }
+                None
+            }
+        }
+    }
+
+    fn process_spill(
+        spill: &mut Option<SpillWriter>,
+        layers: &[MutFixed<Layer>],
+        callback: &Callback,
+    ) {
+        let result = match spill {
+            Some(spill) => spill.append(layers),
+            None => return,
+        };
+        if let Err(err) = result {
+            *spill = None;
+            let err = Error(format!("failed to write spill file: {}", err));
+            callback.on_error(Box::new(err));
+        }
+    }
+
+    fn process_ring(
+        ring: &mut Option<RingBuffer>,
+        layers: &[MutFixed<Layer>],
+        callback: &Callback,
+    ) {
+        let result = match ring {
+            Some(ring) => ring.append(layers),
+            None => return,
+        };
+        match result {
+            Ok(rotations) => {
+                for rotation in rotations {
+                    callback.on_rotated(rotation);
+                }
+            }
+            Err(err) => {
+                *ring = None;
+                let err = Error(format!("failed to write ring buffer: {}", err));
+                callback.on_error(Box::new(err));
             }
         }
     }
 
+    /// Releases the frames before the latest `window` frames.
+    fn process_window(
+        window: usize,
+        frames: &FrameStore,
+        lazy: &Option<Lazy>,
+        filtered: &FilteredFrameStore,
+        filter_map: &mut FnvHashMap<u32, FilterContext>,
+        callback: &Callback,
+    ) {
+        let start = {
+            let frames = frames.read();
+            let start = frames.len().saturating_sub(window);
+            if start <= frames.start() {
+                return;
+            }
+            start
+        };
+        let released = match lazy {
+            Some(lazy) => lazy.release(frames, start),
+            None => frames.write().release_front(start),
+        };
+        for mut frame in released {
+            for layer in frame.fetch_layers() {
+                drop(unsafe { Box::from_raw(layer.as_mut_ptr()) });
+            }
+        }
+        for fctx in filter_map.values_mut() {
+            fctx.offset = fctx.offset.max(start);
+        }
+        for (id, indices) in filtered.write().iter_mut() {
+            if indices.remove_range(..start as u32) > 0 {
+                callback.on_filtered_frames_updated(*id, indices.len() as u32);
+            }
+        }
+        callback.on_frames_evicted(start as u32);
+    }
+
+    fn process_index(index: &mut FrameIndexStore, frames: &[Frame], callback: &Callback) {
+        let result = match index {
+            Some(index) => index.write().append(frames),
+            None => return,
+        };
+        // Stop indexing after a failure, e.g. when the disk is full, and fall
+        // back to the frames held in memory.
+        if let Err(err) = result {
+            *index = None;
+            let err = Error(format!("failed to update frame index: {}", err));
+            callback.on_error(Box::new(err));
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn process_output(
         id: u32,
         output: Box<Output>,
         filter: &Option<Filter>,
+        range: Option<Range<u32>>,
         frames: &FrameStore,
+        lazy: &Option<Lazy>,
+        flags: &FrameFlags,
         callback: &Callback,
     ) {
-        let frames = frames.read();
-        let mut offset = 0;
+        let len = frames.read().len();
+        let (mut offset, end) = match range {
+            Some(range) => (len.min(range.start as usize), len.min(range.end as usize)),
+            None => (0, len),
+        };
         {
             let mut output = output;
-            while offset < frames.len() {
-                let len = OUTPUT_BLOCK_SIZE.min(frames.len() - offset);
+            while offset < end {
+                let len = OUTPUT_BLOCK_SIZE.min(end - offset);
+                let _cache = lazy
+                    .as_ref()
+                    .map(|lazy| lazy.decode(frames, offset..offset + len));
+                let frames = frames.read();
                 let frames = frames
-                    .iter()
-                    .skip(offset)
-                    .take(len)
+                    .range(offset..offset + len)
                     .filter(|frame| {
-                        let ctx = genet_filter::context::Context::new(frame.layers());
+                        let ctx = flags.context(frame);
                         filter.as_ref().map_or(true, |f| f.test(&ctx))
                     })
                     .collect::<Vec<_>>();
                 if let Err(err) = output.write(frames.as_slice()) {
-                    let err = Error(err.description().to_string());
+                    let err = Error(err.to_string());
                     callback.on_output_done(id, Some(Box::new(err)));
                     return;
                 }
                 offset += len;
             }
             if let Err(err) = output.end() {
-                let err = Error(err.description().to_string());
+                let err = Error(err.to_string());
                 callback.on_output_done(id, Some(Box::new(err)));
                 return;
             }
@@ -358,6 +1255,99 @@ impl EventLoop {
         callback.on_output_done(id, None);
     }
 
+    /// Resets the state derived from the decoded frames, and returns the
+    /// state of the cross-frame analysis for decoding them again.
+    ///
+    /// Lazy frames are decoded again on demand, but their root layers are
+    /// kept, so only the timestamps and the `frame.*` attributes are updated
+    /// here. The other frames are decoded again by a `Redecode`.
+    fn process_redecode(
+        profile: &Profile,
+        frames: &FrameStore,
+        frame_index: &FrameIndexStore,
+        lazy: &Option<Lazy>,
+        filtered: &FilteredFrameStore,
+        filter_map: &mut FnvHashMap<u32, FilterContext>,
+        callback: &Callback,
+    ) -> Analyzer {
+        profile.expert().clear();
+        profile.conversations().clear();
+        let mut analyzer = Analyzer::from_profile(profile);
+
+        if let Some(lazy) = lazy {
+            lazy.clear(frames);
+            let (start, len) = {
+                let frames = frames.read();
+                (frames.start(), frames.len())
+            };
+            for index in start..len {
+                let root = {
+                    let frames = frames.read();
+                    let root = &frames.get(index).unwrap().layers()[0];
+                    unsafe { MutFixed::from_ptr(root.as_mut_ptr()) }
+                };
+                let mut frame = Frame::new(index as u32, root);
+                profile.time_shift().update(slice::from_mut(&mut frame));
+                if !profile.frame_flags().is_ignored(index as u32) {
+                    analyzer.process(&mut frame);
+                }
+                if let (Some(frame_index), Some(f)) = (frame_index, frames.read().get(index)) {
+                    let _ = frame_index.write().update(f);
+                }
+            }
+            callback.on_frames_updated(len as u32);
+            callback.on_redecode_progress((len - start) as u32, (len - start) as u32);
+            Self::reset_filters(filtered, filter_map, callback);
+        }
+        analyzer
+    }
+
+    /// Replaces the layers of the stored frames with the layers of the
+    /// frames decoded again.
+    fn process_redecoded(
+        mut vec: Vec<Frame>,
+        profile: &Profile,
+        analyzer: &mut Analyzer,
+        frames: &FrameStore,
+        frame_index: &FrameIndexStore,
+    ) {
+        for frame in &mut vec {
+            if !profile.frame_flags().is_ignored(frame.index()) {
+                analyzer.process(frame);
+            }
+        }
+        profile.resolver().update(&mut vec);
+        profile.geoip().update(&mut vec);
+        profile.expert().update(&mut vec);
+        profile.comparison().update(&mut vec);
+        profile.catalog().update(&vec);
+        profile.conversations().update(&vec);
+        let mut frames = frames.write();
+        for mut frame in vec {
+            if let Some(f) = frames.get_mut(frame.index() as usize) {
+                f.set_layers(frame.fetch_layers());
+                f.set_tree_indices(frame.fetch_tree_indices());
+                if let Some(frame_index) = frame_index {
+                    let _ = frame_index.write().update(f);
+                }
+            }
+        }
+    }
+
+    fn reset_filters(
+        filtered: &FilteredFrameStore,
+        filter_map: &mut FnvHashMap<u32, FilterContext>,
+        callback: &Callback,
+    ) {
+        filtered.write().clear();
+        for (id, fctx) in filter_map.iter_mut() {
+            fctx.filter.clear();
+            fctx.offset = 0;
+            fctx.refilter = None;
+            callback.on_filtered_frames_updated(*id, 0);
+        }
+    }
+
     fn process_push_filter(
         id: u32,
         filter: Option<Filter>,
@@ -365,53 +1355,116 @@ impl EventLoop {
         filter_map: &mut FnvHashMap<u32, FilterContext>,
         callback: &Callback,
     ) {
-        if let Some(filter) = filter {
-            filter_map.insert(
-                id,
-                FilterContext {
-                    offset: 0,
-                    filter: filter.clone(),
+        let prev = filter_map.remove(&id);
+        let filter = match filter {
+            Some(filter) => filter,
+            None => {
+                filtered.write().remove(&id);
+                return;
+            }
+        };
+        match prev {
+            Some(prev) if prev.offset > 0 => {
+                // A pending refilter has not replaced the visible result yet.
+                let end = prev.refilter.as_ref().map_or(prev.offset, |r| r.end);
+                let previous = filtered.read().get(&id).cloned().unwrap_or_default();
+                filter_map.insert(
+                    id,
+                    FilterContext {
+                        filter: IncrementalFilter::with_previous(filter, prev.filter),
+                        offset: 0,
+                        refilter: Some(Refilter {
+                            previous,
+                            end,
+                            indices: RoaringBitmap::new(),
+                        }),
+                    },
+                );
+            }
+            _ => {
+                filter_map.insert(
+                    id,
+                    FilterContext {
+                        filter: IncrementalFilter::new(filter),
+                        offset: 0,
+                        refilter: None,
+                    },
+                );
+                filtered.write().remove(&id);
+                callback.on_filtered_frames_updated(id, 0);
+            }
+        }
+    }
+
+    /// Evaluates the filters using `frame.marked` again from the first frame,
+    /// keeping their previous results visible until then.
+    fn process_refresh_marks(
+        filtered: &FilteredFrameStore,
+        filter_map: &mut FnvHashMap<u32, FilterContext>,
+    ) {
+        for (id, fctx) in filter_map.iter_mut() {
+            if !fctx.filter.clear_marked() || fctx.offset == 0 {
+                continue;
+            }
+            let refilter = match fctx.refilter.take() {
+                Some(refilter) => Refilter {
+                    indices: RoaringBitmap::new(),
+                    ..refilter
+                },
+                None => Refilter {
+                    previous: filtered.read().get(id).cloned().unwrap_or_default(),
+                    end: fctx.offset,
+                    indices: RoaringBitmap::new(),
                 },
-            );
-            callback.on_filtered_frames_updated(id, 0);
-        } else {
-            filter_map.remove(&id);
+            };
+            fctx.offset = 0;
+            fctx.refilter = Some(refilter);
         }
-        filtered.write().remove(&id);
     }
 
     fn process_filters(
         frames: &FrameStore,
+        lazy: &Option<Lazy>,
+        columns: &mut ColumnStore,
+        flags: &FrameFlags,
         filtered: &FilteredFrameStore,
         filter_map: &mut FnvHashMap<u32, FilterContext>,
         callback: &Callback,
     ) {
         for (id, fctx) in filter_map.iter_mut() {
             loop {
-                let (mut indices, end) = {
+                let (indices, end) = {
+                    let len = frames.read().len();
+                    let range = fctx.offset..len.min(fctx.offset + MAX_FILTER_SIZE);
+                    let _cache = match lazy {
+                        Some(lazy) if fctx.filter.needs_frames(range.clone()) => {
+                            Some(lazy.decode(frames, range.clone()))
+                        }
+                        _ => None,
+                    };
                     let frames = frames.read();
-                    let mut indices = frames
-                        .iter()
-                        .skip(fctx.offset)
-                        .take(MAX_FILTER_SIZE)
-                        .filter_map(|frame| {
-                            let ctx = genet_filter::context::Context::new(frame.layers());
-                            if fctx.filter.test(&ctx) {
-                                Some(frame.index())
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<_>>();
-                    fctx.offset = frames.len().min(fctx.offset + MAX_FILTER_SIZE);
-                    (indices, fctx.offset >= frames.len())
+                    let indices = fctx.filter.eval(&frames, columns, flags, range.clone());
+                    fctx.offset = range.end;
+                    (indices, fctx.offset >= len)
                 };
-                if !indices.is_empty() {
+                if let Some(mut refilter) = fctx.refilter.take() {
+                    refilter.indices.extend(indices);
+                    if fctx.offset < refilter.end {
+                        fctx.refilter = Some(refilter);
+                    } else {
+                        let (added, removed) =
+                            refilter::diff(&refilter.previous, &refilter.indices);
+                        let len = refilter.indices.len();
+                        filtered.write().insert(*id, refilter.indices);
+                        callback.on_filtered_frames_diff(*id, added, removed);
+                        callback.on_filtered_frames_updated(*id, len as u32);
+                    }
+                } else if !indices.is_empty() {
                     let len = {
                         let mut filtered = filtered.write();
-                        let mut frames = filtered.entry(*id).or_insert_with(Vec::new);
-                        frames.append(&mut indices);
-                        frames.len()
+                        let bitmap = filtered.entry(*id).or_default();
+                        bitmap.extend(indices);
+                        bitmap.len()
                     };
                     callback.on_filtered_frames_updated(*id, len as u32);
                 }
@@ -438,14 +1491,420 @@ impl fmt::Debug for EventLoop {
 
 #[cfg(test)]
 mod tests {
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        context::Context,
+        decoder::{Decoder, DecoderBox, ExecType, Metadata, Status, Worker},
+        error::Error,
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass, LayerStack, Parent, Payload},
+        reader::CaptureStats,
+        result::Result,
+        slice::ByteSlice,
+        token::Token,
+        variant::{Value, Variant},
+    };
     use genet_filter::Filter;
+    use index;
+    use io::{Input, Output};
+    use lazy::{self, CacheStats};
     use profile::Profile;
+    use roaring::RoaringBitmap;
+    use serde_json;
+    use stats::CaptureSample;
+    use std::{collections::HashMap, env, ops::Range, sync::mpsc, thread, time::Duration};
     use store::{Callback, Store};
+    use test_util;
 
     #[derive(Clone)]
     struct TestCallback {}
     impl Callback for TestCallback {}
 
+    #[derive(Debug)]
+    struct TestInput {
+        len: usize,
+    }
+
+    impl Input for TestInput {
+        fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+            let layers = (0..self.len).map(|_| test_util::root().build()).collect();
+            self.len = 0;
+            Ok(layers)
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestOutput {
+        indices: Vec<u32>,
+        sender: mpsc::Sender<Vec<u32>>,
+    }
+
+    impl Output for TestOutput {
+        fn write(&mut self, frames: &[&Frame]) -> Result<()> {
+            self.indices.extend(frames.iter().map(|f| f.index()));
+            Ok(())
+        }
+
+        fn end(&mut self) -> Result<()> {
+            let _ = self.sender.send(self.indices.clone());
+            Ok(())
+        }
+    }
+
+    /// Reads frames of 13 flows in batches of 300 frames. The root layers
+    /// have the flow, the direction and the sequence number.
+    #[derive(Debug)]
+    struct FlowInput {
+        len: usize,
+        next: usize,
+    }
+
+    impl Input for FlowInput {
+        fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+            let end = self.len.min(self.next + 300);
+            let layers = (self.next..end)
+                .map(|index| {
+                    let (flow, dir) = ((index * 7 % 13) as u8, (index / 13 % 2) as u8);
+                    let seq = (index as u32 / 26 * 10).to_be_bytes();
+                    let mut data = vec![flow, dir];
+                    data.extend_from_slice(&seq);
+                    test_util::root().data(ByteSlice::from(data)).build()
+                })
+                .collect();
+            self.next = end;
+            Ok(layers)
+        }
+    }
+
+    fn class(id: &'static str) -> Fixed<AttrClass> {
+        Fixed::new(AttrClass::builder(id).build())
+    }
+
+    /// Decodes the root layers of FlowInput as `ipv4` and `tcp`.
+    #[derive(Clone)]
+    struct FlowDecoder {}
+
+    impl Decoder for FlowDecoder {
+        fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+            Box::new(FlowWorker {
+                ipv4: Fixed::new(LayerClass::builder("ipv4").build()),
+                tcp: Fixed::new(LayerClass::builder("tcp").build()),
+                attrs: [
+                    "_.src",
+                    "_.dst",
+                    "tcp.src",
+                    "tcp.dst",
+                    "tcp.seq",
+                    "tcp.ack",
+                    "tcp.flags",
+                ]
+                .iter()
+                .map(|id| class(id))
+                .collect(),
+            })
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata::default()
+        }
+    }
+
+    struct FlowWorker {
+        ipv4: Fixed<LayerClass>,
+        tcp: Fixed<LayerClass>,
+        attrs: Vec<Fixed<AttrClass>>,
+    }
+
+    impl Worker for FlowWorker {
+        fn decode(
+            &mut self,
+            _ctx: &mut Context,
+            _stack: &LayerStack,
+            parent: &mut Parent,
+        ) -> Result<Status> {
+            if parent.id() != Token::from("[link-1]") {
+                return Ok(Status::Skip);
+            }
+            let data = parent.data();
+            let (flow, dir) = (data[0], usize::from(data[1]));
+            let seq = u64::from(u32::from_be_bytes([data[2], data[3], data[4], data[5]]));
+            let addrs = [vec![10, 0, 0, flow], vec![10, 0, 1, flow]];
+            let ports = [1000 + u64::from(flow), 80];
+            let attr = |class: &Fixed<AttrClass>, value: Variant| {
+                Attr::builder(class.clone()).value(value).build()
+            };
+
+            let mut ipv4 = Layer::new(self.ipv4.clone(), ByteSlice::new());
+            let src = addrs[dir].clone().into_boxed_slice();
+            let dst = addrs[1 - dir].clone().into_boxed_slice();
+            ipv4.add_attr(attr(&self.attrs[0], src.into()));
+            ipv4.add_attr(attr(&self.attrs[1], dst.into()));
+            parent.add_child(ipv4);
+
+            let mut tcp = Layer::new(self.tcp.clone(), ByteSlice::new());
+            tcp.add_attr(attr(&self.attrs[2], ports[dir].into()));
+            tcp.add_attr(attr(&self.attrs[3], ports[1 - dir].into()));
+            tcp.add_attr(attr(&self.attrs[4], (seq + dir as u64 * 5000).into()));
+            tcp.add_attr(attr(&self.attrs[5], (seq + (1 - dir) as u64 * 5000).into()));
+            tcp.add_attr(attr(&self.attrs[6], 0x10u64.into()));
+            tcp.add_payload(Payload::new(ByteSlice::from(vec![0; 10]), "@data:tcp"));
+            parent.add_child(tcp);
+            Ok(Status::Done)
+        }
+    }
+
+    /// Counts the segments of each flow. The state is kept per flow, so the
+    /// counts do not depend on the number of flow shards.
+    #[derive(Clone)]
+    struct CountDecoder {}
+
+    impl Decoder for CountDecoder {
+        fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+            Box::new(CountWorker {
+                counts: HashMap::new(),
+                class: class("test.count"),
+            })
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                exec_type: ExecType::SerialSync,
+                ..Metadata::default()
+            }
+        }
+    }
+
+    struct CountWorker {
+        counts: HashMap<[u64; 2], u64>,
+        class: Fixed<AttrClass>,
+    }
+
+    impl Worker for CountWorker {
+        fn decode(
+            &mut self,
+            _ctx: &mut Context,
+            _stack: &LayerStack,
+            parent: &mut Parent,
+        ) -> Result<Status> {
+            if parent.id() != Token::from("tcp") {
+                return Ok(Status::Skip);
+            }
+            let mut ports = [0u64; 2];
+            for (port, id) in ports.iter_mut().zip(&["tcp.src", "tcp.dst"]) {
+                *port = parent.attr(*id).unwrap().try_get(parent)?.try_into()?;
+            }
+            ports.sort();
+            let count = self.counts.entry(ports).or_insert(0);
+            *count += 1;
+            let attr = Attr::builder(self.class.clone()).value(*count).build();
+            parent.add_attr(attr);
+            Ok(Status::Done)
+        }
+    }
+
+    /// Returns the attributes of the frames in `range` by layer.
+    fn frame_attrs(store: &Store, range: Range<usize>) -> Vec<Vec<String>> {
+        store
+            .frames(range)
+            .iter()
+            .map(|&frame| {
+                let frame = unsafe { &*frame };
+                frame
+                    .layers()
+                    .iter()
+                    .flat_map(|layer| {
+                        layer.attrs().iter().map(move |attr| {
+                            format!("{} {:?}", attr.id(), attr.try_get(layer).ok())
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn deterministic_order() {
+        let decode = |concurrency: u32, serial_concurrency: u32| {
+            let mut profile = Profile::new();
+            profile.set_concurrency(concurrency);
+            profile.set_serial_concurrency(serial_concurrency);
+            profile.add_decoder(DecoderBox::new(FlowDecoder {}));
+            profile.add_decoder(DecoderBox::new(CountDecoder {}));
+            let mut store = Store::new(profile, TestCallback {});
+            store.set_input(1, FlowInput { len: 3000, next: 0 });
+            while store.len() < 3000 {
+                thread::sleep(Duration::from_millis(10));
+            }
+            frame_attrs(&store, 0..3000)
+        };
+
+        let expected = decode(1, 1);
+        assert!(expected[2999].iter().any(|a| a.starts_with("tcp.stream")));
+        assert!(expected[2999].iter().any(|a| a.starts_with("test.count")));
+        assert!(expected.iter().any(|attrs| attrs
+            .iter()
+            .any(|a| a.starts_with("tcp.analysis.acks_frame"))));
+        for &(concurrency, serial_concurrency) in &[(4, 1), (1, 3), (8, 5)] {
+            assert_eq!(decode(concurrency, serial_concurrency), expected);
+        }
+    }
+
+    /// Adds a `test` layer with the config value of `test.config`.
+    #[derive(Clone)]
+    struct ConfigDecoder {}
+
+    impl Decoder for ConfigDecoder {
+        fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+            Box::new(ConfigWorker {
+                value: ctx.get_config("test.config").parse().unwrap_or(0),
+                layer: Fixed::new(LayerClass::builder("test").build()),
+                class: class("test.config"),
+            })
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata::default()
+        }
+    }
+
+    struct ConfigWorker {
+        value: u64,
+        layer: Fixed<LayerClass>,
+        class: Fixed<AttrClass>,
+    }
+
+    impl Worker for ConfigWorker {
+        fn decode(
+            &mut self,
+            _ctx: &mut Context,
+            _stack: &LayerStack,
+            parent: &mut Parent,
+        ) -> Result<Status> {
+            if parent.id() != Token::from("[link-1]") {
+                return Ok(Status::Skip);
+            }
+            let mut layer = Layer::new(self.layer.clone(), ByteSlice::new());
+            layer.add_attr(Attr::builder(self.class.clone()).value(self.value).build());
+            parent.add_child(layer);
+            Ok(Status::Done)
+        }
+    }
+
+    #[test]
+    fn update_config() {
+        let mut profile = Profile::new();
+        profile.set_config("test.config", "1");
+        profile.add_decoder(DecoderBox::new(ConfigDecoder {}));
+        let mut store = Store::new(profile, TestCallback {});
+        let values = |store: &Store| {
+            store
+                .frames(0..store.len())
+                .iter()
+                .map(|&frame| {
+                    let layer = unsafe { &(*frame).layers()[1] };
+                    layer
+                        .attr("test.config")
+                        .and_then(|attr| attr.try_get(layer).ok())
+                })
+                .collect::<Vec<_>>()
+        };
+        store.set_input(1, TestInput { len: 10 });
+        while store.len() < 10 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(values(&store), vec![Some(Variant::UInt64(1)); 10]);
+
+        store.update_config("test.config", "2");
+        store.redecode();
+        store.set_input(2, TestInput { len: 5 });
+        while store.len() < 15 || values(&store)[0] != Some(Variant::UInt64(2)) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(values(&store), vec![Some(Variant::UInt64(2)); 15]);
+    }
+
+    #[test]
+    fn set_decoders() {
+        let mut profile = Profile::new();
+        profile.add_decoder(DecoderBox::new(ConfigDecoder {}));
+        let mut store = Store::new(profile, TestCallback {});
+        let decoded = |store: &Store| {
+            store
+                .frames(0..store.len())
+                .iter()
+                .filter(|&&frame| unsafe { (*frame).layers().len() } > 1)
+                .count()
+        };
+        store.set_input(1, TestInput { len: 10 });
+        while store.len() < 10 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(decoded(&store), 10);
+
+        store.set_decoders(Vec::new());
+        store.redecode();
+        store.set_input(2, TestInput { len: 5 });
+        while store.len() < 15 || decoded(&store) > 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(decoded(&store), 0);
+    }
+
+    #[derive(Clone)]
+    struct RedecodeCallback {
+        sender: mpsc::Sender<(u32, u32)>,
+    }
+
+    impl Callback for RedecodeCallback {
+        fn on_redecode_progress(&self, frames: u32, total: u32) {
+            let _ = self.sender.send((frames, total));
+        }
+    }
+
+    #[test]
+    fn redecode_progress() {
+        let mut profile = Profile::new();
+        profile.set_serial_concurrency(3);
+        profile.add_decoder(DecoderBox::new(FlowDecoder {}));
+        profile.add_decoder(DecoderBox::new(CountDecoder {}));
+        let (sender, progress) = mpsc::channel();
+        let mut store = Store::new(profile, RedecodeCallback { sender });
+        store.set_input(
+            1,
+            FlowInput {
+                len: 10000,
+                next: 0,
+            },
+        );
+        while store.len() < 10000 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let expected = frame_attrs(&store, 0..10000);
+
+        // The frames are decoded again in batches, and the frames read in
+        // the meantime are stored after them.
+        store.redecode();
+        store.set_input(
+            2,
+            FlowInput {
+                len: 10300,
+                next: 10000,
+            },
+        );
+        let mut last = 0;
+        while last < 10000 {
+            let (frames, total) = progress.recv_timeout(Duration::from_secs(10)).unwrap();
+            assert_eq!(total, 10000);
+            assert!(frames > last);
+            last = frames;
+        }
+        assert_eq!(frame_attrs(&store, 0..10000), expected);
+        while store.len() < 10300 {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
     #[test]
     fn drop() {
         let profile = Profile::new();
@@ -460,4 +1919,393 @@ mod tests {
         assert_eq!(store.frames(100..0).len(), 0);
         assert_eq!(store.filtered_frames(0, 100..0).len(), 0);
     }
+
+    #[test]
+    fn output_range() {
+        let profile = Profile::new();
+        let mut store = Store::new(profile, TestCallback {});
+        store.set_input(1, TestInput { len: 10 });
+        while store.len() < 10 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let output = TestOutput {
+            indices: Vec::new(),
+            sender: sender.clone(),
+        };
+        store.push_output(2, output, None, Some(3..6));
+        assert_eq!(receiver.recv().unwrap(), vec![3, 4, 5]);
+
+        let output = TestOutput {
+            indices: Vec::new(),
+            sender,
+        };
+        store.push_output(3, output, None, Some(8..100));
+        assert_eq!(receiver.recv().unwrap(), vec![8, 9]);
+    }
+
+    #[test]
+    fn pipeline() {
+        let mut profile = Profile::new();
+        profile.set_concurrency(2);
+        profile.set_serial_concurrency(3);
+        let mut store = Store::new(profile, TestCallback {});
+        store.set_input(1, TestInput { len: 1000 });
+        while store.len() < 1000 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let output = TestOutput {
+            indices: Vec::new(),
+            sender,
+        };
+        store.push_output(2, output, None, None);
+        assert_eq!(receiver.recv().unwrap(), (0..1000).collect::<Vec<_>>());
+
+        let stats = store.pipeline_stats();
+        assert_eq!((stats.parallel.threads, stats.parallel.frames), (2, 1000));
+        assert_eq!((stats.serial.threads, stats.serial.frames), (3, 1000));
+    }
+
+    #[test]
+    fn column_filter() {
+        let profile = Profile::new();
+        let mut store = Store::new(profile, TestCallback {});
+        store.set_input(1, TestInput { len: 10 });
+        while store.len() < 10 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let filter = Filter::compile("link.length != 1").unwrap();
+        assert!(filter.plan().is_some());
+        store.set_filter(0, Some(filter));
+        while store.filtered_frames(0, 0..100).len() < 10 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(store.filtered_frames(0, 0..100), (0..10).collect::<Vec<_>>());
+    }
+
+    #[derive(Clone)]
+    struct DiffCallback {
+        sender: mpsc::Sender<(Vec<u32>, Vec<u32>)>,
+    }
+
+    impl Callback for DiffCallback {
+        fn on_filtered_frames_diff(&self, _id: u32, added: Vec<u32>, removed: Vec<u32>) {
+            let _ = self.sender.send((added, removed));
+        }
+    }
+
+    #[test]
+    fn refilter() {
+        let mut profile = Profile::new();
+        profile.add_decoder(DecoderBox::new(FlowDecoder {}));
+        let (sender, diffs) = mpsc::channel();
+        let mut store = Store::new(profile, DiffCallback { sender });
+        store.set_input(1, FlowInput { len: 600, next: 0 });
+        while store.len() < 600 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let expected = |filter: &str| {
+            let mut indices = Vec::new();
+            store.scan(Some(&Filter::compile(filter).unwrap()), |frame| {
+                indices.push(frame.index());
+                true
+            });
+            indices
+        };
+        let wide = expected("tcp.dst == 80");
+        let narrow = expected("tcp.dst == 80 && tcp.src == 1001");
+        let src = expected("tcp.src == 1001");
+
+        store.set_filter(0, Some(Filter::compile("tcp.dst == 80").unwrap()));
+        while store.filtered_frames(0, 0..600).len() < wide.len() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // The previous result stays visible until the diff is reported.
+        let filter = Filter::compile("tcp.dst == 80 && tcp.src == 1001").unwrap();
+        store.set_filter(0, Some(filter));
+        let (added, removed) = diffs.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(added.is_empty());
+        assert_eq!(
+            removed,
+            wide.iter()
+                .filter(|index| !narrow.contains(index))
+                .cloned()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(store.filtered_frames(0, 0..600), narrow);
+
+        store.set_filter(0, Some(Filter::compile("tcp.src == 1001").unwrap()));
+        let (added, removed) = diffs.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(removed.is_empty());
+        assert_eq!(narrow.len() + added.len(), src.len());
+    }
+
+    #[test]
+    fn frame_flags() {
+        let mut profile = Profile::new();
+        profile.add_decoder(DecoderBox::new(FlowDecoder {}));
+        let (sender, diffs) = mpsc::channel();
+        let mut store = Store::new(profile, DiffCallback { sender });
+        store.set_input(1, FlowInput { len: 600, next: 0 });
+        while store.len() < 600 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        store.set_marked(&[3, 5, 7], true);
+        store.set_filter(0, Some(Filter::compile("frame.marked").unwrap()));
+        while store.filtered_len(0) < 3 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(store.filtered_frames(0, 0..600), vec![3, 5, 7]);
+
+        // The filter is evaluated again when the marks are changed.
+        let generation = store.generation();
+        store.set_marked(&[5, 8], false);
+        store.set_marked(&[9], true);
+        assert!(store.generation() > generation);
+        let mut changes = Vec::new();
+        while store.filtered_frames(0, 0..600) != vec![3, 7, 9] {
+            changes.push(diffs.recv_timeout(Duration::from_secs(10)).unwrap());
+        }
+        assert!(!changes.is_empty());
+        let marked = RoaringBitmap::deserialize_from(&store.marked_bitmap()[..]).unwrap();
+        assert_eq!(marked.iter().collect::<Vec<_>>(), vec![3, 7, 9]);
+
+        // Ignored frames are left with their root layer.
+        store.set_ignored(&[1, 2], true);
+        let tcp = Filter::compile("tcp").unwrap();
+        let decoded = |store: &Store| {
+            let mut indices = Vec::new();
+            store.scan(Some(&tcp), |frame| {
+                indices.push(frame.index());
+                indices.len() < 4
+            });
+            indices
+        };
+        while decoded(&store) != vec![0, 3, 4, 5] {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let counts = store.value_counts("tcp.src", None, 600);
+        assert_eq!(counts.iter().map(|c| c.count).sum::<u64>(), 598);
+        let ignored = RoaringBitmap::deserialize_from(&store.ignored_bitmap()[..]).unwrap();
+        assert_eq!(ignored.len(), 2);
+
+        store.set_ignored(&[1, 2], false);
+        while decoded(&store) != vec![0, 1, 2, 3] {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn filtered_rows() {
+        let mut profile = Profile::new();
+        profile.add_decoder(DecoderBox::new(FlowDecoder {}));
+        let mut store = Store::new(profile, TestCallback {});
+        store.set_input(1, FlowInput { len: 600, next: 0 });
+        while store.len() < 600 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let filter = Filter::compile("tcp.src == 1001").unwrap();
+        let mut expected = Vec::new();
+        store.scan(Some(&filter), |frame| {
+            expected.push(frame.index());
+            true
+        });
+        store.set_filter(0, Some(filter));
+        while store.filtered_len(0) < expected.len() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(store.filtered_frames(0, 2..5), &expected[2..5]);
+        assert!(store.filtered_frames(0, 600..700).is_empty());
+        for (row, index) in expected.iter().enumerate() {
+            assert_eq!(store.filtered_select(0, row), Some(*index));
+            assert_eq!(store.filtered_rank(0, *index), row);
+            assert_eq!(store.filtered_rank(0, *index + 1), row + 1);
+        }
+        assert_eq!(store.filtered_select(0, expected.len()), None);
+        assert_eq!(store.filtered_rank(0, 600), expected.len());
+
+        let data = store.filtered_bitmap(0).unwrap();
+        let bitmap = RoaringBitmap::deserialize_from(&data[..]).unwrap();
+        assert_eq!(bitmap.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(store.filtered_bitmap(1), None);
+    }
+
+    #[test]
+    fn summaries() {
+        let mut profile = Profile::new();
+        let dir = env::temp_dir().to_str().unwrap().to_string();
+        profile.set_config(index::DIR_KEY, &serde_json::to_string(&dir).unwrap());
+        let mut store = Store::new(profile, TestCallback {});
+        store.set_input(1, TestInput { len: 10 });
+        while store.len() < 10 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let summaries = store.summaries(8..100);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].index, 8);
+        assert_eq!(summaries[0].protocol, "[link-1]");
+        assert_eq!(store.frame_data(9), Some(Vec::new()));
+        assert_eq!(store.frame_data(10), None);
+    }
+
+    #[test]
+    fn lazy_cache() {
+        let mut profile = Profile::new();
+        profile.set_config(lazy::LAZY_KEY, "true");
+        profile.set_config(lazy::CACHE_SIZE_KEY, "4");
+        let mut store = Store::new(profile, TestCallback {});
+        store.set_input(1, TestInput { len: 10 });
+        while store.len() < 10 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        store.pin_frames(1, 0..2);
+        assert_eq!(store.frames(0..10).len(), 10);
+        assert_eq!(store.frames(5..6).len(), 1);
+        assert_eq!(
+            store.cache_stats(),
+            Some(CacheStats {
+                capacity: 4,
+                decoded: 4,
+                pinned: 2,
+                hits: 1,
+                misses: 10,
+                evictions: 6,
+            })
+        );
+
+        store.unpin_frames(1);
+        store.frames(2..3);
+        let stats = store.cache_stats().unwrap();
+        assert_eq!((stats.decoded, stats.pinned, stats.evictions), (4, 0, 7));
+    }
+
+    #[test]
+    fn lazy_hold() {
+        let mut profile = Profile::new();
+        profile.set_config(lazy::LAZY_KEY, "true");
+        profile.set_config(lazy::CACHE_SIZE_KEY, "4");
+        profile.add_decoder(DecoderBox::new(FlowDecoder {}));
+        let mut store = Store::new(profile, TestCallback {});
+        store.set_input(1, FlowInput { len: 10, next: 0 });
+        while store.len() < 10 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let held = store.held_frames(0..2);
+        let layers = held
+            .iter()
+            .flat_map(|(frame, _)| unsafe { &**frame }.layers().iter())
+            .map(|layer| &**layer as *const Layer)
+            .collect::<Vec<_>>();
+        assert_eq!(layers.len(), 6);
+
+        // Decoding the other frames evicts all of them but the held ones.
+        store.frames(2..10);
+        store.frames(9..10);
+        let stats = store.cache_stats().unwrap();
+        assert_eq!((stats.decoded, stats.evictions), (4, 6));
+        let ids = layers
+            .iter()
+            .map(|layer| unsafe { &**layer }.id().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["[link-1]", "ipv4", "tcp", "[link-1]", "ipv4", "tcp"]);
+        assert_eq!(unsafe { &*held[1].0 }.layers().len(), 3);
+
+        // Released frames are evicted again.
+        ::std::mem::drop(held);
+        store.frames(2..4);
+        let stats = store.cache_stats().unwrap();
+        assert_eq!((stats.decoded, stats.evictions), (4, 8));
+        assert_eq!(unsafe { &*store.frames(0..1)[0] }.layers().len(), 3);
+    }
+
+    #[test]
+    fn lazy_stream() {
+        let decode = |lazy: bool| {
+            let mut profile = Profile::new();
+            if lazy {
+                profile.set_config(lazy::LAZY_KEY, "true");
+                profile.set_config(lazy::CACHE_SIZE_KEY, "8");
+            }
+            profile.add_decoder(DecoderBox::new(FlowDecoder {}));
+            profile.add_decoder(DecoderBox::new(CountDecoder {}));
+            let mut store = Store::new(profile, TestCallback {});
+            store.set_input(1, FlowInput { len: 200, next: 0 });
+            while store.len() < 200 {
+                thread::sleep(Duration::from_millis(10));
+            }
+            store
+        };
+
+        let eager = decode(false);
+        let expected = frame_attrs(&eager, 0..200);
+        assert!(expected[150]
+            .iter()
+            .any(|a| a == "test.count Some(UInt64(12))"));
+
+        // The frames are decoded in the order of the capture, however they
+        // are read.
+        let lazy = decode(true);
+        for range in &[150..160, 20..30, 190..200, 0..8, 100..101, 20..30, 60..200] {
+            assert_eq!(frame_attrs(&lazy, range.clone()), &expected[range.clone()]);
+        }
+        assert_eq!(lazy.cache_stats().unwrap().decoded, 140);
+    }
+
+    #[derive(Debug)]
+    struct LiveInput {
+        reads: u64,
+    }
+
+    impl Input for LiveInput {
+        fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+            if self.reads == 2 {
+                return Err(Box::new(Error::new("end of capture")));
+            }
+            self.reads += 1;
+            Ok(vec![test_util::root().build()])
+        }
+
+        fn stats(&mut self) -> Option<CaptureStats> {
+            Some(CaptureStats {
+                received: self.reads * 3,
+                dropped: self.reads,
+                ..CaptureStats::default()
+            })
+        }
+    }
+
+    #[test]
+    fn capture_stats() {
+        let (sender, receiver) = mpsc::channel();
+        let mut store = Store::new(Profile::new(), StatsCallback { sender });
+        store.set_input(1, LiveInput { reads: 0 });
+        let (id, sample) = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(id, 1);
+        assert_eq!(sample.frames, 2);
+        assert_eq!(sample.stats.received, 6);
+        assert_eq!(sample.stats.lost(), 2);
+        assert_eq!(store.capture_stats(1), vec![sample]);
+        assert_eq!(store.capture_stats(2), vec![]);
+    }
+
+    #[derive(Clone)]
+    struct StatsCallback {
+        sender: mpsc::Sender<(u32, CaptureSample)>,
+    }
+
+    impl Callback for StatsCallback {
+        fn on_capture_stats(&self, id: u32, sample: CaptureSample) {
+            let _ = self.sender.send((id, sample));
+        }
+    }
 }
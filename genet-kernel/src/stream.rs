@@ -0,0 +1,430 @@
+//! Reassembled streams and their text rendering.
+//!
+//! The payloads of a conversation identified by `tcp.stream` or `udp.stream`
+//! are collected in capture order, and returned as they are or decoded as
+//! text. The encoding is detected separately for each direction, since e.g.
+//! a client may send UTF-16 while the server responds in UTF-8.
+//!
+//! If the layer has the `<layer>.stream.payloads` attribute, only the
+//! reassembled `@stream:<layer>` payloads are used, so that retransmitted
+//! data does not appear twice. Otherwise the `@data:<layer>` payloads are
+//! used as they are.
+
+use analysis::attr;
+use genet_abi::{fixed::MutFixed, layer::Layer, token::Token};
+use std::{char, str};
+
+/// The number of bytes used to detect the encoding of a direction.
+const DETECT_LEN: usize = 4096;
+
+/// A text encoding.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    #[serde(rename = "latin1")]
+    Latin1,
+}
+
+impl Encoding {
+    /// Detects the encoding of `data`.
+    ///
+    /// A byte order mark is used if present. UTF-16 is detected by the zero
+    /// bytes of ASCII characters, and anything which is not valid UTF-8 is
+    /// treated as Latin-1.
+    pub fn detect(data: &[u8]) -> Encoding {
+        let data = &data[..data.len().min(DETECT_LEN)];
+        if data.starts_with(&[0xef, 0xbb, 0xbf]) {
+            return Encoding::Utf8;
+        } else if data.starts_with(&[0xff, 0xfe]) {
+            return Encoding::Utf16Le;
+        } else if data.starts_with(&[0xfe, 0xff]) {
+            return Encoding::Utf16Be;
+        }
+
+        let pairs = data.len() / 2;
+        if pairs > 0 {
+            let zeros = |offset: usize| {
+                data.chunks(2)
+                    .filter(|pair| pair.len() == 2 && pair[offset] == 0)
+                    .count()
+            };
+            let (even, odd) = (zeros(0), zeros(1));
+            if odd * 2 > pairs && even * 8 < pairs {
+                return Encoding::Utf16Le;
+            } else if even * 2 > pairs && odd * 8 < pairs {
+                return Encoding::Utf16Be;
+            }
+        }
+
+        match str::from_utf8(data) {
+            Ok(_) => Encoding::Utf8,
+            // A character cut off at the end of the sample.
+            Err(err) if err.error_len().is_none() => Encoding::Utf8,
+            Err(_) => Encoding::Latin1,
+        }
+    }
+}
+
+/// The direction of a chunk.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    /// The sender of the first payload.
+    Client,
+    Server,
+}
+
+impl Side {
+    pub fn from_name(name: &str) -> Option<Side> {
+        match name {
+            "client" => Some(Side::Client),
+            "server" => Some(Side::Server),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Side::Client => "client",
+            Side::Server => "server",
+        }
+    }
+}
+
+/// The payload of a frame in a stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamChunk {
+    /// The index of the frame.
+    pub frame: u32,
+    pub side: Side,
+    /// The offset of the chunk in the data sent by `side`.
+    pub offset: usize,
+    pub data: Vec<u8>,
+}
+
+/// The text of a payload.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TextChunk {
+    /// The index of the frame.
+    pub frame: u32,
+    pub side: Side,
+    pub text: String,
+}
+
+/// A stream rendered as text.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TextStream {
+    pub client: Encoding,
+    pub server: Encoding,
+    pub chunks: Vec<TextChunk>,
+}
+
+/// Decodes the chunks of a direction, keeping characters split between
+/// chunks.
+struct TextDecoder {
+    encoding: Encoding,
+    pending: Vec<u8>,
+    started: bool,
+}
+
+impl TextDecoder {
+    fn new(encoding: Encoding) -> TextDecoder {
+        TextDecoder {
+            encoding,
+            pending: Vec::new(),
+            started: false,
+        }
+    }
+
+    fn decode(&mut self, data: &[u8]) -> String {
+        let mut buf = Vec::new();
+        buf.append(&mut self.pending);
+        buf.extend_from_slice(data);
+        let text = match self.encoding {
+            Encoding::Utf8 => {
+                let len = buf.len() - utf8_tail(&buf);
+                self.pending = buf.split_off(len);
+                String::from_utf8_lossy(&buf).into_owned()
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let mut units = buf
+                    .chunks(2)
+                    .filter(|pair| pair.len() == 2)
+                    .map(|pair| match self.encoding {
+                        Encoding::Utf16Le => u16::from(pair[0]) | u16::from(pair[1]) << 8,
+                        _ => u16::from(pair[0]) << 8 | u16::from(pair[1]),
+                    })
+                    .collect::<Vec<_>>();
+                let mut len = units.len() * 2;
+                // Keep a high surrogate until the low surrogate arrives.
+                if let Some(0xd800..=0xdbff) = units.last() {
+                    units.pop();
+                    len -= 2;
+                }
+                self.pending = buf.split_off(len);
+                char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect()
+            }
+            Encoding::Latin1 => buf.iter().map(|b| char::from(*b)).collect(),
+        };
+        let text = if self.started {
+            &text
+        } else {
+            self.started = !text.is_empty();
+            text.trim_start_matches('\u{feff}')
+        };
+        text.chars().map(printable).collect()
+    }
+}
+
+/// Returns the length of an incomplete UTF-8 sequence at the end of `data`.
+fn utf8_tail(data: &[u8]) -> usize {
+    for i in 1..=data.len().min(3) {
+        let b = data[data.len() - i];
+        if b & 0xc0 != 0x80 {
+            let len = match b {
+                0xf0..=0xff => 4,
+                0xe0..=0xef => 3,
+                0xc0..=0xdf => 2,
+                _ => 1,
+            };
+            return if len > i { i } else { 0 };
+        }
+    }
+    0
+}
+
+/// Replaces control characters except line breaks and tabs with `.`.
+fn printable(c: char) -> char {
+    match c {
+        '\n' | '\r' | '\t' => c,
+        c if c.is_control() => '.',
+        c => c,
+    }
+}
+
+/// Collects the payloads of a stream.
+pub struct StreamBuilder {
+    layer: Token,
+    stream_attr: String,
+    payloads_attr: String,
+    data: Token,
+    reassembled: Token,
+    stream: u64,
+    client: Option<(Vec<u8>, u64)>,
+    chunks: Vec<(u32, Side, Vec<u8>)>,
+}
+
+impl StreamBuilder {
+    /// Creates a new StreamBuilder for the conversation of `layer` (e.g.
+    /// `tcp`) with the stream index `stream`.
+    pub fn new(layer: &str, stream: u64) -> StreamBuilder {
+        StreamBuilder {
+            layer: Token::from(layer),
+            stream_attr: format!("{}.stream", layer),
+            payloads_attr: format!("{}.stream.payloads", layer),
+            data: Token::from(format!("@data:{}", layer).as_str()),
+            reassembled: Token::from(format!("@stream:{}", layer).as_str()),
+            stream,
+            client: None,
+            chunks: Vec::new(),
+        }
+    }
+
+    /// Adds the payloads of the frame `index` if it belongs to the stream.
+    pub fn add(&mut self, index: u32, layers: &[MutFixed<Layer>]) {
+        let pos = match layers.iter().rposition(|layer| layer.id() == self.layer) {
+            Some(pos) => pos,
+            None => return,
+        };
+        let layer = &layers[pos];
+        if attr::<u64>(layer, self.stream_attr.as_str()) != Some(self.stream) {
+            return;
+        }
+
+        let id = self.layer.to_string();
+        let src = layers[..pos]
+            .iter()
+            .rev()
+            .filter_map(|layer| attr::<Vec<u8>>(layer, "_.src"))
+            .next()
+            .unwrap_or_default();
+        let src = (src, attr::<u64>(layer, format!("{}.src", id)).unwrap_or(0));
+
+        let id = if layer.attr(self.payloads_attr.as_str()).is_some() {
+            self.reassembled
+        } else {
+            self.data
+        };
+        let data = layer
+            .payloads()
+            .iter()
+            .filter(|p| p.id() == id)
+            .flat_map(|p| p.data().to_vec())
+            .collect::<Vec<_>>();
+        if data.is_empty() {
+            return;
+        }
+        let side = match self.client {
+            Some(ref client) if *client != src => Side::Server,
+            Some(_) => Side::Client,
+            None => {
+                self.client = Some(src);
+                Side::Client
+            }
+        };
+        self.chunks.push((index, side, data));
+    }
+
+    /// Returns the payloads sent by `side`, or by both sides if `side` is
+    /// None, in capture order.
+    pub fn chunks(self, side: Option<Side>) -> Vec<StreamChunk> {
+        let mut offsets = [0; 2];
+        self.chunks
+            .into_iter()
+            .filter_map(|(frame, s, data)| {
+                let offset = offsets[s as usize];
+                offsets[s as usize] += data.len();
+                match side {
+                    Some(side) if side != s => None,
+                    _ => Some(StreamChunk {
+                        frame,
+                        side: s,
+                        offset,
+                        data,
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    pub fn build(self) -> TextStream {
+        let detect = |side: Side| {
+            let data = self
+                .chunks
+                .iter()
+                .filter(|(_, s, _)| *s == side)
+                .flat_map(|(_, _, data)| data.iter().cloned())
+                .take(DETECT_LEN)
+                .collect::<Vec<_>>();
+            Encoding::detect(&data)
+        };
+        let (client, server) = (detect(Side::Client), detect(Side::Server));
+        let mut decoders = [TextDecoder::new(client), TextDecoder::new(server)];
+        let chunks = self
+            .chunks
+            .iter()
+            .map(|(frame, side, data)| TextChunk {
+                frame: *frame,
+                side: *side,
+                text: decoders[*side as usize].decode(data),
+            })
+            .filter(|chunk| !chunk.text.is_empty())
+            .collect();
+        TextStream {
+            client,
+            server,
+            chunks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{
+        fixed::MutFixed,
+        layer::{Layer, Payload},
+        slice::ByteSlice,
+    };
+    use stream::{Encoding, Side, StreamBuilder, StreamChunk};
+    use test_util;
+
+    fn frame(
+        src: &'static [u8],
+        port: u64,
+        stream: u64,
+        data: &'static [u8],
+    ) -> Vec<MutFixed<Layer>> {
+        vec![
+            test_util::layer("ipv4")
+                .attr("_.src", ByteSlice::from(src))
+                .build(),
+            test_util::layer("udp")
+                .attr("udp.src", port)
+                .attr("udp.stream", stream)
+                .payload(Payload::new(data, "@data:udp"))
+                .build(),
+        ]
+    }
+
+    #[test]
+    fn chunks() {
+        let mut builder = StreamBuilder::new("udp", 0);
+        builder.add(0, &frame(&[10, 0, 0, 1], 5000, 0, b"ping"));
+        builder.add(1, &frame(&[10, 0, 0, 2], 53, 0, b"pong"));
+        builder.add(2, &frame(&[10, 0, 0, 1], 5000, 0, b"ping"));
+        let chunks = builder.chunks(Some(Side::Client));
+        assert_eq!(
+            chunks,
+            vec![
+                StreamChunk {
+                    frame: 0,
+                    side: Side::Client,
+                    offset: 0,
+                    data: b"ping".to_vec(),
+                },
+                StreamChunk {
+                    frame: 2,
+                    side: Side::Client,
+                    offset: 4,
+                    data: b"ping".to_vec(),
+                },
+            ]
+        );
+        assert_eq!(Side::from_name("server"), Some(Side::Server));
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(Encoding::detect(b"GET / HTTP/1.1\r\n"), Encoding::Utf8);
+        assert_eq!(Encoding::detect("caf\u{e9}".as_bytes()), Encoding::Utf8);
+        assert_eq!(Encoding::detect(b"caf\xe9 noir"), Encoding::Latin1);
+        assert_eq!(Encoding::detect(b"h\0e\0l\0l\0o\0"), Encoding::Utf16Le);
+        assert_eq!(Encoding::detect(b"\0h\0e\0l\0l\0o"), Encoding::Utf16Be);
+        assert_eq!(Encoding::detect(b"\xff\xfe"), Encoding::Utf16Le);
+    }
+
+    #[test]
+    fn build() {
+        let mut builder = StreamBuilder::new("udp", 1);
+        builder.add(
+            0,
+            &frame(&[10, 0, 0, 1], 5000, 1, b"\xef\xbb\xbfhello \xe2\x82"),
+        );
+        builder.add(1, &frame(&[10, 0, 0, 2], 53, 1, b"h\0i\0\x07\0"));
+        builder.add(2, &frame(&[10, 0, 0, 1], 5000, 0, b"other stream"));
+        builder.add(3, &frame(&[10, 0, 0, 1], 5000, 1, b"\xac\n"));
+        let stream = builder.build();
+        assert_eq!(stream.client, Encoding::Utf8);
+        assert_eq!(stream.server, Encoding::Utf16Le);
+        let chunks = stream
+            .chunks
+            .iter()
+            .map(|c| (c.frame, c.side, c.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            vec![
+                (0, Side::Client, "hello "),
+                (1, Side::Server, "hi."),
+                (3, Side::Client, "\u{20ac}\n"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Fixtures of the unit tests.
+
+use frame::Frame;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::{Fixed, MutFixed},
+    layer::{Layer, LayerClass, Payload},
+    slice::ByteSlice,
+    variant::Variant,
+};
+
+/// The class of the root layers.
+pub const ROOT: &str = "[link-1]";
+
+/// Builds a layer whose attributes have classes of their own.
+pub struct LayerBuilder {
+    id: &'static str,
+    name: Option<&'static str>,
+    data: ByteSlice,
+    attrs: Vec<Attr>,
+    payloads: Vec<Payload>,
+}
+
+/// Returns a builder of an empty layer of the class `id`.
+pub fn layer(id: &'static str) -> LayerBuilder {
+    LayerBuilder {
+        id,
+        name: None,
+        data: ByteSlice::new(),
+        attrs: Vec::new(),
+        payloads: Vec::new(),
+    }
+}
+
+/// Returns a builder of an empty root layer.
+pub fn root() -> LayerBuilder {
+    layer(ROOT)
+}
+
+impl LayerBuilder {
+    pub fn name(mut self, name: &'static str) -> LayerBuilder {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn data<B: Into<ByteSlice>>(mut self, data: B) -> LayerBuilder {
+        self.data = data.into();
+        self
+    }
+
+    pub fn attr<T: Into<Variant>>(self, id: &'static str, value: T) -> LayerBuilder {
+        let class = Fixed::new(AttrClass::builder(id).build());
+        self.add_attr(Attr::builder(class).value(value).build())
+    }
+
+    /// Adds an attribute whose class has the type `typ`.
+    pub fn typed_attr<T: Into<Variant>>(
+        self,
+        id: &'static str,
+        typ: &'static str,
+        value: T,
+    ) -> LayerBuilder {
+        let class = Fixed::new(AttrClass::builder(id).typ(typ).build());
+        self.add_attr(Attr::builder(class).value(value).build())
+    }
+
+    pub fn add_attr(mut self, attr: Attr) -> LayerBuilder {
+        self.attrs.push(attr);
+        self
+    }
+
+    pub fn payload(mut self, payload: Payload) -> LayerBuilder {
+        self.payloads.push(payload);
+        self
+    }
+
+    pub fn build(self) -> MutFixed<Layer> {
+        let mut class = LayerClass::builder(self.id);
+        if let Some(name) = self.name {
+            class = class.name(name);
+        }
+        let mut layer = Layer::new(Fixed::new(class.build()), self.data);
+        for attr in self.attrs {
+            layer.add_attr(attr);
+        }
+        for payload in self.payloads {
+            layer.add_payload(payload);
+        }
+        MutFixed::new(layer)
+    }
+}
+
+/// Returns a frame of `layers`, the first of which is the root layer.
+pub fn frame(index: u32, layers: Vec<MutFixed<Layer>>) -> Frame {
+    let mut layers = layers.into_iter();
+    let root = layers.next().expect("no root layer");
+    let mut frame = Frame::new(index, root);
+    let mut all = frame.fetch_layers();
+    all.extend(layers);
+    frame.set_layers(all);
+    frame
+}
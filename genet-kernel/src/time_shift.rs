@@ -0,0 +1,319 @@
+//! Correction of the timestamps of a session.
+//!
+//! The timestamps of a capture taken on a machine with an unsynchronized
+//! clock are corrected by a `Shift`, which maps the original timestamps
+//! linearly: by a fixed offset, or by the offset and the drift of the clock
+//! derived from the true times of two frames.
+//!
+//! The `link.timestamp*` attributes of the root layers are replaced by the
+//! corrected ones, so filters, columns, writers and the cross-frame analysis
+//! see the corrected timestamps. The original timestamps are kept as
+//! `link.timestamp.original*`, and `frame.time_shift` is the number of
+//! seconds added to the frame. The `frame.*` attributes of the analysis are
+//! removed with them, so that they are computed again from the corrected
+//! timestamps when the frames are decoded again.
+
+use analysis::{self, attr};
+use frame::Frame;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::Fixed,
+    layer::Layer,
+};
+use parking_lot::RwLock;
+use roaring::RoaringBitmap;
+use std::{fmt, sync::Arc};
+
+const ORIGINAL_SEC_ATTR: &str = "link.timestamp.original.sec";
+
+/// Returns the timestamp of a root layer in nanoseconds before it was
+/// corrected.
+pub(crate) fn original(root: &Layer) -> Option<i128> {
+    match attr::<i64>(root, ORIGINAL_SEC_ATTR) {
+        Some(sec) => {
+            let nsec = attr::<u64>(root, "link.timestamp.original.nsec").unwrap_or(0);
+            Some(i128::from(sec) * 1_000_000_000 + i128::from(nsec))
+        }
+        None => analysis::timestamp(root),
+    }
+}
+
+/// The true time of a frame.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ReferencePoint {
+    /// The index of the frame.
+    pub frame: u32,
+    /// The UNIX time in seconds.
+    pub time: f64,
+}
+
+/// A correction of the timestamps requested by the user.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Correction {
+    /// Adds the seconds to every timestamp.
+    Offset(f64),
+    /// Moves the timestamps of two frames to their true times, and the
+    /// others in proportion, to correct the drift of the clock.
+    Skew([ReferencePoint; 2]),
+}
+
+/// A linear map of timestamps in nanoseconds:
+/// `t + offset + (t - origin) * rate`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Shift {
+    pub origin: i64,
+    pub offset: i64,
+    pub rate: f64,
+}
+
+impl Shift {
+    /// Creates a Shift adding `seconds`.
+    pub fn offset(seconds: f64) -> Shift {
+        Shift {
+            origin: 0,
+            offset: (seconds * 1e9) as i64,
+            rate: 0.0,
+        }
+    }
+
+    /// Creates a Shift moving the timestamps `a.0` and `b.0` to `a.1` and
+    /// `b.1`, or None if the timestamps are the same.
+    pub fn skew(a: (i128, i128), b: (i128, i128)) -> Option<Shift> {
+        if a.0 == b.0 {
+            return None;
+        }
+        let scale = (b.1 - a.1) as f64 / (b.0 - a.0) as f64;
+        Some(Shift {
+            origin: a.0 as i64,
+            offset: (a.1 - a.0) as i64,
+            rate: scale - 1.0,
+        })
+    }
+
+    /// Returns the corrected timestamp in nanoseconds.
+    pub fn apply(&self, time: i128) -> i128 {
+        let drift = ((time - i128::from(self.origin)) as f64 * self.rate) as i128;
+        time + i128::from(self.offset) + drift
+    }
+}
+
+struct Classes {
+    timestamp: Fixed<AttrClass>,
+    sec: Fixed<AttrClass>,
+    usec: Fixed<AttrClass>,
+    nsec: Fixed<AttrClass>,
+    original: Fixed<AttrClass>,
+    original_sec: Fixed<AttrClass>,
+    original_nsec: Fixed<AttrClass>,
+    time_shift: Fixed<AttrClass>,
+}
+
+struct State {
+    shift: Option<Shift>,
+    /// The frames updated since the shift was set.
+    updated: RoaringBitmap,
+}
+
+/// The correction of the timestamps of a session, shared by the clones.
+#[derive(Clone)]
+pub struct TimeShift {
+    state: Arc<RwLock<State>>,
+    classes: Arc<Classes>,
+}
+
+impl fmt::Debug for TimeShift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TimeShift")
+    }
+}
+
+impl Default for TimeShift {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeShift {
+    pub fn new() -> TimeShift {
+        let class = |id: &str| Fixed::new(AttrClass::builder(id).build());
+        let datetime = |id: &str| Fixed::new(AttrClass::builder(id).typ("@datetime:unix").build());
+        TimeShift {
+            state: Arc::new(RwLock::new(State {
+                shift: None,
+                updated: RoaringBitmap::new(),
+            })),
+            classes: Arc::new(Classes {
+                timestamp: datetime("link.timestamp"),
+                sec: class("link.timestamp.sec"),
+                usec: class("link.timestamp.usec"),
+                nsec: class("link.timestamp.nsec"),
+                original: datetime("link.timestamp.original"),
+                original_sec: class(ORIGINAL_SEC_ATTR),
+                original_nsec: class("link.timestamp.original.nsec"),
+                time_shift: class("frame.time_shift"),
+            }),
+        }
+    }
+
+    pub fn get(&self) -> Option<Shift> {
+        self.state.read().shift
+    }
+
+    /// Replaces the shift, or removes it if `shift` is None. The frames
+    /// decoded after this call have the new timestamps.
+    pub fn set(&self, shift: Option<Shift>) {
+        let mut state = self.state.write();
+        state.shift = shift;
+        state.updated.clear();
+    }
+
+    /// Replaces the timestamps of the root layers of `frames` according to
+    /// the shift, which must be done before the cross-frame analysis.
+    pub fn update(&self, frames: &mut [Frame]) {
+        let mut state = self.state.write();
+        for frame in frames {
+            if !state.updated.insert(frame.index()) {
+                continue;
+            }
+            let root = match frame.layers_mut().first_mut() {
+                Some(root) => root,
+                None => continue,
+            };
+            // The frames which have never been shifted are left as they are.
+            let shifted = root.attr(ORIGINAL_SEC_ATTR).is_some();
+            if state.shift.is_none() && !shifted {
+                continue;
+            }
+            if let Some(time) = original(root) {
+                self.replace(root, time, state.shift);
+            }
+        }
+    }
+
+    fn replace(&self, root: &mut Layer, original: i128, shift: Option<Shift>) {
+        root.retain_attrs(|attr| {
+            let id = attr.id().to_string();
+            !id.starts_with("link.timestamp") && !id.starts_with("frame.")
+        });
+        let classes = &self.classes;
+        let time = shift.map_or(original, |shift| shift.apply(original)).max(0);
+        let add = |root: &mut Layer, class: &Fixed<AttrClass>, time: i128| {
+            root.add_attr(
+                Attr::builder(class.clone())
+                    .value(time as f64 / 1e9)
+                    .build(),
+            );
+        };
+        add(root, &classes.timestamp, time);
+        let (sec, nsec) = ((time / 1_000_000_000) as u64, (time % 1_000_000_000) as u64);
+        root.add_attr(Attr::builder(classes.sec.clone()).value(sec).build());
+        root.add_attr(
+            Attr::builder(classes.usec.clone())
+                .value(nsec / 1000)
+                .build(),
+        );
+        root.add_attr(Attr::builder(classes.nsec.clone()).value(nsec).build());
+        if shift.is_some() {
+            add(root, &classes.original, original);
+            let sec = original.div_euclid(1_000_000_000) as i64;
+            let nsec = original.rem_euclid(1_000_000_000) as u64;
+            root.add_attr(
+                Attr::builder(classes.original_sec.clone())
+                    .value(sec)
+                    .build(),
+            );
+            root.add_attr(
+                Attr::builder(classes.original_nsec.clone())
+                    .value(nsec)
+                    .build(),
+            );
+            let seconds = (time - original) as f64 / 1e9;
+            root.add_attr(
+                Attr::builder(classes.time_shift.clone())
+                    .value(seconds)
+                    .build(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use analysis::{self, Analyzer};
+    use frame::Frame;
+    use genet_abi::{token::Token, variant::Variant};
+    use test_util;
+    use time_shift::{original, Shift, TimeShift};
+
+    fn frame(index: u32, sec: u64, usec: u64) -> Frame {
+        let root = test_util::root()
+            .attr("link.timestamp.sec", sec)
+            .attr("link.timestamp.usec", usec)
+            .build();
+        test_util::frame(index, vec![root])
+    }
+
+    fn value(frame: &Frame, id: &str) -> Option<Variant> {
+        frame
+            .attr(Token::from(id))
+            .map(|attr| attr.try_get(&frame.layers()[0]).unwrap())
+    }
+
+    #[test]
+    fn shift() {
+        let shift = Shift::offset(-1.5);
+        assert_eq!(shift.apply(10_000_000_000), 8_500_000_000);
+
+        // The clock runs 1% fast from 100 seconds, and is 2 seconds behind.
+        let sec = |sec: i128| sec * 1_000_000_000;
+        let shift = Shift::skew((sec(100), sec(102)), (sec(200), sec(201))).unwrap();
+        assert_eq!(shift.apply(sec(100)), sec(102));
+        assert_eq!(shift.apply(sec(150)), sec(150) + 1_500_000_000);
+        assert_eq!(shift.apply(sec(200)), sec(201));
+        assert_eq!(Shift::skew((sec(1), sec(1)), (sec(1), sec(2))), None);
+    }
+
+    #[test]
+    fn update() {
+        let mut frames = vec![frame(0, 10, 0), frame(1, 10, 500_000)];
+        let mut analyzer = Analyzer::new();
+        for frame in &mut frames {
+            analyzer.process(frame);
+        }
+        assert_eq!(value(&frames[1], "frame.time_relative"), Some(0.5.into()));
+
+        let shift = TimeShift::new();
+        shift.update(&mut frames);
+        assert_eq!(value(&frames[0], "frame.time_relative"), Some(0.0.into()));
+
+        shift.set(Some(
+            Shift::skew(
+                (10_000_000_000, 20_000_000_000),
+                (10_500_000_000, 21_500_000_000),
+            )
+            .unwrap(),
+        ));
+        shift.update(&mut frames);
+        shift.update(&mut frames);
+        let mut analyzer = Analyzer::new();
+        for frame in &mut frames {
+            analyzer.process(frame);
+        }
+        let root = &frames[1].layers()[0];
+        assert_eq!(analysis::timestamp(root), Some(21_500_000_000));
+        assert_eq!(original(root), Some(10_500_000_000));
+        assert_eq!(value(&frames[1], "link.timestamp.sec"), Some(21u64.into()));
+        assert_eq!(value(&frames[1], "frame.time_shift"), Some(11.0.into()));
+        assert_eq!(value(&frames[1], "frame.time_relative"), Some(1.5.into()));
+
+        // The original timestamps are restored.
+        shift.set(None);
+        shift.update(&mut frames);
+        let root = &frames[1].layers()[0];
+        assert_eq!(analysis::timestamp(root), Some(10_500_000_000));
+        assert_eq!(value(&frames[1], "frame.time_shift"), None);
+        assert_eq!(value(&frames[1], "frame.time_relative"), None);
+    }
+}
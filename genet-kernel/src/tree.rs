@@ -0,0 +1,177 @@
+//! Layer trees of decoded frames.
+//!
+//! The layers of a frame are stored in breadth-first order, along with the
+//! number of children of each layer.
+
+use diff::VariantRef;
+use extract;
+use genet_abi::{attr::Attr, fixed::MutFixed, layer::Layer, variant::Variant};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// A decoded attribute of a layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttrNode {
+    pub id: String,
+    pub typ: String,
+    pub range: (usize, usize),
+    pub value: Variant,
+    /// The value formatted according to the type.
+    pub text: String,
+}
+
+impl AttrNode {
+    fn new(attr: &Attr, layer: &Layer) -> AttrNode {
+        let value = attr.try_get(layer).unwrap_or(Variant::Nil);
+        let range = attr.range();
+        AttrNode {
+            id: attr.id().to_string(),
+            typ: attr.typ().to_string(),
+            range: (range.start, range.end),
+            text: extract::format(attr, value.clone()),
+            value,
+        }
+    }
+}
+
+impl Serialize for AttrNode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(5))?;
+        map.serialize_entry("id", &self.id)?;
+        map.serialize_entry("typ", &self.typ)?;
+        map.serialize_entry("range", &self.range)?;
+        map.serialize_entry("value", &VariantRef(&self.value))?;
+        map.serialize_entry("text", &self.text)?;
+        map.end()
+    }
+}
+
+/// An undecoded payload of a layer.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PayloadNode {
+    pub id: String,
+    pub typ: String,
+    pub length: usize,
+}
+
+/// A layer and its child layers.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LayerNode {
+    pub id: String,
+    pub name: String,
+    pub length: usize,
+    pub attrs: Vec<AttrNode>,
+    pub payloads: Vec<PayloadNode>,
+    pub children: Vec<LayerNode>,
+}
+
+impl LayerNode {
+    fn new(layer: &Layer) -> LayerNode {
+        LayerNode {
+            id: layer.id().to_string(),
+            name: layer.metadata().name().to_string(),
+            length: layer.data().len(),
+            attrs: layer
+                .attrs_iter()
+                .map(|attr| AttrNode::new(attr, layer))
+                .collect(),
+            payloads: layer
+                .payloads()
+                .iter()
+                .map(|payload| PayloadNode {
+                    id: payload.id().to_string(),
+                    typ: payload.typ().to_string(),
+                    length: payload.data().len(),
+                })
+                .collect(),
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Builds the layer tree from `layers` in breadth-first order and the
+/// number of children of each layer.
+///
+/// Layers without an entry in `indices` are leaves.
+pub fn tree(layers: &[MutFixed<Layer>], indices: &[u8]) -> Option<LayerNode> {
+    if layers.is_empty() {
+        return None;
+    }
+    let mut children = Vec::with_capacity(layers.len());
+    let mut next = 1;
+    for index in 0..layers.len() {
+        let len = indices.get(index).map_or(0, |n| *n as usize);
+        let end = (next + len).min(layers.len());
+        children.push(next..end);
+        next = end;
+    }
+    let mut nodes = layers
+        .iter()
+        .map(|layer| Some(LayerNode::new(layer)))
+        .collect::<Vec<_>>();
+    // Children always come after their parent, so the tree is assembled
+    // from the last layer.
+    for index in (0..layers.len()).rev() {
+        let mut node = nodes[index].take().unwrap();
+        node.children = children[index]
+            .clone()
+            .filter_map(|child| nodes[child].take())
+            .collect();
+        nodes[index] = Some(node);
+    }
+    nodes[0].take()
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{layer::Payload, slice::ByteSlice, variant::Variant};
+    use serde_json;
+    use test_util::{self, LayerBuilder};
+    use tree::tree;
+
+    fn layer(id: &'static str) -> LayerBuilder {
+        test_util::layer(id).data(ByteSlice::from(&b"abcd"[..]))
+    }
+
+    #[test]
+    fn build() {
+        assert_eq!(tree(&[], &[]), None);
+
+        let tcp = layer("tcp")
+            .attr("tcp.dst", 80u64)
+            .payload(Payload::new(ByteSlice::from(&b"ab"[..]), "@data:tcp"));
+        let layers = vec![
+            layer("eth").build(),
+            layer("ipv4").build(),
+            layer("arp").build(),
+            tcp.build(),
+        ];
+
+        let root = tree(&layers, &[2, 1, 0, 0]).unwrap();
+        assert_eq!(root.id, "eth");
+        assert_eq!(root.length, 4);
+        let ids = root
+            .children
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["ipv4", "arp"]);
+
+        let tcp = &root.children[0].children[0];
+        assert_eq!(tcp.id, "tcp");
+        assert_eq!(tcp.attrs[0].value, Variant::UInt64(80));
+        assert_eq!(tcp.payloads[0].length, 2);
+        assert!(tcp.children.is_empty());
+
+        let json = serde_json::to_value(&tcp.attrs[0]).unwrap();
+        assert_eq!(json["id"], "tcp.dst");
+        assert_eq!(json["value"], 80);
+
+        // Missing entries are leaves.
+        let root = tree(&layers, &[1]).unwrap();
+        assert_eq!(root.children.len(), 1);
+        assert!(root.children[0].children.is_empty());
+    }
+}
@@ -0,0 +1,599 @@
+//! Decoders compiled to WebAssembly.
+//!
+//! A `.wasm` decoder runs in a wasmtime sandbox: a trap, an out-of-bounds
+//! access or a frame exhausting its fuel fails the decoding of the frame
+//! instead of the host process, and the instance is created again for the
+//! next frame.
+//!
+//! The module exports its `memory` and the following functions.
+//!
+//! - `genet_metadata() -> i64` returns the address of a JSON object in the
+//!   upper 32 bits and its length in the lower 32 bits. The object has the
+//!   fields of `decoder::Metadata`, which are all optional.
+//! - `genet_decode() -> i32` decodes the payloads of the parent layer and
+//!   returns 1 if done, 0 to skip and a negative value on error.
+//!
+//! The host functions are imported from the `genet` module. Strings are
+//! passed as an address and a length of UTF-8 bytes, and ranges as a start
+//! and an end offset.
+//!
+//! - `payload_find(id: str) -> i32` returns the index of the parent payload
+//!   `id`, or -1.
+//! - `payload_len(payload: i32) -> i32`
+//! - `payload_read(payload: i32, offset: i32, dst: i32, len: i32) -> i32`
+//!   copies the payload data to `dst` and returns the copied length.
+//! - `config(key: str, dst: i32, cap: i32) -> i32` copies the JSON value of
+//!   an option declared in the metadata and returns its length, or -1.
+//! - `layer_new(payload: i32, range, id: str) -> i32` creates a child layer
+//!   from `range` of the payload data and returns its handle.
+//! - `layer_attr(layer: i32, range, id: str, typ: str, cast: str) -> i32`
+//!   adds an attribute read by a cast of `dynamic::CastKind`, e.g.
+//!   `uint16be`, and returns 0, or -1 if the cast or the range is invalid.
+//! - `layer_int(layer: i32, range, id: str, typ: str, value: i64)`
+//! - `layer_str(layer: i32, range, id: str, typ: str, value: str)`
+//! - `layer_payload(layer: i32, range, id: str, typ: str) -> i32` adds a
+//!   payload of `range` of the layer data and returns 0, or -1.
+//!
+//! The layers are added to the parent in the order of creation if the
+//! decoding is done.
+
+use fnv::FnvHashMap;
+use genet_abi::{
+    context::Context,
+    decoder::{self, Decoder, DecoderOption, ExecType, Status},
+    error::Error,
+    layer::{Layer, LayerStack, Parent, Payload},
+    result::Result,
+    slice::{ByteSlice, TryGet},
+    token::Token,
+    variant::Variant,
+};
+use genet_sdk::dynamic::Classes;
+use serde_json;
+use std::{io, mem, str};
+use wasmtime::{
+    self, Caller, Config, Engine, Extern, Instance, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TypedFunc,
+};
+
+/// The fuel given to an instance for each frame.
+const FUEL_PER_FRAME: u64 = 10_000_000;
+
+/// The maximum size of the linear memory of an instance.
+const MAX_MEMORY: usize = 64 << 20;
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+struct WasmMetadata {
+    id: String,
+    name: String,
+    description: String,
+    exec_type: ExecType,
+    options: Vec<DecoderOption>,
+}
+
+impl Default for WasmMetadata {
+    fn default() -> Self {
+        WasmMetadata {
+            id: String::new(),
+            name: String::new(),
+            description: String::new(),
+            exec_type: ExecType::ParallelSync,
+            options: Vec::new(),
+        }
+    }
+}
+
+/// A decoder loaded from a WebAssembly module.
+#[derive(Clone)]
+pub struct WasmDecoder {
+    engine: Engine,
+    module: Module,
+    metadata: WasmMetadata,
+}
+
+impl WasmDecoder {
+    /// Loads a decoder from a `.wasm` file.
+    pub fn from_file(path: &str) -> io::Result<WasmDecoder> {
+        let engine = Self::engine()?;
+        let module = Module::from_file(&engine, path).map_err(other)?;
+        Self::from_module(engine, module)
+    }
+
+    /// Loads a decoder from the binary or text format of a module.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<WasmDecoder> {
+        let engine = Self::engine()?;
+        let module = Module::new(&engine, bytes).map_err(other)?;
+        Self::from_module(engine, module)
+    }
+
+    fn engine() -> io::Result<Engine> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config).map_err(other)
+    }
+
+    fn from_module(engine: Engine, module: Module) -> io::Result<WasmDecoder> {
+        let mut decoder = WasmDecoder {
+            engine,
+            module,
+            metadata: WasmMetadata::default(),
+        };
+        let mut instance = decoder
+            .instantiate(FnvHashMap::default(), Classes::new())
+            .map_err(other)?;
+        decoder.metadata = instance.metadata().map_err(other)?;
+        Ok(decoder)
+    }
+
+    fn instantiate(
+        &self,
+        config: FnvHashMap<String, String>,
+        classes: Classes,
+    ) -> wasmtime::Result<WasmInstance> {
+        let host = Host {
+            classes,
+            config,
+            payloads: Vec::new(),
+            layers: Vec::new(),
+            limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build(),
+        };
+        let mut store = Store::new(&self.engine, host);
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(FUEL_PER_FRAME)?;
+        let instance = linker(&self.engine)?.instantiate(&mut store, &self.module)?;
+        let decode = instance.get_typed_func::<(), i32>(&mut store, "genet_decode")?;
+        Ok(WasmInstance {
+            store,
+            instance,
+            decode,
+        })
+    }
+}
+
+impl Decoder for WasmDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<decoder::Worker> {
+        let config = self
+            .metadata
+            .options
+            .iter()
+            .map(|option| (option.key.clone(), ctx.get_config(&option.key).to_string()))
+            .collect();
+        Box::new(WasmWorker {
+            decoder: self.clone(),
+            config,
+            classes: Classes::new(),
+            instance: None,
+        })
+    }
+
+    fn metadata(&self) -> decoder::Metadata {
+        let meta = self.metadata.clone();
+        decoder::Metadata {
+            id: meta.id,
+            name: meta.name,
+            description: meta.description,
+            exec_type: meta.exec_type,
+            options: meta.options,
+        }
+    }
+}
+
+struct WasmWorker {
+    decoder: WasmDecoder,
+    config: FnvHashMap<String, String>,
+    classes: Classes,
+    instance: Option<WasmInstance>,
+}
+
+impl decoder::Worker for WasmWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let result = self.decode_payloads(parent);
+        if result.is_err() {
+            // The state of the instance is unknown after a trap, but the
+            // classes are kept since the frames decoded before refer to them.
+            if let Some(instance) = self.instance.take() {
+                self.classes = instance.store.into_data().classes;
+            }
+        }
+        match result {
+            Ok(status) => Ok(status),
+            Err(err) => {
+                let msg = format!("{}: {}", self.decoder.metadata.id, err);
+                Err(Box::new(Error::new(&msg)))
+            }
+        }
+    }
+}
+
+impl WasmWorker {
+    fn decode_payloads(&mut self, parent: &mut Parent) -> wasmtime::Result<Status> {
+        if self.instance.is_none() {
+            let classes = mem::replace(&mut self.classes, Classes::new());
+            self.instance = Some(self.decoder.instantiate(self.config.clone(), classes)?);
+        }
+        let instance = self.instance.as_mut().unwrap();
+        let store = &mut instance.store;
+        store.set_fuel(FUEL_PER_FRAME)?;
+        store.data_mut().payloads = parent
+            .payloads()
+            .iter()
+            .map(|p| (p.id(), p.data()))
+            .collect();
+        store.data_mut().layers.clear();
+        let result = instance.decode.call(&mut *store, ())?;
+        let layers = store.data_mut().layers.drain(..).collect::<Vec<_>>();
+        match result {
+            1 => {
+                for layer in layers {
+                    parent.add_child(layer);
+                }
+                Ok(Status::Done)
+            }
+            0 => Ok(Status::Skip),
+            code => Err(wasmtime::Error::msg(format!("error code {}", code))),
+        }
+    }
+}
+
+struct WasmInstance {
+    store: Store<Host>,
+    instance: Instance,
+    decode: TypedFunc<(), i32>,
+}
+
+impl WasmInstance {
+    fn metadata(&mut self) -> wasmtime::Result<WasmMetadata> {
+        let func = self
+            .instance
+            .get_typed_func::<(), i64>(&mut self.store, "genet_metadata")?;
+        let value = func.call(&mut self.store, ())?;
+        let memory = match self.instance.get_export(&mut self.store, "memory") {
+            Some(Extern::Memory(memory)) => memory,
+            _ => return Err(wasmtime::Error::msg("no memory exported")),
+        };
+        let json = bytes(memory.data(&self.store), (value >> 32) as i32, value as i32)?;
+        Ok(serde_json::from_slice(json)?)
+    }
+}
+
+struct Host {
+    classes: Classes,
+    config: FnvHashMap<String, String>,
+    payloads: Vec<(Token, ByteSlice)>,
+    layers: Vec<Layer>,
+    limits: StoreLimits,
+}
+
+impl Host {
+    fn payload(&self, index: i32) -> wasmtime::Result<ByteSlice> {
+        self.payloads
+            .get(index as usize)
+            .map(|(_, data)| *data)
+            .ok_or_else(|| wasmtime::Error::msg("invalid payload"))
+    }
+
+    fn layer(&mut self, handle: i32) -> wasmtime::Result<&mut Layer> {
+        self.layers
+            .get_mut(handle as usize)
+            .ok_or_else(|| wasmtime::Error::msg("invalid layer"))
+    }
+}
+
+fn other(err: wasmtime::Error) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+fn bytes(mem: &[u8], ptr: i32, len: i32) -> wasmtime::Result<&[u8]> {
+    let start = ptr as u32 as usize;
+    mem.get(start..start + len as u32 as usize)
+        .ok_or_else(|| wasmtime::Error::msg("out of bounds"))
+}
+
+fn string(mem: &[u8], ptr: i32, len: i32) -> wasmtime::Result<&str> {
+    Ok(str::from_utf8(bytes(mem, ptr, len)?)?)
+}
+
+fn range(start: i32, end: i32) -> ::std::ops::Range<usize> {
+    start as u32 as usize..end as u32 as usize
+}
+
+/// Returns the guest memory and the host state of `caller`.
+fn memory<'a>(caller: &'a mut Caller<Host>) -> wasmtime::Result<(&'a mut [u8], &'a mut Host)> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Ok(memory.data_and_store_mut(caller)),
+        _ => Err(wasmtime::Error::msg("no memory exported")),
+    }
+}
+
+fn linker(engine: &Engine) -> wasmtime::Result<Linker<Host>> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap(
+        "genet",
+        "payload_find",
+        |mut caller: Caller<Host>, id: i32, id_len: i32| {
+            let (mem, host) = memory(&mut caller)?;
+            let id = Token::from(string(mem, id, id_len)?);
+            Ok(host
+                .payloads
+                .iter()
+                .position(|(p, _)| *p == id)
+                .map_or(-1, |i| i as i32))
+        },
+    )?;
+    linker.func_wrap(
+        "genet",
+        "payload_len",
+        |caller: Caller<Host>, payload: i32| Ok(caller.data().payload(payload)?.len() as i32),
+    )?;
+    linker.func_wrap(
+        "genet",
+        "payload_read",
+        |mut caller: Caller<Host>, payload: i32, offset: i32, dst: i32, len: i32| {
+            let (mem, host) = memory(&mut caller)?;
+            let data = host.payload(payload)?;
+            let src = data.get(offset as u32 as usize..).unwrap_or(&[]);
+            let len = src.len().min(len as u32 as usize);
+            let start = dst as u32 as usize;
+            mem.get_mut(start..start + len)
+                .ok_or_else(|| wasmtime::Error::msg("out of bounds"))?
+                .copy_from_slice(&src[..len]);
+            Ok(len as i32)
+        },
+    )?;
+    linker.func_wrap(
+        "genet",
+        "config",
+        |mut caller: Caller<Host>, key: i32, key_len: i32, dst: i32, cap: i32| {
+            let (mem, host) = memory(&mut caller)?;
+            let value = match host.config.get(string(mem, key, key_len)?) {
+                Some(value) => value.as_bytes(),
+                None => return Ok(-1),
+            };
+            let len = value.len().min(cap as u32 as usize);
+            let start = dst as u32 as usize;
+            mem.get_mut(start..start + len)
+                .ok_or_else(|| wasmtime::Error::msg("out of bounds"))?
+                .copy_from_slice(&value[..len]);
+            Ok(value.len() as i32)
+        },
+    )?;
+    linker.func_wrap(
+        "genet",
+        "layer_new",
+        |mut caller: Caller<Host>, payload: i32, start: i32, end: i32, id: i32, id_len: i32| {
+            let (mem, host) = memory(&mut caller)?;
+            let data = host
+                .payload(payload)?
+                .try_get(range(start, end))
+                .map_err(|_| wasmtime::Error::msg("out of range"))?;
+            let class = host.classes.layer(string(mem, id, id_len)?);
+            host.layers.push(Layer::new(class, data));
+            Ok(host.layers.len() as i32 - 1)
+        },
+    )?;
+    linker.func_wrap(
+        "genet",
+        "layer_attr",
+        |mut caller: Caller<Host>,
+         layer: i32,
+         start: i32,
+         end: i32,
+         id: i32,
+         id_len: i32,
+         typ: i32,
+         typ_len: i32,
+         cast: i32,
+         cast_len: i32| {
+            let (mem, host) = memory(&mut caller)?;
+            let (id, typ) = (string(mem, id, id_len)?, string(mem, typ, typ_len)?);
+            let cast = string(mem, cast, cast_len)?;
+            let layer = host.layers.get_mut(layer as usize);
+            let layer = layer.ok_or_else(|| wasmtime::Error::msg("invalid layer"))?;
+            Ok(
+                match host
+                    .classes
+                    .add_attr(layer, id, typ, range(start, end), cast)
+                {
+                    Ok(()) => 0,
+                    Err(_) => -1,
+                },
+            )
+        },
+    )?;
+    linker.func_wrap(
+        "genet",
+        "layer_int",
+        |mut caller: Caller<Host>,
+         layer: i32,
+         start: i32,
+         end: i32,
+         id: i32,
+         id_len: i32,
+         typ: i32,
+         typ_len: i32,
+         value: i64| {
+            let (mem, host) = memory(&mut caller)?;
+            let (id, typ) = (string(mem, id, id_len)?, string(mem, typ, typ_len)?);
+            let layer = host.layers.get_mut(layer as usize);
+            let layer = layer.ok_or_else(|| wasmtime::Error::msg("invalid layer"))?;
+            host.classes
+                .add_value(layer, id, typ, range(start, end), Variant::Int64(value));
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "genet",
+        "layer_str",
+        |mut caller: Caller<Host>,
+         layer: i32,
+         start: i32,
+         end: i32,
+         id: i32,
+         id_len: i32,
+         typ: i32,
+         typ_len: i32,
+         value: i32,
+         value_len: i32| {
+            let (mem, host) = memory(&mut caller)?;
+            let (id, typ) = (string(mem, id, id_len)?, string(mem, typ, typ_len)?);
+            let value = Variant::String(string(mem, value, value_len)?.into());
+            let layer = host.layers.get_mut(layer as usize);
+            let layer = layer.ok_or_else(|| wasmtime::Error::msg("invalid layer"))?;
+            host.classes
+                .add_value(layer, id, typ, range(start, end), value);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "genet",
+        "layer_payload",
+        |mut caller: Caller<Host>,
+         layer: i32,
+         start: i32,
+         end: i32,
+         id: i32,
+         id_len: i32,
+         typ: i32,
+         typ_len: i32| {
+            let (mem, host) = memory(&mut caller)?;
+            let (id, typ) = (string(mem, id, id_len)?, string(mem, typ, typ_len)?);
+            let layer = host.layer(layer)?;
+            Ok(match layer.data().try_get(range(start, end)) {
+                Ok(data) => {
+                    layer.add_payload(Payload::with_typ(data, id, typ));
+                    0
+                }
+                Err(_) => -1,
+            })
+        },
+    )?;
+    Ok(linker)
+}
+
+#[cfg(test)]
+mod tests {
+    use fnv::FnvHashMap;
+    use genet_abi::{
+        context::Context,
+        decoder::{Decoder, DecoderBox, ExecType},
+        fixed::MutFixed,
+        layer::{Layer, Parent, Payload},
+        slice::ByteSlice,
+        token::Token,
+        variant::Variant,
+    };
+    use test_util;
+    use wasm::WasmDecoder;
+
+    const UDP: &str = r#"
+        (module
+          (import "genet" "payload_find" (func $payload_find (param i32 i32) (result i32)))
+          (import "genet" "payload_len" (func $payload_len (param i32) (result i32)))
+          (import "genet" "layer_new"
+            (func $layer_new (param i32 i32 i32 i32 i32) (result i32)))
+          (import "genet" "layer_attr"
+            (func $layer_attr (param i32 i32 i32 i32 i32 i32 i32 i32 i32) (result i32)))
+          (import "genet" "layer_str"
+            (func $layer_str (param i32 i32 i32 i32 i32 i32 i32 i32 i32)))
+          (import "genet" "layer_payload"
+            (func $layer_payload (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "@data:udp")
+          (data (i32.const 16) "udp")
+          (data (i32.const 32) "udp.src")
+          (data (i32.const 48) "udp.dst")
+          (data (i32.const 64) "uint16be")
+          (data (i32.const 80) "@udp:port")
+          (data (i32.const 96) "udp.note")
+          (data (i32.const 112) "wasm")
+          (data (i32.const 128) "@data:dns")
+          (data (i32.const 256)
+            "{\"id\":\"wasm-udp\",\"name\":\"UDP\",\"exec_type\":\"SerialSync\"}")
+          (func (export "genet_metadata") (result i64)
+            (i64.const 0x10000000037))
+          (func (export "genet_decode") (result i32)
+            (local $payload i32) (local $len i32) (local $layer i32)
+            (local.set $payload (call $payload_find (i32.const 0) (i32.const 9)))
+            (if (i32.lt_s (local.get $payload) (i32.const 0))
+              (then (return (i32.const 0))))
+            (local.set $len (call $payload_len (local.get $payload)))
+            (if (i32.eq (local.get $len) (i32.const 0))
+              (then (loop $forever (br $forever))))
+            (if (i32.lt_s (local.get $len) (i32.const 8))
+              (then (return (i32.const -1))))
+            (local.set $layer (call $layer_new (local.get $payload)
+              (i32.const 0) (local.get $len) (i32.const 16) (i32.const 3)))
+            (drop (call $layer_attr (local.get $layer) (i32.const 0) (i32.const 2)
+              (i32.const 32) (i32.const 7) (i32.const 80) (i32.const 9)
+              (i32.const 64) (i32.const 8)))
+            (drop (call $layer_attr (local.get $layer) (i32.const 2) (i32.const 4)
+              (i32.const 48) (i32.const 7) (i32.const 80) (i32.const 9)
+              (i32.const 64) (i32.const 8)))
+            (call $layer_str (local.get $layer) (i32.const 0) (i32.const 0)
+              (i32.const 96) (i32.const 8) (i32.const 0) (i32.const 0)
+              (i32.const 112) (i32.const 4))
+            (drop (call $layer_payload (local.get $layer) (i32.const 8) (local.get $len)
+              (i32.const 128) (i32.const 9) (i32.const 0) (i32.const 0)))
+            (i32.const 1)))
+    "#;
+
+    fn parent_layer(data: &'static [u8]) -> MutFixed<Layer> {
+        test_util::layer("ipv4")
+            .payload(Payload::new(ByteSlice::from(data), "@data:udp"))
+            .build()
+    }
+
+    #[test]
+    fn decode_udp() {
+        let decoder = WasmDecoder::from_bytes(UDP.as_bytes()).unwrap();
+        let meta = decoder.metadata();
+        assert_eq!(meta.id, "wasm-udp");
+        assert_eq!(meta.name, "UDP");
+        assert_eq!(meta.exec_type, ExecType::SerialSync);
+
+        let mut decoder = DecoderBox::new(decoder);
+        let mut ctx = Context::new(FnvHashMap::default());
+        let mut worker = decoder.new_worker(&ctx);
+        let mut layer = parent_layer(b"\x30\x39\x00\x35\x00\x0c\x00\x00dns!");
+        let mut parent = Parent::from_mut_ref(&mut layer);
+        assert!(worker.decode(&mut ctx, &[], &mut parent).unwrap());
+        let layer = unsafe { &*parent.children()[0] };
+        assert_eq!(layer.id(), Token::from("udp"));
+        let value = |id: &str| layer.attr(id).unwrap().try_get(&layer).unwrap();
+        assert_eq!(value("udp.src"), Variant::UInt64(12345));
+        assert_eq!(value("udp.dst"), Variant::UInt64(53));
+        assert_eq!(value("udp.note"), Variant::String("wasm".into()));
+        assert_eq!(layer.payloads()[0].id(), Token::from("@data:dns"));
+        assert_eq!(&layer.payloads()[0].data()[..], b"dns!");
+    }
+
+    #[test]
+    fn sandbox() {
+        let mut decoder = DecoderBox::new(WasmDecoder::from_bytes(UDP.as_bytes()).unwrap());
+        let mut ctx = Context::new(FnvHashMap::default());
+        let mut worker = decoder.new_worker(&ctx);
+
+        // An empty payload makes the module loop until it runs out of fuel.
+        let mut layer = parent_layer(b"");
+        let mut parent = Parent::from_mut_ref(&mut layer);
+        assert!(worker.decode(&mut ctx, &[], &mut parent).is_err());
+
+        let mut layer = parent_layer(b"\x00");
+        let mut parent = Parent::from_mut_ref(&mut layer);
+        assert!(worker.decode(&mut ctx, &[], &mut parent).is_err());
+        assert!(parent.children().is_empty());
+
+        // The instance is created again after a trap.
+        let mut layer = parent_layer(b"\x00\x01\x00\x02\x00\x08\x00\x00");
+        let mut parent = Parent::from_mut_ref(&mut layer);
+        assert!(worker.decode(&mut ctx, &[], &mut parent).unwrap());
+
+        assert!(WasmDecoder::from_bytes(b"(module)").is_err());
+    }
+}
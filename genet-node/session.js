@@ -2,6 +2,7 @@ const native = require('./binding')
 const { Token } = native
 const { Disposable } = require('disposables')
 const { EventEmitter } = require('events')
+const path = require('path')
 function consume (len, layerStack, indexStack) {
   const indices = indexStack.splice(0, len)
   const layers = layerStack.splice(0, len)
@@ -37,6 +38,10 @@ class Frame {
     return this._frame.index
   }
 
+  get protocols () {
+    return this._frame.protocols
+  }
+
   get root () {
     if (!this._root) {
       [this._root] = treefy(this._frame.layers, this._frame.treeIndices)
@@ -63,7 +68,7 @@ class Frame {
 }
 
 class Session extends EventEmitter {
-  constructor (profile, options) {
+  constructor (profile, options = {}) {
     super()
     this._options = options
     this._sess = new native.Session(profile, (json) => {
@@ -79,6 +84,19 @@ class Session extends EventEmitter {
           this._status.filters[Token.string(event.id)] =
             { frames: event.length }
           break
+        case 'frames_evicted':
+          this._status.firstFrame = event.start
+          break
+        case 'rotated':
+          this.emit('rotated', event)
+          break
+        case 'capture_stats':
+          this._status.captureStats[event.id] = event
+          break
+        case 'redecode':
+          this._status.redecode =
+            { frames: event.frames, length: event.length }
+          break
         case 'error':
           this.emit('error', event.error)
           break
@@ -90,9 +108,13 @@ class Session extends EventEmitter {
     this._streamReaders = new Set()
     this._status = {
       filters: {},
+      captureStats: {},
       frames: 0,
+      firstFrame: 0,
       asyncFrames: 0,
       stream: false,
+      spill: null,
+      redecode: null,
     }
   }
 
@@ -106,10 +128,207 @@ class Session extends EventEmitter {
       .map((frame) => new Frame(frame))
   }
 
+  frameSummaries (start, end) {
+    return JSON.parse(this._sess.frameSummaries(start, end))
+  }
+
+  valueCounts (id, filter = '', topN = 10) {
+    return JSON.parse(this._sess.valueCounts(id, filter, topN))
+  }
+
+  coverage (filter = '') {
+    return JSON.parse(this._sess.coverage(filter))
+  }
+
+  streamText (layer, stream) {
+    return JSON.parse(this._sess.streamText(layer, stream))
+  }
+
+  followStream (layer, stream, direction = '') {
+    return this._sess.followStream(layer, stream, direction)
+  }
+
+  conversations (level) {
+    return JSON.parse(this._sess.conversations(level))
+  }
+
+  get annotations () {
+    return JSON.parse(this._sess.annotations())
+  }
+
+  setComment (index, comment = '') {
+    this._sess.setComment(index, comment)
+  }
+
+  setByteAnnotations (index, annotations) {
+    this._sess.setByteAnnotations(index, JSON.stringify(annotations))
+  }
+
+  frameAnnotations (index) {
+    return JSON.parse(this._sess.frameAnnotations(index))
+  }
+
+  get commentedFrames () {
+    return this._sess.commentedFrames()
+  }
+
+  setBookmark (index, bookmarked = true) {
+    this._sess.setBookmark(index, bookmarked)
+  }
+
+  setColorRules (rules) {
+    this._sess.setColorRules(JSON.stringify(rules))
+  }
+
+  exportAnnotations (path) {
+    this._sess.exportAnnotations(path)
+  }
+
+  importAnnotations (path) {
+    this._sess.importAnnotations(path)
+  }
+
+  save (path) {
+    this._sess.save(path)
+  }
+
+  restore (path) {
+    this._sess.restore(path)
+  }
+
+  compare (id, arg = {}, options = {}) {
+    return JSON.parse(
+      this._sess.compare(id, JSON.stringify(arg), JSON.stringify(options)))
+  }
+
+  get comparisonReport () {
+    return JSON.parse(this._sess.comparisonReport())
+  }
+
+  clearComparison () {
+    this._sess.clearComparison()
+  }
+
+  shiftTimestamps (correction) {
+    return JSON.parse(this._sess.shiftTimestamps(JSON.stringify(correction)))
+  }
+
+  get timeShift () {
+    return JSON.parse(this._sess.timeShift())
+  }
+
+  clearTimeShift () {
+    this._sess.clearTimeShift()
+  }
+
+  get expertSummary () {
+    return JSON.parse(this._sess.expertSummary())
+  }
+
+  expertFindings (index) {
+    return JSON.parse(this._sess.expertFindings(index))
+  }
+
+  pinFrames (id, start, end) {
+    this._sess.pinFrames(id, start, end)
+  }
+
+  unpinFrames (id) {
+    this._sess.unpinFrames(id)
+  }
+
+  get cacheStats () {
+    return JSON.parse(this._sess.cacheStats())
+  }
+
+  get pipelineStats () {
+    return JSON.parse(this._sess.pipelineStats())
+  }
+
+  captureStats (handle) {
+    return JSON.parse(this._sess.captureStats(handle))
+  }
+
+  diffFrames (a, b) {
+    const json = this._sess.diffFrames(a, b)
+    return json === null ? null : JSON.parse(json)
+  }
+
+  decodePatched (index, patches = []) {
+    const json = JSON.stringify(patches.map(({ offset, data }) => ({
+      offset,
+      data: Array.from(data),
+    })))
+    const frame = this._sess.decodePatched(index, json)
+    return frame === null ? null : new Frame(frame)
+  }
+
   filteredFrames (id, start, end) {
     return this._sess.filteredFrames(Token.get(id), start, end)
   }
 
+  setColumns (columns) {
+    this._sess.setColumns(JSON.stringify(columns))
+  }
+
+  columnRows (start, end, id) {
+    const rows = (id === undefined)
+      ? this._sess.columnRows(start, end)
+      : this._sess.columnRows(start, end, Token.get(id))
+    return JSON.parse(rows)
+  }
+
+  frameColors (start, end, id) {
+    const colors = (id === undefined)
+      ? this._sess.frameColors(start, end)
+      : this._sess.frameColors(start, end, Token.get(id))
+    return JSON.parse(colors)
+  }
+
+  sortFrames (id, expr, filter = '', descending = false) {
+    return this._sess.sortFrames(id, expr, filter, descending)
+  }
+
+  sortedFrames (id, start, end) {
+    return this._sess.sortedFrames(id, start, end)
+  }
+
+  clearSort (id) {
+    this._sess.clearSort(id)
+  }
+
+  filteredLength (id) {
+    return this._sess.filteredLength(Token.get(id))
+  }
+
+  filteredRank (id, index) {
+    return this._sess.filteredRank(Token.get(id), index)
+  }
+
+  filteredSelect (id, row) {
+    return this._sess.filteredSelect(Token.get(id), row)
+  }
+
+  filteredBitmap (id) {
+    return this._sess.filteredBitmap(Token.get(id))
+  }
+
+  setMarked (indices, marked = true) {
+    this._sess.setMarked(JSON.stringify(indices), marked)
+  }
+
+  setIgnored (indices, ignored = true) {
+    this._sess.setIgnored(JSON.stringify(indices), ignored)
+  }
+
+  get markedBitmap () {
+    return this._sess.markedBitmap()
+  }
+
+  get ignoredBitmap () {
+    return this._sess.ignoredBitmap()
+  }
+
   get status () {
     return this._status
   }
@@ -121,6 +340,40 @@ class Session extends EventEmitter {
     }
   }
 
+  setIoGraph (id, options = null) {
+    if (options === null) {
+      this._sess.setIoGraph(Token.get(id), 0, '', '', '')
+      return
+    }
+    const {
+      interval = 1, filter = '', aggregation = 'count', attr = '',
+    } = options
+    this._sess.setIoGraph(Token.get(id), interval, filter, aggregation, attr)
+  }
+
+  ioGraph (id) {
+    const points = this._sess.ioGraph(Token.get(id))
+    return points === null ? null : JSON.parse(points)
+  }
+
+  setDissectorTableEntry (table, key, decoder = '') {
+    this._sess.setDissectorTableEntry(table, key, decoder)
+  }
+
+  setConfig (key, value) {
+    this._sess.setConfig(key, JSON.stringify(value))
+  }
+
+  setDecodeAs (id, filter = '', decoder = '') {
+    this._sess.setDecodeAs(Token.get(id), filter, decoder)
+  }
+
+  setTimestampFormat ({
+    zone = 'local', reference = 'absolute', resolution = 'us',
+  } = {}) {
+    this._sess.setTimestampFormat(zone, reference, resolution)
+  }
+
   createReader (id, arg = {}) {
     const handle = this._sess.createReader(id, JSON.stringify(arg))
     if (handle === 0) {
@@ -131,7 +384,7 @@ class Session extends EventEmitter {
     })
     disposable.promise = new Promise((res, rej) => {
       this.on('update', (event) => {
-        if (event.id === handle && event.type === 'output') {
+        if (event.id === handle && event.type === 'input') {
           if (event.error === null) {
             res()
           } else {
@@ -143,9 +396,12 @@ class Session extends EventEmitter {
     return disposable
   }
 
-  async createWriter (id, arg = {}, filter = '') {
-    const handle = this._sess.createWriter(
-      id, JSON.stringify(arg), filter)
+  async createWriter (id, arg = {}, filter = '', range = null) {
+    const args = [id, JSON.stringify(arg), filter]
+    if (range !== null) {
+      args.push(range[0], range[1])
+    }
+    const handle = this._sess.createWriter(...args)
     if (handle === 0) {
       throw new Error(`failed to invoke writer: ${id}`)
     }
@@ -154,7 +410,7 @@ class Session extends EventEmitter {
     })
     disposable.promise = new Promise((res, rej) => {
       this.on('update', (event) => {
-        if (event.id === handle && event.type === 'input') {
+        if (event.id === handle && event.type === 'output') {
           if (event.error === null) {
             res()
           } else {
@@ -166,6 +422,30 @@ class Session extends EventEmitter {
     return disposable
   }
 
+  writeRawFrames (id, arg = {}, frames = [], link = 1) {
+    const handle = this._sess.writeRawFrames(id, JSON.stringify(arg), link,
+      frames.map((frame) => Uint8Array.from(frame)))
+    if (handle === 0) {
+      return Promise.reject(new Error(`failed to invoke writer: ${id}`))
+    }
+    return new Promise((res, rej) => {
+      this.on('update', (event) => {
+        if (event.id === handle && event.type === 'output') {
+          if (event.error === null) {
+            res()
+          } else {
+            rej(new Error(event.error))
+          }
+        }
+      })
+    })
+  }
+
+  transmit (iface, frames, options = {}) {
+    return this.writeRawFrames('app.genet.writer.inject',
+      Object.assign({ interface: iface }, options), frames)
+  }
+
   regiterStreamReader (id, arg = {}) {
     const reader = {
       id,
@@ -179,6 +459,12 @@ class Session extends EventEmitter {
 
   startStream () {
     this.stopStream()
+    const { spillDir } = this._options
+    if (spillDir) {
+      const file = path.join(spillDir, `genet-${Date.now()}.gspill`)
+      this._sess.startSpill(file)
+      this._status.spill = file
+    }
     this._streams = Array.from(this._streamReaders)
       .map(({ id, arg }) => this.createReader(id, arg))
     this._status.stream = true
@@ -189,9 +475,32 @@ class Session extends EventEmitter {
       handle.dispose()
     }
     this._streams = []
+    if (this._status.spill !== null) {
+      this._sess.stopSpill()
+    }
     this._status.stream = false
   }
 
+  startRingBuffer (id, arg = {}, options = {}) {
+    this._sess.startRingBuffer(id, JSON.stringify(arg), JSON.stringify(options))
+  }
+
+  stopRingBuffer () {
+    this._sess.stopRingBuffer()
+  }
+
+  setFrameWindow (frames = null) {
+    this._sess.setFrameWindow(frames)
+  }
+
+  get firstFrame () {
+    return this._sess.firstFrame()
+  }
+
+  replaySpill (file) {
+    return this.createReader('app.genet.reader.spill', { file })
+  }
+
   get length () {
     return this._sess.length
   }
@@ -199,6 +508,10 @@ class Session extends EventEmitter {
   get profile () {
     return JSON.parse(this._sess.profile)
   }
+
+  get metadata () {
+    return JSON.parse(this._sess.metadata)
+  }
 }
 
 class Profile extends native.Session.Profile { }
@@ -112,6 +112,25 @@ describe('Session', () => {
             sess.close()
         })
     })
+  describe('#filteredSelect', () => {
+        it('should return null for an unknown filter', function() {
+            const profile = new Profile()
+            const sess = new Session(profile)
+            assert.strictEqual(sess.filteredLength(0), 0)
+            assert.strictEqual(sess.filteredRank(0, 10), 0)
+            assert.strictEqual(sess.filteredSelect(0, 0), null)
+            assert.strictEqual(sess.filteredBitmap(0), null)
+            sess.close()
+        })
+        it('should throw for wrong arguments', () => {
+            const profile = new Profile()
+            const sess = new Session(profile)
+            assert.throws(() => sess.filteredRank(0), TypeError)
+            assert.throws(() => sess.filteredSelect(0, 'aaa'), TypeError)
+            assert.throws(() => sess.filteredBitmap(), TypeError)
+            sess.close()
+        })
+    })
 
   /*
     Describe('#setFilter', function() {
@@ -0,0 +1,214 @@
+use genet_abi::{attr, fixed::MutFixed, layer, variant::Variant};
+use genet_kernel::extract;
+use pyo3::{
+    exceptions::PyKeyError,
+    prelude::*,
+    types::{PyBytes, PyInt, PyList},
+    IntoPyObjectExt,
+};
+
+/// Converts an attribute value to a Python object.
+pub fn value(py: Python, value: Variant) -> PyResult<Py<PyAny>> {
+    match value {
+        Variant::Nil => Ok(py.None()),
+        Variant::Bool(val) => val.into_py_any(py),
+        Variant::Int64(val) => val.into_py_any(py),
+        Variant::UInt64(val) => val.into_py_any(py),
+        Variant::Float64(val) => val.into_py_any(py),
+        Variant::String(val) => val.into_py_any(py),
+        Variant::BigInt(val) => py
+            .get_type::<PyInt>()
+            .call_method1("from_bytes", (PyBytes::new(py, &val), "big", true))
+            .map(|val| val.unbind()),
+        Variant::Buffer(val) => PyBytes::new(py, &val).into_py_any(py),
+        Variant::Slice(val) => PyBytes::new(py, &val).into_py_any(py),
+    }
+}
+
+/// An attribute of a layer.
+#[pyclass(module = "genet", frozen)]
+pub struct Attr {
+    id: String,
+    typ: String,
+    range: (usize, usize),
+    value: Py<PyAny>,
+    text: String,
+}
+
+impl Attr {
+    fn new(py: Python, attr: &attr::Attr, layer: &layer::Layer) -> PyResult<Attr> {
+        let val = attr.try_get(layer).unwrap_or(Variant::Nil);
+        let range = attr.range();
+        Ok(Attr {
+            id: attr.id().to_string(),
+            typ: attr.typ().to_string(),
+            range: (range.start, range.end),
+            text: extract::format(attr, val.clone()),
+            value: value(py, val)?,
+        })
+    }
+}
+
+#[pymethods]
+impl Attr {
+    #[getter]
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The type of the value, e.g. `@ipv4:addr`.
+    #[getter]
+    fn typ(&self) -> &str {
+        &self.typ
+    }
+
+    /// The byte range of the attribute in the layer data.
+    #[getter]
+    fn range(&self) -> (usize, usize) {
+        self.range
+    }
+
+    #[getter]
+    fn value(&self, py: Python) -> Py<PyAny> {
+        self.value.clone_ref(py)
+    }
+
+    /// The value formatted according to the type, e.g. a dotted IPv4
+    /// address.
+    #[getter]
+    fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Attr {}: {}>", self.id, self.text)
+    }
+}
+
+/// A decoded layer.
+#[pyclass(module = "genet", frozen)]
+pub struct Layer {
+    id: String,
+    data: Py<PyBytes>,
+    attrs: Vec<Py<Attr>>,
+}
+
+impl Layer {
+    fn new(py: Python, layer: &layer::Layer) -> PyResult<Layer> {
+        let attrs = layer
+            .attrs_iter()
+            .map(|attr| Py::new(py, Attr::new(py, attr, layer)?))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(Layer {
+            id: layer.id().to_string(),
+            data: PyBytes::new(py, &layer.data()).unbind(),
+            attrs,
+        })
+    }
+
+    fn find(&self, py: Python, id: &str) -> Option<Py<PyAny>> {
+        self.attrs
+            .iter()
+            .find(|attr| attr.get().id == id)
+            .map(|attr| attr.get().value.clone_ref(py))
+    }
+}
+
+#[pymethods]
+impl Layer {
+    #[getter]
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    #[getter]
+    fn data(&self, py: Python) -> Py<PyBytes> {
+        self.data.clone_ref(py)
+    }
+
+    #[getter]
+    fn attrs<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.attrs.iter().map(|attr| attr.clone_ref(py)))
+    }
+
+    /// Returns the value of the attribute `id`.
+    fn __getitem__(&self, py: Python, id: &str) -> PyResult<Py<PyAny>> {
+        self.find(py, id)
+            .ok_or_else(|| PyKeyError::new_err(id.to_string()))
+    }
+
+    #[pyo3(signature = (id, default=None))]
+    fn get(&self, py: Python, id: &str, default: Option<Py<PyAny>>) -> Py<PyAny> {
+        self.find(py, id).or(default).unwrap_or_else(|| py.None())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Layer {}>", self.id)
+    }
+}
+
+/// A decoded frame.
+///
+/// As in filters, an attribute of a frame is looked up from the topmost
+/// layer.
+#[pyclass(module = "genet", frozen)]
+pub struct Frame {
+    index: u32,
+    layers: Vec<Py<Layer>>,
+}
+
+impl Frame {
+    pub fn new(py: Python, index: u32, layers: &[MutFixed<layer::Layer>]) -> PyResult<Frame> {
+        let layers = layers
+            .iter()
+            .map(|layer| Py::new(py, Layer::new(py, layer)?))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(Frame { index, layers })
+    }
+
+    fn find(&self, py: Python, id: &str) -> Option<Py<PyAny>> {
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|layer| layer.get().find(py, id))
+    }
+}
+
+#[pymethods]
+impl Frame {
+    /// The index of the frame in the session.
+    #[getter]
+    fn index(&self) -> u32 {
+        self.index
+    }
+
+    #[getter]
+    fn layers<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.layers.iter().map(|layer| layer.clone_ref(py)))
+    }
+
+    /// Returns the value of the attribute `id` of the topmost layer which
+    /// has it.
+    fn __getitem__(&self, py: Python, id: &str) -> PyResult<Py<PyAny>> {
+        self.find(py, id)
+            .ok_or_else(|| PyKeyError::new_err(id.to_string()))
+    }
+
+    fn __contains__(&self, py: Python, id: &str) -> bool {
+        self.find(py, id).is_some()
+    }
+
+    #[pyo3(signature = (id, default=None))]
+    fn get(&self, py: Python, id: &str, default: Option<Py<PyAny>>) -> Py<PyAny> {
+        self.find(py, id).or(default).unwrap_or_else(|| py.None())
+    }
+
+    fn __repr__(&self) -> String {
+        let ids = self
+            .layers
+            .iter()
+            .map(|layer| layer.get().id.clone())
+            .collect::<Vec<_>>();
+        format!("<Frame {}: {}>", self.index, ids.join(" / "))
+    }
+}
@@ -0,0 +1,42 @@
+//! Python bindings of genet.
+//!
+//! # Examples
+//! ```python
+//! import genet
+//!
+//! profile = genet.Profile()
+//! profile.load_library("libpcap_file.so")
+//! profile.load_library("libeth.so")
+//! session = genet.Session(profile)
+//! session.open("capture.pcap")
+//! session.wait()
+//! for frame in session.frames("tcp.dst == 80"):
+//!     print(frame.index, frame["ipv4.src"])
+//! print(session.value_counts("ipv4.dst", top=10))
+//! ```
+
+// The code generated by the pyo3 macros refers to `::core`.
+extern crate core;
+extern crate genet_abi;
+extern crate genet_filter;
+extern crate genet_kernel;
+extern crate pyo3;
+extern crate serde;
+extern crate serde_json;
+
+mod frame;
+mod profile;
+mod session;
+
+use pyo3::prelude::*;
+
+#[pymodule]
+fn genet(m: &Bound<PyModule>) -> PyResult<()> {
+    m.add_class::<profile::Profile>()?;
+    m.add_class::<session::Session>()?;
+    m.add_class::<session::FrameIter>()?;
+    m.add_class::<frame::Frame>()?;
+    m.add_class::<frame::Layer>()?;
+    m.add_class::<frame::Attr>()?;
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use genet_kernel::{profile, signature::Verification};
+use pyo3::{exceptions::PyIOError, prelude::*};
+
+/// The decoders, readers and config values of sessions.
+#[pyclass(module = "genet", unsendable)]
+pub struct Profile {
+    pub(crate) profile: profile::Profile,
+}
+
+impl Profile {
+    pub fn from_profile(profile: profile::Profile) -> Profile {
+        Profile { profile }
+    }
+}
+
+#[pymethods]
+impl Profile {
+    #[new]
+    fn new() -> Profile {
+        Profile::from_profile(profile::Profile::new())
+    }
+
+    /// Loads a plugin library, and returns the reason if its signature is
+    /// not trusted.
+    fn load_library(&mut self, path: &str) -> PyResult<Option<String>> {
+        match self.profile.load_library(path) {
+            Ok(Verification::Untrusted(reason)) => Ok(Some(reason)),
+            Ok(_) => Ok(None),
+            Err(err) => Err(PyIOError::new_err(format!("{}: {}", path, err))),
+        }
+    }
+
+    /// Sets the config value `key` to `value` in JSON, e.g. `"true"`.
+    fn set_config(&mut self, key: &str, value: &str) {
+        self.profile.set_config(key, value);
+    }
+
+    fn get_config(&self, key: &str) -> Option<String> {
+        self.profile.get_config(key)
+    }
+
+    #[getter]
+    fn concurrency(&self) -> u32 {
+        self.profile.concurrency()
+    }
+
+    #[setter]
+    fn set_concurrency(&mut self, concurrency: u32) {
+        self.profile.set_concurrency(concurrency);
+    }
+
+    /// The IDs of the decoders.
+    #[getter]
+    fn decoders(&self) -> Vec<String> {
+        self.profile
+            .decoders()
+            .map(|decoder| decoder.metadata().id)
+            .collect()
+    }
+
+    /// The IDs of the readers.
+    #[getter]
+    fn readers(&self) -> Vec<String> {
+        self.profile
+            .readers()
+            .map(|reader| reader.metadata().id)
+            .collect()
+    }
+}
@@ -0,0 +1,370 @@
+use frame::Frame;
+use genet_filter::Filter;
+use genet_kernel::session::{self, Callback, Event};
+use profile::Profile;
+use pyo3::{
+    exceptions::{PyIOError, PyIndexError, PyRuntimeError, PyValueError},
+    prelude::*,
+};
+use serde::Serialize;
+use serde_json;
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
+};
+
+/// The number of frames converted at once by a frame iterator.
+const BATCH_SIZE: usize = 256;
+
+const POLL_INTERVAL_MS: u64 = 50;
+
+#[derive(Clone)]
+struct SessionCallback {
+    sender: Sender<Event>,
+}
+
+impl Callback for SessionCallback {
+    fn on_event(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+}
+
+fn compile(filter: Option<&str>) -> PyResult<Option<Filter>> {
+    match filter {
+        Some(filter) if !filter.is_empty() => Filter::compile(filter)
+            .map(Some)
+            .map_err(|err| PyValueError::new_err(err.to_string())),
+        _ => Ok(None),
+    }
+}
+
+/// Converts a serializable value to Python objects through JSON.
+fn to_python<T: Serialize>(py: Python, value: &T) -> PyResult<Py<PyAny>> {
+    let json =
+        serde_json::to_string(value).map_err(|err| PyValueError::new_err(err.to_string()))?;
+    Ok(py.import("json")?.call_method1("loads", (json,))?.unbind())
+}
+
+/// A capture session.
+///
+/// Frames are read and decoded in the background. `wait` blocks until the
+/// opened captures are read and decoded.
+#[pyclass(module = "genet", unsendable)]
+pub struct Session {
+    session: session::Session,
+    events: Receiver<Event>,
+    inputs: HashSet<u32>,
+    errors: Vec<String>,
+}
+
+impl Session {
+    fn handle(&mut self, event: Event) -> PyResult<()> {
+        match event {
+            Event::Input(id, err) => {
+                self.inputs.remove(&id);
+                // Readers of files report the end of the file as an error.
+                if let Some(err) = err {
+                    self.errors.push(err.to_string());
+                }
+                Ok(())
+            }
+            Event::Error(err) => Err(PyRuntimeError::new_err(err.to_string())),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[pymethods]
+impl Session {
+    #[new]
+    #[pyo3(signature = (profile=None))]
+    fn new(profile: Option<PyRef<Profile>>) -> Session {
+        let profile = profile.map_or_else(genet_kernel::profile::Profile::new, |profile| {
+            profile.profile.clone()
+        });
+        let (sender, events) = mpsc::channel();
+        Session {
+            session: session::Session::new(profile, SessionCallback { sender }),
+            events,
+            inputs: HashSet::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Opens a capture file with the reader `reader`, or with a reader
+    /// which supports the file extension.
+    #[pyo3(signature = (path, reader=None))]
+    fn open(&mut self, path: &str, reader: Option<&str>) -> PyResult<u32> {
+        let reader = match reader {
+            Some(reader) => reader.to_string(),
+            None => self
+                .session
+                .profile()
+                .reader_for_file(path)
+                .ok_or_else(|| PyIOError::new_err(format!("{}: no reader found", path)))?,
+        };
+        let arg = serde_json::json!({ "file": path }).to_string();
+        self.create_reader(&reader, &arg)
+    }
+
+    /// Creates an input with the reader `id` and its argument in JSON.
+    fn create_reader(&mut self, id: &str, arg: &str) -> PyResult<u32> {
+        let handle = self.session.create_reader(id, arg);
+        if handle == 0 {
+            while let Ok(event) = self.events.try_recv() {
+                if let Event::Error(err) = event {
+                    return Err(PyIOError::new_err(err.to_string()));
+                }
+            }
+            return Err(PyIOError::new_err(format!("{}: no such reader", id)));
+        }
+        self.inputs.insert(handle);
+        Ok(handle)
+    }
+
+    /// Blocks until the opened inputs are read and their frames are
+    /// decoded. Returns False if `timeout` seconds have passed.
+    #[pyo3(signature = (timeout=None))]
+    fn wait(&mut self, py: Python, timeout: Option<f64>) -> PyResult<bool> {
+        let deadline = timeout.map(|secs| Instant::now() + Duration::from_secs_f64(secs));
+        loop {
+            while let Ok(event) = self.events.try_recv() {
+                self.handle(event)?;
+            }
+            if self.inputs.is_empty() && self.session.pending_frames() == 0 {
+                return Ok(true);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(false);
+            }
+            py.check_signals()?;
+            match self
+                .events
+                .recv_timeout(Duration::from_millis(POLL_INTERVAL_MS))
+            {
+                Ok(event) => self.handle(event)?,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(true),
+            }
+        }
+    }
+
+    /// The errors reported by the inputs, including the end of files.
+    #[getter]
+    fn input_errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+
+    fn __len__(&self) -> usize {
+        self.session.len()
+    }
+
+    /// Returns the frame at `index`.
+    fn frame(&self, py: Python, index: usize) -> PyResult<Frame> {
+        let mut result = None;
+        self.session.scan_from(index, None, |frame| {
+            result = Some(Frame::new(py, frame.index(), frame.layers()));
+            false
+        });
+        result.unwrap_or_else(|| Err(PyIndexError::new_err(index)))
+    }
+
+    /// Returns an iterator over the frames matching the display filter
+    /// `filter`.
+    #[pyo3(signature = (filter=None))]
+    fn frames(slf: Bound<Self>, filter: Option<&str>) -> PyResult<FrameIter> {
+        Ok(FrameIter {
+            session: slf.unbind(),
+            filter: compile(filter)?,
+            next: 0,
+            buffer: VecDeque::new(),
+        })
+    }
+
+    /// Returns the indices of the frames matching `filter`.
+    fn filter(&self, filter: &str) -> PyResult<Vec<u32>> {
+        let filter = compile(Some(filter))?;
+        let mut indices = Vec::new();
+        self.session.scan_from(0, filter.as_ref(), |frame| {
+            indices.push(frame.index());
+            true
+        });
+        Ok(indices)
+    }
+
+    /// Sets the config value `key` to `value` in JSON and decodes the
+    /// frames again.
+    fn set_config(&mut self, key: &str, value: &str) -> PyResult<()> {
+        self.session
+            .set_config(key, value)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Returns the `top` most frequent values of the attribute `id`, or all
+    /// values if `top` is zero.
+    #[pyo3(signature = (id, filter=None, top=0))]
+    fn value_counts(
+        &self,
+        py: Python,
+        id: &str,
+        filter: Option<&str>,
+        top: usize,
+    ) -> PyResult<Py<PyAny>> {
+        let filter = compile(filter)?;
+        to_python(py, &self.session.value_counts(id, filter.as_ref(), top))
+    }
+
+    /// Returns the conversations of `level`, e.g. `tcp`.
+    fn conversations(&self, py: Python, level: &str) -> PyResult<Py<PyAny>> {
+        match self.session.conversations(level) {
+            Some(stats) => to_python(py, &stats),
+            None => Err(PyValueError::new_err(format!("unknown level: {}", level))),
+        }
+    }
+
+    fn expert_summary(&self, py: Python) -> PyResult<Py<PyAny>> {
+        to_python(py, &self.session.expert_summary())
+    }
+
+    /// Returns the undecoded payloads grouped by the last decoded layer.
+    #[pyo3(signature = (filter=None))]
+    fn coverage(&self, py: Python, filter: Option<&str>) -> PyResult<Py<PyAny>> {
+        let filter = compile(filter)?;
+        to_python(py, &self.session.coverage(filter.as_ref()))
+    }
+
+    fn attribute_catalog(&self, py: Python) -> PyResult<Py<PyAny>> {
+        to_python(py, &self.session.attribute_catalog())
+    }
+
+    fn pipeline_stats(&self, py: Python) -> PyResult<Py<PyAny>> {
+        to_python(py, &self.session.pipeline_stats())
+    }
+}
+
+/// An iterator over the frames of a session.
+#[pyclass(module = "genet", unsendable)]
+pub struct FrameIter {
+    session: Py<Session>,
+    filter: Option<Filter>,
+    next: usize,
+    buffer: VecDeque<PyResult<Frame>>,
+}
+
+#[pymethods]
+impl FrameIter {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<Frame>> {
+        if self.buffer.is_empty() {
+            let session = self.session.borrow(py);
+            let buffer = &mut self.buffer;
+            self.next = session
+                .session
+                .scan_from(self.next, self.filter.as_ref(), |frame| {
+                    buffer.push_back(Frame::new(py, frame.index(), frame.layers()));
+                    buffer.len() < BATCH_SIZE
+                });
+        }
+        self.buffer.pop_front().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        context::Context,
+        error::Error,
+        fixed::Fixed,
+        layer::{Layer, LayerClass},
+        reader::{Metadata, Reader, ReaderBox, Worker},
+        result::Result,
+        slice::ByteSlice,
+    };
+    use genet_kernel::profile;
+    use profile::Profile;
+    use pyo3::{prelude::*, types::PyDict};
+    use session::Session;
+    use std::ffi::CString;
+
+    struct TestWorker {
+        count: u64,
+    }
+
+    impl Worker for TestWorker {
+        fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
+            if self.count == 0 {
+                return Err(Box::new(Error::new("end of stream")));
+            }
+            let layers = (0..self.count)
+                .map(|i| {
+                    let class = Fixed::new(LayerClass::builder("eth").build());
+                    let mut layer = Layer::new(class, ByteSlice::new());
+                    let class = Fixed::new(AttrClass::builder("eth.len").build());
+                    layer.add_attr(Attr::builder(class).value(i).build());
+                    layer
+                })
+                .collect();
+            self.count = 0;
+            Ok(layers)
+        }
+    }
+
+    struct TestReader {}
+
+    impl Reader for TestReader {
+        fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+            Ok(Box::new(TestWorker {
+                count: arg.parse().unwrap(),
+            }))
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                id: "test".to_string(),
+                ..Metadata::default()
+            }
+        }
+    }
+
+    #[test]
+    fn frames() {
+        Python::initialize();
+        Python::attach(|py| {
+            let mut profile = profile::Profile::new();
+            profile.add_reader(ReaderBox::new(TestReader {}));
+            let profile = Py::new(py, Profile::from_profile(profile)).unwrap();
+            let session = Py::new(py, Session::new(Some(profile.borrow(py)))).unwrap();
+            let locals = PyDict::new(py);
+            locals.set_item("session", session).unwrap();
+            let script = CString::new(
+                "
+session.create_reader('test', '5')
+assert session.wait(10)
+assert len(session) == 5
+assert session.input_errors == ['end of stream']
+assert session.frame(3)['eth.len'] == 3
+assert session.filter('eth.len >= 2') == [2, 3, 4]
+assert [f.index for f in session.frames('eth.len < 2')] == [0, 1]
+assert session.value_counts('eth.len', top=1)[0]['count'] == 1
+try:
+    session.frame(5)
+    assert False
+except IndexError:
+    pass
+try:
+    session.filter('eth.len >')
+    assert False
+except ValueError:
+    pass
+",
+            )
+            .unwrap();
+            py.run(&script, None, Some(&locals)).unwrap();
+        });
+    }
+}
@@ -0,0 +1,316 @@
+//! Checksum validation.
+//!
+//! Decoders verify the checksums of their headers with the functions of
+//! this module, and add the result as a `<id>.checksum.status` attribute,
+//! which is one of `good`, `bad`, `offloaded` and `unverified`.
+//!
+//! Frames captured on the sending host often carry zero or wrong checksums,
+//! because the checksums are computed later by the network card. Such
+//! checksums are reported as `offloaded` according to the option
+//! `@genet/checksum.offload`:
+//!
+//! - `never`: every wrong checksum is `bad`.
+//! - `outbound`: the frames marked as outbound by the reader.
+//! - `always`: every frame, e.g. for a capture taken on the sending host
+//!   by a reader without the direction of the frames.
+
+use context::Context;
+use decoder::DecoderOption;
+use layer::{Direction, FrameMetadata};
+use lazy_static::lazy_static;
+use std::fmt;
+use token::Token;
+
+/// The config key of the offload option.
+pub const OFFLOAD_KEY: &str = "@genet/checksum.offload";
+
+const OFFLOAD_VALUES: &[&str] = &["never", "outbound", "always"];
+
+/// The result of a checksum validation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Good,
+    Bad,
+    /// The checksum is wrong, but it is left to the network card.
+    Offloaded,
+    /// The checksum is absent, or the data is truncated.
+    Unverified,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Status::Good => "good",
+            Status::Bad => "bad",
+            Status::Offloaded => "offloaded",
+            Status::Unverified => "unverified",
+        })
+    }
+}
+
+impl Status {
+    /// Returns the status as a boxed string, the value of the status
+    /// attributes.
+    pub fn to_value(self) -> Box<str> {
+        self.to_string().into_boxed_str()
+    }
+}
+
+/// Which frames may have offloaded checksums.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Offload {
+    Never,
+    Outbound,
+    Always,
+}
+
+/// Determines the status of checksums according to the offload option.
+#[derive(Clone, Copy, Debug)]
+pub struct Validator {
+    offload: Offload,
+}
+
+impl Validator {
+    pub fn new(offload: Offload) -> Validator {
+        Validator { offload }
+    }
+
+    /// Creates a Validator with the offload option of the session.
+    pub fn from_config(ctx: &Context) -> Validator {
+        let offload = match ctx.get_config(OFFLOAD_KEY).trim_matches('"') {
+            "never" => Offload::Never,
+            "always" => Offload::Always,
+            _ => Offload::Outbound,
+        };
+        Self::new(offload)
+    }
+
+    /// Returns the offload option, to be declared in the metadata of the
+    /// decoders using the Validator.
+    pub fn option() -> DecoderOption {
+        DecoderOption::enumeration(OFFLOAD_KEY, OFFLOAD_VALUES, "outbound").description(
+            "Frames whose wrong checksums are reported as offloaded to the network card",
+        )
+    }
+
+    /// Returns the status of a checksum which is `valid` or not in the
+    /// frame `frame`.
+    pub fn status(&self, valid: bool, frame: FrameMetadata) -> Status {
+        if valid {
+            return Status::Good;
+        }
+        let offloaded = match self.offload {
+            Offload::Never => false,
+            Offload::Outbound => frame.direction() == Direction::Outbound,
+            Offload::Always => true,
+        };
+        if offloaded {
+            Status::Offloaded
+        } else {
+            Status::Bad
+        }
+    }
+}
+
+/// The one's complement sum of the Internet checksum.
+///
+/// Every slice but the last one must have an even length.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InternetSum(u32);
+
+impl InternetSum {
+    pub fn new() -> InternetSum {
+        Self::default()
+    }
+
+    pub fn add(&mut self, data: &[u8]) -> &mut Self {
+        for chunk in data.chunks(2) {
+            let word = u32::from(chunk[0]) << 8 | u32::from(*chunk.get(1).unwrap_or(&0));
+            self.0 += word;
+            if self.0 > 0xffff {
+                self.0 = (self.0 & 0xffff) + (self.0 >> 16);
+            }
+        }
+        self
+    }
+
+    /// Returns the checksum, which is zero if the data includes a valid
+    /// checksum.
+    pub fn checksum(&self) -> u16 {
+        !(self.0 as u16)
+    }
+}
+
+/// Returns the Internet checksum of `data`, used by IPv4, ICMP, UDP and
+/// TCP.
+pub fn internet(data: &[u8]) -> u16 {
+    InternetSum::new().add(data).checksum()
+}
+
+/// Returns the pseudo-header of the upper-layer checksums of `len` bytes
+/// of the protocol `proto`, where `id` and `ip` are the ID and the data of
+/// the IPv4 or IPv6 layer.
+pub fn pseudo_header(id: Token, ip: &[u8], proto: u8, len: usize) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(40);
+    if id == Token::from("ipv4") && ip.len() >= 20 {
+        data.extend_from_slice(&ip[12..20]);
+        data.extend_from_slice(&[0, proto, (len >> 8) as u8, len as u8]);
+    } else if id == Token::from("ipv6") && ip.len() >= 40 {
+        data.extend_from_slice(&ip[8..40]);
+        let len = len as u32;
+        data.extend_from_slice(&[
+            (len >> 24) as u8,
+            (len >> 16) as u8,
+            (len >> 8) as u8,
+            len as u8,
+            0,
+            0,
+            0,
+            proto,
+        ]);
+    } else {
+        return None;
+    }
+    Some(data)
+}
+
+/// Returns true if the IP layer `id` of `ip` holds the whole datagram,
+/// which is not a fragment nor truncated by the snapshot length or the
+/// segmentation offload.
+fn is_complete(id: Token, ip: &[u8]) -> bool {
+    let uint16 = |offset: usize| usize::from(ip[offset]) << 8 | usize::from(ip[offset + 1]);
+    if id == Token::from("ipv4") && ip.len() >= 20 {
+        let len = uint16(2);
+        len != 0 && len <= ip.len() && uint16(6) & 0x3fff == 0
+    } else if id == Token::from("ipv6") && ip.len() >= 40 {
+        let len = uint16(4);
+        len != 0 && 40 + len <= ip.len()
+    } else {
+        false
+    }
+}
+
+/// Returns true if the upper-layer `data` including its checksum is valid
+/// with the pseudo-header of the IP layer `id` of `ip`, or None if it
+/// cannot be verified.
+pub fn verify_upper(id: Token, ip: &[u8], proto: u8, data: &[u8]) -> Option<bool> {
+    if !is_complete(id, ip) {
+        return None;
+    }
+    let pseudo = pseudo_header(id, ip, proto, data.len())?;
+    Some(InternetSum::new().add(&pseudo).add(data).checksum() == 0)
+}
+
+fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut crc = i as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+        }
+        *entry = crc;
+    }
+    table
+}
+
+lazy_static! {
+    static ref CRC32C_TABLE: [u32; 256] = crc32c_table();
+}
+
+/// Returns the CRC32c of `data`.
+pub fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, b| {
+        CRC32C_TABLE[((crc ^ u32::from(*b)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Returns true if the SCTP packet `data` has a valid checksum, or None if
+/// it is shorter than the common header.
+pub fn verify_sctp(data: &[u8]) -> Option<bool> {
+    if data.len() < 12 {
+        return None;
+    }
+    let stored = u32::from(data[8])
+        | u32::from(data[9]) << 8
+        | u32::from(data[10]) << 16
+        | u32::from(data[11]) << 24;
+    let mut copy = data.to_vec();
+    copy[8..12].copy_from_slice(&[0; 4]);
+    Some(crc32c(&copy) == stored)
+}
+
+#[cfg(test)]
+mod tests {
+    use checksum::{self, Offload, Status, Validator};
+    use layer::{Direction, FrameMetadata};
+    use token::Token;
+
+    #[test]
+    fn internet() {
+        let header =
+            b"\x45\x00\x00\x73\x00\x00\x40\x00\x40\x11\xb8\x61\xc0\xa8\x00\x01\xc0\xa8\x00\xc7";
+        assert_eq!(checksum::internet(header), 0);
+        let mut header = header.to_vec();
+        header[10..12].copy_from_slice(&[0, 0]);
+        assert_eq!(checksum::internet(&header), 0xb861);
+
+        let udp = b"\x04\xd2\x00\x35\x00\x0b\x00\x00abc";
+        header[2..4].copy_from_slice(&[0, 31]);
+        let pseudo = checksum::pseudo_header(Token::from("ipv4"), &header, 17, udp.len()).unwrap();
+        let sum = checksum::InternetSum::new()
+            .add(&pseudo)
+            .add(udp)
+            .checksum();
+        let mut ip = header.clone();
+        ip.extend_from_slice(udp);
+        ip[26] = (sum >> 8) as u8;
+        ip[27] = sum as u8;
+        let verify = |id: &str, ip: &[u8], proto: u8| {
+            checksum::verify_upper(Token::from(id), ip, proto, &ip[20..])
+        };
+        assert_eq!(verify("ipv4", &ip, 17), Some(true));
+        assert_eq!(verify("ipv4", &ip, 6), Some(false));
+        assert_eq!(verify("eth", &ip, 17), None);
+
+        // Truncated datagrams and fragments are not verified.
+        assert_eq!(verify("ipv4", &ip[..30], 17), None);
+        ip[6] = 0x20;
+        assert_eq!(verify("ipv4", &ip, 17), None);
+    }
+
+    #[test]
+    fn crc32c() {
+        assert_eq!(checksum::crc32c(b"123456789"), 0xe306_9283);
+        let mut sctp = b"\x13\x88\x13\x88\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        let crc = checksum::crc32c(&sctp);
+        sctp[8..12].copy_from_slice(&[
+            crc as u8,
+            (crc >> 8) as u8,
+            (crc >> 16) as u8,
+            (crc >> 24) as u8,
+        ]);
+        assert_eq!(checksum::verify_sctp(&sctp), Some(true));
+        sctp[0] = 0;
+        assert_eq!(checksum::verify_sctp(&sctp), Some(false));
+        assert_eq!(checksum::verify_sctp(&sctp[..8]), None);
+    }
+
+    #[test]
+    fn status() {
+        let inbound = FrameMetadata::new(1).with_direction(Direction::Inbound);
+        let outbound = FrameMetadata::new(1).with_direction(Direction::Outbound);
+        let validator = Validator::new(Offload::Outbound);
+        assert_eq!(validator.status(true, outbound), Status::Good);
+        assert_eq!(validator.status(false, inbound), Status::Bad);
+        assert_eq!(validator.status(false, outbound), Status::Offloaded);
+        let validator = Validator::new(Offload::Always);
+        assert_eq!(validator.status(false, inbound), Status::Offloaded);
+        let validator = Validator::new(Offload::Never);
+        assert_eq!(validator.status(false, outbound), Status::Bad);
+        assert_eq!(Status::Unverified.to_string(), "unverified");
+    }
+}
@@ -1,19 +1,26 @@
 //! Decoder traits.
 
-pub use genet_abi::decoder::{Decoder, ExecType, Metadata, Status, Worker};
+pub use genet_abi::decoder::{
+    Decoder, DecoderOption, ExecType, Metadata, OptionType, Status, Worker,
+};
 
 #[doc(hidden)]
 pub use genet_abi::decoder::DecoderBox;
 
 /// Registers decoder entries.
+///
+/// Each entry may be preceded by a single attribute, e.g. `#[cfg(feature =
+/// "x")]` to register a decoder only when a feature of the package is
+/// enabled. Either all entries or none of them have an attribute.
 #[macro_export]
 macro_rules! genet_decoders {
-    ( $( $x:expr ), * ) => {
+    ( $( #[$m:meta] $x:expr ), * ) => {
         thread_local! {
             static DISSECTORS: Vec<genet_sdk::decoder::DecoderBox> = {
                 use genet_sdk::decoder::DecoderBox;
                 let mut v = Vec::new();
                 $(
+                    #[$m]
                     v.push(DecoderBox::new($x));
                 )*
                 v
@@ -30,4 +37,7 @@ macro_rules! genet_decoders {
             })
         }
     };
+    ( $( $x:expr ), * ) => {
+        $crate::genet_decoders!($( #[cfg(all())] $x ), *);
+    };
 }
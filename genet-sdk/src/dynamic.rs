@@ -0,0 +1,318 @@
+//! Layers built at runtime without compile-time classes.
+//!
+//! Script decoders describe attributes by their ID, type, byte range and
+//! cast kind instead of `def_attr_class!` statics. The classes are created
+//! on first use and kept in a `Classes` registry, so that building a layer
+//! for each frame does not create new classes.
+//!
+//! # Examples
+//! ```
+//! # extern crate genet_sdk;
+//! # use genet_sdk::{dynamic::{Classes, LayerBuilder}, prelude::*};
+//! # fn main() {
+//! let mut classes = Classes::new();
+//! let data = ByteSlice::from(&b"\x00\x35\x00\x08"[..]);
+//! let mut builder = LayerBuilder::new(&mut classes, "udp", data);
+//! builder.add_attr("udp.dst", "", 0..2, "uint16be").unwrap();
+//! builder.add_attr("udp.length", "", 2..4, "uint16be").unwrap();
+//! let layer = builder.build();
+//! assert_eq!(layer.attrs().len(), 2);
+//! # }
+//! ```
+
+use attr::{Attr, AttrClass};
+use cast;
+use error::Error;
+use fixed::Fixed;
+use layer::{Layer, LayerClass, Payload};
+use result::Result;
+use slice::ByteSlice;
+use std::{collections::HashMap, fmt, ops::Range, str::FromStr};
+use token::Token;
+use variant::Variant;
+
+/// The cast of an attribute, named as in `FromStr`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CastKind {
+    /// No value, e.g. a node grouping other attributes.
+    None,
+    UInt8,
+    Int8,
+    UInt16BE,
+    UInt32BE,
+    UInt64BE,
+    Int16BE,
+    Int32BE,
+    Int64BE,
+    Float32BE,
+    Float64BE,
+    UInt16LE,
+    UInt32LE,
+    UInt64LE,
+    Int16LE,
+    Int32LE,
+    Int64LE,
+    Float32LE,
+    Float64LE,
+    Utf8,
+    Bytes,
+}
+
+const CAST_NAMES: &[(&str, CastKind)] = &[
+    ("none", CastKind::None),
+    ("uint8", CastKind::UInt8),
+    ("int8", CastKind::Int8),
+    ("uint16be", CastKind::UInt16BE),
+    ("uint32be", CastKind::UInt32BE),
+    ("uint64be", CastKind::UInt64BE),
+    ("int16be", CastKind::Int16BE),
+    ("int32be", CastKind::Int32BE),
+    ("int64be", CastKind::Int64BE),
+    ("float32be", CastKind::Float32BE),
+    ("float64be", CastKind::Float64BE),
+    ("uint16le", CastKind::UInt16LE),
+    ("uint32le", CastKind::UInt32LE),
+    ("uint64le", CastKind::UInt64LE),
+    ("int16le", CastKind::Int16LE),
+    ("int32le", CastKind::Int32LE),
+    ("int64le", CastKind::Int64LE),
+    ("float32le", CastKind::Float32LE),
+    ("float64le", CastKind::Float64LE),
+    ("utf8", CastKind::Utf8),
+    ("bytes", CastKind::Bytes),
+];
+
+impl FromStr for CastKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        CAST_NAMES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, kind)| *kind)
+            .ok_or(())
+    }
+}
+
+impl fmt::Display for CastKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = CAST_NAMES
+            .iter()
+            .find(|(_, kind)| kind == self)
+            .map(|(name, _)| *name)
+            .unwrap_or_default();
+        f.pad(name)
+    }
+}
+
+impl CastKind {
+    /// Returns the number of bytes read by the cast, or None if it reads
+    /// the whole range.
+    fn size(self) -> Option<usize> {
+        match self {
+            CastKind::UInt8 | CastKind::Int8 => Some(1),
+            CastKind::UInt16BE | CastKind::Int16BE | CastKind::UInt16LE | CastKind::Int16LE => {
+                Some(2)
+            }
+            CastKind::UInt32BE
+            | CastKind::Int32BE
+            | CastKind::Float32BE
+            | CastKind::UInt32LE
+            | CastKind::Int32LE
+            | CastKind::Float32LE => Some(4),
+            CastKind::UInt64BE
+            | CastKind::Int64BE
+            | CastKind::Float64BE
+            | CastKind::UInt64LE
+            | CastKind::Int64LE
+            | CastKind::Float64LE => Some(8),
+            CastKind::None | CastKind::Utf8 | CastKind::Bytes => None,
+        }
+    }
+
+    fn class(self, id: &str, typ: &str) -> AttrClass {
+        let builder = AttrClass::builder(id).typ(typ);
+        let builder = match self {
+            CastKind::None => builder,
+            CastKind::UInt8 => builder.cast(cast::UInt8()),
+            CastKind::Int8 => builder.cast(cast::Int8()),
+            CastKind::UInt16BE => builder.cast(cast::UInt16BE()),
+            CastKind::UInt32BE => builder.cast(cast::UInt32BE()),
+            CastKind::UInt64BE => builder.cast(cast::UInt64BE()),
+            CastKind::Int16BE => builder.cast(cast::Int16BE()),
+            CastKind::Int32BE => builder.cast(cast::Int32BE()),
+            CastKind::Int64BE => builder.cast(cast::Int64BE()),
+            CastKind::Float32BE => builder.cast(cast::Float32BE()),
+            CastKind::Float64BE => builder.cast(cast::Float64BE()),
+            CastKind::UInt16LE => builder.cast(cast::UInt16LE()),
+            CastKind::UInt32LE => builder.cast(cast::UInt32LE()),
+            CastKind::UInt64LE => builder.cast(cast::UInt64LE()),
+            CastKind::Int16LE => builder.cast(cast::Int16LE()),
+            CastKind::Int32LE => builder.cast(cast::Int32LE()),
+            CastKind::Int64LE => builder.cast(cast::Int64LE()),
+            CastKind::Float32LE => builder.cast(cast::Float32LE()),
+            CastKind::Float64LE => builder.cast(cast::Float64LE()),
+            CastKind::Utf8 => builder.cast(cast::Utf8()),
+            CastKind::Bytes => builder.cast(cast::ByteSlice()),
+        };
+        builder.build()
+    }
+}
+
+/// A registry of the classes created at runtime.
+#[derive(Default)]
+pub struct Classes {
+    layers: HashMap<String, Fixed<LayerClass>>,
+    attrs: HashMap<(String, String, CastKind), Fixed<AttrClass>>,
+}
+
+impl Classes {
+    pub fn new() -> Classes {
+        Self::default()
+    }
+
+    /// Returns the layer class `id`.
+    pub fn layer(&mut self, id: &str) -> Fixed<LayerClass> {
+        if let Some(class) = self.layers.get(id) {
+            return class.clone();
+        }
+        let class = Fixed::new(LayerClass::builder(id).build());
+        self.layers.insert(id.to_string(), class.clone());
+        class
+    }
+
+    /// Returns the attribute class `id` of the type `typ` read by `cast`.
+    pub fn attr(&mut self, id: &str, typ: &str, cast: CastKind) -> Fixed<AttrClass> {
+        let key = (id.to_string(), typ.to_string(), cast);
+        self.attrs
+            .entry(key)
+            .or_insert_with(|| Fixed::new(cast.class(id, typ)))
+            .clone()
+    }
+
+    /// Adds the attribute `id` to `layer`, as `LayerBuilder::add_attr` does.
+    pub fn add_attr(
+        &mut self,
+        layer: &mut Layer,
+        id: &str,
+        typ: &str,
+        range: Range<usize>,
+        cast: &str,
+    ) -> Result<()> {
+        let kind = cast
+            .parse::<CastKind>()
+            .map_err(|_| Box::new(Error::new(&format!("unknown cast: {}", cast))))?;
+        if range.start > range.end || range.end > layer.data().len() {
+            return Err(Box::new(Error::new(&format!("{}: out of range", id))));
+        }
+        match kind.size() {
+            Some(size) if size != range.len() => {
+                let msg = format!("{}: {} needs {} bytes", id, kind, size);
+                return Err(Box::new(Error::new(&msg)));
+            }
+            _ => {}
+        }
+        let class = self.attr(id, typ, kind);
+        layer.add_attr(Attr::builder(class).range(range).build());
+        Ok(())
+    }
+
+    /// Adds the attribute `id` with `value` to `layer`.
+    pub fn add_value<T: Into<Variant>>(
+        &mut self,
+        layer: &mut Layer,
+        id: &str,
+        typ: &str,
+        range: Range<usize>,
+        value: T,
+    ) {
+        let class = self.attr(id, typ, CastKind::None);
+        layer.add_attr(Attr::builder(class).range(range).value(value).build());
+    }
+}
+
+/// A builder object for a layer with classes created at runtime.
+pub struct LayerBuilder<'a> {
+    classes: &'a mut Classes,
+    layer: Layer,
+}
+
+impl<'a> LayerBuilder<'a> {
+    /// Creates a new LayerBuilder for the layer `id` of `data`.
+    pub fn new<B: Into<ByteSlice>>(classes: &'a mut Classes, id: &str, data: B) -> Self {
+        let class = classes.layer(id);
+        LayerBuilder {
+            classes,
+            layer: Layer::new(class, data),
+        }
+    }
+
+    /// Adds the attribute `id` read from `range` of the layer data by the
+    /// cast named `cast`, e.g. `uint16be`.
+    ///
+    /// Returns an error if the cast is unknown or the range does not fit
+    /// the data or the cast.
+    pub fn add_attr(&mut self, id: &str, typ: &str, range: Range<usize>, cast: &str) -> Result<()> {
+        self.classes.add_attr(&mut self.layer, id, typ, range, cast)
+    }
+
+    /// Adds the attribute `id` with a value computed by the script.
+    pub fn add_value<T: Into<Variant>>(
+        &mut self,
+        id: &str,
+        typ: &str,
+        range: Range<usize>,
+        value: T,
+    ) {
+        self.classes
+            .add_value(&mut self.layer, id, typ, range, value);
+    }
+
+    /// Adds a payload of the type `typ`.
+    pub fn add_payload<B: Into<ByteSlice>, T: Into<Token>>(&mut self, data: B, id: T, typ: &str) {
+        self.layer.add_payload(Payload::with_typ(data, id, typ));
+    }
+
+    pub fn build(self) -> Layer {
+        self.layer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dynamic::{CastKind, Classes, LayerBuilder};
+    use slice::ByteSlice;
+    use variant::Variant;
+
+    #[test]
+    fn build() {
+        let mut classes = Classes::new();
+        let data = ByteSlice::from(&b"\x01\x02\x03\x04hi"[..]);
+        let layer = {
+            let mut builder = LayerBuilder::new(&mut classes, "test", data);
+            builder.add_attr("test.a", "", 0..2, "uint16le").unwrap();
+            builder
+                .add_attr("test.b", "@flags", 2..4, "uint16be")
+                .unwrap();
+            builder.add_attr("test.name", "", 4..6, "utf8").unwrap();
+            builder.add_value("test.sum", "", 0..4, 10u64);
+            assert!(builder.add_attr("test.c", "", 0..1, "uint16be").is_err());
+            assert!(builder.add_attr("test.c", "", 4..8, "bytes").is_err());
+            assert!(builder.add_attr("test.c", "", 0..1, "uint128").is_err());
+            builder.build()
+        };
+
+        let value = |id: &str| layer.attr(id).unwrap().try_get(&layer).unwrap();
+        assert_eq!(value("test.a"), Variant::UInt64(0x0201));
+        assert_eq!(value("test.b"), Variant::UInt64(0x0304));
+        assert_eq!(value("test.name"), Variant::String("hi".into()));
+        assert_eq!(value("test.sum"), Variant::UInt64(10));
+        assert_eq!(layer.attrs().len(), 4);
+
+        // Classes are reused across layers.
+        classes.attr("test.a", "", CastKind::UInt16LE);
+        assert_eq!(classes.attrs.len(), 4);
+        assert_eq!("float64le".parse::<CastKind>(), Ok(CastKind::Float64LE));
+        assert_eq!(CastKind::Bytes.to_string(), "bytes");
+    }
+}
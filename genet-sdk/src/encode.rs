@@ -0,0 +1,317 @@
+//! Encoding of crafted layers to bytes.
+//!
+//! A layer to be crafted is declared by `def_encoder!` as a struct of
+//! big-endian fields. The fields marked with `#[encode(...)]` are filled
+//! when the layer is encoded, unless they are set to a non-zero value:
+//!
+//! - `length`: the length of the layer including the payload.
+//! - `payload_length`: the length of the payload.
+//! - `checksum`: the Internet checksum of the header.
+//! - `payload_checksum`: the Internet checksum of the pseudo-header of the
+//!   outer layer, the header and the payload.
+//!
+//! The pseudo-header is returned by `pseudo_header` of the outer layer,
+//! which can be defined in `def_encoder!` after the fields.
+//!
+//! # Examples
+//! ```
+//! #[macro_use]
+//! extern crate genet_sdk;
+//! # use genet_sdk::encode;
+//!
+//! def_encoder! {
+//!     pub struct Udp {
+//!         pub src: u16,
+//!         pub dst: u16,
+//!         #[encode(length)]
+//!         pub length: u16,
+//!         #[encode(payload_checksum)]
+//!         pub checksum: u16,
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let udp = Udp {
+//!     src: 5353,
+//!     dst: 53,
+//!     ..Udp::default()
+//! };
+//! let data = encode::encode(&[&udp], b"hi");
+//! assert_eq!(&data[4..6], &[0, 10]);
+//! # }
+//! ```
+
+use checksum;
+
+/// A fixup of a field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fixup {
+    Length,
+    PayloadLength,
+    Checksum,
+    PayloadChecksum,
+}
+
+/// A field of a crafted layer.
+pub trait Field {
+    /// Appends the bytes of the field to `buf`.
+    fn write(&self, buf: &mut Vec<u8>);
+
+    /// Returns true if the field is left to the fixups.
+    fn is_zero(&self) -> bool {
+        false
+    }
+}
+
+macro_rules! impl_uint_field {
+    ($($ty:ty),*) => {
+        $(
+            impl Field for $ty {
+                fn write(&self, buf: &mut Vec<u8>) {
+                    let size = ::std::mem::size_of::<$ty>();
+                    buf.extend((0..size).rev().map(|i| (u64::from(*self) >> (i * 8)) as u8));
+                }
+
+                fn is_zero(&self) -> bool {
+                    *self == 0
+                }
+            }
+        )*
+    };
+}
+
+impl_uint_field!(u8, u16, u32, u64);
+
+macro_rules! impl_array_field {
+    ($($len:expr),*) => {
+        $(
+            impl Field for [u8; $len] {
+                fn write(&self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(self);
+                }
+
+                fn is_zero(&self) -> bool {
+                    self.iter().all(|b| *b == 0)
+                }
+            }
+        )*
+    };
+}
+
+impl_array_field!(1, 2, 3, 4, 6, 8, 16);
+
+impl Field for Vec<u8> {
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+}
+
+/// A crafted layer.
+pub trait Encode {
+    /// Returns the bytes of the layer followed by `payload`, where `outer`
+    /// is the layer enclosing it.
+    fn encode(&self, payload: &[u8], outer: Option<&Encode>) -> Vec<u8>;
+
+    /// Returns the pseudo-header of the checksums of the payload of `len`
+    /// bytes, e.g. the addresses of an IP layer.
+    fn pseudo_header(&self, _len: usize) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// Encodes the layers from the outermost one with the innermost payload.
+pub fn encode(layers: &[&Encode], payload: &[u8]) -> Vec<u8> {
+    let mut data = payload.to_vec();
+    for (i, layer) in layers.iter().enumerate().rev() {
+        let outer = if i > 0 { Some(layers[i - 1]) } else { None };
+        data = layer.encode(&data, outer);
+    }
+    data
+}
+
+fn write_uint(buf: &mut [u8], value: u64) {
+    let len = buf.len();
+    for (i, b) in buf.iter_mut().enumerate() {
+        let shift = (len - i - 1) * 8;
+        *b = if shift < 64 {
+            (value >> shift) as u8
+        } else {
+            0
+        };
+    }
+}
+
+/// The header of a layer being encoded.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct Header {
+    data: Vec<u8>,
+    fixups: Vec<(usize, usize, Fixup)>,
+}
+
+impl Header {
+    pub fn new() -> Header {
+        Self::default()
+    }
+
+    pub fn push<F: Field>(&mut self, field: &F, fixups: &[Fixup]) {
+        let offset = self.data.len();
+        field.write(&mut self.data);
+        if field.is_zero() {
+            let size = self.data.len() - offset;
+            self.fixups
+                .extend(fixups.iter().map(|fixup| (offset, size, *fixup)));
+        }
+    }
+
+    pub fn finish(self, payload: &[u8], outer: Option<&Encode>) -> Vec<u8> {
+        let Header { mut data, fixups } = self;
+        let header_len = data.len();
+        let len = header_len + payload.len();
+        for (offset, size, fixup) in &fixups {
+            let range = *offset..*offset + *size;
+            match fixup {
+                Fixup::Length => write_uint(&mut data[range], len as u64),
+                Fixup::PayloadLength => write_uint(&mut data[range], payload.len() as u64),
+                _ => {}
+            }
+        }
+        data.extend_from_slice(payload);
+
+        // The checksums cover the lengths.
+        for (offset, size, fixup) in &fixups {
+            let range = *offset..*offset + *size;
+            let value = match fixup {
+                Fixup::Checksum => checksum::internet(&data[..header_len]),
+                Fixup::PayloadChecksum => {
+                    let mut covered = outer
+                        .and_then(|outer| outer.pseudo_header(len))
+                        .unwrap_or_default();
+                    covered.extend_from_slice(&data);
+                    checksum::internet(&covered)
+                }
+                _ => continue,
+            };
+            write_uint(&mut data[range], u64::from(value));
+        }
+        data
+    }
+}
+
+/// Defines a struct of a crafted layer implementing `Encode`.
+#[macro_export]
+macro_rules! def_encoder {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident {
+            $(
+                $(#[encode($fixup:ident)])*
+                pub $field:ident : $ty:ty
+            ),* $(,)*
+        }
+        $($method:item)*
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, Default, PartialEq)]
+        pub struct $name {
+            $(pub $field: $ty),*
+        }
+
+        impl $crate::encode::Encode for $name {
+            fn encode(
+                &self,
+                payload: &[u8],
+                outer: Option<&$crate::encode::Encode>,
+            ) -> Vec<u8> {
+                let mut header = $crate::encode::Header::new();
+                $(
+                    header.push(&self.$field, &[$(def_encoder!(@fixup $fixup)),*]);
+                )*
+                header.finish(payload, outer)
+            }
+
+            $($method)*
+        }
+    };
+    (@fixup length) => ($crate::encode::Fixup::Length);
+    (@fixup payload_length) => ($crate::encode::Fixup::PayloadLength);
+    (@fixup checksum) => ($crate::encode::Fixup::Checksum);
+    (@fixup payload_checksum) => ($crate::encode::Fixup::PayloadChecksum);
+}
+
+#[cfg(test)]
+mod tests {
+    use checksum;
+    use encode::{self, Encode};
+
+    def_encoder! {
+        pub struct Ipv4 {
+            pub version_ihl: u8,
+            pub tos: u8,
+            #[encode(length)]
+            pub total_length: u16,
+            pub id: u16,
+            pub flags_offset: u16,
+            pub ttl: u8,
+            pub protocol: u8,
+            #[encode(checksum)]
+            pub checksum: u16,
+            pub src: [u8; 4],
+            pub dst: [u8; 4],
+        }
+
+        fn pseudo_header(&self, len: usize) -> Option<Vec<u8>> {
+            let mut header = Vec::new();
+            header.extend_from_slice(&self.src);
+            header.extend_from_slice(&self.dst);
+            header.extend_from_slice(&[0, self.protocol, (len >> 8) as u8, len as u8]);
+            Some(header)
+        }
+    }
+
+    def_encoder! {
+        pub struct Udp {
+            pub src: u16,
+            pub dst: u16,
+            #[encode(length)]
+            pub length: u16,
+            #[encode(payload_checksum)]
+            pub checksum: u16,
+        }
+    }
+
+    #[test]
+    fn encode() {
+        let ip = Ipv4 {
+            version_ihl: 0x45,
+            ttl: 64,
+            protocol: 17,
+            src: [192, 168, 0, 1],
+            dst: [192, 168, 0, 2],
+            ..Ipv4::default()
+        };
+        let udp = Udp {
+            src: 1234,
+            dst: 53,
+            ..Udp::default()
+        };
+        let data = encode::encode(&[&ip, &udp], b"abc");
+        assert_eq!(data.len(), 20 + 8 + 3);
+        assert_eq!(&data[2..4], &[0, 31]);
+        assert_eq!(&data[24..26], &[0, 11]);
+        assert_eq!(checksum::internet(&data[..20]), 0);
+
+        let mut covered = ip.pseudo_header(11).unwrap();
+        covered.extend_from_slice(&data[20..]);
+        assert_eq!(checksum::internet(&covered), 0);
+
+        // The fields set explicitly are kept.
+        let udp = Udp {
+            length: 100,
+            checksum: 0xbeef,
+            ..udp
+        };
+        let data = udp.encode(b"abc", None);
+        assert_eq!(&data[4..8], &[0, 100, 0xbe, 0xef]);
+    }
+}
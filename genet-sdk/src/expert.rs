@@ -0,0 +1,19 @@
+//! Expert information.
+//!
+//! A finding is reported by adding an attribute whose type is
+//! `@expert:<severity>`. The description of the attribute class is shown
+//! as the message.
+//!
+//! # Examples
+//! ```
+//! # #[macro_use] extern crate genet_sdk;
+//! # use genet_sdk::prelude::*;
+//! def_attr_class!(MALFORMED_ATTR, "udp.malformed",
+//!     typ: "@expert:error",
+//!     description: "Length field exceeds the datagram",
+//!     value: true
+//! );
+//! # fn main() {}
+//! ```
+
+pub use genet_abi::expert::{Severity, SEVERITY_ATTR, TYPE_PREFIX};
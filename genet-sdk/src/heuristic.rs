@@ -0,0 +1,291 @@
+//! Content-based protocol detection.
+//!
+//! Heuristics are used by transport decoders to guess the payload protocol
+//! when the port tables have no entry (e.g. HTTP on 8080, TLS on 8443).
+
+use context::Context;
+use std::{fmt, str::FromStr};
+use token::Token;
+
+/// Confidence level of a heuristic match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl FromStr for Confidence {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Confidence::Low),
+            "medium" => Ok(Confidence::Medium),
+            "high" => Ok(Confidence::High),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        })
+    }
+}
+
+/// A content-based protocol heuristic.
+pub struct Heuristic {
+    /// Name used in the config.
+    pub name: &'static str,
+
+    /// Payload id assigned when the heuristic matches.
+    pub id: &'static str,
+
+    /// Tests the payload.
+    pub test: fn(&[u8]) -> Option<Confidence>,
+}
+
+/// HTTP/1.x request and response heuristic.
+pub const HTTP: Heuristic = Heuristic {
+    name: "http",
+    id: "@data:http",
+    test: test_http,
+};
+
+/// HTTP/2 connection preface heuristic.
+pub const HTTP2: Heuristic = Heuristic {
+    name: "http2",
+    id: "@data:http2",
+    test: test_http2,
+};
+
+/// TLS record heuristic.
+pub const TLS: Heuristic = Heuristic {
+    name: "tls",
+    id: "@data:tls",
+    test: test_tls,
+};
+
+/// DNS message heuristic.
+pub const DNS: Heuristic = Heuristic {
+    name: "dns",
+    id: "@data:dns",
+    test: test_dns,
+};
+
+/// All built-in heuristics.
+pub const HEURISTICS: &[&Heuristic] = &[&HTTP, &HTTP2, &TLS, &DNS];
+
+/// A set of enabled heuristics for a decoder.
+pub struct Heuristics {
+    list: Vec<(&'static Heuristic, Token)>,
+    confidence: Confidence,
+}
+
+impl Heuristics {
+    /// Creates a new Heuristics.
+    pub fn new(list: &[&'static Heuristic], confidence: Confidence) -> Heuristics {
+        Heuristics {
+            list: list.iter().map(|h| (*h, Token::from(h.id))).collect(),
+            confidence,
+        }
+    }
+
+    /// Creates a new Heuristics from the config.
+    ///
+    /// `<prefix>.heuristics` lists the enabled heuristic names and
+    /// `<prefix>.heuristics.confidence` sets the minimum confidence level.
+    /// `defaults` is used if the former is not set.
+    pub fn from_config(ctx: &Context, prefix: &str, defaults: &[&'static Heuristic]) -> Heuristics {
+        let names = ctx.get_config(&format!("{}.heuristics", prefix));
+        let list = if names.is_empty() {
+            defaults.to_vec()
+        } else {
+            names
+                .split(&[',', '[', ']', '"'][..])
+                .map(|name| name.trim())
+                .filter_map(|name| HEURISTICS.iter().find(|h| h.name == name).cloned())
+                .collect()
+        };
+        let confidence = ctx
+            .get_config(&format!("{}.heuristics.confidence", prefix))
+            .trim_matches('"')
+            .parse()
+            .unwrap_or(Confidence::Medium);
+        Self::new(&list, confidence)
+    }
+
+    /// Returns the payload id and the confidence of the most confident match.
+    pub fn detect(&self, data: &[u8]) -> Option<(Token, Confidence)> {
+        let mut result: Option<(Token, Confidence)> = None;
+        for (heuristic, id) in &self.list {
+            if let Some(confidence) = (heuristic.test)(data) {
+                let better = match result {
+                    Some((_, c)) => confidence > c,
+                    None => true,
+                };
+                if better && confidence >= self.confidence {
+                    result = Some((*id, confidence));
+                }
+            }
+        }
+        result
+    }
+}
+
+const HTTP_METHODS: &[&[u8]] = &[
+    b"GET ",
+    b"POST ",
+    b"PUT ",
+    b"HEAD ",
+    b"DELETE ",
+    b"OPTIONS ",
+    b"PATCH ",
+    b"CONNECT ",
+    b"TRACE ",
+];
+
+fn test_http(data: &[u8]) -> Option<Confidence> {
+    let line = match data.windows(2).position(|w| w == b"\r\n") {
+        Some(pos) => &data[..pos],
+        None => data,
+    };
+    if line.starts_with(b"HTTP/1.") {
+        let status = line.get(9..12)?;
+        if line.get(8) == Some(&b' ') && status.iter().all(|c| c.is_ascii_digit()) {
+            return Some(Confidence::High);
+        }
+        return Some(Confidence::Low);
+    }
+    if HTTP_METHODS.iter().any(|m| line.starts_with(m)) {
+        if line.ends_with(b" HTTP/1.1") || line.ends_with(b" HTTP/1.0") {
+            return Some(Confidence::High);
+        }
+        return Some(Confidence::Medium);
+    }
+    None
+}
+
+fn test_http2(data: &[u8]) -> Option<Confidence> {
+    if data.starts_with(b"PRI * HTTP/2.0\r\n") {
+        Some(Confidence::High)
+    } else {
+        None
+    }
+}
+
+fn test_tls(data: &[u8]) -> Option<Confidence> {
+    if data.len() < 5 {
+        return None;
+    }
+    let content_type = data[0];
+    let (major, minor) = (data[1], data[2]);
+    let len = (usize::from(data[3]) << 8) | usize::from(data[4]);
+    if !(20..=23).contains(&content_type) || major != 3 || minor > 4 || len > 18432 {
+        return None;
+    }
+    match (content_type, data.get(5)) {
+        (22, Some(1)) | (22, Some(2)) => Some(Confidence::High),
+        _ if len + 5 == data.len() => Some(Confidence::Medium),
+        _ => Some(Confidence::Low),
+    }
+}
+
+fn test_dns(data: &[u8]) -> Option<Confidence> {
+    if data.len() < 12 {
+        return None;
+    }
+    let opcode = (data[2] >> 3) & 0x0f;
+    let rcode = data[3] & 0x0f;
+    let count = |i: usize| (usize::from(data[i]) << 8) | usize::from(data[i + 1]);
+    let (qd, an, ns, ar) = (count(4), count(6), count(8), count(10));
+    if opcode > 6 || opcode == 3 || rcode > 11 || qd == 0 || qd > 16 || an + ns + ar > 512 {
+        return None;
+    }
+
+    let mut offset = 12;
+    loop {
+        let len = match data.get(offset) {
+            Some(len) => usize::from(*len),
+            None => return Some(Confidence::Low),
+        };
+        if len == 0 {
+            offset += 1;
+            break;
+        }
+        if len > 63 {
+            return Some(Confidence::Low);
+        }
+        offset += len + 1;
+    }
+    if data.len() < offset + 4 {
+        return Some(Confidence::Low);
+    }
+    let class = count(offset + 2);
+    // mDNS sets the top bit of the class for unicast responses.
+    if class & 0x7fff == 1 || class == 255 {
+        Some(Confidence::High)
+    } else {
+        Some(Confidence::Medium)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heuristic::{test_dns, test_http, test_http2, test_tls, Confidence};
+
+    #[test]
+    fn http() {
+        assert_eq!(
+            test_http(b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n"),
+            Some(Confidence::High)
+        );
+        assert_eq!(
+            test_http(b"HTTP/1.1 200 OK\r\n\r\n"),
+            Some(Confidence::High)
+        );
+        assert_eq!(test_http(b"POST /api"), Some(Confidence::Medium));
+        assert_eq!(test_http(b"\x16\x03\x01\x00\x05"), None);
+    }
+
+    #[test]
+    fn http2() {
+        assert_eq!(
+            test_http2(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\x00\x00\x00\x04"),
+            Some(Confidence::High)
+        );
+        assert_eq!(test_http2(b"PRI * HTTP/2"), None);
+        assert_eq!(test_http(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn tls() {
+        assert_eq!(
+            test_tls(b"\x16\x03\x01\x00\x04\x01\x00\x00\x00"),
+            Some(Confidence::High)
+        );
+        assert_eq!(
+            test_tls(b"\x17\x03\x03\x00\x02\xab\xcd"),
+            Some(Confidence::Medium)
+        );
+        assert_eq!(test_tls(b"\x17\x03\x03\x00\x10\xab"), Some(Confidence::Low));
+        assert_eq!(test_tls(b"GET / HTTP/1.1"), None);
+    }
+
+    #[test]
+    fn dns() {
+        let query = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\
+                      \x07example\x03com\x00\x00\x01\x00\x01";
+        assert_eq!(test_dns(query), Some(Confidence::High));
+        assert_eq!(test_dns(&query[..20]), Some(Confidence::Low));
+        assert_eq!(
+            test_dns(b"\x12\x34\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00"),
+            None
+        );
+    }
+}
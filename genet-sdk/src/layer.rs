@@ -2,4 +2,7 @@
 //!
 //! Type Layer represents a layer of a protocol stack.
 
-pub use genet_abi::layer::{Layer, LayerClass, LayerClassBuilder, LayerStack, Parent, Payload};
+pub use genet_abi::layer::{
+    AttrIter, Direction, FrameMetadata, Layer, LayerClass, LayerClassBuilder, LayerStack, Parent,
+    Payload, EMBEDDED_TYPE,
+};
@@ -7,13 +7,19 @@ extern crate lazy_static;
 
 pub mod attr;
 pub mod cast;
+pub mod checksum;
 pub mod context;
 pub mod decoder;
+pub mod dynamic;
+pub mod encode;
 pub mod error;
+pub mod expert;
 pub mod file;
 pub mod fixed;
 pub mod helper;
+pub mod heuristic;
 pub mod layer;
+pub mod option;
 pub mod prelude;
 pub mod reader;
 pub mod result;
@@ -0,0 +1,368 @@
+//! Type-length-value option lists.
+//!
+//! Many headers end with a list of options, each starting with a code and a
+//! length, e.g. TCP options, IPv6 Hop-by-Hop and Destination Options, and
+//! DHCP options. A `Format` describes how the options of a protocol are
+//! encoded, and an `OptionTable` maps the option codes to their attributes:
+//!
+//! - `value`: an attribute with the range of the value, read by the cast of
+//!   the class.
+//! - `option`: an attribute with the range of the whole option, e.g. for
+//!   options without a value.
+//! - `decoder`: a function adding the attributes of an option with several
+//!   fields.
+//!
+//! Options without an entry are passed to the `unknown` function if any,
+//! unless they are skipped by `ignore`, e.g. padding.
+
+use attr::{Attr, AttrClass};
+use fixed::Fixed;
+use layer::Layer;
+use result::Result;
+use std::{collections::HashMap, ops::Range};
+
+/// The encoding of the code and the length of options.
+#[derive(Clone, Copy, Debug)]
+pub struct Format {
+    /// The size of the code in bytes.
+    pub code_size: usize,
+
+    /// The size of the length in bytes.
+    pub len_size: usize,
+
+    /// The length includes the code and the length.
+    pub len_includes_header: bool,
+
+    /// The options consisting of the code only, e.g. padding.
+    pub single: &'static [u64],
+
+    /// The option ending the list.
+    pub end: Option<u64>,
+}
+
+/// TCP options (RFC 793).
+pub const TCP: Format = Format {
+    code_size: 1,
+    len_size: 1,
+    len_includes_header: true,
+    single: &[0, 1],
+    end: None,
+};
+
+/// IPv6 Hop-by-Hop and Destination Options (RFC 8200).
+pub const IPV6: Format = Format {
+    code_size: 1,
+    len_size: 1,
+    len_includes_header: false,
+    single: &[0],
+    end: None,
+};
+
+/// DHCP options (RFC 2132).
+pub const DHCP: Format = Format {
+    code_size: 1,
+    len_size: 1,
+    len_includes_header: false,
+    single: &[0, 255],
+    end: Some(255),
+};
+
+/// DHCPv6 options (RFC 8415).
+pub const DHCPV6: Format = Format {
+    code_size: 2,
+    len_size: 2,
+    len_includes_header: false,
+    single: &[],
+    end: None,
+};
+
+/// An option. The ranges are relative to the data of the list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tlv {
+    pub code: u64,
+
+    /// The range of the whole option.
+    pub range: Range<usize>,
+
+    /// The range of the value.
+    pub value: Range<usize>,
+}
+
+/// The options of a list.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OptionList {
+    pub options: Vec<Tlv>,
+
+    /// The offset following the last option.
+    pub end: usize,
+
+    /// An option has an invalid length or exceeds the list.
+    pub malformed: bool,
+}
+
+impl OptionList {
+    /// Returns the first option `code`.
+    pub fn get(&self, code: u64) -> Option<&Tlv> {
+        self.options.iter().find(|opt| opt.code == code)
+    }
+}
+
+fn uint(data: &[u8], range: Range<usize>) -> u64 {
+    data[range]
+        .iter()
+        .fold(0, |value, b| value << 8 | u64::from(*b))
+}
+
+impl Format {
+    /// Splits `range` of `data` into options.
+    pub fn parse(&self, data: &[u8], range: Range<usize>) -> OptionList {
+        let end = range.end.min(data.len());
+        let header = self.code_size + self.len_size;
+        let mut list = OptionList::default();
+        let mut offset = range.start;
+        while offset < end {
+            if offset + self.code_size > end {
+                list.malformed = true;
+                break;
+            }
+            let code = uint(data, offset..offset + self.code_size);
+            let value = if self.single.contains(&code) {
+                offset + self.code_size..offset + self.code_size
+            } else {
+                if offset + header > end {
+                    list.malformed = true;
+                    break;
+                }
+                let len = uint(data, offset + self.code_size..offset + header) as usize;
+                let len = if self.len_includes_header {
+                    match len.checked_sub(header) {
+                        Some(len) => len,
+                        None => {
+                            list.malformed = true;
+                            break;
+                        }
+                    }
+                } else {
+                    len
+                };
+                if offset + header + len > end {
+                    list.malformed = true;
+                    break;
+                }
+                offset + header..offset + header + len
+            };
+            list.options.push(Tlv {
+                code,
+                range: offset..value.end,
+                value: value.clone(),
+            });
+            offset = value.end;
+            if self.end == Some(code) {
+                break;
+            }
+        }
+        list.end = offset;
+        list
+    }
+}
+
+/// A function adding the attributes of an option to the layer.
+pub type Decode = fn(&mut Layer, &Tlv) -> Result<()>;
+
+#[derive(Clone)]
+enum Handler {
+    Value(Fixed<AttrClass>),
+    Option(Fixed<AttrClass>),
+    Decode(Decode),
+    Ignore,
+}
+
+/// A registry of the attributes of options.
+pub struct OptionTable {
+    format: Format,
+    handlers: HashMap<u64, Handler>,
+    unknown: Option<Decode>,
+}
+
+impl OptionTable {
+    /// Creates an empty table for options encoded in `format`.
+    pub fn new(format: Format) -> OptionTable {
+        OptionTable {
+            format,
+            handlers: HashMap::new(),
+            unknown: None,
+        }
+    }
+
+    /// Adds an attribute of `class` with the range of the value of the
+    /// options `code`.
+    pub fn value<C: Into<Fixed<AttrClass>>>(mut self, code: u64, class: C) -> OptionTable {
+        self.handlers.insert(code, Handler::Value(class.into()));
+        self
+    }
+
+    /// Adds an attribute of `class` with the range of the options `code`.
+    pub fn option<C: Into<Fixed<AttrClass>>>(mut self, code: u64, class: C) -> OptionTable {
+        self.handlers.insert(code, Handler::Option(class.into()));
+        self
+    }
+
+    /// Calls `decode` for the options `code`.
+    pub fn decoder(mut self, code: u64, decode: Decode) -> OptionTable {
+        self.handlers.insert(code, Handler::Decode(decode));
+        self
+    }
+
+    /// Skips the options `code`.
+    pub fn ignore(mut self, code: u64) -> OptionTable {
+        self.handlers.insert(code, Handler::Ignore);
+        self
+    }
+
+    /// Calls `decode` for the options without an entry.
+    pub fn unknown(mut self, decode: Decode) -> OptionTable {
+        self.unknown = Some(decode);
+        self
+    }
+
+    pub fn format(&self) -> &Format {
+        &self.format
+    }
+
+    /// Adds the attributes of the options in `range` of the layer data, and
+    /// returns the options.
+    pub fn dissect(&self, layer: &mut Layer, range: Range<usize>) -> Result<OptionList> {
+        let list = self.format.parse(&layer.data(), range);
+        for opt in &list.options {
+            match self.handlers.get(&opt.code) {
+                Some(Handler::Value(class)) => layer.add_attr(
+                    Attr::builder(class.clone())
+                        .range(opt.value.clone())
+                        .build(),
+                ),
+                Some(Handler::Option(class)) => layer.add_attr(
+                    Attr::builder(class.clone())
+                        .range(opt.range.clone())
+                        .build(),
+                ),
+                Some(Handler::Decode(decode)) => decode(layer, opt)?,
+                Some(Handler::Ignore) => {}
+                None => {
+                    if let Some(decode) = self.unknown {
+                        decode(layer, opt)?;
+                    }
+                }
+            }
+        }
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use attr::{Attr, AttrClass};
+    use cast;
+    use fixed::Fixed;
+    use layer::{Layer, LayerClass};
+    use option::{self, OptionTable, Tlv};
+    use result::Result;
+    use slice::ByteSlice;
+    use variant::Variant;
+
+    #[test]
+    fn parse() {
+        let data = b"\x01\x01\x02\x04\x05\xb4\x04\x02\x00\x00";
+        let list = option::TCP.parse(data, 0..data.len());
+        let codes = list.options.iter().map(|opt| opt.code).collect::<Vec<_>>();
+        assert_eq!(codes, vec![1, 1, 2, 4, 0, 0]);
+        assert_eq!(list.options[2].value, 4..6);
+        assert_eq!(list.options[3].value, 8..8);
+        assert_eq!(list.end, data.len());
+        assert!(!list.malformed);
+
+        // The End option ends the list.
+        let data = b"\x00\x35\x01\x01\xff\x00\x00";
+        let list = option::DHCP.parse(data, 0..data.len());
+        assert_eq!(list.options.len(), 3);
+        assert_eq!(
+            list.get(53),
+            Some(&Tlv {
+                code: 53,
+                range: 1..4,
+                value: 3..4,
+            })
+        );
+        assert_eq!(list.end, 5);
+
+        let data = b"\x00\x01\x00\x02\xab\xcd\x00\x08\x00\x02\x00";
+        let list = option::DHCPV6.parse(data, 0..data.len());
+        assert_eq!(list.options.len(), 1);
+        assert_eq!(list.options[0].value, 4..6);
+        assert_eq!(list.end, 6);
+        assert!(list.malformed);
+
+        // A TCP option shorter than its header.
+        let list = option::TCP.parse(b"\x02\x01\x00\x00", 0..4);
+        assert!(list.options.is_empty());
+        assert!(list.malformed);
+    }
+
+    fn decode_ts(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+        let class = Fixed::new(AttrClass::builder("test.ts").build());
+        let value = opt.value.end - opt.value.start;
+        layer.add_attr(
+            Attr::builder(class)
+                .range(opt.range.clone())
+                .value(value as u64)
+                .build(),
+        );
+        Ok(())
+    }
+
+    fn decode_unknown(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+        let class = Fixed::new(AttrClass::builder("test.unknown").build());
+        layer.add_attr(
+            Attr::builder(class)
+                .range(opt.range.clone())
+                .value(opt.code)
+                .build(),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dissect() {
+        let mss = Fixed::new(
+            AttrClass::builder("test.mss")
+                .cast(cast::UInt16BE())
+                .build(),
+        );
+        let nop = Fixed::new(
+            AttrClass::builder("test.nop")
+                .typ("@novalue")
+                .value(true)
+                .build(),
+        );
+        let table = OptionTable::new(option::TCP)
+            .value(2, mss)
+            .option(1, nop)
+            .decoder(8, decode_ts)
+            .ignore(0)
+            .unknown(decode_unknown);
+
+        let data =
+            ByteSlice::from(&b"\x02\x04\x05\xb4\x01\x08\x06\x00\x00\x00\x01\x1e\x02\x00"[..]);
+        let class = Fixed::new(LayerClass::builder("test").build());
+        let mut layer = Layer::new(class, data);
+        let list = table.dissect(&mut layer, 0..14).unwrap();
+        assert_eq!(list.options.len(), 5);
+        assert_eq!(layer.attrs().len(), 4);
+
+        let value = |id: &str| layer.attr(id).unwrap().try_get(&layer).unwrap();
+        assert_eq!(value("test.mss"), Variant::UInt64(1460));
+        assert_eq!(value("test.nop"), Variant::Bool(true));
+        assert_eq!(value("test.ts"), Variant::UInt64(4));
+        assert_eq!(value("test.unknown"), Variant::UInt64(30));
+        assert_eq!(layer.attr("test.unknown").unwrap().range(), 11..13);
+    }
+}
@@ -16,6 +16,7 @@ pub use attr_class;
 pub use attr_class_lazy;
 pub use def_attr;
 pub use def_attr_class;
+pub use def_encoder;
 pub use def_layer_class;
 pub use genet_decoders;
 pub use genet_readers;
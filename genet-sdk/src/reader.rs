@@ -1,6 +1,6 @@
 //! Reader traits.
 
-pub use genet_abi::reader::{Metadata, Reader, Worker};
+pub use genet_abi::reader::{CaptureStats, Metadata, Reader, Worker};
 
 #[doc(hidden)]
 pub use genet_abi::reader::ReaderBox;
@@ -0,0 +1,211 @@
+//! A headless genet server.
+//!
+//! Clients drive sessions over JSON-RPC 2.0, one message per line. Each
+//! connection has its own session, and session events are sent to the
+//! client as `event` notifications.
+//!
+//! Connections over TCP can only reach the files in the `--root` directory,
+//! which defaults to the working directory. They can't set config keys
+//! naming files, nor pass reader arguments which may run a program, such as
+//! those of the remote reader. With `--token`, a client has to
+//! send `authenticate {token}` before any other request, and listening on an
+//! address other than loopback requires it.
+//!
+//! ```text
+//! $ genet-server --library libeth.so --listen 127.0.0.1:7070
+//! > {"jsonrpc":"2.0","id":1,"method":"open","params":{"path":"dump.pcap"}}
+//! < {"jsonrpc":"2.0","id":1,"result":1}
+//! < {"jsonrpc":"2.0","method":"event","params":{"type":"frames","length":120}}
+//! > {"jsonrpc":"2.0","id":2,"method":"frame","params":{"index":0}}
+//! ```
+//!
+//! Methods:
+//!
+//! - `authenticate {token}`
+//! - `open {path, reader?}`, `createReader {id, arg}`, `closeReader {handle}`
+//! - `writeRawFrames {id, arg, frames, link?}`, e.g. to transmit crafted
+//!   frames with `app.genet.writer.inject`
+//! - `status`, `frames {start, end}`, `frame {index}`
+//! - `setFilter {id, filter}`, `filteredFrames {id, start, end}`,
+//!   `filteredLength {id}`, `filteredRank {id, index}`, `filteredSelect {id, row}`
+//! - `setColumns {columns}`, `columnRows {start, end, filter?}`
+//! - `setColorRules {rules}`, `frameColors {start, end, filter?}`
+//! - `setComment {index, comment}`, `setByteAnnotations {index, annotations}`,
+//!   `frameAnnotations {index}`, `commentedFrames`
+//! - `setMarked {indices, marked}`, `setIgnored {indices, ignored}`,
+//!   `markedFrames`, `ignoredFrames`
+//! - `compare {id, arg, options?}`, `comparisonReport`, `clearComparison`
+//! - `shiftTimestamps {offset} | {skew: [{frame, time}, {frame, time}]}`,
+//!   `timeShift`, `clearTimeShift`
+//! - `startRingBuffer {id, arg, options?}`, `stopRingBuffer`,
+//!   `setFrameWindow {frames}`, `firstFrame`
+//! - `saveSession {path}`, `restoreSession {path}`
+//! - `sortFrames {id, expr, filter?, descending?}`, `sortedFrames {id, start, end}`,
+//!   `clearSort {id}`
+//! - `setConfig {key, value}`
+//! - `valueCounts {id, filter?, top?}`, `conversations {level}`,
+//!   `coverage {filter?}`, `expertSummary`, `attributeCatalog`,
+//!   `pipelineStats`, `captureStats {handle}`
+
+extern crate clap;
+extern crate genet_abi;
+extern crate genet_filter;
+extern crate genet_kernel;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+
+mod rpc;
+mod server;
+
+use clap::{App, Arg};
+use genet_kernel::{profile::Profile, signature::Verification};
+use server::{Access, Connection};
+use std::{
+    env, fs,
+    io::{self, BufReader},
+    net::TcpListener,
+    process, thread,
+};
+
+/// The environment variable read when `--token` is omitted, which keeps the
+/// token out of the process list.
+const TOKEN_ENV: &str = "GENET_SERVER_TOKEN";
+
+fn main() {
+    let matches = App::new("genet-server")
+        .about("Drives genet sessions over JSON-RPC")
+        .arg(
+            Arg::with_name("listen")
+                .long("listen")
+                .value_name("ADDR")
+                .default_value("127.0.0.1:7070")
+                .help("Accepts connections on ADDR"),
+        )
+        .arg(
+            Arg::with_name("stdio")
+                .long("stdio")
+                .conflicts_with("listen")
+                .help("Serves a single session on stdin and stdout"),
+        )
+        .arg(
+            Arg::with_name("token")
+                .long("token")
+                .value_name("TOKEN")
+                .conflicts_with("stdio")
+                .help("Requires clients to authenticate with TOKEN [env: GENET_SERVER_TOKEN]"),
+        )
+        .arg(
+            Arg::with_name("root")
+                .long("root")
+                .value_name("DIR")
+                .help("Restricts the files clients read and write to DIR"),
+        )
+        .arg(
+            Arg::with_name("library")
+                .long("library")
+                .short("l")
+                .value_name("PATH")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Loads a plugin library"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .value_name("KEY=VALUE")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Sets a config value in JSON"),
+        )
+        .get_matches();
+
+    let mut profile = Profile::new();
+    for path in matches.values_of("library").into_iter().flatten() {
+        match profile.load_library(path) {
+            Ok(Verification::Untrusted(reason)) => eprintln!("{}: untrusted: {}", path, reason),
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+    for config in matches.values_of("config").into_iter().flatten() {
+        match config.find('=') {
+            Some(pos) => profile.set_config(&config[..pos], &config[pos + 1..]),
+            None => {
+                eprintln!("{}: expected KEY=VALUE", config);
+                process::exit(1);
+            }
+        }
+    }
+
+    let root = match matches.value_of("root") {
+        Some(root) => Some(root),
+        None if matches.is_present("stdio") => None,
+        None => Some("."),
+    };
+    let root = root.map(|root| {
+        fs::canonicalize(root).unwrap_or_else(|err| {
+            eprintln!("{}: {}", root, err);
+            process::exit(1);
+        })
+    });
+
+    if matches.is_present("stdio") {
+        let stdin = io::stdin();
+        let access = Access { token: None, root };
+        let mut conn = Connection::new(profile, Box::new(io::stdout()), access);
+        if let Err(err) = conn.serve(stdin.lock()) {
+            eprintln!("{}", err);
+        }
+        return;
+    }
+
+    let addr = matches.value_of("listen").unwrap();
+    let listener = TcpListener::bind(addr).unwrap_or_else(|err| {
+        eprintln!("{}: {}", addr, err);
+        process::exit(1);
+    });
+    let token = matches
+        .value_of("token")
+        .map(|token| token.to_string())
+        .or_else(|| env::var(TOKEN_ENV).ok())
+        .filter(|token| !token.is_empty());
+    let loopback = listener
+        .local_addr()
+        .map(|addr| addr.ip().is_loopback())
+        .unwrap_or(false);
+    if !loopback && token.is_none() {
+        eprintln!(
+            "{}: listening on a non-loopback address requires --token",
+            addr
+        );
+        process::exit(1);
+    }
+    let access = Access { token, root };
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("{}", err);
+                continue;
+            }
+        };
+        let profile = profile.clone();
+        let access = access.clone();
+        thread::spawn(move || {
+            let result = stream.try_clone().and_then(|writer| {
+                let mut conn = Connection::new(profile, Box::new(writer), access);
+                conn.serve(BufReader::new(stream))
+            });
+            if let Err(err) = result {
+                eprintln!("{}", err);
+            }
+        });
+    }
+}
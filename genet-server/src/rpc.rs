@@ -0,0 +1,129 @@
+//! JSON-RPC 2.0 messages.
+//!
+//! Each message is a single line of JSON.
+
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+use std::fmt;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+const UNAUTHORIZED: i64 = -32001;
+
+#[derive(Deserialize, Debug)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// Notifications have no ID and get no response.
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl Request {
+    pub fn parse(line: &str) -> Result<Request, Error> {
+        let value: Value = serde_json::from_str(line).map_err(|err| Error::parse(&err))?;
+        let req: Request =
+            serde_json::from_value(value).map_err(|err| Error::invalid_request(&err))?;
+        if req.jsonrpc != "2.0" {
+            return Err(Error::invalid_request(&"jsonrpc must be \"2.0\""));
+        }
+        Ok(req)
+    }
+
+    /// Deserializes the params. Omitted params are read as an empty object.
+    pub fn params<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        let params = match self.params {
+            Value::Null => json!({}),
+            ref params => params.clone(),
+        };
+        serde_json::from_value(params).map_err(|err| Error::invalid_params(&err))
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Error {
+    pub code: i64,
+    pub message: String,
+}
+
+impl Error {
+    fn new<T: fmt::Display + ?Sized>(code: i64, message: &T) -> Error {
+        Error {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn parse<T: fmt::Display + ?Sized>(message: &T) -> Error {
+        Error::new(PARSE_ERROR, message)
+    }
+
+    pub fn invalid_request<T: fmt::Display + ?Sized>(message: &T) -> Error {
+        Error::new(INVALID_REQUEST, message)
+    }
+
+    pub fn method_not_found(method: &str) -> Error {
+        Error::new(METHOD_NOT_FOUND, &format!("method not found: {}", method))
+    }
+
+    pub fn invalid_params<T: fmt::Display + ?Sized>(message: &T) -> Error {
+        Error::new(INVALID_PARAMS, message)
+    }
+
+    pub fn server<T: fmt::Display + ?Sized>(message: &T) -> Error {
+        Error::new(SERVER_ERROR, message)
+    }
+
+    pub fn unauthorized() -> Error {
+        Error::new(UNAUTHORIZED, "authentication required")
+    }
+}
+
+/// Returns the response to the request `id`.
+pub fn response(id: Value, result: Result<Value, Error>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+}
+
+/// Returns a notification from the server.
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+#[cfg(test)]
+mod tests {
+    use rpc::{Error, Request};
+
+    #[derive(Deserialize, Debug)]
+    struct Params {
+        index: usize,
+    }
+
+    #[test]
+    fn parse() {
+        let req =
+            Request::parse(r#"{"jsonrpc":"2.0","id":1,"method":"frame","params":{"index":3}}"#)
+                .unwrap();
+        assert_eq!(req.id, Some(json!(1)));
+        assert_eq!(req.method, "frame");
+        assert_eq!(req.params::<Params>().unwrap().index, 3);
+
+        let req = Request::parse(r#"{"jsonrpc":"2.0","method":"status"}"#).unwrap();
+        assert_eq!(req.id, None);
+        assert_eq!(req.params::<Params>().unwrap_err().code, -32602);
+
+        assert_eq!(Request::parse("{").unwrap_err().code, -32700);
+        assert_eq!(Request::parse("[]").unwrap_err().code, -32600);
+        assert_eq!(
+            Request::parse(r#"{"jsonrpc":"1.0","method":"status"}"#).unwrap_err(),
+            Error::invalid_request(&"jsonrpc must be \"2.0\"")
+        );
+    }
+}
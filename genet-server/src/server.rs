@@ -0,0 +1,1264 @@
+use genet_filter::Filter;
+use genet_kernel::{
+    annotations::{ByteAnnotation, ColorRule},
+    columns::ColumnDef,
+    compare::CompareOptions,
+    geoip, index,
+    profile::Profile,
+    resolver,
+    ring::RingOptions,
+    session::{Callback, Event, Session},
+    session_file::SessionFile,
+    signature,
+    time_shift::Correction,
+};
+use rpc::{self, Error, Request};
+use serde::Serialize;
+use serde_json::{self, Value};
+use std::{
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Config keys naming files, which clients of a restricted connection can't
+/// set.
+const PATH_KEYS: &[&str] = &[
+    index::DIR_KEY,
+    geoip::DATABASES_KEY,
+    signature::TRUST_STORE_KEY,
+    signature::POLICY_KEY,
+    resolver::RESOLVER_KEY,
+    "@genet/tls.keyLogFile",
+    "@genet/quic.keyLogFile",
+    "@genet/grpc.descriptorSet",
+];
+
+/// Reader and writer argument keys naming a file or a directory, which are
+/// resolved against the root of a restricted connection.
+const PATH_ARGS: &[&str] = &["file", "directory"];
+
+/// Reader and writer argument keys which name neither a file nor a program.
+/// A restricted connection rejects any other key.
+const PLAIN_ARGS: &[&str] = &[
+    "backend",
+    "buffer",
+    "columns",
+    "compression",
+    "expression",
+    "filter",
+    "index",
+    "interface",
+    "link",
+    "loops",
+    "max_open",
+    "pace",
+    "promisc",
+    "queue",
+    "snaplen",
+    "wireless",
+];
+
+/// Readers which run programs on behalf of the client, and which a
+/// restricted connection can't create.
+const EXEC_READERS: &[&str] = &["app.genet.reader.remote"];
+
+type Writer = Arc<Mutex<Box<Write + Send>>>;
+
+fn send(writer: &Writer, msg: &Value) {
+    let mut writer = writer.lock().unwrap();
+    let _ = serde_json::to_writer(&mut *writer, msg);
+    let _ = writer.write_all(b"\n");
+    let _ = writer.flush();
+}
+
+/// Sends session events as `event` notifications.
+#[derive(Clone)]
+struct EventCallback {
+    writer: Writer,
+}
+
+impl Callback for EventCallback {
+    fn on_event(&self, event: Event) {
+        let params = serde_json::to_value(&event).unwrap_or(Value::Null);
+        send(&self.writer, &rpc::notification("event", params));
+    }
+}
+
+fn compile(filter: Option<&str>) -> Result<Option<Filter>, Error> {
+    match filter {
+        Some(filter) if !filter.is_empty() => Filter::compile(filter)
+            .map(Some)
+            .map_err(|err| Error::invalid_params(&err)),
+        _ => Ok(None),
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, Error> {
+    serde_json::to_value(value).map_err(|err| Error::server(&err))
+}
+
+#[derive(Deserialize)]
+struct Authenticate {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct Open {
+    path: String,
+    reader: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Compare {
+    id: String,
+    #[serde(default)]
+    arg: Value,
+    #[serde(default)]
+    options: CompareOptions,
+}
+
+#[derive(Deserialize)]
+struct StartRingBuffer {
+    id: String,
+    #[serde(default)]
+    arg: Value,
+    #[serde(default)]
+    options: RingOptions,
+}
+
+#[derive(Deserialize)]
+struct SetFrameWindow {
+    frames: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct SessionPath {
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct CreateReader {
+    id: String,
+    #[serde(default)]
+    arg: Value,
+}
+
+fn default_link() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+struct WriteRawFrames {
+    id: String,
+    #[serde(default)]
+    arg: Value,
+    #[serde(default = "default_link")]
+    link: u32,
+    frames: Vec<Vec<u8>>,
+}
+
+#[derive(Deserialize)]
+struct Handle {
+    handle: u32,
+}
+
+#[derive(Deserialize)]
+struct SetFilter {
+    id: u32,
+    filter: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Range {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Deserialize)]
+struct FilteredRange {
+    id: u32,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Deserialize)]
+struct FilterId {
+    id: u32,
+}
+
+#[derive(Deserialize)]
+struct FilteredIndex {
+    id: u32,
+    index: u32,
+}
+
+#[derive(Deserialize)]
+struct FilteredRow {
+    id: u32,
+    row: usize,
+}
+
+#[derive(Deserialize)]
+struct SetColumns {
+    columns: Vec<ColumnDef>,
+}
+
+#[derive(Deserialize)]
+struct SetColorRules {
+    rules: Vec<ColorRule>,
+}
+
+#[derive(Deserialize)]
+struct SetComment {
+    index: u32,
+    comment: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SetByteAnnotations {
+    index: u32,
+    annotations: Vec<ByteAnnotation>,
+}
+
+#[derive(Deserialize)]
+struct SetMarked {
+    indices: Vec<u32>,
+    marked: bool,
+}
+
+#[derive(Deserialize)]
+struct SetIgnored {
+    indices: Vec<u32>,
+    ignored: bool,
+}
+
+#[derive(Deserialize)]
+struct FrameIndex {
+    index: u32,
+}
+
+#[derive(Deserialize)]
+struct Rows {
+    start: usize,
+    end: usize,
+    filter: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct SortFrames {
+    id: u32,
+    expr: String,
+    filter: Option<String>,
+    #[serde(default)]
+    descending: bool,
+}
+
+#[derive(Deserialize)]
+struct Index {
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct SetConfig {
+    key: String,
+    value: Value,
+}
+
+#[derive(Deserialize)]
+struct ValueCounts {
+    id: String,
+    filter: Option<String>,
+    #[serde(default)]
+    top: usize,
+}
+
+#[derive(Deserialize)]
+struct Level {
+    level: String,
+}
+
+#[derive(Deserialize)]
+struct FilterParams {
+    filter: Option<String>,
+}
+
+/// Compares the tokens in a time independent of where they differ.
+fn token_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Restrictions on what the client of a connection can do.
+#[derive(Clone, Debug, Default)]
+pub struct Access {
+    /// The token the client has to send with `authenticate` before any other
+    /// request.
+    pub token: Option<String>,
+    /// The canonical path of the directory containing every file the client
+    /// reads or writes. Relative paths are resolved against it.
+    pub root: Option<PathBuf>,
+}
+
+impl Access {
+    /// Resolves `path` against the root, and fails if the file is outside of
+    /// it.
+    fn resolve(&self, path: &str) -> Result<String, Error> {
+        let root = match &self.root {
+            Some(root) => root,
+            None => return Ok(path.to_string()),
+        };
+        let path = root.join(path);
+        let denied = || Error::server(&format!("{}: outside of the root", path.display()));
+        // A file to be written may not exist yet, but its directory has to.
+        let resolved = match path.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(_) => {
+                let name = path.file_name().ok_or_else(denied)?;
+                path.parent()
+                    .and_then(|dir| dir.canonicalize().ok())
+                    .ok_or_else(denied)?
+                    .join(name)
+            }
+        };
+        if resolved.starts_with(root) {
+            Ok(resolved.to_string_lossy().into_owned())
+        } else {
+            Err(denied())
+        }
+    }
+
+    /// Returns the argument of the reader or the writer `id`, whose paths are
+    /// resolved against the root.
+    ///
+    /// With a root, a string or an object argument has to be an object whose
+    /// keys are known not to run a program, and readers running programs are
+    /// rejected.
+    fn arg(&self, id: &str, arg: Value) -> Result<String, Error> {
+        if self.root.is_none() {
+            return Ok(match arg {
+                Value::String(arg) => arg,
+                arg => arg.to_string(),
+            });
+        }
+        if EXEC_READERS.contains(&id) {
+            return Err(Error::server(&format!("{}: not allowed", id)));
+        }
+        let value = match arg {
+            Value::String(arg) => serde_json::from_str(&arg).unwrap_or(Value::String(arg)),
+            arg => arg,
+        };
+        let mut map = match value {
+            Value::Object(map) => map,
+            Value::Null | Value::Bool(_) | Value::Number(_) => return Ok(value.to_string()),
+            _ => return Err(Error::invalid_params(&"arg must be an object")),
+        };
+        for (key, value) in map.iter_mut() {
+            if PATH_ARGS.contains(&key.as_str()) {
+                let path = value
+                    .as_str()
+                    .ok_or_else(|| Error::invalid_params(&format!("{} must be a string", key)))?;
+                *value = json!(self.resolve(path)?);
+            } else if !PLAIN_ARGS.contains(&key.as_str()) {
+                return Err(Error::server(&format!("{}: not allowed", key)));
+            }
+        }
+        Ok(Value::Object(map).to_string())
+    }
+
+    fn check_config(&self, key: &str) -> Result<(), Error> {
+        let denied = PATH_KEYS.iter().any(|path_key| {
+            key == *path_key
+                || (key.starts_with(path_key) && key[path_key.len()..].starts_with('.'))
+        });
+        if self.root.is_some() && denied {
+            Err(Error::server(&format!("{}: not allowed", key)))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A session driven by a client.
+pub struct Connection {
+    session: Session,
+    writer: Writer,
+    access: Access,
+    authenticated: bool,
+}
+
+impl Connection {
+    pub fn new(profile: Profile, writer: Box<Write + Send>, access: Access) -> Connection {
+        let writer = Arc::new(Mutex::new(writer));
+        let callback = EventCallback {
+            writer: writer.clone(),
+        };
+        Connection {
+            session: Session::new(profile, callback),
+            writer,
+            authenticated: access.token.is_none(),
+            access,
+        }
+    }
+
+    /// Reads requests from `input` and writes the responses until the end
+    /// of `input`.
+    pub fn serve<R: BufRead>(&mut self, input: R) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (id, result) = match Request::parse(&line) {
+                Ok(req) => match req.id.clone() {
+                    Some(id) => (id, self.call(&req)),
+                    None => {
+                        let _ = self.call(&req);
+                        continue;
+                    }
+                },
+                Err(err) => (Value::Null, Err(err)),
+            };
+            send(&self.writer, &rpc::response(id, result));
+        }
+        Ok(())
+    }
+
+    pub fn call(&mut self, req: &Request) -> Result<Value, Error> {
+        if !self.authenticated {
+            if req.method != "authenticate" {
+                return Err(Error::unauthorized());
+            }
+            let params: Authenticate = req.params()?;
+            let token = self
+                .access
+                .token
+                .as_ref()
+                .map_or("", |token| token.as_str());
+            if !token_eq(&params.token, token) {
+                return Err(Error::unauthorized());
+            }
+            self.authenticated = true;
+            return Ok(Value::Null);
+        }
+        let session = &mut self.session;
+        let access = &self.access;
+        match req.method.as_str() {
+            "authenticate" => Ok(Value::Null),
+            "open" => {
+                let params: Open = req.params()?;
+                let path = access.resolve(&params.path)?;
+                let reader = match params.reader {
+                    Some(reader) => reader,
+                    None => session.profile().reader_for_file(&path).ok_or_else(|| {
+                        Error::server(&format!("{}: no reader found", params.path))
+                    })?,
+                };
+                let arg = access.arg(&reader, json!({ "file": path }))?;
+                create_reader(session, &reader, &arg)
+            }
+            "createReader" => {
+                let params: CreateReader = req.params()?;
+                let arg = access.arg(&params.id, params.arg)?;
+                create_reader(session, &params.id, &arg)
+            }
+            "writeRawFrames" => {
+                let params: WriteRawFrames = req.params()?;
+                let arg = access.arg(&params.id, params.arg)?;
+                match session.write_raw_frames(&params.id, &arg, params.link, params.frames) {
+                    0 => Err(Error::server(&format!(
+                        "{}: failed to create the writer",
+                        params.id
+                    ))),
+                    handle => Ok(json!(handle)),
+                }
+            }
+            "closeReader" => {
+                let params: Handle = req.params()?;
+                session.close_reader(params.handle);
+                Ok(Value::Null)
+            }
+            "status" => Ok(json!({
+                "frames": session.len(),
+                "pendingFrames": session.pending_frames(),
+            })),
+            "frames" => {
+                let params: Range = req.params()?;
+                to_value(&session.frame_summaries(params.start..params.end))
+            }
+            "frame" => {
+                let params: Index = req.params()?;
+                match session.layer_tree(params.index) {
+                    Some(tree) => to_value(&tree),
+                    None => Err(Error::invalid_params(&format!(
+                        "no such frame: {}",
+                        params.index
+                    ))),
+                }
+            }
+            "setFilter" => {
+                let params: SetFilter = req.params()?;
+                let filter = compile(params.filter.as_deref())?;
+                session.set_filter(params.id, filter);
+                Ok(Value::Null)
+            }
+            "filteredFrames" => {
+                let params: FilteredRange = req.params()?;
+                to_value(&session.filtered_frames(params.id, params.start..params.end))
+            }
+            "filteredLength" => {
+                let params: FilterId = req.params()?;
+                Ok(json!(session.filtered_len(params.id)))
+            }
+            "filteredRank" => {
+                let params: FilteredIndex = req.params()?;
+                Ok(json!(session.filtered_rank(params.id, params.index)))
+            }
+            "filteredSelect" => {
+                let params: FilteredRow = req.params()?;
+                Ok(json!(session.filtered_select(params.id, params.row)))
+            }
+            "setColumns" => {
+                let params: SetColumns = req.params()?;
+                session
+                    .set_columns(params.columns)
+                    .map(|_| Value::Null)
+                    .map_err(|err| Error::invalid_params(&err))
+            }
+            "columnRows" => {
+                let params: Rows = req.params()?;
+                to_value(&session.column_rows(params.start..params.end, params.filter))
+            }
+            "setColorRules" => {
+                let params: SetColorRules = req.params()?;
+                session
+                    .set_color_rules(params.rules)
+                    .map(|_| Value::Null)
+                    .map_err(|err| Error::invalid_params(&err))
+            }
+            "frameColors" => {
+                let params: Rows = req.params()?;
+                to_value(&session.frame_colors(params.start..params.end, params.filter))
+            }
+            "setComment" => {
+                let params: SetComment = req.params()?;
+                session.set_comment(params.index, params.comment.as_deref());
+                Ok(Value::Null)
+            }
+            "setByteAnnotations" => {
+                let params: SetByteAnnotations = req.params()?;
+                session
+                    .set_byte_annotations(params.index, params.annotations)
+                    .map(|_| Value::Null)
+                    .map_err(|err| Error::invalid_params(&err))
+            }
+            "frameAnnotations" => {
+                let params: FrameIndex = req.params()?;
+                to_value(&session.frame_annotations(params.index))
+            }
+            "commentedFrames" => to_value(&session.commented_frames()),
+            "setMarked" => {
+                let params: SetMarked = req.params()?;
+                session.set_marked(&params.indices, params.marked);
+                Ok(Value::Null)
+            }
+            "setIgnored" => {
+                let params: SetIgnored = req.params()?;
+                session.set_ignored(&params.indices, params.ignored);
+                Ok(Value::Null)
+            }
+            "markedFrames" => to_value(&session.marked_frames()),
+            "ignoredFrames" => to_value(&session.ignored_frames()),
+            "compare" => {
+                let params: Compare = req.params()?;
+                let arg = access.arg(&params.id, params.arg)?;
+                session
+                    .compare(&params.id, &arg, &params.options)
+                    .map_err(|err| Error::server(&err))
+                    .and_then(|report| to_value(&report))
+            }
+            "comparisonReport" => to_value(&session.comparison_report()),
+            "clearComparison" => {
+                session.clear_comparison();
+                Ok(Value::Null)
+            }
+            "shiftTimestamps" => {
+                let correction: Correction = req.params()?;
+                session
+                    .shift_timestamps(&correction)
+                    .map_err(|err| Error::server(&err))
+                    .and_then(|shift| to_value(&shift))
+            }
+            "timeShift" => to_value(&session.time_shift()),
+            "clearTimeShift" => {
+                session.set_time_shift(None);
+                Ok(Value::Null)
+            }
+            "startRingBuffer" => {
+                let params: StartRingBuffer = req.params()?;
+                let arg = access.arg(&params.id, params.arg)?;
+                session
+                    .start_ring_buffer(&params.id, &arg, &params.options)
+                    .map(|_| Value::Null)
+                    .map_err(|err| Error::server(&err))
+            }
+            "stopRingBuffer" => {
+                session.stop_ring_buffer();
+                Ok(Value::Null)
+            }
+            "setFrameWindow" => {
+                let params: SetFrameWindow = req.params()?;
+                session.set_frame_window(params.frames);
+                Ok(Value::Null)
+            }
+            "firstFrame" => Ok(json!(session.first_frame())),
+            "saveSession" => {
+                let params: SessionPath = req.params()?;
+                let path = access.resolve(&params.path)?;
+                session
+                    .save(Path::new(&path))
+                    .map(|_| Value::Null)
+                    .map_err(|err| Error::server(&err.to_string()))
+            }
+            "restoreSession" => {
+                let params: SessionPath = req.params()?;
+                let path = access.resolve(&params.path)?;
+                let mut file =
+                    SessionFile::load(Path::new(&path)).map_err(|err| Error::server(&err))?;
+                // The session file may name inputs outside of the root.
+                for input in &mut file.inputs {
+                    input.arg = access.arg(&input.reader, Value::String(input.arg.clone()))?;
+                }
+                session
+                    .restore_file(file)
+                    .map(|_| Value::Null)
+                    .map_err(|err| Error::server(&err))
+            }
+            "sortFrames" => {
+                let params: SortFrames = req.params()?;
+                let filter = compile(params.filter.as_deref())?;
+                session
+                    .sort_frames(params.id, &params.expr, filter.as_ref(), params.descending)
+                    .map(|len| json!(len))
+                    .map_err(|err| Error::invalid_params(&err))
+            }
+            "sortedFrames" => {
+                let params: FilteredRange = req.params()?;
+                to_value(&session.sorted_frames(params.id, params.start..params.end))
+            }
+            "clearSort" => {
+                let params: FilterId = req.params()?;
+                session.clear_sort(params.id);
+                Ok(Value::Null)
+            }
+            "setConfig" => {
+                let params: SetConfig = req.params()?;
+                access.check_config(&params.key)?;
+                session
+                    .set_config(&params.key, &params.value.to_string())
+                    .map(|_| Value::Null)
+                    .map_err(|err| Error::invalid_params(&err))
+            }
+            "valueCounts" => {
+                let params: ValueCounts = req.params()?;
+                let filter = compile(params.filter.as_deref())?;
+                to_value(&session.value_counts(&params.id, filter.as_ref(), params.top))
+            }
+            "conversations" => {
+                let params: Level = req.params()?;
+                match session.conversations(&params.level) {
+                    Some(stats) => to_value(&stats),
+                    None => Err(Error::invalid_params(&format!(
+                        "unknown level: {}",
+                        params.level
+                    ))),
+                }
+            }
+            "coverage" => {
+                let params: FilterParams = req.params()?;
+                let filter = compile(params.filter.as_deref())?;
+                to_value(&session.coverage(filter.as_ref()))
+            }
+            "expertSummary" => to_value(&session.expert_summary()),
+            "attributeCatalog" => to_value(&session.attribute_catalog()),
+            "pipelineStats" => to_value(&session.pipeline_stats()),
+            "captureStats" => {
+                let params: Handle = req.params()?;
+                to_value(&session.capture_stats(params.handle))
+            }
+            method => Err(Error::method_not_found(method)),
+        }
+    }
+}
+
+fn create_reader(session: &mut Session, id: &str, arg: &str) -> Result<Value, Error> {
+    match session.create_reader(id, arg) {
+        0 => Err(Error::server(&format!(
+            "{}: failed to create the reader",
+            id
+        ))),
+        handle => Ok(json!(handle)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        context::Context,
+        error::Error,
+        fixed::Fixed,
+        layer::{Layer, LayerClass},
+        reader::{Metadata, Reader, ReaderBox, Worker},
+        result::Result,
+        slice::ByteSlice,
+    };
+    use genet_kernel::profile::Profile;
+    use rpc::{Error as RpcError, Request};
+    use serde_json::Value;
+    use server::{Access, Connection};
+    use std::{
+        env, fs,
+        io::{self, Cursor, Write},
+        process,
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct TestWorker {
+        count: u64,
+    }
+
+    impl Worker for TestWorker {
+        fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
+            if self.count == 0 {
+                return Err(Box::new(Error::new("end of stream")));
+            }
+            let layers = (0..self.count)
+                .map(|i| {
+                    let class = Fixed::new(LayerClass::builder("eth").build());
+                    let mut layer = Layer::new(class, ByteSlice::new());
+                    let class = Fixed::new(AttrClass::builder("eth.len").build());
+                    layer.add_attr(Attr::builder(class).value(i).build());
+                    let class = Fixed::new(AttrClass::builder("link.timestamp.sec").build());
+                    layer.add_attr(Attr::builder(class).value(i).build());
+                    layer
+                })
+                .collect();
+            self.count = 0;
+            Ok(layers)
+        }
+    }
+
+    struct TestReader {}
+
+    impl Reader for TestReader {
+        fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+            Ok(Box::new(TestWorker {
+                count: arg.parse().unwrap(),
+            }))
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                id: "test".to_string(),
+                ..Metadata::default()
+            }
+        }
+    }
+
+    fn try_call(
+        conn: &mut Connection,
+        method: &str,
+        params: Value,
+    ) -> ::std::result::Result<Value, RpcError> {
+        let req = json!({ "jsonrpc": "2.0", "id": 0, "method": method, "params": params });
+        conn.call(&Request::parse(&req.to_string()).unwrap())
+    }
+
+    fn call(conn: &mut Connection, method: &str, params: Value) -> Value {
+        try_call(conn, method, params).unwrap()
+    }
+
+    fn wait<F: Fn(&mut Connection) -> bool>(conn: &mut Connection, f: F) {
+        for _ in 0..1000 {
+            if f(conn) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("timed out");
+    }
+
+    #[test]
+    fn session() {
+        let mut profile = Profile::new();
+        profile.add_reader(ReaderBox::new(TestReader {}));
+        let output = Output::default();
+        let mut conn = Connection::new(profile, Box::new(output.clone()), Access::default());
+
+        assert_eq!(
+            call(&mut conn, "createReader", json!({"id": "test", "arg": 5})),
+            1
+        );
+        wait(&mut conn, |conn| {
+            call(conn, "status", Value::Null) == json!({"frames": 5, "pendingFrames": 0})
+        });
+
+        let frames = call(&mut conn, "frames", json!({"start": 1, "end": 3}));
+        assert_eq!(frames[1]["index"], 2);
+
+        let frame = call(&mut conn, "frame", json!({"index": 3}));
+        assert_eq!(frame["id"], "eth");
+        assert_eq!(frame["attrs"][0]["value"], 3);
+
+        call(
+            &mut conn,
+            "setFilter",
+            json!({"id": 1, "filter": "eth.len >= 2"}),
+        );
+        wait(&mut conn, |conn| {
+            call(
+                conn,
+                "filteredFrames",
+                json!({"id": 1, "start": 0, "end": 10}),
+            ) == json!([2, 3, 4])
+        });
+        assert_eq!(call(&mut conn, "filteredLength", json!({"id": 1})), 3);
+        assert_eq!(
+            call(&mut conn, "filteredRank", json!({"id": 1, "index": 3})),
+            1
+        );
+        assert_eq!(
+            call(&mut conn, "filteredSelect", json!({"id": 1, "row": 2})),
+            4
+        );
+        assert_eq!(
+            call(&mut conn, "filteredSelect", json!({"id": 1, "row": 3})),
+            Value::Null
+        );
+
+        call(
+            &mut conn,
+            "setColumns",
+            json!({"columns": [{"title": "Length", "expr": "eth.len", "format": "hex"}]}),
+        );
+        assert_eq!(
+            call(
+                &mut conn,
+                "columnRows",
+                json!({"start": 0, "end": 10, "filter": 1})
+            ),
+            json!([
+                {"index": 2, "values": ["0x2"]},
+                {"index": 3, "values": ["0x3"]},
+                {"index": 4, "values": ["0x4"]},
+            ])
+        );
+
+        let rules = json!({"rules": [
+            {"name": "Short", "filter": "eth.len < 2", "background": "#eee"},
+            {"name": "Even", "filter": "eth.len == 2 || eth.len == 4", "foreground": "red"},
+        ]});
+        call(&mut conn, "setColorRules", rules);
+        assert_eq!(
+            call(&mut conn, "frameColors", json!({"start": 0, "end": 10})),
+            json!([0, 0, 1, null, 1])
+        );
+        assert_eq!(
+            call(
+                &mut conn,
+                "frameColors",
+                json!({"start": 0, "end": 2, "filter": 1})
+            ),
+            json!([1, null])
+        );
+
+        call(
+            &mut conn,
+            "setComment",
+            json!({"index": 3, "comment": "retransmission"}),
+        );
+        let bytes = json!([{"start": 0, "end": 2, "text": "header"}]);
+        call(
+            &mut conn,
+            "setByteAnnotations",
+            json!({"index": 1, "annotations": bytes}),
+        );
+        assert_eq!(
+            call(&mut conn, "commentedFrames", Value::Null),
+            json!([1, 3])
+        );
+        assert_eq!(
+            call(&mut conn, "frameAnnotations", json!({"index": 1})),
+            json!({"comment": null, "bytes": bytes})
+        );
+
+        call(
+            &mut conn,
+            "setMarked",
+            json!({"indices": [1, 2, 4], "marked": true}),
+        );
+        call(
+            &mut conn,
+            "setMarked",
+            json!({"indices": [2], "marked": false}),
+        );
+        assert_eq!(call(&mut conn, "markedFrames", Value::Null), json!([1, 4]));
+        call(
+            &mut conn,
+            "setFilter",
+            json!({"id": 2, "filter": "frame.marked"}),
+        );
+        wait(&mut conn, |conn| {
+            call(
+                conn,
+                "filteredFrames",
+                json!({"id": 2, "start": 0, "end": 10}),
+            ) == json!([1, 4])
+        });
+
+        let sort =
+            json!({"id": 1, "expr": "eth.len", "filter": "eth.len != 2", "descending": true});
+        assert_eq!(call(&mut conn, "sortFrames", sort), 4);
+        assert_eq!(
+            call(
+                &mut conn,
+                "sortedFrames",
+                json!({"id": 1, "start": 1, "end": 10})
+            ),
+            json!([3, 1, 0])
+        );
+
+        call(
+            &mut conn,
+            "setIgnored",
+            json!({"indices": [0], "ignored": true}),
+        );
+        assert_eq!(call(&mut conn, "ignoredFrames", Value::Null), json!([0]));
+
+        let counts = call(&mut conn, "valueCounts", json!({"id": "eth.len", "top": 1}));
+        assert_eq!(counts[0]["count"], 1);
+
+        let input = r#"{"jsonrpc":"2.0","id":1,"method":"frame","params":{"index":9}}
+{"jsonrpc":"2.0","id":2,"method":"unknown"}
+{"jsonrpc":"2.0","method":"status"}
+{"#;
+        conn.serve(Cursor::new(input)).unwrap();
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let messages = output
+            .lines()
+            .map(|line| ::serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        let events = messages.iter().filter(|msg| msg["method"] == "event");
+        assert!(events.clone().any(|msg| msg["params"]["type"] == "frames"));
+        assert!(events.clone().any(
+            |msg| msg["params"] == json!({"type": "input", "id": 1, "error": "end of stream"})
+        ));
+        let responses = messages
+            .iter()
+            .filter(|msg| msg.get("method").is_none())
+            .collect::<Vec<_>>();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["error"]["code"], -32602);
+        assert_eq!(responses[1]["error"]["code"], -32601);
+        assert_eq!(responses[2]["id"], Value::Null);
+        assert_eq!(responses[2]["error"]["code"], -32700);
+    }
+
+    #[test]
+    fn compare() {
+        let mut profile = Profile::new();
+        profile.add_reader(ReaderBox::new(TestReader {}));
+        let mut conn = Connection::new(profile, Box::new(Output::default()), Access::default());
+        call(&mut conn, "createReader", json!({"id": "test", "arg": 5}));
+        wait(&mut conn, |conn| {
+            call(conn, "status", Value::Null) == json!({"frames": 5, "pendingFrames": 0})
+        });
+        assert_eq!(
+            call(&mut conn, "comparisonReport", Value::Null),
+            Value::Null
+        );
+
+        let report = call(
+            &mut conn,
+            "compare",
+            json!({"id": "test", "arg": 3, "options": {"window": 0.5}}),
+        );
+        assert_eq!(report["frames"], 5);
+        assert_eq!(report["peerFrames"], 3);
+        assert_eq!(report["matched"], 3);
+        assert_eq!(report["dropped"], json!([3, 4]));
+        assert_eq!(call(&mut conn, "comparisonReport", Value::Null), report);
+
+        let status = |conn: &mut Connection, index: u32| {
+            let frame = call(conn, "frame", json!({ "index": index }));
+            frame["attrs"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|attr| attr["id"] == "compare.status")
+                .map(|attr| attr["value"].clone())
+        };
+        wait(&mut conn, |conn| status(conn, 4) == Some(json!("dropped")));
+        assert_eq!(status(&mut conn, 0), Some(json!("matched")));
+
+        call(&mut conn, "clearComparison", Value::Null);
+        assert_eq!(
+            call(&mut conn, "comparisonReport", Value::Null),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn shift_timestamps() {
+        let mut profile = Profile::new();
+        profile.add_reader(ReaderBox::new(TestReader {}));
+        let mut conn = Connection::new(profile, Box::new(Output::default()), Access::default());
+        call(&mut conn, "createReader", json!({"id": "test", "arg": 5}));
+        wait(&mut conn, |conn| {
+            call(conn, "status", Value::Null) == json!({"frames": 5, "pendingFrames": 0})
+        });
+        assert_eq!(call(&mut conn, "timeShift", Value::Null), Value::Null);
+
+        let value = |conn: &mut Connection, index: u32, id: &str| {
+            let frame = call(conn, "frame", json!({ "index": index }));
+            frame["attrs"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|attr| attr["id"] == id)
+                .map(|attr| attr["value"].clone())
+        };
+
+        // Frame 1 is 10 seconds behind and frame 3 is 12 seconds behind.
+        let shift = call(
+            &mut conn,
+            "shiftTimestamps",
+            json!({"skew": [{"frame": 1, "time": 11.0}, {"frame": 3, "time": 15.0}]}),
+        );
+        assert_eq!(shift["rate"], 1.0);
+        assert_eq!(call(&mut conn, "timeShift", Value::Null), shift);
+        wait(&mut conn, |conn| {
+            value(conn, 2, "link.timestamp.sec") == Some(json!(13))
+        });
+        assert_eq!(value(&mut conn, 2, "frame.time_shift"), Some(json!(11.0)));
+        assert_eq!(value(&mut conn, 4, "frame.time_delta"), Some(json!(2.0)));
+
+        call(&mut conn, "shiftTimestamps", json!({"offset": -1.0}));
+        wait(&mut conn, |conn| {
+            value(conn, 2, "link.timestamp.sec") == Some(json!(1))
+        });
+
+        call(&mut conn, "clearTimeShift", Value::Null);
+        assert_eq!(call(&mut conn, "timeShift", Value::Null), Value::Null);
+        wait(&mut conn, |conn| {
+            value(conn, 2, "frame.time_shift").is_none()
+        });
+        assert_eq!(value(&mut conn, 2, "link.timestamp.sec"), Some(json!(2)));
+    }
+
+    #[test]
+    fn save_restore() {
+        let profile = || {
+            let mut profile = Profile::new();
+            profile.add_reader(ReaderBox::new(TestReader {}));
+            profile
+        };
+        let loaded = |conn: &mut Connection| {
+            call(conn, "status", Value::Null) == json!({"frames": 5, "pendingFrames": 0})
+        };
+        let path = env::temp_dir().join(format!("genet-server-{}.json", process::id()));
+        let path = json!({"path": path.to_str().unwrap()});
+
+        let mut conn = Connection::new(profile(), Box::new(Output::default()), Access::default());
+        call(&mut conn, "createReader", json!({"id": "test", "arg": 5}));
+        wait(&mut conn, loaded);
+        call(
+            &mut conn,
+            "setFilter",
+            json!({"id": 1, "filter": "eth.len >= 3"}),
+        );
+        call(
+            &mut conn,
+            "setComment",
+            json!({"index": 4, "comment": "last"}),
+        );
+        call(&mut conn, "saveSession", path.clone());
+
+        let mut conn = Connection::new(profile(), Box::new(Output::default()), Access::default());
+        call(&mut conn, "restoreSession", path.clone());
+        wait(&mut conn, loaded);
+        wait(&mut conn, |conn| {
+            call(conn, "filteredLength", json!({"id": 1})) == 2
+        });
+        assert_eq!(call(&mut conn, "commentedFrames", Value::Null), json!([4]));
+        fs::remove_file(path["path"].as_str().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn frame_window() {
+        let mut profile = Profile::new();
+        profile.add_reader(ReaderBox::new(TestReader {}));
+        let mut conn = Connection::new(profile, Box::new(Output::default()), Access::default());
+        call(&mut conn, "setFrameWindow", json!({"frames": 2}));
+        call(&mut conn, "createReader", json!({"id": "test", "arg": 5}));
+        wait(&mut conn, |conn| {
+            call(conn, "firstFrame", Value::Null) == json!(3)
+        });
+        assert_eq!(
+            call(&mut conn, "status", Value::Null),
+            json!({"frames": 5, "pendingFrames": 0})
+        );
+        let frames = call(&mut conn, "frames", json!({"start": 0, "end": 5}));
+        let indices = frames
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|frame| frame["index"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(indices, vec![json!(3), json!(4)]);
+    }
+
+    #[test]
+    fn access() {
+        let root = env::temp_dir().join(format!("genet-server-root-{}", process::id()));
+        fs::create_dir_all(root.join("captures")).unwrap();
+        let root = root.canonicalize().unwrap();
+        let access = Access {
+            token: Some("secret".to_string()),
+            root: Some(root.clone()),
+        };
+        let mut profile = Profile::new();
+        profile.add_reader(ReaderBox::new(TestReader {}));
+        let mut conn = Connection::new(profile, Box::new(Output::default()), access);
+
+        let unauthorized =
+            |result: ::std::result::Result<Value, RpcError>| result.unwrap_err().code == -32001;
+        assert!(unauthorized(try_call(&mut conn, "status", Value::Null)));
+        assert!(unauthorized(try_call(
+            &mut conn,
+            "authenticate",
+            json!({"token": "secrets"})
+        )));
+        call(&mut conn, "authenticate", json!({"token": "secret"}));
+        call(&mut conn, "status", Value::Null);
+
+        let denied = |result: ::std::result::Result<Value, RpcError>| {
+            result.unwrap_err().message.ends_with("outside of the root")
+        };
+        assert!(denied(try_call(
+            &mut conn,
+            "open",
+            json!({"path": "/etc/passwd", "reader": "test"})
+        )));
+        assert!(denied(try_call(
+            &mut conn,
+            "open",
+            json!({"path": "captures/../../dump.pcap", "reader": "test"})
+        )));
+        assert!(denied(try_call(
+            &mut conn,
+            "createReader",
+            json!({"id": "test", "arg": {"file": "../dump.pcap"}})
+        )));
+        assert!(denied(try_call(
+            &mut conn,
+            "createReader",
+            json!({"id": "test", "arg": "{\"file\": \"/tmp/dump.pcap\"}"})
+        )));
+        assert!(denied(try_call(
+            &mut conn,
+            "saveSession",
+            json!({"path": "/tmp/session.json"})
+        )));
+        assert!(denied(try_call(
+            &mut conn,
+            "restoreSession",
+            json!({"path": "../session.json"})
+        )));
+        assert!(try_call(
+            &mut conn,
+            "setConfig",
+            json!({"key": "_.frameIndex.dir", "value": "/tmp"})
+        )
+        .is_err());
+        for key in &["_.resolver", "_.resolver.hosts", "@genet/tls.keyLogFile"] {
+            assert!(
+                try_call(&mut conn, "setConfig", json!({"key": key, "value": "/tmp"})).is_err()
+            );
+        }
+
+        // Other keys naming files are resolved, and unknown keys are rejected
+        // since they may name a file or a program.
+        assert!(denied(try_call(
+            &mut conn,
+            "writeRawFrames",
+            json!({"id": "test", "arg": {"directory": "/tmp"}, "frames": []})
+        )));
+        let not_allowed = |result: ::std::result::Result<Value, RpcError>| {
+            result.unwrap_err().message.ends_with("not allowed")
+        };
+        assert!(not_allowed(try_call(
+            &mut conn,
+            "createReader",
+            json!({"id": "test", "arg": {"file": "captures/a.pcap", "identity": "/etc/shadow"}})
+        )));
+        assert!(not_allowed(try_call(
+            &mut conn,
+            "compare",
+            json!({"id": "test", "arg": "{\"cmd\": \"/bin/sh\", \"args\": []}"})
+        )));
+        assert!(not_allowed(try_call(
+            &mut conn,
+            "createReader",
+            json!({"id": "app.genet.reader.remote", "arg": {"interface": "eth0"}})
+        )));
+
+        call(&mut conn, "createReader", json!({"id": "test", "arg": 5}));
+        call(
+            &mut conn,
+            "saveSession",
+            json!({"path": "captures/session.json"}),
+        );
+        assert!(root.join("captures/session.json").exists());
+
+        // Inputs of a session file are restricted as well.
+        let session = json!({
+            "version": 1,
+            "inputs": [{"reader": "test", "arg": "{\"file\": \"/etc/passwd\"}"}],
+        });
+        fs::write(root.join("session.json"), session.to_string()).unwrap();
+        let mut conn = Connection::new(
+            Profile::new(),
+            Box::new(Output::default()),
+            Access {
+                token: None,
+                root: Some(root.clone()),
+            },
+        );
+        assert!(denied(try_call(
+            &mut conn,
+            "restoreSession",
+            json!({"path": "session.json"})
+        )));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+const program = require('commander')
+const { table } = require('table')
+const listPackages = require('./lib/list')
+const { fetchIndex, findUpdates } = require('./lib/registry')
+const {
+  version, genetVersion, genetAbiVersion, userPackagePath, packageIndex,
+} = require('./lib/env')
+program
+  .version(version)
+  .option('-i, --index <url>', 'specify package index')
+  .option('-j, --json', 'use JSON format')
+  .option('-d, --dst <path>', 'specify installation directory')
+  .parse(process.argv)
+
+const dst = program.dst || userPackagePath
+const index = program.index || packageIndex
+if (!index) {
+  console.error('No package index is specified.')
+  console.error('Use --index option or set GENET_PACKAGE_INDEX.')
+  process.exit(1)
+}
+
+Promise.all([fetchIndex(index), listPackages({ userDirs: [dst] })])
+  .then(([packages, installed]) => {
+    const updates =
+      findUpdates(packages, installed, genetVersion, genetAbiVersion)
+    if (program.json) {
+      console.log(JSON.stringify(updates))
+    } else if (updates.length === 0) {
+      console.log('All packages are up to date.')
+    } else {
+      console.log(table(updates.map((update) => [
+        update.name,
+        update.current,
+        update.latest.version
+      ])))
+    }
+    process.exit(0)
+  })
+  .catch((err) => {
+    console.error(`${err.message}`)
+    process.exit(1)
+  })
@@ -0,0 +1,69 @@
+const program = require('commander')
+const semver = require('semver')
+const install = require('./lib/install')
+const listPackages = require('./lib/list')
+const verify = require('./lib/verify')
+const { cargo, npm } = require('./lib/build')
+const {
+  fetchIndex, findUpdates, loadKeys, download,
+} = require('./lib/registry')
+const {
+  version, genetAbiVersion, genetVersion, genetTarget,
+  userPackagePath, trustedKeyPath, packageIndex,
+} = require('./lib/env')
+program
+  .version(version)
+  .option('-i, --index <url>', 'specify package index')
+  .option('-s, --sdk <ver>', 'specify SDK version',
+    (val) => semver.valid(semver.coerce(val)) || false)
+  .option('-d, --dst <path>', 'specify installation directory')
+  .option('-k, --keys <path>', 'specify trusted key directory')
+  .usage('[packages...]')
+  .parse(process.argv)
+
+const dst = program.dst || userPackagePath
+const index = program.index || packageIndex
+if (!index) {
+  console.error('No package index is specified.')
+  console.error('Use --index option or set GENET_PACKAGE_INDEX.')
+  process.exit(1)
+}
+
+const sdkVersion = program.sdk || genetAbiVersion
+async function run (update, keys) {
+  const { latest } = update
+  console.log(`updating ${update.name} ${update.current} -> ${latest.version}`)
+  const dir = await download(latest, keys)
+  await verify(dir, genetVersion)
+  await npm(dir)
+  await cargo(dir, sdkVersion, genetTarget)
+  await install(latest.url, dst, dir)
+}
+
+async function runall () {
+  const [packages, installed, keys] = await Promise.all([
+    fetchIndex(index),
+    listPackages({ userDirs: [dst] }),
+    loadKeys(program.keys || trustedKeyPath)
+  ])
+  const updates = findUpdates(packages, installed, genetVersion, sdkVersion)
+    .filter((update) => program.args.length === 0 ||
+      program.args.includes(update.name) ||
+      program.args.includes(update.id))
+  for (const update of updates) {
+    await run(update, keys)
+  }
+  return updates
+}
+
+runall()
+  .then((updates) => {
+    console.log(updates.length > 0
+      ? '✔ updated'
+      : 'All packages are up to date.')
+    process.exit(0)
+  })
+  .catch((err) => {
+    console.error(`${err.message}`)
+    process.exit(1)
+  })
@@ -8,5 +8,7 @@ program
   .command('install', 'install package')
   .command('uninstall', 'uninstall package')
   .command('list', 'list local packages')
+  .command('outdated', 'list packages with available updates')
+  .command('update', 'update packages from the package index')
   .command('init', 'create a new package template')
   .parse(process.argv)
@@ -32,4 +32,7 @@ module.exports = {
   resourcePath,
   builtinPackagePath,
   userPackagePath: path.join(os.homedir(), '.genet', 'package'),
+  trustedKeyPath: process.env.GENET_TRUSTED_KEYS ||
+    path.join(os.homedir(), '.genet', 'trusted-keys'),
+  packageIndex: process.env.GENET_PACKAGE_INDEX || null,
 }
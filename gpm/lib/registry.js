@@ -0,0 +1,132 @@
+const axios = require('axios')
+const crypto = require('crypto')
+const fs = require('fs-extra')
+const glob = require('glob')
+const objpath = require('object-path')
+const path = require('path')
+const semver = require('semver')
+const tempy = require('tempy')
+const unzipper = require('unzipper')
+
+function isRemote (url) {
+  return /^https?:\/\//.test(url)
+}
+
+async function readSource (url) {
+  if (isRemote(url)) {
+    const { data } = await axios.get(url, { responseType: 'arraybuffer' })
+    return Buffer.from(data)
+  }
+  return fs.readFile(url)
+}
+
+async function fetchIndex (url) {
+  const index = JSON.parse((await readSource(url)).toString('utf8'))
+  if (!Array.isArray(index.packages)) {
+    throw new Error(`invalid package index: ${url}`)
+  }
+  return index.packages
+}
+
+function isCompatible (entry, genetVersion, abiVersion) {
+  if (!semver.valid(entry.version)) {
+    return false
+  }
+  const engine = objpath.get(entry, 'engines.genet', '*')
+  if (genetVersion &&
+    !semver.satisfies(semver.coerce(genetVersion), engine)) {
+    return false
+  }
+  return !abiVersion || !entry.abi ||
+    semver.satisfies(semver.coerce(abiVersion), entry.abi)
+}
+
+function latest (packages, name, genetVersion, abiVersion) {
+  return packages
+    .filter((entry) => entry.name === name &&
+      isCompatible(entry, genetVersion, abiVersion))
+    .sort((a, b) => semver.rcompare(a.version, b.version))[0] || null
+}
+
+function findUpdates (packages, installed, genetVersion, abiVersion) {
+  const updates = []
+  for (const pkg of installed) {
+    const { name, version } = pkg.metadata
+    const entry = latest(packages, name, genetVersion, abiVersion)
+    if (entry && semver.valid(version) && semver.gt(entry.version, version)) {
+      updates.push({
+        id: pkg.id,
+        name,
+        current: version,
+        latest: entry,
+      })
+    }
+  }
+  return updates
+}
+
+async function loadKeys (dir) {
+  const files = glob.sync(path.join(dir, '*.pem'))
+  return Promise.all(files.map((file) => fs.readFile(file, 'utf8')))
+}
+
+function verifySignature (data, signature, keys) {
+  return keys.some((key) => {
+    try {
+      return crypto.createVerify('SHA256')
+        .update(data)
+        .verify(key, signature, 'base64')
+    } catch (err) {
+      return false
+    }
+  })
+}
+
+async function extract (data) {
+  const dir = tempy.directory()
+  await new Promise((resolve, reject) => {
+    const stream = unzipper.Extract({ path: dir })
+    stream.on('close', resolve)
+    stream.on('error', reject)
+    stream.end(data)
+  })
+  if (await fs.pathExists(path.join(dir, 'package.json'))) {
+    return dir
+  }
+  const entries = await fs.readdir(dir)
+  if (entries.length === 1) {
+    return path.join(dir, entries[0])
+  }
+  return dir
+}
+
+async function download (entry, keys) {
+  if (!entry.signature) {
+    throw new Error(`package is not signed: ${entry.name}@${entry.version}`)
+  }
+  if (keys.length === 0) {
+    throw new Error('no trusted keys to verify packages')
+  }
+  const data = await readSource(entry.url)
+  if (entry.sha256) {
+    const digest = crypto.createHash('sha256').update(data)
+      .digest('hex')
+    if (digest !== entry.sha256.toLowerCase()) {
+      throw new Error(`checksum mismatch: ${entry.name}@${entry.version}`)
+    }
+  }
+  if (!verifySignature(data, entry.signature, keys)) {
+    throw new Error(`invalid signature: ${entry.name}@${entry.version}`)
+  }
+  return extract(data)
+}
+
+module.exports = {
+  fetchIndex,
+  isCompatible,
+  latest,
+  findUpdates,
+  loadKeys,
+  verifySignature,
+  download,
+}
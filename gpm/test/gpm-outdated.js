@@ -0,0 +1,74 @@
+/* eslint-env mocha */
+
+const assert = require('assert')
+const execa = require('execa')
+const path = require('path')
+const fs = require('fs-extra')
+const generateVersionFile = require('./lib/version')
+describe('gpm', () => {
+  describe('outdated --json', () => {
+    it('should show compatible updates from the index', () => {
+      const dir = generateVersionFile()
+      const index = path.join(dir, 'index.json')
+      fs.outputJsonSync(index, {
+        packages: [
+          {
+            name: '@genet/userpkg',
+            version: '0.2.0',
+            abi: '^0.2.0',
+            url: 'https://example.com/userpkg-0.2.0.zip',
+          },
+          {
+            name: '@genet/userpkg',
+            version: '0.3.0',
+            abi: '^0.9.0',
+            url: 'https://example.com/userpkg-0.3.0.zip',
+          },
+          {
+            name: '@genet/pkg',
+            version: '1.0.0',
+            url: 'https://example.com/pkg-1.0.0.zip',
+          }
+        ],
+      })
+      const { stdout } = execa.sync(
+        'node', [
+          './gpm.js', 'outdated', '--json', '--index', index,
+          '-d', path.join(dir, 'user')], {
+          stdio: 'pipe',
+          env: { GENET_VERSION_FILE: path.join(dir, '.version') },
+        })
+      const data = JSON.parse(stdout)
+      assert.deepEqual(data.map((update) => [
+        update.name,
+        update.current,
+        update.latest.version
+      ]), [['@genet/userpkg', '0.1.0', '0.2.0']])
+    })
+  })
+  describe('update', () => {
+    it('should fail when the package is not signed', () => {
+      const dir = generateVersionFile()
+      const index = path.join(dir, 'index.json')
+      fs.outputJsonSync(index, {
+        packages: [
+          {
+            name: '@genet/userpkg',
+            version: '0.2.0',
+            url: path.join(dir, 'userpkg.zip'),
+          }
+        ],
+      })
+      assert.throws(() => {
+        execa.sync(
+          'node', [
+            './gpm.js', 'update', '--index', index,
+            '-d', path.join(dir, 'user')], {
+            stdio: 'pipe',
+            env: { GENET_VERSION_FILE: path.join(dir, '.version') },
+          })
+      }, (err) => err.stderr ===
+        'package is not signed: @genet/userpkg@0.2.0\n')
+    })
+  })
+})
@@ -55,6 +55,14 @@ impl Worker for ArpWorker {
                 layer.add_attr(attr!(tha, range: offset..offset + hlen));
                 offset += hlen;
                 layer.add_attr(attr!(tpa, range: offset..offset + plen));
+
+                // A gratuitous ARP announces or updates the sender's own
+                // address.
+                let data = layer.data();
+                let spa = data.get(8 + hlen..8 + hlen + plen);
+                if spa.is_some() && spa == data.get(offset..offset + plen) {
+                    layer.add_attr(attr!(&GRATUITOUS_ATTR, range: 8..offset + plen, value: true));
+                }
             }
         }
 
@@ -114,6 +122,11 @@ def_attr_class!(OP_ATTR, "arp.op",
     typ: "@enum"
 );
 
+def_attr_class!(GRATUITOUS_ATTR, "arp.gratuitous",
+    typ: "@expert:note",
+    description: "Gratuitous ARP"
+);
+
 fn get_hw(val: u64) -> Option<(&'static AttrClass, &'static AttrClass, &'static AttrClass)> {
     match val {
         0x0001 => Some((
@@ -145,6 +158,10 @@ fn get_op(val: u64) -> Option<&'static AttrClass> {
     match val {
         0x0001 => Some(attr_class_lazy!("arp.op.request", typ: "@novalue", value: true)),
         0x0002 => Some(attr_class_lazy!("arp.op.reply", typ: "@novalue", value: true)),
+        0x0003 => Some(attr_class_lazy!("arp.op.reverseRequest", typ: "@novalue", value: true)),
+        0x0004 => Some(attr_class_lazy!("arp.op.reverseReply", typ: "@novalue", value: true)),
+        0x0008 => Some(attr_class_lazy!("arp.op.inverseRequest", typ: "@novalue", value: true)),
+        0x0009 => Some(attr_class_lazy!("arp.op.inverseReply", typ: "@novalue", value: true)),
         _ => None,
     }
 }
@@ -1,9 +1,38 @@
 const m = require('mithril')
 const moment = require('moment')
+const genet = require('@genet/api')
+const digits = {
+  s: 0,
+  ms: 3,
+  us: 6,
+  ns: 9,
+}
 class UNIXDate {
   view (vnode) {
-    const { value } = vnode.attrs.attr
-    return m('span', [moment(value).format()])
+    const { id, value } = vnode.attrs.attr
+    const zone = genet.config.get('_.timestamp.zone', 'local')
+    const resolution = genet.config.get('_.timestamp.resolution', 'us')
+    let date = moment.unix(Math.floor(value))
+    if (zone === 'utc') {
+      date = date.utc()
+    } else if (zone !== 'local') {
+      date = date.utcOffset(zone)
+    }
+
+    // The value has no nanosecond precision, so the fraction is taken from
+    // the nanosecond attribute if the layer has one.
+    const attrs = (vnode.attrs.layer && vnode.attrs.layer.attrs) || []
+    const nsecAttr = attrs.find((attr) => attr.id === `${id}.nsec`)
+    const nsec = nsecAttr
+      ? Number(nsecAttr.value)
+      : Math.floor((value - Math.floor(value)) * 1e9)
+    const frac = digits[resolution] > 0
+      ? `.${String(nsec).padStart(9, '0')
+        .slice(0, digits[resolution])}`
+      : ''
+    return m('span', [
+      date.format('YYYY-MM-DDTHH:mm:ss'), frac, date.format('Z')
+    ])
   }
 }
 module.exports = UNIXDate
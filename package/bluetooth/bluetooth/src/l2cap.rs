@@ -0,0 +1,239 @@
+//! L2CAP decoder for the frames carried by HCI ACL data packets.
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+/// The length of the basic header.
+const HEADER_LEN: usize = 4;
+
+/// The length of the header of a signaling command.
+const COMMAND_HEADER_LEN: usize = 4;
+
+const CID_SIGNALING: u16 = 0x0001;
+const CID_CONNECTIONLESS: u16 = 0x0002;
+const CID_ATT: u16 = 0x0004;
+const CID_LE_SIGNALING: u16 = 0x0005;
+const CID_SMP: u16 = 0x0006;
+
+struct L2capWorker {}
+
+impl Worker for L2capWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:l2cap"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&L2CAP_CLASS, data);
+        let cid = CID_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if let Some(attr) = get_cid(cid) {
+            layer.add_attr(attr!(attr, range: 2..4));
+        }
+        let len: u64 = LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let end = HEADER_LEN + len as usize;
+        if end > data.len() {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+        layer.add_attr(attr!(&PAYLOAD_ATTR, range: HEADER_LEN..end));
+
+        if cid == CID_SIGNALING || cid == CID_LE_SIGNALING {
+            // A C-frame on the BR/EDR signaling channel may carry several
+            // commands.
+            let mut offset = HEADER_LEN;
+            while offset + COMMAND_HEADER_LEN <= end {
+                let code = data[offset];
+                let len = usize::from(data[offset + 2]) | usize::from(data[offset + 3]) << 8;
+                let command_end = offset + COMMAND_HEADER_LEN + len;
+                if command_end > end {
+                    layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+                    break;
+                }
+                layer.add_attr(attr!(&SIGNAL_ATTR, range: offset..command_end));
+                layer.add_attr(attr!(&SIGNAL_CODE_ATTR, range: offset..offset + 1));
+                if let Some(attr) = get_signal_code(code) {
+                    layer.add_attr(attr!(attr, range: offset..offset + 1));
+                }
+                layer.add_attr(attr!(&SIGNAL_IDENTIFIER_ATTR, range: offset + 1..offset + 2));
+                layer.add_attr(attr!(&SIGNAL_LENGTH_ATTR, range: offset + 2..offset + 4));
+                layer.add_attr(
+                    attr!(&SIGNAL_DATA_ATTR, range: offset + COMMAND_HEADER_LEN..command_end),
+                );
+                offset = command_end;
+            }
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+pub struct L2capDecoder {}
+
+impl Decoder for L2capDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(L2capWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(L2CAP_CLASS, "l2cap",
+    header: &LENGTH_ATTR_HEADER,
+    header: &CID_ATTR_HEADER
+);
+
+def_attr!(LENGTH_ATTR_HEADER, &LENGTH_ATTR, range: 0..2);
+
+def_attr!(CID_ATTR_HEADER, &CID_ATTR, range: 2..4);
+
+def_attr_class!(LENGTH_ATTR, "l2cap.length", cast: cast::UInt16LE());
+
+def_attr_class!(CID_ATTR, "l2cap.cid",
+    cast: cast::UInt16LE(),
+    typ: "@enum"
+);
+
+fn get_cid(val: u16) -> Option<&'static AttrClass> {
+    match val {
+        CID_SIGNALING => {
+            Some(attr_class_lazy!("l2cap.cid.signaling", typ: "@novalue", value: true))
+        }
+        CID_CONNECTIONLESS => Some(attr_class_lazy!(
+            "l2cap.cid.connectionless",
+            typ: "@novalue",
+            value: true
+        )),
+        CID_ATT => Some(attr_class_lazy!("l2cap.cid.att", typ: "@novalue", value: true)),
+        CID_LE_SIGNALING => {
+            Some(attr_class_lazy!("l2cap.cid.leSignaling", typ: "@novalue", value: true))
+        }
+        CID_SMP => Some(attr_class_lazy!("l2cap.cid.smp", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+def_attr_class!(PAYLOAD_ATTR, "l2cap.payload", cast: cast::ByteSlice());
+
+def_attr_class!(SIGNAL_ATTR, "l2cap.signal",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(SIGNAL_CODE_ATTR, "l2cap.signal.code",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_signal_code(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0x01 => Some(attr_class_lazy!(
+            "l2cap.signal.code.commandReject",
+            typ: "@novalue",
+            value: true
+        )),
+        0x02 => Some(attr_class_lazy!(
+            "l2cap.signal.code.connectionRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        0x03 => Some(attr_class_lazy!(
+            "l2cap.signal.code.connectionResponse",
+            typ: "@novalue",
+            value: true
+        )),
+        0x04 => Some(attr_class_lazy!(
+            "l2cap.signal.code.configureRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        0x05 => Some(attr_class_lazy!(
+            "l2cap.signal.code.configureResponse",
+            typ: "@novalue",
+            value: true
+        )),
+        0x06 => Some(attr_class_lazy!(
+            "l2cap.signal.code.disconnectionRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        0x07 => Some(attr_class_lazy!(
+            "l2cap.signal.code.disconnectionResponse",
+            typ: "@novalue",
+            value: true
+        )),
+        0x08 => {
+            Some(attr_class_lazy!("l2cap.signal.code.echoRequest", typ: "@novalue", value: true))
+        }
+        0x09 => Some(attr_class_lazy!(
+            "l2cap.signal.code.echoResponse",
+            typ: "@novalue",
+            value: true
+        )),
+        0x0a => Some(attr_class_lazy!(
+            "l2cap.signal.code.informationRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        0x0b => Some(attr_class_lazy!(
+            "l2cap.signal.code.informationResponse",
+            typ: "@novalue",
+            value: true
+        )),
+        0x12 => Some(attr_class_lazy!(
+            "l2cap.signal.code.connectionParameterUpdateRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        0x13 => Some(attr_class_lazy!(
+            "l2cap.signal.code.connectionParameterUpdateResponse",
+            typ: "@novalue",
+            value: true
+        )),
+        0x14 => Some(attr_class_lazy!(
+            "l2cap.signal.code.leCreditBasedConnectionRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        0x15 => Some(attr_class_lazy!(
+            "l2cap.signal.code.leCreditBasedConnectionResponse",
+            typ: "@novalue",
+            value: true
+        )),
+        0x16 => Some(attr_class_lazy!(
+            "l2cap.signal.code.flowControlCredit",
+            typ: "@novalue",
+            value: true
+        )),
+        _ => None,
+    }
+}
+
+def_attr_class!(SIGNAL_IDENTIFIER_ATTR, "l2cap.signal.identifier", cast: cast::UInt8());
+
+def_attr_class!(SIGNAL_LENGTH_ATTR, "l2cap.signal.length", cast: cast::UInt16LE());
+
+def_attr_class!(SIGNAL_DATA_ATTR, "l2cap.signal.data", cast: cast::ByteSlice());
+
+def_attr_class!(MALFORMED_ATTR, "l2cap.malformed",
+    typ: "@expert:error",
+    description: "Length exceeding the frame"
+);
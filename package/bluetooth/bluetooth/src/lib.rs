@@ -0,0 +1,691 @@
+extern crate genet_sdk;
+
+mod l2cap;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+use l2cap::L2capDecoder;
+use std::collections::HashMap;
+
+/// The length of the direction pseudo-header of
+/// `DLT_BLUETOOTH_HCI_H4_WITH_PHDR`.
+const PHDR_LEN: usize = 4;
+
+const TYPE_COMMAND: u8 = 1;
+const TYPE_ACL: u8 = 2;
+const TYPE_SCO: u8 = 3;
+const TYPE_EVENT: u8 = 4;
+const TYPE_ISO: u8 = 5;
+
+const OPCODE_CREATE_CONNECTION: u16 = 0x0405;
+const OPCODE_DISCONNECT: u16 = 0x0406;
+const OPCODE_ACCEPT_CONNECTION_REQUEST: u16 = 0x0409;
+const OPCODE_READ_BD_ADDR: u16 = 0x1009;
+const OPCODE_LE_CREATE_CONNECTION: u16 = 0x200d;
+
+const EVENT_CONNECTION_COMPLETE: u8 = 0x03;
+const EVENT_DISCONNECTION_COMPLETE: u8 = 0x05;
+const EVENT_COMMAND_COMPLETE: u8 = 0x0e;
+const EVENT_COMMAND_STATUS: u8 = 0x0f;
+const EVENT_LE_META: u8 = 0x3e;
+
+const SUBEVENT_LE_CONNECTION_COMPLETE: u8 = 0x01;
+const SUBEVENT_LE_ADVERTISING_REPORT: u8 = 0x02;
+
+/// The packet boundary flag of a continuing fragment.
+const PB_CONTINUING: u16 = 0b01;
+
+/// The length of the basic L2CAP header.
+const L2CAP_HEADER_LEN: usize = 4;
+
+/// The length of a BD_ADDR.
+const BD_ADDR_LEN: usize = 6;
+
+fn u16_le(data: &ByteSlice, offset: usize) -> Result<u16> {
+    let bytes = data.try_get(offset..offset + 2)?;
+    Ok(u16::from(bytes[0]) | u16::from(bytes[1]) << 8)
+}
+
+/// Returns the range of the parameters or the data of `len` bytes at
+/// `offset`, or the rest of the packet if it is truncated.
+fn body(layer: &mut Layer, data: &ByteSlice, offset: usize, len: usize) -> Result<ByteSlice> {
+    let rest = data.try_get(offset..)?;
+    if len > rest.len() {
+        layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+        return Ok(rest);
+    }
+    Ok(rest.try_get(..len)?)
+}
+
+/// Adds a BD_ADDR, which is transmitted in little-endian.
+fn add_bd_addr(layer: &mut Layer, class: &'static AttrClass, data: &ByteSlice, offset: usize) {
+    if let Some(addr) = data.get(offset..offset + BD_ADDR_LEN) {
+        let addr: Vec<u8> = addr.iter().rev().cloned().collect();
+        layer.add_attr(
+            attr!(class, range: offset..offset + BD_ADDR_LEN, value: ByteSlice::from(addr)),
+        );
+    }
+}
+
+fn add_opcode(layer: &mut Layer, data: &ByteSlice, offset: usize) -> Result<u16> {
+    let opcode = u16_le(data, offset)?;
+    layer.add_attr(attr!(&OPCODE_ATTR, range: offset..offset + 2));
+    layer.add_attr(attr!(&OPCODE_OGF_ATTR, range: offset..offset + 2));
+    layer.add_attr(attr!(&OPCODE_OCF_ATTR, range: offset..offset + 2));
+    if let Some(attr) = get_command(opcode) {
+        layer.add_attr(attr!(attr, range: offset..offset + 2));
+    }
+    Ok(opcode)
+}
+
+fn command(layer: &mut Layer, data: &ByteSlice, offset: usize) -> Result<()> {
+    let opcode = add_opcode(layer, data, offset)?;
+    let len: u8 = data.try_get(offset + 2)?;
+    layer.add_attr(attr!(&PARAM_LENGTH_ATTR, range: offset + 2..offset + 3));
+    let start = offset + 3;
+    let params = body(layer, data, start, len.into())?;
+    layer.add_attr(attr!(&PARAMS_ATTR, range: start..start + params.len()));
+
+    match opcode {
+        OPCODE_CREATE_CONNECTION | OPCODE_ACCEPT_CONNECTION_REQUEST => {
+            add_bd_addr(layer, &BD_ADDR_ATTR, data, start);
+        }
+        OPCODE_DISCONNECT if params.len() >= 3 => {
+            layer.add_attr(attr!(&HANDLE_ATTR, range: start..start + 2));
+            layer.add_attr(attr!(&REASON_ATTR, range: start + 2..start + 3));
+        }
+        OPCODE_LE_CREATE_CONNECTION if params.len() >= 12 => {
+            layer.add_attr(attr!(&PEER_ADDRESS_TYPE_ATTR, range: start + 5..start + 6));
+            add_bd_addr(layer, &PEER_ADDRESS_ATTR, data, start + 6);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn event(layer: &mut Layer, data: &ByteSlice, offset: usize) -> Result<()> {
+    let code: u8 = data.try_get(offset)?;
+    layer.add_attr(attr!(&EVENT_ATTR, range: offset..offset + 1));
+    if let Some(attr) = get_event(code) {
+        layer.add_attr(attr!(attr, range: offset..offset + 1));
+    }
+    let len: u8 = data.try_get(offset + 1)?;
+    layer.add_attr(attr!(&PARAM_LENGTH_ATTR, range: offset + 1..offset + 2));
+    let start = offset + 2;
+    let params = body(layer, data, start, len.into())?;
+    layer.add_attr(attr!(&PARAMS_ATTR, range: start..start + params.len()));
+
+    match code {
+        EVENT_COMMAND_COMPLETE if params.len() >= 3 => {
+            layer.add_attr(attr!(&NUM_PACKETS_ATTR, range: start..start + 1));
+            let opcode = add_opcode(layer, data, start + 1)?;
+            if params.len() >= 4 {
+                layer.add_attr(attr!(&STATUS_ATTR, range: start + 3..start + 4));
+            }
+            if opcode == OPCODE_READ_BD_ADDR && params.len() >= 4 + BD_ADDR_LEN {
+                add_bd_addr(layer, &BD_ADDR_ATTR, data, start + 4);
+            }
+        }
+        EVENT_COMMAND_STATUS if params.len() >= 4 => {
+            layer.add_attr(attr!(&STATUS_ATTR, range: start..start + 1));
+            layer.add_attr(attr!(&NUM_PACKETS_ATTR, range: start + 1..start + 2));
+            add_opcode(layer, data, start + 2)?;
+        }
+        EVENT_CONNECTION_COMPLETE if params.len() >= 11 => {
+            layer.add_attr(attr!(&STATUS_ATTR, range: start..start + 1));
+            layer.add_attr(attr!(&HANDLE_ATTR, range: start + 1..start + 3));
+            add_bd_addr(layer, &BD_ADDR_ATTR, data, start + 3);
+            layer.add_attr(attr!(&LINK_TYPE_ATTR, range: start + 9..start + 10));
+            layer.add_attr(attr!(&ENCRYPTION_ATTR, range: start + 10..start + 11));
+        }
+        EVENT_DISCONNECTION_COMPLETE if params.len() >= 4 => {
+            layer.add_attr(attr!(&STATUS_ATTR, range: start..start + 1));
+            layer.add_attr(attr!(&HANDLE_ATTR, range: start + 1..start + 3));
+            layer.add_attr(attr!(&REASON_ATTR, range: start + 3..start + 4));
+        }
+        EVENT_LE_META if !params.is_empty() => le_meta(layer, data, start, &params),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn le_meta(layer: &mut Layer, data: &ByteSlice, start: usize, params: &ByteSlice) {
+    let subevent = params[0];
+    layer.add_attr(attr!(&SUBEVENT_ATTR, range: start..start + 1));
+    if let Some(attr) = get_subevent(subevent) {
+        layer.add_attr(attr!(attr, range: start..start + 1));
+    }
+
+    match subevent {
+        SUBEVENT_LE_CONNECTION_COMPLETE if params.len() >= 12 => {
+            layer.add_attr(attr!(&STATUS_ATTR, range: start + 1..start + 2));
+            layer.add_attr(attr!(&HANDLE_ATTR, range: start + 2..start + 4));
+            layer.add_attr(attr!(&ROLE_ATTR, range: start + 4..start + 5));
+            layer.add_attr(attr!(&PEER_ADDRESS_TYPE_ATTR, range: start + 5..start + 6));
+            add_bd_addr(layer, &PEER_ADDRESS_ATTR, data, start + 6);
+        }
+        SUBEVENT_LE_ADVERTISING_REPORT if params.len() >= 2 => {
+            layer.add_attr(attr!(&NUM_REPORTS_ATTR, range: start + 1..start + 2));
+            advertising_reports(layer, data, start + 2, params.len() - 2, params[1].into());
+        }
+        _ => {}
+    }
+}
+
+/// Adds the LE advertising reports, whose fields are laid out as arrays of
+/// the reports.
+fn advertising_reports(layer: &mut Layer, data: &ByteSlice, start: usize, len: usize, n: usize) {
+    let end = start + len;
+    let event_types = start;
+    let address_types = event_types + n;
+    let addresses = address_types + n;
+    let data_lengths = addresses + n * BD_ADDR_LEN;
+    let lengths = match data.get(data_lengths..data_lengths + n) {
+        Some(lengths) if data_lengths + n <= end => lengths,
+        _ => {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+            return;
+        }
+    };
+    let data_len: usize = lengths.iter().map(|len| usize::from(*len)).sum();
+    let rssis = data_lengths + n + data_len;
+    if rssis + n > end {
+        layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+        return;
+    }
+
+    let mut offset = data_lengths + n;
+    for i in 0..n {
+        let len = usize::from(lengths[i]);
+        layer.add_attr(attr!(&REPORT_ATTR, range: start..end));
+        layer.add_attr(attr!(&REPORT_EVENT_TYPE_ATTR, range: event_types + i..event_types + i + 1));
+        let event_type = data[event_types + i];
+        if let Some(attr) = get_report_event_type(event_type) {
+            layer.add_attr(attr!(attr, range: event_types + i..event_types + i + 1));
+        }
+        layer.add_attr(
+            attr!(&REPORT_ADDRESS_TYPE_ATTR, range: address_types + i..address_types + i + 1),
+        );
+        add_bd_addr(
+            layer,
+            &REPORT_ADDRESS_ATTR,
+            data,
+            addresses + i * BD_ADDR_LEN,
+        );
+        layer.add_attr(attr!(&REPORT_DATA_ATTR, range: offset..offset + len));
+        layer.add_attr(attr!(&REPORT_RSSI_ATTR, range: rssis + i..rssis + i + 1));
+        offset += len;
+    }
+}
+
+struct HciWorker {
+    /// The L2CAP frames being reassembled by the direction and the connection
+    /// handle.
+    fragments: HashMap<(Option<u32>, u16), Vec<u8>>,
+}
+
+impl HciWorker {
+    fn acl(
+        &mut self,
+        layer: &mut Layer,
+        data: &ByteSlice,
+        offset: usize,
+        direction: Option<u32>,
+    ) -> Result<()> {
+        let header = u16_le(data, offset)?;
+        layer.add_attr(attr!(&HANDLE_ATTR, range: offset..offset + 2));
+        layer.add_attr(attr!(&PB_ATTR, range: offset..offset + 2));
+        layer.add_attr(attr!(&BC_ATTR, range: offset..offset + 2));
+        let len = u16_le(data, offset + 2)?;
+        layer.add_attr(attr!(&DATA_LENGTH_ATTR, range: offset + 2..offset + 4));
+        let start = offset + 4;
+        let fragment = body(layer, data, start, len.into())?;
+        layer.add_attr(attr!(&DATA_ATTR, range: start..start + fragment.len()));
+
+        let key = (direction, header & 0x0fff);
+        let frame = if (header >> 12) & 0b11 == PB_CONTINUING {
+            let complete = match self.fragments.get_mut(&key) {
+                Some(frame) => {
+                    frame.extend_from_slice(&fragment);
+                    l2cap_len(frame).is_some_and(|len| frame.len() >= len)
+                }
+                None => false,
+            };
+            if !complete {
+                return Ok(());
+            }
+            layer.add_attr(attr!(&REASSEMBLED_ATTR, value: true));
+            ByteSlice::from(self.fragments.remove(&key).unwrap_or_default())
+        } else {
+            self.fragments.remove(&key);
+            match l2cap_len(&fragment) {
+                Some(len) if fragment.len() < len => {
+                    self.fragments.insert(key, fragment.to_vec());
+                    return Ok(());
+                }
+                Some(_) => fragment,
+                None => return Ok(()),
+            }
+        };
+        layer.add_payload(Payload::new(frame, "@data:l2cap"));
+        Ok(())
+    }
+}
+
+/// Returns the length of the L2CAP frame including the basic header.
+fn l2cap_len(frame: &[u8]) -> Option<usize> {
+    if frame.len() < 2 {
+        return None;
+    }
+    Some(L2CAP_HEADER_LEN + usize::from(u16::from(frame[0]) | u16::from(frame[1]) << 8))
+}
+
+impl Worker for HciWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data = parent.data();
+        let (offset, direction) = if parent.id() == token!("[link-187]") {
+            (0, None)
+        } else if parent.id() == token!("[link-201]") {
+            let phdr = data.try_get(..PHDR_LEN)?;
+            let direction = phdr.iter().fold(0u32, |v, b| v << 8 | u32::from(*b));
+            (PHDR_LEN, Some(direction))
+        } else {
+            return Ok(Status::Skip);
+        };
+
+        let mut layer = Layer::new(&HCI_CLASS, data);
+        if let Some(direction) = direction {
+            layer.add_attr(attr!(&DIRECTION_ATTR, range: 0..PHDR_LEN));
+            if let Some(attr) = get_direction(direction) {
+                layer.add_attr(attr!(attr, range: 0..PHDR_LEN));
+            }
+        }
+        let typ: u8 = data.try_get(offset)?;
+        layer.add_attr(attr!(&TYPE_ATTR, range: offset..offset + 1));
+        if let Some(attr) = get_type(typ) {
+            layer.add_attr(attr!(attr, range: offset..offset + 1));
+        }
+
+        let offset = offset + 1;
+        match typ {
+            TYPE_COMMAND => command(&mut layer, &data, offset)?,
+            TYPE_EVENT => event(&mut layer, &data, offset)?,
+            TYPE_ACL => self.acl(&mut layer, &data, offset, direction)?,
+            TYPE_SCO => {
+                layer.add_attr(attr!(&HANDLE_ATTR, range: offset..offset + 2));
+                let len: u8 = data.try_get(offset + 2)?;
+                layer.add_attr(attr!(&DATA_LENGTH_ATTR, range: offset + 2..offset + 3));
+                let start = offset + 3;
+                let sco = body(&mut layer, &data, start, len.into())?;
+                layer.add_attr(attr!(&DATA_ATTR, range: start..start + sco.len()));
+            }
+            TYPE_ISO => {
+                layer.add_attr(attr!(&HANDLE_ATTR, range: offset..offset + 2));
+                let len = u16_le(&data, offset + 2)? & 0x3fff;
+                layer.add_attr(attr!(&DATA_LENGTH_ATTR, range: offset + 2..offset + 4, value: len));
+                let start = offset + 4;
+                let iso = body(&mut layer, &data, start, len.into())?;
+                layer.add_attr(attr!(&DATA_ATTR, range: start..start + iso.len()));
+            }
+            _ => {}
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct HciDecoder {}
+
+impl Decoder for HciDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(HciWorker {
+            fragments: HashMap::new(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            // ACL fragments are reassembled across frames.
+            exec_type: ExecType::SerialSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(HCI_CLASS, "hci");
+
+def_attr_class!(DIRECTION_ATTR, "hci.direction",
+    cast: cast::UInt32BE(),
+    typ: "@enum"
+);
+
+fn get_direction(val: u32) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("hci.direction.sent", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!("hci.direction.received", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+def_attr_class!(TYPE_ATTR, "hci.type",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_type(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        TYPE_COMMAND => Some(attr_class_lazy!("hci.type.command", typ: "@novalue", value: true)),
+        TYPE_ACL => Some(attr_class_lazy!("hci.type.acl", typ: "@novalue", value: true)),
+        TYPE_SCO => Some(attr_class_lazy!("hci.type.sco", typ: "@novalue", value: true)),
+        TYPE_EVENT => Some(attr_class_lazy!("hci.type.event", typ: "@novalue", value: true)),
+        TYPE_ISO => Some(attr_class_lazy!("hci.type.iso", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+def_attr_class!(OPCODE_ATTR, "hci.opcode",
+    cast: cast::UInt16LE(),
+    typ: "@enum"
+);
+
+def_attr_class!(OPCODE_OGF_ATTR, "hci.opcode.ogf",
+    cast: cast::UInt16LE().map(|v| v >> 10)
+);
+
+def_attr_class!(OPCODE_OCF_ATTR, "hci.opcode.ocf",
+    cast: cast::UInt16LE().map(|v| v & 0x03ff)
+);
+
+fn get_command(val: u16) -> Option<&'static AttrClass> {
+    match val {
+        0x0401 => Some(attr_class_lazy!("hci.opcode.inquiry", typ: "@novalue", value: true)),
+        0x0402 => Some(attr_class_lazy!("hci.opcode.inquiryCancel", typ: "@novalue", value: true)),
+        OPCODE_CREATE_CONNECTION => Some(attr_class_lazy!(
+            "hci.opcode.createConnection",
+            typ: "@novalue",
+            value: true
+        )),
+        OPCODE_DISCONNECT => {
+            Some(attr_class_lazy!("hci.opcode.disconnect", typ: "@novalue", value: true))
+        }
+        OPCODE_ACCEPT_CONNECTION_REQUEST => Some(attr_class_lazy!(
+            "hci.opcode.acceptConnectionRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        0x040a => Some(attr_class_lazy!(
+            "hci.opcode.rejectConnectionRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        0x0419 => Some(attr_class_lazy!(
+            "hci.opcode.remoteNameRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        0x0c01 => Some(attr_class_lazy!("hci.opcode.setEventMask", typ: "@novalue", value: true)),
+        0x0c03 => Some(attr_class_lazy!("hci.opcode.reset", typ: "@novalue", value: true)),
+        0x0c13 => Some(attr_class_lazy!("hci.opcode.writeLocalName", typ: "@novalue", value: true)),
+        0x0c14 => Some(attr_class_lazy!("hci.opcode.readLocalName", typ: "@novalue", value: true)),
+        0x0c1a => {
+            Some(attr_class_lazy!("hci.opcode.writeScanEnable", typ: "@novalue", value: true))
+        }
+        0x1001 => Some(attr_class_lazy!(
+            "hci.opcode.readLocalVersionInformation",
+            typ: "@novalue",
+            value: true
+        )),
+        0x1002 => Some(attr_class_lazy!(
+            "hci.opcode.readLocalSupportedCommands",
+            typ: "@novalue",
+            value: true
+        )),
+        0x1003 => Some(attr_class_lazy!(
+            "hci.opcode.readLocalSupportedFeatures",
+            typ: "@novalue",
+            value: true
+        )),
+        OPCODE_READ_BD_ADDR => {
+            Some(attr_class_lazy!("hci.opcode.readBdAddr", typ: "@novalue", value: true))
+        }
+        0x2001 => Some(attr_class_lazy!("hci.opcode.leSetEventMask", typ: "@novalue", value: true)),
+        0x2002 => {
+            Some(attr_class_lazy!("hci.opcode.leReadBufferSize", typ: "@novalue", value: true))
+        }
+        0x2005 => Some(attr_class_lazy!(
+            "hci.opcode.leSetRandomAddress",
+            typ: "@novalue",
+            value: true
+        )),
+        0x2006 => Some(attr_class_lazy!(
+            "hci.opcode.leSetAdvertisingParameters",
+            typ: "@novalue",
+            value: true
+        )),
+        0x2008 => Some(attr_class_lazy!(
+            "hci.opcode.leSetAdvertisingData",
+            typ: "@novalue",
+            value: true
+        )),
+        0x200a => Some(attr_class_lazy!(
+            "hci.opcode.leSetAdvertisingEnable",
+            typ: "@novalue",
+            value: true
+        )),
+        0x200b => Some(attr_class_lazy!(
+            "hci.opcode.leSetScanParameters",
+            typ: "@novalue",
+            value: true
+        )),
+        0x200c => {
+            Some(attr_class_lazy!("hci.opcode.leSetScanEnable", typ: "@novalue", value: true))
+        }
+        OPCODE_LE_CREATE_CONNECTION => Some(attr_class_lazy!(
+            "hci.opcode.leCreateConnection",
+            typ: "@novalue",
+            value: true
+        )),
+        0x200e => Some(attr_class_lazy!(
+            "hci.opcode.leCreateConnectionCancel",
+            typ: "@novalue",
+            value: true
+        )),
+        _ => None,
+    }
+}
+
+def_attr_class!(EVENT_ATTR, "hci.event",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_event(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0x01 => Some(attr_class_lazy!("hci.event.inquiryComplete", typ: "@novalue", value: true)),
+        0x02 => Some(attr_class_lazy!("hci.event.inquiryResult", typ: "@novalue", value: true)),
+        EVENT_CONNECTION_COMPLETE => Some(attr_class_lazy!(
+            "hci.event.connectionComplete",
+            typ: "@novalue",
+            value: true
+        )),
+        0x04 => Some(attr_class_lazy!("hci.event.connectionRequest", typ: "@novalue", value: true)),
+        EVENT_DISCONNECTION_COMPLETE => Some(attr_class_lazy!(
+            "hci.event.disconnectionComplete",
+            typ: "@novalue",
+            value: true
+        )),
+        0x06 => Some(attr_class_lazy!(
+            "hci.event.authenticationComplete",
+            typ: "@novalue",
+            value: true
+        )),
+        0x07 => Some(attr_class_lazy!(
+            "hci.event.remoteNameRequestComplete",
+            typ: "@novalue",
+            value: true
+        )),
+        0x08 => Some(attr_class_lazy!("hci.event.encryptionChange", typ: "@novalue", value: true)),
+        EVENT_COMMAND_COMPLETE => {
+            Some(attr_class_lazy!("hci.event.commandComplete", typ: "@novalue", value: true))
+        }
+        EVENT_COMMAND_STATUS => {
+            Some(attr_class_lazy!("hci.event.commandStatus", typ: "@novalue", value: true))
+        }
+        0x10 => Some(attr_class_lazy!("hci.event.hardwareError", typ: "@novalue", value: true)),
+        0x13 => Some(attr_class_lazy!(
+            "hci.event.numberOfCompletedPackets",
+            typ: "@novalue",
+            value: true
+        )),
+        EVENT_LE_META => Some(attr_class_lazy!("hci.event.leMeta", typ: "@novalue", value: true)),
+        0xff => Some(attr_class_lazy!("hci.event.vendor", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+def_attr_class!(SUBEVENT_ATTR, "hci.subevent",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_subevent(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        SUBEVENT_LE_CONNECTION_COMPLETE => Some(attr_class_lazy!(
+            "hci.subevent.connectionComplete",
+            typ: "@novalue",
+            value: true
+        )),
+        SUBEVENT_LE_ADVERTISING_REPORT => Some(attr_class_lazy!(
+            "hci.subevent.advertisingReport",
+            typ: "@novalue",
+            value: true
+        )),
+        0x03 => Some(attr_class_lazy!(
+            "hci.subevent.connectionUpdateComplete",
+            typ: "@novalue",
+            value: true
+        )),
+        0x04 => Some(attr_class_lazy!(
+            "hci.subevent.readRemoteFeaturesComplete",
+            typ: "@novalue",
+            value: true
+        )),
+        0x05 => Some(attr_class_lazy!(
+            "hci.subevent.longTermKeyRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        0x0a => Some(attr_class_lazy!(
+            "hci.subevent.enhancedConnectionComplete",
+            typ: "@novalue",
+            value: true
+        )),
+        0x0d => Some(attr_class_lazy!(
+            "hci.subevent.extendedAdvertisingReport",
+            typ: "@novalue",
+            value: true
+        )),
+        _ => None,
+    }
+}
+
+def_attr_class!(PARAM_LENGTH_ATTR, "hci.paramLength", cast: cast::UInt8());
+
+def_attr_class!(PARAMS_ATTR, "hci.params", cast: cast::ByteSlice());
+
+def_attr_class!(NUM_PACKETS_ATTR, "hci.numPackets", cast: cast::UInt8());
+
+def_attr_class!(STATUS_ATTR, "hci.status", cast: cast::UInt8());
+
+def_attr_class!(REASON_ATTR, "hci.reason", cast: cast::UInt8());
+
+def_attr_class!(HANDLE_ATTR, "hci.handle",
+    cast: cast::UInt16LE().map(|v| v & 0x0fff)
+);
+
+def_attr_class!(PB_ATTR, "hci.pb",
+    cast: cast::UInt16LE().map(|v| (v >> 12) & 0b11)
+);
+
+def_attr_class!(BC_ATTR, "hci.bc",
+    cast: cast::UInt16LE().map(|v| (v >> 14) & 0b11)
+);
+
+def_attr_class!(DATA_LENGTH_ATTR, "hci.dataLength", cast: cast::UInt16LE());
+
+def_attr_class!(DATA_ATTR, "hci.data", cast: cast::ByteSlice());
+
+def_attr_class!(BD_ADDR_ATTR, "hci.bdAddr", typ: "@eth:mac");
+
+def_attr_class!(LINK_TYPE_ATTR, "hci.linkType", cast: cast::UInt8());
+
+def_attr_class!(ENCRYPTION_ATTR, "hci.encryption",
+    cast: cast::UInt8().map(|v| v != 0)
+);
+
+def_attr_class!(ROLE_ATTR, "hci.role", cast: cast::UInt8());
+
+def_attr_class!(PEER_ADDRESS_TYPE_ATTR, "hci.peerAddressType", cast: cast::UInt8());
+
+def_attr_class!(PEER_ADDRESS_ATTR, "hci.peerAddress", typ: "@eth:mac");
+
+def_attr_class!(NUM_REPORTS_ATTR, "hci.numReports", cast: cast::UInt8());
+
+def_attr_class!(REPORT_ATTR, "hci.report",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(REPORT_EVENT_TYPE_ATTR, "hci.report.eventType",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_report_event_type(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("hci.report.eventType.advInd", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!(
+            "hci.report.eventType.advDirectInd",
+            typ: "@novalue",
+            value: true
+        )),
+        2 => Some(attr_class_lazy!(
+            "hci.report.eventType.advScanInd",
+            typ: "@novalue",
+            value: true
+        )),
+        3 => Some(attr_class_lazy!(
+            "hci.report.eventType.advNonconnInd",
+            typ: "@novalue",
+            value: true
+        )),
+        4 => Some(attr_class_lazy!("hci.report.eventType.scanRsp", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+def_attr_class!(REPORT_ADDRESS_TYPE_ATTR, "hci.report.addressType", cast: cast::UInt8());
+
+def_attr_class!(REPORT_ADDRESS_ATTR, "hci.report.address", typ: "@eth:mac");
+
+def_attr_class!(REPORT_DATA_ATTR, "hci.report.data", cast: cast::ByteSlice());
+
+def_attr_class!(REPORT_RSSI_ATTR, "hci.report.rssi", cast: cast::Int8());
+
+def_attr_class!(REASSEMBLED_ATTR, "hci.reassembled",
+    typ: "@expert:note",
+    description: "L2CAP frame reassembled from ACL fragments"
+);
+
+def_attr_class!(MALFORMED_ATTR, "hci.malformed",
+    typ: "@expert:error",
+    description: "Parameters or data exceeding the packet"
+);
+
+genet_decoders!(HciDecoder {}, L2capDecoder {});
@@ -0,0 +1,49 @@
+//! Key derivation of TLS 1.3 (RFC 8446, Section 7.1).
+
+use ring::{hkdf, hmac};
+
+/// HKDF-Expand-Label with an empty context.
+pub fn expand_label(prk: &hmac::SigningKey, label: &str, out: &mut [u8]) {
+    let label = format!("tls13 {}", label);
+    let mut info = vec![(out.len() >> 8) as u8, out.len() as u8, label.len() as u8];
+    info.extend_from_slice(label.as_bytes());
+    info.push(0);
+    hkdf::expand(prk, &info, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use hkdf::expand_label;
+    use ring::{digest, hkdf, hmac};
+
+    fn hex(value: &str) -> Vec<u8> {
+        (0..value.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn quic_initial() {
+        // RFC 9001, Appendix A.1.
+        let salt = hmac::SigningKey::new(
+            &digest::SHA256,
+            &hex("38762cf7f55934b34d179ae6a4c80cadccbb7f0a"),
+        );
+        let initial = hkdf::extract(&salt, &hex("8394c8f03e515708"));
+        let mut secret = vec![0; 32];
+        expand_label(&initial, "client in", &mut secret);
+        assert_eq!(
+            secret,
+            hex("c00cf151ca5be075ed0ebfb5c80323c42d6b7db67881289af4008f1f6c357aea")
+        );
+
+        let prk = hmac::SigningKey::new(&digest::SHA256, &secret);
+        let mut key = vec![0; 16];
+        expand_label(&prk, "quic key", &mut key);
+        assert_eq!(key, hex("1f369613dd76d5467730efcbe3b1a22d"));
+        let mut iv = vec![0; 12];
+        expand_label(&prk, "quic iv", &mut iv);
+        assert_eq!(iv, hex("fa044b2f42a3fd3b46fb255c"));
+    }
+}
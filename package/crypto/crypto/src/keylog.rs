@@ -0,0 +1,132 @@
+//! NSS key log files written by browsers, TLS and QUIC libraries via
+//! SSLKEYLOGFILE.
+
+use std::{collections::HashMap, fs, path::PathBuf, time::SystemTime};
+
+/// The secrets of a connection, identified by the client random.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Secrets {
+    /// The TLS 1.2 master secret.
+    pub master: Option<Vec<u8>>,
+    pub client_early: Option<Vec<u8>>,
+    pub client_handshake: Option<Vec<u8>>,
+    pub server_handshake: Option<Vec<u8>>,
+    pub client_traffic: Option<Vec<u8>>,
+    pub server_traffic: Option<Vec<u8>>,
+}
+
+fn hex(value: &str) -> Option<Vec<u8>> {
+    value
+        .as_bytes()
+        .chunks(2)
+        .map(|c| match c {
+            [h, l] => {
+                Some((char::from(*h).to_digit(16)? << 4 | char::from(*l).to_digit(16)?) as u8)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses a key log. Unknown labels and malformed lines are ignored.
+pub fn parse(text: &str) -> HashMap<Vec<u8>, Secrets> {
+    let mut log: HashMap<Vec<u8>, Secrets> = HashMap::new();
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let (label, random, secret) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(label), Some(random), Some(secret)) if !label.starts_with('#') => {
+                (label, random, secret)
+            }
+            _ => continue,
+        };
+        let (random, secret) = match (hex(random), hex(secret)) {
+            (Some(random), Some(secret)) => (random, secret),
+            _ => continue,
+        };
+        let secrets = log.entry(random).or_default();
+        match label {
+            "CLIENT_RANDOM" => secrets.master = Some(secret),
+            "CLIENT_EARLY_TRAFFIC_SECRET" => secrets.client_early = Some(secret),
+            "CLIENT_HANDSHAKE_TRAFFIC_SECRET" => secrets.client_handshake = Some(secret),
+            "SERVER_HANDSHAKE_TRAFFIC_SECRET" => secrets.server_handshake = Some(secret),
+            "CLIENT_TRAFFIC_SECRET_0" => secrets.client_traffic = Some(secret),
+            "SERVER_TRAFFIC_SECRET_0" => secrets.server_traffic = Some(secret),
+            _ => {}
+        }
+    }
+    log.retain(|_, secrets| *secrets != Secrets::default());
+    log
+}
+
+/// A key log file, read again when a client random is missing and the file
+/// has been modified, so that keys appended during a live capture are used.
+pub struct KeyLogFile {
+    path: PathBuf,
+    modified: Option<(SystemTime, u64)>,
+    log: HashMap<Vec<u8>, Secrets>,
+}
+
+impl KeyLogFile {
+    pub fn new<P: Into<PathBuf>>(path: P) -> KeyLogFile {
+        KeyLogFile {
+            path: path.into(),
+            modified: None,
+            log: HashMap::new(),
+        }
+    }
+
+    /// Returns the secrets of the connection with `client_random`.
+    pub fn get(&mut self, client_random: &[u8]) -> Option<&Secrets> {
+        if !self.log.contains_key(client_random) {
+            self.reload();
+        }
+        self.log.get(client_random)
+    }
+
+    fn reload(&mut self) {
+        let modified = fs::metadata(&self.path)
+            .ok()
+            .and_then(|meta| Some((meta.modified().ok()?, meta.len())));
+        if modified.is_none() || modified == self.modified {
+            return;
+        }
+        if let Ok(text) = fs::read_to_string(&self.path) {
+            self.modified = modified;
+            self.log = parse(&text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use keylog::{parse, Secrets};
+
+    #[test]
+    fn parse_log() {
+        let log = parse(
+            "# comment\n\
+             CLIENT_RANDOM 0a0b 01\n\
+             CLIENT_EARLY_TRAFFIC_SECRET 0c0d 02\n\
+             CLIENT_TRAFFIC_SECRET_0 0c0d 03\n\
+             SERVER_TRAFFIC_SECRET_0 0c0d 0x\n\
+             EXPORTER_SECRET 0e0f 04\n\
+             CLIENT_RANDOM 0a0\n",
+        );
+        assert_eq!(log.len(), 2);
+        assert_eq!(
+            log[&vec![0x0a, 0x0b]],
+            Secrets {
+                master: Some(vec![1]),
+                ..Secrets::default()
+            }
+        );
+        assert_eq!(
+            log[&vec![0x0c, 0x0d]],
+            Secrets {
+                client_early: Some(vec![2]),
+                client_traffic: Some(vec![3]),
+                ..Secrets::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! Cryptography shared by the decoders of encrypted protocols.
+
+extern crate ring;
+
+pub mod aes;
+pub mod hkdf;
+pub mod keylog;
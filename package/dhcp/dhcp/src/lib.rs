@@ -0,0 +1,381 @@
+extern crate genet_sdk;
+
+mod v6;
+
+use genet_sdk::{
+    cast,
+    decoder::*,
+    option::{self, OptionTable, Tlv},
+    prelude::*,
+};
+use std::ops::Range;
+use v6::Dhcpv6Decoder;
+
+/// The offset of the magic cookie.
+const COOKIE_OFFSET: usize = 236;
+
+/// The magic cookie preceding the options (RFC 2132).
+const MAGIC_COOKIE: &[u8] = &[0x63, 0x82, 0x53, 0x63];
+
+const OPTION_OVERLOAD: u64 = 52;
+
+/// The options of the file field.
+const OVERLOAD_FILE: u8 = 1;
+
+/// The options of the sname field.
+const OVERLOAD_SNAME: u8 = 2;
+
+lazy_static! {
+    static ref OPTIONS: OptionTable = OptionTable::new(option::DHCP)
+        .ignore(0)
+        .value(1, &SUBNET_MASK_ATTR)
+        .decoder(3, decode_router)
+        .decoder(6, decode_dns)
+        .value(12, &HOST_NAME_ATTR)
+        .value(15, &DOMAIN_NAME_ATTR)
+        .value(28, &BROADCAST_ATTR)
+        .decoder(42, decode_ntp)
+        .value(50, &REQUESTED_ADDR_ATTR)
+        .value(51, &LEASE_TIME_ATTR)
+        .value(OPTION_OVERLOAD, &OVERLOAD_ATTR)
+        .decoder(53, decode_message_type)
+        .value(54, &SERVER_ID_ATTR)
+        .value(55, &PARAMETERS_ATTR)
+        .value(56, &MESSAGE_ATTR)
+        .value(57, &MAX_SIZE_ATTR)
+        .value(58, &RENEWAL_TIME_ATTR)
+        .value(59, &REBINDING_TIME_ATTR)
+        .value(60, &VENDOR_CLASS_ATTR)
+        .value(61, &CLIENT_ID_ATTR)
+        .option(255, &END_ATTR)
+        .unknown(decode_option);
+}
+
+/// Adds an attribute for each IPv4 address of the option.
+fn addrs(layer: &mut Layer, opt: &Tlv, class: &'static AttrClass) {
+    let mut offset = opt.value.start;
+    while offset + 4 <= opt.value.end {
+        layer.add_attr(attr!(class, range: offset..offset + 4));
+        offset += 4;
+    }
+}
+
+fn decode_router(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    addrs(layer, opt, &ROUTER_ATTR);
+    Ok(())
+}
+
+fn decode_dns(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    addrs(layer, opt, &DNS_ATTR);
+    Ok(())
+}
+
+fn decode_ntp(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    addrs(layer, opt, &NTP_ATTR);
+    Ok(())
+}
+
+fn decode_message_type(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    let range = opt.value.clone();
+    let typ: u8 = layer.data().try_get(range.start)?;
+    layer.add_attr(attr!(&MESSAGE_TYPE_ATTR, range: range.clone()));
+    if let Some(attr) = get_message_type(typ.into()) {
+        layer.add_attr(attr!(attr, range: range));
+    }
+    Ok(())
+}
+
+fn decode_option(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    let offset = opt.range.start;
+    layer.add_attr(attr!(&OPTION_ATTR, range: opt.range.clone()));
+    layer.add_attr(attr!(&OPTION_CODE_ATTR, range: offset..offset + 1));
+    layer.add_attr(attr!(&OPTION_DATA_ATTR, range: opt.value.clone()));
+    Ok(())
+}
+
+/// Adds the string in `range` terminated by a null character.
+fn string_attr(layer: &mut Layer, range: Range<usize>, class: &'static AttrClass) -> Result<()> {
+    let data = layer.data().try_get(range.clone())?;
+    let len = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    let value = String::from_utf8_lossy(&data[..len]).into_owned();
+    layer.add_attr(attr!(class, range: range, value: value.into_boxed_str()));
+    Ok(())
+}
+
+struct DhcpWorker {}
+
+impl Worker for DhcpWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:dhcp"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&DHCP_CLASS, data);
+        if layer.data().len() < COOKIE_OFFSET {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let op = OP_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if let Some(attr) = get_op(op) {
+            layer.add_attr(attr!(attr, range: 0..1));
+        }
+
+        let htype: u8 = layer.data().try_get(1)?;
+        let hlen: u8 = layer.data().try_get(2)?;
+        let hlen = usize::from(hlen).min(16);
+        layer.add_attr(attr!(&CHADDR_ATTR, range: 28..28 + hlen));
+        if htype == 1 && hlen == 6 {
+            layer.add_attr(attr!(&CHADDR_ETH_ATTR, range: 28..34));
+        }
+
+        // Plain BOOTP messages have no options.
+        let cookie = COOKIE_OFFSET..COOKIE_OFFSET + 4;
+        let options = if layer
+            .data()
+            .try_get(cookie.clone())
+            .is_ok_and(|c| &c[..] == MAGIC_COOKIE)
+        {
+            layer.add_attr(attr!(&COOKIE_ATTR, range: cookie));
+            let len = layer.data().len();
+            Some(OPTIONS.dissect(&mut layer, COOKIE_OFFSET + 4..len)?)
+        } else {
+            None
+        };
+
+        // The file and sname fields may carry options instead (RFC 2132 9.3).
+        let overload = match options.as_ref().and_then(|o| o.get(OPTION_OVERLOAD)) {
+            Some(opt) if opt.value.len() == 1 => layer.data().try_get(opt.value.start)?,
+            _ => 0,
+        };
+        let mut malformed = options.is_some_and(|o| o.malformed);
+        if overload & OVERLOAD_FILE != 0 {
+            malformed |= OPTIONS.dissect(&mut layer, 108..COOKIE_OFFSET)?.malformed;
+        } else {
+            string_attr(&mut layer, 108..COOKIE_OFFSET, &FILE_ATTR)?;
+        }
+        if overload & OVERLOAD_SNAME != 0 {
+            malformed |= OPTIONS.dissect(&mut layer, 44..108)?.malformed;
+        } else {
+            string_attr(&mut layer, 44..108, &SNAME_ATTR)?;
+        }
+        if malformed {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct DhcpDecoder {}
+
+impl Decoder for DhcpDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        let table = ctx.dissector_table("udp.port");
+        table.add_default(67, "@data:dhcp");
+        table.add_default(68, "@data:dhcp");
+        Box::new(DhcpWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(DHCP_CLASS, "dhcp",
+    header: &OP_ATTR_HEADER,
+    header: attr!(&HTYPE_ATTR, range: 1..2),
+    header: attr!(&HLEN_ATTR, range: 2..3),
+    header: attr!(&HOPS_ATTR, range: 3..4),
+    header: attr!(&XID_ATTR, range: 4..8),
+    header: attr!(&SECS_ATTR, range: 8..10),
+    header: attr!(&FLAGS_ATTR, range: 10..12),
+    header: attr!(&FLAGS_BROADCAST_ATTR, range: 10..11),
+    header: attr!(&CIADDR_ATTR, range: 12..16),
+    header: attr!(&YIADDR_ATTR, range: 16..20),
+    header: attr!(&SIADDR_ATTR, range: 20..24),
+    header: attr!(&GIADDR_ATTR, range: 24..28)
+);
+
+def_attr!(OP_ATTR_HEADER, &OP_ATTR, range: 0..1);
+
+def_attr_class!(OP_ATTR, "dhcp.op",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(HTYPE_ATTR, "dhcp.htype", cast: cast::UInt8());
+
+def_attr_class!(HLEN_ATTR, "dhcp.hlen", cast: cast::UInt8());
+
+def_attr_class!(HOPS_ATTR, "dhcp.hops", cast: cast::UInt8());
+
+def_attr_class!(XID_ATTR, "dhcp.xid", cast: cast::UInt32BE());
+
+def_attr_class!(SECS_ATTR, "dhcp.secs", cast: cast::UInt16BE());
+
+def_attr_class!(FLAGS_ATTR, "dhcp.flags",
+    typ: "@flags",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(FLAGS_BROADCAST_ATTR, "dhcp.flags.broadcast",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(CIADDR_ATTR, "dhcp.ciaddr",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(YIADDR_ATTR, "dhcp.yiaddr",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(SIADDR_ATTR, "dhcp.siaddr",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(GIADDR_ATTR, "dhcp.giaddr",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(CHADDR_ATTR, "dhcp.chaddr", cast: cast::ByteSlice());
+
+def_attr_class!(CHADDR_ETH_ATTR, "dhcp.chaddr.eth",
+    typ: "@eth:mac",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(SNAME_ATTR, "dhcp.sname");
+
+def_attr_class!(FILE_ATTR, "dhcp.file");
+
+def_attr_class!(COOKIE_ATTR, "dhcp.magicCookie", cast: cast::UInt32BE());
+
+def_attr_class!(SUBNET_MASK_ATTR, "dhcp.subnetMask",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(ROUTER_ATTR, "dhcp.router",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(DNS_ATTR, "dhcp.dns",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(HOST_NAME_ATTR, "dhcp.hostName", cast: cast::Utf8());
+
+def_attr_class!(DOMAIN_NAME_ATTR, "dhcp.domainName", cast: cast::Utf8());
+
+def_attr_class!(BROADCAST_ATTR, "dhcp.broadcastAddress",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(NTP_ATTR, "dhcp.ntp",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(REQUESTED_ADDR_ATTR, "dhcp.requestedAddress",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(LEASE_TIME_ATTR, "dhcp.leaseTime", cast: cast::UInt32BE());
+
+def_attr_class!(OVERLOAD_ATTR, "dhcp.overload", cast: cast::UInt8());
+
+def_attr_class!(MESSAGE_TYPE_ATTR, "dhcp.messageType",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(SERVER_ID_ATTR, "dhcp.serverId",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(PARAMETERS_ATTR, "dhcp.parameterRequestList", cast: cast::ByteSlice());
+
+def_attr_class!(MESSAGE_ATTR, "dhcp.message", cast: cast::Utf8());
+
+def_attr_class!(MAX_SIZE_ATTR, "dhcp.maxMessageSize", cast: cast::UInt16BE());
+
+def_attr_class!(RENEWAL_TIME_ATTR, "dhcp.renewalTime", cast: cast::UInt32BE());
+
+def_attr_class!(REBINDING_TIME_ATTR, "dhcp.rebindingTime", cast: cast::UInt32BE());
+
+def_attr_class!(VENDOR_CLASS_ATTR, "dhcp.vendorClassId", cast: cast::Utf8());
+
+def_attr_class!(CLIENT_ID_ATTR, "dhcp.clientId", cast: cast::ByteSlice());
+
+def_attr_class!(END_ATTR, "dhcp.end",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(OPTION_ATTR, "dhcp.option",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(OPTION_CODE_ATTR, "dhcp.option.code", cast: cast::UInt8());
+
+def_attr_class!(OPTION_DATA_ATTR, "dhcp.option.data", cast: cast::ByteSlice());
+
+def_attr_class!(MALFORMED_ATTR, "dhcp.malformed",
+    typ: "@expert:error",
+    description: "Malformed DHCP message"
+);
+
+fn get_op(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        1 => Some(attr_class_lazy!("dhcp.op.request", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("dhcp.op.reply", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+fn get_message_type(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        1 => Some(attr_class_lazy!("dhcp.messageType.discover", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("dhcp.messageType.offer", typ: "@novalue", value: true)),
+        3 => Some(attr_class_lazy!("dhcp.messageType.request", typ: "@novalue", value: true)),
+        4 => Some(attr_class_lazy!("dhcp.messageType.decline", typ: "@novalue", value: true)),
+        5 => Some(attr_class_lazy!("dhcp.messageType.ack", typ: "@novalue", value: true)),
+        6 => Some(attr_class_lazy!("dhcp.messageType.nak", typ: "@novalue", value: true)),
+        7 => Some(attr_class_lazy!("dhcp.messageType.release", typ: "@novalue", value: true)),
+        8 => Some(attr_class_lazy!("dhcp.messageType.inform", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+genet_decoders!(DhcpDecoder {}, Dhcpv6Decoder {});
@@ -0,0 +1,381 @@
+//! DHCPv6 messages (RFC 8415).
+
+use genet_sdk::{
+    cast,
+    decoder::*,
+    option::{self, OptionTable, Tlv},
+    prelude::*,
+};
+
+const MSG_RELAY_FORW: u8 = 12;
+const MSG_RELAY_REPL: u8 = 13;
+
+/// The length of the header of a relay message.
+const RELAY_HEADER_LEN: usize = 34;
+
+lazy_static! {
+    static ref OPTIONS: OptionTable = OptionTable::new(option::DHCPV6)
+        .value(1, &CLIENT_ID_ATTR)
+        .value(2, &SERVER_ID_ATTR)
+        .decoder(3, decode_ia_na)
+        .decoder(4, decode_ia_ta)
+        .decoder(5, decode_ia_addr)
+        .value(6, &ORO_ATTR)
+        .value(7, &PREFERENCE_ATTR)
+        .value(8, &ELAPSED_TIME_ATTR)
+        .decoder(9, decode_relay_message)
+        .decoder(13, decode_status)
+        .option(14, &RAPID_COMMIT_ATTR)
+        .value(18, &INTERFACE_ID_ATTR)
+        .decoder(23, decode_dns)
+        .value(24, &DOMAIN_LIST_ATTR)
+        .decoder(25, decode_ia_pd)
+        .decoder(26, decode_ia_prefix)
+        .unknown(decode_option);
+}
+
+/// Adds the fixed-length fields at the start of the option value, and the
+/// options following them.
+fn fields(layer: &mut Layer, opt: &Tlv, classes: &[(&'static AttrClass, usize)]) -> Result<()> {
+    let len = classes.iter().map(|(_, len)| len).sum::<usize>();
+    if opt.value.len() < len {
+        layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+        return Ok(());
+    }
+    let mut offset = opt.value.start;
+    for (class, len) in classes {
+        layer.add_attr(attr!(*class, range: offset..offset + len));
+        offset += len;
+    }
+    if OPTIONS.dissect(layer, offset..opt.value.end)?.malformed {
+        layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+    }
+    Ok(())
+}
+
+fn decode_ia_na(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    layer.add_attr(attr!(&IA_NA_ATTR, range: opt.range.clone()));
+    fields(
+        layer,
+        opt,
+        &[
+            (&IA_NA_IAID_ATTR, 4),
+            (&IA_NA_T1_ATTR, 4),
+            (&IA_NA_T2_ATTR, 4),
+        ],
+    )
+}
+
+fn decode_ia_ta(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    layer.add_attr(attr!(&IA_TA_ATTR, range: opt.range.clone()));
+    fields(layer, opt, &[(&IA_TA_IAID_ATTR, 4)])
+}
+
+fn decode_ia_pd(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    layer.add_attr(attr!(&IA_PD_ATTR, range: opt.range.clone()));
+    fields(
+        layer,
+        opt,
+        &[
+            (&IA_PD_IAID_ATTR, 4),
+            (&IA_PD_T1_ATTR, 4),
+            (&IA_PD_T2_ATTR, 4),
+        ],
+    )
+}
+
+fn decode_ia_addr(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    layer.add_attr(attr!(&IA_ADDR_ATTR, range: opt.range.clone()));
+    fields(
+        layer,
+        opt,
+        &[
+            (&IA_ADDR_ADDR_ATTR, 16),
+            (&IA_ADDR_PREFERRED_ATTR, 4),
+            (&IA_ADDR_VALID_ATTR, 4),
+        ],
+    )
+}
+
+fn decode_ia_prefix(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    layer.add_attr(attr!(&IA_PREFIX_ATTR, range: opt.range.clone()));
+    fields(
+        layer,
+        opt,
+        &[
+            (&IA_PREFIX_PREFERRED_ATTR, 4),
+            (&IA_PREFIX_VALID_ATTR, 4),
+            (&IA_PREFIX_LENGTH_ATTR, 1),
+            (&IA_PREFIX_PREFIX_ATTR, 16),
+        ],
+    )
+}
+
+fn decode_status(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    let value = opt.value.clone();
+    if value.len() < 2 {
+        layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+        return Ok(());
+    }
+    layer.add_attr(attr!(&STATUS_ATTR, range: opt.range.clone()));
+    layer.add_attr(attr!(&STATUS_CODE_ATTR, range: value.start..value.start + 2));
+    layer.add_attr(attr!(&STATUS_MESSAGE_ATTR, range: value.start + 2..value.end));
+    Ok(())
+}
+
+fn decode_dns(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    let mut offset = opt.value.start;
+    while offset + 16 <= opt.value.end {
+        layer.add_attr(attr!(&DNS_ATTR, range: offset..offset + 16));
+        offset += 16;
+    }
+    Ok(())
+}
+
+/// Adds the relayed message as a payload, which is decoded as another
+/// DHCPv6 layer.
+fn decode_relay_message(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    layer.add_attr(attr!(&RELAY_MESSAGE_ATTR, range: opt.value.clone()));
+    let payload = layer.data().try_get(opt.value.clone())?;
+    layer.add_payload(Payload::new(payload, "@data:dhcpv6"));
+    Ok(())
+}
+
+fn decode_option(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    let offset = opt.range.start;
+    layer.add_attr(attr!(&OPTION_ATTR, range: opt.range.clone()));
+    layer.add_attr(attr!(&OPTION_CODE_ATTR, range: offset..offset + 2));
+    layer.add_attr(attr!(&OPTION_DATA_ATTR, range: opt.value.clone()));
+    Ok(())
+}
+
+struct Dhcpv6Worker {}
+
+impl Worker for Dhcpv6Worker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:dhcpv6"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&DHCPV6_CLASS, data);
+        let typ: u8 = layer.data().try_get(0)?;
+        if let Some(attr) = get_msg_type(typ.into()) {
+            layer.add_attr(attr!(attr, range: 0..1));
+        }
+
+        let offset = if typ == MSG_RELAY_FORW || typ == MSG_RELAY_REPL {
+            layer.data().try_get(..RELAY_HEADER_LEN)?;
+            layer.add_attr(attr!(&HOP_COUNT_ATTR, range: 1..2));
+            layer.add_attr(attr!(&LINK_ADDR_ATTR, range: 2..18));
+            layer.add_attr(attr!(&PEER_ADDR_ATTR, range: 18..34));
+            RELAY_HEADER_LEN
+        } else {
+            let xid = layer.data().try_get(1..4)?;
+            let xid = xid.iter().fold(0u32, |xid, b| xid << 8 | u32::from(*b));
+            layer.add_attr(attr!(&XID_ATTR, range: 1..4, value: xid));
+            4
+        };
+
+        let len = layer.data().len();
+        if OPTIONS.dissect(&mut layer, offset..len)?.malformed {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+pub struct Dhcpv6Decoder {}
+
+impl Decoder for Dhcpv6Decoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        let table = ctx.dissector_table("udp.port");
+        table.add_default(546, "@data:dhcpv6");
+        table.add_default(547, "@data:dhcpv6");
+        Box::new(Dhcpv6Worker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(DHCPV6_CLASS, "dhcpv6",
+    header: attr!(&MSG_TYPE_ATTR, range: 0..1)
+);
+
+def_attr_class!(MSG_TYPE_ATTR, "dhcpv6.msgType",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(XID_ATTR, "dhcpv6.xid");
+
+def_attr_class!(HOP_COUNT_ATTR, "dhcpv6.hopCount", cast: cast::UInt8());
+
+def_attr_class!(LINK_ADDR_ATTR, "dhcpv6.linkAddress",
+    typ: "@ipv6:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(PEER_ADDR_ATTR, "dhcpv6.peerAddress",
+    typ: "@ipv6:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(CLIENT_ID_ATTR, "dhcpv6.clientId", cast: cast::ByteSlice());
+
+def_attr_class!(SERVER_ID_ATTR, "dhcpv6.serverId", cast: cast::ByteSlice());
+
+def_attr_class!(IA_NA_ATTR, "dhcpv6.iaNa",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(IA_NA_IAID_ATTR, "dhcpv6.iaNa.iaid", cast: cast::UInt32BE());
+
+def_attr_class!(IA_NA_T1_ATTR, "dhcpv6.iaNa.t1", cast: cast::UInt32BE());
+
+def_attr_class!(IA_NA_T2_ATTR, "dhcpv6.iaNa.t2", cast: cast::UInt32BE());
+
+def_attr_class!(IA_TA_ATTR, "dhcpv6.iaTa",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(IA_TA_IAID_ATTR, "dhcpv6.iaTa.iaid", cast: cast::UInt32BE());
+
+def_attr_class!(IA_PD_ATTR, "dhcpv6.iaPd",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(IA_PD_IAID_ATTR, "dhcpv6.iaPd.iaid", cast: cast::UInt32BE());
+
+def_attr_class!(IA_PD_T1_ATTR, "dhcpv6.iaPd.t1", cast: cast::UInt32BE());
+
+def_attr_class!(IA_PD_T2_ATTR, "dhcpv6.iaPd.t2", cast: cast::UInt32BE());
+
+def_attr_class!(IA_ADDR_ATTR, "dhcpv6.iaAddr",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(IA_ADDR_ADDR_ATTR, "dhcpv6.iaAddr.address",
+    typ: "@ipv6:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(IA_ADDR_PREFERRED_ATTR, "dhcpv6.iaAddr.preferredLifetime",
+    cast: cast::UInt32BE()
+);
+
+def_attr_class!(IA_ADDR_VALID_ATTR, "dhcpv6.iaAddr.validLifetime",
+    cast: cast::UInt32BE()
+);
+
+def_attr_class!(IA_PREFIX_ATTR, "dhcpv6.iaPrefix",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(IA_PREFIX_PREFERRED_ATTR, "dhcpv6.iaPrefix.preferredLifetime",
+    cast: cast::UInt32BE()
+);
+
+def_attr_class!(IA_PREFIX_VALID_ATTR, "dhcpv6.iaPrefix.validLifetime",
+    cast: cast::UInt32BE()
+);
+
+def_attr_class!(IA_PREFIX_LENGTH_ATTR, "dhcpv6.iaPrefix.length", cast: cast::UInt8());
+
+def_attr_class!(IA_PREFIX_PREFIX_ATTR, "dhcpv6.iaPrefix.prefix",
+    typ: "@ipv6:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(ORO_ATTR, "dhcpv6.optionRequest", cast: cast::ByteSlice());
+
+def_attr_class!(PREFERENCE_ATTR, "dhcpv6.preference", cast: cast::UInt8());
+
+def_attr_class!(ELAPSED_TIME_ATTR, "dhcpv6.elapsedTime", cast: cast::UInt16BE());
+
+def_attr_class!(RELAY_MESSAGE_ATTR, "dhcpv6.relayMessage", cast: cast::ByteSlice());
+
+def_attr_class!(STATUS_ATTR, "dhcpv6.status",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(STATUS_CODE_ATTR, "dhcpv6.status.code", cast: cast::UInt16BE());
+
+def_attr_class!(STATUS_MESSAGE_ATTR, "dhcpv6.status.message", cast: cast::Utf8());
+
+def_attr_class!(RAPID_COMMIT_ATTR, "dhcpv6.rapidCommit",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(INTERFACE_ID_ATTR, "dhcpv6.interfaceId", cast: cast::ByteSlice());
+
+def_attr_class!(DNS_ATTR, "dhcpv6.dns",
+    typ: "@ipv6:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(DOMAIN_LIST_ATTR, "dhcpv6.domainList", cast: cast::ByteSlice());
+
+def_attr_class!(OPTION_ATTR, "dhcpv6.option",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(OPTION_CODE_ATTR, "dhcpv6.option.code", cast: cast::UInt16BE());
+
+def_attr_class!(OPTION_DATA_ATTR, "dhcpv6.option.data", cast: cast::ByteSlice());
+
+def_attr_class!(MALFORMED_ATTR, "dhcpv6.malformed",
+    typ: "@expert:error",
+    description: "Malformed DHCPv6 message"
+);
+
+fn get_msg_type(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        1 => Some(attr_class_lazy!("dhcpv6.msgType.solicit", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("dhcpv6.msgType.advertise", typ: "@novalue", value: true)),
+        3 => Some(attr_class_lazy!("dhcpv6.msgType.request", typ: "@novalue", value: true)),
+        4 => Some(attr_class_lazy!("dhcpv6.msgType.confirm", typ: "@novalue", value: true)),
+        5 => Some(attr_class_lazy!("dhcpv6.msgType.renew", typ: "@novalue", value: true)),
+        6 => Some(attr_class_lazy!("dhcpv6.msgType.rebind", typ: "@novalue", value: true)),
+        7 => Some(attr_class_lazy!("dhcpv6.msgType.reply", typ: "@novalue", value: true)),
+        8 => Some(attr_class_lazy!("dhcpv6.msgType.release", typ: "@novalue", value: true)),
+        9 => Some(attr_class_lazy!("dhcpv6.msgType.decline", typ: "@novalue", value: true)),
+        10 => Some(attr_class_lazy!("dhcpv6.msgType.reconfigure", typ: "@novalue", value: true)),
+        11 => Some(attr_class_lazy!(
+            "dhcpv6.msgType.informationRequest",
+            typ: "@novalue",
+            value: true
+        )),
+        12 => Some(attr_class_lazy!("dhcpv6.msgType.relayForw", typ: "@novalue", value: true)),
+        13 => Some(attr_class_lazy!("dhcpv6.msgType.relayRepl", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
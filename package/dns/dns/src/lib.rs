@@ -0,0 +1,603 @@
+extern crate genet_sdk;
+
+mod name;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+use std::collections::HashMap;
+
+/// The length of the header.
+const HEADER_LEN: usize = 12;
+
+/// The record types with a name.
+const TYPES: &[(u16, &str)] = &[
+    (1, "a"),
+    (2, "ns"),
+    (5, "cname"),
+    (6, "soa"),
+    (12, "ptr"),
+    (13, "hinfo"),
+    (15, "mx"),
+    (16, "txt"),
+    (28, "aaaa"),
+    (33, "srv"),
+    (35, "naptr"),
+    (39, "dname"),
+    (41, "opt"),
+    (43, "ds"),
+    (46, "rrsig"),
+    (47, "nsec"),
+    (48, "dnskey"),
+    (50, "nsec3"),
+    (52, "tlsa"),
+    (64, "svcb"),
+    (65, "https"),
+    (251, "ixfr"),
+    (252, "axfr"),
+    (255, "any"),
+    (257, "caa"),
+];
+
+const TYPE_A: u16 = 1;
+const TYPE_NS: u16 = 2;
+const TYPE_CNAME: u16 = 5;
+const TYPE_SOA: u16 = 6;
+const TYPE_PTR: u16 = 12;
+const TYPE_MX: u16 = 15;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_DNAME: u16 = 39;
+const TYPE_OPT: u16 = 41;
+const TYPE_SVCB: u16 = 64;
+const TYPE_HTTPS: u16 = 65;
+const TYPE_CAA: u16 = 257;
+
+fn type_classes(prefix: &str) -> HashMap<u16, AttrClass> {
+    TYPES
+        .iter()
+        .map(|(typ, name)| {
+            let id = format!("{}.{}", prefix, name);
+            (*typ, attr_class!(id, typ: "@novalue", value: true))
+        })
+        .collect()
+}
+
+lazy_static! {
+    static ref QUERY_TYPES: HashMap<u16, AttrClass> = type_classes("dns.query.type");
+    static ref RR_TYPES: HashMap<u16, AttrClass> = type_classes("dns.rr.type");
+}
+
+fn uint16(msg: &[u8], offset: usize) -> Option<u16> {
+    let b = msg.get(offset..offset + 2)?;
+    Some(u16::from(b[0]) << 8 | u16::from(b[1]))
+}
+
+/// Reads the name at `offset` as an attribute, and returns the offset
+/// following it.
+fn name_attr(
+    msg: &[u8],
+    offset: usize,
+    class: &'static AttrClass,
+    attrs: &mut Vec<Attr>,
+) -> Option<usize> {
+    let (name, len) = name::read(msg, offset)?;
+    attrs.push(attr!(class, range: offset..offset + len, value: name.into_boxed_str()));
+    Some(offset + len)
+}
+
+/// Reads the character-string at `offset` as an attribute, and returns the
+/// offset following it.
+fn string_attr(
+    msg: &[u8],
+    offset: usize,
+    class: &'static AttrClass,
+    attrs: &mut Vec<Attr>,
+) -> Option<usize> {
+    let len = usize::from(*msg.get(offset)?);
+    let value = msg.get(offset + 1..offset + 1 + len)?;
+    let value = String::from_utf8_lossy(value).into_owned();
+    attrs.push(attr!(class, range: offset..offset + 1 + len, value: value.into_boxed_str()));
+    Some(offset + 1 + len)
+}
+
+/// Parses a question, and returns the offset following it.
+fn question(msg: &[u8], offset: usize, attrs: &mut Vec<Attr>) -> Option<usize> {
+    let mut fields = Vec::new();
+    let pos = name_attr(msg, offset, &QUERY_NAME_ATTR, &mut fields)?;
+    let typ = uint16(msg, pos)?;
+    uint16(msg, pos + 2)?;
+    fields.push(attr!(&QUERY_TYPE_ATTR, range: pos..pos + 2));
+    if let Some(class) = QUERY_TYPES.get(&typ) {
+        fields.push(attr!(class, range: pos..pos + 2));
+    }
+    fields.push(attr!(&QUERY_CLASS_ATTR, range: pos + 2..pos + 4));
+    attrs.push(attr!(&QUERY_ATTR, range: offset..pos + 4));
+    attrs.extend(fields);
+    Some(pos + 4)
+}
+
+/// Parses a resource record, and returns the offset following it.
+fn record(msg: &[u8], offset: usize, attrs: &mut Vec<Attr>) -> Option<usize> {
+    let mut fields = Vec::new();
+    let pos = name_attr(msg, offset, &RR_NAME_ATTR, &mut fields)?;
+    let typ = uint16(msg, pos)?;
+    let len = usize::from(uint16(msg, pos + 8)?);
+    let start = pos + 10;
+    let end = start + len;
+    if msg.len() < end {
+        return None;
+    }
+    fields.push(attr!(&RR_TYPE_ATTR, range: pos..pos + 2));
+    if let Some(class) = RR_TYPES.get(&typ) {
+        fields.push(attr!(class, range: pos..pos + 2));
+    }
+    if typ == TYPE_OPT {
+        // The class and the TTL of an OPT record are the EDNS parameters.
+        fields.push(attr!(&OPT_UDP_SIZE_ATTR, range: pos + 2..pos + 4));
+        fields.push(attr!(&OPT_RCODE_ATTR, range: pos + 4..pos + 5));
+        fields.push(attr!(&OPT_VERSION_ATTR, range: pos + 5..pos + 6));
+        fields.push(attr!(&OPT_DO_ATTR, range: pos + 6..pos + 7));
+    } else {
+        fields.push(attr!(&RR_CLASS_ATTR, range: pos + 2..pos + 4));
+        fields.push(attr!(&RR_TTL_ATTR, range: pos + 4..pos + 8));
+    }
+    fields.push(attr!(&RR_LENGTH_ATTR, range: pos + 8..pos + 10));
+    if rdata(msg, typ, start, end, &mut fields).is_none() {
+        fields.push(attr!(&RR_DATA_ATTR, range: start..end));
+    }
+    attrs.push(attr!(&RR_ATTR, range: offset..end));
+    attrs.extend(fields);
+    Some(end)
+}
+
+/// Parses the data of a record of the type `typ`, or returns None if the
+/// type is unknown or the data is malformed.
+fn rdata(msg: &[u8], typ: u16, start: usize, end: usize, attrs: &mut Vec<Attr>) -> Option<()> {
+    let len = end - start;
+    let mut fields = Vec::new();
+    // The names in the data may point to the whole message, but must end
+    // within the data.
+    let pos = match typ {
+        TYPE_A if len == 4 => {
+            fields.push(attr!(&A_ATTR, range: start..end));
+            end
+        }
+        TYPE_AAAA if len == 16 => {
+            fields.push(attr!(&AAAA_ATTR, range: start..end));
+            end
+        }
+        TYPE_NS => name_attr(msg, start, &NS_ATTR, &mut fields)?,
+        TYPE_CNAME => name_attr(msg, start, &CNAME_ATTR, &mut fields)?,
+        TYPE_PTR => name_attr(msg, start, &PTR_ATTR, &mut fields)?,
+        TYPE_DNAME => name_attr(msg, start, &DNAME_ATTR, &mut fields)?,
+        TYPE_MX => {
+            uint16(msg, start)?;
+            fields.push(attr!(&MX_PREFERENCE_ATTR, range: start..start + 2));
+            name_attr(msg, start + 2, &MX_EXCHANGE_ATTR, &mut fields)?
+        }
+        TYPE_TXT => {
+            let mut pos = start;
+            while pos < end {
+                pos = string_attr(msg, pos, &TXT_ATTR, &mut fields)?;
+            }
+            pos
+        }
+        TYPE_SOA => {
+            let pos = name_attr(msg, start, &SOA_MNAME_ATTR, &mut fields)?;
+            let pos = name_attr(msg, pos, &SOA_RNAME_ATTR, &mut fields)?;
+            msg.get(pos..pos + 20)?;
+            fields.push(attr!(&SOA_SERIAL_ATTR, range: pos..pos + 4));
+            fields.push(attr!(&SOA_REFRESH_ATTR, range: pos + 4..pos + 8));
+            fields.push(attr!(&SOA_RETRY_ATTR, range: pos + 8..pos + 12));
+            fields.push(attr!(&SOA_EXPIRE_ATTR, range: pos + 12..pos + 16));
+            fields.push(attr!(&SOA_MINIMUM_ATTR, range: pos + 16..pos + 20));
+            pos + 20
+        }
+        TYPE_SRV => {
+            msg.get(start..start + 6)?;
+            fields.push(attr!(&SRV_PRIORITY_ATTR, range: start..start + 2));
+            fields.push(attr!(&SRV_WEIGHT_ATTR, range: start + 2..start + 4));
+            fields.push(attr!(&SRV_PORT_ATTR, range: start + 4..start + 6));
+            name_attr(msg, start + 6, &SRV_TARGET_ATTR, &mut fields)?
+        }
+        TYPE_SVCB | TYPE_HTTPS => {
+            uint16(msg, start)?;
+            fields.push(attr!(&SVCB_PRIORITY_ATTR, range: start..start + 2));
+            let pos = name_attr(msg, start + 2, &SVCB_TARGET_ATTR, &mut fields)?;
+            if pos > end {
+                return None;
+            }
+            if pos < end {
+                fields.push(attr!(&SVCB_PARAMS_ATTR, range: pos..end));
+            }
+            end
+        }
+        TYPE_CAA => {
+            msg.get(start)?;
+            fields.push(attr!(&CAA_FLAGS_ATTR, range: start..start + 1));
+            let pos = string_attr(msg, start + 1, &CAA_TAG_ATTR, &mut fields)?;
+            if pos > end {
+                return None;
+            }
+            let value = String::from_utf8_lossy(&msg[pos..end]).into_owned();
+            fields.push(attr!(&CAA_VALUE_ATTR, range: pos..end, value: value.into_boxed_str()));
+            end
+        }
+        TYPE_OPT => {
+            let mut pos = start;
+            while pos < end {
+                let len = usize::from(uint16(msg, pos + 2)?);
+                if pos + 4 + len > end {
+                    return None;
+                }
+                fields.push(attr!(&OPT_OPTION_ATTR, range: pos..pos + 4 + len));
+                fields.push(attr!(&OPT_OPTION_CODE_ATTR, range: pos..pos + 2));
+                fields.push(attr!(&OPT_OPTION_DATA_ATTR, range: pos + 4..pos + 4 + len));
+                pos += 4 + len;
+            }
+            pos
+        }
+        _ => return None,
+    };
+    if pos != end {
+        return None;
+    }
+    attrs.extend(fields);
+    Some(())
+}
+
+/// Parses the sections following the header, and returns None if the
+/// message is malformed. The records parsed so far are added anyway.
+fn sections(msg: &[u8], counts: [u16; 4], attrs: &mut Vec<Attr>) -> Option<()> {
+    let mut offset = HEADER_LEN;
+    let sections: [&'static AttrClass; 4] = [
+        &QUESTIONS_ATTR,
+        &ANSWERS_ATTR,
+        &AUTHORITIES_ATTR,
+        &ADDITIONALS_ATTR,
+    ];
+    for (i, (class, count)) in sections.iter().zip(counts.iter()).enumerate() {
+        let start = offset;
+        let mut records = Vec::new();
+        let mut result = Some(());
+        for _ in 0..*count {
+            let next = if i == 0 {
+                question(msg, offset, &mut records)
+            } else {
+                record(msg, offset, &mut records)
+            };
+            match next {
+                Some(next) => offset = next,
+                None => {
+                    result = None;
+                    break;
+                }
+            }
+        }
+        if *count > 0 {
+            attrs.push(attr!(*class, range: start..offset));
+            attrs.extend(records);
+        }
+        result?;
+    }
+    Some(())
+}
+
+struct DnsWorker {}
+
+impl Worker for DnsWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:dns"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&DNS_CLASS, data);
+        let opcode = OPCODE_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if let Some(attr) = get_opcode(opcode) {
+            layer.add_attr(attr!(attr, range: 2..3));
+        }
+        let rcode = RCODE_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if let Some(attr) = get_rcode(rcode) {
+            layer.add_attr(attr!(attr, range: 3..4));
+        }
+
+        let counts = [
+            QDCOUNT_ATTR_HEADER.try_get(&layer)?.try_into()?,
+            ANCOUNT_ATTR_HEADER.try_get(&layer)?.try_into()?,
+            NSCOUNT_ATTR_HEADER.try_get(&layer)?.try_into()?,
+            ARCOUNT_ATTR_HEADER.try_get(&layer)?.try_into()?,
+        ];
+        let mut attrs = Vec::new();
+        let msg = layer.data();
+        if sections(&msg, counts, &mut attrs).is_none() {
+            attrs.push(attr!(&MALFORMED_ATTR, value: true));
+        }
+        for attr in attrs {
+            layer.add_attr(attr);
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct DnsDecoder {}
+
+impl Decoder for DnsDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        let table = ctx.dissector_table("udp.port");
+        table.add_default(53, "@data:dns");
+        table.add_default(5353, "@data:dns");
+        Box::new(DnsWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(DNS_CLASS, "dns",
+    header: attr!(&ID_ATTR, range: 0..2),
+    header: attr!(&FLAGS_ATTR, range: 2..4),
+    header: attr!(&FLAGS_RESPONSE_ATTR, range: 2..3),
+    header: &OPCODE_ATTR_HEADER,
+    header: attr!(&FLAGS_AA_ATTR, range: 2..3),
+    header: attr!(&FLAGS_TC_ATTR, range: 2..3),
+    header: attr!(&FLAGS_RD_ATTR, range: 2..3),
+    header: attr!(&FLAGS_RA_ATTR, range: 3..4),
+    header: attr!(&FLAGS_AD_ATTR, range: 3..4),
+    header: attr!(&FLAGS_CD_ATTR, range: 3..4),
+    header: &RCODE_ATTR_HEADER,
+    header: &QDCOUNT_ATTR_HEADER,
+    header: &ANCOUNT_ATTR_HEADER,
+    header: &NSCOUNT_ATTR_HEADER,
+    header: &ARCOUNT_ATTR_HEADER
+);
+
+def_attr!(OPCODE_ATTR_HEADER, &OPCODE_ATTR, range: 2..3);
+
+def_attr!(RCODE_ATTR_HEADER, &RCODE_ATTR, range: 3..4);
+
+def_attr!(QDCOUNT_ATTR_HEADER, &QDCOUNT_ATTR, range: 4..6);
+
+def_attr!(ANCOUNT_ATTR_HEADER, &ANCOUNT_ATTR, range: 6..8);
+
+def_attr!(NSCOUNT_ATTR_HEADER, &NSCOUNT_ATTR, range: 8..10);
+
+def_attr!(ARCOUNT_ATTR_HEADER, &ARCOUNT_ATTR, range: 10..12);
+
+def_attr_class!(ID_ATTR, "dns.id", cast: cast::UInt16BE());
+
+def_attr_class!(FLAGS_ATTR, "dns.flags",
+    typ: "@flags",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(FLAGS_RESPONSE_ATTR, "dns.flags.response",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(OPCODE_ATTR, "dns.opcode",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| (v >> 3) & 0b1111)
+);
+
+def_attr_class!(FLAGS_AA_ATTR, "dns.flags.authoritative",
+    cast: cast::UInt8().map(|v| (v & 0b0000_0100) != 0)
+);
+
+def_attr_class!(FLAGS_TC_ATTR, "dns.flags.truncated",
+    cast: cast::UInt8().map(|v| (v & 0b0000_0010) != 0)
+);
+
+def_attr_class!(FLAGS_RD_ATTR, "dns.flags.recursionDesired",
+    cast: cast::UInt8().map(|v| (v & 0b0000_0001) != 0)
+);
+
+def_attr_class!(FLAGS_RA_ATTR, "dns.flags.recursionAvailable",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(FLAGS_AD_ATTR, "dns.flags.authenticData",
+    cast: cast::UInt8().map(|v| (v & 0b0010_0000) != 0)
+);
+
+def_attr_class!(FLAGS_CD_ATTR, "dns.flags.checkingDisabled",
+    cast: cast::UInt8().map(|v| (v & 0b0001_0000) != 0)
+);
+
+def_attr_class!(RCODE_ATTR, "dns.rcode",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| v & 0b1111)
+);
+
+def_attr_class!(QDCOUNT_ATTR, "dns.questionCount", cast: cast::UInt16BE());
+
+def_attr_class!(ANCOUNT_ATTR, "dns.answerCount", cast: cast::UInt16BE());
+
+def_attr_class!(NSCOUNT_ATTR, "dns.authorityCount", cast: cast::UInt16BE());
+
+def_attr_class!(ARCOUNT_ATTR, "dns.additionalCount", cast: cast::UInt16BE());
+
+def_attr_class!(MALFORMED_ATTR, "dns.malformed",
+    typ: "@expert:error",
+    description: "Malformed DNS message"
+);
+
+def_attr_class!(QUESTIONS_ATTR, "dns.questions",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(ANSWERS_ATTR, "dns.answers",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(AUTHORITIES_ATTR, "dns.authorities",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(ADDITIONALS_ATTR, "dns.additionals",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(QUERY_ATTR, "dns.query",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(QUERY_NAME_ATTR, "dns.query.name");
+
+def_attr_class!(QUERY_TYPE_ATTR, "dns.query.type",
+    typ: "@enum",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(QUERY_CLASS_ATTR, "dns.query.class", cast: cast::UInt16BE());
+
+def_attr_class!(RR_ATTR, "dns.rr",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(RR_NAME_ATTR, "dns.rr.name");
+
+def_attr_class!(RR_TYPE_ATTR, "dns.rr.type",
+    typ: "@enum",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(RR_CLASS_ATTR, "dns.rr.class", cast: cast::UInt16BE());
+
+def_attr_class!(RR_TTL_ATTR, "dns.rr.ttl", cast: cast::UInt32BE());
+
+def_attr_class!(RR_LENGTH_ATTR, "dns.rr.length", cast: cast::UInt16BE());
+
+def_attr_class!(RR_DATA_ATTR, "dns.rr.data", cast: cast::ByteSlice());
+
+def_attr_class!(A_ATTR, "dns.rr.a",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(AAAA_ATTR, "dns.rr.aaaa",
+    typ: "@ipv6:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(NS_ATTR, "dns.rr.ns");
+
+def_attr_class!(CNAME_ATTR, "dns.rr.cname");
+
+def_attr_class!(PTR_ATTR, "dns.rr.ptr");
+
+def_attr_class!(DNAME_ATTR, "dns.rr.dname");
+
+def_attr_class!(MX_PREFERENCE_ATTR, "dns.rr.mx.preference", cast: cast::UInt16BE());
+
+def_attr_class!(MX_EXCHANGE_ATTR, "dns.rr.mx.exchange");
+
+def_attr_class!(TXT_ATTR, "dns.rr.txt");
+
+def_attr_class!(SOA_MNAME_ATTR, "dns.rr.soa.mname");
+
+def_attr_class!(SOA_RNAME_ATTR, "dns.rr.soa.rname");
+
+def_attr_class!(SOA_SERIAL_ATTR, "dns.rr.soa.serial", cast: cast::UInt32BE());
+
+def_attr_class!(SOA_REFRESH_ATTR, "dns.rr.soa.refresh", cast: cast::UInt32BE());
+
+def_attr_class!(SOA_RETRY_ATTR, "dns.rr.soa.retry", cast: cast::UInt32BE());
+
+def_attr_class!(SOA_EXPIRE_ATTR, "dns.rr.soa.expire", cast: cast::UInt32BE());
+
+def_attr_class!(SOA_MINIMUM_ATTR, "dns.rr.soa.minimum", cast: cast::UInt32BE());
+
+def_attr_class!(SRV_PRIORITY_ATTR, "dns.rr.srv.priority", cast: cast::UInt16BE());
+
+def_attr_class!(SRV_WEIGHT_ATTR, "dns.rr.srv.weight", cast: cast::UInt16BE());
+
+def_attr_class!(SRV_PORT_ATTR, "dns.rr.srv.port", cast: cast::UInt16BE());
+
+def_attr_class!(SRV_TARGET_ATTR, "dns.rr.srv.target");
+
+def_attr_class!(SVCB_PRIORITY_ATTR, "dns.rr.svcb.priority", cast: cast::UInt16BE());
+
+def_attr_class!(SVCB_TARGET_ATTR, "dns.rr.svcb.target");
+
+def_attr_class!(SVCB_PARAMS_ATTR, "dns.rr.svcb.params", cast: cast::ByteSlice());
+
+def_attr_class!(CAA_FLAGS_ATTR, "dns.rr.caa.flags", cast: cast::UInt8());
+
+def_attr_class!(CAA_TAG_ATTR, "dns.rr.caa.tag");
+
+def_attr_class!(CAA_VALUE_ATTR, "dns.rr.caa.value");
+
+def_attr_class!(OPT_UDP_SIZE_ATTR, "dns.rr.opt.udpSize", cast: cast::UInt16BE());
+
+def_attr_class!(OPT_RCODE_ATTR, "dns.rr.opt.extendedRcode", cast: cast::UInt8());
+
+def_attr_class!(OPT_VERSION_ATTR, "dns.rr.opt.version", cast: cast::UInt8());
+
+def_attr_class!(OPT_DO_ATTR, "dns.rr.opt.dnssecOk",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(OPT_OPTION_ATTR, "dns.rr.opt.option",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(OPT_OPTION_CODE_ATTR, "dns.rr.opt.option.code", cast: cast::UInt16BE());
+
+def_attr_class!(OPT_OPTION_DATA_ATTR, "dns.rr.opt.option.data", cast: cast::ByteSlice());
+
+fn get_opcode(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("dns.opcode.query", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!("dns.opcode.inverseQuery", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("dns.opcode.status", typ: "@novalue", value: true)),
+        4 => Some(attr_class_lazy!("dns.opcode.notify", typ: "@novalue", value: true)),
+        5 => Some(attr_class_lazy!("dns.opcode.update", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+fn get_rcode(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("dns.rcode.noError", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!("dns.rcode.formErr", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("dns.rcode.servFail", typ: "@novalue", value: true)),
+        3 => Some(attr_class_lazy!("dns.rcode.nxDomain", typ: "@novalue", value: true)),
+        4 => Some(attr_class_lazy!("dns.rcode.notImp", typ: "@novalue", value: true)),
+        5 => Some(attr_class_lazy!("dns.rcode.refused", typ: "@novalue", value: true)),
+        6 => Some(attr_class_lazy!("dns.rcode.yxDomain", typ: "@novalue", value: true)),
+        7 => Some(attr_class_lazy!("dns.rcode.yxRRSet", typ: "@novalue", value: true)),
+        8 => Some(attr_class_lazy!("dns.rcode.nxRRSet", typ: "@novalue", value: true)),
+        9 => Some(attr_class_lazy!("dns.rcode.notAuth", typ: "@novalue", value: true)),
+        10 => Some(attr_class_lazy!("dns.rcode.notZone", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+genet_decoders!(DnsDecoder {});
@@ -0,0 +1,68 @@
+//! Domain names with message compression (RFC 1035 4.1.4).
+
+/// The maximum length of a name on the wire.
+const MAX_NAME_LEN: usize = 255;
+
+/// Reads the name at `offset` of the message `msg`, and returns the name in
+/// presentation format and the number of bytes it occupies at `offset`.
+///
+/// A compression pointer must point before the labels followed since the
+/// previous pointer, so that a loop of pointers is rejected instead of being
+/// followed forever.
+pub fn read(msg: &[u8], offset: usize) -> Option<(String, usize)> {
+    let mut name = String::new();
+    let mut wire_len = 0;
+    let mut pos = offset;
+    let mut start = offset;
+    let mut len = None;
+    loop {
+        let head = *msg.get(pos)?;
+        match head >> 6 {
+            0b00 => {
+                let label_len = usize::from(head);
+                if label_len == 0 {
+                    let len = len.unwrap_or(pos + 1 - offset);
+                    if name.is_empty() {
+                        name.push('.');
+                    }
+                    return Some((name, len));
+                }
+                wire_len += label_len + 1;
+                if wire_len + 1 > MAX_NAME_LEN {
+                    return None;
+                }
+                let label = msg.get(pos + 1..pos + 1 + label_len)?;
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                push_label(&mut name, label);
+                pos += 1 + label_len;
+            }
+            0b11 => {
+                let target = usize::from(head & 0x3f) << 8 | usize::from(*msg.get(pos + 1)?);
+                if target >= start {
+                    return None;
+                }
+                len.get_or_insert(pos + 2 - offset);
+                pos = target;
+                start = target;
+            }
+            // The extended label types are obsolete.
+            _ => return None,
+        }
+    }
+}
+
+/// Appends a label escaping the dots and the unprintable bytes.
+fn push_label(name: &mut String, label: &[u8]) {
+    for b in label {
+        match b {
+            b'.' | b'\\' => {
+                name.push('\\');
+                name.push(char::from(*b));
+            }
+            0x21..=0x7e => name.push(char::from(*b)),
+            _ => name.push_str(&format!("\\{:03}", b)),
+        }
+    }
+}
@@ -2,34 +2,70 @@ extern crate genet_sdk;
 
 use genet_sdk::{cast, decoder::*, prelude::*};
 
+/// The decoders of the EtherTypes, registered as the defaults of the
+/// `eth.type` dissector table. VLAN tags and tunnels share the table to
+/// dispatch the encapsulated protocol.
+const TYPES: &[(u64, &str)] = &[
+    (0x0800, "@data:ipv4"),
+    (0x0806, "@data:arp"),
+    (0x0842, "@data:wol"),
+    (0x8035, "@data:arp"),
+    (0x8100, "@data:vlan"),
+    (0x86DD, "@data:ipv6"),
+    (0x8847, "@data:mpls"),
+    (0x8848, "@data:mpls"),
+    (0x888E, "@data:eap"),
+    (0x88A8, "@data:vlan"),
+    (0x9100, "@data:vlan"),
+];
+
 struct EthWorker {}
 
 impl Worker for EthWorker {
     fn decode(
         &mut self,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         _stack: &LayerStack,
         parent: &mut Parent,
     ) -> Result<Status> {
+        let data;
+
         if parent.id() == token!("[link-1]") {
-            let mut layer = Layer::new(&ETH_CLASS, parent.data());
-            let len = LEN_ATTR_HEADER.try_get(&layer)?.try_into()?;
-            if len <= 1500 {
-                layer.add_attr(&LEN_ATTR_HEADER);
-            } else {
-                layer.add_attr(&TYPE_ATTR_HEADER);
-            }
-            if let Some((typ, attr)) = get_type(len) {
-                layer.add_attr(attr!(attr, range: 12..14));
-                let payload = parent.data().try_get(14..)?;
-                layer.add_payload(Payload::new(payload, typ));
+            let mut frame = parent.data();
+            if let Some(fcs_len) = parent.frame_metadata().fcs_len() {
+                frame = frame.try_get(..frame.len().saturating_sub(fcs_len.into()))?;
             }
+            data = frame;
+        } else if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:eth"))
+        {
+            // A frame encapsulated by a tunnel, e.g. VXLAN.
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
 
-            parent.add_child(layer);
-            Ok(Status::Done)
+        let mut layer = Layer::new(&ETH_CLASS, data);
+        let len = LEN_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if len <= 1500 {
+            layer.add_attr(&LEN_ATTR_HEADER);
         } else {
-            Ok(Status::Skip)
+            layer.add_attr(&TYPE_ATTR_HEADER);
         }
+        if let Some(attr) = get_type(len) {
+            layer.add_attr(attr!(attr, range: 12..14));
+        }
+        if len > 1500 {
+            if let Some(typ) = ctx.dissector_table("eth.type").get(len) {
+                let payload = data.try_get(14..)?;
+                layer.add_payload(Payload::new(payload, typ));
+            }
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
     }
 }
 
@@ -37,7 +73,11 @@ impl Worker for EthWorker {
 struct EthDecoder {}
 
 impl Decoder for EthDecoder {
-    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        let table = ctx.dissector_table("eth.type");
+        for (typ, id) in TYPES {
+            table.add_default(*typ, *id);
+        }
         Box::new(EthWorker {})
     }
 
@@ -77,28 +117,19 @@ def_attr!(LEN_ATTR_HEADER,  &LEN_ATTR, range: 12..14);
 
 def_attr!(TYPE_ATTR_HEADER,  &TYPE_ATTR, range: 12..14);
 
-fn get_type(val: u64) -> Option<(Token, &'static AttrClass)> {
+fn get_type(val: u64) -> Option<&'static AttrClass> {
     match val {
-        0x0800 => Some((
-            token!("@data:ipv4"),
-            attr_class_lazy!("eth.type.ipv4", typ: "@novalue", value: true),
-        )),
-        0x0806 => Some((
-            token!("@data:arp"),
-            attr_class_lazy!("eth.type.arp", typ: "@novalue", value: true),
-        )),
-        0x0842 => Some((
-            token!("@data:wol"),
-            attr_class_lazy!("eth.type.wol", typ: "@novalue", value: true),
-        )),
-        0x86DD => Some((
-            token!("@data:ipv6"),
-            attr_class_lazy!("eth.type.ipv6", typ: "@novalue", value: true),
-        )),
-        0x888E => Some((
-            token!("@data:eap"),
-            attr_class_lazy!("eth.type.eap", typ: "@novalue", value: true),
-        )),
+        0x0800 => Some(attr_class_lazy!("eth.type.ipv4", typ: "@novalue", value: true)),
+        0x0806 => Some(attr_class_lazy!("eth.type.arp", typ: "@novalue", value: true)),
+        0x0842 => Some(attr_class_lazy!("eth.type.wol", typ: "@novalue", value: true)),
+        0x8035 => Some(attr_class_lazy!("eth.type.rarp", typ: "@novalue", value: true)),
+        0x8100 => Some(attr_class_lazy!("eth.type.vlan", typ: "@novalue", value: true)),
+        0x86DD => Some(attr_class_lazy!("eth.type.ipv6", typ: "@novalue", value: true)),
+        0x8847 => Some(attr_class_lazy!("eth.type.mpls", typ: "@novalue", value: true)),
+        0x8848 => Some(attr_class_lazy!("eth.type.mplsMulticast", typ: "@novalue", value: true)),
+        0x888E => Some(attr_class_lazy!("eth.type.eap", typ: "@novalue", value: true)),
+        0x88A8 => Some(attr_class_lazy!("eth.type.qinq", typ: "@novalue", value: true)),
+        0x9100 => Some(attr_class_lazy!("eth.type.qinqLegacy", typ: "@novalue", value: true)),
         _ => None,
     }
 }
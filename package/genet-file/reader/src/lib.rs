@@ -82,7 +82,7 @@ struct GenetFileWorker {
 }
 
 impl Worker for GenetFileWorker {
-    fn read(&mut self) -> Result<Vec<Layer>> {
+    fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
         let mut layers = Vec::new();
         for _ in 0..self.header.entries {
             let mut frame_buf = vec![0; read_usize(&mut self.reader)?];
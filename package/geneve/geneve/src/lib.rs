@@ -0,0 +1,151 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+/// The length of the header without the options.
+const HEADER_LEN: usize = 8;
+
+/// The length of the header of an option.
+const OPTION_HEADER_LEN: usize = 4;
+
+/// Transparent Ethernet Bridging.
+const PROTOCOL_TEB: u64 = 0x6558;
+
+struct GeneveWorker {}
+
+impl Worker for GeneveWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:geneve"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&GENEVE_CLASS, data);
+        let vni = data.try_get(4..7)?;
+        let vni = vni.iter().fold(0u32, |vni, b| vni << 8 | u32::from(*b));
+        layer.add_attr(attr!(&VNI_ATTR, range: 4..7, value: vni));
+
+        let opt_len: u8 = data.try_get(0)?;
+        let end = HEADER_LEN + usize::from(opt_len & 0b0011_1111) * 4;
+        data.try_get(..end)?;
+        let mut offset = HEADER_LEN;
+        while offset < end {
+            let len: u8 = data.try_get(offset + 3)?;
+            let len = OPTION_HEADER_LEN + usize::from(len & 0b0001_1111) * 4;
+            if offset + len > end {
+                layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+                break;
+            }
+            layer.add_attr(attr!(&OPTION_ATTR, range: offset..offset + len));
+            layer.add_attr(attr!(&OPTION_CLASS_ATTR, range: offset..offset + 2));
+            layer.add_attr(attr!(&OPTION_TYPE_ATTR, range: offset + 2..offset + 3));
+            layer.add_attr(attr!(&OPTION_CRITICAL_ATTR, range: offset + 2..offset + 3));
+            layer.add_attr(
+                attr!(&OPTION_DATA_ATTR, range: offset + OPTION_HEADER_LEN..offset + len),
+            );
+            offset += len;
+        }
+
+        let protocol = PROTOCOL_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let typ = if protocol == PROTOCOL_TEB {
+            Some(token!("@data:eth"))
+        } else {
+            ctx.dissector_table("eth.type").get(protocol)
+        };
+        if let Some(typ) = typ {
+            let payload = data.try_get(end..)?;
+            layer.add_payload(Payload::new(payload, typ));
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct GeneveDecoder {}
+
+impl Decoder for GeneveDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        ctx.dissector_table("udp.port")
+            .add_default(6081, "@data:geneve");
+        Box::new(GeneveWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(GENEVE_CLASS, "geneve",
+    header: attr!(&VERSION_ATTR, bit_range: 0 0..2),
+    header: attr!(&OPTIONS_LENGTH_ATTR, bit_range: 0 2..8),
+    header: attr!(&FLAGS_ATTR, range: 1..2),
+    header: attr!(&FLAGS_OAM_ATTR, range: 1..2),
+    header: attr!(&FLAGS_CRITICAL_ATTR, range: 1..2),
+    header: &PROTOCOL_ATTR_HEADER
+);
+
+def_attr!(PROTOCOL_ATTR_HEADER, &PROTOCOL_ATTR, range: 2..4);
+
+def_attr_class!(VERSION_ATTR, "geneve.version",
+    cast: cast::UInt8().map(|v| v >> 6)
+);
+
+def_attr_class!(OPTIONS_LENGTH_ATTR, "geneve.optionsLength",
+    cast: cast::UInt8().map(|v| (v & 0b0011_1111) * 4)
+);
+
+def_attr_class!(FLAGS_ATTR, "geneve.flags",
+    typ: "@flags",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(FLAGS_OAM_ATTR, "geneve.flags.oam",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(FLAGS_CRITICAL_ATTR, "geneve.flags.critical",
+    cast: cast::UInt8().map(|v| (v & 0b0100_0000) != 0)
+);
+
+def_attr_class!(PROTOCOL_ATTR, "geneve.protocol", cast: cast::UInt16BE());
+
+def_attr_class!(VNI_ATTR, "geneve.vni");
+
+def_attr_class!(OPTION_ATTR, "geneve.option",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(OPTION_CLASS_ATTR, "geneve.option.class", cast: cast::UInt16BE());
+
+def_attr_class!(OPTION_TYPE_ATTR, "geneve.option.type", cast: cast::UInt8());
+
+def_attr_class!(OPTION_CRITICAL_ATTR, "geneve.option.critical",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(OPTION_DATA_ATTR, "geneve.option.data", cast: cast::ByteSlice());
+
+def_attr_class!(MALFORMED_ATTR, "geneve.malformed",
+    typ: "@expert:error",
+    description: "Option exceeding the options length"
+);
+
+genet_decoders!(GeneveDecoder {});
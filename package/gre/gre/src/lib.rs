@@ -0,0 +1,175 @@
+extern crate genet_sdk;
+
+use genet_sdk::{
+    cast,
+    checksum::{self, Validator},
+    decoder::*,
+    prelude::*,
+};
+
+const FLAG_CHECKSUM: u8 = 0b1000_0000;
+const FLAG_ROUTING: u8 = 0b0100_0000;
+const FLAG_KEY: u8 = 0b0010_0000;
+const FLAG_SEQUENCE: u8 = 0b0001_0000;
+
+/// The acknowledgment flag of the enhanced GRE header (RFC 2637).
+const FLAG_ACK: u8 = 0b1000_0000;
+
+/// Transparent Ethernet Bridging, e.g. NVGRE.
+const PROTOCOL_TEB: u64 = 0x6558;
+
+struct GreWorker {
+    validator: Validator,
+}
+
+impl Worker for GreWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:gre"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&GRE_CLASS, data);
+        let flags: u8 = data.try_get(0)?;
+        let version: u8 = data.try_get(1)?;
+        let ack = version & FLAG_ACK != 0;
+        let version = version & 0b111;
+        let protocol = PROTOCOL_ATTR_HEADER.try_get(&layer)?.try_into()?;
+
+        let mut offset = 4;
+        if flags & (FLAG_CHECKSUM | FLAG_ROUTING) != 0 {
+            data.try_get(offset..offset + 4)?;
+            layer.add_attr(attr!(&CHECKSUM_ATTR, range: offset..offset + 2));
+            if flags & FLAG_CHECKSUM != 0 {
+                let valid = checksum::internet(&data) == 0;
+                let status = self.validator.status(valid, parent.frame_metadata());
+                layer.add_attr(
+                    attr!(&CHECKSUM_STATUS_ATTR, range: offset..offset + 2, value: status.to_value()),
+                );
+            }
+            offset += 4;
+        }
+        if flags & FLAG_KEY != 0 {
+            data.try_get(offset..offset + 4)?;
+            if version == 1 {
+                layer.add_attr(attr!(&PAYLOAD_LENGTH_ATTR, range: offset..offset + 2));
+                layer.add_attr(attr!(&CALL_ID_ATTR, range: offset + 2..offset + 4));
+            } else {
+                layer.add_attr(attr!(&KEY_ATTR, range: offset..offset + 4));
+            }
+            offset += 4;
+        }
+        if flags & FLAG_SEQUENCE != 0 {
+            data.try_get(offset..offset + 4)?;
+            layer.add_attr(attr!(&SEQUENCE_ATTR, range: offset..offset + 4));
+            offset += 4;
+        }
+        if version == 1 && ack {
+            data.try_get(offset..offset + 4)?;
+            layer.add_attr(attr!(&ACK_ATTR, range: offset..offset + 4));
+            offset += 4;
+        }
+
+        // The source routes of RFC 1701 are not dissected.
+        if flags & FLAG_ROUTING == 0 {
+            let typ = if protocol == PROTOCOL_TEB {
+                Some(token!("@data:eth"))
+            } else {
+                ctx.dissector_table("eth.type").get(protocol)
+            };
+            if let Some(typ) = typ {
+                let payload = data.try_get(offset..)?;
+                layer.add_payload(Payload::new(payload, typ));
+            }
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct GreDecoder {}
+
+impl Decoder for GreDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        Box::new(GreWorker {
+            validator: Validator::from_config(ctx),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            options: vec![Validator::option()],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(GRE_CLASS, "gre",
+    header: attr!(&FLAGS_ATTR, range: 0..2),
+    header: attr!(&FLAGS_CHECKSUM_ATTR, range: 0..1),
+    header: attr!(&FLAGS_ROUTING_ATTR, range: 0..1),
+    header: attr!(&FLAGS_KEY_ATTR, range: 0..1),
+    header: attr!(&FLAGS_SEQUENCE_ATTR, range: 0..1),
+    header: attr!(&VERSION_ATTR, range: 1..2),
+    header: &PROTOCOL_ATTR_HEADER
+);
+
+def_attr!(PROTOCOL_ATTR_HEADER, &PROTOCOL_ATTR, range: 2..4);
+
+def_attr_class!(FLAGS_ATTR, "gre.flags",
+    typ: "@flags",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(FLAGS_CHECKSUM_ATTR, "gre.flags.checksum",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(FLAGS_ROUTING_ATTR, "gre.flags.routing",
+    cast: cast::UInt8().map(|v| (v & 0b0100_0000) != 0)
+);
+
+def_attr_class!(FLAGS_KEY_ATTR, "gre.flags.key",
+    cast: cast::UInt8().map(|v| (v & 0b0010_0000) != 0)
+);
+
+def_attr_class!(FLAGS_SEQUENCE_ATTR, "gre.flags.sequence",
+    cast: cast::UInt8().map(|v| (v & 0b0001_0000) != 0)
+);
+
+def_attr_class!(VERSION_ATTR, "gre.version",
+    cast: cast::UInt8().map(|v| v & 0b111)
+);
+
+def_attr_class!(PROTOCOL_ATTR, "gre.protocol", cast: cast::UInt16BE());
+
+def_attr_class!(CHECKSUM_ATTR, "gre.checksum", cast: cast::UInt16BE());
+
+def_attr_class!(CHECKSUM_STATUS_ATTR, "gre.checksum.status");
+
+def_attr_class!(KEY_ATTR, "gre.key", cast: cast::UInt32BE());
+
+def_attr_class!(SEQUENCE_ATTR, "gre.sequence", cast: cast::UInt32BE());
+
+def_attr_class!(PAYLOAD_LENGTH_ATTR, "gre.payloadLength", cast: cast::UInt16BE());
+
+def_attr_class!(CALL_ID_ATTR, "gre.callId", cast: cast::UInt16BE());
+
+def_attr_class!(ACK_ATTR, "gre.ack", cast: cast::UInt32BE());
+
+genet_decoders!(GreDecoder {});
@@ -0,0 +1,312 @@
+//! Message types read from a `FileDescriptorSet`, as written by
+//! `protoc --descriptor_set_out`.
+
+use std::collections::HashMap;
+use wire::{self, Field, Value};
+
+pub const TYPE_DOUBLE: u64 = 1;
+pub const TYPE_FLOAT: u64 = 2;
+pub const TYPE_INT64: u64 = 3;
+pub const TYPE_UINT64: u64 = 4;
+pub const TYPE_INT32: u64 = 5;
+pub const TYPE_FIXED64: u64 = 6;
+pub const TYPE_FIXED32: u64 = 7;
+pub const TYPE_BOOL: u64 = 8;
+pub const TYPE_STRING: u64 = 9;
+pub const TYPE_GROUP: u64 = 10;
+pub const TYPE_MESSAGE: u64 = 11;
+pub const TYPE_BYTES: u64 = 12;
+pub const TYPE_UINT32: u64 = 13;
+pub const TYPE_ENUM: u64 = 14;
+pub const TYPE_SFIXED32: u64 = 15;
+pub const TYPE_SFIXED64: u64 = 16;
+pub const TYPE_SINT32: u64 = 17;
+pub const TYPE_SINT64: u64 = 18;
+
+/// A field of a message type.
+#[derive(Debug, Default)]
+pub struct FieldType {
+    pub name: String,
+    pub typ: u64,
+    /// The full name of the message or enum type, without the leading dot.
+    pub type_name: String,
+}
+
+/// A method of a service.
+#[derive(Debug, Default)]
+pub struct Method {
+    pub input: String,
+    pub output: String,
+}
+
+/// The types and the services of a descriptor set, by full name.
+#[derive(Debug, Default)]
+pub struct Registry {
+    messages: HashMap<String, HashMap<u64, FieldType>>,
+    enums: HashMap<String, HashMap<i64, String>>,
+    /// The methods by gRPC path, e.g. `/helloworld.Greeter/SayHello`.
+    methods: HashMap<String, Method>,
+}
+
+impl Registry {
+    /// Parses a serialized `FileDescriptorSet`.
+    pub fn parse(data: &[u8]) -> Option<Registry> {
+        let (files, malformed) = wire::fields(data, 0..data.len());
+        if malformed {
+            return None;
+        }
+        let mut registry = Registry::default();
+        for file in children(data, &files, 1)? {
+            registry.add_file(data, &file)?;
+        }
+        Some(registry)
+    }
+
+    /// Returns the fields of the message type `name` by number.
+    pub fn message(&self, name: &str) -> Option<&HashMap<u64, FieldType>> {
+        self.messages.get(name)
+    }
+
+    /// Returns the name of the value `number` of the enum type `name`.
+    pub fn enum_value(&self, name: &str, number: i64) -> Option<&str> {
+        self.enums
+            .get(name)
+            .and_then(|values| values.get(&number))
+            .map(|name| name.as_str())
+    }
+
+    pub fn method(&self, path: &str) -> Option<&Method> {
+        self.methods.get(path)
+    }
+
+    fn add_file(&mut self, data: &[u8], fields: &[Field]) -> Option<()> {
+        let package = string(data, fields, 2).unwrap_or_default();
+        let prefix = if package.is_empty() {
+            String::new()
+        } else {
+            format!("{}.", package)
+        };
+        for message in children(data, fields, 4)? {
+            self.add_message(data, &message, &prefix)?;
+        }
+        for item in children(data, fields, 5)? {
+            self.add_enum(data, &item, &prefix);
+        }
+        for service in children(data, fields, 6)? {
+            let name = format!(
+                "{}{}",
+                prefix,
+                string(data, &service, 1).unwrap_or_default()
+            );
+            for method in children(data, &service, 2)? {
+                let path = format!("/{}/{}", name, string(data, &method, 1).unwrap_or_default());
+                let method = Method {
+                    input: type_name(data, &method, 2),
+                    output: type_name(data, &method, 3),
+                };
+                self.methods.insert(path, method);
+            }
+        }
+        Some(())
+    }
+
+    fn add_message(&mut self, data: &[u8], fields: &[Field], prefix: &str) -> Option<()> {
+        let name = format!("{}{}", prefix, string(data, fields, 1).unwrap_or_default());
+        let mut types = HashMap::new();
+        for field in children(data, fields, 2)? {
+            let number = varint(&field, 3).unwrap_or(0);
+            let typ = FieldType {
+                name: string(data, &field, 1).unwrap_or_default(),
+                typ: varint(&field, 5).unwrap_or(0),
+                type_name: type_name(data, &field, 6),
+            };
+            types.insert(number, typ);
+        }
+        let prefix = format!("{}.", name);
+        for nested in children(data, fields, 3)? {
+            self.add_message(data, &nested, &prefix)?;
+        }
+        for item in children(data, fields, 4)? {
+            self.add_enum(data, &item, &prefix);
+        }
+        self.messages.insert(name, types);
+        Some(())
+    }
+
+    fn add_enum(&mut self, data: &[u8], fields: &[Field], prefix: &str) {
+        let name = format!("{}{}", prefix, string(data, fields, 1).unwrap_or_default());
+        let values = children(data, fields, 2)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|value| {
+                let number = varint(&value, 2)? as i32;
+                Some((i64::from(number), string(data, &value, 1)?))
+            })
+            .collect();
+        self.enums.insert(name, values);
+    }
+}
+
+/// Returns the fields of the embedded messages `number`.
+fn children(data: &[u8], fields: &[Field], number: u64) -> Option<Vec<Vec<Field>>> {
+    let mut children = Vec::new();
+    for field in fields.iter().filter(|f| f.number == number) {
+        if let Value::Len(range) = &field.value {
+            match wire::fields(data, range.clone()) {
+                (fields, false) => children.push(fields),
+                _ => return None,
+            }
+        }
+    }
+    Some(children)
+}
+
+fn string(data: &[u8], fields: &[Field], number: u64) -> Option<String> {
+    fields
+        .iter()
+        .rev()
+        .find(|f| f.number == number)
+        .and_then(|f| match &f.value {
+            Value::Len(range) => Some(String::from_utf8_lossy(&data[range.clone()]).into_owned()),
+            _ => None,
+        })
+}
+
+fn type_name(data: &[u8], fields: &[Field], number: u64) -> String {
+    let name = string(data, fields, number).unwrap_or_default();
+    name.trim_start_matches('.').to_string()
+}
+
+fn varint(fields: &[Field], number: u64) -> Option<u64> {
+    fields
+        .iter()
+        .rev()
+        .find(|f| f.number == number)
+        .and_then(|f| match f.value {
+            Value::Varint(n) => Some(n),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use descriptor::{Registry, TYPE_ENUM, TYPE_MESSAGE, TYPE_STRING};
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+        while value >= 0x80 {
+            data.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        data.push(value as u8);
+        data
+    }
+
+    fn int(number: u64, value: u64) -> Vec<u8> {
+        let mut data = varint(number << 3);
+        data.extend(varint(value));
+        data
+    }
+
+    fn len(number: u64, value: &[u8]) -> Vec<u8> {
+        let mut data = varint(number << 3 | 2);
+        data.extend(varint(value.len() as u64));
+        data.extend_from_slice(value);
+        data
+    }
+
+    fn field(name: &str, number: u64, typ: u64, type_name: &str) -> Vec<u8> {
+        let mut data = len(1, name.as_bytes());
+        data.extend(int(3, number));
+        data.extend(int(5, typ));
+        if !type_name.is_empty() {
+            data.extend(len(6, type_name.as_bytes()));
+        }
+        data
+    }
+
+    /// Returns a descriptor set of a file in the package `echo`.
+    fn descriptor_set() -> Vec<u8> {
+        let mut kind = len(1, b"Kind");
+        kind.extend(len(2, &[len(1, b"A"), int(2, 0)].concat()));
+        kind.extend(len(2, &[len(1, b"B"), int(2, -1i64 as u64)].concat()));
+
+        let mut message = len(1, b"Message");
+        message.extend(len(2, &field("text", 1, TYPE_STRING, "")));
+        message.extend(len(2, &field("kind", 2, TYPE_ENUM, ".echo.Message.Kind")));
+        message.extend(len(
+            2,
+            &field("inner", 3, TYPE_MESSAGE, ".echo.Message.Inner"),
+        ));
+        message.extend(len(3, &len(1, b"Inner")));
+        message.extend(len(4, &kind));
+
+        let mut method = len(1, b"Say");
+        method.extend(len(2, b".echo.Message"));
+        method.extend(len(3, b".echo.Message.Inner"));
+        let mut service = len(1, b"Echo");
+        service.extend(len(2, &method));
+
+        let mut file = len(1, b"echo.proto");
+        file.extend(len(2, b"echo"));
+        file.extend(len(4, &message));
+        file.extend(len(6, &service));
+        len(1, &file)
+    }
+
+    #[test]
+    fn parse() {
+        let registry = Registry::parse(&descriptor_set()).unwrap();
+
+        let message = registry.message("echo.Message").unwrap();
+        assert_eq!(message.len(), 3);
+        assert_eq!(message[&1].name, "text");
+        assert_eq!(message[&1].typ, TYPE_STRING);
+        assert_eq!(message[&2].type_name, "echo.Message.Kind");
+        assert_eq!(message[&3].type_name, "echo.Message.Inner");
+        assert!(registry.message("echo.Message.Inner").unwrap().is_empty());
+        assert!(registry.message("Message").is_none());
+
+        assert_eq!(registry.enum_value("echo.Message.Kind", 0), Some("A"));
+        assert_eq!(registry.enum_value("echo.Message.Kind", -1), Some("B"));
+        assert_eq!(registry.enum_value("echo.Message.Kind", 1), None);
+
+        let method = registry.method("/echo.Echo/Say").unwrap();
+        assert_eq!(method.input, "echo.Message");
+        assert_eq!(method.output, "echo.Message.Inner");
+        assert!(registry.method("/Echo/Say").is_none());
+    }
+
+    #[test]
+    fn without_package() {
+        let mut file = len(4, &len(1, b"Empty"));
+        file.extend(len(5, &len(1, b"Kind")));
+        let registry = Registry::parse(&len(1, &file)).unwrap();
+        assert!(registry.message("Empty").is_some());
+        assert_eq!(registry.enum_value("Kind", 0), None);
+        assert!(Registry::parse(&[]).unwrap().message("Empty").is_none());
+    }
+
+    #[test]
+    fn malformed() {
+        let data = descriptor_set();
+        // Truncated.
+        assert!(Registry::parse(&data[..data.len() - 1]).is_none());
+        // A varint longer than 10 bytes.
+        let mut long = vec![0x08];
+        long.extend(vec![0xff; 11]);
+        assert!(Registry::parse(&long).is_none());
+
+        // A malformed nested message, whose length is longer than its parent.
+        let message = [len(1, b"Message"), vec![0x12, 0x05, 0x0a]].concat();
+        let file = len(4, &message);
+        assert!(Registry::parse(&len(1, &file)).is_none());
+
+        // A malformed field list of a message.
+        let message = [len(1, b"Message"), len(2, &[0x0b, 0x10])].concat();
+        assert!(Registry::parse(&len(1, &len(4, &message))).is_none());
+
+        // An unterminated group in a file.
+        assert!(Registry::parse(&len(1, &[0x0b, 0x10, 0x01])).is_none());
+    }
+}
@@ -0,0 +1,298 @@
+//! HTTP/2 frames of a connection (RFC 9113), as far as needed to follow the
+//! header fields and the data of the streams.
+
+use header::{Field, Table};
+use hpack::huffman::HuffmanDecoder;
+use std::mem;
+
+/// The client connection preface.
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const FRAME_DATA: u8 = 0x0;
+const FRAME_HEADERS: u8 = 0x1;
+const FRAME_RST_STREAM: u8 = 0x3;
+const FRAME_PUSH_PROMISE: u8 = 0x5;
+const FRAME_CONTINUATION: u8 = 0x9;
+
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+const FRAME_HEADER_LEN: usize = 9;
+
+/// What a frame tells about a stream.
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    /// A complete field block, or None if it could not be decoded.
+    Headers {
+        stream: u32,
+        fields: Option<Vec<Field>>,
+        end: bool,
+    },
+    Data {
+        stream: u32,
+        data: Vec<u8>,
+        end: bool,
+    },
+    Reset {
+        stream: u32,
+    },
+}
+
+/// The frames sent by an endpoint.
+#[derive(Default)]
+struct Direction {
+    buf: Vec<u8>,
+    /// The preface has been skipped, or the first bytes did not match it.
+    started: bool,
+    table: Table,
+    /// The stream ID, the END_STREAM flag and the fragments of a field block
+    /// waiting for CONTINUATION frames.
+    block: Option<(u32, bool, Vec<u8>)>,
+    /// The framing is lost, e.g. by a malformed frame.
+    broken: bool,
+}
+
+/// The HTTP/2 state of a connection.
+pub struct Connection {
+    directions: [Direction; 2],
+    huffman: HuffmanDecoder,
+}
+
+impl Default for Connection {
+    fn default() -> Connection {
+        Connection {
+            directions: Default::default(),
+            huffman: HuffmanDecoder::new(),
+        }
+    }
+}
+
+/// Returns the payload of a frame without the padding, or None if the
+/// padding is longer than the frame.
+fn unpad(flags: u8, payload: &[u8]) -> Option<&[u8]> {
+    if flags & FLAG_PADDED == 0 {
+        return Some(payload);
+    }
+    let (len, payload) = payload.split_first()?;
+    let end = payload.len().checked_sub(usize::from(*len))?;
+    Some(&payload[..end])
+}
+
+impl Connection {
+    /// Processes the stream data sent by `dir`, and returns the events of the
+    /// frames completed by it.
+    pub fn push(&mut self, dir: usize, data: &[u8]) -> Vec<Event> {
+        let Connection {
+            directions,
+            huffman,
+        } = self;
+        let direction = &mut directions[dir];
+        let mut events = Vec::new();
+        if direction.broken {
+            return events;
+        }
+        let mut buf = mem::take(&mut direction.buf);
+        buf.extend_from_slice(data);
+
+        if !direction.started {
+            let len = buf.len().min(PREFACE.len());
+            if buf[..len] != PREFACE[..len] {
+                direction.started = true;
+            } else if len == PREFACE.len() {
+                buf.drain(..len);
+                direction.started = true;
+            } else {
+                direction.buf = buf;
+                return events;
+            }
+        }
+
+        let mut pos = 0;
+        while let Some(header) = buf.get(pos..pos + FRAME_HEADER_LEN) {
+            let len = header[..3].iter().fold(0, |n, b| n << 8 | *b as usize);
+            let typ = header[3];
+            let flags = header[4];
+            let stream = header[5..].iter().fold(0, |n, b| n << 8 | u32::from(*b)) & 0x7fff_ffff;
+            let start = pos + FRAME_HEADER_LEN;
+            if buf.len() - start < len {
+                break;
+            }
+            pos = start + len;
+            match direction.frame(typ, flags, stream, &buf[start..pos], huffman) {
+                Some(Some(event)) => events.push(event),
+                Some(None) => {}
+                None => {
+                    direction.broken = true;
+                    return events;
+                }
+            }
+        }
+        buf.drain(..pos);
+        direction.buf = buf;
+        events
+    }
+}
+
+impl Direction {
+    /// Processes a frame, and returns None if it is malformed.
+    fn frame(
+        &mut self,
+        typ: u8,
+        flags: u8,
+        stream: u32,
+        payload: &[u8],
+        huffman: &mut HuffmanDecoder,
+    ) -> Option<Option<Event>> {
+        let end = flags & FLAG_END_STREAM != 0;
+
+        // A field block must be followed by its CONTINUATION frames.
+        if let Some((id, end, mut block)) = self.block.take() {
+            if typ != FRAME_CONTINUATION || stream != id {
+                return None;
+            }
+            block.extend_from_slice(payload);
+            if flags & FLAG_END_HEADERS == 0 {
+                self.block = Some((id, end, block));
+                return Some(None);
+            }
+            let fields = self.table.decode(&block, huffman);
+            return Some(Some(Event::Headers {
+                stream,
+                fields,
+                end,
+            }));
+        }
+
+        let block = match typ {
+            FRAME_DATA => {
+                let data = unpad(flags, payload)?.to_vec();
+                return Some(Some(Event::Data { stream, data, end }));
+            }
+            FRAME_RST_STREAM => return Some(Some(Event::Reset { stream })),
+            FRAME_HEADERS => {
+                let block = unpad(flags, payload)?;
+                if flags & FLAG_PRIORITY != 0 {
+                    block.get(5..)?
+                } else {
+                    block
+                }
+            }
+            // The promised request is decoded to keep the table in sync, but
+            // the stream is not followed.
+            FRAME_PUSH_PROMISE => unpad(flags, payload)?.get(4..)?,
+            FRAME_CONTINUATION => return None,
+            _ => return Some(None),
+        };
+        if flags & FLAG_END_HEADERS == 0 {
+            self.block = Some((stream, end, block.to_vec()));
+            return Some(None);
+        }
+        let fields = self.table.decode(block, huffman);
+        if typ == FRAME_PUSH_PROMISE {
+            return Some(None);
+        }
+        Some(Some(Event::Headers {
+            stream,
+            fields,
+            end,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use h2::{Connection, Event, PREFACE};
+
+    fn frame(typ: u8, flags: u8, stream: u32, payload: &[u8]) -> Vec<u8> {
+        let len = payload.len();
+        let mut data = vec![(len >> 16) as u8, (len >> 8) as u8, len as u8, typ, flags];
+        data.extend_from_slice(&stream.to_be_bytes());
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn preface_and_frames() {
+        let mut conn = Connection::default();
+        let mut data = PREFACE.to_vec();
+        data.extend(frame(0x4, 0, 0, &[]));
+        // :method POST, :path /echo.Echo/Say
+        data.extend(frame(0x1, 0x4, 1, b"\x83\x04\x0e/echo.Echo/Say"));
+        data.extend(frame(0x0, 0x9, 1, b"\x02abc\x00\x00"));
+
+        // The preface and the frames may be split anywhere.
+        assert_eq!(conn.push(0, &data[..10]), vec![]);
+        assert_eq!(conn.push(0, &data[10..40]), vec![]);
+        assert_eq!(
+            conn.push(0, &data[40..]),
+            vec![
+                Event::Headers {
+                    stream: 1,
+                    fields: Some(vec![
+                        (b":method".to_vec(), b"POST".to_vec()),
+                        (b":path".to_vec(), b"/echo.Echo/Say".to_vec()),
+                    ]),
+                    end: false,
+                },
+                Event::Data {
+                    stream: 1,
+                    data: b"abc".to_vec(),
+                    end: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn continuation() {
+        let mut conn = Connection::default();
+        let mut data = frame(0x1, 0x21, 3, b"\x00\x00\x00\x01\x10\x88");
+        data.extend(frame(0x9, 0x4, 3, b"\x5f\x10application/grpc"));
+        data.extend(frame(0x3, 0, 3, b"\x00\x00\x00\x08"));
+        assert_eq!(
+            conn.push(1, &data),
+            vec![
+                Event::Headers {
+                    stream: 3,
+                    fields: Some(vec![
+                        (b":status".to_vec(), b"200".to_vec()),
+                        (b"content-type".to_vec(), b"application/grpc".to_vec()),
+                    ]),
+                    end: true,
+                },
+                Event::Reset { stream: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        // The padding is longer than the frame.
+        let mut conn = Connection::default();
+        assert_eq!(conn.push(0, &frame(0x0, 0x8, 1, b"\x05ab")), vec![]);
+        assert_eq!(conn.push(0, &frame(0x0, 0, 1, b"ab")), vec![]);
+
+        // A CONTINUATION frame without a field block.
+        let mut conn = Connection::default();
+        assert_eq!(conn.push(0, &frame(0x9, 0x4, 1, b"\x88")), vec![]);
+
+        // A field block interrupted by another frame.
+        let mut conn = Connection::default();
+        let mut data = frame(0x1, 0, 1, b"\x88");
+        data.extend(frame(0x0, 0, 1, b"ab"));
+        assert_eq!(conn.push(0, &data), vec![]);
+
+        // A field block which cannot be decoded.
+        let mut conn = Connection::default();
+        assert_eq!(
+            conn.push(0, &frame(0x1, 0x4, 1, b"\x80")),
+            vec![Event::Headers {
+                stream: 1,
+                fields: None,
+                end: false,
+            }]
+        );
+    }
+}
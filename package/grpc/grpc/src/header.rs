@@ -0,0 +1,278 @@
+//! HPACK field block decoding (RFC 7541).
+
+use hpack::huffman::HuffmanDecoder;
+use std::collections::VecDeque;
+
+/// The size of a dynamic table entry in addition to its name and value.
+const ENTRY_OVERHEAD: usize = 32;
+
+/// The initial size of the dynamic table (SETTINGS_HEADER_TABLE_SIZE).
+const DEFAULT_CAPACITY: usize = 4096;
+
+pub type Field = (Vec<u8>, Vec<u8>);
+
+/// The static table (RFC 7541, Appendix A).
+static STATIC_TABLE: &[(&str, &str)] = &[
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// Reads an integer with a prefix of the low `bits` bits of the byte at
+/// `pos` (RFC 7541, Section 5.1).
+fn integer(data: &[u8], pos: &mut usize, bits: u32) -> Option<u64> {
+    let max = (1u16 << bits) as u64 - 1;
+    let mut value = u64::from(*data.get(*pos)?) & max;
+    *pos += 1;
+    if value < max {
+        return Some(value);
+    }
+    let mut shift = 0;
+    loop {
+        let b = *data.get(*pos)?;
+        *pos += 1;
+        if shift > 56 {
+            return None;
+        }
+        value += u64::from(b & 0x7f) << shift;
+        shift += 7;
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+/// Reads a string literal (RFC 7541, Section 5.2).
+fn string(data: &[u8], pos: &mut usize, huffman: &mut HuffmanDecoder) -> Option<Vec<u8>> {
+    let encoded = *data.get(*pos)? & 0x80 != 0;
+    let len = integer(data, pos, 7)? as usize;
+    if len > data.len() - *pos {
+        return None;
+    }
+    let range = *pos..*pos + len;
+    *pos += len;
+    if encoded {
+        huffman.decode(&data[range]).ok()
+    } else {
+        Some(data[range].to_vec())
+    }
+}
+
+/// The dynamic table of a decoder.
+pub struct Table {
+    /// The entries from the newest.
+    entries: VecDeque<Field>,
+    capacity: usize,
+    size: usize,
+}
+
+impl Default for Table {
+    fn default() -> Table {
+        Table {
+            entries: VecDeque::new(),
+            capacity: DEFAULT_CAPACITY,
+            size: 0,
+        }
+    }
+}
+
+impl Table {
+    /// Returns the entry of an index into the static and the dynamic
+    /// tables, which starts at 1.
+    fn get(&self, index: u64) -> Option<Field> {
+        let index = (index as usize).checked_sub(1)?;
+        match STATIC_TABLE.get(index) {
+            Some((name, value)) => Some((name.as_bytes().to_vec(), value.as_bytes().to_vec())),
+            None => self.entries.get(index - STATIC_TABLE.len()).cloned(),
+        }
+    }
+
+    fn insert(&mut self, field: Field) {
+        self.size += field.0.len() + field.1.len() + ENTRY_OVERHEAD;
+        self.entries.push_front(field);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            match self.entries.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + ENTRY_OVERHEAD,
+                None => break,
+            }
+        }
+    }
+
+    /// Decodes a field block (RFC 7541, Section 6). Returns None if it is
+    /// malformed, after which the table is out of sync with the encoder.
+    pub fn decode(&mut self, block: &[u8], huffman: &mut HuffmanDecoder) -> Option<Vec<Field>> {
+        let mut pos = 0;
+        let mut fields = Vec::new();
+        while pos < block.len() {
+            let first = block[pos];
+            if first & 0x80 != 0 {
+                // Indexed Header Field
+                let index = integer(block, &mut pos, 7)?;
+                fields.push(self.get(index)?);
+            } else if first & 0xe0 == 0x20 {
+                // Dynamic Table Size Update
+                self.capacity = integer(block, &mut pos, 5)? as usize;
+                self.evict();
+            } else {
+                // Literal Header Field with Incremental Indexing, without
+                // Indexing or Never Indexed.
+                let indexing = first & 0x40 != 0;
+                let index = integer(block, &mut pos, if indexing { 6 } else { 4 })?;
+                let name = if index == 0 {
+                    string(block, &mut pos, huffman)?
+                } else {
+                    self.get(index)?.0
+                };
+                let value = string(block, &mut pos, huffman)?;
+                if indexing {
+                    self.insert((name.clone(), value.clone()));
+                }
+                fields.push((name, value));
+            }
+        }
+        Some(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Field, Table};
+    use hpack::huffman::HuffmanDecoder;
+
+    fn fields(list: &[(&str, &str)]) -> Vec<Field> {
+        list.iter()
+            .map(|(name, value)| (name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn requests() {
+        // RFC 7541, Appendix C.4.
+        let mut table = Table::default();
+        let mut huffman = HuffmanDecoder::new();
+        let first = b"\x82\x86\x84\x41\x8c\xf1\xe3\xc2\xe5\xf2\x3a\x6b\xa0\xab\x90\xf4\xff";
+        assert_eq!(
+            table.decode(first, &mut huffman),
+            Some(fields(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+            ]))
+        );
+        let second = b"\x82\x86\x84\xbe\x58\x86\xa8\xeb\x10\x64\x9c\xbf";
+        assert_eq!(
+            table.decode(second, &mut huffman),
+            Some(fields(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+                ("cache-control", "no-cache"),
+            ]))
+        );
+        assert_eq!(table.size, 110);
+    }
+
+    #[test]
+    fn eviction() {
+        let mut table = Table::default();
+        let mut huffman = HuffmanDecoder::new();
+        // A size update to 64 bytes, then two literals which are indexed.
+        let block = b"\x3f\x21\x40\x01a\x01b\x40\x01c\x01d";
+        assert_eq!(
+            table.decode(block, &mut huffman),
+            Some(fields(&[("a", "b"), ("c", "d")]))
+        );
+        assert_eq!(table.entries.len(), 1);
+        assert_eq!(
+            table.decode(b"\xbe", &mut huffman),
+            Some(fields(&[("c", "d")]))
+        );
+        assert_eq!(table.decode(b"\xbf", &mut huffman), None);
+    }
+
+    #[test]
+    fn malformed() {
+        let mut table = Table::default();
+        let mut huffman = HuffmanDecoder::new();
+        // Index 0.
+        assert_eq!(table.decode(b"\x80", &mut huffman), None);
+        // A truncated integer.
+        assert_eq!(table.decode(b"\xff\x80", &mut huffman), None);
+        // An integer overflowing 64 bits.
+        assert_eq!(
+            table.decode(
+                b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01",
+                &mut huffman
+            ),
+            None
+        );
+        // A string longer than the block.
+        assert_eq!(table.decode(b"\x40\x05ab", &mut huffman), None);
+        // A size update without its integer.
+        assert_eq!(table.decode(b"\x3f", &mut huffman), None);
+    }
+}
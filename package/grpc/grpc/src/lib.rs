@@ -0,0 +1,574 @@
+extern crate genet_sdk;
+extern crate hpack;
+
+mod descriptor;
+mod h2;
+mod header;
+mod message;
+mod wire;
+
+use descriptor::Registry;
+use genet_sdk::{cast, decoder::*, dynamic::Classes, prelude::*, variant::Variant};
+use h2::Event;
+use message::Dissector;
+use std::{
+    collections::{HashMap, VecDeque},
+    fs, mem,
+};
+
+/// Config key for the path of a descriptor set.
+const DESCRIPTOR_SET_KEY: &str = "@genet/grpc.descriptorSet";
+
+/// Config key for the message types by port or path.
+const MESSAGE_TYPES_KEY: &str = "@genet/grpc.messageTypes";
+
+/// The length of the prefix of a message: the flags and the length.
+const PREFIX_LEN: usize = 5;
+
+/// The flag of a gRPC-Web frame carrying trailers instead of a message.
+const FLAG_TRAILERS: u8 = 0x80;
+
+/// The ID prefix of the fields of a message.
+const FIELDS_PREFIX: &str = "grpc.message.fields";
+
+type Endpoint = (Vec<u8>, u64);
+
+/// Returns the value of the attribute `id` of the topmost layer having it.
+fn stack_value<T>(stack: &LayerStack, id: Token) -> Result<Option<T>>
+where
+    Variant: Value<T>,
+{
+    for layer in stack.layers().rev() {
+        if let Some(attr) = layer.attr(id) {
+            return Ok(Some(attr.try_get(layer)?.try_into()?));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the value of the attribute `id` of the parent.
+fn parent_value<T>(parent: &Parent, id: Token) -> Result<Option<T>>
+where
+    Variant: Value<T>,
+{
+    match parent.attr(id) {
+        Some(attr) => Ok(Some(attr.try_get(parent)?.try_into()?)),
+        None => Ok(None),
+    }
+}
+
+fn is_grpc(content_type: &str) -> bool {
+    content_type.starts_with("application/grpc")
+}
+
+/// A request and its response.
+#[derive(Clone, Default)]
+struct Call {
+    path: String,
+    /// The content type of either message is gRPC.
+    grpc: bool,
+}
+
+/// A stream of an HTTP/2 connection.
+#[derive(Default)]
+struct H2Stream {
+    call: Call,
+    /// The direction of the client.
+    client: usize,
+    /// The incomplete messages by direction.
+    pending: [Vec<u8>; 2],
+    ended: [bool; 2],
+}
+
+/// The state of an HTTP/2 connection.
+#[derive(Default)]
+struct H2Flow {
+    frames: h2::Connection,
+    streams: HashMap<u32, H2Stream>,
+    closed: [bool; 2],
+}
+
+impl H2Flow {
+    /// Marks the end of a stream by `dir`, and forgets the stream once both
+    /// directions have ended.
+    fn end(&mut self, stream: u32, dir: usize) {
+        let ended = match self.streams.get_mut(&stream) {
+            Some(stream) => {
+                stream.ended[dir] = true;
+                stream.ended[0] && stream.ended[1]
+            }
+            None => false,
+        };
+        if ended {
+            self.streams.remove(&stream);
+        }
+    }
+}
+
+/// The message types configured for a port or a path.
+#[derive(Debug, PartialEq)]
+struct Rule {
+    request: String,
+    response: String,
+}
+
+/// Parses rules such as
+/// `50051=helloworld.HelloRequest:helloworld.HelloReply,/echo.Echo/Say=echo.Message`.
+/// A single type applies to both directions.
+fn parse_rules(config: &str) -> HashMap<String, Rule> {
+    config
+        .split(',')
+        .filter_map(|rule| {
+            let mut parts = rule.splitn(2, '=');
+            let key = parts.next()?.trim();
+            let mut types = parts.next()?.splitn(2, ':');
+            let request = types.next()?.trim().to_string();
+            let response = types
+                .next()
+                .map(|typ| typ.trim().to_string())
+                .unwrap_or_else(|| request.clone());
+            Some((key.to_string(), Rule { request, response }))
+        })
+        .collect()
+}
+
+/// Returns the length of the complete messages at the start of `data`.
+fn complete_len(data: &[u8]) -> usize {
+    let mut end = 0;
+    while end + PREFIX_LEN <= data.len() {
+        let len = data[end + 1..end + PREFIX_LEN]
+            .iter()
+            .fold(0, |n, b| n << 8 | *b as usize);
+        if len > data.len() - end - PREFIX_LEN {
+            break;
+        }
+        end += PREFIX_LEN + len;
+    }
+    end
+}
+
+struct GrpcWorker {
+    registry: Option<Registry>,
+    rules: HashMap<String, Rule>,
+    classes: Classes,
+    /// The calls by the client, the server and the HTTP/3 stream ID, which
+    /// is zero for HTTP/1.1.
+    calls: HashMap<(Endpoint, Endpoint, u64), VecDeque<Call>>,
+    /// The incomplete messages of HTTP/3 streams by the sender, the receiver
+    /// and the stream ID.
+    pending: HashMap<(Endpoint, Endpoint, u64), Vec<u8>>,
+    /// The HTTP/2 connections by the transport, true for TLS, and the
+    /// ordered endpoints.
+    connections: HashMap<(bool, Endpoint, Endpoint), H2Flow>,
+}
+
+impl GrpcWorker {
+    /// Returns the message type of a call, or `None` if the messages are
+    /// not gRPC. The port is the one of the server.
+    fn message_type(&self, call: &Call, port: u64, request: bool) -> Option<Option<String>> {
+        let rule = self
+            .rules
+            .get(&call.path)
+            .filter(|_| !call.path.is_empty())
+            .or_else(|| self.rules.get(&port.to_string()));
+        if let Some(rule) = rule {
+            let typ = if request {
+                &rule.request
+            } else {
+                &rule.response
+            };
+            return Some(Some(typ.clone()));
+        }
+        let method = self
+            .registry
+            .as_ref()
+            .and_then(|registry| registry.method(&call.path));
+        if let Some(method) = method {
+            let typ = if request {
+                &method.input
+            } else {
+                &method.output
+            };
+            return Some(Some(typ.clone()));
+        }
+        if call.grpc {
+            Some(None)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the layer of the messages of an HTTP/1.1 body.
+    fn http(&mut self, stack: &LayerStack, parent: &Parent) -> Result<Option<Layer>> {
+        let (src, dst) = endpoints(stack, token!("tcp.src"), token!("tcp.dst"))?;
+        let content_type: String =
+            parent_value(parent, token!("http.contentType"))?.unwrap_or_default();
+        let (call, port, request) = match parent_value(parent, token!("http.uri"))? {
+            Some(path) => {
+                let call = Call {
+                    path,
+                    grpc: is_grpc(&content_type),
+                };
+                self.calls
+                    .entry((src, dst.clone(), 0))
+                    .or_default()
+                    .push_back(call.clone());
+                (call, dst.1, true)
+            }
+            None => {
+                let key = (dst, src.clone(), 0);
+                let mut call = self
+                    .calls
+                    .get_mut(&key)
+                    .and_then(|calls| calls.pop_front())
+                    .unwrap_or_default();
+                if self.calls.get(&key).is_some_and(|calls| calls.is_empty()) {
+                    self.calls.remove(&key);
+                }
+                call.grpc |= is_grpc(&content_type);
+                (call, src.1, false)
+            }
+        };
+        let body = match payload(parent, token!("@body:http")) {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let typ = match self.message_type(&call, port, request) {
+            Some(typ) => typ,
+            None => return Ok(None),
+        };
+        self.layer(body, &call.path, typ.as_deref()).map(Some)
+    }
+
+    /// Returns the layer of the complete messages of an HTTP/3 frame.
+    fn http3(&mut self, stack: &LayerStack, parent: &Parent) -> Result<Option<Layer>> {
+        let (src, dst) = endpoints(stack, token!("udp.src"), token!("udp.dst"))?;
+        let stream: u64 = parent_value(parent, token!("http3.streamId"))?.unwrap_or(0);
+        let content_type: String =
+            parent_value(parent, token!("http3.contentType"))?.unwrap_or_default();
+
+        // HEADERS frames.
+        if let Some(path) = parent_value(parent, token!("http3.path"))? {
+            let call = Call {
+                path,
+                grpc: is_grpc(&content_type),
+            };
+            self.calls
+                .entry((src, dst, stream))
+                .or_default()
+                .push_back(call);
+            return Ok(None);
+        }
+        if is_grpc(&content_type) {
+            let key = (dst.clone(), src.clone(), stream);
+            if let Some(call) = self.calls.get_mut(&key).and_then(|c| c.front_mut()) {
+                call.grpc = true;
+            }
+        }
+
+        let body = match payload(parent, token!("@body:http3")) {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let request_key = (src.clone(), dst.clone(), stream);
+        let response_key = (dst.clone(), src.clone(), stream);
+        let (call, port, request) = if let Some(calls) = self.calls.get(&request_key) {
+            (calls.front().cloned(), dst.1, true)
+        } else if let Some(calls) = self.calls.get(&response_key) {
+            (calls.front().cloned(), src.1, false)
+        } else {
+            (None, dst.1, true)
+        };
+        let call = call.unwrap_or_default();
+        let typ = match self.message_type(&call, port, request) {
+            Some(typ) => typ,
+            None => return Ok(None),
+        };
+
+        // A message may span several DATA frames.
+        let mut data = self.pending.remove(&request_key).unwrap_or_default();
+        data.extend_from_slice(&body);
+        let rest = data.split_off(complete_len(&data));
+        if !rest.is_empty() {
+            self.pending.insert(request_key, rest);
+        }
+        if data.is_empty() {
+            return Ok(None);
+        }
+        self.layer(data, &call.path, typ.as_deref()).map(Some)
+    }
+
+    /// Returns the layers of the complete messages of the HTTP/2 frames in
+    /// the stream data of a TCP or TLS layer.
+    fn http2(&mut self, stack: &LayerStack, parent: &Parent) -> Result<Vec<Layer>> {
+        let (id, tls) = if parent.id() == token!("tls") {
+            (token!("@data:tls"), true)
+        } else if parent.attr(token!("tcp.stream.payloads")).is_some() {
+            (token!("@stream:tcp"), false)
+        } else {
+            // Wait for the stream reassembler.
+            return Ok(Vec::new());
+        };
+        let payloads: Vec<&Payload> = parent.payloads().iter().filter(|p| p.id() == id).collect();
+        let is_http2 = payloads.iter().any(|p| p.typ() == token!("@data:http2"));
+
+        // Both directions share a flow keyed by the ordered endpoints.
+        let (src, dst) = endpoints(stack, token!("tcp.src"), token!("tcp.dst"))?;
+        let flags: u64 = stack_value(stack, token!("tcp.flags"))?.unwrap_or(0);
+        let (key, dir) = if src <= dst {
+            ((tls, src, dst), 0)
+        } else {
+            ((tls, dst, src), 1)
+        };
+        if !is_http2 && !self.connections.contains_key(&key) {
+            return Ok(Vec::new());
+        }
+        // The ports of the senders by direction.
+        let ports = [(key.1).1, (key.2).1];
+        let data = payloads.iter().fold(Vec::new(), |mut data, p| {
+            data.extend_from_slice(&p.data());
+            data
+        });
+
+        let mut flow = self.connections.remove(&key).unwrap_or_default();
+        let mut layers = Vec::new();
+        for event in flow.frames.push(dir, &data) {
+            match event {
+                Event::Headers {
+                    stream,
+                    fields,
+                    end,
+                } => {
+                    let fields = fields.unwrap_or_default();
+                    let header = |name: &[u8]| {
+                        fields
+                            .iter()
+                            .find(|(n, _)| n.as_slice() == name)
+                            .map(|(_, value)| String::from_utf8_lossy(value).into_owned())
+                    };
+                    let grpc = header(b"content-type").is_some_and(|typ| is_grpc(&typ));
+                    if let Some(path) = header(b":path") {
+                        let call = Call { path, grpc };
+                        flow.streams.insert(
+                            stream,
+                            H2Stream {
+                                call,
+                                client: dir,
+                                ..H2Stream::default()
+                            },
+                        );
+                    } else if let Some(stream) = flow.streams.get_mut(&stream) {
+                        stream.call.grpc |= grpc;
+                    }
+                    if end {
+                        flow.end(stream, dir);
+                    }
+                }
+                Event::Data { stream, data, end } => {
+                    // The request headers may not be captured.
+                    let state = flow.streams.entry(stream).or_insert_with(|| H2Stream {
+                        client: dir,
+                        ..H2Stream::default()
+                    });
+                    let port = ports[1 - state.client];
+                    let typ = self.message_type(&state.call, port, state.client == dir);
+                    if let Some(typ) = typ {
+                        // A message may span several DATA frames.
+                        let mut data = [mem::take(&mut state.pending[dir]), data].concat();
+                        state.pending[dir] = data.split_off(complete_len(&data));
+                        if !data.is_empty() {
+                            layers.push(self.layer(data, &state.call.path, typ.as_deref())?);
+                        }
+                    }
+                    if end {
+                        flow.end(stream, dir);
+                    }
+                }
+                Event::Reset { stream } => {
+                    flow.streams.remove(&stream);
+                }
+            }
+        }
+
+        // FIN or RST.
+        if flags & 0x5 != 0 {
+            flow.closed[dir] = true;
+        }
+        if !flow.closed[0] || !flow.closed[1] {
+            self.connections.insert(key, flow);
+        }
+        Ok(layers)
+    }
+
+    /// Returns the layer of the messages in `data` of the type `typ`.
+    fn layer<B: Into<ByteSlice>>(
+        &mut self,
+        data: B,
+        path: &str,
+        typ: Option<&str>,
+    ) -> Result<Layer> {
+        let mut layer = Layer::new(&GRPC_CLASS, data);
+        if !path.is_empty() {
+            layer.add_attr(attr!(&PATH_ATTR, value: path.to_string().into_boxed_str()));
+        }
+
+        let data = layer.data();
+        let end = complete_len(&data);
+        let mut offset = 0;
+        while offset < end {
+            let flags = data[offset];
+            let len = data[offset + 1..offset + PREFIX_LEN]
+                .iter()
+                .fold(0, |n, b| n << 8 | *b as usize);
+            let start = offset + PREFIX_LEN;
+            let range = start..start + len;
+            layer.add_attr(attr!(&MESSAGE_ATTR, range: offset..range.end));
+            layer.add_attr(
+                attr!(&COMPRESSED_ATTR, range: offset..offset + 1, value: flags & 1 != 0),
+            );
+            layer.add_attr(attr!(&LENGTH_ATTR, range: offset + 1..start));
+            if flags & FLAG_TRAILERS != 0 {
+                let trailers = String::from_utf8_lossy(&data[range.clone()]).into_owned();
+                layer.add_attr(
+                    attr!(&TRAILERS_ATTR, range: range.clone(), value: trailers.into_boxed_str()),
+                );
+            } else if flags & 1 != 0 {
+                layer.add_attr(attr!(&NOT_DECODED_ATTR, range: range.clone()));
+            } else {
+                if let Some(typ) = typ {
+                    layer.add_attr(attr!(&TYPE_ATTR, value: typ.to_string().into_boxed_str()));
+                }
+                layer.add_attr(attr!(&FIELDS_ATTR, range: range.clone()));
+                let mut dissector = Dissector {
+                    registry: self.registry.as_ref(),
+                    classes: &mut self.classes,
+                    layer: &mut layer,
+                };
+                if !dissector.message(range.clone(), typ, FIELDS_PREFIX, 0)? {
+                    layer.add_attr(attr!(&MALFORMED_ATTR, range: range.clone()));
+                }
+            }
+            offset = range.end;
+        }
+        if end < data.len() {
+            layer.add_attr(attr!(&MALFORMED_ATTR, range: end..data.len()));
+        }
+        Ok(layer)
+    }
+}
+
+fn payload(parent: &Parent, id: Token) -> Option<ByteSlice> {
+    parent
+        .payloads()
+        .iter()
+        .find(|p| p.id() == id)
+        .map(|p| p.data())
+}
+
+/// Returns the source and destination endpoints of the frame, given the
+/// port attributes of the transport.
+fn endpoints(stack: &LayerStack, src: Token, dst: Token) -> Result<(Endpoint, Endpoint)> {
+    let saddr: Vec<u8> = stack_value(stack, token!("_.src"))?.unwrap_or_default();
+    let daddr: Vec<u8> = stack_value(stack, token!("_.dst"))?.unwrap_or_default();
+    let sport: u64 = stack_value(stack, src)?.unwrap_or(0);
+    let dport: u64 = stack_value(stack, dst)?.unwrap_or(0);
+    Ok(((saddr, sport), (daddr, dport)))
+}
+
+impl Worker for GrpcWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let layers: Vec<Layer> = if parent.id() == token!("http") {
+            self.http(stack, parent)?.into_iter().collect()
+        } else if parent.id() == token!("http3") {
+            self.http3(stack, parent)?.into_iter().collect()
+        } else if parent.id() == token!("tcp") || parent.id() == token!("tls") {
+            self.http2(stack, parent)?
+        } else {
+            return Ok(Status::Skip);
+        };
+        if layers.is_empty() {
+            return Ok(Status::Skip);
+        }
+        for layer in layers {
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct GrpcDecoder {}
+
+impl Decoder for GrpcDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        let path = ctx.get_config(DESCRIPTOR_SET_KEY).trim_matches('"');
+        let registry = if path.is_empty() {
+            None
+        } else {
+            fs::read(path).ok().and_then(|data| Registry::parse(&data))
+        };
+        let rules = parse_rules(ctx.get_config(MESSAGE_TYPES_KEY).trim_matches('"'));
+        Box::new(GrpcWorker {
+            registry,
+            rules,
+            classes: Classes::new(),
+            calls: HashMap::new(),
+            pending: HashMap::new(),
+            connections: HashMap::new(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        let descriptor_set = DecoderOption::path(DESCRIPTOR_SET_KEY).description(
+            "Path of a descriptor set written by protoc --descriptor_set_out --include_imports",
+        );
+        let message_types = DecoderOption::string(MESSAGE_TYPES_KEY, "").description(
+            "Message types by server port or request path, e.g. \
+             50051=helloworld.HelloRequest:helloworld.HelloReply,/echo.Echo/Say=echo.Message",
+        );
+        Metadata {
+            exec_type: ExecType::SerialSync,
+            options: vec![descriptor_set, message_types],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(GRPC_CLASS, "grpc");
+
+def_attr_class!(PATH_ATTR, "grpc.path");
+
+def_attr_class!(MESSAGE_ATTR, "grpc.message",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(COMPRESSED_ATTR, "grpc.message.compressed");
+
+def_attr_class!(LENGTH_ATTR, "grpc.message.length", cast: cast::UInt32BE());
+
+def_attr_class!(TYPE_ATTR, "grpc.message.type");
+
+def_attr_class!(FIELDS_ATTR, "grpc.message.fields",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(TRAILERS_ATTR, "grpc.message.trailers");
+
+def_attr_class!(NOT_DECODED_ATTR, "grpc.message.notDecoded",
+    typ: "@expert:note",
+    description: "Compressed message not decoded"
+);
+
+def_attr_class!(MALFORMED_ATTR, "grpc.malformed",
+    typ: "@expert:error",
+    description: "Truncated or malformed message"
+);
+
+genet_decoders!(GrpcDecoder {});
@@ -0,0 +1,216 @@
+//! Dissection of Protocol Buffers messages into attributes.
+//!
+//! The attribute IDs follow the field names, e.g. `<prefix>.user.id`, so
+//! the classes are created at runtime. Repeated fields and map entries add
+//! an attribute per element with the same ID. Fields without a type in the
+//! descriptor set are shown as `<prefix>.unknown` with the wire type.
+
+use descriptor::*;
+use genet_sdk::{dynamic::Classes, prelude::*, variant::Variant};
+use std::ops::Range;
+use wire::{self, Field, Value, WIRE_I32, WIRE_I64, WIRE_LEN, WIRE_SGROUP, WIRE_VARINT};
+
+/// Messages nested deeper than this are shown as bytes.
+const MAX_DEPTH: usize = 32;
+
+pub struct Dissector<'a> {
+    pub registry: Option<&'a Registry>,
+    pub classes: &'a mut Classes,
+    pub layer: &'a mut Layer,
+}
+
+impl<'a> Dissector<'a> {
+    /// Adds the fields in `range` of the layer data as the message type
+    /// `typ`, and returns false if the message is malformed.
+    pub fn message(
+        &mut self,
+        range: Range<usize>,
+        typ: Option<&str>,
+        prefix: &str,
+        depth: usize,
+    ) -> Result<bool> {
+        let data = self.layer.data();
+        let (fields, malformed) = wire::fields(&data, range);
+        let types = match (self.registry, typ) {
+            (Some(registry), Some(typ)) => registry.message(typ),
+            _ => None,
+        };
+        for field in &fields {
+            let typ = types.and_then(|types| types.get(&field.number));
+            let known = match typ {
+                Some(typ) => self.field(&data, field, typ, prefix, depth)?,
+                None => false,
+            };
+            if !known {
+                self.unknown(field, prefix)?;
+            }
+        }
+        Ok(!malformed)
+    }
+
+    /// Adds a field of the type `typ`, and returns false if the wire type
+    /// does not match.
+    fn field(
+        &mut self,
+        data: &[u8],
+        field: &Field,
+        typ: &FieldType,
+        prefix: &str,
+        depth: usize,
+    ) -> Result<bool> {
+        let id = format!("{}.{}", prefix, typ.name);
+        let range = field.range.clone();
+        match (&field.value, typ.typ) {
+            (Value::Len(value), TYPE_STRING) => {
+                let string = String::from_utf8_lossy(&data[value.clone()]).into_owned();
+                self.add(&id, "", value.clone(), string.into_boxed_str());
+            }
+            (Value::Len(value), TYPE_BYTES) => {
+                self.classes
+                    .add_attr(self.layer, &id, "", value.clone(), "bytes")?;
+            }
+            (Value::Len(value), TYPE_MESSAGE) | (Value::Group(value), TYPE_GROUP) => {
+                if depth >= MAX_DEPTH {
+                    self.classes
+                        .add_attr(self.layer, &id, "", value.clone(), "bytes")?;
+                } else {
+                    self.add(&id, "@nested", range.clone(), true);
+                    if !self.message(value.clone(), Some(&typ.type_name), &id, depth + 1)? {
+                        self.layer.add_attr(attr!(&::MALFORMED_ATTR, range: range));
+                    }
+                }
+            }
+            // Packed repeated scalars.
+            (Value::Len(value), _) => match wire_type(typ.typ) {
+                Some(WIRE_VARINT) => {
+                    let mut pos = value.start;
+                    while pos < value.end {
+                        let start = pos;
+                        match wire::varint(&data[..value.end], &mut pos) {
+                            Some(raw) => self.scalar(&id, typ, start..pos, raw),
+                            None => return Ok(false),
+                        }
+                    }
+                }
+                Some(WIRE_I64) | Some(WIRE_I32) => {
+                    let size = if wire_type(typ.typ) == Some(WIRE_I64) {
+                        8
+                    } else {
+                        4
+                    };
+                    if value.len() % size != 0 {
+                        return Ok(false);
+                    }
+                    for start in value.clone().step_by(size) {
+                        let bytes = &data[start..start + size];
+                        let raw = bytes.iter().rev().fold(0, |n, b| n << 8 | u64::from(*b));
+                        self.scalar(&id, typ, start..start + size, raw);
+                    }
+                }
+                _ => return Ok(false),
+            },
+            (Value::Varint(raw), _) if wire_type(typ.typ) == Some(WIRE_VARINT) => {
+                self.scalar(&id, typ, range, *raw)
+            }
+            (Value::I64(raw), _) if wire_type(typ.typ) == Some(WIRE_I64) => {
+                self.scalar(&id, typ, range, *raw)
+            }
+            (Value::I32(raw), _) if wire_type(typ.typ) == Some(WIRE_I32) => {
+                self.scalar(&id, typ, range, u64::from(*raw))
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn scalar(&mut self, id: &str, typ: &FieldType, range: Range<usize>, raw: u64) {
+        let value = match typ.typ {
+            TYPE_DOUBLE => Variant::Float64(f64::from_bits(raw)),
+            TYPE_FLOAT => Variant::Float64(f64::from(f32::from_bits(raw as u32))),
+            TYPE_INT64 | TYPE_SFIXED64 => Variant::Int64(raw as i64),
+            TYPE_INT32 | TYPE_SFIXED32 => Variant::Int64(i64::from(raw as i32)),
+            TYPE_UINT64 | TYPE_FIXED64 => Variant::UInt64(raw),
+            TYPE_UINT32 | TYPE_FIXED32 => Variant::UInt64(u64::from(raw as u32)),
+            TYPE_SINT64 => Variant::Int64(zigzag(raw)),
+            TYPE_SINT32 => Variant::Int64(i64::from(zigzag(raw) as i32)),
+            TYPE_BOOL => Variant::Bool(raw != 0),
+            TYPE_ENUM => {
+                let number = i64::from(raw as i32);
+                self.add(id, "@enum", range.clone(), number);
+                let name = self
+                    .registry
+                    .and_then(|registry| registry.enum_value(&typ.type_name, number));
+                if let Some(name) = name {
+                    self.add(&format!("{}.{}", id, name), "@novalue", range, true);
+                }
+                return;
+            }
+            _ => return,
+        };
+        self.add(id, "", range, value);
+    }
+
+    /// Adds a field by the wire type.
+    fn unknown(&mut self, field: &Field, prefix: &str) -> Result<()> {
+        let id = format!("{}.unknown", prefix);
+        self.add(&id, "@nested", field.range.clone(), true);
+        self.add(
+            &format!("{}.number", id),
+            "",
+            field.tag.clone(),
+            field.number,
+        );
+        let wire_type = format!("{}.wireType", id);
+        self.add(
+            &wire_type,
+            "@enum",
+            field.tag.clone(),
+            u64::from(field.wire_type),
+        );
+        if let Some(name) = wire_type_name(field.wire_type) {
+            let id = format!("{}.{}", wire_type, name);
+            self.add(&id, "@novalue", field.tag.clone(), true);
+        }
+        let value = format!("{}.value", id);
+        let range = field.tag.end..field.range.end;
+        match &field.value {
+            Value::Varint(raw) | Value::I64(raw) => self.add(&value, "", range, *raw),
+            Value::I32(raw) => self.add(&value, "", range, u64::from(*raw)),
+            Value::Len(range) | Value::Group(range) => {
+                self.classes
+                    .add_attr(self.layer, &value, "", range.clone(), "bytes")?
+            }
+        }
+        Ok(())
+    }
+
+    fn add<T: Into<Variant>>(&mut self, id: &str, typ: &str, range: Range<usize>, value: T) {
+        self.classes.add_value(self.layer, id, typ, range, value);
+    }
+}
+
+/// Returns the wire type of the scalar type `typ`.
+fn wire_type(typ: u64) -> Option<u8> {
+    match typ {
+        TYPE_INT64 | TYPE_UINT64 | TYPE_INT32 | TYPE_BOOL | TYPE_UINT32 | TYPE_ENUM
+        | TYPE_SINT32 | TYPE_SINT64 => Some(WIRE_VARINT),
+        TYPE_DOUBLE | TYPE_FIXED64 | TYPE_SFIXED64 => Some(WIRE_I64),
+        TYPE_FLOAT | TYPE_FIXED32 | TYPE_SFIXED32 => Some(WIRE_I32),
+        _ => None,
+    }
+}
+
+fn wire_type_name(wire_type: u8) -> Option<&'static str> {
+    match wire_type {
+        WIRE_VARINT => Some("varint"),
+        WIRE_I64 => Some("i64"),
+        WIRE_LEN => Some("len"),
+        WIRE_SGROUP => Some("group"),
+        WIRE_I32 => Some("i32"),
+        _ => None,
+    }
+}
+
+fn zigzag(raw: u64) -> i64 {
+    (raw >> 1) as i64 ^ -((raw & 1) as i64)
+}
@@ -0,0 +1,279 @@
+//! Protocol Buffers wire format.
+
+use std::ops::Range;
+
+pub const WIRE_VARINT: u8 = 0;
+pub const WIRE_I64: u8 = 1;
+pub const WIRE_LEN: u8 = 2;
+pub const WIRE_SGROUP: u8 = 3;
+pub const WIRE_EGROUP: u8 = 4;
+pub const WIRE_I32: u8 = 5;
+
+/// Groups nested deeper than this are treated as malformed.
+const MAX_GROUP_DEPTH: usize = 32;
+
+/// The value of a field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Varint(u64),
+    I64(u64),
+    I32(u32),
+    /// The range of a length-delimited value.
+    Len(Range<usize>),
+    /// The range of the fields between the start and the end group tags.
+    Group(Range<usize>),
+}
+
+/// A field. The ranges are relative to the whole data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub number: u64,
+    pub wire_type: u8,
+    /// The range of the tag.
+    pub tag: Range<usize>,
+    /// The range of the whole field.
+    pub range: Range<usize>,
+    pub value: Value,
+}
+
+/// Reads a varint at `pos`, advancing `pos`.
+pub fn varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let b = *data.get(*pos + i)?;
+        value |= u64::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            *pos += i + 1;
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Splits `range` of `data` into fields, and returns them with whether the
+/// data following the last field is malformed.
+pub fn fields(data: &[u8], range: Range<usize>) -> (Vec<Field>, bool) {
+    let data = &data[..range.end];
+    let mut fields = Vec::new();
+    let mut pos = range.start;
+    while pos < data.len() {
+        match field(data, &mut pos, 0) {
+            Some(field) => {
+                // An end group tag outside of a group.
+                if field.wire_type == WIRE_EGROUP {
+                    return (fields, true);
+                }
+                fields.push(field);
+            }
+            None => return (fields, true),
+        }
+    }
+    (fields, false)
+}
+
+fn field(data: &[u8], pos: &mut usize, depth: usize) -> Option<Field> {
+    let start = *pos;
+    let tag = varint(data, pos)?;
+    let number = tag >> 3;
+    let wire_type = (tag & 0x7) as u8;
+    if number == 0 {
+        return None;
+    }
+    let tag_range = start..*pos;
+    let value = match wire_type {
+        WIRE_VARINT => Value::Varint(varint(data, pos)?),
+        WIRE_I64 => {
+            let bytes = data.get(*pos..*pos + 8)?;
+            *pos += 8;
+            Value::I64(bytes.iter().rev().fold(0, |n, b| n << 8 | u64::from(*b)))
+        }
+        WIRE_I32 => {
+            let bytes = data.get(*pos..*pos + 4)?;
+            *pos += 4;
+            Value::I32(bytes.iter().rev().fold(0, |n, b| n << 8 | u32::from(*b)))
+        }
+        WIRE_LEN => {
+            let len = varint(data, pos)? as usize;
+            if len > data.len() - *pos {
+                return None;
+            }
+            *pos += len;
+            Value::Len(*pos - len..*pos)
+        }
+        WIRE_SGROUP => {
+            if depth >= MAX_GROUP_DEPTH {
+                return None;
+            }
+            let fields_start = *pos;
+            loop {
+                let end = *pos;
+                let inner = field(data, pos, depth + 1)?;
+                if inner.wire_type == WIRE_EGROUP {
+                    if inner.number != number {
+                        return None;
+                    }
+                    break Value::Group(fields_start..end);
+                }
+            }
+        }
+        WIRE_EGROUP => Value::Group(*pos..*pos),
+        _ => return None,
+    };
+    Some(Field {
+        number,
+        wire_type,
+        tag: tag_range,
+        range: start..*pos,
+        value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use wire::{fields, varint, Field, Value, MAX_GROUP_DEPTH};
+
+    #[test]
+    fn varints() {
+        let mut pos = 0;
+        assert_eq!(varint(b"\x01\xac\x02", &mut pos), Some(1));
+        assert_eq!(varint(b"\x01\xac\x02", &mut pos), Some(300));
+        assert_eq!(pos, 3);
+
+        let max = b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01";
+        let mut pos = 0;
+        assert_eq!(varint(max, &mut pos), Some(u64::MAX));
+        assert_eq!(pos, 10);
+
+        // Truncated, and longer than 10 bytes.
+        let mut pos = 0;
+        assert_eq!(varint(b"\x80\x80", &mut pos), None);
+        assert_eq!(varint(&[0x80; 11], &mut pos), None);
+        assert_eq!(pos, 0);
+    }
+
+    #[test]
+    fn scalars() {
+        let data =
+            b"\x08\x96\x01\x11\x01\x00\x00\x00\x00\x00\x00\x80\x1d\x02\x01\x00\x00\x22\x02hi";
+        let (list, malformed) = fields(data, 0..data.len());
+        assert!(!malformed);
+        assert_eq!(
+            list,
+            vec![
+                Field {
+                    number: 1,
+                    wire_type: 0,
+                    tag: 0..1,
+                    range: 0..3,
+                    value: Value::Varint(150),
+                },
+                Field {
+                    number: 2,
+                    wire_type: 1,
+                    tag: 3..4,
+                    range: 3..12,
+                    value: Value::I64(0x8000_0000_0000_0001),
+                },
+                Field {
+                    number: 3,
+                    wire_type: 5,
+                    tag: 12..13,
+                    range: 12..17,
+                    value: Value::I32(0x0102),
+                },
+                Field {
+                    number: 4,
+                    wire_type: 2,
+                    tag: 17..18,
+                    range: 17..21,
+                    value: Value::Len(19..21),
+                },
+            ]
+        );
+
+        // A range of the data.
+        let (list, malformed) = fields(data, 17..21);
+        assert!(!malformed);
+        assert_eq!(list[0].value, Value::Len(19..21));
+    }
+
+    #[test]
+    fn malformed_lengths() {
+        // Longer than the data.
+        let (list, malformed) = fields(b"\x08\x01\x12\x05abc", 0..7);
+        assert!(malformed);
+        assert_eq!(list.len(), 1);
+
+        // Longer than the range, although not than the data.
+        let (list, malformed) = fields(b"\x12\x03abc", 0..4);
+        assert!(malformed);
+        assert!(list.is_empty());
+
+        // Truncated fixed-size values and a truncated varint.
+        assert!(fields(b"\x09\x01\x02", 0..3).1);
+        assert!(fields(b"\x0d\x01\x02\x03", 0..4).1);
+        assert!(fields(b"\x08\x80", 0..2).1);
+        assert!(fields(b"\x12\x80", 0..2).1);
+    }
+
+    #[test]
+    fn malformed_tags() {
+        // Field number zero.
+        assert!(fields(b"\x00\x01", 0..2).1);
+        // Wire types 6 and 7.
+        assert!(fields(b"\x0e", 0..1).1);
+        assert!(fields(b"\x0f", 0..1).1);
+        // A truncated tag.
+        let (list, malformed) = fields(b"\x08\x01\x88", 0..3);
+        assert!(malformed);
+        assert_eq!(list.len(), 1);
+    }
+
+    #[test]
+    fn groups() {
+        // Group 1 containing field 2 and group 3, which contains field 4.
+        let data = b"\x0b\x10\x01\x1b\x20\x02\x1c\x0c\x28\x03";
+        let (list, malformed) = fields(data, 0..data.len());
+        assert!(!malformed);
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].wire_type, 3);
+        assert_eq!(list[0].range, 0..8);
+        assert_eq!(list[0].value, Value::Group(1..7));
+        assert_eq!(list[1].value, Value::Varint(3));
+
+        let (inner, malformed) = fields(data, 1..7);
+        assert!(!malformed);
+        assert_eq!(inner.len(), 2);
+        assert_eq!(inner[1].value, Value::Group(4..6));
+
+        // An empty group.
+        let (list, malformed) = fields(b"\x0b\x0c", 0..2);
+        assert!(!malformed);
+        assert_eq!(list[0].value, Value::Group(1..1));
+    }
+
+    #[test]
+    fn malformed_groups() {
+        // Not terminated.
+        assert!(fields(b"\x0b\x10\x01", 0..3).1);
+        // Terminated by the end tag of another field.
+        assert!(fields(b"\x0b\x10\x01\x14", 0..4).1);
+        // An end tag outside of a group.
+        let (list, malformed) = fields(b"\x08\x01\x0c", 0..3);
+        assert!(malformed);
+        assert_eq!(list.len(), 1);
+        // A malformed field in a group.
+        assert!(fields(b"\x0b\x12\x05a\x0c", 0..5).1);
+
+        // Nested too deeply.
+        let nested = |depth: usize| {
+            let mut data = vec![0x0b; depth];
+            data.extend(vec![0x0c; depth]);
+            data
+        };
+        let data = nested(MAX_GROUP_DEPTH);
+        assert!(!fields(&data, 0..data.len()).1);
+        let data = nested(MAX_GROUP_DEPTH + 1);
+        assert!(fields(&data, 0..data.len()).1);
+    }
+}
@@ -0,0 +1,49 @@
+//! Content codings of bodies.
+
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use parser::MAX_MESSAGE_LEN;
+use std::io::{self, Read};
+
+/// The result of decoding a body.
+pub enum Decoded {
+    Body(Vec<u8>),
+    /// The coding is not supported, e.g. `br`.
+    Unsupported,
+    Error,
+}
+
+fn read_all<R: Read>(reader: R) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    reader.take(MAX_MESSAGE_LEN as u64).read_to_end(&mut body)?;
+    Ok(body)
+}
+
+fn decode_one(coding: &str, body: &[u8]) -> Decoded {
+    let result = match coding {
+        "identity" | "" => return Decoded::Body(body.to_vec()),
+        "gzip" | "x-gzip" => read_all(GzDecoder::new(body)),
+        // Some servers send raw deflate data without the zlib wrapper.
+        "deflate" => {
+            read_all(ZlibDecoder::new(body)).or_else(|_| read_all(DeflateDecoder::new(body)))
+        }
+        _ => return Decoded::Unsupported,
+    };
+    match result {
+        Ok(body) => Decoded::Body(body),
+        Err(_) => Decoded::Error,
+    }
+}
+
+/// Decodes `body` with the codings of a Content-Encoding header, which are
+/// applied in the listed order.
+pub fn decode(encoding: &str, body: &[u8]) -> Decoded {
+    let mut body = body.to_vec();
+    for coding in encoding.rsplit(',') {
+        let coding = coding.trim().to_ascii_lowercase();
+        match decode_one(&coding, &body) {
+            Decoded::Body(decoded) => body = decoded,
+            other => return other,
+        }
+    }
+    Decoded::Body(body)
+}
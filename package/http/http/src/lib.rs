@@ -0,0 +1,282 @@
+extern crate flate2;
+extern crate genet_sdk;
+
+mod content;
+mod parser;
+
+use content::Decoded;
+use genet_sdk::{cast, decoder::*, prelude::*, variant::Variant};
+use parser::{Message, Parser, StartLine};
+use std::collections::{HashMap, VecDeque};
+
+type Endpoint = (Vec<u8>, u64);
+
+/// The state of a connection.
+#[derive(Default)]
+struct Flow {
+    directions: [Parser; 2],
+    closed: [bool; 2],
+    /// The requests waiting for a response, true for HEAD requests.
+    heads: VecDeque<bool>,
+}
+
+/// Returns the value of the attribute `id` of the topmost layer having it.
+fn stack_value<T>(stack: &LayerStack, id: Token) -> Result<Option<T>>
+where
+    Variant: Value<T>,
+{
+    for layer in stack.layers().rev() {
+        if let Some(attr) = layer.attr(id) {
+            return Ok(Some(attr.try_get(layer)?.try_into()?));
+        }
+    }
+    Ok(None)
+}
+
+struct HttpWorker {
+    /// The flows by the transport of the messages, true for TLS, and the
+    /// ordered endpoints.
+    flows: HashMap<(bool, Endpoint, Endpoint), Flow>,
+}
+
+impl HttpWorker {
+    /// Returns the stream data of the frame, and whether it is decrypted
+    /// from TLS.
+    fn stream_data(parent: &Parent) -> Option<(Vec<u8>, bool)> {
+        let (id, tls) = if parent.id() == token!("tls") {
+            (token!("@data:tls"), true)
+        } else if parent.id() == token!("tcp") {
+            (token!("@stream:tcp"), false)
+        } else {
+            return None;
+        };
+        let data = parent
+            .payloads()
+            .iter()
+            .filter(|p| p.id() == id)
+            .fold(Vec::new(), |mut data, p| {
+                data.extend_from_slice(&p.data());
+                data
+            });
+        Some((data, tls))
+    }
+}
+
+impl Worker for HttpWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        // Wait for the stream reassembler.
+        if parent.id() == token!("tcp") && parent.attr(token!("tcp.stream.payloads")).is_none() {
+            return Ok(Status::Skip);
+        }
+
+        let (data, tls) = match Self::stream_data(parent) {
+            Some(data) => data,
+            None => return Ok(Status::Skip),
+        };
+
+        let src: Vec<u8> = stack_value(stack, token!("_.src"))?.unwrap_or_default();
+        let dst: Vec<u8> = stack_value(stack, token!("_.dst"))?.unwrap_or_default();
+        let sport: u64 = stack_value(stack, token!("tcp.src"))?.unwrap_or(0);
+        let dport: u64 = stack_value(stack, token!("tcp.dst"))?.unwrap_or(0);
+        let flags: u64 = stack_value(stack, token!("tcp.flags"))?.unwrap_or(0);
+
+        // Both directions share a flow keyed by the ordered endpoints.
+        let a = (src, sport);
+        let b = (dst, dport);
+        let (key, dir) = if a <= b {
+            ((tls, a, b), 0)
+        } else {
+            ((tls, b, a), 1)
+        };
+        let is_http = parent
+            .payloads()
+            .iter()
+            .any(|p| p.typ() == token!("@data:http"));
+        if !is_http && !self.flows.contains_key(&key) {
+            return Ok(Status::Skip);
+        }
+
+        let mut messages = {
+            let flow = self.flows.entry(key.clone()).or_default();
+            let Flow {
+                directions, heads, ..
+            } = flow;
+            directions[dir].push(&data, heads)
+        };
+
+        // FIN or RST.
+        if flags & 0x5 != 0 {
+            let flow = self.flows.get_mut(&key).unwrap();
+            messages.extend(flow.directions[dir].close());
+            flow.closed[dir] = true;
+            if flow.closed[0] && flow.closed[1] {
+                self.flows.remove(&key);
+            }
+        }
+
+        for msg in messages {
+            parent.add_child(message_layer(msg));
+        }
+        Ok(Status::Done)
+    }
+}
+
+fn message_layer(msg: Message) -> Layer {
+    let mut attrs = Vec::new();
+    match msg.start.clone() {
+        Some(StartLine::Request {
+            method,
+            uri,
+            version,
+        }) => {
+            let value = String::from_utf8_lossy(&msg.data[method.clone()]).into_owned();
+            attrs.push(attr!(&METHOD_ATTR, range: method, value: value.into_boxed_str()));
+            let value = String::from_utf8_lossy(&msg.data[uri.clone()]).into_owned();
+            attrs.push(attr!(&URI_ATTR, range: uri, value: value.into_boxed_str()));
+            let value = String::from_utf8_lossy(&msg.data[version.clone()]).into_owned();
+            attrs.push(attr!(&VERSION_ATTR, range: version, value: value.into_boxed_str()));
+        }
+        Some(StartLine::Response {
+            version,
+            status,
+            status_range,
+            reason,
+        }) => {
+            let value = String::from_utf8_lossy(&msg.data[version.clone()]).into_owned();
+            attrs.push(attr!(&VERSION_ATTR, range: version, value: value.into_boxed_str()));
+            attrs.push(attr!(&STATUS_ATTR, range: status_range, value: u64::from(status)));
+            let value = String::from_utf8_lossy(&msg.data[reason.clone()]).into_owned();
+            attrs.push(attr!(&REASON_ATTR, range: reason, value: value.into_boxed_str()));
+        }
+        None => {}
+    }
+
+    if let (Some(first), Some(last)) = (msg.headers.first(), msg.headers.last()) {
+        attrs.push(attr!(&HEADERS_ATTR, range: first.0.start..last.1.end));
+    }
+    for (name, value) in &msg.headers {
+        let range = name.start..value.end;
+        let name_str = String::from_utf8_lossy(&msg.data[name.clone()]).into_owned();
+        let value_str = String::from_utf8_lossy(&msg.data[value.clone()]).into_owned();
+        attrs.push(attr!(&HEADER_ATTR, range: range));
+        attrs.push(attr!(&HEADER_NAME_ATTR, range: name.clone(), value: name_str.clone().into_boxed_str()));
+        attrs.push(attr!(&HEADER_VALUE_ATTR, range: value.clone(), value: value_str.clone().into_boxed_str()));
+        if let Some(class) = get_header(&name_str.to_ascii_lowercase()) {
+            if name_str.eq_ignore_ascii_case("content-length") {
+                if let Ok(len) = value_str.trim().parse::<u64>() {
+                    attrs.push(attr!(class, range: value.clone(), value: len));
+                }
+            } else {
+                attrs.push(attr!(class, range: value.clone(), value: value_str.into_boxed_str()));
+            }
+        }
+    }
+
+    let body_range = msg.head_len..msg.data.len();
+    let mut body = None;
+    if !msg.body.is_empty() {
+        attrs.push(attr!(&BODY_ATTR, range: body_range));
+        match content::decode(msg.header("content-encoding").unwrap_or(""), &msg.body) {
+            Decoded::Body(decoded) => body = Some(decoded),
+            Decoded::Unsupported => attrs.push(attr!(&UNSUPPORTED_ATTR, value: true)),
+            Decoded::Error => attrs.push(attr!(&DECODE_ERROR_ATTR, value: true)),
+        }
+    }
+    if msg.truncated {
+        attrs.push(attr!(&TRUNCATED_ATTR, value: true));
+    }
+
+    let mut layer = Layer::new(&HTTP_CLASS, ByteSlice::from(msg.data));
+    for attr in attrs {
+        layer.add_attr(attr);
+    }
+    if let Some(body) = body {
+        layer.add_payload(Payload::new(ByteSlice::from(body), "@body:http"));
+    }
+    layer
+}
+
+#[derive(Clone)]
+struct HttpDecoder {}
+
+impl Decoder for HttpDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        let table = ctx.dissector_table("tcp.port");
+        table.add_default(80, "@data:http");
+        table.add_default(8080, "@data:http");
+        Box::new(HttpWorker {
+            flows: HashMap::new(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::SerialSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(HTTP_CLASS, "http");
+
+def_attr_class!(METHOD_ATTR, "http.method");
+
+def_attr_class!(URI_ATTR, "http.uri");
+
+def_attr_class!(VERSION_ATTR, "http.version");
+
+def_attr_class!(STATUS_ATTR, "http.status");
+
+def_attr_class!(REASON_ATTR, "http.reason");
+
+def_attr_class!(HEADERS_ATTR, "http.headers",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(HEADER_ATTR, "http.header",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(HEADER_NAME_ATTR, "http.header.name");
+
+def_attr_class!(HEADER_VALUE_ATTR, "http.header.value");
+
+def_attr_class!(BODY_ATTR, "http.body", cast: cast::ByteSlice());
+
+def_attr_class!(TRUNCATED_ATTR, "http.body.truncated",
+    typ: "@expert:note",
+    description: "Body too large to be kept"
+);
+
+def_attr_class!(UNSUPPORTED_ATTR, "http.body.unsupported",
+    typ: "@expert:note",
+    description: "Unsupported content coding"
+);
+
+def_attr_class!(DECODE_ERROR_ATTR, "http.body.decodeError",
+    typ: "@expert:warn",
+    description: "Malformed content coding"
+);
+
+fn get_header(name: &str) -> Option<&'static AttrClass> {
+    match name {
+        "host" => Some(attr_class_lazy!("http.host")),
+        "user-agent" => Some(attr_class_lazy!("http.userAgent")),
+        "content-type" => Some(attr_class_lazy!("http.contentType")),
+        "content-length" => Some(attr_class_lazy!("http.contentLength")),
+        "content-encoding" => Some(attr_class_lazy!("http.contentEncoding")),
+        "transfer-encoding" => Some(attr_class_lazy!("http.transferEncoding")),
+        "location" => Some(attr_class_lazy!("http.location")),
+        "server" => Some(attr_class_lazy!("http.server")),
+        _ => None,
+    }
+}
+
+genet_decoders!(HttpDecoder {});
@@ -0,0 +1,361 @@
+//! Incremental parser of HTTP/1.x messages.
+
+use std::{collections::VecDeque, ops::Range, str};
+
+/// Heads longer than this are rejected.
+const MAX_HEAD_LEN: usize = 64 * 1024;
+
+/// The bytes of a message kept for its layer. The rest of the body is
+/// skipped.
+pub const MAX_MESSAGE_LEN: usize = 16 * 1024 * 1024;
+
+/// How the end of a body is determined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Framing {
+    Length(usize),
+    Chunked(Chunk),
+    UntilClose,
+}
+
+/// The state of a chunked body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chunk {
+    Size,
+    Data(usize),
+    DataEnd,
+    Trailer,
+}
+
+/// The start line of a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartLine {
+    Request {
+        method: Range<usize>,
+        uri: Range<usize>,
+        version: Range<usize>,
+    },
+    Response {
+        version: Range<usize>,
+        status: u16,
+        status_range: Range<usize>,
+        reason: Range<usize>,
+    },
+}
+
+/// A complete message.
+#[derive(Debug, Default)]
+pub struct Message {
+    /// The head and the body as sent.
+    pub data: Vec<u8>,
+    pub start: Option<StartLine>,
+    /// The ranges of the names and the values of the headers.
+    pub headers: Vec<(Range<usize>, Range<usize>)>,
+    pub head_len: usize,
+    /// The body without the chunked transfer coding.
+    pub body: Vec<u8>,
+    /// True if the body exceeds `MAX_MESSAGE_LEN`.
+    pub truncated: bool,
+}
+
+impl Message {
+    /// Returns the value of the first header of `name`.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| self.data[n.clone()].eq_ignore_ascii_case(name.as_bytes()))
+            .and_then(|(_, v)| str::from_utf8(&self.data[v.clone()]).ok())
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        match self.start {
+            Some(StartLine::Response { status, .. }) => Some(status),
+            _ => None,
+        }
+    }
+
+    fn push_raw(&mut self, data: &[u8]) {
+        if self.data.len() + data.len() <= MAX_MESSAGE_LEN {
+            self.data.extend_from_slice(data);
+        } else {
+            self.truncated = true;
+        }
+    }
+
+    fn push_body(&mut self, data: &[u8]) {
+        self.push_raw(data);
+        if self.body.len() + data.len() <= MAX_MESSAGE_LEN {
+            self.body.extend_from_slice(data);
+        } else {
+            self.truncated = true;
+        }
+    }
+
+    fn framing(&self, head_request: bool) -> Option<Framing> {
+        let chunked = self.header("transfer-encoding").is_some_and(|coding| {
+            coding
+                .rsplit(',')
+                .next()
+                .is_some_and(|last| last.trim().eq_ignore_ascii_case("chunked"))
+        });
+        let length = self
+            .header("content-length")
+            .and_then(|len| len.trim().parse::<usize>().ok());
+        match self.status() {
+            None if chunked => Some(Framing::Chunked(Chunk::Size)),
+            None => Some(Framing::Length(length.unwrap_or(0))),
+            // The connection is taken over by another protocol.
+            Some(101) => None,
+            Some(status) if status < 200 || status == 204 || status == 304 || head_request => {
+                Some(Framing::Length(0))
+            }
+            Some(_) if chunked => Some(Framing::Chunked(Chunk::Size)),
+            Some(_) => Some(length.map_or(Framing::UntilClose, Framing::Length)),
+        }
+    }
+}
+
+/// Finds the end of the head, and returns the length of the head.
+fn head_end(buf: &[u8]) -> Option<usize> {
+    let mut prev = None;
+    for (i, b) in buf.iter().enumerate() {
+        if *b == b'\n' {
+            if let Some(prev) = prev {
+                if buf[prev + 1..i].iter().all(|b| *b == b'\r') {
+                    return Some(i + 1);
+                }
+            }
+            prev = Some(i);
+        }
+    }
+    None
+}
+
+/// Returns the range of `line` within `buf` trimmed of whitespaces.
+fn trim(buf: &[u8], range: Range<usize>) -> Range<usize> {
+    let mut range = range;
+    while range.start < range.end && buf[range.start].is_ascii_whitespace() {
+        range.start += 1;
+    }
+    while range.end > range.start && buf[range.end - 1].is_ascii_whitespace() {
+        range.end -= 1;
+    }
+    range
+}
+
+/// Splits `range` at the first space.
+fn split(buf: &[u8], range: Range<usize>) -> Option<(Range<usize>, Range<usize>)> {
+    let pos = buf[range.clone()].iter().position(|b| *b == b' ')?;
+    Some((
+        range.start..range.start + pos,
+        range.start + pos + 1..range.end,
+    ))
+}
+
+fn parse_head(msg: &mut Message) -> Option<()> {
+    let head = msg.data.clone();
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, b) in head.iter().enumerate() {
+        if *b == b'\n' {
+            lines.push(trim(&head, start..i));
+            start = i + 1;
+        }
+    }
+    let (line, headers) = lines.split_first()?;
+    let (first, rest) = split(&head, line.clone())?;
+    msg.start = Some(if head[first.clone()].starts_with(b"HTTP/") {
+        let (status_range, reason) =
+            split(&head, rest.clone()).unwrap_or((rest, line.end..line.end));
+        let status = str::from_utf8(&head[status_range.clone()])
+            .ok()?
+            .parse()
+            .ok()?;
+        StartLine::Response {
+            version: first,
+            status,
+            status_range,
+            reason,
+        }
+    } else {
+        let (uri, version) = split(&head, rest)?;
+        if !head[version.clone()].starts_with(b"HTTP/")
+            || !head[first.clone()].iter().all(|b| b.is_ascii_uppercase())
+        {
+            return None;
+        }
+        StartLine::Request {
+            method: first,
+            uri,
+            version,
+        }
+    });
+    for line in headers.iter().filter(|line| !line.is_empty()) {
+        let colon = head[line.clone()].iter().position(|b| *b == b':')?;
+        let name = line.start..line.start + colon;
+        let value = trim(&head, line.start + colon + 1..line.end);
+        msg.headers.push((name, value));
+    }
+    Some(())
+}
+
+/// Parses the messages of a direction of a connection.
+#[derive(Debug, Default)]
+pub struct Parser {
+    buf: Vec<u8>,
+    message: Option<(Message, Framing)>,
+    /// The direction is no longer HTTP, or the messages are malformed.
+    broken: bool,
+}
+
+impl Parser {
+    /// Parses `data`, and returns the messages completed by it. `heads` is
+    /// the queue of the requests waiting for a response, true for HEAD
+    /// requests, whose responses have no body.
+    pub fn push(&mut self, data: &[u8], heads: &mut VecDeque<bool>) -> Vec<Message> {
+        let mut messages = Vec::new();
+        if self.broken {
+            return messages;
+        }
+        self.buf.extend_from_slice(data);
+        let mut pos = 0;
+        loop {
+            let (mut msg, framing) = match self.message.take() {
+                Some(message) => message,
+                None => {
+                    // Empty lines between messages are ignored.
+                    pos += self.buf[pos..]
+                        .iter()
+                        .take_while(|b| **b == b'\r' || **b == b'\n')
+                        .count();
+                    let len = match head_end(&self.buf[pos..]) {
+                        Some(len) => len,
+                        None => {
+                            if self.buf.len() - pos > MAX_HEAD_LEN {
+                                self.broken = true;
+                            }
+                            break;
+                        }
+                    };
+                    let mut msg = Message {
+                        data: self.buf[pos..pos + len].to_vec(),
+                        head_len: len,
+                        ..Message::default()
+                    };
+                    pos += len;
+                    if parse_head(&mut msg).is_none() {
+                        self.broken = true;
+                        break;
+                    }
+                    let head_request = match msg.status() {
+                        None => {
+                            let method = msg.start.as_ref().and_then(|line| match line {
+                                StartLine::Request { method, .. } => Some(method.clone()),
+                                _ => None,
+                            });
+                            heads.push_back(method.is_some_and(|m| &msg.data[m] == b"HEAD"));
+                            false
+                        }
+                        Some(status) if status >= 200 || status == 101 => {
+                            heads.pop_front().unwrap_or(false)
+                        }
+                        Some(_) => heads.front().cloned().unwrap_or(false),
+                    };
+                    match msg.framing(head_request) {
+                        Some(framing) => (msg, framing),
+                        None => {
+                            messages.push(msg);
+                            self.broken = true;
+                            break;
+                        }
+                    }
+                }
+            };
+            let (done, framing) = self.body(&mut msg, framing, &mut pos);
+            if done {
+                messages.push(msg);
+            } else {
+                self.message = Some((msg, framing));
+                break;
+            }
+            if self.broken {
+                break;
+            }
+        }
+        self.buf.drain(..pos.min(self.buf.len()));
+        messages
+    }
+
+    /// Completes a message whose body ends at the end of the connection.
+    pub fn close(&mut self) -> Option<Message> {
+        match self.message.take() {
+            Some((msg, Framing::UntilClose)) => Some(msg),
+            Some(message) => {
+                self.message = Some(message);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Reads the body from `pos`, and returns true if the body is complete.
+    fn body(&mut self, msg: &mut Message, framing: Framing, pos: &mut usize) -> (bool, Framing) {
+        let mut framing = framing;
+        loop {
+            let rest = &self.buf[*pos..];
+            match framing {
+                Framing::Length(len) => {
+                    let n = len.min(rest.len());
+                    msg.push_body(&rest[..n]);
+                    *pos += n;
+                    return (len == n, Framing::Length(len - n));
+                }
+                Framing::UntilClose => {
+                    msg.push_body(rest);
+                    *pos += rest.len();
+                    return (false, framing);
+                }
+                Framing::Chunked(Chunk::Data(len)) => {
+                    let n = len.min(rest.len());
+                    msg.push_body(&rest[..n]);
+                    *pos += n;
+                    if n < len {
+                        return (false, Framing::Chunked(Chunk::Data(len - n)));
+                    }
+                    framing = Framing::Chunked(Chunk::DataEnd);
+                }
+                Framing::Chunked(chunk) => {
+                    let end = match rest.iter().position(|b| *b == b'\n') {
+                        Some(end) => end + 1,
+                        None => {
+                            if rest.len() > MAX_HEAD_LEN {
+                                self.broken = true;
+                            }
+                            return (false, framing);
+                        }
+                    };
+                    let line = &rest[..end];
+                    msg.push_raw(line);
+                    *pos += end;
+                    let text = String::from_utf8_lossy(line);
+                    let text = text.trim();
+                    framing = match chunk {
+                        Chunk::Size => {
+                            let size = text.split(';').next().unwrap_or("").trim();
+                            match usize::from_str_radix(size, 16) {
+                                Ok(0) => Framing::Chunked(Chunk::Trailer),
+                                Ok(size) => Framing::Chunked(Chunk::Data(size)),
+                                Err(_) => {
+                                    self.broken = true;
+                                    return (true, framing);
+                                }
+                            }
+                        }
+                        Chunk::DataEnd => Framing::Chunked(Chunk::Size),
+                        Chunk::Trailer if text.is_empty() => return (true, framing),
+                        _ => framing,
+                    };
+                }
+            }
+        }
+    }
+}
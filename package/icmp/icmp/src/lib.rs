@@ -0,0 +1,340 @@
+extern crate genet_sdk;
+
+use genet_sdk::{
+    cast,
+    checksum::{self, Validator},
+    decoder::*,
+    prelude::*,
+};
+
+/// The offset of the datagram quoted by an error message.
+const QUOTED_OFFSET: usize = 8;
+
+struct IcmpWorker {
+    validator: Validator,
+}
+
+impl IcmpWorker {
+    fn checksum_status(&self, layer: &Layer, parent: &Parent, v6: bool) -> checksum::Status {
+        let data = layer.data();
+        let valid = if v6 {
+            checksum::verify_upper(parent.id(), &parent.data(), 58, &data)
+        } else {
+            Some(checksum::internet(&data) == 0)
+        };
+        match valid {
+            Some(valid) => self.validator.status(valid, parent.frame_metadata()),
+            None => checksum::Status::Unverified,
+        }
+    }
+}
+
+impl Worker for IcmpWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:icmp"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let v6 = parent.id() == token!("ipv6");
+        let attrs = if v6 { Attrs::v6() } else { Attrs::v4() };
+        let mut layer = Layer::new(attrs.class, data);
+        let typ: u8 = attrs.typ.try_get(&layer)?.try_into()?;
+        let code: u8 = attrs.code.try_get(&layer)?.try_into()?;
+        let message = if v6 {
+            get_v6_type(typ)
+        } else {
+            get_v4_type(typ)
+        };
+        if let Some(message) = message {
+            layer.add_attr(attr!(message.attr, range: 0..1));
+            match message.body {
+                Body::Echo => {
+                    layer.add_attr(attr!(attrs.id, range: 4..6));
+                    layer.add_attr(attr!(attrs.seq, range: 6..8));
+                }
+                Body::Error => {
+                    if v6 && typ == 2 {
+                        layer.add_attr(attr!(attrs.mtu, range: 4..8));
+                    } else if !v6 && typ == 3 && code == 4 {
+                        layer.add_attr(attr!(attrs.mtu, range: 6..8));
+                    } else if !v6 && typ == 5 {
+                        layer.add_attr(attr!(&GATEWAY_ATTR, range: 4..8));
+                    }
+
+                    // The quoted datagram is dissected from scratch as a
+                    // child of this layer.
+                    let data = layer.data();
+                    if data.len() > QUOTED_OFFSET {
+                        let id = if v6 {
+                            token!("@data:ipv6")
+                        } else {
+                            token!("@data:ipv4")
+                        };
+                        let quoted = data.try_get(QUOTED_OFFSET..)?;
+                        layer.add_payload(Payload::embedded(quoted, id));
+                    }
+                }
+                Body::Neighbor => {
+                    layer.add_attr(attr!(&TARGET_ATTR, range: 8..24));
+                }
+                Body::Other => {}
+            }
+        }
+
+        let status = self.checksum_status(&layer, parent, v6);
+        layer.add_attr(attr!(attrs.checksum_status, range: 2..4, value: status.to_value()));
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct IcmpDecoder {}
+
+impl Decoder for IcmpDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        Box::new(IcmpWorker {
+            validator: Validator::from_config(ctx),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            options: vec![Validator::option()],
+            ..Metadata::default()
+        }
+    }
+}
+
+/// The body following the checksum.
+enum Body {
+    Echo,
+    Error,
+    Neighbor,
+    Other,
+}
+
+struct Message {
+    attr: &'static AttrClass,
+    body: Body,
+}
+
+/// The classes of ICMP or ICMPv6.
+struct Attrs {
+    class: &'static LayerClass,
+    typ: &'static Attr,
+    code: &'static Attr,
+    checksum_status: &'static AttrClass,
+    id: &'static AttrClass,
+    seq: &'static AttrClass,
+    mtu: &'static AttrClass,
+}
+
+impl Attrs {
+    fn v4() -> Attrs {
+        Attrs {
+            class: &ICMP_CLASS,
+            typ: &ICMP_TYPE_ATTR_HEADER,
+            code: &ICMP_CODE_ATTR_HEADER,
+            checksum_status: &ICMP_CHECKSUM_STATUS_ATTR,
+            id: &ICMP_ID_ATTR,
+            seq: &ICMP_SEQ_ATTR,
+            mtu: &ICMP_MTU_ATTR,
+        }
+    }
+
+    fn v6() -> Attrs {
+        Attrs {
+            class: &ICMPV6_CLASS,
+            typ: &ICMPV6_TYPE_ATTR_HEADER,
+            code: &ICMPV6_CODE_ATTR_HEADER,
+            checksum_status: &ICMPV6_CHECKSUM_STATUS_ATTR,
+            id: &ICMPV6_ID_ATTR,
+            seq: &ICMPV6_SEQ_ATTR,
+            mtu: &ICMPV6_MTU_ATTR,
+        }
+    }
+}
+
+def_layer_class!(ICMP_CLASS, "icmp",
+    header: &ICMP_TYPE_ATTR_HEADER,
+    header: &ICMP_CODE_ATTR_HEADER,
+    header: attr!(&ICMP_CHECKSUM_ATTR, range: 2..4)
+);
+
+def_layer_class!(ICMPV6_CLASS, "icmpv6",
+    header: &ICMPV6_TYPE_ATTR_HEADER,
+    header: &ICMPV6_CODE_ATTR_HEADER,
+    header: attr!(&ICMPV6_CHECKSUM_ATTR, range: 2..4)
+);
+
+def_attr!(ICMP_TYPE_ATTR_HEADER, &ICMP_TYPE_ATTR, range: 0..1);
+
+def_attr!(ICMP_CODE_ATTR_HEADER, &ICMP_CODE_ATTR, range: 1..2);
+
+def_attr!(ICMPV6_TYPE_ATTR_HEADER, &ICMPV6_TYPE_ATTR, range: 0..1);
+
+def_attr!(ICMPV6_CODE_ATTR_HEADER, &ICMPV6_CODE_ATTR, range: 1..2);
+
+def_attr_class!(ICMP_TYPE_ATTR, "icmp.type",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+def_attr_class!(ICMP_CODE_ATTR, "icmp.code", cast: cast::UInt8());
+
+def_attr_class!(ICMP_CHECKSUM_ATTR, "icmp.checksum", cast: cast::UInt16BE());
+
+def_attr_class!(ICMP_CHECKSUM_STATUS_ATTR, "icmp.checksum.status");
+
+def_attr_class!(ICMP_ID_ATTR, "icmp.id", cast: cast::UInt16BE());
+
+def_attr_class!(ICMP_SEQ_ATTR, "icmp.seq", cast: cast::UInt16BE());
+
+def_attr_class!(ICMP_MTU_ATTR, "icmp.mtu", cast: cast::UInt16BE());
+
+def_attr_class!(GATEWAY_ATTR, "icmp.gateway",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(ICMPV6_TYPE_ATTR, "icmpv6.type",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+def_attr_class!(ICMPV6_CODE_ATTR, "icmpv6.code", cast: cast::UInt8());
+
+def_attr_class!(ICMPV6_CHECKSUM_ATTR, "icmpv6.checksum", cast: cast::UInt16BE());
+
+def_attr_class!(ICMPV6_CHECKSUM_STATUS_ATTR, "icmpv6.checksum.status");
+
+def_attr_class!(ICMPV6_ID_ATTR, "icmpv6.id", cast: cast::UInt16BE());
+
+def_attr_class!(ICMPV6_SEQ_ATTR, "icmpv6.seq", cast: cast::UInt16BE());
+
+def_attr_class!(ICMPV6_MTU_ATTR, "icmpv6.mtu", cast: cast::UInt32BE());
+
+def_attr_class!(TARGET_ATTR, "icmpv6.target",
+    typ: "@ipv6:addr",
+    cast: cast::ByteSlice()
+);
+
+fn get_v4_type(val: u8) -> Option<Message> {
+    let message = |attr, body| Some(Message { attr, body });
+    match val {
+        0 => message(
+            attr_class_lazy!("icmp.type.echoReply", typ: "@novalue", value: true),
+            Body::Echo,
+        ),
+        3 => message(
+            attr_class_lazy!("icmp.type.destinationUnreachable", typ: "@novalue", value: true),
+            Body::Error,
+        ),
+        4 => message(
+            attr_class_lazy!("icmp.type.sourceQuench", typ: "@novalue", value: true),
+            Body::Error,
+        ),
+        5 => message(
+            attr_class_lazy!("icmp.type.redirect", typ: "@novalue", value: true),
+            Body::Error,
+        ),
+        8 => message(
+            attr_class_lazy!("icmp.type.echoRequest", typ: "@novalue", value: true),
+            Body::Echo,
+        ),
+        9 => message(
+            attr_class_lazy!("icmp.type.routerAdvertisement", typ: "@novalue", value: true),
+            Body::Other,
+        ),
+        10 => message(
+            attr_class_lazy!("icmp.type.routerSolicitation", typ: "@novalue", value: true),
+            Body::Other,
+        ),
+        11 => message(
+            attr_class_lazy!("icmp.type.timeExceeded", typ: "@novalue", value: true),
+            Body::Error,
+        ),
+        12 => message(
+            attr_class_lazy!("icmp.type.parameterProblem", typ: "@novalue", value: true),
+            Body::Error,
+        ),
+        13 => message(
+            attr_class_lazy!("icmp.type.timestamp", typ: "@novalue", value: true),
+            Body::Echo,
+        ),
+        14 => message(
+            attr_class_lazy!("icmp.type.timestampReply", typ: "@novalue", value: true),
+            Body::Echo,
+        ),
+        _ => None,
+    }
+}
+
+fn get_v6_type(val: u8) -> Option<Message> {
+    let message = |attr, body| Some(Message { attr, body });
+    match val {
+        1 => message(
+            attr_class_lazy!("icmpv6.type.destinationUnreachable", typ: "@novalue", value: true),
+            Body::Error,
+        ),
+        2 => message(
+            attr_class_lazy!("icmpv6.type.packetTooBig", typ: "@novalue", value: true),
+            Body::Error,
+        ),
+        3 => message(
+            attr_class_lazy!("icmpv6.type.timeExceeded", typ: "@novalue", value: true),
+            Body::Error,
+        ),
+        4 => message(
+            attr_class_lazy!("icmpv6.type.parameterProblem", typ: "@novalue", value: true),
+            Body::Error,
+        ),
+        128 => message(
+            attr_class_lazy!("icmpv6.type.echoRequest", typ: "@novalue", value: true),
+            Body::Echo,
+        ),
+        129 => message(
+            attr_class_lazy!("icmpv6.type.echoReply", typ: "@novalue", value: true),
+            Body::Echo,
+        ),
+        133 => message(
+            attr_class_lazy!("icmpv6.type.routerSolicitation", typ: "@novalue", value: true),
+            Body::Other,
+        ),
+        134 => message(
+            attr_class_lazy!("icmpv6.type.routerAdvertisement", typ: "@novalue", value: true),
+            Body::Other,
+        ),
+        135 => message(
+            attr_class_lazy!("icmpv6.type.neighborSolicitation", typ: "@novalue", value: true),
+            Body::Neighbor,
+        ),
+        136 => message(
+            attr_class_lazy!("icmpv6.type.neighborAdvertisement", typ: "@novalue", value: true),
+            Body::Neighbor,
+        ),
+        137 => message(
+            attr_class_lazy!("icmpv6.type.redirect", typ: "@novalue", value: true),
+            Body::Other,
+        ),
+        _ => None,
+    }
+}
+
+genet_decoders!(IcmpDecoder {});
@@ -0,0 +1,133 @@
+//! The AES-128 modes used by RSNA: CCM (RFC 3610), CMAC (RFC 4493) and key
+//! unwrap (RFC 3394).
+
+use genet_crypto::aes::{xor, Aes, Block, BLOCK_LEN};
+
+/// Returns the key schedule of a 16-byte key.
+fn aes128(key: &[u8]) -> Option<Aes> {
+    if key.len() == 16 {
+        Aes::new(key)
+    } else {
+        None
+    }
+}
+
+/// Decrypts and authenticates CCM with 2-byte lengths and an 8-byte MIC,
+/// as used by CCMP. Returns None if the MIC does not match.
+pub fn ccm_open(key: &[u8], nonce: &[u8; 13], aad: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    const MIC_LEN: usize = 8;
+    let aes = aes128(key)?;
+    let len = data.len().checked_sub(MIC_LEN)?;
+    if len > 0xffff || aad.len() > 0xff00 {
+        return None;
+    }
+    let (data, mic) = data.split_at(len);
+
+    let counter = |i: usize| {
+        let mut block = [0; BLOCK_LEN];
+        block[0] = 0x01;
+        block[1..14].copy_from_slice(nonce);
+        block[14] = (i >> 8) as u8;
+        block[15] = i as u8;
+        aes.encrypt(&mut block);
+        block
+    };
+    let mut plain = data.to_vec();
+    for (i, chunk) in plain.chunks_mut(BLOCK_LEN).enumerate() {
+        let stream = counter(i + 1);
+        for (p, s) in chunk.iter_mut().zip(&stream) {
+            *p ^= s;
+        }
+    }
+
+    // Adata, M = 8 and L = 2.
+    let mut mac = [0; BLOCK_LEN];
+    mac[0] = 0x59;
+    mac[1..14].copy_from_slice(nonce);
+    mac[14] = (len >> 8) as u8;
+    mac[15] = len as u8;
+    aes.encrypt(&mut mac);
+    let mut header = vec![(aad.len() >> 8) as u8, aad.len() as u8];
+    header.extend_from_slice(aad);
+    for chunk in header.chunks(BLOCK_LEN).chain(plain.chunks(BLOCK_LEN)) {
+        xor(&mut mac, chunk);
+        aes.encrypt(&mut mac);
+    }
+    xor(&mut mac, &counter(0));
+    if mac[..MIC_LEN] == *mic {
+        Some(plain)
+    } else {
+        None
+    }
+}
+
+/// Computes AES-CMAC.
+pub fn cmac(key: &[u8], data: &[u8]) -> Option<Block> {
+    let aes = aes128(key)?;
+    let subkey = |block: &Block| {
+        let mut key = [0; BLOCK_LEN];
+        for i in 0..BLOCK_LEN {
+            key[i] = block[i] << 1 | block.get(i + 1).map_or(0, |b| b >> 7);
+        }
+        if block[0] & 0x80 != 0 {
+            key[BLOCK_LEN - 1] ^= 0x87;
+        }
+        key
+    };
+    let mut l = [0; BLOCK_LEN];
+    aes.encrypt(&mut l);
+    let k1 = subkey(&l);
+    let k2 = subkey(&k1);
+
+    let (body, last) = if data.is_empty() {
+        (data, data)
+    } else {
+        data.split_at((data.len() - 1) / BLOCK_LEN * BLOCK_LEN)
+    };
+    let mut mac = [0; BLOCK_LEN];
+    for chunk in body.chunks(BLOCK_LEN) {
+        xor(&mut mac, chunk);
+        aes.encrypt(&mut mac);
+    }
+    let mut block = [0; BLOCK_LEN];
+    block[..last.len()].copy_from_slice(last);
+    if last.len() == BLOCK_LEN {
+        xor(&mut block, &k1);
+    } else {
+        block[last.len()] = 0x80;
+        xor(&mut block, &k2);
+    }
+    xor(&mut mac, &block);
+    aes.encrypt(&mut mac);
+    Some(mac)
+}
+
+/// Unwraps a key wrapped with the default IV. Returns None if the integrity
+/// check fails.
+pub fn unwrap_key(kek: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    let aes = aes128(kek)?;
+    if data.len() < 16 || data.len() & 7 != 0 {
+        return None;
+    }
+    let n = data.len() / 8 - 1;
+    let mut a = [0; 8];
+    a.copy_from_slice(&data[..8]);
+    let mut r = data[8..].to_vec();
+    for j in (0..6).rev() {
+        for i in (1..=n).rev() {
+            let t = (n * j + i) as u64;
+            let mut block = [0; BLOCK_LEN];
+            block[..8].copy_from_slice(&a);
+            xor(&mut block, &t.to_be_bytes());
+            block[8..].copy_from_slice(&r[(i - 1) * 8..i * 8]);
+            aes.decrypt(&mut block);
+            a.copy_from_slice(&block[..8]);
+            r[(i - 1) * 8..i * 8].copy_from_slice(&block[8..]);
+        }
+    }
+    if a == [0xa6; 8] {
+        Some(r)
+    } else {
+        None
+    }
+}
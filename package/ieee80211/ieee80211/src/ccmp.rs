@@ -0,0 +1,28 @@
+//! CCMP (IEEE 802.11, 12.5.3).
+
+use aes;
+use header::Header;
+
+pub const HEADER_LEN: usize = 8;
+const MIC_LEN: usize = 8;
+const EXT_IV: u8 = 0x20;
+
+/// Returns the key ID of a protected frame body, or None if it does not
+/// start with a CCMP header.
+pub fn key_id(body: &[u8]) -> Option<u8> {
+    if body.len() < HEADER_LEN + MIC_LEN || body[3] & EXT_IV == 0 {
+        return None;
+    }
+    Some(body[3] >> 6)
+}
+
+/// Decrypts a protected frame body with a temporal key.
+pub fn open(header: &Header, body: &[u8], tk: &[u8]) -> Option<Vec<u8>> {
+    key_id(body)?;
+    let mut nonce = [0; 13];
+    nonce[0] = header.priority();
+    nonce[1..7].copy_from_slice(&header.addrs[1]);
+    let pn = [body[7], body[6], body[5], body[4], body[1], body[0]];
+    nonce[7..].copy_from_slice(&pn);
+    aes::ccm_open(tk, &nonce, &header.aad(), &body[HEADER_LEN..])
+}
@@ -0,0 +1,244 @@
+//! EAPOL-Key frames and the key derivation of the 4-way handshake.
+
+use aes;
+use ring::{digest, hmac};
+use std::cmp;
+
+const KEY_INFO_VERSION: u16 = 0x0007;
+const KEY_INFO_PAIRWISE: u16 = 0x0008;
+const KEY_INFO_ACK: u16 = 0x0080;
+const KEY_INFO_MIC: u16 = 0x0100;
+const KEY_INFO_ENCRYPTED: u16 = 0x1000;
+
+/// HMAC-SHA1 MIC and AES key wrap.
+const VERSION_HMAC_SHA1: u16 = 2;
+/// AES-CMAC MIC and AES key wrap.
+const VERSION_AES_CMAC: u16 = 3;
+/// Defined by the AKM, e.g. SAE, which uses the same keys as version 3.
+const VERSION_AKM: u16 = 0;
+
+const DESCRIPTOR_RSN: u8 = 2;
+const EAPOL_KEY: u8 = 3;
+
+const NONCE_OFFSET: usize = 17;
+const NONCE_LEN: usize = 32;
+const MIC_OFFSET: usize = 81;
+const MIC_LEN: usize = 16;
+const KEY_DATA_OFFSET: usize = 99;
+
+/// The KDE of a group temporal key.
+const GTK_KDE: [u8; 4] = [0x00, 0x0f, 0xac, 0x01];
+
+/// An EAPOL-Key frame of an RSN.
+pub struct KeyFrame<'a> {
+    data: &'a [u8],
+    info: u16,
+}
+
+impl<'a> KeyFrame<'a> {
+    /// Parses an EAPOL frame, or returns None if it is not an EAPOL-Key frame.
+    pub fn parse(data: &'a [u8]) -> Option<KeyFrame<'a>> {
+        if data.len() < KEY_DATA_OFFSET || data[1] != EAPOL_KEY || data[4] != DESCRIPTOR_RSN {
+            return None;
+        }
+        let len = 4 + ((data[2] as usize) << 8 | data[3] as usize);
+        let data = data.get(..len)?;
+        if len < KEY_DATA_OFFSET {
+            return None;
+        }
+        let key_data_len =
+            (data[KEY_DATA_OFFSET - 2] as usize) << 8 | data[KEY_DATA_OFFSET - 1] as usize;
+        if len < KEY_DATA_OFFSET + key_data_len {
+            return None;
+        }
+        Some(KeyFrame {
+            data,
+            info: (data[5] as u16) << 8 | data[6] as u16,
+        })
+    }
+
+    fn version(&self) -> u16 {
+        self.info & KEY_INFO_VERSION
+    }
+
+    fn nonce(&self) -> &[u8] {
+        &self.data[NONCE_OFFSET..NONCE_OFFSET + NONCE_LEN]
+    }
+
+    fn key_data(&self) -> &[u8] {
+        let len = (self.data[KEY_DATA_OFFSET - 2] as usize) << 8
+            | self.data[KEY_DATA_OFFSET - 1] as usize;
+        &self.data[KEY_DATA_OFFSET..KEY_DATA_OFFSET + len]
+    }
+
+    /// Returns the number of the message of the 4-way handshake, or None if
+    /// it is a message of the group key handshake.
+    pub fn message(&self) -> Option<u8> {
+        if self.info & KEY_INFO_PAIRWISE == 0 {
+            return None;
+        }
+        let ack = self.info & KEY_INFO_ACK != 0;
+        let mic = self.info & KEY_INFO_MIC != 0;
+        Some(match (ack, mic) {
+            (true, false) => 1,
+            (true, true) => 3,
+            // Message 2 carries the RSNE of the supplicant.
+            (false, _) if !self.key_data().is_empty() => 2,
+            (false, _) => 4,
+        })
+    }
+}
+
+/// A pairwise transient key for CCMP.
+pub struct Ptk {
+    kck: Vec<u8>,
+    kek: Vec<u8>,
+    pub tk: Vec<u8>,
+}
+
+const PTK_LEN: usize = 48;
+const LABEL: &[u8] = b"Pairwise key expansion";
+
+impl Ptk {
+    /// Derives a PTK (IEEE 802.11, 12.7.1.3).
+    fn derive(pmk: &[u8], version: u16, context: &[u8]) -> Option<Ptk> {
+        let mut ptk = Vec::with_capacity(PTK_LEN);
+        match version {
+            VERSION_HMAC_SHA1 => {
+                let key = hmac::SigningKey::new(&digest::SHA1, pmk);
+                for i in 0.. {
+                    if ptk.len() >= PTK_LEN {
+                        break;
+                    }
+                    let mut ctx = hmac::SigningContext::with_key(&key);
+                    ctx.update(LABEL);
+                    ctx.update(&[0]);
+                    ctx.update(context);
+                    ctx.update(&[i]);
+                    ptk.extend_from_slice(ctx.sign().as_ref());
+                }
+            }
+            VERSION_AES_CMAC | VERSION_AKM => {
+                let key = hmac::SigningKey::new(&digest::SHA256, pmk);
+                let bits = (PTK_LEN as u16 * 8).to_le_bytes();
+                for i in 1u16.. {
+                    if ptk.len() >= PTK_LEN {
+                        break;
+                    }
+                    let mut ctx = hmac::SigningContext::with_key(&key);
+                    ctx.update(&i.to_le_bytes());
+                    ctx.update(LABEL);
+                    ctx.update(context);
+                    ctx.update(&bits);
+                    ptk.extend_from_slice(ctx.sign().as_ref());
+                }
+            }
+            _ => return None,
+        }
+        Some(Ptk {
+            kck: ptk[..16].to_vec(),
+            kek: ptk[16..32].to_vec(),
+            tk: ptk[32..48].to_vec(),
+        })
+    }
+
+    /// Returns true if the MIC of `frame` is computed with this key.
+    fn verify(&self, frame: &KeyFrame) -> bool {
+        let mut data = frame.data.to_vec();
+        let mic = data[MIC_OFFSET..MIC_OFFSET + MIC_LEN].to_vec();
+        for b in &mut data[MIC_OFFSET..MIC_OFFSET + MIC_LEN] {
+            *b = 0;
+        }
+        match frame.version() {
+            VERSION_HMAC_SHA1 => {
+                let key = hmac::SigningKey::new(&digest::SHA1, &self.kck);
+                hmac::sign(&key, &data).as_ref()[..MIC_LEN] == mic[..]
+            }
+            VERSION_AES_CMAC | VERSION_AKM => {
+                aes::cmac(&self.kck, &data).is_some_and(|m| m[..] == mic[..])
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the key ID and the GTK sent in the encrypted key data.
+    pub fn gtk(&self, frame: &KeyFrame) -> Option<(u8, Vec<u8>)> {
+        if frame.info & KEY_INFO_ENCRYPTED == 0 || !self.verify(frame) {
+            return None;
+        }
+        let data = aes::unwrap_key(&self.kek, frame.key_data())?;
+        let mut data = &data[..];
+        while data.len() >= 2 {
+            let (typ, len) = (data[0], data[1] as usize);
+            // Padding starts with 0xdd followed by zeros.
+            if typ == 0xdd && len == 0 {
+                break;
+            }
+            let body = data.get(2..2 + len)?;
+            if typ == 0xdd && body.len() > 6 && body[..4] == GTK_KDE {
+                return Some((body[4] & 0x03, body[6..].to_vec()));
+            }
+            data = &data[2 + len..];
+        }
+        None
+    }
+}
+
+/// The state of the 4-way handshakes between an AP and a station.
+#[derive(Default)]
+pub struct Handshake {
+    anonce: Option<Vec<u8>>,
+    snonce: Option<Vec<u8>>,
+    /// A frame with a MIC, to find the PMK for the nonces.
+    unverified: Option<Vec<u8>>,
+    /// The keys of the last handshakes, newest first, so that frames sent
+    /// during a rekey are decrypted with the previous key.
+    pub keys: Vec<Ptk>,
+}
+
+impl Handshake {
+    /// Processes a key frame and returns true if a new PTK has been derived.
+    pub fn push(&mut self, frame: &KeyFrame, pmks: &[Vec<u8>], aa: &[u8], spa: &[u8]) -> bool {
+        let nonce = Some(frame.nonce().to_vec());
+        match frame.message() {
+            Some(1) if self.anonce != nonce => {
+                self.anonce = nonce;
+                self.snonce = None;
+                self.unverified = None;
+            }
+            Some(2) => {
+                self.snonce = nonce;
+                self.unverified = Some(frame.data.to_vec());
+            }
+            Some(3) => {
+                if self.anonce != nonce {
+                    self.anonce = nonce;
+                    self.unverified = Some(frame.data.to_vec());
+                }
+            }
+            _ => return false,
+        }
+
+        let ptk = match (&self.anonce, &self.snonce, &self.unverified) {
+            (Some(anonce), Some(snonce), Some(unverified)) => {
+                let unverified = KeyFrame::parse(unverified).unwrap();
+                let mut context = cmp::min(aa, spa).to_vec();
+                context.extend_from_slice(cmp::max(aa, spa));
+                context.extend_from_slice(cmp::min(anonce, snonce));
+                context.extend_from_slice(cmp::max(anonce, snonce));
+                pmks.iter()
+                    .filter_map(|pmk| Ptk::derive(pmk, unverified.version(), &context))
+                    .find(|ptk| ptk.verify(&unverified))
+            }
+            _ => return false,
+        };
+        self.unverified = None;
+        if let Some(ptk) = ptk {
+            self.keys.insert(0, ptk);
+            self.keys.truncate(2);
+            true
+        } else {
+            false
+        }
+    }
+}
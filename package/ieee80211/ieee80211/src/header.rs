@@ -0,0 +1,125 @@
+//! MAC headers of data frames.
+
+pub type Addr = [u8; 6];
+
+pub const TYPE_DATA: u8 = 2;
+
+const FLAG_TO_DS: u8 = 0x01;
+const FLAG_FROM_DS: u8 = 0x02;
+const FLAG_PROTECTED: u8 = 0x40;
+const FLAG_ORDER: u8 = 0x80;
+
+const SUBTYPE_NO_DATA: u8 = 0x04;
+const SUBTYPE_QOS: u8 = 0x08;
+
+fn addr(data: &[u8], offset: usize) -> Addr {
+    let mut addr = [0; 6];
+    addr.copy_from_slice(&data[offset..offset + 6]);
+    addr
+}
+
+/// The MAC header of a data frame.
+pub struct Header {
+    pub fc: [u8; 2],
+    pub addrs: [Addr; 3],
+    pub addr4: Option<Addr>,
+    pub seq: [u8; 2],
+    pub qos: Option<[u8; 2]>,
+    /// The length of the header, including the HT Control field.
+    pub len: usize,
+}
+
+impl Header {
+    /// Parses the header of a data frame with a body, or returns None for
+    /// other frames.
+    pub fn parse(data: &[u8]) -> Option<Header> {
+        if data.len() < 24 || (data[0] >> 2) & 0x03 != TYPE_DATA {
+            return None;
+        }
+        let subtype = data[0] >> 4;
+        if subtype & SUBTYPE_NO_DATA != 0 {
+            return None;
+        }
+        let mut header = Header {
+            fc: [data[0], data[1]],
+            addrs: [addr(data, 4), addr(data, 10), addr(data, 16)],
+            addr4: None,
+            seq: [data[22], data[23]],
+            qos: None,
+            len: 24,
+        };
+        if header.fc[1] & (FLAG_TO_DS | FLAG_FROM_DS) == FLAG_TO_DS | FLAG_FROM_DS {
+            header.addr4 = Some(addr(data.get(..30)?, 24));
+            header.len = 30;
+        }
+        if subtype & SUBTYPE_QOS != 0 {
+            let qos = data.get(header.len..header.len + 2)?;
+            header.qos = Some([qos[0], qos[1]]);
+            header.len += 2;
+            if header.fc[1] & FLAG_ORDER != 0 {
+                header.len += 4;
+            }
+        }
+        if data.len() < header.len {
+            return None;
+        }
+        Some(header)
+    }
+
+    pub fn is_protected(&self) -> bool {
+        self.fc[1] & FLAG_PROTECTED != 0
+    }
+
+    /// Returns the BSSID, or None for frames between APs.
+    pub fn bssid(&self) -> Option<Addr> {
+        match self.fc[1] & (FLAG_TO_DS | FLAG_FROM_DS) {
+            0 => Some(self.addrs[2]),
+            FLAG_TO_DS => Some(self.addrs[0]),
+            FLAG_FROM_DS => Some(self.addrs[1]),
+            _ => None,
+        }
+    }
+
+    /// Returns the station associated with the AP, or None if the frame is
+    /// group addressed or not between an AP and a station.
+    pub fn station(&self) -> Option<Addr> {
+        match self.fc[1] & (FLAG_TO_DS | FLAG_FROM_DS) {
+            FLAG_TO_DS => Some(self.addrs[1]),
+            FLAG_FROM_DS if !self.is_group() => Some(self.addrs[0]),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the receiver address is a group address.
+    pub fn is_group(&self) -> bool {
+        self.addrs[0][0] & 0x01 != 0
+    }
+
+    /// Returns the additional authentication data of CCMP.
+    pub fn aad(&self) -> Vec<u8> {
+        let mut fc = self.fc;
+        if self.qos.is_some() {
+            fc[0] &= 0x8f;
+            fc[1] &= !FLAG_ORDER;
+        }
+        // Retry, Power Management and More Data are masked.
+        fc[1] = fc[1] & 0xc7 | FLAG_PROTECTED;
+        let mut aad = fc.to_vec();
+        for addr in &self.addrs {
+            aad.extend_from_slice(addr);
+        }
+        aad.extend_from_slice(&[self.seq[0] & 0x0f, 0]);
+        if let Some(addr4) = &self.addr4 {
+            aad.extend_from_slice(addr4);
+        }
+        if let Some(qos) = &self.qos {
+            aad.extend_from_slice(&[qos[0] & 0x0f, 0]);
+        }
+        aad
+    }
+
+    /// Returns the TID of a QoS data frame, or 0.
+    pub fn priority(&self) -> u8 {
+        self.qos.map_or(0, |qos| qos[0] & 0x0f)
+    }
+}
@@ -0,0 +1,59 @@
+//! Pairwise master keys configured by `@genet/ieee80211.keys`.
+//!
+//! The value is a JSON array of either a WPA/WPA2-Personal passphrase with
+//! the SSID, or a PMK in hex, which is needed for WPA3-SAE because the PMK
+//! of SAE cannot be derived from the password of a captured exchange:
+//!
+//! ```json
+//! [{ "passphrase": "password", "ssid": "IEEE" }, { "pmk": "f42c6fc5..." }]
+//! ```
+
+use ring::{digest, pbkdf2};
+use serde_json::{self, Value};
+
+const PMK_LEN: usize = 32;
+
+fn hex(value: &str) -> Option<Vec<u8>> {
+    value
+        .as_bytes()
+        .chunks(2)
+        .map(|c| match c {
+            [h, l] => {
+                Some((char::from(*h).to_digit(16)? << 4 | char::from(*l).to_digit(16)?) as u8)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Derives the PMK from a passphrase (IEEE 802.11, Annex J.4).
+pub fn psk(passphrase: &str, ssid: &str) -> Vec<u8> {
+    let mut pmk = vec![0; PMK_LEN];
+    pbkdf2::derive(
+        &digest::SHA1,
+        4096,
+        ssid.as_bytes(),
+        passphrase.as_bytes(),
+        &mut pmk,
+    );
+    pmk
+}
+
+/// Parses the value of the config and returns the PMKs. Malformed entries
+/// are ignored.
+pub fn parse(value: &str) -> Vec<Vec<u8>> {
+    let entries: Vec<Value> = serde_json::from_str(value).unwrap_or_default();
+    entries
+        .iter()
+        .filter_map(|entry| {
+            if let Some(pmk) = entry["pmk"].as_str() {
+                hex(pmk).filter(|pmk| pmk.len() == PMK_LEN)
+            } else {
+                match (entry["passphrase"].as_str(), entry["ssid"].as_str()) {
+                    (Some(passphrase), Some(ssid)) => Some(psk(passphrase, ssid)),
+                    _ => None,
+                }
+            }
+        })
+        .collect()
+}
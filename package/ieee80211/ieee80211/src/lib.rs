@@ -0,0 +1,490 @@
+extern crate genet_crypto;
+extern crate genet_sdk;
+extern crate ring;
+#[macro_use]
+extern crate serde_json;
+
+mod aes;
+mod ccmp;
+mod eapol;
+mod header;
+mod keys;
+mod mgmt;
+mod radiotap;
+
+use eapol::{Handshake, KeyFrame};
+use genet_sdk::{cast, decoder::*, prelude::*};
+use header::{Addr, Header};
+use radiotap::RadiotapDecoder;
+use std::collections::{BTreeSet, HashMap};
+
+/// Config key for the PMKs and passphrases used to decrypt frames.
+const KEYS_KEY: &str = "@genet/ieee80211.keys";
+
+/// Session metadata key for the associations whose PTK has been derived.
+const ASSOCIATIONS_KEY: &str = "ieee80211.associations";
+
+const TYPE_MANAGEMENT: u8 = 0;
+const TYPE_CONTROL: u8 = 1;
+
+const SUBTYPE_BLOCK_ACK_REQ: u8 = 8;
+const SUBTYPE_BLOCK_ACK: u8 = 9;
+
+const FLAG_PROTECTED: u8 = 0x40;
+const FLAG_ORDER: u8 = 0x80;
+
+const ETHERTYPE_EAPOL: u64 = 0x888e;
+
+fn mac(addr: &Addr) -> String {
+    addr.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Returns the payload of an LLC/SNAP header and its EtherType.
+fn snap(body: &ByteSlice) -> Option<(u64, ByteSlice)> {
+    if body.len() < 8 || body[..3] != [0xaa, 0xaa, 0x03] || body[3..5] != [0, 0] {
+        return None;
+    }
+    let ethertype = (body[6] as u64) << 8 | body[7] as u64;
+    Some((ethertype, body.try_get(8..).ok()?))
+}
+
+struct Ieee80211Worker {
+    pmks: Vec<Vec<u8>>,
+    /// The handshakes keyed by the BSSID and the station.
+    handshakes: HashMap<(Addr, Addr), Handshake>,
+    /// The GTKs keyed by the BSSID and the key ID.
+    group_keys: HashMap<(Addr, u8), Vec<u8>>,
+    associations: BTreeSet<(Addr, Addr)>,
+}
+
+impl Ieee80211Worker {
+    fn decrypt(&self, header: &Header, body: &[u8]) -> Option<Vec<u8>> {
+        let bssid = header.bssid()?;
+        if header.is_group() {
+            let gtk = self.group_keys.get(&(bssid, ccmp::key_id(body)?))?;
+            ccmp::open(header, body, gtk)
+        } else {
+            self.handshakes
+                .get(&(bssid, header.station()?))?
+                .keys
+                .iter()
+                .find_map(|ptk| ccmp::open(header, body, &ptk.tk))
+        }
+    }
+
+    fn add_payload(&mut self, ctx: &Context, layer: &mut Layer, header: &Header, body: ByteSlice) {
+        let (ethertype, payload) = match snap(&body) {
+            Some(snap) => snap,
+            None => return,
+        };
+        layer.add_attr(attr!(&LLC_TYPE_ATTR, value: ethertype));
+        if ethertype == ETHERTYPE_EAPOL {
+            self.eapol(ctx, layer, header, &payload);
+        }
+        if let Some((typ, attr)) = get_ethertype(ethertype) {
+            layer.add_attr(attr!(attr));
+            layer.add_payload(Payload::new(payload, typ));
+        }
+    }
+
+    fn eapol(&mut self, ctx: &Context, layer: &mut Layer, header: &Header, data: &[u8]) {
+        let frame = match KeyFrame::parse(data) {
+            Some(frame) => frame,
+            None => return,
+        };
+        if let Some(message) = frame.message() {
+            layer.add_attr(attr!(&EAPOL_MESSAGE_ATTR, value: u64::from(message)));
+        }
+        let (bssid, sta) = match (header.bssid(), header.station()) {
+            (Some(bssid), Some(sta)) if !self.pmks.is_empty() => (bssid, sta),
+            _ => return,
+        };
+        let handshake = self.handshakes.entry((bssid, sta)).or_default();
+        if handshake.push(&frame, &self.pmks, &bssid, &sta)
+            && self.associations.insert((bssid, sta))
+        {
+            let associations: Vec<_> = self
+                .associations
+                .iter()
+                .map(|(bssid, sta)| json!({ "bssid": mac(bssid), "station": mac(sta) }))
+                .collect();
+            ctx.set_metadata(ASSOCIATIONS_KEY, &json!(associations).to_string());
+        }
+        if let Some((id, gtk)) = handshake.keys.iter().find_map(|ptk| ptk.gtk(&frame)) {
+            self.group_keys.insert((bssid, id), gtk);
+        }
+    }
+}
+
+impl Worker for Ieee80211Worker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data = if parent.id() == token!("[link-105]") {
+            let data = parent.data();
+            match parent.frame_metadata().fcs_len() {
+                Some(fcs_len) => data.try_get(..data.len().saturating_sub(fcs_len.into()))?,
+                None => data,
+            }
+        } else if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:ieee80211"))
+        {
+            payload.data()
+        } else {
+            return Ok(Status::Skip);
+        };
+        if data.len() < 10 {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&IEEE80211_CLASS, data);
+        let typ = (data[0] >> 2) & 0x03;
+        let subtype = data[0] >> 4;
+        if let Some(attr) = get_type(typ) {
+            layer.add_attr(attr!(attr, range: 0..1));
+        }
+        if let Some(attr) = get_subtype(typ, subtype) {
+            layer.add_attr(attr!(attr, range: 0..1));
+        }
+        layer.add_attr(attr!(&ADDR1_ATTR, range: 4..10));
+        if data.len() >= 16 {
+            layer.add_attr(attr!(&ADDR2_ATTR, range: 10..16));
+        }
+        if typ != TYPE_CONTROL && data.len() >= 24 {
+            layer.add_attr(attr!(&ADDR3_ATTR, range: 16..22));
+            layer.add_attr(attr!(&SEQ_ATTR, range: 22..24));
+            layer.add_attr(attr!(&FRAGMENT_ATTR, range: 22..24));
+            let addrs = match data[1] & 0x03 {
+                0 => Some((10..16, 4..10, Some(16..22))),
+                1 => Some((10..16, 16..22, Some(4..10))),
+                2 => Some((16..22, 4..10, Some(10..16))),
+                _ if data.len() >= 30 => {
+                    layer.add_attr(attr!(&ADDR4_ATTR, range: 24..30));
+                    Some((24..30, 16..22, None))
+                }
+                _ => None,
+            };
+            if let Some((src, dst, bssid)) = addrs {
+                layer.add_attr(attr!(&SRC_ATTR, range: src));
+                layer.add_attr(attr!(&DST_ATTR, range: dst));
+                if let Some(bssid) = bssid {
+                    layer.add_attr(attr!(&BSSID_ATTR, range: bssid));
+                }
+            }
+        }
+
+        if typ == TYPE_MANAGEMENT && data.len() >= 24 && data[1] & FLAG_PROTECTED == 0 {
+            // The HT Control field is present in management frames with the
+            // Order flag.
+            let len = if data[1] & FLAG_ORDER != 0 { 28 } else { 24 };
+            mgmt::dissect(&mut layer, &data, subtype, len);
+        } else if typ == TYPE_CONTROL
+            && (subtype == SUBTYPE_BLOCK_ACK_REQ || subtype == SUBTYPE_BLOCK_ACK)
+            && data.len() >= 20
+        {
+            layer.add_attr(attr!(&BA_TID_ATTR, range: 16..18));
+            layer.add_attr(attr!(&BA_SSN_ATTR, range: 18..20));
+        }
+
+        if let Some(header) = Header::parse(&data) {
+            if header.qos.is_some() {
+                let offset = if header.addr4.is_some() { 30 } else { 24 };
+                layer.add_attr(attr!(&QOS_TID_ATTR, range: offset..offset + 1));
+                layer.add_attr(attr!(&QOS_ACK_POLICY_ATTR, range: offset..offset + 1));
+                layer.add_attr(attr!(&QOS_AMSDU_ATTR, range: offset..offset + 1));
+            }
+            if !header.is_protected() {
+                let body = data.try_get(header.len..)?;
+                self.add_payload(ctx, &mut layer, &header, body);
+            } else if let Some(plain) = self.decrypt(&header, &data[header.len..]) {
+                layer.add_attr(attr!(&DECRYPTED_ATTR));
+                self.add_payload(ctx, &mut layer, &header, ByteSlice::from(plain));
+            }
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct Ieee80211Decoder {}
+
+impl Decoder for Ieee80211Decoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        Box::new(Ieee80211Worker {
+            pmks: keys::parse(ctx.get_config(KEYS_KEY)),
+            handshakes: HashMap::new(),
+            group_keys: HashMap::new(),
+            associations: BTreeSet::new(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            // The keys are derived from the handshakes of earlier frames.
+            exec_type: ExecType::SerialSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(IEEE80211_CLASS, "ieee80211",
+    alias: "_.src" "ieee80211.src",
+    alias: "_.dst" "ieee80211.dst",
+    header: attr!(&TYPE_ATTR, range: 0..1),
+    header: attr!(&SUBTYPE_ATTR, range: 0..1),
+    header: attr!(&FLAGS_ATTR, range: 1..2),
+    header: attr!(&FLAGS_TO_DS_ATTR, range: 1..2),
+    header: attr!(&FLAGS_FROM_DS_ATTR, range: 1..2),
+    header: attr!(&FLAGS_MORE_FRAGMENTS_ATTR, range: 1..2),
+    header: attr!(&FLAGS_RETRY_ATTR, range: 1..2),
+    header: attr!(&FLAGS_POWER_MANAGEMENT_ATTR, range: 1..2),
+    header: attr!(&FLAGS_MORE_DATA_ATTR, range: 1..2),
+    header: attr!(&FLAGS_PROTECTED_ATTR, range: 1..2),
+    header: attr!(&FLAGS_ORDER_ATTR, range: 1..2),
+    header: attr!(&DURATION_ATTR, range: 2..4)
+);
+
+def_attr_class!(TYPE_ATTR, "ieee80211.type",
+    cast: cast::UInt8().map(|v| (v >> 2) & 0b00000011),
+    typ: "@enum"
+);
+
+def_attr_class!(SUBTYPE_ATTR, "ieee80211.subtype",
+    cast: cast::UInt8().map(|v| v >> 4),
+    typ: "@enum"
+);
+
+def_attr_class!(FLAGS_ATTR, "ieee80211.flags",
+    cast: cast::UInt8(),
+    typ: "@flags"
+);
+
+def_attr_class!(FLAGS_TO_DS_ATTR, "ieee80211.flags.toDS",
+    cast: cast::UInt8().map(|v| v & 0b00000001 != 0)
+);
+
+def_attr_class!(FLAGS_FROM_DS_ATTR, "ieee80211.flags.fromDS",
+    cast: cast::UInt8().map(|v| v & 0b00000010 != 0)
+);
+
+def_attr_class!(FLAGS_MORE_FRAGMENTS_ATTR, "ieee80211.flags.moreFragments",
+    cast: cast::UInt8().map(|v| v & 0b00000100 != 0)
+);
+
+def_attr_class!(FLAGS_RETRY_ATTR, "ieee80211.flags.retry",
+    cast: cast::UInt8().map(|v| v & 0b00001000 != 0)
+);
+
+def_attr_class!(FLAGS_POWER_MANAGEMENT_ATTR, "ieee80211.flags.powerManagement",
+    cast: cast::UInt8().map(|v| v & 0b00010000 != 0)
+);
+
+def_attr_class!(FLAGS_MORE_DATA_ATTR, "ieee80211.flags.moreData",
+    cast: cast::UInt8().map(|v| v & 0b00100000 != 0)
+);
+
+def_attr_class!(FLAGS_PROTECTED_ATTR, "ieee80211.flags.protected",
+    cast: cast::UInt8().map(|v| v & 0b01000000 != 0)
+);
+
+def_attr_class!(FLAGS_ORDER_ATTR, "ieee80211.flags.order",
+    cast: cast::UInt8().map(|v| v & 0b10000000 != 0)
+);
+
+def_attr_class!(DURATION_ATTR, "ieee80211.duration", cast: cast::UInt16LE());
+
+def_attr_class!(ADDR1_ATTR, "ieee80211.addr1",
+    typ: "@eth:mac",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(ADDR2_ATTR, "ieee80211.addr2",
+    typ: "@eth:mac",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(ADDR3_ATTR, "ieee80211.addr3",
+    typ: "@eth:mac",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(ADDR4_ATTR, "ieee80211.addr4",
+    typ: "@eth:mac",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(SRC_ATTR, "ieee80211.src",
+    typ: "@eth:mac",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(DST_ATTR, "ieee80211.dst",
+    typ: "@eth:mac",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(BSSID_ATTR, "ieee80211.bssid",
+    typ: "@eth:mac",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(SEQ_ATTR, "ieee80211.seq",
+    cast: cast::UInt16LE().map(|v| v >> 4)
+);
+
+def_attr_class!(FRAGMENT_ATTR, "ieee80211.fragment",
+    cast: cast::UInt16LE().map(|v| v & 0x000f)
+);
+
+def_attr_class!(QOS_TID_ATTR, "ieee80211.qos.tid",
+    cast: cast::UInt8().map(|v| v & 0x0f)
+);
+
+def_attr_class!(QOS_ACK_POLICY_ATTR, "ieee80211.qos.ackPolicy",
+    cast: cast::UInt8().map(|v| (v >> 5) & 0b11)
+);
+
+def_attr_class!(QOS_AMSDU_ATTR, "ieee80211.qos.amsdu",
+    cast: cast::UInt8().map(|v| v & 0x80 != 0)
+);
+
+def_attr_class!(BA_TID_ATTR, "ieee80211.ba.tid",
+    cast: cast::UInt16LE().map(|v| v >> 12)
+);
+
+def_attr_class!(BA_SSN_ATTR, "ieee80211.ba.ssn",
+    cast: cast::UInt16LE().map(|v| v >> 4)
+);
+
+def_attr_class!(LLC_TYPE_ATTR, "ieee80211.llc.type", typ: "@enum");
+
+def_attr_class!(EAPOL_MESSAGE_ATTR, "ieee80211.eapol.message");
+
+def_attr_class!(DECRYPTED_ATTR, "ieee80211.decrypted",
+    typ: "@novalue",
+    value: true
+);
+
+fn get_type(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("ieee80211.type.management", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!("ieee80211.type.control", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("ieee80211.type.data", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+fn get_subtype(typ: u8, subtype: u8) -> Option<&'static AttrClass> {
+    match (typ, subtype) {
+        (0, 0) => Some(attr_class_lazy!(
+            "ieee80211.subtype.assocReq",
+            typ: "@novalue",
+            value: true
+        )),
+        (0, 1) => Some(attr_class_lazy!(
+            "ieee80211.subtype.assocResp",
+            typ: "@novalue",
+            value: true
+        )),
+        (0, 2) => Some(attr_class_lazy!(
+            "ieee80211.subtype.reassocReq",
+            typ: "@novalue",
+            value: true
+        )),
+        (0, 3) => Some(attr_class_lazy!(
+            "ieee80211.subtype.reassocResp",
+            typ: "@novalue",
+            value: true
+        )),
+        (0, 4) => {
+            Some(attr_class_lazy!("ieee80211.subtype.probeReq", typ: "@novalue", value: true))
+        }
+        (0, 5) => Some(attr_class_lazy!(
+            "ieee80211.subtype.probeResp",
+            typ: "@novalue",
+            value: true
+        )),
+        (0, 8) => Some(attr_class_lazy!("ieee80211.subtype.beacon", typ: "@novalue", value: true)),
+        (0, 9) => Some(attr_class_lazy!("ieee80211.subtype.atim", typ: "@novalue", value: true)),
+        (0, 10) => {
+            Some(attr_class_lazy!("ieee80211.subtype.disassoc", typ: "@novalue", value: true))
+        }
+        (0, 11) => Some(attr_class_lazy!("ieee80211.subtype.auth", typ: "@novalue", value: true)),
+        (0, 12) => Some(attr_class_lazy!("ieee80211.subtype.deauth", typ: "@novalue", value: true)),
+        (0, 13) => Some(attr_class_lazy!("ieee80211.subtype.action", typ: "@novalue", value: true)),
+        (0, 14) => Some(attr_class_lazy!(
+            "ieee80211.subtype.actionNoAck",
+            typ: "@novalue",
+            value: true
+        )),
+        (1, 4) => Some(attr_class_lazy!(
+            "ieee80211.subtype.beamformingReportPoll",
+            typ: "@novalue",
+            value: true
+        )),
+        (1, 5) => Some(attr_class_lazy!(
+            "ieee80211.subtype.vhtNdpAnnouncement",
+            typ: "@novalue",
+            value: true
+        )),
+        (1, 7) => Some(attr_class_lazy!(
+            "ieee80211.subtype.controlWrapper",
+            typ: "@novalue",
+            value: true
+        )),
+        (1, 8) => Some(attr_class_lazy!(
+            "ieee80211.subtype.blockAckReq",
+            typ: "@novalue",
+            value: true
+        )),
+        (1, 9) => {
+            Some(attr_class_lazy!("ieee80211.subtype.blockAck", typ: "@novalue", value: true))
+        }
+        (1, 10) => Some(attr_class_lazy!("ieee80211.subtype.psPoll", typ: "@novalue", value: true)),
+        (1, 11) => Some(attr_class_lazy!("ieee80211.subtype.rts", typ: "@novalue", value: true)),
+        (1, 12) => Some(attr_class_lazy!("ieee80211.subtype.cts", typ: "@novalue", value: true)),
+        (1, 13) => Some(attr_class_lazy!("ieee80211.subtype.ack", typ: "@novalue", value: true)),
+        (1, 14) => Some(attr_class_lazy!("ieee80211.subtype.cfEnd", typ: "@novalue", value: true)),
+        (2, 0) => Some(attr_class_lazy!("ieee80211.subtype.data", typ: "@novalue", value: true)),
+        (2, 4) => Some(attr_class_lazy!("ieee80211.subtype.null", typ: "@novalue", value: true)),
+        (2, 8) => Some(attr_class_lazy!("ieee80211.subtype.qosData", typ: "@novalue", value: true)),
+        (2, 12) => {
+            Some(attr_class_lazy!("ieee80211.subtype.qosNull", typ: "@novalue", value: true))
+        }
+        _ => None,
+    }
+}
+
+fn get_ethertype(val: u64) -> Option<(Token, &'static AttrClass)> {
+    match val {
+        0x0800 => Some((
+            token!("@data:ipv4"),
+            attr_class_lazy!("ieee80211.llc.type.ipv4", typ: "@novalue", value: true),
+        )),
+        0x0806 => Some((
+            token!("@data:arp"),
+            attr_class_lazy!("ieee80211.llc.type.arp", typ: "@novalue", value: true),
+        )),
+        0x86DD => Some((
+            token!("@data:ipv6"),
+            attr_class_lazy!("ieee80211.llc.type.ipv6", typ: "@novalue", value: true),
+        )),
+        0x888E => Some((
+            token!("@data:eap"),
+            attr_class_lazy!("ieee80211.llc.type.eap", typ: "@novalue", value: true),
+        )),
+        _ => None,
+    }
+}
+
+genet_decoders!(Ieee80211Decoder {}, RadiotapDecoder {});
@@ -0,0 +1,589 @@
+//! Bodies of management frames: the fixed fields followed by the elements.
+
+use genet_sdk::{cast, prelude::*};
+use std::ptr;
+
+const SUBTYPE_ASSOC_REQ: u8 = 0;
+const SUBTYPE_ASSOC_RESP: u8 = 1;
+const SUBTYPE_REASSOC_REQ: u8 = 2;
+const SUBTYPE_REASSOC_RESP: u8 = 3;
+const SUBTYPE_PROBE_REQ: u8 = 4;
+const SUBTYPE_PROBE_RESP: u8 = 5;
+const SUBTYPE_BEACON: u8 = 8;
+const SUBTYPE_DISASSOC: u8 = 10;
+const SUBTYPE_AUTH: u8 = 11;
+const SUBTYPE_DEAUTH: u8 = 12;
+const SUBTYPE_ACTION: u8 = 13;
+const SUBTYPE_ACTION_NO_ACK: u8 = 14;
+
+/// The authentication algorithm whose frames carry SAE fields instead of
+/// elements.
+const AUTH_SAE: u16 = 3;
+
+const ELEMENT_SSID: u8 = 0;
+const ELEMENT_SUPPORTED_RATES: u8 = 1;
+const ELEMENT_DS_PARAMETER_SET: u8 = 3;
+const ELEMENT_TIM: u8 = 5;
+const ELEMENT_COUNTRY: u8 = 7;
+const ELEMENT_RSN: u8 = 48;
+const ELEMENT_EXTENDED_SUPPORTED_RATES: u8 = 50;
+const ELEMENT_HT_OPERATION: u8 = 61;
+const ELEMENT_VENDOR_SPECIFIC: u8 = 221;
+const ELEMENT_EXTENSION: u8 = 255;
+
+/// The length of the header of an element.
+const ELEMENT_HEADER_LEN: usize = 2;
+
+/// The length of a cipher or AKM suite selector.
+const SUITE_LEN: usize = 4;
+
+/// Adds the fields of the body of a management frame beginning at `offset`.
+pub fn dissect(layer: &mut Layer, data: &ByteSlice, subtype: u8, offset: usize) {
+    let fields: Vec<(&'static AttrClass, usize)> = match subtype {
+        SUBTYPE_BEACON | SUBTYPE_PROBE_RESP => vec![
+            (&TIMESTAMP_ATTR, 8),
+            (&BEACON_INTERVAL_ATTR, 2),
+            (&CAPABILITIES_ATTR, 2),
+        ],
+        SUBTYPE_PROBE_REQ => vec![],
+        SUBTYPE_ASSOC_REQ => vec![(&CAPABILITIES_ATTR, 2), (&LISTEN_INTERVAL_ATTR, 2)],
+        SUBTYPE_REASSOC_REQ => vec![
+            (&CAPABILITIES_ATTR, 2),
+            (&LISTEN_INTERVAL_ATTR, 2),
+            (&CURRENT_AP_ATTR, 6),
+        ],
+        SUBTYPE_ASSOC_RESP | SUBTYPE_REASSOC_RESP => {
+            vec![(&CAPABILITIES_ATTR, 2), (&STATUS_ATTR, 2), (&AID_ATTR, 2)]
+        }
+        SUBTYPE_AUTH => vec![
+            (&AUTH_ALGORITHM_ATTR, 2),
+            (&AUTH_SEQ_ATTR, 2),
+            (&STATUS_ATTR, 2),
+        ],
+        SUBTYPE_DISASSOC | SUBTYPE_DEAUTH => vec![(&REASON_ATTR, 2)],
+        SUBTYPE_ACTION | SUBTYPE_ACTION_NO_ACK => {
+            if let Some(category) = data.get(offset) {
+                layer.add_attr(attr!(&ACTION_CATEGORY_ATTR, range: offset..offset + 1));
+                if let Some(attr) = get_action_category(*category) {
+                    layer.add_attr(attr!(attr, range: offset..offset + 1));
+                }
+            }
+            return;
+        }
+        _ => return,
+    };
+
+    let mut offset = offset;
+    for (class, len) in fields {
+        if offset + len > data.len() {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+            return;
+        }
+        layer.add_attr(attr!(class, range: offset..offset + len));
+        if ptr::eq(class, &*CAPABILITIES_ATTR) {
+            add_capabilities(layer, offset);
+        }
+        offset += len;
+    }
+
+    if subtype == SUBTYPE_AUTH {
+        let algorithm = u16::from(data[offset - 6]) | u16::from(data[offset - 5]) << 8;
+        if let Some(attr) = get_auth_algorithm(algorithm) {
+            layer.add_attr(attr!(attr, range: offset - 6..offset - 4));
+        }
+        if algorithm == AUTH_SAE {
+            return;
+        }
+    }
+    elements(layer, data, offset);
+}
+
+fn add_capabilities(layer: &mut Layer, offset: usize) {
+    let range = offset..offset + 2;
+    layer.add_attr(attr!(&CAPABILITIES_ESS_ATTR, range: range.clone()));
+    layer.add_attr(attr!(&CAPABILITIES_IBSS_ATTR, range: range.clone()));
+    layer.add_attr(attr!(&CAPABILITIES_PRIVACY_ATTR, range: range.clone()));
+    layer.add_attr(attr!(&CAPABILITIES_SHORT_PREAMBLE_ATTR, range: range.clone()));
+    layer.add_attr(attr!(&CAPABILITIES_SHORT_SLOT_TIME_ATTR, range: range));
+}
+
+fn elements(layer: &mut Layer, data: &ByteSlice, mut offset: usize) {
+    while offset < data.len() {
+        let (id, len) = match data.get(offset..offset + ELEMENT_HEADER_LEN) {
+            Some(header) => (header[0], usize::from(header[1])),
+            None => {
+                layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+                return;
+            }
+        };
+        let start = offset + ELEMENT_HEADER_LEN;
+        let end = start + len;
+        if end > data.len() {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+            return;
+        }
+        layer.add_attr(attr!(&ELEMENT_ATTR, range: offset..end));
+        layer.add_attr(attr!(&ELEMENT_ID_ATTR, range: offset..offset + 1));
+        if let Some(attr) = get_element_id(id) {
+            layer.add_attr(attr!(attr, range: offset..offset + 1));
+        }
+        layer.add_attr(attr!(&ELEMENT_LENGTH_ATTR, range: offset + 1..start));
+        layer.add_attr(attr!(&ELEMENT_DATA_ATTR, range: start..end));
+        element(layer, data, id, start, end);
+        offset = end;
+    }
+}
+
+fn element(layer: &mut Layer, data: &ByteSlice, id: u8, start: usize, end: usize) {
+    let len = end - start;
+    match id {
+        ELEMENT_SSID => layer.add_attr(attr!(&SSID_ATTR, range: start..end)),
+        ELEMENT_SUPPORTED_RATES | ELEMENT_EXTENDED_SUPPORTED_RATES => {
+            for offset in start..end {
+                layer.add_attr(attr!(&SUPPORTED_RATES_ATTR, range: offset..offset + 1));
+            }
+        }
+        ELEMENT_DS_PARAMETER_SET if len >= 1 => {
+            layer.add_attr(attr!(&CHANNEL_ATTR, range: start..start + 1));
+        }
+        ELEMENT_TIM if len >= 2 => {
+            layer.add_attr(attr!(&TIM_DTIM_COUNT_ATTR, range: start..start + 1));
+            layer.add_attr(attr!(&TIM_DTIM_PERIOD_ATTR, range: start + 1..start + 2));
+        }
+        ELEMENT_COUNTRY if len >= 2 => {
+            layer.add_attr(attr!(&COUNTRY_ATTR, range: start..start + 2));
+        }
+        ELEMENT_RSN => rsn(layer, data, start, end),
+        ELEMENT_HT_OPERATION if len >= 1 => {
+            layer.add_attr(attr!(&HT_PRIMARY_CHANNEL_ATTR, range: start..start + 1));
+        }
+        ELEMENT_VENDOR_SPECIFIC if len >= 3 => {
+            layer.add_attr(attr!(&VENDOR_OUI_ATTR, range: start..start + 3));
+        }
+        ELEMENT_EXTENSION if len >= 1 => {
+            layer.add_attr(attr!(&ELEMENT_EXTENSION_ID_ATTR, range: start..start + 1));
+        }
+        _ => {}
+    }
+}
+
+fn suite(data: &ByteSlice, offset: usize) -> u32 {
+    data[offset..offset + SUITE_LEN]
+        .iter()
+        .fold(0u32, |v, b| v << 8 | u32::from(*b))
+}
+
+/// Adds a list of suite selectors preceded by its count, and returns the
+/// offset after the list.
+fn suites(
+    layer: &mut Layer,
+    data: &ByteSlice,
+    offset: usize,
+    end: usize,
+    class: &'static AttrClass,
+    get: fn(u32) -> Option<&'static AttrClass>,
+) -> Option<usize> {
+    if offset + 2 > end {
+        return None;
+    }
+    let count = usize::from(data[offset]) | usize::from(data[offset + 1]) << 8;
+    let mut offset = offset + 2;
+    if offset + count * SUITE_LEN > end {
+        layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+        return None;
+    }
+    for _ in 0..count {
+        layer.add_attr(attr!(class, range: offset..offset + SUITE_LEN));
+        if let Some(attr) = get(suite(data, offset)) {
+            layer.add_attr(attr!(attr, range: offset..offset + SUITE_LEN));
+        }
+        offset += SUITE_LEN;
+    }
+    Some(offset)
+}
+
+/// Adds the fields of an RSN element, which may end after any of them.
+fn rsn(layer: &mut Layer, data: &ByteSlice, start: usize, end: usize) {
+    if start + 2 + SUITE_LEN > end {
+        return;
+    }
+    layer.add_attr(attr!(&RSN_VERSION_ATTR, range: start..start + 2));
+    let offset = start + 2;
+    layer.add_attr(attr!(&RSN_GROUP_CIPHER_ATTR, range: offset..offset + SUITE_LEN));
+    if let Some(attr) = get_group_cipher(suite(data, offset)) {
+        layer.add_attr(attr!(attr, range: offset..offset + SUITE_LEN));
+    }
+    let offset = offset + SUITE_LEN;
+
+    let offset = suites(
+        layer,
+        data,
+        offset,
+        end,
+        &RSN_PAIRWISE_CIPHER_ATTR,
+        get_pairwise_cipher,
+    )
+    .and_then(|offset| suites(layer, data, offset, end, &RSN_AKM_ATTR, get_akm));
+    if let Some(offset) = offset.filter(|offset| offset + 2 <= end) {
+        layer.add_attr(attr!(&RSN_CAPABILITIES_ATTR, range: offset..offset + 2));
+    }
+}
+
+def_attr_class!(TIMESTAMP_ATTR, "ieee80211.timestamp", cast: cast::UInt64LE());
+
+def_attr_class!(BEACON_INTERVAL_ATTR, "ieee80211.beaconInterval", cast: cast::UInt16LE());
+
+def_attr_class!(CAPABILITIES_ATTR, "ieee80211.capabilities",
+    cast: cast::UInt16LE(),
+    typ: "@flags"
+);
+
+def_attr_class!(CAPABILITIES_ESS_ATTR, "ieee80211.capabilities.ess",
+    cast: cast::UInt16LE().map(|v| v & 0x0001 != 0)
+);
+
+def_attr_class!(CAPABILITIES_IBSS_ATTR, "ieee80211.capabilities.ibss",
+    cast: cast::UInt16LE().map(|v| v & 0x0002 != 0)
+);
+
+def_attr_class!(CAPABILITIES_PRIVACY_ATTR, "ieee80211.capabilities.privacy",
+    cast: cast::UInt16LE().map(|v| v & 0x0010 != 0)
+);
+
+def_attr_class!(CAPABILITIES_SHORT_PREAMBLE_ATTR, "ieee80211.capabilities.shortPreamble",
+    cast: cast::UInt16LE().map(|v| v & 0x0020 != 0)
+);
+
+def_attr_class!(CAPABILITIES_SHORT_SLOT_TIME_ATTR, "ieee80211.capabilities.shortSlotTime",
+    cast: cast::UInt16LE().map(|v| v & 0x0400 != 0)
+);
+
+def_attr_class!(LISTEN_INTERVAL_ATTR, "ieee80211.listenInterval", cast: cast::UInt16LE());
+
+def_attr_class!(CURRENT_AP_ATTR, "ieee80211.currentAp",
+    typ: "@eth:mac",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(STATUS_ATTR, "ieee80211.status", cast: cast::UInt16LE());
+
+def_attr_class!(AID_ATTR, "ieee80211.aid",
+    cast: cast::UInt16LE().map(|v| v & 0x3fff)
+);
+
+def_attr_class!(AUTH_ALGORITHM_ATTR, "ieee80211.auth.algorithm",
+    cast: cast::UInt16LE(),
+    typ: "@enum"
+);
+
+fn get_auth_algorithm(val: u16) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("ieee80211.auth.algorithm.open", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!(
+            "ieee80211.auth.algorithm.sharedKey",
+            typ: "@novalue",
+            value: true
+        )),
+        2 => Some(attr_class_lazy!("ieee80211.auth.algorithm.ft", typ: "@novalue", value: true)),
+        AUTH_SAE => {
+            Some(attr_class_lazy!("ieee80211.auth.algorithm.sae", typ: "@novalue", value: true))
+        }
+        _ => None,
+    }
+}
+
+def_attr_class!(AUTH_SEQ_ATTR, "ieee80211.auth.seq", cast: cast::UInt16LE());
+
+def_attr_class!(REASON_ATTR, "ieee80211.reason", cast: cast::UInt16LE());
+
+def_attr_class!(ACTION_CATEGORY_ATTR, "ieee80211.action.category",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_action_category(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!(
+            "ieee80211.action.category.spectrumManagement",
+            typ: "@novalue",
+            value: true
+        )),
+        1 => Some(attr_class_lazy!("ieee80211.action.category.qos", typ: "@novalue", value: true)),
+        3 => Some(attr_class_lazy!(
+            "ieee80211.action.category.blockAck",
+            typ: "@novalue",
+            value: true
+        )),
+        4 => Some(attr_class_lazy!(
+            "ieee80211.action.category.public",
+            typ: "@novalue",
+            value: true
+        )),
+        5 => Some(attr_class_lazy!(
+            "ieee80211.action.category.radioMeasurement",
+            typ: "@novalue",
+            value: true
+        )),
+        6 => Some(attr_class_lazy!("ieee80211.action.category.ft", typ: "@novalue", value: true)),
+        7 => Some(attr_class_lazy!("ieee80211.action.category.ht", typ: "@novalue", value: true)),
+        8 => Some(attr_class_lazy!(
+            "ieee80211.action.category.saQuery",
+            typ: "@novalue",
+            value: true
+        )),
+        10 => Some(attr_class_lazy!("ieee80211.action.category.wnm", typ: "@novalue", value: true)),
+        12 => {
+            Some(attr_class_lazy!("ieee80211.action.category.tdls", typ: "@novalue", value: true))
+        }
+        13 => {
+            Some(attr_class_lazy!("ieee80211.action.category.mesh", typ: "@novalue", value: true))
+        }
+        15 => Some(attr_class_lazy!(
+            "ieee80211.action.category.selfProtected",
+            typ: "@novalue",
+            value: true
+        )),
+        21 => Some(attr_class_lazy!("ieee80211.action.category.vht", typ: "@novalue", value: true)),
+        127 => Some(attr_class_lazy!(
+            "ieee80211.action.category.vendorSpecific",
+            typ: "@novalue",
+            value: true
+        )),
+        _ => None,
+    }
+}
+
+def_attr_class!(ELEMENT_ATTR, "ieee80211.element",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(ELEMENT_ID_ATTR, "ieee80211.element.id",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_element_id(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        ELEMENT_SSID => {
+            Some(attr_class_lazy!("ieee80211.element.id.ssid", typ: "@novalue", value: true))
+        }
+        ELEMENT_SUPPORTED_RATES => Some(attr_class_lazy!(
+            "ieee80211.element.id.supportedRates",
+            typ: "@novalue",
+            value: true
+        )),
+        ELEMENT_DS_PARAMETER_SET => Some(attr_class_lazy!(
+            "ieee80211.element.id.dsParameterSet",
+            typ: "@novalue",
+            value: true
+        )),
+        ELEMENT_TIM => {
+            Some(attr_class_lazy!("ieee80211.element.id.tim", typ: "@novalue", value: true))
+        }
+        ELEMENT_COUNTRY => Some(attr_class_lazy!(
+            "ieee80211.element.id.country",
+            typ: "@novalue",
+            value: true
+        )),
+        42 => Some(attr_class_lazy!("ieee80211.element.id.erp", typ: "@novalue", value: true)),
+        45 => Some(attr_class_lazy!(
+            "ieee80211.element.id.htCapabilities",
+            typ: "@novalue",
+            value: true
+        )),
+        ELEMENT_RSN => {
+            Some(attr_class_lazy!("ieee80211.element.id.rsn", typ: "@novalue", value: true))
+        }
+        ELEMENT_EXTENDED_SUPPORTED_RATES => Some(attr_class_lazy!(
+            "ieee80211.element.id.extendedSupportedRates",
+            typ: "@novalue",
+            value: true
+        )),
+        ELEMENT_HT_OPERATION => Some(attr_class_lazy!(
+            "ieee80211.element.id.htOperation",
+            typ: "@novalue",
+            value: true
+        )),
+        127 => Some(attr_class_lazy!(
+            "ieee80211.element.id.extendedCapabilities",
+            typ: "@novalue",
+            value: true
+        )),
+        191 => Some(attr_class_lazy!(
+            "ieee80211.element.id.vhtCapabilities",
+            typ: "@novalue",
+            value: true
+        )),
+        192 => Some(attr_class_lazy!(
+            "ieee80211.element.id.vhtOperation",
+            typ: "@novalue",
+            value: true
+        )),
+        ELEMENT_VENDOR_SPECIFIC => Some(attr_class_lazy!(
+            "ieee80211.element.id.vendorSpecific",
+            typ: "@novalue",
+            value: true
+        )),
+        ELEMENT_EXTENSION => Some(attr_class_lazy!(
+            "ieee80211.element.id.extension",
+            typ: "@novalue",
+            value: true
+        )),
+        _ => None,
+    }
+}
+
+def_attr_class!(ELEMENT_LENGTH_ATTR, "ieee80211.element.length", cast: cast::UInt8());
+
+def_attr_class!(ELEMENT_DATA_ATTR, "ieee80211.element.data", cast: cast::ByteSlice());
+
+def_attr_class!(ELEMENT_EXTENSION_ID_ATTR, "ieee80211.element.extensionId", cast: cast::UInt8());
+
+def_attr_class!(SSID_ATTR, "ieee80211.ssid", cast: cast::Utf8());
+
+def_attr_class!(SUPPORTED_RATES_ATTR, "ieee80211.supportedRates",
+    cast: cast::UInt8().map(|v| f64::from(v & 0x7f) / 2.0)
+);
+
+def_attr_class!(CHANNEL_ATTR, "ieee80211.channel", cast: cast::UInt8());
+
+def_attr_class!(TIM_DTIM_COUNT_ATTR, "ieee80211.tim.dtimCount", cast: cast::UInt8());
+
+def_attr_class!(TIM_DTIM_PERIOD_ATTR, "ieee80211.tim.dtimPeriod", cast: cast::UInt8());
+
+def_attr_class!(COUNTRY_ATTR, "ieee80211.country", cast: cast::Utf8());
+
+def_attr_class!(HT_PRIMARY_CHANNEL_ATTR, "ieee80211.ht.primaryChannel", cast: cast::UInt8());
+
+def_attr_class!(VENDOR_OUI_ATTR, "ieee80211.vendor.oui", cast: cast::ByteSlice());
+
+def_attr_class!(RSN_VERSION_ATTR, "ieee80211.rsn.version", cast: cast::UInt16LE());
+
+def_attr_class!(RSN_GROUP_CIPHER_ATTR, "ieee80211.rsn.groupCipher",
+    cast: cast::UInt32BE(),
+    typ: "@enum"
+);
+
+fn get_group_cipher(val: u32) -> Option<&'static AttrClass> {
+    match val {
+        0x000f_ac02 => Some(attr_class_lazy!(
+            "ieee80211.rsn.groupCipher.tkip",
+            typ: "@novalue",
+            value: true
+        )),
+        0x000f_ac04 => Some(attr_class_lazy!(
+            "ieee80211.rsn.groupCipher.ccmp128",
+            typ: "@novalue",
+            value: true
+        )),
+        0x000f_ac08 => Some(attr_class_lazy!(
+            "ieee80211.rsn.groupCipher.gcmp128",
+            typ: "@novalue",
+            value: true
+        )),
+        0x000f_ac09 => Some(attr_class_lazy!(
+            "ieee80211.rsn.groupCipher.gcmp256",
+            typ: "@novalue",
+            value: true
+        )),
+        0x000f_ac0a => Some(attr_class_lazy!(
+            "ieee80211.rsn.groupCipher.ccmp256",
+            typ: "@novalue",
+            value: true
+        )),
+        _ => None,
+    }
+}
+
+def_attr_class!(RSN_PAIRWISE_CIPHER_ATTR, "ieee80211.rsn.pairwiseCipher",
+    cast: cast::UInt32BE(),
+    typ: "@enum"
+);
+
+fn get_pairwise_cipher(val: u32) -> Option<&'static AttrClass> {
+    match val {
+        0x000f_ac02 => Some(attr_class_lazy!(
+            "ieee80211.rsn.pairwiseCipher.tkip",
+            typ: "@novalue",
+            value: true
+        )),
+        0x000f_ac04 => Some(attr_class_lazy!(
+            "ieee80211.rsn.pairwiseCipher.ccmp128",
+            typ: "@novalue",
+            value: true
+        )),
+        0x000f_ac08 => Some(attr_class_lazy!(
+            "ieee80211.rsn.pairwiseCipher.gcmp128",
+            typ: "@novalue",
+            value: true
+        )),
+        0x000f_ac09 => Some(attr_class_lazy!(
+            "ieee80211.rsn.pairwiseCipher.gcmp256",
+            typ: "@novalue",
+            value: true
+        )),
+        0x000f_ac0a => Some(attr_class_lazy!(
+            "ieee80211.rsn.pairwiseCipher.ccmp256",
+            typ: "@novalue",
+            value: true
+        )),
+        _ => None,
+    }
+}
+
+def_attr_class!(RSN_AKM_ATTR, "ieee80211.rsn.akm",
+    cast: cast::UInt32BE(),
+    typ: "@enum"
+);
+
+fn get_akm(val: u32) -> Option<&'static AttrClass> {
+    match val {
+        0x000f_ac01 => {
+            Some(attr_class_lazy!("ieee80211.rsn.akm.ieee8021x", typ: "@novalue", value: true))
+        }
+        0x000f_ac02 => {
+            Some(attr_class_lazy!("ieee80211.rsn.akm.psk", typ: "@novalue", value: true))
+        }
+        0x000f_ac03 => {
+            Some(attr_class_lazy!("ieee80211.rsn.akm.ftIeee8021x", typ: "@novalue", value: true))
+        }
+        0x000f_ac04 => {
+            Some(attr_class_lazy!("ieee80211.rsn.akm.ftPsk", typ: "@novalue", value: true))
+        }
+        0x000f_ac05 => Some(attr_class_lazy!(
+            "ieee80211.rsn.akm.ieee8021xSha256",
+            typ: "@novalue",
+            value: true
+        )),
+        0x000f_ac06 => {
+            Some(attr_class_lazy!("ieee80211.rsn.akm.pskSha256", typ: "@novalue", value: true))
+        }
+        0x000f_ac08 => {
+            Some(attr_class_lazy!("ieee80211.rsn.akm.sae", typ: "@novalue", value: true))
+        }
+        0x000f_ac09 => {
+            Some(attr_class_lazy!("ieee80211.rsn.akm.ftSae", typ: "@novalue", value: true))
+        }
+        0x000f_ac0c => {
+            Some(attr_class_lazy!("ieee80211.rsn.akm.suiteB192", typ: "@novalue", value: true))
+        }
+        0x000f_ac12 => {
+            Some(attr_class_lazy!("ieee80211.rsn.akm.owe", typ: "@novalue", value: true))
+        }
+        0x000f_ac18 => {
+            Some(attr_class_lazy!("ieee80211.rsn.akm.saeExtKey", typ: "@novalue", value: true))
+        }
+        _ => None,
+    }
+}
+
+def_attr_class!(RSN_CAPABILITIES_ATTR, "ieee80211.rsn.capabilities",
+    cast: cast::UInt16LE(),
+    typ: "@flags"
+);
+
+def_attr_class!(MALFORMED_ATTR, "ieee80211.malformed",
+    typ: "@expert:error",
+    description: "Field or element exceeding the frame"
+);
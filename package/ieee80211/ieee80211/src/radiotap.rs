@@ -0,0 +1,389 @@
+//! Radiotap headers of captured 802.11 frames.
+//!
+//! The fields are aligned to their natural boundaries from the beginning of
+//! the header, and the parsing stops at the first unknown field as its
+//! alignment and length are not known.
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+/// The length of the header without the fields.
+const HEADER_LEN: usize = 8;
+
+const FCS_LEN: usize = 4;
+
+const PRESENT_RADIOTAP_NAMESPACE: u32 = 1 << 29;
+const PRESENT_VENDOR_NAMESPACE: u32 = 1 << 30;
+const PRESENT_EXT: u32 = 1 << 31;
+
+/// The number of the fields in a presence bitmap.
+const FIELDS_PER_BITMAP: usize = 29;
+
+/// The length of the vendor namespace field.
+const VENDOR_NAMESPACE_LEN: usize = 6;
+
+const FLAG_FCS: u8 = 0x10;
+
+/// The alignments and the lengths of the fields defined in the radiotap
+/// namespace.
+const FIELDS: [(usize, usize); 28] = [
+    (8, 8),  // TSFT
+    (1, 1),  // Flags
+    (1, 1),  // Rate
+    (2, 4),  // Channel
+    (1, 2),  // FHSS
+    (1, 1),  // Antenna signal (dBm)
+    (1, 1),  // Antenna noise (dBm)
+    (2, 2),  // Lock quality
+    (2, 2),  // TX attenuation
+    (2, 2),  // TX attenuation (dB)
+    (1, 1),  // TX power (dBm)
+    (1, 1),  // Antenna
+    (1, 1),  // Antenna signal (dB)
+    (1, 1),  // Antenna noise (dB)
+    (2, 2),  // RX flags
+    (2, 2),  // TX flags
+    (1, 1),  // RTS retries
+    (1, 1),  // Data retries
+    (4, 8),  // XChannel
+    (1, 3),  // MCS
+    (4, 8),  // A-MPDU status
+    (2, 12), // VHT
+    (8, 12), // Timestamp
+    (2, 12), // HE
+    (2, 12), // HE-MU
+    (2, 6),  // HE-MU-other-user
+    (1, 1),  // 0-length-PSDU
+    (2, 4),  // L-SIG
+];
+
+fn u32_le(data: &[u8]) -> u32 {
+    data.iter().rev().fold(0u32, |v, b| v << 8 | u32::from(*b))
+}
+
+/// Returns the channel number of a frequency in MHz.
+fn channel(freq: u64) -> Option<u64> {
+    match freq {
+        2484 => Some(14),
+        2412..=2472 => Some((freq - 2407) / 5),
+        5000..=5900 => Some((freq - 5000) / 5),
+        5955..=7115 => Some((freq - 5950) / 5),
+        _ => None,
+    }
+}
+
+/// Adds the attributes of a field, and returns the flags if the field is
+/// the Flags.
+fn add_field(layer: &mut Layer, data: &[u8], index: usize, offset: usize) -> Option<u8> {
+    match index {
+        0 => layer.add_attr(attr!(&TSFT_ATTR, range: offset..offset + 8)),
+        1 => {
+            layer.add_attr(attr!(&FLAGS_ATTR, range: offset..offset + 1));
+            layer.add_attr(attr!(&FLAGS_CFP_ATTR, range: offset..offset + 1));
+            layer.add_attr(attr!(&FLAGS_SHORT_PREAMBLE_ATTR, range: offset..offset + 1));
+            layer.add_attr(attr!(&FLAGS_WEP_ATTR, range: offset..offset + 1));
+            layer.add_attr(attr!(&FLAGS_FRAGMENTATION_ATTR, range: offset..offset + 1));
+            layer.add_attr(attr!(&FLAGS_FCS_ATTR, range: offset..offset + 1));
+            layer.add_attr(attr!(&FLAGS_DATA_PAD_ATTR, range: offset..offset + 1));
+            layer.add_attr(attr!(&FLAGS_BAD_FCS_ATTR, range: offset..offset + 1));
+            layer.add_attr(attr!(&FLAGS_SHORT_GI_ATTR, range: offset..offset + 1));
+            return Some(data[offset]);
+        }
+        2 => layer.add_attr(attr!(&RATE_ATTR, range: offset..offset + 1)),
+        3 => {
+            let freq = u64::from(data[offset]) | u64::from(data[offset + 1]) << 8;
+            if let Some(channel) = channel(freq) {
+                layer.add_attr(attr!(&CHANNEL_ATTR, range: offset..offset + 2, value: channel));
+            }
+            layer.add_attr(attr!(&CHANNEL_FREQUENCY_ATTR, range: offset..offset + 2));
+            layer.add_attr(attr!(&CHANNEL_FLAGS_ATTR, range: offset + 2..offset + 4));
+            layer.add_attr(attr!(&CHANNEL_FLAGS_CCK_ATTR, range: offset + 2..offset + 4));
+            layer.add_attr(attr!(&CHANNEL_FLAGS_OFDM_ATTR, range: offset + 2..offset + 4));
+            layer.add_attr(attr!(&CHANNEL_FLAGS_2GHZ_ATTR, range: offset + 2..offset + 4));
+            layer.add_attr(attr!(&CHANNEL_FLAGS_5GHZ_ATTR, range: offset + 2..offset + 4));
+            layer.add_attr(attr!(&CHANNEL_FLAGS_PASSIVE_ATTR, range: offset + 2..offset + 4));
+        }
+        5 => layer.add_attr(attr!(&SIGNAL_ATTR, range: offset..offset + 1)),
+        6 => layer.add_attr(attr!(&NOISE_ATTR, range: offset..offset + 1)),
+        7 => layer.add_attr(attr!(&LOCK_QUALITY_ATTR, range: offset..offset + 2)),
+        8 => layer.add_attr(attr!(&TX_ATTENUATION_ATTR, range: offset..offset + 2)),
+        9 => layer.add_attr(attr!(&DB_TX_ATTENUATION_ATTR, range: offset..offset + 2)),
+        10 => layer.add_attr(attr!(&TX_POWER_ATTR, range: offset..offset + 1)),
+        11 => layer.add_attr(attr!(&ANTENNA_ATTR, range: offset..offset + 1)),
+        12 => layer.add_attr(attr!(&DB_SIGNAL_ATTR, range: offset..offset + 1)),
+        13 => layer.add_attr(attr!(&DB_NOISE_ATTR, range: offset..offset + 1)),
+        14 => {
+            layer.add_attr(attr!(&RX_FLAGS_ATTR, range: offset..offset + 2));
+            layer.add_attr(attr!(&RX_FLAGS_BAD_PLCP_ATTR, range: offset..offset + 2));
+        }
+        15 => layer.add_attr(attr!(&TX_FLAGS_ATTR, range: offset..offset + 2)),
+        16 => layer.add_attr(attr!(&RTS_RETRIES_ATTR, range: offset..offset + 1)),
+        17 => layer.add_attr(attr!(&DATA_RETRIES_ATTR, range: offset..offset + 1)),
+        19 => {
+            layer.add_attr(attr!(&MCS_BANDWIDTH_ATTR, range: offset + 1..offset + 2));
+            layer.add_attr(attr!(&MCS_SHORT_GI_ATTR, range: offset + 1..offset + 2));
+            layer.add_attr(attr!(&MCS_INDEX_ATTR, range: offset + 2..offset + 3));
+        }
+        20 => layer.add_attr(attr!(&AMPDU_REFERENCE_ATTR, range: offset..offset + 4)),
+        21 => {
+            layer.add_attr(attr!(&VHT_BANDWIDTH_ATTR, range: offset + 3..offset + 4));
+            layer.add_attr(attr!(&VHT_MCS_ATTR, range: offset + 4..offset + 5));
+            layer.add_attr(attr!(&VHT_NSS_ATTR, range: offset + 4..offset + 5));
+        }
+        _ => {}
+    }
+    None
+}
+
+struct RadiotapWorker {}
+
+impl Worker for RadiotapWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("[link-127]") {
+            return Ok(Status::Skip);
+        }
+
+        let data = parent.data();
+        let mut layer = Layer::new(&RADIOTAP_CLASS, data);
+        let len: u64 = LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let len = len as usize;
+        if len < HEADER_LEN || len > data.len() {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let mut bitmaps = Vec::new();
+        let mut offset = 4;
+        while let Some(bitmap) = data.get(offset..offset + 4).filter(|_| offset + 4 <= len) {
+            let bitmap = u32_le(bitmap);
+            bitmaps.push(bitmap);
+            offset += 4;
+            if bitmap & PRESENT_EXT == 0 {
+                break;
+            }
+        }
+
+        // The fields of a bitmap in the vendor namespace are skipped, and the
+        // radiotap namespace restarts from the first field.
+        let mut flags = None;
+        let mut base = 0;
+        let mut vendor = false;
+        'bitmaps: for bitmap in bitmaps {
+            if !vendor {
+                for bit in (0..FIELDS_PER_BITMAP).filter(|bit| bitmap & (1 << bit) != 0) {
+                    let (align, size) = match FIELDS.get(base + bit) {
+                        Some(field) => *field,
+                        None => break 'bitmaps,
+                    };
+                    offset = offset.next_multiple_of(align);
+                    if offset + size > len {
+                        layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+                        break 'bitmaps;
+                    }
+                    if let Some(value) = add_field(&mut layer, &data, base + bit, offset) {
+                        flags.get_or_insert(value);
+                    }
+                    offset += size;
+                }
+            }
+            if bitmap & PRESENT_VENDOR_NAMESPACE != 0 {
+                offset = offset.next_multiple_of(2);
+                let skip = match data.get(offset..offset + VENDOR_NAMESPACE_LEN) {
+                    Some(field) => usize::from(field[4]) | usize::from(field[5]) << 8,
+                    None => break,
+                };
+                offset += VENDOR_NAMESPACE_LEN + skip;
+                vendor = true;
+            } else if bitmap & PRESENT_RADIOTAP_NAMESPACE != 0 {
+                base = 0;
+                vendor = false;
+            } else {
+                base += 32;
+            }
+        }
+
+        let end = if flags.is_some_and(|flags| flags & FLAG_FCS != 0) {
+            data.len().saturating_sub(FCS_LEN).max(len)
+        } else {
+            data.len()
+        };
+        let payload = data.try_get(len..end)?;
+        layer.add_payload(Payload::new(payload, "@data:ieee80211"));
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+pub struct RadiotapDecoder {}
+
+impl Decoder for RadiotapDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(RadiotapWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(RADIOTAP_CLASS, "radiotap",
+    header: attr!(&VERSION_ATTR, range: 0..1),
+    header: &LENGTH_ATTR_HEADER,
+    header: attr!(&PRESENT_ATTR, range: 4..8)
+);
+
+def_attr!(LENGTH_ATTR_HEADER, &LENGTH_ATTR, range: 2..4);
+
+def_attr_class!(VERSION_ATTR, "radiotap.version", cast: cast::UInt8());
+
+def_attr_class!(LENGTH_ATTR, "radiotap.length", cast: cast::UInt16LE());
+
+def_attr_class!(PRESENT_ATTR, "radiotap.present",
+    cast: cast::UInt32LE(),
+    typ: "@flags"
+);
+
+def_attr_class!(TSFT_ATTR, "radiotap.tsft", cast: cast::UInt64LE());
+
+def_attr_class!(FLAGS_ATTR, "radiotap.flags",
+    cast: cast::UInt8(),
+    typ: "@flags"
+);
+
+def_attr_class!(FLAGS_CFP_ATTR, "radiotap.flags.cfp",
+    cast: cast::UInt8().map(|v| v & 0x01 != 0)
+);
+
+def_attr_class!(FLAGS_SHORT_PREAMBLE_ATTR, "radiotap.flags.shortPreamble",
+    cast: cast::UInt8().map(|v| v & 0x02 != 0)
+);
+
+def_attr_class!(FLAGS_WEP_ATTR, "radiotap.flags.wep",
+    cast: cast::UInt8().map(|v| v & 0x04 != 0)
+);
+
+def_attr_class!(FLAGS_FRAGMENTATION_ATTR, "radiotap.flags.fragmentation",
+    cast: cast::UInt8().map(|v| v & 0x08 != 0)
+);
+
+def_attr_class!(FLAGS_FCS_ATTR, "radiotap.flags.fcs",
+    cast: cast::UInt8().map(|v| v & FLAG_FCS != 0)
+);
+
+def_attr_class!(FLAGS_DATA_PAD_ATTR, "radiotap.flags.dataPad",
+    cast: cast::UInt8().map(|v| v & 0x20 != 0)
+);
+
+def_attr_class!(FLAGS_BAD_FCS_ATTR, "radiotap.flags.badFcs",
+    cast: cast::UInt8().map(|v| v & 0x40 != 0)
+);
+
+def_attr_class!(FLAGS_SHORT_GI_ATTR, "radiotap.flags.shortGi",
+    cast: cast::UInt8().map(|v| v & 0x80 != 0)
+);
+
+def_attr_class!(RATE_ATTR, "radiotap.rate",
+    cast: cast::UInt8().map(|v| f64::from(v) / 2.0)
+);
+
+def_attr_class!(CHANNEL_ATTR, "radiotap.channel");
+
+def_attr_class!(CHANNEL_FREQUENCY_ATTR, "radiotap.channel.frequency", cast: cast::UInt16LE());
+
+def_attr_class!(CHANNEL_FLAGS_ATTR, "radiotap.channel.flags",
+    cast: cast::UInt16LE(),
+    typ: "@flags"
+);
+
+def_attr_class!(CHANNEL_FLAGS_CCK_ATTR, "radiotap.channel.flags.cck",
+    cast: cast::UInt16LE().map(|v| v & 0x0020 != 0)
+);
+
+def_attr_class!(CHANNEL_FLAGS_OFDM_ATTR, "radiotap.channel.flags.ofdm",
+    cast: cast::UInt16LE().map(|v| v & 0x0040 != 0)
+);
+
+def_attr_class!(CHANNEL_FLAGS_2GHZ_ATTR, "radiotap.channel.flags.spectrum2GHz",
+    cast: cast::UInt16LE().map(|v| v & 0x0080 != 0)
+);
+
+def_attr_class!(CHANNEL_FLAGS_5GHZ_ATTR, "radiotap.channel.flags.spectrum5GHz",
+    cast: cast::UInt16LE().map(|v| v & 0x0100 != 0)
+);
+
+def_attr_class!(CHANNEL_FLAGS_PASSIVE_ATTR, "radiotap.channel.flags.passive",
+    cast: cast::UInt16LE().map(|v| v & 0x0200 != 0)
+);
+
+def_attr_class!(SIGNAL_ATTR, "radiotap.signal", cast: cast::Int8());
+
+def_attr_class!(NOISE_ATTR, "radiotap.noise", cast: cast::Int8());
+
+def_attr_class!(LOCK_QUALITY_ATTR, "radiotap.lockQuality", cast: cast::UInt16LE());
+
+def_attr_class!(TX_ATTENUATION_ATTR, "radiotap.txAttenuation", cast: cast::UInt16LE());
+
+def_attr_class!(DB_TX_ATTENUATION_ATTR, "radiotap.dbTxAttenuation", cast: cast::UInt16LE());
+
+def_attr_class!(TX_POWER_ATTR, "radiotap.txPower", cast: cast::Int8());
+
+def_attr_class!(ANTENNA_ATTR, "radiotap.antenna", cast: cast::UInt8());
+
+def_attr_class!(DB_SIGNAL_ATTR, "radiotap.dbSignal", cast: cast::UInt8());
+
+def_attr_class!(DB_NOISE_ATTR, "radiotap.dbNoise", cast: cast::UInt8());
+
+def_attr_class!(RX_FLAGS_ATTR, "radiotap.rxFlags",
+    cast: cast::UInt16LE(),
+    typ: "@flags"
+);
+
+def_attr_class!(RX_FLAGS_BAD_PLCP_ATTR, "radiotap.rxFlags.badPlcp",
+    cast: cast::UInt16LE().map(|v| v & 0x0002 != 0)
+);
+
+def_attr_class!(TX_FLAGS_ATTR, "radiotap.txFlags",
+    cast: cast::UInt16LE(),
+    typ: "@flags"
+);
+
+def_attr_class!(RTS_RETRIES_ATTR, "radiotap.rtsRetries", cast: cast::UInt8());
+
+def_attr_class!(DATA_RETRIES_ATTR, "radiotap.dataRetries", cast: cast::UInt8());
+
+def_attr_class!(MCS_BANDWIDTH_ATTR, "radiotap.mcs.bandwidth",
+    cast: cast::UInt8().map(|v| v & 0b11)
+);
+
+def_attr_class!(MCS_SHORT_GI_ATTR, "radiotap.mcs.shortGi",
+    cast: cast::UInt8().map(|v| v & 0b100 != 0)
+);
+
+def_attr_class!(MCS_INDEX_ATTR, "radiotap.mcs.index", cast: cast::UInt8());
+
+def_attr_class!(AMPDU_REFERENCE_ATTR, "radiotap.ampdu.reference", cast: cast::UInt32LE());
+
+def_attr_class!(VHT_BANDWIDTH_ATTR, "radiotap.vht.bandwidth", cast: cast::UInt8());
+
+def_attr_class!(VHT_MCS_ATTR, "radiotap.vht.mcs",
+    cast: cast::UInt8().map(|v| v >> 4)
+);
+
+def_attr_class!(VHT_NSS_ATTR, "radiotap.vht.nss",
+    cast: cast::UInt8().map(|v| v & 0x0f)
+);
+
+def_attr_class!(MALFORMED_ATTR, "radiotap.malformed",
+    typ: "@expert:error",
+    description: "Field exceeding the header length"
+);
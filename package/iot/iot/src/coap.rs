@@ -0,0 +1,406 @@
+//! CoAP decoder.
+//!
+//! The payloads of a block-wise transfer (RFC 7959) are reassembled by the
+//! endpoints and the block option, and the message carrying the last block
+//! has the whole body as a `@body:coap` payload.
+
+use flow::{self, Cursor, Endpoint};
+use genet_sdk::{cast, decoder::*, prelude::*};
+use std::{collections::HashMap, ops::Range};
+
+/// The port of CoAP servers.
+const PORT: u64 = 5683;
+
+const HEADER_LEN: usize = 4;
+
+/// The longest token.
+const MAX_TOKEN_LEN: usize = 8;
+
+const PAYLOAD_MARKER: u8 = 0xff;
+
+const OPTION_URI_PATH: u64 = 11;
+const OPTION_BLOCK2: u64 = 23;
+const OPTION_BLOCK1: u64 = 27;
+
+/// The value of a Block1 or Block2 option.
+struct Block {
+    option: u64,
+    num: u64,
+    more: bool,
+    size: u64,
+}
+
+impl Block {
+    fn parse(option: u64, value: u64) -> Option<Block> {
+        // The size exponent 7 is reserved.
+        let szx = value & 0b111;
+        if szx == 7 {
+            return None;
+        }
+        Some(Block {
+            option,
+            num: value >> 4,
+            more: value & 0b1000 != 0,
+            size: 16 << szx,
+        })
+    }
+}
+
+struct CoapWorker {
+    /// The bodies being reassembled by the endpoints and the block option.
+    transfers: HashMap<(Endpoint, Endpoint, u64), Vec<u8>>,
+}
+
+impl CoapWorker {
+    /// Adds a block to the transfer, and returns the body if it is the last
+    /// one of several blocks.
+    fn reassemble(
+        &mut self,
+        key: (Endpoint, Endpoint, u64),
+        block: &Block,
+        payload: &[u8],
+    ) -> Option<Vec<u8>> {
+        if block.num == 0 {
+            self.transfers.insert(key.clone(), payload.to_vec());
+        } else {
+            // A missing block aborts the transfer.
+            let expected = block.num * block.size;
+            match self.transfers.get_mut(&key) {
+                Some(ref mut body) if body.len() as u64 == expected => {
+                    body.extend_from_slice(payload);
+                }
+                _ => {
+                    self.transfers.remove(&key);
+                    return None;
+                }
+            }
+        }
+        if block.more {
+            return None;
+        }
+        self.transfers.remove(&key).filter(|_| block.num > 0)
+    }
+}
+
+impl Worker for CoapWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:coap"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&COAP_CLASS, data);
+        let tkl = usize::from(data.try_get(0).map(|b: u8| b & 0x0f).unwrap_or(0));
+        if data.len() < HEADER_LEN + tkl || tkl > MAX_TOKEN_LEN {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let typ: u8 = data.try_get(0)?;
+        if let Some(attr) = get_type((typ >> 4) & 0b11) {
+            layer.add_attr(attr!(attr, bit_range: 0 2..4));
+        }
+        let code: u8 = data.try_get(1)?;
+        if let Some(attr) = get_code(code) {
+            layer.add_attr(attr!(attr, range: 1..2));
+        }
+        if tkl > 0 {
+            layer.add_attr(attr!(&TOKEN_ATTR, range: HEADER_LEN..HEADER_LEN + tkl));
+        }
+
+        let mut c = Cursor::new(&data, HEADER_LEN + tkl);
+        let mut blocks = Vec::new();
+        let mut path = Vec::new();
+        let mut payload = None;
+        let mut number = 0;
+        while let Some(b) = c.u8() {
+            if b == PAYLOAD_MARKER {
+                payload = c.skip(c.remaining());
+                break;
+            }
+            let start = c.pos() - 1;
+            let value = match option_header(b, &mut c).and_then(|(delta, len)| {
+                number += delta;
+                c.skip(len)
+            }) {
+                Some(value) => value,
+                None => {
+                    layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+                    break;
+                }
+            };
+
+            layer.add_attr(attr!(&OPTION_ATTR, range: start..value.end));
+            layer.add_attr(attr!(&OPTION_NUMBER_ATTR, range: start..start + 1, value: number));
+            if !value.is_empty() {
+                layer.add_attr(attr!(&OPTION_VALUE_ATTR, range: value.clone()));
+            }
+            let bytes = &data[value.clone()];
+            if let Some(attr) = option_attr(number, value.clone(), bytes) {
+                layer.add_attr(attr);
+            }
+            match number {
+                OPTION_URI_PATH => path.push((value, String::from_utf8_lossy(bytes).into_owned())),
+                OPTION_BLOCK1 | OPTION_BLOCK2 => {
+                    if let Some(block) = Block::parse(number, uint(bytes)) {
+                        block_attrs(&mut layer, &block, value);
+                        blocks.push(block);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(first), Some(last)) = (path.first(), path.last()) {
+            let value = path.iter().fold(String::new(), |mut path, (_, segment)| {
+                path.push('/');
+                path.push_str(segment);
+                path
+            });
+            let range = first.0.start..last.0.end;
+            layer.add_attr(attr!(&PATH_ATTR, range: range, value: value.into_boxed_str()));
+        }
+
+        if let Some(range) = payload {
+            // The payload marker must be followed by a payload.
+            if range.is_empty() {
+                layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+            } else {
+                layer.add_attr(attr!(&PAYLOAD_ATTR, range: range.clone()));
+                let (src, dst) = flow::endpoints(stack, token!("udp.src"), token!("udp.dst"))?;
+                for block in &blocks {
+                    let key = (src.clone(), dst.clone(), block.option);
+                    if let Some(body) = self.reassemble(key, block, &data[range.clone()]) {
+                        layer.add_attr(attr!(&REASSEMBLED_ATTR, value: true));
+                        layer.add_payload(Payload::new(ByteSlice::from(body), "@body:coap"));
+                    }
+                }
+            }
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+/// Reads the extended delta and length of an option.
+fn option_header(b: u8, c: &mut Cursor) -> Option<(u64, usize)> {
+    let delta = extended(b >> 4, c)?;
+    let len = extended(b & 0x0f, c)?;
+    Some((u64::from(delta), len as usize))
+}
+
+fn extended(n: u8, c: &mut Cursor) -> Option<u32> {
+    match n {
+        13 => c.u8().map(|v| u32::from(v) + 13),
+        14 => c.u16().map(|v| u32::from(v) + 269),
+        15 => None,
+        _ => Some(u32::from(n)),
+    }
+}
+
+/// Returns the value of an unsigned integer option.
+fn uint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |v, b| v << 8 | u64::from(*b))
+}
+
+fn block_attrs(layer: &mut Layer, block: &Block, range: Range<usize>) {
+    let (num, more, size): (&'static AttrClass, &'static AttrClass, &'static AttrClass) =
+        if block.option == OPTION_BLOCK1 {
+            (&BLOCK1_NUM_ATTR, &BLOCK1_MORE_ATTR, &BLOCK1_SIZE_ATTR)
+        } else {
+            (&BLOCK2_NUM_ATTR, &BLOCK2_MORE_ATTR, &BLOCK2_SIZE_ATTR)
+        };
+    layer.add_attr(attr!(num, range: range.clone(), value: block.num));
+    layer.add_attr(attr!(more, range: range.clone(), value: block.more));
+    layer.add_attr(attr!(size, range: range, value: block.size));
+}
+
+fn option_attr(number: u64, range: Range<usize>, bytes: &[u8]) -> Option<Attr> {
+    let string = || String::from_utf8_lossy(bytes).into_owned().into_boxed_str();
+    let attr = match number {
+        1 => attr!(attr_class_lazy!("coap.ifMatch", cast: cast::ByteSlice()), range: range),
+        3 => attr!(attr_class_lazy!("coap.uriHost"), range: range, value: string()),
+        4 => attr!(attr_class_lazy!("coap.etag", cast: cast::ByteSlice()), range: range),
+        5 => {
+            attr!(attr_class_lazy!("coap.ifNoneMatch", typ: "@novalue", value: true), range: range)
+        }
+        6 => attr!(attr_class_lazy!("coap.observe"), range: range, value: uint(bytes)),
+        7 => attr!(attr_class_lazy!("coap.uriPort"), range: range, value: uint(bytes)),
+        8 => attr!(attr_class_lazy!("coap.locationPath"), range: range, value: string()),
+        11 => attr!(attr_class_lazy!("coap.uriPath"), range: range, value: string()),
+        12 => attr!(attr_class_lazy!("coap.contentFormat"), range: range, value: uint(bytes)),
+        14 => attr!(attr_class_lazy!("coap.maxAge"), range: range, value: uint(bytes)),
+        15 => attr!(attr_class_lazy!("coap.uriQuery"), range: range, value: string()),
+        17 => attr!(attr_class_lazy!("coap.accept"), range: range, value: uint(bytes)),
+        20 => attr!(attr_class_lazy!("coap.locationQuery"), range: range, value: string()),
+        28 => attr!(attr_class_lazy!("coap.size2"), range: range, value: uint(bytes)),
+        35 => attr!(attr_class_lazy!("coap.proxyUri"), range: range, value: string()),
+        39 => attr!(attr_class_lazy!("coap.proxyScheme"), range: range, value: string()),
+        60 => attr!(attr_class_lazy!("coap.size1"), range: range, value: uint(bytes)),
+        _ => return None,
+    };
+    Some(attr)
+}
+
+#[derive(Clone)]
+pub struct CoapDecoder {}
+
+impl Decoder for CoapDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        ctx.dissector_table("udp.port")
+            .add_default(PORT, "@data:coap");
+        Box::new(CoapWorker {
+            transfers: HashMap::new(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::SerialSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(COAP_CLASS, "coap",
+    header: attr!(&VERSION_ATTR, bit_range: 0 0..2),
+    header: attr!(&TYPE_ATTR, bit_range: 0 2..4),
+    header: attr!(&TOKEN_LENGTH_ATTR, bit_range: 0 4..8),
+    header: attr!(&CODE_ATTR, range: 1..2),
+    header: attr!(&MESSAGE_ID_ATTR, range: 2..4)
+);
+
+def_attr_class!(VERSION_ATTR, "coap.version",
+    cast: cast::UInt8().map(|v| v >> 6)
+);
+
+def_attr_class!(TYPE_ATTR, "coap.type",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| (v >> 4) & 0b11)
+);
+
+def_attr_class!(TOKEN_LENGTH_ATTR, "coap.tokenLength",
+    cast: cast::UInt8().map(|v| v & 0x0f)
+);
+
+def_attr_class!(CODE_ATTR, "coap.code",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(MESSAGE_ID_ATTR, "coap.messageId", cast: cast::UInt16BE());
+
+def_attr_class!(TOKEN_ATTR, "coap.token", cast: cast::ByteSlice());
+
+def_attr_class!(OPTION_ATTR, "coap.option",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(OPTION_NUMBER_ATTR, "coap.option.number");
+
+def_attr_class!(OPTION_VALUE_ATTR, "coap.option.value", cast: cast::ByteSlice());
+
+def_attr_class!(BLOCK1_NUM_ATTR, "coap.block1.num");
+
+def_attr_class!(BLOCK1_MORE_ATTR, "coap.block1.more");
+
+def_attr_class!(BLOCK1_SIZE_ATTR, "coap.block1.size");
+
+def_attr_class!(BLOCK2_NUM_ATTR, "coap.block2.num");
+
+def_attr_class!(BLOCK2_MORE_ATTR, "coap.block2.more");
+
+def_attr_class!(BLOCK2_SIZE_ATTR, "coap.block2.size");
+
+def_attr_class!(PATH_ATTR, "coap.path");
+
+def_attr_class!(PAYLOAD_ATTR, "coap.payload", cast: cast::ByteSlice());
+
+def_attr_class!(REASSEMBLED_ATTR, "coap.reassembled",
+    typ: "@expert:note",
+    description: "Body reassembled from blocks"
+);
+
+def_attr_class!(MALFORMED_ATTR, "coap.malformed",
+    typ: "@expert:error",
+    description: "Malformed CoAP message"
+);
+
+fn get_type(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("coap.type.confirmable", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!("coap.type.nonConfirmable", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("coap.type.acknowledgement", typ: "@novalue", value: true)),
+        3 => Some(attr_class_lazy!("coap.type.reset", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+/// Returns the class of the code, which is c.dd in the registry.
+fn get_code(val: u8) -> Option<&'static AttrClass> {
+    match (val >> 5, val & 0x1f) {
+        (0, 0) => Some(attr_class_lazy!("coap.code.empty", typ: "@novalue", value: true)),
+        (0, 1) => Some(attr_class_lazy!("coap.code.get", typ: "@novalue", value: true)),
+        (0, 2) => Some(attr_class_lazy!("coap.code.post", typ: "@novalue", value: true)),
+        (0, 3) => Some(attr_class_lazy!("coap.code.put", typ: "@novalue", value: true)),
+        (0, 4) => Some(attr_class_lazy!("coap.code.delete", typ: "@novalue", value: true)),
+        (0, 5) => Some(attr_class_lazy!("coap.code.fetch", typ: "@novalue", value: true)),
+        (0, 6) => Some(attr_class_lazy!("coap.code.patch", typ: "@novalue", value: true)),
+        (0, 7) => Some(attr_class_lazy!("coap.code.iPatch", typ: "@novalue", value: true)),
+        (2, 1) => Some(attr_class_lazy!("coap.code.created", typ: "@novalue", value: true)),
+        (2, 2) => Some(attr_class_lazy!("coap.code.deleted", typ: "@novalue", value: true)),
+        (2, 3) => Some(attr_class_lazy!("coap.code.valid", typ: "@novalue", value: true)),
+        (2, 4) => Some(attr_class_lazy!("coap.code.changed", typ: "@novalue", value: true)),
+        (2, 5) => Some(attr_class_lazy!("coap.code.content", typ: "@novalue", value: true)),
+        (2, 31) => Some(attr_class_lazy!("coap.code.continue", typ: "@novalue", value: true)),
+        (4, 0) => Some(attr_class_lazy!("coap.code.badRequest", typ: "@novalue", value: true)),
+        (4, 1) => Some(attr_class_lazy!("coap.code.unauthorized", typ: "@novalue", value: true)),
+        (4, 2) => Some(attr_class_lazy!("coap.code.badOption", typ: "@novalue", value: true)),
+        (4, 3) => Some(attr_class_lazy!("coap.code.forbidden", typ: "@novalue", value: true)),
+        (4, 4) => Some(attr_class_lazy!("coap.code.notFound", typ: "@novalue", value: true)),
+        (4, 5) => {
+            Some(attr_class_lazy!("coap.code.methodNotAllowed", typ: "@novalue", value: true))
+        }
+        (4, 6) => Some(attr_class_lazy!("coap.code.notAcceptable", typ: "@novalue", value: true)),
+        (4, 8) => Some(
+            attr_class_lazy!("coap.code.requestEntityIncomplete", typ: "@novalue", value: true),
+        ),
+        (4, 12) => {
+            Some(attr_class_lazy!("coap.code.preconditionFailed", typ: "@novalue", value: true))
+        }
+        (4, 13) => {
+            Some(attr_class_lazy!("coap.code.requestEntityTooLarge", typ: "@novalue", value: true))
+        }
+        (4, 15) => Some(
+            attr_class_lazy!("coap.code.unsupportedContentFormat", typ: "@novalue", value: true),
+        ),
+        (5, 0) => {
+            Some(attr_class_lazy!("coap.code.internalServerError", typ: "@novalue", value: true))
+        }
+        (5, 1) => Some(attr_class_lazy!("coap.code.notImplemented", typ: "@novalue", value: true)),
+        (5, 2) => Some(attr_class_lazy!("coap.code.badGateway", typ: "@novalue", value: true)),
+        (5, 3) => {
+            Some(attr_class_lazy!("coap.code.serviceUnavailable", typ: "@novalue", value: true))
+        }
+        (5, 4) => Some(attr_class_lazy!("coap.code.gatewayTimeout", typ: "@novalue", value: true)),
+        (5, 5) => {
+            Some(attr_class_lazy!("coap.code.proxyingNotSupported", typ: "@novalue", value: true))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,114 @@
+//! Connection tracking shared by the decoders.
+
+use genet_sdk::{prelude::*, variant::Variant};
+use std::ops::Range;
+
+/// An address and a port.
+pub type Endpoint = (Vec<u8>, u64);
+
+/// Returns the value of the attribute `id` of the topmost layer having it.
+pub fn stack_value<T>(stack: &LayerStack, id: Token) -> Result<Option<T>>
+where
+    Variant: Value<T>,
+{
+    for layer in stack.layers().rev() {
+        if let Some(attr) = layer.attr(id) {
+            return Ok(Some(attr.try_get(layer)?.try_into()?));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the source and destination endpoints of the frame, given the
+/// port attributes of the transport.
+pub fn endpoints(stack: &LayerStack, src: Token, dst: Token) -> Result<(Endpoint, Endpoint)> {
+    let saddr: Vec<u8> = stack_value(stack, token!("_.src"))?.unwrap_or_default();
+    let daddr: Vec<u8> = stack_value(stack, token!("_.dst"))?.unwrap_or_default();
+    let sport: u64 = stack_value(stack, src)?.unwrap_or(0);
+    let dport: u64 = stack_value(stack, dst)?.unwrap_or(0);
+    Ok(((saddr, sport), (daddr, dport)))
+}
+
+/// Returns the key shared by both directions of a connection, and the
+/// direction of the frame.
+#[cfg(any(feature = "mqtt", feature = "modbus"))]
+pub fn key(src: Endpoint, dst: Endpoint) -> ((Endpoint, Endpoint), usize) {
+    if src <= dst {
+        ((src, dst), 0)
+    } else {
+        ((dst, src), 1)
+    }
+}
+
+/// Returns the reassembled stream data of a TCP segment, or `None` if the
+/// parent is not a segment handled by the stream reassembler.
+#[cfg(any(feature = "mqtt", feature = "modbus"))]
+pub fn stream_data(parent: &Parent) -> Option<Vec<u8>> {
+    if parent.id() != token!("tcp") || parent.attr(token!("tcp.stream.payloads")).is_none() {
+        return None;
+    }
+    let data = parent
+        .payloads()
+        .iter()
+        .filter(|p| p.id() == token!("@stream:tcp"))
+        .fold(Vec::new(), |mut data, p| {
+            data.extend_from_slice(&p.data());
+            data
+        });
+    Some(data)
+}
+
+/// Returns true if the payload of the TCP segment is dispatched to `typ`.
+#[cfg(any(feature = "mqtt", feature = "modbus"))]
+pub fn is_typ(parent: &Parent, typ: Token) -> bool {
+    parent
+        .payloads()
+        .iter()
+        .any(|p| p.id() == token!("@data:tcp") && p.typ() == typ)
+}
+
+/// Returns true if the TCP segment closes its direction (FIN or RST).
+#[cfg(any(feature = "mqtt", feature = "modbus"))]
+pub fn is_closing(stack: &LayerStack) -> Result<bool> {
+    let flags: u64 = stack_value(stack, token!("tcp.flags"))?.unwrap_or(0);
+    Ok(flags & 0x5 != 0)
+}
+
+/// Reads big-endian fields with bounds checking.
+pub struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(data: &'a [u8], pos: usize) -> Cursor<'a> {
+        Cursor { data, pos }
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    /// Skips `len` bytes and returns their range.
+    pub fn skip(&mut self, len: usize) -> Option<Range<usize>> {
+        if self.remaining() < len {
+            return None;
+        }
+        let range = self.pos..self.pos + len;
+        self.pos += len;
+        Some(range)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.skip(1).map(|r| self.data[r.start])
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.skip(2)
+            .map(|r| u16::from(self.data[r.start]) << 8 | u16::from(self.data[r.start + 1]))
+    }
+}
@@ -0,0 +1,29 @@
+extern crate genet_sdk;
+
+#[cfg(any(feature = "mqtt", feature = "coap", feature = "modbus"))]
+mod flow;
+
+#[cfg(feature = "coap")]
+mod coap;
+#[cfg(feature = "modbus")]
+mod modbus;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+
+#[cfg(feature = "coap")]
+use coap::CoapDecoder;
+#[cfg(feature = "modbus")]
+use modbus::ModbusDecoder;
+#[cfg(feature = "mqtt")]
+use mqtt::MqttDecoder;
+
+use genet_sdk::prelude::*;
+
+genet_decoders!(
+    #[cfg(feature = "mqtt")]
+    MqttDecoder {},
+    #[cfg(feature = "coap")]
+    CoapDecoder {},
+    #[cfg(feature = "modbus")]
+    ModbusDecoder {}
+);
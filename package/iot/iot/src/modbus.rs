@@ -0,0 +1,440 @@
+//! Modbus/TCP decoder.
+
+use flow::{self, Cursor, Endpoint};
+use genet_sdk::{cast, decoder::*, prelude::*};
+use std::{collections::HashMap, mem};
+
+/// The port of Modbus/TCP servers.
+const PORT: u64 = 502;
+
+/// The length of the MBAP header and the function code.
+const HEADER_LEN: usize = 8;
+
+/// The offset of the length, which counts the bytes following it.
+const LEN_OFFSET: usize = 6;
+
+/// A request waiting for a response.
+struct Request {
+    function: u8,
+    /// The first register or coil read by the request.
+    address: Option<u16>,
+}
+
+/// The state of a connection.
+#[derive(Default)]
+struct Flow {
+    buffers: [Vec<u8>; 2],
+    closed: [bool; 2],
+    /// The requests by the transaction identifier.
+    requests: HashMap<u16, Request>,
+}
+
+struct ModbusWorker {
+    flows: HashMap<(Endpoint, Endpoint), Flow>,
+}
+
+impl Worker for ModbusWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data = match flow::stream_data(parent) {
+            Some(data) => data,
+            None => return Ok(Status::Skip),
+        };
+
+        let (src, dst) = flow::endpoints(stack, token!("tcp.src"), token!("tcp.dst"))?;
+        let request = src.1 != PORT;
+        let (key, dir) = flow::key(src, dst);
+        if !flow::is_typ(parent, token!("@data:modbus")) && !self.flows.contains_key(&key) {
+            return Ok(Status::Skip);
+        }
+
+        let mut layers = Vec::new();
+        let flow = self.flows.entry(key.clone()).or_default();
+        flow.buffers[dir].extend_from_slice(&data);
+        while flow.buffers[dir].len() >= HEADER_LEN {
+            let buf = &flow.buffers[dir];
+            let protocol = u16::from(buf[2]) << 8 | u16::from(buf[3]);
+            let len = usize::from(buf[4]) << 8 | usize::from(buf[5]);
+
+            // Not Modbus, or too short for the unit identifier and the function.
+            if protocol != 0 || len < 2 {
+                let data = ByteSlice::from(flow.buffers[dir].split_off(0));
+                let mut layer = Layer::new(&MODBUS_CLASS, data);
+                layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+                layers.push(layer);
+                break;
+            }
+            if buf.len() < LEN_OFFSET + len {
+                break;
+            }
+            let rest = flow.buffers[dir].split_off(LEN_OFFSET + len);
+            let adu = mem::replace(&mut flow.buffers[dir], rest);
+            layers.push(adu_layer(adu, request, &mut flow.requests));
+        }
+
+        if flow::is_closing(stack)? {
+            flow.closed[dir] = true;
+            if flow.closed[0] && flow.closed[1] {
+                self.flows.remove(&key);
+            }
+        }
+
+        for layer in layers {
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+fn adu_layer(adu: Vec<u8>, request: bool, requests: &mut HashMap<u16, Request>) -> Layer {
+    let mut layer = Layer::new(&MODBUS_CLASS, ByteSlice::from(adu));
+    let data = layer.data();
+    let transaction = u16::from(data[0]) << 8 | u16::from(data[1]);
+    let function = data[7];
+    if let Some(attr) = get_function(function & 0x7f) {
+        layer.add_attr(attr!(attr, range: 7..8));
+    }
+
+    let mut c = Cursor::new(&data, HEADER_LEN);
+    let complete = if function & 0x80 != 0 {
+        requests.remove(&transaction);
+        layer.add_attr(attr!(&RESPONSE_ATTR, range: 7..8));
+        exception(&mut layer, &mut c)
+    } else if request {
+        layer.add_attr(attr!(&REQUEST_ATTR, range: 7..8));
+        let fields = request_fields(&mut layer, &mut c, function);
+        let address = fields.and_then(|address| address);
+        requests.insert(transaction, Request { function, address });
+        fields.map(|_| ())
+    } else {
+        layer.add_attr(attr!(&RESPONSE_ATTR, range: 7..8));
+        let address = requests
+            .remove(&transaction)
+            .filter(|r| r.function == function)
+            .and_then(|r| r.address);
+        response_fields(&mut layer, &mut c, function, address)
+    };
+    if complete.is_none() {
+        layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+    }
+    layer
+}
+
+fn exception(layer: &mut Layer, c: &mut Cursor) -> Option<()> {
+    let range = c.skip(1)?;
+    let code = layer.data()[range.start];
+    layer.add_attr(attr!(&EXCEPTION_ATTR, range: range.clone()));
+    if let Some(attr) = get_exception(code) {
+        layer.add_attr(attr!(attr, range: range));
+    }
+    Some(())
+}
+
+/// Adds the fields of a request, and returns the first address read by it,
+/// or `None` if the request is truncated.
+fn request_fields(layer: &mut Layer, c: &mut Cursor, function: u8) -> Option<Option<u16>> {
+    match function {
+        1..=4 => {
+            let address = field(layer, c, &ADDRESS_ATTR)?;
+            field(layer, c, &QUANTITY_ATTR)?;
+            Some(Some(address))
+        }
+        5 => {
+            field(layer, c, &ADDRESS_ATTR)?;
+            field(layer, c, &VALUE_ATTR)?;
+            Some(None)
+        }
+        6 => {
+            let address = field(layer, c, &ADDRESS_ATTR)?;
+            registers(layer, c, 2, Some(address))?;
+            Some(None)
+        }
+        15 => {
+            field(layer, c, &ADDRESS_ATTR)?;
+            field(layer, c, &QUANTITY_ATTR)?;
+            let len = byte_count(layer, c)?;
+            coils(layer, c, len)?;
+            Some(None)
+        }
+        16 => {
+            let address = field(layer, c, &ADDRESS_ATTR)?;
+            field(layer, c, &QUANTITY_ATTR)?;
+            let len = byte_count(layer, c)?;
+            registers(layer, c, len, Some(address))?;
+            Some(None)
+        }
+        23 => {
+            let read = field(layer, c, &READ_ADDRESS_ATTR)?;
+            field(layer, c, &READ_QUANTITY_ATTR)?;
+            let address = field(layer, c, &ADDRESS_ATTR)?;
+            field(layer, c, &QUANTITY_ATTR)?;
+            let len = byte_count(layer, c)?;
+            registers(layer, c, len, Some(address))?;
+            Some(Some(read))
+        }
+        _ => {
+            rest(layer, c);
+            Some(None)
+        }
+    }
+}
+
+fn response_fields(
+    layer: &mut Layer,
+    c: &mut Cursor,
+    function: u8,
+    address: Option<u16>,
+) -> Option<()> {
+    match function {
+        1 | 2 => {
+            let len = byte_count(layer, c)?;
+            coils(layer, c, len)
+        }
+        3 | 4 | 23 => {
+            let len = byte_count(layer, c)?;
+            registers(layer, c, len, address)
+        }
+        5 => {
+            field(layer, c, &ADDRESS_ATTR)?;
+            field(layer, c, &VALUE_ATTR).map(|_| ())
+        }
+        6 => {
+            let address = field(layer, c, &ADDRESS_ATTR)?;
+            registers(layer, c, 2, Some(address))
+        }
+        15 | 16 => {
+            field(layer, c, &ADDRESS_ATTR)?;
+            field(layer, c, &QUANTITY_ATTR).map(|_| ())
+        }
+        _ => {
+            rest(layer, c);
+            Some(())
+        }
+    }
+}
+
+/// Adds a 16-bit field.
+fn field(layer: &mut Layer, c: &mut Cursor, class: &'static AttrClass) -> Option<u16> {
+    let start = c.pos();
+    let value = c.u16()?;
+    layer.add_attr(attr!(class, range: start..start + 2));
+    Some(value)
+}
+
+fn byte_count(layer: &mut Layer, c: &mut Cursor) -> Option<usize> {
+    let start = c.pos();
+    let len = c.u8()?;
+    layer.add_attr(attr!(&BYTE_COUNT_ATTR, range: start..start + 1));
+    Some(usize::from(len))
+}
+
+fn coils(layer: &mut Layer, c: &mut Cursor, len: usize) -> Option<()> {
+    let range = c.skip(len)?;
+    layer.add_attr(attr!(&COILS_ATTR, range: range));
+    Some(())
+}
+
+/// Adds the registers in the next `len` bytes, numbered from `address` if
+/// known.
+fn registers(layer: &mut Layer, c: &mut Cursor, len: usize, address: Option<u16>) -> Option<()> {
+    let range = c.skip(len)?;
+    let end = range.end;
+    for (i, offset) in range.step_by(2).enumerate() {
+        if offset + 2 > end {
+            break;
+        }
+        let range = offset..offset + 2;
+        layer.add_attr(attr!(&REGISTER_ATTR, range: range.clone()));
+        if let Some(address) = address {
+            let address = u64::from(address) + i as u64;
+            layer.add_attr(attr!(&REGISTER_ADDRESS_ATTR, range: range.clone(), value: address));
+        }
+        layer.add_attr(attr!(&REGISTER_VALUE_ATTR, range: range));
+    }
+    Some(())
+}
+
+fn rest(layer: &mut Layer, c: &mut Cursor) {
+    let len = c.remaining();
+    if let Some(range) = c.skip(len).filter(|r| !r.is_empty()) {
+        layer.add_attr(attr!(&DATA_ATTR, range: range));
+    }
+}
+
+#[derive(Clone)]
+pub struct ModbusDecoder {}
+
+impl Decoder for ModbusDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        ctx.dissector_table("tcp.port")
+            .add_default(PORT, "@data:modbus");
+        Box::new(ModbusWorker {
+            flows: HashMap::new(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::SerialSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(MODBUS_CLASS, "modbus",
+    header: attr!(&TRANSACTION_ID_ATTR, range: 0..2),
+    header: attr!(&PROTOCOL_ID_ATTR, range: 2..4),
+    header: attr!(&LENGTH_ATTR, range: 4..6),
+    header: attr!(&UNIT_ID_ATTR, range: 6..7),
+    header: attr!(&FUNCTION_ATTR, range: 7..8)
+);
+
+def_attr_class!(TRANSACTION_ID_ATTR, "modbus.transactionId", cast: cast::UInt16BE());
+
+def_attr_class!(PROTOCOL_ID_ATTR, "modbus.protocolId", cast: cast::UInt16BE());
+
+def_attr_class!(LENGTH_ATTR, "modbus.length", cast: cast::UInt16BE());
+
+def_attr_class!(UNIT_ID_ATTR, "modbus.unitId", cast: cast::UInt8());
+
+def_attr_class!(FUNCTION_ATTR, "modbus.function",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| v & 0x7f)
+);
+
+def_attr_class!(REQUEST_ATTR, "modbus.request",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(RESPONSE_ATTR, "modbus.response",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(EXCEPTION_ATTR, "modbus.exception",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(ADDRESS_ATTR, "modbus.address", cast: cast::UInt16BE());
+
+def_attr_class!(QUANTITY_ATTR, "modbus.quantity", cast: cast::UInt16BE());
+
+def_attr_class!(READ_ADDRESS_ATTR, "modbus.readAddress", cast: cast::UInt16BE());
+
+def_attr_class!(READ_QUANTITY_ATTR, "modbus.readQuantity", cast: cast::UInt16BE());
+
+def_attr_class!(VALUE_ATTR, "modbus.value", cast: cast::UInt16BE());
+
+def_attr_class!(BYTE_COUNT_ATTR, "modbus.byteCount", cast: cast::UInt8());
+
+def_attr_class!(COILS_ATTR, "modbus.coils", cast: cast::ByteSlice());
+
+def_attr_class!(REGISTER_ATTR, "modbus.register",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(REGISTER_ADDRESS_ATTR, "modbus.register.address");
+
+def_attr_class!(REGISTER_VALUE_ATTR, "modbus.register.value", cast: cast::UInt16BE());
+
+def_attr_class!(DATA_ATTR, "modbus.data", cast: cast::ByteSlice());
+
+def_attr_class!(MALFORMED_ATTR, "modbus.malformed",
+    typ: "@expert:error",
+    description: "Malformed Modbus/TCP message"
+);
+
+fn get_function(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        1 => Some(attr_class_lazy!("modbus.function.readCoils", typ: "@novalue", value: true)),
+        2 => Some(
+            attr_class_lazy!("modbus.function.readDiscreteInputs", typ: "@novalue", value: true),
+        ),
+        3 => Some(
+            attr_class_lazy!("modbus.function.readHoldingRegisters", typ: "@novalue", value: true),
+        ),
+        4 => Some(
+            attr_class_lazy!("modbus.function.readInputRegisters", typ: "@novalue", value: true),
+        ),
+        5 => {
+            Some(attr_class_lazy!("modbus.function.writeSingleCoil", typ: "@novalue", value: true))
+        }
+        6 => Some(
+            attr_class_lazy!("modbus.function.writeSingleRegister", typ: "@novalue", value: true),
+        ),
+        7 => Some(
+            attr_class_lazy!("modbus.function.readExceptionStatus", typ: "@novalue", value: true),
+        ),
+        8 => Some(attr_class_lazy!("modbus.function.diagnostics", typ: "@novalue", value: true)),
+        11 => Some(
+            attr_class_lazy!("modbus.function.getCommEventCounter", typ: "@novalue", value: true),
+        ),
+        12 => {
+            Some(attr_class_lazy!("modbus.function.getCommEventLog", typ: "@novalue", value: true))
+        }
+        15 => Some(
+            attr_class_lazy!("modbus.function.writeMultipleCoils", typ: "@novalue", value: true),
+        ),
+        16 => Some(
+            attr_class_lazy!("modbus.function.writeMultipleRegisters", typ: "@novalue", value: true),
+        ),
+        17 => {
+            Some(attr_class_lazy!("modbus.function.reportServerId", typ: "@novalue", value: true))
+        }
+        20 => {
+            Some(attr_class_lazy!("modbus.function.readFileRecord", typ: "@novalue", value: true))
+        }
+        21 => {
+            Some(attr_class_lazy!("modbus.function.writeFileRecord", typ: "@novalue", value: true))
+        }
+        22 => Some(
+            attr_class_lazy!("modbus.function.maskWriteRegister", typ: "@novalue", value: true),
+        ),
+        23 => Some(
+            attr_class_lazy!("modbus.function.readWriteMultipleRegisters", typ: "@novalue", value: true),
+        ),
+        24 => Some(attr_class_lazy!("modbus.function.readFifoQueue", typ: "@novalue", value: true)),
+        43 => Some(
+            attr_class_lazy!("modbus.function.encapsulatedInterface", typ: "@novalue", value: true),
+        ),
+        _ => None,
+    }
+}
+
+fn get_exception(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        1 => {
+            Some(attr_class_lazy!("modbus.exception.illegalFunction", typ: "@novalue", value: true))
+        }
+        2 => Some(
+            attr_class_lazy!("modbus.exception.illegalDataAddress", typ: "@novalue", value: true),
+        ),
+        3 => Some(
+            attr_class_lazy!("modbus.exception.illegalDataValue", typ: "@novalue", value: true),
+        ),
+        4 => Some(
+            attr_class_lazy!("modbus.exception.serverDeviceFailure", typ: "@novalue", value: true),
+        ),
+        5 => Some(attr_class_lazy!("modbus.exception.acknowledge", typ: "@novalue", value: true)),
+        6 => Some(
+            attr_class_lazy!("modbus.exception.serverDeviceBusy", typ: "@novalue", value: true),
+        ),
+        8 => Some(
+            attr_class_lazy!("modbus.exception.memoryParityError", typ: "@novalue", value: true),
+        ),
+        10 => Some(
+            attr_class_lazy!("modbus.exception.gatewayPathUnavailable", typ: "@novalue", value: true),
+        ),
+        11 => Some(
+            attr_class_lazy!("modbus.exception.gatewayTargetFailed", typ: "@novalue", value: true),
+        ),
+        _ => None,
+    }
+}
@@ -0,0 +1,550 @@
+//! MQTT decoder.
+//!
+//! The subscriptions of a connection are tracked from the acknowledged
+//! SUBSCRIBE and UNSUBSCRIBE packets, and kept by the client identifier when
+//! the connection is closed so that a client resuming its session with the
+//! clean session flag unset gets them back. The messages published by the
+//! server have an `mqtt.subscription` attribute for each matching topic
+//! filter.
+
+use flow::{self, Cursor, Endpoint};
+use genet_sdk::{cast, decoder::*, prelude::*};
+use std::{collections::HashMap, mem};
+
+/// The port of MQTT servers.
+const PORT: u64 = 1883;
+
+/// The protocol level of MQTT 5.0.
+const LEVEL_V5: u8 = 5;
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const PUBCOMP: u8 = 7;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const UNSUBSCRIBE: u8 = 10;
+const UNSUBACK: u8 = 11;
+const DISCONNECT: u8 = 14;
+const AUTH: u8 = 15;
+
+const FLAG_USERNAME: u8 = 0b1000_0000;
+const FLAG_PASSWORD: u8 = 0b0100_0000;
+const FLAG_WILL: u8 = 0b0000_0100;
+const FLAG_CLEAN_SESSION: u8 = 0b0000_0010;
+
+/// The reason codes from 0x80 are failures.
+const REASON_FAILURE: u8 = 0x80;
+
+/// The state of a connection.
+#[derive(Default)]
+struct Flow {
+    buffers: [Vec<u8>; 2],
+    closed: [bool; 2],
+    /// The protocol level of the CONNECT packet.
+    level: u8,
+    client: Option<String>,
+    /// The topic filters subscribed by the client.
+    subscriptions: Vec<String>,
+    /// The topic filters of the SUBSCRIBE and UNSUBSCRIBE packets waiting for
+    /// an acknowledgment, by the packet identifier.
+    pending: HashMap<u16, Vec<String>>,
+}
+
+struct MqttWorker {
+    flows: HashMap<(Endpoint, Endpoint), Flow>,
+    /// The subscriptions of the closed connections by the client identifier.
+    sessions: HashMap<String, Vec<String>>,
+}
+
+impl Worker for MqttWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data = match flow::stream_data(parent) {
+            Some(data) => data,
+            None => return Ok(Status::Skip),
+        };
+
+        let (src, dst) = flow::endpoints(stack, token!("tcp.src"), token!("tcp.dst"))?;
+        let from_server = src.1 == PORT;
+        let (key, dir) = flow::key(src, dst);
+        if !flow::is_typ(parent, token!("@data:mqtt")) && !self.flows.contains_key(&key) {
+            return Ok(Status::Skip);
+        }
+
+        let mut layers = Vec::new();
+        let flow = self.flows.entry(key.clone()).or_default();
+        flow.buffers[dir].extend_from_slice(&data);
+        while !flow.buffers[dir].is_empty() {
+            match framing(&flow.buffers[dir]) {
+                Framing::Complete(len) => {
+                    let rest = flow.buffers[dir].split_off(len);
+                    let packet = mem::replace(&mut flow.buffers[dir], rest);
+                    let mut dissector = Dissector {
+                        flow: &mut *flow,
+                        sessions: &mut self.sessions,
+                        from_server,
+                    };
+                    layers.push(dissector.packet_layer(packet));
+                }
+                Framing::Incomplete => break,
+                Framing::Malformed => {
+                    let data = ByteSlice::from(flow.buffers[dir].split_off(0));
+                    let mut layer = Layer::new(&MQTT_CLASS, data);
+                    layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+                    layers.push(layer);
+                }
+            }
+        }
+
+        if flow::is_closing(stack)? {
+            flow.closed[dir] = true;
+            if flow.closed[0] && flow.closed[1] {
+                let mut flow = self.flows.remove(&key).unwrap();
+                if let Some(client) = flow.client.take() {
+                    self.sessions
+                        .insert(client, mem::take(&mut flow.subscriptions));
+                }
+            }
+        }
+
+        for layer in layers {
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+/// The first packet of a buffer.
+enum Framing {
+    /// The length of the complete packet.
+    Complete(usize),
+    Incomplete,
+    /// The remaining length exceeds four bytes.
+    Malformed,
+}
+
+fn framing(buf: &[u8]) -> Framing {
+    let mut c = Cursor::new(buf, 1);
+    match varint(&mut c) {
+        Some(Some(len)) if c.remaining() >= len as usize => {
+            Framing::Complete(c.pos() + len as usize)
+        }
+        Some(None) => Framing::Malformed,
+        _ => Framing::Incomplete,
+    }
+}
+
+/// Reads a variable byte integer, which is `Some(None)` if it exceeds four
+/// bytes.
+fn varint(c: &mut Cursor) -> Option<Option<u32>> {
+    let mut value = 0u32;
+    for i in 0..4 {
+        let b = c.u8()?;
+        value |= u32::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some(Some(value));
+        }
+    }
+    Some(None)
+}
+
+/// Returns true if the topic name matches the topic filter.
+fn matches(filter: &str, topic: &str) -> bool {
+    // A shared subscription is $share/{ShareName}/{filter}.
+    let filter = if filter.starts_with("$share/") {
+        match filter.splitn(3, '/').nth(2) {
+            Some(filter) => filter,
+            None => return false,
+        }
+    } else {
+        filter
+    };
+
+    // The wildcards at the first level do not match the topics beginning
+    // with a $ character.
+    if topic.starts_with('$') && (filter.starts_with('+') || filter.starts_with('#')) {
+        return false;
+    }
+
+    let mut filter = filter.split('/');
+    let mut topic = topic.split('/');
+    loop {
+        match (filter.next(), topic.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(f), Some(t)) if f == t => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Dissects the packets of a connection.
+struct Dissector<'a> {
+    flow: &'a mut Flow,
+    sessions: &'a mut HashMap<String, Vec<String>>,
+    from_server: bool,
+}
+
+impl<'a> Dissector<'a> {
+    fn packet_layer(&mut self, packet: Vec<u8>) -> Layer {
+        let mut layer = Layer::new(&MQTT_CLASS, ByteSlice::from(packet));
+        let data = layer.data();
+        let typ = data[0] >> 4;
+        if let Some(attr) = get_type(typ) {
+            layer.add_attr(attr!(attr, range: 0..1));
+        }
+
+        let mut c = Cursor::new(&data, 1);
+        if let Some(Some(len)) = varint(&mut c) {
+            layer.add_attr(attr!(&LENGTH_ATTR, range: 1..c.pos(), value: u64::from(len)));
+        }
+
+        let complete = match typ {
+            CONNECT => self.connect(&mut layer, &mut c),
+            CONNACK => self.connack(&mut layer, &mut c),
+            PUBLISH => self.publish(&mut layer, &mut c),
+            PUBACK..=PUBCOMP => self.ack(&mut layer, &mut c),
+            SUBSCRIBE | UNSUBSCRIBE => self.subscribe(&mut layer, &mut c, typ),
+            SUBACK | UNSUBACK => self.suback(&mut layer, &mut c, typ),
+            DISCONNECT | AUTH => self.reason(&mut layer, &mut c),
+            _ => Some(()),
+        };
+        if complete.is_none() {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+        }
+        layer
+    }
+
+    fn v5(&self) -> bool {
+        self.flow.level == LEVEL_V5
+    }
+
+    fn connect(&mut self, layer: &mut Layer, c: &mut Cursor) -> Option<()> {
+        string(layer, c, &PROTOCOL_NAME_ATTR)?;
+        let level = c.u8()?;
+        layer.add_attr(attr!(&PROTOCOL_LEVEL_ATTR, range: c.pos() - 1..c.pos()));
+        self.flow.level = level;
+
+        let flags = c.u8()?;
+        let range = c.pos() - 1..c.pos();
+        layer.add_attr(attr!(&CONNECT_FLAGS_ATTR, range: range.clone()));
+        layer.add_attr(attr!(&CONNECT_FLAGS_USERNAME_ATTR, range: range.clone()));
+        layer.add_attr(attr!(&CONNECT_FLAGS_PASSWORD_ATTR, range: range.clone()));
+        layer.add_attr(attr!(&CONNECT_FLAGS_WILL_RETAIN_ATTR, range: range.clone()));
+        layer.add_attr(attr!(&CONNECT_FLAGS_WILL_QOS_ATTR, range: range.clone()));
+        layer.add_attr(attr!(&CONNECT_FLAGS_WILL_ATTR, range: range.clone()));
+        layer.add_attr(attr!(&CONNECT_FLAGS_CLEAN_SESSION_ATTR, range: range));
+        field(layer, c, &KEEP_ALIVE_ATTR)?;
+        self.properties(layer, c)?;
+
+        let client = string(layer, c, &CLIENT_ID_ATTR)?;
+        if flags & FLAG_WILL != 0 {
+            self.properties(layer, c)?;
+            string(layer, c, &WILL_TOPIC_ATTR)?;
+            binary(layer, c, &WILL_MESSAGE_ATTR)?;
+        }
+        if flags & FLAG_USERNAME != 0 {
+            string(layer, c, &USERNAME_ATTR)?;
+        }
+        if flags & FLAG_PASSWORD != 0 {
+            binary(layer, c, &PASSWORD_ATTR)?;
+        }
+
+        // A clean session discards the subscriptions of the previous one.
+        let previous = self.sessions.remove(&client);
+        self.flow.subscriptions = if flags & FLAG_CLEAN_SESSION != 0 {
+            Vec::new()
+        } else {
+            previous.unwrap_or_default()
+        };
+        self.flow.client = Some(client);
+        Some(())
+    }
+
+    fn connack(&mut self, layer: &mut Layer, c: &mut Cursor) -> Option<()> {
+        c.u8()?;
+        layer.add_attr(attr!(&SESSION_PRESENT_ATTR, range: c.pos() - 1..c.pos()));
+        c.u8()?;
+        layer.add_attr(attr!(&REASON_CODE_ATTR, range: c.pos() - 1..c.pos()));
+        self.properties(layer, c)
+    }
+
+    fn publish(&mut self, layer: &mut Layer, c: &mut Cursor) -> Option<()> {
+        layer.add_attr(attr!(&PUBLISH_DUP_ATTR, range: 0..1));
+        layer.add_attr(attr!(&PUBLISH_QOS_ATTR, range: 0..1));
+        layer.add_attr(attr!(&PUBLISH_RETAIN_ATTR, range: 0..1));
+        let qos = (layer.data()[0] >> 1) & 0b11;
+
+        let start = c.pos() + 2;
+        let topic = string(layer, c, &TOPIC_ATTR)?;
+        if self.from_server {
+            for filter in self
+                .flow
+                .subscriptions
+                .iter()
+                .filter(|f| matches(f, &topic))
+            {
+                let value = filter.clone().into_boxed_str();
+                layer.add_attr(attr!(&SUBSCRIPTION_ATTR, range: start..c.pos(), value: value));
+            }
+        }
+        if qos > 0 {
+            field(layer, c, &PACKET_ID_ATTR)?;
+        }
+        self.properties(layer, c)?;
+        let len = c.remaining();
+        let range = c.skip(len)?;
+        layer.add_attr(attr!(&PAYLOAD_ATTR, range: range));
+        Some(())
+    }
+
+    /// PUBACK, PUBREC, PUBREL and PUBCOMP.
+    fn ack(&mut self, layer: &mut Layer, c: &mut Cursor) -> Option<()> {
+        field(layer, c, &PACKET_ID_ATTR)?;
+        self.reason(layer, c)
+    }
+
+    /// Adds the optional reason code and properties of MQTT 5.0.
+    fn reason(&mut self, layer: &mut Layer, c: &mut Cursor) -> Option<()> {
+        if c.remaining() > 0 {
+            c.u8()?;
+            layer.add_attr(attr!(&REASON_CODE_ATTR, range: c.pos() - 1..c.pos()));
+        }
+        if c.remaining() > 0 {
+            self.properties(layer, c)?;
+        }
+        Some(())
+    }
+
+    fn subscribe(&mut self, layer: &mut Layer, c: &mut Cursor, typ: u8) -> Option<()> {
+        let id = field(layer, c, &PACKET_ID_ATTR)?;
+        self.properties(layer, c)?;
+        let mut filters = Vec::new();
+        while c.remaining() > 0 {
+            filters.push(string(layer, c, &TOPIC_FILTER_ATTR)?);
+            if typ == SUBSCRIBE {
+                c.u8()?;
+                layer.add_attr(attr!(&REQUESTED_QOS_ATTR, range: c.pos() - 1..c.pos()));
+            }
+        }
+        self.flow.pending.insert(id, filters);
+        Some(())
+    }
+
+    fn suback(&mut self, layer: &mut Layer, c: &mut Cursor, typ: u8) -> Option<()> {
+        let id = field(layer, c, &PACKET_ID_ATTR)?;
+        self.properties(layer, c)?;
+        let filters = self.flow.pending.remove(&id).unwrap_or_default();
+
+        // An UNSUBACK packet of MQTT 3.1.1 has no reason codes.
+        if typ == UNSUBACK && !self.v5() {
+            let subscriptions = &mut self.flow.subscriptions;
+            subscriptions.retain(|s| !filters.contains(s));
+            return Some(());
+        }
+
+        let mut filters = filters.into_iter();
+        while c.remaining() > 0 {
+            let code = c.u8()?;
+            let range = c.pos() - 1..c.pos();
+            layer.add_attr(attr!(&REASON_CODE_ATTR, range: range.clone()));
+            let filter = match filters.next() {
+                Some(filter) => filter,
+                None => continue,
+            };
+            layer.add_attr(
+                attr!(&TOPIC_FILTER_ATTR, range: range, value: filter.clone().into_boxed_str()),
+            );
+            if code >= REASON_FAILURE {
+                continue;
+            }
+            let subscriptions = &mut self.flow.subscriptions;
+            subscriptions.retain(|s| *s != filter);
+            if typ == SUBACK {
+                subscriptions.push(filter);
+            }
+        }
+        Some(())
+    }
+
+    /// Adds the properties of MQTT 5.0, which are not dissected.
+    fn properties(&mut self, layer: &mut Layer, c: &mut Cursor) -> Option<()> {
+        if !self.v5() {
+            return Some(());
+        }
+        let len = varint(c)??;
+        let range = c.skip(len as usize)?;
+        if !range.is_empty() {
+            layer.add_attr(attr!(&PROPERTIES_ATTR, range: range));
+        }
+        Some(())
+    }
+}
+
+/// Adds a 16-bit field.
+fn field(layer: &mut Layer, c: &mut Cursor, class: &'static AttrClass) -> Option<u16> {
+    let value = c.u16()?;
+    layer.add_attr(attr!(class, range: c.pos() - 2..c.pos()));
+    Some(value)
+}
+
+/// Adds a UTF-8 string prefixed by its length.
+fn string(layer: &mut Layer, c: &mut Cursor, class: &'static AttrClass) -> Option<String> {
+    let len = c.u16()?;
+    let range = c.skip(usize::from(len))?;
+    let value = String::from_utf8_lossy(&layer.data()[range.clone()]).into_owned();
+    layer.add_attr(attr!(class, range: range, value: value.clone().into_boxed_str()));
+    Some(value)
+}
+
+/// Adds binary data prefixed by its length.
+fn binary(layer: &mut Layer, c: &mut Cursor, class: &'static AttrClass) -> Option<()> {
+    let len = c.u16()?;
+    let range = c.skip(usize::from(len))?;
+    layer.add_attr(attr!(class, range: range));
+    Some(())
+}
+
+#[derive(Clone)]
+pub struct MqttDecoder {}
+
+impl Decoder for MqttDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        ctx.dissector_table("tcp.port")
+            .add_default(PORT, "@data:mqtt");
+        Box::new(MqttWorker {
+            flows: HashMap::new(),
+            sessions: HashMap::new(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::SerialSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(MQTT_CLASS, "mqtt",
+    header: attr!(&TYPE_ATTR, range: 0..1)
+);
+
+def_attr_class!(TYPE_ATTR, "mqtt.type",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| v >> 4)
+);
+
+def_attr_class!(LENGTH_ATTR, "mqtt.length");
+
+def_attr_class!(PUBLISH_DUP_ATTR, "mqtt.dup",
+    cast: cast::UInt8().map(|v| (v & 0b1000) != 0)
+);
+
+def_attr_class!(PUBLISH_QOS_ATTR, "mqtt.qos",
+    cast: cast::UInt8().map(|v| (v >> 1) & 0b11)
+);
+
+def_attr_class!(PUBLISH_RETAIN_ATTR, "mqtt.retain",
+    cast: cast::UInt8().map(|v| (v & 0b0001) != 0)
+);
+
+def_attr_class!(PROTOCOL_NAME_ATTR, "mqtt.protocolName");
+
+def_attr_class!(PROTOCOL_LEVEL_ATTR, "mqtt.protocolLevel", cast: cast::UInt8());
+
+def_attr_class!(CONNECT_FLAGS_ATTR, "mqtt.connectFlags",
+    typ: "@flags",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(CONNECT_FLAGS_USERNAME_ATTR, "mqtt.connectFlags.username",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(CONNECT_FLAGS_PASSWORD_ATTR, "mqtt.connectFlags.password",
+    cast: cast::UInt8().map(|v| (v & 0b0100_0000) != 0)
+);
+
+def_attr_class!(CONNECT_FLAGS_WILL_RETAIN_ATTR, "mqtt.connectFlags.willRetain",
+    cast: cast::UInt8().map(|v| (v & 0b0010_0000) != 0)
+);
+
+def_attr_class!(CONNECT_FLAGS_WILL_QOS_ATTR, "mqtt.connectFlags.willQos",
+    cast: cast::UInt8().map(|v| (v >> 3) & 0b11)
+);
+
+def_attr_class!(CONNECT_FLAGS_WILL_ATTR, "mqtt.connectFlags.will",
+    cast: cast::UInt8().map(|v| (v & 0b0000_0100) != 0)
+);
+
+def_attr_class!(CONNECT_FLAGS_CLEAN_SESSION_ATTR, "mqtt.connectFlags.cleanSession",
+    cast: cast::UInt8().map(|v| (v & 0b0000_0010) != 0)
+);
+
+def_attr_class!(KEEP_ALIVE_ATTR, "mqtt.keepAlive", cast: cast::UInt16BE());
+
+def_attr_class!(CLIENT_ID_ATTR, "mqtt.clientId");
+
+def_attr_class!(WILL_TOPIC_ATTR, "mqtt.willTopic");
+
+def_attr_class!(WILL_MESSAGE_ATTR, "mqtt.willMessage", cast: cast::ByteSlice());
+
+def_attr_class!(USERNAME_ATTR, "mqtt.username");
+
+def_attr_class!(PASSWORD_ATTR, "mqtt.password", cast: cast::ByteSlice());
+
+def_attr_class!(SESSION_PRESENT_ATTR, "mqtt.sessionPresent",
+    cast: cast::UInt8().map(|v| (v & 1) != 0)
+);
+
+def_attr_class!(REASON_CODE_ATTR, "mqtt.reasonCode", cast: cast::UInt8());
+
+def_attr_class!(PACKET_ID_ATTR, "mqtt.packetId", cast: cast::UInt16BE());
+
+def_attr_class!(TOPIC_ATTR, "mqtt.topic");
+
+def_attr_class!(SUBSCRIPTION_ATTR, "mqtt.subscription");
+
+def_attr_class!(PAYLOAD_ATTR, "mqtt.payload", cast: cast::ByteSlice());
+
+def_attr_class!(TOPIC_FILTER_ATTR, "mqtt.topicFilter");
+
+def_attr_class!(REQUESTED_QOS_ATTR, "mqtt.requestedQos",
+    cast: cast::UInt8().map(|v| v & 0b11)
+);
+
+def_attr_class!(PROPERTIES_ATTR, "mqtt.properties", cast: cast::ByteSlice());
+
+def_attr_class!(MALFORMED_ATTR, "mqtt.malformed",
+    typ: "@expert:error",
+    description: "Malformed MQTT packet"
+);
+
+fn get_type(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        1 => Some(attr_class_lazy!("mqtt.type.connect", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("mqtt.type.connack", typ: "@novalue", value: true)),
+        3 => Some(attr_class_lazy!("mqtt.type.publish", typ: "@novalue", value: true)),
+        4 => Some(attr_class_lazy!("mqtt.type.puback", typ: "@novalue", value: true)),
+        5 => Some(attr_class_lazy!("mqtt.type.pubrec", typ: "@novalue", value: true)),
+        6 => Some(attr_class_lazy!("mqtt.type.pubrel", typ: "@novalue", value: true)),
+        7 => Some(attr_class_lazy!("mqtt.type.pubcomp", typ: "@novalue", value: true)),
+        8 => Some(attr_class_lazy!("mqtt.type.subscribe", typ: "@novalue", value: true)),
+        9 => Some(attr_class_lazy!("mqtt.type.suback", typ: "@novalue", value: true)),
+        10 => Some(attr_class_lazy!("mqtt.type.unsubscribe", typ: "@novalue", value: true)),
+        11 => Some(attr_class_lazy!("mqtt.type.unsuback", typ: "@novalue", value: true)),
+        12 => Some(attr_class_lazy!("mqtt.type.pingreq", typ: "@novalue", value: true)),
+        13 => Some(attr_class_lazy!("mqtt.type.pingresp", typ: "@novalue", value: true)),
+        14 => Some(attr_class_lazy!("mqtt.type.disconnect", typ: "@novalue", value: true)),
+        15 => Some(attr_class_lazy!("mqtt.type.auth", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
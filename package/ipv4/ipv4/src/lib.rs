@@ -1,8 +1,15 @@
 extern crate genet_sdk;
 
-use genet_sdk::{cast, decoder::*, prelude::*};
-
-struct IPv4Worker {}
+use genet_sdk::{
+    cast,
+    checksum::{self, Validator},
+    decoder::*,
+    prelude::*,
+};
+
+struct IPv4Worker {
+    validator: Validator,
+}
 
 impl Worker for IPv4Worker {
     fn decode(
@@ -25,9 +32,27 @@ impl Worker for IPv4Worker {
 
         let mut layer = Layer::new(&IPV4_CLASS, data);
         let proto = PROTO_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let hlen: usize = HLEN_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let start = hlen.max(5) * 4;
+        let status = match layer.data().get(0..start) {
+            Some(header) => self
+                .validator
+                .status(checksum::internet(header) == 0, parent.frame_metadata()),
+            None => checksum::Status::Unverified,
+        };
+        layer.add_attr(attr!(&CHECKSUM_STATUS_ATTR, range: 10..12, value: status.to_value()));
         if let Some((typ, attr)) = get_proto(proto) {
             layer.add_attr(attr!(attr, range: 9..10));
-            let payload = layer.data().try_get(20..)?;
+            // Offloaded (TSO/GSO) packets may exceed the 16-bit total length,
+            // which is then left zero or stale; the payload then extends to
+            // the end of the frame.
+            let len: usize = LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+            let end = if len < start || len > layer.data().len() || layer.data().len() > 0xffff {
+                layer.data().len()
+            } else {
+                len
+            };
+            let payload = layer.data().try_get(start..end)?;
             layer.add_payload(Payload::new(payload, typ));
         }
 
@@ -40,13 +65,16 @@ impl Worker for IPv4Worker {
 struct IPv4Decoder {}
 
 impl Decoder for IPv4Decoder {
-    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
-        Box::new(IPv4Worker {})
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        Box::new(IPv4Worker {
+            validator: Validator::from_config(ctx),
+        })
     }
 
     fn metadata(&self) -> Metadata {
         Metadata {
             exec_type: ExecType::ParallelSync,
+            options: vec![Validator::option()],
             ..Metadata::default()
         }
     }
@@ -56,9 +84,9 @@ def_layer_class!(IPV4_CLASS, "ipv4",
     alias: "_.src" "ipv4.src",
     alias: "_.dst" "ipv4.dst",
     header: attr!(&VERSION_ATTR, bit_range: 0 0..4),
-    header: attr!(&HLEN_ATTR, bit_range: 0 4..8),
+    header: &HLEN_ATTR_HEADER,
     header: attr!(&TOS_ATTR, range: 1..2),
-    header: attr!(&LENGTH_ATTR, range: 2..4),
+    header: &LENGTH_ATTR_HEADER,
     header: attr!(&ID_ATTR, range: 4..6),
     header: attr!(&FLAGS_ATTR, bit_range: 6 0..1),
     header: attr!(&FLAGS_RV_ATTR, bit_range: 6 1..2),
@@ -72,6 +100,10 @@ def_layer_class!(IPV4_CLASS, "ipv4",
     header: attr!(&DST_ATTR, range: 16..20)
 );
 
+def_attr!(HLEN_ATTR_HEADER,  &HLEN_ATTR, bit_range: 0 4..8);
+
+def_attr!(LENGTH_ATTR_HEADER,  &LENGTH_ATTR, range: 2..4);
+
 def_attr!(PROTO_ATTR_HEADER,  &PROTO_ATTR, range: 9..10);
 
 def_attr_class!(VERSION_ATTR, "ipv4.version",
@@ -118,6 +150,8 @@ def_attr_class!(PROTO_ATTR, "ipv4.protocol",
 
 def_attr_class!(CHECKSUM_ATTR, "ipv4.checksum", cast: cast::UInt16BE());
 
+def_attr_class!(CHECKSUM_STATUS_ATTR, "ipv4.checksum.status");
+
 def_attr_class!(SRC_ATTR, "ipv4.src",
     typ: "@ipv4:addr",
     cast: cast::ByteSlice()
@@ -138,6 +172,10 @@ fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
             token!("@data:igmp"),
             attr_class_lazy!("ipv4.protocol.igmp", typ: "@novalue", value: true),
         )),
+        0x04 => Some((
+            token!("@data:ipv4"),
+            attr_class_lazy!("ipv4.protocol.ipv4", typ: "@novalue", value: true),
+        )),
         0x06 => Some((
             token!("@data:tcp"),
             attr_class_lazy!("ipv4.protocol.tcp", typ: "@novalue", value: true),
@@ -146,8 +184,72 @@ fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
             token!("@data:udp"),
             attr_class_lazy!("ipv4.protocol.udp", typ: "@novalue", value: true),
         )),
+        0x29 => Some((
+            token!("@data:ipv6"),
+            attr_class_lazy!("ipv4.protocol.ipv6", typ: "@novalue", value: true),
+        )),
+        0x2f => Some((
+            token!("@data:gre"),
+            attr_class_lazy!("ipv4.protocol.gre", typ: "@novalue", value: true),
+        )),
         _ => None,
     }
 }
 
 genet_decoders!(IPv4Decoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{decoder::DecoderBox, prelude::*};
+    use IPv4Decoder;
+
+    fn packet(ihl: u8, total_len: u16, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        data[0] = 0x40 | ihl;
+        data[2] = (total_len >> 8) as u8;
+        data[3] = total_len as u8;
+        data[9] = 6;
+        data
+    }
+
+    /// Decodes `data` and returns the range of the payload.
+    fn payload(data: Vec<u8>) -> (usize, usize) {
+        let data = ByteSlice::from(data);
+        let mut root = Layer::new(Fixed::new(LayerClass::builder("[link-1]").build()), data);
+        root.add_payload(Payload::new(data, "@data:ipv4"));
+        let mut ctx = Context::new(Default::default());
+        let mut worker = DecoderBox::new(IPv4Decoder {}).new_worker(&ctx);
+        let mut parent = Parent::from_mut_ref(&mut root);
+        assert!(worker.decode(&mut ctx, &[], &mut parent).unwrap());
+        let layer = unsafe { &*parent.children()[0] };
+        let payload = layer.payloads()[0].data();
+        let start = payload.as_ptr() as usize - data.as_ptr() as usize;
+        (start, start + payload.len())
+    }
+
+    #[test]
+    fn oversized() {
+        // Zero total length of an offloaded packet.
+        assert_eq!(payload(packet(5, 0, 70_000)), (20, 70_000));
+        // Total length truncated to 16 bits.
+        assert_eq!(
+            payload(packet(5, (70_000 & 0xffff) as u16, 70_000)),
+            (20, 70_000)
+        );
+        // Total length of the first segment.
+        assert_eq!(payload(packet(5, 1500, 70_000)), (20, 70_000));
+        assert_eq!(payload(packet(6, 0, 70_000)), (24, 70_000));
+        assert_eq!(payload(packet(15, 0, 0x1_0000)), (60, 0x1_0000));
+    }
+
+    #[test]
+    fn total_length() {
+        // Padding after the packet.
+        assert_eq!(payload(packet(5, 40, 60)), (20, 40));
+        assert_eq!(payload(packet(5, 1500, 100)), (20, 100));
+        assert_eq!(payload(packet(5, 10, 100)), (20, 100));
+        assert_eq!(payload(packet(6, 22, 100)), (24, 100));
+        // A header length under the minimum is read as 5.
+        assert_eq!(payload(packet(2, 40, 60)), (20, 40));
+    }
+}
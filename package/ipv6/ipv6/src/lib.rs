@@ -1,8 +1,21 @@
 extern crate genet_sdk;
 
-use genet_sdk::{cast, decoder::*, prelude::*};
+use genet_sdk::{
+    cast,
+    decoder::*,
+    option::{self, OptionTable, Tlv},
+    prelude::*,
+};
 
-struct IPv6Worker {}
+/// Config key for the maximum number of extension headers in a packet.
+const MAX_HEADERS_KEY: &str = "@genet/ipv6.maxExtensionHeaders";
+
+const DEFAULT_MAX_HEADERS: usize = 8;
+
+struct IPv6Worker {
+    max_headers: usize,
+    options: OptionTable,
+}
 
 impl Worker for IPv6Worker {
     fn decode(
@@ -24,31 +37,30 @@ impl Worker for IPv6Worker {
         }
 
         let mut layer = Layer::new(&IPV6_CLASS, data);
-        let nheader = NHEADER_ATTR_HEADER.try_get(&layer)?.try_into()?;
 
-        loop {
-            match nheader {
-                // TODO:
-                // case 0 | 60 # Hop-by-Hop Options, Destination Options
-                // case 43  # Routing
-                // case 44  # Fragment
-                // case 51  # Authentication Header
-                // case 50  # Encapsulating Security Payload
-                // case 135 # Mobility
-                // No Next Header
-                59 => break,
-                _ => break,
-            }
-        }
+        // A zero payload length is used by jumbograms and by offloaded
+        // (TSO/GSO) packets exceeding 64 KiB, which may also leave it stale;
+        // the payload then extends to the end of the frame.
+        let len: usize = LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let end = match len {
+            0 => layer.data().len(),
+            _ if layer.data().len() > 40 + 0xffff => layer.data().len(),
+            len => (40 + len).min(layer.data().len()),
+        };
 
-        let range = NHEADER_ATTR_HEADER.range();
+        let chain = self.parse_chain(&mut layer, end);
+        let range = chain.nheader..chain.nheader + 1;
         let proto_attr = attr!(&PROTOCOL_ATTR, range: range.clone());
         let proto = proto_attr.try_get(&layer)?.try_into()?;
         layer.add_attr(proto_attr);
-        if let Some((typ, attr)) = get_proto(proto) {
-            layer.add_attr(attr!(attr, range: range.clone()));
-            let payload = layer.data().try_get(40..)?;
-            layer.add_payload(Payload::new(payload, typ));
+        if let Some(malformed) = chain.malformed {
+            layer.add_attr(attr!(malformed));
+        } else if chain.dispatch {
+            if let Some((typ, attr)) = get_proto(proto) {
+                layer.add_attr(attr!(attr, range: range.clone()));
+                let payload = layer.data().try_get(chain.offset..end)?;
+                layer.add_payload(Payload::new(payload, typ));
+            }
         }
 
         parent.add_child(layer);
@@ -56,17 +68,155 @@ impl Worker for IPv6Worker {
     }
 }
 
+/// The result of parsing an extension header chain.
+struct Chain {
+    /// The offset of the Next Header field of the upper-layer protocol.
+    nheader: usize,
+    /// The offset of the upper-layer payload.
+    offset: usize,
+    /// False if the payload is not an upper-layer header, e.g. ESP or a
+    /// non-first fragment.
+    dispatch: bool,
+    malformed: Option<&'static AttrClass>,
+}
+
+impl IPv6Worker {
+    fn parse_chain(&self, layer: &mut Layer, end: usize) -> Chain {
+        let data = layer.data();
+        let mut chain = Chain {
+            nheader: 6,
+            offset: 40,
+            dispatch: true,
+            malformed: None,
+        };
+        let mut count = 0;
+        while let Ok(nheader) = data.try_get(chain.nheader) {
+            if !is_extension(nheader) {
+                return chain;
+            }
+            if count >= self.max_headers {
+                chain.malformed = Some(&MALFORMED_LIMIT_ATTR);
+                return chain;
+            }
+            // Hop-by-Hop Options must immediately follow the IPv6 header.
+            if nheader == 0 && count > 0 {
+                chain.malformed = Some(&MALFORMED_ORDER_ATTR);
+                return chain;
+            }
+            let offset = chain.offset;
+            let len = match nheader {
+                // Encapsulating Security Payload
+                50 => {
+                    layer.add_attr(attr!(&ESP_ATTR, range: offset..end));
+                    layer.add_attr(attr!(&ESP_SPI_ATTR, range: offset..offset + 4));
+                    chain.dispatch = false;
+                    return chain;
+                }
+                // Fragment
+                44 => Ok(8),
+                // Authentication Header
+                51 => data.try_get(offset + 1).map(|len| (len as usize + 2) * 4),
+                _ => data.try_get(offset + 1).map(|len| (len as usize + 1) * 8),
+            };
+            let len = match len {
+                Ok(len) if offset + len <= end => len,
+                _ => {
+                    chain.malformed = Some(&MALFORMED_TRUNCATED_ATTR);
+                    return chain;
+                }
+            };
+            let range = offset..offset + len;
+            match nheader {
+                0 | 60 => {
+                    let class: &'static AttrClass = if nheader == 0 {
+                        &HOP_BY_HOP_ATTR
+                    } else {
+                        &DESTINATION_ATTR
+                    };
+                    layer.add_attr(attr!(class, range: range));
+                    // The header length is valid, so a malformed option only
+                    // hides the options following it.
+                    let _ = self.options.dissect(layer, offset + 2..offset + len);
+                }
+                43 => {
+                    layer.add_attr(attr!(&ROUTING_ATTR, range: range));
+                    layer.add_attr(attr!(&ROUTING_TYPE_ATTR, range: offset + 2..offset + 3));
+                    layer.add_attr(attr!(&ROUTING_LEFT_ATTR, range: offset + 3..offset + 4));
+                }
+                44 => {
+                    layer.add_attr(attr!(&FRAGMENT_ATTR, range: range));
+                    layer.add_attr(attr!(&FRAGMENT_OFFSET_ATTR, range: offset + 2..offset + 4));
+                    layer.add_attr(attr!(&FRAGMENT_MORE_ATTR, range: offset + 2..offset + 4));
+                    layer.add_attr(attr!(&FRAGMENT_ID_ATTR, range: offset + 4..offset + 8));
+                    // Only the first fragment has the upper-layer header.
+                    let frag_offset =
+                        (u16::from(data[offset + 2]) << 5) | u16::from(data[offset + 3] >> 3);
+                    if frag_offset != 0 {
+                        chain.dispatch = false;
+                    }
+                }
+                51 => {
+                    layer.add_attr(attr!(&AH_ATTR, range: range));
+                    layer.add_attr(attr!(&AH_SPI_ATTR, range: offset + 4..offset + 8));
+                }
+                _ => layer.add_attr(attr!(&MOBILITY_ATTR, range: range)),
+            }
+            chain.nheader = offset;
+            chain.offset = offset + len;
+            count += 1;
+        }
+        chain
+    }
+}
+
+fn decode_option(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    let offset = opt.range.start;
+    layer.add_attr(attr!(&OPTION_ATTR, range: opt.range.clone()));
+    layer.add_attr(attr!(&OPTION_TYPE_ATTR, range: offset..offset + 1));
+    layer.add_attr(attr!(&OPTION_DATA_ATTR, range: opt.value.clone()));
+    Ok(())
+}
+
+/// Returns true if `nheader` is an extension header.
+fn is_extension(nheader: u8) -> bool {
+    match nheader {
+        // Hop-by-Hop Options, Routing, Fragment, ESP, AH, Destination Options,
+        // Mobility
+        0 | 43 | 44 | 50 | 51 | 60 | 135 => true,
+        _ => false,
+    }
+}
+
 #[derive(Clone)]
 struct IPv6Decoder {}
 
 impl Decoder for IPv6Decoder {
-    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
-        Box::new(IPv6Worker {})
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        let max_headers = ctx
+            .get_config(MAX_HEADERS_KEY)
+            .trim_matches('"')
+            .parse()
+            .unwrap_or(DEFAULT_MAX_HEADERS);
+        let options = OptionTable::new(option::IPV6)
+            .option(0, &OPTION_PAD1_ATTR)
+            .option(1, &OPTION_PADN_ATTR)
+            .value(5, &OPTION_ROUTER_ALERT_ATTR)
+            .value(0xc2, &OPTION_JUMBO_ATTR)
+            .unknown(decode_option);
+        Box::new(IPv6Worker {
+            max_headers,
+            options,
+        })
     }
 
     fn metadata(&self) -> Metadata {
+        let max_headers = DecoderOption::int(MAX_HEADERS_KEY, 0..=255, DEFAULT_MAX_HEADERS as i64)
+            .description(
+                "Maximum number of extension headers before a packet is marked as malformed",
+            );
         Metadata {
             exec_type: ExecType::ParallelSync,
+            options: vec![max_headers],
             ..Metadata::default()
         }
     }
@@ -78,13 +228,15 @@ def_layer_class!(IPV6_CLASS, "ipv6",
     header: attr!(&VERSION_ATTR, bit_range: 0 0..4),
     header: attr!(&TRAFFIC_ATTR, bit_range: 0 4..12),
     header: attr!(&FLOW_ATTR, bit_range: 1 4..24),
-    header: attr!(&LENGTH_ATTR, range: 4..6),
+    header: &LENGTH_ATTR_HEADER,
     header: &NHEADER_ATTR_HEADER,
     header: attr!(&HLIMIT_ATTR, range: 7..8),
     header: attr!(&SRC_ATTR, range: 8..24),
     header: attr!(&DST_ATTR, range: 24..40)
 );
 
+def_attr!(LENGTH_ATTR_HEADER,  &LENGTH_ATTR, range: 4..6);
+
 def_attr!(NHEADER_ATTR_HEADER,  &NHEADER_ATTR, range: 6..7);
 
 def_attr_class!(VERSION_ATTR, "ipv6.version",
@@ -95,13 +247,13 @@ def_attr_class!(TRAFFIC_ATTR, "ipv6.trafficClass",
     cast: cast::UInt16BE().map(|v| (v >> 4) & 0xff)
 );
 
-def_attr_class!(FLOW_ATTR, "ipv6.flowLabel", 
+def_attr_class!(FLOW_ATTR, "ipv6.flowLabel",
     cast:
         cast::ByteSlice()
             .map(|v| (((v[2] as u32) & 0xf) << 16) | ((v[1] as u32) << 8) | v[2] as u32)
 );
 
-def_attr_class!(LENGTH_ATTR, "ipv6.payloadLength", cast: cast::UInt8());
+def_attr_class!(LENGTH_ATTR, "ipv6.payloadLength", cast: cast::UInt16BE());
 
 def_attr_class!(NHEADER_ATTR, "ipv6.nextHeader", cast: cast::UInt8());
 
@@ -117,6 +269,97 @@ def_attr_class!(DST_ATTR, "ipv6.dst",
     cast: cast::ByteSlice()
 );
 
+def_attr_class!(HOP_BY_HOP_ATTR, "ipv6.hopByHop",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(DESTINATION_ATTR, "ipv6.destinationOptions",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(OPTION_ATTR, "ipv6.option",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(OPTION_TYPE_ATTR, "ipv6.option.type", cast: cast::UInt8());
+
+def_attr_class!(OPTION_DATA_ATTR, "ipv6.option.data", cast: cast::ByteSlice());
+
+def_attr_class!(OPTION_PAD1_ATTR, "ipv6.option.pad1",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(OPTION_PADN_ATTR, "ipv6.option.padN",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(OPTION_ROUTER_ALERT_ATTR, "ipv6.option.routerAlert", cast: cast::UInt16BE());
+
+def_attr_class!(OPTION_JUMBO_ATTR, "ipv6.option.jumboPayload", cast: cast::UInt32BE());
+
+def_attr_class!(MOBILITY_ATTR, "ipv6.mobility",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(ROUTING_ATTR, "ipv6.routing",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(ROUTING_TYPE_ATTR, "ipv6.routing.type", cast: cast::UInt8());
+
+def_attr_class!(ROUTING_LEFT_ATTR, "ipv6.routing.segmentsLeft", cast: cast::UInt8());
+
+def_attr_class!(FRAGMENT_ATTR, "ipv6.fragment",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(FRAGMENT_OFFSET_ATTR, "ipv6.fragment.offset",
+    cast: cast::UInt16BE().map(|v| v >> 3)
+);
+
+def_attr_class!(FRAGMENT_MORE_ATTR, "ipv6.fragment.more",
+    cast: cast::UInt16BE().map(|v| v & 1 == 1)
+);
+
+def_attr_class!(FRAGMENT_ID_ATTR, "ipv6.fragment.id", cast: cast::UInt32BE());
+
+def_attr_class!(AH_ATTR, "ipv6.ah",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(AH_SPI_ATTR, "ipv6.ah.spi", cast: cast::UInt32BE());
+
+def_attr_class!(ESP_ATTR, "ipv6.esp",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(ESP_SPI_ATTR, "ipv6.esp.spi", cast: cast::UInt32BE());
+
+def_attr_class!(MALFORMED_LIMIT_ATTR, "ipv6.malformed.tooManyHeaders",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(MALFORMED_ORDER_ATTR, "ipv6.malformed.hopByHopNotFirst",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(MALFORMED_TRUNCATED_ATTR, "ipv6.malformed.truncated",
+    typ: "@novalue",
+    value: true
+);
+
 def_attr_class!(PROTOCOL_ATTR, "ipv6.protocol",
     typ: "@enum",
     cast: cast::UInt8()
@@ -128,6 +371,10 @@ fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
             token!("@data:igmp"),
             attr_class_lazy!("ipv6.protocol.igmp", typ: "@novalue", value: true),
         )),
+        0x04 => Some((
+            token!("@data:ipv4"),
+            attr_class_lazy!("ipv6.protocol.ipv4", typ: "@novalue", value: true),
+        )),
         0x06 => Some((
             token!("@data:tcp"),
             attr_class_lazy!("ipv6.protocol.tcp", typ: "@novalue", value: true),
@@ -136,6 +383,14 @@ fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
             token!("@data:udp"),
             attr_class_lazy!("ipv6.protocol.udp", typ: "@novalue", value: true),
         )),
+        0x29 => Some((
+            token!("@data:ipv6"),
+            attr_class_lazy!("ipv6.protocol.ipv6", typ: "@novalue", value: true),
+        )),
+        0x2f => Some((
+            token!("@data:gre"),
+            attr_class_lazy!("ipv6.protocol.gre", typ: "@novalue", value: true),
+        )),
         0x3a => Some((
             token!("@data:icmp"),
             attr_class_lazy!("ipv6.protocol.icmp", typ: "@novalue", value: true),
@@ -145,3 +400,149 @@ fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
 }
 
 genet_decoders!(IPv6Decoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{decoder::DecoderBox, prelude::*};
+    use IPv6Decoder;
+
+    /// Returns a packet of `len` bytes whose headers are `headers`, starting
+    /// with the Next Header of the IPv6 header.
+    fn packet(payload_len: u16, headers: &[u8], len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        data[0] = 0x60;
+        data[4] = (payload_len >> 8) as u8;
+        data[5] = payload_len as u8;
+        data[6] = headers[0];
+        data[40..40 + headers.len() - 1].copy_from_slice(&headers[1..]);
+        data
+    }
+
+    fn decode(data: Vec<u8>) -> (Vec<Token>, Option<(usize, usize)>) {
+        decode_with(&[], data)
+    }
+
+    /// Decodes `data` with `config` and returns the attributes of the IPv6
+    /// layer and the range of the payload if any.
+    fn decode_with(config: &[(&str, &str)], data: Vec<u8>) -> (Vec<Token>, Option<(usize, usize)>) {
+        let data = ByteSlice::from(data);
+        let mut root = Layer::new(Fixed::new(LayerClass::builder("[link-1]").build()), data);
+        root.add_payload(Payload::new(data, "@data:ipv6"));
+        let config = config
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let mut ctx = Context::new(config);
+        let mut worker = DecoderBox::new(IPv6Decoder {}).new_worker(&ctx);
+        let mut parent = Parent::from_mut_ref(&mut root);
+        assert!(worker.decode(&mut ctx, &[], &mut parent).unwrap());
+        let layer = unsafe { &*parent.children()[0] };
+        let attrs = layer.attrs_iter().map(|attr| attr.id()).collect();
+        let payload = layer.payloads().get(0).map(|payload| {
+            let start = payload.data().as_ptr() as usize - data.as_ptr() as usize;
+            (start, start + payload.data().len())
+        });
+        (attrs, payload)
+    }
+
+    #[test]
+    fn oversized() {
+        // Zero payload length of an offloaded packet.
+        assert_eq!(decode(packet(0, &[6], 70_040)).1, Some((40, 70_040)));
+        // Payload length truncated to 16 bits.
+        assert_eq!(
+            decode(packet((70_000 & 0xffff) as u16, &[6], 70_040)).1,
+            Some((40, 70_040))
+        );
+        // Payload length of the first segment.
+        assert_eq!(decode(packet(1460, &[6], 70_040)).1, Some((40, 70_040)));
+
+        // A jumbogram with the Jumbo Payload option.
+        let (attrs, payload) = decode(packet(0, &[0, 6, 0, 0xc2, 4, 0, 1, 0x11, 0x18], 70_040));
+        assert_eq!(payload, Some((48, 70_040)));
+        assert!(attrs.contains(&token!("ipv6.option.jumboPayload")));
+    }
+
+    #[test]
+    fn payload_length() {
+        // Padding after the packet.
+        assert_eq!(decode(packet(20, &[6], 100)).1, Some((40, 60)));
+        assert_eq!(decode(packet(1460, &[6], 100)).1, Some((40, 100)));
+        assert_eq!(decode(packet(0, &[6], 100)).1, Some((40, 100)));
+    }
+
+    #[test]
+    fn order() {
+        // Hop-by-Hop Options after Destination Options.
+        let (attrs, payload) = decode(packet(16, &[60, 0, 0, 0, 0, 0, 0, 0, 6], 56));
+        assert!(attrs.contains(&token!("ipv6.destinationOptions")));
+        assert!(attrs.contains(&token!("ipv6.malformed.hopByHopNotFirst")));
+        assert_eq!(payload, None);
+
+        let (attrs, payload) = decode(packet(16, &[0, 60, 0, 0, 0, 0, 0, 0, 0, 6], 56));
+        assert!(attrs.contains(&token!("ipv6.hopByHop")));
+        assert!(attrs.contains(&token!("ipv6.destinationOptions")));
+        assert_eq!(payload, Some((56, 56)));
+    }
+
+    #[test]
+    fn limit() {
+        // Destination Options headers followed by TCP.
+        let chain = |count: usize| {
+            let mut headers = vec![60];
+            for i in 0..count {
+                let nheader = if i + 1 == count { 6 } else { 60 };
+                headers.extend_from_slice(&[nheader, 0, 0, 0, 0, 0, 0, 0]);
+            }
+            packet((count * 8) as u16, &headers, 40 + count * 8)
+        };
+
+        assert_eq!(decode(chain(8)).1, Some((104, 104)));
+        let (attrs, payload) = decode(chain(9));
+        assert!(attrs.contains(&token!("ipv6.malformed.tooManyHeaders")));
+        assert_eq!(payload, None);
+
+        let config = [("@genet/ipv6.maxExtensionHeaders", "2")];
+        assert_eq!(decode_with(&config, chain(2)).1, Some((56, 56)));
+        let (attrs, payload) = decode_with(&config, chain(3));
+        assert!(attrs.contains(&token!("ipv6.malformed.tooManyHeaders")));
+        assert_eq!(payload, None);
+    }
+
+    #[test]
+    fn truncated() {
+        // Destination Options of 24 bytes in a payload of 16 bytes.
+        let (attrs, payload) = decode(packet(16, &[60, 6, 2], 100));
+        assert!(!attrs.contains(&token!("ipv6.destinationOptions")));
+        assert!(attrs.contains(&token!("ipv6.malformed.truncated")));
+        assert_eq!(payload, None);
+
+        // Authentication Header beyond the frame.
+        let (attrs, payload) = decode(packet(0, &[51, 6], 41));
+        assert!(attrs.contains(&token!("ipv6.malformed.truncated")));
+        assert_eq!(payload, None);
+    }
+
+    #[test]
+    fn esp() {
+        let (attrs, payload) = decode(packet(16, &[50, 0, 0, 1, 0], 56));
+        assert!(attrs.contains(&token!("ipv6.esp")));
+        assert!(attrs.contains(&token!("ipv6.esp.spi")));
+        assert!(!attrs.contains(&token!("ipv6.malformed.truncated")));
+        assert_eq!(payload, None);
+    }
+
+    #[test]
+    fn fragment() {
+        // The first fragment with the M flag.
+        let (attrs, payload) = decode(packet(28, &[44, 6, 0, 0x00, 0x01, 0, 0, 0, 1], 68));
+        assert!(attrs.contains(&token!("ipv6.fragment")));
+        assert_eq!(payload, Some((48, 68)));
+
+        // A non-first fragment at the offset of 8 bytes.
+        let (attrs, payload) = decode(packet(28, &[44, 6, 0, 0x00, 0x08, 0, 0, 0, 1], 68));
+        assert!(attrs.contains(&token!("ipv6.fragment")));
+        assert!(attrs.contains(&token!("ipv6.fragment.offset")));
+        assert_eq!(payload, None);
+    }
+}
@@ -0,0 +1,115 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+/// The length of a label stack entry.
+const ENTRY_LEN: usize = 4;
+
+struct MplsWorker {}
+
+impl Worker for MplsWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:mpls"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&MPLS_CLASS, data);
+        let mut offset = 0;
+        let mut bottom = false;
+        while !bottom {
+            let entry = match data.get(offset..offset + ENTRY_LEN) {
+                Some(entry) => entry,
+                None => break,
+            };
+            let label =
+                u32::from(entry[0]) << 12 | u32::from(entry[1]) << 4 | u32::from(entry[2]) >> 4;
+            bottom = entry[2] & 1 != 0;
+            layer.add_attr(attr!(&ENTRY_ATTR, range: offset..offset + ENTRY_LEN));
+            layer.add_attr(attr!(&LABEL_ATTR, bit_range: offset 0..20, value: label));
+            layer.add_attr(attr!(&TC_ATTR, bit_range: offset 20..23));
+            layer.add_attr(attr!(&BOTTOM_ATTR, bit_range: offset 23..24));
+            layer.add_attr(attr!(&TTL_ATTR, range: offset + 3..offset + 4));
+            offset += ENTRY_LEN;
+        }
+        if !bottom {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        // The label stack does not identify the payload, which is guessed
+        // from its first nibble.
+        let payload = data.try_get(offset..)?;
+        match payload.first().map(|b| b >> 4) {
+            Some(4) => layer.add_payload(Payload::new(payload, "@data:ipv4")),
+            Some(6) => layer.add_payload(Payload::new(payload, "@data:ipv6")),
+            // An Ethernet pseudowire with a control word (RFC 4448).
+            Some(0) if payload.len() >= 4 => {
+                layer.add_attr(attr!(&CONTROL_WORD_ATTR, range: offset..offset + 4));
+                let payload = data.try_get(offset + 4..)?;
+                layer.add_payload(Payload::new(payload, "@data:eth"));
+            }
+            _ => {}
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct MplsDecoder {}
+
+impl Decoder for MplsDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(MplsWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(MPLS_CLASS, "mpls");
+
+def_attr_class!(ENTRY_ATTR, "mpls.entry",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(LABEL_ATTR, "mpls.entry.label");
+
+def_attr_class!(TC_ATTR, "mpls.entry.tc",
+    cast: cast::UInt8().map(|v| (v >> 1) & 0b111)
+);
+
+def_attr_class!(BOTTOM_ATTR, "mpls.entry.bottom",
+    cast: cast::UInt8().map(|v| (v & 1) != 0)
+);
+
+def_attr_class!(TTL_ATTR, "mpls.entry.ttl", cast: cast::UInt8());
+
+def_attr_class!(CONTROL_WORD_ATTR, "mpls.controlWord", cast: cast::UInt32BE());
+
+def_attr_class!(MALFORMED_ATTR, "mpls.malformed",
+    typ: "@expert:error",
+    description: "Label stack without a bottom entry"
+);
+
+genet_decoders!(MplsDecoder {});
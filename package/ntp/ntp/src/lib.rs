@@ -8,37 +8,21 @@ impl Worker for NtpWorker {
     fn decode(
         &mut self,
         _ctx: &mut Context,
-        stack: &LayerStack,
+        _stack: &LayerStack,
         parent: &mut Parent,
     ) -> Result<Status> {
-        if parent.id() != token!("udp") {
-            return Ok(Status::Skip);
-        }
-
         let data;
 
-        if let Some(payload) = parent.payloads().iter().next() {
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:ntp"))
+        {
             data = payload.data();
         } else {
             return Ok(Status::Skip);
         }
 
-        let parent_src: u16 = stack
-            .attr(token!("udp.src"))
-            .unwrap()
-            .try_get(parent)?
-            .try_into()?;
-
-        let parent_dst: u16 = stack
-            .attr(token!("udp.dst"))
-            .unwrap()
-            .try_get(parent)?
-            .try_into()?;
-
-        if parent_src != 123 && parent_dst != 123 {
-            return Ok(Status::Skip);
-        }
-
         let mut layer = Layer::new(&NTP_CLASS, data);
         let leap_type = LEAP_ATTR_HEADER.try_get(&layer)?.try_into()?;
 
@@ -70,7 +54,9 @@ impl Worker for NtpWorker {
 struct NtpDecoder {}
 
 impl Decoder for NtpDecoder {
-    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        ctx.dissector_table("udp.port")
+            .add_default(123, "@data:ntp");
         Box::new(NtpWorker {})
     }
 
@@ -0,0 +1,147 @@
+//! Endace ERF files.
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use genet_sdk::{prelude::*, reader::*};
+use serde_json;
+use std::{
+    fs::File,
+    io::{self, BufReader, Error, ErrorKind, Read},
+};
+use {read_block, Arg, LinkClasses, Record, DROP_COUNT_CLASS, INTERFACE_CLASS};
+
+const HEADER_LEN: usize = 16;
+const EXT_HEADER_FLAG: u8 = 0x80;
+
+const TYPE_HDLC_POS: u8 = 1;
+const TYPE_ETH: u8 = 2;
+const TYPE_COLOR_HDLC_POS: u8 = 10;
+const TYPE_COLOR_ETH: u8 = 11;
+const TYPE_DSM_COLOR_HDLC_POS: u8 = 15;
+const TYPE_DSM_COLOR_ETH: u8 = 16;
+const TYPE_COLOR_HASH_POS: u8 = 19;
+const TYPE_COLOR_HASH_ETH: u8 = 20;
+const TYPE_IPV4: u8 = 22;
+const TYPE_IPV6: u8 = 23;
+const TYPE_META: u8 = 27;
+const TYPE_PAD: u8 = 48;
+
+const DLT_ETHERNET: u32 = 1;
+const DLT_C_HDLC: u32 = 104;
+const DLT_ERF: u32 = 197;
+const DLT_IPV4: u32 = 228;
+const DLT_IPV6: u32 = 229;
+
+#[derive(Clone)]
+pub struct ErfFileReader {}
+
+impl Reader for ErfFileReader {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let reader = BufReader::new(File::open(&arg.file)?);
+        Ok(Box::new(ErfFileWorker {
+            reader,
+            classes: LinkClasses::default(),
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.erf-file".into(),
+            filters: vec![FileType::new("ERF File", &["erf"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+struct ErfFileWorker {
+    reader: BufReader<File>,
+    classes: LinkClasses,
+}
+
+impl ErfFileWorker {
+    fn read_one(&mut self) -> io::Result<Option<Layer>> {
+        let mut header = [0; HEADER_LEN];
+        self.reader.read_exact(&mut header)?;
+        let mut cursor = &header[..];
+        let ts = cursor.read_u64::<LittleEndian>()?;
+        let typ = cursor.read_u8()?;
+        let flags = cursor.read_u8()?;
+        let rlen = cursor.read_u16::<BigEndian>()?;
+        let lctr = cursor.read_u16::<BigEndian>()?;
+        let wlen = cursor.read_u16::<BigEndian>()?;
+
+        let body_len = (rlen as usize)
+            .checked_sub(HEADER_LEN)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "wrong record length"))?;
+        let mut body = vec![0; body_len];
+        self.reader.read_exact(&mut body)?;
+
+        let record_type = typ & !EXT_HEADER_FLAG;
+        if record_type == TYPE_PAD || record_type == TYPE_META {
+            return Ok(None);
+        }
+
+        // Skip extension headers; the top bit of each header is the
+        // continuation flag.
+        let mut offset = 0;
+        if typ & EXT_HEADER_FLAG != 0 {
+            loop {
+                let more = body.get(offset).map(|b| b & EXT_HEADER_FLAG != 0);
+                offset += 8;
+                if more != Some(true) {
+                    break;
+                }
+            }
+        }
+
+        let (link, data) = match record_type {
+            TYPE_ETH | TYPE_COLOR_ETH | TYPE_DSM_COLOR_ETH | TYPE_COLOR_HASH_ETH => {
+                // Ethernet records have a 2-byte offset and pad field.
+                (DLT_ETHERNET, body.get(offset + 2..).map(|d| d.to_vec()))
+            }
+            TYPE_HDLC_POS | TYPE_COLOR_HDLC_POS | TYPE_DSM_COLOR_HDLC_POS | TYPE_COLOR_HASH_POS => {
+                (DLT_C_HDLC, body.get(offset..).map(|d| d.to_vec()))
+            }
+            TYPE_IPV4 => (DLT_IPV4, body.get(offset..).map(|d| d.to_vec())),
+            TYPE_IPV6 => (DLT_IPV6, body.get(offset..).map(|d| d.to_vec())),
+            _ => {
+                // Keep unknown records intact with the ERF link type.
+                let mut data = header.to_vec();
+                data.extend_from_slice(&body);
+                (DLT_ERF, Some(data))
+            }
+        };
+        let mut data =
+            data.ok_or_else(|| Error::new(ErrorKind::InvalidData, "wrong record length"))?;
+        if link != DLT_ERF && data.len() > wlen as usize {
+            data.truncate(wlen as usize);
+        }
+
+        let ts_sec = ts >> 32;
+        let ts_nsec = (((ts & 0xffff_ffff) * 1_000_000_000) >> 32) as u32;
+        let mut layer = Record {
+            link,
+            data,
+            orig_len: u64::from(wlen),
+            ts_sec,
+            ts_nsec,
+        }
+        .into_layer(&mut self.classes);
+        layer.add_attr(attr!(&INTERFACE_CLASS, value: u64::from(flags & 0x03)));
+        layer.add_attr(attr!(&DROP_COUNT_CLASS, value: u64::from(lctr)));
+        layer.add_attr(attr!(&ERF_TYPE_CLASS, value: u64::from(record_type)));
+        layer.add_attr(attr!(&ERF_FLAGS_CLASS, value: u64::from(flags)));
+        Ok(Some(layer))
+    }
+}
+
+impl Worker for ErfFileWorker {
+    fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
+        read_block(|| self.read_one())
+    }
+}
+
+def_attr_class!(ERF_TYPE_CLASS, "link.erf.type");
+def_attr_class!(ERF_FLAGS_CLASS, "link.erf.flags",
+    typ: "@int:hex"
+);
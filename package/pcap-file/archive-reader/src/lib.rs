@@ -0,0 +1,104 @@
+extern crate byteorder;
+extern crate genet_sdk;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+mod erf;
+mod netmon;
+mod snoop;
+
+use genet_sdk::{layer::FrameMetadata, prelude::*};
+use std::{collections::HashMap, io};
+
+const BLOCK_SIZE: usize = 65535;
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+}
+
+/// Link layer classes keyed by the link type.
+#[derive(Default)]
+struct LinkClasses {
+    classes: HashMap<u32, Fixed<LayerClass>>,
+}
+
+impl LinkClasses {
+    fn get(&mut self, link: u32) -> Fixed<LayerClass> {
+        self.classes
+            .entry(link)
+            .or_insert_with(|| {
+                Fixed::new(layer_class!(
+                    format!("[link-{}]", link),
+                    header: attr!(&TYPE_CLASS, value: i64::from(link))
+                ))
+            })
+            .clone()
+    }
+}
+
+/// A record common to all the formats.
+struct Record {
+    link: u32,
+    data: Vec<u8>,
+    orig_len: u64,
+    ts_sec: u64,
+    ts_nsec: u32,
+}
+
+impl Record {
+    fn into_layer(self, classes: &mut LinkClasses) -> Layer {
+        let mut layer = Layer::new(classes.get(self.link), ByteSlice::from(self.data));
+        layer.set_frame_metadata(FrameMetadata::new(self.link));
+        layer.add_attr(attr!(&LENGTH_CLASS, value: self.orig_len));
+        layer.add_attr(attr!(
+            &TS_CLASS,
+            value: self.ts_sec as f64 + f64::from(self.ts_nsec) / 1_000_000_000f64
+        ));
+        layer.add_attr(attr!(&TS_SEC_CLASS, value: self.ts_sec));
+        layer.add_attr(attr!(&TS_USEC_CLASS, value: u64::from(self.ts_nsec / 1000)));
+        layer.add_attr(attr!(&TS_NSEC_CLASS, value: u64::from(self.ts_nsec)));
+        layer
+    }
+}
+
+/// Reads records in blocks, returning the error only if no record is read.
+fn read_block<F>(mut read_one: F) -> Result<Vec<Layer>>
+where
+    F: FnMut() -> io::Result<Option<Layer>>,
+{
+    let mut layers = Vec::new();
+    while layers.len() < BLOCK_SIZE {
+        match read_one() {
+            Ok(Some(layer)) => layers.push(layer),
+            Ok(None) => {}
+            Err(err) => {
+                if layers.is_empty() {
+                    return Err(err.into());
+                }
+                break;
+            }
+        }
+    }
+    Ok(layers)
+}
+
+def_attr_class!(TYPE_CLASS, "link.type");
+def_attr_class!(LENGTH_CLASS, "link.length");
+def_attr_class!(TS_CLASS, "link.timestamp",
+    typ: "@datetime:unix"
+);
+def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
+def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+def_attr_class!(TS_NSEC_CLASS, "link.timestamp.nsec");
+def_attr_class!(INTERFACE_CLASS, "link.interface");
+def_attr_class!(DROP_COUNT_CLASS, "link.dropCount");
+
+genet_readers!(
+    snoop::SnoopFileReader {},
+    erf::ErfFileReader {},
+    netmon::NetmonFileReader {}
+);
@@ -0,0 +1,200 @@
+//! Microsoft Network Monitor 1.x and 2.x (.cap) files.
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use genet_sdk::{prelude::*, reader::*};
+use serde_json;
+use std::{
+    fs::File,
+    io::{self, BufReader, Error, ErrorKind, Read, Seek, SeekFrom},
+};
+use {read_block, Arg, LinkClasses, Record};
+
+const MAGIC_V1: &[u8; 4] = b"RTSS";
+const MAGIC_V2: &[u8; 4] = b"GMBU";
+
+/// Seconds between 1601-01-01 and 1970-01-01.
+const FILETIME_EPOCH: u64 = 11_644_473_600;
+
+/// Maps an NDIS medium to a pcap link type.
+fn link_type(network: u16) -> Option<u32> {
+    match network {
+        // Ethernet
+        1 => Some(1),
+        // Token Ring
+        2 => Some(6),
+        // FDDI
+        3 => Some(10),
+        // ATM
+        4 => Some(123),
+        // IEEE 802.11
+        6 => Some(105),
+        // Raw IPv4 and IPv6
+        0xe000 => Some(228),
+        0xe001 => Some(229),
+        _ => None,
+    }
+}
+
+/// Converts a SYSTEMTIME to seconds since the Unix epoch.
+fn system_time(fields: &[u16; 8]) -> (u64, u32) {
+    let (year, month, day) = (
+        i64::from(fields[0]),
+        i64::from(fields[1]),
+        i64::from(fields[3]),
+    );
+    // Days from civil, see http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let sec = days * 86400
+        + i64::from(fields[4]) * 3600
+        + i64::from(fields[5]) * 60
+        + i64::from(fields[6]);
+    (sec.max(0) as u64, u32::from(fields[7]) * 1_000_000)
+}
+
+#[derive(Clone)]
+pub struct NetmonFileReader {}
+
+impl Reader for NetmonFileReader {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let mut reader = BufReader::new(File::open(&arg.file)?);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let v2 = if &magic == MAGIC_V1 {
+            false
+        } else if &magic == MAGIC_V2 {
+            true
+        } else {
+            return Err(Error::new(ErrorKind::InvalidData, "wrong magic number").into());
+        };
+        let minor = reader.read_u8()?;
+        let _major = reader.read_u8()?;
+        let network = reader.read_u16::<LittleEndian>()?;
+        let mut start = [0; 8];
+        for field in &mut start {
+            *field = reader.read_u16::<LittleEndian>()?;
+        }
+        let table_offset = reader.read_u32::<LittleEndian>()?;
+        let table_len = reader.read_u32::<LittleEndian>()?;
+
+        reader.seek(SeekFrom::Start(u64::from(table_offset)))?;
+        let mut offsets = Vec::with_capacity(table_len as usize / 4);
+        for _ in 0..table_len / 4 {
+            offsets.push(reader.read_u32::<LittleEndian>()?);
+        }
+        offsets.reverse();
+
+        Ok(Box::new(NetmonFileWorker {
+            reader,
+            v2,
+            minor,
+            network,
+            start: system_time(&start),
+            offsets,
+            classes: LinkClasses::default(),
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.netmon-file".into(),
+            filters: vec![FileType::new("NetMon File", &["cap"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+struct NetmonFileWorker {
+    reader: BufReader<File>,
+    v2: bool,
+    minor: u8,
+    network: u16,
+    start: (u64, u32),
+    offsets: Vec<u32>,
+    classes: LinkClasses,
+}
+
+impl NetmonFileWorker {
+    fn read_one(&mut self) -> io::Result<Option<Layer>> {
+        let offset = self
+            .offsets
+            .pop()
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "end of frame table"))?;
+        self.reader.seek(SeekFrom::Start(u64::from(offset)))?;
+
+        let (delta_nsec, orig_len, incl_len) = if self.v2 {
+            let delta = self.reader.read_u64::<LittleEndian>()?;
+            let orig_len = self.reader.read_u32::<LittleEndian>()?;
+            let incl_len = self.reader.read_u32::<LittleEndian>()?;
+            let delta = delta
+                .checked_mul(1000)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "wrong time delta"))?;
+            (delta, orig_len, incl_len)
+        } else {
+            let delta = self.reader.read_u32::<LittleEndian>()?;
+            let orig_len = self.reader.read_u16::<LittleEndian>()?;
+            let incl_len = self.reader.read_u16::<LittleEndian>()?;
+            (
+                u64::from(delta) * 1_000_000,
+                u32::from(orig_len),
+                u32::from(incl_len),
+            )
+        };
+
+        let mut data = vec![0; incl_len as usize];
+        self.reader.read_exact(&mut data)?;
+
+        // Version 2.1 and later store the medium of each frame in a trailer.
+        let mut network = self.network;
+        if self.v2 && self.minor >= 1 {
+            network = self.reader.read_u16::<LittleEndian>()?;
+        }
+        // Version 2.2 and later follow it with the index of the process info.
+        if self.v2 && self.minor >= 2 {
+            let _process_info_index = self.reader.read_u32::<LittleEndian>()?;
+        }
+        // Skip process info and other metadata frames.
+        let link = match link_type(network) {
+            Some(link) => link,
+            None => return Ok(None),
+        };
+
+        let (mut ts_sec, mut ts_nsec) = self.start;
+        // Version 2.3 and later also store the UTC time of each frame.
+        if self.v2 && self.minor >= 3 {
+            let filetime = self.reader.read_u64::<LittleEndian>()?;
+            if filetime > 0 {
+                ts_sec = (filetime / 10_000_000).saturating_sub(FILETIME_EPOCH);
+                ts_nsec = (filetime % 10_000_000) as u32 * 100;
+            }
+        } else {
+            let nsec = u64::from(ts_nsec).saturating_add(delta_nsec);
+            ts_sec += nsec / 1_000_000_000;
+            ts_nsec = (nsec % 1_000_000_000) as u32;
+        }
+
+        Ok(Some(
+            Record {
+                link,
+                data,
+                orig_len: u64::from(orig_len),
+                ts_sec,
+                ts_nsec,
+            }
+            .into_layer(&mut self.classes),
+        ))
+    }
+}
+
+impl Worker for NetmonFileWorker {
+    fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
+        read_block(|| self.read_one())
+    }
+}
@@ -0,0 +1,104 @@
+//! Solaris snoop (RFC 1761) files.
+
+use byteorder::{BigEndian, ReadBytesExt};
+use genet_sdk::{prelude::*, reader::*};
+use serde_json;
+use std::{
+    fs::File,
+    io::{self, BufReader, Error, ErrorKind, Read},
+};
+use {read_block, Arg, LinkClasses, Record, DROP_COUNT_CLASS};
+
+const MAGIC: &[u8; 8] = b"snoop\0\0\0";
+
+/// Maps a snoop datalink type to a pcap link type.
+fn link_type(datalink: u32) -> Option<u32> {
+    match datalink {
+        // IEEE 802.3 and Ethernet
+        0 | 4 => Some(1),
+        // IEEE 802.5 Token Ring
+        2 => Some(6),
+        // FDDI
+        8 => Some(10),
+        // IP over InfiniBand
+        26 => Some(242),
+        _ => None,
+    }
+}
+
+#[derive(Clone)]
+pub struct SnoopFileReader {}
+
+impl Reader for SnoopFileReader {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let mut reader = BufReader::new(File::open(&arg.file)?);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "wrong magic number").into());
+        }
+        let _version = reader.read_u32::<BigEndian>()?;
+        let datalink = reader.read_u32::<BigEndian>()?;
+        let link = link_type(datalink)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "unsupported datalink type"))?;
+
+        Ok(Box::new(SnoopFileWorker {
+            reader,
+            link,
+            classes: LinkClasses::default(),
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.snoop-file".into(),
+            filters: vec![FileType::new("Snoop File", &["snoop"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+struct SnoopFileWorker {
+    reader: BufReader<File>,
+    link: u32,
+    classes: LinkClasses,
+}
+
+impl SnoopFileWorker {
+    fn read_one(&mut self) -> io::Result<Option<Layer>> {
+        let orig_len = self.reader.read_u32::<BigEndian>()?;
+        let incl_len = self.reader.read_u32::<BigEndian>()?;
+        let record_len = self.reader.read_u32::<BigEndian>()?;
+        let drops = self.reader.read_u32::<BigEndian>()?;
+        let ts_sec = self.reader.read_u32::<BigEndian>()?;
+        let ts_usec = self.reader.read_u32::<BigEndian>()?;
+
+        let body_len = record_len
+            .checked_sub(24)
+            .filter(|len| *len >= incl_len)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "wrong record length"))?;
+        let mut data = vec![0; body_len as usize];
+        self.reader.read_exact(&mut data)?;
+        data.truncate(incl_len as usize);
+
+        let mut layer = Record {
+            link: self.link,
+            data,
+            orig_len: u64::from(orig_len),
+            // A corrupt record may have a microsecond field over a second.
+            ts_sec: u64::from(ts_sec) + u64::from(ts_usec) / 1_000_000,
+            ts_nsec: (u64::from(ts_usec) % 1_000_000 * 1000) as u32,
+        }
+        .into_layer(&mut self.classes);
+        layer.add_attr(attr!(&DROP_COUNT_CLASS, value: u64::from(drops)));
+        Ok(Some(layer))
+    }
+}
+
+impl Worker for SnoopFileWorker {
+    fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
+        read_block(|| self.read_one())
+    }
+}
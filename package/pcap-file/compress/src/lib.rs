@@ -0,0 +1,153 @@
+//! Transparent compression of capture files.
+//!
+//! Compressed inputs are detected by their magic bytes and decompressed as
+//! a stream, so archived captures can be opened without a temporary
+//! decompressed copy.
+
+extern crate flate2;
+extern crate lz4;
+extern crate serde;
+extern crate zstd;
+
+#[macro_use]
+extern crate serde_derive;
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+use std::{
+    io::{self, Cursor, Error, ErrorKind, Read, Write},
+    path::Path,
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const LZ4_MAGIC: &[u8] = &[0x04, 0x22, 0x4d, 0x18];
+
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+impl Compression {
+    /// Returns the compression implied by the extension of `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Compression {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            Some("lz4") => Compression::Lz4,
+            _ => Compression::None,
+        }
+    }
+
+    /// Returns the compression of a stream starting with `magic`.
+    pub fn detect(magic: &[u8]) -> Compression {
+        if magic.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if magic.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if magic.starts_with(LZ4_MAGIC) {
+            Compression::Lz4
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Returns a reader decompressing `reader` if it starts with the magic
+/// bytes of a supported format.
+pub fn decoder<R: 'static + Read + Send>(mut reader: R) -> io::Result<Box<Read + Send>> {
+    let mut magic = [0u8; 4];
+    let mut len = 0;
+    while len < magic.len() {
+        match reader.read(&mut magic[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    let compression = Compression::detect(&magic[..len]);
+    let reader = Cursor::new(magic[..len].to_vec()).chain(reader);
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        Compression::Lz4 => Box::new(lz4::Decoder::new(reader)?),
+    })
+}
+
+enum Inner<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+    Lz4(lz4::Encoder<W>),
+}
+
+/// A writer compressing its output.
+///
+/// The stream is terminated by `finish`, or when the encoder is dropped.
+pub struct Encoder<W: Write> {
+    inner: Option<Inner<W>>,
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(writer: W, compression: Compression) -> io::Result<Encoder<W>> {
+        let inner = match compression {
+            Compression::None => Inner::Plain(writer),
+            Compression::Gzip => Inner::Gzip(GzEncoder::new(writer, Default::default())),
+            Compression::Zstd => Inner::Zstd(zstd::Encoder::new(writer, ZSTD_LEVEL)?),
+            Compression::Lz4 => Inner::Lz4(lz4::EncoderBuilder::new().build(writer)?),
+        };
+        Ok(Encoder { inner: Some(inner) })
+    }
+
+    /// Writes the end of the compressed stream and flushes the writer.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let mut writer = match self.inner.take() {
+            None => return Ok(()),
+            Some(Inner::Plain(writer)) => writer,
+            Some(Inner::Gzip(encoder)) => encoder.finish()?,
+            Some(Inner::Zstd(encoder)) => encoder.finish()?,
+            Some(Inner::Lz4(encoder)) => {
+                let (writer, result) = encoder.finish();
+                result?;
+                writer
+            }
+        };
+        writer.flush()
+    }
+
+    fn inner(&mut self) -> io::Result<&mut Write> {
+        match self.inner {
+            None => Err(Error::new(
+                ErrorKind::Other,
+                "compressed stream is finished",
+            )),
+            Some(Inner::Plain(ref mut writer)) => Ok(writer),
+            Some(Inner::Gzip(ref mut writer)) => Ok(writer),
+            Some(Inner::Zstd(ref mut writer)) => Ok(writer),
+            Some(Inner::Lz4(ref mut writer)) => Ok(writer),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner()?.flush()
+    }
+}
+
+impl<W: Write> Drop for Encoder<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
@@ -1,12 +1,33 @@
 const m = require('mithril')
+const path = require('path')
 const { remote: { dialog } } = require('electron')
+
+const compressionExtensions = {
+  none: '',
+  gzip: '.gz',
+  zstd: '.zst',
+  lz4: '.lz4',
+}
+
 class OutputView {
   view (vnode) {
+    const compression = () =>
+      vnode.dom.querySelector('[name=compression]').value
+    const withExtension = (file) => {
+      const ext = compressionExtensions[compression()]
+      return file.endsWith(ext) ? file : file + ext
+    }
     return m('ul', [
+      m('li', [
+        m('label', 'Compression '),
+        m('select', { name: 'compression' },
+          Object.keys(compressionExtensions).map((name) =>
+            m('option', { value: name }, name)))
+      ]),
       m('li', [
         m('input', {
           type: 'button',
-          value: 'Export',
+          value: 'Export as Pcap',
           onclick: () => {
             const file = dialog.showSaveDialog({
               properties: ['openFile'],
@@ -16,7 +37,79 @@ class OutputView {
               }],
             })
             if (typeof file !== 'undefined') {
-              vnode.attrs.callback('app.genet.writer.pcap-file', { file })
+              vnode.attrs.callback('app.genet.writer.pcap-file', {
+                file: withExtension(file),
+                compression: compression(),
+              })
+            }
+          },
+        })
+      ]),
+      m('li', [
+        m('input', {
+          type: 'button',
+          value: 'Export as Pcapng',
+          onclick: () => {
+            const file = dialog.showSaveDialog({
+              properties: ['openFile'],
+              filters: [{
+                name: 'Pcapng File',
+                extensions: ['pcapng'],
+              }],
+            })
+            if (typeof file !== 'undefined') {
+              vnode.attrs.callback('app.genet.writer.pcapng-file', {
+                file: withExtension(file),
+                compression: compression(),
+              })
+            }
+          },
+        })
+      ]),
+      m('li', [
+        m('input', {
+          type: 'button',
+          value: 'Split by Conversation',
+          onclick: () => {
+            const dirs = dialog.showOpenDialog({
+              properties: ['openDirectory', 'createDirectory'],
+            })
+            if (typeof dirs !== 'undefined') {
+              vnode.attrs.callback('app.genet.writer.pcap-conversations', {
+                directory: dirs[0],
+              })
+            }
+          },
+        })
+      ]),
+      m('li', [
+        m('textarea', {
+          name: 'groups',
+          placeholder: 'One group per line, e.g. dns: udp.dst == 53',
+        }),
+        m('input', {
+          type: 'button',
+          value: 'Split by Filter Groups',
+          onclick: () => {
+            const groups = vnode.dom.querySelector('[name=groups]').value
+              .split('\n')
+              .map((line) => line.split(':'))
+              .filter((pair) => pair.length >= 2 && pair[0].trim())
+              .map(([name, ...filter]) => ({
+                name: name.trim(),
+                filter: filter.join(':').trim(),
+              }))
+            if (groups.length === 0) {
+              return
+            }
+            const dirs = dialog.showOpenDialog({
+              properties: ['openDirectory', 'createDirectory'],
+            })
+            if (typeof dirs !== 'undefined') {
+              for (const { name, filter } of groups) {
+                const file = path.join(dirs[0], `${name}.pcap`)
+                vnode.attrs.callback('app.genet.writer.pcap-file', { file }, filter)
+              }
             }
           },
         })
@@ -0,0 +1,527 @@
+extern crate byteorder;
+extern crate genet_sdk;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use genet_sdk::{
+    layer::{Direction, FrameMetadata},
+    prelude::*,
+    reader::*,
+};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, Error, ErrorKind, Read, Seek, SeekFrom},
+    net::{Ipv4Addr, Ipv6Addr},
+    str,
+};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const PACKET_BLOCK: u32 = 0x0000_0002;
+const SIMPLE_PACKET_BLOCK: u32 = 0x0000_0003;
+const NAME_RESOLUTION_BLOCK: u32 = 0x0000_0004;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+
+/// Blocks larger than this are treated as corrupted. Packets of offloaded
+/// captures may exceed 64 KiB, but not this.
+const MAX_BLOCK_SIZE: usize = 1 << 28;
+
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+}
+
+#[derive(Clone)]
+struct PcapngFileReader {}
+
+impl Reader for PcapngFileReader {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let file = File::open(&arg.file)?;
+        let mut reader = BufReader::new(file);
+
+        if reader.read_u32::<BigEndian>()? != SECTION_HEADER_BLOCK {
+            return Err(Error::new(ErrorKind::InvalidData, "wrong magic number").into());
+        }
+        reader.seek(SeekFrom::Start(0))?;
+
+        Ok(Box::new(PcapngFileWorker {
+            le: true,
+            reader,
+            interfaces: Vec::new(),
+            section: SectionInfo::default(),
+            sections: 0,
+            interface_infos: Vec::new(),
+            names: BTreeMap::new(),
+            pending: None,
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.pcapng-file".into(),
+            filters: vec![FileType::new("Pcapng File", &["pcapng"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+#[derive(Serialize, Default)]
+struct SectionInfo {
+    comment: Option<String>,
+    hardware: Option<String>,
+    os: Option<String>,
+    application: Option<String>,
+}
+
+#[derive(Serialize)]
+struct InterfaceInfo {
+    section: usize,
+    id: usize,
+    link_type: u16,
+    snaplen: u32,
+    tsresol: u8,
+    name: Option<String>,
+    description: Option<String>,
+    filter: Option<String>,
+    os: Option<String>,
+    comment: Option<String>,
+}
+
+struct Interface {
+    link_class: Fixed<LayerClass>,
+    frame: FrameMetadata,
+    snaplen: u32,
+    units_per_sec: u64,
+    tsresol: u8,
+    tsoffset: i64,
+    name: Option<String>,
+}
+
+struct Packet<'a> {
+    interface: usize,
+    timestamp: Option<u64>,
+    orig_len: u32,
+    data: &'a [u8],
+    options: &'a [u8],
+}
+
+struct PcapngFileWorker {
+    le: bool,
+    reader: BufReader<File>,
+    interfaces: Vec<Interface>,
+    section: SectionInfo,
+    sections: usize,
+    interface_infos: Vec<InterfaceInfo>,
+    names: BTreeMap<String, Vec<String>>,
+    /// An error after a partial batch, reported by the next read.
+    pending: Option<io::Error>,
+}
+
+impl PcapngFileWorker {
+    fn u16(&self, data: &[u8]) -> u16 {
+        if self.le {
+            LittleEndian::read_u16(data)
+        } else {
+            BigEndian::read_u16(data)
+        }
+    }
+
+    fn u32(&self, data: &[u8]) -> u32 {
+        if self.le {
+            LittleEndian::read_u32(data)
+        } else {
+            BigEndian::read_u32(data)
+        }
+    }
+
+    fn u64(&self, data: &[u8]) -> u64 {
+        if self.le {
+            LittleEndian::read_u64(data)
+        } else {
+            BigEndian::read_u64(data)
+        }
+    }
+
+    fn options<'a>(&self, mut data: &'a [u8]) -> Vec<(u16, &'a [u8])> {
+        let mut options = Vec::new();
+        while data.len() >= 4 {
+            let code = self.u16(&data[0..2]);
+            let len = self.u16(&data[2..4]) as usize;
+            if code == OPT_ENDOFOPT || data.len() < 4 + len {
+                break;
+            }
+            options.push((code, &data[4..4 + len]));
+            data = &data[(4 + padded(len)).min(data.len())..];
+        }
+        options
+    }
+
+    fn read_block(&mut self) -> io::Result<(u32, Vec<u8>)> {
+        let mut header = [0u8; 8];
+        self.reader.read_exact(&mut header)?;
+        let typ = self.u32(&header[0..4]);
+
+        if typ == SECTION_HEADER_BLOCK {
+            let mut magic = [0u8; 4];
+            self.reader.read_exact(&mut magic)?;
+            self.le = match BigEndian::read_u32(&magic) {
+                0x4d3c_2b1a => true,
+                0x1a2b_3c4d => false,
+                _ => return Err(Error::new(ErrorKind::InvalidData, "wrong byte-order magic")),
+            };
+            let len = self.u32(&header[4..8]) as usize;
+            if len < 16 || len > MAX_BLOCK_SIZE {
+                return Err(Error::new(ErrorKind::InvalidData, "invalid block length"));
+            }
+            let mut body = vec![0; len - 12];
+            body[0..4].copy_from_slice(&magic);
+            self.reader.read_exact(&mut body[4..])?;
+            let mut trailer = [0u8; 4];
+            self.reader.read_exact(&mut trailer)?;
+            return Ok((typ, body));
+        }
+
+        let len = self.u32(&header[4..8]) as usize;
+        if len < 12 || len > MAX_BLOCK_SIZE {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid block length"));
+        }
+        let mut body = vec![0; len - 12];
+        self.reader.read_exact(&mut body)?;
+        let mut trailer = [0u8; 4];
+        self.reader.read_exact(&mut trailer)?;
+        Ok((typ, body))
+    }
+
+    fn parse_section(&mut self, ctx: &Context, body: &[u8]) -> io::Result<()> {
+        if body.len() < 16 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "truncated section header",
+            ));
+        }
+        self.interfaces.clear();
+        self.sections += 1;
+        let mut section = SectionInfo::default();
+        for (code, value) in self.options(&body[16..]) {
+            let value = Some(option_string(value));
+            match code {
+                OPT_COMMENT => section.comment = value,
+                2 => section.hardware = value,
+                3 => section.os = value,
+                4 => section.application = value,
+                _ => {}
+            }
+        }
+        self.section = section;
+        ctx.set_metadata(
+            "pcapng.section",
+            &serde_json::to_string(&self.section).unwrap_or_default(),
+        );
+        Ok(())
+    }
+
+    fn parse_interface(&mut self, ctx: &Context, body: &[u8]) -> io::Result<()> {
+        if body.len() < 8 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "truncated interface block",
+            ));
+        }
+        let link_type = self.u16(&body[0..2]);
+        let snaplen = self.u32(&body[4..8]);
+        let mut info = InterfaceInfo {
+            section: self.sections.saturating_sub(1),
+            id: self.interfaces.len(),
+            link_type,
+            snaplen,
+            tsresol: 6,
+            name: None,
+            description: None,
+            filter: None,
+            os: None,
+            comment: None,
+        };
+        let mut tsoffset = 0;
+        let mut frame = FrameMetadata::new(u32::from(link_type)).with_interface(info.id as u32);
+        for (code, value) in self.options(&body[8..]) {
+            match code {
+                OPT_COMMENT => info.comment = Some(option_string(value)),
+                2 => info.name = Some(option_string(value)),
+                3 => info.description = Some(option_string(value)),
+                9 if !value.is_empty() => info.tsresol = value[0],
+                11 if !value.is_empty() => info.filter = Some(option_string(&value[1..])),
+                12 => info.os = Some(option_string(value)),
+                // if_fcslen is in bits.
+                13 if !value.is_empty() => frame = frame.with_fcs_len(value[0] / 8),
+                14 if value.len() >= 8 => tsoffset = self.u64(value) as i64,
+                _ => {}
+            }
+        }
+
+        let units_per_sec = if info.tsresol & 0x80 == 0 {
+            10u64.checked_pow(u32::from(info.tsresol))
+        } else {
+            1u64.checked_shl(u32::from(info.tsresol & 0x7f))
+        }
+        .unwrap_or(1_000_000);
+
+        self.interfaces.push(Interface {
+            link_class: Fixed::new(layer_class!(
+                format!("[link-{}]", link_type),
+                header: attr!(&TYPE_CLASS, value: i64::from(link_type))
+            )),
+            frame,
+            snaplen,
+            units_per_sec,
+            tsresol: info.tsresol,
+            tsoffset,
+            name: info.name.clone(),
+        });
+        self.interface_infos.push(info);
+        ctx.set_metadata(
+            "pcapng.interfaces",
+            &serde_json::to_string(&self.interface_infos).unwrap_or_default(),
+        );
+        Ok(())
+    }
+
+    fn parse_names(&mut self, ctx: &Context, body: &[u8]) {
+        let mut data = body;
+        while data.len() >= 4 {
+            let typ = self.u16(&data[0..2]);
+            let len = self.u16(&data[2..4]) as usize;
+            if typ == 0 || data.len() < 4 + len {
+                break;
+            }
+            let value = &data[4..4 + len];
+            let entry = match typ {
+                1 if len >= 4 => Some((
+                    Ipv4Addr::new(value[0], value[1], value[2], value[3]).to_string(),
+                    &value[4..],
+                )),
+                2 if len >= 16 => {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(&value[0..16]);
+                    Some((Ipv6Addr::from(octets).to_string(), &value[16..]))
+                }
+                _ => None,
+            };
+            if let Some((addr, names)) = entry {
+                let names = names
+                    .split(|c| *c == 0)
+                    .filter(|s| !s.is_empty())
+                    .map(|s| String::from_utf8_lossy(s).into_owned());
+                self.names.entry(addr).or_default().extend(names);
+            }
+            data = &data[(4 + padded(len)).min(data.len())..];
+        }
+        ctx.set_metadata(
+            "pcapng.names",
+            &serde_json::to_string(&self.names).unwrap_or_default(),
+        );
+    }
+
+    fn parse_packet<'a>(&self, typ: u32, body: &'a [u8]) -> io::Result<Packet<'a>> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "truncated packet block");
+        match typ {
+            ENHANCED_PACKET_BLOCK | PACKET_BLOCK => {
+                if body.len() < 20 {
+                    return Err(invalid());
+                }
+                let interface = if typ == PACKET_BLOCK {
+                    self.u16(&body[0..2]) as usize
+                } else {
+                    self.u32(&body[0..4]) as usize
+                };
+                let timestamp =
+                    u64::from(self.u32(&body[4..8])) << 32 | u64::from(self.u32(&body[8..12]));
+                let cap_len = self.u32(&body[12..16]) as usize;
+                let orig_len = self.u32(&body[16..20]);
+                if body.len() < 20 + cap_len {
+                    return Err(invalid());
+                }
+                Ok(Packet {
+                    interface,
+                    timestamp: Some(timestamp),
+                    orig_len,
+                    data: &body[20..20 + cap_len],
+                    options: &body[(20 + padded(cap_len)).min(body.len())..],
+                })
+            }
+            _ => {
+                if body.len() < 4 {
+                    return Err(invalid());
+                }
+                let orig_len = self.u32(&body[0..4]);
+                let snaplen = self.interfaces.first().map(|i| i.snaplen).unwrap_or(0);
+                let mut cap_len = (orig_len as usize).min(body.len() - 4);
+                if snaplen > 0 {
+                    cap_len = cap_len.min(snaplen as usize);
+                }
+                Ok(Packet {
+                    interface: 0,
+                    timestamp: None,
+                    orig_len,
+                    data: &body[4..4 + cap_len],
+                    options: &[],
+                })
+            }
+        }
+    }
+
+    fn read_one(&mut self, ctx: &Context) -> io::Result<Layer> {
+        loop {
+            let (typ, body) = self.read_block()?;
+            match typ {
+                SECTION_HEADER_BLOCK => self.parse_section(ctx, &body)?,
+                INTERFACE_DESCRIPTION_BLOCK => self.parse_interface(ctx, &body)?,
+                NAME_RESOLUTION_BLOCK => self.parse_names(ctx, &body),
+                ENHANCED_PACKET_BLOCK | PACKET_BLOCK | SIMPLE_PACKET_BLOCK => {
+                    return self.create_layer(typ, &body)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn create_layer(&self, typ: u32, body: &[u8]) -> io::Result<Layer> {
+        let packet = self.parse_packet(typ, body)?;
+        let iface = self
+            .interfaces
+            .get(packet.interface)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "unknown interface"))?;
+
+        let payload = ByteSlice::from(packet.data.to_vec());
+        let mut layer = Layer::new(iface.link_class.clone(), payload);
+        let mut frame = iface.frame;
+        layer.add_attr(attr!(&LENGTH_CLASS, value: u64::from(packet.orig_len)));
+
+        if let Some(ts) = packet.timestamp {
+            let sec = (ts / iface.units_per_sec) as i64 + iface.tsoffset;
+            let nsec = (u128::from(ts % iface.units_per_sec) * 1_000_000_000
+                / u128::from(iface.units_per_sec)) as u64;
+            layer.add_attr(attr!(
+                &TS_CLASS,
+                value: sec as f64 + nsec as f64 / 1_000_000_000f64
+            ));
+            layer.add_attr(attr!(&TS_SEC_CLASS, value: sec as u64));
+            layer.add_attr(attr!(&TS_USEC_CLASS, value: nsec / 1000));
+            layer.add_attr(attr!(&TS_NSEC_CLASS, value: nsec));
+            // Ticks of the interface resolution, which may be finer than
+            // nanoseconds, e.g. from hardware timestamping.
+            let raw = i128::from(ts) + i128::from(iface.tsoffset) * i128::from(iface.units_per_sec);
+            layer.add_attr(attr!(&TS_RAW_CLASS, value: raw as u64));
+        }
+
+        layer.add_attr(attr!(&INTERFACE_CLASS, value: packet.interface as u64));
+        if let Some(name) = &iface.name {
+            layer.add_attr(attr!(
+                &INTERFACE_NAME_CLASS,
+                value: name.clone().into_boxed_str()
+            ));
+        }
+        layer.add_attr(attr!(
+            &INTERFACE_TSRESOL_CLASS,
+            value: u64::from(iface.tsresol)
+        ));
+
+        let mut comments = Vec::new();
+        for (code, value) in self.options(packet.options) {
+            match code {
+                OPT_COMMENT => comments.push(option_string(value)),
+                2 if value.len() >= 4 => {
+                    let flags = self.u32(value);
+                    layer.add_attr(attr!(&FLAGS_CLASS, value: flags));
+                    frame = frame.with_direction(match flags & 0b11 {
+                        1 => Direction::Inbound,
+                        2 => Direction::Outbound,
+                        _ => Direction::Unknown,
+                    });
+                    // The FCS length in octets, or 0 if unknown.
+                    let fcs_len = (flags >> 5) & 0xf;
+                    if fcs_len > 0 {
+                        frame = frame.with_fcs_len(fcs_len as u8);
+                    }
+                }
+                4 if value.len() >= 8 => {
+                    layer.add_attr(attr!(&DROP_COUNT_CLASS, value: self.u64(value)));
+                }
+                _ => {}
+            }
+        }
+        if !comments.is_empty() {
+            layer.add_attr(attr!(
+                &COMMENT_CLASS,
+                value: comments.join("\n").into_boxed_str()
+            ));
+        }
+
+        layer.set_frame_metadata(frame);
+        Ok(layer)
+    }
+}
+
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn option_string(value: &[u8]) -> String {
+    let value = match value.iter().position(|c| *c == 0) {
+        Some(pos) => &value[..pos],
+        None => value,
+    };
+    String::from_utf8_lossy(value).into_owned()
+}
+
+const BLOCK_SIZE: usize = 65535;
+
+impl Worker for PcapngFileWorker {
+    fn read(&mut self, ctx: &mut Context) -> Result<Vec<Layer>> {
+        if let Some(err) = self.pending.take() {
+            return Err(err.into());
+        }
+        let mut layers = Vec::with_capacity(BLOCK_SIZE);
+        for _ in 0..BLOCK_SIZE {
+            match self.read_one(ctx) {
+                Ok(layer) => layers.push(layer),
+                Err(err) => {
+                    if layers.is_empty() {
+                        return Err(err.into());
+                    }
+                    self.pending = Some(err);
+                    break;
+                }
+            }
+        }
+        Ok(layers)
+    }
+}
+
+def_attr_class!(TYPE_CLASS, "link.type");
+def_attr_class!(LENGTH_CLASS, "link.length");
+def_attr_class!(TS_CLASS, "link.timestamp",
+    typ: "@datetime:unix"
+);
+def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
+def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+def_attr_class!(TS_NSEC_CLASS, "link.timestamp.nsec");
+def_attr_class!(TS_RAW_CLASS, "link.timestamp.raw");
+def_attr_class!(INTERFACE_CLASS, "link.interface");
+def_attr_class!(INTERFACE_NAME_CLASS, "link.interface.name");
+def_attr_class!(INTERFACE_TSRESOL_CLASS, "link.interface.tsresol");
+def_attr_class!(COMMENT_CLASS, "link.comment");
+def_attr_class!(FLAGS_CLASS, "link.flags",
+    typ: "@int:hex"
+);
+def_attr_class!(DROP_COUNT_CLASS, "link.dropCount");
+
+genet_readers!(PcapngFileReader {});
@@ -0,0 +1,270 @@
+extern crate byteorder;
+extern crate genet_sdk;
+extern crate pcap_compress;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use genet_sdk::{prelude::*, writer::*};
+use pcap_compress::{Compression, Encoder};
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+
+const OPT_COMMENT: u16 = 1;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+const EPB_FLAGS: u16 = 2;
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+    /// Defaults to the compression implied by the file extension.
+    compression: Option<Compression>,
+}
+
+#[derive(Clone)]
+struct PcapngFileWriter {}
+
+impl Writer for PcapngFileWriter {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let compression = arg
+            .compression
+            .unwrap_or_else(|| Compression::from_path(&arg.file));
+        let file = File::create(&arg.file)?;
+        let mut worker = PcapngFileWorker {
+            writer: Encoder::new(BufWriter::new(file), compression)?,
+            interfaces: HashMap::new(),
+        };
+        worker.write_section_header()?;
+        Ok(Box::new(worker))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.writer.pcapng-file".into(),
+            filters: vec![FileType::new("Pcapng File", &["pcapng"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct InterfaceKey {
+    id: u64,
+    link: u16,
+}
+
+struct Interface {
+    id: u32,
+    tsresol: u8,
+}
+
+/// Returns the number of timestamp units per second of `if_tsresol`, or None
+/// if it does not fit in 64 bits.
+fn units_per_sec(tsresol: u8) -> Option<u64> {
+    if tsresol & 0x80 == 0 {
+        10u64.checked_pow(u32::from(tsresol))
+    } else {
+        1u64.checked_shl(u32::from(tsresol & 0x7f))
+    }
+}
+
+struct PcapngFileWorker {
+    writer: Encoder<BufWriter<File>>,
+    interfaces: HashMap<InterfaceKey, Interface>,
+}
+
+impl PcapngFileWorker {
+    fn write_block(&mut self, typ: u32, body: &[u8], options: &[u8]) -> Result<()> {
+        let len = (12 + padded(body.len()) + options.len()) as u32;
+        self.writer.write_u32::<LittleEndian>(typ)?;
+        self.writer.write_u32::<LittleEndian>(len)?;
+        self.writer.write_all(body)?;
+        self.writer
+            .write_all(&[0; 3][..padded(body.len()) - body.len()])?;
+        self.writer.write_all(options)?;
+        self.writer.write_u32::<LittleEndian>(len)?;
+        Ok(())
+    }
+
+    fn write_section_header(&mut self) -> Result<()> {
+        let mut body = Vec::new();
+        body.write_u32::<LittleEndian>(0x1a2b_3c4d)?;
+        body.write_u16::<LittleEndian>(1)?;
+        body.write_u16::<LittleEndian>(0)?;
+        body.write_i64::<LittleEndian>(-1)?;
+        let mut options = Options::new();
+        options.push(SHB_USERAPPL, b"genet")?;
+        self.write_block(SECTION_HEADER_BLOCK, &body, &options.end()?)
+    }
+
+    fn interface(&mut self, layer: &Layer) -> Result<(u32, u8)> {
+        let mut key = InterfaceKey { id: 0, link: 0 };
+        // Keep nanoseconds unless the source interface has another resolution.
+        let mut tsresol = if layer.attr(token!("link.timestamp.nsec")).is_some() {
+            9
+        } else {
+            6
+        };
+        let mut name = None;
+        if let Some(attr) = layer.attr(token!("link.interface")) {
+            key.id = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.type")) {
+            let link: i64 = attr.try_get(layer)?.try_into()?;
+            key.link = link as u16;
+        }
+        if let Some(attr) = layer.attr(token!("link.interface.tsresol")) {
+            let value: u64 = attr.try_get(layer)?.try_into()?;
+            // Microseconds for a resolution which does not fit in 64 bits.
+            tsresol = match units_per_sec(value as u8) {
+                Some(_) => value as u8,
+                None => 6,
+            };
+        }
+        if let Some(attr) = layer.attr(token!("link.interface.name")) {
+            let value: String = attr.try_get(layer)?.try_into()?;
+            name = Some(value);
+        }
+
+        if let Some(iface) = self.interfaces.get(&key) {
+            return Ok((iface.id, iface.tsresol));
+        }
+
+        let id = self.interfaces.len() as u32;
+        let mut body = Vec::new();
+        body.write_u16::<LittleEndian>(key.link)?;
+        body.write_u16::<LittleEndian>(0)?;
+        body.write_u32::<LittleEndian>(0)?;
+        let mut options = Options::new();
+        if let Some(name) = name {
+            options.push(IF_NAME, name.as_bytes())?;
+        }
+        if tsresol != 6 {
+            options.push(IF_TSRESOL, &[tsresol])?;
+        }
+        self.write_block(INTERFACE_DESCRIPTION_BLOCK, &body, &options.end()?)?;
+        self.interfaces.insert(key, Interface { id, tsresol });
+        Ok((id, tsresol))
+    }
+}
+
+impl Worker for PcapngFileWorker {
+    fn write(&mut self, _index: u32, stack: &LayerStack) -> Result<()> {
+        if let Some(layer) = stack.bottom() {
+            let (interface, tsresol) = self.interface(layer)?;
+            let data = layer.data();
+            let mut orig_len = data.len() as u64;
+            let mut ts_sec: u64 = 0;
+            let mut ts_nsec = 0;
+
+            if let Some(attr) = layer.attr(token!("link.length")) {
+                orig_len = attr.try_get(layer)?.try_into()?;
+            }
+            if let Some(attr) = layer.attr(token!("link.timestamp.sec")) {
+                ts_sec = attr.try_get(layer)?.try_into()?;
+            }
+            if let Some(attr) = layer.attr(token!("link.timestamp.nsec")) {
+                ts_nsec = attr.try_get(layer)?.try_into()?;
+            } else if let Some(attr) = layer.attr(token!("link.timestamp.usec")) {
+                let usec: u64 = attr.try_get(layer)?.try_into()?;
+                ts_nsec = usec * 1000;
+            }
+
+            let units_per_sec = units_per_sec(tsresol).unwrap_or(1_000_000);
+            let source_tsresol = match layer.attr(token!("link.interface.tsresol")) {
+                Some(attr) => Some(attr.try_get(layer)?.try_into()?),
+                None => None,
+            };
+            let ts = match layer.attr(token!("link.timestamp.raw")) {
+                // Raw ticks are written as is if they have the resolution of
+                // the interface block, so that sub-nanosecond ticks are kept.
+                Some(attr) if source_tsresol == Some(u64::from(tsresol)) => {
+                    attr.try_get(layer)?.try_into()?
+                }
+                _ => {
+                    (u128::from(ts_sec) * u128::from(units_per_sec)
+                        + u128::from(ts_nsec) * u128::from(units_per_sec) / 1_000_000_000)
+                        as u64
+                }
+            };
+
+            let mut body = Vec::new();
+            body.write_u32::<LittleEndian>(interface)?;
+            body.write_u32::<LittleEndian>((ts >> 32) as u32)?;
+            body.write_u32::<LittleEndian>(ts as u32)?;
+            body.write_u32::<LittleEndian>(data.len() as u32)?;
+            body.write_u32::<LittleEndian>(orig_len as u32)?;
+            body.write_all(&data)?;
+            body.resize(padded(body.len()), 0);
+
+            let mut options = Options::new();
+            if let Some(attr) = layer.attr(token!("link.comment")) {
+                let comment: String = attr.try_get(layer)?.try_into()?;
+                for line in comment.split('\n') {
+                    options.push(OPT_COMMENT, line.as_bytes())?;
+                }
+            }
+            if let Some(attr) = layer.attr(token!("link.flags")) {
+                let flags: u64 = attr.try_get(layer)?.try_into()?;
+                let mut value = Vec::new();
+                value.write_u32::<LittleEndian>(flags as u32)?;
+                options.push(EPB_FLAGS, &value)?;
+            }
+            self.write_block(ENHANCED_PACKET_BLOCK, &body, &options.end()?)?;
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+struct Options {
+    data: Vec<u8>,
+}
+
+impl Options {
+    fn new() -> Options {
+        Options { data: Vec::new() }
+    }
+
+    fn push(&mut self, code: u16, value: &[u8]) -> Result<()> {
+        // Option lengths are 16-bit; longer values are truncated.
+        let value = &value[..value.len().min(usize::from(u16::MAX))];
+        self.data.write_u16::<LittleEndian>(code)?;
+        self.data.write_u16::<LittleEndian>(value.len() as u16)?;
+        self.data.extend_from_slice(value);
+        let len = padded(self.data.len());
+        self.data.resize(len, 0);
+        Ok(())
+    }
+
+    fn end(mut self) -> Result<Vec<u8>> {
+        if !self.data.is_empty() {
+            self.data.write_u32::<LittleEndian>(0)?;
+        }
+        Ok(self.data)
+    }
+}
+
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+genet_writers!(PcapngFileWriter {});
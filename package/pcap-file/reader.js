@@ -1,6 +1,43 @@
+const fs = require('fs')
+
+function isStream (file) {
+  if (file === '-') {
+    return true
+  }
+  try {
+    return fs.statSync(file).isFIFO()
+  } catch (err) {
+    return false
+  }
+}
+
+function isCompressed (file) {
+  return ['.gz', '.zst', '.lz4'].some((ext) => file.endsWith(ext))
+}
+
 module.exports = (sess, arg) => {
+  if (isStream(arg.file) || isCompressed(arg.file)) {
+    sess.createReader('app.genet.reader.pcap-stream', arg)
+    return true
+  }
   if (arg.file.endsWith('.pcap')) {
     sess.createReader('app.genet.reader.pcap-file', arg)
     return true
   }
+  if (arg.file.endsWith('.pcapng')) {
+    sess.createReader('app.genet.reader.pcapng-file', arg)
+    return true
+  }
+  if (arg.file.endsWith('.snoop')) {
+    sess.createReader('app.genet.reader.snoop-file', arg)
+    return true
+  }
+  if (arg.file.endsWith('.erf')) {
+    sess.createReader('app.genet.reader.erf-file', arg)
+    return true
+  }
+  if (arg.file.endsWith('.cap')) {
+    sess.createReader('app.genet.reader.netmon-file', arg)
+    return true
+  }
 }
@@ -7,7 +7,7 @@ extern crate serde_json;
 extern crate serde_derive;
 
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
-use genet_sdk::{prelude::*, reader::*};
+use genet_sdk::{layer::FrameMetadata, prelude::*, reader::*};
 use std::{
     fs::File,
     io::{self, BufReader, Error, ErrorKind, Read},
@@ -57,9 +57,16 @@ impl Reader for PcapFileReader {
             )
         };
 
+        // The upper bits of the link type may hold the length of the FCS.
+        let link = network & LINKTYPE_MASK;
+        let mut frame = FrameMetadata::new(link);
+        if network & FCS_LEN_PRESENT != 0 {
+            frame = frame.with_fcs_len((network >> 28) as u8 * 2);
+        }
+
         let link_class = Fixed::new(layer_class!(
-            format!("[link-{}]", network),
-            header: attr!(&TYPE_CLASS, value: i64::from(network))
+            format!("[link-{}]", link),
+            header: attr!(&TYPE_CLASS, value: i64::from(link))
         ));
 
         Ok(Box::new(PcapFileWorker {
@@ -67,6 +74,7 @@ impl Reader for PcapFileReader {
             nsec,
             reader,
             link_class,
+            frame,
         }))
     }
 
@@ -84,11 +92,12 @@ struct PcapFileWorker {
     nsec: bool,
     reader: BufReader<File>,
     link_class: Fixed<LayerClass>,
+    frame: FrameMetadata,
 }
 
 impl PcapFileWorker {
     fn read_one(&mut self) -> io::Result<Layer> {
-        let (ts_sec, mut ts_usec, inc_len, orig_len) = if self.le {
+        let (ts_sec, mut ts_nsec, inc_len, orig_len) = if self.le {
             (
                 self.reader.read_u32::<LittleEndian>()?,
                 self.reader.read_u32::<LittleEndian>()?,
@@ -105,25 +114,27 @@ impl PcapFileWorker {
         };
 
         if !self.nsec {
-            ts_usec *= 1000;
+            ts_nsec *= 1000;
         }
 
-        let mut data = Vec::<u8>::with_capacity(inc_len as usize);
-        unsafe {
-            data.set_len(inc_len as usize);
+        if inc_len as usize > MAX_RECORD_SIZE {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid record length"));
         }
+        let mut data = vec![0; inc_len as usize];
         self.reader.read_exact(&mut data)?;
 
         let payload = ByteSlice::from(data);
         let mut layer = Layer::new(self.link_class.clone(), payload);
+        layer.set_frame_metadata(self.frame);
 
         layer.add_attr(attr!(&LENGTH_CLASS, value: u64::from(orig_len)));
         layer.add_attr(attr!(
             &TS_CLASS,
-            value: f64::from(ts_sec) + f64::from(ts_usec) / 1_000_000f64
+            value: f64::from(ts_sec) + f64::from(ts_nsec) / 1_000_000_000f64
         ));
         layer.add_attr(attr!(&TS_SEC_CLASS, value: u64::from(ts_sec)));
-        layer.add_attr(attr!(&TS_USEC_CLASS, value: u64::from(ts_usec)));
+        layer.add_attr(attr!(&TS_USEC_CLASS, value: u64::from(ts_nsec / 1000)));
+        layer.add_attr(attr!(&TS_NSEC_CLASS, value: u64::from(ts_nsec)));
 
         Ok(layer)
     }
@@ -131,8 +142,15 @@ impl PcapFileWorker {
 
 const BLOCK_SIZE: usize = 65535;
 
+const LINKTYPE_MASK: u32 = 0x03ff_ffff;
+const FCS_LEN_PRESENT: u32 = 0x0400_0000;
+
+/// Records larger than this are treated as corrupted. Packets of offloaded
+/// captures may exceed 64 KiB, but not this.
+const MAX_RECORD_SIZE: usize = 1 << 28;
+
 impl Worker for PcapFileWorker {
-    fn read(&mut self) -> Result<Vec<Layer>> {
+    fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
         let mut layers = Vec::with_capacity(BLOCK_SIZE);
         for _ in 0..BLOCK_SIZE {
             match self.read_one() {
@@ -155,5 +173,6 @@ def_attr_class!(TS_CLASS, "link.timestamp",
 );
 def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
 def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+def_attr_class!(TS_NSEC_CLASS, "link.timestamp.nsec");
 
 genet_readers!(PcapFileReader {});
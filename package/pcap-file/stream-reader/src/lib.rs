@@ -0,0 +1,170 @@
+extern crate byteorder;
+extern crate genet_sdk;
+extern crate pcap_compress;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+mod parse;
+
+use genet_sdk::{layer::FrameMetadata, prelude::*, reader::*};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, Error, ErrorKind, Read},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+const BLOCK_SIZE: usize = 65535;
+const READ_TIMEOUT_MS: u64 = 100;
+
+fn default_file() -> String {
+    "-".into()
+}
+
+fn default_buffer() -> usize {
+    65536
+}
+
+#[derive(Deserialize)]
+struct Arg {
+    /// A path to a named pipe or a file, or `-` for the standard input.
+    /// Gzip, zstd and lz4 compressed streams are decompressed transparently.
+    #[serde(default = "default_file")]
+    file: String,
+    /// The maximum number of records read ahead of the session.
+    #[serde(default = "default_buffer")]
+    buffer: usize,
+}
+
+#[derive(Clone)]
+struct PcapStreamReader {}
+
+impl Reader for PcapStreamReader {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let input: Box<Read + Send> = if arg.file == "-" {
+            Box::new(io::stdin())
+        } else {
+            Box::new(File::open(&arg.file)?)
+        };
+        let input = pcap_compress::decoder(input)?;
+        let (sender, receiver) = mpsc::sync_channel(arg.buffer.max(1));
+        let thread = thread::spawn(move || {
+            parse::parse(BufReader::new(input), |record| sender.send(record).is_ok())
+        });
+        Ok(Box::new(PcapStreamWorker {
+            receiver,
+            thread: Some(thread),
+            classes: LinkClasses::default(),
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.pcap-stream".into(),
+            filters: vec![FileType::new(
+                "Compressed Capture File",
+                &["gz", "zst", "lz4"],
+            )],
+            ..Metadata::default()
+        }
+    }
+}
+
+/// Link layer classes keyed by the link type.
+#[derive(Default)]
+struct LinkClasses {
+    classes: HashMap<u32, Fixed<LayerClass>>,
+}
+
+impl LinkClasses {
+    fn get(&mut self, link: u32) -> Fixed<LayerClass> {
+        self.classes
+            .entry(link)
+            .or_insert_with(|| {
+                Fixed::new(layer_class!(
+                    format!("[link-{}]", link),
+                    header: attr!(&TYPE_CLASS, value: i64::from(link))
+                ))
+            })
+            .clone()
+    }
+}
+
+/// A record read from a pcap or pcapng stream.
+struct Record {
+    link: u32,
+    data: Vec<u8>,
+    orig_len: u64,
+    ts_sec: u64,
+    ts_nsec: u32,
+}
+
+impl Record {
+    fn into_layer(self, classes: &mut LinkClasses) -> Layer {
+        let mut layer = Layer::new(classes.get(self.link), ByteSlice::from(self.data));
+        layer.set_frame_metadata(FrameMetadata::new(self.link));
+        layer.add_attr(attr!(&LENGTH_CLASS, value: self.orig_len));
+        layer.add_attr(attr!(
+            &TS_CLASS,
+            value: self.ts_sec as f64 + f64::from(self.ts_nsec) / 1_000_000_000f64
+        ));
+        layer.add_attr(attr!(&TS_SEC_CLASS, value: self.ts_sec));
+        layer.add_attr(attr!(&TS_USEC_CLASS, value: u64::from(self.ts_nsec / 1000)));
+        layer.add_attr(attr!(&TS_NSEC_CLASS, value: u64::from(self.ts_nsec)));
+        layer
+    }
+}
+
+struct PcapStreamWorker {
+    receiver: Receiver<Record>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+    classes: LinkClasses,
+}
+
+impl PcapStreamWorker {
+    /// Returns the reason the stream has ended.
+    fn finish(&mut self) -> Error {
+        match self.thread.take().map(|thread| thread.join()) {
+            Some(Ok(Err(err))) => err,
+            Some(Err(_)) => Error::new(ErrorKind::Other, "stream reader panicked"),
+            _ => Error::new(ErrorKind::UnexpectedEof, "end of stream"),
+        }
+    }
+}
+
+impl Worker for PcapStreamWorker {
+    fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
+        // Deliver whatever has arrived so far instead of waiting for a full block.
+        let timeout = Duration::from_millis(READ_TIMEOUT_MS);
+        let mut layers = Vec::new();
+        match self.receiver.recv_timeout(timeout) {
+            Ok(record) => layers.push(record.into_layer(&mut self.classes)),
+            Err(RecvTimeoutError::Timeout) => return Ok(layers),
+            Err(RecvTimeoutError::Disconnected) => return Err(self.finish().into()),
+        }
+        while layers.len() < BLOCK_SIZE {
+            match self.receiver.try_recv() {
+                Ok(record) => layers.push(record.into_layer(&mut self.classes)),
+                Err(_) => break,
+            }
+        }
+        Ok(layers)
+    }
+}
+
+def_attr_class!(TYPE_CLASS, "link.type");
+def_attr_class!(LENGTH_CLASS, "link.length");
+def_attr_class!(TS_CLASS, "link.timestamp",
+    typ: "@datetime:unix"
+);
+def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
+def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+def_attr_class!(TS_NSEC_CLASS, "link.timestamp.nsec");
+
+genet_readers!(PcapStreamReader {});
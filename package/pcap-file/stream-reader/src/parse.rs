@@ -0,0 +1,288 @@
+//! Incremental parsers for pcap and pcapng streams.
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::io::{self, Error, ErrorKind, Read};
+use Record;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const PACKET_BLOCK: u32 = 0x0000_0002;
+const SIMPLE_PACKET_BLOCK: u32 = 0x0000_0003;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_IF_TSRESOL: u16 = 9;
+
+/// Records larger than this are treated as corrupted.
+const MAX_RECORD_SIZE: usize = 1 << 28;
+
+#[derive(Clone, Copy)]
+struct Endian {
+    little: bool,
+}
+
+impl Endian {
+    fn u16(self, buf: &[u8]) -> u16 {
+        if self.little {
+            LittleEndian::read_u16(buf)
+        } else {
+            BigEndian::read_u16(buf)
+        }
+    }
+
+    fn u32(self, buf: &[u8]) -> u32 {
+        if self.little {
+            LittleEndian::read_u32(buf)
+        } else {
+            BigEndian::read_u32(buf)
+        }
+    }
+}
+
+fn truncated() -> Error {
+    Error::new(
+        ErrorKind::UnexpectedEof,
+        "truncated record at end of stream",
+    )
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Fills the buffer, returning false if the stream ends before the first byte.
+fn fill<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    if len == buf.len() {
+        Ok(true)
+    } else if len == 0 {
+        Ok(false)
+    } else {
+        Err(truncated())
+    }
+}
+
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<()> {
+    if buf.is_empty() || fill(reader, buf)? {
+        Ok(())
+    } else {
+        Err(truncated())
+    }
+}
+
+/// Parses records until the stream ends or the callback returns false.
+/// The stream may end cleanly only at a record boundary.
+pub fn parse<R: Read, F: FnMut(Record) -> bool>(mut reader: R, f: F) -> io::Result<()> {
+    let mut magic = [0u8; 4];
+    if !fill(&mut reader, &mut magic)? {
+        return Ok(());
+    }
+    if BigEndian::read_u32(&magic) == SECTION_HEADER_BLOCK {
+        parse_pcapng(reader, magic, f)
+    } else {
+        parse_pcap(reader, magic, f)
+    }
+}
+
+fn parse_pcap<R: Read, F: FnMut(Record) -> bool>(
+    mut reader: R,
+    magic: [u8; 4],
+    mut f: F,
+) -> io::Result<()> {
+    let (little, nsec) = match BigEndian::read_u32(&magic) {
+        0xd4c3_b2a1 => (true, false),
+        0xa1b2_c3d4 => (false, false),
+        0x4d3c_b2a1 => (true, true),
+        0xa1b2_3c4d => (false, true),
+        _ => return Err(invalid("wrong magic number")),
+    };
+    let endian = Endian { little };
+
+    let mut header = [0u8; 20];
+    read_exact(&mut reader, &mut header)?;
+    let link = endian.u32(&header[16..]);
+
+    let mut rec = [0u8; 16];
+    while fill(&mut reader, &mut rec)? {
+        let ts_sec = endian.u32(&rec[0..]);
+        let ts_frac = endian.u32(&rec[4..]);
+        let incl_len = endian.u32(&rec[8..]) as usize;
+        let orig_len = endian.u32(&rec[12..]);
+        if incl_len > MAX_RECORD_SIZE {
+            return Err(invalid("record too large"));
+        }
+        let mut data = vec![0u8; incl_len];
+        read_exact(&mut reader, &mut data)?;
+        let record = Record {
+            link,
+            data,
+            orig_len: u64::from(orig_len),
+            ts_sec: u64::from(ts_sec),
+            ts_nsec: if nsec { ts_frac } else { ts_frac * 1000 },
+        };
+        if !f(record) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Resolution {
+    Decimal(u32),
+    Binary(u32),
+}
+
+impl Resolution {
+    /// Splits a timestamp into seconds and nanoseconds.
+    fn split(self, ts: u64) -> (u64, u32) {
+        match self {
+            Resolution::Decimal(exp) => {
+                let unit = 10u64.pow(exp.min(19));
+                let frac = u128::from(ts % unit) * 1_000_000_000 / u128::from(unit);
+                (ts / unit, frac as u32)
+            }
+            Resolution::Binary(exp) => {
+                let exp = exp.min(63);
+                let frac = (u128::from(ts & ((1 << exp) - 1)) * 1_000_000_000) >> exp;
+                (ts >> exp, frac as u32)
+            }
+        }
+    }
+}
+
+struct Interface {
+    link: u32,
+    resolution: Resolution,
+}
+
+fn parse_interface(endian: Endian, body: &[u8]) -> io::Result<Interface> {
+    if body.len() < 8 {
+        return Err(invalid("interface description block too short"));
+    }
+    let mut iface = Interface {
+        link: u32::from(endian.u16(&body[0..])),
+        resolution: Resolution::Decimal(6),
+    };
+    let mut opts = &body[8..];
+    while opts.len() >= 4 {
+        let code = endian.u16(&opts[0..]);
+        let len = endian.u16(&opts[2..]) as usize;
+        if code == OPT_ENDOFOPT || opts.len() < 4 + len {
+            break;
+        }
+        if code == OPT_IF_TSRESOL && len >= 1 {
+            let value = opts[4];
+            iface.resolution = if value & 0x80 != 0 {
+                Resolution::Binary(u32::from(value & 0x7f))
+            } else {
+                Resolution::Decimal(u32::from(value))
+            };
+        }
+        opts = &opts[(4 + ((len + 3) & !3)).min(opts.len())..];
+    }
+    Ok(iface)
+}
+
+fn parse_pcapng<R: Read, F: FnMut(Record) -> bool>(
+    mut reader: R,
+    magic: [u8; 4],
+    mut f: F,
+) -> io::Result<()> {
+    let mut endian = Endian { little: true };
+    let mut interfaces: Vec<Interface> = Vec::new();
+
+    let mut head = [0u8; 8];
+    head[..4].copy_from_slice(&magic);
+    read_exact(&mut reader, &mut head[4..])?;
+    loop {
+        let mut typ = endian.u32(&head[0..]);
+        let mut consumed = 8;
+        if BigEndian::read_u32(&head[0..]) == SECTION_HEADER_BLOCK {
+            typ = SECTION_HEADER_BLOCK;
+            let mut bom = [0u8; 4];
+            read_exact(&mut reader, &mut bom)?;
+            endian.little = match LittleEndian::read_u32(&bom) {
+                BYTE_ORDER_MAGIC => true,
+                _ if BigEndian::read_u32(&bom) == BYTE_ORDER_MAGIC => false,
+                _ => return Err(invalid("wrong byte-order magic")),
+            };
+            interfaces.clear();
+            consumed += 4;
+        }
+
+        let len = endian.u32(&head[4..]) as usize;
+        if len < consumed + 4 || len & 3 != 0 || len > MAX_RECORD_SIZE {
+            return Err(invalid("wrong block length"));
+        }
+        let mut body = vec![0u8; len - consumed];
+        read_exact(&mut reader, &mut body)?;
+        let body = &body[..body.len() - 4];
+
+        let record = match typ {
+            INTERFACE_DESCRIPTION_BLOCK => {
+                interfaces.push(parse_interface(endian, body)?);
+                None
+            }
+            ENHANCED_PACKET_BLOCK | PACKET_BLOCK if body.len() >= 20 => {
+                let id = if typ == PACKET_BLOCK {
+                    u32::from(endian.u16(&body[0..]))
+                } else {
+                    endian.u32(&body[0..])
+                };
+                let iface = interfaces
+                    .get(id as usize)
+                    .ok_or_else(|| invalid("unknown interface"))?;
+                let ts =
+                    u64::from(endian.u32(&body[4..])) << 32 | u64::from(endian.u32(&body[8..]));
+                let caplen = endian.u32(&body[12..]) as usize;
+                let orig_len = endian.u32(&body[16..]);
+                let data = body
+                    .get(20..20 + caplen)
+                    .ok_or_else(|| invalid("wrong captured length"))?;
+                let (ts_sec, ts_nsec) = iface.resolution.split(ts);
+                Some(Record {
+                    link: iface.link,
+                    data: data.to_vec(),
+                    orig_len: u64::from(orig_len),
+                    ts_sec,
+                    ts_nsec,
+                })
+            }
+            SIMPLE_PACKET_BLOCK if body.len() >= 4 => {
+                let iface = interfaces
+                    .first()
+                    .ok_or_else(|| invalid("unknown interface"))?;
+                let orig_len = endian.u32(&body[0..]);
+                let caplen = (orig_len as usize).min(body.len() - 4);
+                Some(Record {
+                    link: iface.link,
+                    data: body[4..4 + caplen].to_vec(),
+                    orig_len: u64::from(orig_len),
+                    ts_sec: 0,
+                    ts_nsec: 0,
+                })
+            }
+            _ => None,
+        };
+
+        if let Some(record) = record {
+            if !f(record) {
+                break;
+            }
+        }
+        if !fill(&mut reader, &mut head)? {
+            break;
+        }
+    }
+    Ok(())
+}
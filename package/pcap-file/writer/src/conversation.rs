@@ -0,0 +1,183 @@
+//! Splits frames into one pcap file per conversation.
+
+use genet_sdk::{prelude::*, variant::Variant, writer::*};
+use serde_json;
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::BufWriter,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+};
+use PcapFile;
+
+fn default_max_open() -> usize {
+    64
+}
+
+#[derive(Deserialize)]
+struct Arg {
+    directory: String,
+    #[serde(default = "default_max_open")]
+    max_open: usize,
+}
+
+#[derive(Clone)]
+pub struct ConversationWriter {}
+
+impl Writer for ConversationWriter {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        fs::create_dir_all(&arg.directory)?;
+        Ok(Box::new(ConversationWorker {
+            directory: PathBuf::from(arg.directory),
+            max_open: arg.max_open.max(1),
+            files: HashMap::new(),
+            networks: HashMap::new(),
+            clock: 0,
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.writer.pcap-conversations".into(),
+            ..Metadata::default()
+        }
+    }
+}
+
+fn address(layer: &Layer, id: Token) -> Option<String> {
+    match layer.attr(id)?.try_get(layer).ok()? {
+        Variant::Slice(b) if b.len() == 4 => {
+            Some(Ipv4Addr::new(b[0], b[1], b[2], b[3]).to_string())
+        }
+        Variant::Slice(b) if b.len() == 16 => {
+            let mut addr = [0u8; 16];
+            addr.copy_from_slice(&b);
+            // Colons are not allowed in file names on some platforms.
+            Some(Ipv6Addr::from(addr).to_string().replace(':', "."))
+        }
+        _ => None,
+    }
+}
+
+fn port(layer: &Layer, id: Token) -> Option<u64> {
+    layer.attr(id)?.try_get(layer).ok()?.try_into().ok()
+}
+
+/// Returns a file name identifying the conversation of the frame, which is
+/// the same for both directions.
+fn conversation_key(stack: &LayerStack) -> String {
+    let network = stack
+        .layer(token!("ipv4"))
+        .map(|layer| ("ipv4", layer))
+        .or_else(|| stack.layer(token!("ipv6")).map(|layer| ("ipv6", layer)));
+    let (proto, layer) = match network {
+        Some(network) => network,
+        None => return "other".into(),
+    };
+    let (src, dst) = match (
+        address(layer, Token::from(format!("{}.src", proto))),
+        address(layer, Token::from(format!("{}.dst", proto))),
+    ) {
+        (Some(src), Some(dst)) => (src, dst),
+        _ => return "other".into(),
+    };
+
+    for transport in &["tcp", "udp"] {
+        if let Some(layer) = stack.layer(Token::from(*transport)) {
+            let src_port = port(layer, Token::from(format!("{}.src", transport)));
+            let dst_port = port(layer, Token::from(format!("{}.dst", transport)));
+            if let (Some(src_port), Some(dst_port)) = (src_port, dst_port) {
+                let mut ends = [
+                    format!("{}_{}", src, src_port),
+                    format!("{}_{}", dst, dst_port),
+                ];
+                ends.sort();
+                return format!("{}_{}-{}", transport, ends[0], ends[1]);
+            }
+        }
+    }
+
+    let mut ends = [src, dst];
+    ends.sort();
+    format!("{}_{}-{}", proto, ends[0], ends[1])
+}
+
+struct OpenFile {
+    file: PcapFile<BufWriter<File>>,
+    last_used: u64,
+}
+
+struct ConversationWorker {
+    directory: PathBuf,
+    max_open: usize,
+    files: HashMap<String, OpenFile>,
+    networks: HashMap<String, Option<u32>>,
+    clock: u64,
+}
+
+impl ConversationWorker {
+    fn open(&mut self, key: &str) -> Result<()> {
+        if self.files.contains_key(key) {
+            return Ok(());
+        }
+        if self.files.len() >= self.max_open {
+            self.close_oldest()?;
+        }
+        let path = self.directory.join(format!("{}.pcap", key));
+        let file = match self.networks.get(key) {
+            Some(network) => {
+                let file = OpenOptions::new().append(true).open(path)?;
+                PcapFile::resume(BufWriter::new(file), *network)
+            }
+            None => PcapFile::new(BufWriter::new(File::create(path)?))?,
+        };
+        self.files.insert(
+            key.to_string(),
+            OpenFile {
+                file,
+                last_used: self.clock,
+            },
+        );
+        Ok(())
+    }
+
+    /// Closes the least recently used file to limit the number of open files.
+    fn close_oldest(&mut self) -> Result<()> {
+        let oldest = self
+            .files
+            .iter()
+            .min_by_key(|(_, f)| f.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = oldest {
+            if let Some(mut open) = self.files.remove(&key) {
+                open.file.flush()?;
+                self.networks.insert(key, open.file.network);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Worker for ConversationWorker {
+    fn write(&mut self, _index: u32, stack: &LayerStack) -> Result<()> {
+        if let Some(layer) = stack.bottom() {
+            let key = conversation_key(stack);
+            self.open(&key)?;
+            self.clock += 1;
+            if let Some(open) = self.files.get_mut(&key) {
+                open.last_used = self.clock;
+                open.file.write(layer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        for open in self.files.values_mut() {
+            open.file.flush()?;
+        }
+        Ok(())
+    }
+}
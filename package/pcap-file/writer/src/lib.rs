@@ -1,22 +1,28 @@
 extern crate byteorder;
 extern crate genet_sdk;
+extern crate pcap_compress;
 extern crate serde;
 extern crate serde_json;
 
 #[macro_use]
 extern crate serde_derive;
 
+mod conversation;
+
 use byteorder::{LittleEndian, WriteBytesExt};
 use genet_sdk::{prelude::*, writer::*};
+use pcap_compress::{Compression, Encoder};
 
 use std::{
     fs::File,
-    io::{BufWriter, Write},
+    io::{BufWriter, Error, ErrorKind, Write},
 };
 
 #[derive(Deserialize)]
 struct Arg {
     file: String,
+    /// Defaults to the compression implied by the file extension.
+    compression: Option<Compression>,
 }
 
 #[derive(Clone)]
@@ -25,12 +31,12 @@ struct PcapFileWriter {}
 impl Writer for PcapFileWriter {
     fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
         let arg: Arg = serde_json::from_str(arg)?;
-        let file = File::create(&arg.file)?;
-        let mut writer = BufWriter::new(file);
-        writer.write_all(&[0x4d, 0x3c, 0xb2, 0xa1])?;
+        let compression = arg
+            .compression
+            .unwrap_or_else(|| Compression::from_path(&arg.file));
+        let file = Encoder::new(BufWriter::new(File::create(&arg.file)?), compression)?;
         Ok(Box::new(PcapFileWorker {
-            writer,
-            header: false,
+            file: PcapFile::new(file)?,
         }))
     }
 
@@ -43,15 +49,37 @@ impl Writer for PcapFileWriter {
     }
 }
 
-struct PcapFileWorker {
-    writer: BufWriter<File>,
-    header: bool,
+/// A pcap file with nanosecond timestamps.
+struct PcapFile<W: Write> {
+    writer: W,
+    network: Option<u32>,
 }
 
-impl PcapFileWorker {
+impl<W: Write> PcapFile<W> {
+    fn new(mut writer: W) -> Result<PcapFile<W>> {
+        writer.write_all(&[0x4d, 0x3c, 0xb2, 0xa1])?;
+        Ok(PcapFile {
+            writer,
+            network: None,
+        })
+    }
+
+    /// Continues writing to a file whose header has been written.
+    fn resume(writer: W, network: Option<u32>) -> PcapFile<W> {
+        PcapFile { writer, network }
+    }
+
     fn write_header(&mut self, snaplen: u32, network: u32) -> Result<()> {
-        if !self.header {
-            self.header = true;
+        if let Some(header) = self.network {
+            if header != network {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "pcap does not support multiple link types; use pcapng instead",
+                )
+                .into());
+            }
+        } else {
+            self.network = Some(network);
             let var_major = 2u16;
             let var_minor = 4u16;
             let thiszone = 0i32;
@@ -65,40 +93,62 @@ impl PcapFileWorker {
         }
         Ok(())
     }
+
+    fn write(&mut self, layer: &Layer) -> Result<()> {
+        let incl_len = layer.data().len();
+        let mut orig_len = 0;
+        let mut ts_sec = 0;
+        let mut ts_nsec = 0;
+        let mut link = 0;
+
+        if let Some(attr) = layer.attr(token!("link.length")) {
+            orig_len = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.type")) {
+            link = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.timestamp.sec")) {
+            ts_sec = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.timestamp.nsec")) {
+            ts_nsec = attr.try_get(layer)?.try_into()?;
+        } else if let Some(attr) = layer.attr(token!("link.timestamp.usec")) {
+            let ts_usec: u64 = attr.try_get(layer)?.try_into()?;
+            ts_nsec = ts_usec * 1000;
+        }
+
+        self.write_header(0, link as u32)?;
+
+        self.writer.write_u32::<LittleEndian>(ts_sec as u32)?;
+        self.writer.write_u32::<LittleEndian>(ts_nsec as u32)?;
+        self.writer.write_u32::<LittleEndian>(incl_len as u32)?;
+        self.writer.write_u32::<LittleEndian>(orig_len as u32)?;
+        self.writer.write_all(&layer.data())?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+struct PcapFileWorker {
+    file: PcapFile<Encoder<BufWriter<File>>>,
 }
 
 impl Worker for PcapFileWorker {
     fn write(&mut self, _index: u32, stack: &LayerStack) -> Result<()> {
         if let Some(layer) = stack.bottom() {
-            let incl_len = layer.data().len();
-            let mut orig_len = 0;
-            let mut ts_sec = 0;
-            let mut ts_usec = 0;
-            let mut link = 0;
-
-            if let Some(attr) = layer.attr(token!("link.length")) {
-                orig_len = attr.try_get(layer)?.try_into()?;
-            }
-            if let Some(attr) = layer.attr(token!("link.type")) {
-                link = attr.try_get(layer)?.try_into()?;
-            }
-            if let Some(attr) = layer.attr(token!("link.timestamp.sec")) {
-                ts_sec = attr.try_get(layer)?.try_into()?;
-            }
-            if let Some(attr) = layer.attr(token!("link.timestamp.usec")) {
-                ts_usec = attr.try_get(layer)?.try_into()?;
-            }
-
-            self.write_header(0, link as u32)?;
-
-            self.writer.write_u32::<LittleEndian>(ts_sec as u32)?;
-            self.writer.write_u32::<LittleEndian>(ts_usec as u32)?;
-            self.writer.write_u32::<LittleEndian>(incl_len as u32)?;
-            self.writer.write_u32::<LittleEndian>(orig_len as u32)?;
-            self.writer.write_all(&layer.data())?;
+            self.file.write(layer)?;
         }
         Ok(())
     }
+
+    fn end(&mut self) -> Result<()> {
+        self.file.writer.finish()?;
+        Ok(())
+    }
 }
 
-genet_writers!(PcapFileWriter {});
+genet_writers!(PcapFileWriter {}, conversation::ConversationWriter {});
@@ -0,0 +1,314 @@
+//! BPF device capture for macOS and BSDs.
+
+use genet_sdk::reader::CaptureStats;
+use libc::{self, c_int, c_uint, c_ulong, c_void};
+use std::{ffi::CString, io, mem, ptr, slice};
+use {Instruction, Options, Packet};
+
+const BIOCGBLEN: c_ulong = 0x4004_4266;
+const BIOCSBLEN: c_ulong = 0xc004_4266;
+const BIOCSETF: c_ulong = 0x8000_4267 | ((mem::size_of::<BpfProgram>() as c_ulong) << 16);
+const BIOCPROMISC: c_ulong = 0x2000_4269;
+const BIOCGDLT: c_ulong = 0x4004_426a;
+const BIOCSETIF: c_ulong = 0x8020_426c;
+const BIOCGSTATS: c_ulong = 0x4008_426f;
+const BIOCIMMEDIATE: c_ulong = 0x8004_4270;
+const BIOCSHDRCMPLT: c_ulong = 0x8004_4275;
+const BIOCSDLT: c_ulong = 0x8004_4278;
+
+const DLT_IEEE802_11_RADIO: c_uint = 127;
+
+const BUFFER_SIZE: c_uint = 1 << 20;
+
+#[cfg(target_os = "macos")]
+const ALIGNMENT: usize = 4;
+
+#[cfg(not(target_os = "macos"))]
+const ALIGNMENT: usize = mem::size_of::<libc::c_long>();
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+struct Timeval {
+    tv_sec: i32,
+    tv_usec: i32,
+}
+
+#[cfg(not(target_os = "macos"))]
+type Timeval = libc::timeval;
+
+#[repr(C)]
+struct BpfHdr {
+    bh_tstamp: Timeval,
+    bh_caplen: u32,
+    bh_datalen: u32,
+    bh_hdrlen: u16,
+}
+
+#[repr(C)]
+struct BpfInsn {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+#[repr(C)]
+struct BpfProgram {
+    bf_len: c_uint,
+    bf_insns: *const BpfInsn,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct BpfStat {
+    bs_recv: c_uint,
+    bs_drop: c_uint,
+}
+
+#[repr(C)]
+struct Ifreq {
+    ifr_name: [u8; 16],
+    _pad: [u8; 16],
+}
+
+pub struct Capture {
+    fd: c_int,
+    buffer: Vec<u8>,
+    link: u32,
+    snaplen: u32,
+}
+
+fn check(ret: c_int) -> io::Result<c_int> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+fn open_device() -> io::Result<c_int> {
+    for i in 0..256 {
+        let path = CString::new(format!("/dev/bpf{}", i))?;
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_RDWR) };
+        if fd >= 0 {
+            return Ok(fd);
+        }
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::EBUSY) {
+            return Err(err);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "no bpf device available",
+    ))
+}
+
+impl Capture {
+    pub fn open(opt: &Options) -> io::Result<Capture> {
+        let fd = open_device()?;
+        let mut capture = Capture {
+            fd,
+            buffer: Vec::new(),
+            link: 0,
+            snaplen: opt.snaplen,
+        };
+
+        let mut len = BUFFER_SIZE;
+        unsafe {
+            check(libc::ioctl(fd, BIOCSBLEN, &mut len as *mut c_uint))?;
+            check(libc::ioctl(fd, BIOCGBLEN, &mut len as *mut c_uint))?;
+        }
+        capture.buffer = vec![0; len as usize];
+
+        let mut req: Ifreq = unsafe { mem::zeroed() };
+        let name = opt.interface.as_bytes();
+        let name_len = name.len().min(req.ifr_name.len() - 1);
+        req.ifr_name[..name_len].copy_from_slice(&name[..name_len]);
+
+        let mut immediate: c_uint = 1;
+        let mut link: c_uint = 0;
+        unsafe {
+            check(libc::ioctl(fd, BIOCSETIF, &mut req as *mut Ifreq))?;
+            check(libc::ioctl(
+                fd,
+                BIOCIMMEDIATE,
+                &mut immediate as *mut c_uint,
+            ))?;
+            if opt.monitor() {
+                // Selecting the radiotap link type enables monitor mode.
+                let mut dlt = DLT_IEEE802_11_RADIO;
+                check(libc::ioctl(fd, BIOCSDLT, &mut dlt as *mut c_uint))?;
+            }
+            check(libc::ioctl(fd, BIOCGDLT, &mut link as *mut c_uint))?;
+            if opt.promisc {
+                check(libc::ioctl(fd, BIOCPROMISC, ptr::null_mut::<c_void>()))?;
+            }
+        }
+        capture.link = link;
+
+        match &opt.filter {
+            Some(filter) => capture.set_filter(filter)?,
+            None => capture.set_filter(&[])?,
+        }
+
+        Ok(capture)
+    }
+
+    /// BPF provides no way to tune a wireless interface.
+    pub fn set_frequency(_interface: &str, _mhz: u32) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "channel control is not supported on this platform",
+        ))
+    }
+
+    pub fn link(&self) -> u32 {
+        self.link
+    }
+
+    pub fn set_filter(&mut self, filter: &[Instruction]) -> io::Result<()> {
+        // BPF has no snaplen option; the filter return value truncates packets.
+        const BPF_RET_K: u16 = 0x06;
+        let snaplen = self.snaplen;
+        let mut insns = filter
+            .iter()
+            .map(|i| BpfInsn {
+                code: i.code,
+                jt: i.jt,
+                jf: i.jf,
+                k: if i.code == BPF_RET_K {
+                    i.k.min(snaplen)
+                } else {
+                    i.k
+                },
+            })
+            .collect::<Vec<_>>();
+        if insns.is_empty() {
+            insns.push(BpfInsn {
+                code: BPF_RET_K,
+                jt: 0,
+                jf: 0,
+                k: snaplen,
+            });
+        }
+        let mut prog = BpfProgram {
+            bf_len: insns.len() as c_uint,
+            bf_insns: insns.as_ptr(),
+        };
+        unsafe {
+            check(libc::ioctl(self.fd, BIOCSETF, &mut prog as *mut BpfProgram))?;
+        }
+        Ok(())
+    }
+
+    /// BPF counts the frames dropped by the buffer only.
+    pub fn stats(&mut self) -> io::Result<CaptureStats> {
+        let mut stat = BpfStat::default();
+        unsafe {
+            check(libc::ioctl(self.fd, BIOCGSTATS, &mut stat as *mut BpfStat))?;
+        }
+        Ok(CaptureStats {
+            received: u64::from(stat.bs_recv),
+            dropped: u64::from(stat.bs_drop),
+            ..CaptureStats::default()
+        })
+    }
+
+    pub fn read<F: FnMut(Packet)>(&mut self, timeout: i32, mut f: F) -> io::Result<()> {
+        let mut pfd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { check(libc::poll(&mut pfd, 1, timeout))? } == 0 {
+            return Ok(());
+        }
+
+        let len = unsafe {
+            libc::read(
+                self.fd,
+                self.buffer.as_mut_ptr() as *mut c_void,
+                self.buffer.len(),
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let len = len as usize;
+        let mut offset = 0;
+        while offset + mem::size_of::<BpfHdr>() <= len {
+            let hdr = unsafe { &*(self.buffer.as_ptr().add(offset) as *const BpfHdr) };
+            let start = offset + hdr.bh_hdrlen as usize;
+            let end = (start + hdr.bh_caplen as usize).min(len);
+            let data =
+                unsafe { slice::from_raw_parts(self.buffer.as_ptr().add(start), end - start) };
+            f(Packet {
+                ts_sec: hdr.bh_tstamp.tv_sec as u64,
+                ts_nsec: hdr.bh_tstamp.tv_usec as u32 * 1000,
+                len: hdr.bh_datalen,
+                data,
+                queue: None,
+                cpu: None,
+            });
+            offset += (hdr.bh_hdrlen as usize + hdr.bh_caplen as usize + ALIGNMENT - 1)
+                & !(ALIGNMENT - 1);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// A BPF device transmitting frames on an interface.
+pub struct Injector {
+    fd: c_int,
+}
+
+impl Injector {
+    pub fn open(interface: &str) -> io::Result<Injector> {
+        let injector = Injector { fd: open_device()? };
+        let mut req: Ifreq = unsafe { mem::zeroed() };
+        let name = interface.as_bytes();
+        let name_len = name.len().min(req.ifr_name.len() - 1);
+        req.ifr_name[..name_len].copy_from_slice(&name[..name_len]);
+
+        // The source addresses of the frames are sent as they are.
+        let mut complete: c_uint = 1;
+        unsafe {
+            check(libc::ioctl(injector.fd, BIOCSETIF, &mut req as *mut Ifreq))?;
+            check(libc::ioctl(
+                injector.fd,
+                BIOCSHDRCMPLT,
+                &mut complete as *mut c_uint,
+            ))?;
+        }
+        Ok(injector)
+    }
+
+    pub fn send(&self, data: &[u8]) -> io::Result<()> {
+        let len = unsafe { libc::write(self.fd, data.as_ptr() as *const c_void, data.len()) };
+        if len < 0 {
+            Err(io::Error::last_os_error())
+        } else if (len as usize) < data.len() {
+            Err(io::Error::new(io::ErrorKind::WriteZero, "frame truncated"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for Injector {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
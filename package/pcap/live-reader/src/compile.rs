@@ -0,0 +1,802 @@
+//! Compiles tcpdump-style capture filters to classic BPF.
+//!
+//! Supported primitives:
+//!
+//! - `ip`, `ip6`, `arp`, `tcp`, `udp`, `icmp`, `icmp6`
+//! - `[ip|ip6] [src|dst] host <addr>`
+//! - `[src|dst] net <addr>/<len>`
+//! - `[tcp|udp] [src|dst] port <port>`
+//! - `less <len>`, `greater <len>`
+//!
+//! combined with `and` (`&&`), `or` (`||`), `not` (`!`) and parentheses.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr},
+};
+use Instruction;
+
+const BPF_LD: u16 = 0x00;
+const BPF_LDX: u16 = 0x01;
+const BPF_ALU: u16 = 0x04;
+const BPF_JMP: u16 = 0x05;
+const BPF_RET: u16 = 0x06;
+
+const BPF_W: u16 = 0x00;
+const BPF_H: u16 = 0x08;
+const BPF_B: u16 = 0x10;
+
+const BPF_ABS: u16 = 0x20;
+const BPF_IND: u16 = 0x40;
+const BPF_LEN: u16 = 0x80;
+const BPF_MSH: u16 = 0xa0;
+
+const BPF_AND: u16 = 0x50;
+
+const BPF_JA: u16 = 0x00;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGT: u16 = 0x20;
+const BPF_JGE: u16 = 0x30;
+const BPF_JSET: u16 = 0x40;
+
+const ETHERTYPE_IPV4: u32 = 0x0800;
+const ETHERTYPE_ARP: u32 = 0x0806;
+const ETHERTYPE_IPV6: u32 = 0x86dd;
+
+const PROTO_ICMP: u32 = 1;
+const PROTO_TCP: u32 = 6;
+const PROTO_UDP: u32 = 17;
+const PROTO_ICMPV6: u32 = 58;
+
+fn error<T: Into<String>>(msg: T) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg.into())
+}
+
+/// Offsets of the network layer for a link type.
+#[derive(Clone, Copy)]
+struct Layout {
+    ethertype: Option<u32>,
+    l3: u32,
+}
+
+impl Layout {
+    fn new(link: u32) -> io::Result<Layout> {
+        match link {
+            // Ethernet
+            1 => Ok(Layout {
+                ethertype: Some(12),
+                l3: 14,
+            }),
+            // Linux cooked capture
+            113 => Ok(Layout {
+                ethertype: Some(14),
+                l3: 16,
+            }),
+            // Raw IP
+            12 | 14 | 101 | 228 | 229 => Ok(Layout {
+                ethertype: None,
+                l3: 0,
+            }),
+            _ => Err(error(format!(
+                "capture filters are not supported on link type {}",
+                link
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Load {
+    Abs(u16, u32),
+    /// Relative to the end of the IPv4 header.
+    Ind(u16, u32),
+    Len,
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Eq,
+    Gt,
+    Ge,
+    Set,
+}
+
+enum Expr {
+    Const(bool),
+    Test(Load, Option<u32>, Op, u32),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+fn and(a: Expr, b: Expr) -> Expr {
+    Expr::And(Box::new(a), Box::new(b))
+}
+
+fn or(a: Expr, b: Expr) -> Expr {
+    Expr::Or(Box::new(a), Box::new(b))
+}
+
+fn not(a: Expr) -> Expr {
+    Expr::Not(Box::new(a))
+}
+
+fn any<I: Iterator<Item = Expr>>(exprs: I) -> Expr {
+    exprs
+        .fold(None, |acc, expr| match acc {
+            Some(acc) => Some(or(acc, expr)),
+            None => Some(expr),
+        })
+        .unwrap_or(Expr::Const(false))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Dir {
+    Src,
+    Dst,
+    Any,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Family {
+    V4,
+    V6,
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+    layout: Layout,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> io::Result<&'a str> {
+        let token = self
+            .peek()
+            .ok_or_else(|| error("unexpected end of filter"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn accept(&mut self, words: &[&str]) -> bool {
+        match self.peek() {
+            Some(token) if words.contains(&token) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> io::Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.accept(&["or", "||"]) {
+            expr = or(expr, self.parse_and()?);
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> io::Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        while self.accept(&["and", "&&"]) {
+            expr = and(expr, self.parse_unary()?);
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> io::Result<Expr> {
+        if self.accept(&["not", "!"]) {
+            return Ok(not(self.parse_unary()?));
+        }
+        if self.accept(&["("]) {
+            let expr = self.parse_or()?;
+            if !self.accept(&[")"]) {
+                return Err(error("expected ')'"));
+            }
+            return Ok(expr);
+        }
+        self.parse_primitive()
+    }
+
+    fn parse_primitive(&mut self) -> io::Result<Expr> {
+        let proto = match self.peek() {
+            Some(p @ "ip") | Some(p @ "ip6") | Some(p @ "arp") | Some(p @ "tcp")
+            | Some(p @ "udp") | Some(p @ "icmp") | Some(p @ "icmp6") => {
+                self.pos += 1;
+                Some(p)
+            }
+            _ => None,
+        };
+        let dir = if self.accept(&["src"]) {
+            Dir::Src
+        } else if self.accept(&["dst"]) {
+            Dir::Dst
+        } else {
+            Dir::Any
+        };
+        match self.peek() {
+            Some("host") => {
+                self.pos += 1;
+                let addr = self.next()?;
+                let addr = addr
+                    .parse::<IpAddr>()
+                    .map_err(|_| error(format!("invalid address: {}", addr)))?;
+                match (proto, addr) {
+                    (None, IpAddr::V4(_)) | (Some("ip"), IpAddr::V4(_)) => {}
+                    (None, IpAddr::V6(_)) | (Some("ip6"), IpAddr::V6(_)) => {}
+                    _ => return Err(error("unexpected qualifier for host")),
+                }
+                Ok(self.host(dir, addr))
+            }
+            Some("net") => {
+                self.pos += 1;
+                if proto.is_some() && proto != Some("ip") {
+                    return Err(error("unexpected qualifier for net"));
+                }
+                let net = self.next()?;
+                self.net(dir, net)
+            }
+            Some("port") => {
+                self.pos += 1;
+                let port = self.next()?;
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| error(format!("invalid port: {}", port)))?;
+                let protos: &[u32] = match proto {
+                    None => &[PROTO_TCP, PROTO_UDP],
+                    Some("tcp") => &[PROTO_TCP],
+                    Some("udp") => &[PROTO_UDP],
+                    _ => return Err(error("unexpected qualifier for port")),
+                };
+                Ok(self.port(dir, protos, u32::from(port)))
+            }
+            Some("less") | Some("greater") if proto.is_none() && dir == Dir::Any => {
+                let op = self.next()?;
+                let len = self.next()?;
+                let len = len
+                    .parse::<u32>()
+                    .map_err(|_| error(format!("invalid length: {}", len)))?;
+                Ok(if op == "less" {
+                    not(Expr::Test(Load::Len, None, Op::Gt, len))
+                } else {
+                    Expr::Test(Load::Len, None, Op::Ge, len)
+                })
+            }
+            _ => match proto {
+                Some(proto) if dir == Dir::Any => Ok(self.proto(proto)),
+                _ => Err(error(format!(
+                    "unexpected token: {}",
+                    self.peek().unwrap_or("end of filter")
+                ))),
+            },
+        }
+    }
+
+    fn ethertype(&self, typ: u32) -> Expr {
+        match self.layout.ethertype {
+            Some(offset) => Expr::Test(Load::Abs(BPF_H, offset), None, Op::Eq, typ),
+            None => {
+                let version = match typ {
+                    ETHERTYPE_IPV4 => 0x40,
+                    ETHERTYPE_IPV6 => 0x60,
+                    _ => return Expr::Const(false),
+                };
+                Expr::Test(
+                    Load::Abs(BPF_B, self.layout.l3),
+                    Some(0xf0),
+                    Op::Eq,
+                    version,
+                )
+            }
+        }
+    }
+
+    fn ip_proto(&self, family: Family, proto: u32) -> Expr {
+        match family {
+            Family::V4 => and(
+                self.ethertype(ETHERTYPE_IPV4),
+                Expr::Test(Load::Abs(BPF_B, self.layout.l3 + 9), None, Op::Eq, proto),
+            ),
+            Family::V6 => and(
+                self.ethertype(ETHERTYPE_IPV6),
+                Expr::Test(Load::Abs(BPF_B, self.layout.l3 + 6), None, Op::Eq, proto),
+            ),
+        }
+    }
+
+    fn proto(&self, proto: &str) -> Expr {
+        match proto {
+            "ip" => self.ethertype(ETHERTYPE_IPV4),
+            "ip6" => self.ethertype(ETHERTYPE_IPV6),
+            "arp" => self.ethertype(ETHERTYPE_ARP),
+            "icmp" => self.ip_proto(Family::V4, PROTO_ICMP),
+            "icmp6" => self.ip_proto(Family::V6, PROTO_ICMPV6),
+            "tcp" => or(
+                self.ip_proto(Family::V4, PROTO_TCP),
+                self.ip_proto(Family::V6, PROTO_TCP),
+            ),
+            _ => or(
+                self.ip_proto(Family::V4, PROTO_UDP),
+                self.ip_proto(Family::V6, PROTO_UDP),
+            ),
+        }
+    }
+
+    fn directions<F: Fn(u32) -> Expr>(dir: Dir, src: u32, dst: u32, test: F) -> Expr {
+        match dir {
+            Dir::Src => test(src),
+            Dir::Dst => test(dst),
+            Dir::Any => or(test(src), test(dst)),
+        }
+    }
+
+    fn host(&self, dir: Dir, addr: IpAddr) -> Expr {
+        let l3 = self.layout.l3;
+        match addr {
+            IpAddr::V4(addr) => and(
+                self.ethertype(ETHERTYPE_IPV4),
+                Self::directions(dir, l3 + 12, l3 + 16, |offset| {
+                    Expr::Test(Load::Abs(BPF_W, offset), None, Op::Eq, u32::from(addr))
+                }),
+            ),
+            IpAddr::V6(addr) => {
+                let octets = addr.octets();
+                and(
+                    self.ethertype(ETHERTYPE_IPV6),
+                    Self::directions(dir, l3 + 8, l3 + 24, |offset| {
+                        (1..4).fold(Self::word(offset, &octets, 0), |expr, i| {
+                            and(expr, Self::word(offset, &octets, i))
+                        })
+                    }),
+                )
+            }
+        }
+    }
+
+    fn word(offset: u32, octets: &[u8; 16], index: usize) -> Expr {
+        let value = octets[index * 4..index * 4 + 4]
+            .iter()
+            .fold(0u32, |acc, b| (acc << 8) | u32::from(*b));
+        Expr::Test(
+            Load::Abs(BPF_W, offset + index as u32 * 4),
+            None,
+            Op::Eq,
+            value,
+        )
+    }
+
+    fn net(&self, dir: Dir, net: &str) -> io::Result<Expr> {
+        let invalid = || error(format!("invalid network: {}", net));
+        let mut parts = net.splitn(2, '/');
+        let addr = parts
+            .next()
+            .and_then(|addr| addr.parse::<Ipv4Addr>().ok())
+            .ok_or_else(invalid)?;
+        let len = match parts.next() {
+            Some(len) => len.parse::<u32>().map_err(|_| invalid())?,
+            None => 32,
+        };
+        if len > 32 {
+            return Err(invalid());
+        }
+        let mask = if len == 0 { 0 } else { !0u32 << (32 - len) };
+        let addr = u32::from(addr) & mask;
+        let l3 = self.layout.l3;
+        Ok(and(
+            self.ethertype(ETHERTYPE_IPV4),
+            Self::directions(dir, l3 + 12, l3 + 16, |offset| {
+                Expr::Test(Load::Abs(BPF_W, offset), Some(mask), Op::Eq, addr)
+            }),
+        ))
+    }
+
+    fn port(&self, dir: Dir, protos: &[u32], port: u32) -> Expr {
+        let l3 = self.layout.l3;
+        let v4 = any(protos.iter().map(|proto| self.ip_proto(Family::V4, *proto)));
+        let v4 = and(
+            and(
+                v4,
+                not(Expr::Test(Load::Abs(BPF_H, l3 + 6), None, Op::Set, 0x1fff)),
+            ),
+            Self::directions(dir, 0, 2, |offset| {
+                Expr::Test(Load::Ind(BPF_H, offset), None, Op::Eq, port)
+            }),
+        );
+        let v6 = any(protos.iter().map(|proto| self.ip_proto(Family::V6, *proto)));
+        let v6 = and(
+            v6,
+            Self::directions(dir, l3 + 40, l3 + 42, |offset| {
+                Expr::Test(Load::Abs(BPF_H, offset), None, Op::Eq, port)
+            }),
+        );
+        or(v4, v6)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Label(usize);
+
+struct Insn {
+    code: u16,
+    jt: Option<Label>,
+    jf: Option<Label>,
+    k: u32,
+}
+
+struct Codegen {
+    insns: Vec<Insn>,
+    labels: Vec<usize>,
+    l3: u32,
+}
+
+impl Codegen {
+    fn label(&mut self) -> Label {
+        self.labels.push(0);
+        Label(self.labels.len() - 1)
+    }
+
+    fn bind(&mut self, label: Label) {
+        self.labels[label.0] = self.insns.len();
+    }
+
+    fn emit(&mut self, code: u16, k: u32) {
+        self.insns.push(Insn {
+            code,
+            jt: None,
+            jf: None,
+            k,
+        });
+    }
+
+    fn jump(&mut self, code: u16, k: u32, jt: Label, jf: Label) {
+        self.insns.push(Insn {
+            code,
+            jt: Some(jt),
+            jf: Some(jf),
+            k,
+        });
+    }
+
+    fn expr(&mut self, expr: &Expr, t: Label, f: Label) {
+        match expr {
+            Expr::Const(value) => {
+                let target = if *value { t } else { f };
+                self.jump(BPF_JMP | BPF_JA, 0, target, target);
+            }
+            Expr::Test(load, mask, op, value) => {
+                match load {
+                    Load::Abs(size, offset) => self.emit(BPF_LD | size | BPF_ABS, *offset),
+                    Load::Ind(size, offset) => {
+                        self.emit(BPF_LDX | BPF_B | BPF_MSH, self.l3);
+                        self.emit(BPF_LD | size | BPF_IND, self.l3 + offset);
+                    }
+                    Load::Len => self.emit(BPF_LD | BPF_W | BPF_LEN, 0),
+                }
+                if let Some(mask) = mask {
+                    self.emit(BPF_ALU | BPF_AND, *mask);
+                }
+                let op = match op {
+                    Op::Eq => BPF_JEQ,
+                    Op::Gt => BPF_JGT,
+                    Op::Ge => BPF_JGE,
+                    Op::Set => BPF_JSET,
+                };
+                self.jump(BPF_JMP | op, *value, t, f);
+            }
+            Expr::Not(expr) => self.expr(expr, f, t),
+            Expr::And(a, b) => {
+                let next = self.label();
+                self.expr(a, next, f);
+                self.bind(next);
+                self.expr(b, t, f);
+            }
+            Expr::Or(a, b) => {
+                let next = self.label();
+                self.expr(a, t, next);
+                self.bind(next);
+                self.expr(b, t, f);
+            }
+        }
+    }
+
+    fn finish(self, snaplen: u32) -> io::Result<Vec<Instruction>> {
+        let labels = self.labels;
+        let mut prog = Vec::with_capacity(self.insns.len() + 2);
+        for (index, insn) in self.insns.iter().enumerate() {
+            let offset = |label: Option<Label>| match label {
+                Some(label) => labels[label.0] - index - 1,
+                None => 0,
+            };
+            let (jt, jf) = (offset(insn.jt), offset(insn.jf));
+            if insn.code == BPF_JMP | BPF_JA {
+                prog.push(Instruction {
+                    code: insn.code,
+                    jt: 0,
+                    jf: 0,
+                    k: jt as u32,
+                });
+                continue;
+            }
+            if jt > 0xff || jf > 0xff {
+                return Err(error("capture filter is too large"));
+            }
+            prog.push(Instruction {
+                code: insn.code,
+                jt: jt as u8,
+                jf: jf as u8,
+                k: insn.k,
+            });
+        }
+        prog.push(Instruction {
+            code: BPF_RET,
+            jt: 0,
+            jf: 0,
+            k: snaplen,
+        });
+        prog.push(Instruction {
+            code: BPF_RET,
+            jt: 0,
+            jf: 0,
+            k: 0,
+        });
+        Ok(prog)
+    }
+}
+
+fn tokenize(filter: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in filter.char_indices() {
+        let delim = c.is_whitespace() || c == '(' || c == ')' || c == '!';
+        if delim {
+            if let Some(s) = start.take() {
+                tokens.push(&filter[s..i]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&filter[i..=i]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&filter[s..]);
+    }
+    tokens
+}
+
+/// Compiles a capture filter for the given link type.
+///
+/// Matching packets are truncated to `snaplen` bytes.
+pub fn compile(filter: &str, link: u32, snaplen: u32) -> io::Result<Vec<Instruction>> {
+    let layout = Layout::new(link)?;
+    let mut parser = Parser {
+        tokens: tokenize(filter),
+        pos: 0,
+        layout,
+    };
+    let expr = if parser.tokens.is_empty() {
+        Expr::Const(true)
+    } else {
+        parser.parse_or()?
+    };
+    if let Some(token) = parser.peek() {
+        return Err(error(format!("unexpected token: {}", token)));
+    }
+
+    let mut gen = Codegen {
+        insns: Vec::new(),
+        labels: Vec::new(),
+        l3: layout.l3,
+    };
+    let accept = gen.label();
+    let reject = gen.label();
+    gen.expr(&expr, accept, reject);
+    gen.labels[accept.0] = gen.insns.len();
+    gen.labels[reject.0] = gen.insns.len() + 1;
+    gen.finish(snaplen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compiles `filter` with the snaplen 2048 into `(code, jt, jf, k)`.
+    fn insns(filter: &str, link: u32) -> Vec<(u16, u8, u8, u32)> {
+        compile(filter, link, 2048)
+            .unwrap()
+            .iter()
+            .map(|insn| (insn.code, insn.jt, insn.jf, insn.k))
+            .collect()
+    }
+
+    fn err(filter: &str, link: u32) -> String {
+        compile(filter, link, 2048).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn constant() {
+        assert_eq!(
+            insns("", 1),
+            [(0x05, 0, 0, 0), (0x06, 0, 0, 2048), (0x06, 0, 0, 0)]
+        );
+        // Raw IP has no ARP.
+        assert_eq!(
+            insns("arp", 101),
+            [(0x05, 0, 0, 1), (0x06, 0, 0, 2048), (0x06, 0, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn proto() {
+        assert_eq!(
+            insns("ip", 1),
+            [
+                (0x28, 0, 0, 12),
+                (0x15, 0, 1, 0x0800),
+                (0x06, 0, 0, 2048),
+                (0x06, 0, 0, 0),
+            ]
+        );
+        assert_eq!(
+            insns("not ip", 1),
+            [
+                (0x28, 0, 0, 12),
+                (0x15, 1, 0, 0x0800),
+                (0x06, 0, 0, 2048),
+                (0x06, 0, 0, 0),
+            ]
+        );
+        assert_eq!(
+            insns("ip", 101),
+            [
+                (0x30, 0, 0, 0),
+                (0x54, 0, 0, 0xf0),
+                (0x15, 0, 1, 0x40),
+                (0x06, 0, 0, 2048),
+                (0x06, 0, 0, 0),
+            ]
+        );
+        assert_eq!(
+            insns("tcp", 1),
+            [
+                (0x28, 0, 0, 12),
+                (0x15, 0, 2, 0x0800),
+                (0x30, 0, 0, 23),
+                (0x15, 4, 0, 6),
+                (0x28, 0, 0, 12),
+                (0x15, 0, 3, 0x86dd),
+                (0x30, 0, 0, 20),
+                (0x15, 0, 1, 6),
+                (0x06, 0, 0, 2048),
+                (0x06, 0, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn host() {
+        assert_eq!(
+            insns("src host 10.0.0.1", 1),
+            [
+                (0x28, 0, 0, 12),
+                (0x15, 0, 3, 0x0800),
+                (0x20, 0, 0, 26),
+                (0x15, 0, 1, 0x0a00_0001),
+                (0x06, 0, 0, 2048),
+                (0x06, 0, 0, 0),
+            ]
+        );
+        assert_eq!(
+            insns("dst host ::1", 1),
+            [
+                (0x28, 0, 0, 12),
+                (0x15, 0, 9, 0x86dd),
+                (0x20, 0, 0, 38),
+                (0x15, 0, 7, 0),
+                (0x20, 0, 0, 42),
+                (0x15, 0, 5, 0),
+                (0x20, 0, 0, 46),
+                (0x15, 0, 3, 0),
+                (0x20, 0, 0, 50),
+                (0x15, 0, 1, 1),
+                (0x06, 0, 0, 2048),
+                (0x06, 0, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn net() {
+        assert_eq!(
+            insns("net 192.168.1.1/16", 113),
+            [
+                (0x28, 0, 0, 14),
+                (0x15, 0, 7, 0x0800),
+                (0x20, 0, 0, 28),
+                (0x54, 0, 0, 0xffff_0000),
+                (0x15, 3, 0, 0xc0a8_0000),
+                (0x20, 0, 0, 32),
+                (0x54, 0, 0, 0xffff_0000),
+                (0x15, 0, 1, 0xc0a8_0000),
+                (0x06, 0, 0, 2048),
+                (0x06, 0, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn port() {
+        assert_eq!(
+            insns("tcp dst port 80", 1),
+            [
+                (0x28, 0, 0, 12),
+                (0x15, 0, 7, 0x0800),
+                (0x30, 0, 0, 23),
+                (0x15, 0, 5, 6),
+                (0x28, 0, 0, 20),
+                (0x45, 3, 0, 0x1fff),
+                (0xb1, 0, 0, 14),
+                (0x48, 0, 0, 16),
+                (0x15, 6, 0, 80),
+                (0x28, 0, 0, 12),
+                (0x15, 0, 5, 0x86dd),
+                (0x30, 0, 0, 20),
+                (0x15, 0, 3, 6),
+                (0x28, 0, 0, 56),
+                (0x15, 0, 1, 80),
+                (0x06, 0, 0, 2048),
+                (0x06, 0, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn len() {
+        assert_eq!(
+            insns("less 100 or greater 1000", 1),
+            [
+                (0x80, 0, 0, 0),
+                (0x25, 0, 2, 100),
+                (0x80, 0, 0, 0),
+                (0x35, 0, 1, 1000),
+                (0x06, 0, 0, 2048),
+                (0x06, 0, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            insns("arp or ip and udp", 1),
+            insns("arp or (ip and udp)", 1)
+        );
+        assert_eq!(insns("!arp && ip6", 1), insns("(not arp) and ip6", 1));
+        assert_ne!(insns("not arp and ip6", 1), insns("not (arp and ip6)", 1));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            err("tcp", 127),
+            "capture filters are not supported on link type 127"
+        );
+        assert_eq!(err("tcp port", 1), "unexpected end of filter");
+        assert_eq!(err("port 70000", 1), "invalid port: 70000");
+        assert_eq!(err("host 10.0.0", 1), "invalid address: 10.0.0");
+        assert_eq!(err("net 10.0.0.0/33", 1), "invalid network: 10.0.0.0/33");
+        assert_eq!(err("ip host ::1", 1), "unexpected qualifier for host");
+        assert_eq!(err("icmp port 1", 1), "unexpected qualifier for port");
+        assert_eq!(err("src tcp", 1), "unexpected token: tcp");
+        assert_eq!(err("(tcp", 1), "expected ')'");
+        assert_eq!(err("tcp udp", 1), "unexpected token: udp");
+
+        let filter = vec!["ip6 host ::1"; 16].join(" or ");
+        assert_eq!(err(&filter, 1), "capture filter is too large");
+    }
+}
@@ -0,0 +1,201 @@
+//! Transmission of frames on an interface.
+//!
+//! Unlike `app.genet.writer.replay`, which pipes the frames to `pcap-cli`,
+//! the frames are sent from the process with the same sockets as the
+//! capture.
+
+use genet_sdk::{prelude::*, writer::*};
+use serde_json;
+use std::{
+    io::{self, ErrorKind},
+    mem, thread,
+    time::{Duration, Instant},
+};
+use Injector;
+
+/// The number of times a frame is sent again while the queue of the
+/// interface is full.
+const MAX_RETRIES: u32 = 100;
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+enum Pace {
+    /// Follows the original timestamps, scaled by the multiplier.
+    Timestamp(f64),
+    Pps(f64),
+    Mbps(f64),
+    TopSpeed,
+}
+
+impl Pace {
+    fn rate(&self) -> Option<f64> {
+        match self {
+            Pace::Timestamp(rate) | Pace::Pps(rate) | Pace::Mbps(rate) => Some(*rate),
+            Pace::TopSpeed => None,
+        }
+    }
+}
+
+fn default_pace() -> Pace {
+    Pace::Timestamp(1.0)
+}
+
+fn default_loops() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+struct Arg {
+    interface: String,
+    #[serde(default = "default_pace")]
+    pace: Pace,
+    /// The number of times the frames are sent.
+    #[serde(default = "default_loops")]
+    loops: u32,
+}
+
+/// Delays the frames according to a Pace.
+struct Pacer {
+    pace: Pace,
+    start: Instant,
+    first: Option<f64>,
+    frames: u64,
+    bytes: u64,
+}
+
+impl Pacer {
+    fn new(pace: Pace) -> Pacer {
+        Pacer {
+            pace,
+            start: Instant::now(),
+            first: None,
+            frames: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Returns the offset of the next frame from the start.
+    fn offset(&mut self, ts: f64) -> Option<f64> {
+        let ts = ts - *self.first.get_or_insert(ts);
+        match self.pace {
+            Pace::Timestamp(multiplier) => Some(ts / multiplier),
+            Pace::Pps(pps) => Some(self.frames as f64 / pps),
+            Pace::Mbps(mbps) => Some(self.bytes as f64 * 8.0 / (mbps * 1_000_000.0)),
+            Pace::TopSpeed => None,
+        }
+    }
+
+    /// Waits for the time of the frame at `ts` of `len` bytes.
+    fn wait(&mut self, ts: f64, len: usize) {
+        if let Some(offset) = self.offset(ts) {
+            let target = self.start + Duration::from_micros((offset.max(0.0) * 1e6) as u64);
+            let now = Instant::now();
+            if target > now {
+                thread::sleep(target - now);
+            }
+        }
+        self.frames += 1;
+        self.bytes += len as u64;
+    }
+
+    fn restart(&mut self) {
+        *self = Pacer::new(self.pace);
+    }
+}
+
+#[derive(Clone)]
+pub struct InjectWriter {}
+
+impl Writer for InjectWriter {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        if let Some(rate) = arg.pace.rate() {
+            if rate.is_nan() || rate <= 0.0 {
+                return Err(
+                    io::Error::new(ErrorKind::InvalidInput, "the pace must be positive").into(),
+                );
+            }
+        }
+        Ok(Box::new(InjectWorker {
+            injector: Injector::open(&arg.interface)?,
+            pacer: Pacer::new(arg.pace),
+            loops: arg.loops.max(1),
+            frames: Vec::new(),
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.writer.inject".into(),
+            ..Metadata::default()
+        }
+    }
+}
+
+struct InjectWorker {
+    injector: Injector,
+    pacer: Pacer,
+    loops: u32,
+    /// The frames sent again for the remaining loops.
+    frames: Vec<(f64, Vec<u8>)>,
+}
+
+impl InjectWorker {
+    fn send(&mut self, ts: f64, data: &[u8]) -> io::Result<()> {
+        self.pacer.wait(ts, data.len());
+        let mut retries = 0;
+        loop {
+            match self.injector.send(data) {
+                Err(ref err) if is_busy(err) && retries < MAX_RETRIES => {
+                    retries += 1;
+                    thread::sleep(Duration::from_millis(1));
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn is_busy(err: &io::Error) -> bool {
+    err.kind() == ErrorKind::WouldBlock || err.raw_os_error() == Some(libc::ENOBUFS)
+}
+
+fn timestamp(layer: &Layer) -> Result<f64> {
+    let attr = |id| -> Result<Option<u64>> {
+        match layer.attr(id) {
+            Some(attr) => Ok(Some(attr.try_get(layer)?.try_into()?)),
+            None => Ok(None),
+        }
+    };
+    let sec = attr(token!("link.timestamp.sec"))?.unwrap_or(0);
+    let nsec = match attr(token!("link.timestamp.nsec"))? {
+        Some(nsec) => nsec,
+        None => attr(token!("link.timestamp.usec"))?.unwrap_or(0) * 1000,
+    };
+    Ok(sec as f64 + nsec as f64 / 1e9)
+}
+
+impl Worker for InjectWorker {
+    fn write(&mut self, _index: u32, stack: &LayerStack) -> Result<()> {
+        if let Some(layer) = stack.bottom() {
+            let ts = timestamp(layer)?;
+            let data = layer.data();
+            self.send(ts, &data)?;
+            if self.loops > 1 {
+                self.frames.push((ts, data.to_vec()));
+            }
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        let frames = mem::take(&mut self.frames);
+        for _ in 1..self.loops {
+            self.pacer.restart();
+            for (ts, data) in &frames {
+                self.send(*ts, data)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+extern crate genet_sdk;
+extern crate libc;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+mod compile;
+mod inject;
+mod wireless;
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[cfg(target_os = "linux")]
+mod xdp;
+
+#[cfg(target_os = "linux")]
+use linux::{Capture, Injector};
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+mod bpf;
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+use bpf::{Capture, Injector};
+
+use genet_sdk::{layer::FrameMetadata, prelude::*, reader::*};
+use std::{ffi::CStr, io, ptr};
+use wireless::{Hopper, Wireless};
+
+const READ_TIMEOUT_MS: i32 = 100;
+
+/// A classic BPF instruction.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Instruction {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+fn default_snaplen() -> u32 {
+    2048
+}
+
+fn default_promisc() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Packet,
+    Xdp,
+}
+
+fn default_backend() -> Backend {
+    Backend::Packet
+}
+
+#[derive(Deserialize)]
+pub struct Options {
+    interface: String,
+    #[serde(default = "default_snaplen")]
+    snaplen: u32,
+    #[serde(default = "default_promisc")]
+    promisc: bool,
+    #[serde(default)]
+    filter: Option<Vec<Instruction>>,
+    #[serde(default)]
+    expression: Option<String>,
+    #[serde(default)]
+    wireless: Option<Wireless>,
+    #[serde(default = "default_backend")]
+    backend: Backend,
+    /// The receive queue to capture with the XDP backend.
+    #[serde(default)]
+    queue: u32,
+}
+
+impl Options {
+    fn monitor(&self) -> bool {
+        match &self.wireless {
+            Some(wireless) => wireless.monitor,
+            None => false,
+        }
+    }
+}
+
+pub struct Packet<'a> {
+    ts_sec: u64,
+    ts_nsec: u32,
+    len: u32,
+    data: &'a [u8],
+    queue: Option<u32>,
+    cpu: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct Device {
+    name: String,
+    loopback: bool,
+    up: bool,
+}
+
+fn devices() -> Vec<Device> {
+    let mut devices: Vec<Device> = Vec::new();
+    unsafe {
+        let mut addrs: *mut libc::ifaddrs = ptr::null_mut();
+        if libc::getifaddrs(&mut addrs) != 0 {
+            return devices;
+        }
+        let mut cur = addrs;
+        while !cur.is_null() {
+            let name = CStr::from_ptr((*cur).ifa_name)
+                .to_string_lossy()
+                .into_owned();
+            let flags = (*cur).ifa_flags as libc::c_int;
+            if !devices.iter().any(|d| d.name == name) {
+                devices.push(Device {
+                    name,
+                    loopback: flags & libc::IFF_LOOPBACK != 0,
+                    up: flags & libc::IFF_UP != 0,
+                });
+            }
+            cur = (*cur).ifa_next;
+        }
+        libc::freeifaddrs(addrs);
+    }
+    devices
+}
+
+#[derive(Clone)]
+struct LiveReader {}
+
+impl Reader for LiveReader {
+    fn new_worker(&self, ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        ctx.set_metadata(
+            "live.interfaces",
+            &serde_json::to_string(&devices()).unwrap_or_default(),
+        );
+        let opt: Options = serde_json::from_str(arg)?;
+        let capture = Source::open(&opt)?;
+        let hopper = match &opt.wireless {
+            Some(wireless) => Hopper::start(&opt.interface, wireless)?,
+            None => None,
+        };
+        let link_class = Fixed::new(layer_class!(
+            format!("[link-{}]", capture.link()),
+            header: attr!(&TYPE_CLASS, value: u64::from(capture.link()))
+        ));
+        Ok(Box::new(LiveWorker {
+            capture,
+            link_class,
+            interface: opt.interface,
+            hopper,
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.live".into(),
+            ..Metadata::default()
+        }
+    }
+}
+
+enum Source {
+    Capture(Capture),
+    #[cfg(target_os = "linux")]
+    Xdp(xdp::XdpCapture),
+}
+
+impl Source {
+    fn open(opt: &Options) -> io::Result<Source> {
+        match opt.backend {
+            Backend::Packet => {
+                let mut capture = Capture::open(opt)?;
+                if let Some(expr) = &opt.expression {
+                    capture.set_filter(&compile::compile(expr, capture.link(), opt.snaplen)?)?;
+                }
+                Ok(Source::Capture(capture))
+            }
+            #[cfg(target_os = "linux")]
+            Backend::Xdp => Ok(Source::Xdp(xdp::XdpCapture::open(opt)?)),
+            #[cfg(not(target_os = "linux"))]
+            Backend::Xdp => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "XDP capture is only supported on Linux",
+            )),
+        }
+    }
+
+    fn link(&self) -> u32 {
+        match self {
+            Source::Capture(capture) => capture.link(),
+            #[cfg(target_os = "linux")]
+            Source::Xdp(capture) => capture.link(),
+        }
+    }
+
+    fn stats(&mut self) -> io::Result<CaptureStats> {
+        match self {
+            Source::Capture(capture) => capture.stats(),
+            #[cfg(target_os = "linux")]
+            Source::Xdp(capture) => capture.stats(),
+        }
+    }
+
+    fn read<F: FnMut(Packet)>(&mut self, timeout: i32, f: F) -> io::Result<()> {
+        match self {
+            Source::Capture(capture) => capture.read(timeout, f),
+            #[cfg(target_os = "linux")]
+            Source::Xdp(capture) => capture.read(timeout, f),
+        }
+    }
+}
+
+struct LiveWorker {
+    capture: Source,
+    link_class: Fixed<LayerClass>,
+    interface: String,
+    hopper: Option<Hopper>,
+}
+
+impl Worker for LiveWorker {
+    fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
+        if let Some(hopper) = &self.hopper {
+            hopper.check()?;
+        }
+        let mut layers = Vec::new();
+        let link_class = &self.link_class;
+        let frame = FrameMetadata::new(self.capture.link());
+        let interface = &self.interface;
+        self.capture.read(READ_TIMEOUT_MS, |pkt| {
+            let payload = ByteSlice::from(pkt.data.to_vec());
+            let mut layer = Layer::new(link_class.clone(), payload);
+            layer.set_frame_metadata(frame);
+            layer.add_attr(attr!(&LENGTH_CLASS, value: u64::from(pkt.len)));
+            layer.add_attr(attr!(
+                &TS_CLASS,
+                value: pkt.ts_sec as f64 + f64::from(pkt.ts_nsec) / 1_000_000_000f64
+            ));
+            layer.add_attr(attr!(&TS_SEC_CLASS, value: pkt.ts_sec));
+            layer.add_attr(attr!(&TS_USEC_CLASS, value: u64::from(pkt.ts_nsec / 1000)));
+            layer.add_attr(attr!(&TS_NSEC_CLASS, value: u64::from(pkt.ts_nsec)));
+            layer.add_attr(attr!(
+                &INTERFACE_NAME_CLASS,
+                value: interface.clone().into_boxed_str()
+            ));
+            if let Some(queue) = pkt.queue {
+                layer.add_attr(attr!(&QUEUE_CLASS, value: u64::from(queue)));
+            }
+            if let Some(cpu) = pkt.cpu {
+                layer.add_attr(attr!(&CPU_CLASS, value: u64::from(cpu)));
+            }
+            layers.push(layer);
+        })?;
+        Ok(layers)
+    }
+
+    fn stats(&mut self) -> Option<CaptureStats> {
+        self.capture.stats().ok()
+    }
+}
+
+def_attr_class!(TYPE_CLASS, "link.type");
+def_attr_class!(LENGTH_CLASS, "link.length");
+def_attr_class!(TS_CLASS, "link.timestamp",
+    typ: "@datetime:unix"
+);
+def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
+def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+def_attr_class!(TS_NSEC_CLASS, "link.timestamp.nsec");
+def_attr_class!(INTERFACE_NAME_CLASS, "link.interface.name");
+def_attr_class!(QUEUE_CLASS, "link.queue");
+def_attr_class!(CPU_CLASS, "link.cpu");
+
+genet_readers!(LiveReader {});
+genet_writers!(inject::InjectWriter {});
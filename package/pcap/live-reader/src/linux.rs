@@ -0,0 +1,524 @@
+//! AF_PACKET capture with TPACKET_V3 ring buffers.
+
+use genet_sdk::reader::CaptureStats;
+use libc::{self, c_int, c_uint, c_ushort, c_void};
+use std::{ffi::CString, fs, io, mem, ptr, slice};
+use {Instruction, Options, Packet};
+
+const ETH_P_ALL: u16 = 0x0003;
+const SOL_PACKET: c_int = 263;
+const PACKET_ADD_MEMBERSHIP: c_int = 1;
+const PACKET_RX_RING: c_int = 5;
+const PACKET_STATISTICS: c_int = 6;
+const PACKET_VERSION: c_int = 10;
+const PACKET_MR_PROMISC: c_ushort = 1;
+const TPACKET_V3: c_int = 2;
+const TP_STATUS_KERNEL: u32 = 0;
+const TP_STATUS_USER: u32 = 1;
+const SO_ATTACH_FILTER: c_int = 26;
+const SIOCGIFHWADDR: libc::c_ulong = 0x8927;
+const SIOCGIFFLAGS: libc::c_ulong = 0x8913;
+const SIOCSIFFLAGS: libc::c_ulong = 0x8914;
+const SIOCSIWFREQ: libc::c_ulong = 0x8b04;
+const SIOCSIWMODE: libc::c_ulong = 0x8b06;
+const IW_MODE_MONITOR: u32 = 6;
+const IW_FREQ_FIXED: u8 = 1;
+
+const ARPHRD_ETHER: u16 = 1;
+const ARPHRD_LOOPBACK: u16 = 772;
+const ARPHRD_IEEE80211_RADIOTAP: u16 = 803;
+const ARPHRD_NONE: u16 = 0xfffe;
+
+const BLOCK_SIZE: u32 = 1 << 20;
+const BLOCK_NR: u32 = 32;
+const FRAME_SIZE: u32 = 1 << 11;
+const BLOCK_TIMEOUT_MS: u32 = 64;
+
+#[repr(C)]
+struct TpacketReq3 {
+    tp_block_size: c_uint,
+    tp_block_nr: c_uint,
+    tp_frame_size: c_uint,
+    tp_frame_nr: c_uint,
+    tp_retire_blk_tov: c_uint,
+    tp_sizeof_priv: c_uint,
+    tp_feature_req_word: c_uint,
+}
+
+#[repr(C)]
+struct TpacketBdTs {
+    ts_sec: c_uint,
+    ts_nsec: c_uint,
+}
+
+#[repr(C)]
+struct TpacketHdrV1 {
+    block_status: u32,
+    num_pkts: u32,
+    offset_to_first_pkt: u32,
+    blk_len: u32,
+    seq_num: u64,
+    ts_first_pkt: TpacketBdTs,
+    ts_last_pkt: TpacketBdTs,
+}
+
+#[repr(C)]
+struct TpacketBlockDesc {
+    version: u32,
+    offset_to_priv: u32,
+    hdr: TpacketHdrV1,
+}
+
+#[repr(C)]
+struct Tpacket3Hdr {
+    tp_next_offset: u32,
+    tp_sec: u32,
+    tp_nsec: u32,
+    tp_snaplen: u32,
+    tp_len: u32,
+    tp_status: u32,
+    tp_mac: u16,
+    tp_net: u16,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct TpacketStatsV3 {
+    tp_packets: c_uint,
+    tp_drops: c_uint,
+    tp_freeze_q_cnt: c_uint,
+}
+
+#[repr(C)]
+struct PacketMreq {
+    mr_ifindex: c_int,
+    mr_type: c_ushort,
+    mr_alen: c_ushort,
+    mr_address: [u8; 8],
+}
+
+#[repr(C)]
+struct SockFprog {
+    len: c_ushort,
+    filter: *const libc::sock_filter,
+}
+
+#[repr(C)]
+struct Ifreq {
+    ifr_name: [u8; libc::IFNAMSIZ],
+    ifr_hwaddr: libc::sockaddr,
+    _pad: [u8; 8],
+}
+
+#[repr(C)]
+struct IfreqFlags {
+    ifr_name: [u8; libc::IFNAMSIZ],
+    ifr_flags: libc::c_short,
+    _pad: [u8; 22],
+}
+
+#[repr(C)]
+struct IwFreq {
+    m: i32,
+    e: i16,
+    i: u8,
+    flags: u8,
+}
+
+#[repr(C)]
+struct IwreqFreq {
+    ifr_name: [u8; libc::IFNAMSIZ],
+    freq: IwFreq,
+    _pad: [u8; 8],
+}
+
+#[repr(C)]
+struct IwreqMode {
+    ifr_name: [u8; libc::IFNAMSIZ],
+    mode: u32,
+    _pad: [u8; 12],
+}
+
+/// The receive counters of an interface, which include the frames not
+/// captured.
+#[derive(Clone, Copy, Default)]
+pub struct InterfaceCounters {
+    pub dropped: u64,
+    pub errors: u64,
+}
+
+impl InterfaceCounters {
+    /// Reads the counters from sysfs, or zeros if they are not available.
+    pub fn read(interface: &str) -> InterfaceCounters {
+        let counter = |name: &str| {
+            fs::read_to_string(format!("/sys/class/net/{}/statistics/{}", interface, name))
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+        InterfaceCounters {
+            dropped: counter("rx_dropped") + counter("rx_fifo_errors"),
+            errors: counter("rx_errors"),
+        }
+    }
+
+    /// Returns the counts since `base` was read.
+    pub fn since(&self, base: &InterfaceCounters) -> InterfaceCounters {
+        InterfaceCounters {
+            dropped: self.dropped.saturating_sub(base.dropped),
+            errors: self.errors.saturating_sub(base.errors),
+        }
+    }
+}
+
+pub struct Capture {
+    fd: c_int,
+    ring: *mut u8,
+    block: u32,
+    link: u32,
+    snaplen: u32,
+    interface: String,
+    counters: InterfaceCounters,
+    stats: CaptureStats,
+}
+
+unsafe impl Send for Capture {}
+
+fn check(ret: c_int) -> io::Result<c_int> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+fn copy_name(dst: &mut [u8; libc::IFNAMSIZ], interface: &str) {
+    let len = interface.len().min(libc::IFNAMSIZ - 1);
+    dst[..len].copy_from_slice(&interface.as_bytes()[..len]);
+}
+
+/// Issues an ioctl on a control socket.
+fn ioctl<T>(req: libc::c_ulong, arg: &mut T) -> io::Result<()> {
+    unsafe {
+        let fd = check(libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0))?;
+        let ret = check(libc::ioctl(fd, req, arg as *mut T));
+        libc::close(fd);
+        ret?;
+    }
+    Ok(())
+}
+
+fn setsockopt<T>(fd: c_int, level: c_int, name: c_int, value: &T) -> io::Result<()> {
+    unsafe {
+        check(libc::setsockopt(
+            fd,
+            level,
+            name,
+            value as *const T as *const c_void,
+            mem::size_of::<T>() as libc::socklen_t,
+        ))?;
+    }
+    Ok(())
+}
+
+impl Capture {
+    pub fn open(opt: &Options) -> io::Result<Capture> {
+        let name = CString::new(opt.interface.as_str())?;
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no such interface"));
+        }
+
+        let fd = unsafe {
+            check(libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_RAW,
+                c_int::from(ETH_P_ALL.to_be()),
+            ))?
+        };
+        let mut capture = Capture {
+            fd,
+            ring: ptr::null_mut(),
+            block: 0,
+            link: 1,
+            snaplen: opt.snaplen,
+            interface: opt.interface.clone(),
+            counters: InterfaceCounters::read(&opt.interface),
+            stats: CaptureStats::default(),
+        };
+
+        if opt.monitor() {
+            Capture::set_monitor(&opt.interface)?;
+        }
+        capture.link = capture.link_type(&opt.interface)?;
+        setsockopt(fd, SOL_PACKET, PACKET_VERSION, &TPACKET_V3)?;
+
+        if let Some(filter) = &opt.filter {
+            capture.set_filter(filter)?;
+        }
+
+        let req = TpacketReq3 {
+            tp_block_size: BLOCK_SIZE,
+            tp_block_nr: BLOCK_NR,
+            tp_frame_size: FRAME_SIZE,
+            tp_frame_nr: BLOCK_SIZE / FRAME_SIZE * BLOCK_NR,
+            tp_retire_blk_tov: BLOCK_TIMEOUT_MS,
+            tp_sizeof_priv: 0,
+            tp_feature_req_word: 0,
+        };
+        setsockopt(fd, SOL_PACKET, PACKET_RX_RING, &req)?;
+
+        // The kernel pins the pages of the ring, so MAP_LOCKED is not needed,
+        // and it would fail under the default RLIMIT_MEMLOCK.
+        let ring = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                (BLOCK_SIZE * BLOCK_NR) as usize,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+        if ring == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        capture.ring = ring as *mut u8;
+
+        let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as c_ushort;
+        addr.sll_protocol = ETH_P_ALL.to_be();
+        addr.sll_ifindex = index as c_int;
+        unsafe {
+            check(libc::bind(
+                fd,
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            ))?;
+        }
+
+        if opt.promisc {
+            let mreq = PacketMreq {
+                mr_ifindex: index as c_int,
+                mr_type: PACKET_MR_PROMISC,
+                mr_alen: 0,
+                mr_address: [0; 8],
+            };
+            setsockopt(fd, SOL_PACKET, PACKET_ADD_MEMBERSHIP, &mreq)?;
+        }
+
+        // Discards the counts of the frames of other interfaces received
+        // before the socket was bound.
+        capture.packet_stats()?;
+        Ok(capture)
+    }
+
+    fn link_type(&self, interface: &str) -> io::Result<u32> {
+        let mut req: Ifreq = unsafe { mem::zeroed() };
+        copy_name(&mut req.ifr_name, interface);
+        unsafe {
+            check(libc::ioctl(self.fd, SIOCGIFHWADDR, &mut req as *mut Ifreq))?;
+        }
+        Ok(match req.ifr_hwaddr.sa_family {
+            ARPHRD_ETHER | ARPHRD_LOOPBACK => 1,
+            ARPHRD_IEEE80211_RADIOTAP => 127,
+            ARPHRD_NONE => 101,
+            _ => 1,
+        })
+    }
+
+    /// Switches a wireless interface into monitor mode. The interface has to be
+    /// brought down while the mode changes.
+    fn set_monitor(interface: &str) -> io::Result<()> {
+        let mut flags: IfreqFlags = unsafe { mem::zeroed() };
+        copy_name(&mut flags.ifr_name, interface);
+        ioctl(SIOCGIFFLAGS, &mut flags)?;
+        let up = flags.ifr_flags & libc::IFF_UP as libc::c_short != 0;
+        if up {
+            flags.ifr_flags &= !(libc::IFF_UP as libc::c_short);
+            ioctl(SIOCSIFFLAGS, &mut flags)?;
+        }
+
+        let mut req: IwreqMode = unsafe { mem::zeroed() };
+        copy_name(&mut req.ifr_name, interface);
+        req.mode = IW_MODE_MONITOR;
+        let result = ioctl(SIOCSIWMODE, &mut req);
+
+        if up {
+            flags.ifr_flags |= libc::IFF_UP as libc::c_short;
+            ioctl(SIOCSIFFLAGS, &mut flags)?;
+        }
+        result
+    }
+
+    /// Tunes a wireless interface to a frequency in MHz.
+    pub fn set_frequency(interface: &str, mhz: u32) -> io::Result<()> {
+        let mut req: IwreqFreq = unsafe { mem::zeroed() };
+        copy_name(&mut req.ifr_name, interface);
+        req.freq = IwFreq {
+            m: mhz as i32,
+            e: 6,
+            i: 0,
+            flags: IW_FREQ_FIXED,
+        };
+        ioctl(SIOCSIWFREQ, &mut req)
+    }
+
+    pub fn link(&self) -> u32 {
+        self.link
+    }
+
+    pub fn set_filter(&mut self, filter: &[Instruction]) -> io::Result<()> {
+        let filter = filter
+            .iter()
+            .map(|i| libc::sock_filter {
+                code: i.code,
+                jt: i.jt,
+                jf: i.jf,
+                k: i.k,
+            })
+            .collect::<Vec<_>>();
+        let prog = SockFprog {
+            len: filter.len() as c_ushort,
+            filter: filter.as_ptr(),
+        };
+        setsockopt(self.fd, libc::SOL_SOCKET, SO_ATTACH_FILTER, &prog)
+    }
+
+    /// Reads the counters of the socket, which are reset by the kernel.
+    fn packet_stats(&self) -> io::Result<TpacketStatsV3> {
+        let mut stats = TpacketStatsV3::default();
+        let mut len = mem::size_of::<TpacketStatsV3>() as libc::socklen_t;
+        unsafe {
+            check(libc::getsockopt(
+                self.fd,
+                SOL_PACKET,
+                PACKET_STATISTICS,
+                &mut stats as *mut TpacketStatsV3 as *mut c_void,
+                &mut len,
+            ))?;
+        }
+        Ok(stats)
+    }
+
+    pub fn stats(&mut self) -> io::Result<CaptureStats> {
+        // The packets include the dropped ones.
+        let stats = self.packet_stats()?;
+        self.stats.received += u64::from(stats.tp_packets);
+        self.stats.dropped += u64::from(stats.tp_drops);
+        self.stats.overruns += u64::from(stats.tp_freeze_q_cnt);
+        let counters = InterfaceCounters::read(&self.interface).since(&self.counters);
+        Ok(CaptureStats {
+            if_dropped: counters.dropped,
+            errors: counters.errors,
+            ..self.stats
+        })
+    }
+
+    pub fn read<F: FnMut(Packet)>(&mut self, timeout: i32, mut f: F) -> io::Result<()> {
+        let mut pfd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN | libc::POLLERR,
+            revents: 0,
+        };
+        let mut blocks = 0;
+        let mut polled = false;
+        while blocks < BLOCK_NR {
+            let offset = (self.block * BLOCK_SIZE) as isize;
+            let block = unsafe { self.ring.offset(offset) } as *mut TpacketBlockDesc;
+            let status = unsafe { ptr::read_volatile(&(*block).hdr.block_status) };
+            if status & TP_STATUS_USER == 0 {
+                if blocks > 0 || polled {
+                    break;
+                }
+                unsafe { check(libc::poll(&mut pfd, 1, timeout))? };
+                polled = true;
+                continue;
+            }
+
+            unsafe {
+                let hdr = &(*block).hdr;
+                let mut pkt = (block as *mut u8).offset(hdr.offset_to_first_pkt as isize);
+                for _ in 0..hdr.num_pkts {
+                    let h = &*(pkt as *const Tpacket3Hdr);
+                    let caplen = h.tp_snaplen.min(self.snaplen);
+                    let data =
+                        slice::from_raw_parts(pkt.offset(h.tp_mac as isize), caplen as usize);
+                    f(Packet {
+                        ts_sec: u64::from(h.tp_sec),
+                        ts_nsec: h.tp_nsec,
+                        len: h.tp_len,
+                        data,
+                        queue: None,
+                        cpu: None,
+                    });
+                    pkt = pkt.offset(h.tp_next_offset as isize);
+                }
+                ptr::write_volatile(&mut (*block).hdr.block_status, TP_STATUS_KERNEL);
+            }
+            self.block = (self.block + 1) % BLOCK_NR;
+            blocks += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.ring.is_null() {
+                libc::munmap(self.ring as *mut c_void, (BLOCK_SIZE * BLOCK_NR) as usize);
+            }
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// An AF_PACKET socket transmitting frames on an interface.
+pub struct Injector {
+    fd: c_int,
+}
+
+impl Injector {
+    pub fn open(interface: &str) -> io::Result<Injector> {
+        let name = CString::new(interface)?;
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no such interface"));
+        }
+
+        // A socket of protocol zero receives no frames.
+        let fd = unsafe { check(libc::socket(libc::AF_PACKET, libc::SOCK_RAW, 0))? };
+        let injector = Injector { fd };
+        let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as c_ushort;
+        addr.sll_ifindex = index as c_int;
+        unsafe {
+            check(libc::bind(
+                fd,
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            ))?;
+        }
+        Ok(injector)
+    }
+
+    pub fn send(&self, data: &[u8]) -> io::Result<()> {
+        let len = unsafe { libc::send(self.fd, data.as_ptr() as *const c_void, data.len(), 0) };
+        if len < 0 {
+            Err(io::Error::last_os_error())
+        } else if (len as usize) < data.len() {
+            Err(io::Error::new(io::ErrorKind::WriteZero, "frame truncated"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for Injector {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
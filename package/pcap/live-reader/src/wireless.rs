@@ -0,0 +1,112 @@
+//! Channel control for 802.11 monitor-mode capture.
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use Capture;
+
+fn default_dwell() -> u64 {
+    250
+}
+
+#[derive(Deserialize)]
+pub struct Wireless {
+    /// Puts the interface into monitor mode and captures radiotap headers.
+    #[serde(default)]
+    pub monitor: bool,
+    /// Channels to tune to. The capture hops through them in order if more
+    /// than one is given. Values of 1000 or more are frequencies in MHz.
+    #[serde(default)]
+    pub channels: Vec<u32>,
+    /// Time spent on each channel in milliseconds.
+    #[serde(default = "default_dwell")]
+    pub dwell: u64,
+}
+
+/// Returns the center frequency of a channel in MHz.
+pub fn frequency(channel: u32) -> io::Result<u32> {
+    match channel {
+        1..=13 => Ok(2407 + channel * 5),
+        14 => Ok(2484),
+        32..=177 => Ok(5000 + channel * 5),
+        c if c >= 1000 => Ok(c),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unknown channel: {}", channel),
+        )),
+    }
+}
+
+/// Cycles an interface through a list of channels on a background thread.
+pub struct Hopper {
+    stop: Arc<AtomicBool>,
+    error: Arc<Mutex<Option<io::Error>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Hopper {
+    /// Tunes to the first channel, and starts hopping if there are others.
+    pub fn start(interface: &str, opt: &Wireless) -> io::Result<Option<Hopper>> {
+        let freqs = opt
+            .channels
+            .iter()
+            .map(|c| frequency(*c))
+            .collect::<io::Result<Vec<_>>>()?;
+        if freqs.is_empty() {
+            return Ok(None);
+        }
+        Capture::set_frequency(interface, freqs[0])?;
+        if freqs.len() == 1 {
+            return Ok(None);
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let error = Arc::new(Mutex::new(None));
+        let interface = interface.to_string();
+        let dwell = Duration::from_millis(opt.dwell.max(1));
+        let thread = {
+            let stop = stop.clone();
+            let error = error.clone();
+            thread::spawn(move || {
+                for freq in freqs.iter().cycle().skip(1) {
+                    thread::sleep(dwell);
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    if let Err(err) = Capture::set_frequency(&interface, *freq) {
+                        *error.lock().unwrap() = Some(err);
+                        return;
+                    }
+                }
+            })
+        };
+        Ok(Some(Hopper {
+            stop,
+            error,
+            thread: Some(thread),
+        }))
+    }
+
+    /// Returns an error if the hopper has failed to switch the channel.
+    pub fn check(&self) -> io::Result<()> {
+        match self.error.lock().unwrap().take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Hopper {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
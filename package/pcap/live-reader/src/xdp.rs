@@ -0,0 +1,1105 @@
+//! AF_XDP capture with the filter running in an XDP program.
+//!
+//! The classic BPF filter is translated into eBPF and attached to the
+//! interface, so that frames which do not match never leave the kernel.
+//! Matching frames are redirected to an AF_XDP socket bound to one receive
+//! queue, and everything else passes on to the network stack unchanged.
+
+use compile;
+use genet_sdk::reader::CaptureStats;
+use libc::{self, c_int, c_void};
+use linux::InterfaceCounters;
+use std::{
+    ffi::CString,
+    io, mem, ptr, slice,
+    sync::atomic::{fence, Ordering},
+    thread,
+    time::Duration,
+};
+use {Instruction, Options, Packet};
+
+const AF_XDP: c_int = 44;
+const SOL_XDP: c_int = 283;
+const XDP_MMAP_OFFSETS: c_int = 1;
+const XDP_RX_RING: c_int = 2;
+const XDP_UMEM_REG: c_int = 4;
+const XDP_UMEM_FILL_RING: c_int = 5;
+const XDP_UMEM_COMPLETION_RING: c_int = 6;
+const XDP_STATISTICS: c_int = 7;
+const XDP_PGOFF_RX_RING: libc::off_t = 0;
+const XDP_UMEM_PGOFF_FILL_RING: libc::off_t = 0x1_0000_0000;
+const XDP_UMEM_PGOFF_COMPLETION_RING: libc::off_t = 0x1_8000_0000;
+
+const BPF_MAP_CREATE: c_int = 0;
+const BPF_MAP_UPDATE_ELEM: c_int = 2;
+const BPF_PROG_LOAD: c_int = 5;
+const BPF_LINK_CREATE: c_int = 28;
+const BPF_MAP_TYPE_XSKMAP: u32 = 17;
+const BPF_PROG_TYPE_XDP: u32 = 6;
+const BPF_XDP: u32 = 37;
+const BPF_PSEUDO_MAP_FD: u8 = 1;
+
+const FRAME_SIZE: u32 = 2048;
+const FRAME_NR: u32 = 4096;
+const UMEM_LEN: usize = (FRAME_SIZE * FRAME_NR) as usize;
+const LOG_SIZE: usize = 1 << 16;
+const BIND_RETRIES: u32 = 20;
+const BIND_RETRY_INTERVAL_MS: u64 = 50;
+
+/// Marks the metadata written by the XDP program in front of each frame.
+const META_MAGIC: u32 = 0x6765_6e74;
+const META_SIZE: u32 = 8;
+
+#[repr(C)]
+struct XdpUmemReg {
+    addr: u64,
+    len: u64,
+    chunk_size: u32,
+    headroom: u32,
+    flags: u32,
+    tx_metadata_len: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct XdpRingOffset {
+    producer: u64,
+    consumer: u64,
+    desc: u64,
+    flags: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct XdpMmapOffsets {
+    rx: XdpRingOffset,
+    tx: XdpRingOffset,
+    fr: XdpRingOffset,
+    cr: XdpRingOffset,
+}
+
+#[repr(C)]
+struct SockaddrXdp {
+    sxdp_family: u16,
+    sxdp_flags: u16,
+    sxdp_ifindex: u32,
+    sxdp_queue_id: u32,
+    sxdp_shared_umem_fd: u32,
+}
+
+#[repr(C)]
+struct XdpDesc {
+    addr: u64,
+    len: u32,
+    options: u32,
+}
+
+#[repr(C)]
+struct MapCreateAttr {
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+    map_flags: u32,
+}
+
+#[repr(C)]
+struct MapElemAttr {
+    map_fd: u32,
+    _pad: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+#[repr(C)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+}
+
+#[repr(C)]
+struct LinkCreateAttr {
+    prog_fd: u32,
+    target_ifindex: u32,
+    attach_type: u32,
+    flags: u32,
+}
+
+fn check(ret: c_int) -> io::Result<c_int> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+fn bpf<T>(cmd: c_int, attr: &mut T) -> io::Result<c_int> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *mut T as *mut c_void,
+            mem::size_of::<T>(),
+        )
+    };
+    check(ret as c_int)
+}
+
+fn setsockopt<T>(fd: c_int, name: c_int, value: &T) -> io::Result<()> {
+    unsafe {
+        check(libc::setsockopt(
+            fd,
+            SOL_XDP,
+            name,
+            value as *const T as *const c_void,
+            mem::size_of::<T>() as libc::socklen_t,
+        ))?;
+    }
+    Ok(())
+}
+
+/// A ring shared with the kernel.
+struct Ring {
+    map: *mut c_void,
+    len: usize,
+    producer: *mut u32,
+    consumer: *mut u32,
+    desc: *mut u8,
+}
+
+impl Ring {
+    fn map<T>(fd: c_int, off: &XdpRingOffset, pgoff: libc::off_t) -> io::Result<Ring> {
+        let len = off.desc as usize + FRAME_NR as usize * mem::size_of::<T>();
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                pgoff,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let base = map as *mut u8;
+        unsafe {
+            Ok(Ring {
+                map,
+                len,
+                producer: base.add(off.producer as usize) as *mut u32,
+                consumer: base.add(off.consumer as usize) as *mut u32,
+                desc: base.add(off.desc as usize),
+            })
+        }
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.map, self.len);
+        }
+    }
+}
+
+/// The counters of an AF_XDP socket. Kernels before 5.9 fill the first
+/// three only.
+#[repr(C)]
+#[derive(Default)]
+struct XdpStatistics {
+    rx_dropped: u64,
+    rx_invalid_descs: u64,
+    tx_invalid_descs: u64,
+    rx_ring_full: u64,
+    rx_fill_ring_empty_descs: u64,
+    tx_ring_empty_descs: u64,
+}
+
+pub struct XdpCapture {
+    fd: c_int,
+    umem: *mut u8,
+    fill: Option<Ring>,
+    completion: Option<Ring>,
+    rx: Option<Ring>,
+    map_fd: c_int,
+    prog_fd: c_int,
+    link_fd: c_int,
+    queue: u32,
+    snaplen: u32,
+    interface: String,
+    counters: InterfaceCounters,
+    received: u64,
+}
+
+unsafe impl Send for XdpCapture {}
+
+impl XdpCapture {
+    pub fn open(opt: &Options) -> io::Result<XdpCapture> {
+        let name = CString::new(opt.interface.as_str())?;
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no such interface"));
+        }
+
+        let program = translate(&filter(opt)?)?;
+
+        let mut capture = XdpCapture {
+            fd: unsafe { check(libc::socket(AF_XDP, libc::SOCK_RAW, 0))? },
+            umem: ptr::null_mut(),
+            fill: None,
+            completion: None,
+            rx: None,
+            map_fd: -1,
+            prog_fd: -1,
+            link_fd: -1,
+            queue: opt.queue,
+            snaplen: opt.snaplen,
+            interface: opt.interface.clone(),
+            counters: InterfaceCounters::read(&opt.interface),
+            received: 0,
+        };
+        let fd = capture.fd;
+
+        let umem = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                UMEM_LEN,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if umem == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        capture.umem = umem as *mut u8;
+
+        setsockopt(
+            fd,
+            XDP_UMEM_REG,
+            &XdpUmemReg {
+                addr: umem as u64,
+                len: UMEM_LEN as u64,
+                chunk_size: FRAME_SIZE,
+                headroom: 0,
+                flags: 0,
+                tx_metadata_len: 0,
+            },
+        )?;
+        setsockopt(fd, XDP_UMEM_FILL_RING, &FRAME_NR)?;
+        setsockopt(fd, XDP_UMEM_COMPLETION_RING, &FRAME_NR)?;
+        setsockopt(fd, XDP_RX_RING, &FRAME_NR)?;
+
+        let mut off = XdpMmapOffsets::default();
+        let mut len = mem::size_of::<XdpMmapOffsets>() as libc::socklen_t;
+        unsafe {
+            check(libc::getsockopt(
+                fd,
+                SOL_XDP,
+                XDP_MMAP_OFFSETS,
+                &mut off as *mut XdpMmapOffsets as *mut c_void,
+                &mut len,
+            ))?;
+        }
+        let fill = Ring::map::<u64>(fd, &off.fr, XDP_UMEM_PGOFF_FILL_RING)?;
+        capture.completion = Some(Ring::map::<u64>(
+            fd,
+            &off.cr,
+            XDP_UMEM_PGOFF_COMPLETION_RING,
+        )?);
+        capture.rx = Some(Ring::map::<XdpDesc>(fd, &off.rx, XDP_PGOFF_RX_RING)?);
+
+        // Hand all the frames to the kernel.
+        unsafe {
+            let addrs = fill.desc as *mut u64;
+            for i in 0..FRAME_NR {
+                *addrs.add(i as usize) = u64::from(i * FRAME_SIZE);
+            }
+            fence(Ordering::Release);
+            ptr::write_volatile(fill.producer, FRAME_NR);
+        }
+        capture.fill = Some(fill);
+
+        let addr = SockaddrXdp {
+            sxdp_family: AF_XDP as u16,
+            sxdp_flags: 0,
+            sxdp_ifindex: index,
+            sxdp_queue_id: opt.queue,
+            sxdp_shared_umem_fd: 0,
+        };
+        // The kernel releases the queue of a closed socket asynchronously, so
+        // it may still be busy right after the previous capture has stopped.
+        let mut retries = 0;
+        loop {
+            let ret = unsafe {
+                libc::bind(
+                    fd,
+                    &addr as *const SockaddrXdp as *const libc::sockaddr,
+                    mem::size_of::<SockaddrXdp>() as libc::socklen_t,
+                )
+            };
+            match check(ret) {
+                Ok(_) => break,
+                Err(ref err)
+                    if err.raw_os_error() == Some(libc::EBUSY) && retries < BIND_RETRIES =>
+                {
+                    retries += 1;
+                    thread::sleep(Duration::from_millis(BIND_RETRY_INTERVAL_MS));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        capture.map_fd = bpf(
+            BPF_MAP_CREATE,
+            &mut MapCreateAttr {
+                map_type: BPF_MAP_TYPE_XSKMAP,
+                key_size: 4,
+                value_size: 4,
+                max_entries: opt.queue + 1,
+                map_flags: 0,
+            },
+        )?;
+        let key = opt.queue;
+        let value = fd as u32;
+        bpf(
+            BPF_MAP_UPDATE_ELEM,
+            &mut MapElemAttr {
+                map_fd: capture.map_fd as u32,
+                _pad: 0,
+                key: &key as *const u32 as u64,
+                value: &value as *const u32 as u64,
+                flags: 0,
+            },
+        )?;
+
+        capture.prog_fd = load(&program.link(capture.map_fd))?;
+
+        // The program is detached when the link is closed.
+        capture.link_fd = bpf(
+            BPF_LINK_CREATE,
+            &mut LinkCreateAttr {
+                prog_fd: capture.prog_fd as u32,
+                target_ifindex: index,
+                attach_type: BPF_XDP,
+                flags: 0,
+            },
+        )?;
+
+        Ok(capture)
+    }
+
+    pub fn link(&self) -> u32 {
+        1
+    }
+
+    pub fn stats(&mut self) -> io::Result<CaptureStats> {
+        let mut stats = XdpStatistics::default();
+        let mut len = mem::size_of::<XdpStatistics>() as libc::socklen_t;
+        unsafe {
+            check(libc::getsockopt(
+                self.fd,
+                SOL_XDP,
+                XDP_STATISTICS,
+                &mut stats as *mut XdpStatistics as *mut c_void,
+                &mut len,
+            ))?;
+        }
+        let dropped = stats.rx_dropped + stats.rx_ring_full;
+        let counters = InterfaceCounters::read(&self.interface).since(&self.counters);
+        Ok(CaptureStats {
+            received: self.received + dropped,
+            dropped,
+            if_dropped: counters.dropped,
+            errors: counters.errors,
+            overruns: stats.rx_fill_ring_empty_descs,
+        })
+    }
+
+    pub fn read<F: FnMut(Packet)>(&mut self, timeout: i32, mut f: F) -> io::Result<()> {
+        let (rx, fill) = match (&self.rx, &self.fill) {
+            (Some(rx), Some(fill)) => (rx, fill),
+            _ => return Ok(()),
+        };
+        let mut cons = unsafe { ptr::read_volatile(rx.consumer) };
+        let mut prod = unsafe { ptr::read_volatile(rx.producer) };
+        if cons == prod {
+            let mut pfd = libc::pollfd {
+                fd: self.fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            unsafe { check(libc::poll(&mut pfd, 1, timeout))? };
+            prod = unsafe { ptr::read_volatile(rx.producer) };
+        }
+        fence(Ordering::Acquire);
+
+        // XDP provides no timestamps, so frames are stamped when received here.
+        let mut ts: libc::timespec = unsafe { mem::zeroed() };
+        unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut ts) };
+
+        let umem = unsafe { slice::from_raw_parts(self.umem, UMEM_LEN) };
+        let mut fill_prod = unsafe { ptr::read_volatile(fill.producer) };
+        while cons != prod {
+            unsafe {
+                let desc = &*(rx.desc as *const XdpDesc).add(slot(cons));
+                let start = desc.addr as usize;
+                let len = desc.len.min(self.snaplen) as usize;
+                f(Packet {
+                    ts_sec: ts.tv_sec as u64,
+                    ts_nsec: ts.tv_nsec as u32,
+                    len: desc.len,
+                    data: &umem[start..start + len],
+                    queue: Some(self.queue),
+                    cpu: metadata(umem, desc.addr),
+                });
+                *(fill.desc as *mut u64).add(slot(fill_prod)) = chunk(desc.addr);
+            }
+            fill_prod = fill_prod.wrapping_add(1);
+            cons = cons.wrapping_add(1);
+            self.received += 1;
+        }
+        fence(Ordering::Release);
+        unsafe {
+            ptr::write_volatile(fill.producer, fill_prod);
+            ptr::write_volatile(rx.consumer, cons);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for XdpCapture {
+    fn drop(&mut self) {
+        self.rx = None;
+        self.fill = None;
+        self.completion = None;
+        unsafe {
+            for fd in &[self.link_fd, self.prog_fd, self.map_fd, self.fd] {
+                if *fd >= 0 {
+                    libc::close(*fd);
+                }
+            }
+            if !self.umem.is_null() {
+                libc::munmap(self.umem as *mut c_void, UMEM_LEN);
+            }
+        }
+    }
+}
+
+/// Returns the classic BPF filter to translate, compiling the expression if
+/// any. XDP always sees Ethernet frames.
+fn filter(opt: &Options) -> io::Result<Vec<Instruction>> {
+    match (&opt.expression, &opt.filter) {
+        (Some(expr), _) => compile::compile(expr, 1, opt.snaplen),
+        (None, Some(filter)) => Ok(filter.clone()),
+        (None, None) => Ok(Vec::new()),
+    }
+}
+
+/// Returns the index of the descriptor at the free-running ring position
+/// `pos`.
+fn slot(pos: u32) -> usize {
+    (pos & (FRAME_NR - 1)) as usize
+}
+
+/// Returns the address of the UMEM chunk containing `addr`.
+fn chunk(addr: u64) -> u64 {
+    addr & !u64::from(FRAME_SIZE - 1)
+}
+
+/// Returns the CPU written by the XDP program in front of the frame at
+/// `addr`, if the metadata fits in the headroom of its chunk.
+fn metadata(umem: &[u8], addr: u64) -> Option<u32> {
+    if addr - chunk(addr) < u64::from(META_SIZE) {
+        return None;
+    }
+    let addr = addr as usize;
+    let read = |offset: usize| {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&umem[addr - offset..addr - offset + 4]);
+        u32::from_ne_bytes(bytes)
+    };
+    if read(4) == META_MAGIC {
+        Some(read(8))
+    } else {
+        None
+    }
+}
+
+fn load(insns: &[u64]) -> io::Result<c_int> {
+    let license = CString::new("Dual MIT/GPL").unwrap();
+    let mut attr = ProgLoadAttr {
+        prog_type: BPF_PROG_TYPE_XDP,
+        insn_cnt: insns.len() as u32,
+        insns: insns.as_ptr() as u64,
+        license: license.as_ptr() as u64,
+        log_level: 0,
+        log_size: 0,
+        log_buf: 0,
+        kern_version: 0,
+        prog_flags: 0,
+    };
+    match bpf(BPF_PROG_LOAD, &mut attr) {
+        Ok(fd) => Ok(fd),
+        Err(err) => {
+            // Load again to get the verifier log.
+            let mut log = vec![0u8; LOG_SIZE];
+            attr.log_level = 1;
+            attr.log_size = LOG_SIZE as u32;
+            attr.log_buf = log.as_mut_ptr() as u64;
+            if bpf(BPF_PROG_LOAD, &mut attr).is_ok() {
+                return Err(err);
+            }
+            let len = log.iter().position(|b| *b == 0).unwrap_or(LOG_SIZE);
+            let log = String::from_utf8_lossy(&log[..len]);
+            let last = log.lines().rev().find(|l| !l.is_empty()).unwrap_or("");
+            Err(io::Error::new(
+                err.kind(),
+                format!("failed to load XDP program: {}: {}", err, last),
+            ))
+        }
+    }
+}
+
+// eBPF instruction classes and fields.
+const LD: u8 = 0x00;
+const LDX: u8 = 0x01;
+const ST: u8 = 0x02;
+const STX: u8 = 0x03;
+const ALU: u8 = 0x04;
+const JMP: u8 = 0x05;
+const JMP32: u8 = 0x06;
+const ALU64: u8 = 0x07;
+
+const W: u8 = 0x00;
+const H: u8 = 0x08;
+const B: u8 = 0x10;
+const DW: u8 = 0x18;
+const IMM: u8 = 0x00;
+const MEM: u8 = 0x60;
+const K: u8 = 0x00;
+const X: u8 = 0x08;
+
+const ADD: u8 = 0x00;
+const DIV: u8 = 0x30;
+const AND: u8 = 0x50;
+const LSH: u8 = 0x60;
+const NEG: u8 = 0x80;
+const MOD: u8 = 0x90;
+const MOV: u8 = 0xb0;
+const END: u8 = 0xd0;
+const TO_BE: u8 = 0x08;
+
+const JA: u8 = 0x00;
+const JEQ: u8 = 0x10;
+const JGT: u8 = 0x20;
+const JNE: u8 = 0x50;
+const CALL: u8 = 0x80;
+const EXIT: u8 = 0x90;
+
+const FUNC_GET_SMP_PROCESSOR_ID: i32 = 8;
+const FUNC_REDIRECT_MAP: i32 = 51;
+const FUNC_XDP_ADJUST_META: i32 = 54;
+const FUNC_XDP_GET_BUFF_LEN: i32 = 188;
+
+const XDP_PASS: i32 = 2;
+
+const XDP_MD_DATA: i16 = 0;
+const XDP_MD_DATA_END: i16 = 4;
+const XDP_MD_DATA_META: i16 = 8;
+const XDP_MD_RX_QUEUE_INDEX: i16 = 16;
+
+const R0: u8 = 0;
+const R1: u8 = 1;
+const R2: u8 = 2;
+const R3: u8 = 3;
+
+// The accumulator and index registers of classic BPF live in caller-saved
+// registers, since no helper is called while filtering.
+const A: u8 = R2;
+const IDX: u8 = R3;
+const TMP: u8 = 4;
+const OFF: u8 = 5;
+const CTX: u8 = 6;
+const LEN: u8 = 7;
+const DATA: u8 = 8;
+const DATA_END: u8 = 9;
+const FP: u8 = 10;
+
+const SCRATCH_SIZE: u32 = 16;
+const MAX_PACKET_OFFSET: u32 = 0xffff;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Insn(usize),
+    Accept,
+    Reject,
+}
+
+/// An eBPF program whose jumps are resolved at the end.
+struct Program {
+    insns: Vec<(u8, u8, u8, i16, i32)>,
+    jumps: Vec<(usize, Target)>,
+    starts: Vec<usize>,
+    map_insn: usize,
+}
+
+impl Program {
+    fn emit(&mut self, code: u8, dst: u8, src: u8, off: i16, imm: i32) {
+        self.insns.push((code, dst, src, off, imm));
+    }
+
+    fn jump(&mut self, code: u8, dst: u8, src: u8, imm: i32, target: Target) {
+        self.jumps.push((self.insns.len(), target));
+        self.emit(code, dst, src, 0, imm);
+    }
+
+    /// Loads a big-endian value at `R1` into `dst`, rejecting the frame if it
+    /// is out of bounds.
+    fn load_packet(&mut self, dst: u8, size: u16) {
+        let (bytes, size) = match size & 0x18 {
+            0x00 => (4, W),
+            0x08 => (2, H),
+            _ => (1, B),
+        };
+        self.emit(ALU64 | MOV | X, TMP, R1, 0, 0);
+        self.emit(ALU64 | ADD | K, TMP, 0, 0, bytes);
+        self.jump(JMP | JGT | X, TMP, DATA_END, 0, Target::Reject);
+        self.emit(LDX | MEM | size, dst, R1, 0, 0);
+        if bytes > 1 {
+            self.emit(ALU | END | TO_BE, dst, 0, 0, bytes * 8);
+        }
+    }
+
+    fn load_abs(&mut self, dst: u8, size: u16, k: u32) {
+        if k > MAX_PACKET_OFFSET {
+            self.jump(JMP | JA, 0, 0, 0, Target::Reject);
+            return;
+        }
+        self.emit(ALU64 | MOV | X, R1, DATA, 0, 0);
+        self.emit(ALU64 | ADD | K, R1, 0, 0, k as i32);
+        self.load_packet(dst, size);
+    }
+
+    fn load_ind(&mut self, dst: u8, size: u16, k: u32) {
+        if k > MAX_PACKET_OFFSET {
+            self.jump(JMP | JA, 0, 0, 0, Target::Reject);
+            return;
+        }
+        // Bound the offset so that the verifier accepts the access.
+        self.emit(ALU | MOV | X, OFF, IDX, 0, 0);
+        self.emit(ALU64 | ADD | K, OFF, 0, 0, k as i32);
+        self.jump(
+            JMP | JGT | K,
+            OFF,
+            0,
+            MAX_PACKET_OFFSET as i32,
+            Target::Reject,
+        );
+        self.emit(ALU64 | MOV | X, R1, DATA, 0, 0);
+        self.emit(ALU64 | ADD | X, R1, OFF, 0, 0);
+        self.load_packet(dst, size);
+    }
+
+    fn scratch(k: u32) -> io::Result<i16> {
+        if k < SCRATCH_SIZE {
+            Ok(-4 * (k as i16 + 1))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid scratch memory index",
+            ))
+        }
+    }
+
+    /// Resolves the jumps and the map reference into raw instructions.
+    fn link(&self, map_fd: c_int) -> Vec<u64> {
+        let accept = self.starts[self.starts.len() - 1];
+        let reject = self.starts[self.starts.len() - 2];
+        let mut insns = self.insns.clone();
+        for (index, target) in &self.jumps {
+            let pos = match target {
+                Target::Insn(i) => self.starts[*i],
+                Target::Accept => accept,
+                Target::Reject => reject,
+            };
+            insns[*index].3 = (pos as isize - *index as isize - 1) as i16;
+        }
+        insns[self.map_insn].4 = map_fd;
+
+        // The verifier rejects unreachable instructions, which are left behind
+        // by unconditional jumps in the translation.
+        let mut reachable = vec![false; insns.len()];
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            if i >= insns.len() || reachable[i] {
+                continue;
+            }
+            reachable[i] = true;
+            let (code, _, _, off, _) = insns[i];
+            if code == LD | IMM | DW {
+                reachable[i + 1] = true;
+                stack.push(i + 2);
+                continue;
+            }
+            if code == JMP | EXIT {
+                continue;
+            }
+            if is_jump(code) {
+                stack.push((i as isize + 1 + off as isize) as usize);
+                if code == JMP | JA {
+                    continue;
+                }
+            }
+            stack.push(i + 1);
+        }
+
+        let mut pos = Vec::with_capacity(insns.len() + 1);
+        let mut len = 0;
+        for r in &reachable {
+            pos.push(len);
+            if *r {
+                len += 1;
+            }
+        }
+        pos.push(len);
+
+        insns
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| reachable[*i])
+            .map(|(i, &(code, dst, src, off, imm))| {
+                let off = if is_jump(code) {
+                    let target = (i as isize + 1 + off as isize) as usize;
+                    (pos[target] as isize - pos[i] as isize - 1) as i16
+                } else {
+                    off
+                };
+                u64::from(code)
+                    | u64::from(dst & 0xf | (src & 0xf) << 4) << 8
+                    | u64::from(off as u16) << 16
+                    | u64::from(imm as u32) << 32
+            })
+            .collect()
+    }
+}
+
+fn is_jump(code: u8) -> bool {
+    let class = code & 0x07;
+    let op = code & 0xf0;
+    (class == JMP || class == JMP32) && op != CALL && op != EXIT
+}
+
+/// Translates a classic BPF filter into an XDP program.
+fn translate(filter: &[Instruction]) -> io::Result<Program> {
+    let mut p = Program {
+        insns: Vec::new(),
+        jumps: Vec::new(),
+        starts: Vec::new(),
+        map_insn: 0,
+    };
+
+    // Store the CPU and a magic number in the metadata area.
+    p.emit(ALU64 | MOV | X, CTX, R1, 0, 0);
+    p.emit(JMP | CALL, 0, 0, 0, FUNC_GET_SMP_PROCESSOR_ID);
+    p.emit(ALU64 | MOV | X, LEN, R0, 0, 0);
+    p.emit(ALU64 | MOV | X, R1, CTX, 0, 0);
+    p.emit(ALU64 | MOV | K, R2, 0, 0, -(META_SIZE as i32));
+    p.emit(JMP | CALL, 0, 0, 0, FUNC_XDP_ADJUST_META);
+    p.emit(JMP | JNE | K, R0, 0, 7, 0);
+    p.emit(LDX | MEM | W, R2, CTX, XDP_MD_DATA, 0);
+    p.emit(LDX | MEM | W, R3, CTX, XDP_MD_DATA_META, 0);
+    p.emit(ALU64 | MOV | X, TMP, R3, 0, 0);
+    p.emit(ALU64 | ADD | K, TMP, 0, 0, META_SIZE as i32);
+    p.emit(JMP | JGT | X, TMP, R2, 2, 0);
+    p.emit(STX | MEM | W, R3, LEN, 0, 0);
+    p.emit(ST | MEM | W, R3, 0, 4, META_MAGIC as i32);
+
+    p.emit(ALU64 | MOV | X, R1, CTX, 0, 0);
+    p.emit(JMP | CALL, 0, 0, 0, FUNC_XDP_GET_BUFF_LEN);
+    p.emit(ALU64 | MOV | X, LEN, R0, 0, 0);
+    p.emit(LDX | MEM | W, DATA, CTX, XDP_MD_DATA, 0);
+    p.emit(LDX | MEM | W, DATA_END, CTX, XDP_MD_DATA_END, 0);
+    p.emit(ALU | MOV | K, A, 0, 0, 0);
+    p.emit(ALU | MOV | K, IDX, 0, 0, 0);
+    for i in 0..SCRATCH_SIZE {
+        p.emit(ST | MEM | W, FP, 0, Program::scratch(i)?, 0);
+    }
+
+    let target = |i: usize, rel: u32| -> io::Result<Target> {
+        let t = i + 1 + rel as usize;
+        if t < filter.len() {
+            Ok(Target::Insn(t))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "jump out of the filter",
+            ))
+        }
+    };
+
+    if filter.is_empty() {
+        p.jump(JMP | JA, 0, 0, 0, Target::Accept);
+    }
+    for (i, insn) in filter.iter().enumerate() {
+        p.starts.push(p.insns.len());
+        let code = insn.code;
+        let k = insn.k;
+        let op = (code & 0xf0) as u8;
+        let src = (code & 0x08) as u8;
+        match code & 0x07 {
+            0x00 | 0x01 => {
+                let dst = if code & 0x07 == 0x00 { A } else { IDX };
+                match code & 0xe0 {
+                    0x00 => p.emit(ALU | MOV | K, dst, 0, 0, k as i32),
+                    0x20 => p.load_abs(dst, code, k),
+                    0x40 => p.load_ind(dst, code, k),
+                    0x60 => p.emit(LDX | MEM | W, dst, FP, Program::scratch(k)?, 0),
+                    0x80 => p.emit(ALU | MOV | X, dst, LEN, 0, 0),
+                    0xa0 => {
+                        p.load_abs(IDX, 0x10, k);
+                        p.emit(ALU | AND | K, IDX, 0, 0, 0xf);
+                        p.emit(ALU | LSH | K, IDX, 0, 0, 2);
+                    }
+                    _ => return Err(unsupported(code)),
+                }
+            }
+            0x02 => p.emit(STX | MEM | W, FP, A, Program::scratch(k)?, 0),
+            0x03 => p.emit(STX | MEM | W, FP, IDX, Program::scratch(k)?, 0),
+            0x04 => {
+                if op == NEG {
+                    p.emit(ALU | NEG, A, 0, 0, 0);
+                } else if (op == DIV || op == MOD) && src == X {
+                    p.jump(JMP32 | JEQ | K, IDX, 0, 0, Target::Reject);
+                    p.emit(ALU | op | X, A, IDX, 0, 0);
+                } else if (op == DIV || op == MOD) && k == 0 {
+                    p.jump(JMP | JA, 0, 0, 0, Target::Reject);
+                } else if op > 0xa0 {
+                    return Err(unsupported(code));
+                } else if src == X {
+                    p.emit(ALU | op | X, A, IDX, 0, 0);
+                } else {
+                    p.emit(ALU | op | K, A, 0, 0, k as i32);
+                }
+            }
+            0x05 => {
+                if op == JA {
+                    let t = target(i, k)?;
+                    p.jump(JMP | JA, 0, 0, 0, t);
+                } else if op > 0x40 {
+                    return Err(unsupported(code));
+                } else {
+                    let jt = target(i, u32::from(insn.jt))?;
+                    let jf = target(i, u32::from(insn.jf))?;
+                    if src == X {
+                        p.jump(JMP32 | op | X, A, IDX, 0, jt);
+                    } else {
+                        p.jump(JMP32 | op | K, A, 0, k as i32, jt);
+                    }
+                    if insn.jf > 0 {
+                        p.jump(JMP | JA, 0, 0, 0, jf);
+                    }
+                }
+            }
+            0x06 => {
+                if code & 0x18 == 0x10 {
+                    p.jump(JMP32 | JEQ | K, A, 0, 0, Target::Reject);
+                    p.jump(JMP | JA, 0, 0, 0, Target::Accept);
+                } else if k == 0 {
+                    p.jump(JMP | JA, 0, 0, 0, Target::Reject);
+                } else {
+                    p.jump(JMP | JA, 0, 0, 0, Target::Accept);
+                }
+            }
+            _ => match code {
+                0x07 => p.emit(ALU | MOV | X, IDX, A, 0, 0),
+                0x87 => p.emit(ALU | MOV | X, A, IDX, 0, 0),
+                _ => return Err(unsupported(code)),
+            },
+        }
+    }
+    if !filter.is_empty() {
+        p.jump(JMP | JA, 0, 0, 0, Target::Reject);
+    }
+
+    p.starts.push(p.insns.len());
+    p.emit(ALU64 | MOV | K, R0, 0, 0, XDP_PASS);
+    p.emit(JMP | EXIT, 0, 0, 0, 0);
+
+    p.starts.push(p.insns.len());
+    p.map_insn = p.insns.len();
+    p.emit(LD | IMM | DW, R1, BPF_PSEUDO_MAP_FD, 0, 0);
+    p.emit(0, 0, 0, 0, 0);
+    p.emit(LDX | MEM | W, R2, CTX, XDP_MD_RX_QUEUE_INDEX, 0);
+    p.emit(ALU64 | MOV | K, R3, 0, 0, XDP_PASS);
+    p.emit(JMP | CALL, 0, 0, 0, FUNC_REDIRECT_MAP);
+    p.emit(JMP | EXIT, 0, 0, 0, 0);
+    Ok(p)
+}
+
+fn unsupported(code: u16) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("unsupported BPF instruction: {:#x}", code),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    fn options(json: &str) -> Options {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn insn(code: u16, jt: u8, jf: u8, k: u32) -> Instruction {
+        Instruction { code, jt, jf, k }
+    }
+
+    /// Translates `filter` and links it to a map at the fd 42.
+    fn link(filter: &[Instruction]) -> Vec<u64> {
+        translate(filter).unwrap().link(42)
+    }
+
+    fn has(insns: &[u64], code: u8, dst: u8, imm: i32) -> bool {
+        insns.iter().any(|insn| {
+            *insn as u8 == code && (insn >> 8) as u8 & 0xf == dst && (insn >> 32) as i32 == imm
+        })
+    }
+
+    /// Returns true if the program may redirect frames to the socket.
+    fn redirects(insns: &[u64]) -> bool {
+        has(insns, JMP | CALL, 0, FUNC_REDIRECT_MAP)
+    }
+
+    /// Returns true if the program may pass frames to the network stack.
+    fn passes(insns: &[u64]) -> bool {
+        has(insns, ALU64 | MOV | K, R0, XDP_PASS)
+    }
+
+    #[test]
+    fn ring_index() {
+        assert_eq!(slot(0), 0);
+        assert_eq!(slot(FRAME_NR - 1), FRAME_NR as usize - 1);
+        assert_eq!(slot(FRAME_NR), 0);
+
+        // The producer and consumer positions wrap around at 2^32.
+        let mut pos = u32::max_value() - 1;
+        let mut slots = Vec::new();
+        while pos != 2 {
+            slots.push(slot(pos));
+            pos = pos.wrapping_add(1);
+        }
+        assert_eq!(slots, [FRAME_NR as usize - 2, FRAME_NR as usize - 1, 0, 1]);
+
+        let size = u64::from(FRAME_SIZE);
+        assert_eq!(chunk(0), 0);
+        assert_eq!(chunk(size - 1), 0);
+        assert_eq!(chunk(size * 3 + 256), size * 3);
+        assert_eq!(
+            chunk(size * u64::from(FRAME_NR - 1)),
+            size * u64::from(FRAME_NR - 1)
+        );
+    }
+
+    #[test]
+    fn frame_metadata() {
+        let mut umem = vec![0; FRAME_SIZE as usize * 2];
+        let addr = FRAME_SIZE as usize + 256;
+        umem[addr - 8..addr - 4].copy_from_slice(&3u32.to_ne_bytes());
+        umem[addr - 4..addr].copy_from_slice(&META_MAGIC.to_ne_bytes());
+        assert_eq!(metadata(&umem, addr as u64), Some(3));
+
+        // No metadata in front of the frame.
+        assert_eq!(metadata(&umem, addr as u64 + 8), None);
+
+        // The metadata would start in the previous chunk.
+        let addr = FRAME_SIZE as usize + 4;
+        umem[addr - 4..addr].copy_from_slice(&META_MAGIC.to_ne_bytes());
+        assert_eq!(metadata(&umem, addr as u64), None);
+    }
+
+    #[test]
+    fn handoff() {
+        let reject = vec![insn(0x06, 0, 0, 0)];
+
+        // The expression takes precedence and is compiled for Ethernet.
+        let opt = options(
+            r#"{"interface": "eth0", "expression": "tcp",
+                "filter": [{"code": 6, "jt": 0, "jf": 0, "k": 0}]}"#,
+        );
+        assert_eq!(
+            filter(&opt).unwrap(),
+            compile::compile("tcp", 1, 2048).unwrap()
+        );
+
+        let opt =
+            options(r#"{"interface": "eth0", "filter": [{"code": 6, "jt": 0, "jf": 0, "k": 0}]}"#);
+        assert_eq!(filter(&opt).unwrap(), reject);
+
+        let opt = options(r#"{"interface": "eth0"}"#);
+        assert_eq!(filter(&opt).unwrap(), []);
+
+        let opt = options(r#"{"interface": "eth0", "expression": "tcp port"}"#);
+        assert_eq!(
+            filter(&opt).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn program() {
+        let accept = link(&[]);
+        assert!(redirects(&accept));
+        assert!(!passes(&accept));
+
+        let reject = link(&[insn(0x06, 0, 0, 0)]);
+        assert!(!redirects(&reject));
+        assert!(passes(&reject));
+
+        let tcp = link(&compile::compile("tcp", 1, 2048).unwrap());
+        assert!(redirects(&tcp));
+        assert!(passes(&tcp));
+
+        for insns in &[accept, reject, tcp] {
+            // The map fd is referenced by the redirect.
+            let map = insns.iter().position(|insn| *insn as u8 == LD | IMM | DW);
+            if let Some(map) = map {
+                assert_eq!((insns[map] >> 12) as u8 & 0xf, BPF_PSEUDO_MAP_FD);
+                assert_eq!((insns[map] >> 32) as i32, 42);
+                assert_eq!(insns[map + 1], 0);
+            }
+            // Every jump stays in the program.
+            for (i, insn) in insns.iter().enumerate() {
+                if is_jump(*insn as u8) {
+                    let target = i as isize + 1 + isize::from((insn >> 16) as i16);
+                    assert!(target >= 0 && (target as usize) < insns.len());
+                }
+            }
+            assert_eq!(*insns.last().unwrap() as u8, JMP | EXIT);
+        }
+    }
+
+    #[test]
+    fn invalid_program() {
+        // A jump out of the filter.
+        assert!(translate(&[insn(0x05, 0, 0, 1)]).is_err());
+        assert!(translate(&[insn(0x15, 1, 0, 0), insn(0x06, 0, 0, 0)]).is_err());
+        // A scratch memory index out of range.
+        assert!(translate(&[insn(0x02, 0, 0, 16), insn(0x06, 0, 0, 0)]).is_err());
+        // An ALU operation without a classic BPF counterpart.
+        assert!(translate(&[insn(0xc4, 0, 0, 0), insn(0x06, 0, 0, 0)]).is_err());
+    }
+}
@@ -24,17 +24,47 @@ class PcapView {
 
   async create (ifs, link) {
     const sess = await genet.session.create()
-    const args = ['capture', ifs]
     const snaplen = genet.config.get('@genet/pcap.snapshotLength')
+    const expression = genet.config.get('@genet/pcap.captureFilter', '')
+    let name = 'app.genet.reader.live'
+    let stream = {
+      interface: ifs,
+      promisc: genet.config.get('@genet/pcap.promiscuous', true),
+    }
     if (Number.isInteger(snaplen)) {
-      args.push('-l', `${snaplen}`)
+      stream.snaplen = snaplen
     }
-    const stream = {
-      cmd: cli,
-      args,
-      link,
+    if (expression) {
+      stream.expression = expression
+    }
+    if (genet.config.get('@genet/pcap.captureBackend', 'packet') === 'xdp') {
+      stream.backend = 'xdp'
+      stream.queue = genet.config.get('@genet/pcap.xdpQueue', 0)
+    }
+    const channels = genet.config.get('@genet/pcap.wifiChannels', '')
+      .split(',')
+      .map((ch) => Number.parseInt(ch, 10))
+      .filter((ch) => Number.isInteger(ch))
+    const monitor = genet.config.get('@genet/pcap.monitorMode', false)
+    if (monitor || channels.length > 0) {
+      stream.wireless = {
+        monitor,
+        channels,
+        dwell: genet.config.get('@genet/pcap.channelDwell', 250),
+      }
+    }
+    if (process.platform === 'win32') {
+      const args = ['capture', ifs]
+      if (Number.isInteger(snaplen)) {
+        args.push('-l', `${snaplen}`)
+      }
+      name = 'app.genet.reader.pcap'
+      stream = {
+        cmd: cli,
+        args,
+        link,
+      }
     }
-    const name = 'app.genet.reader.pcap'
     genet.resumer.set('core:session:stream-reader', {
       name,
       stream,
@@ -45,6 +75,29 @@ class PcapView {
     genet.action.emit('core:session:created', sess)
   }
 
+  async createRemote (host, ifs) {
+    const sess = await genet.session.create()
+    const name = 'app.genet.reader.remote'
+    const snaplen = genet.config.get('@genet/pcap.snapshotLength')
+    const stream = {
+      host,
+      interface: ifs,
+      filter: genet.config.get('@genet/pcap.captureFilter', ''),
+    }
+    if (Number.isInteger(snaplen)) {
+      stream.snaplen = snaplen
+    }
+    genet.resumer.set('core:session:stream-reader', {
+      name,
+      stream,
+    })
+    sess.regiterStreamReader(name, stream)
+    sess.startStream()
+    genet.workspace.set('_.pcap.remoteHost', host)
+    genet.workspace.set('_.pcap.remoteInterface', ifs)
+    genet.action.emit('core:session:created', sess)
+  }
+
   view (vnode) {
     const ifs = genet.workspace.get('_.pcap.interface')
     if (!this.permission) {
@@ -83,6 +136,34 @@ class PcapView {
               vnode.attrs.callback()
             },
           })
+        ]),
+        m('li', [
+          m('input', {
+            type: 'text',
+            name: 'remote-host',
+            placeholder: 'user@host',
+            value: genet.workspace.get('_.pcap.remoteHost', ''),
+          }),
+          m('input', {
+            type: 'text',
+            name: 'remote-ifs',
+            placeholder: 'Interface',
+            value: genet.workspace.get('_.pcap.remoteInterface', ''),
+          })
+        ]),
+        m('li', [
+          m('input', {
+            type: 'button',
+            value: 'Start Remote Capture (SSH)',
+            onclick: () => {
+              const host = vnode.dom.querySelector('[name=remote-host]').value
+              const ifs = vnode.dom.querySelector('[name=remote-ifs]').value
+              if (host && ifs) {
+                this.createRemote(host, ifs)
+                vnode.attrs.callback()
+              }
+            },
+          })
         ])
       ])
     ])
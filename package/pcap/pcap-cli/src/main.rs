@@ -5,13 +5,91 @@ extern crate pcap;
 extern crate serde_json;
 
 use clap::{App, Arg, SubCommand};
-use pcap::Pcap;
+use pcap::{Header, Pcap};
 use std::{
-    io::{stdout, Write},
+    io::{stdin, stdout, BufRead, Read, Write},
     sync::mpsc::RecvTimeoutError,
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
 };
 
+/// Controls the interval between injected frames.
+enum Pace {
+    /// Follows the original timestamps, scaled by the multiplier.
+    Timestamp(f64),
+    Pps(f64),
+    Mbps(f64),
+    TopSpeed,
+}
+
+impl Pace {
+    /// Returns the offset of the next frame from the start of the replay.
+    fn offset(&self, ts: f64, frames: u64, bytes: u64) -> Option<f64> {
+        match self {
+            Pace::Timestamp(multiplier) => Some(ts / multiplier),
+            Pace::Pps(pps) => Some(frames as f64 / pps),
+            Pace::Mbps(mbps) => Some(bytes as f64 * 8.0 / (mbps * 1_000_000.0)),
+            Pace::TopSpeed => None,
+        }
+    }
+}
+
+fn replay(pcap: &Pcap, device: &str, pace: &Pace) {
+    let injector = match pcap.open_injector(device) {
+        Ok(i) => i,
+        Err(e) => {
+            eprintln!("error: {:?}", e);
+            std::process::exit(1)
+        }
+    };
+
+    let stdin = stdin();
+    let mut input = stdin.lock();
+    let start = Instant::now();
+    let mut first_ts = None;
+    let mut frames = 0;
+    let mut bytes = 0;
+    loop {
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            _ => {}
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let header: Header = match serde_json::from_str(line) {
+            Ok(h) => h,
+            Err(e) => {
+                eprintln!("error: {:?}", e);
+                std::process::exit(1)
+            }
+        };
+        let mut data = vec![0u8; header.datalen as usize];
+        if input.read_exact(&mut data).is_err() {
+            return;
+        }
+
+        let ts = f64::from(header.ts_sec) + f64::from(header.ts_usec) / 1_000_000f64;
+        let ts = ts - *first_ts.get_or_insert(ts);
+        if let Some(offset) = pace.offset(ts, frames, bytes) {
+            let target = start + Duration::from_micros((offset.max(0.0) * 1_000_000f64) as u64);
+            let now = Instant::now();
+            if target > now {
+                thread::sleep(target - now);
+            }
+        }
+
+        if let Err(e) = injector.send(&data) {
+            eprintln!("error: {:?}", e);
+            std::process::exit(1)
+        }
+        frames += 1;
+        bytes += data.len() as u64;
+    }
+}
+
 fn main() {
     let capture = SubCommand::with_name("capture")
         .arg(
@@ -29,6 +107,37 @@ fn main() {
 
     let status = SubCommand::with_name("devices");
 
+    let inject = SubCommand::with_name("inject")
+        .arg(
+            Arg::with_name("DEVICE")
+                .help("Sets the output interface to use")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("multiplier")
+                .short("x")
+                .help("Replays frames at the original pace scaled by the factor")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("pps")
+                .short("p")
+                .help("Replays frames at the given packets per second")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("mbps")
+                .short("M")
+                .help("Replays frames at the given megabits per second")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("topspeed")
+                .short("T")
+                .help("Replays frames as fast as possible"),
+        );
+
     let matches = App::new("pcap-cli")
         .arg(
             Arg::with_name("timeout")
@@ -38,6 +147,7 @@ fn main() {
         )
         .subcommand(capture)
         .subcommand(status)
+        .subcommand(inject)
         .get_matches();
 
     let timeout: u64 = matches.value_of("timeout").unwrap().parse().unwrap_or(1000);
@@ -57,6 +167,25 @@ fn main() {
         println!();
     }
 
+    if let Some(matches) = matches.subcommand_matches("inject") {
+        let value = |name| {
+            matches
+                .value_of(name)
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| *v > 0.0)
+        };
+        let pace = if matches.is_present("topspeed") {
+            Pace::TopSpeed
+        } else if let Some(pps) = value("pps") {
+            Pace::Pps(pps)
+        } else if let Some(mbps) = value("mbps") {
+            Pace::Mbps(mbps)
+        } else {
+            Pace::Timestamp(value("multiplier").unwrap_or(1.0))
+        };
+        replay(&pcap, matches.value_of("DEVICE").unwrap(), &pace);
+    }
+
     if let Some(matches) = matches.subcommand_matches("capture") {
         let snaplen: u32 = matches
             .value_of("snaplen")
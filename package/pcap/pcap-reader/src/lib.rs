@@ -6,7 +6,7 @@ extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
 
-use genet_sdk::{prelude::*, reader::*};
+use genet_sdk::{layer::FrameMetadata, prelude::*, reader::*};
 use pcap::Header;
 
 use std::{
@@ -45,6 +45,7 @@ impl Reader for PcapReader {
             child,
             reader,
             link_class,
+            frame: FrameMetadata::new(arg.link),
         }))
     }
 
@@ -60,10 +61,11 @@ struct PcapWorker {
     child: Child,
     reader: BufReader<ChildStdout>,
     link_class: Fixed<LayerClass>,
+    frame: FrameMetadata,
 }
 
 impl Worker for PcapWorker {
-    fn read(&mut self) -> Result<Vec<Layer>> {
+    fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
         let mut header = String::new();
         self.reader.read_line(&mut header)?;
         let header = header.trim();
@@ -75,6 +77,7 @@ impl Worker for PcapWorker {
         self.reader.read_exact(&mut data)?;
         let payload = ByteSlice::from(data);
         let mut layer = Layer::new(self.link_class.clone(), payload);
+        layer.set_frame_metadata(self.frame);
         layer.add_attr(attr!(
             &LENGTH_CLASS,
             value: u64::from(header.actlen)
@@ -32,6 +32,7 @@ pub enum Error {
     DLLNotFound,
     DLLFuncNotFound,
     OpenFailed(String),
+    SendFailed(String),
 }
 
 pub type FrameReceiver = Receiver<(Header, Box<[u8]>)>;
@@ -144,6 +145,28 @@ impl Pcap {
         Ok(recv)
     }
 
+    pub fn open_injector(&self, ifs: &str) -> Result<Injector, Error> {
+        use std::ffi::CString;
+        let ifs = CString::new(ifs).unwrap();
+        let errbuf = [0u8; PCAP_ERRBUF_SIZE];
+        let pcap = unsafe {
+            (self.syms.pcap_open_live)(ifs.as_ptr(), 65535, 0, 0, errbuf.as_ptr() as *mut c_char)
+        };
+        if pcap.is_null() {
+            let mut msg = String::new();
+            if let Some(pos) = errbuf.iter().position(|c| *c == 0) {
+                if let Ok(s) = String::from_utf8(errbuf[..pos].to_vec()) {
+                    msg = s;
+                }
+            }
+            return Err(Error::OpenFailed(msg));
+        }
+        Ok(Injector {
+            pcap,
+            syms: self.syms.clone(),
+        })
+    }
+
     pub fn devices(&self) -> Option<Vec<Device>> {
         use ffi::*;
         use std::ptr;
@@ -199,6 +222,33 @@ impl Drop for Pcap {
     }
 }
 
+/// A handle to transmit raw frames on an interface.
+#[derive(Debug)]
+pub struct Injector {
+    pcap: *mut ffi::Pcap,
+    syms: ffi::Symbols,
+}
+
+impl Injector {
+    pub fn send(&self, data: &[u8]) -> Result<(), Error> {
+        unsafe {
+            if (self.syms.pcap_sendpacket)(self.pcap, data.as_ptr(), data.len() as i32) < 0 {
+                let msg = ffi::getstr((self.syms.pcap_geterr)(self.pcap));
+                return Err(Error::SendFailed(msg));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Injector {
+    fn drop(&mut self) {
+        unsafe {
+            (self.syms.pcap_close)(self.pcap);
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Device {
     id: String,
@@ -510,6 +560,9 @@ mod ffi {
         ) -> c_int,
         pub pcap_breakloop: unsafe extern "C" fn(pcap: *mut Pcap),
         pub pcap_close: unsafe extern "C" fn(pcap: *mut Pcap),
+        pub pcap_sendpacket:
+            unsafe extern "C" fn(pcap: *mut Pcap, buf: *const c_uchar, size: c_int) -> c_int,
+        pub pcap_geterr: unsafe extern "C" fn(pcap: *mut Pcap) -> *mut c_char,
     }
 
     impl Symbols {
@@ -524,6 +577,8 @@ mod ffi {
                 pcap_loop,
                 pcap_breakloop,
                 pcap_close,
+                pcap_sendpacket,
+                pcap_geterr,
             })
         }
 
@@ -541,6 +596,8 @@ mod ffi {
             let pcap_loop;
             let pcap_breakloop;
             let pcap_close;
+            let pcap_sendpacket;
+            let pcap_geterr;
 
             {
                 let pcap_findalldevs_: libloading::Symbol<
@@ -575,6 +632,16 @@ mod ffi {
                 let pcap_close_: libloading::Symbol<
                     unsafe extern "C" fn(pcap: *mut Pcap),
                 >;
+                let pcap_sendpacket_: libloading::Symbol<
+                    unsafe extern "C" fn(
+                        pcap: *mut Pcap,
+                        buf: *const c_uchar,
+                        size: c_int,
+                    ) -> c_int,
+                >;
+                let pcap_geterr_: libloading::Symbol<
+                    unsafe extern "C" fn(pcap: *mut Pcap) -> *mut c_char,
+                >;
 
                 unsafe {
                     pcap_findalldevs_ = lib
@@ -598,6 +665,12 @@ mod ffi {
                     pcap_breakloop_ = lib
                         .get(b"pcap_breakloop")
                         .map_err(|_| super::Error::DLLFuncNotFound)?;
+                    pcap_sendpacket_ = lib
+                        .get(b"pcap_sendpacket")
+                        .map_err(|_| super::Error::DLLFuncNotFound)?;
+                    pcap_geterr_ = lib
+                        .get(b"pcap_geterr")
+                        .map_err(|_| super::Error::DLLFuncNotFound)?;
                 }
 
                 pcap_findalldevs = *pcap_findalldevs_.deref();
@@ -607,6 +680,8 @@ mod ffi {
                 pcap_loop = *pcap_loop_.deref();
                 pcap_breakloop = *pcap_breakloop_.deref();
                 pcap_close = *pcap_close_.deref();
+                pcap_sendpacket = *pcap_sendpacket_.deref();
+                pcap_geterr = *pcap_geterr_.deref();
             }
 
             Ok(Symbols {
@@ -618,6 +693,8 @@ mod ffi {
                 pcap_loop,
                 pcap_breakloop,
                 pcap_close,
+                pcap_sendpacket,
+                pcap_geterr,
             })
         }
     }
@@ -676,5 +753,7 @@ mod ffi {
         ) -> c_int;
         fn pcap_breakloop(pcap: *mut Pcap);
         fn pcap_close(pcap: *mut Pcap);
+        fn pcap_sendpacket(pcap: *mut Pcap, buf: *const c_uchar, size: c_int) -> c_int;
+        fn pcap_geterr(pcap: *mut Pcap) -> *mut c_char;
     }
 }
@@ -0,0 +1,425 @@
+extern crate byteorder;
+extern crate genet_sdk;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use genet_sdk::{layer::FrameMetadata, prelude::*, reader::*};
+use std::{
+    io::{self, BufReader, Error, ErrorKind, Read},
+    path::Path,
+    process::{Child, ChildStdout, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+/// Records larger than this are treated as corrupted.
+const MAX_RECORD_SIZE: usize = 1 << 28;
+
+fn default_ssh() -> String {
+    "ssh".into()
+}
+
+fn default_command() -> String {
+    "tcpdump".into()
+}
+
+fn default_snaplen() -> u32 {
+    2048
+}
+
+fn default_retries() -> u32 {
+    5
+}
+
+/// Captures on a remote host by running `tcpdump -w -` over SSH.
+#[derive(Deserialize)]
+struct Arg {
+    host: String,
+    interface: String,
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    identity: Option<String>,
+    /// The local ssh client, whose file name has to be `ssh`.
+    #[serde(default = "default_ssh")]
+    ssh: String,
+    /// The name or the path of tcpdump on the remote host, which is passed
+    /// to the remote shell as a single word.
+    #[serde(default = "default_command")]
+    command: String,
+    #[serde(default = "default_snaplen")]
+    snaplen: u32,
+    #[serde(default)]
+    filter: String,
+    #[serde(default = "default_retries")]
+    retries: u32,
+}
+
+impl Arg {
+    /// Rejects a host which ssh would parse as an option, e.g.
+    /// `-oProxyCommand=...`, and an ssh client which is not ssh.
+    fn validate(&self) -> io::Result<()> {
+        if self.host.is_empty() || self.host.starts_with('-') {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid host: {}", self.host),
+            ));
+        }
+        let ssh = Path::new(&self.ssh);
+        if ssh.file_stem().and_then(|name| name.to_str()) != Some("ssh")
+            || self.ssh.starts_with('-')
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid ssh client: {}", self.ssh),
+            ));
+        }
+        if self.command.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "empty command"));
+        }
+        Ok(())
+    }
+
+    fn ssh(&self, remote: &[String]) -> Command {
+        let mut cmd = Command::new(&self.ssh);
+        cmd.arg("-o").arg("BatchMode=yes").arg("-T");
+        if let Some(port) = self.port {
+            cmd.arg("-p").arg(port.to_string());
+        }
+        if let Some(identity) = &self.identity {
+            cmd.arg("-i").arg(identity);
+        }
+        cmd.arg(&self.host).arg("--").args(remote);
+        cmd.stdin(Stdio::null()).stderr(Stdio::null());
+        cmd
+    }
+
+    fn capture(&self) -> Command {
+        let mut args = vec![
+            quote(&self.command),
+            "-U".into(),
+            "-w".into(),
+            "-".into(),
+            "-s".into(),
+            self.snaplen.to_string(),
+            "-i".into(),
+            quote(&self.interface),
+        ];
+        if !self.filter.is_empty() {
+            args.push(quote(&self.filter));
+        }
+        self.ssh(&args)
+    }
+}
+
+/// Quotes an argument for the remote shell.
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+#[derive(Serialize)]
+struct Device {
+    name: String,
+    description: String,
+    up: bool,
+}
+
+/// Splits a trailing group such as ` [Up, Running]` off `line`.
+fn split_group(line: &str, open: char, close: char) -> (&str, &str) {
+    match line.rfind(open) {
+        Some(pos) if pos > 0 && line.ends_with(close) => {
+            (line[..pos].trim_end(), &line[pos + 1..line.len() - 1])
+        }
+        _ => (line, ""),
+    }
+}
+
+/// Parses the output of `tcpdump -D`.
+fn parse_devices(output: &str) -> Vec<Device> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = line[line.find('.')? + 1..].trim();
+            let (line, flags) = split_group(line, '[', ']');
+            let (name, description) = split_group(line, '(', ')');
+            Some(Device {
+                name: name.into(),
+                description: description.into(),
+                up: flags.split(',').any(|f| f.trim() == "Up"),
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct RemoteReader {}
+
+impl Reader for RemoteReader {
+    fn new_worker(&self, ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        arg.validate()?;
+        if let Ok(output) = arg.ssh(&[quote(&arg.command), "-D".into()]).output() {
+            let devices = parse_devices(&String::from_utf8_lossy(&output.stdout));
+            ctx.set_metadata(
+                "remote.interfaces",
+                &serde_json::to_string(&devices).unwrap_or_default(),
+            );
+        }
+        let stream = Stream::connect(&arg)?;
+        Ok(Box::new(RemoteWorker {
+            arg,
+            stream: Some(stream),
+            retries: 0,
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.remote".into(),
+            ..Metadata::default()
+        }
+    }
+}
+
+/// A record of a pcap stream.
+struct Record {
+    ts_sec: u32,
+    /// Nanoseconds, which are wider than the field since a corrupted
+    /// microsecond value may not fit in 32 bits once scaled.
+    ts_nsec: u64,
+    orig_len: u32,
+    data: Vec<u8>,
+}
+
+impl Record {
+    fn read<R: Read>(reader: &mut R, le: bool, nsec: bool) -> io::Result<Record> {
+        let mut read_u32 = || {
+            if le {
+                reader.read_u32::<LittleEndian>()
+            } else {
+                reader.read_u32::<BigEndian>()
+            }
+        };
+        let ts_sec = read_u32()?;
+        let ts_frac = u64::from(read_u32()?);
+        let inc_len = read_u32()?;
+        let orig_len = read_u32()?;
+        let ts_nsec = if nsec { ts_frac } else { ts_frac * 1000 };
+
+        if inc_len as usize > MAX_RECORD_SIZE {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid record length"));
+        }
+        let mut data = vec![0; inc_len as usize];
+        reader.read_exact(&mut data)?;
+        Ok(Record {
+            ts_sec,
+            ts_nsec,
+            orig_len,
+            data,
+        })
+    }
+}
+
+/// A pcap stream from the remote tcpdump.
+struct Stream {
+    child: Child,
+    reader: BufReader<ChildStdout>,
+    le: bool,
+    nsec: bool,
+    link_class: Fixed<LayerClass>,
+    frame: FrameMetadata,
+}
+
+impl Stream {
+    fn connect(arg: &Arg) -> io::Result<Stream> {
+        let mut child = arg.capture().stdout(Stdio::piped()).spawn()?;
+        let mut reader = BufReader::new(
+            child
+                .stdout
+                .take()
+                .ok_or_else(|| Error::new(ErrorKind::Other, "no stdout"))?,
+        );
+
+        let (le, nsec) = match reader.read_u32::<BigEndian>()? {
+            0xd4c3_b2a1 => (true, false),
+            0xa1b2_c3d4 => (false, false),
+            0x4d3c_b2a1 => (true, true),
+            0xa1b2_3c4d => (false, true),
+            _ => {
+                let _ = child.kill();
+                return Err(Error::new(ErrorKind::InvalidData, "wrong magic number"));
+            }
+        };
+        let mut header = [0; 20];
+        reader.read_exact(&mut header)?;
+        let network = if le {
+            (&header[16..]).read_u32::<LittleEndian>()?
+        } else {
+            (&header[16..]).read_u32::<BigEndian>()?
+        };
+
+        let link_class = Fixed::new(layer_class!(
+            format!("[link-{}]", network),
+            header: attr!(&TYPE_CLASS, value: i64::from(network))
+        ));
+        Ok(Stream {
+            child,
+            reader,
+            le,
+            nsec,
+            link_class,
+            frame: FrameMetadata::new(network),
+        })
+    }
+
+    fn read_one(&mut self, interface: &str) -> io::Result<Layer> {
+        let Record {
+            ts_sec,
+            ts_nsec,
+            orig_len,
+            data,
+        } = Record::read(&mut self.reader, self.le, self.nsec)?;
+
+        let mut layer = Layer::new(self.link_class.clone(), ByteSlice::from(data));
+        layer.set_frame_metadata(self.frame);
+        layer.add_attr(attr!(&LENGTH_CLASS, value: u64::from(orig_len)));
+        layer.add_attr(attr!(
+            &TS_CLASS,
+            value: f64::from(ts_sec) + ts_nsec as f64 / 1_000_000_000f64
+        ));
+        layer.add_attr(attr!(&TS_SEC_CLASS, value: u64::from(ts_sec)));
+        layer.add_attr(attr!(&TS_USEC_CLASS, value: ts_nsec / 1000));
+        layer.add_attr(attr!(&TS_NSEC_CLASS, value: ts_nsec));
+        layer.add_attr(attr!(
+            &INTERFACE_NAME_CLASS,
+            value: interface.to_string().into_boxed_str()
+        ));
+        Ok(layer)
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct RemoteWorker {
+    arg: Arg,
+    stream: Option<Stream>,
+    retries: u32,
+}
+
+impl Worker for RemoteWorker {
+    fn read(&mut self, _ctx: &mut Context) -> Result<Vec<Layer>> {
+        loop {
+            if self.stream.is_none() {
+                // Back off exponentially between reconnection attempts.
+                thread::sleep(Duration::from_millis(500 << self.retries.min(6)));
+                match Stream::connect(&self.arg) {
+                    Ok(stream) => self.stream = Some(stream),
+                    Err(err) => {
+                        self.retries += 1;
+                        if self.retries > self.arg.retries {
+                            return Err(err.into());
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            let result = match &mut self.stream {
+                Some(stream) => stream.read_one(&self.arg.interface),
+                None => continue,
+            };
+            match result {
+                Ok(layer) => {
+                    self.retries = 0;
+                    return Ok(vec![layer]);
+                }
+                Err(err) => {
+                    self.stream = None;
+                    self.retries += 1;
+                    if self.retries > self.arg.retries {
+                        return Err(err.into());
+                    }
+                }
+            }
+        }
+    }
+}
+
+def_attr_class!(TYPE_CLASS, "link.type");
+def_attr_class!(LENGTH_CLASS, "link.length");
+def_attr_class!(TS_CLASS, "link.timestamp",
+    typ: "@datetime:unix"
+);
+def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
+def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+def_attr_class!(TS_NSEC_CLASS, "link.timestamp.nsec");
+def_attr_class!(INTERFACE_NAME_CLASS, "link.interface.name");
+
+genet_readers!(RemoteReader {});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arg(host: &str) -> Arg {
+        serde_json::from_str(&format!(
+            r#"{{"host": {}, "interface": "eth0"}}"#,
+            serde_json::to_string(host).unwrap()
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn validate() {
+        assert!(arg("user@example.com").validate().is_ok());
+        assert!(arg("-oProxyCommand=touch /tmp/x").validate().is_err());
+        assert!(arg("-p22").validate().is_err());
+        assert!(arg("").validate().is_err());
+
+        let mut valid = arg("example.com");
+        valid.ssh = "/usr/bin/ssh".into();
+        assert!(valid.validate().is_ok());
+        for ssh in &["/bin/sh", "sshd", "", "-ssh"] {
+            let mut invalid = arg("example.com");
+            invalid.ssh = ssh.to_string();
+            assert!(invalid.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn quote_command() {
+        let mut arg = arg("example.com");
+        arg.command = "tcpdump; rm -rf ~".into();
+        let cmd = format!("{:?}", arg.capture());
+        assert!(cmd.contains("'tcpdump; rm -rf ~'"));
+    }
+
+    #[test]
+    fn record() {
+        let record = |ts_frac: u32, inc_len: u32, data: &[u8]| {
+            let mut buf = Vec::new();
+            for n in &[1u32, ts_frac, inc_len, inc_len] {
+                buf.extend_from_slice(&n.to_le_bytes());
+            }
+            buf.extend_from_slice(data);
+            buf
+        };
+        let buf = record(4_294_968, 2, b"ab");
+        let rec = Record::read(&mut &buf[..], true, false).unwrap();
+        assert_eq!(rec.ts_nsec, 4_294_968_000);
+        assert_eq!(rec.data, b"ab");
+
+        let buf = record(0, 0xffff_ffff, b"");
+        let err = Record::read(&mut &buf[..], true, false).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,122 @@
+extern crate genet_sdk;
+extern crate pcap;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use genet_sdk::{prelude::*, writer::*};
+use pcap::Header;
+
+use std::{
+    io::{BufWriter, Error, ErrorKind, Read, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+/// Transmits frames by piping them to `pcap-cli inject`.
+#[derive(Deserialize)]
+struct Arg {
+    cmd: String,
+    args: Vec<String>,
+}
+
+#[derive(Clone)]
+struct ReplayWriter {}
+
+impl Writer for ReplayWriter {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let mut child = Command::new(&arg.cmd)
+            .args(&arg.args)
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let writer = BufWriter::new(
+            child
+                .stdin
+                .take()
+                .ok_or_else(|| Error::new(ErrorKind::Other, "no stdin"))?,
+        );
+        Ok(Box::new(ReplayWorker {
+            child,
+            writer: Some(writer),
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.writer.replay".into(),
+            ..Metadata::default()
+        }
+    }
+}
+
+struct ReplayWorker {
+    child: Child,
+    writer: Option<BufWriter<ChildStdin>>,
+}
+
+impl ReplayWorker {
+    fn error(&mut self) -> Error {
+        let mut msg = String::new();
+        if let Some(stderr) = &mut self.child.stderr {
+            let _ = stderr.read_to_string(&mut msg);
+        }
+        Error::new(ErrorKind::Other, msg.trim().to_string())
+    }
+}
+
+impl Worker for ReplayWorker {
+    fn write(&mut self, _index: u32, stack: &LayerStack) -> Result<()> {
+        if let Some(layer) = stack.bottom() {
+            let data = layer.data();
+            let mut header = Header {
+                datalen: data.len() as u32,
+                actlen: data.len() as u32,
+                ts_sec: 0,
+                ts_usec: 0,
+            };
+            if let Some(attr) = layer.attr(token!("link.length")) {
+                header.actlen = attr.try_get(layer)?.try_into()?;
+            }
+            if let Some(attr) = layer.attr(token!("link.timestamp.sec")) {
+                header.ts_sec = attr.try_get(layer)?.try_into()?;
+            }
+            if let Some(attr) = layer.attr(token!("link.timestamp.usec")) {
+                header.ts_usec = attr.try_get(layer)?.try_into()?;
+            }
+
+            let result = match &mut self.writer {
+                Some(writer) => serde_json::to_writer(&mut *writer, &header)
+                    .map_err(Error::from)
+                    .and_then(|_| writer.write_all(b"\n"))
+                    .and_then(|_| writer.write_all(&data)),
+                None => Ok(()),
+            };
+            if result.is_err() {
+                self.writer = None;
+                return Err(self.error().into());
+            }
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        if !self.child.wait()?.success() {
+            return Err(self.error().into());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ReplayWorker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+genet_writers!(ReplayWriter {});
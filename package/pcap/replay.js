@@ -0,0 +1,92 @@
+const cli = require('./cli')
+const m = require('mithril')
+const genet = require('@genet/api')
+const { execFile } = require('child_process')
+class ReplayView {
+  constructor () {
+    this.devices = []
+    execFile(cli, ['devices'], (error, stdout) => {
+      if (!error) {
+        this.devices = JSON.parse(stdout)
+        m.redraw()
+      }
+    })
+  }
+
+  view (vnode) {
+    const ifs = genet.workspace.get('_.pcap.replay.interface')
+    const mode = genet.workspace.get('_.pcap.replay.mode', 'timestamp')
+    const rate = genet.workspace.get('_.pcap.replay.rate', '1')
+    return m('ul', [
+      m('li', [
+        m('select', { name: 'ifs' }, this.devices.map((dev) =>
+          m('option', {
+            value: dev.id,
+            selected: ifs === dev.id,
+          }, [dev.name])))
+      ]),
+      m('li', [
+        m('select', { name: 'mode' }, [
+          m('option', {
+            value: 'timestamp',
+            selected: mode === 'timestamp',
+          }, ['Original Timing (x)']),
+          m('option', {
+            value: 'pps',
+            selected: mode === 'pps',
+          }, ['Packets per Second']),
+          m('option', {
+            value: 'mbps',
+            selected: mode === 'mbps',
+          }, ['Megabits per Second']),
+          m('option', {
+            value: 'topspeed',
+            selected: mode === 'topspeed',
+          }, ['Top Speed'])
+        ]),
+        m('input', {
+          type: 'text',
+          name: 'rate',
+          value: rate,
+        })
+      ]),
+      m('li', [
+        m('input', {
+          type: 'button',
+          value: 'Replay to Interface',
+          onclick: () => {
+            const device = vnode.dom.querySelector('[name=ifs]').value
+            const mode = vnode.dom.querySelector('[name=mode]').value
+            const rate = vnode.dom.querySelector('[name=rate]').value
+            if (!device) {
+              return
+            }
+            const args = ['inject', device]
+            switch (mode) {
+              case 'pps':
+                args.push('-p', rate)
+                break
+              case 'mbps':
+                args.push('-M', rate)
+                break
+              case 'topspeed':
+                args.push('-T')
+                break
+              default:
+                args.push('-x', rate)
+            }
+            genet.workspace.set('_.pcap.replay.interface', device)
+            genet.workspace.set('_.pcap.replay.mode', mode)
+            genet.workspace.set('_.pcap.replay.rate', rate)
+            vnode.attrs.callback('app.genet.writer.replay', {
+              cmd: cli,
+              args,
+            })
+          },
+        })
+      ])
+    ])
+  }
+}
+
+module.exports = ReplayView
@@ -0,0 +1,223 @@
+//! Packet protection of QUIC version 1 (RFC 9001, Section 5).
+
+use genet_crypto::{
+    aes::{Aes, Block},
+    hkdf::expand_label,
+};
+use ring::{aead, digest, hkdf, hmac};
+
+/// The salt of the Initial secrets (RFC 9001, Section 5.2).
+const INITIAL_SALT: [u8; 20] = [
+    0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17, 0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad,
+    0xcc, 0xbb, 0x7f, 0x0a,
+];
+
+const NONCE_LEN: usize = 12;
+
+/// The length of the header protection sample.
+const SAMPLE_LEN: usize = 16;
+
+/// The offset of the header protection sample from the packet number, as if
+/// the packet number were 4 bytes long.
+const SAMPLE_OFFSET: usize = 4;
+
+enum HeaderProtection {
+    Aes,
+    ChaCha20,
+}
+
+/// A TLS 1.3 cipher suite.
+pub struct Suite {
+    pub id: u16,
+    aead: &'static aead::Algorithm,
+    hash: &'static digest::Algorithm,
+    hp: HeaderProtection,
+}
+
+static SUITES: &[Suite] = &[
+    Suite {
+        id: 0x1301,
+        aead: &aead::AES_128_GCM,
+        hash: &digest::SHA256,
+        hp: HeaderProtection::Aes,
+    },
+    Suite {
+        id: 0x1302,
+        aead: &aead::AES_256_GCM,
+        hash: &digest::SHA384,
+        hp: HeaderProtection::Aes,
+    },
+    Suite {
+        id: 0x1303,
+        aead: &aead::CHACHA20_POLY1305,
+        hash: &digest::SHA256,
+        hp: HeaderProtection::ChaCha20,
+    },
+];
+
+/// Returns the cipher suite `id`, or None if it is not supported.
+pub fn suite(id: u16) -> Option<&'static Suite> {
+    SUITES.iter().find(|suite| suite.id == id)
+}
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+fn le32(b: &[u8]) -> u32 {
+    u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24
+}
+
+/// Returns the ChaCha20 block of `counter` (RFC 8439, Section 2.3).
+fn chacha20_block(key: &[u8], counter: u32, nonce: &[u8]) -> [u8; 64] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+    for i in 0..8 {
+        state[4 + i] = le32(&key[i * 4..]);
+    }
+    state[12] = counter;
+    for i in 0..3 {
+        state[13 + i] = le32(&nonce[i * 4..]);
+    }
+    let mut s = state;
+    for _ in 0..10 {
+        quarter_round(&mut s, 0, 4, 8, 12);
+        quarter_round(&mut s, 1, 5, 9, 13);
+        quarter_round(&mut s, 2, 6, 10, 14);
+        quarter_round(&mut s, 3, 7, 11, 15);
+        quarter_round(&mut s, 0, 5, 10, 15);
+        quarter_round(&mut s, 1, 6, 11, 12);
+        quarter_round(&mut s, 2, 7, 8, 13);
+        quarter_round(&mut s, 3, 4, 9, 14);
+    }
+    let mut block = [0; 64];
+    for (i, (s, state)) in s.iter().zip(state.iter()).enumerate() {
+        block[i * 4..][..4].copy_from_slice(&s.wrapping_add(*state).to_le_bytes());
+    }
+    block
+}
+
+#[derive(Clone)]
+enum HeaderKey {
+    Aes(Aes),
+    ChaCha20(Vec<u8>),
+}
+
+impl HeaderKey {
+    /// Returns the mask of the header protection (RFC 9001, Section 5.4).
+    fn mask(&self, sample: &[u8]) -> [u8; 5] {
+        let mut mask = [0; 5];
+        match self {
+            HeaderKey::Aes(aes) => {
+                let mut block: Block = [0; SAMPLE_LEN];
+                block.copy_from_slice(sample);
+                aes.encrypt(&mut block);
+                mask.copy_from_slice(&block[..5]);
+            }
+            HeaderKey::ChaCha20(key) => {
+                let block = chacha20_block(key, le32(sample), &sample[4..]);
+                mask.copy_from_slice(&block[..5]);
+            }
+        }
+        mask
+    }
+}
+
+/// The read keys of one direction of a packet number space.
+pub struct Keys {
+    suite: &'static Suite,
+    secret: Vec<u8>,
+    key: aead::OpeningKey,
+    iv: Vec<u8>,
+    hp: HeaderKey,
+}
+
+impl Keys {
+    /// Creates a new Keys from a TLS 1.3 traffic secret.
+    pub fn new(suite: &'static Suite, secret: &[u8]) -> Option<Keys> {
+        let mut hp = vec![0; suite.aead.key_len()];
+        let prk = hmac::SigningKey::new(suite.hash, secret);
+        expand_label(&prk, "quic hp", &mut hp);
+        let hp = match suite.hp {
+            HeaderProtection::Aes => HeaderKey::Aes(Aes::new(&hp)?),
+            HeaderProtection::ChaCha20 => HeaderKey::ChaCha20(hp),
+        };
+        Self::with_header_key(suite, secret, hp)
+    }
+
+    fn with_header_key(suite: &'static Suite, secret: &[u8], hp: HeaderKey) -> Option<Keys> {
+        let mut key = vec![0; suite.aead.key_len()];
+        let mut iv = vec![0; NONCE_LEN];
+        let prk = hmac::SigningKey::new(suite.hash, secret);
+        expand_label(&prk, "quic key", &mut key);
+        expand_label(&prk, "quic iv", &mut iv);
+        Some(Keys {
+            suite,
+            secret: secret.to_vec(),
+            key: aead::OpeningKey::new(suite.aead, &key).ok()?,
+            iv,
+            hp,
+        })
+    }
+
+    /// Creates the Initial keys derived from the Destination Connection ID
+    /// of the first Initial packet of the client (RFC 9001, Section 5.2).
+    pub fn initial(dcid: &[u8], client: bool) -> Option<Keys> {
+        let suite = suite(0x1301)?;
+        let salt = hmac::SigningKey::new(suite.hash, &INITIAL_SALT);
+        let initial = hkdf::extract(&salt, dcid);
+        let mut secret = vec![0; suite.hash.output_len];
+        let label = if client { "client in" } else { "server in" };
+        expand_label(&initial, label, &mut secret);
+        Keys::new(suite, &secret)
+    }
+
+    /// Returns the keys of the next key phase (RFC 9001, Section 6). The
+    /// header protection key is not updated.
+    pub fn update(&self) -> Option<Keys> {
+        let mut secret = vec![0; self.suite.hash.output_len];
+        let prk = hmac::SigningKey::new(self.suite.hash, &self.secret);
+        expand_label(&prk, "quic ku", &mut secret);
+        Self::with_header_key(self.suite, &secret, self.hp.clone())
+    }
+
+    /// Removes the header protection of `packet` in place, and returns the
+    /// length of the packet number.
+    pub fn unprotect(&self, packet: &mut [u8], pn_offset: usize) -> Option<usize> {
+        let sample = packet.get(pn_offset + SAMPLE_OFFSET..)?.get(..SAMPLE_LEN)?;
+        let mask = self.hp.mask(sample);
+        packet[0] ^= mask[0] & if packet[0] & 0x80 != 0 { 0x0f } else { 0x1f };
+        let pn_len = usize::from(packet[0] & 0x03) + 1;
+        for (b, m) in packet[pn_offset..pn_offset + pn_len]
+            .iter_mut()
+            .zip(&mask[1..])
+        {
+            *b ^= m;
+        }
+        Some(pn_len)
+    }
+
+    /// Decrypts the payload of a packet whose header protection has been
+    /// removed, and returns the plaintext, or None if the packet cannot be
+    /// authenticated.
+    pub fn open(&self, pn: u64, header: &[u8], payload: &[u8]) -> Option<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        nonce.copy_from_slice(&self.iv);
+        for (n, p) in nonce[NONCE_LEN - 8..].iter_mut().zip(&pn.to_be_bytes()) {
+            *n ^= p;
+        }
+        let mut buf = payload.to_vec();
+        let len = aead::open_in_place(&self.key, &nonce, header, 0, &mut buf)
+            .ok()?
+            .len();
+        buf.truncate(len);
+        Some(buf)
+    }
+}
@@ -0,0 +1,197 @@
+//! Frames (RFC 9000, Section 19).
+
+use packet::{bytes, varint, varint_range};
+use std::ops::Range;
+
+/// A variable-length integer and its range.
+pub type Int = (u64, Range<usize>);
+
+#[derive(Debug, Clone)]
+pub enum Frame {
+    Padding,
+    Ping,
+    Ack {
+        largest: Int,
+        delay: Int,
+        ranges: Int,
+    },
+    ResetStream {
+        id: Int,
+        code: Int,
+        final_size: Int,
+    },
+    StopSending {
+        id: Int,
+        code: Int,
+    },
+    Crypto {
+        offset: Int,
+        data: Range<usize>,
+    },
+    NewToken {
+        token: Range<usize>,
+    },
+    Stream {
+        id: Int,
+        offset: Option<Int>,
+        data: Range<usize>,
+        fin: bool,
+    },
+    MaxData(Int),
+    MaxStreamData {
+        id: Int,
+        maximum: Int,
+    },
+    MaxStreams(Int),
+    DataBlocked(Int),
+    StreamDataBlocked {
+        id: Int,
+        limit: Int,
+    },
+    StreamsBlocked(Int),
+    NewConnectionId {
+        sequence: Int,
+        retire_prior_to: Int,
+        cid: Range<usize>,
+        reset_token: Range<usize>,
+    },
+    RetireConnectionId(Int),
+    PathChallenge(Range<usize>),
+    PathResponse(Range<usize>),
+    ConnectionClose {
+        code: Int,
+        frame_type: Option<Int>,
+        reason: Range<usize>,
+    },
+    HandshakeDone,
+    Datagram(Range<usize>),
+}
+
+/// Reads a frame at `pos`, and returns its type and the frame.
+pub fn parse(data: &[u8], pos: &mut usize) -> Option<(u64, Frame)> {
+    let typ = varint(data, pos)?;
+    let frame = match typ {
+        0x00 => {
+            // Consecutive PADDING frames are read as one.
+            while data.get(*pos) == Some(&0) {
+                *pos += 1;
+            }
+            Frame::Padding
+        }
+        0x01 => Frame::Ping,
+        0x02 | 0x03 => {
+            let largest = varint_range(data, pos)?;
+            let delay = varint_range(data, pos)?;
+            let ranges = varint_range(data, pos)?;
+            varint(data, pos)?;
+            for _ in 0..ranges.0 {
+                varint(data, pos)?;
+                varint(data, pos)?;
+            }
+            if typ == 0x03 {
+                for _ in 0..3 {
+                    varint(data, pos)?;
+                }
+            }
+            Frame::Ack {
+                largest,
+                delay,
+                ranges,
+            }
+        }
+        0x04 => Frame::ResetStream {
+            id: varint_range(data, pos)?,
+            code: varint_range(data, pos)?,
+            final_size: varint_range(data, pos)?,
+        },
+        0x05 => Frame::StopSending {
+            id: varint_range(data, pos)?,
+            code: varint_range(data, pos)?,
+        },
+        0x06 => {
+            let offset = varint_range(data, pos)?;
+            let len = varint(data, pos)?;
+            Frame::Crypto {
+                offset,
+                data: bytes(data, pos, len as usize)?,
+            }
+        }
+        0x07 => {
+            let len = varint(data, pos)?;
+            Frame::NewToken {
+                token: bytes(data, pos, len as usize)?,
+            }
+        }
+        0x08..=0x0f => {
+            let id = varint_range(data, pos)?;
+            let offset = if typ & 0x04 != 0 {
+                Some(varint_range(data, pos)?)
+            } else {
+                None
+            };
+            let len = if typ & 0x02 != 0 {
+                varint(data, pos)? as usize
+            } else {
+                data.len() - *pos
+            };
+            Frame::Stream {
+                id,
+                offset,
+                data: bytes(data, pos, len)?,
+                fin: typ & 0x01 != 0,
+            }
+        }
+        0x10 => Frame::MaxData(varint_range(data, pos)?),
+        0x11 => Frame::MaxStreamData {
+            id: varint_range(data, pos)?,
+            maximum: varint_range(data, pos)?,
+        },
+        0x12 | 0x13 => Frame::MaxStreams(varint_range(data, pos)?),
+        0x14 => Frame::DataBlocked(varint_range(data, pos)?),
+        0x15 => Frame::StreamDataBlocked {
+            id: varint_range(data, pos)?,
+            limit: varint_range(data, pos)?,
+        },
+        0x16 | 0x17 => Frame::StreamsBlocked(varint_range(data, pos)?),
+        0x18 => {
+            let sequence = varint_range(data, pos)?;
+            let retire_prior_to = varint_range(data, pos)?;
+            let len = usize::from(*data.get(*pos)?);
+            *pos += 1;
+            Frame::NewConnectionId {
+                sequence,
+                retire_prior_to,
+                cid: bytes(data, pos, len)?,
+                reset_token: bytes(data, pos, 16)?,
+            }
+        }
+        0x19 => Frame::RetireConnectionId(varint_range(data, pos)?),
+        0x1a => Frame::PathChallenge(bytes(data, pos, 8)?),
+        0x1b => Frame::PathResponse(bytes(data, pos, 8)?),
+        0x1c | 0x1d => {
+            let code = varint_range(data, pos)?;
+            let frame_type = if typ == 0x1c {
+                Some(varint_range(data, pos)?)
+            } else {
+                None
+            };
+            let len = varint(data, pos)?;
+            Frame::ConnectionClose {
+                code,
+                frame_type,
+                reason: bytes(data, pos, len as usize)?,
+            }
+        }
+        0x1e => Frame::HandshakeDone,
+        0x30 | 0x31 => {
+            let len = if typ == 0x31 {
+                varint(data, pos)? as usize
+            } else {
+                data.len() - *pos
+            };
+            Frame::Datagram(bytes(data, pos, len)?)
+        }
+        _ => return None,
+    };
+    Some((typ, frame))
+}
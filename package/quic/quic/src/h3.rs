@@ -0,0 +1,197 @@
+//! HTTP/3 frames on the streams of a connection (RFC 9114).
+
+use hpack::huffman::HuffmanDecoder;
+use packet::varint;
+use qpack::{self, Field, Table};
+use std::collections::HashMap;
+use stream::Stream;
+
+pub const FRAME_DATA: u64 = 0x00;
+pub const FRAME_HEADERS: u64 = 0x01;
+pub const FRAME_CANCEL_PUSH: u64 = 0x03;
+pub const FRAME_SETTINGS: u64 = 0x04;
+pub const FRAME_PUSH_PROMISE: u64 = 0x05;
+pub const FRAME_GOAWAY: u64 = 0x07;
+pub const FRAME_MAX_PUSH_ID: u64 = 0x0d;
+
+const STREAM_CONTROL: u64 = 0x00;
+const STREAM_PUSH: u64 = 0x01;
+const STREAM_ENCODER: u64 = 0x02;
+
+const SETTINGS_QPACK_MAX_TABLE_CAPACITY: u64 = 0x01;
+
+/// Frames longer than this stop the dissection of the stream.
+const MAX_FRAME_LEN: u64 = 1 << 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// A unidirectional stream whose type has not been read.
+    Unidirectional,
+    /// A push stream whose push ID has not been read.
+    Push,
+    Frames,
+    Encoder,
+    Ignored,
+}
+
+struct H3Stream {
+    data: Stream,
+    buf: Vec<u8>,
+    kind: Kind,
+}
+
+/// A complete frame.
+pub struct Frame {
+    pub stream: u64,
+    pub typ: u64,
+    /// The frame including the type and the length.
+    pub data: Vec<u8>,
+    /// The offset of the length.
+    pub length_offset: usize,
+    /// The offset of the payload.
+    pub payload_offset: usize,
+    /// The fields of a HEADERS or PUSH_PROMISE frame.
+    pub fields: Option<Result<Vec<Field>, qpack::Error>>,
+}
+
+impl Frame {
+    pub fn payload(&self) -> &[u8] {
+        &self.data[self.payload_offset..]
+    }
+
+    /// Returns the identifiers and the values of a SETTINGS frame.
+    pub fn settings(&self) -> Vec<(u64, u64)> {
+        let payload = self.payload();
+        let mut settings = Vec::new();
+        let mut pos = 0;
+        while let (Some(id), Some(value)) = (varint(payload, &mut pos), varint(payload, &mut pos)) {
+            settings.push((id, value));
+        }
+        settings
+    }
+}
+
+/// The HTTP/3 state of a connection.
+pub struct Connection {
+    streams: HashMap<u64, H3Stream>,
+    /// The dynamic tables of the encoders by sender.
+    tables: [Table; 2],
+    huffman: HuffmanDecoder,
+}
+
+impl Default for Connection {
+    fn default() -> Connection {
+        Connection {
+            streams: HashMap::new(),
+            tables: Default::default(),
+            huffman: HuffmanDecoder::new(),
+        }
+    }
+}
+
+impl Connection {
+    /// Processes STREAM frame data sent by `dir`, and returns the HTTP/3
+    /// frames completed by it.
+    pub fn push(&mut self, dir: usize, id: u64, offset: u64, data: &[u8]) -> Vec<Frame> {
+        let Connection {
+            streams,
+            tables,
+            huffman,
+        } = self;
+        let stream = streams.entry(id).or_insert_with(|| H3Stream {
+            data: Stream::default(),
+            buf: Vec::new(),
+            kind: if id & 0x02 != 0 {
+                Kind::Unidirectional
+            } else {
+                Kind::Frames
+            },
+        });
+        let mut frames = Vec::new();
+        if stream.kind == Kind::Ignored {
+            return frames;
+        }
+        let data = stream.data.push(offset, data);
+        stream.buf.extend_from_slice(&data);
+
+        let mut pos = 0;
+        loop {
+            match stream.kind {
+                Kind::Unidirectional => match varint(&stream.buf, &mut pos) {
+                    Some(STREAM_CONTROL) => stream.kind = Kind::Frames,
+                    Some(STREAM_PUSH) => stream.kind = Kind::Push,
+                    Some(STREAM_ENCODER) => stream.kind = Kind::Encoder,
+                    Some(_) => stream.kind = Kind::Ignored,
+                    None => break,
+                },
+                Kind::Push => match varint(&stream.buf, &mut pos) {
+                    Some(_) => stream.kind = Kind::Frames,
+                    None => break,
+                },
+                Kind::Encoder => {
+                    tables[dir].encoder_stream(&stream.buf[pos..], huffman);
+                    pos = stream.buf.len();
+                    break;
+                }
+                Kind::Ignored => {
+                    pos = stream.buf.len();
+                    break;
+                }
+                Kind::Frames => {
+                    let start = pos;
+                    let mut next = pos;
+                    let header = varint(&stream.buf, &mut next).and_then(|typ| {
+                        let length_offset = next - start;
+                        let len = varint(&stream.buf, &mut next)?;
+                        Some((typ, length_offset, len))
+                    });
+                    let (typ, length_offset, len) = match header {
+                        Some(header) => header,
+                        None => break,
+                    };
+                    if len > MAX_FRAME_LEN {
+                        stream.kind = Kind::Ignored;
+                        continue;
+                    }
+                    let end = next + len as usize;
+                    if stream.buf.len() < end {
+                        break;
+                    }
+                    let mut frame = Frame {
+                        stream: id,
+                        typ,
+                        data: stream.buf[start..end].to_vec(),
+                        length_offset,
+                        payload_offset: next - start,
+                        fields: None,
+                    };
+                    match typ {
+                        FRAME_HEADERS => {
+                            frame.fields = Some(tables[dir].decode(frame.payload(), huffman));
+                        }
+                        FRAME_PUSH_PROMISE => {
+                            let mut offset = 0;
+                            if varint(frame.payload(), &mut offset).is_some() {
+                                let block = &frame.payload()[offset..];
+                                frame.fields = Some(tables[dir].decode(block, huffman));
+                            }
+                        }
+                        FRAME_SETTINGS => {
+                            // The setting limits the encoder of the peer.
+                            for (id, value) in frame.settings() {
+                                if id == SETTINGS_QPACK_MAX_TABLE_CAPACITY {
+                                    tables[1 - dir].max_capacity = value;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    frames.push(frame);
+                    pos = end;
+                }
+            }
+        }
+        stream.buf.drain(..pos);
+        frames
+    }
+}
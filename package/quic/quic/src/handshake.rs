@@ -0,0 +1,152 @@
+//! TLS handshake messages carried by CRYPTO frames (RFC 9001, Section 4).
+
+pub const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+pub const HANDSHAKE_SERVER_HELLO: u8 = 2;
+pub const HANDSHAKE_ENCRYPTED_EXTENSIONS: u8 = 8;
+
+const EXT_SERVER_NAME: u16 = 0x0000;
+const EXT_ALPN: u16 = 0x0010;
+
+struct Cursor<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn new(buf: &'a [u8]) -> Cursor<'a> {
+        Cursor { buf }
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < len {
+            return None;
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|b| u16::from(b[0]) << 8 | u16::from(b[1]))
+    }
+
+    fn vec8(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()?;
+        self.bytes(len as usize)
+    }
+
+    fn vec16(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()?;
+        self.bytes(len as usize)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+/// Splits the handshake byte stream into messages.
+#[derive(Default)]
+pub struct Messages {
+    buffer: Vec<u8>,
+}
+
+impl Messages {
+    /// Appends handshake data and returns the complete messages.
+    pub fn push(&mut self, data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        self.buffer.extend_from_slice(data);
+        let mut messages = Vec::new();
+        let mut offset = 0;
+        while self.buffer.len() >= offset + 4 {
+            let msg = &self.buffer[offset..];
+            let len = (msg[1] as usize) << 16 | (msg[2] as usize) << 8 | msg[3] as usize;
+            if msg.len() < 4 + len {
+                break;
+            }
+            messages.push((msg[0], msg[4..4 + len].to_vec()));
+            offset += 4 + len;
+        }
+        self.buffer.drain(..offset);
+        messages
+    }
+
+    /// Returns the length of the incomplete message.
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ClientHello {
+    pub random: Vec<u8>,
+    pub server_name: Option<String>,
+    /// The first protocol offered by the ALPN extension.
+    pub alpn: Option<Vec<u8>>,
+}
+
+/// Returns the first protocol of an ALPN extension.
+fn alpn(data: &mut Cursor) -> Option<Vec<u8>> {
+    let mut list = Cursor::new(data.vec16()?);
+    list.vec8().map(|p| p.to_vec())
+}
+
+/// Parses a ClientHello message body.
+pub fn client_hello(body: &[u8]) -> Option<ClientHello> {
+    let mut c = Cursor::new(body);
+    c.u16()?;
+    let mut hello = ClientHello {
+        random: c.bytes(32)?.to_vec(),
+        ..ClientHello::default()
+    };
+    c.vec8()?;
+    c.vec16()?;
+    c.vec8()?;
+    let mut exts = Cursor::new(c.vec16()?);
+    while !exts.is_empty() {
+        let typ = exts.u16()?;
+        let mut data = Cursor::new(exts.vec16()?);
+        match typ {
+            EXT_SERVER_NAME => {
+                let mut list = Cursor::new(data.vec16()?);
+                while !list.is_empty() {
+                    let name_type = list.u8()?;
+                    let name = list.vec16()?;
+                    if name_type == 0 {
+                        hello.server_name = Some(String::from_utf8_lossy(name).into_owned());
+                        break;
+                    }
+                }
+            }
+            EXT_ALPN => hello.alpn = alpn(&mut data),
+            _ => {}
+        }
+    }
+    Some(hello)
+}
+
+/// Returns the cipher suite of a ServerHello message body.
+pub fn server_hello_cipher(body: &[u8]) -> Option<u16> {
+    let mut c = Cursor::new(body);
+    c.u16()?;
+    c.bytes(32)?;
+    c.vec8()?;
+    c.u16()
+}
+
+/// Returns the protocol selected by the ALPN extension of an
+/// EncryptedExtensions message body.
+pub fn encrypted_extensions_alpn(body: &[u8]) -> Option<Vec<u8>> {
+    let mut exts = Cursor::new(Cursor::new(body).vec16()?);
+    while !exts.is_empty() {
+        let typ = exts.u16()?;
+        let mut data = Cursor::new(exts.vec16()?);
+        if typ == EXT_ALPN {
+            return alpn(&mut data);
+        }
+    }
+    None
+}
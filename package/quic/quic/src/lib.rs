@@ -0,0 +1,951 @@
+extern crate genet_crypto;
+extern crate genet_sdk;
+extern crate hpack;
+extern crate ring;
+
+mod crypto;
+mod frame;
+mod h3;
+mod handshake;
+mod packet;
+mod qpack;
+mod stream;
+
+use crypto::Keys;
+use frame::Frame;
+use genet_crypto::keylog::KeyLogFile;
+use genet_sdk::{cast, decoder::*, prelude::*, variant::Variant};
+use handshake::Messages;
+use packet::{Epoch, Header, PacketType};
+use std::collections::{BTreeSet, HashMap};
+use stream::Stream;
+
+/// Config key for the path of a key log file written via SSLKEYLOGFILE.
+const KEY_LOG_FILE_KEY: &str = "@genet/quic.keyLogFile";
+
+/// Handshake messages larger than this are not parsed.
+const MAX_HANDSHAKE_SIZE: usize = 1 << 16;
+
+const CLIENT: usize = 0;
+const SERVER: usize = 1;
+
+type Endpoint = (Vec<u8>, u64);
+
+/// Returns the value of the attribute `id` of the topmost layer having it.
+fn stack_value<T>(stack: &LayerStack, id: Token) -> Result<Option<T>>
+where
+    Variant: Value<T>,
+{
+    for layer in stack.layers().rev() {
+        if let Some(attr) = layer.attr(id) {
+            return Ok(Some(attr.try_get(layer)?.try_into()?));
+        }
+    }
+    Ok(None)
+}
+
+/// The state of a connection, shared by the paths of the connection.
+#[derive(Default)]
+struct Connection {
+    /// The Destination Connection ID of the first Initial packet of the
+    /// client, which identifies the connection.
+    id: Vec<u8>,
+    /// The endpoints of the client and the server of the current path.
+    path: Option<(Endpoint, Endpoint)>,
+    /// The keys by epoch and sender.
+    keys: [[Option<Keys>; 2]; 4],
+    /// The key phase of the 1-RTT keys by sender.
+    key_phase: [bool; 2],
+    /// The largest packet numbers by packet number space and sender.
+    largest: [[Option<u64>; 2]; 3],
+    /// The CRYPTO streams by packet number space and sender.
+    crypto: [[(Stream, Messages); 2]; 3],
+    client_random: Option<Vec<u8>>,
+    cipher: Option<u16>,
+    server_name: Option<String>,
+    /// The protocol selected by ALPN, or the first protocol offered by the
+    /// client until the selected one is known.
+    alpn: Option<Vec<u8>>,
+    h3: h3::Connection,
+}
+
+impl Connection {
+    fn new(id: &[u8]) -> Connection {
+        let mut conn = Connection {
+            id: id.to_vec(),
+            ..Connection::default()
+        };
+        conn.set_initial_keys(id);
+        conn
+    }
+
+    fn set_initial_keys(&mut self, dcid: &[u8]) {
+        self.keys[0] = [Keys::initial(dcid, true), Keys::initial(dcid, false)];
+    }
+
+    fn is_h3(&self) -> bool {
+        self.alpn
+            .as_ref()
+            .is_some_and(|alpn| alpn.starts_with(b"h3"))
+    }
+
+    fn handshake(&mut self, space: usize, dir: usize, offset: u64, data: &[u8]) {
+        let (stream, messages) = &mut self.crypto[space][dir];
+        let data = stream.push(offset, data);
+        let messages = if messages.pending() + data.len() > MAX_HANDSHAKE_SIZE {
+            *messages = Messages::default();
+            Vec::new()
+        } else {
+            messages.push(&data)
+        };
+        for (typ, body) in messages {
+            match typ {
+                handshake::HANDSHAKE_CLIENT_HELLO => {
+                    if let Some(hello) = handshake::client_hello(&body) {
+                        self.client_random = Some(hello.random);
+                        self.server_name = hello.server_name;
+                        self.alpn = hello.alpn;
+                    }
+                }
+                handshake::HANDSHAKE_SERVER_HELLO => {
+                    self.cipher = handshake::server_hello_cipher(&body);
+                }
+                handshake::HANDSHAKE_ENCRYPTED_EXTENSIONS => {
+                    if let Some(alpn) = handshake::encrypted_extensions_alpn(&body) {
+                        self.alpn = Some(alpn);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The result of the removal of the packet protection.
+enum Protection {
+    Decrypted {
+        pn: u64,
+        pn_len: usize,
+        /// The end of the plaintext, which is followed by the tag.
+        end: usize,
+    },
+    NoKeys,
+    Failed,
+}
+
+struct QuicWorker {
+    connections: Vec<Connection>,
+    /// The connections and the owners of the connection IDs.
+    cids: HashMap<Vec<u8>, (usize, usize)>,
+    /// The lengths of the connection IDs seen, to find the Destination
+    /// Connection ID of short headers.
+    cid_lens: BTreeSet<usize>,
+    /// The connections and the senders by path, for connection IDs of zero
+    /// length.
+    paths: HashMap<(Endpoint, Endpoint), (usize, usize)>,
+    keylog: Option<KeyLogFile>,
+}
+
+impl QuicWorker {
+    fn add_cid(&mut self, cid: &[u8], conn: usize, owner: usize) {
+        if !cid.is_empty() {
+            self.cids.entry(cid.to_vec()).or_insert((conn, owner));
+            self.cid_lens.insert(cid.len());
+        }
+    }
+
+    /// Parses the header of a packet, and returns the connection and the
+    /// sender of the packet if known.
+    fn header(
+        &mut self,
+        data: &[u8],
+        path: &(Endpoint, Endpoint),
+    ) -> Option<(Header, Option<(usize, usize)>)> {
+        if data[0] & 0x80 != 0 {
+            let header = Header::long(data)?;
+            let dcid = &data[header.dcid.clone()];
+            let found = match self.cids.get(dcid) {
+                Some(&(conn, owner)) if !dcid.is_empty() => Some((conn, 1 - owner)),
+                _ => self.paths.get(path).cloned(),
+            };
+            let found = match found {
+                None if header.typ == Some(PacketType::Initial) => {
+                    let conn = self.connections.len();
+                    self.connections.push(Connection::new(dcid));
+                    self.add_cid(dcid, conn, SERVER);
+                    Some((conn, CLIENT))
+                }
+                found => found,
+            };
+            if let (Some((conn, dir)), Some(scid)) = (found, &header.scid) {
+                self.add_cid(&data[scid.clone()], conn, dir);
+            }
+            Some((header, found))
+        } else {
+            let found = self
+                .cid_lens
+                .iter()
+                .rev()
+                .filter_map(|len| Some((*len, *self.cids.get(data.get(1..1 + len)?)?)))
+                .next();
+            match found {
+                Some((len, (conn, owner))) => {
+                    Some((Header::short(data, len)?, Some((conn, 1 - owner))))
+                }
+                None => Some((Header::short(data, 0)?, self.paths.get(path).cloned())),
+            }
+        }
+    }
+
+    /// Returns the keys of `epoch` derived from the key log.
+    fn logged_keys(&mut self, conn: usize, epoch: Epoch, dir: usize) -> Option<Keys> {
+        let conn = &self.connections[conn];
+        let secrets = self.keylog.as_mut()?.get(conn.client_random.as_ref()?)?;
+        let secret = match (epoch, dir) {
+            (Epoch::ZeroRtt, CLIENT) => &secrets.client_early,
+            (Epoch::Handshake, CLIENT) => &secrets.client_handshake,
+            (Epoch::Handshake, _) => &secrets.server_handshake,
+            (Epoch::OneRtt, CLIENT) => &secrets.client_traffic,
+            (Epoch::OneRtt, _) => &secrets.server_traffic,
+            _ => return None,
+        };
+        // 0-RTT packets precede the ServerHello.
+        let suite = crypto::suite(conn.cipher.unwrap_or(0x1301))?;
+        Keys::new(suite, secret.as_ref()?)
+    }
+
+    /// Removes the packet protection of `buf` in place.
+    fn unprotect(
+        &mut self,
+        buf: &mut Vec<u8>,
+        header: &Header,
+        epoch: Epoch,
+        (conn, dir): (usize, usize),
+    ) -> Protection {
+        let index = epoch as usize;
+        if self.connections[conn].keys[index][dir].is_none() {
+            self.connections[conn].keys[index][dir] = self.logged_keys(conn, epoch, dir);
+        }
+        let conn = &mut self.connections[conn];
+        let protected = buf.clone();
+        let (pn, pn_len, plain) = {
+            let keys = match &conn.keys[index][dir] {
+                Some(keys) => keys,
+                None => return Protection::NoKeys,
+            };
+            let pn_len = match keys.unprotect(buf, header.pn_offset) {
+                Some(pn_len) => pn_len,
+                None => return Protection::Failed,
+            };
+            let start = header.pn_offset + pn_len;
+            let truncated = buf[header.pn_offset..start]
+                .iter()
+                .fold(0, |pn, b| pn << 8 | u64::from(*b));
+            let space = epoch.space();
+            let pn = packet::packet_number(conn.largest[space][dir], truncated, pn_len);
+            let phase = buf[0] & 0x04 != 0;
+            let plain = if epoch == Epoch::OneRtt && phase != conn.key_phase[dir] {
+                keys.update()
+                    .and_then(|next| {
+                        let plain = next.open(pn, &buf[..start], &buf[start..])?;
+                        Some((next, plain))
+                    })
+                    .map(|(next, plain)| {
+                        conn.keys[index][dir] = Some(next);
+                        conn.key_phase[dir] = phase;
+                        plain
+                    })
+            } else {
+                keys.open(pn, &buf[..start], &buf[start..])
+            };
+            (pn, pn_len, plain)
+        };
+        match plain {
+            Some(plain) => {
+                let space = epoch.space();
+                let largest = &mut conn.largest[space][dir];
+                *largest = Some(largest.map_or(pn, |largest| largest.max(pn)));
+                let start = header.pn_offset + pn_len;
+                buf[start..start + plain.len()].copy_from_slice(&plain);
+                Protection::Decrypted {
+                    pn,
+                    pn_len,
+                    end: start + plain.len(),
+                }
+            }
+            None => {
+                *buf = protected;
+                Protection::Failed
+            }
+        }
+    }
+
+    /// Decodes a packet at the start of `data`, and returns its length and
+    /// the layers of the packet and the HTTP/3 frames.
+    fn packet(&mut self, data: &[u8], path: &(Endpoint, Endpoint)) -> Option<(usize, Vec<Layer>)> {
+        let (header, found) = self.header(data, path)?;
+        let mut buf = data[..header.len].to_vec();
+        let mut attrs = header_attrs(&header, &buf);
+        let mut layers = Vec::new();
+
+        let (conn, dir) = match found {
+            Some(found) => found,
+            None => {
+                if header.typ.and_then(|typ| typ.epoch()).is_some() {
+                    attrs.push(attr!(&UNDECRYPTED_ATTR, value: true));
+                }
+                layers.push(packet_layer(buf, attrs));
+                return Some((header.len, layers));
+            }
+        };
+        self.paths.insert(path.clone(), (conn, dir));
+
+        let endpoints = if dir == CLIENT {
+            path.clone()
+        } else {
+            (path.1.clone(), path.0.clone())
+        };
+        let migrated = {
+            let conn = &mut self.connections[conn];
+            let migrated = conn.path.as_ref().is_some_and(|path| *path != endpoints);
+            conn.path = Some(endpoints);
+            migrated
+        };
+        if migrated {
+            attrs.push(attr!(&MIGRATION_ATTR, value: true));
+        }
+
+        if header.typ == Some(PacketType::Retry) {
+            // The client uses the Source Connection ID of the Retry packet for
+            // the Initial keys.
+            if let Some(scid) = &header.scid {
+                self.connections[conn].set_initial_keys(&data[scid.clone()]);
+            }
+        }
+
+        if let Some(epoch) = header.typ.and_then(|typ| typ.epoch()) {
+            match self.unprotect(&mut buf, &header, epoch, (conn, dir)) {
+                Protection::Decrypted { pn, pn_len, end } => {
+                    let pn_range = header.pn_offset..header.pn_offset + pn_len;
+                    attrs.push(attr!(&PACKET_NUMBER_ATTR, range: pn_range, value: pn));
+                    if epoch == Epoch::OneRtt {
+                        attrs.push(attr!(&KEY_PHASE_ATTR, range: 0..1, value: buf[0] & 0x04 != 0));
+                    }
+                    let start = header.pn_offset + pn_len;
+                    let frames = self.frames(&buf[..end], start, epoch, (conn, dir), &mut attrs);
+                    layers.extend(frames.into_iter().map(http3_layer));
+                }
+                Protection::NoKeys => attrs.push(attr!(&UNDECRYPTED_ATTR, value: true)),
+                Protection::Failed => attrs.push(attr!(&DECRYPTION_FAILED_ATTR, value: true)),
+            }
+        }
+
+        let conn = &self.connections[conn];
+        attrs.push(attr!(&CONNECTION_ATTR, value: conn.id.clone().into_boxed_slice()));
+        if let Some(name) = &conn.server_name {
+            attrs.push(attr!(&SNI_ATTR, value: name.clone().into_boxed_str()));
+        }
+        if let Some(alpn) = &conn.alpn {
+            let alpn = String::from_utf8_lossy(alpn).into_owned();
+            attrs.push(attr!(&ALPN_ATTR, value: alpn.into_boxed_str()));
+        }
+        layers.insert(0, packet_layer(buf, attrs));
+        Some((header.len, layers))
+    }
+
+    /// Parses the frames of a decrypted payload from `start`, and returns the
+    /// HTTP/3 frames completed by them.
+    fn frames(
+        &mut self,
+        payload: &[u8],
+        start: usize,
+        epoch: Epoch,
+        (conn, dir): (usize, usize),
+        attrs: &mut Vec<Attr>,
+    ) -> Vec<h3::Frame> {
+        let mut h3_frames = Vec::new();
+        attrs.push(attr!(&FRAMES_ATTR, range: start..payload.len()));
+        let mut pos = start;
+        while pos < payload.len() {
+            let frame_start = pos;
+            let (typ, frame) = match frame::parse(payload, &mut pos) {
+                Some(frame) => frame,
+                None => {
+                    attrs.push(attr!(&MALFORMED_ATTR, range: frame_start..payload.len()));
+                    break;
+                }
+            };
+            attrs.push(attr!(&FRAME_ATTR, range: frame_start..pos));
+            attrs.push(attr!(&FRAME_TYPE_ATTR, range: frame_start..frame_start + 1, value: typ));
+            if let Some(attr) = get_frame_type(typ) {
+                attrs.push(attr!(attr, range: frame_start..frame_start + 1));
+            }
+            frame_attrs(&frame, attrs);
+
+            match frame {
+                Frame::Crypto { offset, data } => {
+                    let conn = &mut self.connections[conn];
+                    conn.handshake(epoch.space(), dir, offset.0, &payload[data]);
+                }
+                Frame::Stream {
+                    id, offset, data, ..
+                } => {
+                    let conn = &mut self.connections[conn];
+                    if conn.is_h3() {
+                        let offset = offset.map_or(0, |(offset, _)| offset);
+                        h3_frames.extend(conn.h3.push(dir, id.0, offset, &payload[data]));
+                    }
+                }
+                Frame::NewConnectionId { cid, .. } => {
+                    self.add_cid(&payload[cid], conn, dir);
+                }
+                _ => {}
+            }
+        }
+        h3_frames
+    }
+}
+
+fn header_attrs(header: &Header, data: &[u8]) -> Vec<Attr> {
+    let mut attrs = Vec::new();
+    if let Some(typ) = header.typ {
+        attrs.push(attr!(&TYPE_ATTR, range: 0..1, value: typ as u64));
+        attrs.push(attr!(get_packet_type(typ), range: 0..1));
+    }
+    if header.version.is_some() {
+        attrs.push(attr!(&VERSION_ATTR, range: 1..5));
+    }
+    if !header.dcid.is_empty() {
+        attrs.push(attr!(&DCID_ATTR, range: header.dcid.clone()));
+    }
+    if let Some(scid) = &header.scid {
+        if !scid.is_empty() {
+            attrs.push(attr!(&SCID_ATTR, range: scid.clone()));
+        }
+    }
+    if let Some(token) = &header.token {
+        if !token.is_empty() {
+            attrs.push(attr!(&TOKEN_ATTR, range: token.clone()));
+        }
+    }
+    if let Some((length, range)) = &header.length {
+        attrs.push(attr!(&LENGTH_ATTR, range: range.clone(), value: *length));
+    }
+    for range in &header.versions {
+        attrs.push(attr!(&SUPPORTED_VERSION_ATTR, range: range.clone()));
+    }
+    if let Some(tag) = &header.retry_tag {
+        attrs.push(attr!(&INTEGRITY_TAG_ATTR, range: tag.clone()));
+    }
+    if data.len() < header.len {
+        attrs.push(attr!(&MALFORMED_ATTR, value: true));
+    }
+    attrs
+}
+
+fn frame_attrs(frame: &Frame, attrs: &mut Vec<Attr>) {
+    let mut int = |class: &'static AttrClass, (value, range): &frame::Int| {
+        attrs.push(attr!(class, range: range.clone(), value: *value));
+    };
+    match frame {
+        Frame::Ack {
+            largest,
+            delay,
+            ranges,
+        } => {
+            int(&ACK_LARGEST_ATTR, largest);
+            int(&ACK_DELAY_ATTR, delay);
+            int(&ACK_RANGES_ATTR, ranges);
+        }
+        Frame::ResetStream {
+            id,
+            code,
+            final_size,
+        } => {
+            int(&STREAM_ID_ATTR, id);
+            int(&ERROR_CODE_ATTR, code);
+            int(&FINAL_SIZE_ATTR, final_size);
+        }
+        Frame::StopSending { id, code } => {
+            int(&STREAM_ID_ATTR, id);
+            int(&ERROR_CODE_ATTR, code);
+        }
+        Frame::Crypto { offset, data } => {
+            int(&OFFSET_ATTR, offset);
+            int(&FRAME_LENGTH_ATTR, &(data.len() as u64, data.clone()));
+        }
+        Frame::Stream {
+            id,
+            offset,
+            data,
+            fin,
+        } => {
+            int(&STREAM_ID_ATTR, id);
+            if let Some(offset) = offset {
+                int(&OFFSET_ATTR, offset);
+            }
+            int(&FRAME_LENGTH_ATTR, &(data.len() as u64, data.clone()));
+            attrs.push(attr!(&FIN_ATTR, value: *fin));
+        }
+        Frame::MaxData(maximum) | Frame::MaxStreams(maximum) => int(&MAXIMUM_ATTR, maximum),
+        Frame::MaxStreamData { id, maximum } => {
+            int(&STREAM_ID_ATTR, id);
+            int(&MAXIMUM_ATTR, maximum);
+        }
+        Frame::DataBlocked(limit) | Frame::StreamsBlocked(limit) => int(&LIMIT_ATTR, limit),
+        Frame::StreamDataBlocked { id, limit } => {
+            int(&STREAM_ID_ATTR, id);
+            int(&LIMIT_ATTR, limit);
+        }
+        Frame::NewConnectionId {
+            sequence,
+            retire_prior_to,
+            cid,
+            reset_token,
+        } => {
+            int(&SEQUENCE_ATTR, sequence);
+            int(&RETIRE_PRIOR_TO_ATTR, retire_prior_to);
+            attrs.push(attr!(&FRAME_CID_ATTR, range: cid.clone()));
+            attrs.push(attr!(&RESET_TOKEN_ATTR, range: reset_token.clone()));
+        }
+        Frame::RetireConnectionId(sequence) => int(&SEQUENCE_ATTR, sequence),
+        Frame::NewToken { token } => attrs.push(attr!(&FRAME_TOKEN_ATTR, range: token.clone())),
+        Frame::PathChallenge(data) | Frame::PathResponse(data) => {
+            attrs.push(attr!(&PATH_DATA_ATTR, range: data.clone()))
+        }
+        Frame::ConnectionClose {
+            code,
+            frame_type,
+            reason,
+        } => {
+            int(&ERROR_CODE_ATTR, code);
+            if let Some(frame_type) = frame_type {
+                int(&CLOSE_FRAME_TYPE_ATTR, frame_type);
+            }
+            attrs.push(attr!(&REASON_ATTR, range: reason.clone()));
+        }
+        Frame::Datagram(data) => int(&FRAME_LENGTH_ATTR, &(data.len() as u64, data.clone())),
+        Frame::Padding | Frame::Ping | Frame::HandshakeDone => {}
+    }
+}
+
+fn packet_layer(data: Vec<u8>, attrs: Vec<Attr>) -> Layer {
+    let mut layer = Layer::new(&QUIC_CLASS, ByteSlice::from(data));
+    for attr in attrs {
+        layer.add_attr(attr);
+    }
+    layer
+}
+
+fn http3_layer(frame: h3::Frame) -> Layer {
+    let mut attrs = Vec::new();
+    attrs.push(attr!(&H3_STREAM_ID_ATTR, value: frame.stream));
+    attrs.push(attr!(&H3_TYPE_ATTR, range: 0..frame.length_offset, value: frame.typ));
+    if let Some(attr) = get_h3_type(frame.typ) {
+        attrs.push(attr!(attr, range: 0..frame.length_offset));
+    }
+    let payload = frame.payload_offset..frame.data.len();
+    let length = (
+        frame.length_offset..frame.payload_offset,
+        payload.len() as u64,
+    );
+    attrs.push(attr!(&H3_LENGTH_ATTR, range: length.0, value: length.1));
+
+    match frame.typ {
+        h3::FRAME_SETTINGS => {
+            attrs.push(attr!(&H3_SETTINGS_ATTR, range: payload.clone()));
+            for (id, value) in frame.settings() {
+                attrs.push(attr!(&H3_SETTING_ATTR, value: true));
+                attrs.push(attr!(&H3_SETTING_ID_ATTR, value: id));
+                attrs.push(attr!(&H3_SETTING_VALUE_ATTR, value: value));
+            }
+        }
+        h3::FRAME_CANCEL_PUSH
+        | h3::FRAME_GOAWAY
+        | h3::FRAME_MAX_PUSH_ID
+        | h3::FRAME_PUSH_PROMISE => {
+            let mut pos = 0;
+            if let Some((id, range)) = packet::varint_range(frame.payload(), &mut pos) {
+                let range = range.start + payload.start..range.end + payload.start;
+                attrs.push(attr!(&H3_ID_ATTR, range: range, value: id));
+            }
+        }
+        _ => {}
+    }
+
+    match &frame.fields {
+        Some(Ok(fields)) => {
+            attrs.push(attr!(&H3_HEADERS_ATTR, range: payload.clone()));
+            for (name, value) in fields {
+                let name = String::from_utf8_lossy(name).into_owned();
+                let value = String::from_utf8_lossy(value).into_owned();
+                attrs.push(attr!(&H3_HEADER_ATTR, value: true));
+                attrs.push(attr!(&H3_HEADER_NAME_ATTR, value: name.clone().into_boxed_str()));
+                attrs.push(attr!(&H3_HEADER_VALUE_ATTR, value: value.clone().into_boxed_str()));
+                if let Some(class) = get_h3_header(&name) {
+                    if name == ":status" || name == "content-length" {
+                        if let Ok(value) = value.parse::<u64>() {
+                            attrs.push(attr!(class, value: value));
+                        }
+                    } else {
+                        attrs.push(attr!(class, value: value.into_boxed_str()));
+                    }
+                }
+            }
+        }
+        Some(Err(qpack::Error::Blocked)) => {
+            attrs.push(attr!(&H3_BLOCKED_ATTR, range: payload.clone()))
+        }
+        Some(Err(qpack::Error::Malformed)) => {
+            attrs.push(attr!(&H3_QPACK_ERROR_ATTR, range: payload.clone()))
+        }
+        None => {}
+    }
+
+    let mut layer = Layer::new(&HTTP3_CLASS, ByteSlice::from(frame.data.clone()));
+    for attr in attrs {
+        layer.add_attr(attr);
+    }
+    if frame.typ == h3::FRAME_DATA && !payload.is_empty() {
+        let body = frame.data[payload].to_vec();
+        layer.add_payload(Payload::new(ByteSlice::from(body), "@body:http3"));
+    }
+    layer
+}
+
+impl Worker for QuicWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:quic"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let src: Vec<u8> = stack_value(stack, token!("_.src"))?.unwrap_or_default();
+        let dst: Vec<u8> = stack_value(stack, token!("_.dst"))?.unwrap_or_default();
+        let sport: u64 = stack_value(stack, token!("udp.src"))?.unwrap_or(0);
+        let dport: u64 = stack_value(stack, token!("udp.dst"))?.unwrap_or(0);
+        let path = ((src, sport), (dst, dport));
+
+        // A datagram may contain coalesced packets.
+        let mut layers = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            match self.packet(&data[pos..], &path) {
+                Some((len, packet)) => {
+                    layers.extend(packet);
+                    pos += len;
+                }
+                None => break,
+            }
+            // A short header packet extends to the end of the datagram.
+        }
+        if layers.is_empty() {
+            return Ok(Status::Skip);
+        }
+        for layer in layers {
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct QuicDecoder {}
+
+impl Decoder for QuicDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        ctx.dissector_table("udp.port")
+            .add_default(443, "@data:quic");
+        let path = ctx.get_config(KEY_LOG_FILE_KEY).trim_matches('"');
+        let keylog = if path.is_empty() {
+            None
+        } else {
+            Some(KeyLogFile::new(path))
+        };
+        Box::new(QuicWorker {
+            connections: Vec::new(),
+            cids: HashMap::new(),
+            cid_lens: BTreeSet::new(),
+            paths: HashMap::new(),
+            keylog,
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::SerialSync,
+            options: vec![DecoderOption::path(KEY_LOG_FILE_KEY).description(
+                "Path of a key log file written via SSLKEYLOGFILE to decrypt QUIC packets",
+            )],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(QUIC_CLASS, "quic");
+
+def_attr_class!(TYPE_ATTR, "quic.type", typ: "@enum");
+
+def_attr_class!(VERSION_ATTR, "quic.version", cast: cast::UInt32BE());
+
+def_attr_class!(DCID_ATTR, "quic.dcid", cast: cast::ByteSlice());
+
+def_attr_class!(SCID_ATTR, "quic.scid", cast: cast::ByteSlice());
+
+def_attr_class!(TOKEN_ATTR, "quic.token", cast: cast::ByteSlice());
+
+def_attr_class!(LENGTH_ATTR, "quic.length");
+
+def_attr_class!(PACKET_NUMBER_ATTR, "quic.packetNumber");
+
+def_attr_class!(KEY_PHASE_ATTR, "quic.keyPhase");
+
+def_attr_class!(SUPPORTED_VERSION_ATTR, "quic.supportedVersion", cast: cast::UInt32BE());
+
+def_attr_class!(INTEGRITY_TAG_ATTR, "quic.integrityTag", cast: cast::ByteSlice());
+
+def_attr_class!(CONNECTION_ATTR, "quic.connection");
+
+def_attr_class!(SNI_ATTR, "quic.sni");
+
+def_attr_class!(ALPN_ATTR, "quic.alpn");
+
+def_attr_class!(FRAMES_ATTR, "quic.frames",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(FRAME_ATTR, "quic.frame",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(FRAME_TYPE_ATTR, "quic.frame.type", typ: "@enum");
+
+def_attr_class!(ACK_LARGEST_ATTR, "quic.frame.largestAcknowledged");
+
+def_attr_class!(ACK_DELAY_ATTR, "quic.frame.ackDelay");
+
+def_attr_class!(ACK_RANGES_ATTR, "quic.frame.ackRangeCount");
+
+def_attr_class!(STREAM_ID_ATTR, "quic.frame.streamId");
+
+def_attr_class!(OFFSET_ATTR, "quic.frame.offset");
+
+def_attr_class!(FRAME_LENGTH_ATTR, "quic.frame.length");
+
+def_attr_class!(FIN_ATTR, "quic.frame.fin");
+
+def_attr_class!(ERROR_CODE_ATTR, "quic.frame.errorCode");
+
+def_attr_class!(FINAL_SIZE_ATTR, "quic.frame.finalSize");
+
+def_attr_class!(MAXIMUM_ATTR, "quic.frame.maximum");
+
+def_attr_class!(LIMIT_ATTR, "quic.frame.limit");
+
+def_attr_class!(SEQUENCE_ATTR, "quic.frame.sequence");
+
+def_attr_class!(RETIRE_PRIOR_TO_ATTR, "quic.frame.retirePriorTo");
+
+def_attr_class!(FRAME_CID_ATTR, "quic.frame.connectionId", cast: cast::ByteSlice());
+
+def_attr_class!(RESET_TOKEN_ATTR, "quic.frame.resetToken", cast: cast::ByteSlice());
+
+def_attr_class!(FRAME_TOKEN_ATTR, "quic.frame.token", cast: cast::ByteSlice());
+
+def_attr_class!(PATH_DATA_ATTR, "quic.frame.data", cast: cast::ByteSlice());
+
+def_attr_class!(CLOSE_FRAME_TYPE_ATTR, "quic.frame.frameType");
+
+def_attr_class!(REASON_ATTR, "quic.frame.reason", cast: cast::Utf8());
+
+def_attr_class!(UNDECRYPTED_ATTR, "quic.undecrypted",
+    typ: "@expert:note",
+    description: "Keys not available"
+);
+
+def_attr_class!(DECRYPTION_FAILED_ATTR, "quic.decryptionFailed",
+    typ: "@expert:warn",
+    description: "Packet cannot be decrypted"
+);
+
+def_attr_class!(MIGRATION_ATTR, "quic.migration",
+    typ: "@expert:note",
+    description: "Connection migrated to a new path"
+);
+
+def_attr_class!(MALFORMED_ATTR, "quic.malformed",
+    typ: "@expert:warn",
+    description: "Malformed packet"
+);
+
+def_layer_class!(HTTP3_CLASS, "http3");
+
+def_attr_class!(H3_STREAM_ID_ATTR, "http3.streamId");
+
+def_attr_class!(H3_TYPE_ATTR, "http3.type", typ: "@enum");
+
+def_attr_class!(H3_LENGTH_ATTR, "http3.length");
+
+def_attr_class!(H3_ID_ATTR, "http3.id");
+
+def_attr_class!(H3_SETTINGS_ATTR, "http3.settings",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(H3_SETTING_ATTR, "http3.setting",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(H3_SETTING_ID_ATTR, "http3.setting.id");
+
+def_attr_class!(H3_SETTING_VALUE_ATTR, "http3.setting.value");
+
+def_attr_class!(H3_HEADERS_ATTR, "http3.headers",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(H3_HEADER_ATTR, "http3.header",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(H3_HEADER_NAME_ATTR, "http3.header.name");
+
+def_attr_class!(H3_HEADER_VALUE_ATTR, "http3.header.value");
+
+def_attr_class!(H3_BLOCKED_ATTR, "http3.qpack.blocked",
+    typ: "@expert:note",
+    description: "Dynamic table entries not captured"
+);
+
+def_attr_class!(H3_QPACK_ERROR_ATTR, "http3.qpack.error",
+    typ: "@expert:warn",
+    description: "Malformed field section"
+);
+
+fn get_packet_type(typ: PacketType) -> &'static AttrClass {
+    match typ {
+        PacketType::Initial => attr_class_lazy!("quic.type.initial", typ: "@novalue", value: true),
+        PacketType::ZeroRtt => attr_class_lazy!("quic.type.zeroRtt", typ: "@novalue", value: true),
+        PacketType::Handshake => {
+            attr_class_lazy!("quic.type.handshake", typ: "@novalue", value: true)
+        }
+        PacketType::Retry => attr_class_lazy!("quic.type.retry", typ: "@novalue", value: true),
+        PacketType::VersionNegotiation => attr_class_lazy!(
+            "quic.type.versionNegotiation",
+            typ: "@novalue",
+            value: true
+        ),
+        PacketType::OneRtt => attr_class_lazy!("quic.type.oneRtt", typ: "@novalue", value: true),
+    }
+}
+
+fn get_frame_type(typ: u64) -> Option<&'static AttrClass> {
+    match typ {
+        0x00 => Some(attr_class_lazy!("quic.frame.type.padding", typ: "@novalue", value: true)),
+        0x01 => Some(attr_class_lazy!("quic.frame.type.ping", typ: "@novalue", value: true)),
+        0x02 | 0x03 => Some(attr_class_lazy!("quic.frame.type.ack", typ: "@novalue", value: true)),
+        0x04 => Some(attr_class_lazy!("quic.frame.type.resetStream", typ: "@novalue", value: true)),
+        0x05 => Some(attr_class_lazy!("quic.frame.type.stopSending", typ: "@novalue", value: true)),
+        0x06 => Some(attr_class_lazy!("quic.frame.type.crypto", typ: "@novalue", value: true)),
+        0x07 => Some(attr_class_lazy!("quic.frame.type.newToken", typ: "@novalue", value: true)),
+        0x08..=0x0f => {
+            Some(attr_class_lazy!("quic.frame.type.stream", typ: "@novalue", value: true))
+        }
+        0x10 => Some(attr_class_lazy!("quic.frame.type.maxData", typ: "@novalue", value: true)),
+        0x11 => {
+            Some(attr_class_lazy!("quic.frame.type.maxStreamData", typ: "@novalue", value: true))
+        }
+        0x12 | 0x13 => {
+            Some(attr_class_lazy!("quic.frame.type.maxStreams", typ: "@novalue", value: true))
+        }
+        0x14 => Some(attr_class_lazy!("quic.frame.type.dataBlocked", typ: "@novalue", value: true)),
+        0x15 => Some(
+            attr_class_lazy!("quic.frame.type.streamDataBlocked", typ: "@novalue", value: true),
+        ),
+        0x16 | 0x17 => {
+            Some(attr_class_lazy!("quic.frame.type.streamsBlocked", typ: "@novalue", value: true))
+        }
+        0x18 => {
+            Some(attr_class_lazy!("quic.frame.type.newConnectionId", typ: "@novalue", value: true))
+        }
+        0x19 => Some(
+            attr_class_lazy!("quic.frame.type.retireConnectionId", typ: "@novalue", value: true),
+        ),
+        0x1a => {
+            Some(attr_class_lazy!("quic.frame.type.pathChallenge", typ: "@novalue", value: true))
+        }
+        0x1b => {
+            Some(attr_class_lazy!("quic.frame.type.pathResponse", typ: "@novalue", value: true))
+        }
+        0x1c | 0x1d => {
+            Some(attr_class_lazy!("quic.frame.type.connectionClose", typ: "@novalue", value: true))
+        }
+        0x1e => {
+            Some(attr_class_lazy!("quic.frame.type.handshakeDone", typ: "@novalue", value: true))
+        }
+        0x30 | 0x31 => {
+            Some(attr_class_lazy!("quic.frame.type.datagram", typ: "@novalue", value: true))
+        }
+        _ => None,
+    }
+}
+
+fn get_h3_type(typ: u64) -> Option<&'static AttrClass> {
+    match typ {
+        h3::FRAME_DATA => Some(attr_class_lazy!("http3.type.data", typ: "@novalue", value: true)),
+        h3::FRAME_HEADERS => {
+            Some(attr_class_lazy!("http3.type.headers", typ: "@novalue", value: true))
+        }
+        h3::FRAME_CANCEL_PUSH => {
+            Some(attr_class_lazy!("http3.type.cancelPush", typ: "@novalue", value: true))
+        }
+        h3::FRAME_SETTINGS => {
+            Some(attr_class_lazy!("http3.type.settings", typ: "@novalue", value: true))
+        }
+        h3::FRAME_PUSH_PROMISE => {
+            Some(attr_class_lazy!("http3.type.pushPromise", typ: "@novalue", value: true))
+        }
+        h3::FRAME_GOAWAY => {
+            Some(attr_class_lazy!("http3.type.goaway", typ: "@novalue", value: true))
+        }
+        h3::FRAME_MAX_PUSH_ID => {
+            Some(attr_class_lazy!("http3.type.maxPushId", typ: "@novalue", value: true))
+        }
+        _ => None,
+    }
+}
+
+fn get_h3_header(name: &str) -> Option<&'static AttrClass> {
+    match name {
+        ":method" => Some(attr_class_lazy!("http3.method")),
+        ":scheme" => Some(attr_class_lazy!("http3.scheme")),
+        ":authority" => Some(attr_class_lazy!("http3.authority")),
+        ":path" => Some(attr_class_lazy!("http3.path")),
+        ":status" => Some(attr_class_lazy!("http3.status")),
+        "content-type" => Some(attr_class_lazy!("http3.contentType")),
+        "content-length" => Some(attr_class_lazy!("http3.contentLength")),
+        "user-agent" => Some(attr_class_lazy!("http3.userAgent")),
+        "server" => Some(attr_class_lazy!("http3.server")),
+        _ => None,
+    }
+}
+
+genet_decoders!(QuicDecoder {});
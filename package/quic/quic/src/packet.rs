@@ -0,0 +1,213 @@
+//! Packet headers (RFC 9000, Section 17).
+
+use std::ops::Range;
+
+pub const VERSION_1: u32 = 1;
+
+/// The maximum length of a connection ID of version 1.
+const MAX_CID_LEN: usize = 20;
+
+/// The length of the integrity tag of a Retry packet.
+const RETRY_TAG_LEN: usize = 16;
+
+/// Reads a variable-length integer at `pos`, and advances `pos`.
+pub fn varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let first = *data.get(*pos)?;
+    let len = 1 << (first >> 6);
+    let bytes = data.get(*pos..*pos + len)?;
+    let value = bytes[1..].iter().fold(u64::from(first & 0x3f), |value, b| {
+        value << 8 | u64::from(*b)
+    });
+    *pos += len;
+    Some(value)
+}
+
+/// Reads a variable-length integer at `pos`, and returns it with its range.
+pub fn varint_range(data: &[u8], pos: &mut usize) -> Option<(u64, Range<usize>)> {
+    let start = *pos;
+    let value = varint(data, pos)?;
+    Some((value, start..*pos))
+}
+
+/// Reads a byte string of `len` bytes at `pos`, and returns its range.
+pub fn bytes(data: &[u8], pos: &mut usize, len: usize) -> Option<Range<usize>> {
+    let range = *pos..pos.checked_add(len)?;
+    if range.end > data.len() {
+        return None;
+    }
+    *pos = range.end;
+    Some(range)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PacketType {
+    Initial,
+    ZeroRtt,
+    Handshake,
+    Retry,
+    VersionNegotiation,
+    OneRtt,
+}
+
+/// The keys of a packet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Epoch {
+    Initial,
+    ZeroRtt,
+    Handshake,
+    OneRtt,
+}
+
+impl PacketType {
+    pub fn epoch(self) -> Option<Epoch> {
+        match self {
+            PacketType::Initial => Some(Epoch::Initial),
+            PacketType::ZeroRtt => Some(Epoch::ZeroRtt),
+            PacketType::Handshake => Some(Epoch::Handshake),
+            PacketType::OneRtt => Some(Epoch::OneRtt),
+            _ => None,
+        }
+    }
+}
+
+impl Epoch {
+    /// Returns the packet number space, where 0-RTT and 1-RTT packets share
+    /// the application data space.
+    pub fn space(self) -> usize {
+        match self {
+            Epoch::Initial => 0,
+            Epoch::Handshake => 1,
+            Epoch::ZeroRtt | Epoch::OneRtt => 2,
+        }
+    }
+}
+
+/// A packet header. The ranges are relative to the start of the packet.
+#[derive(Debug, Clone)]
+pub struct Header {
+    /// None for a long header of an unknown version.
+    pub typ: Option<PacketType>,
+    pub version: Option<u32>,
+    pub dcid: Range<usize>,
+    pub scid: Option<Range<usize>>,
+    pub token: Option<Range<usize>>,
+    /// The Length field of a long header.
+    pub length: Option<(u64, Range<usize>)>,
+    /// The versions of a Version Negotiation packet.
+    pub versions: Vec<Range<usize>>,
+    /// The integrity tag of a Retry packet.
+    pub retry_tag: Option<Range<usize>>,
+    /// The offset of the packet number of a protected packet.
+    pub pn_offset: usize,
+    /// The length of the packet, which is followed by coalesced packets.
+    pub len: usize,
+}
+
+impl Header {
+    fn new(typ: Option<PacketType>, dcid: Range<usize>, len: usize) -> Header {
+        Header {
+            typ,
+            version: None,
+            dcid,
+            scid: None,
+            token: None,
+            length: None,
+            versions: Vec::new(),
+            retry_tag: None,
+            pn_offset: 0,
+            len,
+        }
+    }
+
+    /// Parses a long header.
+    pub fn long(data: &[u8]) -> Option<Header> {
+        let first = *data.first()?;
+        let version =
+            u32::from_be_bytes([*data.get(1)?, *data.get(2)?, *data.get(3)?, *data.get(4)?]);
+        let mut pos = 5;
+        let len = usize::from(*data.get(pos)?);
+        pos += 1;
+        let dcid = bytes(data, &mut pos, len)?;
+        let len = usize::from(*data.get(pos)?);
+        pos += 1;
+        let scid = bytes(data, &mut pos, len)?;
+        if version == VERSION_1 && (dcid.len() > MAX_CID_LEN || scid.len() > MAX_CID_LEN) {
+            return None;
+        }
+
+        let typ = match version {
+            0 => PacketType::VersionNegotiation,
+            VERSION_1 => match (first >> 4) & 0x03 {
+                0 => PacketType::Initial,
+                1 => PacketType::ZeroRtt,
+                2 => PacketType::Handshake,
+                _ => PacketType::Retry,
+            },
+            _ => {
+                let mut header = Header::new(None, dcid, data.len());
+                header.version = Some(version);
+                header.scid = Some(scid);
+                return Some(header);
+            }
+        };
+        let mut header = Header::new(Some(typ), dcid, data.len());
+        header.version = Some(version);
+        header.scid = Some(scid);
+        match typ {
+            PacketType::VersionNegotiation => {
+                header.versions = (pos..data.len() - (data.len() - pos) % 4)
+                    .step_by(4)
+                    .map(|start| start..start + 4)
+                    .collect();
+            }
+            PacketType::Retry => {
+                let end = data.len().checked_sub(RETRY_TAG_LEN)?;
+                if end < pos {
+                    return None;
+                }
+                header.token = Some(pos..end);
+                header.retry_tag = Some(end..data.len());
+            }
+            _ => {
+                if typ == PacketType::Initial {
+                    let len = varint(data, &mut pos)?;
+                    header.token = Some(bytes(data, &mut pos, len as usize)?);
+                }
+                let (length, range) = varint_range(data, &mut pos)?;
+                header.length = Some((length, range));
+                header.pn_offset = pos;
+                header.len = pos.checked_add(length as usize)?;
+                if header.len > data.len() {
+                    return None;
+                }
+            }
+        }
+        Some(header)
+    }
+
+    /// Parses a short header with a Destination Connection ID of `dcid_len`
+    /// bytes.
+    pub fn short(data: &[u8], dcid_len: usize) -> Option<Header> {
+        let mut pos = 1;
+        let dcid = bytes(data, &mut pos, dcid_len)?;
+        let mut header = Header::new(Some(PacketType::OneRtt), dcid, data.len());
+        header.pn_offset = pos;
+        Some(header)
+    }
+}
+
+/// Returns the packet number closest to the next packet number expected
+/// (RFC 9000, Appendix A.3).
+pub fn packet_number(largest: Option<u64>, truncated: u64, pn_len: usize) -> u64 {
+    let expected = largest.map_or(0, |largest| largest + 1);
+    let win = 1u64 << (pn_len * 8);
+    let half = win / 2;
+    let candidate = (expected & !(win - 1)) | truncated;
+    if candidate + half <= expected && candidate < (1 << 62) - win {
+        candidate + win
+    } else if candidate > expected + half && candidate >= win {
+        candidate - win
+    } else {
+        candidate
+    }
+}
@@ -0,0 +1,379 @@
+//! QPACK field compression (RFC 9204).
+
+use hpack::huffman::HuffmanDecoder;
+use packet::bytes;
+use std::{collections::VecDeque, mem};
+
+/// The size of a dynamic table entry in addition to its name and value.
+const ENTRY_OVERHEAD: usize = 32;
+
+pub type Field = (Vec<u8>, Vec<u8>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The field section references dynamic table entries which have not
+    /// been seen, e.g. since the encoder stream is not captured.
+    Blocked,
+    Malformed,
+}
+
+/// The static table (RFC 9204, Appendix A).
+static STATIC_TABLE: &[(&str, &str)] = &[
+    (":authority", ""),
+    (":path", "/"),
+    ("age", "0"),
+    ("content-disposition", ""),
+    ("content-length", "0"),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("referer", ""),
+    ("set-cookie", ""),
+    (":method", "CONNECT"),
+    (":method", "DELETE"),
+    (":method", "GET"),
+    (":method", "HEAD"),
+    (":method", "OPTIONS"),
+    (":method", "POST"),
+    (":method", "PUT"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "103"),
+    (":status", "200"),
+    (":status", "304"),
+    (":status", "404"),
+    (":status", "503"),
+    ("accept", "*/*"),
+    ("accept", "application/dns-message"),
+    ("accept-encoding", "gzip, deflate, br"),
+    ("accept-ranges", "bytes"),
+    ("access-control-allow-headers", "cache-control"),
+    ("access-control-allow-headers", "content-type"),
+    ("access-control-allow-origin", "*"),
+    ("cache-control", "max-age=0"),
+    ("cache-control", "max-age=2592000"),
+    ("cache-control", "max-age=604800"),
+    ("cache-control", "no-cache"),
+    ("cache-control", "no-store"),
+    ("cache-control", "public, max-age=31536000"),
+    ("content-encoding", "br"),
+    ("content-encoding", "gzip"),
+    ("content-type", "application/dns-message"),
+    ("content-type", "application/javascript"),
+    ("content-type", "application/json"),
+    ("content-type", "application/x-www-form-urlencoded"),
+    ("content-type", "image/gif"),
+    ("content-type", "image/jpeg"),
+    ("content-type", "image/png"),
+    ("content-type", "text/css"),
+    ("content-type", "text/html; charset=utf-8"),
+    ("content-type", "text/plain"),
+    ("content-type", "text/plain;charset=utf-8"),
+    ("range", "bytes=0-"),
+    ("strict-transport-security", "max-age=31536000"),
+    (
+        "strict-transport-security",
+        "max-age=31536000; includesubdomains",
+    ),
+    (
+        "strict-transport-security",
+        "max-age=31536000; includesubdomains; preload",
+    ),
+    ("vary", "accept-encoding"),
+    ("vary", "origin"),
+    ("x-content-type-options", "nosniff"),
+    ("x-xss-protection", "1; mode=block"),
+    (":status", "100"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "302"),
+    (":status", "400"),
+    (":status", "403"),
+    (":status", "421"),
+    (":status", "425"),
+    (":status", "500"),
+    ("accept-language", ""),
+    ("access-control-allow-credentials", "FALSE"),
+    ("access-control-allow-credentials", "TRUE"),
+    ("access-control-allow-headers", "*"),
+    ("access-control-allow-methods", "get"),
+    ("access-control-allow-methods", "get, post, options"),
+    ("access-control-allow-methods", "options"),
+    ("access-control-expose-headers", "content-length"),
+    ("access-control-request-headers", "content-type"),
+    ("access-control-request-method", "get"),
+    ("access-control-request-method", "post"),
+    ("alt-svc", "clear"),
+    ("authorization", ""),
+    (
+        "content-security-policy",
+        "script-src 'none'; object-src 'none'; base-uri 'none'",
+    ),
+    ("early-data", "1"),
+    ("expect-ct", ""),
+    ("forwarded", ""),
+    ("if-range", ""),
+    ("origin", ""),
+    ("purpose", "prefetch"),
+    ("server", ""),
+    ("timing-allow-origin", "*"),
+    ("upgrade-insecure-requests", "1"),
+    ("user-agent", ""),
+    ("x-forwarded-for", ""),
+    ("x-frame-options", "deny"),
+    ("x-frame-options", "sameorigin"),
+];
+
+fn static_field(index: u64) -> Option<Field> {
+    STATIC_TABLE
+        .get(index as usize)
+        .map(|(name, value)| (name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+}
+
+/// Reads an integer with a prefix of the low `bits` bits of the byte at
+/// `pos` (RFC 7541, Section 5.1).
+fn integer(data: &[u8], pos: &mut usize, bits: u32) -> Option<u64> {
+    let max = (1u16 << bits) as u64 - 1;
+    let mut value = u64::from(*data.get(*pos)?) & max;
+    *pos += 1;
+    if value < max {
+        return Some(value);
+    }
+    let mut shift = 0;
+    loop {
+        let b = *data.get(*pos)?;
+        *pos += 1;
+        if shift > 56 {
+            return None;
+        }
+        value += u64::from(b & 0x7f) << shift;
+        shift += 7;
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+/// Reads a string literal whose length has a prefix of `bits` bits, which
+/// follows the Huffman flag.
+fn string(
+    data: &[u8],
+    pos: &mut usize,
+    bits: u32,
+    huffman: &mut HuffmanDecoder,
+) -> Option<Vec<u8>> {
+    let encoded = *data.get(*pos)? & (1 << bits) != 0;
+    let len = integer(data, pos, bits)?;
+    let range = bytes(data, pos, len as usize)?;
+    if encoded {
+        huffman.decode(&data[range]).ok()
+    } else {
+        Some(data[range].to_vec())
+    }
+}
+
+/// The dynamic table of an encoder.
+#[derive(Default)]
+pub struct Table {
+    /// The entries from the oldest.
+    entries: VecDeque<Field>,
+    /// The number of entries inserted.
+    inserted: u64,
+    capacity: u64,
+    size: u64,
+    /// The maximum capacity allowed by the decoder, which is used to decode
+    /// the Required Insert Count.
+    pub max_capacity: u64,
+    /// The encoder stream data of an incomplete instruction.
+    buf: Vec<u8>,
+    broken: bool,
+}
+
+impl Table {
+    fn get(&self, absolute: u64) -> Option<&Field> {
+        let evicted = self.inserted - self.entries.len() as u64;
+        self.entries.get(absolute.checked_sub(evicted)? as usize)
+    }
+
+    /// Returns the entry of an index relative to the last inserted entry.
+    fn get_relative(&self, index: u64) -> Option<&Field> {
+        self.get(self.inserted.checked_sub(index + 1)?)
+    }
+
+    fn insert(&mut self, field: Field) {
+        self.size += (field.0.len() + field.1.len() + ENTRY_OVERHEAD) as u64;
+        self.entries.push_back(field);
+        self.inserted += 1;
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            match self.entries.pop_front() {
+                Some((name, value)) => {
+                    self.size -= (name.len() + value.len() + ENTRY_OVERHEAD) as u64
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Processes the data of the encoder stream.
+    pub fn encoder_stream(&mut self, data: &[u8], huffman: &mut HuffmanDecoder) {
+        if self.broken {
+            return;
+        }
+        let mut buf = mem::take(&mut self.buf);
+        buf.extend_from_slice(data);
+        let mut pos = 0;
+        while pos < buf.len() {
+            let mut next = pos;
+            match self.instruction(&buf, &mut next, huffman) {
+                Some(true) => pos = next,
+                Some(false) => {
+                    self.broken = true;
+                    return;
+                }
+                // The instruction is incomplete.
+                None => break,
+            }
+        }
+        buf.drain(..pos);
+        self.buf = buf;
+    }
+
+    /// Processes an encoder instruction (RFC 9204, Section 4.3), and returns
+    /// false if it is invalid.
+    fn instruction(
+        &mut self,
+        data: &[u8],
+        pos: &mut usize,
+        huffman: &mut HuffmanDecoder,
+    ) -> Option<bool> {
+        let first = data[*pos];
+        if first & 0x80 != 0 {
+            // Insert with Name Reference
+            let index = integer(data, pos, 6)?;
+            let value = string(data, pos, 7, huffman)?;
+            let name = if first & 0x40 != 0 {
+                static_field(index).map(|(name, _)| name)
+            } else {
+                self.get_relative(index).map(|(name, _)| name.clone())
+            };
+            Some(name.map(|name| self.insert((name, value))).is_some())
+        } else if first & 0x40 != 0 {
+            // Insert with Literal Name
+            let name = string(data, pos, 5, huffman)?;
+            let value = string(data, pos, 7, huffman)?;
+            self.insert((name, value));
+            Some(true)
+        } else if first & 0x20 != 0 {
+            // Set Dynamic Table Capacity
+            self.capacity = integer(data, pos, 5)?;
+            self.evict();
+            Some(true)
+        } else {
+            // Duplicate
+            let index = integer(data, pos, 5)?;
+            let field = self.get_relative(index).cloned();
+            Some(field.map(|field| self.insert(field)).is_some())
+        }
+    }
+
+    /// Decodes the Required Insert Count (RFC 9204, Section 4.5.1.1).
+    fn required_insert_count(&self, encoded: u64) -> Option<u64> {
+        if encoded == 0 {
+            return Some(0);
+        }
+        let max_entries = self.max_capacity.max(self.capacity) / ENTRY_OVERHEAD as u64;
+        let full_range = 2 * max_entries;
+        if encoded > full_range {
+            return None;
+        }
+        let max_value = self.inserted + max_entries;
+        let max_wrapped = max_value / full_range * full_range;
+        let mut count = max_wrapped + encoded - 1;
+        if count > max_value {
+            if count <= full_range {
+                return None;
+            }
+            count -= full_range;
+        }
+        if count == 0 {
+            None
+        } else {
+            Some(count)
+        }
+    }
+
+    /// Decodes an encoded field section (RFC 9204, Section 4.5).
+    pub fn decode(&self, block: &[u8], huffman: &mut HuffmanDecoder) -> Result<Vec<Field>, Error> {
+        let mut pos = 0;
+        let encoded = integer(block, &mut pos, 8).ok_or(Error::Malformed)?;
+        let count = self
+            .required_insert_count(encoded)
+            .ok_or(Error::Malformed)?;
+        let negative = block.get(pos).ok_or(Error::Malformed)? & 0x80 != 0;
+        let delta = integer(block, &mut pos, 7).ok_or(Error::Malformed)?;
+        let base = if negative {
+            count.checked_sub(delta + 1).ok_or(Error::Malformed)?
+        } else {
+            count + delta
+        };
+        if count > self.inserted {
+            return Err(Error::Blocked);
+        }
+
+        let dynamic = |absolute: Option<u64>| {
+            absolute
+                .and_then(|absolute| self.get(absolute))
+                .cloned()
+                .ok_or(Error::Blocked)
+        };
+        let mut fields = Vec::new();
+        while pos < block.len() {
+            let first = block[pos];
+            let field = if first & 0x80 != 0 {
+                // Indexed Field Line
+                let index = integer(block, &mut pos, 6).ok_or(Error::Malformed)?;
+                if first & 0x40 != 0 {
+                    static_field(index).ok_or(Error::Malformed)?
+                } else {
+                    dynamic(base.checked_sub(index + 1))?
+                }
+            } else if first & 0x40 != 0 {
+                // Literal Field Line with Name Reference
+                let index = integer(block, &mut pos, 4).ok_or(Error::Malformed)?;
+                let (name, _) = if first & 0x10 != 0 {
+                    static_field(index).ok_or(Error::Malformed)?
+                } else {
+                    dynamic(base.checked_sub(index + 1))?
+                };
+                let value = string(block, &mut pos, 7, huffman).ok_or(Error::Malformed)?;
+                (name, value)
+            } else if first & 0x20 != 0 {
+                // Literal Field Line with Literal Name
+                let name = string(block, &mut pos, 3, huffman).ok_or(Error::Malformed)?;
+                let value = string(block, &mut pos, 7, huffman).ok_or(Error::Malformed)?;
+                (name, value)
+            } else if first & 0x10 != 0 {
+                // Indexed Field Line with Post-Base Index
+                let index = integer(block, &mut pos, 4).ok_or(Error::Malformed)?;
+                dynamic(base.checked_add(index))?
+            } else {
+                // Literal Field Line with Post-Base Name Reference
+                let index = integer(block, &mut pos, 3).ok_or(Error::Malformed)?;
+                let (name, _) = dynamic(base.checked_add(index))?;
+                let value = string(block, &mut pos, 7, huffman).ok_or(Error::Malformed)?;
+                (name, value)
+            };
+            fields.push(field);
+        }
+        Ok(fields)
+    }
+}
@@ -0,0 +1,52 @@
+//! Reassembly of the byte streams of CRYPTO and STREAM frames.
+
+use std::collections::BTreeMap;
+
+/// The maximum number of bytes buffered ahead of a gap.
+const MAX_PENDING: usize = 1 << 20;
+
+/// Orders data received at arbitrary offsets.
+#[derive(Default)]
+pub struct Stream {
+    /// The offset of the data to be returned next.
+    offset: u64,
+    pending: BTreeMap<u64, Vec<u8>>,
+    pending_len: usize,
+}
+
+impl Stream {
+    /// Adds data at `offset`, and returns the data following the data
+    /// returned before. Retransmitted data is returned once.
+    pub fn push(&mut self, offset: u64, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        if offset <= self.offset {
+            let skip = (self.offset - offset) as usize;
+            if skip < data.len() {
+                out.extend_from_slice(&data[skip..]);
+                self.offset += (data.len() - skip) as u64;
+            }
+        } else if self.pending_len + data.len() <= MAX_PENDING {
+            self.pending_len += data.len();
+            if let Some(old) = self.pending.insert(offset, data.to_vec()) {
+                self.pending_len -= old.len();
+            }
+            return out;
+        } else {
+            return out;
+        }
+
+        while let Some((&start, _)) = self.pending.iter().next() {
+            if start > self.offset {
+                break;
+            }
+            let data = self.pending.remove(&start).unwrap_or_default();
+            self.pending_len -= data.len();
+            let skip = (self.offset - start) as usize;
+            if skip < data.len() {
+                out.extend_from_slice(&data[skip..]);
+                self.offset += (data.len() - skip) as u64;
+            }
+        }
+        out
+    }
+}
@@ -6,7 +6,9 @@ use std::collections::{BTreeMap, HashMap};
 #[derive(Debug)]
 struct Stream {
     pub id: u64,
+    pub isn: u32,
     pub seq: i64,
+    pub closed: bool,
     pub len: usize,
     offset: usize,
     slices: BTreeMap<usize, ByteSlice>,
@@ -16,7 +18,9 @@ impl Stream {
     fn new(id: u64) -> Stream {
         return Stream {
             id: id,
+            isn: 0,
             seq: -1,
+            closed: false,
             len: 0,
             offset: 0,
             slices: BTreeMap::new(),
@@ -57,12 +61,14 @@ impl Stream {
 
 struct TcpStreamWorker {
     map: HashMap<(ByteSlice, ByteSlice, u32, u32), Stream>,
+    streams: u64,
 }
 
 impl TcpStreamWorker {
     fn new() -> TcpStreamWorker {
         TcpStreamWorker {
             map: HashMap::new(),
+            streams: 0,
         }
     }
 }
@@ -75,12 +81,12 @@ impl Worker for TcpStreamWorker {
         parent: &mut Parent,
     ) -> Result<Status> {
         if parent.id() == token!("tcp") {
-            let slice: ByteSlice = parent
+            let (slice, typ) = parent
                 .payloads()
                 .iter()
                 .find(|p| p.id() == token!("@data:tcp"))
-                .unwrap()
-                .data();
+                .map(|p| (p.data(), p.typ()))
+                .unwrap();
 
             let stream_id = {
                 let parent_src: ByteSlice = stack
@@ -106,12 +112,6 @@ impl Worker for TcpStreamWorker {
                 (parent_src, parent_dst, src, dst)
             };
 
-            let id = self.map.len();
-            let stream = self
-                .map
-                .entry(stream_id)
-                .or_insert_with(|| Stream::new(id as u64));
-
             let seq: u32 = parent
                 .attr(token!("tcp.seq"))
                 .unwrap()
@@ -129,9 +129,29 @@ impl Worker for TcpStreamWorker {
                 .try_into()?;
 
             let syn = (flags & (0x1 << 1)) != 0;
+            let fin_or_rst = (flags & 0x5) != 0;
+
+            // A SYN with a new initial sequence number, or after the stream
+            // is closed, starts a new connection on the reused 4-tuple.
+            let reused = syn
+                && self
+                    .map
+                    .get(&stream_id)
+                    .map_or(false, |s| s.seq >= 0 && (s.closed || s.isn != seq));
+            if reused {
+                self.map.remove(&stream_id);
+            }
+
+            let id = self.streams;
+            let stream = self.map.entry(stream_id).or_insert_with(|| Stream::new(id));
+            if stream.id == id {
+                self.streams += 1;
+            }
+
             if syn {
                 if stream.seq < 0 {
                     let offset = stream.len;
+                    stream.isn = seq;
                     stream.seq = seq as i64;
                     stream.len += slice.len();
                     stream.put(offset, slice);
@@ -155,9 +175,13 @@ impl Worker for TcpStreamWorker {
                 }
             }
 
+            if fin_or_rst {
+                stream.closed = true;
+            }
+
             let payloads = stream.fetch();
             for payload in payloads {
-                parent.add_payload(Payload::new(payload, "@stream:tcp"));
+                parent.add_payload(Payload::with_typ(payload, "@stream:tcp", typ));
             }
 
             parent.add_attr(attr!(&STREAM_ATTR));
@@ -184,7 +208,7 @@ impl Decoder for TcpStreamDecoder {
     }
 }
 
-def_attr_class!(STREAM_ATTR, "tcp.stream",
+def_attr_class!(STREAM_ATTR, "tcp.stream.payloads",
     typ: "@novalue",
     cast: cast::UInt8().map(|v| v)
 );
@@ -1,13 +1,24 @@
 extern crate genet_sdk;
 
-use genet_sdk::{cast, decoder::*, prelude::*};
-
-struct TcpWorker {}
+use genet_sdk::{
+    cast,
+    checksum::{self, Validator},
+    decoder::*,
+    heuristic::{self, Heuristics},
+    option::{self, OptionTable, Tlv},
+    prelude::*,
+};
+
+struct TcpWorker {
+    heuristics: Heuristics,
+    validator: Validator,
+    options: OptionTable,
+}
 
 impl Worker for TcpWorker {
     fn decode(
         &mut self,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         _stack: &LayerStack,
         parent: &mut Parent,
     ) -> Result<Status> {
@@ -27,61 +38,75 @@ impl Worker for TcpWorker {
 
         let data_offset: usize = OFFSET_ATTR_HEADER.try_get(&layer)?.try_into()?;
         let data_offset = data_offset * 4;
-        let mut offset = 20;
+        let options = self.options.dissect(&mut layer, 20..data_offset)?;
+        layer.add_attr(attr!(&OPTIONS_ATTR, range: 20..options.end));
 
-        while offset < data_offset {
-            let typ = layer.data().try_get(offset)?;
-            if typ <= 1 {
-                if typ == 1 {
-                    layer.add_attr(attr!(&OPTIONS_NOP_ATTR, range: offset..offset + 1));
-                }
-                offset += 1;
-                continue;
-            }
-            let len = layer.data().try_get(offset + 1)? as usize;
-            match typ {
-                2 => {
-                    layer.add_attr(attr!(&OPTIONS_MSS_ATTR, range: offset + 2..offset + len));
-                }
-                3 => {
-                    layer.add_attr(attr!(&OPTIONS_SCALE_ATTR, range: offset + 2..offset + len));
-                }
-                4 => {
-                    layer.add_attr(attr!(&OPTIONS_SACKP_ATTR, range: offset..offset + len));
-                }
-                5 => {
-                    layer.add_attr(attr!(&OPTIONS_SACK_ATTR, range: offset + 2..offset + len));
-                }
-                8 => {
-                    layer.add_attr(attr!(&OPTIONS_TS_ATTR, range: offset..offset + len));
-                    layer.add_attr(attr!(&OPTIONS_TS_MY_ATTR, range: offset + 2..offset + 6));
-                    layer.add_attr(attr!(&OPTIONS_TS_ECHO_ATTR, range: offset + 6..offset + 10));
-                }
-                _ => {}
-            }
-            offset += len;
-        }
-        layer.add_attr(attr!(&OPTIONS_ATTR, range: 20..offset));
+        let status = match checksum::verify_upper(parent.id(), &parent.data(), 6, &layer.data()) {
+            Some(valid) => self.validator.status(valid, parent.frame_metadata()),
+            None => checksum::Status::Unverified,
+        };
+        layer.add_attr(attr!(&CHECKSUM_STATUS_ATTR, range: 16..18, value: status.to_value()));
 
+        let src = SRC_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let dst = DST_ATTR_HEADER.try_get(&layer)?.try_into()?;
         let payload = layer.data().try_get(data_offset..)?;
-        layer.add_payload(Payload::new(payload, "@data:tcp"));
+        let table = ctx.dissector_table("tcp.port");
+        let typ = match table.get(dst).or_else(|| table.get(src)) {
+            Some(typ) => typ,
+            None => match self.heuristics.detect(&payload) {
+                Some((typ, confidence)) => {
+                    layer.add_attr(
+                        attr!(&HEURISTIC_ATTR, value: confidence.to_string().into_boxed_str()),
+                    );
+                    typ
+                }
+                None => Token::null(),
+            },
+        };
+
+        layer.add_payload(Payload::with_typ(payload, "@data:tcp", typ));
 
         parent.add_child(layer);
         Ok(Status::Done)
     }
 }
 
+fn decode_ts(layer: &mut Layer, opt: &Tlv) -> Result<()> {
+    layer.add_attr(attr!(&OPTIONS_TS_ATTR, range: opt.range.clone()));
+    if opt.value.len() >= 8 {
+        let offset = opt.value.start;
+        layer.add_attr(attr!(&OPTIONS_TS_MY_ATTR, range: offset..offset + 4));
+        layer.add_attr(attr!(&OPTIONS_TS_ECHO_ATTR, range: offset + 4..offset + 8));
+    }
+    Ok(())
+}
+
 #[derive(Clone)]
 struct TcpDecoder {}
 
 impl Decoder for TcpDecoder {
-    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
-        Box::new(TcpWorker {})
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        Box::new(TcpWorker {
+            heuristics: Heuristics::from_config(
+                ctx,
+                "@genet/tcp",
+                &[&heuristic::HTTP, &heuristic::HTTP2, &heuristic::TLS],
+            ),
+            validator: Validator::from_config(ctx),
+            options: OptionTable::new(option::TCP)
+                .option(1, &OPTIONS_NOP_ATTR)
+                .value(2, &OPTIONS_MSS_ATTR)
+                .value(3, &OPTIONS_SCALE_ATTR)
+                .option(4, &OPTIONS_SACKP_ATTR)
+                .value(5, &OPTIONS_SACK_ATTR)
+                .decoder(8, decode_ts),
+        })
     }
 
     fn metadata(&self) -> Metadata {
         Metadata {
             exec_type: ExecType::ParallelSync,
+            options: vec![Validator::option()],
             ..Metadata::default()
         }
     }
@@ -90,8 +115,8 @@ impl Decoder for TcpDecoder {
 def_layer_class!(
     TCP_CLASS,
     "tcp",
-    header: attr!(&SRC_ATTR, range: 0..2),
-    header: attr!(&DST_ATTR, range: 2..4),
+    header: &SRC_ATTR_HEADER,
+    header: &DST_ATTR_HEADER,
     header: attr!(&SEQ_ATTR, range: 4..8),
     header: attr!(&ACK_ATTR, range: 8..12),
     header: &OFFSET_ATTR_HEADER,
@@ -110,6 +135,10 @@ def_layer_class!(
     header: attr!(&URGENT_ATTR, range: 18..20)
 );
 
+def_attr!(SRC_ATTR_HEADER,  &SRC_ATTR, range: 0..2);
+
+def_attr!(DST_ATTR_HEADER,  &DST_ATTR, range: 2..4);
+
 def_attr!(OFFSET_ATTR_HEADER,  &OFFSET_ATTR, range: 12..13);
 
 def_attr_class!(SRC_ATTR, "tcp.src",
@@ -173,8 +202,12 @@ def_attr_class!(WINDOW_ATTR, "tcp.window", cast: cast::UInt16BE());
 
 def_attr_class!(CHECKSUM_ATTR, "tcp.checksum", cast: cast::UInt16BE());
 
+def_attr_class!(CHECKSUM_STATUS_ATTR, "tcp.checksum.status");
+
 def_attr_class!(URGENT_ATTR, "tcp.urgent", cast: cast::UInt16BE());
 
+def_attr_class!(HEURISTIC_ATTR, "tcp.heuristic");
+
 def_attr_class!(OPTIONS_ATTR, "tcp.options",
     typ: "@nested",
     value: true
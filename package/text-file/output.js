@@ -0,0 +1,84 @@
+const m = require('mithril')
+const genet = require('@genet/api')
+const { remote: { dialog } } = require('electron')
+class OutputView {
+  export (vnode, id, name, extension) {
+    const columns = vnode.dom.querySelector('[name=columns]').value
+    genet.workspace.set('_.textFile.columns', columns)
+    const file = dialog.showSaveDialog({
+      properties: ['openFile'],
+      filters: [{
+        name,
+        extensions: [extension],
+      }],
+    })
+    if (typeof file !== 'undefined') {
+      vnode.attrs.callback(id, {
+        file,
+        columns: columns.split(','),
+      })
+    }
+  }
+
+  view (vnode) {
+    const columns = genet.workspace.get('_.textFile.columns',
+      genet.config.get('@genet/text-file.columns'))
+    return m('ul', [
+      m('li', [
+        m('input', {
+          type: 'text',
+          name: 'columns',
+          placeholder: 'e.g. $.index, ipv4.src, tcp.dst',
+          value: columns,
+        })
+      ]),
+      m('li', [
+        m('input', {
+          type: 'button',
+          value: 'Export as CSV',
+          onclick: () => this.export(vnode,
+            'app.genet.writer.csv-file', 'CSV File', 'csv'),
+        })
+      ]),
+      m('li', [
+        m('input', {
+          type: 'button',
+          value: 'Export as JSON Lines',
+          onclick: () => this.export(vnode,
+            'app.genet.writer.jsonl-file', 'JSON Lines File', 'jsonl'),
+        })
+      ]),
+      m('li', [
+        m('input', {
+          type: 'text',
+          name: 'index',
+          placeholder: 'Index name',
+          value: genet.workspace.get('_.textFile.index', 'genet'),
+        }),
+        m('input', {
+          type: 'button',
+          value: 'Export as Elasticsearch Bulk',
+          onclick: () => {
+            const index = vnode.dom.querySelector('[name=index]').value || 'genet'
+            genet.workspace.set('_.textFile.index', index)
+            const file = dialog.showSaveDialog({
+              properties: ['openFile'],
+              filters: [{
+                name: 'Elasticsearch Bulk File',
+                extensions: ['ndjson'],
+              }],
+            })
+            if (typeof file !== 'undefined') {
+              vnode.attrs.callback('app.genet.writer.elasticsearch-file', {
+                file,
+                index,
+              })
+            }
+          },
+        })
+      ])
+    ])
+  }
+}
+
+module.exports = OutputView
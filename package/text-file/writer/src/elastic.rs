@@ -0,0 +1,228 @@
+//! Elasticsearch bulk-index ndjson with ECS field names.
+
+use attr_value;
+use genet_sdk::{prelude::*, writer::*};
+use serde_json::{self, map::Map, Value as Json};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+fn default_index() -> String {
+    "genet".into()
+}
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+    #[serde(default = "default_index")]
+    index: String,
+}
+
+#[derive(Clone)]
+pub struct ElasticWriter {}
+
+impl Writer for ElasticWriter {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let file = File::create(&arg.file)?;
+        let mut action = Map::new();
+        action.insert("_index".into(), Json::from(arg.index));
+        let mut line = Map::new();
+        line.insert("index".into(), Json::Object(action));
+        Ok(Box::new(ElasticWorker {
+            writer: BufWriter::new(file),
+            action: Json::Object(line).to_string(),
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.writer.elasticsearch-file".into(),
+            filters: vec![FileType::new("Elasticsearch Bulk File", &["ndjson"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+/// Formats a Unix time as an RFC 3339 string in UTC.
+fn rfc3339(sec: i64, nsec: u32) -> String {
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let sec = sec.max(0);
+    let days = sec / 86400;
+    let secs = sec % 86400;
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        nsec
+    )
+}
+
+/// Returns the key of a layer in the layer tree.
+fn layer_key(layer: &Layer) -> String {
+    let id = layer.id().to_string();
+    if id.starts_with("[link-") {
+        "link".into()
+    } else {
+        id
+    }
+}
+
+/// Inserts a value at a dotted path. A field which is both a value and a
+/// parent of other fields keeps its own value under `value`.
+fn insert(obj: &mut Map<String, Json>, path: &[&str], value: Json) {
+    let key = path[0].to_string();
+    if path.len() == 1 {
+        if let Some(Json::Object(child)) = obj.get_mut(&key) {
+            child.insert("value".into(), value);
+            return;
+        }
+        obj.insert(key, value);
+        return;
+    }
+    let mut child = match obj.remove(&key) {
+        Some(Json::Object(child)) => child,
+        Some(leaf) => {
+            let mut child = Map::new();
+            child.insert("value".into(), leaf);
+            child
+        }
+        None => Map::new(),
+    };
+    insert(&mut child, &path[1..], value);
+    obj.insert(key, Json::Object(child));
+}
+
+fn layer_tree(stack: &LayerStack) -> Json {
+    let mut nodes = stack
+        .layers()
+        .map(|layer| {
+            let key = layer_key(layer);
+            let prefix = format!("{}.", key);
+            let mut obj = Map::new();
+            for attr in layer.headers().iter().chain(layer.attrs().iter()) {
+                let id = attr.id().to_string();
+                let path = if id.starts_with(&prefix) {
+                    &id[prefix.len()..]
+                } else {
+                    &id[..]
+                };
+                let path = path.split('.').collect::<Vec<_>>();
+                insert(&mut obj, &path, attr_value(attr, layer));
+            }
+            (key, obj)
+        })
+        .collect::<Vec<_>>();
+
+    // Nest each layer in its parent.
+    let mut child: Option<(String, Map<String, Json>)> = None;
+    while let Some((key, mut obj)) = nodes.pop() {
+        if let Some((k, v)) = child.take() {
+            obj.insert(k, Json::Object(v));
+        }
+        child = Some((key, obj));
+    }
+    let mut tree = Map::new();
+    if let Some((k, v)) = child {
+        tree.insert(k, Json::Object(v));
+    }
+    Json::Object(tree)
+}
+
+fn find(stack: &LayerStack, id: &str) -> Json {
+    for layer in stack.layers().rev() {
+        if let Some(attr) = layer.attr(Token::from(id)) {
+            return attr_value(attr, layer);
+        }
+    }
+    Json::Null
+}
+
+fn endpoint(stack: &LayerStack, dir: &str, network: Option<&str>, transport: Option<&str>) -> Json {
+    let mut obj = Map::new();
+    let mut fields = vec![("mac", format!("eth.{}", dir))];
+    if let Some(network) = network {
+        fields.push(("ip", format!("{}.{}", network, dir)));
+    }
+    if let Some(transport) = transport {
+        fields.push(("port", format!("{}.{}", transport, dir)));
+    }
+    for (key, id) in &fields {
+        let value = find(stack, id);
+        if !value.is_null() {
+            obj.insert(key.to_string(), value);
+        }
+    }
+    Json::Object(obj)
+}
+
+struct ElasticWorker {
+    writer: BufWriter<File>,
+    action: String,
+}
+
+impl Worker for ElasticWorker {
+    fn write(&mut self, index: u32, stack: &LayerStack) -> Result<()> {
+        let has = |id: &str| stack.layer(Token::from(id)).is_some();
+        let network = ["ipv4", "ipv6"].iter().cloned().find(|id| has(id));
+        let transport = ["tcp", "udp"].iter().cloned().find(|id| has(id));
+
+        let mut doc = Map::new();
+        let sec = find(stack, "link.timestamp.sec").as_i64().unwrap_or(0);
+        let nsec = find(stack, "link.timestamp.nsec").as_u64().unwrap_or(0);
+        doc.insert("@timestamp".into(), Json::from(rfc3339(sec, nsec as u32)));
+
+        let mut event = Map::new();
+        event.insert("kind".into(), Json::from("event"));
+        event.insert("dataset".into(), Json::from("genet.frame"));
+        event.insert("sequence".into(), Json::from(index));
+        doc.insert("event".into(), Json::Object(event));
+
+        let mut net = Map::new();
+        if let Some(network) = network {
+            net.insert("type".into(), Json::from(network));
+        }
+        if let Some(transport) = transport {
+            net.insert("transport".into(), Json::from(transport));
+        }
+        if let Some(top) = stack.top() {
+            net.insert("protocol".into(), Json::from(layer_key(top)));
+        }
+        let bytes = find(stack, "link.length");
+        if !bytes.is_null() {
+            net.insert("bytes".into(), bytes);
+        }
+        doc.insert("network".into(), Json::Object(net));
+
+        doc.insert("source".into(), endpoint(stack, "src", network, transport));
+        doc.insert(
+            "destination".into(),
+            endpoint(stack, "dst", network, transport),
+        );
+        doc.insert("genet".into(), layer_tree(stack));
+
+        writeln!(self.writer, "{}", self.action)?;
+        serde_json::to_writer(&mut self.writer, &Json::Object(doc))?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+extern crate genet_sdk;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+mod elastic;
+
+use genet_sdk::{prelude::*, variant::Variant, writer::*};
+use serde_json::Value as Json;
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+const INDEX_COLUMN: &str = "$.index";
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+    columns: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Clone)]
+struct TextFileWriter {
+    format: Format,
+}
+
+impl Writer for TextFileWriter {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let file = File::create(&arg.file)?;
+        let mut writer = BufWriter::new(file);
+        let columns = arg
+            .columns
+            .iter()
+            .map(|c| c.trim())
+            .filter(|c| !c.is_empty())
+            .map(|c| Column {
+                name: c.to_string(),
+                id: Token::from(c),
+                prefix: format!("{}.", c),
+            })
+            .collect::<Vec<_>>();
+        if self.format == Format::Csv {
+            let header = columns
+                .iter()
+                .map(|c| csv_escape(&c.name))
+                .collect::<Vec<_>>();
+            writeln!(writer, "{}", header.join(","))?;
+        }
+        Ok(Box::new(TextFileWorker {
+            writer,
+            format: self.format,
+            columns,
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        match self.format {
+            Format::Csv => Metadata {
+                id: "app.genet.writer.csv-file".into(),
+                filters: vec![FileType::new("CSV File", &["csv"])],
+                ..Metadata::default()
+            },
+            Format::JsonLines => Metadata {
+                id: "app.genet.writer.jsonl-file".into(),
+                filters: vec![FileType::new("JSON Lines File", &["jsonl"])],
+                ..Metadata::default()
+            },
+        }
+    }
+}
+
+struct Column {
+    name: String,
+    id: Token,
+    prefix: String,
+}
+
+/// Converts an attribute value to JSON, formatting well-known types.
+fn to_json(typ: &str, value: Variant) -> Json {
+    match (typ, value) {
+        ("@ipv4:addr", Variant::Slice(b)) if b.len() == 4 => {
+            Json::from(Ipv4Addr::new(b[0], b[1], b[2], b[3]).to_string())
+        }
+        ("@ipv6:addr", Variant::Slice(b)) if b.len() == 16 => {
+            let mut addr = [0u8; 16];
+            addr.copy_from_slice(&b);
+            Json::from(Ipv6Addr::from(addr).to_string())
+        }
+        ("@eth:mac", Variant::Slice(b)) => Json::from(
+            b.iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(":"),
+        ),
+        (_, Variant::Nil) => Json::Null,
+        (_, Variant::Bool(v)) => Json::from(v),
+        (_, Variant::Int64(v)) => Json::from(v),
+        (_, Variant::UInt64(v)) => Json::from(v),
+        (_, Variant::Float64(v)) => Json::from(v),
+        (_, Variant::String(v)) => Json::from(v.to_string()),
+        (_, Variant::BigInt(v)) | (_, Variant::Buffer(v)) => Json::from(hex(&v)),
+        (_, Variant::Slice(v)) => Json::from(hex(&v)),
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn attr_value(attr: &Attr, layer: &Layer) -> Json {
+    match attr.try_get(layer) {
+        Ok(value) => to_json(&attr.typ().to_string(), value),
+        Err(_) => Json::Null,
+    }
+}
+
+struct TextFileWorker {
+    writer: BufWriter<File>,
+    format: Format,
+    columns: Vec<Column>,
+}
+
+impl TextFileWorker {
+    /// Returns the value of each column. A column without a matching attribute,
+    /// e.g. a layer name, is flattened into the attributes under its prefix.
+    fn values(&self, index: u32, stack: &LayerStack) -> Vec<Vec<(String, Json)>> {
+        self.columns
+            .iter()
+            .map(|column| {
+                if column.name == INDEX_COLUMN {
+                    return vec![(column.name.clone(), Json::from(index))];
+                }
+                for layer in stack.layers().rev() {
+                    if let Some(attr) = layer.attr(column.id) {
+                        return vec![(column.name.clone(), attr_value(attr, layer))];
+                    }
+                }
+                let mut values = Vec::new();
+                for layer in stack.layers() {
+                    for attr in layer.headers().iter().chain(layer.attrs().iter()) {
+                        let id = attr.id().to_string();
+                        if id.starts_with(&column.prefix) {
+                            values.push((id, attr_value(attr, layer)));
+                        }
+                    }
+                }
+                values
+            })
+            .collect()
+    }
+}
+
+impl Worker for TextFileWorker {
+    fn write(&mut self, index: u32, stack: &LayerStack) -> Result<()> {
+        let values = self.values(index, stack);
+        match self.format {
+            Format::JsonLines => {
+                let mut first = true;
+                self.writer.write_all(b"{")?;
+                for (id, value) in values.iter().flat_map(|v| v.iter()) {
+                    if !first {
+                        self.writer.write_all(b",")?;
+                    }
+                    first = false;
+                    serde_json::to_writer(&mut self.writer, id)?;
+                    self.writer.write_all(b":")?;
+                    serde_json::to_writer(&mut self.writer, value)?;
+                }
+                self.writer.write_all(b"}\n")?;
+            }
+            Format::Csv => {
+                let row = self
+                    .columns
+                    .iter()
+                    .zip(values)
+                    .map(|(column, values)| {
+                        let exact = values.len() == 1 && values[0].0 == column.name;
+                        let field = if values.is_empty() {
+                            String::new()
+                        } else if exact {
+                            match &values[0].1 {
+                                Json::String(s) => s.clone(),
+                                Json::Null => String::new(),
+                                value => value.to_string(),
+                            }
+                        } else {
+                            Json::Object(values.into_iter().collect()).to_string()
+                        };
+                        csv_escape(&field)
+                    })
+                    .collect::<Vec<_>>();
+                writeln!(self.writer, "{}", row.join(","))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+genet_writers!(
+    TextFileWriter {
+        format: Format::Csv
+    },
+    TextFileWriter {
+        format: Format::JsonLines
+    },
+    elastic::ElasticWriter {}
+);
@@ -0,0 +1,227 @@
+//! Record decryption of the AEAD cipher suites of TLS 1.2 and TLS 1.3.
+//!
+//! CBC and stream cipher suites are not supported.
+
+use genet_crypto::hkdf::expand_label;
+use ring::{aead, digest, hmac};
+
+/// An AEAD cipher suite.
+pub struct Suite {
+    pub id: u16,
+    aead: &'static aead::Algorithm,
+    hash: &'static digest::Algorithm,
+    /// The length of the implicit part of the nonce in TLS 1.2. The rest of
+    /// the nonce is sent with each record if it is shorter than 12 bytes.
+    fixed_iv_len: usize,
+}
+
+const NONCE_LEN: usize = 12;
+
+static SUITES: &[Suite] = &[
+    // TLS 1.3
+    Suite {
+        id: 0x1301,
+        aead: &aead::AES_128_GCM,
+        hash: &digest::SHA256,
+        fixed_iv_len: NONCE_LEN,
+    },
+    Suite {
+        id: 0x1302,
+        aead: &aead::AES_256_GCM,
+        hash: &digest::SHA384,
+        fixed_iv_len: NONCE_LEN,
+    },
+    Suite {
+        id: 0x1303,
+        aead: &aead::CHACHA20_POLY1305,
+        hash: &digest::SHA256,
+        fixed_iv_len: NONCE_LEN,
+    },
+    // TLS 1.2
+    Suite {
+        id: 0x009c,
+        aead: &aead::AES_128_GCM,
+        hash: &digest::SHA256,
+        fixed_iv_len: 4,
+    },
+    Suite {
+        id: 0x009d,
+        aead: &aead::AES_256_GCM,
+        hash: &digest::SHA384,
+        fixed_iv_len: 4,
+    },
+    Suite {
+        id: 0x009e,
+        aead: &aead::AES_128_GCM,
+        hash: &digest::SHA256,
+        fixed_iv_len: 4,
+    },
+    Suite {
+        id: 0x009f,
+        aead: &aead::AES_256_GCM,
+        hash: &digest::SHA384,
+        fixed_iv_len: 4,
+    },
+    Suite {
+        id: 0xc02b,
+        aead: &aead::AES_128_GCM,
+        hash: &digest::SHA256,
+        fixed_iv_len: 4,
+    },
+    Suite {
+        id: 0xc02c,
+        aead: &aead::AES_256_GCM,
+        hash: &digest::SHA384,
+        fixed_iv_len: 4,
+    },
+    Suite {
+        id: 0xc02f,
+        aead: &aead::AES_128_GCM,
+        hash: &digest::SHA256,
+        fixed_iv_len: 4,
+    },
+    Suite {
+        id: 0xc030,
+        aead: &aead::AES_256_GCM,
+        hash: &digest::SHA384,
+        fixed_iv_len: 4,
+    },
+    Suite {
+        id: 0xcca8,
+        aead: &aead::CHACHA20_POLY1305,
+        hash: &digest::SHA256,
+        fixed_iv_len: NONCE_LEN,
+    },
+    Suite {
+        id: 0xcca9,
+        aead: &aead::CHACHA20_POLY1305,
+        hash: &digest::SHA256,
+        fixed_iv_len: NONCE_LEN,
+    },
+    Suite {
+        id: 0xccaa,
+        aead: &aead::CHACHA20_POLY1305,
+        hash: &digest::SHA256,
+        fixed_iv_len: NONCE_LEN,
+    },
+];
+
+/// Returns the cipher suite `id`, or None if it is not supported.
+pub fn suite(id: u16) -> Option<&'static Suite> {
+    SUITES.iter().find(|suite| suite.id == id)
+}
+
+/// The TLS 1.2 PRF (RFC 5246, Section 5).
+fn prf(hash: &'static digest::Algorithm, secret: &[u8], label: &[u8], seed: &[u8], out: &mut [u8]) {
+    let key = hmac::SigningKey::new(hash, secret);
+    let mut label_seed = label.to_vec();
+    label_seed.extend_from_slice(seed);
+    let mut a = hmac::sign(&key, &label_seed);
+    for chunk in out.chunks_mut(hash.output_len) {
+        let mut ctx = hmac::SigningContext::with_key(&key);
+        ctx.update(a.as_ref());
+        ctx.update(&label_seed);
+        chunk.copy_from_slice(&ctx.sign().as_ref()[..chunk.len()]);
+        a = hmac::sign(&key, a.as_ref());
+    }
+}
+
+/// The read state of one direction of a connection.
+pub struct Decrypter {
+    key: aead::OpeningKey,
+    iv: Vec<u8>,
+    explicit_nonce: bool,
+    tls13: bool,
+    seq: u64,
+}
+
+impl Decrypter {
+    /// Creates a new Decrypter from a TLS 1.2 master secret.
+    pub fn tls12(
+        suite: &Suite,
+        master: &[u8],
+        client_random: &[u8],
+        server_random: &[u8],
+        client: bool,
+    ) -> Option<Decrypter> {
+        let key_len = suite.aead.key_len();
+        let iv_len = suite.fixed_iv_len;
+        let mut block = vec![0; (key_len + iv_len) * 2];
+        let mut seed = server_random.to_vec();
+        seed.extend_from_slice(client_random);
+        prf(suite.hash, master, b"key expansion", &seed, &mut block);
+        let (keys, ivs) = block.split_at(key_len * 2);
+        let index = if client { 0 } else { 1 };
+        Some(Decrypter {
+            key: aead::OpeningKey::new(suite.aead, &keys[index * key_len..][..key_len]).ok()?,
+            iv: ivs[index * iv_len..][..iv_len].to_vec(),
+            explicit_nonce: iv_len < NONCE_LEN,
+            tls13: false,
+            seq: 0,
+        })
+    }
+
+    /// Creates a new Decrypter from a TLS 1.3 traffic secret.
+    pub fn tls13(suite: &Suite, secret: &[u8]) -> Option<Decrypter> {
+        let mut key = vec![0; suite.aead.key_len()];
+        let mut iv = vec![0; NONCE_LEN];
+        let prk = hmac::SigningKey::new(suite.hash, secret);
+        expand_label(&prk, "key", &mut key);
+        expand_label(&prk, "iv", &mut iv);
+        Some(Decrypter {
+            key: aead::OpeningKey::new(suite.aead, &key).ok()?,
+            iv,
+            explicit_nonce: false,
+            tls13: true,
+            seq: 0,
+        })
+    }
+
+    /// Decrypts a record and returns the content type and the plaintext, or
+    /// None if the record cannot be authenticated.
+    pub fn open(&mut self, header: &[u8; 5], fragment: &[u8]) -> Option<(u8, Vec<u8>)> {
+        let seq = self.seq.to_be_bytes();
+        self.seq += 1;
+        let mut nonce = [0; NONCE_LEN];
+        let mut fragment = fragment;
+        if self.explicit_nonce {
+            if fragment.len() < NONCE_LEN - self.iv.len() {
+                return None;
+            }
+            let (explicit, rest) = fragment.split_at(NONCE_LEN - self.iv.len());
+            nonce[..self.iv.len()].copy_from_slice(&self.iv);
+            nonce[self.iv.len()..].copy_from_slice(explicit);
+            fragment = rest;
+        } else {
+            nonce.copy_from_slice(&self.iv);
+            for (n, s) in nonce[NONCE_LEN - 8..].iter_mut().zip(&seq) {
+                *n ^= s;
+            }
+        }
+
+        let ad = if self.tls13 {
+            header.to_vec()
+        } else {
+            let len = fragment.len().checked_sub(self.key.algorithm().tag_len())?;
+            let mut ad = seq.to_vec();
+            ad.extend_from_slice(&header[..3]);
+            ad.extend_from_slice(&[(len >> 8) as u8, len as u8]);
+            ad
+        };
+
+        let mut buf = fragment.to_vec();
+        let len = aead::open_in_place(&self.key, &nonce, &ad, 0, &mut buf)
+            .ok()?
+            .len();
+        buf.truncate(len);
+        if self.tls13 {
+            // The content type follows the content and is followed by padding.
+            let len = buf.iter().rposition(|b| *b != 0)?;
+            let typ = buf[len];
+            buf.truncate(len);
+            Some((typ, buf))
+        } else {
+            Some((header[0], buf))
+        }
+    }
+}
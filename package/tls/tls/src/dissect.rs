@@ -0,0 +1,397 @@
+//! Structural dissection of records and handshake messages.
+
+use genet_sdk::{cast, prelude::*};
+use hello::{
+    EXT_ALPN, EXT_SERVER_NAME, EXT_SUPPORTED_VERSIONS, HANDSHAKE_CLIENT_HELLO,
+    HANDSHAKE_SERVER_HELLO,
+};
+use record::{self, Record};
+use std::ops::Range;
+
+/// Reads fields within a range of the layer data, and returns their ranges.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], range: Range<usize>) -> Reader<'a> {
+        Reader {
+            data: &data[..range.end],
+            pos: range.start,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Option<Range<usize>> {
+        let end = self.pos.checked_add(len)?;
+        if end > self.data.len() {
+            return None;
+        }
+        let range = self.pos..end;
+        self.pos = end;
+        Some(range)
+    }
+
+    fn uint(&mut self, len: usize) -> Option<(usize, Range<usize>)> {
+        let range = self.take(len)?;
+        let value = self.data[range.clone()]
+            .iter()
+            .fold(0, |value, b| value << 8 | usize::from(*b));
+        Some((value, range))
+    }
+
+    fn vec(&mut self, len: usize) -> Option<Range<usize>> {
+        let (len, _) = self.uint(len)?;
+        self.take(len)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+/// Returns the attributes of `records`, whose headers and fragments are
+/// concatenated in `data`.
+pub fn records(data: &[u8], records: &[Record]) -> Vec<Attr> {
+    let mut attrs = Vec::new();
+    let mut offset = 0;
+    for record in records {
+        let fragment = offset + 5..offset + 5 + record.fragment.len();
+        attrs.push(attr!(&RECORD_ATTR, range: offset..fragment.end));
+        attrs.push(attr!(&RECORD_TYPE_ATTR, range: offset..offset + 1));
+        if let Some(class) = get_content_type(record.typ()) {
+            attrs.push(attr!(class, range: offset..offset + 1));
+        }
+        attrs.push(attr!(&RECORD_VERSION_ATTR, range: offset + 1..offset + 3));
+        attrs.push(attr!(&RECORD_LENGTH_ATTR, range: offset + 3..offset + 5));
+        if record.encrypted {
+            attrs.push(attr!(&ENCRYPTED_ATTR, range: fragment.clone()));
+        } else if record.typ() == record::CONTENT_HANDSHAKE {
+            if let Some(start) = record.message_offset {
+                handshake(data, fragment.start + start..fragment.end, &mut attrs);
+            }
+        } else if record.typ() == record::CONTENT_ALERT && fragment.len() == 2 {
+            let start = fragment.start;
+            attrs.push(attr!(&ALERT_ATTR, range: fragment.clone()));
+            attrs.push(attr!(&ALERT_LEVEL_ATTR, range: start..start + 1));
+            attrs.push(attr!(&ALERT_DESCRIPTION_ATTR, range: start + 1..start + 2));
+        }
+        offset = fragment.end;
+    }
+    attrs
+}
+
+/// Adds the attributes of the handshake messages starting in `range`. The
+/// last message may continue in the next record.
+fn handshake(data: &[u8], range: Range<usize>, attrs: &mut Vec<Attr>) {
+    let end = range.end;
+    let mut r = Reader::new(data, range);
+    while !r.is_empty() {
+        let start = r.pos;
+        let (typ, typ_range) = match r.uint(1) {
+            Some(typ) => typ,
+            None => break,
+        };
+        let (len, len_range) = match r.uint(3) {
+            Some(len) => len,
+            None => break,
+        };
+        let body = r.pos..end.min(r.pos + len);
+        r.pos = body.end;
+        attrs.push(attr!(&HANDSHAKE_ATTR, range: start..body.end));
+        attrs.push(attr!(&HANDSHAKE_TYPE_ATTR, range: typ_range.clone()));
+        if let Some(class) = get_handshake_type(typ as u8) {
+            attrs.push(attr!(class, range: typ_range));
+        }
+        attrs.push(attr!(&HANDSHAKE_LENGTH_ATTR, range: len_range, value: len as u64));
+        if body.len() < len {
+            break;
+        }
+        match typ as u8 {
+            HANDSHAKE_CLIENT_HELLO => {
+                client_hello(data, body, attrs);
+            }
+            HANDSHAKE_SERVER_HELLO => {
+                server_hello(data, body, attrs);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn client_hello(data: &[u8], body: Range<usize>, attrs: &mut Vec<Attr>) -> Option<()> {
+    let mut r = Reader::new(data, body);
+    attrs.push(attr!(&VERSION_ATTR, range: r.take(2)?));
+    attrs.push(attr!(&RANDOM_ATTR, range: r.take(32)?));
+    attrs.push(attr!(&SESSION_ID_ATTR, range: r.vec(1)?));
+    let ciphers = r.vec(2)?;
+    attrs.push(attr!(&CIPHER_SUITES_ATTR, range: ciphers.clone()));
+    let mut list = Reader::new(data, ciphers);
+    while let Some(range) = list.take(2) {
+        attrs.push(attr!(&CIPHER_SUITE_ATTR, range: range));
+    }
+    attrs.push(attr!(&COMPRESSION_METHODS_ATTR, range: r.vec(1)?));
+    if !r.is_empty() {
+        extensions(data, r.vec(2)?, attrs)?;
+    }
+    Some(())
+}
+
+fn server_hello(data: &[u8], body: Range<usize>, attrs: &mut Vec<Attr>) -> Option<()> {
+    let mut r = Reader::new(data, body);
+    attrs.push(attr!(&VERSION_ATTR, range: r.take(2)?));
+    attrs.push(attr!(&RANDOM_ATTR, range: r.take(32)?));
+    attrs.push(attr!(&SESSION_ID_ATTR, range: r.vec(1)?));
+    attrs.push(attr!(&CIPHER_SUITE_ATTR, range: r.take(2)?));
+    attrs.push(attr!(&COMPRESSION_METHOD_ATTR, range: r.take(1)?));
+    if !r.is_empty() {
+        extensions(data, r.vec(2)?, attrs)?;
+    }
+    Some(())
+}
+
+fn extensions(data: &[u8], range: Range<usize>, attrs: &mut Vec<Attr>) -> Option<()> {
+    attrs.push(attr!(&EXTENSIONS_ATTR, range: range.clone()));
+    let mut r = Reader::new(data, range);
+    while !r.is_empty() {
+        let start = r.pos;
+        let (typ, typ_range) = r.uint(2)?;
+        let (len, len_range) = r.uint(2)?;
+        let body = r.take(len)?;
+        attrs.push(attr!(&EXTENSION_ATTR, range: start..body.end));
+        attrs.push(attr!(&EXTENSION_TYPE_ATTR, range: typ_range.clone()));
+        if let Some(class) = get_extension_type(typ as u16) {
+            attrs.push(attr!(class, range: typ_range));
+        }
+        attrs.push(attr!(&EXTENSION_LENGTH_ATTR, range: len_range));
+        let mut ext = Reader::new(data, body.clone());
+        match typ as u16 {
+            EXT_SERVER_NAME if !body.is_empty() => {
+                let mut list = Reader::new(data, ext.vec(2)?);
+                while !list.is_empty() {
+                    let (name_type, _) = list.uint(1)?;
+                    let name = list.vec(2)?;
+                    if name_type == 0 {
+                        attrs.push(attr!(&SERVER_NAME_ATTR, range: name));
+                    }
+                }
+            }
+            EXT_ALPN => {
+                let mut list = Reader::new(data, ext.vec(2)?);
+                while !list.is_empty() {
+                    attrs.push(attr!(&ALPN_ATTR, range: list.vec(1)?));
+                }
+            }
+            EXT_SUPPORTED_VERSIONS => {
+                // A ServerHello has the selected version instead of a list.
+                let mut list = if body.len() == 2 {
+                    ext
+                } else {
+                    Reader::new(data, ext.vec(1)?)
+                };
+                while let Some(range) = list.take(2) {
+                    attrs.push(attr!(&SUPPORTED_VERSION_ATTR, range: range));
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(())
+}
+
+def_attr_class!(RECORD_ATTR, "tls.record",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(RECORD_TYPE_ATTR, "tls.record.contentType",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(RECORD_VERSION_ATTR, "tls.record.version", cast: cast::UInt16BE());
+
+def_attr_class!(RECORD_LENGTH_ATTR, "tls.record.length", cast: cast::UInt16BE());
+
+def_attr_class!(ENCRYPTED_ATTR, "tls.record.encrypted", cast: cast::ByteSlice());
+
+def_attr_class!(ALERT_ATTR, "tls.alert",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(ALERT_LEVEL_ATTR, "tls.alert.level", cast: cast::UInt8());
+
+def_attr_class!(ALERT_DESCRIPTION_ATTR, "tls.alert.description", cast: cast::UInt8());
+
+def_attr_class!(HANDSHAKE_ATTR, "tls.handshake",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(HANDSHAKE_TYPE_ATTR, "tls.handshake.type",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(HANDSHAKE_LENGTH_ATTR, "tls.handshake.length");
+
+def_attr_class!(VERSION_ATTR, "tls.handshake.version", cast: cast::UInt16BE());
+
+def_attr_class!(RANDOM_ATTR, "tls.handshake.random", cast: cast::ByteSlice());
+
+def_attr_class!(SESSION_ID_ATTR, "tls.handshake.sessionId", cast: cast::ByteSlice());
+
+def_attr_class!(CIPHER_SUITES_ATTR, "tls.handshake.cipherSuites",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(CIPHER_SUITE_ATTR, "tls.handshake.cipherSuite", cast: cast::UInt16BE());
+
+def_attr_class!(COMPRESSION_METHODS_ATTR, "tls.handshake.compressionMethods",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(COMPRESSION_METHOD_ATTR, "tls.handshake.compressionMethod",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(EXTENSIONS_ATTR, "tls.handshake.extensions",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(EXTENSION_ATTR, "tls.handshake.extension",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(EXTENSION_TYPE_ATTR, "tls.handshake.extension.type",
+    typ: "@enum",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(EXTENSION_LENGTH_ATTR, "tls.handshake.extension.length",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(SERVER_NAME_ATTR, "tls.handshake.extension.serverName",
+    cast: cast::Utf8()
+);
+
+def_attr_class!(ALPN_ATTR, "tls.handshake.extension.alpn", cast: cast::Utf8());
+
+def_attr_class!(SUPPORTED_VERSION_ATTR, "tls.handshake.extension.supportedVersion",
+    cast: cast::UInt16BE()
+);
+
+fn get_content_type(typ: u8) -> Option<&'static AttrClass> {
+    match typ {
+        20 => Some(
+            attr_class_lazy!("tls.record.contentType.changeCipherSpec", typ: "@novalue", value: true),
+        ),
+        21 => Some(attr_class_lazy!("tls.record.contentType.alert", typ: "@novalue", value: true)),
+        22 => {
+            Some(attr_class_lazy!("tls.record.contentType.handshake", typ: "@novalue", value: true))
+        }
+        23 => Some(
+            attr_class_lazy!("tls.record.contentType.applicationData", typ: "@novalue", value: true),
+        ),
+        _ => None,
+    }
+}
+
+fn get_handshake_type(typ: u8) -> Option<&'static AttrClass> {
+    match typ {
+        0 => {
+            Some(attr_class_lazy!("tls.handshake.type.helloRequest", typ: "@novalue", value: true))
+        }
+        1 => Some(attr_class_lazy!("tls.handshake.type.clientHello", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("tls.handshake.type.serverHello", typ: "@novalue", value: true)),
+        4 => Some(
+            attr_class_lazy!("tls.handshake.type.newSessionTicket", typ: "@novalue", value: true),
+        ),
+        5 => Some(
+            attr_class_lazy!("tls.handshake.type.endOfEarlyData", typ: "@novalue", value: true),
+        ),
+        8 => Some(
+            attr_class_lazy!("tls.handshake.type.encryptedExtensions", typ: "@novalue", value: true),
+        ),
+        11 => {
+            Some(attr_class_lazy!("tls.handshake.type.certificate", typ: "@novalue", value: true))
+        }
+        12 => Some(
+            attr_class_lazy!("tls.handshake.type.serverKeyExchange", typ: "@novalue", value: true),
+        ),
+        13 => Some(
+            attr_class_lazy!("tls.handshake.type.certificateRequest", typ: "@novalue", value: true),
+        ),
+        14 => Some(
+            attr_class_lazy!("tls.handshake.type.serverHelloDone", typ: "@novalue", value: true),
+        ),
+        15 => Some(
+            attr_class_lazy!("tls.handshake.type.certificateVerify", typ: "@novalue", value: true),
+        ),
+        16 => Some(
+            attr_class_lazy!("tls.handshake.type.clientKeyExchange", typ: "@novalue", value: true),
+        ),
+        20 => Some(attr_class_lazy!("tls.handshake.type.finished", typ: "@novalue", value: true)),
+        24 => Some(attr_class_lazy!("tls.handshake.type.keyUpdate", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+fn get_extension_type(typ: u16) -> Option<&'static AttrClass> {
+    match typ {
+        0x0000 => Some(
+            attr_class_lazy!("tls.handshake.extension.type.serverName", typ: "@novalue", value: true),
+        ),
+        0x0005 => Some(
+            attr_class_lazy!("tls.handshake.extension.type.statusRequest", typ: "@novalue", value: true),
+        ),
+        0x000a => Some(
+            attr_class_lazy!("tls.handshake.extension.type.supportedGroups", typ: "@novalue", value: true),
+        ),
+        0x000b => Some(
+            attr_class_lazy!("tls.handshake.extension.type.ecPointFormats", typ: "@novalue", value: true),
+        ),
+        0x000d => Some(
+            attr_class_lazy!("tls.handshake.extension.type.signatureAlgorithms", typ: "@novalue", value: true),
+        ),
+        0x0010 => Some(
+            attr_class_lazy!("tls.handshake.extension.type.alpn", typ: "@novalue", value: true),
+        ),
+        0x0012 => Some(
+            attr_class_lazy!("tls.handshake.extension.type.signedCertificateTimestamp", typ: "@novalue", value: true),
+        ),
+        0x0015 => Some(
+            attr_class_lazy!("tls.handshake.extension.type.padding", typ: "@novalue", value: true),
+        ),
+        0x0017 => Some(
+            attr_class_lazy!("tls.handshake.extension.type.extendedMasterSecret", typ: "@novalue", value: true),
+        ),
+        0x0023 => Some(
+            attr_class_lazy!("tls.handshake.extension.type.sessionTicket", typ: "@novalue", value: true),
+        ),
+        0x0029 => Some(
+            attr_class_lazy!("tls.handshake.extension.type.preSharedKey", typ: "@novalue", value: true),
+        ),
+        0x002a => Some(
+            attr_class_lazy!("tls.handshake.extension.type.earlyData", typ: "@novalue", value: true),
+        ),
+        0x002b => Some(
+            attr_class_lazy!("tls.handshake.extension.type.supportedVersions", typ: "@novalue", value: true),
+        ),
+        0x002d => Some(
+            attr_class_lazy!("tls.handshake.extension.type.pskKeyExchangeModes", typ: "@novalue", value: true),
+        ),
+        0x0033 => Some(
+            attr_class_lazy!("tls.handshake.extension.type.keyShare", typ: "@novalue", value: true),
+        ),
+        0xff01 => Some(
+            attr_class_lazy!("tls.handshake.extension.type.renegotiationInfo", typ: "@novalue", value: true),
+        ),
+        _ => None,
+    }
+}
@@ -0,0 +1,142 @@
+//! JA3, JA3S and JA4 fingerprints.
+
+use hello::{is_grease, ClientHello, ServerHello, EXT_ALPN, EXT_SERVER_NAME};
+use md5;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn strip_grease(values: &[u16]) -> Vec<u16> {
+    values.iter().cloned().filter(|v| !is_grease(*v)).collect()
+}
+
+/// Returns the JA3 string of a ClientHello.
+pub fn ja3(hello: &ClientHello) -> String {
+    format!(
+        "{},{},{},{},{}",
+        hello.version,
+        join(&strip_grease(&hello.ciphers)),
+        join(&strip_grease(&hello.extensions)),
+        join(&strip_grease(&hello.groups)),
+        join(&hello.point_formats)
+    )
+}
+
+/// Returns the JA3S string of a ServerHello.
+pub fn ja3s(hello: &ServerHello) -> String {
+    format!(
+        "{},{},{}",
+        hello.version,
+        hello.cipher,
+        join(&strip_grease(&hello.extensions))
+    )
+}
+
+/// Returns the MD5 digest of a JA3 or JA3S string.
+pub fn md5_hex(value: &str) -> String {
+    format!("{:x}", md5::compute(value.as_bytes()))
+}
+
+fn version_code(version: u16) -> &'static str {
+    match version {
+        0x0304 => "13",
+        0x0303 => "12",
+        0x0302 => "11",
+        0x0301 => "10",
+        0x0300 => "s3",
+        0x0200 => "s2",
+        0xfeff => "d1",
+        0xfefd => "d2",
+        0xfefc => "d3",
+        _ => "00",
+    }
+}
+
+fn alpn_code(alpn: &Option<Vec<u8>>) -> String {
+    let alpn = match alpn {
+        Some(alpn) if !alpn.is_empty() => alpn,
+        _ => return "00".into(),
+    };
+    let first = alpn[0];
+    let last = alpn[alpn.len() - 1];
+    if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+        format!("{}{}", first as char, last as char)
+    } else {
+        let first = format!("{:02x}", first);
+        let last = format!("{:02x}", last);
+        format!("{}{}", &first[..1], &last[1..])
+    }
+}
+
+fn hex_list(values: &[u16]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{:04x}", v))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn truncated_hash(value: &str) -> String {
+    if value.is_empty() {
+        return "000000000000".into();
+    }
+    let mut hex = String::new();
+    for b in Sha256::digest(value.as_bytes()).iter().take(6) {
+        let _ = write!(hex, "{:02x}", b);
+    }
+    hex
+}
+
+/// Returns the JA4 fingerprint of a ClientHello sent over TCP.
+pub fn ja4(hello: &ClientHello) -> String {
+    let ciphers = strip_grease(&hello.ciphers);
+    let extensions = strip_grease(&hello.extensions);
+    let version = strip_grease(&hello.versions)
+        .into_iter()
+        .max()
+        .unwrap_or(hello.version);
+    let sni = if hello.extensions.contains(&EXT_SERVER_NAME) {
+        'd'
+    } else {
+        'i'
+    };
+
+    let mut sorted_ciphers = ciphers.clone();
+    sorted_ciphers.sort();
+    let mut sorted_extensions = extensions
+        .iter()
+        .cloned()
+        .filter(|e| *e != EXT_SERVER_NAME && *e != EXT_ALPN)
+        .collect::<Vec<_>>();
+    sorted_extensions.sort();
+    let mut ext_string = hex_list(&sorted_extensions);
+    if !hello.signature_algorithms.is_empty() {
+        ext_string = format!(
+            "{}_{}",
+            ext_string,
+            hex_list(&strip_grease(&hello.signature_algorithms))
+        );
+    }
+
+    format!(
+        "t{}{}{:02}{:02}{}_{}_{}",
+        version_code(version),
+        sni,
+        ciphers.len().min(99),
+        extensions.len().min(99),
+        alpn_code(&hello.alpn),
+        truncated_hash(&hex_list(&sorted_ciphers)),
+        truncated_hash(if sorted_extensions.is_empty() {
+            ""
+        } else {
+            &ext_string
+        })
+    )
+}
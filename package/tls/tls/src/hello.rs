@@ -0,0 +1,206 @@
+//! ClientHello and ServerHello parsers.
+
+pub const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+pub const HANDSHAKE_SERVER_HELLO: u8 = 2;
+pub const HANDSHAKE_ENCRYPTED_EXTENSIONS: u8 = 8;
+pub const HANDSHAKE_FINISHED: u8 = 20;
+
+pub const VERSION_TLS13: u16 = 0x0304;
+
+/// The random of a ServerHello which is a HelloRetryRequest.
+const HELLO_RETRY_REQUEST: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+
+pub const EXT_SERVER_NAME: u16 = 0x0000;
+pub const EXT_SUPPORTED_GROUPS: u16 = 0x000a;
+pub const EXT_EC_POINT_FORMATS: u16 = 0x000b;
+pub const EXT_SIGNATURE_ALGORITHMS: u16 = 0x000d;
+pub const EXT_ALPN: u16 = 0x0010;
+pub const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// Returns true if the value is a GREASE value (RFC 8701).
+pub fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+struct Cursor<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn new(buf: &'a [u8]) -> Cursor<'a> {
+        Cursor { buf }
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < len {
+            return None;
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|b| u16::from(b[0]) << 8 | u16::from(b[1]))
+    }
+
+    fn vec8(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()?;
+        self.bytes(len as usize)
+    }
+
+    fn vec16(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()?;
+        self.bytes(len as usize)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+fn u16_list(buf: &[u8]) -> Vec<u16> {
+    buf.chunks(2)
+        .filter(|c| c.len() == 2)
+        .map(|c| u16::from(c[0]) << 8 | u16::from(c[1]))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct ClientHello {
+    pub version: u16,
+    pub random: Vec<u8>,
+    pub ciphers: Vec<u16>,
+    pub extensions: Vec<u16>,
+    pub groups: Vec<u16>,
+    pub point_formats: Vec<u8>,
+    pub signature_algorithms: Vec<u16>,
+    pub versions: Vec<u16>,
+    pub alpn: Option<Vec<u8>>,
+    pub server_name: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ServerHello {
+    pub version: u16,
+    pub random: Vec<u8>,
+    pub cipher: u16,
+    pub extensions: Vec<u16>,
+    /// The version selected by the supported_versions extension.
+    pub selected_version: Option<u16>,
+    pub alpn: Option<Vec<u8>>,
+}
+
+impl ServerHello {
+    pub fn is_retry_request(&self) -> bool {
+        self.random[..] == HELLO_RETRY_REQUEST[..]
+    }
+
+    pub fn is_tls13(&self) -> bool {
+        self.selected_version == Some(VERSION_TLS13)
+    }
+}
+
+/// Returns the first protocol of an ALPN extension.
+fn alpn(data: &mut Cursor) -> Option<Vec<u8>> {
+    let mut list = Cursor::new(data.vec16()?);
+    list.vec8().map(|p| p.to_vec())
+}
+
+/// Parses a ClientHello message body.
+pub fn client_hello(typ: u8, body: &[u8]) -> Option<ClientHello> {
+    if typ != HANDSHAKE_CLIENT_HELLO {
+        return None;
+    }
+    let mut c = Cursor::new(body);
+    let mut hello = ClientHello {
+        version: c.u16()?,
+        ..ClientHello::default()
+    };
+    hello.random = c.bytes(32)?.to_vec();
+    c.vec8()?;
+    hello.ciphers = u16_list(c.vec16()?);
+    c.vec8()?;
+    if c.is_empty() {
+        return Some(hello);
+    }
+    let mut exts = Cursor::new(c.vec16()?);
+    while !exts.is_empty() {
+        let typ = exts.u16()?;
+        let mut data = Cursor::new(exts.vec16()?);
+        hello.extensions.push(typ);
+        match typ {
+            EXT_SERVER_NAME => {
+                let mut list = Cursor::new(data.vec16()?);
+                while !list.is_empty() {
+                    let name_type = list.u8()?;
+                    let name = list.vec16()?;
+                    if name_type == 0 {
+                        hello.server_name = Some(String::from_utf8_lossy(name).into_owned());
+                        break;
+                    }
+                }
+            }
+            EXT_SUPPORTED_GROUPS => hello.groups = u16_list(data.vec16()?),
+            EXT_EC_POINT_FORMATS => hello.point_formats = data.vec8()?.to_vec(),
+            EXT_SIGNATURE_ALGORITHMS => hello.signature_algorithms = u16_list(data.vec16()?),
+            EXT_SUPPORTED_VERSIONS => hello.versions = u16_list(data.vec8()?),
+            EXT_ALPN => hello.alpn = alpn(&mut data),
+            _ => {}
+        }
+    }
+    Some(hello)
+}
+
+/// Parses a ServerHello message body.
+pub fn server_hello(typ: u8, body: &[u8]) -> Option<ServerHello> {
+    if typ != HANDSHAKE_SERVER_HELLO {
+        return None;
+    }
+    let mut c = Cursor::new(body);
+    let mut hello = ServerHello {
+        version: c.u16()?,
+        ..ServerHello::default()
+    };
+    hello.random = c.bytes(32)?.to_vec();
+    c.vec8()?;
+    hello.cipher = c.u16()?;
+    c.u8()?;
+    if c.is_empty() {
+        return Some(hello);
+    }
+    let mut exts = Cursor::new(c.vec16()?);
+    while !exts.is_empty() {
+        let typ = exts.u16()?;
+        let mut data = Cursor::new(exts.vec16()?);
+        hello.extensions.push(typ);
+        match typ {
+            EXT_SUPPORTED_VERSIONS => hello.selected_version = data.u16(),
+            EXT_ALPN => hello.alpn = alpn(&mut data),
+            _ => {}
+        }
+    }
+    Some(hello)
+}
+
+/// Returns the protocol selected by the ALPN extension of a TLS 1.3
+/// EncryptedExtensions message body.
+pub fn encrypted_extensions_alpn(body: &[u8]) -> Option<Vec<u8>> {
+    let mut exts = Cursor::new(Cursor::new(body).vec16()?);
+    while !exts.is_empty() {
+        let typ = exts.u16()?;
+        let mut data = Cursor::new(exts.vec16()?);
+        if typ == EXT_ALPN {
+            return alpn(&mut data);
+        }
+    }
+    None
+}
@@ -0,0 +1,386 @@
+extern crate genet_crypto;
+extern crate genet_sdk;
+extern crate md5;
+extern crate ring;
+extern crate sha2;
+
+mod decrypt;
+mod dissect;
+mod fingerprint;
+mod hello;
+mod record;
+
+use decrypt::Decrypter;
+use genet_crypto::keylog::KeyLogFile;
+use genet_sdk::{decoder::*, prelude::*};
+use record::{Messages, Record, Records};
+use std::collections::HashMap;
+
+/// Config key for the path of a key log file written via SSLKEYLOGFILE.
+const KEY_LOG_FILE_KEY: &str = "@genet/tls.keyLogFile";
+
+/// Handshake messages larger than this are not parsed.
+const MAX_HANDSHAKE_SIZE: usize = 1 << 16;
+
+type Endpoint = (ByteSlice, u32);
+
+/// The state of one direction of a flow.
+#[derive(Default)]
+struct Direction {
+    records: Records,
+    messages: Messages,
+    decrypter: Option<Decrypter>,
+    /// The records except ChangeCipherSpec are encrypted.
+    encrypted: bool,
+    /// The Finished message has been sent, so TLS 1.3 records are encrypted
+    /// with the application traffic secret.
+    finished: bool,
+    /// The records are split for the dissection, but not decrypted.
+    opaque: bool,
+    done: bool,
+}
+
+impl Direction {
+    fn finish(&mut self) {
+        self.done = true;
+        self.records = Records::default();
+        self.messages = Messages::default();
+        self.decrypter = None;
+    }
+
+    fn stop(&mut self) {
+        self.opaque = true;
+        self.decrypter = None;
+    }
+}
+
+/// Fingerprints and keys collected from the handshake of a flow.
+#[derive(Default)]
+struct Flow {
+    directions: [Direction; 2],
+    /// The direction of the ClientHello.
+    client: Option<usize>,
+    client_random: Vec<u8>,
+    server_random: Vec<u8>,
+    cipher: Option<u16>,
+    tls13: bool,
+    alpn: Option<Vec<u8>>,
+    server_name: Option<String>,
+    ja3: Option<String>,
+    ja3s: Option<String>,
+    ja4: Option<String>,
+}
+
+impl Flow {
+    /// Processes stream data and returns the complete records and the
+    /// decrypted application data.
+    fn push(
+        &mut self,
+        dir: usize,
+        data: &[u8],
+        keylog: &mut Option<KeyLogFile>,
+    ) -> (Vec<Record>, Vec<u8>) {
+        let mut app_data = Vec::new();
+        if self.directions[dir].done {
+            return (Vec::new(), app_data);
+        }
+        let mut records = match self.directions[dir].records.push(data) {
+            Some(records) => records,
+            None => {
+                self.directions[dir].finish();
+                return (Vec::new(), app_data);
+            }
+        };
+        for record in &mut records {
+            if self.directions[dir].done {
+                break;
+            }
+            if record.typ() == record::CONTENT_CHANGE_CIPHER_SPEC {
+                // TLS 1.3 sends it only for middlebox compatibility.
+                if !self.tls13 {
+                    self.directions[dir].encrypted = true;
+                }
+                continue;
+            }
+            record.encrypted = self.directions[dir].encrypted;
+            if !record.encrypted {
+                if record.typ() == record::CONTENT_HANDSHAKE {
+                    let remaining = self.directions[dir].messages.remaining();
+                    record.message_offset = remaining.map(|len| len.min(record.fragment.len()));
+                    self.handshake(dir, &record.fragment, keylog);
+                }
+                continue;
+            }
+            if self.directions[dir].opaque {
+                continue;
+            }
+            match self.decrypt(dir, record, keylog) {
+                Some((record::CONTENT_HANDSHAKE, plain)) => self.handshake(dir, &plain, keylog),
+                Some((record::CONTENT_APPLICATION_DATA, plain)) => {
+                    app_data.extend_from_slice(&plain)
+                }
+                Some(_) => {}
+                None => self.directions[dir].stop(),
+            }
+        }
+        (records, app_data)
+    }
+
+    fn handshake(&mut self, dir: usize, data: &[u8], keylog: &Option<KeyLogFile>) {
+        let messages = self.directions[dir].messages.push(data);
+        if self.directions[dir].messages.pending() > MAX_HANDSHAKE_SIZE {
+            self.directions[dir].finish();
+        }
+        if self.directions[dir].opaque {
+            return;
+        }
+        for (typ, body) in messages {
+            if let Some(hello) = hello::client_hello(typ, &body) {
+                if self.ja3.is_none() {
+                    self.server_name = hello.server_name.clone();
+                    self.ja3 = Some(fingerprint::ja3(&hello));
+                    self.ja4 = Some(fingerprint::ja4(&hello));
+                }
+                self.client = Some(dir);
+                self.client_random = hello.random;
+            } else if let Some(hello) = hello::server_hello(typ, &body) {
+                if self.ja3s.is_none() {
+                    self.ja3s = Some(fingerprint::ja3s(&hello));
+                }
+                if !hello.is_retry_request() {
+                    self.server_random = hello.random.clone();
+                    self.cipher = Some(hello.cipher);
+                    self.tls13 = hello.is_tls13();
+                    self.alpn = hello.alpn;
+                    if self.tls13 {
+                        for dir in &mut self.directions {
+                            dir.encrypted = true;
+                        }
+                    }
+                }
+            } else if typ == hello::HANDSHAKE_ENCRYPTED_EXTENSIONS {
+                self.alpn = hello::encrypted_extensions_alpn(&body);
+            } else if typ == hello::HANDSHAKE_FINISHED && self.tls13 {
+                self.directions[dir].finished = true;
+                self.directions[dir].decrypter = None;
+            }
+        }
+
+        // Without keys, only the first messages are used for fingerprints.
+        if keylog.is_none() && self.ja3.is_some() && self.ja3s.is_some() {
+            for dir in &mut self.directions {
+                dir.stop();
+            }
+        }
+    }
+
+    fn decrypt(
+        &mut self,
+        dir: usize,
+        record: &Record,
+        keylog: &mut Option<KeyLogFile>,
+    ) -> Option<(u8, Vec<u8>)> {
+        if self.directions[dir].decrypter.is_none() {
+            self.directions[dir].decrypter = self.decrypter(dir, keylog.as_mut()?);
+        }
+        self.directions[dir]
+            .decrypter
+            .as_mut()?
+            .open(&record.header, &record.fragment)
+    }
+
+    fn decrypter(&self, dir: usize, keylog: &mut KeyLogFile) -> Option<Decrypter> {
+        let client = self.client? == dir;
+        let suite = decrypt::suite(self.cipher?)?;
+        let secrets = keylog.get(&self.client_random)?;
+        if self.tls13 {
+            let secret = match (client, self.directions[dir].finished) {
+                (true, false) => &secrets.client_handshake,
+                (false, false) => &secrets.server_handshake,
+                (true, true) => &secrets.client_traffic,
+                (false, true) => &secrets.server_traffic,
+            };
+            Decrypter::tls13(suite, secret.as_ref()?)
+        } else {
+            Decrypter::tls12(
+                suite,
+                secrets.master.as_ref()?,
+                &self.client_random,
+                &self.server_random,
+                client,
+            )
+        }
+    }
+
+    /// Returns the payload type of the application data.
+    fn app_data_type(&self) -> Token {
+        match self.alpn.as_deref() {
+            Some(b"h2") => token!("@data:http2"),
+            Some(b"http/1.1") => token!("@data:http"),
+            _ => Token::null(),
+        }
+    }
+
+    fn add_attrs(&self, layer: &mut Layer) {
+        if let Some(name) = &self.server_name {
+            layer.add_attr(attr!(&SNI_ATTR, value: name.clone().into_boxed_str()));
+        }
+        if let Some(ja3) = &self.ja3 {
+            let hash = fingerprint::md5_hex(ja3);
+            layer.add_attr(attr!(&JA3_ATTR, value: hash.into_boxed_str()));
+            layer.add_attr(attr!(&JA3_FULL_ATTR, value: ja3.clone().into_boxed_str()));
+        }
+        if let Some(ja3s) = &self.ja3s {
+            let hash = fingerprint::md5_hex(ja3s);
+            layer.add_attr(attr!(&JA3S_ATTR, value: hash.into_boxed_str()));
+            layer.add_attr(attr!(&JA3S_FULL_ATTR, value: ja3s.clone().into_boxed_str()));
+        }
+        if let Some(ja4) = &self.ja4 {
+            layer.add_attr(attr!(&JA4_ATTR, value: ja4.clone().into_boxed_str()));
+        }
+    }
+}
+
+struct TlsWorker {
+    flows: HashMap<(Endpoint, Endpoint), Flow>,
+    keylog: Option<KeyLogFile>,
+}
+
+impl Worker for TlsWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("tcp") {
+            return Ok(Status::Skip);
+        }
+
+        // Wait for the stream reassembler.
+        if parent.attr(token!("tcp.stream.payloads")).is_none() {
+            return Ok(Status::Skip);
+        }
+
+        let data = match parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:tcp") && p.typ() == token!("@data:tls"))
+        {
+            Some(payload) => payload.data(),
+            None => return Ok(Status::Skip),
+        };
+        if data.is_empty() {
+            return Ok(Status::Skip);
+        }
+
+        let src: ByteSlice = stack
+            .attr(token!("_.src"))
+            .unwrap()
+            .try_get(parent)?
+            .try_into()?;
+        let dst: ByteSlice = stack
+            .attr(token!("_.dst"))
+            .unwrap()
+            .try_get(parent)?
+            .try_into()?;
+        let sport: u32 = parent
+            .attr(token!("tcp.src"))
+            .unwrap()
+            .try_get(parent)?
+            .try_into()?;
+        let dport: u32 = parent
+            .attr(token!("tcp.dst"))
+            .unwrap()
+            .try_get(parent)?
+            .try_into()?;
+
+        // Both directions share a flow keyed by the ordered endpoints.
+        let a = (src, sport);
+        let b = (dst, dport);
+        let (key, dir) = if (&a.0[..], a.1) <= (&b.0[..], b.1) {
+            ((a, b), 0)
+        } else {
+            ((b, a), 1)
+        };
+        let flow = self.flows.entry(key).or_default();
+        let mut records = Vec::new();
+        let mut app_data = Vec::new();
+        for payload in parent
+            .payloads()
+            .iter()
+            .filter(|p| p.id() == token!("@stream:tcp"))
+        {
+            let (complete, data) = flow.push(dir, &payload.data(), &mut self.keylog);
+            records.extend(complete);
+            app_data.extend(data);
+        }
+
+        // The layer consists of the records completed by the segment.
+        let mut layer = if records.is_empty() {
+            Layer::new(&TLS_CLASS, data)
+        } else {
+            let mut data = Vec::new();
+            for record in &records {
+                data.extend_from_slice(&record.header);
+                data.extend_from_slice(&record.fragment);
+            }
+            let attrs = dissect::records(&data, &records);
+            let mut layer = Layer::new(&TLS_CLASS, ByteSlice::from(data));
+            for attr in attrs {
+                layer.add_attr(attr);
+            }
+            layer
+        };
+        flow.add_attrs(&mut layer);
+        if !app_data.is_empty() {
+            let typ = flow.app_data_type();
+            layer.add_payload(Payload::with_typ(app_data, "@data:tls", typ));
+        }
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct TlsDecoder {}
+
+impl Decoder for TlsDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        ctx.dissector_table("tcp.port")
+            .add_default(443, "@data:tls");
+        let path = ctx.get_config(KEY_LOG_FILE_KEY).trim_matches('"');
+        let keylog = if path.is_empty() {
+            None
+        } else {
+            Some(KeyLogFile::new(path))
+        };
+        Box::new(TlsWorker {
+            flows: HashMap::new(),
+            keylog,
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::SerialSync,
+            options: vec![DecoderOption::path(KEY_LOG_FILE_KEY).description(
+                "Path of a key log file written via SSLKEYLOGFILE to decrypt TLS 1.2 and 1.3 records",
+            )],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(TLS_CLASS, "tls",
+    alias: "_.fingerprint" "tls.ja4"
+);
+
+def_attr_class!(SNI_ATTR, "tls.sni");
+def_attr_class!(JA3_ATTR, "tls.ja3");
+def_attr_class!(JA3_FULL_ATTR, "tls.ja3.full");
+def_attr_class!(JA3S_ATTR, "tls.ja3s");
+def_attr_class!(JA3S_FULL_ATTR, "tls.ja3s.full");
+def_attr_class!(JA4_ATTR, "tls.ja4");
+
+genet_decoders!(TlsDecoder {});
@@ -0,0 +1,112 @@
+//! Record and handshake message reassembly.
+
+pub const CONTENT_CHANGE_CIPHER_SPEC: u8 = 20;
+pub const CONTENT_ALERT: u8 = 21;
+pub const CONTENT_HANDSHAKE: u8 = 22;
+pub const CONTENT_APPLICATION_DATA: u8 = 23;
+
+/// The maximum length of a record fragment, including the expansion by
+/// encryption.
+const MAX_FRAGMENT_LEN: usize = (1 << 14) + 2048;
+
+pub struct Record {
+    pub header: [u8; 5],
+    pub fragment: Vec<u8>,
+    /// The fragment is encrypted.
+    pub encrypted: bool,
+    /// The offset in the fragment of the first handshake message starting in
+    /// it, or None if it is unknown.
+    pub message_offset: Option<usize>,
+}
+
+impl Record {
+    pub fn typ(&self) -> u8 {
+        self.header[0]
+    }
+}
+
+/// Splits a stream into records.
+#[derive(Default)]
+pub struct Records {
+    buffer: Vec<u8>,
+}
+
+impl Records {
+    /// Appends stream data and returns the complete records, or None if the
+    /// stream is not TLS.
+    pub fn push(&mut self, data: &[u8]) -> Option<Vec<Record>> {
+        self.buffer.extend_from_slice(data);
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while self.buffer.len() >= offset + 5 {
+            let mut header = [0; 5];
+            header.copy_from_slice(&self.buffer[offset..offset + 5]);
+            let len = (header[3] as usize) << 8 | header[4] as usize;
+            if header[0] < CONTENT_CHANGE_CIPHER_SPEC
+                || header[0] > CONTENT_APPLICATION_DATA
+                || header[1] != 3
+                || len > MAX_FRAGMENT_LEN
+            {
+                return None;
+            }
+            if self.buffer.len() < offset + 5 + len {
+                break;
+            }
+            let fragment = self.buffer[offset + 5..offset + 5 + len].to_vec();
+            records.push(Record {
+                header,
+                fragment,
+                encrypted: false,
+                message_offset: None,
+            });
+            offset += 5 + len;
+        }
+        self.buffer.drain(..offset);
+        Some(records)
+    }
+}
+
+/// Reassembles handshake messages spanning several records.
+#[derive(Default)]
+pub struct Messages {
+    buffer: Vec<u8>,
+}
+
+impl Messages {
+    /// Appends a handshake fragment and returns the complete messages.
+    pub fn push(&mut self, data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        self.buffer.extend_from_slice(data);
+        let mut messages = Vec::new();
+        let mut offset = 0;
+        while self.buffer.len() >= offset + 4 {
+            let msg = &self.buffer[offset..];
+            let len = (msg[1] as usize) << 16 | (msg[2] as usize) << 8 | msg[3] as usize;
+            if msg.len() < 4 + len {
+                break;
+            }
+            messages.push((msg[0], msg[4..4 + len].to_vec()));
+            offset += 4 + len;
+        }
+        self.buffer.drain(..offset);
+        messages
+    }
+
+    /// Returns the length of the incomplete message.
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the length of the rest of the incomplete message, or None if
+    /// its header is incomplete.
+    pub fn remaining(&self) -> Option<usize> {
+        let msg = &self.buffer;
+        match msg.len() {
+            0 => Some(0),
+            1..=3 => None,
+            pending => {
+                let len = (msg[1] as usize) << 16 | (msg[2] as usize) << 8 | msg[3] as usize;
+                Some(4 + len - pending)
+            }
+        }
+    }
+}
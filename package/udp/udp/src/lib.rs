@@ -1,13 +1,39 @@
 extern crate genet_sdk;
 
-use genet_sdk::{cast, decoder::*, prelude::*};
+use genet_sdk::{
+    cast,
+    checksum::{self, Validator},
+    decoder::*,
+    heuristic::{self, Heuristics},
+    prelude::*,
+};
 
-struct UdpWorker {}
+struct UdpWorker {
+    heuristics: Heuristics,
+    validator: Validator,
+}
+
+impl UdpWorker {
+    fn checksum_status(&self, layer: &Layer, parent: &Parent) -> Result<checksum::Status> {
+        let stored: u16 = CHECKSUM_ATTR_HEADER.try_get(layer)?.try_into()?;
+        let len: usize = LEN_ATTR_HEADER.try_get(layer)?.try_into()?;
+        let data = layer.data();
+        // A zero checksum over IPv4 means that it is not computed.
+        if (stored == 0 && parent.id() == token!("ipv4")) || len < 8 || len > data.len() {
+            return Ok(checksum::Status::Unverified);
+        }
+        let valid = checksum::verify_upper(parent.id(), &parent.data(), 17, &data[..len]);
+        Ok(match valid {
+            Some(valid) => self.validator.status(valid, parent.frame_metadata()),
+            None => checksum::Status::Unverified,
+        })
+    }
+}
 
 impl Worker for UdpWorker {
     fn decode(
         &mut self,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         _stack: &LayerStack,
         parent: &mut Parent,
     ) -> Result<Status> {
@@ -24,8 +50,25 @@ impl Worker for UdpWorker {
         }
 
         let mut layer = Layer::new(&UDP_CLASS, data);
+        let src = SRC_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let dst = DST_ATTR_HEADER.try_get(&layer)?.try_into()?;
         let payload = data.try_get(8..)?;
-        layer.add_payload(Payload::new(payload, ""));
+        let status = self.checksum_status(&layer, parent)?;
+        layer.add_attr(attr!(&CHECKSUM_STATUS_ATTR, range: 6..8, value: status.to_value()));
+        let table = ctx.dissector_table("udp.port");
+        let id = match table.get(dst).or_else(|| table.get(src)) {
+            Some(id) => id,
+            None => match self.heuristics.detect(&payload) {
+                Some((id, confidence)) => {
+                    layer.add_attr(
+                        attr!(&HEURISTIC_ATTR, value: confidence.to_string().into_boxed_str()),
+                    );
+                    id
+                }
+                None => Token::null(),
+            },
+        };
+        layer.add_payload(Payload::new(payload, id));
 
         parent.add_child(layer);
         Ok(Status::Done)
@@ -36,13 +79,17 @@ impl Worker for UdpWorker {
 struct UdpDecoder {}
 
 impl Decoder for UdpDecoder {
-    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
-        Box::new(UdpWorker {})
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        Box::new(UdpWorker {
+            heuristics: Heuristics::from_config(ctx, "@genet/udp", &[&heuristic::DNS]),
+            validator: Validator::from_config(ctx),
+        })
     }
 
     fn metadata(&self) -> Metadata {
         Metadata {
             exec_type: ExecType::ParallelSync,
+            options: vec![Validator::option()],
             ..Metadata::default()
         }
     }
@@ -51,12 +98,20 @@ impl Decoder for UdpDecoder {
 def_layer_class!(UDP_CLASS, "udp",
     alias: "_.src" "udp.src",
     alias: "_.dst" "udp.dst",
-    header: attr!(&SRC_ATTR, range: 0..2),
-    header: attr!(&DST_ATTR, range: 2..4),
-    header: attr!(&LEN_ATTR, range: 4..6),
-    header: attr!(&CHECKSUM_ATTR, range: 6..8)
+    header: &SRC_ATTR_HEADER,
+    header: &DST_ATTR_HEADER,
+    header: &LEN_ATTR_HEADER,
+    header: &CHECKSUM_ATTR_HEADER
 );
 
+def_attr!(SRC_ATTR_HEADER, &SRC_ATTR, range: 0..2);
+
+def_attr!(DST_ATTR_HEADER, &DST_ATTR, range: 2..4);
+
+def_attr!(LEN_ATTR_HEADER, &LEN_ATTR, range: 4..6);
+
+def_attr!(CHECKSUM_ATTR_HEADER, &CHECKSUM_ATTR, range: 6..8);
+
 def_attr_class!(SRC_ATTR, "udp.src",
     typ: "@udp:port",
     cast: cast::UInt16BE()
@@ -71,4 +126,8 @@ def_attr_class!(LEN_ATTR, "udp.length", cast: cast::UInt16BE());
 
 def_attr_class!(CHECKSUM_ATTR, "udp.checksum", cast: cast::UInt16BE());
 
+def_attr_class!(CHECKSUM_STATUS_ATTR, "udp.checksum.status");
+
+def_attr_class!(HEURISTIC_ATTR, "udp.heuristic");
+
 genet_decoders!(UdpDecoder {});
@@ -0,0 +1,401 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+/// The length of the USBPCAP_BUFFER_PACKET_HEADER.
+const HEADER_LEN: usize = 27;
+
+/// The length of a setup packet.
+const SETUP_LEN: usize = 8;
+
+const TRANSFER_CONTROL: u8 = 2;
+
+const STAGE_SETUP: u8 = 0;
+
+/// The standard request to get a descriptor.
+const REQUEST_GET_DESCRIPTOR: u8 = 0x06;
+
+struct UsbWorker {}
+
+impl Worker for UsbWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("[link-249]") {
+            return Ok(Status::Skip);
+        }
+
+        let data = parent.data();
+        if data.len() < HEADER_LEN {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&USB_CLASS, data);
+        let header_len: u64 = HEADER_LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let header_len = header_len as usize;
+        let transfer: u8 = data.try_get(22)?;
+        if let Some(attr) = get_transfer(transfer) {
+            layer.add_attr(attr!(attr, range: 22..23));
+        }
+        if header_len < HEADER_LEN || header_len > data.len() {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let mut offset = header_len;
+        if transfer == TRANSFER_CONTROL && header_len > HEADER_LEN {
+            let stage: u8 = data.try_get(HEADER_LEN)?;
+            layer.add_attr(attr!(&STAGE_ATTR, range: HEADER_LEN..HEADER_LEN + 1));
+            if let Some(attr) = get_stage(stage) {
+                layer.add_attr(attr!(attr, range: HEADER_LEN..HEADER_LEN + 1));
+            }
+            if stage == STAGE_SETUP {
+                if let Some(setup) = data.get(offset..offset + SETUP_LEN) {
+                    add_setup(&mut layer, offset, setup);
+                    offset += SETUP_LEN;
+                }
+            }
+        }
+
+        let len: u64 = DATA_LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let end = header_len + len as usize;
+        if end > data.len() {
+            layer.add_attr(attr!(&MALFORMED_ATTR, value: true));
+        } else if offset < end {
+            layer.add_attr(attr!(&DATA_ATTR, range: offset..end));
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+fn add_setup(layer: &mut Layer, offset: usize, setup: &[u8]) {
+    layer.add_attr(attr!(&SETUP_ATTR, range: offset..offset + SETUP_LEN));
+    layer.add_attr(attr!(&SETUP_REQUEST_TYPE_ATTR, range: offset..offset + 1));
+    layer.add_attr(attr!(&SETUP_REQUEST_TYPE_DIRECTION_ATTR, range: offset..offset + 1));
+    layer.add_attr(attr!(&SETUP_REQUEST_TYPE_TYPE_ATTR, range: offset..offset + 1));
+    if let Some(attr) = get_request_type((setup[0] >> 5) & 0b11) {
+        layer.add_attr(attr!(attr, range: offset..offset + 1));
+    }
+    layer.add_attr(attr!(&SETUP_REQUEST_TYPE_RECIPIENT_ATTR, range: offset..offset + 1));
+    if let Some(attr) = get_recipient(setup[0] & 0x1f) {
+        layer.add_attr(attr!(attr, range: offset..offset + 1));
+    }
+
+    // The requests other than the standard ones are defined by the classes
+    // and the vendors.
+    let standard = (setup[0] >> 5) & 0b11 == 0;
+    layer.add_attr(attr!(&SETUP_REQUEST_ATTR, range: offset + 1..offset + 2));
+    if let Some(attr) = get_request(setup[1]).filter(|_| standard) {
+        layer.add_attr(attr!(attr, range: offset + 1..offset + 2));
+    }
+    layer.add_attr(attr!(&SETUP_VALUE_ATTR, range: offset + 2..offset + 4));
+    if standard && setup[1] == REQUEST_GET_DESCRIPTOR {
+        layer.add_attr(attr!(&SETUP_DESCRIPTOR_INDEX_ATTR, range: offset + 2..offset + 3));
+        layer.add_attr(attr!(&SETUP_DESCRIPTOR_TYPE_ATTR, range: offset + 3..offset + 4));
+        if let Some(attr) = get_descriptor_type(setup[3]) {
+            layer.add_attr(attr!(attr, range: offset + 3..offset + 4));
+        }
+    }
+    layer.add_attr(attr!(&SETUP_INDEX_ATTR, range: offset + 4..offset + 6));
+    layer.add_attr(attr!(&SETUP_LENGTH_ATTR, range: offset + 6..offset + 8));
+}
+
+#[derive(Clone)]
+struct UsbDecoder {}
+
+impl Decoder for UsbDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(UsbWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(USB_CLASS, "usb",
+    header: &HEADER_LENGTH_ATTR_HEADER,
+    header: attr!(&IRP_ID_ATTR, range: 2..10),
+    header: attr!(&STATUS_ATTR, range: 10..14),
+    header: attr!(&FUNCTION_ATTR, range: 14..16),
+    header: attr!(&INFO_ATTR, range: 16..17),
+    header: attr!(&INFO_FROM_DEVICE_ATTR, range: 16..17),
+    header: attr!(&BUS_ATTR, range: 17..19),
+    header: attr!(&DEVICE_ATTR, range: 19..21),
+    header: attr!(&ENDPOINT_ATTR, range: 21..22),
+    header: attr!(&ENDPOINT_IN_ATTR, range: 21..22),
+    header: attr!(&TRANSFER_ATTR, range: 22..23),
+    header: &DATA_LENGTH_ATTR_HEADER
+);
+
+def_attr!(HEADER_LENGTH_ATTR_HEADER, &HEADER_LENGTH_ATTR, range: 0..2);
+
+def_attr!(DATA_LENGTH_ATTR_HEADER, &DATA_LENGTH_ATTR, range: 23..27);
+
+def_attr_class!(HEADER_LENGTH_ATTR, "usb.headerLength", cast: cast::UInt16LE());
+
+def_attr_class!(IRP_ID_ATTR, "usb.irpId", cast: cast::UInt64LE());
+
+def_attr_class!(STATUS_ATTR, "usb.status", cast: cast::UInt32LE());
+
+def_attr_class!(FUNCTION_ATTR, "usb.function", cast: cast::UInt16LE());
+
+def_attr_class!(INFO_ATTR, "usb.info",
+    cast: cast::UInt8(),
+    typ: "@flags"
+);
+
+def_attr_class!(INFO_FROM_DEVICE_ATTR, "usb.info.fromDevice",
+    cast: cast::UInt8().map(|v| v & 0b0000_0001 != 0)
+);
+
+def_attr_class!(BUS_ATTR, "usb.bus", cast: cast::UInt16LE());
+
+def_attr_class!(DEVICE_ATTR, "usb.device", cast: cast::UInt16LE());
+
+def_attr_class!(ENDPOINT_ATTR, "usb.endpoint",
+    cast: cast::UInt8().map(|v| v & 0x0f)
+);
+
+def_attr_class!(ENDPOINT_IN_ATTR, "usb.endpoint.in",
+    cast: cast::UInt8().map(|v| v & 0b1000_0000 != 0)
+);
+
+def_attr_class!(TRANSFER_ATTR, "usb.transfer",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_transfer(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("usb.transfer.isochronous", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!("usb.transfer.interrupt", typ: "@novalue", value: true)),
+        TRANSFER_CONTROL => {
+            Some(attr_class_lazy!("usb.transfer.control", typ: "@novalue", value: true))
+        }
+        3 => Some(attr_class_lazy!("usb.transfer.bulk", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+def_attr_class!(DATA_LENGTH_ATTR, "usb.dataLength", cast: cast::UInt32LE());
+
+def_attr_class!(STAGE_ATTR, "usb.stage",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_stage(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        STAGE_SETUP => Some(attr_class_lazy!("usb.stage.setup", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!("usb.stage.data", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("usb.stage.status", typ: "@novalue", value: true)),
+        3 => Some(attr_class_lazy!("usb.stage.complete", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+def_attr_class!(SETUP_ATTR, "usb.setup",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(SETUP_REQUEST_TYPE_ATTR, "usb.setup.requestType",
+    cast: cast::UInt8(),
+    typ: "@flags"
+);
+
+def_attr_class!(SETUP_REQUEST_TYPE_DIRECTION_ATTR, "usb.setup.requestType.deviceToHost",
+    cast: cast::UInt8().map(|v| v & 0b1000_0000 != 0)
+);
+
+def_attr_class!(SETUP_REQUEST_TYPE_TYPE_ATTR, "usb.setup.requestType.type",
+    cast: cast::UInt8().map(|v| (v >> 5) & 0b11),
+    typ: "@enum"
+);
+
+fn get_request_type(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!(
+            "usb.setup.requestType.type.standard",
+            typ: "@novalue",
+            value: true
+        )),
+        1 => Some(attr_class_lazy!(
+            "usb.setup.requestType.type.class",
+            typ: "@novalue",
+            value: true
+        )),
+        2 => Some(attr_class_lazy!(
+            "usb.setup.requestType.type.vendor",
+            typ: "@novalue",
+            value: true
+        )),
+        _ => None,
+    }
+}
+
+def_attr_class!(SETUP_REQUEST_TYPE_RECIPIENT_ATTR, "usb.setup.requestType.recipient",
+    cast: cast::UInt8().map(|v| v & 0x1f),
+    typ: "@enum"
+);
+
+fn get_recipient(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!(
+            "usb.setup.requestType.recipient.device",
+            typ: "@novalue",
+            value: true
+        )),
+        1 => Some(attr_class_lazy!(
+            "usb.setup.requestType.recipient.interface",
+            typ: "@novalue",
+            value: true
+        )),
+        2 => Some(attr_class_lazy!(
+            "usb.setup.requestType.recipient.endpoint",
+            typ: "@novalue",
+            value: true
+        )),
+        3 => Some(attr_class_lazy!(
+            "usb.setup.requestType.recipient.other",
+            typ: "@novalue",
+            value: true
+        )),
+        _ => None,
+    }
+}
+
+def_attr_class!(SETUP_REQUEST_ATTR, "usb.setup.request",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_request(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0x00 => Some(attr_class_lazy!("usb.setup.request.getStatus", typ: "@novalue", value: true)),
+        0x01 => Some(attr_class_lazy!(
+            "usb.setup.request.clearFeature",
+            typ: "@novalue",
+            value: true
+        )),
+        0x03 => {
+            Some(attr_class_lazy!("usb.setup.request.setFeature", typ: "@novalue", value: true))
+        }
+        0x05 => {
+            Some(attr_class_lazy!("usb.setup.request.setAddress", typ: "@novalue", value: true))
+        }
+        REQUEST_GET_DESCRIPTOR => Some(attr_class_lazy!(
+            "usb.setup.request.getDescriptor",
+            typ: "@novalue",
+            value: true
+        )),
+        0x07 => Some(attr_class_lazy!(
+            "usb.setup.request.setDescriptor",
+            typ: "@novalue",
+            value: true
+        )),
+        0x08 => Some(attr_class_lazy!(
+            "usb.setup.request.getConfiguration",
+            typ: "@novalue",
+            value: true
+        )),
+        0x09 => Some(attr_class_lazy!(
+            "usb.setup.request.setConfiguration",
+            typ: "@novalue",
+            value: true
+        )),
+        0x0a => Some(attr_class_lazy!(
+            "usb.setup.request.getInterface",
+            typ: "@novalue",
+            value: true
+        )),
+        0x0b => Some(attr_class_lazy!(
+            "usb.setup.request.setInterface",
+            typ: "@novalue",
+            value: true
+        )),
+        0x0c => {
+            Some(attr_class_lazy!("usb.setup.request.synchFrame", typ: "@novalue", value: true))
+        }
+        _ => None,
+    }
+}
+
+def_attr_class!(SETUP_VALUE_ATTR, "usb.setup.value", cast: cast::UInt16LE());
+
+def_attr_class!(SETUP_DESCRIPTOR_INDEX_ATTR, "usb.setup.descriptorIndex", cast: cast::UInt8());
+
+def_attr_class!(SETUP_DESCRIPTOR_TYPE_ATTR, "usb.setup.descriptorType",
+    cast: cast::UInt8(),
+    typ: "@enum"
+);
+
+fn get_descriptor_type(val: u8) -> Option<&'static AttrClass> {
+    match val {
+        0x01 => Some(attr_class_lazy!(
+            "usb.setup.descriptorType.device",
+            typ: "@novalue",
+            value: true
+        )),
+        0x02 => Some(attr_class_lazy!(
+            "usb.setup.descriptorType.configuration",
+            typ: "@novalue",
+            value: true
+        )),
+        0x03 => Some(attr_class_lazy!(
+            "usb.setup.descriptorType.string",
+            typ: "@novalue",
+            value: true
+        )),
+        0x04 => Some(attr_class_lazy!(
+            "usb.setup.descriptorType.interface",
+            typ: "@novalue",
+            value: true
+        )),
+        0x05 => Some(attr_class_lazy!(
+            "usb.setup.descriptorType.endpoint",
+            typ: "@novalue",
+            value: true
+        )),
+        0x06 => Some(attr_class_lazy!(
+            "usb.setup.descriptorType.deviceQualifier",
+            typ: "@novalue",
+            value: true
+        )),
+        0x0f => {
+            Some(attr_class_lazy!("usb.setup.descriptorType.bos", typ: "@novalue", value: true))
+        }
+        0x21 => {
+            Some(attr_class_lazy!("usb.setup.descriptorType.hid", typ: "@novalue", value: true))
+        }
+        0x22 => Some(attr_class_lazy!(
+            "usb.setup.descriptorType.hidReport",
+            typ: "@novalue",
+            value: true
+        )),
+        _ => None,
+    }
+}
+
+def_attr_class!(SETUP_INDEX_ATTR, "usb.setup.index", cast: cast::UInt16LE());
+
+def_attr_class!(SETUP_LENGTH_ATTR, "usb.setup.length", cast: cast::UInt16LE());
+
+def_attr_class!(DATA_ATTR, "usb.data", cast: cast::ByteSlice());
+
+def_attr_class!(MALFORMED_ATTR, "usb.malformed",
+    typ: "@expert:error",
+    description: "Header or data length exceeding the packet"
+);
+
+genet_decoders!(UsbDecoder {});
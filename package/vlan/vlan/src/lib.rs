@@ -0,0 +1,87 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+struct VlanWorker {}
+
+impl Worker for VlanWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:vlan"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        // Each tag of a stacked (QinQ) frame is a layer, and the inner tag is
+        // dispatched through the EtherType of the outer one.
+        let mut layer = Layer::new(&VLAN_CLASS, data);
+        let len = LEN_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if len <= 1500 {
+            layer.add_attr(&LEN_ATTR_HEADER);
+        } else {
+            layer.add_attr(&TYPE_ATTR_HEADER);
+            if let Some(typ) = ctx.dissector_table("eth.type").get(len) {
+                let payload = data.try_get(4..)?;
+                layer.add_payload(Payload::new(payload, typ));
+            }
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct VlanDecoder {}
+
+impl Decoder for VlanDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(VlanWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(VLAN_CLASS, "vlan",
+    header: attr!(&PRIORITY_ATTR, bit_range: 0 0..3),
+    header: attr!(&DEI_ATTR, bit_range: 0 3..4),
+    header: attr!(&ID_ATTR, bit_range: 0 4..16)
+);
+
+def_attr!(LEN_ATTR_HEADER, &LEN_ATTR, range: 2..4);
+
+def_attr!(TYPE_ATTR_HEADER, &TYPE_ATTR, range: 2..4);
+
+def_attr_class!(PRIORITY_ATTR, "vlan.priority",
+    cast: cast::UInt8().map(|v| v >> 5)
+);
+
+def_attr_class!(DEI_ATTR, "vlan.dei",
+    cast: cast::UInt8().map(|v| (v & 0b0001_0000) != 0)
+);
+
+def_attr_class!(ID_ATTR, "vlan.id",
+    cast: cast::UInt16BE().map(|v| v & 0xfff)
+);
+
+def_attr_class!(LEN_ATTR, "vlan.len", cast: cast::UInt16BE());
+
+def_attr_class!(TYPE_ATTR, "vlan.type", cast: cast::UInt16BE());
+
+genet_decoders!(VlanDecoder {});
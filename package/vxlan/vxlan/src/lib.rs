@@ -0,0 +1,81 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+/// The length of the header.
+const HEADER_LEN: usize = 8;
+
+/// The VNI is valid.
+const FLAG_VNI: u8 = 0b0000_1000;
+
+struct VxlanWorker {}
+
+impl Worker for VxlanWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:vxlan"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&VXLAN_CLASS, data);
+        let flags: u8 = data.try_get(0)?;
+        if flags & FLAG_VNI != 0 {
+            let vni = data.try_get(4..7)?;
+            let vni = vni.iter().fold(0u32, |vni, b| vni << 8 | u32::from(*b));
+            layer.add_attr(attr!(&VNI_ATTR, range: 4..7, value: vni));
+        }
+        let payload = data.try_get(HEADER_LEN..)?;
+        layer.add_payload(Payload::new(payload, "@data:eth"));
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct VxlanDecoder {}
+
+impl Decoder for VxlanDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        ctx.dissector_table("udp.port")
+            .add_default(4789, "@data:vxlan");
+        Box::new(VxlanWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            exec_type: ExecType::ParallelSync,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(VXLAN_CLASS, "vxlan",
+    header: attr!(&FLAGS_ATTR, range: 0..1),
+    header: attr!(&FLAGS_VNI_ATTR, range: 0..1)
+);
+
+def_attr_class!(FLAGS_ATTR, "vxlan.flags",
+    typ: "@flags",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(FLAGS_VNI_ATTR, "vxlan.flags.vni",
+    cast: cast::UInt8().map(|v| (v & 0b0000_1000) != 0)
+);
+
+def_attr_class!(VNI_ATTR, "vxlan.vni");
+
+genet_decoders!(VxlanDecoder {});
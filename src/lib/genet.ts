@@ -34,7 +34,7 @@ export default class Genet {
       logger.domain = argv.loggerDomain
     }
     this.config = config
-    this.gpm = new Gpm()
+    this.gpm = new Gpm(config)
     this.workspace = new Workspace(argv.profile)
     this.keybind = new KeyBind(argv.profile, logger)
     this.packages = new PackageManager(config, components, logger)
@@ -1,15 +1,18 @@
 import gpm from '@genet/gpm'
 import listPackages from '@genet/gpm/lib/list'
 import execa from 'execa'
+import Config from './config'
 import Env from './env'
 const { EventEmitter } = require('events')
 
 export default class Gpm extends EventEmitter {
+    private _config: Config
     private _installerLog: string
     private _tasks: number
 
-    constructor() {
+    constructor(config: Config) {
         super()
+        this._config = config
         this._installerLog = ''
         this._tasks = 0
     }
@@ -34,7 +37,30 @@ export default class Gpm extends EventEmitter {
     }
 
     async install(id) {
-        const promise = execa(process.execPath, [gpm, 'install', id], {
+        return this._exec(['install', id], 'Installed', `Installation failed: ${id}`, id)
+    }
+
+    async outdated() {
+        const index = this._config.get('_.package.index', '')
+        if (!index) {
+            return []
+        }
+        const { stdout } = await execa(process.execPath, [gpm, 'outdated', '--json', '--index', index], {
+            env: {
+                ELECTRON_RUN_AS_NODE: '1'
+            },
+            stdio: 'pipe'
+        })
+        return JSON.parse(stdout)
+    }
+
+    async update(id) {
+        const index = this._config.get('_.package.index', '')
+        return this._exec(['update', '--index', index, id], 'Updated', `Update failed: ${id}`, id)
+    }
+
+    private _exec(args, title, failure, id) {
+        const promise = execa(process.execPath, [gpm].concat(args), {
             env: {
                 ELECTRON_RUN_AS_NODE: '1',
                 RUSTFLAGS: process.env.RUSTFLAGS || '-C target-cpu=native'
@@ -44,12 +70,12 @@ export default class Gpm extends EventEmitter {
         this._tasks += 1
         promise.then(() => {
             this._tasks -= 1
-            this.emit('finish', 'Installed', id)
+            this.emit('finish', title, id)
             this.emit('reload')
             this.emit('update')
         }, () => {
             this._tasks -= 1
-            this.emit('error', 'Error', `Installation failed: ${id}`)
+            this.emit('error', 'Error', failure)
             this.emit('reload')
             this.emit('update')
         })
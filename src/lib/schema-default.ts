@@ -35,6 +35,97 @@ export default {
       maximum: 8,
       default: 0,
     },
+    '_.decoder.serialConcurrency': {
+      description: 'Number of threads for stateful decoders, partitioned by flow',
+      type: 'integer',
+      minimum: 0,
+      maximum: 8,
+      default: 1,
+    },
+    '_.timestamp.zone': {
+      description: 'utc, local or a fixed offset such as +09:00',
+      type: 'string',
+      default: 'local',
+    },
+    '_.timestamp.reference': {
+      type: 'string',
+      enum: ['absolute', 'relative', 'delta'],
+      enumTitles: [
+        'Absolute',
+        'Relative to the first frame',
+        'Delta from the previous frame'
+      ],
+      default: 'absolute',
+    },
+    '_.timestamp.resolution': {
+      type: 'string',
+      enum: ['s', 'ms', 'us', 'ns'],
+      enumTitles: [
+        'Seconds',
+        'Milliseconds',
+        'Microseconds',
+        'Nanoseconds'
+      ],
+      default: 'us',
+    },
+    '_.link.userDlt': {
+      description: 'Starting decoders for custom link types, e.g. 147=[link-1], 148=@data:ipv4',
+      type: 'array',
+      items: {
+        type: 'string',
+      },
+      default: [],
+    },
+    '_.decodeAs.patterns': {
+      description: 'Decode payloads by magic bytes, e.g. tcp:474554=@data:http, udp:cafe@4=@data:custom',
+      type: 'array',
+      items: {
+        type: 'string',
+      },
+      default: [],
+    },
+    '_.frameIndex.dir': {
+      description: 'Directory for the on-disk frame index of large captures (disabled if empty)',
+      type: 'string',
+      default: '',
+    },
+    '_.capture.spillDir': {
+      description: 'Directory to record live captures into replayable spill files (disabled if empty)',
+      type: 'string',
+      default: '',
+    },
+    '_.decode.lazy': {
+      description: 'Decode frames on demand instead of on capture',
+      type: 'boolean',
+      default: false,
+    },
+    '_.decode.cacheSize': {
+      description: 'Maximum number of decoded frames kept in memory in lazy mode',
+      type: 'integer',
+      minimum: 1,
+      default: 65536,
+    },
+    '_.package.index': {
+      description: 'URL of the package index to check for updates (disabled if empty)',
+      type: 'string',
+      default: '',
+    },
+    '_.plugin.signaturePolicy': {
+      description: 'What to do with plugin libraries not signed by a trusted key',
+      type: 'string',
+      enum: ['allow', 'warn', 'deny'],
+      enumTitles: [
+        'Allow',
+        'Load with a warning',
+        'Do not load'
+      ],
+      default: 'allow',
+    },
+    '_.plugin.trustStore': {
+      description: 'Path to a file of trusted ed25519 public keys in hex, one per line',
+      type: 'string',
+      default: '',
+    },
     '_.dev.tabReloading': {
       description: 'Touch $HOME/.genet/.reload to reload all tabs',
       type: 'boolean',
@@ -96,16 +96,22 @@ export default class Session extends EventEmitter {
   async create() {
     const profile = new native.Session.Profile()
     profile.concurrency = genet.config.get('_.decoder.concurrency')
+    profile.serialConcurrency = genet.config.get('_.decoder.serialConcurrency')
     for (const [key, value] of Object.entries(this._config.toJSON())) {
       profile.setConfig(key, JSON.stringify(value))
     }
     for (const file of this._libs) {
       try {
-        profile.loadLibrary(file)
+        const warning = profile.loadLibrary(file)
+        if (warning) {
+          this.emit('warning', new Error(`Loaded untrusted ${file}: ${warning}`))
+        }
       } catch (err) {
         this.emit('error', new Error(`Filed to load ${file}: ${err.message}`))
       }
     }
-    return new native.Session(profile, {})
+    return new native.Session(profile, {
+      spillDir: genet.config.get('_.capture.spillDir'),
+    })
   }
 }
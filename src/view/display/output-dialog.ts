@@ -183,9 +183,11 @@ export default class OutputDialog {
             m(PanelView, {
               ...panel,
               attrs: {
-                callback: (id, options) => {
+                callback: (id, options, filter = '') => {
                   const { sess } = vnode.attrs
-                  sess.createWriter(id, options, this.filter)
+                  const filters = [this.filter, filter].filter((f) => f)
+                  sess.createWriter(id, options,
+                    filters.map((f) => `(${f})`).join(' && '))
                     .then(() => {
                       genet.notify.show(options.file || '', {
                         type: 'sussess',
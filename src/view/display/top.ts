@@ -143,6 +143,12 @@ export default class TopView {
         title: 'Session Error',
       })
     })
+    genet.session.on('warning', (err) => {
+      genet.notify.show(err.message, {
+        type: 'warn',
+        title: 'Untrusted Plugin',
+      })
+    })
     genet.packages.once('updated', () => {
       genet.action.on('core:session:created', (sess) => {
         sess.on('update', () => m.redraw())
@@ -17,6 +17,21 @@ export default class ButtonBoxView {
           }
         },
       }),
+      m('input', {
+        type: 'button',
+        value: pkg.update
+          ? `Update to ${pkg.update.latest.version}`
+          : 'Update',
+        style: {
+          display: pkg.update
+            ? 'block'
+            : 'none',
+        },
+        disabled: genet.gpm.tasks > 0,
+        onclick: () => {
+          genet.gpm.update(pkg.update.name)
+        },
+      }),
       m('input', {
         type: 'button',
         value: 'Uninstall',
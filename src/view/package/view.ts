@@ -24,11 +24,16 @@ export default class PackageView {
   }
 
   update() {
-    genet.gpm.list().then((list) => {
+    const outdated = genet.gpm.outdated().catch((err) => {
+      genet.logger.warn(err.message)
+      return []
+    })
+    Promise.all([genet.gpm.list(), outdated]).then(([list, updates]) => {
       const diasbaledPackages = genet.config.get('_.disabledPackages', [])
       this.packages = list.map((pkg) => ({
         ...pkg,
-        disabled: diasbaledPackages.includes(pkg.id)
+        disabled: diasbaledPackages.includes(pkg.id),
+        update: updates.find(({ id }) => id === pkg.id),
       }))
       m.redraw()
     })